@@ -15,6 +15,7 @@ fn get_test_config() -> S3StorageConfig {
         path_style: std::env::var("S3_PATH_STYLE").is_ok(),
         timeout_secs: 30,
         max_retries: 3,
+        ..Default::default()
     }
 }
 
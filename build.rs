@@ -0,0 +1,31 @@
+//! Embed the git commit and compiler version for `GET /debug/runtime`
+
+use std::process::Command;
+
+/// Run a command and return its trimmed output, if it succeeds
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let output = String::from_utf8(output.stdout).ok()?;
+    Some(output.trim().to_string())
+}
+
+fn main() {
+    let git_sha = std::env::var("SCRIBE_GIT_SHA")
+        .ok()
+        .or_else(|| command_output("git", &["rev-parse", "--short=12", "HEAD"]));
+    if let Some(git_sha) = git_sha {
+        println!("cargo:rustc-env=SCRIBE_GIT_SHA={}", git_sha);
+    }
+
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    if let Some(version) = command_output(&rustc, &["--version"]) {
+        println!("cargo:rustc-env=SCRIBE_RUSTC_VERSION={}", version);
+    }
+
+    println!("cargo:rerun-if-env-changed=SCRIBE_GIT_SHA");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs/heads");
+}
@@ -0,0 +1,316 @@
+//! HTTP access log
+//!
+//! With `[logging.access]` enabled every node writes one JSON line per sampled HTTP
+//! request to rotating files of its own, apart from the application log: method,
+//! route pattern and redacted path, status, latency, request and response bytes,
+//! the caller's principal and the trace ID that correlates the request with log
+//! lines and outgoing calls (see [`crate::trace_context`]). Server errors are
+//! recorded regardless of sampling unless `always_log_errors` is turned off.
+//!
+//! Lines are written by a background thread; when it falls behind, lines are
+//! dropped rather than slowing requests down.
+
+use crate::error::{Result, ScribeError};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+
+/// Directory of the access log under the data directory, unless configured
+pub const DEFAULT_ACCESS_LOG_DIR: &str = "access-log";
+
+/// How often access log files are rotated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessLogRotation {
+    /// A new file every hour
+    Hourly,
+    /// A new file every day
+    #[default]
+    Daily,
+    /// A single file that is never rotated
+    Never,
+}
+
+impl AccessLogRotation {
+    fn rotation(self) -> Rotation {
+        match self {
+            AccessLogRotation::Hourly => Rotation::HOURLY,
+            AccessLogRotation::Daily => Rotation::DAILY,
+            AccessLogRotation::Never => Rotation::NEVER,
+        }
+    }
+}
+
+/// HTTP access log configuration (`[logging.access]`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccessLogConfig {
+    /// Write the access log
+    #[serde(default)]
+    pub enabled: bool,
+    /// Directory of the log files; `<data_dir>/access-log` if unset
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+    /// Name prefix of the log files
+    #[serde(default = "default_access_log_prefix")]
+    pub file_prefix: String,
+    /// How often files are rotated
+    #[serde(default)]
+    pub rotation: AccessLogRotation,
+    /// Rotated files kept, oldest deleted first; 0 keeps every file
+    #[serde(default = "default_access_log_max_files")]
+    pub max_files: usize,
+    /// Share of requests recorded, from 0.0 to 1.0
+    #[serde(default = "default_access_log_sample_rate")]
+    pub sample_rate: f64,
+    /// Record every response with a 5xx status, whatever the sample rate
+    #[serde(default = "default_always_log_errors")]
+    pub always_log_errors: bool,
+}
+
+fn default_access_log_prefix() -> String {
+    "access".to_string()
+}
+
+fn default_access_log_max_files() -> usize {
+    7
+}
+
+fn default_access_log_sample_rate() -> f64 {
+    1.0
+}
+
+fn default_always_log_errors() -> bool {
+    true
+}
+
+impl Default for AccessLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: None,
+            file_prefix: default_access_log_prefix(),
+            rotation: AccessLogRotation::default(),
+            max_files: default_access_log_max_files(),
+            sample_rate: default_access_log_sample_rate(),
+            always_log_errors: default_always_log_errors(),
+        }
+    }
+}
+
+impl AccessLogConfig {
+    /// Check the sample rate and file prefix
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if !(0.0..=1.0).contains(&self.sample_rate) {
+            return Err("Access log sample_rate must be between 0.0 and 1.0".to_string());
+        }
+        if self.file_prefix.is_empty() {
+            return Err("Access log file_prefix must not be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// One request in the access log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessRecord {
+    /// Time the response was sent (milliseconds since the Unix epoch)
+    pub timestamp_ms: u64,
+    /// HTTP method
+    pub method: String,
+    /// Route pattern the request matched, e.g. `/v1/kv/:key`
+    pub route: String,
+    /// Request path, with keys redacted as configured in `[logging.keys]`
+    pub path: String,
+    /// Response status code
+    pub status: u16,
+    /// Time from receiving the request to sending the response headers
+    pub latency_us: u64,
+    /// Size of the request body, when the client announced it
+    pub request_bytes: Option<u64>,
+    /// Size of the response body, when known before streaming it
+    pub response_bytes: Option<u64>,
+    /// Caller identity: `cert:<fingerprint>` or `anonymous`
+    pub principal: String,
+    /// Address the request came from
+    pub client_addr: Option<String>,
+    /// W3C trace ID of the request
+    pub correlation_id: Option<String>,
+}
+
+/// Sampled writer of [`AccessRecord`]s
+///
+/// Cheap to clone; clones write to the same file.
+#[derive(Clone)]
+pub struct AccessLog {
+    writer: Arc<Mutex<NonBlocking>>,
+    sample_rate: f64,
+    always_log_errors: bool,
+    /// Flushes pending lines once the last clone is dropped
+    _guard: Arc<WorkerGuard>,
+}
+
+impl std::fmt::Debug for AccessLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccessLog")
+            .field("sample_rate", &self.sample_rate)
+            .field("always_log_errors", &self.always_log_errors)
+            .finish()
+    }
+}
+
+impl AccessLog {
+    /// Open the log files configured in `config`, under `data_dir` by default
+    pub fn open(config: &AccessLogConfig, data_dir: &Path) -> Result<Self> {
+        let dir = config
+            .dir
+            .clone()
+            .unwrap_or_else(|| data_dir.join(DEFAULT_ACCESS_LOG_DIR));
+        std::fs::create_dir_all(&dir)?;
+        let mut builder = RollingFileAppender::builder()
+            .rotation(config.rotation.rotation())
+            .filename_prefix(&config.file_prefix)
+            .filename_suffix("log");
+        if config.max_files > 0 {
+            builder = builder.max_log_files(config.max_files);
+        }
+        let appender = builder.build(&dir).map_err(|e| {
+            ScribeError::Configuration(format!("Failed to open access log in {:?}: {}", dir, e))
+        })?;
+        Ok(Self::with_writer(
+            appender,
+            config.sample_rate,
+            config.always_log_errors,
+        ))
+    }
+
+    /// Write records to `writer`
+    pub fn with_writer<W: Write + Send + 'static>(
+        writer: W,
+        sample_rate: f64,
+        always_log_errors: bool,
+    ) -> Self {
+        let (writer, guard) = tracing_appender::non_blocking(writer);
+        Self {
+            writer: Arc::new(Mutex::new(writer)),
+            sample_rate,
+            always_log_errors,
+            _guard: Arc::new(guard),
+        }
+    }
+
+    /// Whether a response with `status` is recorded
+    pub fn sample(&self, status: u16) -> bool {
+        (self.always_log_errors && status >= 500) || fastrand::f64() < self.sample_rate
+    }
+
+    /// Append `record` to the log
+    pub fn record(&self, record: &AccessRecord) {
+        let mut line = match serde_json::to_vec(record) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("Failed to encode access log record: {}", e);
+                return;
+            }
+        };
+        line.push(b'\n');
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = writer.write_all(&line) {
+            warn!("Failed to write access log: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writer appending to a buffer shared with the test
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn record(status: u16) -> AccessRecord {
+        AccessRecord {
+            timestamp_ms: 1_760_000_000_000,
+            method: "PUT".to_string(),
+            route: "/v1/kv/:key".to_string(),
+            path: "/v1/kv/sha256:2c26b46b68ffc68f".to_string(),
+            status,
+            latency_us: 1_250,
+            request_bytes: Some(5),
+            response_bytes: None,
+            principal: "anonymous".to_string(),
+            client_addr: Some("10.0.0.7:51234".to_string()),
+            correlation_id: Some("4bf92f3577b34da6a3ce929d0e0e4736".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_records_json_lines() {
+        let buffer = SharedBuffer::default();
+        let log = AccessLog::with_writer(buffer.clone(), 1.0, true);
+        log.record(&record(200));
+        log.clone().record(&record(503));
+        // Dropping the last clone flushes the background writer
+        drop(log);
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let records: Vec<AccessRecord> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records, vec![record(200), record(503)]);
+    }
+
+    #[test]
+    fn test_sampling_keeps_server_errors() {
+        let log = AccessLog::with_writer(std::io::sink(), 0.0, true);
+        assert!(!log.sample(200));
+        assert!(!log.sample(404));
+        assert!(log.sample(500));
+
+        let log = AccessLog::with_writer(std::io::sink(), 0.0, false);
+        assert!(!log.sample(500));
+        let log = AccessLog::with_writer(std::io::sink(), 1.0, false);
+        assert!(log.sample(200));
+    }
+
+    #[test]
+    fn test_open_writes_under_data_dir() {
+        let data_dir = std::env::temp_dir().join(format!("access-log-{}", fastrand::u64(..)));
+        let config = AccessLogConfig {
+            enabled: true,
+            rotation: AccessLogRotation::Never,
+            ..AccessLogConfig::default()
+        };
+        let log = AccessLog::open(&config, &data_dir).unwrap();
+        log.record(&record(200));
+        drop(log);
+
+        let file = data_dir.join(DEFAULT_ACCESS_LOG_DIR).join("access.log");
+        let contents = std::fs::read_to_string(&file).unwrap();
+        assert_eq!(contents.lines().count(), 1);
+        std::fs::remove_dir_all(&data_dir).unwrap();
+
+        let invalid = AccessLogConfig {
+            sample_rate: 1.5,
+            ..AccessLogConfig::default()
+        };
+        assert!(invalid.validate().is_err());
+        assert!(AccessLogConfig::default().validate().is_ok());
+    }
+}
@@ -0,0 +1,356 @@
+//! Typed event log of administrative actions
+//!
+//! Every administrative action (membership change, freeze, config reload, compaction
+//! trigger, join token creation) is recorded as a structured [`AdminEvent`] in a
+//! dedicated sled tree, together with the identity of the actor as established by the
+//! auth layer. Requests rejected by a listener's network policy are recorded too,
+//! with the client address as the actor. The log is local to the node that performed
+//! the action; it is not replicated through Raft.
+
+use crate::error::{Result, ScribeError};
+use crate::types::NodeId;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the sled tree holding the event log
+pub const ADMIN_EVENTS_TREE: &str = "admin_events";
+
+/// Default number of events returned by a query
+pub const DEFAULT_EVENT_LIMIT: usize = 100;
+
+/// Largest number of events returned by a single query
+pub const MAX_EVENT_LIMIT: usize = 1000;
+
+/// Administrative action recorded in the log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AdminAction {
+    /// A node was added to or removed from the cluster membership
+    MembershipChange {
+        /// Node affected by the change
+        node_id: NodeId,
+        /// What changed (e.g. "add_learner", "remove")
+        change: String,
+    },
+    /// Writes were frozen or unfrozen
+    Freeze {
+        /// Whether writes are now frozen
+        frozen: bool,
+    },
+    /// Configuration was reloaded
+    ConfigReload {
+        /// Hash of the configuration after the reload
+        config_hash: String,
+    },
+    /// Compaction was triggered manually
+    CompactionTrigger,
+    /// A join token was issued
+    TokenCreated {
+        /// ID of the issued token
+        token_id: String,
+        /// Expiry time (seconds since the Unix epoch)
+        expires_at: u64,
+    },
+    /// A named tag of the ledger state was created
+    TagCreated {
+        /// Tag name
+        name: String,
+        /// Raft log index the tag captures
+        raft_index: u64,
+    },
+    /// A named tag of the ledger state was deleted
+    TagDeleted {
+        /// Tag name
+        name: String,
+    },
+    /// The write fencing epoch of a namespace was advanced
+    EpochAdvanced {
+        /// Namespace (key prefix)
+        namespace: String,
+        /// New epoch
+        epoch: u64,
+    },
+    /// Leadership was handed to another voter
+    LeaderTransfer {
+        /// Voter leadership was handed to
+        target: NodeId,
+        /// Leader after the transfer, if one took over
+        new_leader: Option<NodeId>,
+    },
+    /// A namespace migration was started, resumed or paused
+    NamespaceMigration {
+        /// ID of the migration
+        id: String,
+        /// What was done ("start" or "pause")
+        change: String,
+    },
+    /// A bulk import was committed, in full or in part
+    BulkImport {
+        /// Records committed
+        records: u64,
+    },
+    /// A request was rejected by the network policy of its listener
+    RequestRejected {
+        /// Listener the request arrived on ("data" or "admin")
+        listener: String,
+        /// HTTP method of the request
+        method: String,
+        /// Path of the request
+        path: String,
+    },
+}
+
+impl AdminAction {
+    /// Kind of the action, as used for filtering
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AdminAction::MembershipChange { .. } => "membership_change",
+            AdminAction::Freeze { .. } => "freeze",
+            AdminAction::ConfigReload { .. } => "config_reload",
+            AdminAction::CompactionTrigger => "compaction_trigger",
+            AdminAction::TokenCreated { .. } => "token_created",
+            AdminAction::TagCreated { .. } => "tag_created",
+            AdminAction::TagDeleted { .. } => "tag_deleted",
+            AdminAction::EpochAdvanced { .. } => "epoch_advanced",
+            AdminAction::LeaderTransfer { .. } => "leader_transfer",
+            AdminAction::NamespaceMigration { .. } => "namespace_migration",
+            AdminAction::BulkImport { .. } => "bulk_import",
+            AdminAction::RequestRejected { .. } => "request_rejected",
+        }
+    }
+}
+
+/// A recorded administrative action
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdminEvent {
+    /// Monotonic event ID (log order)
+    pub id: u64,
+    /// Time the action was recorded (milliseconds since the Unix epoch)
+    pub timestamp_ms: u64,
+    /// Node that performed the action
+    pub node_id: NodeId,
+    /// Identity of whoever requested the action
+    pub actor: String,
+    /// The action itself
+    pub action: AdminAction,
+    /// Whether the action succeeded
+    pub success: bool,
+    /// Error message when the action failed
+    pub error: Option<String>,
+}
+
+/// Filter for querying the event log
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdminEventFilter {
+    /// Only events of this kind (e.g. "membership_change")
+    pub kind: Option<String>,
+    /// Only events requested by this actor
+    pub actor: Option<String>,
+    /// Only events recorded at or after this time (milliseconds since the Unix epoch)
+    pub since_ms: Option<u64>,
+    /// Only events recorded before this time (milliseconds since the Unix epoch)
+    pub until_ms: Option<u64>,
+    /// Maximum number of events to return (defaults to [`DEFAULT_EVENT_LIMIT`])
+    pub limit: Option<usize>,
+}
+
+impl AdminEventFilter {
+    fn matches(&self, event: &AdminEvent) -> bool {
+        self.kind
+            .as_deref()
+            .is_none_or(|kind| kind == event.action.kind())
+            && self
+                .actor
+                .as_deref()
+                .is_none_or(|actor| actor == event.actor)
+            && self
+                .since_ms
+                .is_none_or(|since| event.timestamp_ms >= since)
+            && self.until_ms.is_none_or(|until| event.timestamp_ms < until)
+    }
+}
+
+/// Persistent log of administrative actions
+#[derive(Clone)]
+pub struct AdminEventLog {
+    db: sled::Db,
+    tree: sled::Tree,
+    node_id: NodeId,
+}
+
+impl AdminEventLog {
+    /// Open the event log in `db` for the local node
+    pub fn open(db: &sled::Db, node_id: NodeId) -> Result<Self> {
+        Ok(Self {
+            db: db.clone(),
+            tree: db.open_tree(ADMIN_EVENTS_TREE)?,
+            node_id,
+        })
+    }
+
+    /// Record an action requested by `actor`
+    ///
+    /// `outcome` is the result of the action; failed actions are recorded too.
+    pub fn record(
+        &self,
+        actor: impl Into<String>,
+        action: AdminAction,
+        outcome: std::result::Result<(), String>,
+    ) -> Result<AdminEvent> {
+        let event = AdminEvent {
+            id: self.db.generate_id()?,
+            timestamp_ms: now_ms(),
+            node_id: self.node_id,
+            actor: actor.into(),
+            action,
+            success: outcome.is_ok(),
+            error: outcome.err(),
+        };
+
+        self.tree
+            .insert(event.id.to_be_bytes(), serde_json::to_vec(&event)?)?;
+        Ok(event)
+    }
+
+    /// Query events matching `filter`, newest first
+    pub fn query(&self, filter: &AdminEventFilter) -> Result<Vec<AdminEvent>> {
+        let limit = filter
+            .limit
+            .unwrap_or(DEFAULT_EVENT_LIMIT)
+            .min(MAX_EVENT_LIMIT);
+
+        let mut events = Vec::new();
+        for entry in self.tree.iter().rev() {
+            if events.len() >= limit {
+                break;
+            }
+            let (_, value) = entry?;
+            let event: AdminEvent = serde_json::from_slice(&value)
+                .map_err(|e| ScribeError::Storage(format!("Corrupt admin event entry: {}", e)))?;
+            if filter.matches(&event) {
+                events.push(event);
+            }
+        }
+
+        Ok(events)
+    }
+
+    /// Number of recorded events
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    /// Check if no events have been recorded
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log() -> AdminEventLog {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        AdminEventLog::open(&db, 1).unwrap()
+    }
+
+    #[test]
+    fn test_record_and_query_newest_first() {
+        let log = log();
+        log.record(
+            "cluster-secret",
+            AdminAction::TokenCreated {
+                token_id: "abc".to_string(),
+                expires_at: 100,
+            },
+            Ok(()),
+        )
+        .unwrap();
+        log.record(
+            "token:abc",
+            AdminAction::MembershipChange {
+                node_id: 2,
+                change: "add_learner".to_string(),
+            },
+            Err("not leader".to_string()),
+        )
+        .unwrap();
+
+        let events = log.query(&AdminEventFilter::default()).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].action.kind(), "membership_change");
+        assert!(!events[0].success);
+        assert_eq!(events[0].error.as_deref(), Some("not leader"));
+        assert_eq!(events[1].action.kind(), "token_created");
+        assert!(events[0].id > events[1].id);
+        assert_eq!(events[1].node_id, 1);
+    }
+
+    #[test]
+    fn test_query_filters() {
+        let log = log();
+        for i in 0..5 {
+            log.record(
+                if i % 2 == 0 { "alice" } else { "bob" },
+                AdminAction::CompactionTrigger,
+                Ok(()),
+            )
+            .unwrap();
+        }
+        log.record("alice", AdminAction::Freeze { frozen: true }, Ok(()))
+            .unwrap();
+
+        let filter = AdminEventFilter {
+            actor: Some("alice".to_string()),
+            ..AdminEventFilter::default()
+        };
+        assert_eq!(log.query(&filter).unwrap().len(), 4);
+
+        let filter = AdminEventFilter {
+            kind: Some("compaction_trigger".to_string()),
+            actor: Some("bob".to_string()),
+            ..AdminEventFilter::default()
+        };
+        assert_eq!(log.query(&filter).unwrap().len(), 2);
+
+        let filter = AdminEventFilter {
+            limit: Some(3),
+            ..AdminEventFilter::default()
+        };
+        assert_eq!(log.query(&filter).unwrap().len(), 3);
+
+        let filter = AdminEventFilter {
+            since_ms: Some(now_ms() + 60_000),
+            ..AdminEventFilter::default()
+        };
+        assert!(log.query(&filter).unwrap().is_empty());
+        assert_eq!(log.len(), 6);
+    }
+
+    #[test]
+    fn test_event_json_shape() {
+        let log = log();
+        let event = log
+            .record(
+                "cert:ab12",
+                AdminAction::ConfigReload {
+                    config_hash: "deadbeef".to_string(),
+                },
+                Ok(()),
+            )
+            .unwrap();
+
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["action"]["kind"], "config_reload");
+        assert_eq!(json["action"]["config_hash"], "deadbeef");
+        assert_eq!(json["actor"], "cert:ab12");
+    }
+}
@@ -0,0 +1,402 @@
+//! Priority-aware admission control
+//!
+//! Requests carry a [`Priority`] (HTTP header `X-Priority: high|normal|low`). The
+//! [`AdmissionController`] bounds the number of requests in flight and gives each
+//! class a different share of the slots: low priority traffic (bulk imports) may only
+//! use part of the capacity, and a slice is reserved for high priority traffic
+//! (control plane, latency-sensitive reads). When slots free up, queued requests are
+//! admitted highest priority first.
+
+use crate::error::{Result, ScribeError};
+use crate::metrics::ADMISSION_QUEUED;
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+
+/// HTTP header carrying the request priority
+pub const PRIORITY_HEADER: &str = "x-priority";
+
+/// Request priority class
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Bulk traffic (imports, backfills)
+    Low,
+    /// Regular traffic
+    #[default]
+    Normal,
+    /// Control-plane and latency-sensitive traffic
+    High,
+}
+
+impl Priority {
+    /// All classes, highest first (admission order)
+    pub const ALL: [Priority; 3] = [Priority::High, Priority::Normal, Priority::Low];
+
+    /// Lowercase name, as used in the header and metrics
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Priority::Low => "low",
+            Priority::Normal => "normal",
+            Priority::High => "high",
+        }
+    }
+
+    fn index(self) -> usize {
+        match self {
+            Priority::High => 0,
+            Priority::Normal => 1,
+            Priority::Low => 2,
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Priority {
+    type Err = ScribeError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "high" => Ok(Priority::High),
+            "normal" => Ok(Priority::Normal),
+            "low" => Ok(Priority::Low),
+            other => Err(ScribeError::Other(format!(
+                "Invalid priority '{}' (expected high, normal or low)",
+                other
+            ))),
+        }
+    }
+}
+
+struct AdmissionState {
+    in_flight: usize,
+    /// Waiters per class, indexed by [`Priority::index`]
+    waiters: [VecDeque<oneshot::Sender<()>>; 3],
+}
+
+struct AdmissionInner {
+    /// Slots each class may use, indexed by [`Priority::index`]
+    limits: [usize; 3],
+    state: Mutex<AdmissionState>,
+}
+
+impl AdmissionInner {
+    fn lock(&self) -> std::sync::MutexGuard<'_, AdmissionState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Hand freed slots to queued requests, highest priority first
+    fn release(&self) {
+        let mut state = self.lock();
+        state.in_flight -= 1;
+
+        'admit: loop {
+            for priority in Priority::ALL {
+                let i = priority.index();
+                if state.in_flight >= self.limits[i] {
+                    continue;
+                }
+                while let Some(waiter) = state.waiters[i].pop_front() {
+                    // Skip waiters whose request was cancelled
+                    if waiter.send(()).is_ok() {
+                        state.in_flight += 1;
+                        continue 'admit;
+                    }
+                }
+            }
+            break;
+        }
+    }
+}
+
+/// Bounds concurrent requests, admitting higher priority classes first
+#[derive(Clone)]
+pub struct AdmissionController {
+    inner: Arc<AdmissionInner>,
+}
+
+impl AdmissionController {
+    /// Create a controller for `max_in_flight` concurrent requests
+    ///
+    /// Low priority requests may occupy at most `low_share_percent` of the slots, and
+    /// `high_reserve_percent` of the slots are only available to high priority
+    /// requests. Every class always gets at least one slot.
+    pub fn new(
+        max_in_flight: usize,
+        low_share_percent: u8,
+        high_reserve_percent: u8,
+    ) -> Result<Self> {
+        if max_in_flight == 0 {
+            return Err(ScribeError::Configuration(
+                "Max in-flight requests must be greater than 0".to_string(),
+            ));
+        }
+        if low_share_percent > 100 || high_reserve_percent > 100 {
+            return Err(ScribeError::Configuration(
+                "Priority shares must be percentages between 0 and 100".to_string(),
+            ));
+        }
+
+        let share = |percent: u8| (max_in_flight * percent as usize / 100).max(1);
+        let normal = (max_in_flight - max_in_flight * high_reserve_percent as usize / 100).max(1);
+        let low = share(low_share_percent).min(normal);
+
+        Ok(Self {
+            inner: Arc::new(AdmissionInner {
+                limits: [max_in_flight, normal, low],
+                state: Mutex::new(AdmissionState {
+                    in_flight: 0,
+                    waiters: Default::default(),
+                }),
+            }),
+        })
+    }
+
+    /// Wait for a slot for a request of `priority`
+    ///
+    /// The slot is held until the returned permit is dropped.
+    pub async fn acquire(&self, priority: Priority) -> AdmissionPermit {
+        let receiver = {
+            let mut state = self.inner.lock();
+            let i = priority.index();
+            let higher_waiting = state.waiters[..i].iter().any(|w| !w.is_empty());
+
+            if state.in_flight < self.inner.limits[i]
+                && state.waiters[i].is_empty()
+                && !higher_waiting
+            {
+                state.in_flight += 1;
+                None
+            } else {
+                let (sender, receiver) = oneshot::channel();
+                state.waiters[i].push_back(sender);
+                Some(receiver)
+            }
+        };
+
+        if let Some(receiver) = receiver {
+            ADMISSION_QUEUED
+                .with_label_values(&[priority.as_str()])
+                .inc();
+            let mut queued = QueuedRequest {
+                receiver: Some(receiver),
+                inner: Arc::clone(&self.inner),
+            };
+            if let Some(receiver) = queued.receiver.as_mut() {
+                // Senders are only dropped together with the controller, which the
+                // queued request keeps alive, so this resolves once admitted
+                let _ = receiver.await;
+            }
+            queued.receiver = None;
+        }
+
+        AdmissionPermit {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+
+    /// Number of requests currently admitted
+    pub fn in_flight(&self) -> usize {
+        self.inner.lock().in_flight
+    }
+
+    /// Number of requests of `priority` waiting for a slot
+    pub fn queued(&self, priority: Priority) -> usize {
+        self.inner.lock().waiters[priority.index()].len()
+    }
+
+    /// Slots available to `priority`
+    pub fn limit(&self, priority: Priority) -> usize {
+        self.inner.limits[priority.index()]
+    }
+
+    /// Share of all slots currently in use, as a percentage
+    pub fn load_percent(&self) -> u8 {
+        let in_flight = self.in_flight();
+        (in_flight * 100 / self.inner.limits[0]).min(100) as u8
+    }
+
+    /// Number of requests of any priority waiting for a slot
+    pub fn queue_depth(&self) -> usize {
+        self.inner.lock().waiters.iter().map(VecDeque::len).sum()
+    }
+}
+
+/// Round a queue depth down to a power of ten (0, 1, 10, 100, ...)
+///
+/// Load hints report buckets rather than exact depths, which change with every
+/// request and would make clients flap between nodes.
+pub fn queue_depth_bucket(depth: usize) -> u64 {
+    match depth {
+        0 => 0,
+        depth => 10u64.pow((depth as u64).ilog10()),
+    }
+}
+
+/// Request waiting for a slot
+///
+/// If the waiting request is cancelled after a slot was handed to it, the slot is
+/// released again so it does not leak.
+struct QueuedRequest {
+    receiver: Option<oneshot::Receiver<()>>,
+    inner: Arc<AdmissionInner>,
+}
+
+impl Drop for QueuedRequest {
+    fn drop(&mut self) {
+        if let Some(mut receiver) = self.receiver.take() {
+            receiver.close();
+            if receiver.try_recv().is_ok() {
+                self.inner.release();
+            }
+        }
+    }
+}
+
+/// Slot held by an admitted request; released on drop
+pub struct AdmissionPermit {
+    inner: Arc<AdmissionInner>,
+}
+
+impl Drop for AdmissionPermit {
+    fn drop(&mut self) {
+        self.inner.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_priority_parse() {
+        assert_eq!("high".parse::<Priority>().unwrap(), Priority::High);
+        assert_eq!(" Normal ".parse::<Priority>().unwrap(), Priority::Normal);
+        assert_eq!("LOW".parse::<Priority>().unwrap(), Priority::Low);
+        assert!("urgent".parse::<Priority>().is_err());
+        assert_eq!(Priority::default(), Priority::Normal);
+    }
+
+    #[test]
+    fn test_class_limits() {
+        let controller = AdmissionController::new(100, 50, 10).unwrap();
+        assert_eq!(controller.limit(Priority::High), 100);
+        assert_eq!(controller.limit(Priority::Normal), 90);
+        assert_eq!(controller.limit(Priority::Low), 50);
+
+        // Tiny pools still admit every class
+        let controller = AdmissionController::new(1, 0, 100).unwrap();
+        assert_eq!(controller.limit(Priority::Normal), 1);
+        assert_eq!(controller.limit(Priority::Low), 1);
+
+        assert!(AdmissionController::new(0, 50, 10).is_err());
+        assert!(AdmissionController::new(10, 101, 10).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_low_priority_capped() {
+        let controller = AdmissionController::new(4, 50, 25).unwrap();
+
+        let _low1 = controller.acquire(Priority::Low).await;
+        let _low2 = controller.acquire(Priority::Low).await;
+
+        // Low priority share (2 slots) is exhausted
+        let blocked =
+            tokio::time::timeout(Duration::from_millis(50), controller.acquire(Priority::Low))
+                .await;
+        assert!(blocked.is_err());
+
+        // Higher classes still get in
+        let _normal = controller.acquire(Priority::Normal).await;
+        let _high = controller.acquire(Priority::High).await;
+        assert_eq!(controller.in_flight(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_high_priority_admitted_first() {
+        let controller = AdmissionController::new(1, 100, 0).unwrap();
+        let held = controller.acquire(Priority::Normal).await;
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut tasks = Vec::new();
+        for priority in [Priority::Low, Priority::Normal, Priority::High] {
+            let waiter = controller.clone();
+            let order = Arc::clone(&order);
+            tasks.push(tokio::spawn(async move {
+                let _permit = waiter.acquire(priority).await;
+                order.lock().unwrap().push(priority);
+            }));
+            // Queue in a deterministic order
+            while controller.queued(priority) == 0 {
+                tokio::task::yield_now().await;
+            }
+        }
+
+        drop(held);
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert_eq!(
+            *order.lock().unwrap(),
+            vec![Priority::High, Priority::Normal, Priority::Low]
+        );
+        assert_eq!(controller.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_load_reporting() {
+        let controller = AdmissionController::new(4, 100, 0).unwrap();
+        assert_eq!(controller.load_percent(), 0);
+
+        let held: Vec<_> =
+            futures::future::join_all((0..3).map(|_| controller.acquire(Priority::Normal))).await;
+        assert_eq!(controller.load_percent(), 75);
+        let _last = controller.acquire(Priority::Normal).await;
+        assert_eq!(controller.load_percent(), 100);
+
+        let waiter = controller.clone();
+        let queued = tokio::spawn(async move {
+            let _permit = waiter.acquire(Priority::Low).await;
+        });
+        while controller.queue_depth() == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(controller.queue_depth(), 1);
+        drop(held);
+        queued.await.unwrap();
+        assert_eq!(controller.queue_depth(), 0);
+
+        assert_eq!(queue_depth_bucket(0), 0);
+        assert_eq!(queue_depth_bucket(1), 1);
+        assert_eq!(queue_depth_bucket(9), 1);
+        assert_eq!(queue_depth_bucket(10), 10);
+        assert_eq!(queue_depth_bucket(999), 100);
+        assert_eq!(queue_depth_bucket(1000), 1000);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_waiter_does_not_leak_slot() {
+        let controller = AdmissionController::new(1, 100, 0).unwrap();
+        let held = controller.acquire(Priority::Normal).await;
+
+        let cancelled = tokio::time::timeout(
+            Duration::from_millis(20),
+            controller.acquire(Priority::Normal),
+        )
+        .await;
+        assert!(cancelled.is_err());
+
+        drop(held);
+        assert_eq!(controller.in_flight(), 0);
+        let _permit = controller.acquire(Priority::Normal).await;
+        assert_eq!(controller.in_flight(), 1);
+    }
+}
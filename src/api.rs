@@ -3,13 +3,40 @@
 //! This module provides the high-level API for distributed operations,
 //! including write request forwarding, batching, read operations, caching, and timeout handling.
 
+use crate::admission::{AdmissionController, Priority};
 use crate::cache::HotDataCache;
 use crate::config::ApiConfig;
-use crate::consensus::{AppRequest, AppResponse, ConsensusNode};
+use crate::consensus::{
+    AppRequest, AppResponse, ChangeEvent, ChangeKind, ConsensusNode, KeyChange, StoredValue,
+};
+use crate::contention::{ContentionReport, ContentionTracker};
+use crate::crypto::MerkleTree;
 use crate::error::{Result, ScribeError};
-use crate::types::{Key, NodeId, Value};
+use crate::hotkeys::{HotKeyReport, HotKeyTracker, KeyOp};
+use crate::logging::log_key;
+use crate::metrics::{observe_api_latency, observe_read_tier, API_BATCH_SIZE};
+use crate::namespace_migration::{
+    checkpoint_key, BatchKey, MigrationCheckpoint, MigrationRun, MigrationSpec, MigrationState,
+    MIGRATION_KEY_PREFIX,
+};
+use crate::quota::KeyUsage;
+use crate::read_path::{ReadPathConfig, ReadTier};
+use crate::shadow::{ShadowOp, ShadowStats, ShadowWriter};
+use crate::storage::archival::ArchivalManager;
+use crate::storage::segment::Segment;
+use crate::sync::{self, BucketDigest, KeyDigest};
+use crate::transaction::{abort, ConflictableTransactionError, DistributedTxn, TxnResult};
+use crate::types::{
+    Fence, Key, LedgerTag, LockLease, NodeId, QueueItem, QueueStats, SegmentAnchor, SegmentId,
+    Session, SessionRecord, TxnWrite, UsageDelta, Value,
+};
+use crate::wire::ProofBundle;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::Serialize;
+use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
 use tokio::time::timeout;
 
 /// Default timeout for write operations
@@ -24,11 +51,62 @@ const DEFAULT_BATCH_SIZE: usize = 100;
 /// Default cache capacity for hot data
 const DEFAULT_CACHE_CAPACITY: usize = 1000;
 
+/// Default maximum number of unacked items per queue
+const DEFAULT_QUEUE_CAPACITY: usize = 10_000;
+
+/// Longest a read waits for the local node to apply a consistency token
+const DEFAULT_MIN_APPLIED_WAIT: Duration = Duration::from_secs(5);
+
+/// Most times a transaction is proposed before giving up on conflicting writes
+const MAX_TXN_ATTEMPTS: usize = 10;
+
+/// Tiers exports and digests read demoted values from, regardless of the read path
+const ARCHIVE_TIERS: &[ReadTier] = &[ReadTier::Segments, ReadTier::S3];
+
+/// Prefix of the keys sessions are stored under
+pub const SESSION_KEY_PREFIX: &str = "__sessions/";
+
+/// Log position of a write, returned to clients for read-your-writes
+///
+/// A read carrying the token is only answered once the serving node has applied
+/// the write, which gives causal consistency without linearizable reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ConsistencyToken(u64);
+
+impl ConsistencyToken {
+    /// Create a token for the given applied log index
+    pub fn new(applied_index: u64) -> Self {
+        Self(applied_index)
+    }
+
+    /// Log index a node must have applied to satisfy the token
+    pub fn applied_index(&self) -> u64 {
+        self.0
+    }
+}
+
+impl std::fmt::Display for ConsistencyToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::str::FromStr for ConsistencyToken {
+    type Err = ScribeError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        s.trim()
+            .parse()
+            .map(Self)
+            .map_err(|_| ScribeError::Other(format!("Invalid consistency token '{}'", s)))
+    }
+}
+
 /// Read consistency level
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ReadConsistency {
     /// Linearizable read - guarantees reading the latest committed data
-    /// Read must be served by the leader
+    /// Served by the leader, or by a follower after getting a read index from it
     Linearizable,
     /// Stale read - may return slightly outdated data
     /// Can be served by any node (including followers)
@@ -36,6 +114,81 @@ pub enum ReadConsistency {
     Stale,
 }
 
+/// Storage tier a value was read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageTier {
+    /// In-memory state machine (or the hot data cache)
+    Hot,
+    /// Archived segment in S3, fetched on demand
+    Cold,
+}
+
+impl StorageTier {
+    /// Get the tier as a string
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StorageTier::Hot => "hot",
+            StorageTier::Cold => "cold",
+        }
+    }
+}
+
+/// Result of re-checking a tag against the state it captured
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TagVerification {
+    /// The verified tag
+    pub tag: LedgerTag,
+    /// Merkle root (hex) recomputed from the captured state
+    pub computed_root: Option<String>,
+    /// Demoted keys whose archived value could not be read back
+    pub unreadable_keys: Vec<String>,
+    /// Whether the roots match and every demoted key could be read back
+    pub verified: bool,
+}
+
+/// Proof chain from the value of a key to a segment root committed through Raft
+///
+/// Hashing the key and value into a leaf and combining it with `siblings` in order
+/// (the path node being the right child where `directions` is `true`) yields the
+/// Merkle root of the archived segment, which must equal the root `anchor`
+/// committed at its Raft index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KeyProof {
+    /// The proven key
+    pub key: String,
+    /// Archived segment holding the value
+    pub segment_id: SegmentId,
+    /// Leaf hash (hex) of the key and its archived value
+    pub leaf: String,
+    /// Sibling hashes (hex) from the leaf up to the segment root
+    pub siblings: Vec<String>,
+    /// Whether the path node is the right child, level by level
+    pub directions: Vec<bool>,
+    /// Segment root (hex) the proof leads to
+    pub computed_root: Option<String>,
+    /// The segment root committed through Raft
+    pub anchor: SegmentAnchor,
+    /// Raft index the archival manifest records for the anchor, if it tracks the
+    /// segment
+    pub manifest_anchor_index: Option<u64>,
+    /// Whether the proof leads to the committed root
+    pub verified: bool,
+    /// The proof with the committed root, for export to offline verifiers
+    #[serde(skip)]
+    pub bundle: ProofBundle,
+}
+
+/// Outcome of [`DistributedApi::archive_idle`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ArchivalRun {
+    /// Idle keys written to segments
+    pub keys: usize,
+    /// Segments archived to S3
+    pub segments: usize,
+    /// Keys demoted to their archived segment
+    pub demoted: usize,
+}
+
 /// Distributed API for handling read/write requests with caching
 pub struct DistributedApi {
     /// The consensus node
@@ -46,6 +199,22 @@ pub struct DistributedApi {
     max_batch_size: usize,
     /// Hot data cache
     cache: Arc<HotDataCache>,
+    /// Optional mirror of successful writes (shadow mode)
+    shadow: Option<Arc<ShadowWriter>>,
+    /// Optional admission control for batch chunks
+    admission: Option<AdmissionController>,
+    /// Per-prefix counters and hot key estimates
+    hot_keys: HotKeyTracker,
+    /// Per-prefix conflicts of conditional writes
+    contention: ContentionTracker,
+    /// Archived segments backing values demoted to cold storage
+    archival: Option<Arc<ArchivalManager>>,
+    /// Maximum number of unacked items per queue
+    queue_capacity: usize,
+    /// Storage tier order of reads
+    read_path: Arc<ReadPathConfig>,
+    /// Clock skew between nodes that leases allow for
+    max_clock_skew: Duration,
 }
 
 impl DistributedApi {
@@ -56,6 +225,14 @@ impl DistributedApi {
             write_timeout: DEFAULT_WRITE_TIMEOUT,
             max_batch_size: DEFAULT_BATCH_SIZE,
             cache: Arc::new(HotDataCache::with_capacity(DEFAULT_CACHE_CAPACITY)),
+            shadow: None,
+            admission: None,
+            hot_keys: HotKeyTracker::new(),
+            contention: ContentionTracker::new(),
+            archival: None,
+            queue_capacity: DEFAULT_QUEUE_CAPACITY,
+            read_path: Arc::new(ReadPathConfig::default()),
+            max_clock_skew: Duration::ZERO,
         }
     }
 
@@ -66,6 +243,14 @@ impl DistributedApi {
             write_timeout: Duration::from_secs(config.write_timeout_secs),
             max_batch_size: config.max_batch_size,
             cache: Arc::new(HotDataCache::with_capacity(config.cache_capacity)),
+            shadow: None,
+            admission: None,
+            hot_keys: HotKeyTracker::new(),
+            contention: ContentionTracker::new(),
+            archival: None,
+            queue_capacity: config.max_queue_length,
+            read_path: Arc::new(config.read_path.clone()),
+            max_clock_skew: Duration::ZERO,
         }
     }
 
@@ -76,6 +261,14 @@ impl DistributedApi {
             write_timeout,
             max_batch_size: DEFAULT_BATCH_SIZE,
             cache: Arc::new(HotDataCache::with_capacity(DEFAULT_CACHE_CAPACITY)),
+            shadow: None,
+            admission: None,
+            hot_keys: HotKeyTracker::new(),
+            contention: ContentionTracker::new(),
+            archival: None,
+            queue_capacity: DEFAULT_QUEUE_CAPACITY,
+            read_path: Arc::new(ReadPathConfig::default()),
+            max_clock_skew: Duration::ZERO,
         }
     }
 
@@ -86,6 +279,14 @@ impl DistributedApi {
             write_timeout: DEFAULT_WRITE_TIMEOUT,
             max_batch_size,
             cache: Arc::new(HotDataCache::with_capacity(DEFAULT_CACHE_CAPACITY)),
+            shadow: None,
+            admission: None,
+            hot_keys: HotKeyTracker::new(),
+            contention: ContentionTracker::new(),
+            archival: None,
+            queue_capacity: DEFAULT_QUEUE_CAPACITY,
+            read_path: Arc::new(ReadPathConfig::default()),
+            max_clock_skew: Duration::ZERO,
         }
     }
 
@@ -100,6 +301,14 @@ impl DistributedApi {
             write_timeout,
             max_batch_size,
             cache: Arc::new(HotDataCache::with_capacity(DEFAULT_CACHE_CAPACITY)),
+            shadow: None,
+            admission: None,
+            hot_keys: HotKeyTracker::new(),
+            contention: ContentionTracker::new(),
+            archival: None,
+            queue_capacity: DEFAULT_QUEUE_CAPACITY,
+            read_path: Arc::new(ReadPathConfig::default()),
+            max_clock_skew: Duration::ZERO,
         }
     }
 
@@ -110,6 +319,14 @@ impl DistributedApi {
             write_timeout: DEFAULT_WRITE_TIMEOUT,
             max_batch_size: DEFAULT_BATCH_SIZE,
             cache: Arc::new(HotDataCache::with_capacity(cache_capacity)),
+            shadow: None,
+            admission: None,
+            hot_keys: HotKeyTracker::new(),
+            contention: ContentionTracker::new(),
+            archival: None,
+            queue_capacity: DEFAULT_QUEUE_CAPACITY,
+            read_path: Arc::new(ReadPathConfig::default()),
+            max_clock_skew: Duration::ZERO,
         }
     }
 
@@ -125,6 +342,81 @@ impl DistributedApi {
             write_timeout,
             max_batch_size,
             cache: Arc::new(HotDataCache::with_capacity(cache_capacity)),
+            shadow: None,
+            admission: None,
+            hot_keys: HotKeyTracker::new(),
+            contention: ContentionTracker::new(),
+            archival: None,
+            queue_capacity: DEFAULT_QUEUE_CAPACITY,
+            read_path: Arc::new(ReadPathConfig::default()),
+            max_clock_skew: Duration::ZERO,
+        }
+    }
+
+    /// Mirror successful writes to a shadow target
+    pub fn with_shadow(mut self, shadow: ShadowWriter) -> Self {
+        self.shadow = Some(Arc::new(shadow));
+        self
+    }
+
+    /// Admit batch chunks through an admission controller, honoring batch priority
+    pub fn with_admission(mut self, admission: AdmissionController) -> Self {
+        self.admission = Some(admission);
+        self
+    }
+
+    /// Read demoted values through from archived segments
+    pub fn with_archival(mut self, archival: Arc<ArchivalManager>) -> Self {
+        self.archival = Some(archival);
+        self
+    }
+
+    /// Bound the number of unacked items per queue
+    pub fn with_queue_capacity(mut self, queue_capacity: usize) -> Self {
+        self.queue_capacity = queue_capacity.max(1);
+        self
+    }
+
+    /// Allow for clocks of different nodes being up to `max_skew` apart
+    ///
+    /// A lock, queue or session lease is then only taken over once it ran out at
+    /// least `max_skew` ago on this node's clock.
+    pub fn with_max_clock_skew(mut self, max_skew: Duration) -> Self {
+        self.max_clock_skew = max_skew;
+        self
+    }
+
+    /// Replace the hot data cache, e.g. with one that spills to disk
+    pub fn with_cache(mut self, cache: HotDataCache) -> Self {
+        self.cache = Arc::new(cache);
+        self
+    }
+
+    /// Walk the storage tiers of reads in the order `read_path` sets per namespace
+    pub fn with_read_path(mut self, read_path: ReadPathConfig) -> Self {
+        self.read_path = Arc::new(read_path);
+        self
+    }
+
+    /// Get shadow write counters, if shadow mode is enabled
+    pub fn shadow_stats(&self) -> Option<ShadowStats> {
+        self.shadow.as_ref().map(|shadow| shadow.stats())
+    }
+
+    /// The hottest keys and busiest prefixes handled by this node
+    pub fn hot_keys(&self, limit: usize) -> HotKeyReport {
+        self.hot_keys.report(limit)
+    }
+
+    /// The key prefixes whose conditional writes conflict most on this node
+    pub fn contention(&self, limit: usize) -> ContentionReport {
+        self.contention.report(limit)
+    }
+
+    /// Queue a successful write for mirroring
+    fn mirror(&self, op: ShadowOp) {
+        if let Some(shadow) = &self.shadow {
+            shadow.mirror(op);
         }
     }
 
@@ -132,139 +424,2003 @@ impl DistributedApi {
     ///
     /// This method:
     /// 1. Checks if the current node is the leader
-    /// 2. If not leader, the request will fail with NotLeader error (client should retry with leader)
+    /// 2. If not leader, the request will fail with `ScribeError::NotLeader` (client should retry with leader)
     /// 3. If leader, proposes the write to Raft
     /// 4. Waits for consensus with timeout
     /// 5. Returns success once committed
     /// 6. Invalidates cache entry for the key
-    pub async fn put(&self, key: Key, value: Value) -> Result<()> {
+    ///
+    /// Returns the consistency token of the write.
+    pub async fn put(&self, key: Key, value: Value) -> Result<ConsistencyToken> {
+        self.propose_put(key, value, None).await
+    }
+
+    /// Put a key-value pair on behalf of a writer holding `fence`
+    ///
+    /// Fails with `ScribeError::Conflict` if the namespace has moved past the fence's
+    /// epoch, i.e. a coordinator handed the namespace to another writer.
+    pub async fn put_fenced(
+        &self,
+        key: Key,
+        value: Value,
+        fence: Fence,
+    ) -> Result<ConsistencyToken> {
+        self.propose_put(key, value, Some(fence)).await
+    }
+
+    async fn propose_put(
+        &self,
+        key: Key,
+        value: Value,
+        fence: Option<Fence>,
+    ) -> Result<ConsistencyToken> {
+        let started = Instant::now();
+        self.hot_keys.record(&key, KeyOp::Write);
         let request = AppRequest::Put {
             key: key.clone(),
             value: value.clone(),
         };
+        let request = fence_request(fence.clone(), request);
 
         // Execute write with timeout
-        let result = timeout(self.write_timeout, self.consensus.client_write(request)).await;
+        let result = timeout(
+            self.write_timeout,
+            self.consensus.client_write_indexed(request),
+        )
+        .await;
+        observe_api_latency("put", started);
 
         match result {
-            Ok(Ok(AppResponse::PutOk)) => {
+            Ok(Ok((AppResponse::PutOk, index))) => {
+                self.mirror(ShadowOp::Put {
+                    key: key.clone(),
+                    value: value.clone(),
+                });
                 // Update cache with new value
                 self.cache.put(key, value);
-                Ok(())
+                Ok(ConsistencyToken::new(index))
             }
-            Ok(Ok(AppResponse::Error { message })) => {
+            Ok(Ok((AppResponse::StaleEpoch { current }, _))) => {
+                Err(stale_epoch_error(fence.as_ref(), current))
+            }
+            Ok(Ok((AppResponse::Error { message }, _))) => {
+                Err(ScribeError::Consensus(format!("Write failed: {}", message)))
+            }
+            Ok(Err(e)) => Err(consensus_error(e, "Consensus error")),
+            Err(_) => Err(ScribeError::Consensus("Write timeout".to_string())),
+            _ => Err(ScribeError::Consensus("Unexpected response".to_string())),
+        }
+    }
+
+    /// Put a key-value pair that expires after `ttl`
+    ///
+    /// The expiry deadline is fixed when the write is proposed; once it passes the
+    /// leader removes the key and subscribers receive an `Expire` change event.
+    pub async fn put_with_ttl(
+        &self,
+        key: Key,
+        value: Value,
+        ttl: Duration,
+    ) -> Result<ConsistencyToken> {
+        self.hot_keys.record(&key, KeyOp::Write);
+        let (_, expires_at) = lease_window(ttl)?;
+        self.propose_put_expiring(key, value, expires_at, "put_ttl")
+            .await
+    }
+
+    /// Propose a put that expires at `expires_at` (milliseconds since UNIX epoch)
+    async fn propose_put_expiring(
+        &self,
+        key: Key,
+        value: Value,
+        expires_at: u64,
+        operation: &str,
+    ) -> Result<ConsistencyToken> {
+        let started = Instant::now();
+        let request = AppRequest::PutWithTtl {
+            key: key.clone(),
+            value: value.clone(),
+            expires_at,
+        };
+
+        // Execute write with timeout
+        let result = timeout(
+            self.write_timeout,
+            self.consensus.client_write_indexed(request),
+        )
+        .await;
+        observe_api_latency(operation, started);
+
+        match result {
+            Ok(Ok((AppResponse::PutOk, index))) => {
+                self.mirror(ShadowOp::Put {
+                    key: key.clone(),
+                    value,
+                });
+                // Cached copies must not outlive the TTL, so don't cache
+                self.cache.remove(&key);
+                Ok(ConsistencyToken::new(index))
+            }
+            Ok(Ok((AppResponse::Error { message }, _))) => {
                 Err(ScribeError::Consensus(format!("Write failed: {}", message)))
             }
-            Ok(Err(e)) => Err(ScribeError::Consensus(format!("Consensus error: {}", e))),
+            Ok(Err(e)) => Err(consensus_error(e, "Consensus error")),
             Err(_) => Err(ScribeError::Consensus("Write timeout".to_string())),
             _ => Err(ScribeError::Consensus("Unexpected response".to_string())),
         }
     }
 
     /// Delete a key with timeout and automatic forwarding
-    pub async fn delete(&self, key: Key) -> Result<()> {
-        let request = AppRequest::Delete { key: key.clone() };
+    ///
+    /// Returns the consistency token of the delete.
+    pub async fn delete(&self, key: Key) -> Result<ConsistencyToken> {
+        self.propose_delete(key, None).await
+    }
+
+    /// Delete a key on behalf of a writer holding `fence`
+    ///
+    /// Fails with `ScribeError::Conflict` if the namespace has moved past the fence's
+    /// epoch.
+    pub async fn delete_fenced(&self, key: Key, fence: Fence) -> Result<ConsistencyToken> {
+        self.propose_delete(key, Some(fence)).await
+    }
+
+    async fn propose_delete(&self, key: Key, fence: Option<Fence>) -> Result<ConsistencyToken> {
+        let started = Instant::now();
+        self.hot_keys.record(&key, KeyOp::Delete);
+        let request = fence_request(fence.clone(), AppRequest::Delete { key: key.clone() });
 
         // Execute delete with timeout
-        let result = timeout(self.write_timeout, self.consensus.client_write(request)).await;
+        let result = timeout(
+            self.write_timeout,
+            self.consensus.client_write_indexed(request),
+        )
+        .await;
+        observe_api_latency("delete", started);
 
         match result {
-            Ok(Ok(AppResponse::DeleteOk)) => {
+            Ok(Ok((AppResponse::DeleteOk, index))) => {
                 // Remove from cache
                 self.cache.remove(&key);
-                Ok(())
+                self.mirror(ShadowOp::Delete { key });
+                Ok(ConsistencyToken::new(index))
+            }
+            Ok(Ok((AppResponse::StaleEpoch { current }, _))) => {
+                Err(stale_epoch_error(fence.as_ref(), current))
             }
-            Ok(Ok(AppResponse::Error { message })) => Err(ScribeError::Consensus(format!(
+            Ok(Ok((AppResponse::Error { message }, _))) => Err(ScribeError::Consensus(format!(
                 "Delete failed: {}",
                 message
             ))),
-            Ok(Err(e)) => Err(ScribeError::Consensus(format!("Consensus error: {}", e))),
+            Ok(Err(e)) => Err(consensus_error(e, "Consensus error")),
             Err(_) => Err(ScribeError::Consensus("Delete timeout".to_string())),
             _ => Err(ScribeError::Consensus("Unexpected response".to_string())),
         }
     }
 
+    /// Store `value` at `key` and return the value it replaced
+    ///
+    /// Read and write happen in one log entry, so two concurrent callers never both
+    /// see the same previous value. A value whose TTL elapsed counts as absent; the
+    /// new value has no TTL. Fails for keys demoted to cold storage. Returns the
+    /// previous value and the consistency token of the write.
+    pub async fn get_and_set(
+        &self,
+        key: Key,
+        value: Value,
+    ) -> Result<(Option<Value>, ConsistencyToken)> {
+        self.hot_keys.record(&key, KeyOp::Write);
+        let request = AppRequest::GetAndSet {
+            key: key.clone(),
+            value: value.clone(),
+            now: now_millis()?,
+        };
+        let (previous, token) = self.propose_swap(request, "get_and_set").await?;
+
+        self.mirror(ShadowOp::Put {
+            key: key.clone(),
+            value: value.clone(),
+        });
+        self.cache.put(key, value);
+        Ok((previous, token))
+    }
+
+    /// Delete `key` and return the value it held
+    ///
+    /// Like [`DistributedApi::get_and_set`], at most one of several concurrent callers
+    /// receives the value, which makes a key usable as a single-consumer slot.
+    /// Returns the previous value and the consistency token of the delete.
+    pub async fn get_and_delete(&self, key: Key) -> Result<(Option<Value>, ConsistencyToken)> {
+        self.hot_keys.record(&key, KeyOp::Delete);
+        let request = AppRequest::GetAndDelete {
+            key: key.clone(),
+            now: now_millis()?,
+        };
+        let (previous, token) = self.propose_swap(request, "get_and_delete").await?;
+
+        self.cache.remove(&key);
+        self.mirror(ShadowOp::Delete { key });
+        Ok((previous, token))
+    }
+
+    /// Propose a get-and-set or get-and-delete and return the previous value
+    async fn propose_swap(
+        &self,
+        request: AppRequest,
+        operation: &str,
+    ) -> Result<(Option<Value>, ConsistencyToken)> {
+        let started = Instant::now();
+        let result = timeout(
+            self.write_timeout,
+            self.consensus.client_write_indexed(request),
+        )
+        .await;
+        observe_api_latency(operation, started);
+
+        match result {
+            Ok(Ok((AppResponse::Previous { value }, index))) => {
+                Ok((value, ConsistencyToken::new(index)))
+            }
+            Ok(Ok((AppResponse::Error { message }, _))) => {
+                Err(ScribeError::Consensus(format!("Write failed: {}", message)))
+            }
+            Ok(Err(e)) => Err(consensus_error(e, "Consensus error")),
+            Err(_) => Err(ScribeError::Consensus("Write timeout".to_string())),
+            _ => Err(ScribeError::Consensus("Unexpected response".to_string())),
+        }
+    }
+
+    /// Replace the value of `key` with `new` if it still holds `expected`
+    ///
+    /// `None` stands for an absent key, both as the expected value and as the new
+    /// one, which deletes the key. The comparison and the write happen in one log
+    /// entry, so of several concurrent callers expecting the same value only one
+    /// succeeds. A value whose TTL elapsed counts as absent; a new value has no TTL.
+    /// Fails for keys demoted to cold storage. Returns whether the swap took place
+    /// and the consistency token of the entry.
+    pub async fn compare_and_swap(
+        &self,
+        key: Key,
+        expected: Option<Value>,
+        new: Option<Value>,
+    ) -> Result<(bool, ConsistencyToken)> {
+        let started = Instant::now();
+        let op = if new.is_some() {
+            KeyOp::Write
+        } else {
+            KeyOp::Delete
+        };
+        self.hot_keys.record(&key, op);
+        let request = AppRequest::CompareAndSwap {
+            key: key.clone(),
+            expected,
+            new: new.clone(),
+            now: now_millis()?,
+        };
+
+        // Execute write with timeout
+        let result = timeout(
+            self.write_timeout,
+            self.consensus.client_write_indexed(request),
+        )
+        .await;
+        observe_api_latency("compare_and_swap", started);
+
+        match result {
+            Ok(Ok((AppResponse::CasOk { swapped, .. }, index))) => {
+                self.contention.record_cas(&key, swapped);
+                match new {
+                    Some(value) if swapped => {
+                        self.mirror(ShadowOp::Put {
+                            key: key.clone(),
+                            value: value.clone(),
+                        });
+                        self.cache.put(key, value);
+                    }
+                    None if swapped => {
+                        self.cache.remove(&key);
+                        self.mirror(ShadowOp::Delete { key });
+                    }
+                    // The cached value may be what made the swap fail
+                    _ => {
+                        self.cache.remove(&key);
+                    }
+                }
+                Ok((swapped, ConsistencyToken::new(index)))
+            }
+            Ok(Ok((AppResponse::Error { message }, _))) => {
+                Err(ScribeError::Consensus(format!("Write failed: {}", message)))
+            }
+            Ok(Err(e)) => Err(consensus_error(e, "Consensus error")),
+            Err(_) => Err(ScribeError::Consensus("Write timeout".to_string())),
+            _ => Err(ScribeError::Consensus("Unexpected response".to_string())),
+        }
+    }
+
+    /// Store `value` at `key` unless the key already exists
+    ///
+    /// See [`DistributedApi::compare_and_swap`]. Returns whether the value was stored
+    /// and the consistency token of the entry.
+    pub async fn put_if_absent(&self, key: Key, value: Value) -> Result<(bool, ConsistencyToken)> {
+        self.compare_and_swap(key, None, Some(value)).await
+    }
+
+    /// Apply an RFC 7386 merge patch to the JSON document at `key`
+    ///
+    /// The patch is executed by the state machine (read current, merge, write) as a
+    /// single log entry, so concurrent patches never lose each other's updates. A
+    /// missing key is created from the patch. Fails with `ScribeError::Serialization`
+    /// if the stored value is not JSON. Returns the merged document and the
+    /// consistency token of the write.
+    pub async fn patch_json(
+        &self,
+        key: Key,
+        patch: &serde_json::Value,
+    ) -> Result<(Value, ConsistencyToken)> {
+        let started = Instant::now();
+        self.hot_keys.record(&key, KeyOp::Write);
+        let request = AppRequest::JsonMergePatch {
+            key: key.clone(),
+            patch: serde_json::to_vec(patch)?,
+        };
+
+        // Execute write with timeout
+        let result = timeout(
+            self.write_timeout,
+            self.consensus.client_write_indexed(request),
+        )
+        .await;
+        observe_api_latency("patch", started);
+
+        match result {
+            Ok(Ok((AppResponse::PatchOk { value }, index))) => {
+                // Mirror the merged document, the shadow may not hold the original
+                self.mirror(ShadowOp::Put {
+                    key: key.clone(),
+                    value: value.clone(),
+                });
+                self.cache.put(key, value.clone());
+                Ok((value, ConsistencyToken::new(index)))
+            }
+            Ok(Ok((AppResponse::Error { message }, _))) => Err(ScribeError::Serialization(
+                format!("Patch failed: {}", message),
+            )),
+            Ok(Err(e)) => Err(consensus_error(e, "Consensus error")),
+            Err(_) => Err(ScribeError::Consensus("Write timeout".to_string())),
+            _ => Err(ScribeError::Consensus("Unexpected response".to_string())),
+        }
+    }
+
+    /// Atomically move the value at `from` to `to`
+    ///
+    /// Runs as a single state-machine operation (copy + tombstone `from`), replacing
+    /// any value at `to` and keeping the TTL. Both keys appear in the change history.
+    /// Fails with `ScribeError::NotFound` if `from` does not exist. Returns the
+    /// consistency token of the rename.
+    pub async fn rename(&self, from: Key, to: Key) -> Result<ConsistencyToken> {
+        let started = Instant::now();
+        self.hot_keys.record(&from, KeyOp::Delete);
+        self.hot_keys.record(&to, KeyOp::Write);
+        let request = AppRequest::Rename {
+            from: from.clone(),
+            to: to.clone(),
+        };
+
+        // Execute write with timeout
+        let result = timeout(
+            self.write_timeout,
+            self.consensus.client_write_indexed(request),
+        )
+        .await;
+        observe_api_latency("rename", started);
+
+        match result {
+            Ok(Ok((AppResponse::RenameOk { value: Some(value) }, index))) => {
+                self.cache.remove(&from);
+                self.cache.remove(&to);
+                if from != to {
+                    self.mirror(ShadowOp::Put { key: to, value });
+                    self.mirror(ShadowOp::Delete { key: from });
+                }
+                Ok(ConsistencyToken::new(index))
+            }
+            Ok(Ok((AppResponse::RenameOk { value: None }, _))) => Err(ScribeError::NotFound(
+                format!("Key '{}' does not exist", log_key(&from)),
+            )),
+            Ok(Ok((AppResponse::Error { message }, _))) => Err(ScribeError::Consensus(format!(
+                "Rename failed: {}",
+                message
+            ))),
+            Ok(Err(e)) => Err(consensus_error(e, "Consensus error")),
+            Err(_) => Err(ScribeError::Consensus("Write timeout".to_string())),
+            _ => Err(ScribeError::Consensus("Unexpected response".to_string())),
+        }
+    }
+
+    /// Run `f` as a transaction over several keys and commit it through Raft
+    ///
+    /// `f` reads values from the local state machine and buffers its writes, which
+    /// are then proposed as one log entry along with the version of every key read.
+    /// The entry is only applied if none of those keys changed in the meantime;
+    /// otherwise `f` runs again on fresh values, up to a bounded number of attempts
+    /// before failing with `ScribeError::Conflict`. Reading a key for the first time
+    /// also makes `f` run again once the key is loaded (see [`crate::transaction`]).
+    /// A transaction that only reads still commits a log entry, which is what
+    /// confirms its reads were consistent.
+    ///
+    /// Returns the value of the committed run and the consistency token of the
+    /// transaction.
+    pub async fn transaction<F, A>(&self, f: F) -> Result<(A, ConsistencyToken)>
+    where
+        F: Fn(&DistributedTxn) -> TxnResult<A>,
+    {
+        let started = Instant::now();
+        let result = self.run_transaction(f).await;
+        observe_api_latency("transaction", started);
+        result
+    }
+
+    /// Run `f` until a run of it commits
+    async fn run_transaction<F, A>(&self, f: F) -> Result<(A, ConsistencyToken)>
+    where
+        F: Fn(&DistributedTxn) -> TxnResult<A>,
+    {
+        let txn = DistributedTxn::new();
+        let mut attempts = 0;
+        loop {
+            txn.begin();
+            let outcome = f(&txn);
+
+            let missing = txn.missing();
+            if !missing.is_empty() {
+                for key in missing {
+                    let (stored, version) = self.consensus.lookup_versioned_local(&key).await;
+                    let value = self.resolve(&key, stored, ARCHIVE_TIERS).await?;
+                    txn.load(key, value.map(|(value, _)| value), version);
+                }
+                continue;
+            }
+
+            let value = match outcome {
+                Ok(value) => Some(value),
+                Err(ConflictableTransactionError::Abort(e)) => return Err(e),
+                Err(ConflictableTransactionError::Storage(e)) => return Err(e.into()),
+                Err(_) => None,
+            };
+            if attempts == MAX_TXN_ATTEMPTS {
+                return Err(ScribeError::Conflict(format!(
+                    "Transaction still conflicts after {} attempts",
+                    attempts
+                )));
+            }
+            attempts += 1;
+            let Some(value) = value else {
+                // `f` reported a conflict of its own; retry on fresh values
+                txn.reload();
+                continue;
+            };
+
+            let (reads, writes) = txn.operations();
+            for read in &reads {
+                self.hot_keys.record(&read.key, KeyOp::Read);
+            }
+            for write in &writes {
+                let op = match write.value {
+                    Some(_) => KeyOp::Write,
+                    None => KeyOp::Delete,
+                };
+                self.hot_keys.record(&write.key, op);
+            }
+            let touched: Vec<Key> = reads
+                .iter()
+                .map(|read| read.key.clone())
+                .chain(writes.iter().map(|write| write.key.clone()))
+                .collect();
+            let request = AppRequest::Transaction {
+                reads,
+                writes: writes.clone(),
+            };
+
+            // Execute write with timeout
+            let result = timeout(
+                self.write_timeout,
+                self.consensus.client_write_indexed(request),
+            )
+            .await;
+
+            match result {
+                Ok(Ok((AppResponse::TxnOk, index))) => {
+                    self.contention
+                        .record_transaction(touched.iter().map(Vec::as_slice), None);
+                    for write in writes {
+                        match write.value {
+                            Some(value) => {
+                                self.mirror(ShadowOp::Put {
+                                    key: write.key.clone(),
+                                    value: value.clone(),
+                                });
+                                self.cache.put(write.key, value);
+                            }
+                            None => {
+                                self.cache.remove(&write.key);
+                                self.mirror(ShadowOp::Delete { key: write.key });
+                            }
+                        }
+                    }
+                    return Ok((value, ConsistencyToken::new(index)));
+                }
+                Ok(Ok((AppResponse::TxnConflict { key }, index))) => {
+                    self.contention.record_transaction(
+                        touched.iter().map(Vec::as_slice),
+                        Some(key.as_slice()),
+                    );
+                    // Retry on the values that won, once this node has applied them
+                    self.consensus
+                        .wait_for_applied(index, DEFAULT_MIN_APPLIED_WAIT)
+                        .await?;
+                    txn.reload();
+                }
+                Ok(Ok((AppResponse::Error { message }, _))) => {
+                    return Err(ScribeError::Consensus(format!(
+                        "Transaction failed: {}",
+                        message
+                    )));
+                }
+                Ok(Err(e)) => return Err(consensus_error(e, "Consensus error")),
+                Err(_) => return Err(ScribeError::Consensus("Write timeout".to_string())),
+                _ => return Err(ScribeError::Consensus("Unexpected response".to_string())),
+            }
+        }
+    }
+
     /// Get a value by key with specified consistency level
     ///
     /// This method provides two consistency levels:
-    /// - Linearizable: Reads the latest committed data from the leader
+    /// - Linearizable: Reads the latest committed data, on the leader or on a
+    ///   follower that has caught up with the leader's commit index
     /// - Stale: Reads from local state machine (may be slightly outdated)
     ///
     /// Both modes use the cache for performance optimization.
     pub async fn get(&self, key: Key, consistency: ReadConsistency) -> Result<Option<Value>> {
+        Ok(self
+            .get_tiered(key, consistency)
+            .await?
+            .map(|(value, _)| value))
+    }
+
+    /// Get a value and the storage tier it was read from
+    ///
+    /// The tiers are consulted in the order configured for the key's namespace (see
+    /// [`crate::read_path`]). Values demoted to an archived segment are fetched from
+    /// S3 transparently, at a higher latency, and are not added to the hot data cache;
+    /// if the order leaves out every tier holding the segment, the key is reported as
+    /// not found.
+    pub async fn get_tiered(
+        &self,
+        key: Key,
+        consistency: ReadConsistency,
+    ) -> Result<Option<(Value, StorageTier)>> {
+        let started = Instant::now();
+        self.hot_keys.record(&key, KeyOp::Read);
+        let order = self.read_path.order_for(&key);
+
         // Try cache first for stale reads
-        if consistency == ReadConsistency::Stale {
-            if let Some(value) = self.cache.get(&key) {
-                return Ok(Some(value));
+        if consistency == ReadConsistency::Stale && order.contains(&ReadTier::Cache) {
+            let cache_started = Instant::now();
+            let cached = self.cache.get(&key);
+            observe_read_tier(ReadTier::Cache.as_str(), cached.is_some(), cache_started);
+            if let Some(value) = cached {
+                observe_api_latency("get_stale", started);
+                return Ok(Some((value, StorageTier::Hot)));
             }
         }
 
-        let result = match consistency {
+        let local_started = Instant::now();
+        let stored = match consistency {
             ReadConsistency::Linearizable => self.get_linearizable(key.clone()).await,
             ReadConsistency::Stale => self.get_stale(key.clone()).await,
         };
+        observe_local_tier(&stored, local_started);
+        let result = match stored {
+            Ok(stored) => self.resolve(&key, stored, order).await,
+            Err(e) => Err(e),
+        };
+        let operation = match consistency {
+            ReadConsistency::Linearizable => "get_linearizable",
+            ReadConsistency::Stale => "get_stale",
+        };
+        observe_api_latency(operation, started);
 
-        // Update cache on successful read
-        if let Ok(Some(ref value)) = result {
-            self.cache.put(key, value.clone());
+        // Update cache on successful read; cached copies must not outlive a TTL,
+        // so values with one are not cached
+        if let Ok(Some((ref value, StorageTier::Hot))) = result {
+            if self.consensus.expires_at_local(&key).await.is_none() {
+                self.cache.put(key, value.clone());
+            }
         }
 
         result
     }
 
-    /// Get a value with linearizable consistency (from leader only)
-    async fn get_linearizable(&self, key: Key) -> Result<Option<Value>> {
+    /// Turn a state machine lookup into a value, reading demoted values from the
+    /// segment tiers of `order`
+    async fn resolve(
+        &self,
+        key: &[u8],
+        stored: Option<StoredValue>,
+        order: &[ReadTier],
+    ) -> Result<Option<(Value, StorageTier)>> {
+        let segment_id = match stored {
+            None => return Ok(None),
+            Some(StoredValue::Hot(value)) => return Ok(Some((value, StorageTier::Hot))),
+            Some(StoredValue::Cold(segment_id)) => segment_id,
+        };
+
+        for tier in order {
+            let started = Instant::now();
+            let value = match tier {
+                ReadTier::Segments => match self.archival_for(key, segment_id) {
+                    Ok(archival) => archival.local_value(segment_id, key).await,
+                    Err(e) => Err(e),
+                },
+                ReadTier::S3 => self.read_archived(key, segment_id).await.map(Some),
+                ReadTier::Cache | ReadTier::Local => continue,
+            };
+            observe_read_tier(tier.as_str(), matches!(value, Ok(Some(_))), started);
+            if let Some(value) = value? {
+                return Ok(Some((value, StorageTier::Cold)));
+            }
+        }
+
+        // None of the tiers this namespace may read holds the archived segment
+        Ok(None)
+    }
+
+    /// Archival manager holding the segment `key` was demoted to
+    fn archival_for(&self, key: &[u8], segment_id: SegmentId) -> Result<&ArchivalManager> {
+        self.archival.as_deref().ok_or_else(|| {
+            ScribeError::Storage(format!(
+                "Key '{}' is archived in segment {} but S3 archival is not configured",
+                log_key(key),
+                segment_id
+            ))
+        })
+    }
+
+    /// Read the value of `key` from the archived segment `segment_id` in S3
+    async fn read_archived(&self, key: &[u8], segment_id: SegmentId) -> Result<Value> {
+        let segment = self
+            .archival_for(key, segment_id)?
+            .retrieve_segment(segment_id)
+            .await?
+            .ok_or_else(|| {
+                ScribeError::Storage(format!("Archived segment {} not found", segment_id))
+            })?;
+        match segment.get(&key.to_vec()) {
+            Some(value) => Ok(value.clone()),
+            None => Err(ScribeError::Storage(format!(
+                "Archived segment {} does not contain key '{}'",
+                segment_id,
+                log_key(key)
+            ))),
+        }
+    }
+
+    /// Get a value with linearizable consistency (leader, or follower via read index)
+    async fn get_linearizable(&self, key: Key) -> Result<Option<StoredValue>> {
         // Execute read with timeout
         let result = timeout(
             DEFAULT_READ_TIMEOUT,
-            self.consensus.client_read(key.as_slice()),
+            self.consensus.client_lookup(key.as_slice()),
         )
         .await;
 
         match result {
             Ok(Ok(value)) => Ok(value),
-            Ok(Err(e)) => Err(ScribeError::Consensus(format!("Read error: {}", e))),
+            Ok(Err(e)) => Err(consensus_error(e, "Read error")),
             Err(_) => Err(ScribeError::Consensus("Read timeout".to_string())),
         }
     }
 
     /// Get a value with stale consistency (from local state machine)
-    async fn get_stale(&self, key: Key) -> Result<Option<Value>> {
+    async fn get_stale(&self, key: Key) -> Result<Option<StoredValue>> {
         // Read from local state machine (no timeout needed, it's a local operation)
-        Ok(self.consensus.client_read_local(key.as_slice()).await)
+        Ok(self.consensus.client_lookup_local(key.as_slice()).await)
     }
 
-    /// Get a value with default linearizable consistency
-    pub async fn get_default(&self, key: Key) -> Result<Option<Value>> {
-        self.get(key, ReadConsistency::Linearizable).await
+    /// Get a value once this node has applied the write identified by `token`
+    ///
+    /// Waits (bounded) for the local state machine to catch up, then reads it
+    /// directly, bypassing the cache which may lag behind applied writes. Fails with
+    /// `ScribeError::Timeout` if this node does not catch up in time.
+    pub async fn get_at_least(&self, key: Key, token: ConsistencyToken) -> Result<Option<Value>> {
+        Ok(self
+            .get_at_least_tiered(key, token)
+            .await?
+            .map(|(value, _)| value))
     }
 
-    /// Batch write multiple key-value pairs
+    /// Like [`DistributedApi::get_at_least`], also returning the storage tier
+    pub async fn get_at_least_tiered(
+        &self,
+        key: Key,
+        token: ConsistencyToken,
+    ) -> Result<Option<(Value, StorageTier)>> {
+        self.hot_keys.record(&key, KeyOp::Read);
+        self.consensus
+            .wait_for_applied(token.applied_index(), DEFAULT_MIN_APPLIED_WAIT)
+            .await?;
+        let started = Instant::now();
+        let stored = self.get_stale(key.clone()).await;
+        observe_local_tier(&stored, started);
+        self.resolve(&key, stored?, self.read_path.order_for(&key))
+            .await
+    }
+
+    /// Demote the current value of `key` to the archived segment `segment_id`
     ///
-    /// This method batches multiple writes into a single Raft proposal when possible.
-    /// If the batch size exceeds max_batch_size, it will be split into multiple proposals.
-    pub async fn put_batch(&self, items: Vec<(Key, Value)>) -> Result<Vec<Result<()>>> {
-        if items.is_empty() {
-            return Ok(vec![]);
+    /// The segment must already hold exactly the current value; this is checked
+    /// against S3 before the in-memory copy is dropped cluster-wide. Reads then fetch
+    /// the value from the segment. Returns `false` if the key was written
+    /// concurrently, in which case it stays in memory.
+    pub async fn demote(&self, key: Key, segment_id: SegmentId) -> Result<bool> {
+        let value = match self.get_stale(key.clone()).await? {
+            Some(StoredValue::Hot(value)) => value,
+            Some(StoredValue::Cold(current)) if current == segment_id => return Ok(true),
+            Some(StoredValue::Cold(current)) => {
+                return Err(ScribeError::Storage(format!(
+                    "Key '{}' is already archived in segment {}",
+                    log_key(&key),
+                    current
+                )))
+            }
+            None => {
+                return Err(ScribeError::NotFound(format!(
+                    "Key '{}' does not exist",
+                    log_key(&key)
+                )))
+            }
+        };
+
+        let archived = self.read_archived(&key, segment_id).await?;
+        if archived != value {
+            return Err(ScribeError::Storage(format!(
+                "Archived segment {} does not hold the current value of '{}'",
+                segment_id,
+                log_key(&key)
+            )));
         }
 
-        let mut results = Vec::with_capacity(items.len());
+        let request = AppRequest::demote(key.clone(), segment_id, &value);
+        let result = timeout(
+            self.write_timeout,
+            self.consensus.client_write_indexed(request),
+        )
+        .await;
 
-        // Process items in batches
-        for chunk in items.chunks(self.max_batch_size) {
-            for (key, value) in chunk {
-                let result = self.put(key.clone(), value.clone()).await;
-                results.push(result);
+        match result {
+            Ok(Ok((AppResponse::DemoteOk { demoted }, _))) => {
+                if demoted {
+                    self.cache.remove(&key);
+                }
+                Ok(demoted)
             }
+            Ok(Err(e)) => Err(consensus_error(e, "Consensus error")),
+            Err(_) => Err(ScribeError::Consensus("Write timeout".to_string())),
+            _ => Err(ScribeError::Consensus("Unexpected response".to_string())),
         }
-
-        Ok(results)
     }
 
-    /// Check if this node is the leader
-    pub async fn is_leader(&self) -> bool {
-        self.consensus.is_leader().await
-    }
+    /// Archive values not modified for `idle_for` to S3 and demote them
+    ///
+    /// Up to `limit` idle keys held in memory are written to the segments of their
+    /// storage class. Every flushed segment is then archived with its manifest entry,
+    /// its Merkle root anchored (see [`DistributedApi::anchor_segment`]) and the
+    /// segment dropped locally, and the keys it holds are demoted (see
+    /// [`DistributedApi::demote`]), so reads fetch them from S3. Keys written in the
+    /// meantime stay in memory, as do keys under the reserved `__` prefix. A segment
+    /// that fails to archive stays local and is retried by the next run.
+    pub async fn archive_idle(&self, idle_for: Duration, limit: usize) -> Result<ArchivalRun> {
+        let archival = self
+            .archival
+            .as_deref()
+            .ok_or_else(|| ScribeError::Storage("S3 archival is not configured".to_string()))?;
+        let cutoff = now_millis()?.saturating_sub(idle_for.as_millis() as u64);
+        let mut run = ArchivalRun::default();
+        for change in self.consensus.changes_between(0, cutoff).await {
+            if run.keys == limit {
+                break;
+            }
+            if change.deleted || change.timestamp > cutoff || change.key.starts_with(b"__") {
+                continue;
+            }
+            if let Some(StoredValue::Hot(value)) = self.get_stale(change.key.clone()).await? {
+                archival.put(change.key, value)?;
+                run.keys += 1;
+            }
+        }
+
+        let mut failures = Vec::new();
+        for segments in archival.segment_managers() {
+            segments.flush_active()?;
+            for segment in segments.get_flushed_segments()? {
+                if let Err(e) = archival.archive_segment(&segment).await {
+                    failures.push(format!("segment {}: {}", segment.segment_id, e));
+                    continue;
+                }
+                // Demoted keys must be provable against a committed root
+                if let Err(e) = self.anchor_segment(&segment).await {
+                    failures.push(format!("segment {}: {}", segment.segment_id, e));
+                    continue;
+                }
+                for key in segment.data.keys() {
+                    match self.demote(key.clone(), segment.segment_id).await {
+                        Ok(true) => run.demoted += 1,
+                        // Written, deleted or archived again since: it stays where it is
+                        Ok(false) | Err(ScribeError::Storage(_) | ScribeError::NotFound(_)) => {}
+                        // Keep the segment so the next run demotes its keys
+                        Err(e) => return Err(e),
+                    }
+                }
+                segments.remove_flushed(&[segment.segment_id])?;
+                run.segments += 1;
+            }
+        }
+
+        if !failures.is_empty() {
+            return Err(ScribeError::Storage(format!(
+                "Failed to archive {} segment(s), archived {}: {}",
+                failures.len(),
+                run.segments,
+                failures.join("; ")
+            )));
+        }
+        Ok(run)
+    }
+
+    /// Commit the Merkle root of the archived `segment` through Raft
+    ///
+    /// The Raft index of the anchor is recorded in the archival manifest. Anchoring a
+    /// segment again returns its first anchor; fails with `ScribeError::Conflict` if
+    /// that anchor holds a different root.
+    pub async fn anchor_segment(&self, segment: &Segment) -> Result<SegmentAnchor> {
+        let merkle_root = segment.compute_merkle_root().ok_or_else(|| {
+            ScribeError::Storage(format!("Segment {} is empty", segment.segment_id))
+        })?;
+        let request = AppRequest::AnchorSegment {
+            segment_id: segment.segment_id,
+            merkle_root: merkle_root.clone(),
+            key_count: segment.data.len(),
+            now: now_millis()?,
+        };
+        let result = timeout(
+            self.write_timeout,
+            self.consensus.client_write_indexed(request),
+        )
+        .await;
+
+        let anchor = match result {
+            Ok(Ok((AppResponse::SegmentAnchored { anchor }, _))) => anchor,
+            Ok(Ok((AppResponse::AnchorExists { anchor }, _))) => {
+                if anchor.merkle_root != hex::encode(&merkle_root) {
+                    return Err(ScribeError::Conflict(format!(
+                        "Segment {} is anchored with Merkle root {} at Raft index {}",
+                        anchor.segment_id, anchor.merkle_root, anchor.raft_index
+                    )));
+                }
+                anchor
+            }
+            Ok(Err(e)) => return Err(consensus_error(e, "Consensus error")),
+            Err(_) => return Err(ScribeError::Consensus("Write timeout".to_string())),
+            _ => return Err(ScribeError::Consensus("Unexpected response".to_string())),
+        };
+
+        if let Some(manifest) = self.archival.as_deref().and_then(|a| a.manifest()) {
+            manifest
+                .anchor_segment(anchor.segment_id, anchor.raft_index)
+                .await?;
+        }
+        Ok(anchor)
+    }
+
+    /// Prove the current value of `key` against the committed root of its segment
+    ///
+    /// Reads the key linearizably and the archived segment from S3. Only values
+    /// demoted to an anchored segment can be proven: fails with
+    /// `ScribeError::Conflict` for a value still held in memory or demoted to a
+    /// segment that was never anchored. Returns `None` if the key does not exist.
+    pub async fn prove_key(&self, key: Key) -> Result<Option<KeyProof>> {
+        let segment_id = match self.get_linearizable(key.clone()).await? {
+            None => return Ok(None),
+            Some(StoredValue::Hot(_)) => {
+                return Err(ScribeError::Conflict(format!(
+                    "Key '{}' is held in memory and not part of an anchored segment yet",
+                    log_key(&key)
+                )))
+            }
+            Some(StoredValue::Cold(segment_id)) => segment_id,
+        };
+        let anchor = self
+            .consensus
+            .segment_anchor_local(segment_id)
+            .await
+            .ok_or_else(|| {
+                ScribeError::Conflict(format!(
+                    "Segment {} holding '{}' was never anchored",
+                    segment_id,
+                    log_key(&key)
+                ))
+            })?;
+
+        let archival = self.archival_for(&key, segment_id)?;
+        let segment = archival
+            .retrieve_segment(segment_id)
+            .await?
+            .ok_or_else(|| {
+                ScribeError::Storage(format!("Archived segment {} not found", segment_id))
+            })?;
+        let proof = MerkleTree::from_pairs(segment.data.into_iter().collect())
+            .get_proof(&key)
+            .ok_or_else(|| {
+                ScribeError::DataCorruption(format!(
+                    "Archived segment {} does not hold '{}'",
+                    segment_id,
+                    log_key(&key)
+                ))
+            })?;
+        let manifest_anchor_index = match archival.manifest() {
+            Some(manifest) => manifest
+                .get_segment(segment_id)
+                .await
+                .and_then(|entry| entry.anchor_index),
+            None => None,
+        };
+
+        let anchored_root = hex::decode(&anchor.merkle_root).map_err(|e| {
+            ScribeError::DataCorruption(format!(
+                "Anchor of segment {} holds an invalid root: {}",
+                segment_id, e
+            ))
+        })?;
+
+        let computed_root = proof.root_hash().map(hex::encode);
+        Ok(Some(KeyProof {
+            key: String::from_utf8_lossy(&key).into_owned(),
+            segment_id,
+            leaf: hex::encode(proof.leaf_hash()),
+            siblings: proof.siblings.iter().map(hex::encode).collect(),
+            directions: proof.directions.clone(),
+            verified: computed_root.as_ref() == Some(&anchor.merkle_root),
+            computed_root,
+            anchor,
+            manifest_anchor_index,
+            bundle: ProofBundle::new(proof, anchored_root),
+        }))
+    }
+
+    /// Acquire the lock `name` for `holder`, leased for `ttl`
+    ///
+    /// Returns the lease and its fencing token; send the token with every action the
+    /// lock guards so that other systems can reject a holder whose lease ran out.
+    /// Acquiring again as the current holder extends the lease and keeps the token.
+    /// Fails with `ScribeError::Conflict` while another holder's lease is valid.
+    pub async fn acquire_lock(&self, name: &str, holder: &str, ttl: Duration) -> Result<LockLease> {
+        let started = Instant::now();
+        let (_, expires_at) = lease_window(ttl)?;
+        let now = self.lease_clock()?;
+        let request = AppRequest::AcquireLock {
+            name: name.to_string(),
+            holder: holder.to_string(),
+            now,
+            expires_at,
+        };
+        let result = self.propose_lock(name, request).await;
+        observe_api_latency("lock_acquire", started);
+        result
+    }
+
+    /// Extend the lease with fencing token `token` to `ttl` from now
+    ///
+    /// Fails with `ScribeError::Conflict` if the lease expired or was released.
+    pub async fn renew_lock(&self, name: &str, token: u64, ttl: Duration) -> Result<LockLease> {
+        let started = Instant::now();
+        let (_, expires_at) = lease_window(ttl)?;
+        let now = self.lease_clock()?;
+        let request = AppRequest::RenewLock {
+            name: name.to_string(),
+            token,
+            now,
+            expires_at,
+        };
+        let result = self.propose_lock(name, request).await;
+        observe_api_latency("lock_renew", started);
+        result
+    }
+
+    /// Release the lease with fencing token `token`
+    ///
+    /// Returns `false` if the token no longer holds the lock.
+    pub async fn release_lock(&self, name: &str, token: u64) -> Result<bool> {
+        let started = Instant::now();
+        let request = AppRequest::ReleaseLock {
+            name: name.to_string(),
+            token,
+        };
+        let result = timeout(
+            self.write_timeout,
+            self.consensus.client_write_indexed(request),
+        )
+        .await;
+        observe_api_latency("lock_release", started);
+
+        match result {
+            Ok(Ok((AppResponse::LockReleased { released }, _))) => Ok(released),
+            Ok(Err(e)) => Err(consensus_error(e, "Consensus error")),
+            Err(_) => Err(ScribeError::Consensus("Write timeout".to_string())),
+            _ => Err(ScribeError::Consensus("Unexpected response".to_string())),
+        }
+    }
+
+    /// Advance the write fencing epoch of `namespace`
+    ///
+    /// With `epoch`, the namespace moves to that epoch (a no-op if it is already
+    /// there); otherwise it moves up by one. Writes fenced with an older epoch are
+    /// rejected from then on. Returns the new epoch, or fails with
+    /// `ScribeError::Conflict` if `epoch` is older than the current one.
+    pub async fn advance_epoch(&self, namespace: &str, epoch: Option<u64>) -> Result<u64> {
+        let request = AppRequest::AdvanceEpoch {
+            namespace: namespace.to_string(),
+            epoch,
+        };
+        let result = timeout(
+            self.write_timeout,
+            self.consensus.client_write_indexed(request),
+        )
+        .await;
+
+        match result {
+            Ok(Ok((AppResponse::EpochOk { epoch }, _))) => Ok(epoch),
+            Ok(Ok((AppResponse::StaleEpoch { current }, _))) => Err(ScribeError::Conflict(
+                format!("Namespace '{}' is already at epoch {}", namespace, current),
+            )),
+            Ok(Err(e)) => Err(consensus_error(e, "Consensus error")),
+            Err(_) => Err(ScribeError::Consensus("Write timeout".to_string())),
+            _ => Err(ScribeError::Consensus("Unexpected response".to_string())),
+        }
+    }
+
+    /// Add usage deltas to the replicated quota usage of their API keys
+    ///
+    /// Must run on the leader; followers hand their deltas to it.
+    pub async fn record_usage(&self, usage: Vec<UsageDelta>) -> Result<()> {
+        if usage.is_empty() {
+            return Ok(());
+        }
+        let result = timeout(
+            self.write_timeout,
+            self.consensus
+                .client_write_indexed(AppRequest::RecordUsage { usage }),
+        )
+        .await;
+
+        match result {
+            Ok(Ok((AppResponse::UsageRecorded, _))) => Ok(()),
+            Ok(Err(e)) => Err(consensus_error(e, "Consensus error")),
+            Err(_) => Err(ScribeError::Consensus("Write timeout".to_string())),
+            _ => Err(ScribeError::Consensus("Unexpected response".to_string())),
+        }
+    }
+
+    /// Recorded quota usage of API key `key_id`, read from this node's state machine
+    pub async fn quota_usage(&self, key_id: &str) -> Option<KeyUsage> {
+        self.consensus.quota_usage_local(key_id).await
+    }
+
+    /// Recorded quota usage of every API key, read from this node's state machine
+    pub async fn quota_usages(&self) -> Vec<KeyUsage> {
+        self.consensus.quota_usages_local().await
+    }
+
+    /// Write fencing epoch of `namespace`, read from this node's state machine
+    pub async fn epoch(&self, namespace: &str) -> u64 {
+        self.consensus.epoch_local(namespace).await
+    }
+
+    /// Current lease on lock `name`, read from this node's state machine
+    pub async fn lock_info(&self, name: &str) -> Option<LockLease> {
+        self.consensus.lock_local(name).await
+    }
+
+    /// Propose a lock acquisition or renewal and turn a denial into a conflict
+    async fn propose_lock(&self, name: &str, request: AppRequest) -> Result<LockLease> {
+        let result = timeout(
+            self.write_timeout,
+            self.consensus.client_write_indexed(request),
+        )
+        .await;
+
+        match result {
+            Ok(Ok((AppResponse::LockOk { lease }, _))) => Ok(lease),
+            Ok(Ok((
+                AppResponse::LockDenied {
+                    current: Some(lease),
+                },
+                _,
+            ))) => Err(ScribeError::Conflict(format!(
+                "Lock '{}' is held by '{}' until {}",
+                name, lease.holder, lease.expires_at
+            ))),
+            Ok(Ok((AppResponse::LockDenied { current: None }, _))) => Err(ScribeError::Conflict(
+                format!("Lock '{}' is not held with this token", name),
+            )),
+            Ok(Err(e)) => Err(consensus_error(e, "Consensus error")),
+            Err(_) => Err(ScribeError::Consensus("Write timeout".to_string())),
+            _ => Err(ScribeError::Consensus("Unexpected response".to_string())),
+        }
+    }
+
+    /// Create a session holding `data` that expires after `idle_timeout` without a
+    /// touch
+    ///
+    /// The session is a TTL key under [`SESSION_KEY_PREFIX`] with a random ID. Touches
+    /// and reads push the expiry forward in the same log entry that checks the
+    /// session is still alive, so an expired session is never revived.
+    pub async fn create_session(
+        &self,
+        data: serde_json::Value,
+        idle_timeout: Duration,
+    ) -> Result<Session> {
+        let (created_at, expires_at) = lease_window(idle_timeout)?;
+        let id = new_session_id()?;
+        let record = SessionRecord {
+            data,
+            idle_timeout_ms: idle_timeout.as_millis() as u64,
+            created_at,
+        };
+        let value = serde_json::to_vec(&record)
+            .map_err(|e| ScribeError::Serialization(format!("Invalid session: {}", e)))?;
+        self.propose_put_expiring(session_key(&id), value, expires_at, "session_create")
+            .await?;
+        Ok(Session {
+            id,
+            data: record.data,
+            idle_timeout_ms: record.idle_timeout_ms,
+            created_at,
+            expires_at,
+        })
+    }
+
+    /// Push the expiry of session `id` to its idle timeout from now
+    ///
+    /// Returns the new expiry, or `None` if the session expired or never existed.
+    pub async fn touch_session(&self, id: &str) -> Result<Option<u64>> {
+        let session = self.propose_touch(id, "session_touch").await?;
+        Ok(session.map(|session| session.expires_at))
+    }
+
+    /// Read session `id`, touching it like [`DistributedApi::touch_session`]
+    ///
+    /// Every read goes through consensus, since it moves the expiry.
+    pub async fn get_session(&self, id: &str) -> Result<Option<Session>> {
+        self.propose_touch(id, "session_get").await
+    }
+
+    /// Destroy session `id`; returns `false` if it expired or never existed
+    pub async fn destroy_session(&self, id: &str) -> Result<bool> {
+        let (previous, _) = self.get_and_delete(session_key(id)).await?;
+        Ok(previous.is_some())
+    }
+
+    /// Time other holders' leases are checked against: now, less the clock skew
+    /// allowed for
+    fn lease_clock(&self) -> Result<u64> {
+        Ok(now_millis()?.saturating_sub(self.max_clock_skew.as_millis() as u64))
+    }
+
+    /// Propose a session touch and decode the session it returns
+    async fn propose_touch(&self, id: &str, operation: &str) -> Result<Option<Session>> {
+        let started = Instant::now();
+        let request = AppRequest::TouchSession {
+            key: session_key(id),
+            now: self.lease_clock()?,
+        };
+        let result = timeout(
+            self.write_timeout,
+            self.consensus.client_write_indexed(request),
+        )
+        .await;
+        observe_api_latency(operation, started);
+
+        match result {
+            Ok(Ok((
+                AppResponse::SessionTouched {
+                    value: Some(value),
+                    expires_at,
+                },
+                _,
+            ))) => {
+                let record: SessionRecord = serde_json::from_slice(&value).map_err(|e| {
+                    ScribeError::Serialization(format!("Invalid session record: {}", e))
+                })?;
+                Ok(Some(Session {
+                    id: id.to_string(),
+                    data: record.data,
+                    idle_timeout_ms: record.idle_timeout_ms,
+                    created_at: record.created_at,
+                    expires_at,
+                }))
+            }
+            Ok(Ok((AppResponse::SessionTouched { value: None, .. }, _))) => Ok(None),
+            Ok(Err(e)) => Err(consensus_error(e, "Consensus error")),
+            Err(_) => Err(ScribeError::Consensus("Write timeout".to_string())),
+            _ => Err(ScribeError::Consensus("Unexpected response".to_string())),
+        }
+    }
+
+    /// Append `payload` to `queue` and return the item's ID
+    ///
+    /// IDs increase with every enqueue, so items are delivered in enqueue order.
+    /// Fails with `ScribeError::Conflict` while the queue holds its capacity of
+    /// unacked items.
+    pub async fn enqueue(&self, queue: &str, payload: Value) -> Result<u64> {
+        let started = Instant::now();
+        let request = AppRequest::Enqueue {
+            queue: queue.to_string(),
+            payload,
+            capacity: self.queue_capacity,
+        };
+        let result = timeout(
+            self.write_timeout,
+            self.consensus.client_write_indexed(request),
+        )
+        .await;
+        observe_api_latency("queue_enqueue", started);
+
+        match result {
+            Ok(Ok((AppResponse::Enqueued { id }, _))) => Ok(id),
+            Ok(Ok((AppResponse::QueueFull { length }, _))) => Err(ScribeError::Conflict(format!(
+                "Queue '{}' is full ({} items)",
+                queue, length
+            ))),
+            Ok(Err(e)) => Err(consensus_error(e, "Consensus error")),
+            Err(_) => Err(ScribeError::Consensus("Write timeout".to_string())),
+            _ => Err(ScribeError::Consensus("Unexpected response".to_string())),
+        }
+    }
+
+    /// Take the oldest visible item of `queue`, leased for `visibility_timeout`
+    ///
+    /// The item stays in the queue, hidden from other consumers until the lease runs
+    /// out; unless it is acked by then, it is delivered again with a new receipt.
+    /// Returns `None` if no item is visible.
+    pub async fn dequeue(
+        &self,
+        queue: &str,
+        visibility_timeout: Duration,
+    ) -> Result<Option<QueueItem>> {
+        let started = Instant::now();
+        let (_, visible_until) = lease_window(visibility_timeout)?;
+        let now = self.lease_clock()?;
+        let request = AppRequest::Dequeue {
+            queue: queue.to_string(),
+            now,
+            visible_until,
+        };
+        let result = timeout(
+            self.write_timeout,
+            self.consensus.client_write_indexed(request),
+        )
+        .await;
+        observe_api_latency("queue_dequeue", started);
+
+        match result {
+            Ok(Ok((AppResponse::Dequeued { item }, _))) => Ok(item),
+            Ok(Err(e)) => Err(consensus_error(e, "Consensus error")),
+            Err(_) => Err(ScribeError::Consensus("Write timeout".to_string())),
+            _ => Err(ScribeError::Consensus("Unexpected response".to_string())),
+        }
+    }
+
+    /// Remove item `id` from `queue` once it has been processed
+    ///
+    /// `receipt` is the receipt of the delivery being acked. Returns `false` if the
+    /// item was already acked or was delivered again after its lease ran out.
+    pub async fn ack(&self, queue: &str, id: u64, receipt: u64) -> Result<bool> {
+        let started = Instant::now();
+        let request = AppRequest::Ack {
+            queue: queue.to_string(),
+            id,
+            receipt,
+        };
+        let result = timeout(
+            self.write_timeout,
+            self.consensus.client_write_indexed(request),
+        )
+        .await;
+        observe_api_latency("queue_ack", started);
+
+        match result {
+            Ok(Ok((AppResponse::Acked { acked }, _))) => Ok(acked),
+            Ok(Err(e)) => Err(consensus_error(e, "Consensus error")),
+            Err(_) => Err(ScribeError::Consensus("Write timeout".to_string())),
+            _ => Err(ScribeError::Consensus("Unexpected response".to_string())),
+        }
+    }
+
+    /// Length of `queue`, read from this node's state machine
+    pub async fn queue_stats(&self, queue: &str) -> QueueStats {
+        self.consensus.queue_stats_local(queue).await
+    }
+
+    /// Tag the current ledger state as `name`
+    ///
+    /// The tag records the Raft index it was created at, the Merkle root of the state
+    /// and, with S3 archival, this node's manifest version. The state itself is kept
+    /// so it can be verified and exported later, whatever is written afterwards. Tags
+    /// are immutable: fails with `ScribeError::Conflict` if the name is taken.
+    pub async fn create_tag(&self, name: &str) -> Result<LedgerTag> {
+        let started = Instant::now();
+        let manifest_version = match self.archival.as_ref().and_then(|a| a.manifest()) {
+            Some(manifest) => manifest.get_version().await,
+            None => 0,
+        };
+        let request = AppRequest::CreateTag {
+            name: name.to_string(),
+            manifest_version,
+            now: now_millis()?,
+        };
+        let result = timeout(
+            self.write_timeout,
+            self.consensus.client_write_indexed(request),
+        )
+        .await;
+        observe_api_latency("tag_create", started);
+
+        match result {
+            Ok(Ok((AppResponse::TagCreated { tag }, _))) => Ok(tag),
+            Ok(Ok((AppResponse::TagExists { tag }, _))) => Err(ScribeError::Conflict(format!(
+                "Tag '{}' already exists (Raft index {})",
+                name, tag.raft_index
+            ))),
+            Ok(Err(e)) => Err(consensus_error(e, "Consensus error")),
+            Err(_) => Err(ScribeError::Consensus("Write timeout".to_string())),
+            _ => Err(ScribeError::Consensus("Unexpected response".to_string())),
+        }
+    }
+
+    /// Delete tag `name`, releasing the state it captured
+    ///
+    /// Returns `false` if no such tag exists.
+    pub async fn delete_tag(&self, name: &str) -> Result<bool> {
+        let request = AppRequest::DeleteTag {
+            name: name.to_string(),
+        };
+        let result = timeout(
+            self.write_timeout,
+            self.consensus.client_write_indexed(request),
+        )
+        .await;
+
+        match result {
+            Ok(Ok((AppResponse::TagDeleted { deleted }, _))) => Ok(deleted),
+            Ok(Err(e)) => Err(consensus_error(e, "Consensus error")),
+            Err(_) => Err(ScribeError::Consensus("Write timeout".to_string())),
+            _ => Err(ScribeError::Consensus("Unexpected response".to_string())),
+        }
+    }
+
+    /// All tags, oldest first, read from this node's state machine
+    pub async fn tags(&self) -> Vec<LedgerTag> {
+        self.consensus.tags_local().await
+    }
+
+    /// Tag `name`, read from this node's state machine
+    pub async fn tag(&self, name: &str) -> Option<LedgerTag> {
+        self.consensus
+            .tagged_state_local(name)
+            .await
+            .map(|state| state.tag.clone())
+    }
+
+    /// Re-check tag `name` against the state it captured
+    ///
+    /// Recomputes the Merkle root of the captured state and reads the value of every
+    /// demoted key back from S3. Returns `None` if no such tag exists.
+    pub async fn verify_tag(&self, name: &str) -> Result<Option<TagVerification>> {
+        let Some(state) = self.consensus.tagged_state_local(name).await else {
+            return Ok(None);
+        };
+
+        let computed_root = state.merkle_root().map(hex::encode);
+        let mut unreadable_keys = Vec::new();
+        for (key, segment_id) in &state.cold {
+            if self.read_archived(key, *segment_id).await.is_err() {
+                unreadable_keys.push(log_key(key).to_string());
+            }
+        }
+
+        Ok(Some(TagVerification {
+            verified: computed_root == state.tag.merkle_root && unreadable_keys.is_empty(),
+            tag: state.tag.clone(),
+            computed_root,
+            unreadable_keys,
+        }))
+    }
+
+    /// Key-value pairs of the state captured by tag `name`, ordered by key
+    ///
+    /// Values of demoted keys are fetched from S3; the export fails if one cannot be
+    /// read. Returns `None` if no such tag exists.
+    pub async fn export_tag(&self, name: &str) -> Result<Option<Vec<(Key, Value)>>> {
+        let Some(state) = self.consensus.tagged_state_local(name).await else {
+            return Ok(None);
+        };
+
+        let mut pairs: Vec<(Key, Value)> = state
+            .values
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        for (key, segment_id) in &state.cold {
+            let stored = Some(StoredValue::Cold(*segment_id));
+            if let Some((value, _)) = self.resolve(key, stored, ARCHIVE_TIERS).await? {
+                pairs.push((key.clone(), value));
+            }
+        }
+        pairs.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(Some(pairs))
+    }
+
+    /// Start namespace migration `id` of `spec`, or resume it from its checkpoint
+    ///
+    /// The first start stores a new checkpoint. Starting again resumes a paused or
+    /// failed migration after its last committed batch, at the batch size and rate
+    /// of `spec`, which must migrate the same keys as before. A completed migration
+    /// is returned unchanged. The batches are then committed by
+    /// [`DistributedApi::run_migration`]; see [`crate::namespace_migration`].
+    pub async fn start_migration(
+        &self,
+        id: &str,
+        spec: MigrationSpec,
+    ) -> Result<MigrationCheckpoint> {
+        let key = checkpoint_key(id);
+        let now = now_millis()?;
+        let (checkpoint, _) = self
+            .transaction(|txn| {
+                let checkpoint = match txn.get(&key)? {
+                    None => MigrationCheckpoint::new(id, spec.clone(), now),
+                    Some(stored) => {
+                        let mut checkpoint =
+                            MigrationCheckpoint::from_bytes(&stored).or_else(abort)?;
+                        if !checkpoint.spec.same_keys(&spec) {
+                            return abort(ScribeError::Conflict(format!(
+                                "Migration '{}' was started with other prefixes or options",
+                                id
+                            )));
+                        }
+                        if checkpoint.state == MigrationState::Completed {
+                            return Ok(checkpoint);
+                        }
+                        checkpoint.spec = spec.clone();
+                        checkpoint.state = MigrationState::Running;
+                        checkpoint.error = None;
+                        checkpoint.updated_at_ms = now;
+                        checkpoint
+                    }
+                };
+                txn.put(&key, checkpoint.to_bytes().or_else(abort)?)?;
+                Ok(checkpoint)
+            })
+            .await?;
+        Ok(checkpoint)
+    }
+
+    /// Commit the batches of a started migration until every source key is migrated
+    /// or `run` is stopped
+    ///
+    /// Each batch takes the next source keys after the cursor on this node and
+    /// commits their migration together with the advanced checkpoint, as one
+    /// transaction that fails with `ScribeError::Conflict` if the stored checkpoint
+    /// is no longer `checkpoint` (another run of the migration moved it on). Batches
+    /// are spaced to stay under the migration's rate.
+    ///
+    /// Returns the checkpoint as last stored, completed or paused. If a batch fails,
+    /// the checkpoint is marked failed, as far as this node still can.
+    pub async fn run_migration(
+        &self,
+        run: &MigrationRun,
+        mut checkpoint: MigrationCheckpoint,
+    ) -> Result<MigrationCheckpoint> {
+        match self.migrate_batches(run, &mut checkpoint).await {
+            Ok(true) => {
+                self.finish_migration(&checkpoint, MigrationState::Completed, None)
+                    .await
+            }
+            Ok(false) => {
+                self.finish_migration(&checkpoint, MigrationState::Paused, None)
+                    .await
+            }
+            Err(e) => {
+                // Best effort: a node that lost leadership cannot store it
+                let error = Some(e.to_string());
+                let _ = self
+                    .finish_migration(&checkpoint, MigrationState::Failed, error)
+                    .await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Commit batches of `checkpoint`, keeping it up to date; returns whether every
+    /// source key was migrated, or `false` once `run` is stopped
+    async fn migrate_batches(
+        &self,
+        run: &MigrationRun,
+        checkpoint: &mut MigrationCheckpoint,
+    ) -> Result<bool> {
+        let key = checkpoint_key(&checkpoint.id);
+        loop {
+            if run.is_stopped() {
+                return Ok(false);
+            }
+            let cursor = checkpoint.cursor_key();
+            let sources = self
+                .consensus
+                .keys_with_prefix_local(
+                    checkpoint.spec.source.as_bytes(),
+                    cursor.as_deref(),
+                    checkpoint.spec.batch_size,
+                )
+                .await;
+            if sources.is_empty() {
+                return Ok(true);
+            }
+
+            let started = Instant::now();
+            let now = now_millis()?;
+            let previous = &*checkpoint;
+            let (next, _) = self
+                .transaction(|txn| {
+                    let stored = match txn.get(&key)? {
+                        Some(stored) => MigrationCheckpoint::from_bytes(&stored).or_else(abort)?,
+                        None => {
+                            return abort(ScribeError::NotFound(format!(
+                                "Migration '{}' has no checkpoint",
+                                previous.id
+                            )))
+                        }
+                    };
+                    if stored != *previous {
+                        return abort(ScribeError::Conflict(format!(
+                            "Migration '{}' was moved on by another run",
+                            previous.id
+                        )));
+                    }
+
+                    // Read every key before giving up on the missing ones, so they
+                    // are all loaded before the next run
+                    let mut batch = Vec::with_capacity(sources.len());
+                    let mut missing = false;
+                    for source in &sources {
+                        let Some(target) = previous.spec.target_key(source) else {
+                            continue;
+                        };
+                        match (txn.get(source), txn.get(&target)) {
+                            (Ok(value), Ok(existing)) => batch.push(BatchKey {
+                                source: source.clone(),
+                                value,
+                                target,
+                                existing,
+                            }),
+                            _ => missing = true,
+                        }
+                    }
+                    if missing {
+                        return Err(ConflictableTransactionError::Conflict);
+                    }
+
+                    let mut next = previous.clone();
+                    for (write, value) in next.apply_batch(&batch, now) {
+                        match value {
+                            Some(value) => txn.put(&write, value)?,
+                            None => txn.delete(&write)?,
+                        }
+                    }
+                    txn.put(&key, next.to_bytes().or_else(abort)?)?;
+                    Ok(next)
+                })
+                .await?;
+            *checkpoint = next;
+
+            let pause = checkpoint
+                .spec
+                .throttle(sources.len())
+                .saturating_sub(started.elapsed());
+            if !pause.is_zero() {
+                tokio::time::sleep(pause).await;
+            }
+        }
+    }
+
+    /// Store `checkpoint` as `state`, unless another run moved it on
+    async fn finish_migration(
+        &self,
+        checkpoint: &MigrationCheckpoint,
+        state: MigrationState,
+        error: Option<String>,
+    ) -> Result<MigrationCheckpoint> {
+        let key = checkpoint_key(&checkpoint.id);
+        let now = now_millis()?;
+        let (finished, _) = self
+            .transaction(|txn| {
+                let stored = match txn.get(&key)? {
+                    Some(stored) => Some(MigrationCheckpoint::from_bytes(&stored).or_else(abort)?),
+                    None => None,
+                };
+                if stored.as_ref() != Some(checkpoint) {
+                    return abort(ScribeError::Conflict(format!(
+                        "Migration '{}' was moved on by another run",
+                        checkpoint.id
+                    )));
+                }
+                let mut finished = checkpoint.clone();
+                finished.state = state;
+                finished.error = error.clone();
+                finished.updated_at_ms = now;
+                txn.put(&key, finished.to_bytes().or_else(abort)?)?;
+                Ok(finished)
+            })
+            .await?;
+        Ok(finished)
+    }
+
+    /// Stale read of the checkpoint of migration `id`
+    pub async fn migration(&self, id: &str) -> Result<Option<MigrationCheckpoint>> {
+        self.read_checkpoint(&checkpoint_key(id)).await
+    }
+
+    /// Stale read of every migration checkpoint, ordered by ID
+    pub async fn migrations(&self) -> Result<Vec<MigrationCheckpoint>> {
+        let keys = self
+            .consensus
+            .keys_with_prefix_local(MIGRATION_KEY_PREFIX.as_bytes(), None, usize::MAX)
+            .await;
+        let mut migrations = Vec::with_capacity(keys.len());
+        for key in keys {
+            if let Some(checkpoint) = self.read_checkpoint(&key).await? {
+                migrations.push(checkpoint);
+            }
+        }
+        Ok(migrations)
+    }
+
+    async fn read_checkpoint(&self, key: &[u8]) -> Result<Option<MigrationCheckpoint>> {
+        let (stored, _) = self.consensus.lookup_versioned_local(key).await;
+        match self.resolve(key, stored, ARCHIVE_TIERS).await? {
+            Some((value, _)) => MigrationCheckpoint::from_bytes(&value).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Merkle root of every sync bucket of this node's data (see [`crate::sync`])
+    ///
+    /// Served from the local state machine. Values demoted to cold storage are
+    /// fetched from S3 so both tiers hash alike.
+    pub async fn sync_buckets(&self, buckets: u32) -> Result<Vec<BucketDigest>> {
+        let pairs = self.local_pairs(|_| true).await?;
+        Ok(sync::bucket_digests(&pairs, buckets))
+    }
+
+    /// Value digest of every key in sync bucket `bucket`, served like
+    /// [`DistributedApi::sync_buckets`]
+    pub async fn sync_bucket_keys(&self, bucket: u32, buckets: u32) -> Result<Vec<KeyDigest>> {
+        let pairs = self
+            .local_pairs(|key| sync::bucket_of(key, buckets) == bucket)
+            .await?;
+        let changes: HashMap<Key, KeyChange> = self
+            .consensus
+            .changes_between(0, u64::MAX)
+            .await
+            .into_iter()
+            .map(|change| (change.key.clone(), change))
+            .collect();
+
+        let mut digests = sync::key_digests(&pairs);
+        for digest in &mut digests {
+            if let Some(change) = changes.get(digest.key.as_bytes()) {
+                digest.version = Some(change.version);
+                digest.modified_at = Some(change.timestamp);
+            }
+        }
+        Ok(digests)
+    }
+
+    /// Key-value pairs of this node's keys matching `include`, resolving demoted keys
+    async fn local_pairs(&self, include: impl Fn(&[u8]) -> bool) -> Result<Vec<(Key, Value)>> {
+        let mut pairs = Vec::new();
+        for (key, stored) in self.consensus.entries_local().await {
+            if !include(&key) {
+                continue;
+            }
+            if let Some((value, _)) = self.resolve(&key, Some(stored), ARCHIVE_TIERS).await? {
+                pairs.push((key, value));
+            }
+        }
+        Ok(pairs)
+    }
+
+    /// Start a bulk export: catch up with the leader
+    ///
+    /// A linearizable read makes sure this node has applied every write committed
+    /// before the call; the returned token marks that point. Read the keys with
+    /// [`DistributedApi::export_page`].
+    pub async fn export_start(&self) -> Result<ConsistencyToken> {
+        self.get_linearizable(Vec::new()).await?;
+        Ok(self.applied_token().await)
+    }
+
+    /// Up to `limit` keys of the ledger sorting after `after`, in key order, for a
+    /// bulk export
+    ///
+    /// Each page reads the local state machine as it is then, so a key written
+    /// during an export appears with its old or its new value. Demoted keys are
+    /// returned as stored; read their values with [`DistributedApi::export_value`].
+    pub async fn export_page(&self, after: Option<&[u8]>, limit: usize) -> Vec<(Key, StoredValue)> {
+        self.consensus.entries_after_local(after, limit).await
+    }
+
+    /// Value of an entry returned by [`DistributedApi::export_page`], reading
+    /// demoted values from cold storage
+    pub async fn export_value(&self, key: &[u8], stored: StoredValue) -> Result<Option<Value>> {
+        Ok(self
+            .resolve(key, Some(stored), ARCHIVE_TIERS)
+            .await?
+            .map(|(value, _)| value))
+    }
+
+    /// Get a value with default linearizable consistency
+    pub async fn get_default(&self, key: Key) -> Result<Option<Value>> {
+        self.get(key, ReadConsistency::Linearizable).await
+    }
+
+    /// Subscribe to change events applied on this node
+    ///
+    /// Keys removed by TTL expiry are reported with `ChangeKind::Expire`, distinct
+    /// from explicit deletes.
+    pub fn subscribe_changes(&self) -> broadcast::Receiver<ChangeEvent> {
+        self.consensus.subscribe_changes()
+    }
+
+    /// Consistency token of everything this node has applied
+    ///
+    /// A read served from the local state machine reflects at least this token, so
+    /// a client passing it on to other nodes never reads older state there.
+    pub async fn applied_token(&self) -> ConsistencyToken {
+        ConsistencyToken::new(self.consensus.applied_index().await)
+    }
+
+    /// Get the version of a key on this node: the log index of its last put or
+    /// delete, `None` if it was never written
+    pub async fn key_version(&self, key: &[u8]) -> Option<u64> {
+        self.consensus.key_version_local(key).await
+    }
+
+    /// Wait until the version of `key` on this node exceeds `version`
+    ///
+    /// Returns as soon as it does, or once `wait` has elapsed, with the key's version
+    /// at that point; on timeout this is still `version` or lower.
+    pub async fn wait_for_change(&self, key: &[u8], version: u64, wait: Duration) -> Option<u64> {
+        // Subscribe before reading the current version so no change slips in between
+        let mut events = self.consensus.subscribe_changes();
+        let deadline = tokio::time::Instant::now() + wait;
+        loop {
+            let current = self.key_version(key).await;
+            if current.is_some_and(|current| current > version) {
+                return current;
+            }
+            loop {
+                match tokio::time::timeout_at(deadline, events.recv()).await {
+                    Ok(Ok(event)) if event.key == key && event.version > version => break,
+                    Ok(Ok(_)) => {}
+                    // Missed events may include this key
+                    Ok(Err(broadcast::error::RecvError::Lagged(_))) => break,
+                    Ok(Err(broadcast::error::RecvError::Closed)) | Err(_) => return current,
+                }
+            }
+        }
+    }
+
+    /// Evict keys from the hot data cache when they are deleted or expire
+    pub fn start_cache_invalidation(&self) -> tokio::task::JoinHandle<()> {
+        let mut events = self.consensus.subscribe_changes();
+        let cache = Arc::clone(&self.cache);
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) if event.kind != ChangeKind::Put => {
+                        cache.remove(&event.key);
+                    }
+                    Ok(_) => {}
+                    // Missed events may hide removals, so drop everything
+                    Err(broadcast::error::RecvError::Lagged(_)) => cache.clear(),
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+
+    /// List keys modified within `[since, until]` (milliseconds since UNIX epoch)
+    ///
+    /// Deleted keys are included with `deleted = true` so incremental sync consumers
+    /// can apply removals. Results are ordered by modification time.
+    pub async fn keys_modified_between(&self, since: u64, until: u64) -> Vec<KeyChange> {
+        self.consensus.changes_between(since, until).await
+    }
+
+    /// List up to `limit` keys modified within `[since, until]`, starting after the
+    /// change at `after` (its timestamp and key)
+    ///
+    /// Results are ordered by modification time, then key, like
+    /// [`keys_modified_between`](Self::keys_modified_between).
+    pub async fn keys_modified_page(
+        &self,
+        since: u64,
+        until: u64,
+        after: Option<&(u64, Key)>,
+        limit: usize,
+    ) -> Vec<KeyChange> {
+        self.consensus
+            .changes_page(since, until, after, limit)
+            .await
+    }
+
+    /// Batch write multiple key-value pairs
+    ///
+    /// This method batches multiple writes into a single Raft proposal when possible.
+    /// If the batch size exceeds max_batch_size, it will be split into multiple proposals.
+    pub async fn put_batch(&self, items: Vec<(Key, Value)>) -> Result<Vec<Result<()>>> {
+        self.put_batch_with_priority(items, Priority::Normal).await
+    }
+
+    /// Batch write multiple key-value pairs at the given priority
+    ///
+    /// With an admission controller configured, every chunk of up to max_batch_size
+    /// items waits for a slot at `priority`, so low priority bulk batches give way to
+    /// higher priority traffic between chunks instead of holding capacity throughout.
+    pub async fn put_batch_with_priority(
+        &self,
+        items: Vec<(Key, Value)>,
+        priority: Priority,
+    ) -> Result<Vec<Result<()>>> {
+        if items.is_empty() {
+            return Ok(vec![]);
+        }
+        let started = Instant::now();
+        API_BATCH_SIZE.observe(items.len() as f64);
+
+        let mut results = Vec::with_capacity(items.len());
+
+        // Process items in batches
+        for chunk in items.chunks(self.max_batch_size) {
+            let _permit = match &self.admission {
+                Some(admission) => Some(admission.acquire(priority).await),
+                None => None,
+            };
+            for (key, value) in chunk {
+                let result = self.put(key.clone(), value.clone()).await.map(|_| ());
+                results.push(result);
+            }
+        }
+        observe_api_latency("put_batch", started);
+
+        Ok(results)
+    }
+
+    /// Apply puts, and deletes where the value is `None`, in a single Raft entry
+    ///
+    /// Unlike [`DistributedApi::put_batch`], which proposes every key on its own, the
+    /// writes are committed together in one proposal and applied in one step, so a
+    /// bulk load pays for consensus once per batch. A key written more than once
+    /// takes its last write. Returns the consistency token of the batch.
+    pub async fn write_batch(&self, writes: Vec<(Key, Option<Value>)>) -> Result<ConsistencyToken> {
+        self.propose_batch(writes, "write_batch", false).await
+    }
+
+    /// Put a batch of `records` read by a bulk import, in a single Raft entry
+    ///
+    /// Like [`DistributedApi::write_batch`], but only the leader accepts it, and the
+    /// keys are neither counted as hot nor loaded into the hot data cache, so an
+    /// import does not evict the values clients are reading.
+    pub async fn import_batch(&self, records: Vec<(Key, Value)>) -> Result<ConsistencyToken> {
+        if !self.is_leader().await {
+            return Err(ScribeError::NotLeader {
+                leader_id: self.current_leader().await,
+            });
+        }
+        let writes = records
+            .into_iter()
+            .map(|(key, value)| (key, Some(value)))
+            .collect();
+        self.propose_batch(writes, "import_batch", true).await
+    }
+
+    async fn propose_batch(
+        &self,
+        writes: Vec<(Key, Option<Value>)>,
+        operation: &str,
+        bulk: bool,
+    ) -> Result<ConsistencyToken> {
+        let started = Instant::now();
+        API_BATCH_SIZE.observe(writes.len() as f64);
+
+        // Keep the last write of every key, in the order of those writes
+        let mut last = HashMap::with_capacity(writes.len());
+        for (i, (key, _)) in writes.iter().enumerate() {
+            last.insert(key.clone(), i);
+        }
+        let writes: Vec<TxnWrite> = writes
+            .into_iter()
+            .enumerate()
+            .filter(|(i, (key, _))| last.get(key) == Some(i))
+            .map(|(_, (key, value))| TxnWrite { key, value })
+            .collect();
+        if !bulk {
+            for write in &writes {
+                let op = match write.value {
+                    Some(_) => KeyOp::Write,
+                    None => KeyOp::Delete,
+                };
+                self.hot_keys.record(&write.key, op);
+            }
+        }
+        let request = AppRequest::Batch {
+            writes: writes.clone(),
+        };
+
+        // Execute write with timeout
+        let result = timeout(
+            self.write_timeout,
+            self.consensus.client_write_indexed(request),
+        )
+        .await;
+        observe_api_latency(operation, started);
+
+        match result {
+            Ok(Ok((AppResponse::BatchOk, index))) => {
+                for write in writes {
+                    match write.value {
+                        Some(value) => {
+                            self.mirror(ShadowOp::Put {
+                                key: write.key.clone(),
+                                value: value.clone(),
+                            });
+                            if bulk {
+                                self.cache.remove(&write.key);
+                            } else {
+                                self.cache.put(write.key, value);
+                            }
+                        }
+                        None => {
+                            self.cache.remove(&write.key);
+                            self.mirror(ShadowOp::Delete { key: write.key });
+                        }
+                    }
+                }
+                Ok(ConsistencyToken::new(index))
+            }
+            Ok(Ok((AppResponse::Error { message }, _))) => {
+                Err(ScribeError::Consensus(format!("Batch failed: {}", message)))
+            }
+            Ok(Err(e)) => Err(consensus_error(e, "Consensus error")),
+            Err(_) => Err(ScribeError::Consensus("Write timeout".to_string())),
+            _ => Err(ScribeError::Consensus("Unexpected response".to_string())),
+        }
+    }
+
+    /// Check if this node is the leader
+    pub async fn is_leader(&self) -> bool {
+        self.consensus.is_leader().await
+    }
 
     /// Get the current leader ID
     pub async fn current_leader(&self) -> Option<NodeId> {
@@ -290,6 +2446,98 @@ impl DistributedApi {
     pub fn cache_capacity(&self) -> usize {
         self.cache.capacity()
     }
+
+    /// Up to `limit` keys held in the hot data cache, most recently used first
+    pub fn recent_cache_keys(&self, limit: usize) -> Vec<Key> {
+        self.cache.recent_keys(limit)
+    }
+
+    /// Load the values of `keys`, hottest first, into the hot data cache
+    ///
+    /// A linearizable read of the first key makes sure this node has caught up with
+    /// the leader; the values are then read from the local state machine. Keys that
+    /// no longer exist, were demoted to cold storage or are written while warming
+    /// are skipped. Returns the number of values cached.
+    pub async fn warm_cache(&self, keys: &[Key]) -> Result<usize> {
+        let Some(first) = keys.first() else {
+            return Ok(0);
+        };
+        self.get_linearizable(first.clone()).await?;
+
+        let mut warmed = 0;
+        // Coldest first, so the hottest keys end up most recently used
+        for key in keys.iter().take(self.cache.capacity()).rev() {
+            let (stored, version) = self.consensus.lookup_versioned_local(key).await;
+            let Some(StoredValue::Hot(value)) = stored else {
+                continue;
+            };
+            if self.consensus.key_version_local(key).await == version {
+                self.cache.put(key.clone(), value);
+                warmed += 1;
+            }
+        }
+        Ok(warmed)
+    }
+}
+
+/// Convert a consensus error into a `ScribeError`, preserving `NotLeader`
+/// Current time and the end of a lease of length `ttl` (milliseconds since UNIX epoch)
+/// Record the state machine lookup of a read; only values held in memory are hits
+fn observe_local_tier(stored: &Result<Option<StoredValue>>, started: Instant) {
+    let hit = matches!(stored, Ok(Some(StoredValue::Hot(_))));
+    observe_read_tier(ReadTier::Local.as_str(), hit, started);
+}
+
+fn lease_window(ttl: Duration) -> Result<(u64, u64)> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| ScribeError::Other(format!("System clock error: {}", e)))?;
+    Ok((now.as_millis() as u64, (now + ttl).as_millis() as u64))
+}
+
+/// Key of session `id`
+pub fn session_key(id: &str) -> Key {
+    format!("{}{}", SESSION_KEY_PREFIX, id).into_bytes()
+}
+
+/// Random, unguessable session ID: 128 bits in hex
+fn new_session_id() -> Result<String> {
+    let mut id = [0u8; 16];
+    SystemRandom::new()
+        .fill(&mut id)
+        .map_err(|_| ScribeError::Other("Failed to generate session ID".to_string()))?;
+    Ok(hex::encode(id))
+}
+
+/// Current time in milliseconds since UNIX epoch, for requests applied by replicas
+fn now_millis() -> Result<u64> {
+    lease_window(Duration::ZERO).map(|(now, _)| now)
+}
+
+/// Wrap a key write in `fence`, if given
+fn fence_request(fence: Option<Fence>, request: AppRequest) -> AppRequest {
+    match fence {
+        Some(fence) => AppRequest::fenced(fence, request),
+        None => request,
+    }
+}
+
+/// Conflict reported when a fenced write is refused
+fn stale_epoch_error(fence: Option<&Fence>, current: u64) -> ScribeError {
+    match fence {
+        Some(fence) => ScribeError::Conflict(format!(
+            "Epoch {} of namespace '{}' is stale (current epoch {})",
+            fence.epoch, fence.namespace, current
+        )),
+        None => ScribeError::Conflict(format!("Stale epoch (current epoch {})", current)),
+    }
+}
+
+fn consensus_error(err: Box<dyn std::error::Error + Send + Sync>, context: &str) -> ScribeError {
+    match err.downcast::<ScribeError>() {
+        Ok(err) => *err,
+        Err(err) => ScribeError::Consensus(format!("{}: {}", context, err)),
+    }
 }
 
 #[cfg(test)]
@@ -302,142 +2550,491 @@ mod tests {
         let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
         let api = DistributedApi::new(consensus);
 
-        assert_eq!(api.write_timeout, DEFAULT_WRITE_TIMEOUT);
-        assert_eq!(api.max_batch_size, DEFAULT_BATCH_SIZE);
+        assert_eq!(api.write_timeout, DEFAULT_WRITE_TIMEOUT);
+        assert_eq!(api.max_batch_size, DEFAULT_BATCH_SIZE);
+    }
+
+    #[tokio::test]
+    async fn test_api_with_custom_timeout() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
+        let custom_timeout = Duration::from_secs(60);
+        let api = DistributedApi::with_timeout(consensus, custom_timeout);
+
+        assert_eq!(api.write_timeout, custom_timeout);
+    }
+
+    #[tokio::test]
+    async fn test_api_with_custom_batch_size() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
+        let api = DistributedApi::with_batch_size(consensus, 200);
+
+        assert_eq!(api.max_batch_size, 200);
+    }
+
+    #[tokio::test]
+    async fn test_api_is_leader_before_init() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
+        let api = DistributedApi::new(consensus);
+
+        // Before initialization, should not be leader
+        assert!(!api.is_leader().await);
+    }
+
+    #[tokio::test]
+    async fn test_api_current_leader_before_init() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
+        let api = DistributedApi::new(consensus);
+
+        // Before initialization, there should be no leader
+        assert_eq!(api.current_leader().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_api_put_before_init() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
+        let api = DistributedApi::new(consensus);
+
+        // Writing before initialization should fail
+        let result = api.put(b"test_key".to_vec(), b"test_value".to_vec()).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_api_put_after_init() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
+
+        // Initialize as single-node cluster
+        consensus.initialize().await.unwrap();
+
+        // Wait for election
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+
+        let api = DistributedApi::new(consensus);
+
+        // Should be leader now
+        assert!(api.is_leader().await);
+
+        // Write should succeed
+        let result = api.put(b"test_key".to_vec(), b"test_value".to_vec()).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_api_delete_after_init() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
+
+        // Initialize as single-node cluster
+        consensus.initialize().await.unwrap();
+
+        // Wait for election
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+
+        let api = DistributedApi::new(consensus);
+
+        // Put a value first
+        api.put(b"test_key".to_vec(), b"test_value".to_vec())
+            .await
+            .unwrap();
+
+        // Delete should succeed
+        let result = api.delete(b"test_key".to_vec()).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_api_batch_put_empty() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
+        let api = DistributedApi::new(consensus);
+
+        // Empty batch should succeed immediately
+        let results = api.put_batch(vec![]).await.unwrap();
+        assert_eq!(results.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_api_batch_put_after_init() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
+
+        // Initialize as single-node cluster
+        consensus.initialize().await.unwrap();
+
+        // Wait for election
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+
+        let api = DistributedApi::new(consensus);
+
+        // Batch write should succeed
+        let items = vec![
+            (b"key1".to_vec(), b"value1".to_vec()),
+            (b"key2".to_vec(), b"value2".to_vec()),
+            (b"key3".to_vec(), b"value3".to_vec()),
+        ];
+
+        let results = api.put_batch(items).await.unwrap();
+        assert_eq!(results.len(), 3);
+
+        // All should succeed
+        for result in results {
+            assert!(result.is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_api_write_batch() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
+        consensus.initialize().await.unwrap();
+
+        // Wait for election
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+
+        let api = DistributedApi::new(Arc::clone(&consensus));
+        api.put(b"gone".to_vec(), b"old".to_vec()).await.unwrap();
+        let before = consensus.applied_index().await;
+
+        let token = api
+            .write_batch(vec![
+                (b"a".to_vec(), Some(b"1".to_vec())),
+                (b"gone".to_vec(), None),
+                (b"b".to_vec(), Some(b"2".to_vec())),
+                (b"a".to_vec(), Some(b"3".to_vec())),
+            ])
+            .await
+            .unwrap();
+
+        // One entry for the whole batch, with the last write of a repeated key
+        assert_eq!(token.applied_index(), before + 1);
+        let read = |key: &[u8]| api.get(key.to_vec(), ReadConsistency::Linearizable);
+        assert_eq!(read(b"a").await.unwrap(), Some(b"3".to_vec()));
+        assert_eq!(read(b"b").await.unwrap(), Some(b"2".to_vec()));
+        assert_eq!(read(b"gone").await.unwrap(), None);
     }
 
     #[tokio::test]
-    async fn test_api_with_custom_timeout() {
+    async fn test_api_export_and_import() {
         let db = sled::Config::new().temporary(true).open().unwrap();
         let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
-        let custom_timeout = Duration::from_secs(60);
-        let api = DistributedApi::with_timeout(consensus, custom_timeout);
+        consensus.initialize().await.unwrap();
 
-        assert_eq!(api.write_timeout, custom_timeout);
+        // Wait for election
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+
+        let api = DistributedApi::new(Arc::clone(&consensus));
+        api.put(b"b".to_vec(), b"2".to_vec()).await.unwrap();
+        let before = consensus.applied_index().await;
+
+        let token = api
+            .import_batch(vec![
+                (b"c".to_vec(), b"3".to_vec()),
+                (b"a".to_vec(), b"1".to_vec()),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(token.applied_index(), before + 1);
+        // Imported values are not cached
+        assert_eq!(api.cache_size(), 1);
+
+        let exported = api.export_start().await.unwrap();
+        assert!(exported >= token);
+        let mut pairs = Vec::new();
+        let mut after = None;
+        loop {
+            let page = api.export_page(after.as_deref(), 2).await;
+            after = page.last().map(|(key, _)| key.clone());
+            let last_page = page.len() < 2;
+            for (key, stored) in page {
+                let value = api.export_value(&key, stored).await.unwrap().unwrap();
+                pairs.push((key, value));
+            }
+            if last_page {
+                break;
+            }
+        }
+        assert_eq!(
+            pairs,
+            vec![
+                (b"a".to_vec(), b"1".to_vec()),
+                (b"b".to_vec(), b"2".to_vec()),
+                (b"c".to_vec(), b"3".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_consistency_token_parse() {
+        let token: ConsistencyToken = " 42 ".parse().unwrap();
+        assert_eq!(token.applied_index(), 42);
+        assert_eq!(token.to_string(), "42");
+        assert!("abc".parse::<ConsistencyToken>().is_err());
     }
 
     #[tokio::test]
-    async fn test_api_with_custom_batch_size() {
+    async fn test_api_read_your_writes_token() {
         let db = sled::Config::new().temporary(true).open().unwrap();
         let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
-        let api = DistributedApi::with_batch_size(consensus, 200);
+        consensus.initialize().await.unwrap();
 
-        assert_eq!(api.max_batch_size, 200);
+        // Wait for election
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+
+        let api = DistributedApi::new(consensus);
+        let first = api.put(b"key".to_vec(), b"v1".to_vec()).await.unwrap();
+        let second = api.put(b"key".to_vec(), b"v2".to_vec()).await.unwrap();
+        assert!(second > first);
+
+        let value = api.get_at_least(b"key".to_vec(), second).await.unwrap();
+        assert_eq!(value, Some(b"v2".to_vec()));
+
+        let deleted = api.delete(b"key".to_vec()).await.unwrap();
+        assert!(deleted > second);
+        assert_eq!(
+            api.get_at_least(b"key".to_vec(), deleted).await.unwrap(),
+            None
+        );
     }
 
     #[tokio::test]
-    async fn test_api_is_leader_before_init() {
+    async fn test_api_patch_json() {
         let db = sled::Config::new().temporary(true).open().unwrap();
         let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
-        let api = DistributedApi::new(consensus);
+        consensus.initialize().await.unwrap();
 
-        // Before initialization, should not be leader
-        assert!(!api.is_leader().await);
+        // Wait for election
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+
+        let api = DistributedApi::new(consensus);
+        let (created, _) = api
+            .patch_json(b"doc".to_vec(), &serde_json::json!({"a": 1, "b": {"c": 2}}))
+            .await
+            .unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&created).unwrap(),
+            serde_json::json!({"a": 1, "b": {"c": 2}})
+        );
+
+        let (merged, token) = api
+            .patch_json(
+                b"doc".to_vec(),
+                &serde_json::json!({"a": null, "b": {"d": 3}}),
+            )
+            .await
+            .unwrap();
+        let stored = api.get_at_least(b"doc".to_vec(), token).await.unwrap();
+        assert_eq!(stored, Some(merged.clone()));
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&merged).unwrap(),
+            serde_json::json!({"b": {"c": 2, "d": 3}})
+        );
+
+        api.put(b"raw".to_vec(), b"not json".to_vec())
+            .await
+            .unwrap();
+        let result = api
+            .patch_json(b"raw".to_vec(), &serde_json::json!({"a": 1}))
+            .await;
+        assert!(matches!(result, Err(ScribeError::Serialization(_))));
     }
 
     #[tokio::test]
-    async fn test_api_current_leader_before_init() {
+    async fn test_api_rename() {
         let db = sled::Config::new().temporary(true).open().unwrap();
         let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
+        consensus.initialize().await.unwrap();
+
+        // Wait for election
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+
         let api = DistributedApi::new(consensus);
+        api.put(b"old".to_vec(), b"value".to_vec()).await.unwrap();
+        // Warm the cache so a stale entry would be visible
+        api.get(b"old".to_vec(), ReadConsistency::Stale)
+            .await
+            .unwrap();
 
-        // Before initialization, there should be no leader
-        assert_eq!(api.current_leader().await, None);
+        let token = api.rename(b"old".to_vec(), b"new".to_vec()).await.unwrap();
+        assert_eq!(
+            api.get_at_least(b"new".to_vec(), token).await.unwrap(),
+            Some(b"value".to_vec())
+        );
+        assert_eq!(
+            api.get(b"old".to_vec(), ReadConsistency::Stale)
+                .await
+                .unwrap(),
+            None
+        );
+
+        let missing = api.rename(b"old".to_vec(), b"other".to_vec()).await;
+        assert!(matches!(missing, Err(ScribeError::NotFound(_))));
     }
 
     #[tokio::test]
-    async fn test_api_put_before_init() {
+    async fn test_api_get_and_set_and_delete() {
         let db = sled::Config::new().temporary(true).open().unwrap();
         let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
-        let api = DistributedApi::new(consensus);
+        consensus.initialize().await.unwrap();
 
-        // Writing before initialization should fail
-        let result = api.put(b"test_key".to_vec(), b"test_value".to_vec()).await;
-        assert!(result.is_err());
+        // Wait for election
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+
+        let api = DistributedApi::new(consensus);
+        let (previous, _) = api
+            .get_and_set(b"slot".to_vec(), b"a".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(previous, None);
+        let (previous, _) = api
+            .get_and_set(b"slot".to_vec(), b"b".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(previous, Some(b"a".to_vec()));
+
+        let (previous, token) = api.get_and_delete(b"slot".to_vec()).await.unwrap();
+        assert_eq!(previous, Some(b"b".to_vec()));
+        assert_eq!(
+            api.get_at_least(b"slot".to_vec(), token).await.unwrap(),
+            None
+        );
+        let (previous, _) = api.get_and_delete(b"slot".to_vec()).await.unwrap();
+        assert_eq!(previous, None);
     }
 
     #[tokio::test]
-    async fn test_api_put_after_init() {
+    async fn test_api_transaction() {
         let db = sled::Config::new().temporary(true).open().unwrap();
         let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
-
-        // Initialize as single-node cluster
         consensus.initialize().await.unwrap();
 
         // Wait for election
         tokio::time::sleep(Duration::from_millis(2000)).await;
 
         let api = DistributedApi::new(consensus);
+        api.put(b"alice".to_vec(), b"100".to_vec()).await.unwrap();
+
+        let transfer = |amount: u64| {
+            api.transaction(move |txn| {
+                let balance = |key: &[u8]| -> TxnResult<u64> {
+                    Ok(txn
+                        .get(key)?
+                        .map(|value| String::from_utf8_lossy(&value).parse().unwrap())
+                        .unwrap_or(0))
+                };
+                let available = balance(b"alice")?;
+                if available < amount {
+                    return crate::transaction::abort(ScribeError::Conflict(
+                        "Insufficient balance".to_string(),
+                    ));
+                }
+                let credited = balance(b"bob")?;
+                txn.put(b"alice", (available - amount).to_string())?;
+                txn.put(b"bob", (credited + amount).to_string())?;
+                Ok(available - amount)
+            })
+        };
 
-        // Should be leader now
-        assert!(api.is_leader().await);
-
-        // Write should succeed
-        let result = api.put(b"test_key".to_vec(), b"test_value".to_vec()).await;
-        assert!(result.is_ok());
+        let (remaining, token) = transfer(30).await.unwrap();
+        assert!(api.applied_token().await >= token);
+        assert_eq!(remaining, 70);
+        assert_eq!(
+            api.get_at_least(b"bob".to_vec(), token).await.unwrap(),
+            Some(b"30".to_vec())
+        );
+        assert_eq!(api.key_version(b"alice").await, Some(token.applied_index()));
+
+        // An aborted transaction proposes nothing
+        let err = transfer(500).await.unwrap_err();
+        assert!(matches!(err, ScribeError::Conflict(_)));
+        assert_eq!(api.key_version(b"bob").await, Some(token.applied_index()));
+        let alice = api.get(b"alice".to_vec(), ReadConsistency::Linearizable);
+        assert_eq!(alice.await.unwrap(), Some(b"70".to_vec()));
     }
 
     #[tokio::test]
-    async fn test_api_delete_after_init() {
+    async fn test_api_compare_and_swap() {
         let db = sled::Config::new().temporary(true).open().unwrap();
         let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
-
-        // Initialize as single-node cluster
         consensus.initialize().await.unwrap();
 
         // Wait for election
         tokio::time::sleep(Duration::from_millis(2000)).await;
 
         let api = DistributedApi::new(consensus);
-
-        // Put a value first
-        api.put(b"test_key".to_vec(), b"test_value".to_vec())
+        let key = b"leader".to_vec();
+        let (stored, token) = api.put_if_absent(key.clone(), b"a".to_vec()).await.unwrap();
+        assert!(stored);
+        assert_eq!(api.key_version(&key).await, Some(token.applied_index()));
+        let (stored, _) = api.put_if_absent(key.clone(), b"b".to_vec()).await.unwrap();
+        assert!(!stored);
+
+        let (swapped, _) = api
+            .compare_and_swap(key.clone(), Some(b"b".to_vec()), Some(b"c".to_vec()))
             .await
             .unwrap();
-
-        // Delete should succeed
-        let result = api.delete(b"test_key".to_vec()).await;
-        assert!(result.is_ok());
+        assert!(!swapped);
+        let (swapped, token) = api
+            .compare_and_swap(key.clone(), Some(b"a".to_vec()), None)
+            .await
+            .unwrap();
+        assert!(swapped);
+        assert_eq!(api.get_at_least(key, token).await.unwrap(), None);
+
+        // The two swaps that found another value are reported as conflicts
+        let report = api.contention(10);
+        assert_eq!(report.total_attempts, 4);
+        assert_eq!(report.total_conflicts, 2);
+        assert_eq!(report.prefixes[0].counts.prefix, "(none)");
+        assert_eq!(report.prefixes[0].counts.cas_conflicts, 2);
     }
 
     #[tokio::test]
-    async fn test_api_batch_put_empty() {
+    async fn test_api_get_at_least_times_out() {
         let db = sled::Config::new().temporary(true).open().unwrap();
         let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
-        let api = DistributedApi::new(consensus);
+        consensus.initialize().await.unwrap();
 
-        // Empty batch should succeed immediately
-        let results = api.put_batch(vec![]).await.unwrap();
-        assert_eq!(results.len(), 0);
+        // Wait for election
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+
+        // A token far ahead of anything written is never satisfied
+        let api = DistributedApi::new(consensus);
+        let result = api
+            .get_at_least(b"key".to_vec(), ConsistencyToken::new(1_000_000))
+            .await;
+        assert!(matches!(result, Err(ScribeError::Timeout(_))));
     }
 
     #[tokio::test]
-    async fn test_api_batch_put_after_init() {
+    async fn test_api_batch_put_with_admission() {
         let db = sled::Config::new().temporary(true).open().unwrap();
         let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
-
-        // Initialize as single-node cluster
         consensus.initialize().await.unwrap();
 
         // Wait for election
         tokio::time::sleep(Duration::from_millis(2000)).await;
 
-        let api = DistributedApi::new(consensus);
-
-        // Batch write should succeed
-        let items = vec![
-            (b"key1".to_vec(), b"value1".to_vec()),
-            (b"key2".to_vec(), b"value2".to_vec()),
-            (b"key3".to_vec(), b"value3".to_vec()),
-        ];
+        let admission = AdmissionController::new(2, 50, 0).unwrap();
+        let api = DistributedApi::with_batch_size(consensus, 2).with_admission(admission.clone());
 
-        let results = api.put_batch(items).await.unwrap();
-        assert_eq!(results.len(), 3);
+        let items = (0..5)
+            .map(|i| (format!("key{}", i).into_bytes(), b"value".to_vec()))
+            .collect();
+        let results = api
+            .put_batch_with_priority(items, Priority::Low)
+            .await
+            .unwrap();
 
-        // All should succeed
-        for result in results {
-            assert!(result.is_ok());
-        }
+        assert_eq!(results.len(), 5);
+        assert!(results.iter().all(|r| r.is_ok()));
+        // Every chunk released its slot
+        assert_eq!(admission.in_flight(), 0);
     }
 
     #[tokio::test]
@@ -600,6 +3197,326 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_api_keys_modified_between() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
+        consensus.initialize().await.unwrap();
+
+        // Wait for election
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+
+        let api = DistributedApi::new(consensus);
+        api.put(b"key1".to_vec(), b"value1".to_vec()).await.unwrap();
+        api.delete(b"key1".to_vec()).await.unwrap();
+
+        let changes = api.keys_modified_between(0, u64::MAX).await;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].key, b"key1".to_vec());
+        assert!(changes[0].deleted);
+
+        assert!(api.keys_modified_between(0, 0).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_api_tracks_hot_keys() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
+        consensus.initialize().await.unwrap();
+
+        // Wait for election
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+
+        let api = DistributedApi::new(consensus);
+        api.put(b"user:hot".to_vec(), b"v".to_vec()).await.unwrap();
+        for _ in 0..5 {
+            api.get(b"user:hot".to_vec(), ReadConsistency::Stale)
+                .await
+                .unwrap();
+        }
+        api.delete(b"session:1".to_vec()).await.unwrap();
+
+        let report = api.hot_keys(10);
+        assert_eq!(report.total_ops, 7);
+        assert_eq!(report.top_keys[0].key, "user:hot");
+        assert_eq!(report.prefixes[0].prefix, "user");
+        assert_eq!(report.prefixes[0].reads, 5);
+        assert_eq!(report.prefixes[0].writes, 1);
+        assert_eq!(report.prefixes[1].deletes, 1);
+    }
+
+    #[tokio::test]
+    async fn test_api_demoted_key_requires_archival() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
+        consensus.initialize().await.unwrap();
+
+        // Wait for election
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+
+        let api = DistributedApi::new(Arc::clone(&consensus));
+        api.put(b"key".to_vec(), b"value".to_vec()).await.unwrap();
+
+        // Demotion checks the archive first
+        assert!(matches!(
+            api.demote(b"key".to_vec(), 9).await,
+            Err(ScribeError::Storage(_))
+        ));
+        assert!(matches!(
+            api.demote(b"missing".to_vec(), 9).await,
+            Err(ScribeError::NotFound(_))
+        ));
+
+        consensus
+            .client_write(AppRequest::demote(b"key".to_vec(), 9, b"value"))
+            .await
+            .unwrap();
+        api.clear_cache();
+        assert!(matches!(
+            api.get(b"key".to_vec(), ReadConsistency::Stale).await,
+            Err(ScribeError::Storage(_))
+        ));
+        assert_eq!(consensus.client_read_local(b"key").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_api_anchor_segment() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
+        consensus.initialize().await.unwrap();
+
+        // Wait for election
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+
+        let api = DistributedApi::new(Arc::clone(&consensus));
+        let mut segment = Segment::new(9);
+        segment.put(b"a".to_vec(), b"1".to_vec());
+        segment.put(b"b".to_vec(), b"2".to_vec());
+        let anchor = api.anchor_segment(&segment).await.unwrap();
+        assert_eq!(anchor.segment_id, 9);
+        assert_eq!(
+            Some(anchor.merkle_root.clone()),
+            segment.compute_merkle_root().map(hex::encode)
+        );
+        assert_eq!(anchor.key_count, 2);
+
+        // Anchoring again is idempotent, but the root cannot change
+        assert_eq!(api.anchor_segment(&segment).await.unwrap(), anchor);
+        segment.put(b"c".to_vec(), b"3".to_vec());
+        assert!(matches!(
+            api.anchor_segment(&segment).await,
+            Err(ScribeError::Conflict(_))
+        ));
+        assert!(api.anchor_segment(&Segment::new(10)).await.is_err());
+
+        // Only values demoted to an anchored segment can be proven
+        assert!(api.prove_key(b"missing".to_vec()).await.unwrap().is_none());
+        api.put(b"hot".to_vec(), b"v".to_vec()).await.unwrap();
+        assert!(matches!(
+            api.prove_key(b"hot".to_vec()).await,
+            Err(ScribeError::Conflict(_))
+        ));
+        consensus
+            .client_write(AppRequest::demote(b"hot".to_vec(), 11, b"v"))
+            .await
+            .unwrap();
+        assert!(matches!(
+            api.prove_key(b"hot".to_vec()).await,
+            Err(ScribeError::Conflict(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_api_locks() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
+        consensus.initialize().await.unwrap();
+
+        // Wait for election
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+
+        let api = DistributedApi::new(consensus);
+        let ttl = Duration::from_secs(30);
+        let lease = api.acquire_lock("jobs", "worker-1", ttl).await.unwrap();
+        assert_eq!(lease.holder, "worker-1");
+        assert_eq!(api.lock_info("jobs").await, Some(lease.clone()));
+
+        assert!(matches!(
+            api.acquire_lock("jobs", "worker-2", ttl).await,
+            Err(ScribeError::Conflict(_))
+        ));
+        let renewed = api.renew_lock("jobs", lease.token, ttl).await.unwrap();
+        assert_eq!(renewed.token, lease.token);
+
+        assert!(api.release_lock("jobs", lease.token).await.unwrap());
+        assert!(!api.release_lock("jobs", lease.token).await.unwrap());
+        assert!(matches!(
+            api.renew_lock("jobs", lease.token, ttl).await,
+            Err(ScribeError::Conflict(_))
+        ));
+
+        // Every new grant carries a higher fencing token
+        let next = api.acquire_lock("jobs", "worker-2", ttl).await.unwrap();
+        assert!(next.token > lease.token);
+        assert_eq!(api.lock_info("missing").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_api_queue() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
+        consensus.initialize().await.unwrap();
+
+        // Wait for election
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+
+        let api = DistributedApi::new(consensus).with_queue_capacity(2);
+        let first = api.enqueue("jobs", b"first".to_vec()).await.unwrap();
+        let second = api.enqueue("jobs", b"second".to_vec()).await.unwrap();
+        assert!(second > first);
+        assert!(matches!(
+            api.enqueue("jobs", b"third".to_vec()).await,
+            Err(ScribeError::Conflict(_))
+        ));
+
+        let visibility = Duration::from_secs(30);
+        let item = api.dequeue("jobs", visibility).await.unwrap().unwrap();
+        assert_eq!((item.id, item.attempts), (first, 1));
+        assert_eq!(item.payload, b"first".to_vec());
+        let stats = api.queue_stats("jobs").await;
+        assert_eq!((stats.length, stats.in_flight), (2, 1));
+
+        assert!(api.ack("jobs", item.id, item.receipt).await.unwrap());
+        assert!(!api.ack("jobs", item.id, item.receipt).await.unwrap());
+
+        // An expired lease makes the item visible again
+        let item = api
+            .dequeue("jobs", Duration::from_millis(50))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(item.id, second);
+        assert!(api.dequeue("jobs", visibility).await.unwrap().is_none());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let retry = api.dequeue("jobs", visibility).await.unwrap().unwrap();
+        assert_eq!((retry.id, retry.attempts), (second, 2));
+        assert!(!api.ack("jobs", item.id, item.receipt).await.unwrap());
+        assert!(api.ack("jobs", retry.id, retry.receipt).await.unwrap());
+        assert_eq!(api.queue_stats("jobs").await.length, 0);
+    }
+
+    #[tokio::test]
+    async fn test_api_tags() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
+        consensus.initialize().await.unwrap();
+
+        // Wait for election
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+
+        let api = DistributedApi::new(Arc::clone(&consensus));
+        api.put(b"b".to_vec(), b"2".to_vec()).await.unwrap();
+        api.put(b"a".to_vec(), b"1".to_vec()).await.unwrap();
+        let tag = api.create_tag("release-1").await.unwrap();
+        assert_eq!((tag.key_count, tag.manifest_version), (2, 0));
+        assert!(matches!(
+            api.create_tag("release-1").await,
+            Err(ScribeError::Conflict(_))
+        ));
+
+        // Later writes do not change what the tag exports
+        api.put(b"a".to_vec(), b"changed".to_vec()).await.unwrap();
+        api.delete(b"b".to_vec()).await.unwrap();
+        assert_eq!(
+            api.export_tag("release-1").await.unwrap().unwrap(),
+            vec![
+                (b"a".to_vec(), b"1".to_vec()),
+                (b"b".to_vec(), b"2".to_vec())
+            ]
+        );
+        let verification = api.verify_tag("release-1").await.unwrap().unwrap();
+        assert!(verification.verified);
+        assert_eq!(verification.computed_root, tag.merkle_root);
+
+        // A demoted key cannot be read back without archival
+        consensus
+            .client_write(AppRequest::demote(b"a".to_vec(), 9, b"changed"))
+            .await
+            .unwrap();
+        api.create_tag("release-2").await.unwrap();
+        let verification = api.verify_tag("release-2").await.unwrap().unwrap();
+        assert!(!verification.verified);
+        assert_eq!(verification.unreadable_keys, vec!["a".to_string()]);
+        assert!(api.export_tag("release-2").await.is_err());
+
+        let names: Vec<String> = api.tags().await.into_iter().map(|t| t.name).collect();
+        assert_eq!(names, vec!["release-1", "release-2"]);
+        assert!(api.delete_tag("release-1").await.unwrap());
+        assert_eq!(api.tag("release-1").await, None);
+        assert!(api.verify_tag("release-1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_api_put_with_ttl_expires() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
+        consensus.initialize().await.unwrap();
+
+        // Wait for election
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+
+        let api = DistributedApi::new(consensus.clone());
+        let mut events = api.subscribe_changes();
+        api.put_with_ttl(b"key1".to_vec(), b"value1".to_vec(), Duration::ZERO)
+            .await
+            .unwrap();
+
+        assert_eq!(consensus.expire_due_keys(Duration::ZERO).await, 1);
+        assert_eq!(events.recv().await.unwrap().kind, ChangeKind::Put);
+        assert_eq!(events.recv().await.unwrap().kind, ChangeKind::Expire);
+
+        let value = api
+            .get(b"key1".to_vec(), ReadConsistency::Stale)
+            .await
+            .unwrap();
+        assert_eq!(value, None);
+    }
+
+    #[tokio::test]
+    async fn test_api_shadow_mirrors_writes() {
+        use crate::shadow::ShadowTarget;
+
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
+        consensus.initialize().await.unwrap();
+
+        // Wait for election
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+
+        let shadow = ShadowWriter::start(
+            ShadowTarget::Namespace {
+                consensus: consensus.clone(),
+                prefix: b"shadow/".to_vec(),
+            },
+            16,
+        )
+        .unwrap();
+        let api = DistributedApi::new(consensus.clone()).with_shadow(shadow);
+
+        api.put(b"key1".to_vec(), b"value1".to_vec()).await.unwrap();
+        api.delete(b"key1".to_vec()).await.unwrap();
+
+        for _ in 0..50 {
+            if api.shadow_stats().unwrap().mirrored == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        assert_eq!(api.shadow_stats().unwrap().mirrored, 2);
+        assert_eq!(consensus.client_read_local(b"shadow/key1").await, None);
+    }
+
     #[tokio::test]
     async fn test_api_read_consistency_enum() {
         // Just verify the enum values exist and can be used
@@ -609,4 +3526,36 @@ mod tests {
         assert_eq!(ReadConsistency::Linearizable, ReadConsistency::Linearizable);
         assert_ne!(ReadConsistency::Linearizable, ReadConsistency::Stale);
     }
+
+    #[tokio::test]
+    async fn test_api_linearizable_read_not_leader() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
+        let api = DistributedApi::new(consensus);
+
+        // Uninitialized node is not the leader
+        let result = api
+            .get(b"key".to_vec(), ReadConsistency::Linearizable)
+            .await;
+        assert!(matches!(
+            result,
+            Err(ScribeError::NotLeader { leader_id: None })
+        ));
+    }
+
+    #[test]
+    fn test_consensus_error_mapping() {
+        let err: Box<dyn std::error::Error + Send + Sync> =
+            Box::new(ScribeError::NotLeader { leader_id: Some(3) });
+        assert!(matches!(
+            consensus_error(err, "ctx"),
+            ScribeError::NotLeader { leader_id: Some(3) }
+        ));
+
+        let err: Box<dyn std::error::Error + Send + Sync> = Box::new(std::io::Error::other("boom"));
+        assert!(matches!(
+            consensus_error(err, "ctx"),
+            ScribeError::Consensus(msg) if msg == "ctx: boom"
+        ));
+    }
 }
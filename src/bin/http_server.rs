@@ -1,13 +1,18 @@
 use axum::{
-    body::Bytes,
-    extract::{Path, State},
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
     http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
     routing::{delete, get, put},
     Json, Router,
 };
-use hyra_scribe_ledger::{logging, metrics, HyraScribeLedger};
+use hyra_scribe_ledger::backup::BackupSigner;
+use hyra_scribe_ledger::http_metrics::HttpMetricsLayer;
+use hyra_scribe_ledger::runtime::RuntimeConfig;
+use hyra_scribe_ledger::storage::{prefix_range, KeyRange};
+use hyra_scribe_ledger::{logging, metrics, wire, HyraScribeLedger};
 use serde::{Deserialize, Serialize};
+use std::ops::Bound;
 use std::sync::{atomic::AtomicU64, Arc};
 use std::time::Instant;
 use tower_http::cors::CorsLayer;
@@ -28,6 +33,43 @@ struct ErrorResponse {
     error: String,
 }
 
+/// Entries returned by a scan when the request sets no limit
+const DEFAULT_SCAN_LIMIT: usize = 100;
+
+/// Largest page a scan returns
+const MAX_SCAN_LIMIT: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+struct ScanQuery {
+    /// Only return keys starting with this prefix (default: all keys)
+    #[serde(default)]
+    prefix: String,
+    /// Page size (default: DEFAULT_SCAN_LIMIT)
+    limit: Option<usize>,
+    /// Cursor: only return keys after the hex-encoded key given in `next`
+    after: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GetQuery {
+    /// Return the value the key held at this time (milliseconds since the Unix
+    /// epoch) instead of the current one
+    at: Option<u64>,
+}
+
+/// Environment variable naming the signing key file (from `scribe-ctl backup keygen`)
+/// that signs the roots of exported proof bundles
+const PROOF_SIGNING_KEY_ENV: &str = "SCRIBE_PROOF_SIGNING_KEY";
+
+/// Revisions returned by a history request when it sets no limit
+const DEFAULT_HISTORY_LIMIT: usize = 20;
+
+#[derive(Debug, Deserialize)]
+struct HistoryQuery {
+    /// Most recent revisions to return (default: DEFAULT_HISTORY_LIMIT)
+    limit: Option<usize>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct MetricsResponse {
     total_keys: usize,
@@ -95,15 +137,18 @@ struct AppState {
     gets: Arc<AtomicU64>,
     puts: Arc<AtomicU64>,
     deletes: Arc<AtomicU64>,
+    /// Signs the roots of exported proof bundles
+    proof_signer: Option<BackupSigner>,
 }
 
 impl AppState {
-    fn new(ledger: HyraScribeLedger) -> Self {
+    fn new(ledger: HyraScribeLedger, proof_signer: Option<BackupSigner>) -> Self {
         Self {
             ledger: Arc::new(ledger),
             gets: Arc::new(AtomicU64::new(0)),
             puts: Arc::new(AtomicU64::new(0)),
             deletes: Arc::new(AtomicU64::new(0)),
+            proof_signer,
         }
     }
 }
@@ -118,14 +163,13 @@ async fn put_handler(
     let start = Instant::now();
     let correlation_id = logging::generate_correlation_id();
 
-    debug!(correlation_id = %correlation_id, key = %key, "PUT request received");
+    debug!(correlation_id = %correlation_id, key = %logging::log_key(key.as_bytes()), "PUT request received");
 
     state
         .puts
         .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
     // Track metrics
-    metrics::PUT_REQUESTS.inc();
     metrics::OPS_TOTAL.inc();
 
     // Check content type to determine if we're handling binary or JSON
@@ -159,11 +203,10 @@ async fn put_handler(
     };
 
     let duration = start.elapsed();
-    metrics::PUT_LATENCY.observe(duration.as_secs_f64());
 
     match result {
         Ok(()) => {
-            info!(correlation_id = %correlation_id, key = %key, latency_ms = %duration.as_millis(), "PUT request successful");
+            info!(correlation_id = %correlation_id, key = %logging::log_key(key.as_bytes()), latency_ms = %duration.as_millis(), "PUT request successful");
             (
                 StatusCode::OK,
                 Json(serde_json::json!({"status": "ok", "message": "Value stored successfully"})),
@@ -171,7 +214,7 @@ async fn put_handler(
                 .into_response()
         }
         Err(e) => {
-            error!(correlation_id = %correlation_id, key = %key, error = %e, "PUT request failed");
+            error!(correlation_id = %correlation_id, key = %logging::log_key(key.as_bytes()), error = %e, "PUT request failed");
             metrics::ERRORS_TOTAL.inc();
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -188,19 +231,19 @@ async fn put_handler(
 async fn get_handler(
     State(state): State<Arc<AppState>>,
     Path(key): Path<String>,
+    Query(query): Query<GetQuery>,
     headers: HeaderMap,
 ) -> Response {
     let start = Instant::now();
     let correlation_id = logging::generate_correlation_id();
 
-    debug!(correlation_id = %correlation_id, key = %key, "GET request received");
+    debug!(correlation_id = %correlation_id, key = %logging::log_key(key.as_bytes()), at = ?query.at, "GET request received");
 
     state
         .gets
         .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
     // Track metrics
-    metrics::GET_REQUESTS.inc();
     metrics::OPS_TOTAL.inc();
 
     let accept = headers
@@ -208,11 +251,14 @@ async fn get_handler(
         .and_then(|v| v.to_str().ok())
         .unwrap_or("application/json");
 
-    let result = match state.ledger.get(&key) {
+    let value = match query.at {
+        Some(at) => state.ledger.get_at(&key, at),
+        None => state.ledger.get(&key),
+    };
+    let result = match value {
         Ok(Some(value_bytes)) => {
             let duration = start.elapsed();
-            metrics::GET_LATENCY.observe(duration.as_secs_f64());
-            info!(correlation_id = %correlation_id, key = %key, latency_ms = %duration.as_millis(), "GET request successful");
+            info!(correlation_id = %correlation_id, key = %logging::log_key(key.as_bytes()), latency_ms = %duration.as_millis(), "GET request successful");
 
             if accept.contains("application/octet-stream") {
                 // Return binary data directly
@@ -233,7 +279,7 @@ async fn get_handler(
                     )
                         .into_response(),
                     Err(_) => {
-                        warn!(correlation_id = %correlation_id, key = %key, "Value is binary data");
+                        warn!(correlation_id = %correlation_id, key = %logging::log_key(key.as_bytes()), "Value is binary data");
                         // If not valid UTF-8, return error
                         (
                             StatusCode::BAD_REQUEST,
@@ -247,15 +293,11 @@ async fn get_handler(
             }
         }
         Ok(None) => {
-            let duration = start.elapsed();
-            metrics::GET_LATENCY.observe(duration.as_secs_f64());
-            debug!(correlation_id = %correlation_id, key = %key, "GET request - key not found");
+            debug!(correlation_id = %correlation_id, key = %logging::log_key(key.as_bytes()), "GET request - key not found");
             (StatusCode::NOT_FOUND, Json(GetResponse { value: None })).into_response()
         }
         Err(e) => {
-            let duration = start.elapsed();
-            metrics::GET_LATENCY.observe(duration.as_secs_f64());
-            error!(correlation_id = %correlation_id, key = %key, error = %e, "GET request failed");
+            error!(correlation_id = %correlation_id, key = %logging::log_key(key.as_bytes()), error = %e, "GET request failed");
             metrics::ERRORS_TOTAL.inc();
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -279,55 +321,28 @@ async fn delete_handler(State(state): State<Arc<AppState>>, Path(key): Path<Stri
     let start = Instant::now();
     let correlation_id = logging::generate_correlation_id();
 
-    debug!(correlation_id = %correlation_id, key = %key, "DELETE request received");
+    debug!(correlation_id = %correlation_id, key = %logging::log_key(key.as_bytes()), "DELETE request received");
 
     state
         .deletes
         .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
     // Track metrics
-    metrics::DELETE_REQUESTS.inc();
     metrics::OPS_TOTAL.inc();
 
-    // Check if key exists first
-    let result = match state.ledger.get(&key) {
-        Ok(Some(_)) => {
-            // Key exists, perform deletion by setting to empty (sled doesn't have direct delete)
-            // We'll use remove via batch operation
-            let mut batch = HyraScribeLedger::new_batch();
-            batch.remove(key.as_bytes());
-            match state.ledger.apply_batch(batch) {
-                Ok(()) => {
-                    let duration = start.elapsed();
-                    metrics::DELETE_LATENCY.observe(duration.as_secs_f64());
-                    info!(correlation_id = %correlation_id, key = %key, latency_ms = %duration.as_millis(), "DELETE request successful");
-                    (
-                        StatusCode::OK,
-                        Json(
-                            serde_json::json!({"status": "ok", "message": "Key deleted successfully"}),
-                        ),
-                    )
-                        .into_response()
-                }
-                Err(e) => {
-                    let duration = start.elapsed();
-                    metrics::DELETE_LATENCY.observe(duration.as_secs_f64());
-                    error!(correlation_id = %correlation_id, key = %key, error = %e, "DELETE request failed");
-                    metrics::ERRORS_TOTAL.inc();
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(ErrorResponse {
-                            error: format!("Failed to delete key: {}", e),
-                        }),
-                    )
-                        .into_response()
-                }
-            }
-        }
-        Ok(None) => {
+    // Deleting through the ledger records the deletion in the key's history
+    let result = match state.ledger.delete(&key) {
+        Ok(true) => {
             let duration = start.elapsed();
-            metrics::DELETE_LATENCY.observe(duration.as_secs_f64());
-            debug!(correlation_id = %correlation_id, key = %key, "DELETE request - key not found");
+            info!(correlation_id = %correlation_id, key = %logging::log_key(key.as_bytes()), latency_ms = %duration.as_millis(), "DELETE request successful");
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({"status": "ok", "message": "Key deleted successfully"})),
+            )
+                .into_response()
+        }
+        Ok(false) => {
+            debug!(correlation_id = %correlation_id, key = %logging::log_key(key.as_bytes()), "DELETE request - key not found");
             (
                 StatusCode::NOT_FOUND,
                 Json(ErrorResponse {
@@ -337,14 +352,12 @@ async fn delete_handler(State(state): State<Arc<AppState>>, Path(key): Path<Stri
                 .into_response()
         }
         Err(e) => {
-            let duration = start.elapsed();
-            metrics::DELETE_LATENCY.observe(duration.as_secs_f64());
-            error!(correlation_id = %correlation_id, key = %key, error = %e, "DELETE request failed");
+            error!(correlation_id = %correlation_id, key = %logging::log_key(key.as_bytes()), error = %e, "DELETE request failed");
             metrics::ERRORS_TOTAL.inc();
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(ErrorResponse {
-                    error: format!("Failed to check key: {}", e),
+                    error: format!("Failed to delete key: {}", e),
                 }),
             )
                 .into_response()
@@ -354,6 +367,173 @@ async fn delete_handler(State(state): State<Arc<AppState>>, Path(key): Path<Stri
     result
 }
 
+// History endpoint - lists the values a key held, newest first
+//
+// The body is `{"key":..,"revisions":[{"version":..,"timestamp_ms":..,"value":..},..]}`.
+// Deletions have a null `value`; values that are not UTF-8 are returned
+// hex-encoded as `value_hex`.
+async fn history_handler(
+    State(state): State<Arc<AppState>>,
+    Path(key): Path<String>,
+    Query(query): Query<HistoryQuery>,
+) -> Response {
+    let limit = query.limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+    if limit == 0 || limit > MAX_SCAN_LIMIT {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: format!("limit must be between 1 and {}", MAX_SCAN_LIMIT),
+            }),
+        )
+            .into_response();
+    }
+
+    metrics::OPS_TOTAL.inc();
+    debug!(key = %logging::log_key(key.as_bytes()), limit, "HISTORY request received");
+
+    match state.ledger.history(&key, limit) {
+        Ok(revisions) => {
+            let revisions: Vec<serde_json::Value> = revisions
+                .into_iter()
+                .map(|revision| {
+                    let mut item = serde_json::json!({
+                        "version": revision.version,
+                        "timestamp_ms": revision.timestamp_ms,
+                    });
+                    match revision.value {
+                        Some(value) => match String::from_utf8(value) {
+                            Ok(value) => item["value"] = value.into(),
+                            Err(e) => item["value_hex"] = hex::encode(e.into_bytes()).into(),
+                        },
+                        None => item["value"] = serde_json::Value::Null,
+                    }
+                    item
+                })
+                .collect();
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({ "key": key, "revisions": revisions })),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            error!(key = %logging::log_key(key.as_bytes()), error = %e, "HISTORY request failed");
+            metrics::ERRORS_TOTAL.inc();
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: format!("Failed to read history: {}", e),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
+// Scan endpoint - streams the entries under a prefix in key order, one page at a time
+//
+// The body is `{"items":[{"key":..,"value":..},..],"next":..}`. Keys and values that
+// are not UTF-8 are returned hex-encoded as `key_hex` and `value_hex`. `next` is the
+// cursor to pass as `after` for the following page (the last key, hex-encoded), or
+// null after the last page.
+async fn scan_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ScanQuery>,
+) -> Response {
+    let limit = query.limit.unwrap_or(DEFAULT_SCAN_LIMIT);
+    if limit == 0 || limit > MAX_SCAN_LIMIT {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: format!("limit must be between 1 and {}", MAX_SCAN_LIMIT),
+            }),
+        )
+            .into_response();
+    }
+
+    let after = match query.after.as_deref().map(hex::decode).transpose() {
+        Ok(after) => after,
+        Err(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: "after must be a cursor returned as next".to_string(),
+                }),
+            )
+                .into_response();
+        }
+    };
+
+    metrics::OPS_TOTAL.inc();
+    debug!(prefix = %logging::log_key(query.prefix.as_bytes()), limit, "SCAN request received");
+
+    let entries = state
+        .ledger
+        .range(scan_range(query.prefix.as_bytes(), after.as_deref()));
+
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/json")],
+        Body::from_stream(futures::stream::iter(scan_chunks(entries, limit))),
+    )
+        .into_response()
+}
+
+/// Keys under `prefix` that follow the cursor `after`, if any
+fn scan_range(prefix: &[u8], after: Option<&[u8]>) -> KeyRange {
+    let (start, end) = prefix_range(prefix);
+    let start = match after {
+        Some(after) if after >= prefix => Bound::Excluded(after.to_vec()),
+        _ => start,
+    };
+    (start, end)
+}
+
+/// JSON chunks of a scan page of at most `limit` entries, read lazily from `entries`
+fn scan_chunks(
+    mut entries: sled::Iter,
+    limit: usize,
+) -> impl Iterator<Item = Result<String, sled::Error>> {
+    let mut sent = 0;
+    let mut last_key = None;
+    let mut finished = false;
+    std::iter::once(Ok("{\"items\":[".to_string())).chain(std::iter::from_fn(move || {
+        if finished {
+            return None;
+        }
+        match entries.next().transpose() {
+            Ok(Some((key, value))) if sent < limit => {
+                let mut item = serde_json::Map::new();
+                match std::str::from_utf8(&key) {
+                    Ok(key) => item.insert("key".to_string(), key.into()),
+                    Err(_) => item.insert("key_hex".to_string(), hex::encode(&key).into()),
+                };
+                match std::str::from_utf8(&value) {
+                    Ok(value) => item.insert("value".to_string(), value.into()),
+                    Err(_) => item.insert("value_hex".to_string(), hex::encode(&value).into()),
+                };
+                let item = serde_json::Value::Object(item);
+                let separator = if sent == 0 { "" } else { "," };
+                sent += 1;
+                last_key = Some(hex::encode(&key));
+                Some(Ok(format!("{}{}", separator, item)))
+            }
+            Ok(more) => {
+                // One entry past the page means there is another page
+                finished = true;
+                let next = more.and(last_key.take());
+                Some(Ok(format!("],\"next\":{}}}", serde_json::json!(next))))
+            }
+            Err(e) => {
+                error!(error = %e, "SCAN request failed");
+                metrics::ERRORS_TOTAL.inc();
+                finished = true;
+                Some(Err(e))
+            }
+        }
+    }))
+}
+
 // Health check endpoint
 async fn health_handler() -> Json<serde_json::Value> {
     Json(serde_json::json!({
@@ -405,16 +585,16 @@ async fn prometheus_metrics_handler() -> Response {
 //
 // To add a node to a cluster in production:
 // 1. Start the new node with scribe-node binary
-// 2. From the leader node, call: consensus.add_learner(node_id, BasicNode { addr })
-// 3. Wait for log replication to catch up
-// 4. From the leader node, call: consensus.change_membership(members)
+// 2. POST /cluster/nodes/add on the scribe-node leader, which adds the node as a
+//    learner, waits for it to catch up and promotes it to voter
+// 3. Follow the change at GET /cluster/nodes/changes/:id
 async fn cluster_join_handler(Json(payload): Json<ClusterJoinRequest>) -> Response {
     (
         StatusCode::NOT_IMPLEMENTED,
         Json(serde_json::json!({
             "error": "Cluster management not available in standalone HTTP server",
             "message": format!("Node {} joining at {}", payload.node_id, payload.address),
-            "note": "Use scribe-node binary with ConsensusNode for distributed cluster operations"
+            "note": "Use POST /cluster/nodes/add on a scribe-node leader"
         })),
     )
         .into_response()
@@ -426,15 +606,15 @@ async fn cluster_join_handler(Json(payload): Json<ClusterJoinRequest>) -> Respon
 // For distributed cluster operations, use the scribe-node binary.
 //
 // To remove a node from a cluster in production:
-// 1. From the leader node, call: consensus.change_membership() without the departing node
-// 2. Stop the departing node
+// 1. POST /cluster/nodes/remove on the scribe-node leader
+// 2. Stop the departing node once the change has completed
 async fn cluster_leave_handler(Json(payload): Json<ClusterLeaveRequest>) -> Response {
     (
         StatusCode::NOT_IMPLEMENTED,
         Json(serde_json::json!({
             "error": "Cluster management not available in standalone HTTP server",
             "message": format!("Node {} leaving cluster", payload.node_id),
-            "note": "Use scribe-node binary with ConsensusNode for distributed cluster operations"
+            "note": "Use POST /cluster/nodes/remove on a scribe-node leader"
         })),
     )
         .into_response()
@@ -588,12 +768,55 @@ async fn verify_handler(State(state): State<Arc<AppState>>, Path(key): Path<Stri
     }
 }
 
-#[tokio::main(flavor = "multi_thread", worker_threads = 4)]
-async fn main() -> anyhow::Result<()> {
+// Proof export endpoint - a sealed proof bundle for offline checks with scribe-verify
+async fn proof_handler(State(state): State<Arc<AppState>>, Path(key): Path<String>) -> Response {
+    match state.ledger.proof_bundle(&key, state.proof_signer.as_ref()) {
+        Ok(Some(bundle)) => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, "application/octet-stream")],
+            wire::seal(&bundle),
+        )
+            .into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Key not found".to_string(),
+            }),
+        )
+            .into_response(),
+        Err(e) => {
+            metrics::ERRORS_TOTAL.inc();
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: format!("Failed to export proof: {}", e),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
+fn main() -> anyhow::Result<()> {
     // Initialize logging with default configuration
     let log_config = logging::LogConfig::default();
     let _guard = logging::init_logging(log_config);
 
+    // Keys are redacted in logs as set by SCRIBE_LOG_KEYS
+    let mut key_logging = logging::KeyLoggingConfig::default();
+    key_logging.apply_env_overrides();
+    key_logging.validate().map_err(anyhow::Error::msg)?;
+    logging::set_key_logging(key_logging);
+
+    // Runtime sizing and pinning come from SCRIBE_WORKER_THREADS,
+    // SCRIBE_MAX_BLOCKING_THREADS and SCRIBE_PIN_CORES
+    let mut runtime_config = RuntimeConfig::default();
+    runtime_config.apply_env_overrides();
+    runtime_config.validate().map_err(anyhow::Error::msg)?;
+    runtime_config.build()?.block_on(serve())
+}
+
+async fn serve() -> anyhow::Result<()> {
     info!("Starting Hyra Scribe Ledger HTTP Server...");
 
     // Initialize Prometheus metrics
@@ -601,8 +824,16 @@ async fn main() -> anyhow::Result<()> {
     info!("Metrics system initialized");
 
     // Initialize the ledger with optimized configuration
-    let ledger = HyraScribeLedger::temp()?;
-    let app_state = Arc::new(AppState::new(ledger));
+    let ledger = HyraScribeLedger::temp()?.with_history()?;
+    let proof_signer = match std::env::var_os(PROOF_SIGNING_KEY_ENV) {
+        Some(path) => {
+            let signer = BackupSigner::from_key_file(&path)?;
+            info!(public_key = %signer.public_key_hex(), "Signing proof bundle roots");
+            Some(signer)
+        }
+        None => None,
+    };
+    let app_state = Arc::new(AppState::new(ledger, proof_signer));
 
     info!("Ledger initialized");
 
@@ -611,8 +842,11 @@ async fn main() -> anyhow::Result<()> {
     let app = Router::new()
         .route("/:key", get(get_handler))
         .route("/:key", put(put_handler))
+        .route("/scan", get(scan_handler))
         .route("/:key", delete(delete_handler))
         .route("/verify/:key", get(verify_handler))
+        .route("/proof/:key", get(proof_handler))
+        .route("/history/:key", get(history_handler))
         .route("/health", get(health_handler))
         .route("/metrics", get(metrics_handler))
         .route("/metrics/prometheus", get(prometheus_metrics_handler))
@@ -628,6 +862,7 @@ async fn main() -> anyhow::Result<()> {
             axum::routing::post(cluster_leave_handler),
         )
         .with_state(app_state)
+        .layer(HttpMetricsLayer)
         .layer(CorsLayer::permissive());
 
     info!("Server starting on http://0.0.0.0:3000");
@@ -637,8 +872,12 @@ async fn main() -> anyhow::Result<()> {
     info!("  GET    /metrics/prometheus      - Prometheus metrics endpoint");
     info!("  PUT    /:key                    - Store a value (JSON or binary)");
     info!("  GET    /:key                    - Retrieve a value (JSON or binary)");
+    info!("  GET    /:key?at=<ms>            - Retrieve the value held at a point in time");
+    info!("  GET    /history/:key?limit=     - List earlier values of a key, newest first");
     info!("  DELETE /:key                    - Delete a key");
+    info!("  GET    /scan?prefix=&limit=&after= - List entries in key order, paginated");
     info!("  GET    /verify/:key             - Verify a key with Merkle proof");
+    info!("  GET    /proof/:key              - Export a proof bundle for scribe-verify");
     info!("");
     info!("Cluster management endpoints:");
     info!("  POST   /cluster/nodes/add       - Add a node to the cluster");
@@ -656,11 +895,20 @@ async fn main() -> anyhow::Result<()> {
     println!("  curl -X PUT http://localhost:3000/binary -H 'Content-Type: application/octet-stream' --data-binary @file.bin");
     println!("  curl -H 'Accept: application/octet-stream' http://localhost:3000/binary");
     println!();
+    println!("  # List keys under a prefix, then fetch the next page:");
+    println!("  curl 'http://localhost:3000/scan?prefix=user:&limit=100'");
+    println!("  curl 'http://localhost:3000/scan?prefix=user:&limit=100&after=757365723a626f62'");
+    println!();
+    println!("  # Earlier values, and the value an hour ago:");
+    println!("  curl http://localhost:3000/history/test");
+    println!("  curl \"http://localhost:3000/test?at=$(( $(date +%s%3N) - 3600000 ))\"");
+    println!();
     println!("  # Delete:");
     println!("  curl -X DELETE http://localhost:3000/test");
     println!();
     println!("  # Verify:");
     println!("  curl http://localhost:3000/verify/test");
+    println!("  curl -o test.proof http://localhost:3000/proof/test && scribe-verify test.proof");
     println!();
     println!("  # Metrics:");
     println!("  curl http://localhost:3000/metrics");
@@ -675,3 +923,73 @@ async fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Body of the scan page of at most `limit` entries in `range`
+    fn scan_page(ledger: &HyraScribeLedger, range: KeyRange, limit: usize) -> serde_json::Value {
+        let body: String = scan_chunks(ledger.range(range), limit)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        serde_json::from_str(&body).unwrap()
+    }
+
+    #[test]
+    fn test_scan_chunks_encode_keys_and_values() {
+        let ledger = HyraScribeLedger::temp().unwrap();
+        ledger.put("user:alice", "a").unwrap();
+        ledger.put(b"user:\xff", b"\x00\xfe".as_slice()).unwrap();
+
+        let page = scan_page(&ledger, prefix_range(b"user:"), 10);
+        assert_eq!(
+            page["items"],
+            serde_json::json!([
+                { "key": "user:alice", "value": "a" },
+                { "key_hex": "757365723aff", "value_hex": "00fe" },
+            ])
+        );
+        assert!(page["next"].is_null());
+
+        let page = scan_page(&ledger, prefix_range(b"none:"), 10);
+        assert_eq!(page, serde_json::json!({ "items": [], "next": null }));
+    }
+
+    #[test]
+    fn test_scan_pages_follow_the_cursor() {
+        let ledger = HyraScribeLedger::temp().unwrap();
+        ledger.put("other", "x").unwrap();
+        ledger.put("user:a", "1").unwrap();
+        ledger.put(b"user:\xff", b"2".as_slice()).unwrap();
+        ledger.put(b"user:\xff\x01", b"3".as_slice()).unwrap();
+
+        let mut keys = Vec::new();
+        let mut after: Option<Vec<u8>> = None;
+        loop {
+            let page = scan_page(&ledger, scan_range(b"user:", after.as_deref()), 2);
+            for item in page["items"].as_array().unwrap() {
+                keys.push(match item.get("key") {
+                    Some(key) => key.as_str().unwrap().as_bytes().to_vec(),
+                    None => hex::decode(item["key_hex"].as_str().unwrap()).unwrap(),
+                });
+            }
+            match page["next"].as_str() {
+                Some(next) => after = Some(hex::decode(next).unwrap()),
+                None => break,
+            }
+        }
+        assert_eq!(
+            keys,
+            vec![
+                b"user:a".to_vec(),
+                b"user:\xff".to_vec(),
+                b"user:\xff\x01".to_vec()
+            ]
+        );
+
+        // A cursor before the prefix starts at the prefix
+        let page = scan_page(&ledger, scan_range(b"user:", Some(b"a")), 10);
+        assert_eq!(page["items"].as_array().unwrap().len(), 3);
+    }
+}
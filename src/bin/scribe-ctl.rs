@@ -0,0 +1,556 @@
+//! Scribe Ctl - Cluster administration tool
+//!
+//! Talks to a node's client API to perform cluster administration tasks such as
+//! issuing join tokens for new nodes, syncs one cluster's data into another,
+//! reports conflicts between actively replicated clusters, creates, verifies and
+//! restores encrypted, signed backups of tag exports, and estimates the resources a
+//! workload needs before deployment.
+
+use anyhow::{bail, Context, Result};
+use clap::{Args, Parser, Subcommand};
+use hyra_scribe_ledger::backup::{
+    is_encrypted, open_backup, parse_export, seal_backup, verify_signature, BackupKey, BackupSigner,
+};
+use hyra_scribe_ledger::capacity::{simulate, CostModel, NodeLayout, Projection, Workload};
+use hyra_scribe_ledger::config::Config;
+use hyra_scribe_ledger::http_client::{leader_aware_client, send_following_leader, ClusterClient};
+use hyra_scribe_ledger::replication::ConflictDetector;
+use hyra_scribe_ledger::security::parse_ttl;
+use hyra_scribe_ledger::sync::{DifferentialSync, DEFAULT_SYNC_BUCKETS};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Hyra Scribe Ledger - Cluster administration
+#[derive(Parser, Debug)]
+#[command(name = "scribe-ctl")]
+#[command(version = env!("CARGO_PKG_VERSION"))]
+#[command(about = "Administer a Scribe Ledger cluster", long_about = None)]
+struct Cli {
+    /// Client API URL of any cluster node (its admin listener, if the cluster
+    /// serves admin endpoints on a separate port)
+    #[arg(long, default_value = "http://127.0.0.1:8001")]
+    node: String,
+
+    /// Cluster secret used to authenticate administrative requests
+    /// (defaults to SCRIBE_CLUSTER_SECRET)
+    #[arg(long)]
+    secret: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Cluster membership administration
+    Cluster {
+        #[command(subcommand)]
+        command: ClusterCommand,
+    },
+    /// Copy the keys that differ from a source cluster to a target cluster
+    Sync {
+        /// Client API URL of a source cluster node (repeat for failover)
+        #[arg(long, required = true)]
+        source: Vec<String>,
+        /// Client API URL of a target cluster node (repeat for failover)
+        #[arg(long, required = true)]
+        target: Vec<String>,
+        /// Number of buckets keys are compared in
+        #[arg(long, default_value_t = DEFAULT_SYNC_BUCKETS)]
+        buckets: u32,
+        /// Delete keys the target holds but the source does not
+        #[arg(long)]
+        delete: bool,
+        /// Only report what would change
+        #[arg(long)]
+        dry_run: bool,
+        /// Keep syncing at this interval (e.g. 5m) instead of running once
+        #[arg(long)]
+        interval: Option<String>,
+    },
+    /// Bidirectional replication between two clusters
+    Replication {
+        #[command(subcommand)]
+        command: ReplicationCommand,
+    },
+    /// Encrypted, signed backups of tag exports
+    Backup {
+        #[command(subcommand)]
+        command: BackupCommand,
+    },
+    /// Estimate the disk, S3 and memory usage of a node under a steady write workload
+    Simulate(SimulateArgs),
+}
+
+/// Workload and node settings of a capacity simulation
+#[derive(Args, Debug)]
+struct SimulateArgs {
+    /// Sustained writes per second
+    #[arg(long)]
+    writes_per_sec: f64,
+    /// Average value size in bytes
+    #[arg(long)]
+    avg_value_size: u64,
+    /// Average key size in bytes
+    #[arg(long, default_value_t = 32)]
+    avg_key_size: u64,
+    /// Days each write is kept
+    #[arg(long)]
+    retention_days: u64,
+    /// Days to project (default: the retention period, after which usage levels off)
+    #[arg(long)]
+    days: Option<u64>,
+    /// Days between projections
+    #[arg(long, default_value_t = 30)]
+    step_days: u64,
+    /// Node configuration file to take segment, snapshot and mmap settings from
+    /// (default: the built-in defaults)
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// File of representative values, one per line, to measure compression with
+    /// (default: synthetic JSON values)
+    #[arg(long)]
+    sample: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+enum ClusterCommand {
+    /// Manage join tokens
+    Token {
+        #[command(subcommand)]
+        command: TokenCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum TokenCommand {
+    /// Issue a one-time join token on the leader
+    Create {
+        /// Token lifetime (e.g. 90s, 30m, 1h, 7d)
+        #[arg(long, default_value = "1h")]
+        ttl: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ReplicationCommand {
+    /// Report the keys that differ between two actively replicated clusters
+    Conflicts {
+        /// Client API URL of a node of the first cluster (repeat for failover)
+        #[arg(long, required = true)]
+        left: Vec<String>,
+        /// Client API URL of a node of the second cluster (repeat for failover)
+        #[arg(long, required = true)]
+        right: Vec<String>,
+        /// Number of buckets keys are compared in
+        #[arg(long, default_value_t = DEFAULT_SYNC_BUCKETS)]
+        buckets: u32,
+        /// Keep checking at this interval (e.g. 5m) instead of running once
+        #[arg(long)]
+        interval: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum BackupCommand {
+    /// Generate an Ed25519 signing key and print its public key
+    Keygen {
+        /// File to write the signing key to
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Export a tag into a backup file, encrypted and signed if keys are given
+    Create {
+        /// Tag to export
+        #[arg(long)]
+        tag: String,
+        /// Backup file to write; the signature goes to `<out>.sig`
+        #[arg(long)]
+        out: PathBuf,
+        #[command(flatten)]
+        encryption: EncryptionArgs,
+        /// Signing key file from `backup keygen`
+        #[arg(long)]
+        signing_key: Option<PathBuf>,
+    },
+    /// Check a backup's signature, and its contents if it can be decrypted
+    Verify {
+        /// Backup file
+        file: PathBuf,
+        /// Hex public key printed by `backup keygen`
+        #[arg(long)]
+        public_key: String,
+        /// Signature file (default: `<file>.sig`)
+        #[arg(long)]
+        signature: Option<PathBuf>,
+        #[command(flatten)]
+        encryption: EncryptionArgs,
+    },
+    /// Verify a backup and write its keys to the cluster
+    Restore {
+        /// Backup file
+        file: PathBuf,
+        /// Hex public key printed by `backup keygen`
+        #[arg(long, required_unless_present = "unsigned")]
+        public_key: Option<String>,
+        /// Signature file (default: `<file>.sig`)
+        #[arg(long)]
+        signature: Option<PathBuf>,
+        /// Restore without checking a signature
+        #[arg(long, conflicts_with = "public_key")]
+        unsigned: bool,
+        #[command(flatten)]
+        encryption: EncryptionArgs,
+    },
+}
+
+/// Backup encryption secret
+#[derive(Args, Debug)]
+struct EncryptionArgs {
+    /// Encryption key file (64 hex characters)
+    #[arg(long, conflicts_with = "passphrase_env")]
+    key_file: Option<PathBuf>,
+    /// Environment variable holding an encryption passphrase
+    #[arg(long)]
+    passphrase_env: Option<String>,
+}
+
+impl EncryptionArgs {
+    fn key(&self) -> Result<Option<BackupKey>> {
+        if let Some(path) = &self.key_file {
+            return Ok(Some(BackupKey::from_key_file(path)?));
+        }
+        match &self.passphrase_env {
+            Some(var) => {
+                let passphrase = std::env::var(var)
+                    .with_context(|| format!("Passphrase variable {} is not set", var))?;
+                Ok(Some(BackupKey::Passphrase(passphrase)))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CreateTokenResponse {
+    token: String,
+    cluster_id: String,
+    expires_at: u64,
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    match &cli.command {
+        Command::Cluster {
+            command:
+                ClusterCommand::Token {
+                    command: TokenCommand::Create { ttl },
+                },
+        } => create_token(&cli, ttl).await,
+        Command::Sync {
+            source,
+            target,
+            buckets,
+            delete,
+            dry_run,
+            interval,
+        } => {
+            let sync = DifferentialSync::new(
+                ClusterClient::new(source.clone())?,
+                ClusterClient::new(target.clone())?,
+            )
+            .with_buckets(*buckets)
+            .with_delete_extra(*delete)
+            .with_dry_run(*dry_run);
+            let interval = interval
+                .as_deref()
+                .map(parse_ttl)
+                .transpose()
+                .map_err(|e| anyhow::anyhow!(e))?;
+            run_sync(&sync, interval).await
+        }
+        Command::Replication {
+            command:
+                ReplicationCommand::Conflicts {
+                    left,
+                    right,
+                    buckets,
+                    interval,
+                },
+        } => {
+            let detector = ConflictDetector::new(
+                ClusterClient::new(left.clone())?,
+                ClusterClient::new(right.clone())?,
+            )
+            .with_buckets(*buckets);
+            let interval = interval
+                .as_deref()
+                .map(parse_ttl)
+                .transpose()
+                .map_err(|e| anyhow::anyhow!(e))?;
+            run_conflict_check(&detector, interval).await
+        }
+        Command::Backup { command } => run_backup(&cli, command).await,
+        Command::Simulate(args) => run_simulate(args),
+    }
+}
+
+/// Output of `simulate`
+#[derive(Serialize)]
+struct SimulationReport<'a> {
+    workload: &'a Workload,
+    layout: &'a NodeLayout,
+    costs: &'a CostModel,
+    projection: &'a [Projection],
+}
+
+/// Measure per-write costs and print the projected usage as JSON
+fn run_simulate(args: &SimulateArgs) -> Result<()> {
+    if !args.writes_per_sec.is_finite() || args.writes_per_sec <= 0.0 || args.retention_days == 0 {
+        bail!("--writes-per-sec and --retention-days must be greater than 0");
+    }
+    let config = match &args.config {
+        Some(path) => Config::from_file(&path.to_string_lossy())?,
+        None => Config::default_for_node(1),
+    };
+    let samples: Vec<Vec<u8>> = match &args.sample {
+        Some(path) => std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| line.as_bytes().to_vec())
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let workload = Workload {
+        writes_per_sec: args.writes_per_sec,
+        avg_key_size: args.avg_key_size,
+        avg_value_size: args.avg_value_size,
+        retention_days: args.retention_days,
+    };
+    let layout = NodeLayout::from_config(&config);
+    let costs = CostModel::measure(&workload, &samples, &layout)?;
+    let days = args.days.unwrap_or(args.retention_days);
+    let projection = simulate(&workload, &layout, &costs, days, args.step_days);
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&SimulationReport {
+            workload: &workload,
+            layout: &layout,
+            costs: &costs,
+            projection: &projection,
+        })?
+    );
+    Ok(())
+}
+
+/// Path of the detached signature of `file`
+fn signature_path(file: &Path, signature: Option<&PathBuf>) -> PathBuf {
+    signature.cloned().unwrap_or_else(|| {
+        let mut path = file.as_os_str().to_owned();
+        path.push(".sig");
+        PathBuf::from(path)
+    })
+}
+
+async fn run_backup(cli: &Cli, command: &BackupCommand) -> Result<()> {
+    match command {
+        BackupCommand::Keygen { out } => {
+            let (signer, pkcs8) = BackupSigner::generate()?;
+            write_private(out, &hex::encode(pkcs8))?;
+            eprintln!("Signing key written to {}; public key:", out.display());
+            println!("{}", signer.public_key_hex());
+        }
+        BackupCommand::Create {
+            tag,
+            out,
+            encryption,
+            signing_key,
+        } => {
+            let url = format!(
+                "{}/admin/tags/{}/export",
+                cli.node.trim_end_matches('/'),
+                tag
+            );
+            let response = reqwest::get(&url).await?;
+            let status = response.status();
+            if !status.is_success() {
+                let message = response.text().await.unwrap_or_default();
+                bail!("Failed to export tag '{}' ({}): {}", tag, status, message);
+            }
+            let export = response.bytes().await?;
+
+            let signer = signing_key
+                .as_ref()
+                .map(BackupSigner::from_key_file)
+                .transpose()?;
+            let sealed = seal_backup(&export, encryption.key()?.as_ref(), signer.as_ref())?;
+            std::fs::write(out, &sealed.data)?;
+            if let Some(signature) = &sealed.signature {
+                std::fs::write(signature_path(out, None), format!("{}\n", signature))?;
+            }
+            println!(
+                "{}",
+                serde_json::json!({
+                    "file": out,
+                    "pairs": parse_export(&export)?.len(),
+                    "encrypted": is_encrypted(&sealed.data),
+                    "signed": sealed.signature.is_some(),
+                })
+            );
+        }
+        BackupCommand::Verify {
+            file,
+            public_key,
+            signature,
+            encryption,
+        } => {
+            let data = std::fs::read(file)?;
+            let signature = std::fs::read_to_string(signature_path(file, signature.as_ref()))
+                .context("Failed to read the backup signature")?;
+            verify_signature(&data, &signature, public_key)?;
+
+            // Contents can only be checked if the backup can be read
+            let encrypted = is_encrypted(&data);
+            let pairs = match (encrypted, encryption.key()?) {
+                (true, None) => None,
+                (_, key) => {
+                    let export = open_backup(&data, None, None, key.as_ref())?;
+                    Some(parse_export(&export)?.len())
+                }
+            };
+            println!(
+                "{}",
+                serde_json::json!({
+                    "file": file,
+                    "signature": "valid",
+                    "encrypted": encrypted,
+                    "pairs": pairs,
+                })
+            );
+        }
+        BackupCommand::Restore {
+            file,
+            public_key,
+            signature,
+            unsigned: _,
+            encryption,
+        } => {
+            let data = std::fs::read(file)?;
+            let signature = match public_key {
+                Some(_) => Some(
+                    std::fs::read_to_string(signature_path(file, signature.as_ref()))
+                        .context("Failed to read the backup signature")?,
+                ),
+                None => None,
+            };
+            let export = open_backup(
+                &data,
+                signature.as_deref(),
+                public_key.as_deref(),
+                encryption.key()?.as_ref(),
+            )?;
+            let pairs = parse_export(&export)?;
+
+            let client = ClusterClient::new(vec![cli.node.clone()])?;
+            for (key, value) in &pairs {
+                client.put(key, value.clone()).await?;
+            }
+            println!(
+                "{}",
+                serde_json::json!({ "file": file, "restored": pairs.len() })
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Write a secret to a file readable only by its owner
+fn write_private(path: &Path, contents: &str) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    std::io::Write::write_all(&mut file, contents.as_bytes())?;
+    Ok(())
+}
+
+/// Sync once, or every `interval` until interrupted, printing each report as JSON
+async fn run_sync(sync: &DifferentialSync, interval: Option<std::time::Duration>) -> Result<()> {
+    loop {
+        match sync.run().await {
+            Ok(report) => println!("{}", serde_json::to_string(&report)?),
+            // A periodic sync retries on the next tick
+            Err(e) if interval.is_some() => eprintln!("Sync failed: {}", e),
+            Err(e) => bail!("Sync failed: {}", e),
+        }
+        match interval {
+            Some(interval) => tokio::time::sleep(interval).await,
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Compare the clusters once, or every `interval` until interrupted, printing each
+/// report as JSON
+async fn run_conflict_check(
+    detector: &ConflictDetector,
+    interval: Option<std::time::Duration>,
+) -> Result<()> {
+    loop {
+        match detector.run().await {
+            Ok(report) => println!("{}", serde_json::to_string(&report)?),
+            // A periodic check retries on the next tick
+            Err(e) if interval.is_some() => eprintln!("Conflict check failed: {}", e),
+            Err(e) => bail!("Conflict check failed: {}", e),
+        }
+        match interval {
+            Some(interval) => tokio::time::sleep(interval).await,
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Issue a join token and print it to stdout
+async fn create_token(cli: &Cli, ttl: &str) -> Result<()> {
+    let ttl = parse_ttl(ttl).map_err(|e| anyhow::anyhow!(e))?;
+    let Some(secret) = cli
+        .secret
+        .clone()
+        .or_else(|| std::env::var("SCRIBE_CLUSTER_SECRET").ok())
+    else {
+        bail!("A cluster secret is required (--secret or SCRIBE_CLUSTER_SECRET)");
+    };
+
+    let client = leader_aware_client()?;
+    let url = format!("{}/cluster/tokens", cli.node.trim_end_matches('/'));
+    let body = serde_json::json!({ "ttl_secs": ttl.as_secs() });
+
+    let response = send_following_leader(&url, |url| {
+        client.post(url).bearer_auth(&secret).json(&body)
+    })
+    .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let message = response.text().await.unwrap_or_default();
+        bail!("Failed to create join token ({}): {}", status, message);
+    }
+
+    let created: CreateTokenResponse = response.json().await?;
+    eprintln!(
+        "Single-use join token for cluster '{}' (expires at Unix time {}):",
+        created.cluster_id, created.expires_at
+    );
+    println!("{}", created.token);
+    Ok(())
+}
@@ -6,22 +6,109 @@
 
 use anyhow::Result;
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::IntoResponse,
-    routing::{delete, get, put},
+    body::{Body, HttpBody},
+    extract::{
+        ConnectInfo, Extension, MatchedPath, OriginalUri, Path, Query, RawPathParams, Request,
+        State,
+    },
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    middleware::Next,
+    routing::{delete, get, patch, post, put},
     Router,
 };
 use bytes::Bytes;
-use clap::Parser;
-use hyra_scribe_ledger::api::{DistributedApi, ReadConsistency};
-use hyra_scribe_ledger::cluster::{ClusterConfig, ClusterInitializer, InitMode};
-use hyra_scribe_ledger::config::Config;
-use hyra_scribe_ledger::consensus::ConsensusNode;
-use hyra_scribe_ledger::discovery::DiscoveryService;
+use clap::{Parser, Subcommand};
+use futures::StreamExt;
+use hyra_scribe_ledger::access_log::{AccessLog, AccessRecord};
+use hyra_scribe_ledger::admin_events::{AdminAction, AdminEventFilter, AdminEventLog};
+use hyra_scribe_ledger::admission::{
+    queue_depth_bucket, AdmissionController, Priority, PRIORITY_HEADER,
+};
+use hyra_scribe_ledger::api::{ConsistencyToken, DistributedApi, ReadConsistency, StorageTier};
+use hyra_scribe_ledger::backup::ExportedPair;
+use hyra_scribe_ledger::bulk::{self, StreamDecoder};
+use hyra_scribe_ledger::cache::{HotDataCache, WarmKeys, WARM_KEYS_TREE};
+use hyra_scribe_ledger::canary::ReadMirror;
+use hyra_scribe_ledger::clock_skew::ClockSkewMonitor;
+use hyra_scribe_ledger::cluster::{
+    BootstrapManifest, ClusterConfig, ClusterInitializer, InitMode, JoinRequest,
+};
+use hyra_scribe_ledger::config::{Config, Profile, S3Config};
+use hyra_scribe_ledger::consensus::{
+    serve_raft_rpc_with_auth, ChangeEvent, ConsensusNode, KeyChange, LeadershipChange,
+    SnapshotStore, SNAPSHOT_BUILD_TIMEOUT,
+};
+use hyra_scribe_ledger::demo::{DemoDataGenerator, DemoKind, DEFAULT_DEMO_VALUE_SIZE};
+use hyra_scribe_ledger::discovery::{DiscoveryService, JoinPolicy};
+use hyra_scribe_ledger::error::ScribeError;
+use hyra_scribe_ledger::error_history::{ErrorFilter, ErrorHistory};
+use hyra_scribe_ledger::health::{
+    disk_free_ratio, HealthMonitor, HealthSignals, CLEAR_CACHE, RESTART_ARCHIVAL, STEP_DOWN,
+};
+use hyra_scribe_ledger::http_client::{
+    negotiate_api_version, parse_wait, value_etag, AckRequest, AckResponse, AcquireLockRequest,
+    AdvanceEpochRequest, BatchOp, CreateSessionRequest, DequeueRequest, EnqueueResponse,
+    EpochResponse, ReleaseLockRequest, ReleaseLockResponse, RenewLockRequest, TouchSessionResponse,
+    API_VERSION_HEADER, CONSISTENCY_TOKEN_HEADER, FENCE_EPOCH_HEADER, FENCE_NAMESPACE_HEADER,
+    KEY_VERSION_HEADER, MIN_APPLIED_HEADER, NODE_LOAD_HEADER, NODE_QUEUE_DEPTH_HEADER,
+    NODE_ROLE_HEADER, QUEUE_ATTEMPTS_HEADER, QUEUE_ITEM_ID_HEADER, QUEUE_RECEIPT_HEADER,
+    QUEUE_VISIBLE_AT_HEADER, RAFT_LEADER_HEADER, RAFT_LEADER_ID_HEADER, SERVED_BY_HEADER,
+    TAG_MERKLE_ROOT_HEADER, TAG_RAFT_INDEX_HEADER, TIER_HEADER,
+};
+use hyra_scribe_ledger::http_metrics::HttpMetricsLayer;
+use hyra_scribe_ledger::learner_progress::DEFAULT_CAUGHT_UP_LAG;
+use hyra_scribe_ledger::logging::{log_key, log_path, set_key_logging};
+use hyra_scribe_ledger::manifest::ManifestManager;
+use hyra_scribe_ledger::membership::{ChangePhase, MembershipChanges, MembershipOp};
+use hyra_scribe_ledger::metrics;
+use hyra_scribe_ledger::metrics_push::MetricsPusher;
+use hyra_scribe_ledger::migration::{
+    open_legacy, LegacyBatches, MigrationProgress, MigrationVerification,
+    DEFAULT_MIGRATION_BATCH_SIZE,
+};
+use hyra_scribe_ledger::mirror::{
+    router as mirror_router, SegmentMirror, DEFAULT_MIRROR_CACHE_SEGMENTS,
+    DEFAULT_MIRROR_REFRESH_INTERVAL,
+};
+use hyra_scribe_ledger::namespace_migration::{
+    self as namespace_migration, MigrationCheckpoint, MigrationSpec, MigrationState,
+    RunningMigrations,
+};
+use hyra_scribe_ledger::placement::{PlacementConfig, PlacementReport};
+use hyra_scribe_ledger::quota::{api_key_id, QuotaTracker};
+use hyra_scribe_ledger::raft_history::{parse_since, RaftHistory, MAX_SAMPLE_LIMIT};
+use hyra_scribe_ledger::runtime_info::RuntimeReport;
+use hyra_scribe_ledger::security::access_trace::AccessTraceFilter;
+use hyra_scribe_ledger::security::{
+    client_cert_auth, AccessOp, AccessTrace, AuthMiddleware, ClientCertIdentity, JoinTokenManager,
+    NetworkPolicy, NetworkPolicyConfig, NetworkPolicyLayer, RpcAuthenticator, TenantKeyring,
+    TlsServerConfig,
+};
+use hyra_scribe_ledger::selftest;
+use hyra_scribe_ledger::shadow::{ShadowTarget, ShadowWriter};
+use hyra_scribe_ledger::status::{cluster_overview, placement_report, ClusterOverview, NodeStatus};
+use hyra_scribe_ledger::storage::archival::{ArchivalManager, TieringPolicy};
+use hyra_scribe_ledger::storage::consistency::StartupCheckMode;
+use hyra_scribe_ledger::storage::s3::S3StorageConfig;
+use hyra_scribe_ledger::storage::segment::SegmentManager;
+use hyra_scribe_ledger::subscription::SubscriptionQuery;
+use hyra_scribe_ledger::sync::{DEFAULT_SYNC_BUCKETS, MAX_SYNC_BUCKETS};
+use hyra_scribe_ledger::trace_context::{
+    self, TraceContext, TRACEPARENT_HEADER, TRACESTATE_HEADER,
+};
+use hyra_scribe_ledger::types::{Fence, NodeId, SegmentId, UsageDelta};
+use hyra_scribe_ledger::wire::seal;
+use openraft::BasicNode;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::convert::Infallible;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tower_http::cors::CorsLayer;
 use tracing::{error, info, warn};
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
@@ -40,21 +127,78 @@ struct Cli {
     #[arg(short, long, value_name = "ID")]
     node_id: Option<u64>,
 
+    /// Configuration profile layered over the config file: dev, staging or prod
+    /// (defaults to SCRIBE_PROFILE)
+    #[arg(long, value_name = "PROFILE")]
+    profile: Option<Profile>,
+
     /// Bootstrap a new cluster (first node)
     #[arg(short, long)]
     bootstrap: bool,
 
+    /// Form the initial cluster from a manifest listing every member
+    #[arg(long, value_name = "FILE", conflicts_with = "bootstrap")]
+    bootstrap_manifest: Option<PathBuf>,
+
+    /// One-time join token issued by the cluster leader (overrides config file)
+    #[arg(long, value_name = "TOKEN")]
+    join_token: Option<String>,
+
     /// Log level (trace, debug, info, warn, error)
     #[arg(short, long, default_value = "info")]
     log_level: String,
+
+    #[command(subcommand)]
+    command: Option<NodeCommand>,
+}
+
+#[derive(Subcommand, Debug)]
+enum NodeCommand {
+    /// Check storage, Merkle proofs, S3, discovery and Raft storage, then exit
+    Selftest,
+    /// Serve reads and segment verification from the S3 archive only (no Raft, no sled)
+    Mirror {
+        /// Seconds between checks for newly archived segments (default 60)
+        #[arg(long)]
+        refresh_secs: Option<u64>,
+        /// Number of decoded segments kept in memory
+        #[arg(long, default_value_t = DEFAULT_MIRROR_CACHE_SEGMENTS)]
+        cache_segments: usize,
+    },
+    /// Populate a fresh node with synthetic users, JSON documents and blobs, then exit
+    DemoSeed {
+        /// Number of records to write
+        #[arg(long, default_value_t = 10_000)]
+        records: usize,
+        /// Size of each value in bytes
+        #[arg(long, default_value_t = DEFAULT_DEMO_VALUE_SIZE)]
+        value_size: usize,
+        /// Seed of the generator; the same seed writes the same records
+        #[arg(long, default_value_t = 1)]
+        seed: u64,
+    },
+    /// Import a standalone ledger database into a fresh node through Raft, then exit
+    MigrateLegacy {
+        /// Directory of the standalone ledger's sled database
+        #[arg(long)]
+        from: PathBuf,
+        /// Pairs proposed per batch
+        #[arg(long, default_value_t = DEFAULT_MIGRATION_BATCH_SIZE)]
+        batch_size: usize,
+        /// Skip reading every imported key back and comparing it with the original
+        #[arg(long)]
+        no_verify: bool,
+    },
+    /// Rewrite the snapshots on disk into the configured storage layout, then exit
+    Migrate,
 }
 
-#[tokio::main(flavor = "multi_thread")]
-async fn main() -> Result<()> {
+fn main() -> Result<()> {
     let cli = Cli::parse();
 
-    // Initialize tracing/logging
-    setup_logging(&cli.log_level)?;
+    // Initialize tracing/logging, keeping recent subsystem errors for /admin/errors
+    let error_history = ErrorHistory::default();
+    setup_logging(&cli.log_level, &error_history)?;
 
     // Print startup banner
     print_banner();
@@ -66,65 +210,180 @@ async fn main() -> Result<()> {
     if let Some(node_id) = cli.node_id {
         config.node.id = node_id;
     }
+    if let Some(token) = &cli.join_token {
+        config.discovery.join_token = Some(token.clone());
+    }
+
+    // Keys are redacted in log lines, reports and audit entries from here on
+    set_key_logging(config.logging.keys.clone());
+
+    // Size the runtime from the configuration, then run the node on it
+    let runtime = config.runtime.build()?;
+    info!(
+        "Runtime started with {} worker threads",
+        runtime.metrics().num_workers()
+    );
+    if !config.runtime.pin_cores.is_empty() {
+        info!(
+            "Runtime threads pinned to CPUs {:?}",
+            config.runtime.pin_cores
+        );
+    }
+    runtime.block_on(run(cli, config, error_history))
+}
+
+/// Run the node (or the selected subcommand) with the loaded configuration
+async fn run(cli: Cli, config: Config, error_history: ErrorHistory) -> Result<()> {
+    if let Some(NodeCommand::Selftest) = cli.command {
+        let report = selftest::run(&config).await;
+        print!("{}", report);
+        if !report.passed() {
+            std::process::exit(1);
+        }
+        println!("All subsystem checks passed");
+        return Ok(());
+    }
+    if let Some(NodeCommand::Mirror {
+        refresh_secs,
+        cache_segments,
+    }) = cli.command
+    {
+        let refresh = refresh_secs
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_MIRROR_REFRESH_INTERVAL);
+        return run_mirror(&config, refresh, cache_segments).await;
+    }
+    if let Some(NodeCommand::DemoSeed {
+        records,
+        value_size,
+        seed,
+    }) = cli.command
+    {
+        return run_demo_seed(&config, records, value_size, seed).await;
+    }
+    if let Some(NodeCommand::MigrateLegacy {
+        from,
+        batch_size,
+        no_verify,
+    }) = cli.command
+    {
+        return run_migrate_legacy(&config, &from, batch_size, !no_verify).await;
+    }
+    if let Some(NodeCommand::Migrate) = cli.command {
+        return run_migrate(&config).await;
+    }
 
     // Print configuration overview with fancy TUI
     print_config_overview(&config);
 
+    // Register Prometheus metrics
+    metrics::init_metrics();
+
     // Create data directory if it doesn't exist
     std::fs::create_dir_all(&config.node.data_dir)?;
 
     // Initialize storage
-    let db_path = config.node.data_dir.join("db");
-    let db = sled::open(&db_path)?;
-    info!("Storage initialized at {:?}", db_path);
+    let db = open_db(&config)?;
+    let admin_events = AdminEventLog::open(&db, config.node.id)?;
+    let access_trace = if config.security.access_trace.enabled() {
+        info!(
+            "Tracing key access in namespaces {:?}",
+            config.security.access_trace.namespaces
+        );
+        Some(AccessTrace::open(
+            &db,
+            config.security.access_trace.clone(),
+        )?)
+    } else {
+        None
+    };
+    let access_log = if config.logging.access.enabled {
+        let access_log = AccessLog::open(&config.logging.access, &config.node.data_dir)?;
+        info!(
+            "Writing the HTTP access log, sampling {} of requests",
+            config.logging.access.sample_rate
+        );
+        Some(access_log)
+    } else {
+        None
+    };
+    let history_config = &config.consensus.metrics_history;
+    let raft_history = if history_config.enabled {
+        Some(RaftHistory::open(
+            &db,
+            Duration::from_secs(history_config.retention_secs),
+        )?)
+    } else {
+        None
+    };
 
     // Initialize S3 storage if configured
-    if let Some(s3_config) = &config.storage.s3 {
-        info!("S3 storage configuration detected");
-        info!("  Bucket: {}", s3_config.bucket);
-        info!("  Region: {}", s3_config.region);
-        if let Some(endpoint) = &s3_config.endpoint {
-            info!("  Endpoint: {}", endpoint);
-        }
-        info!("  Path style: {}", s3_config.path_style);
-        info!("  Pool size: {}", s3_config.pool_size);
-        info!("  Timeout: {}s", s3_config.timeout_secs);
-        info!("  Max retries: {}", s3_config.max_retries);
+    let archival = open_archival(&config, &db).await?;
 
-        // Create S3 storage config from the TOML config
-        let s3_storage_config = hyra_scribe_ledger::storage::s3::S3StorageConfig {
-            bucket: s3_config.bucket.clone(),
-            region: s3_config.region.clone(),
-            endpoint: s3_config.endpoint.clone(),
-            access_key_id: s3_config.access_key_id.clone(),
-            secret_access_key: s3_config.secret_access_key.clone(),
-            path_style: s3_config.path_style,
-            timeout_secs: s3_config.timeout_secs,
-            max_retries: s3_config.max_retries,
-        };
+    // Create consensus node, resuming from the snapshot on disk if there is one
+    let consensus = match open_snapshot_store(&config)? {
+        Some(snapshots) => {
+            ConsensusNode::new_with_snapshot_store(
+                config.node.id,
+                db.clone(),
+                &config.consensus,
+                snapshots,
+            )
+            .await
+        }
+        None => {
+            ConsensusNode::new_with_scribe_config(config.node.id, db.clone(), &config.consensus)
+                .await
+        }
+    };
+    let consensus =
+        Arc::new(consensus.map_err(|e| anyhow::anyhow!("Failed to create consensus node: {}", e))?);
+    info!("Consensus node created with ID {}", config.node.id);
+    consensus
+        .set_raft_compression(config.network.raft_compression.clone())
+        .await;
 
-        // Try to initialize S3 storage (this will validate configuration)
-        match hyra_scribe_ledger::storage::s3::S3Storage::new(s3_storage_config).await {
-            Ok(_s3_storage) => {
-                info!("✓ S3 storage initialized successfully");
-                // S3 storage is ready for use by archival tier when needed
-            }
-            Err(e) => {
-                warn!("Failed to initialize S3 storage: {}", e);
-                warn!("Node will continue without S3 archival support");
-            }
+    // Sign and verify Raft RPCs with the cluster secret (validated to be set)
+    let rpc_auth = match (
+        &config.discovery.cluster_secret,
+        config.network.rpc_auth.enabled,
+    ) {
+        (Some(secret), true) => {
+            info!(
+                "Raft RPC authentication enabled (max clock skew {} ms)",
+                config.network.rpc_auth.max_clock_skew_ms
+            );
+            Some(Arc::new(RpcAuthenticator::new(
+                config.node.id,
+                secret,
+                &config.network.rpc_auth,
+            )))
         }
-    } else {
-        info!("S3 storage not configured (running with local storage only)");
-    }
+        _ => None,
+    };
+    consensus.set_rpc_auth(rpc_auth.clone()).await;
 
-    // Create consensus node
-    let consensus = Arc::new(
-        ConsensusNode::new_with_scribe_config(config.node.id, db, &config.consensus)
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to create consensus node: {}", e))?,
-    );
-    info!("Consensus node created with ID {}", config.node.id);
+    // Answer Raft RPCs from peers
+    let raft_listener =
+        tokio::net::TcpListener::bind(format!("{}:{}", config.node.address, config.network.raft_port))
+            .await?;
+    info!("Raft RPC server listening on {}", raft_listener.local_addr()?);
+    let raft = consensus.raft();
+    let snapshot_throttle = consensus.snapshot_receive_throttle();
+    let peer_versions = consensus.peer_versions();
+    tokio::spawn(async move {
+        if let Err(e) = serve_raft_rpc_with_auth(
+            raft,
+            raft_listener,
+            snapshot_throttle,
+            rpc_auth,
+            peer_versions,
+        )
+        .await
+        {
+            error!("Raft RPC server stopped: {}", e);
+        }
+    });
 
     // Create discovery service
     let discovery_config = hyra_scribe_ledger::discovery::DiscoveryConfig {
@@ -143,7 +402,33 @@ async fn main() -> Result<()> {
         cluster_secret: config.discovery.cluster_secret.clone(),
     };
 
-    let discovery = Arc::new(DiscoveryService::new(discovery_config)?);
+    // Join tokens gate admission of new nodes when required
+    let join_tokens = match (&config.discovery.cluster_secret, config.discovery.require_join_token) {
+        (Some(secret), true) => Some(Arc::new(
+            JoinTokenManager::new(config.discovery.cluster_id.clone(), secret)
+                .map_err(|e| anyhow::anyhow!("Invalid join token configuration: {}", e))?,
+        )),
+        _ => None,
+    };
+
+    // Versions learned through discovery count towards version gating, and the
+    // clocks heartbeats carry towards the skew of each peer
+    let clock_skew = ClockSkewMonitor::new(config.clock.clone());
+    let mut discovery = DiscoveryService::new(discovery_config)?
+        .with_wire_format(config.discovery.wire_format)
+        .with_peer_versions(consensus.peer_versions())
+        .with_clock_skew(clock_skew.clone());
+    if let Some(token) = &config.discovery.join_token {
+        discovery = discovery.with_join_token(token.clone());
+    }
+    if let Some(tokens) = &join_tokens {
+        let membership = consensus.clone();
+        discovery = discovery.with_join_policy(JoinPolicy::new(tokens.clone(), move |node_id| {
+            membership.is_member(node_id)
+        }));
+        info!("Join tokens required for new nodes");
+    }
+    let discovery = Arc::new(discovery);
     info!("Discovery service created");
 
     // Start discovery service
@@ -151,58 +436,380 @@ async fn main() -> Result<()> {
     info!("Discovery service started");
 
     // Create cluster initializer
+    let mode = if let Some(path) = &cli.bootstrap_manifest {
+        let manifest = BootstrapManifest::from_file(path)?;
+        info!(
+            "Loaded bootstrap manifest {:?} with {} member(s)",
+            path,
+            manifest.members.len()
+        );
+        InitMode::Manifest(manifest)
+    } else if cli.bootstrap {
+        InitMode::Bootstrap
+    } else {
+        InitMode::Join
+    };
+    let mode_name = match mode {
+        InitMode::Bootstrap => "Bootstrap",
+        InitMode::Join => "Join",
+        InitMode::Manifest(_) => "Manifest",
+    };
+
     let cluster_config = ClusterConfig {
-        mode: if cli.bootstrap {
-            InitMode::Bootstrap
-        } else {
-            InitMode::Join
-        },
+        mode,
         seed_addrs: Vec::new(),
         discovery_timeout_ms: 5000,
         min_peers_for_join: 1,
     };
 
-    let initializer = ClusterInitializer::new(discovery.clone(), consensus.clone(), cluster_config);
+    let mut initializer = ClusterInitializer::new(discovery.clone(), consensus.clone(), cluster_config)
+        .with_client_scheme(if config.security.tls.enabled { "https" } else { "http" })
+        .with_admin_port(config.network.admin.port);
+    if let Some(token) = &config.discovery.join_token {
+        initializer = initializer.with_join_token(token.clone());
+    }
 
     // Initialize cluster
-    info!(
-        "Initializing cluster in {} mode",
-        if cli.bootstrap { "Bootstrap" } else { "Join" }
-    );
+    info!("Initializing cluster in {} mode", mode_name);
     if let Err(e) = initializer.initialize().await {
         error!("Failed to initialize cluster: {}", e);
         return Err(e.into());
     }
 
-    // Create distributed API
-    let api = Arc::new(DistributedApi::new(consensus.clone()));
+    // Admission control shared by the HTTP layer and the write batching layer
+    let admission = AdmissionController::new(
+        config.api.max_in_flight_requests,
+        config.api.low_priority_share,
+        config.api.high_priority_reserve,
+    )?;
+
+    // Hot data cache, spilling evicted entries to disk when configured
+    let cache = HotDataCache::with_capacity(config.api.cache_capacity).with_overflow(
+        db.open_tree("cache_overflow")?,
+        config.api.cache_overflow_capacity,
+    )?;
+    let warm_keys = if config.api.cache_warm_keys > 0 {
+        Some(Arc::new(WarmKeys::new(db.open_tree(WARM_KEYS_TREE)?)))
+    } else {
+        None
+    };
+
+    // Create distributed API, mirroring writes when shadow mode is enabled
+    let mut api = DistributedApi::new(consensus.clone())
+        .with_cache(cache)
+        .with_admission(admission.clone())
+        .with_queue_capacity(config.api.max_queue_length)
+        .with_read_path(config.api.read_path.clone())
+        .with_max_clock_skew(config.clock.max_skew());
+    if let Some(archival) = &archival {
+        api = api.with_archival(archival.clone());
+    }
+    if config.shadow.enabled {
+        let target = match (&config.shadow.target_url, &config.shadow.namespace) {
+            (Some(base_url), _) => ShadowTarget::Cluster {
+                base_url: base_url.clone(),
+            },
+            (None, Some(namespace)) => ShadowTarget::Namespace {
+                consensus: consensus.clone(),
+                prefix: namespace.clone().into_bytes(),
+            },
+            (None, None) => anyhow::bail!("Shadow mode requires target_url or namespace"),
+        };
+        info!("Shadow mode enabled, mirroring writes");
+        api = api.with_shadow(ShadowWriter::start(target, config.shadow.queue_capacity)?);
+    }
+    let api = Arc::new(api);
+    let cache_invalidation = api.start_cache_invalidation();
+
+    // Replay sampled reads against the canary, comparing value hashes
+    let canary = match (&config.canary.target_url, config.canary.enabled) {
+        (Some(target_url), true) => {
+            info!(
+                "Canary mirroring enabled: {}% of reads to {}",
+                config.canary.sample_percent, target_url
+            );
+            Some(Arc::new(ReadMirror::start(
+                target_url.clone(),
+                config.canary.sample_percent,
+                config.canary.queue_capacity,
+                Duration::from_millis(config.canary.timeout_ms),
+            )?))
+        }
+        _ => None,
+    };
+
+    // Push metrics for deployments that cannot be scraped
+    let metrics_pusher = if config.metrics.push.enabled {
+        let pusher = Arc::new(MetricsPusher::new(
+            &config.metrics.push,
+            format!("{}:{}", config.node.address, config.network.client_port),
+        )?);
+        info!(
+            "Pushing metrics to {} every {}s",
+            pusher.url(),
+            config.metrics.push.interval_secs
+        );
+        let task = pusher.start();
+        Some((pusher, task))
+    } else {
+        None
+    };
+
+    // Pick up rotated S3 credentials without a restart
+    let credential_watcher = archival
+        .as_ref()
+        .and_then(|archival| archival.start_credential_watcher());
+
+    // Expire keys with elapsed TTLs (only acts while this node is leader), allowing
+    // for the clock of the writer having been behind
+    let expiry_sweeper =
+        consensus.start_expiry_sweeper(EXPIRY_SWEEP_INTERVAL, config.clock.max_skew());
+
+    // Forget deleted keys once the change feed no longer needs to list them
+    let tombstone_pruner = (config.storage.tombstone_retention_secs > 0).then(|| {
+        consensus.start_tombstone_pruner(
+            TOMBSTONE_PRUNE_INTERVAL,
+            Duration::from_secs(config.storage.tombstone_retention_secs),
+        )
+    });
+
+    // Keep a local history of Raft metrics for post-incident analysis
+    let metrics_history = raft_history.clone().map(|history| {
+        consensus.start_metrics_history(
+            history,
+            Duration::from_secs(config.consensus.metrics_history.interval_secs),
+        )
+    });
+
+    // Score the node's health and run the configured self-healing actions
+    let health = if config.health.enabled {
+        let monitor = Arc::new(health_monitor(
+            &config,
+            &api,
+            &consensus,
+            archival.as_ref(),
+        )?);
+        let (consensus, archival, discovery) =
+            (consensus.clone(), archival.clone(), discovery.clone());
+        let data_dir = config.node.data_dir.clone();
+        let task = monitor.start(move || {
+            let (consensus, archival, discovery, db, data_dir) = (
+                consensus.clone(),
+                archival.clone(),
+                discovery.clone(),
+                db.clone(),
+                data_dir.clone(),
+            );
+            async move {
+                health_signals(&consensus, archival.as_deref(), &discovery, &db, &data_dir).await
+            }
+        });
+        Some((monitor, task))
+    } else {
+        None
+    };
+
+    // Prepare TLS for the client API, if enabled
+    let tls = if config.security.tls.enabled {
+        let tls = TlsServerConfig::new(config.security.tls.clone())
+            .map_err(|e| anyhow::anyhow!("Invalid TLS configuration: {}", e))?;
+        Some(tls)
+    } else {
+        None
+    };
+    let scheme = if tls.is_some() { "https" } else { "http" };
+
+    let policies = ListenerPolicies {
+        data: policy_layer(&config.network.policy, "data", &admin_events)?,
+        admin: policy_layer(&config.network.admin.policy, "admin", &admin_events)?,
+    };
 
     // Create app state
     let app_state = AppState {
         api,
+        consensus: consensus.clone(),
+        discovery: discovery.clone(),
+        join_tokens,
+        admin_events,
+        error_history,
+        access_trace,
+        access_log,
+        raft_history,
+        admission,
+        http_client: reqwest::Client::new(),
+        config_hash: config.config_hash(),
         node_id: config.node.id,
+        scheme,
+        archival,
+        canary,
+        health: health.as_ref().map(|(monitor, _)| monitor.clone()),
+        admin_port: config.network.admin.port,
+        permissive_cors: config.network.permissive_cors,
+        placement: config.placement.clone(),
+        clock_skew,
+        // Followers elect a new leader within their election timeout
+        leader_transfer_timeout: Duration::from_millis(config.consensus.election_timeout_max * 2),
+        quotas: config
+            .quotas
+            .enabled
+            .then(|| Arc::new(QuotaTracker::new(config.quotas.clone()))),
+        membership: MembershipChanges::new(),
+        migrations: RunningMigrations::new(),
     };
 
+    // Replicate the usage counted against quotas, if enabled
+    let quota_flush = app_state.quotas.clone().map(|quotas| {
+        let state = app_state.clone();
+        tokio::spawn(async move {
+            let mut interval =
+                tokio::time::interval(Duration::from_millis(quotas.config().flush_interval_ms));
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let usage = quotas.take_pending();
+                if usage.is_empty() {
+                    continue;
+                }
+                if let Err(e) = flush_usage(&state, usage.clone()).await {
+                    warn!("Flushing quota usage failed: {}", e);
+                    quotas.restore(usage);
+                }
+            }
+        })
+    });
+
+    // Move leadership towards the clients, if enabled
+    let placement_task = config.placement.auto_transfer.then(|| {
+        let state = app_state.clone();
+        tokio::spawn(async move {
+            let mut interval =
+                tokio::time::interval(Duration::from_secs(state.placement.interval_secs));
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if !state.consensus.is_leader().await {
+                    continue;
+                }
+                let min_improvement = state.placement.min_improvement();
+                if let Err(e) = rebalance_leader(&state, min_improvement, "placement").await {
+                    warn!("Leader placement failed: {}", e);
+                }
+            }
+        })
+    });
+
+    // Move idle values to S3 (only acts while this node is leader), if enabled
+    let archival_config = &config.storage.archival;
+    let auto_archival = (archival_config.enabled && app_state.archival.is_some()).then(|| {
+        let state = app_state.clone();
+        let idle_for = Duration::from_secs(archival_config.idle_secs);
+        let max_keys = archival_config.max_keys_per_run;
+        let interval = Duration::from_secs(archival_config.interval_secs);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if !state.consensus.is_leader().await {
+                    continue;
+                }
+                match state.api.archive_idle(idle_for, max_keys).await {
+                    Ok(run) if run.segments > 0 => info!(
+                        "Archived {} idle key(s) in {} segment(s), {} demoted",
+                        run.keys, run.segments, run.demoted
+                    ),
+                    Ok(_) => {}
+                    Err(e) => warn!("Archiving idle values failed: {}", e),
+                }
+                if let Some(archival) = &state.archival {
+                    if let Err(e) = archival.enforce_retention().await {
+                        warn!("Enforcing segment retention failed: {}", e);
+                    }
+                }
+            }
+        })
+    });
+
+    // Warm the cache with the keys saved before the restart, then keep saving the
+    // recently used keys, if enabled
+    let cache_warming = warm_keys.clone().map(|warm_keys| {
+        let api = app_state.api.clone();
+        let limit = config.api.cache_warm_keys;
+        let save_interval = Duration::from_secs(config.api.cache_warm_save_interval_secs);
+        tokio::spawn(async move {
+            warm_cache(&api, &warm_keys, limit).await;
+            let mut interval = tokio::time::interval(save_interval);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                save_warm_keys(&api, &warm_keys, limit);
+            }
+        })
+    });
+
     // Start HTTP server
     let http_addr = format!("0.0.0.0:{}", config.network.client_port);
     info!("Starting HTTP API server on {}", http_addr);
+    let admin_addr = config.network.admin.listen_addr();
+    if let Some(addr) = &admin_addr {
+        info!("Starting admin API server on {}", addr);
+    }
     
     let http_addr_clone = http_addr.clone();
+    let cache_api = app_state.api.clone();
     let http_server = tokio::spawn(async move {
-        if let Err(e) = start_http_server(&http_addr_clone, app_state).await {
+        if let Err(e) =
+            start_http_server(&http_addr_clone, admin_addr, app_state, tls, policies).await
+        {
             error!("HTTP server error: {}", e);
         }
     });
 
     info!("Node {} is ready", config.node.id);
-    info!("HTTP API available at http://{}", http_addr);
+    info!("HTTP API available at {}://{}", scheme, http_addr);
     info!("Press Ctrl+C to shutdown gracefully");
 
     // Wait for shutdown signal
     wait_for_shutdown_signal().await;
     
-    // Abort HTTP server
+    // Abort HTTP server and background tasks
     http_server.abort();
+    expiry_sweeper.abort();
+    if let Some(tombstone_pruner) = tombstone_pruner {
+        tombstone_pruner.abort();
+    }
+    if let Some((_, task)) = health {
+        task.abort();
+    }
+    if let Some(metrics_history) = metrics_history {
+        metrics_history.abort();
+    }
+    cache_invalidation.abort();
+    if let Some(placement_task) = placement_task {
+        placement_task.abort();
+    }
+    if let Some(quota_flush) = quota_flush {
+        quota_flush.abort();
+    }
+    if let Some(auto_archival) = auto_archival {
+        auto_archival.abort();
+    }
+    if let Some(cache_warming) = cache_warming {
+        cache_warming.abort();
+    }
+    if let Some(warm_keys) = &warm_keys {
+        save_warm_keys(&cache_api, warm_keys, config.api.cache_warm_keys);
+    }
+    if let Some(credential_watcher) = credential_watcher {
+        credential_watcher.abort();
+    }
+    if let Some((pusher, task)) = metrics_pusher {
+        task.abort();
+        // Short-lived nodes would otherwise lose the metrics since the last push
+        if let Err(e) = pusher.push().await {
+            warn!("Final metrics push failed: {}", e);
+        }
+    }
 
     // Graceful shutdown
     info!("Shutdown signal received, stopping node...");
@@ -222,8 +829,99 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Health monitor with the built-in self-healing actions registered
+fn health_monitor(
+    config: &Config,
+    api: &Arc<DistributedApi>,
+    consensus: &Arc<ConsensusNode>,
+    archival: Option<&Arc<ArchivalManager>>,
+) -> Result<HealthMonitor> {
+    let api = api.clone();
+    let stepping_down = consensus.clone();
+    // Followers elect a new leader within their election timeout
+    let step_down_timeout = Duration::from_millis(config.consensus.election_timeout_max * 2);
+    let mut monitor = HealthMonitor::new(config.health.clone())
+        .with_action(CLEAR_CACHE, move || {
+            api.clear_cache();
+            async { Ok(()) }
+        })
+        .with_action(STEP_DOWN, move || {
+            let consensus = stepping_down.clone();
+            async move {
+                // Only the leader has leadership to hand over
+                if !consensus.is_leader().await {
+                    return Ok(());
+                }
+                consensus.step_down(step_down_timeout).await
+            }
+        });
+    if let Some(archival) = archival {
+        let archival = archival.clone();
+        monitor = monitor.with_action(RESTART_ARCHIVAL, move || {
+            let archival = archival.clone();
+            async move {
+                let recovery = archival.recover_interrupted_rolls().await?;
+                let archived = archival.archive_old_segments().await?;
+                info!(
+                    "Archival restarted: {} interrupted roll(s) reconciled, {} segment(s) archived",
+                    recovery.completed.len() + recovery.rolled_back.len(),
+                    archived.len()
+                );
+                Ok(())
+            }
+        });
+    }
+    monitor.check_actions()?;
+    Ok(monitor)
+}
+
+/// Read the subsystem signals the health score is computed from
+async fn health_signals(
+    consensus: &ConsensusNode,
+    archival: Option<&ArchivalManager>,
+    discovery: &DiscoveryService,
+    db: &sled::Db,
+    data_dir: &std::path::Path,
+) -> HealthSignals {
+    let metrics = consensus.metrics().await;
+    let applied = metrics.last_applied.map_or(0, |log_id| log_id.index);
+    let storage_error = db
+        .open_tree("health")
+        .and_then(|tree| tree.insert("probe", &metrics.current_term.to_be_bytes()[..]))
+        .err()
+        .map(|e| e.to_string());
+    let archival_backlog = archival.and_then(|archival| match archival.backlog() {
+        Ok(backlog) => Some(backlog.pending_segments),
+        Err(e) => {
+            warn!("Failed to read archival backlog: {}", e);
+            None
+        }
+    });
+    let disk_free_ratio = match disk_free_ratio(data_dir) {
+        Ok(ratio) => Some(ratio),
+        Err(e) => {
+            warn!("Failed to read free space of {:?}: {}", data_dir, e);
+            None
+        }
+    };
+    let peers = discovery.get_peers();
+
+    HealthSignals {
+        storage_error,
+        has_leader: metrics.current_leader.is_some(),
+        apply_lag: metrics.last_log_index.unwrap_or(0).saturating_sub(applied),
+        archival_backlog,
+        disk_free_ratio,
+        peers_total: peers.len(),
+        peers_alive: peers
+            .iter()
+            .filter(|peer| discovery.is_peer_alive(peer.node_id))
+            .count(),
+    }
+}
+
 /// Setup logging with tracing-subscriber
-fn setup_logging(log_level: &str) -> Result<()> {
+fn setup_logging(log_level: &str, error_history: &ErrorHistory) -> Result<()> {
     let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| {
         EnvFilter::new(format!(
             "scribe_node={},hyra_scribe_ledger={}",
@@ -233,6 +931,7 @@ fn setup_logging(log_level: &str) -> Result<()> {
 
     tracing_subscriber::registry()
         .with(fmt::layer().with_target(true).with_thread_ids(true))
+        .with(error_history.layer())
         .with(filter)
         .init();
 
@@ -301,8 +1000,11 @@ fn print_config_overview(config: &Config) {
     println!("{}{}", separator, RESET);
     let base_url = format!("http://localhost:{}", config.network.client_port);
     println!("{}📤 PUT/GET{} {}/{{key}}  - Data operations", BRIGHT_GREEN, RESET, base_url);
+    println!("{}🕒 GET{} {}/keys?since=&until=  - Keys modified in a time window", BRIGHT_GREEN, RESET, base_url);
+    println!("{}🔔 GET{} {}/events  - Change stream (put/delete/expire)", BRIGHT_GREEN, RESET, base_url);
     println!("{}📊 GET{} {}/raft/status  - Raft status", BRIGHT_GREEN, RESET, base_url);
     println!("{}📈 GET{} {}/raft/metrics  - Performance metrics", BRIGHT_GREEN, RESET, base_url);
+    println!("{}🕰  GET{} {}/raft/metrics/history?since=15m  - Raft metrics history", BRIGHT_GREEN, RESET, base_url);
     println!("{}📋 GET{} {}/raft/events  - Recent events", BRIGHT_GREEN, RESET, base_url);
     println!("{}📺 WS{} {}  ws://{}/raft/live  - Live monitoring", BRIGHT_GREEN, WHITE, RESET, format!("localhost:{}", config.network.client_port));
     
@@ -383,9 +1085,23 @@ fn get_dir_size(path: &std::path::Path) -> std::io::Result<u64> {
 
 /// Load configuration from file or use defaults
 fn load_config(cli: &Cli) -> Result<Config> {
+    let profile = match cli.profile {
+        Some(profile) => Some(profile),
+        None => Profile::from_env()?,
+    };
+    if let Some(profile) = profile {
+        info!("Using configuration profile '{}'", profile);
+    }
+
     if let Some(config_path) = &cli.config {
         info!("Loading configuration from {:?}", config_path);
-        Ok(Config::from_file(config_path.to_str().unwrap())?)
+        Ok(Config::from_file_with_profile(
+            config_path.to_str().unwrap(),
+            profile,
+        )?)
+    } else if let Some(profile) = profile {
+        let node_id = cli.node_id.unwrap_or(1);
+        Ok(Config::for_profile(node_id, profile)?)
     } else {
         warn!("No config file specified, using default configuration");
         // Use default config for node 1
@@ -395,10 +1111,99 @@ fn load_config(cli: &Cli) -> Result<Config> {
 }
 
 // HTTP API types
+/// Interval between sweeps for keys with elapsed TTLs
+const EXPIRY_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Interval between prunings of the tombstones of deleted keys
+const TOMBSTONE_PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Changes returned by `GET /keys` when no limit is given
+const DEFAULT_KEYS_LIMIT: usize = 1000;
+
+/// Largest page of changes `GET /keys` returns
+const MAX_KEYS_LIMIT: usize = 10_000;
+
+/// Keys and prefixes returned by `GET /admin/hotkeys` when no limit is given
+const DEFAULT_HOT_KEYS_LIMIT: usize = 20;
+
+/// Longest accepted tag name, in bytes
+const MAX_TAG_NAME_LEN: usize = 128;
+
+/// Most operations accepted by one `POST /batch`
+const MAX_BATCH_OPS: usize = 10_000;
+
+/// Records written per batch by `demo-seed`
+const DEMO_BATCH_SIZE: usize = 500;
+
+/// Longest `demo-seed` and `migrate-legacy` wait for the bootstrapped node to
+/// elect itself leader
+const BOOTSTRAP_LEADER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Seconds between progress reports of `migrate-legacy`
+const MIGRATION_PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Pause between the elections of a node asked to take over leadership
+const CAMPAIGN_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Timeout for asking a voter to take over leadership
+const CAMPAIGN_REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Timeout for handing quota usage to the leader
+const QUOTA_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Interval between checks of a learner's catch-up while adding a voter
+const MEMBERSHIP_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Default time a new learner may take to catch up before it is promoted
+const DEFAULT_CATCH_UP_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Longest a restarted node retries warming its cache, e.g. while no leader is known
+const CACHE_WARM_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Pause between attempts to warm the cache
+const CACHE_WARM_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
 #[derive(Clone)]
 struct AppState {
     api: Arc<DistributedApi>,
+    consensus: Arc<ConsensusNode>,
+    discovery: Arc<DiscoveryService>,
+    join_tokens: Option<Arc<JoinTokenManager>>,
+    admin_events: AdminEventLog,
+    /// Recent warnings and errors of each subsystem
+    error_history: ErrorHistory,
+    /// Trace of key accesses in regulated namespaces, if any are configured
+    access_trace: Option<AccessTrace>,
+    /// Sampled log of HTTP requests, if enabled
+    access_log: Option<AccessLog>,
+    /// Local Raft metrics history, if enabled
+    raft_history: Option<RaftHistory>,
+    admission: AdmissionController,
+    http_client: reqwest::Client,
+    config_hash: String,
     node_id: u64,
+    scheme: &'static str,
+    archival: Option<Arc<ArchivalManager>>,
+    /// Mirror of sampled reads to a canary, if enabled
+    canary: Option<Arc<ReadMirror>>,
+    /// Health scoring, if enabled
+    health: Option<Arc<HealthMonitor>>,
+    /// Port of the separate admin listener, if enabled
+    admin_port: Option<u16>,
+    /// Whether browsers may call the API from any origin
+    permissive_cors: bool,
+    /// Latency-aware leader placement
+    placement: PlacementConfig,
+    /// Clock offsets of peers measured from heartbeats
+    clock_skew: ClockSkewMonitor,
+    /// How long a leadership transfer may take
+    leader_transfer_timeout: Duration,
+    /// Request quotas per API key, if enabled
+    quotas: Option<Arc<QuotaTracker>>,
+    /// Membership changes requested over HTTP
+    membership: MembershipChanges,
+    /// Namespace migrations running on this node
+    migrations: RunningMigrations,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -415,71 +1220,3498 @@ async fn health_handler(State(state): State<AppState>) -> impl IntoResponse {
     })
 }
 
+/// Map an API error to a response, redirecting to the leader on NotLeader
+///
+/// Returns 307 naming the leader's client address in `X-Raft-Leader` when it is
+/// known through discovery, and 421 Misdirected Request otherwise; the `Location`
+/// of the redirect is added by [`leader_redirect_middleware`]. Operations some
+/// member's wire version does not support yet get 501 Not Implemented.
+async fn error_response(state: &AppState, err: ScribeError) -> Response {
+    let leader_id = match err {
+        ScribeError::NotLeader { leader_id } => leader_id.or(state.api.current_leader().await),
+        ScribeError::Timeout(message) => {
+            return (StatusCode::SERVICE_UNAVAILABLE, message).into_response()
+        }
+        ScribeError::Unsupported(message) => {
+            return (StatusCode::NOT_IMPLEMENTED, message).into_response()
+        }
+        e => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Error: {}", e)).into_response(),
+    };
+
+    let leader_addr = leader_id
+        .filter(|id| *id != state.node_id)
+        .and_then(|id| Some((id, state.discovery.get_peer(id)?.client_addr.to_string())));
+
+    let mut response = match &leader_addr {
+        Some((id, addr)) => {
+            let mut response = (
+                StatusCode::TEMPORARY_REDIRECT,
+                format!("Not leader, redirecting to node {}", id),
+            )
+                .into_response();
+            if let Ok(value) = HeaderValue::from_str(addr) {
+                response.headers_mut().insert(RAFT_LEADER_HEADER, value);
+            }
+            response
+        }
+        None => (
+            StatusCode::MISDIRECTED_REQUEST,
+            "Not leader and leader address is unknown".to_string(),
+        )
+            .into_response(),
+    };
+
+    if let Some(id) = leader_id {
+        response
+            .headers_mut()
+            .insert(RAFT_LEADER_ID_HEADER, HeaderValue::from(id));
+    }
+    response
+}
+
+/// Query parameters for `PUT /:key`
+#[derive(Deserialize)]
+struct PutQuery {
+    /// Expire the key after this many seconds
+    ttl: Option<u64>,
+}
+
 async fn put_handler(
     State(state): State<AppState>,
     Path(key): Path<String>,
+    Query(query): Query<PutQuery>,
+    headers: HeaderMap,
     body: Bytes,
-) -> impl IntoResponse {
+) -> Response {
+    let fence = match fence_from_headers(&headers) {
+        Ok(fence) => fence,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
     let value = body.to_vec();
-    match state.api.put(key.into_bytes(), value).await {
-        Ok(_) => (StatusCode::OK, "OK".to_string()),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Error: {}", e),
-        ),
+    match precondition_from_headers(&headers) {
+        Ok(None) => {}
+        Ok(Some(_)) if query.ttl.is_some() || fence.is_some() => {
+            return (
+                StatusCode::BAD_REQUEST,
+                "Conditional writes cannot set a ttl or fence".to_string(),
+            )
+                .into_response()
+        }
+        Ok(Some(precondition)) => {
+            return conditional_write(&state, &key, precondition, Some(value)).await
+        }
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    }
+    let result = match (query.ttl, fence) {
+        (Some(_), Some(_)) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                "Fenced writes cannot set a ttl".to_string(),
+            )
+                .into_response()
+        }
+        (Some(ttl), None) => {
+            state
+                .api
+                .put_with_ttl(key.clone().into_bytes(), value, Duration::from_secs(ttl))
+                .await
+        }
+        (None, Some(fence)) => {
+            state
+                .api
+                .put_fenced(key.clone().into_bytes(), value, fence)
+                .await
+        }
+        (None, None) => state.api.put(key.clone().into_bytes(), value).await,
+    };
+    match result {
+        Ok(token) => write_ok_response(token),
+        Err(ScribeError::Conflict(message)) => (StatusCode::CONFLICT, message).into_response(),
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// Fence of a write, sent as `X-Fence-Namespace` and `X-Fence-Epoch`
+fn fence_from_headers(headers: &HeaderMap) -> Result<Option<Fence>, String> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .map(|v| v.to_str().map_err(|_| format!("Invalid {} header", name)))
+            .transpose()
+    };
+    match (header(FENCE_NAMESPACE_HEADER)?, header(FENCE_EPOCH_HEADER)?) {
+        (Some(namespace), Some(epoch)) => Ok(Some(Fence {
+            namespace: namespace.to_string(),
+            epoch: epoch
+                .parse()
+                .map_err(|_| format!("Invalid {} header", FENCE_EPOCH_HEADER))?,
+        })),
+        (None, None) => Ok(None),
+        _ => Err(format!(
+            "{} and {} must be sent together",
+            FENCE_NAMESPACE_HEADER, FENCE_EPOCH_HEADER
+        )),
+    }
+}
+
+/// Precondition of a conditional write
+enum WritePrecondition {
+    /// `If-None-Match: *`: the key must not exist
+    Absent,
+    /// `If-Match`: the key must hold a value with one of these entity tags, or any
+    /// value for `*`
+    Matches(Vec<String>),
+}
+
+/// Precondition sent as `If-Match` or `If-None-Match: *`, if any
+fn precondition_from_headers(headers: &HeaderMap) -> Result<Option<WritePrecondition>, String> {
+    let header = |name: header::HeaderName| {
+        headers
+            .get(&name)
+            .map(|v| v.to_str().map_err(|_| format!("Invalid {} header", name)))
+            .transpose()
+    };
+    match (header(header::IF_MATCH)?, header(header::IF_NONE_MATCH)?) {
+        (Some(_), Some(_)) => Err("If-Match and If-None-Match are exclusive".to_string()),
+        (Some(tags), None) => Ok(Some(WritePrecondition::Matches(
+            tags.split(',').map(|tag| tag.trim().to_string()).collect(),
+        ))),
+        (None, Some("*")) => Ok(Some(WritePrecondition::Absent)),
+        (None, Some(_)) => Err("Writes only support If-None-Match: *".to_string()),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Write `new` at `key`, or delete it for `None`, if `precondition` holds
+///
+/// The value an `If-Match` tag refers to is read from the leader, then swapped
+/// atomically, so a concurrent write in between still fails the request with
+/// `412 Precondition Failed`.
+async fn conditional_write(
+    state: &AppState,
+    key: &str,
+    precondition: WritePrecondition,
+    new: Option<Vec<u8>>,
+) -> Response {
+    let precondition_failed =
+        || (StatusCode::PRECONDITION_FAILED, "Precondition failed".to_string()).into_response();
+    let expected = match precondition {
+        WritePrecondition::Absent => None,
+        WritePrecondition::Matches(tags) => {
+            let current = state
+                .api
+                .get(key.as_bytes().to_vec(), ReadConsistency::Linearizable)
+                .await;
+            match current {
+                Ok(Some(value))
+                    if tags
+                        .iter()
+                        .any(|tag| tag == "*" || *tag == value_etag(&value)) =>
+                {
+                    Some(value)
+                }
+                Ok(_) => return precondition_failed(),
+                Err(e) => return error_response(state, e).await,
+            }
+        }
+    };
+    match state
+        .api
+        .compare_and_swap(key.as_bytes().to_vec(), expected, new)
+        .await
+    {
+        Ok((true, token)) => write_ok_response(token),
+        Ok((false, _)) => precondition_failed(),
+        Err(e) => error_response(state, e).await,
     }
 }
 
+/// Successful write response carrying the write's consistency token
+fn write_ok_response(token: ConsistencyToken) -> Response {
+    let mut response = (StatusCode::OK, "OK".to_string()).into_response();
+    response
+        .headers_mut()
+        .insert(CONSISTENCY_TOKEN_HEADER, HeaderValue::from(token.applied_index()));
+    response
+}
+
+/// Query of `GET /:key`
+#[derive(Deserialize)]
+struct GetQuery {
+    /// Hold the request up to this long (e.g. `30s`) for the key to change
+    wait: Option<String>,
+    /// Answer as soon as the key's version exceeds this; `304 Not Modified` if it
+    /// does not within `wait`
+    #[serde(rename = "if-version")]
+    if_version: Option<u64>,
+}
+
+/// Read a key; with `X-Min-Applied` the read waits until this node has applied
+/// that consistency token (read-your-writes), and with `if-version` (and `wait`)
+/// it long-polls until the key changes. The response's `X-Consistency-Token` is the
+/// state the read was served from, for clients carrying a session across nodes.
 async fn get_handler(
     State(state): State<AppState>,
     Path(key): Path<String>,
-) -> impl IntoResponse {
-    match state.api.get(key.into_bytes(), ReadConsistency::Stale).await {
-        Ok(Some(value)) => (
-            StatusCode::OK,
-            String::from_utf8_lossy(&value).to_string(),
-        ),
-        Ok(None) => (StatusCode::NOT_FOUND, "Not found".to_string()),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Error: {}", e),
-        ),
+    Query(query): Query<GetQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let min_applied = match headers.get(MIN_APPLIED_HEADER).map(|v| {
+        v.to_str()
+            .map_err(|e| e.to_string())
+            .and_then(|v| v.parse::<ConsistencyToken>().map_err(|e| e.to_string()))
+    }) {
+        Some(Ok(token)) => Some(token),
+        Some(Err(e)) => return (StatusCode::BAD_REQUEST, e).into_response(),
+        None => None,
+    };
+
+    let version = match query.if_version {
+        Some(if_version) => {
+            let wait = match query.wait.as_deref().map(parse_wait).transpose() {
+                Ok(wait) => wait.unwrap_or_default(),
+                Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+            };
+            let version = state
+                .api
+                .wait_for_change(key.as_bytes(), if_version, wait)
+                .await;
+            if version.is_none_or(|version| version <= if_version) {
+                return with_key_version(StatusCode::NOT_MODIFIED.into_response(), version);
+            }
+            version
+        }
+        None if query.wait.is_some() => {
+            return (
+                StatusCode::BAD_REQUEST,
+                "wait requires if-version".to_string(),
+            )
+                .into_response()
+        }
+        None => state.api.key_version(key.as_bytes()).await,
+    };
+
+    let result = match min_applied {
+        Some(token) => {
+            state
+                .api
+                .get_at_least_tiered(key.clone().into_bytes(), token)
+                .await
+        }
+        None => {
+            state
+                .api
+                .get_tiered(key.clone().into_bytes(), ReadConsistency::Stale)
+                .await
+        }
+    };
+    // Taken after the read, so it never understates what the read reflects
+    let applied = state.api.applied_token().await;
+    let with_version =
+        |response, version| with_read_token(with_key_version(response, version), applied);
+    match result {
+        Ok(Some((value, tier))) => {
+            let body = String::from_utf8_lossy(&value).to_string();
+            if let Some(canary) = &state.canary {
+                canary.mirror(key.as_bytes(), Some(body.as_bytes()));
+            }
+            // Let caching clients revalidate without transferring the value again
+            let etag = value_etag(&value);
+            let not_modified = headers
+                .get(header::IF_NONE_MATCH)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|tags| tags.split(',').any(|tag| tag.trim() == etag));
+            if not_modified {
+                let response = (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+                return with_version(response, version);
+            }
+            let mut response = (StatusCode::OK, [(header::ETAG, etag)], body).into_response();
+            if tier == StorageTier::Cold {
+                response
+                    .headers_mut()
+                    .insert(TIER_HEADER, HeaderValue::from_static(tier.as_str()));
+            }
+            with_version(response, version)
+        }
+        Ok(None) => {
+            if let Some(canary) = &state.canary {
+                canary.mirror(key.as_bytes(), None);
+            }
+            with_version(
+                (StatusCode::NOT_FOUND, "Not found".to_string()).into_response(),
+                version,
+            )
+        }
+        Err(e) => error_response(&state, e).await,
     }
 }
 
-async fn delete_handler(
-    State(state): State<AppState>,
-    Path(key): Path<String>,
-) -> impl IntoResponse {
-    match state.api.delete(key.into_bytes()).await {
-        Ok(_) => (StatusCode::OK, "OK".to_string()),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Error: {}", e),
-        ),
+/// Attach the version of the read key, if it was ever written
+fn with_key_version(mut response: Response, version: Option<u64>) -> Response {
+    if let Some(version) = version {
+        response
+            .headers_mut()
+            .insert(KEY_VERSION_HEADER, HeaderValue::from(version));
     }
+    response
 }
 
-async fn metrics_handler(State(state): State<AppState>) -> impl IntoResponse {
-    let metrics = state.api.metrics().await;
-    axum::Json(metrics)
+/// Attach the consistency token of the state a read was served from
+fn with_read_token(mut response: Response, token: ConsistencyToken) -> Response {
+    response
+        .headers_mut()
+        .insert(CONSISTENCY_TOKEN_HEADER, HeaderValue::from(token.applied_index()));
+    response
 }
 
-/// Start HTTP API server
-async fn start_http_server(addr: &str, state: AppState) -> Result<()> {
-    let app = Router::new()
-        .route("/health", get(health_handler))
-        .route("/metrics", get(metrics_handler))
-        .route("/:key", put(put_handler))
-        .route("/:key", get(get_handler))
-        .route("/:key", delete(delete_handler))
-        .with_state(state)
-        .layer(CorsLayer::permissive());
+/// Query of `DELETE /:key`
+#[derive(Deserialize)]
+struct DeleteQuery {
+    /// `previous` to answer with the deleted value
+    #[serde(rename = "return")]
+    return_value: Option<String>,
+}
 
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    info!("HTTP server listening on {}", addr);
-    
-    axum::serve(listener, app).await?;
+async fn delete_handler(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    Query(query): Query<DeleteQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let fence = match fence_from_headers(&headers) {
+        Ok(fence) => fence,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    match precondition_from_headers(&headers) {
+        Ok(None) => {}
+        Ok(Some(WritePrecondition::Matches(_))) if fence.is_some() => {
+            return (
+                StatusCode::BAD_REQUEST,
+                "Conditional deletes cannot be fenced".to_string(),
+            )
+                .into_response()
+        }
+        Ok(Some(precondition @ WritePrecondition::Matches(_)))
+            if query.return_value.is_none() =>
+        {
+            return conditional_write(&state, &key, precondition, None).await
+        }
+        Ok(Some(_)) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                "Deletes only support If-Match without a return value".to_string(),
+            )
+                .into_response()
+        }
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    }
+    match query.return_value.as_deref() {
+        None => {
+            let result = match fence {
+                Some(fence) => {
+                    state
+                        .api
+                        .delete_fenced(key.clone().into_bytes(), fence)
+                        .await
+                }
+                None => state.api.delete(key.clone().into_bytes()).await,
+            };
+            match result {
+                Ok(token) => write_ok_response(token),
+                Err(ScribeError::Conflict(message)) => {
+                    (StatusCode::CONFLICT, message).into_response()
+                }
+                Err(e) => error_response(&state, e).await,
+            }
+        }
+        Some(_) if fence.is_some() => (
+            StatusCode::BAD_REQUEST,
+            "Fenced deletes cannot return the previous value".to_string(),
+        )
+            .into_response(),
+        Some("previous") => match state.api.get_and_delete(key.clone().into_bytes()).await {
+            Ok((previous, token)) => previous_value_response(previous, token),
+            Err(e) => error_response(&state, e).await,
+        },
+        Some(other) => (
+            StatusCode::BAD_REQUEST,
+            format!("Unsupported return value '{}', expected 'previous'", other),
+        )
+            .into_response(),
+    }
+}
+
+/// Store the body at a key and answer with the value it replaced
+async fn swap_handler(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    body: Bytes,
+) -> Response {
+    match state
+        .api
+        .get_and_set(key.clone().into_bytes(), body.to_vec())
+        .await
+    {
+        Ok((previous, token)) => previous_value_response(previous, token),
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// Answer a get-and-set or get-and-delete: `200` with the previous value, or `204`
+/// if the key held none
+fn previous_value_response(previous: Option<Vec<u8>>, token: ConsistencyToken) -> Response {
+    let mut response = match previous {
+        Some(value) => (StatusCode::OK, value).into_response(),
+        None => StatusCode::NO_CONTENT.into_response(),
+    };
+    response
+        .headers_mut()
+        .insert(CONSISTENCY_TOKEN_HEADER, HeaderValue::from(token.applied_index()));
+    response
+}
+
+/// Apply an array of puts and deletes as one Raft entry
+///
+/// Either every operation is committed or none is. Responds like a single write,
+/// with the consistency token of the batch.
+async fn batch_handler(
+    State(state): State<AppState>,
+    axum::Json(ops): axum::Json<Vec<BatchOp>>,
+) -> Response {
+    if ops.is_empty() || ops.len() > MAX_BATCH_OPS {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("A batch holds 1 to {} operations", MAX_BATCH_OPS),
+        )
+            .into_response();
+    }
+    let mut writes = Vec::with_capacity(ops.len());
+    for op in ops {
+        let (key, value) = match op {
+            BatchOp::Put { key, value } => (key, Some(value.into_bytes())),
+            BatchOp::Delete { key } => (key, None),
+        };
+        if key.is_empty() {
+            return (
+                StatusCode::BAD_REQUEST,
+                "Keys must not be empty".to_string(),
+            )
+                .into_response();
+        }
+        writes.push((key.into_bytes(), value));
+    }
+
+    match state.api.write_batch(writes).await {
+        Ok(token) => write_ok_response(token),
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// Body of `POST /:key/rename`
+#[derive(Deserialize)]
+struct RenameRequest {
+    /// Key the value is moved to (replaced if it exists)
+    new_key: String,
+}
+
+/// Atomically move a key's value to `new_key`
+async fn rename_handler(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    axum::Json(request): axum::Json<RenameRequest>,
+) -> Response {
+    if request.new_key.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            "new_key must not be empty".to_string(),
+        )
+            .into_response();
+    }
+
+    match state
+        .api
+        .rename(key.clone().into_bytes(), request.new_key.into_bytes())
+        .await
+    {
+        Ok(token) => write_ok_response(token),
+        Err(ScribeError::NotFound(message)) => (StatusCode::NOT_FOUND, message).into_response(),
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// Apply an RFC 7386 merge patch to the JSON document at `key`
+///
+/// The merge runs inside the state machine, so concurrent patches don't race.
+/// Responds with the merged document.
+async fn patch_doc_handler(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    body: Bytes,
+) -> Response {
+    let patch: serde_json::Value = match serde_json::from_slice(&body) {
+        Ok(patch) => patch,
+        Err(e) => {
+            return (StatusCode::BAD_REQUEST, format!("Invalid merge patch: {}", e))
+                .into_response()
+        }
+    };
+
+    match state.api.patch_json(key.clone().into_bytes(), &patch).await {
+        Ok((document, token)) => {
+            let mut response = (
+                StatusCode::OK,
+                [(header::CONTENT_TYPE, "application/json")],
+                document,
+            )
+                .into_response();
+            response
+                .headers_mut()
+                .insert(CONSISTENCY_TOKEN_HEADER, HeaderValue::from(token.applied_index()));
+            response
+        }
+        Err(ScribeError::Serialization(message)) => {
+            (StatusCode::UNPROCESSABLE_ENTITY, message).into_response()
+        }
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// Acquire a lock lease; responds with the lease and its fencing token, or 409
+/// while another holder's lease is valid
+async fn lock_acquire_handler(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    axum::Json(request): axum::Json<AcquireLockRequest>,
+) -> Response {
+    if request.ttl_secs == 0 {
+        return (
+            StatusCode::BAD_REQUEST,
+            "ttl_secs must be positive".to_string(),
+        )
+            .into_response();
+    }
+
+    match state
+        .api
+        .acquire_lock(
+            &name,
+            &request.holder,
+            Duration::from_secs(request.ttl_secs),
+        )
+        .await
+    {
+        Ok(lease) => axum::Json(lease).into_response(),
+        Err(ScribeError::Conflict(message)) => (StatusCode::CONFLICT, message).into_response(),
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// Extend a lock lease; 409 if the lease expired or was released
+async fn lock_renew_handler(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    axum::Json(request): axum::Json<RenewLockRequest>,
+) -> Response {
+    if request.ttl_secs == 0 {
+        return (
+            StatusCode::BAD_REQUEST,
+            "ttl_secs must be positive".to_string(),
+        )
+            .into_response();
+    }
+
+    match state
+        .api
+        .renew_lock(&name, request.token, Duration::from_secs(request.ttl_secs))
+        .await
+    {
+        Ok(lease) => axum::Json(lease).into_response(),
+        Err(ScribeError::Conflict(message)) => (StatusCode::CONFLICT, message).into_response(),
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// Release a lock lease
+async fn lock_release_handler(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    axum::Json(request): axum::Json<ReleaseLockRequest>,
+) -> Response {
+    match state.api.release_lock(&name, request.token).await {
+        Ok(released) => axum::Json(ReleaseLockResponse { released }).into_response(),
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// Write fencing epoch of a namespace, as applied on this node
+async fn epoch_handler(State(state): State<AppState>, Path(namespace): Path<String>) -> Response {
+    let epoch = state.api.epoch(&namespace).await;
+    axum::Json(EpochResponse { namespace, epoch }).into_response()
+}
+
+/// Advance the write fencing epoch of a namespace; responds with the new epoch, or
+/// 409 if the requested epoch is older than the current one
+async fn advance_epoch_handler(
+    State(state): State<AppState>,
+    identity: Option<Extension<ClientCertIdentity>>,
+    Path(namespace): Path<String>,
+    request: Option<axum::Json<AdvanceEpochRequest>>,
+) -> Response {
+    let epoch = request.and_then(|axum::Json(request)| request.epoch);
+    match state.api.advance_epoch(&namespace, epoch).await {
+        Ok(epoch) => {
+            info!("Advanced epoch of namespace '{}' to {}", namespace, epoch);
+            record_admin_event(
+                &state,
+                request_actor(identity.as_deref(), "api"),
+                AdminAction::EpochAdvanced {
+                    namespace: namespace.clone(),
+                    epoch,
+                },
+                Ok(()),
+            );
+            axum::Json(EpochResponse { namespace, epoch }).into_response()
+        }
+        Err(ScribeError::Conflict(message)) => (StatusCode::CONFLICT, message).into_response(),
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// Current lease on a lock, as applied on this node
+async fn lock_info_handler(State(state): State<AppState>, Path(name): Path<String>) -> Response {
+    match state.api.lock_info(&name).await {
+        Some(lease) => axum::Json(lease).into_response(),
+        None => (StatusCode::NOT_FOUND, format!("Lock '{}' is not held", name)).into_response(),
+    }
+}
+
+/// Create a session; responds with the session and its random ID
+async fn create_session_handler(
+    State(state): State<AppState>,
+    axum::Json(request): axum::Json<CreateSessionRequest>,
+) -> Response {
+    if request.idle_timeout_secs == 0 {
+        return (
+            StatusCode::BAD_REQUEST,
+            "idle_timeout_secs must be positive".to_string(),
+        )
+            .into_response();
+    }
+
+    match state
+        .api
+        .create_session(request.data, Duration::from_secs(request.idle_timeout_secs))
+        .await
+    {
+        Ok(session) => (StatusCode::CREATED, axum::Json(session)).into_response(),
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// Read a session, pushing its expiry forward; 404 if it expired or never existed
+async fn get_session_handler(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    match state.api.get_session(&id).await {
+        Ok(Some(session)) => axum::Json(session).into_response(),
+        Ok(None) => session_not_found(&id),
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// Push the expiry of a session forward without reading it
+async fn touch_session_handler(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    match state.api.touch_session(&id).await {
+        Ok(Some(expires_at)) => axum::Json(TouchSessionResponse { expires_at }).into_response(),
+        Ok(None) => session_not_found(&id),
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// Destroy a session; 404 if it expired or never existed
+async fn destroy_session_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Response {
+    match state.api.destroy_session(&id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => session_not_found(&id),
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+fn session_not_found(id: &str) -> Response {
+    (StatusCode::NOT_FOUND, format!("Session '{}' not found", id)).into_response()
+}
+
+/// Append the body to a queue; responds with the item's ID, or 409 while the queue
+/// is full
+async fn enqueue_handler(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    body: Bytes,
+) -> Response {
+    match state.api.enqueue(&name, body.to_vec()).await {
+        Ok(id) => (StatusCode::CREATED, axum::Json(EnqueueResponse { id })).into_response(),
+        Err(ScribeError::Conflict(message)) => (StatusCode::CONFLICT, message).into_response(),
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// Lease the oldest visible item of a queue; responds with its payload and the
+/// item headers, or 204 if no item is visible
+async fn dequeue_handler(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    axum::Json(request): axum::Json<DequeueRequest>,
+) -> Response {
+    if request.visibility_timeout_secs == 0 {
+        return (
+            StatusCode::BAD_REQUEST,
+            "visibility_timeout_secs must be positive".to_string(),
+        )
+            .into_response();
+    }
+
+    match state
+        .api
+        .dequeue(&name, Duration::from_secs(request.visibility_timeout_secs))
+        .await
+    {
+        Ok(Some(item)) => {
+            let mut response = (StatusCode::OK, item.payload).into_response();
+            let headers = response.headers_mut();
+            headers.insert(QUEUE_ITEM_ID_HEADER, HeaderValue::from(item.id));
+            headers.insert(QUEUE_RECEIPT_HEADER, HeaderValue::from(item.receipt));
+            headers.insert(QUEUE_ATTEMPTS_HEADER, HeaderValue::from(item.attempts));
+            headers.insert(QUEUE_VISIBLE_AT_HEADER, HeaderValue::from(item.visible_at));
+            response
+        }
+        Ok(None) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// Remove a processed item from a queue
+async fn ack_handler(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    axum::Json(request): axum::Json<AckRequest>,
+) -> Response {
+    match state.api.ack(&name, request.id, request.receipt).await {
+        Ok(acked) => axum::Json(AckResponse { acked }).into_response(),
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// Length of a queue, as applied on this node
+async fn queue_stats_handler(State(state): State<AppState>, Path(name): Path<String>) -> Response {
+    axum::Json(state.api.queue_stats(&name).await).into_response()
+}
+
+/// Query parameters for `GET /keys`
+#[derive(Deserialize)]
+struct KeysQuery {
+    /// Window start in milliseconds since UNIX epoch (default: 0)
+    since: Option<u64>,
+    /// Window end in milliseconds since UNIX epoch (default: unbounded)
+    until: Option<u64>,
+    /// Page size (default: DEFAULT_KEYS_LIMIT)
+    limit: Option<usize>,
+    /// Only return changes after this one, as given in `next`
+    cursor: Option<String>,
+}
+
+/// A key change as listed by `GET /keys`
+///
+/// Keys that are not UTF-8 are given hex-encoded as `key_hex` instead of `key`.
+#[derive(Serialize)]
+struct KeyChangeResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key_hex: Option<String>,
+    timestamp: u64,
+    deleted: bool,
+}
+
+impl From<&KeyChange> for KeyChangeResponse {
+    fn from(change: &KeyChange) -> Self {
+        let (key, key_hex) = match std::str::from_utf8(&change.key) {
+            Ok(key) => (Some(key.to_string()), None),
+            Err(_) => (None, Some(hex::encode(&change.key))),
+        };
+        Self {
+            key,
+            key_hex,
+            timestamp: change.timestamp,
+            deleted: change.deleted,
+        }
+    }
+}
+
+/// Cursor of `GET /keys` following `change`: its timestamp and hex-encoded key
+fn keys_cursor(change: &KeyChange) -> String {
+    format!("{}:{}", change.timestamp, hex::encode(&change.key))
+}
+
+/// Timestamp and key of a `GET /keys` cursor
+fn parse_keys_cursor(cursor: &str) -> Option<(u64, Vec<u8>)> {
+    let (timestamp, key) = cursor.split_once(':')?;
+    Some((timestamp.parse().ok()?, hex::decode(key).ok()?))
+}
+
+/// List keys modified within a time window, for incremental sync consumers
+///
+/// Changes are listed by modification time, a page at a time; `next` is the cursor
+/// of the following page, or null after the last one.
+async fn keys_handler(
+    State(state): State<AppState>,
+    Query(query): Query<KeysQuery>,
+) -> Response {
+    let since = query.since.unwrap_or(0);
+    let until = query.until.unwrap_or(u64::MAX);
+    if since > until {
+        return (
+            StatusCode::BAD_REQUEST,
+            "'since' must not be after 'until'".to_string(),
+        )
+            .into_response();
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_KEYS_LIMIT);
+    if limit == 0 || limit > MAX_KEYS_LIMIT {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("'limit' must be between 1 and {}", MAX_KEYS_LIMIT),
+        )
+            .into_response();
+    }
+    let cursor = match query.cursor.as_deref().map(parse_keys_cursor) {
+        None => None,
+        Some(Some(cursor)) => Some(cursor),
+        Some(None) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                "'cursor' must be a cursor returned as 'next'".to_string(),
+            )
+                .into_response();
+        }
+    };
+
+    // One change past the page tells whether there is another page
+    let mut changes = state
+        .api
+        .keys_modified_page(since, until, cursor.as_ref(), limit + 1)
+        .await;
+    let next = if changes.len() > limit {
+        changes.truncate(limit);
+        changes.last().map(keys_cursor)
+    } else {
+        None
+    };
+    let keys: Vec<KeyChangeResponse> = changes.iter().map(KeyChangeResponse::from).collect();
+
+    axum::Json(serde_json::json!({ "keys": keys, "next": next })).into_response()
+}
+
+/// Query of the `/sync/buckets` endpoints
+#[derive(Deserialize)]
+struct SyncQuery {
+    /// Number of buckets keys are split into (default: 256)
+    buckets: Option<u32>,
+}
+
+impl SyncQuery {
+    /// Requested number of buckets, or why it is out of range
+    fn buckets(&self) -> std::result::Result<u32, String> {
+        let buckets = self.buckets.unwrap_or(DEFAULT_SYNC_BUCKETS);
+        if buckets == 0 || buckets > MAX_SYNC_BUCKETS {
+            return Err(format!(
+                "'buckets' must be between 1 and {}",
+                MAX_SYNC_BUCKETS
+            ));
+        }
+        Ok(buckets)
+    }
+}
+
+/// Merkle root of every sync bucket, for differential sync
+async fn sync_buckets_handler(
+    State(state): State<AppState>,
+    Query(query): Query<SyncQuery>,
+) -> Response {
+    let buckets = match query.buckets() {
+        Ok(buckets) => buckets,
+        Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
+    };
+    match state.api.sync_buckets(buckets).await {
+        Ok(digests) => axum::Json(digests).into_response(),
+        Err(e) => (StatusCode::BAD_GATEWAY, format!("Error: {}", e)).into_response(),
+    }
+}
+
+/// Value digest of every key in one sync bucket
+async fn sync_bucket_keys_handler(
+    State(state): State<AppState>,
+    Path(bucket): Path<u32>,
+    Query(query): Query<SyncQuery>,
+) -> Response {
+    let buckets = match query.buckets() {
+        Ok(buckets) => buckets,
+        Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
+    };
+    if bucket >= buckets {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("Bucket {} is out of range (buckets={})", bucket, buckets),
+        )
+            .into_response();
+    }
+    match state.api.sync_bucket_keys(bucket, buckets).await {
+        Ok(digests) => axum::Json(digests).into_response(),
+        Err(e) => (StatusCode::BAD_GATEWAY, format!("Error: {}", e)).into_response(),
+    }
+}
+
+/// Serialize a change event for the event stream
+fn change_event_to_sse(event: &ChangeEvent) -> Event {
+    Event::default()
+        .event(event.kind.as_str())
+        .json_data(serde_json::json!({
+            "key": String::from_utf8_lossy(&event.key),
+            "kind": event.kind.as_str(),
+            "timestamp": event.timestamp,
+            "version": event.version,
+        }))
+        .unwrap_or_default()
+}
+
+/// Stream change events (put, delete, expire) as server-sent events, leaving out
+/// those the subscription's filter rejects
+async fn events_handler(
+    State(state): State<AppState>,
+    Query(query): Query<SubscriptionQuery>,
+) -> Response {
+    let filter = match query.compile() {
+        Ok(filter) => filter,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let receiver = state.api.subscribe_changes();
+    let stream = futures::stream::unfold(receiver, move |mut receiver| {
+        let filter = filter.clone();
+        async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) if filter.matches(&event) => {
+                        return Some((Ok::<_, Infallible>(change_event_to_sse(&event)), receiver))
+                    }
+                    Ok(_) => {}
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Event stream subscriber lagged, skipped {} events", skipped);
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        }
+    });
+
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+fn leadership_to_sse(change: &LeadershipChange) -> Event {
+    Event::default()
+        .event("leadership")
+        .json_data(change)
+        .unwrap_or_default()
+}
+
+/// Stream leadership changes seen by this node as server-sent events, starting with
+/// the current leadership
+async fn leadership_events_handler(State(state): State<AppState>) -> impl IntoResponse {
+    // Subscribe before reading the current state so no change slips in between
+    let receiver = state.consensus.subscribe_leadership();
+    let current = state.consensus.leadership();
+    let stream = futures::stream::unfold(
+        (Some(current), receiver),
+        |(current, mut receiver)| async move {
+            if let Some(current) = current {
+                let event = leadership_to_sse(&current);
+                return Some((Ok::<_, Infallible>(event), (None, receiver)));
+            }
+            loop {
+                match receiver.recv().await {
+                    Ok(change) => {
+                        let event = leadership_to_sse(&change);
+                        return Some((Ok(event), (None, receiver)));
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!(
+                            "Leadership stream subscriber lagged, skipped {} changes",
+                            skipped
+                        );
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        },
+    );
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// Request body for `POST /cluster/tokens`
+#[derive(Deserialize)]
+struct CreateTokenRequest {
+    /// Token lifetime in seconds
+    ttl_secs: u64,
+}
+
+/// Issue a one-time join token (leader only, authenticated with the cluster secret)
+async fn create_token_handler(
+    State(state): State<AppState>,
+    identity: Option<Extension<ClientCertIdentity>>,
+    headers: HeaderMap,
+    axum::Json(req): axum::Json<CreateTokenRequest>,
+) -> Response {
+    let Some(tokens) = &state.join_tokens else {
+        return (StatusCode::NOT_FOUND, "Join tokens are not enabled".to_string()).into_response();
+    };
+
+    let presented = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    if !presented.is_some_and(|secret| tokens.matches_secret(secret)) {
+        return (StatusCode::UNAUTHORIZED, "Invalid cluster secret".to_string()).into_response();
+    }
+
+    // Tokens are consumed on the leader, so they must be issued there too
+    if !state.consensus.is_leader().await {
+        let err = ScribeError::NotLeader { leader_id: None };
+        return error_response(&state, err).await;
+    }
+
+    match tokens.issue(Duration::from_secs(req.ttl_secs)) {
+        Ok((token, issued)) => {
+            info!("Issued join token expiring at {}", issued.expires_at);
+            record_admin_event(
+                &state,
+                request_actor(identity.as_deref(), "cluster-secret"),
+                AdminAction::TokenCreated {
+                    token_id: issued.token_id.clone(),
+                    expires_at: issued.expires_at,
+                },
+                Ok(()),
+            );
+            axum::Json(serde_json::json!({
+                "token": token,
+                "cluster_id": issued.cluster_id,
+                "expires_at": issued.expires_at,
+            }))
+            .into_response()
+        }
+        Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+    }
+}
+
+/// Add a node presenting a valid join token as a learner (leader only)
+async fn join_handler(
+    State(state): State<AppState>,
+    axum::Json(req): axum::Json<JoinRequest>,
+) -> Response {
+    let Some(tokens) = &state.join_tokens else {
+        return (StatusCode::NOT_FOUND, "Join tokens are not enabled".to_string()).into_response();
+    };
+
+    if !state.consensus.is_leader().await {
+        let err = ScribeError::NotLeader { leader_id: None };
+        return error_response(&state, err).await;
+    }
+
+    let token = match tokens.consume(&req.token) {
+        Ok(token) => token,
+        Err(e) => {
+            warn!("Rejected join request from node {}: {}", req.node_id, e);
+            return (StatusCode::FORBIDDEN, e).into_response();
+        }
+    };
+
+    info!("Adding node {} at {} as learner", req.node_id, req.raft_addr);
+    state
+        .consensus
+        .register_peer(req.node_id, req.raft_addr.clone())
+        .await;
+    let result = state
+        .consensus
+        .add_learner(req.node_id, BasicNode::new(req.raft_addr))
+        .await
+        .map_err(|e| e.to_string());
+    record_admin_event(
+        &state,
+        format!("join-token:{}", token.token_id),
+        AdminAction::MembershipChange {
+            node_id: req.node_id,
+            change: "add_learner".to_string(),
+        },
+        result.clone(),
+    );
+
+    match result {
+        Ok(()) => (StatusCode::OK, "Added as learner".to_string()).into_response(),
+        Err(e) => {
+            error!("Failed to add node {} as learner: {}", req.node_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("Error: {}", e)).into_response()
+        }
+    }
+}
+
+/// Body of `POST /cluster/nodes/add`
+#[derive(Deserialize)]
+struct AddNodeRequest {
+    node_id: NodeId,
+    /// Raft address of the node
+    #[serde(alias = "address")]
+    raft_addr: String,
+    /// Keep the node a learner instead of promoting it to voter
+    #[serde(default)]
+    learner: bool,
+    /// Entries the learner may trail the leader by and be promoted
+    max_lag: Option<u64>,
+    /// Seconds the learner may take to catch up before the change fails
+    timeout_secs: Option<u64>,
+}
+
+/// Body of `POST /cluster/nodes/remove`
+#[derive(Deserialize)]
+struct RemoveNodeRequest {
+    node_id: NodeId,
+}
+
+/// Add a node as a learner and, unless `learner` is set, promote it to voter once
+/// it has caught up; 202 with the change, followed at
+/// `GET /cluster/nodes/changes/:id`
+async fn add_node_handler(
+    State(state): State<AppState>,
+    identity: Option<Extension<ClientCertIdentity>>,
+    axum::Json(req): axum::Json<AddNodeRequest>,
+) -> Response {
+    if !state.consensus.is_leader().await {
+        let err = ScribeError::NotLeader { leader_id: None };
+        return error_response(&state, err).await;
+    }
+    if state.consensus.voter_ids().contains(&req.node_id) {
+        return (
+            StatusCode::CONFLICT,
+            format!("Node {} is already a voter", req.node_id),
+        )
+            .into_response();
+    }
+
+    let operation = if req.learner {
+        MembershipOp::AddLearner
+    } else {
+        MembershipOp::AddVoter
+    };
+    let change = match state
+        .membership
+        .begin(req.node_id, operation, Some(req.raft_addr.clone()))
+    {
+        Ok(change) => change,
+        Err(e) => return (StatusCode::CONFLICT, e).into_response(),
+    };
+    info!(
+        "Adding node {} at {} ({:?}, change {})",
+        req.node_id, req.raft_addr, operation, change.id
+    );
+
+    let actor = request_actor(identity.as_deref(), "api");
+    let task_state = state.clone();
+    let id = change.id;
+    tokio::spawn(async move {
+        let result = add_node(&task_state, id, &req, &actor).await;
+        if let Err(e) = &result {
+            warn!("Adding node {} failed: {}", req.node_id, e);
+        }
+        task_state
+            .membership
+            .finish(id, result.map_err(|e| e.to_string()));
+    });
+    (StatusCode::ACCEPTED, axum::Json(change)).into_response()
+}
+
+/// Run membership change `id`: add `req.node_id` as a learner, wait until it has
+/// caught up, then promote it
+async fn add_node(
+    state: &AppState,
+    id: u64,
+    req: &AddNodeRequest,
+    actor: &str,
+) -> Result<(), ScribeError> {
+    state
+        .consensus
+        .register_peer(req.node_id, req.raft_addr.clone())
+        .await;
+    // A node that joined as a learner before is only promoted
+    if !state.consensus.is_member(req.node_id) {
+        let result = state
+            .consensus
+            .start_learner(req.node_id, BasicNode::new(req.raft_addr.clone()))
+            .await;
+        record_membership_event(state, actor, req.node_id, "add_learner", &result);
+        result?;
+    }
+    if req.learner {
+        return Ok(());
+    }
+
+    state.membership.set_phase(id, ChangePhase::CatchingUp);
+    let max_lag = req.max_lag.unwrap_or(DEFAULT_CAUGHT_UP_LAG);
+    let timeout = req
+        .timeout_secs
+        .map_or(DEFAULT_CATCH_UP_TIMEOUT, Duration::from_secs);
+    let deadline = Instant::now() + timeout;
+    loop {
+        // Fails if this node lost leadership in the meantime
+        let report = state.consensus.learner_progress(max_lag).await?;
+        let progress = report
+            .learners
+            .into_iter()
+            .find(|learner| learner.node_id == req.node_id)
+            .ok_or_else(|| {
+                ScribeError::Cluster(format!("Node {} is no longer a learner", req.node_id))
+            })?;
+        let ready = progress.ready_to_promote;
+        state.membership.set_progress(id, progress);
+        if ready {
+            break;
+        }
+        if Instant::now() >= deadline {
+            return Err(ScribeError::Timeout(format!(
+                "Node {} did not catch up within {}s",
+                req.node_id,
+                timeout.as_secs()
+            )));
+        }
+        tokio::time::sleep(MEMBERSHIP_POLL_INTERVAL).await;
+    }
+
+    state.membership.set_phase(id, ChangePhase::Promoting);
+    let result = state.consensus.promote_learner(req.node_id).await;
+    record_membership_event(state, actor, req.node_id, "promote_voter", &result);
+    result
+}
+
+/// Remove a voter or learner from the cluster; 202 with the change, followed at
+/// `GET /cluster/nodes/changes/:id`
+async fn remove_node_handler(
+    State(state): State<AppState>,
+    identity: Option<Extension<ClientCertIdentity>>,
+    axum::Json(req): axum::Json<RemoveNodeRequest>,
+) -> Response {
+    if !state.consensus.is_leader().await {
+        let err = ScribeError::NotLeader { leader_id: None };
+        return error_response(&state, err).await;
+    }
+    if !state.consensus.is_member(req.node_id) {
+        return (
+            StatusCode::NOT_FOUND,
+            format!("Node {} is not a member", req.node_id),
+        )
+            .into_response();
+    }
+    let voters = state.consensus.voter_ids();
+    if voters.len() == 1 && voters.contains(&req.node_id) {
+        return (
+            StatusCode::CONFLICT,
+            format!("Node {} is the last voter", req.node_id),
+        )
+            .into_response();
+    }
+
+    let change = match state
+        .membership
+        .begin(req.node_id, MembershipOp::Remove, None)
+    {
+        Ok(change) => change,
+        Err(e) => return (StatusCode::CONFLICT, e).into_response(),
+    };
+    info!("Removing node {} (change {})", req.node_id, change.id);
+
+    let actor = request_actor(identity.as_deref(), "api");
+    let task_state = state.clone();
+    let id = change.id;
+    tokio::spawn(async move {
+        let result = task_state.consensus.remove_member(req.node_id).await;
+        record_membership_event(&task_state, &actor, req.node_id, "remove", &result);
+        if let Err(e) = &result {
+            warn!("Removing node {} failed: {}", req.node_id, e);
+        }
+        task_state
+            .membership
+            .finish(id, result.map_err(|e| e.to_string()));
+    });
+    (StatusCode::ACCEPTED, axum::Json(change)).into_response()
+}
+
+/// Running and recent membership changes, newest first
+async fn membership_changes_handler(State(state): State<AppState>) -> Response {
+    axum::Json(state.membership.list()).into_response()
+}
+
+/// One membership change with its phase and the learner's catch-up progress
+async fn membership_change_handler(State(state): State<AppState>, Path(id): Path<u64>) -> Response {
+    match state.membership.get(id) {
+        Some(change) => axum::Json(change).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            format!("No membership change {} on this node", id),
+        )
+            .into_response(),
+    }
+}
+
+/// Record a step of a membership change in the admin event log
+fn record_membership_event(
+    state: &AppState,
+    actor: &str,
+    node_id: NodeId,
+    change: &str,
+    result: &Result<(), ScribeError>,
+) {
+    record_admin_event(
+        state,
+        actor.to_string(),
+        AdminAction::MembershipChange {
+            node_id,
+            change: change.to_string(),
+        },
+        result.as_ref().map_err(|e| e.to_string()).copied(),
+    );
+}
+
+/// Snapshot this node's state and purge the log it covers; peers that fall behind
+/// or join afterwards install the snapshot instead of replaying the log
+async fn compact_handler(
+    State(state): State<AppState>,
+    identity: Option<Extension<ClientCertIdentity>>,
+) -> Response {
+    let result = state.consensus.compact_log(SNAPSHOT_BUILD_TIMEOUT).await;
+    record_admin_event(
+        &state,
+        request_actor(identity.as_deref(), "api"),
+        AdminAction::CompactionTrigger,
+        result.as_ref().map(|_| ()).map_err(|e| e.to_string()),
+    );
+    match result {
+        Ok(snapshot) => {
+            info!("Compacted the log up to index {}", snapshot.index);
+            axum::Json(serde_json::json!({ "snapshot_index": snapshot.index })).into_response()
+        }
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// A namespace migration as served over HTTP
+#[derive(Serialize)]
+struct MigrationStatus {
+    #[serde(flatten)]
+    checkpoint: MigrationCheckpoint,
+    /// Whether this node is committing its batches
+    active: bool,
+}
+
+fn migration_status(state: &AppState, checkpoint: MigrationCheckpoint) -> MigrationStatus {
+    let active = state.migrations.is_running(&checkpoint.id);
+    MigrationStatus { checkpoint, active }
+}
+
+/// Start namespace migration `id` on the leader, or resume it; 202 with its
+/// checkpoint, followed at `GET /admin/migrations/:id`
+async fn start_migration_handler(
+    State(state): State<AppState>,
+    identity: Option<Extension<ClientCertIdentity>>,
+    Path(id): Path<String>,
+    axum::Json(spec): axum::Json<MigrationSpec>,
+) -> Response {
+    if let Err(e) = namespace_migration::validate_id(&id).and_then(|()| spec.validate()) {
+        return (StatusCode::BAD_REQUEST, e).into_response();
+    }
+    if !state.consensus.is_leader().await {
+        let err = ScribeError::NotLeader { leader_id: None };
+        return error_response(&state, err).await;
+    }
+    let Some(run) = state.migrations.start(&id) else {
+        return (
+            StatusCode::CONFLICT,
+            format!("Migration '{}' is already running", id),
+        )
+            .into_response();
+    };
+
+    let result = state.api.start_migration(&id, spec).await;
+    record_admin_event(
+        &state,
+        request_actor(identity.as_deref(), "api"),
+        AdminAction::NamespaceMigration {
+            id: id.clone(),
+            change: "start".to_string(),
+        },
+        result.as_ref().map(|_| ()).map_err(|e| e.to_string()),
+    );
+    let checkpoint = match result {
+        Ok(checkpoint) => checkpoint,
+        Err(ScribeError::Conflict(message)) => {
+            return (StatusCode::CONFLICT, message).into_response()
+        }
+        Err(e) => return error_response(&state, e).await,
+    };
+    if checkpoint.state == MigrationState::Completed {
+        drop(run);
+        return axum::Json(migration_status(&state, checkpoint)).into_response();
+    }
+    info!(
+        "Migrating '{}' to '{}' ({:?}, migration '{}', {} batches done)",
+        checkpoint.spec.source,
+        checkpoint.spec.target,
+        checkpoint.spec.mode,
+        id,
+        checkpoint.batches
+    );
+
+    let task_state = state.clone();
+    let started = checkpoint.clone();
+    tokio::spawn(async move {
+        match task_state.api.run_migration(&run, started).await {
+            Ok(checkpoint) => info!(
+                "Migration '{}' is {:?}: {} keys migrated, {} unchanged, {} skipped",
+                checkpoint.id,
+                checkpoint.state,
+                checkpoint.migrated,
+                checkpoint.unchanged,
+                checkpoint.skipped
+            ),
+            Err(e) => warn!("Migration '{}' failed: {}", run.id(), e),
+        }
+    });
+    (
+        StatusCode::ACCEPTED,
+        axum::Json(migration_status(&state, checkpoint)),
+    )
+        .into_response()
+}
+
+/// Ask migration `id` to stop after its current batch; it is marked paused
+async fn pause_migration_handler(
+    State(state): State<AppState>,
+    identity: Option<Extension<ClientCertIdentity>>,
+    Path(id): Path<String>,
+) -> Response {
+    if !state.migrations.stop(&id) {
+        if !state.consensus.is_leader().await {
+            let err = ScribeError::NotLeader { leader_id: None };
+            return error_response(&state, err).await;
+        }
+        return (
+            StatusCode::CONFLICT,
+            format!("Migration '{}' is not running", id),
+        )
+            .into_response();
+    }
+    record_admin_event(
+        &state,
+        request_actor(identity.as_deref(), "api"),
+        AdminAction::NamespaceMigration {
+            id: id.clone(),
+            change: "pause".to_string(),
+        },
+        Ok(()),
+    );
+    StatusCode::ACCEPTED.into_response()
+}
+
+/// Every namespace migration, ordered by ID
+async fn list_migrations_handler(State(state): State<AppState>) -> Response {
+    match state.api.migrations().await {
+        Ok(migrations) => {
+            let migrations: Vec<MigrationStatus> = migrations
+                .into_iter()
+                .map(|checkpoint| migration_status(&state, checkpoint))
+                .collect();
+            axum::Json(serde_json::json!({ "migrations": migrations })).into_response()
+        }
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// One namespace migration and how far it got
+async fn migration_handler(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    match state.api.migration(&id).await {
+        Ok(Some(checkpoint)) => axum::Json(migration_status(&state, checkpoint)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, format!("No migration '{}'", id)).into_response(),
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// Identity of the caller: the client certificate fingerprint when one was
+/// presented, otherwise `fallback` (the credential the handler checked)
+fn request_actor(identity: Option<&ClientCertIdentity>, fallback: &str) -> String {
+    identity
+        .and_then(|identity| identity.fingerprint.as_deref())
+        .map(|fingerprint| format!("cert:{}", fingerprint))
+        .unwrap_or_else(|| fallback.to_string())
+}
+
+/// Record an administrative action, logging (not failing) if the log is unwritable
+fn record_admin_event(
+    state: &AppState,
+    actor: String,
+    action: AdminAction,
+    outcome: std::result::Result<(), String>,
+) {
+    if let Err(e) = state.admin_events.record(actor, action, outcome) {
+        error!("Failed to record admin event: {}", e);
+    }
+}
+
+/// Query the administrative event log, newest first
+async fn admin_events_handler(
+    State(state): State<AppState>,
+    Query(filter): Query<AdminEventFilter>,
+) -> Response {
+    match state.admin_events.query(&filter) {
+        Ok(events) => axum::Json(events).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Error: {}", e)).into_response(),
+    }
+}
+
+/// Recent errors of each subsystem, newest first
+async fn errors_handler(
+    State(state): State<AppState>,
+    Query(filter): Query<ErrorFilter>,
+) -> Response {
+    axum::Json(state.error_history.query(&filter)).into_response()
+}
+
+/// Last health evaluation; 404 if health scoring is disabled, 503 before the first
+async fn node_health_handler(State(state): State<AppState>) -> Response {
+    let Some(monitor) = &state.health else {
+        return (
+            StatusCode::NOT_FOUND,
+            "Health scoring is not enabled".to_string(),
+        )
+            .into_response();
+    };
+    match monitor.last_report() {
+        Some(report) => axum::Json(report).into_response(),
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Health has not been evaluated yet".to_string(),
+        )
+            .into_response(),
+    }
+}
+
+/// Query the key access trace, newest first; 404 if no namespace is traced
+async fn access_trace_handler(
+    State(state): State<AppState>,
+    Query(filter): Query<AccessTraceFilter>,
+) -> Response {
+    let Some(trace) = &state.access_trace else {
+        return (
+            StatusCode::NOT_FOUND,
+            "Access tracing is not enabled".to_string(),
+        )
+            .into_response();
+    };
+    match trace.query(&filter) {
+        Ok(records) => axum::Json(records).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Error: {}", e)).into_response(),
+    }
+}
+
+/// Body of `POST /admin/demote/:key`
+#[derive(Deserialize)]
+struct DemoteRequest {
+    /// Archived segment holding the key's current value
+    segment_id: SegmentId,
+}
+
+/// Drop a key's in-memory value in favour of the archived segment holding it
+async fn demote_handler(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    axum::Json(request): axum::Json<DemoteRequest>,
+) -> Response {
+    if state.archival.is_none() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "S3 archival is not configured".to_string(),
+        )
+            .into_response();
+    }
+
+    match state
+        .api
+        .demote(key.clone().into_bytes(), request.segment_id)
+        .await
+    {
+        Ok(demoted) => axum::Json(serde_json::json!({ "demoted": demoted })).into_response(),
+        Err(ScribeError::NotFound(message)) => (StatusCode::NOT_FOUND, message).into_response(),
+        Err(ScribeError::Storage(message)) => (StatusCode::CONFLICT, message).into_response(),
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// Query of `GET /admin/hotkeys` and `GET /admin/contention`
+#[derive(Deserialize)]
+struct HotKeysQuery {
+    /// Maximum number of keys and prefixes returned
+    limit: Option<usize>,
+}
+
+/// Hottest keys and busiest key prefixes seen by this node
+async fn hot_keys_handler(
+    State(state): State<AppState>,
+    Query(query): Query<HotKeysQuery>,
+) -> Response {
+    let limit = query.limit.unwrap_or(DEFAULT_HOT_KEYS_LIMIT);
+    axum::Json(state.api.hot_keys(limit)).into_response()
+}
+
+/// Key prefixes whose compare-and-swaps and transactions conflict most on this node
+async fn contention_handler(
+    State(state): State<AppState>,
+    Query(query): Query<HotKeysQuery>,
+) -> Response {
+    let limit = query.limit.unwrap_or(DEFAULT_HOT_KEYS_LIMIT);
+    axum::Json(state.api.contention(limit)).into_response()
+}
+
+/// Meter requests that present an API key, refusing them with 429 once the key
+/// has used up one of its quotas
+async fn quota_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let Some(quotas) = &state.quotas else {
+        return next.run(request).await;
+    };
+    let Some(api_key) = AuthMiddleware::extract_api_key(request.headers()) else {
+        return next.run(request).await;
+    };
+    let key_id = api_key_id(&api_key);
+    let recorded = state.api.quota_usage(&key_id).await;
+    if let Err(exceeded) = quotas.check(&key_id, recorded.as_ref(), unix_time_ms()) {
+        metrics::QUOTA_REJECTED
+            .with_label_values(&[exceeded.limit.as_str()])
+            .inc();
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, exceeded.retry_after_secs.to_string())],
+            axum::Json(exceeded),
+        )
+            .into_response();
+    }
+
+    let request_bytes = content_length(request.headers()).unwrap_or(0);
+    let response = next.run(request).await;
+    let response_bytes = content_length(response.headers())
+        .or_else(|| response.body().size_hint().exact())
+        .unwrap_or(0);
+    quotas.record(&key_id, request_bytes + response_bytes, unix_time_ms());
+    response
+}
+
+/// Milliseconds since the Unix epoch
+fn unix_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Usage and limits of every API key that has used the cluster
+async fn quotas_handler(State(state): State<AppState>) -> Response {
+    let Some(quotas) = &state.quotas else {
+        return (StatusCode::NOT_FOUND, "Quotas are not enabled".to_string()).into_response();
+    };
+    let now_ms = unix_time_ms();
+    let mut recorded: std::collections::BTreeMap<String, _> = state
+        .api
+        .quota_usages()
+        .await
+        .into_iter()
+        .map(|usage| (usage.key_id.clone(), Some(usage)))
+        .collect();
+    for key_id in quotas.pending_keys() {
+        recorded.entry(key_id).or_insert(None);
+    }
+    let reports: Vec<_> = recorded
+        .iter()
+        .map(|(key_id, usage)| quotas.report(key_id, usage.as_ref(), now_ms))
+        .collect();
+    axum::Json(reports).into_response()
+}
+
+/// Usage and limits of one API key, by key ID
+async fn quota_handler(State(state): State<AppState>, Path(key_id): Path<String>) -> Response {
+    let Some(quotas) = &state.quotas else {
+        return (StatusCode::NOT_FOUND, "Quotas are not enabled".to_string()).into_response();
+    };
+    let recorded = state.api.quota_usage(&key_id).await;
+    axum::Json(quotas.report(&key_id, recorded.as_ref(), unix_time_ms())).into_response()
+}
+
+/// Record quota usage handed over by a follower; 204 once it is replicated
+async fn record_usage_handler(
+    State(state): State<AppState>,
+    axum::Json(usage): axum::Json<Vec<UsageDelta>>,
+) -> Response {
+    if state.quotas.is_none() {
+        return (StatusCode::NOT_FOUND, "Quotas are not enabled".to_string()).into_response();
+    }
+    match state.api.record_usage(usage).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// Tokio runtime metrics, process memory and file handles, and build information
+async fn runtime_debug_handler() -> Response {
+    axum::Json(RuntimeReport::collect()).into_response()
+}
+
+/// S3 archive bucket, archival backlog and credential expiry
+async fn storage_stats_handler(State(state): State<AppState>) -> Response {
+    let Some(archival) = &state.archival else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "S3 archival is not configured".to_string(),
+        )
+            .into_response();
+    };
+
+    match archival.storage_stats() {
+        Ok(stats) => axum::Json(stats).into_response(),
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// Re-validate an archived segment against its recorded Merkle roots
+async fn verify_segment_handler(
+    State(state): State<AppState>,
+    Path(segment_id): Path<SegmentId>,
+) -> Response {
+    let Some(archival) = &state.archival else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "S3 archival is not configured".to_string(),
+        )
+            .into_response();
+    };
+
+    match archival.verify_segment(segment_id).await {
+        Ok(Some(report)) => axum::Json(report).into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            format!("Segment {} is not archived", segment_id),
+        )
+            .into_response(),
+        Err(e) => (StatusCode::BAD_GATEWAY, format!("Error: {}", e)).into_response(),
+    }
+}
+
+/// Query of `GET /verify/:key`
+#[derive(Deserialize)]
+struct VerifyQuery {
+    /// `bundle` for a sealed proof bundle, `bundle-json` for the same bundle as
+    /// JSON; the proof chain with its anchor if absent
+    format: Option<String>,
+}
+
+/// Proof chain from the value of a key to the Merkle root its archived segment
+/// anchored through Raft; 409 if the value is not in an anchored segment. With
+/// `format` the proof is exported as a bundle against the anchored root that
+/// `scribe-verify`, or a verifier in any language, checks offline
+async fn verify_key_handler(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    Query(query): Query<VerifyQuery>,
+) -> Response {
+    if !matches!(
+        query.format.as_deref(),
+        None | Some("bundle") | Some("bundle-json")
+    ) {
+        return (
+            StatusCode::BAD_REQUEST,
+            "format must be bundle or bundle-json".to_string(),
+        )
+            .into_response();
+    }
+
+    match state.api.prove_key(key.as_bytes().to_vec()).await {
+        Ok(Some(proof)) => match query.format.as_deref() {
+            Some("bundle") => (
+                StatusCode::OK,
+                [(header::CONTENT_TYPE, "application/octet-stream")],
+                seal(&proof.bundle),
+            )
+                .into_response(),
+            Some(_) => (
+                StatusCode::OK,
+                [(header::CONTENT_TYPE, "application/json")],
+                proof.bundle.to_json(),
+            )
+                .into_response(),
+            None => axum::Json(proof).into_response(),
+        },
+        Ok(None) => (StatusCode::NOT_FOUND, "Not found".to_string()).into_response(),
+        Err(ScribeError::Conflict(message)) => (StatusCode::CONFLICT, message).into_response(),
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// Tag the current ledger state; 409 if the name is taken
+async fn create_tag_handler(
+    State(state): State<AppState>,
+    identity: Option<Extension<ClientCertIdentity>>,
+    Path(name): Path<String>,
+) -> Response {
+    if name.is_empty() || name.len() > MAX_TAG_NAME_LEN {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("Tag names must be 1 to {} bytes long", MAX_TAG_NAME_LEN),
+        )
+            .into_response();
+    }
+
+    match state.api.create_tag(&name).await {
+        Ok(tag) => {
+            info!(
+                "Created tag '{}' at Raft index {}",
+                tag.name, tag.raft_index
+            );
+            record_admin_event(
+                &state,
+                request_actor(identity.as_deref(), "api"),
+                AdminAction::TagCreated {
+                    name: tag.name.clone(),
+                    raft_index: tag.raft_index,
+                },
+                Ok(()),
+            );
+            (StatusCode::CREATED, axum::Json(tag)).into_response()
+        }
+        Err(ScribeError::Conflict(message)) => (StatusCode::CONFLICT, message).into_response(),
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// All tags, oldest first
+async fn list_tags_handler(State(state): State<AppState>) -> Response {
+    axum::Json(serde_json::json!({ "tags": state.api.tags().await })).into_response()
+}
+
+/// A single tag
+async fn tag_info_handler(State(state): State<AppState>, Path(name): Path<String>) -> Response {
+    match state.api.tag(&name).await {
+        Some(tag) => axum::Json(tag).into_response(),
+        None => tag_not_found(&name),
+    }
+}
+
+/// Delete a tag and the state it captured
+async fn delete_tag_handler(
+    State(state): State<AppState>,
+    identity: Option<Extension<ClientCertIdentity>>,
+    Path(name): Path<String>,
+) -> Response {
+    match state.api.delete_tag(&name).await {
+        Ok(true) => {
+            record_admin_event(
+                &state,
+                request_actor(identity.as_deref(), "api"),
+                AdminAction::TagDeleted { name },
+                Ok(()),
+            );
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(false) => tag_not_found(&name),
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// Re-check a tag's Merkle root against the state it captured
+async fn verify_tag_handler(State(state): State<AppState>, Path(name): Path<String>) -> Response {
+    match state.api.verify_tag(&name).await {
+        Ok(Some(report)) => axum::Json(report).into_response(),
+        Ok(None) => tag_not_found(&name),
+        Err(e) => (StatusCode::BAD_GATEWAY, format!("Error: {}", e)).into_response(),
+    }
+}
+
+/// Export the state captured by a tag as JSON lines, ordered by key
+///
+/// The tag's Raft index and Merkle root are returned in response headers, so the
+/// export can be tied to the checkpoint it came from.
+async fn export_tag_handler(State(state): State<AppState>, Path(name): Path<String>) -> Response {
+    let Some(tag) = state.api.tag(&name).await else {
+        return tag_not_found(&name);
+    };
+    let pairs = match state.api.export_tag(&name).await {
+        Ok(Some(pairs)) => pairs,
+        Ok(None) => return tag_not_found(&name),
+        Err(e) => return (StatusCode::BAD_GATEWAY, format!("Error: {}", e)).into_response(),
+    };
+
+    let mut body = String::new();
+    for (key, value) in pairs {
+        if let Ok(line) = serde_json::to_string(&ExportedPair::new(&key, value)) {
+            body.push_str(&line);
+            body.push('\n');
+        }
+    }
+
+    let mut response = ([(header::CONTENT_TYPE, "application/x-ndjson")], body).into_response();
+    let headers = response.headers_mut();
+    headers.insert(TAG_RAFT_INDEX_HEADER, HeaderValue::from(tag.raft_index));
+    if let Some(root) = tag
+        .merkle_root
+        .and_then(|root| HeaderValue::from_str(&root).ok())
+    {
+        headers.insert(TAG_MERKLE_ROOT_HEADER, root);
+    }
+    response
+}
+
+/// Records read from the state machine per chunk of an export
+const EXPORT_CHUNK_RECORDS: usize = 1000;
+
+/// Most records an import commits in one Raft entry
+const IMPORT_BATCH_RECORDS: usize = 1000;
+
+/// Most bytes of keys and values an import commits in one Raft entry; a larger
+/// record is committed on its own
+const IMPORT_BATCH_BYTES: usize = 4 * 1024 * 1024;
+
+/// Stream every key of the ledger in the bulk format, ordered by key
+///
+/// The export starts once this node has caught up with the leader; the response
+/// header carries the consistency token of that point. Keys are then read in
+/// pages of `EXPORT_CHUNK_RECORDS`, each following the last key of the previous
+/// one, so only one page is held at a time. Demoted values are read from cold
+/// storage as the stream goes. If a value cannot be read the stream is aborted
+/// before its trailer, so the export is not mistaken for a complete one.
+async fn export_handler(State(state): State<AppState>) -> Response {
+    let token = match state.api.export_start().await {
+        Ok(token) => token,
+        Err(e) => return error_response(&state, e).await,
+    };
+    info!("Exporting keys from Raft index {}", token.applied_index());
+
+    let header = futures::stream::once(async {
+        Ok::<_, std::io::Error>(Bytes::from_static(&bulk::STREAM_HEADER[..]))
+    });
+    let records = futures::stream::unfold(
+        Some((Arc::clone(&state.api), None::<Vec<u8>>, 0u64)),
+        |export| async move {
+            let (api, after, mut records) = export?;
+            let page = api
+                .export_page(after.as_deref(), EXPORT_CHUNK_RECORDS)
+                .await;
+            let last_page = page.len() < EXPORT_CHUNK_RECORDS;
+            let after = page.last().map(|(key, _)| key.clone());
+            let mut chunk = Vec::new();
+            for (key, stored) in page {
+                let value = match api.export_value(&key, stored).await {
+                    Ok(Some(value)) => value,
+                    Ok(None) => {
+                        let message =
+                            format!("Archived value of key '{}' is not available", log_key(&key));
+                        error!("Export aborted: {}", message);
+                        return Some((Err(std::io::Error::other(message)), None));
+                    }
+                    Err(e) => {
+                        error!("Export aborted: {}", e);
+                        return Some((Err(std::io::Error::other(e.to_string())), None));
+                    }
+                };
+                bulk::write_record(&mut chunk, &key, &value);
+                records += 1;
+            }
+            if last_page {
+                bulk::write_trailer(&mut chunk, records);
+                return Some((Ok(Bytes::from(chunk)), None));
+            }
+            Some((Ok(Bytes::from(chunk)), Some((api, after, records))))
+        },
+    );
+
+    let mut response = (
+        [(header::CONTENT_TYPE, "application/octet-stream")],
+        Body::from_stream(header.chain(records)),
+    )
+        .into_response();
+    response.headers_mut().insert(
+        CONSISTENCY_TOKEN_HEADER,
+        HeaderValue::from(token.applied_index()),
+    );
+    response
+}
+
+/// Response of `POST /import`
+#[derive(Serialize)]
+struct ImportResponse {
+    /// Records committed
+    records: u64,
+}
+
+/// Import a stream in the bulk format on the leader
+///
+/// Records are committed in batches of up to [`IMPORT_BATCH_RECORDS`] records or
+/// [`IMPORT_BATCH_BYTES`] bytes, one Raft entry each, instead of one consensus
+/// round per key. The response is sent once the last batch is applied, with its
+/// consistency token, so a read carrying the token sees the whole import. An
+/// invalid or incomplete stream fails the import; batches committed before are kept
+/// and counted in the error.
+async fn import_handler(
+    State(state): State<AppState>,
+    identity: Option<Extension<ClientCertIdentity>>,
+    body: Body,
+) -> Response {
+    if !state.api.is_leader().await {
+        let leader_id = state.api.current_leader().await;
+        return error_response(&state, ScribeError::NotLeader { leader_id }).await;
+    }
+
+    let mut committed = 0;
+    let result = import_stream(&state.api, body, &mut committed).await;
+    if committed > 0 {
+        record_admin_event(
+            &state,
+            request_actor(identity.as_deref(), "api"),
+            AdminAction::BulkImport { records: committed },
+            result.as_ref().map(|_| ()).map_err(|e| e.to_string()),
+        );
+    }
+
+    match result {
+        Ok(token) => {
+            info!(
+                "Imported {} records up to Raft index {}",
+                committed,
+                token.map_or(0, |token| token.applied_index())
+            );
+            let mut response = axum::Json(ImportResponse { records: committed }).into_response();
+            if let Some(token) = token {
+                response.headers_mut().insert(
+                    CONSISTENCY_TOKEN_HEADER,
+                    HeaderValue::from(token.applied_index()),
+                );
+            }
+            response
+        }
+        Err(ScribeError::NotLeader { leader_id }) if committed == 0 => {
+            error_response(&state, ScribeError::NotLeader { leader_id }).await
+        }
+        Err(e) => {
+            let status = match e {
+                ScribeError::Serialization(_) | ScribeError::Network(_) => StatusCode::BAD_REQUEST,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            warn!("Import failed after {} records: {}", committed, e);
+            (
+                status,
+                format!(
+                    "Error: {} ({} records were committed before the failure)",
+                    e, committed
+                ),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Decode `body` and commit its records in batches, counting them in `committed`
+///
+/// Returns the token of the last batch, `None` for a stream without records.
+async fn import_stream(
+    api: &DistributedApi,
+    body: Body,
+    committed: &mut u64,
+) -> std::result::Result<Option<ConsistencyToken>, ScribeError> {
+    let mut chunks = body.into_data_stream();
+    let mut decoder = StreamDecoder::new();
+    let mut batch = Vec::new();
+    let mut batch_bytes = 0;
+    let mut token = None;
+
+    while let Some(chunk) = chunks.next().await {
+        let chunk =
+            chunk.map_err(|e| ScribeError::Network(format!("Reading the stream failed: {}", e)))?;
+        for (key, value) in decoder.feed(&chunk)? {
+            batch_bytes += key.len() + value.len();
+            batch.push((key, value));
+            if batch.len() >= IMPORT_BATCH_RECORDS || batch_bytes >= IMPORT_BATCH_BYTES {
+                let records = std::mem::take(&mut batch);
+                batch_bytes = 0;
+                let count = records.len() as u64;
+                token = Some(api.import_batch(records).await?);
+                *committed += count;
+            }
+        }
+    }
+
+    // Records after the last full batch are only committed if the stream is complete
+    decoder.finish()?;
+    if !batch.is_empty() {
+        let count = batch.len() as u64;
+        token = Some(api.import_batch(batch).await?);
+        *committed += count;
+    }
+    Ok(token)
+}
+
+fn tag_not_found(name: &str) -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        format!("Tag '{}' does not exist", name),
+    )
+        .into_response()
+}
+
+/// Status of this node
+async fn node_status_handler(State(state): State<AppState>) -> Response {
+    let status =
+        NodeStatus::collect(&state.consensus, &state.api, None, state.config_hash.clone()).await;
+    axum::Json(status).into_response()
+}
+
+/// Membership, Raft progress, storage, cache and config hash of every member
+async fn cluster_overview_handler(State(state): State<AppState>) -> Response {
+    axum::Json(overview(&state).await).into_response()
+}
+
+/// Status of every member, as seen from this node
+async fn overview(state: &AppState) -> ClusterOverview {
+    let local =
+        NodeStatus::collect(&state.consensus, &state.api, None, state.config_hash.clone()).await;
+    cluster_overview(
+        &state.consensus,
+        &state.discovery,
+        &state.http_client,
+        state.scheme,
+        local,
+    )
+    .await
+}
+
+/// Leader recommended from the round trips and client load members report
+async fn placement_handler(State(state): State<AppState>) -> Response {
+    axum::Json(placement_report(&overview(&state).await)).into_response()
+}
+
+/// Clock offsets of the peers this node hears heartbeats from
+async fn clock_skew_handler(State(state): State<AppState>) -> Response {
+    axum::Json(state.clock_skew.report(state.node_id)).into_response()
+}
+
+/// Query of `POST /admin/placement/transfer`
+#[derive(Deserialize)]
+struct PlacementTransferQuery {
+    /// Estimated median improvement required to move leadership, in milliseconds
+    min_improvement_ms: Option<u64>,
+}
+
+/// Recommendation acted upon by `POST /admin/placement/transfer`
+#[derive(Serialize)]
+struct PlacementTransfer {
+    /// Recommendation the decision was based on
+    report: PlacementReport,
+    /// Leader after the transfer, `None` if leadership stayed
+    transferred_to: Option<NodeId>,
+}
+
+/// Hand leadership to the recommended voter if that saves enough latency (leader only)
+async fn placement_transfer_handler(
+    State(state): State<AppState>,
+    identity: Option<Extension<ClientCertIdentity>>,
+    Query(query): Query<PlacementTransferQuery>,
+) -> Response {
+    let min_improvement = query
+        .min_improvement_ms
+        .map_or(state.placement.min_improvement(), Duration::from_millis);
+    let actor = request_actor(identity.as_deref(), "api");
+    match rebalance_leader(&state, min_improvement, &actor).await {
+        Ok(transfer) => axum::Json(transfer).into_response(),
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// Move leadership to the recommended voter if that cuts the estimated median
+/// client latency by at least `min_improvement` (leader only)
+async fn rebalance_leader(
+    state: &AppState,
+    min_improvement: Duration,
+    actor: &str,
+) -> Result<PlacementTransfer, ScribeError> {
+    if !state.consensus.is_leader().await {
+        return Err(ScribeError::NotLeader {
+            leader_id: state.consensus.current_leader().await,
+        });
+    }
+    let report = placement_report(&overview(state).await);
+    let Some(target) = report.transfer_target(min_improvement) else {
+        return Ok(PlacementTransfer {
+            report,
+            transferred_to: None,
+        });
+    };
+
+    info!(
+        "Moving leadership to node {}, saving an estimated {}us of median latency",
+        target,
+        report.improvement_us.unwrap_or_default()
+    );
+    let result = state
+        .consensus
+        .transfer_leadership(
+            target,
+            state.leader_transfer_timeout,
+            request_campaign(state, target),
+        )
+        .await;
+    record_admin_event(
+        state,
+        actor.to_string(),
+        AdminAction::LeaderTransfer {
+            target,
+            new_leader: result.as_ref().ok().copied(),
+        },
+        result.as_ref().map(|_| ()).map_err(|e| e.to_string()),
+    );
+    Ok(PlacementTransfer {
+        report,
+        transferred_to: Some(result?),
+    })
+}
+
+/// Ask `target` to start elections until it takes over leadership from this node
+async fn request_campaign(state: &AppState, target: NodeId) -> Result<(), ScribeError> {
+    let peer = state.discovery.get_peer(target).ok_or_else(|| {
+        ScribeError::Discovery(format!("Client address of node {} is unknown", target))
+    })?;
+    let mut addr = peer.client_addr;
+    if let Some(port) = state.admin_port {
+        addr.set_port(port);
+    }
+    let url = format!(
+        "{}://{}/admin/placement/campaign?previous={}",
+        state.scheme, addr, state.node_id
+    );
+    let response = trace_context::inject(state.http_client.post(&url))
+        .timeout(CAMPAIGN_REQUEST_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| ScribeError::Network(format!("Failed to reach node {}: {}", target, e)))?;
+    if !response.status().is_success() {
+        return Err(ScribeError::Cluster(format!(
+            "Node {} refused to take over leadership: {}",
+            target,
+            response.status()
+        )));
+    }
+    Ok(())
+}
+
+/// Load the values of the keys saved before the restart into the hot data cache,
+/// retrying until this node can read through the leader
+async fn warm_cache(api: &DistributedApi, warm_keys: &WarmKeys, limit: usize) {
+    let keys = match warm_keys.load(limit) {
+        Ok(keys) if !keys.is_empty() => keys,
+        Ok(_) => return,
+        Err(e) => {
+            warn!("Loading the saved cache keys failed: {}", e);
+            return;
+        }
+    };
+    let deadline = Instant::now() + CACHE_WARM_TIMEOUT;
+    loop {
+        match api.warm_cache(&keys).await {
+            Ok(warmed) => {
+                info!(
+                    "Warmed the cache with {} of {} saved keys",
+                    warmed,
+                    keys.len()
+                );
+                return;
+            }
+            Err(e) if Instant::now() >= deadline => {
+                warn!("Warming the cache failed: {}", e);
+                return;
+            }
+            Err(_) => tokio::time::sleep(CACHE_WARM_RETRY_INTERVAL).await,
+        }
+    }
+}
+
+/// Save the most recently used cache keys for warming after a restart
+///
+/// An empty cache is not saved, so a node stopped before warming finished keeps
+/// the keys saved earlier.
+fn save_warm_keys(api: &DistributedApi, warm_keys: &WarmKeys, limit: usize) {
+    let keys = api.recent_cache_keys(limit);
+    if keys.is_empty() {
+        return;
+    }
+    if let Err(e) = warm_keys.save(&keys) {
+        warn!("Saving the recently used cache keys failed: {}", e);
+    }
+}
+
+/// Replicate quota usage counted by this node: through Raft on the leader,
+/// otherwise by handing it to the leader
+async fn flush_usage(state: &AppState, usage: Vec<UsageDelta>) -> Result<(), ScribeError> {
+    if state.consensus.is_leader().await {
+        return state.api.record_usage(usage).await;
+    }
+    let leader = state
+        .consensus
+        .current_leader()
+        .await
+        .ok_or_else(|| ScribeError::NotLeader { leader_id: None })?;
+    let peer = state.discovery.get_peer(leader).ok_or_else(|| {
+        ScribeError::Discovery(format!("Client address of node {} is unknown", leader))
+    })?;
+    let mut addr = peer.client_addr;
+    if let Some(port) = state.admin_port {
+        addr.set_port(port);
+    }
+    let url = format!("{}://{}/admin/quotas/usage", state.scheme, addr);
+    let response = trace_context::inject(state.http_client.post(&url))
+        .json(&usage)
+        .timeout(QUOTA_FLUSH_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| ScribeError::Network(format!("Failed to reach node {}: {}", leader, e)))?;
+    if !response.status().is_success() {
+        return Err(ScribeError::Cluster(format!(
+            "Leader {} refused quota usage: {}",
+            leader,
+            response.status()
+        )));
+    }
+    Ok(())
+}
+
+/// Query of `POST /admin/placement/campaign`
+#[derive(Deserialize)]
+struct CampaignQuery {
+    /// Leader handing over leadership
+    previous: Option<NodeId>,
+}
+
+/// Start elections on this node until it leads, as asked by a leader handing
+/// leadership over; 202 once the elections started
+async fn campaign_handler(
+    State(state): State<AppState>,
+    Query(query): Query<CampaignQuery>,
+) -> Response {
+    let consensus = state.consensus.clone();
+    let timeout = state.leader_transfer_timeout;
+    tokio::spawn(async move {
+        if let Err(e) = consensus
+            .campaign(query.previous, CAMPAIGN_RETRY_INTERVAL, timeout)
+            .await
+        {
+            warn!("Taking over leadership failed: {}", e);
+        }
+    });
+    StatusCode::ACCEPTED.into_response()
+}
+
+/// Query of `GET /cluster/learners`
+#[derive(Deserialize)]
+struct LearnerProgressQuery {
+    /// Entries a learner may trail the leader by and count as caught up
+    max_lag: Option<u64>,
+}
+
+/// Catch-up progress of every learner (leader only)
+async fn learner_progress_handler(
+    State(state): State<AppState>,
+    Query(query): Query<LearnerProgressQuery>,
+) -> Response {
+    let max_lag = query.max_lag.unwrap_or(DEFAULT_CAUGHT_UP_LAG);
+    match state.consensus.learner_progress(max_lag).await {
+        Ok(report) => axum::Json(report).into_response(),
+        Err(e) => error_response(&state, e).await,
+    }
+}
+
+/// Shadow write divergence counters
+async fn shadow_stats_handler(State(state): State<AppState>) -> Response {
+    match state.api.shadow_stats() {
+        Some(stats) => axum::Json(stats).into_response(),
+        None => (StatusCode::NOT_FOUND, "Shadow mode is not enabled".to_string()).into_response(),
+    }
+}
+
+/// Canary read divergence counters
+async fn canary_stats_handler(State(state): State<AppState>) -> Response {
+    match &state.canary {
+        Some(canary) => axum::Json(canary.stats()).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            "Canary mirroring is not enabled".to_string(),
+        )
+            .into_response(),
+    }
+}
+
+async fn metrics_handler(State(state): State<AppState>) -> impl IntoResponse {
+    let metrics = state.api.metrics().await;
+    axum::Json(metrics)
+}
+
+/// Query of `GET /raft/metrics/history`
+#[derive(Deserialize)]
+struct RaftHistoryQuery {
+    /// Milliseconds since the epoch, or a duration ago such as `15m`; all retained
+    /// samples if absent
+    since: Option<String>,
+    /// Maximum number of samples to return
+    limit: Option<usize>,
+}
+
+/// Raft metrics samples recorded on this node, oldest first
+async fn raft_history_handler(
+    State(state): State<AppState>,
+    Query(query): Query<RaftHistoryQuery>,
+) -> Response {
+    let Some(history) = &state.raft_history else {
+        return (
+            StatusCode::NOT_FOUND,
+            "Raft metrics history is disabled".to_string(),
+        )
+            .into_response();
+    };
+
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    let since_ms = match query.since.as_deref() {
+        Some(since) => match parse_since(since, now_ms) {
+            Ok(since_ms) => since_ms,
+            Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+        },
+        None => 0,
+    };
+    match history.since(since_ms, query.limit.unwrap_or(MAX_SAMPLE_LIMIT)) {
+        Ok(samples) => axum::Json(samples).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Error: {}", e)).into_response(),
+    }
+}
+
+/// Prometheus metrics in the text exposition format
+async fn prometheus_metrics_handler() -> Response {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics::get_metrics(),
+    )
+        .into_response()
+}
+
+/// Admit requests by priority (`X-Priority: high|normal|low`)
+///
+/// Requests without the header are normal priority, except control-plane endpoints
+/// which default to high. Health checks bypass admission.
+async fn admission_middleware(
+    State(admission): State<AdmissionController>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path();
+    if path == "/health" {
+        return next.run(request).await;
+    }
+
+    let priority = match request.headers().get(PRIORITY_HEADER) {
+        Some(value) => match value.to_str().map(str::parse::<Priority>) {
+            Ok(Ok(priority)) => priority,
+            _ => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    "Invalid X-Priority header (expected high, normal or low)".to_string(),
+                )
+                    .into_response()
+            }
+        },
+        None if path.starts_with("/cluster/")
+            || path.starts_with("/admin/")
+            || path.starts_with("/debug/")
+            || path == "/metrics"
+            || path == "/metrics/prometheus" =>
+        {
+            Priority::High
+        }
+        None => Priority::Normal,
+    };
+
+    let _permit = admission.acquire(priority).await;
+    next.run(request).await
+}
+
+/// Attach load hints and the serving node's ID to every response
+///
+/// Smart clients use the hints to send stale reads to less loaded followers; the
+/// node ID shows which replica served a request.
+async fn load_hints_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(request).await;
+    let role = if state.consensus.leadership().is_leader {
+        "leader"
+    } else {
+        "follower"
+    };
+    let headers = response.headers_mut();
+    headers.insert(SERVED_BY_HEADER, HeaderValue::from(state.node_id));
+    headers.insert(
+        NODE_LOAD_HEADER,
+        HeaderValue::from(u16::from(state.admission.load_percent())),
+    );
+    headers.insert(
+        NODE_QUEUE_DEPTH_HEADER,
+        HeaderValue::from(queue_depth_bucket(state.admission.queue_depth())),
+    );
+    headers.insert(NODE_ROLE_HEADER, HeaderValue::from_static(role));
+    response
+}
+
+/// Kind of key access a data route performs, for access tracing
+///
+/// A rename is traced as a write of its source key.
+fn access_op(method: &Method, route: &str) -> Option<AccessOp> {
+    let route = route
+        .strip_prefix("/v1/kv")
+        .or_else(|| route.strip_prefix("/v1"))
+        .unwrap_or(route);
+    match (route, method.as_str()) {
+        ("/:key", "GET" | "HEAD") => Some(AccessOp::Read),
+        ("/:key", "PUT") | ("/:key/swap" | "/:key/rename", "POST") | ("/doc/:key", "PATCH") => {
+            Some(AccessOp::Write)
+        }
+        ("/:key", "DELETE") => Some(AccessOp::Delete),
+        _ => None,
+    }
+}
+
+/// Record admitted reads and writes of keys in traced namespaces, with the caller's
+/// identity and the response status
+async fn access_trace_middleware(
+    State(state): State<AppState>,
+    matched_path: Option<MatchedPath>,
+    params: Option<RawPathParams>,
+    identity: Option<Extension<ClientCertIdentity>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(trace) = &state.access_trace else {
+        return next.run(request).await;
+    };
+    let op = matched_path
+        .as_ref()
+        .and_then(|path| access_op(request.method(), path.as_str()));
+    let key = params.as_ref().and_then(|params| {
+        params
+            .iter()
+            .find(|(name, _)| *name == "key")
+            .map(|(_, key)| key.to_string())
+    });
+    let (Some(op), Some(key)) = (op, key) else {
+        return next.run(request).await;
+    };
+
+    let response = next.run(request).await;
+    let principal = request_actor(identity.as_deref(), "anonymous");
+    if let Err(e) = trace.record(&principal, op, key.as_bytes(), response.status().as_u16()) {
+        error!("Failed to record key access: {}", e);
+    }
+    response
+}
+
+/// Record sampled requests in the access log, with the caller's identity and the
+/// trace ID of the request
+async fn access_log_middleware(
+    State(state): State<AppState>,
+    matched_path: Option<MatchedPath>,
+    identity: Option<Extension<ClientCertIdentity>>,
+    connect_info: Option<ConnectInfo<std::net::SocketAddr>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(access_log) = &state.access_log else {
+        return next.run(request).await;
+    };
+    let started = Instant::now();
+    let method = request.method().to_string();
+    let path = log_path(request.uri().path());
+    let request_bytes = content_length(request.headers());
+
+    let response = next.run(request).await;
+    let status = response.status().as_u16();
+    if !access_log.sample(status) {
+        return response;
+    }
+    access_log.record(&AccessRecord {
+        timestamp_ms: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+        method,
+        route: matched_path.map_or_else(
+            || "unmatched".to_string(),
+            |route| route.as_str().to_string(),
+        ),
+        path,
+        status,
+        latency_us: started.elapsed().as_micros() as u64,
+        request_bytes,
+        response_bytes: content_length(response.headers())
+            .or_else(|| response.body().size_hint().exact()),
+        principal: request_actor(identity.as_deref(), "anonymous"),
+        client_addr: connect_info.map(|ConnectInfo(addr)| addr.to_string()),
+        correlation_id: TraceContext::current().map(|context| context.trace_id()),
+    });
+    response
+}
+
+/// Body size announced in the `Content-Length` header
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(header::CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+/// Negotiate the API version from the `x-api-version` header and report it on the
+/// response; 406 if the client accepts no supported version
+async fn api_version_middleware(request: Request, next: Next) -> Response {
+    let requested = request
+        .headers()
+        .get(API_VERSION_HEADER)
+        .and_then(|value| value.to_str().ok());
+    let version = match negotiate_api_version(requested) {
+        Ok(version) => version,
+        Err(e) => return (StatusCode::NOT_ACCEPTABLE, e.to_string()).into_response(),
+    };
+
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .insert(API_VERSION_HEADER, HeaderValue::from(version));
+    response
+}
+
+/// Point a leader redirect at the path and query of the original request on the
+/// leader
+///
+/// The request URI is reused as received, still percent-encoded, so keys with
+/// reserved or non-ASCII characters, the `/v1` prefix and query parameters such as
+/// `ttl` or `return` reach the leader unchanged. Admin paths are redirected to the
+/// leader's admin port when admin endpoints are served on a separate listener.
+async fn leader_redirect_middleware(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(request).await;
+    if response.status() != StatusCode::TEMPORARY_REDIRECT {
+        return response;
+    }
+    let leader = response
+        .headers()
+        .get(RAFT_LEADER_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<std::net::SocketAddr>().ok());
+    let Some(mut leader) = leader else {
+        return response;
+    };
+    if let Some(port) = state
+        .admin_port
+        .filter(|_| is_admin_path(uri.path().trim_start_matches('/')))
+    {
+        leader.set_port(port);
+    }
+
+    let path = uri.path_and_query().map_or("/", |path| path.as_str());
+    let location = format!("{}://{}{}", state.scheme, leader, path);
+    let headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&location) {
+        headers.insert(header::LOCATION, value);
+    }
+    if let Ok(value) = HeaderValue::from_str(&leader.to_string()) {
+        headers.insert(RAFT_LEADER_HEADER, value);
+    }
+    response
+}
+
+/// Mark responses of unversioned data routes as deprecated, linking the `/v1`
+/// route that replaces them
+async fn deprecated_route_middleware(
+    matched_path: Option<MatchedPath>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path();
+    let successor = match matched_path.as_ref().map(|route| route.as_str()) {
+        Some(route) if route.starts_with("/:key") => format!("/v1/kv{}", path),
+        _ => format!("/v1{}", path),
+    };
+
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert("deprecation", HeaderValue::from_static("true"));
+    let link = format!("<{}>; rel=\"successor-version\"", successor);
+    if let Ok(value) = HeaderValue::from_str(&link) {
+        headers.insert(header::LINK, value);
+    }
+    response
+}
+
+/// Handle the request inside a span continuing the caller's W3C trace, so logs and
+/// outgoing calls (leader redirects, S3) are parented to it
+async fn trace_context_middleware(request: Request, next: Next) -> Response {
+    // Owned copies, so no borrow of the request is held across the await
+    let (traceparent, tracestate) = {
+        let header = |name: &str| {
+            request
+                .headers()
+                .get(name)
+                .and_then(|value: &HeaderValue| value.to_str().ok())
+                .map(str::to_string)
+        };
+        (header(TRACEPARENT_HEADER), header(TRACESTATE_HEADER))
+    };
+    let context = TraceContext::for_request(traceparent.as_deref(), tracestate.as_deref());
+    context.scope(next.run(request)).await
+}
+
+/// Whether `path` (without the leading slash) is served by the admin router
+fn is_admin_path(path: &str) -> bool {
+    path.starts_with("admin/")
+        || path.starts_with("debug/")
+        || path == "cluster/tokens"
+        || path == "cluster/join"
+        || path == "export"
+        || path == "import"
+        || path.starts_with("cluster/nodes/")
+}
+
+/// Admin and membership-changing endpoints
+fn admin_routes() -> Router<AppState> {
+    Router::new()
+        .route("/cluster/tokens", post(create_token_handler))
+        .route("/cluster/join", post(join_handler))
+        .route("/cluster/nodes/add", post(add_node_handler))
+        .route("/cluster/nodes/remove", post(remove_node_handler))
+        .route("/cluster/nodes/changes", get(membership_changes_handler))
+        .route("/cluster/nodes/changes/:id", get(membership_change_handler))
+        .route("/admin/compact", post(compact_handler))
+        .route("/admin/events", get(admin_events_handler))
+        .route("/admin/errors", get(errors_handler))
+        .route("/admin/health", get(node_health_handler))
+        .route("/admin/access-trace", get(access_trace_handler))
+        .route("/admin/hotkeys", get(hot_keys_handler))
+        .route("/admin/contention", get(contention_handler))
+        .route("/admin/quotas", get(quotas_handler))
+        .route("/admin/quotas/usage", post(record_usage_handler))
+        .route("/admin/quotas/:key_id", get(quota_handler))
+        .route("/admin/migrations", get(list_migrations_handler))
+        .route(
+            "/admin/migrations/:id",
+            get(migration_handler).post(start_migration_handler),
+        )
+        .route("/admin/migrations/:id/pause", post(pause_migration_handler))
+        .route(
+            "/admin/placement/transfer",
+            post(placement_transfer_handler),
+        )
+        .route("/admin/placement/campaign", post(campaign_handler))
+        .route(
+            "/admin/epochs/:namespace",
+            get(epoch_handler).post(advance_epoch_handler),
+        )
+        .route("/debug/runtime", get(runtime_debug_handler))
+        .route("/admin/demote/:key", post(demote_handler))
+        .route("/admin/verify-segment/:id", post(verify_segment_handler))
+        .route("/admin/tags", get(list_tags_handler))
+        .route(
+            "/admin/tags/:name",
+            post(create_tag_handler)
+                .get(tag_info_handler)
+                .delete(delete_tag_handler),
+        )
+        .route("/admin/tags/:name/verify", post(verify_tag_handler))
+        .route("/admin/tags/:name/export", get(export_tag_handler))
+        .route("/export", get(export_handler))
+        .route("/import", post(import_handler))
+}
+
+/// Data-plane endpoints, including read-only cluster status
+///
+/// Key-value and coordination endpoints are served under `/v1`, keys under
+/// `/v1/kv/:key`; their unversioned paths remain as deprecated aliases. Health,
+/// metrics and status endpoints are not versioned.
+fn data_routes() -> Router<AppState> {
+    let v1 = coordination_routes()
+        .route("/kv/:key/rename", post(rename_handler))
+        .route("/kv/:key/swap", post(swap_handler))
+        .route(
+            "/kv/:key",
+            put(put_handler).get(get_handler).delete(delete_handler),
+        );
+    let deprecated = coordination_routes()
+        .route("/:key/rename", post(rename_handler))
+        .route("/:key/swap", post(swap_handler))
+        .route("/:key", put(put_handler))
+        .route("/:key", get(get_handler))
+        .route("/:key", delete(delete_handler))
+        .layer(axum::middleware::from_fn(deprecated_route_middleware));
+
+    Router::new()
+        .route("/health", get(health_handler))
+        .route("/metrics", get(metrics_handler))
+        .route("/metrics/prometheus", get(prometheus_metrics_handler))
+        .route("/raft/metrics/history", get(raft_history_handler))
+        .route("/shadow/stats", get(shadow_stats_handler))
+        .route("/canary/stats", get(canary_stats_handler))
+        .route("/storage/stats", get(storage_stats_handler))
+        .route("/cluster/node", get(node_status_handler))
+        .route("/cluster/overview", get(cluster_overview_handler))
+        .route("/cluster/learners", get(learner_progress_handler))
+        .route("/cluster/placement", get(placement_handler))
+        .route("/cluster/clock", get(clock_skew_handler))
+        .route("/verify/:key", get(verify_key_handler))
+        .route("/cluster/leader/events", get(leadership_events_handler))
+        .nest("/v1", v1)
+        .merge(deprecated)
+}
+
+/// Versioned data-plane endpoints other than single keys, served both under `/v1`
+/// and at their deprecated unversioned paths
+fn coordination_routes() -> Router<AppState> {
+    Router::new()
+        .route("/keys", get(keys_handler))
+        .route("/events", get(events_handler))
+        .route("/sync/buckets", get(sync_buckets_handler))
+        .route("/sync/buckets/:bucket", get(sync_bucket_keys_handler))
+        .route("/locks/:name", get(lock_info_handler))
+        .route("/locks/:name/acquire", post(lock_acquire_handler))
+        .route("/locks/:name/renew", post(lock_renew_handler))
+        .route("/locks/:name/release", post(lock_release_handler))
+        .route("/sessions", post(create_session_handler))
+        .route(
+            "/sessions/:id",
+            get(get_session_handler).delete(destroy_session_handler),
+        )
+        .route("/sessions/:id/touch", post(touch_session_handler))
+        .route("/queues/:name", get(queue_stats_handler))
+        .route("/queues/:name", post(enqueue_handler))
+        .route("/queues/:name/dequeue", post(dequeue_handler))
+        .route("/queues/:name/ack", post(ack_handler))
+        .route("/doc/:key", patch(patch_doc_handler))
+        .route("/batch", post(batch_handler))
+}
+
+/// Network policies of the data and admin endpoints, if configured
+struct ListenerPolicies {
+    data: Option<NetworkPolicyLayer>,
+    admin: Option<NetworkPolicyLayer>,
+}
+
+/// Enforce `policy` on the endpoints of `listener`, auditing rejected requests
+fn policy_layer(
+    policy: &NetworkPolicyConfig,
+    listener: &'static str,
+    admin_events: &AdminEventLog,
+) -> Result<Option<NetworkPolicyLayer>> {
+    if policy.is_empty() {
+        return Ok(None);
+    }
+    let policy = NetworkPolicy::from_config(policy).map_err(|e| anyhow::anyhow!(e))?;
+    Ok(Some(
+        NetworkPolicyLayer::new(policy, listener).with_audit_log(admin_events.clone()),
+    ))
+}
+
+/// Wrap `app` in `layer`, if any
+fn with_policy(app: Router, layer: Option<NetworkPolicyLayer>) -> Router {
+    match layer {
+        Some(layer) => app.layer(layer),
+        None => app,
+    }
+}
+
+/// Serve the client API on `addr`
+///
+/// With `admin_addr` set, admin and membership endpoints are served only on that
+/// listener; otherwise they share the client listener. Both listeners use the same
+/// middleware and TLS settings. Network policies apply to the endpoints rather than
+/// the port, so admin endpoints on the client port keep the admin policy. Each policy
+/// wraps all other middleware, so a rejected request is neither admitted, logged,
+/// traced nor redirected to the leader.
+async fn start_http_server(
+    addr: &str,
+    admin_addr: Option<String>,
+    state: AppState,
+    tls: Option<TlsServerConfig>,
+    policies: ListenerPolicies,
+) -> Result<()> {
+    let tls = tls.map(Arc::new);
+    let data_routes = data_routes().route_layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        quota_middleware,
+    ));
+    let data_app = with_policy(
+        with_shared_layers(data_routes, state.clone(), tls.clone()),
+        policies.data,
+    );
+    let admin_app = with_policy(
+        with_shared_layers(admin_routes(), state, tls.clone()),
+        policies.admin,
+    );
+    match admin_addr {
+        Some(admin_addr) => {
+            tokio::try_join!(
+                serve_router(addr, data_app, tls.clone()),
+                serve_router(&admin_addr, admin_app, tls),
+            )?;
+        }
+        None => {
+            // Merged last, the data endpoints' fallback answers unknown paths
+            let app = admin_app.merge(data_app);
+            serve_router(addr, app, tls).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Apply the middleware shared by all endpoints to `routes`
+fn with_shared_layers(
+    routes: Router<AppState>,
+    state: AppState,
+    tls: Option<Arc<TlsServerConfig>>,
+) -> Router {
+    let admission = state.admission.clone();
+    let cors = cors_layer(state.permissive_cors);
+    let app = routes
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            access_trace_middleware,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            admission,
+            admission_middleware,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            load_hints_middleware,
+        ))
+        .layer(axum::middleware::from_fn(api_version_middleware))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            leader_redirect_middleware,
+        ))
+        .layer(HttpMetricsLayer)
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            access_log_middleware,
+        ))
+        .layer(axum::middleware::from_fn(trace_context_middleware))
+        .with_state(state);
+
+    match tls {
+        // Client-cert roles are only known on TLS connections
+        Some(tls) => app
+            .layer(axum::middleware::from_fn_with_state(tls, client_cert_auth))
+            .layer(cors),
+        None => app.layer(cors),
+    }
+}
+
+/// Serve `app` on `addr`
+async fn serve_router(addr: &str, app: Router, tls: Option<Arc<TlsServerConfig>>) -> Result<()> {
+    match tls {
+        Some(tls) => {
+            // TLS termination with HTTP/2 (ALPN)
+            let acceptor = tls.acceptor().map_err(|e| anyhow::anyhow!(e))?;
+            let addr: std::net::SocketAddr = addr.parse()?;
+            info!("HTTPS server listening on {} (HTTP/2 enabled)", addr);
+
+            axum_server::bind(addr)
+                .acceptor(acceptor)
+                .serve(app.into_make_service_with_connect_info::<std::net::SocketAddr>())
+                .await?;
+        }
+        None => {
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            info!("HTTP server listening on {}", addr);
+
+            // Client addresses are needed by the network policies
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+/// CORS policy of the client API: any origin, or same-origin requests only
+fn cors_layer(permissive: bool) -> CorsLayer {
+    if permissive {
+        CorsLayer::permissive()
+    } else {
+        CorsLayer::new()
+    }
+}
+
+/// Create S3 storage config from the TOML config
+fn s3_storage_config(s3_config: &S3Config) -> S3StorageConfig {
+    S3StorageConfig {
+        bucket: s3_config.bucket.clone(),
+        region: s3_config.region.clone(),
+        endpoint: s3_config.endpoint.clone(),
+        access_key_id: s3_config.access_key_id.clone(),
+        secret_access_key: s3_config.secret_access_key.clone(),
+        path_style: s3_config.path_style,
+        timeout_secs: s3_config.timeout_secs,
+        max_retries: s3_config.max_retries,
+        credential_source: s3_config.credentials.clone(),
+        credential_refresh_secs: s3_config.credential_refresh_secs,
+    }
+}
+
+/// Open the node's sled database under the data directory
+fn open_db(config: &Config) -> Result<sled::Db> {
+    let db_path = config.node.data_dir.join("db");
+    let db = sled::Config::new()
+        .path(&db_path)
+        .flush_every_ms(Some(config.storage.flush_every_ms).filter(|ms| *ms > 0))
+        .open()?;
+    info!("Storage initialized at {:?}", db_path);
+    Ok(db)
+}
+
+/// Open the snapshot store in `<data_dir>/snapshots` if snapshots are persisted
+fn open_snapshot_store(config: &Config) -> Result<Option<Arc<SnapshotStore>>> {
+    let snapshots = &config.storage.snapshots;
+    if !snapshots.persist {
+        return Ok(None);
+    }
+    let path = config.node.data_dir.join("snapshots");
+    let store = SnapshotStore::open(&path, &snapshots.layout).map_err(|e| {
+        anyhow::anyhow!(
+            "Failed to open snapshots at {:?}: {} (run `scribe-node migrate` after changing the layout)",
+            path,
+            e
+        )
+    })?;
+    info!(
+        "Snapshots persisted at {:?} with namespaces {:?}",
+        path, snapshots.layout.namespaces
+    );
+    Ok(Some(Arc::new(store)))
+}
+
+/// Open S3 archival if configured
+///
+/// A bucket that cannot be reached is logged and the node runs without archival.
+async fn open_archival(config: &Config, db: &sled::Db) -> Result<Option<Arc<ArchivalManager>>> {
+    let mut archival = None;
+    if let Some(s3_config) = &config.storage.s3 {
+        info!("S3 storage configuration detected");
+        info!("  Bucket: {}", s3_config.bucket);
+        info!("  Region: {}", s3_config.region);
+        if let Some(endpoint) = &s3_config.endpoint {
+            info!("  Endpoint: {}", endpoint);
+        }
+        info!("  Path style: {}", s3_config.path_style);
+        info!("  Pool size: {}", s3_config.pool_size);
+        info!("  Timeout: {}s", s3_config.timeout_secs);
+        info!("  Max retries: {}", s3_config.max_retries);
+        if let Some(source) = &s3_config.credentials {
+            info!(
+                "  Credentials: {} source, refreshed every {}s",
+                source.kind(),
+                s3_config.credential_refresh_secs
+            );
+        }
+
+        let s3_storage_config = s3_storage_config(s3_config);
+
+        // Storage classes fill their own segments, drawing IDs from the same generator
+        let ids = config.storage.segment_ids.generator(config.node.id)?;
+        let open_segments = |dir: PathBuf| -> Result<Arc<SegmentManager>> {
+            let mut segment_manager =
+                SegmentManager::with_limits(config.storage.segment_size, config.segment_max_age())
+                    .with_id_generator(Arc::clone(&ids));
+            if config.storage.mmap_segments {
+                segment_manager = segment_manager.with_segment_dir(dir)?;
+                info!(
+                    "✓ Flushed segments mapped from {:?}",
+                    segment_manager.segment_dir()
+                );
+            }
+            Ok(Arc::new(segment_manager))
+        };
+        let segments_dir = config.node.data_dir.join("segments");
+        let segment_manager = open_segments(segments_dir.clone())?;
+
+        // Try to initialize S3 storage (this will validate configuration)
+        match ArchivalManager::new(s3_storage_config, segment_manager, TieringPolicy::default())
+            .await
+        {
+            Ok(manager) => {
+                info!("✓ S3 storage initialized successfully");
+                let mut manager = manager.with_manifest(Arc::new(ManifestManager::new()));
+                for (name, class) in config.storage.classes.iter() {
+                    let segments = open_segments(segments_dir.join(name))?;
+                    manager = manager.with_storage_class(name, class.clone(), segments);
+                    info!(
+                        "✓ Storage class '{}' routes {}",
+                        name,
+                        class.prefixes.join(", ")
+                    );
+                }
+                if config.security.encryption.enabled {
+                    let keyring = TenantKeyring::from_config(
+                        &config.security.encryption,
+                        db.open_tree("tenant_keys")?,
+                    )?;
+                    manager = manager.with_keyring(Arc::new(keyring));
+                    info!("✓ Per-tenant segment encryption enabled");
+                }
+                match manager.recover_interrupted_rolls().await {
+                    Ok(recovery) if !recovery.is_empty() => info!(
+                        "✓ Recovered interrupted segment rolls: {:?} completed, {:?} rolled back",
+                        recovery.completed, recovery.rolled_back
+                    ),
+                    Ok(_) => {}
+                    Err(e) => warn!("Failed to recover interrupted segment rolls: {}", e),
+                }
+                check_segment_consistency(&manager, config.storage.startup_check).await;
+                archival = Some(Arc::new(manager));
+            }
+            Err(e) => {
+                warn!("Failed to initialize S3 storage: {}", e);
+                warn!("Node will continue without S3 archival support");
+            }
+        }
+    } else {
+        info!("S3 storage not configured (running with local storage only)");
+    }
+    Ok(archival)
+}
+
+/// Cross-check the manifest against the local segments and log what disagrees
+///
+/// A failed check is logged and does not stop the node.
+async fn check_segment_consistency(archival: &ArchivalManager, mode: StartupCheckMode) {
+    if mode == StartupCheckMode::Off {
+        return;
+    }
+    let report = match archival
+        .check_consistency(mode == StartupCheckMode::Repair)
+        .await
+    {
+        Ok(report) => report,
+        Err(e) => {
+            warn!("Segment consistency check failed: {}", e);
+            return;
+        }
+    };
+
+    info!(
+        "Segment consistency check: {} local segment(s), {} in manifest, {} pending archival",
+        report.local_segments,
+        report.manifest_segments,
+        report.pending_archival.len()
+    );
+    if !report.missing_locally.is_empty() {
+        warn!(
+            "{} segment(s) referenced by the manifest are missing locally: {:?}",
+            report.missing_locally.len(),
+            report.missing_locally
+        );
+    }
+    if !report.unregistered.is_empty() {
+        warn!(
+            "{} archived local segment(s) are missing from the manifest: {:?}",
+            report.unregistered.len(),
+            report.unregistered
+        );
+    }
+    if mode == StartupCheckMode::Repair && !report.is_consistent() {
+        info!(
+            "✓ Repaired segments: {} fetched from S3, {} dropped from the manifest, {} registered",
+            report.fetched.len(),
+            report.dropped.len(),
+            report.registered.len()
+        );
+    }
+}
+
+/// Populate a fresh node with synthetic data through the regular write path
+///
+/// The node is bootstrapped as a single-node cluster and the records are written
+/// through Raft. With S3 configured, they also fill local segments, rolled at the
+/// configured segment size; every segment is archived and the blobs are demoted to
+/// their archived segment, so reads exercise both storage tiers. Start the node
+/// afterwards without `--bootstrap` to serve the data.
+async fn run_demo_seed(
+    config: &Config,
+    records: usize,
+    value_size: usize,
+    seed: u64,
+) -> Result<()> {
+    let db = open_fresh_db(config, "demo-seed")?;
+    let archival = open_archival(config, &db).await?;
+    let consensus = bootstrap_single_node(config, db).await?;
+
+    let mut api = DistributedApi::new(consensus.clone());
+    if let Some(archival) = &archival {
+        api = api.with_archival(archival.clone());
+    }
+
+    let started = Instant::now();
+    let mut counts = [0usize; 3];
+    let mut blobs = HashSet::new();
+    let mut batch = Vec::with_capacity(DEMO_BATCH_SIZE);
+    for record in DemoDataGenerator::new(seed, value_size).take(records) {
+        counts[record.kind as usize] += 1;
+        if let Some(archival) = &archival {
+            archival.put(record.key.clone(), record.value.clone())?;
+        }
+        if record.kind == DemoKind::Blob {
+            blobs.insert(record.key.clone());
+        }
+        batch.push((record.key, record.value));
+        if batch.len() == DEMO_BATCH_SIZE {
+            write_batch(&api, std::mem::take(&mut batch)).await?;
+        }
+    }
+    write_batch(&api, batch).await?;
+    println!(
+        "Wrote {} records ({} users, {} documents, {} blobs) in {:.1}s",
+        records,
+        counts[DemoKind::User as usize],
+        counts[DemoKind::Document as usize],
+        counts[DemoKind::Blob as usize],
+        started.elapsed().as_secs_f64()
+    );
+
+    if let Some(archival) = &archival {
+        let mut archived = 0;
+        let mut demoted = 0;
+        for segments in archival.segment_managers() {
+            segments.flush_active()?;
+            for segment in segments.get_flushed_segments()? {
+                archival.archive_segment(&segment).await?;
+                api.anchor_segment(&segment).await?;
+                for key in segment.data.keys().filter(|key| blobs.contains(*key)) {
+                    if api.demote(key.clone(), segment.segment_id).await? {
+                        demoted += 1;
+                    }
+                }
+                segments.remove_flushed(&[segment.segment_id])?;
+                archived += 1;
+            }
+        }
+        println!(
+            "Archived {} segment(s) to S3 and demoted {} blobs to them",
+            archived, demoted
+        );
+    } else {
+        println!("S3 is not configured: every record is held locally");
+    }
+
+    consensus
+        .shutdown()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to stop Raft: {}", e))?;
+    println!("Start the node without --bootstrap to serve the demo data");
+    Ok(())
+}
+
+/// Open the database of a fresh node for `command`, refusing one that holds data
+fn open_fresh_db(config: &Config, command: &str) -> Result<sled::Db> {
+    let db_path = config.node.data_dir.join("db");
+    if db_path.exists() {
+        anyhow::bail!(
+            "{} populates a fresh node, but {:?} already exists",
+            command,
+            db_path
+        );
+    }
+    std::fs::create_dir_all(&config.node.data_dir)?;
+    open_db(config)
+}
+
+/// Bootstrap a single-node cluster on `db` and wait until the node leads it
+async fn bootstrap_single_node(config: &Config, db: sled::Db) -> Result<Arc<ConsensusNode>> {
+    let consensus = Arc::new(
+        ConsensusNode::new_with_scribe_config(config.node.id, db, &config.consensus)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to create consensus node: {}", e))?,
+    );
+    consensus
+        .initialize()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to bootstrap the node: {}", e))?;
+    let deadline = Instant::now() + BOOTSTRAP_LEADER_TIMEOUT;
+    while consensus.current_leader().await != Some(config.node.id) {
+        if Instant::now() >= deadline {
+            anyhow::bail!(
+                "Node did not become leader within {:?}",
+                BOOTSTRAP_LEADER_TIMEOUT
+            );
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    Ok(consensus)
+}
+
+/// Rewrite the snapshots in `<data_dir>/snapshots` into the configured layout
+async fn run_migrate(config: &Config) -> Result<()> {
+    let path = config.node.data_dir.join("snapshots");
+    if !path.exists() {
+        println!("No snapshots at {:?}, nothing to migrate", path);
+        return Ok(());
+    }
+    let rewritten = SnapshotStore::migrate(&path, &config.storage.snapshots.layout).await?;
+    println!("Migrated {:?}: rewrote {} keys", path, rewritten);
+    Ok(())
+}
+
+/// Import a standalone ledger into a fresh node through Raft proposals
+///
+/// The node is bootstrapped as a single-node cluster and the ledger's pairs are
+/// proposed in key order, `batch_size` at a time, with a progress report every few
+/// seconds. With `verify`, every key is read back and compared with the original
+/// afterwards; any difference fails the command. The standalone database is only
+/// read. Start the node afterwards without `--bootstrap` and add the other nodes.
+async fn run_migrate_legacy(
+    config: &Config,
+    from: &std::path::Path,
+    batch_size: usize,
+    verify: bool,
+) -> Result<()> {
+    let legacy = open_legacy(from)?;
+    let db = open_fresh_db(config, "migrate-legacy")?;
+    let consensus = bootstrap_single_node(config, db).await?;
+    let api = DistributedApi::new(consensus.clone());
+
+    let mut progress = MigrationProgress::new(legacy.len());
+    println!("Importing {} keys from {:?}", progress.total_keys, from);
+    let mut reported = Instant::now();
+    for batch in LegacyBatches::new(&legacy, batch_size) {
+        let batch = batch?;
+        progress.record(&batch);
+        write_batch(&api, batch).await?;
+        if reported.elapsed() >= MIGRATION_PROGRESS_INTERVAL {
+            println!("  {}", progress);
+            reported = Instant::now();
+        }
+    }
+    println!("Imported {}", progress);
+
+    if verify {
+        let mut verification = MigrationVerification::default();
+        for batch in LegacyBatches::new(&legacy, batch_size) {
+            for (key, value) in batch? {
+                let actual = api.get(key.clone(), ReadConsistency::Stale).await?;
+                verification.check(&key, &value, actual.as_deref());
+            }
+        }
+        if !verification.is_ok() {
+            let examples: Vec<String> = verification
+                .examples
+                .iter()
+                .map(|key| log_key(key).to_string())
+                .collect();
+            anyhow::bail!(
+                "Verification failed: {} of {} keys missing, {} mismatched (e.g. {})",
+                verification.missing,
+                verification.checked,
+                verification.mismatched,
+                examples.join(", ")
+            );
+        }
+        println!("Verified {} keys", verification.checked);
+    }
+
+    consensus
+        .shutdown()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to stop Raft: {}", e))?;
+    println!("Start the node without --bootstrap to serve the imported data");
+    Ok(())
+}
+
+/// Write one batch of pairs, failing on the first rejected write
+async fn write_batch(api: &DistributedApi, batch: Vec<(Vec<u8>, Vec<u8>)>) -> Result<()> {
+    for result in api.put_batch(batch).await? {
+        result?;
+    }
+    Ok(())
+}
+
+/// Run as a read-only mirror of the S3 archive on the client port
+///
+/// The mirror holds no Raft or sled state; it indexes the archived segments at
+/// startup and every `refresh` afterwards, and stops on SIGTERM or SIGINT.
+async fn run_mirror(config: &Config, refresh: Duration, cache_segments: usize) -> Result<()> {
+    let s3_config = config
+        .storage
+        .s3
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("Mirror mode requires [storage.s3] in the config"))?;
+    info!("Mirroring S3 bucket {}", s3_config.bucket);
+
+    let mirror = Arc::new(
+        SegmentMirror::new(s3_storage_config(s3_config))
+            .await?
+            .with_cache_segments(cache_segments),
+    );
+    // A bucket that cannot be read yet is retried by the refresh task
+    match mirror.refresh().await {
+        Ok(report) => info!(
+            "✓ Indexed {} archived segments ({} unreadable)",
+            report.segments_added, report.segments_unreadable
+        ),
+        Err(e) => warn!("Initial mirror refresh failed: {}", e),
+    }
+    mirror.start_refresh(refresh);
+
+    let app = mirror_router(mirror)
+        .layer(HttpMetricsLayer)
+        .layer(axum::middleware::from_fn(trace_context_middleware))
+        .layer(cors_layer(config.network.permissive_cors));
+    let addr = format!("{}:{}", config.node.address, config.network.client_port);
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    info!("Read-only mirror listening on {}", addr);
+
+    axum::serve(listener, app)
+        .with_graceful_shutdown(wait_for_shutdown_signal())
+        .await?;
+    info!("Mirror stopped");
     Ok(())
 }
 
@@ -0,0 +1,108 @@
+//! Scribe Verify - Offline proof bundle verification
+//!
+//! Checks a proof bundle exported by a node (`GET /proof/:key`, or
+//! `GET /verify/:key?format=bundle` and `?format=bundle-json` on cluster nodes),
+//! sealed or as JSON, without contacting the cluster: that the value hashes up to the bundled Merkle root and, when the
+//! root is signed, that the signature matches and comes from a trusted key. Prints
+//! a verdict for people, or as JSON with `--json`, and exits with 0 if the bundle
+//! is valid, 1 if it is not and 2 if it cannot be read.
+
+use clap::Parser;
+use hyra_scribe_ledger::wire::{ProofBundle, ProofVerdict, SignatureStatus};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// Hyra Scribe Ledger - Offline proof verification
+#[derive(Parser, Debug)]
+#[command(name = "scribe-verify")]
+#[command(version = env!("CARGO_PKG_VERSION"))]
+#[command(about = "Verify an exported proof bundle offline", long_about = None)]
+struct Cli {
+    /// Proof bundle file, sealed or JSON
+    bundle: PathBuf,
+
+    /// Hex public key allowed to sign the root (repeat for several); if given, the
+    /// root must be signed by one of them
+    #[arg(long = "trusted-key")]
+    trusted_keys: Vec<String>,
+
+    /// Key the bundle must prove
+    #[arg(long)]
+    key: Option<String>,
+
+    /// Print the verdict as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let bundle = match std::fs::read(&cli.bundle)
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| ProofBundle::parse(&bytes).map_err(|e| e.to_string()))
+    {
+        Ok(bundle) => bundle,
+        Err(e) => {
+            eprintln!("Cannot read proof bundle {}: {}", cli.bundle.display(), e);
+            return ExitCode::from(2);
+        }
+    };
+
+    let mut verdict = bundle.check(&cli.trusted_keys);
+    if let Some(key) = &cli.key {
+        if key.as_bytes() != bundle.proof.key {
+            verdict.problems.push(format!(
+                "The bundle proves key '{}', not '{}'",
+                verdict.key, key
+            ));
+            verdict.valid = false;
+        }
+    }
+
+    if cli.json {
+        match serde_json::to_string_pretty(&verdict) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                eprintln!("Cannot encode verdict: {}", e);
+                return ExitCode::from(2);
+            }
+        }
+    } else {
+        print_verdict(&verdict);
+    }
+
+    if verdict.valid {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn print_verdict(verdict: &ProofVerdict) {
+    println!("Key:        {} (hex {})", verdict.key, verdict.key_hex);
+    println!(
+        "Value:      {} bytes, SHA-256 {}",
+        verdict.value_len, verdict.value_sha256
+    );
+    println!("Root:       {}", verdict.root_hash);
+    println!("Path:       {} hashes", verdict.path_length);
+    println!(
+        "Inclusion:  {}",
+        if verdict.inclusion { "ok" } else { "FAILED" }
+    );
+    let signer = verdict.signer.as_deref().unwrap_or_default();
+    match verdict.signature {
+        SignatureStatus::Unsigned => println!("Signature:  none"),
+        SignatureStatus::Trusted => println!("Signature:  ok, trusted key {}", signer),
+        SignatureStatus::Untrusted => println!("Signature:  ok, by untrusted key {}", signer),
+        SignatureStatus::Invalid => println!("Signature:  INVALID (key {})", signer),
+    }
+    println!(
+        "Verdict:    {}",
+        if verdict.valid { "VALID" } else { "INVALID" }
+    );
+    for problem in &verdict.problems {
+        println!("  - {}", problem);
+    }
+}
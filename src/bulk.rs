@@ -0,0 +1,240 @@
+//! Binary stream format of bulk exports and imports
+//!
+//! `GET /export` streams every key of the ledger in this format and `POST /import`
+//! reads it back, so a whole keyspace can be moved between clusters, or loaded from
+//! another store by a converter writing the same format:
+//!
+//! ```text
+//! header   "SCRIBEX" followed by the format version (1)
+//! record   key length (u32, big-endian, at least 1) | key | value length (u32) | value
+//! trailer  0 (u32) | number of records (u64, big-endian)
+//! ```
+//!
+//! Records of an export are ordered by key. The trailer marks a complete stream:
+//! a stream cut short, e.g. by a dropped connection, has none and is refused by
+//! [`StreamDecoder::finish`].
+
+use crate::error::{Result, ScribeError};
+use crate::types::{Key, Value};
+
+/// First bytes of every stream: a magic string and the format version
+pub const STREAM_HEADER: &[u8; 8] = b"SCRIBEX\x01";
+
+/// Longest key or value a stream may hold, in bytes
+pub const MAX_FIELD_LEN: usize = 64 * 1024 * 1024;
+
+/// Length prefix of the trailer; keys are never empty
+const END_MARKER: u32 = 0;
+
+/// Append a record to `out`
+pub fn write_record(out: &mut Vec<u8>, key: &[u8], value: &[u8]) {
+    out.extend_from_slice(&(key.len() as u32).to_be_bytes());
+    out.extend_from_slice(key);
+    out.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    out.extend_from_slice(value);
+}
+
+/// Append the trailer of a stream holding `records` records to `out`
+pub fn write_trailer(out: &mut Vec<u8>, records: u64) {
+    out.extend_from_slice(&END_MARKER.to_be_bytes());
+    out.extend_from_slice(&records.to_be_bytes());
+}
+
+/// Encode `pairs` as a complete stream
+pub fn encode<'a>(pairs: impl IntoIterator<Item = (&'a [u8], &'a [u8])>) -> Vec<u8> {
+    let mut out = STREAM_HEADER.to_vec();
+    let mut records = 0;
+    for (key, value) in pairs {
+        write_record(&mut out, key, value);
+        records += 1;
+    }
+    write_trailer(&mut out, records);
+    out
+}
+
+/// Incremental decoder of a stream arriving in chunks of any size
+#[derive(Debug, Default)]
+pub struct StreamDecoder {
+    /// Bytes received but not decoded yet
+    pending: Vec<u8>,
+    header_read: bool,
+    records: u64,
+    /// Record count of the trailer, once read
+    trailer: Option<u64>,
+}
+
+impl StreamDecoder {
+    /// Create a decoder expecting the stream header
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records decoded so far
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    /// Decode the records completed by `chunk`
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<Vec<(Key, Value)>> {
+        if self.trailer.is_some() {
+            if chunk.is_empty() {
+                return Ok(Vec::new());
+            }
+            return Err(stream_error("Data after the end of the stream"));
+        }
+        self.pending.extend_from_slice(chunk);
+
+        let mut position = 0;
+        if !self.header_read {
+            if self.pending.len() < STREAM_HEADER.len() {
+                return Ok(Vec::new());
+            }
+            if self.pending[..STREAM_HEADER.len()] != STREAM_HEADER[..] {
+                return Err(stream_error("Not a Scribe export stream of version 1"));
+            }
+            self.header_read = true;
+            position = STREAM_HEADER.len();
+        }
+
+        let mut records = Vec::new();
+        loop {
+            let rest = &self.pending[position..];
+            let Some(key_len) = read_u32(rest) else {
+                break;
+            };
+            if key_len == END_MARKER {
+                let Some(count) = rest.get(4..12) else {
+                    break;
+                };
+                let count = u64::from_be_bytes(count.try_into().expect("8 bytes"));
+                if count != self.records + records.len() as u64 {
+                    return Err(stream_error(&format!(
+                        "Trailer counts {} records, the stream holds {}",
+                        count,
+                        self.records + records.len() as u64
+                    )));
+                }
+                if rest.len() > 12 {
+                    return Err(stream_error("Data after the end of the stream"));
+                }
+                self.trailer = Some(count);
+                position = self.pending.len();
+                break;
+            }
+
+            let key_len = field_len(key_len)?;
+            let Some(value_len) = rest.get(4 + key_len..).and_then(read_u32) else {
+                break;
+            };
+            let value_len = field_len(value_len)?;
+            let end = 8 + key_len + value_len;
+            if rest.len() < end {
+                break;
+            }
+            records.push((
+                rest[4..4 + key_len].to_vec(),
+                rest[8 + key_len..end].to_vec(),
+            ));
+            position += end;
+        }
+
+        self.pending.drain(..position);
+        self.records += records.len() as u64;
+        Ok(records)
+    }
+
+    /// Check that the stream ended with its trailer; returns the number of records
+    pub fn finish(&self) -> Result<u64> {
+        match self.trailer {
+            Some(records) => Ok(records),
+            None => Err(stream_error(&format!(
+                "Stream ended without its trailer after {} records",
+                self.records
+            ))),
+        }
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(..4)?.try_into().ok()?))
+}
+
+fn field_len(len: u32) -> Result<usize> {
+    let len = len as usize;
+    if len > MAX_FIELD_LEN {
+        return Err(stream_error(&format!(
+            "Record field of {} bytes exceeds the limit of {} bytes",
+            len, MAX_FIELD_LEN
+        )));
+    }
+    Ok(len)
+}
+
+fn stream_error(message: &str) -> ScribeError {
+    ScribeError::Serialization(format!("Invalid export stream: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs() -> Vec<(Vec<u8>, Vec<u8>)> {
+        vec![
+            (b"a".to_vec(), b"1".to_vec()),
+            (b"b".to_vec(), Vec::new()),
+            (b"c".to_vec(), vec![0xff; 300]),
+        ]
+    }
+
+    fn stream() -> Vec<u8> {
+        encode(
+            pairs()
+                .iter()
+                .map(|(key, value)| (key.as_slice(), value.as_slice())),
+        )
+    }
+
+    #[test]
+    fn test_decode_in_chunks_of_any_size() {
+        let stream = stream();
+        for chunk_size in [1, 3, 7, 64, stream.len()] {
+            let mut decoder = StreamDecoder::new();
+            let mut decoded = Vec::new();
+            for chunk in stream.chunks(chunk_size) {
+                decoded.extend(decoder.feed(chunk).unwrap());
+            }
+            assert_eq!(decoded, pairs(), "chunks of {}", chunk_size);
+            assert_eq!(decoder.finish().unwrap(), 3);
+        }
+    }
+
+    #[test]
+    fn test_incomplete_and_invalid_streams() {
+        let stream = stream();
+
+        // Cut short: the records read so far are returned, but the stream is incomplete
+        let mut decoder = StreamDecoder::new();
+        let decoded = decoder.feed(&stream[..stream.len() - 12]).unwrap();
+        assert_eq!(decoded.len(), 3);
+        assert!(decoder.finish().is_err());
+
+        let mut decoder = StreamDecoder::new();
+        assert!(decoder.feed(b"SCRIBEX\x02").is_err());
+
+        let mut wrong_count = stream.clone();
+        let last = wrong_count.len() - 1;
+        wrong_count[last] = 4;
+        assert!(StreamDecoder::new().feed(&wrong_count).is_err());
+
+        let mut trailing = stream.clone();
+        trailing.push(0);
+        assert!(StreamDecoder::new().feed(&trailing).is_err());
+        let mut decoder = StreamDecoder::new();
+        decoder.feed(&stream).unwrap();
+        assert!(decoder.feed(b"x").is_err());
+
+        let mut oversized = STREAM_HEADER.to_vec();
+        oversized.extend_from_slice(&(MAX_FIELD_LEN as u32 + 1).to_be_bytes());
+        assert!(StreamDecoder::new().feed(&oversized).is_err());
+    }
+}
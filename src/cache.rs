@@ -2,18 +2,87 @@
 //!
 //! This module provides an LRU cache for frequently accessed key-value pairs
 //! to reduce the load on the storage backend and improve read performance.
+//!
+//! Entries evicted from memory can spill to a small on-disk tree instead of being
+//! dropped (see [`HotDataCache::with_overflow`]), which keeps serving a working set
+//! slightly larger than the memory capacity from the cache. Lookups are counted in
+//! `scribe_ledger_cache_lookups_total` by outcome: `memory_hit`, `disk_hit` or
+//! `miss`.
+//!
+//! [`WarmKeys`] saves the most recently used keys so a restarted node can load their
+//! values again before clients read them (see
+//! [`crate::api::DistributedApi::warm_cache`]).
 
+use crate::error::Result;
+use crate::metrics::CACHE_LOOKUPS;
 use crate::types::{Key, Value};
 use lru::LruCache;
 use std::num::NonZeroUsize;
 use std::sync::Mutex;
+use tracing::warn;
 
 /// Default cache capacity (number of entries)
 const DEFAULT_CACHE_CAPACITY: usize = 1000;
 
+/// Name of the sled tree holding the keys saved by [`WarmKeys`]
+pub const WARM_KEYS_TREE: &str = "cache_warm_keys";
+
 /// Hot data cache using LRU eviction policy
 pub struct HotDataCache {
     cache: Mutex<LruCache<Key, Value>>,
+    /// Entries spilled from memory; always locked after `cache`
+    overflow: Option<Mutex<DiskOverflow>>,
+}
+
+/// Entries evicted from memory, kept in a sled tree
+///
+/// The order of the keys is tracked in memory, so the tree drops the least recently
+/// spilled entry once it is full.
+struct DiskOverflow {
+    tree: sled::Tree,
+    order: LruCache<Key, ()>,
+}
+
+impl DiskOverflow {
+    /// Keep an entry evicted from memory
+    fn spill(&mut self, key: Key, value: Value) {
+        if let Err(e) = self.tree.insert(&key, value) {
+            warn!("Failed to spill cache entry to disk: {}", e);
+            return;
+        }
+        if let Some((dropped, _)) = self.order.push(key.clone(), ()) {
+            if dropped != key {
+                self.discard(&dropped);
+            }
+        }
+    }
+
+    /// Remove the entry of `key` and return its value
+    fn take(&mut self, key: &Key) -> Option<Value> {
+        self.order.pop(key)?;
+        match self.tree.remove(key) {
+            Ok(value) => value.map(|value| value.to_vec()),
+            Err(e) => {
+                warn!("Failed to read spilled cache entry: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Remove the entry of `key`, if any
+    fn discard(&mut self, key: &Key) {
+        self.order.pop(key);
+        if let Err(e) = self.tree.remove(key) {
+            warn!("Failed to drop spilled cache entry: {}", e);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.order.clear();
+        if let Err(e) = self.tree.clear() {
+            warn!("Failed to clear spilled cache entries: {}", e);
+        }
+    }
 }
 
 impl HotDataCache {
@@ -27,50 +96,162 @@ impl HotDataCache {
         let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::new(1).unwrap());
         Self {
             cache: Mutex::new(LruCache::new(capacity)),
+            overflow: None,
         }
     }
 
+    /// Spill entries evicted from memory to `tree`, keeping up to `capacity` of them
+    ///
+    /// The tree is cleared first: entries left from an earlier run missed the
+    /// invalidations since and may be stale. A `capacity` of 0 disables the overflow.
+    pub fn with_overflow(mut self, tree: sled::Tree, capacity: usize) -> Result<Self> {
+        tree.clear()?;
+        self.overflow = NonZeroUsize::new(capacity).map(|capacity| {
+            Mutex::new(DiskOverflow {
+                tree,
+                order: LruCache::new(capacity),
+            })
+        });
+        Ok(self)
+    }
+
     /// Get a value from the cache
+    ///
+    /// A value found on disk moves back into memory.
     pub fn get(&self, key: &Key) -> Option<Value> {
         let mut cache = self.cache.lock().unwrap();
-        cache.get(key).cloned()
+        if let Some(value) = cache.get(key) {
+            CACHE_LOOKUPS.with_label_values(&["memory_hit"]).inc();
+            return Some(value.clone());
+        }
+
+        let spilled = self.overflow.as_ref().and_then(|overflow| {
+            let mut overflow = overflow.lock().unwrap();
+            let value = overflow.take(key)?;
+            if let Some((evicted, evicted_value)) = cache.push(key.clone(), value.clone()) {
+                overflow.spill(evicted, evicted_value);
+            }
+            Some(value)
+        });
+        let outcome = if spilled.is_some() {
+            "disk_hit"
+        } else {
+            "miss"
+        };
+        CACHE_LOOKUPS.with_label_values(&[outcome]).inc();
+        spilled
     }
 
     /// Put a value into the cache
     pub fn put(&self, key: Key, value: Value) {
         let mut cache = self.cache.lock().unwrap();
-        cache.put(key, value);
+        match &self.overflow {
+            Some(overflow) => {
+                let mut overflow = overflow.lock().unwrap();
+                overflow.discard(&key);
+                if let Some((evicted, evicted_value)) = cache.push(key.clone(), value) {
+                    if evicted != key {
+                        overflow.spill(evicted, evicted_value);
+                    }
+                }
+            }
+            None => {
+                cache.put(key, value);
+            }
+        }
     }
 
     /// Remove a value from the cache
     pub fn remove(&self, key: &Key) -> Option<Value> {
         let mut cache = self.cache.lock().unwrap();
-        cache.pop(key)
+        let value = cache.pop(key);
+        let spilled = self
+            .overflow
+            .as_ref()
+            .and_then(|overflow| overflow.lock().unwrap().take(key));
+        value.or(spilled)
     }
 
     /// Clear all entries from the cache
     pub fn clear(&self) {
         let mut cache = self.cache.lock().unwrap();
         cache.clear();
+        if let Some(overflow) = &self.overflow {
+            overflow.lock().unwrap().clear();
+        }
     }
 
-    /// Get the number of entries in the cache
+    /// Get the number of entries in the cache, in memory and on disk
     pub fn len(&self) -> usize {
         let cache = self.cache.lock().unwrap();
-        cache.len()
+        cache.len() + self.overflow_len()
+    }
+
+    /// Get the number of entries spilled to disk
+    pub fn overflow_len(&self) -> usize {
+        self.overflow
+            .as_ref()
+            .map_or(0, |overflow| overflow.lock().unwrap().order.len())
     }
 
     /// Check if the cache is empty
     pub fn is_empty(&self) -> bool {
-        let cache = self.cache.lock().unwrap();
-        cache.is_empty()
+        self.len() == 0
     }
 
-    /// Get cache capacity
+    /// Get cache capacity (entries held in memory)
     pub fn capacity(&self) -> usize {
         let cache = self.cache.lock().unwrap();
         cache.cap().get()
     }
+
+    /// Up to `limit` keys held in memory, most recently used first
+    pub fn recent_keys(&self, limit: usize) -> Vec<Key> {
+        let cache = self.cache.lock().unwrap();
+        cache
+            .iter()
+            .take(limit)
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+}
+
+/// Recently used cache keys, saved to warm the cache after a restart
+///
+/// Only the keys are saved: their values are read again when warming, so a value
+/// written while the node was down is never served from an old copy.
+pub struct WarmKeys {
+    tree: sled::Tree,
+}
+
+impl WarmKeys {
+    /// Save and load keys in `tree`
+    pub fn new(tree: sled::Tree) -> Self {
+        Self { tree }
+    }
+
+    /// Replace the saved keys with `keys`, hottest first
+    pub fn save(&self, keys: &[Key]) -> Result<()> {
+        let mut batch = sled::Batch::default();
+        for position in self.tree.iter().keys() {
+            batch.remove(position?);
+        }
+        for (position, key) in keys.iter().enumerate() {
+            batch.insert((position as u64).to_be_bytes().to_vec(), key.as_slice());
+        }
+        self.tree.apply_batch(batch)?;
+        Ok(())
+    }
+
+    /// Up to `limit` saved keys, hottest first
+    pub fn load(&self, limit: usize) -> Result<Vec<Key>> {
+        self.tree
+            .iter()
+            .values()
+            .take(limit)
+            .map(|key| Ok(key?.to_vec()))
+            .collect()
+    }
 }
 
 impl Default for HotDataCache {
@@ -188,4 +369,74 @@ mod tests {
         assert_eq!(cache.get(&b"key3".to_vec()), Some(b"value3".to_vec()));
         assert_eq!(cache.get(&b"key4".to_vec()), Some(b"value4".to_vec()));
     }
+
+    #[test]
+    fn test_cache_spills_to_disk() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let tree = db.open_tree("cache_overflow").unwrap();
+        tree.insert("stale", "left over").unwrap();
+        let cache = HotDataCache::with_capacity(2)
+            .with_overflow(tree.clone(), 2)
+            .unwrap();
+        assert!(cache.is_empty());
+
+        for i in 1..=4 {
+            cache.put(format!("key{}", i).into_bytes(), vec![i]);
+        }
+        // key1 and key2 were evicted from memory to disk
+        assert_eq!(cache.len(), 4);
+        assert_eq!(cache.overflow_len(), 2);
+        assert_eq!(tree.len(), 2);
+
+        let disk_hits = CACHE_LOOKUPS.with_label_values(&["disk_hit"]).get();
+        assert_eq!(cache.get(&b"key1".to_vec()), Some(vec![1]));
+        assert!(CACHE_LOOKUPS.with_label_values(&["disk_hit"]).get() > disk_hits);
+
+        // Reading key1 moved it back to memory, spilling key3
+        assert_eq!(cache.overflow_len(), 2);
+        assert!(tree.contains_key("key3").unwrap());
+        assert!(!tree.contains_key("key1").unwrap());
+
+        // The disk tier drops its least recently spilled entry (key2) when full
+        cache.put(b"key5".to_vec(), vec![5]);
+        assert_eq!(cache.overflow_len(), 2);
+        assert_eq!(cache.get(&b"key2".to_vec()), None);
+
+        // Writes and removals reach spilled entries
+        cache.put(b"key3".to_vec(), vec![33]);
+        assert_eq!(cache.get(&b"key3".to_vec()), Some(vec![33]));
+        assert!(cache.remove(&b"key4".to_vec()).is_some());
+        assert_eq!(cache.get(&b"key4".to_vec()), None);
+
+        cache.clear();
+        assert!(cache.is_empty());
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn test_warm_keys_round_trip() {
+        let cache = HotDataCache::with_capacity(3);
+        for i in 1..=4 {
+            cache.put(format!("key{}", i).into_bytes(), vec![i]);
+        }
+        cache.get(&b"key2".to_vec());
+        assert_eq!(
+            cache.recent_keys(10),
+            vec![b"key2".to_vec(), b"key4".to_vec(), b"key3".to_vec()]
+        );
+        assert_eq!(cache.recent_keys(1), vec![b"key2".to_vec()]);
+
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let warm = WarmKeys::new(db.open_tree(WARM_KEYS_TREE).unwrap());
+        assert!(warm.load(10).unwrap().is_empty());
+        warm.save(&cache.recent_keys(10)).unwrap();
+        assert_eq!(
+            warm.load(2).unwrap(),
+            vec![b"key2".to_vec(), b"key4".to_vec()]
+        );
+
+        // Saving again replaces the old keys
+        warm.save(&[b"key9".to_vec()]).unwrap();
+        assert_eq!(warm.load(10).unwrap(), vec![b"key9".to_vec()]);
+    }
 }
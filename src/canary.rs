@@ -0,0 +1,246 @@
+//! Read mirroring for canary testing
+//!
+//! A sampled share of client reads is replayed asynchronously against a canary node
+//! or cluster running a new version. The canary's response is compared with the
+//! value this node served by SHA-256 hash only, so values are never held in the
+//! mirroring queue. Mirroring never delays or fails the primary read; outcomes are
+//! tracked in divergence counters instead.
+
+use crate::error::{Result, ScribeError};
+use crate::http_client::key_url;
+use crate::logging::log_key;
+use crate::metrics::CANARY_READS;
+use crate::types::Key;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+/// Read to replay against the canary
+struct MirroredRead {
+    key: Key,
+    /// Hash of the value served by this node, `None` if the key was not found
+    primary: Option<[u8; 32]>,
+}
+
+/// Point-in-time view of the canary counters
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CanaryStats {
+    /// Reads for which the canary returned the same value
+    pub matched: u64,
+    /// Reads for which the canary returned a different value or presence
+    pub diverged: u64,
+    /// Reads the canary did not answer with a value or a 404
+    pub failed: u64,
+    /// Sampled reads never mirrored because the queue was full
+    pub dropped: u64,
+}
+
+#[derive(Default)]
+struct CanaryCounters {
+    matched: AtomicU64,
+    diverged: AtomicU64,
+    failed: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl CanaryCounters {
+    fn record(&self, counter: &AtomicU64, outcome: &str) {
+        counter.fetch_add(1, Ordering::Relaxed);
+        CANARY_READS.with_label_values(&[outcome]).inc();
+    }
+}
+
+/// Asynchronous mirror of sampled reads to a canary
+pub struct ReadMirror {
+    sender: mpsc::Sender<MirroredRead>,
+    sample_percent: f64,
+    counters: Arc<CanaryCounters>,
+}
+
+impl ReadMirror {
+    /// Start mirroring `sample_percent` percent of reads to the client API at
+    /// `target_url`, waiting at most `timeout` for each canary response
+    pub fn start(
+        target_url: String,
+        sample_percent: f64,
+        queue_capacity: usize,
+        timeout: Duration,
+    ) -> Result<Self> {
+        if queue_capacity == 0 {
+            return Err(ScribeError::Configuration(
+                "Canary queue capacity must be greater than 0".to_string(),
+            ));
+        }
+        if !(0.0..=100.0).contains(&sample_percent) {
+            return Err(ScribeError::Configuration(
+                "Canary sample percentage must be between 0 and 100".to_string(),
+            ));
+        }
+        key_url(&target_url, b"")?;
+
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(|e| ScribeError::Network(format!("Failed to create HTTP client: {}", e)))?;
+        let (sender, mut receiver) = mpsc::channel::<MirroredRead>(queue_capacity);
+        let counters = Arc::new(CanaryCounters::default());
+
+        let worker_counters = Arc::clone(&counters);
+        tokio::spawn(async move {
+            while let Some(read) = receiver.recv().await {
+                match fetch_hash(&client, &target_url, &read.key).await {
+                    Ok(canary) if canary == read.primary => {
+                        worker_counters.record(&worker_counters.matched, "matched");
+                    }
+                    Ok(_) => {
+                        warn!("Canary read diverged for key {}", log_key(&read.key));
+                        worker_counters.record(&worker_counters.diverged, "diverged");
+                    }
+                    Err(e) => {
+                        debug!("Canary read failed: {}", e);
+                        worker_counters.record(&worker_counters.failed, "failed");
+                    }
+                }
+            }
+            debug!("Canary read mirror stopped");
+        });
+
+        Ok(Self {
+            sender,
+            sample_percent,
+            counters,
+        })
+    }
+
+    /// Queue a sampled read for mirroring without waiting for it
+    ///
+    /// `served` is the response body this node returned, `None` if the key was not
+    /// found.
+    pub fn mirror(&self, key: &[u8], served: Option<&[u8]>) {
+        if fastrand::f64() * 100.0 >= self.sample_percent {
+            return;
+        }
+        let read = MirroredRead {
+            key: key.to_vec(),
+            primary: served.map(hash),
+        };
+        if self.sender.try_send(read).is_err() {
+            self.counters.record(&self.counters.dropped, "dropped");
+        }
+    }
+
+    /// Get the current divergence counters
+    pub fn stats(&self) -> CanaryStats {
+        CanaryStats {
+            matched: self.counters.matched.load(Ordering::Relaxed),
+            diverged: self.counters.diverged.load(Ordering::Relaxed),
+            failed: self.counters.failed.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+fn hash(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+/// Read `key` from the canary, returning the hash of its value
+async fn fetch_hash(
+    client: &reqwest::Client,
+    base_url: &str,
+    key: &[u8],
+) -> Result<Option<[u8; 32]>> {
+    let response = client
+        .get(key_url(base_url, key)?)
+        .send()
+        .await
+        .map_err(|e| ScribeError::Network(format!("Canary request failed: {}", e)))?;
+    match response.status() {
+        StatusCode::OK => {
+            let body = response
+                .bytes()
+                .await
+                .map_err(|e| ScribeError::Network(format!("Canary response failed: {}", e)))?;
+            Ok(Some(hash(&body)))
+        }
+        StatusCode::NOT_FOUND => Ok(None),
+        status => Err(ScribeError::Network(format!("Canary returned {}", status))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::Path;
+    use axum::http::StatusCode as HttpStatus;
+    use axum::routing::get;
+
+    async fn canary_url() -> String {
+        let app = axum::Router::new().route(
+            "/:key",
+            get(|Path(key): Path<String>| async move {
+                match key.as_str() {
+                    "same" => (HttpStatus::OK, "value".to_string()),
+                    "changed" => (HttpStatus::OK, "new value".to_string()),
+                    "broken" => (HttpStatus::INTERNAL_SERVER_ERROR, String::new()),
+                    _ => (HttpStatus::NOT_FOUND, String::new()),
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_read_mirror_compares_hashes() {
+        let mirror =
+            ReadMirror::start(canary_url().await, 100.0, 16, Duration::from_secs(5)).unwrap();
+
+        mirror.mirror(b"same", Some(b"value"));
+        mirror.mirror(b"missing", None);
+        mirror.mirror(b"changed", Some(b"value"));
+        mirror.mirror(b"missing", Some(b"value"));
+        mirror.mirror(b"broken", Some(b"value"));
+
+        for _ in 0..100 {
+            let stats = mirror.stats();
+            if stats.matched + stats.diverged + stats.failed == 5 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(
+            mirror.stats(),
+            CanaryStats {
+                matched: 2,
+                diverged: 2,
+                failed: 1,
+                dropped: 0,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_mirror_sampling_and_validation() {
+        let url = "http://127.0.0.1:1".to_string();
+        let timeout = Duration::from_secs(1);
+        assert!(ReadMirror::start(url.clone(), 50.0, 0, timeout).is_err());
+        assert!(ReadMirror::start(url.clone(), 150.0, 16, timeout).is_err());
+        assert!(ReadMirror::start("not a url".to_string(), 50.0, 16, timeout).is_err());
+
+        // Nothing is sampled at 0%
+        let mirror = ReadMirror::start(url, 0.0, 16, timeout).unwrap();
+        for _ in 0..100 {
+            mirror.mirror(b"key", None);
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(mirror.stats(), CanaryStats::default());
+    }
+}
@@ -0,0 +1,312 @@
+//! Capacity planning estimates
+//!
+//! Projects the disk, S3 and memory usage of a node under a steady write workload,
+//! to size clusters before deployment (`scribe-ctl simulate`). Per-write costs are
+//! measured by encoding sample entries the way the node stores them: as bincode
+//! segments, gzip-compressed for archival. The model assumes an append-only ledger:
+//! every write creates a new key, which is deleted from the node and the archive
+//! once the retention period has passed.
+
+use crate::config::Config;
+use crate::error::{Result, ScribeError};
+use crate::storage::archival::TieringPolicy;
+use crate::storage::segment::Segment;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Serialize;
+use std::io::Write;
+
+/// Bytes a Raft log entry adds to the key and value of a put: the bincode log ID
+/// (term, leader node and index), payload and request tags, key and value lengths,
+/// and the 8-byte sled key of the entry
+pub const LOG_ENTRY_OVERHEAD: u64 = 56;
+
+/// Bytes a snapshot adds to the key and value of each live key: length prefixes of
+/// the key and value, and the key's change record
+pub const SNAPSHOT_ENTRY_OVERHEAD: u64 = 41;
+
+/// Copies of each key the state machine holds in memory (value map, change map,
+/// change record and time index)
+pub const KEY_COPIES_IN_MEMORY: u64 = 4;
+
+/// Estimated bytes the state machine adds per live key beyond its key copies and
+/// value: map and index slots, vector headers and allocator overhead
+pub const MEMORY_OVERHEAD_PER_KEY: u64 = 256;
+
+/// Number of sample entries segments are measured with
+const SAMPLE_ENTRIES: usize = 1_000;
+
+const SECS_PER_DAY: u64 = 86_400;
+
+/// Steady write workload to plan for
+#[derive(Debug, Clone, Serialize)]
+pub struct Workload {
+    /// Sustained writes per second
+    pub writes_per_sec: f64,
+    /// Average key size in bytes
+    pub avg_key_size: u64,
+    /// Average value size in bytes
+    pub avg_value_size: u64,
+    /// Days each write is kept
+    pub retention_days: u64,
+}
+
+/// Storage settings of the simulated node
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeLayout {
+    /// Segment size threshold in bytes
+    pub segment_size: u64,
+    /// Age in seconds at which segments are archived to S3
+    pub tiering_age_secs: u64,
+    /// gzip level of archived segments
+    pub compression_level: u32,
+    /// Raft log entries kept at most between snapshots
+    pub log_entries_kept: u64,
+    /// Whether flushed segments are kept on disk rather than in memory
+    pub mmap_segments: bool,
+}
+
+impl NodeLayout {
+    /// Layout of a node running with `config` and the default tiering policy
+    pub fn from_config(config: &Config) -> Self {
+        let tiering = TieringPolicy::default();
+        Self {
+            segment_size: config.storage.segment_size as u64,
+            tiering_age_secs: tiering.age_threshold_secs,
+            compression_level: tiering.compression_level,
+            log_entries_kept: config.consensus.snapshot_logs_since_last
+                + config.consensus.max_in_snapshot_log_to_keep,
+            mmap_segments: config.storage.mmap_segments,
+        }
+    }
+}
+
+/// Measured storage cost of a single write
+#[derive(Debug, Clone, Serialize)]
+pub struct CostModel {
+    /// Bytes of the write's Raft log entry
+    pub log_entry_bytes: u64,
+    /// Bytes of the write's entry in a segment
+    pub segment_entry_bytes: u64,
+    /// Fixed bytes of each segment
+    pub segment_overhead_bytes: u64,
+    /// Size of an archived segment over its uncompressed size
+    pub compression_ratio: f64,
+    /// Bytes of the write's key in a snapshot
+    pub snapshot_entry_bytes: u64,
+    /// Bytes the state machine holds in memory for the write's key
+    pub memory_bytes_per_key: u64,
+}
+
+impl CostModel {
+    /// Measure the cost of `workload`'s writes, compressing segments of `samples`
+    /// (representative values), or of synthetic JSON values if none are given
+    pub fn measure(workload: &Workload, samples: &[Vec<u8>], layout: &NodeLayout) -> Result<Self> {
+        let mut rng = fastrand::Rng::with_seed(SAMPLE_ENTRIES as u64);
+
+        let mut segment = Segment::new(0);
+        let segment_overhead = segment.serialize()?.len() as u64;
+        for i in 0..SAMPLE_ENTRIES {
+            let value = match samples {
+                [] => synthetic_value(i, workload.avg_value_size as usize, &mut rng),
+                samples => samples[i % samples.len()].clone(),
+            };
+            segment.put(sample_key(i, workload.avg_key_size as usize), value);
+        }
+        let encoded = segment.serialize()?;
+        let entry_overhead =
+            (encoded.len() as u64 - segment_overhead - segment.size as u64) / SAMPLE_ENTRIES as u64;
+
+        let compress = || {
+            let mut encoder =
+                GzEncoder::new(Vec::new(), Compression::new(layout.compression_level));
+            encoder.write_all(&encoded)?;
+            encoder.finish()
+        };
+        let compressed =
+            compress().map_err(|e| ScribeError::Other(format!("Compression error: {}", e)))?;
+
+        let (key_size, value_size) = (workload.avg_key_size, workload.avg_value_size);
+        Ok(Self {
+            log_entry_bytes: key_size + value_size + LOG_ENTRY_OVERHEAD,
+            segment_entry_bytes: key_size + value_size + entry_overhead,
+            segment_overhead_bytes: segment_overhead,
+            compression_ratio: compressed.len() as f64 / encoded.len() as f64,
+            snapshot_entry_bytes: 2 * key_size + value_size + SNAPSHOT_ENTRY_OVERHEAD,
+            memory_bytes_per_key: KEY_COPIES_IN_MEMORY * key_size
+                + value_size
+                + MEMORY_OVERHEAD_PER_KEY,
+        })
+    }
+}
+
+/// Sample key `i`, zero-padded to `size` bytes
+fn sample_key(i: usize, size: usize) -> Vec<u8> {
+    format!("{:0size$}", i, size = size).into_bytes()
+}
+
+/// Synthetic JSON document of about `size` bytes: a few typed fields and a random
+/// hex payload, standing in for application values
+fn synthetic_value(i: usize, size: usize, rng: &mut fastrand::Rng) -> Vec<u8> {
+    let mut value = format!(
+        "{{\"id\":{},\"ts\":{},\"kind\":\"event\",\"payload\":\"",
+        i,
+        1_760_000_000_000u64 + i as u64
+    );
+    while value.len() + 2 < size {
+        value.push(char::from_digit(rng.u32(0..16), 16).unwrap_or('0'));
+    }
+    value.push_str("\"}");
+    value.into_bytes()
+}
+
+/// Projected usage of a node at one point in time
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Projection {
+    /// Days since the workload started
+    pub day: u64,
+    /// Keys within the retention period
+    pub live_keys: u64,
+    /// Raft log on disk
+    pub raft_log_bytes: u64,
+    /// Segments not yet archived to S3
+    pub local_segment_bytes: u64,
+    /// Local disk: the Raft log, plus local segments when they are memory-mapped
+    pub disk_bytes: u64,
+    /// Compressed segments in S3
+    pub s3_bytes: u64,
+    /// Steady-state memory: the state machine, plus local segments unless they are
+    /// memory-mapped
+    pub memory_bytes: u64,
+    /// Size of a snapshot sent to a new or lagging follower
+    pub snapshot_bytes: u64,
+    /// Memory while a snapshot is built, which copies the state and serializes it
+    pub peak_memory_bytes: u64,
+}
+
+/// Project usage under `workload` on day 1, every `step_days` days, and on day
+/// `days`
+pub fn simulate(
+    workload: &Workload,
+    layout: &NodeLayout,
+    costs: &CostModel,
+    days: u64,
+    step_days: u64,
+) -> Vec<Projection> {
+    let step_days = step_days.max(1);
+    let mut checkpoints: Vec<u64> = std::iter::once(1)
+        .chain((step_days..days).step_by(step_days as usize))
+        .filter(|day| *day < days)
+        .collect();
+    checkpoints.dedup();
+    checkpoints.push(days.max(1));
+
+    checkpoints
+        .into_iter()
+        .map(|day| project(workload, layout, costs, day))
+        .collect()
+}
+
+/// Usage on `day`
+fn project(workload: &Workload, layout: &NodeLayout, costs: &CostModel, day: u64) -> Projection {
+    let writes_in = |secs: u64| (workload.writes_per_sec * secs as f64).round() as u64;
+    let total_writes = writes_in(day * SECS_PER_DAY);
+    let live_keys = writes_in(day.min(workload.retention_days) * SECS_PER_DAY);
+
+    let raft_log_bytes = total_writes.min(layout.log_entries_kept) * costs.log_entry_bytes;
+
+    let segment_bytes = |writes: u64| {
+        let data = writes * costs.segment_entry_bytes;
+        data + data.div_ceil(layout.segment_size.max(1)) * costs.segment_overhead_bytes
+    };
+    let local_writes = live_keys.min(writes_in(layout.tiering_age_secs));
+    let local_segment_bytes = segment_bytes(local_writes);
+    let s3_bytes =
+        (segment_bytes(live_keys - local_writes) as f64 * costs.compression_ratio).round() as u64;
+
+    let (disk_bytes, memory_bytes) = match layout.mmap_segments {
+        true => (raft_log_bytes + local_segment_bytes, 0),
+        false => (raft_log_bytes, local_segment_bytes),
+    };
+    let memory_bytes = memory_bytes + live_keys * costs.memory_bytes_per_key;
+    let snapshot_bytes = live_keys * costs.snapshot_entry_bytes;
+
+    Projection {
+        day,
+        live_keys,
+        raft_log_bytes,
+        local_segment_bytes,
+        disk_bytes,
+        s3_bytes,
+        memory_bytes,
+        snapshot_bytes,
+        peak_memory_bytes: memory_bytes + 2 * snapshot_bytes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workload() -> Workload {
+        Workload {
+            writes_per_sec: 100.0,
+            avg_key_size: 32,
+            avg_value_size: 512,
+            retention_days: 30,
+        }
+    }
+
+    fn layout() -> NodeLayout {
+        NodeLayout::from_config(&Config::default_for_node(1))
+    }
+
+    #[test]
+    fn test_measure_costs() {
+        let costs = CostModel::measure(&workload(), &[], &layout()).unwrap();
+        // bincode length prefixes of the key and value
+        assert_eq!(costs.segment_entry_bytes, 32 + 512 + 16);
+        assert_eq!(costs.log_entry_bytes, 32 + 512 + LOG_ENTRY_OVERHEAD);
+        assert!(costs.compression_ratio > 0.0 && costs.compression_ratio < 1.0);
+
+        // Incompressible samples barely shrink
+        let mut rng = fastrand::Rng::with_seed(7);
+        let samples: Vec<Vec<u8>> = (0..SAMPLE_ENTRIES)
+            .map(|_| (0..512).map(|_| rng.u8(..)).collect())
+            .collect();
+        let random = CostModel::measure(&workload(), &samples, &layout()).unwrap();
+        assert!(random.compression_ratio > costs.compression_ratio);
+        assert!(random.compression_ratio > 0.9);
+    }
+
+    #[test]
+    fn test_simulate_levels_off_after_retention() {
+        let layout = layout();
+        let costs = CostModel::measure(&workload(), &[], &layout).unwrap();
+        let projections = simulate(&workload(), &layout, &costs, 90, 30);
+        let days: Vec<u64> = projections.iter().map(|p| p.day).collect();
+        assert_eq!(days, vec![1, 30, 60, 90]);
+
+        assert_eq!(projections[0].live_keys, 100 * SECS_PER_DAY);
+        assert_eq!(projections[1].live_keys, 30 * 100 * SECS_PER_DAY);
+        assert_eq!(
+            projections[1],
+            Projection {
+                day: 30,
+                ..projections[3].clone()
+            }
+        );
+        assert!(projections[0].s3_bytes < projections[1].s3_bytes);
+
+        // The Raft log is bounded by snapshots
+        assert_eq!(
+            projections[0].raft_log_bytes,
+            layout.log_entries_kept * costs.log_entry_bytes
+        );
+        // One hour of writes waits for archival
+        assert_eq!(
+            projections[0].local_segment_bytes / costs.segment_entry_bytes,
+            100 * 3600
+        );
+    }
+}
@@ -0,0 +1,539 @@
+//! Typed client SDK
+//!
+//! [`ScribeClient`] is the entry point for applications talking to a cluster over
+//! HTTP. It is configured once with [`ScribeClient::builder`] and then shared:
+//!
+//! - connections to every node are pooled and kept alive between requests
+//! - requests go to the node that answered last, and follow NotLeader redirects to
+//!   the leader, which later requests then go to directly
+//! - unreachable nodes, timeouts, server errors, overload and elections are retried
+//!   on the next node with exponential backoff and jitter, as the [`RetryPolicy`]
+//!   allows; conflicts and other final answers never are
+//! - values can be raw bytes, strings or any serde type stored as JSON
+//!
+//! [`BlockingScribeClient`] offers the same operations to code that does not run
+//! an async runtime. Locks, queues, sessions and the other coordination features
+//! are reached through [`ScribeClient::cluster`].
+//!
+//! ```no_run
+//! use hyra_scribe_ledger::client::ScribeClient;
+//!
+//! # async fn example() -> hyra_scribe_ledger::error::Result<()> {
+//! let client = ScribeClient::builder()
+//!     .endpoints(["http://node1:8001", "http://node2:8001"])
+//!     .build()?;
+//! client.put("user:alice", "Alice Smith").await?;
+//! let name = client.get_string("user:alice").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{Result, ScribeError};
+use crate::http_client::{ClientSession, ClusterClient};
+pub use crate::retry::{RetryEvent, RetryPolicy, RetryReason};
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::time::Duration;
+
+/// Default timeout of a single request
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default timeout of establishing a connection
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Default idle connections kept per node
+const DEFAULT_POOL_MAX_IDLE_PER_HOST: usize = 16;
+
+/// Default time an idle pooled connection is kept
+const DEFAULT_POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Configuration of a [`ScribeClient`]
+#[derive(Debug, Clone)]
+pub struct ScribeClientBuilder {
+    endpoints: Vec<String>,
+    request_timeout: Duration,
+    connect_timeout: Duration,
+    pool_max_idle_per_host: usize,
+    pool_idle_timeout: Duration,
+    retry: RetryPolicy,
+    session: Option<ClientSession>,
+}
+
+impl Default for ScribeClientBuilder {
+    fn default() -> Self {
+        Self {
+            endpoints: Vec::new(),
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            pool_max_idle_per_host: DEFAULT_POOL_MAX_IDLE_PER_HOST,
+            pool_idle_timeout: DEFAULT_POOL_IDLE_TIMEOUT,
+            retry: RetryPolicy::default(),
+            session: None,
+        }
+    }
+}
+
+impl ScribeClientBuilder {
+    /// Nodes to send requests to (e.g. "http://10.0.0.1:8001"); any subset of the
+    /// cluster works, as the client follows redirects to the leader
+    pub fn endpoints<I, S>(mut self, endpoints: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.endpoints = endpoints.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Timeout of a single request, per attempt (default: 5s)
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Timeout of establishing a connection (default: 2s)
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Idle connections kept open per node (default: 16)
+    pub fn pool_max_idle_per_host(mut self, connections: usize) -> Self {
+        self.pool_max_idle_per_host = connections;
+        self
+    }
+
+    /// How long an idle connection is kept open (default: 90s)
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = timeout;
+        self
+    }
+
+    /// Attempts a request makes before failing (default: 8)
+    pub fn max_attempts(mut self, attempts: usize) -> Self {
+        self.retry = self.retry.with_max_attempts(attempts);
+        self
+    }
+
+    /// Pause before the first retry, doubled after every further failure up to
+    /// `max` (default: 50ms up to 2s)
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.retry = self.retry.with_backoff(initial, max);
+        self
+    }
+
+    /// Retry failed requests as `policy` says, replacing the attempts and backoff
+    /// set so far (default: [`RetryPolicy::new`])
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Call `observer` before every retry, e.g. to log it or count it in metrics
+    pub fn on_retry<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(&RetryEvent) + Send + Sync + 'static,
+    {
+        self.retry = self.retry.on_retry(observer);
+        self
+    }
+
+    /// Make reads observe the client's own writes on whichever node serves them
+    pub fn session(mut self, session: ClientSession) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    /// Create the client
+    pub fn build(self) -> Result<ScribeClient> {
+        if self.retry.max_attempts() == 0 {
+            return Err(ScribeError::Configuration(
+                "max_attempts must be at least 1".to_string(),
+            ));
+        }
+        // Redirects are followed by the cluster client, which learns the leader
+        let http = Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .timeout(self.request_timeout)
+            .connect_timeout(self.connect_timeout)
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .tcp_nodelay(true)
+            .build()
+            .map_err(|e| ScribeError::Network(format!("Failed to create HTTP client: {}", e)))?;
+        let mut cluster = ClusterClient::new(self.endpoints)?
+            .with_http_client(http)
+            .with_retry_policy(self.retry);
+        if let Some(session) = self.session {
+            cluster = cluster.with_session(session);
+        }
+        Ok(ScribeClient { cluster })
+    }
+
+    /// Create a client for code without an async runtime
+    pub fn build_blocking(self) -> Result<BlockingScribeClient> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| ScribeError::Other(format!("Failed to start client runtime: {}", e)))?;
+        // The HTTP client must be created inside the runtime it runs on
+        let client = runtime.block_on(async { self.build() })?;
+        Ok(BlockingScribeClient { runtime, client })
+    }
+}
+
+/// Async client for a Scribe Ledger cluster
+///
+/// Cheap to clone; clones share the connection pool and the leader they learned.
+#[derive(Clone)]
+pub struct ScribeClient {
+    cluster: ClusterClient,
+}
+
+impl ScribeClient {
+    /// Start configuring a client
+    pub fn builder() -> ScribeClientBuilder {
+        ScribeClientBuilder::default()
+    }
+
+    /// Create a client for `endpoints` with the default settings
+    pub fn new<I, S>(endpoints: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self::builder().endpoints(endpoints).build()
+    }
+
+    /// The underlying cluster client, for locks, queues, sessions, watches and
+    /// conditional reads
+    pub fn cluster(&self) -> &ClusterClient {
+        &self.cluster
+    }
+
+    /// Store `value` at `key`
+    pub async fn put(&self, key: impl AsRef<[u8]>, value: impl Into<Vec<u8>>) -> Result<()> {
+        self.cluster.put(key.as_ref(), value.into()).await
+    }
+
+    /// Get the value of `key`, `None` if it does not exist
+    pub async fn get(&self, key: impl AsRef<[u8]>) -> Result<Option<Vec<u8>>> {
+        self.cluster.get(key.as_ref()).await
+    }
+
+    /// Get the value of `key` as UTF-8 text
+    pub async fn get_string(&self, key: impl AsRef<[u8]>) -> Result<Option<String>> {
+        self.get(key)
+            .await?
+            .map(|value| {
+                String::from_utf8(value)
+                    .map_err(|e| ScribeError::Serialization(format!("Value is not UTF-8: {}", e)))
+            })
+            .transpose()
+    }
+
+    /// Store `value` at `key` as JSON
+    pub async fn put_json<T: Serialize>(&self, key: impl AsRef<[u8]>, value: &T) -> Result<()> {
+        self.put(key, encode_json(value)?).await
+    }
+
+    /// Get the value of `key` decoded from JSON
+    pub async fn get_json<T: DeserializeOwned>(&self, key: impl AsRef<[u8]>) -> Result<Option<T>> {
+        self.get(key)
+            .await?
+            .map(|value| decode_json(&value))
+            .transpose()
+    }
+
+    /// Delete `key`
+    pub async fn delete(&self, key: impl AsRef<[u8]>) -> Result<()> {
+        self.cluster.delete(key.as_ref()).await
+    }
+
+    /// Store `value` unless `key` exists; returns whether it was stored
+    pub async fn put_if_absent(
+        &self,
+        key: impl AsRef<[u8]>,
+        value: impl Into<Vec<u8>>,
+    ) -> Result<bool> {
+        self.cluster.put_if_absent(key.as_ref(), value.into()).await
+    }
+
+    /// Replace the value of `key` with `new` (`None`: delete it) if it still holds
+    /// `expected`; returns whether the swap took place
+    pub async fn compare_and_swap(
+        &self,
+        key: impl AsRef<[u8]>,
+        expected: impl AsRef<[u8]>,
+        new: Option<Vec<u8>>,
+    ) -> Result<bool> {
+        self.cluster
+            .compare_and_swap(key.as_ref(), expected.as_ref(), new)
+            .await
+    }
+}
+
+/// Blocking client for a Scribe Ledger cluster
+///
+/// Runs a [`ScribeClient`] on a runtime of its own. Calling it from within an async
+/// runtime panics; use [`ScribeClient`] there.
+pub struct BlockingScribeClient {
+    runtime: tokio::runtime::Runtime,
+    client: ScribeClient,
+}
+
+impl BlockingScribeClient {
+    /// Create a client for `endpoints` with the default settings
+    pub fn new<I, S>(endpoints: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        ScribeClient::builder()
+            .endpoints(endpoints)
+            .build_blocking()
+    }
+
+    /// Store `value` at `key`
+    pub fn put(&self, key: impl AsRef<[u8]>, value: impl Into<Vec<u8>>) -> Result<()> {
+        self.runtime.block_on(self.client.put(key, value))
+    }
+
+    /// Get the value of `key`, `None` if it does not exist
+    pub fn get(&self, key: impl AsRef<[u8]>) -> Result<Option<Vec<u8>>> {
+        self.runtime.block_on(self.client.get(key))
+    }
+
+    /// Get the value of `key` as UTF-8 text
+    pub fn get_string(&self, key: impl AsRef<[u8]>) -> Result<Option<String>> {
+        self.runtime.block_on(self.client.get_string(key))
+    }
+
+    /// Store `value` at `key` as JSON
+    pub fn put_json<T: Serialize>(&self, key: impl AsRef<[u8]>, value: &T) -> Result<()> {
+        self.runtime.block_on(self.client.put_json(key, value))
+    }
+
+    /// Get the value of `key` decoded from JSON
+    pub fn get_json<T: DeserializeOwned>(&self, key: impl AsRef<[u8]>) -> Result<Option<T>> {
+        self.runtime.block_on(self.client.get_json(key))
+    }
+
+    /// Delete `key`
+    pub fn delete(&self, key: impl AsRef<[u8]>) -> Result<()> {
+        self.runtime.block_on(self.client.delete(key))
+    }
+
+    /// Store `value` unless `key` exists; returns whether it was stored
+    pub fn put_if_absent(&self, key: impl AsRef<[u8]>, value: impl Into<Vec<u8>>) -> Result<bool> {
+        self.runtime.block_on(self.client.put_if_absent(key, value))
+    }
+
+    /// Replace the value of `key` with `new` (`None`: delete it) if it still holds
+    /// `expected`; returns whether the swap took place
+    pub fn compare_and_swap(
+        &self,
+        key: impl AsRef<[u8]>,
+        expected: impl AsRef<[u8]>,
+        new: Option<Vec<u8>>,
+    ) -> Result<bool> {
+        self.runtime
+            .block_on(self.client.compare_and_swap(key, expected, new))
+    }
+
+    /// The async client this client runs
+    pub fn async_client(&self) -> &ScribeClient {
+        &self.client
+    }
+}
+
+fn encode_json<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    serde_json::to_vec(value)
+        .map_err(|e| ScribeError::Serialization(format!("Failed to encode value: {}", e)))
+}
+
+fn decode_json<T: DeserializeOwned>(value: &[u8]) -> Result<T> {
+    serde_json::from_slice(value)
+        .map_err(|e| ScribeError::Serialization(format!("Invalid JSON value: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Bytes;
+    use axum::extract::{Path, State};
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use axum::Router;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    type Store = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+    /// Serve a single-node key-value API on a random port
+    async fn serve(store: Store) -> String {
+        async fn get_key(
+            State(store): State<Store>,
+            Path(key): Path<String>,
+        ) -> (StatusCode, Vec<u8>) {
+            match store.lock().unwrap().get(&key) {
+                Some(value) => (StatusCode::OK, value.clone()),
+                None => (StatusCode::NOT_FOUND, Vec::new()),
+            }
+        }
+        async fn put_key(State(store): State<Store>, Path(key): Path<String>, body: Bytes) {
+            store.lock().unwrap().insert(key, body.to_vec());
+        }
+        async fn delete_key(State(store): State<Store>, Path(key): Path<String>) {
+            store.lock().unwrap().remove(&key);
+        }
+
+        let app = Router::new()
+            .route("/:key", get(get_key).put(put_key).delete(delete_key))
+            .with_state(store);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{}", addr)
+    }
+
+    #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+    struct Profile {
+        name: String,
+        age: u32,
+    }
+
+    #[test]
+    fn test_builder_validates() {
+        assert!(ScribeClient::builder().build().is_err());
+        assert!(ScribeClient::builder()
+            .endpoints(["http://10.0.0.1:8001"])
+            .max_attempts(0)
+            .build()
+            .is_err());
+
+        let client = ScribeClient::builder()
+            .endpoints(["http://10.0.0.1:8001", "http://10.0.0.2:8001"])
+            .session(ClientSession::resume(42))
+            .build()
+            .unwrap();
+        assert_eq!(client.cluster().endpoints().len(), 2);
+        assert_eq!(client.cluster().session().unwrap().token(), 42);
+    }
+
+    #[tokio::test]
+    async fn test_typed_operations() {
+        let store = Store::default();
+        let endpoint = serve(store.clone()).await;
+        // The first node is unreachable; the client moves on to the second
+        let client = ScribeClient::builder()
+            .endpoints(["http://127.0.0.1:1".to_string(), endpoint])
+            .backoff(Duration::from_millis(1), Duration::from_millis(5))
+            .build()
+            .unwrap();
+
+        client.put("greeting", "hello").await.unwrap();
+        assert_eq!(
+            client.get_string("greeting").await.unwrap().as_deref(),
+            Some("hello")
+        );
+
+        let alice = Profile {
+            name: "Alice".to_string(),
+            age: 30,
+        };
+        client.put_json("user:alice", &alice).await.unwrap();
+        assert_eq!(
+            client.get_json::<Profile>("user:alice").await.unwrap(),
+            Some(alice)
+        );
+        assert!(client.get_json::<Profile>("greeting").await.is_err());
+
+        client.delete("greeting").await.unwrap();
+        assert_eq!(client.get("greeting").await.unwrap(), None);
+        assert_eq!(store.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_retry_policy() {
+        // Answers 503 to the first two reads and 409 to every write
+        let reads = Arc::new(Mutex::new(0));
+        let writes = Arc::new(Mutex::new(0));
+        let app = Router::new().route(
+            "/:key",
+            get({
+                let reads = reads.clone();
+                move || async move {
+                    let mut reads = reads.lock().unwrap();
+                    *reads += 1;
+                    match *reads {
+                        1 | 2 => (
+                            StatusCode::SERVICE_UNAVAILABLE,
+                            [("retry-after", "0")],
+                            Vec::new(),
+                        ),
+                        _ => (StatusCode::OK, [("retry-after", "0")], b"v".to_vec()),
+                    }
+                }
+            })
+            .put({
+                let writes = writes.clone();
+                move || async move {
+                    *writes.lock().unwrap() += 1;
+                    StatusCode::CONFLICT
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let retries = Arc::new(Mutex::new(Vec::new()));
+        let client = ScribeClient::builder()
+            .endpoints([endpoint.clone()])
+            .backoff(Duration::from_millis(1), Duration::from_millis(5))
+            .on_retry({
+                let retries = retries.clone();
+                move |event| retries.lock().unwrap().push((event.attempt, event.reason))
+            })
+            .build()
+            .unwrap();
+        assert_eq!(client.get("k").await.unwrap(), Some(b"v".to_vec()));
+        assert_eq!(
+            *retries.lock().unwrap(),
+            vec![
+                (1, RetryReason::Backpressure),
+                (2, RetryReason::Backpressure)
+            ]
+        );
+
+        // Conflicts are never retried
+        assert!(client.put("k", "v").await.is_err());
+        assert_eq!(*writes.lock().unwrap(), 1);
+        assert_eq!(retries.lock().unwrap().len(), 2);
+
+        // Reasons the policy leaves out fail on the first attempt
+        *reads.lock().unwrap() = 0;
+        let client = ScribeClient::builder()
+            .endpoints([endpoint])
+            .retry_policy(RetryPolicy::new().with_retry(RetryReason::Backpressure, false))
+            .build()
+            .unwrap();
+        assert!(client.get("k").await.is_err());
+        assert_eq!(*reads.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_blocking_client() {
+        let store = Store::default();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let endpoint = runtime.block_on(serve(store.clone()));
+
+        let client = BlockingScribeClient::new([endpoint]).unwrap();
+        client.put("count", "1").unwrap();
+        assert_eq!(client.get("count").unwrap(), Some(b"1".to_vec()));
+        assert_eq!(client.get_string("missing").unwrap(), None);
+        assert_eq!(store.lock().unwrap().get("count"), Some(&b"1".to_vec()));
+    }
+}
@@ -0,0 +1,499 @@
+//! Client-side read cache invalidated through the change stream
+//!
+//! [`CachingClient`] wraps a [`ClusterClient`] and keeps recently read values in the
+//! application process. A background task follows a node's change stream
+//! (`GET /events`, server-sent events) and drops keys as soon as they change, so hot
+//! keys are served at local latency.
+//!
+//! Staleness is bounded: an entry is served from memory only while the change stream
+//! is connected and for at most `max_staleness` after it was last validated. Past that,
+//! or while the stream is down, reads revalidate with a conditional GET
+//! (`If-None-Match`), which costs a round trip but no value transfer when nothing
+//! changed. The bound also covers events the stream's node applies late or drops
+//! for a lagging subscriber.
+
+use crate::error::{Result, ScribeError};
+use crate::http_client::{ClusterClient, ConditionalGet};
+use lru::LruCache;
+use reqwest::header::ACCEPT;
+use reqwest::Client;
+use serde::Deserialize;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Default number of cached values
+const DEFAULT_CAPACITY: usize = 1000;
+
+/// Default upper bound on how long a value is served without revalidation
+const DEFAULT_MAX_STALENESS: Duration = Duration::from_secs(10);
+
+/// Pause before reconnecting a dropped change stream
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+
+/// Timeout for establishing the change stream connection
+const STREAM_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A silent change stream is considered dead after this long
+///
+/// Nodes send a keep-alive comment every 15 seconds.
+const STREAM_IDLE_TIMEOUT: Duration = Duration::from_secs(45);
+
+/// Read statistics of a [`CachingClient`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Reads served from memory
+    pub hits: u64,
+    /// Reads confirmed unchanged by the server (`304 Not Modified`)
+    pub revalidations: u64,
+    /// Reads that transferred the value
+    pub misses: u64,
+    /// Cached values dropped because the key changed
+    pub invalidations: u64,
+}
+
+struct CachedValue {
+    value: Vec<u8>,
+    etag: Option<String>,
+    validated_at: Instant,
+}
+
+/// What a read has to do for a key
+#[derive(Debug, PartialEq, Eq)]
+enum Lookup {
+    /// Serve the cached value
+    Hit(Vec<u8>),
+    /// Ask the server whether the cached value (with this entity tag) is current
+    Revalidate(Option<String>),
+    /// Fetch the value
+    Miss,
+}
+
+struct CacheState {
+    entries: LruCache<Vec<u8>, CachedValue>,
+    /// Bumped on every invalidation; a read only fills the cache if it did not move
+    /// while the request was in flight
+    generation: u64,
+    /// Whether the change stream is connected
+    subscribed: bool,
+    stats: CacheStats,
+}
+
+impl CacheState {
+    fn new(capacity: usize) -> Self {
+        Self {
+            entries: LruCache::new(NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN)),
+            generation: 0,
+            subscribed: false,
+            stats: CacheStats::default(),
+        }
+    }
+
+    fn lookup(&mut self, key: &[u8], max_staleness: Duration) -> Lookup {
+        let subscribed = self.subscribed;
+        match self.entries.get(key) {
+            Some(entry) if subscribed && entry.validated_at.elapsed() < max_staleness => {
+                Lookup::Hit(entry.value.clone())
+            }
+            Some(entry) => Lookup::Revalidate(entry.etag.clone()),
+            None => Lookup::Miss,
+        }
+    }
+
+    /// Store a fetched value unless the cache was invalidated since `generation`
+    fn fill(&mut self, key: &[u8], value: Vec<u8>, etag: Option<String>, generation: u64) {
+        if self.generation != generation {
+            return;
+        }
+        self.entries.put(
+            key.to_vec(),
+            CachedValue {
+                value,
+                etag,
+                validated_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Mark the cached value current again if it still carries `etag`
+    fn revalidated(&mut self, key: &[u8], etag: &str) -> Option<Vec<u8>> {
+        let entry = self.entries.get_mut(key)?;
+        if entry.etag.as_deref() != Some(etag) {
+            return None;
+        }
+        entry.validated_at = Instant::now();
+        Some(entry.value.clone())
+    }
+
+    fn invalidate(&mut self, key: &[u8]) {
+        self.generation += 1;
+        if self.entries.pop(key).is_some() {
+            self.stats.invalidations += 1;
+        }
+    }
+
+    fn invalidate_all(&mut self) {
+        self.generation += 1;
+        self.entries.clear();
+    }
+}
+
+/// [`ClusterClient`] wrapper that caches reads and invalidates them from the change stream
+///
+/// Writes made through this client invalidate the key locally as well, so a process
+/// always reads its own writes.
+pub struct CachingClient {
+    client: ClusterClient,
+    state: Arc<Mutex<CacheState>>,
+    max_staleness: Duration,
+    watcher: JoinHandle<()>,
+}
+
+impl CachingClient {
+    /// Wrap `client` and start following the change stream of its nodes
+    ///
+    /// Must be called within a Tokio runtime.
+    pub fn new(client: ClusterClient) -> Self {
+        let state = Arc::new(Mutex::new(CacheState::new(DEFAULT_CAPACITY)));
+        let watcher = tokio::spawn(follow_changes(
+            client.endpoints().to_vec(),
+            Arc::clone(&state),
+        ));
+
+        Self {
+            client,
+            state,
+            max_staleness: DEFAULT_MAX_STALENESS,
+            watcher,
+        }
+    }
+
+    /// Set how many values are cached (least recently used are evicted first)
+    pub fn with_capacity(self, capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        self.state.lock().unwrap().entries.resize(capacity);
+        self
+    }
+
+    /// Set how long a value may be served without asking the server
+    pub fn with_max_staleness(mut self, max_staleness: Duration) -> Self {
+        self.max_staleness = max_staleness;
+        self
+    }
+
+    /// The wrapped client
+    pub fn inner(&self) -> &ClusterClient {
+        &self.client
+    }
+
+    /// Read a value, from memory when the cached copy is known to be current
+    pub async fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let (lookup, generation) = {
+            let mut state = self.state.lock().unwrap();
+            let lookup = state.lookup(key, self.max_staleness);
+            if let Lookup::Hit(value) = lookup {
+                state.stats.hits += 1;
+                return Ok(Some(value));
+            }
+            (lookup, state.generation)
+        };
+
+        let etag = match &lookup {
+            Lookup::Revalidate(etag) => etag.as_deref(),
+            _ => None,
+        };
+        match self.client.get_conditional(key, etag).await? {
+            ConditionalGet::NotModified => {
+                {
+                    let mut state = self.state.lock().unwrap();
+                    if let Some(value) = state.revalidated(key, etag.unwrap_or_default()) {
+                        state.stats.revalidations += 1;
+                        return Ok(Some(value));
+                    }
+                }
+                // Evicted or invalidated while the request was in flight
+                self.fetch(key).await
+            }
+            ConditionalGet::Found { value, etag } => {
+                let mut state = self.state.lock().unwrap();
+                state.stats.misses += 1;
+                state.fill(key, value.clone(), etag, generation);
+                Ok(Some(value))
+            }
+            ConditionalGet::NotFound => {
+                self.state.lock().unwrap().entries.pop(key);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Fetch a value unconditionally and cache it
+    async fn fetch(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let generation = self.state.lock().unwrap().generation;
+        match self.client.get_conditional(key, None).await? {
+            ConditionalGet::Found { value, etag } => {
+                let mut state = self.state.lock().unwrap();
+                state.stats.misses += 1;
+                state.fill(key, value.clone(), etag, generation);
+                Ok(Some(value))
+            }
+            ConditionalGet::NotFound => Ok(None),
+            ConditionalGet::NotModified => Err(ScribeError::Network(
+                "Unconditional read returned 304 Not Modified".to_string(),
+            )),
+        }
+    }
+
+    /// Store a value
+    pub async fn put(&self, key: &[u8], value: Vec<u8>) -> Result<()> {
+        let result = self.client.put(key, value).await;
+        self.invalidate(key);
+        result
+    }
+
+    /// Delete a value
+    pub async fn delete(&self, key: &[u8]) -> Result<()> {
+        let result = self.client.delete(key).await;
+        self.invalidate(key);
+        result
+    }
+
+    /// Drop the cached value of `key`
+    pub fn invalidate(&self, key: &[u8]) {
+        self.state.lock().unwrap().invalidate(key);
+    }
+
+    /// Drop every cached value
+    pub fn clear(&self) {
+        self.state.lock().unwrap().invalidate_all();
+    }
+
+    /// Whether the change stream is connected, i.e. reads may be served from memory
+    pub fn is_subscribed(&self) -> bool {
+        self.state.lock().unwrap().subscribed
+    }
+
+    /// Number of cached values
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+
+    /// Check if nothing is cached
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Read statistics since the client was created
+    pub fn stats(&self) -> CacheStats {
+        self.state.lock().unwrap().stats
+    }
+}
+
+impl Drop for CachingClient {
+    fn drop(&mut self) {
+        self.watcher.abort();
+    }
+}
+
+/// Follow the change stream, moving to the next node whenever it drops
+async fn follow_changes(endpoints: Vec<String>, state: Arc<Mutex<CacheState>>) {
+    let client = match Client::builder()
+        .connect_timeout(STREAM_CONNECT_TIMEOUT)
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("Failed to create change stream client: {}", e);
+            return;
+        }
+    };
+
+    for index in 0.. {
+        let url = format!(
+            "{}/events",
+            endpoints[index % endpoints.len()].trim_end_matches('/')
+        );
+        match stream_changes(&client, &url, &state).await {
+            Ok(()) => debug!("Change stream {} closed", url),
+            Err(e) => warn!("Change stream {} failed: {}", url, e),
+        }
+        state.lock().unwrap().subscribed = false;
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+/// Apply the events of one change stream connection to the cache until it ends
+async fn stream_changes(client: &Client, url: &str, state: &Mutex<CacheState>) -> Result<()> {
+    let mut response = client
+        .get(url)
+        .header(ACCEPT, "text/event-stream")
+        .send()
+        .await
+        .map_err(|e| ScribeError::Network(e.to_string()))?;
+    if !response.status().is_success() {
+        return Err(ScribeError::Network(format!(
+            "Change stream returned {}",
+            response.status()
+        )));
+    }
+
+    // Changes made before the subscription started were missed
+    {
+        let mut state = state.lock().unwrap();
+        state.invalidate_all();
+        state.subscribed = true;
+    }
+
+    let mut parser = EventStreamParser::default();
+    loop {
+        let chunk = tokio::time::timeout(STREAM_IDLE_TIMEOUT, response.chunk())
+            .await
+            .map_err(|_| ScribeError::Timeout("Change stream went silent".to_string()))?
+            .map_err(|e| ScribeError::Network(e.to_string()))?;
+        let Some(chunk) = chunk else {
+            return Ok(());
+        };
+
+        let keys = parser.feed(&chunk);
+        if !keys.is_empty() {
+            let mut state = state.lock().unwrap();
+            for key in keys {
+                state.invalidate(&key);
+            }
+        }
+    }
+}
+
+/// `data` payload of a change event
+#[derive(Deserialize)]
+struct StreamedChange {
+    key: String,
+}
+
+/// Incremental parser extracting changed keys from a server-sent event stream
+#[derive(Default)]
+struct EventStreamParser {
+    buffer: Vec<u8>,
+}
+
+impl EventStreamParser {
+    /// Feed received bytes, returning the keys of every complete change event
+    fn feed(&mut self, chunk: &[u8]) -> Vec<Vec<u8>> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut keys = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let Some(data) = line.trim_end().strip_prefix("data:") else {
+                continue;
+            };
+            match serde_json::from_str::<StreamedChange>(data.trim_start()) {
+                Ok(change) => keys.push(change.key.into_bytes()),
+                Err(e) => debug!("Ignoring unparseable change event: {}", e),
+            }
+        }
+        keys
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_stream_parser_handles_split_chunks() {
+        let mut parser = EventStreamParser::default();
+
+        assert!(parser.feed(b"event: put\ndata: {\"key\":\"al").is_empty());
+        assert_eq!(
+            parser.feed(b"pha\",\"kind\":\"put\",\"timestamp\":1}\n\n"),
+            vec![b"alpha".to_vec()]
+        );
+
+        // Keep-alive comments and unknown payloads are ignored
+        let keys = parser.feed(
+            b": keep-alive\n\ndata: not json\n\nevent: delete\r\ndata:{\"key\":\"beta\"}\r\n\r\n",
+        );
+        assert_eq!(keys, vec![b"beta".to_vec()]);
+    }
+
+    #[test]
+    fn test_lookup_requires_subscription_and_freshness() {
+        let mut state = CacheState::new(10);
+        assert_eq!(state.lookup(b"key", Duration::from_secs(60)), Lookup::Miss);
+
+        state.fill(b"key", b"value".to_vec(), Some("\"tag\"".to_string()), 0);
+        // Without a change stream, changes could go unnoticed
+        assert_eq!(
+            state.lookup(b"key", Duration::from_secs(60)),
+            Lookup::Revalidate(Some("\"tag\"".to_string()))
+        );
+
+        state.subscribed = true;
+        assert_eq!(
+            state.lookup(b"key", Duration::from_secs(60)),
+            Lookup::Hit(b"value".to_vec())
+        );
+        assert_eq!(
+            state.lookup(b"key", Duration::ZERO),
+            Lookup::Revalidate(Some("\"tag\"".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_invalidation_discards_in_flight_reads() {
+        let mut state = CacheState::new(10);
+        state.subscribed = true;
+        state.fill(b"key", b"old".to_vec(), None, 0);
+
+        // A read starts, then the key changes before its response arrives
+        let generation = state.generation;
+        state.invalidate(b"key");
+        state.fill(b"key", b"old".to_vec(), None, generation);
+
+        assert_eq!(state.lookup(b"key", Duration::from_secs(60)), Lookup::Miss);
+        assert_eq!(state.stats.invalidations, 1);
+    }
+
+    #[test]
+    fn test_revalidated_requires_matching_etag() {
+        let mut state = CacheState::new(10);
+        state.fill(b"key", b"value".to_vec(), Some("\"a\"".to_string()), 0);
+
+        assert_eq!(state.revalidated(b"key", "\"b\""), None);
+        assert_eq!(state.revalidated(b"key", "\"a\""), Some(b"value".to_vec()));
+        assert_eq!(state.revalidated(b"missing", "\"a\""), None);
+    }
+
+    #[test]
+    fn test_capacity_evicts_least_recently_used() {
+        let mut state = CacheState::new(2);
+        state.subscribed = true;
+        state.fill(b"a", b"1".to_vec(), None, 0);
+        state.fill(b"b", b"2".to_vec(), None, 0);
+        state.lookup(b"a", Duration::from_secs(60));
+        state.fill(b"c", b"3".to_vec(), None, 0);
+
+        assert_eq!(state.lookup(b"b", Duration::from_secs(60)), Lookup::Miss);
+        assert_eq!(
+            state.lookup(b"a", Duration::from_secs(60)),
+            Lookup::Hit(b"1".to_vec())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_caching_client_unreachable_cluster() {
+        let client = ClusterClient::new(vec!["http://127.0.0.1:1".to_string()])
+            .unwrap()
+            .with_retries(1, Duration::from_millis(1));
+        let cached = CachingClient::new(client).with_capacity(5);
+
+        assert!(cached.get(b"key").await.is_err());
+        assert!(!cached.is_subscribed());
+        assert!(cached.is_empty());
+        assert_eq!(cached.stats(), CacheStats::default());
+    }
+}
@@ -0,0 +1,299 @@
+//! Clock skew between peers
+//!
+//! Manifest timestamps, TTLs and leases compare the wall clocks of different nodes.
+//! Every discovery heartbeat carries the sender's clock, and the receiver records
+//! the difference to its own in [`ClockSkewMonitor`]. The median of the last
+//! [`SKEW_SAMPLES`] differences is the peer's estimated offset; it includes the
+//! one-way delay of the heartbeat, a few milliseconds on a LAN. Offsets are exported
+//! as `scribe_ledger_clock_skew_milliseconds` by peer and served at
+//! `GET /cluster/clock`. A warning is logged once a peer is off by more than
+//! `warn_skew_ms`, an error once it is off by more than `max_skew_ms`.
+//!
+//! `max_skew_ms` is the skew leases and TTLs allow for: a lock or queue lease is
+//! taken over only once it ran out that long ago on the clock of the node taking it,
+//! and the leader expires a key only that long after its TTL elapsed on its own
+//! clock, so a clock running ahead never ends a lease or TTL early.
+
+use crate::metrics::{CLOCK_SKEW, CLOCK_SKEW_MAX};
+use crate::types::NodeId;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// Heartbeats the offset of a peer is the median of
+pub const SKEW_SAMPLES: usize = 9;
+
+/// Clock skew configuration (`[clock]`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClockConfig {
+    /// Skew between the clocks of any two nodes that leases and TTLs allow for,
+    /// in milliseconds
+    #[serde(default = "default_max_skew_ms")]
+    pub max_skew_ms: u64,
+    /// Offset of a peer's clock above which a warning is logged, in milliseconds
+    #[serde(default = "default_warn_skew_ms")]
+    pub warn_skew_ms: u64,
+}
+
+fn default_max_skew_ms() -> u64 {
+    500
+}
+
+fn default_warn_skew_ms() -> u64 {
+    100
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        Self {
+            max_skew_ms: default_max_skew_ms(),
+            warn_skew_ms: default_warn_skew_ms(),
+        }
+    }
+}
+
+impl ClockConfig {
+    /// Check the warning threshold
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.warn_skew_ms == 0 {
+            return Err("Clock warn_skew_ms must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+
+    /// Skew leases and TTLs allow for
+    pub fn max_skew(&self) -> Duration {
+        Duration::from_millis(self.max_skew_ms)
+    }
+}
+
+/// How far off a peer's clock is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkewLevel {
+    /// Within `warn_skew_ms`
+    Ok,
+    /// Above `warn_skew_ms`
+    Warn,
+    /// Above `max_skew_ms`: leases and TTLs may end early
+    Exceeded,
+}
+
+/// Estimated clock offset of a peer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerSkew {
+    pub node_id: NodeId,
+    /// Offset of the peer's clock from this node's, positive if it runs ahead
+    pub offset_ms: i64,
+    /// Heartbeats the estimate is based on
+    pub samples: usize,
+    pub level: SkewLevel,
+}
+
+/// Clock offsets of every peer heard from, as served at `GET /cluster/clock`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClockSkewReport {
+    /// Node that measured the offsets
+    pub node_id: NodeId,
+    pub max_skew_ms: u64,
+    pub warn_skew_ms: u64,
+    /// Largest absolute offset of any peer
+    pub max_observed_ms: u64,
+    /// Peers ordered by node ID
+    pub peers: Vec<PeerSkew>,
+}
+
+struct PeerClock {
+    /// Newest last
+    samples: VecDeque<i64>,
+    offset_ms: i64,
+    level: SkewLevel,
+}
+
+/// Clock offsets of this node's peers
+///
+/// Cheap to clone; clones share the offsets.
+#[derive(Clone)]
+pub struct ClockSkewMonitor {
+    config: ClockConfig,
+    peers: Arc<Mutex<HashMap<NodeId, PeerClock>>>,
+}
+
+impl ClockSkewMonitor {
+    /// Create a monitor without measurements
+    pub fn new(config: ClockConfig) -> Self {
+        Self {
+            config,
+            peers: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Thresholds of the monitor
+    pub fn config(&self) -> &ClockConfig {
+        &self.config
+    }
+
+    /// Record a heartbeat `peer` sent at `sent_at_ms` by its clock and this node
+    /// received at `received_at_ms` by its own (milliseconds since the Unix epoch)
+    pub fn record(&self, peer: NodeId, sent_at_ms: u64, received_at_ms: u64) -> PeerSkew {
+        let sample = sent_at_ms as i64 - received_at_ms as i64;
+        let mut peers = self.peers.lock().unwrap_or_else(|e| e.into_inner());
+        let clock = peers.entry(peer).or_insert_with(|| PeerClock {
+            samples: VecDeque::with_capacity(SKEW_SAMPLES),
+            offset_ms: 0,
+            level: SkewLevel::Ok,
+        });
+        if clock.samples.len() == SKEW_SAMPLES {
+            clock.samples.pop_front();
+        }
+        clock.samples.push_back(sample);
+
+        let mut sorted: Vec<i64> = clock.samples.iter().copied().collect();
+        sorted.sort_unstable();
+        clock.offset_ms = sorted[sorted.len() / 2];
+
+        let level = self.level(clock.offset_ms.unsigned_abs());
+        if level != clock.level {
+            self.log_level(peer, clock.offset_ms, level);
+            clock.level = level;
+        }
+
+        CLOCK_SKEW
+            .with_label_values(&[&peer.to_string()])
+            .set(clock.offset_ms);
+        let skew = peer_skew(peer, clock);
+        CLOCK_SKEW_MAX.set(max_observed(&peers) as i64);
+        skew
+    }
+
+    /// Forget `peer`, e.g. once it left or stopped sending heartbeats
+    pub fn remove(&self, peer: NodeId) {
+        let mut peers = self.peers.lock().unwrap_or_else(|e| e.into_inner());
+        if peers.remove(&peer).is_some() {
+            let _ = CLOCK_SKEW.remove_label_values(&[&peer.to_string()]);
+            CLOCK_SKEW_MAX.set(max_observed(&peers) as i64);
+        }
+    }
+
+    /// Largest absolute offset of any peer, in milliseconds
+    pub fn max_observed_ms(&self) -> u64 {
+        max_observed(&self.peers.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Offsets of every peer, measured by `node_id`
+    pub fn report(&self, node_id: NodeId) -> ClockSkewReport {
+        let peers = self.peers.lock().unwrap_or_else(|e| e.into_inner());
+        let mut skews: Vec<PeerSkew> = peers
+            .iter()
+            .map(|(peer, clock)| peer_skew(*peer, clock))
+            .collect();
+        skews.sort_by_key(|skew| skew.node_id);
+        ClockSkewReport {
+            node_id,
+            max_skew_ms: self.config.max_skew_ms,
+            warn_skew_ms: self.config.warn_skew_ms,
+            max_observed_ms: max_observed(&peers),
+            peers: skews,
+        }
+    }
+
+    fn level(&self, offset_ms: u64) -> SkewLevel {
+        if offset_ms > self.config.max_skew_ms {
+            SkewLevel::Exceeded
+        } else if offset_ms > self.config.warn_skew_ms {
+            SkewLevel::Warn
+        } else {
+            SkewLevel::Ok
+        }
+    }
+
+    fn log_level(&self, peer: NodeId, offset_ms: i64, level: SkewLevel) {
+        match level {
+            SkewLevel::Exceeded => error!(
+                "Clock of node {} is {} ms off, more than the {} ms leases and TTLs allow for",
+                peer, offset_ms, self.config.max_skew_ms
+            ),
+            SkewLevel::Warn => warn!(
+                "Clock of node {} is {} ms off (warning above {} ms)",
+                peer, offset_ms, self.config.warn_skew_ms
+            ),
+            SkewLevel::Ok => info!(
+                "Clock of node {} is back within {} ms",
+                peer, self.config.warn_skew_ms
+            ),
+        }
+    }
+}
+
+fn peer_skew(node_id: NodeId, clock: &PeerClock) -> PeerSkew {
+    PeerSkew {
+        node_id,
+        offset_ms: clock.offset_ms,
+        samples: clock.samples.len(),
+        level: clock.level,
+    }
+}
+
+fn max_observed(peers: &HashMap<NodeId, PeerClock>) -> u64 {
+    peers
+        .values()
+        .map(|clock| clock.offset_ms.unsigned_abs())
+        .max()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_is_median_of_recent_heartbeats() {
+        let monitor = ClockSkewMonitor::new(ClockConfig::default());
+        let received = 1_700_000_000_000;
+
+        // One heartbeat delayed in the network does not move the estimate
+        for (i, delay) in [2, 3, 250, 2, 4].into_iter().enumerate() {
+            let at = received + i as u64 * 1000;
+            monitor.record(2, at + 40 - delay, at);
+        }
+        let skew = monitor.record(2, received + 5040 - 3, received + 5000);
+        assert_eq!(skew.offset_ms, 37);
+        assert_eq!(skew.samples, 6);
+        assert_eq!(skew.level, SkewLevel::Ok);
+
+        // Only the last SKEW_SAMPLES heartbeats count
+        for i in 0..SKEW_SAMPLES as u64 {
+            monitor.record(2, received + i, received + i + 150);
+        }
+        let report = monitor.report(1);
+        assert_eq!(report.peers[0].offset_ms, -150);
+        assert_eq!(report.peers[0].samples, SKEW_SAMPLES);
+        assert_eq!(report.peers[0].level, SkewLevel::Warn);
+        assert_eq!(report.max_observed_ms, 150);
+    }
+
+    #[test]
+    fn test_levels_and_removal() {
+        let monitor = ClockSkewMonitor::new(ClockConfig {
+            max_skew_ms: 500,
+            warn_skew_ms: 100,
+        });
+        let now = 1_700_000_000_000;
+        assert_eq!(monitor.record(2, now + 50, now).level, SkewLevel::Ok);
+        assert_eq!(
+            monitor.record(3, now + 2_000, now).level,
+            SkewLevel::Exceeded
+        );
+        assert_eq!(monitor.max_observed_ms(), 2_000);
+
+        let report = monitor.report(1);
+        let peers: Vec<NodeId> = report.peers.iter().map(|skew| skew.node_id).collect();
+        assert_eq!(peers, vec![2, 3]);
+
+        monitor.remove(3);
+        assert_eq!(monitor.max_observed_ms(), 50);
+        assert_eq!(monitor.report(1).peers.len(), 1);
+    }
+}
@@ -1,15 +1,22 @@
 //! Cluster initialization and management
 //!
 //! This module provides automatic cluster formation by coordinating node discovery
-//! with Raft consensus cluster initialization.
+//! with Raft consensus cluster initialization, or from a declarative bootstrap
+//! manifest listing every initial member.
 
 use crate::consensus::ConsensusNode;
 use crate::discovery::{DiscoveryService, PeerInfo};
 use crate::error::{Result, ScribeError};
+use crate::http_client::{leader_aware_client, send_following_leader};
 use crate::types::NodeId;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::net::SocketAddr;
+use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::time::sleep;
+use tokio::net::TcpStream;
+use tokio::time::{sleep, timeout};
 use tracing::{debug, info, warn};
 
 /// Cluster initialization mode
@@ -19,6 +26,126 @@ pub enum InitMode {
     Bootstrap,
     /// Join an existing cluster
     Join,
+    /// Form the initial cluster from a bootstrap manifest
+    Manifest(BootstrapManifest),
+}
+
+/// Initial cluster member listed in a bootstrap manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestMember {
+    /// Node ID
+    pub id: NodeId,
+    /// Raft RPC address
+    pub raft_addr: SocketAddr,
+    /// Client API address
+    pub client_addr: SocketAddr,
+}
+
+/// Declarative description of the initial cluster membership
+///
+/// ```toml
+/// [[members]]
+/// id = 1
+/// raft_addr = "10.0.0.1:9001"
+/// client_addr = "10.0.0.1:8001"
+///
+/// [[members]]
+/// id = 2
+/// raft_addr = "10.0.0.2:9001"
+/// client_addr = "10.0.0.2:8001"
+/// ```
+///
+/// The first listed member initializes Raft with the full voter set once every
+/// member is reachable; the others wait to be contacted by the leader.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BootstrapManifest {
+    /// Initial voters, in order
+    pub members: Vec<ManifestMember>,
+    /// How long the first member waits for the others to become reachable (milliseconds)
+    #[serde(default = "default_member_timeout_ms")]
+    pub member_timeout_ms: u64,
+}
+
+fn default_member_timeout_ms() -> u64 {
+    120_000
+}
+
+impl BootstrapManifest {
+    /// Load and validate a manifest from a TOML file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let content = std::fs::read_to_string(path.as_ref()).map_err(|e| {
+            ScribeError::Configuration(format!(
+                "Failed to read bootstrap manifest {:?}: {}",
+                path.as_ref(),
+                e
+            ))
+        })?;
+        Self::from_toml(&content)
+    }
+
+    /// Parse and validate a manifest from a TOML string
+    pub fn from_toml(content: &str) -> Result<Self> {
+        let manifest: Self = toml::from_str(content)?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    /// Validate the manifest
+    pub fn validate(&self) -> Result<()> {
+        if self.members.is_empty() {
+            return Err(ScribeError::Configuration(
+                "Bootstrap manifest must list at least one member".to_string(),
+            ));
+        }
+
+        let mut ids = HashSet::new();
+        let mut raft_addrs = HashSet::new();
+        for member in &self.members {
+            if member.id == 0 {
+                return Err(ScribeError::Configuration(
+                    "Bootstrap manifest member ID must be non-zero".to_string(),
+                ));
+            }
+            if !ids.insert(member.id) {
+                return Err(ScribeError::Configuration(format!(
+                    "Duplicate member ID {} in bootstrap manifest",
+                    member.id
+                )));
+            }
+            if !raft_addrs.insert(member.raft_addr) {
+                return Err(ScribeError::Configuration(format!(
+                    "Duplicate Raft address {} in bootstrap manifest",
+                    member.raft_addr
+                )));
+            }
+        }
+
+        if self.member_timeout_ms == 0 {
+            return Err(ScribeError::Configuration(
+                "Bootstrap manifest member_timeout_ms must be greater than 0".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Look up a member by node ID
+    pub fn member(&self, node_id: NodeId) -> Option<&ManifestMember> {
+        self.members.iter().find(|m| m.id == node_id)
+    }
+
+    /// Node responsible for initializing Raft (the first listed member)
+    pub fn initializer_id(&self) -> Option<NodeId> {
+        self.members.first().map(|m| m.id)
+    }
+
+    /// Initial voter set mapped to Raft addresses
+    pub fn voters(&self) -> BTreeMap<NodeId, String> {
+        self.members
+            .iter()
+            .map(|m| (m.id, m.raft_addr.to_string()))
+            .collect()
+    }
 }
 
 /// Configuration for cluster initialization
@@ -45,6 +172,17 @@ impl Default for ClusterConfig {
     }
 }
 
+/// Request sent to the leader's `POST /cluster/join` endpoint
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JoinRequest {
+    /// ID of the joining node
+    pub node_id: NodeId,
+    /// Raft address of the joining node
+    pub raft_addr: String,
+    /// One-time join token issued by the leader
+    pub token: String,
+}
+
 /// Cluster initializer that coordinates discovery and consensus
 pub struct ClusterInitializer {
     /// Discovery service
@@ -55,6 +193,12 @@ pub struct ClusterInitializer {
     config: ClusterConfig,
     /// Node ID
     node_id: NodeId,
+    /// Join token presented to the leader (optional)
+    join_token: Option<String>,
+    /// Scheme of the leader's client API ("http" or "https")
+    client_scheme: &'static str,
+    /// Port of the leader's admin listener, if admin endpoints are served separately
+    admin_port: Option<u16>,
 }
 
 impl ClusterInitializer {
@@ -70,14 +214,37 @@ impl ClusterInitializer {
             consensus,
             config,
             node_id,
+            join_token: None,
+            client_scheme: "http",
+            admin_port: None,
         }
     }
 
+    /// Ask the leader to add this node as a learner using a one-time join token
+    pub fn with_join_token(mut self, token: String) -> Self {
+        self.join_token = Some(token);
+        self
+    }
+
+    /// Set the scheme used to reach the leader's client API (default: "http")
+    pub fn with_client_scheme(mut self, scheme: &'static str) -> Self {
+        self.client_scheme = scheme;
+        self
+    }
+
+    /// Send join requests to the leader's admin listener on `port` instead of its
+    /// client port
+    pub fn with_admin_port(mut self, port: Option<u16>) -> Self {
+        self.admin_port = port;
+        self
+    }
+
     /// Initialize the cluster based on configuration
     pub async fn initialize(&self) -> Result<()> {
-        match self.config.mode {
+        match &self.config.mode {
             InitMode::Bootstrap => self.bootstrap().await,
             InitMode::Join => self.join_cluster().await,
+            InitMode::Manifest(manifest) => self.bootstrap_from_manifest(manifest).await,
         }
     }
 
@@ -98,6 +265,105 @@ impl ClusterInitializer {
         Ok(())
     }
 
+    /// Form the initial cluster from a bootstrap manifest
+    ///
+    /// Every member registers the others with the network layer. The first listed
+    /// member waits until all members are reachable and then initializes Raft with the
+    /// full voter set; the rest wait for the leader to replicate to them. Nodes that
+    /// already have Raft state skip initialization, so restarts are safe.
+    async fn bootstrap_from_manifest(&self, manifest: &BootstrapManifest) -> Result<()> {
+        if manifest.member(self.node_id).is_none() {
+            return Err(ScribeError::Cluster(format!(
+                "Node {} is not listed in the bootstrap manifest",
+                self.node_id
+            )));
+        }
+
+        for member in manifest.members.iter().filter(|m| m.id != self.node_id) {
+            info!(
+                "Registering manifest member node {} at {}",
+                member.id, member.raft_addr
+            );
+            self.consensus
+                .register_peer(member.id, member.raft_addr.to_string())
+                .await;
+        }
+
+        let metrics = self.consensus.metrics().await;
+        if metrics.last_log_index.is_some() || metrics.current_term > 0 {
+            info!(
+                "Node {} continuing with existing cluster state (term: {}, last_log: {:?})",
+                self.node_id, metrics.current_term, metrics.last_log_index
+            );
+            return Ok(());
+        }
+
+        let initializer_id = manifest.initializer_id().unwrap_or(self.node_id);
+        if initializer_id != self.node_id {
+            info!(
+                "Node {} waiting for node {} to initialize the cluster",
+                self.node_id, initializer_id
+            );
+            return Ok(());
+        }
+
+        self.wait_for_members(manifest).await?;
+
+        info!(
+            "Initializing cluster with {} voter(s) from bootstrap manifest",
+            manifest.members.len()
+        );
+        self.consensus
+            .initialize_with_members(manifest.voters())
+            .await
+            .map_err(|e| ScribeError::Consensus(format!("Failed to bootstrap cluster: {}", e)))?;
+
+        info!(
+            "Successfully bootstrapped cluster from manifest with node {}",
+            self.node_id
+        );
+        Ok(())
+    }
+
+    /// Wait until every other manifest member accepts connections on its Raft address
+    async fn wait_for_members(&self, manifest: &BootstrapManifest) -> Result<()> {
+        let deadline = Duration::from_millis(manifest.member_timeout_ms);
+        let start = std::time::Instant::now();
+        let check_interval = Duration::from_millis(500);
+        let connect_timeout = Duration::from_millis(1000);
+
+        loop {
+            let mut unreachable = Vec::new();
+            for member in manifest.members.iter().filter(|m| m.id != self.node_id) {
+                let reachable = matches!(
+                    timeout(connect_timeout, TcpStream::connect(member.raft_addr)).await,
+                    Ok(Ok(_))
+                );
+                if !reachable {
+                    unreachable.push(member.id);
+                }
+            }
+
+            if unreachable.is_empty() {
+                info!(
+                    "All {} manifest member(s) reachable",
+                    manifest.members.len()
+                );
+                return Ok(());
+            }
+
+            if start.elapsed() > deadline {
+                return Err(ScribeError::Cluster(format!(
+                    "Timed out waiting for manifest members {:?} to become reachable",
+                    unreachable
+                )));
+            }
+
+            debug!("Waiting for manifest members {:?}...", unreachable);
+            sleep(check_interval).await;
+        }
+    }
+
     /// Join an existing cluster
     async fn join_cluster(&self) -> Result<()> {
         info!(
@@ -224,39 +490,65 @@ impl ClusterInitializer {
 
     /// Request to join the cluster through the leader
     ///
-    /// This prepares the node to join the cluster. The actual join process requires
-    /// the leader to call add_learner() and then change_membership() on its ConsensusNode.
-    ///
-    /// In a complete distributed system, this would send an RPC request to the leader
-    /// to initiate the join process. For the current implementation, cluster membership
-    /// is managed through the ConsensusNode API (add_learner and change_membership methods).
+    /// With a join token configured, this asks the leader (following NotLeader
+    /// redirects) to add this node as a learner via `POST /cluster/join`; the leader
+    /// consumes the token, so each token admits one node. Nodes that already have Raft
+    /// state are members and skip the request.
     ///
-    /// Operators should use the cluster management endpoints or CLI tools to add nodes,
-    /// which will call the appropriate ConsensusNode methods on the leader.
+    /// Without a token the join is coordinated externally through the leader's
+    /// ConsensusNode (add_learner followed by change_membership).
     async fn request_join(&self, leader: &PeerInfo) -> Result<()> {
         info!(
             "Requesting to join cluster via leader node {} at {}",
             leader.node_id, leader.raft_addr
         );
 
-        // Log the Raft address that will be used for this node
-        let my_raft_addr = self
-            .discovery
-            .get_peers()
-            .first()
-            .map(|p| p.raft_addr.to_string())
-            .unwrap_or_else(|| "unknown".to_string());
+        let my_raft_addr = self.discovery.local_info().raft_addr.to_string();
 
-        info!(
-            "Node {} ready to join cluster (Raft addr: {}). Leader must call add_learner and change_membership.",
-            self.node_id, my_raft_addr
-        );
+        let Some(token) = &self.join_token else {
+            info!(
+                "Node {} ready to join cluster (Raft addr: {}). Leader must call add_learner and change_membership.",
+                self.node_id, my_raft_addr
+            );
+            return Ok(());
+        };
+
+        let metrics = self.consensus.metrics().await;
+        if metrics.last_log_index.is_some() || metrics.current_term > 0 {
+            info!(
+                "Node {} already has cluster state, skipping join request",
+                self.node_id
+            );
+            return Ok(());
+        }
 
-        // The actual join is coordinated externally through the leader's ConsensusNode:
-        // 1. Leader calls consensus.add_learner(node_id, BasicNode { addr: raft_addr })
-        // 2. Leader waits for log replication to catch up
-        // 3. Leader calls consensus.change_membership() to promote to voter
+        let request = JoinRequest {
+            node_id: self.node_id,
+            raft_addr: my_raft_addr,
+            token: token.clone(),
+        };
+        let mut join_addr = leader.client_addr;
+        if let Some(port) = self.admin_port {
+            join_addr.set_port(port);
+        }
+        let url = format!("{}://{}/cluster/join", self.client_scheme, join_addr);
 
+        let client = leader_aware_client()
+            .map_err(|e| ScribeError::Network(format!("Failed to create HTTP client: {}", e)))?;
+        let response = send_following_leader(&url, |url| client.post(url).json(&request))
+            .await
+            .map_err(|e| ScribeError::Network(format!("Join request failed: {}", e)))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(ScribeError::Cluster(format!(
+                "Join request rejected ({}): {}",
+                status, body
+            )));
+        }
+
+        info!("Node {} added to the cluster as a learner", self.node_id);
         Ok(())
     }
 
@@ -318,6 +610,155 @@ mod tests {
         assert_ne!(InitMode::Bootstrap, InitMode::Join);
     }
 
+    const TEST_MANIFEST: &str = r#"
+        [[members]]
+        id = 1
+        raft_addr = "127.0.0.1:9001"
+        client_addr = "127.0.0.1:8001"
+
+        [[members]]
+        id = 2
+        raft_addr = "127.0.0.1:9002"
+        client_addr = "127.0.0.1:8002"
+    "#;
+
+    fn test_discovery(node_id: NodeId, discovery_port: u16) -> Arc<DiscoveryService> {
+        let discovery_config = DiscoveryConfig {
+            node_id,
+            raft_addr: test_raft_addr(TEST_RAFT_PORT),
+            client_addr: test_client_addr(TEST_CLIENT_PORT),
+            discovery_port,
+            broadcast_addr: TEST_IP.to_string(),
+            seed_addrs: Vec::new(),
+            heartbeat_interval_ms: 500,
+            failure_timeout_ms: 1500,
+            cluster_secret: None,
+        };
+        Arc::new(DiscoveryService::new(discovery_config).unwrap())
+    }
+
+    fn manifest_cluster_config(manifest: BootstrapManifest) -> ClusterConfig {
+        ClusterConfig {
+            mode: InitMode::Manifest(manifest),
+            seed_addrs: Vec::new(),
+            discovery_timeout_ms: 1000,
+            min_peers_for_join: 1,
+        }
+    }
+
+    #[test]
+    fn test_bootstrap_manifest_parse() {
+        let manifest = BootstrapManifest::from_toml(TEST_MANIFEST).unwrap();
+        assert_eq!(manifest.members.len(), 2);
+        assert_eq!(manifest.initializer_id(), Some(1));
+        assert_eq!(manifest.member_timeout_ms, 120_000);
+        assert_eq!(
+            manifest.member(2).unwrap().client_addr,
+            test_client_addr(8002)
+        );
+
+        let voters = manifest.voters();
+        assert_eq!(voters.get(&1).map(String::as_str), Some("127.0.0.1:9001"));
+        assert_eq!(voters.get(&2).map(String::as_str), Some("127.0.0.1:9002"));
+    }
+
+    #[test]
+    fn test_bootstrap_manifest_validation() {
+        assert!(BootstrapManifest::from_toml("members = []").is_err());
+
+        let duplicate_id = r#"
+            [[members]]
+            id = 1
+            raft_addr = "127.0.0.1:9001"
+            client_addr = "127.0.0.1:8001"
+
+            [[members]]
+            id = 1
+            raft_addr = "127.0.0.1:9002"
+            client_addr = "127.0.0.1:8002"
+        "#;
+        assert!(BootstrapManifest::from_toml(duplicate_id).is_err());
+
+        let duplicate_addr = r#"
+            [[members]]
+            id = 1
+            raft_addr = "127.0.0.1:9001"
+            client_addr = "127.0.0.1:8001"
+
+            [[members]]
+            id = 2
+            raft_addr = "127.0.0.1:9001"
+            client_addr = "127.0.0.1:8002"
+        "#;
+        assert!(BootstrapManifest::from_toml(duplicate_addr).is_err());
+
+        let bad_addr = r#"
+            [[members]]
+            id = 1
+            raft_addr = "not-an-address"
+            client_addr = "127.0.0.1:8001"
+        "#;
+        assert!(BootstrapManifest::from_toml(bad_addr).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_manifest_bootstrap_single_member() {
+        let manifest = BootstrapManifest {
+            members: vec![ManifestMember {
+                id: TEST_NODE_ID,
+                raft_addr: test_raft_addr(TEST_RAFT_PORT),
+                client_addr: test_client_addr(TEST_CLIENT_PORT),
+            }],
+            member_timeout_ms: 1000,
+        };
+
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let consensus = Arc::new(ConsensusNode::new(TEST_NODE_ID, db).await.unwrap());
+        let initializer = ClusterInitializer::new(
+            test_discovery(TEST_NODE_ID, 17011),
+            consensus.clone(),
+            manifest_cluster_config(manifest),
+        );
+
+        assert!(initializer.initialize().await.is_ok());
+
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+        assert!(consensus.is_leader().await);
+    }
+
+    #[tokio::test]
+    async fn test_manifest_bootstrap_waits_for_initializer() {
+        let manifest = BootstrapManifest::from_toml(TEST_MANIFEST).unwrap();
+
+        // Node 2 is not the first member, so it must not initialize Raft itself
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let consensus = Arc::new(ConsensusNode::new(2, db).await.unwrap());
+        let initializer = ClusterInitializer::new(
+            test_discovery(2, 17012),
+            consensus.clone(),
+            manifest_cluster_config(manifest),
+        );
+
+        assert!(initializer.initialize().await.is_ok());
+        assert_eq!(consensus.metrics().await.current_term, 0);
+        assert!(!consensus.is_leader().await);
+    }
+
+    #[tokio::test]
+    async fn test_manifest_bootstrap_rejects_unlisted_node() {
+        let manifest = BootstrapManifest::from_toml(TEST_MANIFEST).unwrap();
+
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let consensus = Arc::new(ConsensusNode::new(3, db).await.unwrap());
+        let initializer = ClusterInitializer::new(
+            test_discovery(3, 17013),
+            consensus,
+            manifest_cluster_config(manifest),
+        );
+
+        assert!(initializer.initialize().await.is_err());
+    }
+
     #[tokio::test]
     async fn test_cluster_initializer_bootstrap() {
         let discovery_config = DiscoveryConfig {
@@ -2,8 +2,14 @@
 //!
 //! This module contains the configuration system for the distributed ledger.
 
+mod profile;
 mod settings;
 
+pub use profile::{Profile, PROFILE_ENV};
+
 pub use settings::{
-    ApiConfig, Config, ConsensusConfig, DiscoveryConfig, NetworkConfig, NodeConfig, StorageConfig,
+    AdminListenerConfig, ApiConfig, AutoArchivalConfig, CanaryConfig, Config, ConsensusConfig,
+    DiscoveryConfig, LoggingConfig, MetricsConfig, MetricsHistoryConfig, MetricsPushConfig,
+    MetricsPushMode, NetworkConfig, NodeConfig, RaftCompressionConfig, RpcAuthConfig, S3Config,
+    SecurityConfig, ShadowConfig, SnapshotStoreConfig, SnapshotTransferConfig, StorageConfig,
 };
@@ -0,0 +1,186 @@
+//! Configuration profiles
+//!
+//! A profile layers environment-specific settings over a base configuration so
+//! development, staging and production configs share one base file instead of
+//! drifting copies. The layers, each overriding the previous one, are:
+//!
+//! 1. the base configuration file (or the built-in defaults without one)
+//! 2. the profile's built-in defaults ([`Profile::defaults`])
+//! 3. `<name>.<profile>.toml` next to the base file, if it exists
+//! 4. environment variable overrides
+//!
+//! The profile is selected with `--profile` or `SCRIBE_PROFILE`.
+
+use crate::error::{Result, ScribeError};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Environment variable selecting the profile
+pub const PROFILE_ENV: &str = "SCRIBE_PROFILE";
+
+/// Deployment environment a configuration is layered for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Profile {
+    /// Local development: temporary storage, a single node, permissive CORS
+    Dev,
+    /// Pre-production: production durability and authentication, permissive CORS
+    Staging,
+    /// Production: frequent storage flushes, authenticated cluster traffic and
+    /// no cross-origin browser access
+    Prod,
+}
+
+impl Profile {
+    /// Name of the profile as used on the command line and in overlay file names
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Profile::Dev => "dev",
+            Profile::Staging => "staging",
+            Profile::Prod => "prod",
+        }
+    }
+
+    /// Profile selected by `SCRIBE_PROFILE`, if set
+    pub fn from_env() -> Result<Option<Self>> {
+        match std::env::var(PROFILE_ENV) {
+            Ok(name) if !name.is_empty() => name.parse().map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Built-in settings of the profile, layered over the base configuration
+    pub fn defaults(self) -> toml::Table {
+        let mut table = toml::Table::new();
+        match self {
+            Profile::Dev => {
+                let data_dir = std::env::temp_dir().join("scribe-ledger-dev");
+                set(
+                    &mut table,
+                    &["node", "data_dir"],
+                    data_dir.to_string_lossy().into_owned(),
+                );
+                set(
+                    &mut table,
+                    &["network", "seed_peers"],
+                    toml::Value::Array(Vec::new()),
+                );
+                set(&mut table, &["network", "permissive_cors"], true);
+                // Keep discovery broadcasts on this machine
+                set(&mut table, &["discovery", "broadcast_addr"], "127.0.0.1");
+            }
+            Profile::Staging | Profile::Prod => {
+                set(&mut table, &["storage", "flush_every_ms"], 50);
+                set(&mut table, &["network", "rpc_auth", "enabled"], true);
+                set(&mut table, &["discovery", "require_join_token"], true);
+                set(
+                    &mut table,
+                    &["network", "permissive_cors"],
+                    self == Profile::Staging,
+                );
+            }
+        }
+        table
+    }
+
+    /// Overlay file of the profile for the base file `base`
+    /// (`config.toml` becomes `config.prod.toml`)
+    pub fn overlay_path(self, base: &Path) -> PathBuf {
+        let stem = base
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        base.with_file_name(format!("{}.{}.toml", stem, self.as_str()))
+    }
+}
+
+impl FromStr for Profile {
+    type Err = ScribeError;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "dev" | "development" => Ok(Profile::Dev),
+            "staging" => Ok(Profile::Staging),
+            "prod" | "production" => Ok(Profile::Prod),
+            _ => Err(ScribeError::Configuration(format!(
+                "Unknown configuration profile '{}' (expected dev, staging or prod)",
+                name
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Set the value at `path`, creating intermediate tables
+fn set(table: &mut toml::Table, path: &[&str], value: impl Into<toml::Value>) {
+    let (last, parents) = path.split_last().expect("path is not empty");
+    let mut table = table;
+    for name in parents {
+        table = match table
+            .entry(name.to_string())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        {
+            toml::Value::Table(child) => child,
+            _ => unreachable!("profile defaults only nest tables"),
+        };
+    }
+    table.insert(last.to_string(), value.into());
+}
+
+/// Merge `overlay` into `base`: tables are merged key by key, other values replaced
+pub fn merge(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => merge(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_profile() {
+        assert_eq!("dev".parse::<Profile>().unwrap(), Profile::Dev);
+        assert_eq!("Production".parse::<Profile>().unwrap(), Profile::Prod);
+        assert!("qa".parse::<Profile>().is_err());
+        assert_eq!(
+            Profile::Staging.overlay_path(Path::new("/etc/scribe/node.toml")),
+            PathBuf::from("/etc/scribe/node.staging.toml")
+        );
+    }
+
+    #[test]
+    fn test_merge_tables() {
+        let mut base: toml::Table = toml::from_str(
+            r#"
+            [node]
+            id = 1
+            data_dir = "./data"
+            [network]
+            seed_peers = ["2@a:9002", "3@b:9003"]
+            "#,
+        )
+        .unwrap();
+        merge(&mut base, Profile::Dev.defaults());
+
+        assert_eq!(base["node"]["id"].as_integer(), Some(1));
+        assert_ne!(base["node"]["data_dir"].as_str(), Some("./data"));
+        assert_eq!(base["network"]["seed_peers"].as_array().unwrap().len(), 0);
+        assert_eq!(base["network"]["permissive_cors"].as_bool(), Some(true));
+
+        let prod = Profile::Prod.defaults();
+        assert_eq!(prod["network"]["rpc_auth"]["enabled"].as_bool(), Some(true));
+        assert_eq!(prod["network"]["permissive_cors"].as_bool(), Some(false));
+    }
+}
@@ -3,10 +3,27 @@
 //! This module provides configuration management with TOML file parsing and
 //! environment variable override support.
 
+use super::profile::{self, Profile};
+use crate::access_log::AccessLogConfig;
+use crate::clock_skew::ClockConfig;
 use crate::error::{Result, ScribeError};
+use crate::health::HealthConfig;
+use crate::ids::{IdScheme, MAX_SNOWFLAKE_NODE_ID};
+use crate::logging::KeyLoggingConfig;
+use crate::placement::PlacementConfig;
+use crate::quota::QuotaConfig;
+use crate::read_path::ReadPathConfig;
+use crate::runtime::RuntimeConfig;
+use crate::security::{AccessTraceConfig, EncryptionConfig, NetworkPolicyConfig, TlsConfig};
+use crate::storage::classes::StorageClasses;
+use crate::storage::consistency::StartupCheckMode;
+use crate::storage::credentials::{CredentialSource, DEFAULT_CREDENTIAL_REFRESH_SECS};
+use crate::storage::layout::StorageLayout;
+use crate::wire::WireFormat;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::net::SocketAddr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 /// Main configuration structure for the distributed ledger
@@ -26,6 +43,36 @@ pub struct Config {
     /// Discovery configuration
     #[serde(default)]
     pub discovery: DiscoveryConfig,
+    /// Security configuration
+    #[serde(default)]
+    pub security: SecurityConfig,
+    /// Shadow write configuration
+    #[serde(default)]
+    pub shadow: ShadowConfig,
+    /// Read mirroring to a canary
+    #[serde(default)]
+    pub canary: CanaryConfig,
+    /// Metrics export
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    /// Tokio runtime sizing and CPU pinning
+    #[serde(default)]
+    pub runtime: RuntimeConfig,
+    /// Logging of keys and other potentially sensitive data
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    /// Composite health scoring and self-healing actions
+    #[serde(default)]
+    pub health: HealthConfig,
+    /// Latency-aware leader placement
+    #[serde(default)]
+    pub placement: PlacementConfig,
+    /// Request quotas per API key
+    #[serde(default)]
+    pub quotas: QuotaConfig,
+    /// Clock skew monitoring and the skew leases and TTLs allow for
+    #[serde(default)]
+    pub clock: ClockConfig,
 }
 
 /// Node configuration
@@ -52,6 +99,139 @@ pub struct NetworkConfig {
     /// Format: ["node_id@host:port", "node_id@host:port"]
     #[serde(default)]
     pub seed_peers: Vec<String>,
+    /// Compression of Raft RPCs sent to peers
+    #[serde(default)]
+    pub raft_compression: RaftCompressionConfig,
+    /// Separate listener for admin and membership endpoints
+    #[serde(default)]
+    pub admin: AdminListenerConfig,
+    /// Signing and replay protection of Raft RPCs
+    #[serde(default)]
+    pub rpc_auth: RpcAuthConfig,
+    /// Allow cross-origin browser requests from any origin
+    #[serde(default = "default_permissive_cors")]
+    pub permissive_cors: bool,
+    /// Client addresses allowed on or denied from the client HTTP API
+    #[serde(default)]
+    pub policy: NetworkPolicyConfig,
+}
+
+fn default_permissive_cors() -> bool {
+    true
+}
+
+/// Signing and replay protection of Raft RPCs between nodes
+///
+/// Complements TLS: every RPC carries a timestamp, a random nonce and an HMAC keyed
+/// by the cluster secret, so a captured RPC cannot be replayed against a node, even
+/// one that was left without TLS by mistake.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcAuthConfig {
+    /// Sign RPCs sent to peers and verify RPCs received (requires cluster_secret)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Largest accepted difference between the sender's and this node's clock
+    #[serde(default = "default_rpc_max_clock_skew_ms")]
+    pub max_clock_skew_ms: u64,
+    /// Also accept unsigned RPCs, while signing is rolled out across the cluster
+    #[serde(default)]
+    pub accept_unsigned: bool,
+}
+
+fn default_rpc_max_clock_skew_ms() -> u64 {
+    30_000
+}
+
+impl Default for RpcAuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_clock_skew_ms: default_rpc_max_clock_skew_ms(),
+            accept_unsigned: false,
+        }
+    }
+}
+
+/// Listener for admin and membership-changing endpoints
+///
+/// With a port set, `/admin/*`, `/cluster/tokens` and `/cluster/join` are served only
+/// on this listener and no longer on the client port, so the KV API can be exposed to
+/// applications without exposing membership changes. Without a port, every endpoint
+/// is served on the client port.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AdminListenerConfig {
+    /// Port for the admin listener (disabled if unset)
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// Interface to bind the admin listener to (default: all interfaces)
+    #[serde(default)]
+    pub bind_address: Option<String>,
+    /// Bind the admin listener to 127.0.0.1 only
+    #[serde(default)]
+    pub localhost_only: bool,
+    /// Client addresses allowed on or denied from the admin endpoints
+    #[serde(default)]
+    pub policy: NetworkPolicyConfig,
+}
+
+impl AdminListenerConfig {
+    /// Address to bind the admin listener to, if it is enabled
+    pub fn listen_addr(&self) -> Option<String> {
+        let port = self.port?;
+        let host = if self.localhost_only {
+            "127.0.0.1"
+        } else {
+            self.bind_address.as_deref().unwrap_or("0.0.0.0")
+        };
+        Some(format!("{}:{}", host, port))
+    }
+}
+
+/// Compression of Raft replication traffic, e.g. for learners across a WAN link
+///
+/// Payloads are deflate-compressed per message. The receiving node answers in kind,
+/// so only the sending side needs this setting; peers that cannot decode compressed
+/// messages are detected and sent plain messages instead.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RaftCompressionConfig {
+    /// Compress messages to peers
+    #[serde(default)]
+    pub enabled: bool,
+    /// Peers to compress messages to; empty means every peer
+    #[serde(default)]
+    pub peers: Vec<u64>,
+    /// Compression level from 1 (fastest) to 9 (smallest)
+    #[serde(default = "default_raft_compression_level")]
+    pub level: u32,
+    /// Messages smaller than this many bytes are sent uncompressed
+    #[serde(default = "default_raft_compression_min_bytes")]
+    pub min_size_bytes: usize,
+}
+
+fn default_raft_compression_level() -> u32 {
+    6
+}
+
+fn default_raft_compression_min_bytes() -> usize {
+    1024
+}
+
+impl Default for RaftCompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            peers: Vec::new(),
+            level: default_raft_compression_level(),
+            min_size_bytes: default_raft_compression_min_bytes(),
+        }
+    }
+}
+
+impl RaftCompressionConfig {
+    /// Whether messages to `peer` are compressed
+    pub fn applies_to(&self, peer: u64) -> bool {
+        self.enabled && (self.peers.is_empty() || self.peers.contains(&peer))
+    }
 }
 
 /// Storage configuration
@@ -61,9 +241,101 @@ pub struct StorageConfig {
     pub segment_size: usize,
     /// Maximum cache size in bytes
     pub max_cache_size: usize,
+    /// Maximum age of the active segment in seconds before it is rolled regardless
+    /// of size (0 disables time-based rolling)
+    #[serde(default = "default_segment_max_age_secs")]
+    pub segment_max_age_secs: u64,
+    /// Interval in milliseconds at which sled flushes writes to disk in the
+    /// background (0 disables background flushes)
+    #[serde(default = "default_flush_every_ms")]
+    pub flush_every_ms: u64,
+    /// Seconds a deleted or expired key stays listed as deleted by the change feed
+    /// (`GET /keys`) before it is forgotten (0 keeps it for good)
+    #[serde(default = "default_tombstone_retention_secs")]
+    pub tombstone_retention_secs: u64,
+    /// Write flushed segments to `<data_dir>/segments` and read them through memory maps
+    /// instead of keeping them in memory until they are archived
+    #[serde(default)]
+    pub mmap_segments: bool,
+    /// How segment IDs are generated: `snowflake` (time-ordered, unique across the
+    /// cluster) or `sequential` (per-node counter)
+    #[serde(default)]
+    pub segment_ids: IdScheme,
+    /// Startup check of the manifest against the local segments when S3 is
+    /// configured: `off`, `report` or `repair`
+    #[serde(default)]
+    pub startup_check: StartupCheckMode,
+    /// Storage classes by name, routing key prefixes to their own segments and
+    /// archival settings (requires S3)
+    #[serde(default)]
+    pub classes: StorageClasses,
     /// S3 storage configuration (optional)
     #[serde(default)]
     pub s3: Option<S3Config>,
+    /// Automatic archival of idle values to S3 (requires S3)
+    #[serde(default)]
+    pub archival: AutoArchivalConfig,
+    /// Raft snapshots kept on disk in `<data_dir>/snapshots`
+    #[serde(default)]
+    pub snapshots: SnapshotStoreConfig,
+}
+
+/// Raft snapshots kept on disk
+///
+/// A node that persists its snapshots resumes from the latest one after a restart.
+/// The values of the snapshot are laid out as configured here; changing the layout
+/// of existing snapshots requires `scribe-node migrate`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotStoreConfig {
+    /// Whether to write snapshots to disk
+    #[serde(default)]
+    pub persist: bool,
+    /// How the values of the snapshot are laid out on disk
+    #[serde(flatten)]
+    pub layout: StorageLayout,
+}
+
+/// Automatic archival of values that are no longer written to S3
+///
+/// The leader periodically moves idle values into segments, archives them and
+/// demotes the keys, so their values leave memory and are read from S3.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AutoArchivalConfig {
+    /// Whether to archive idle values
+    #[serde(default)]
+    pub enabled: bool,
+    /// Seconds between archival runs
+    #[serde(default = "default_archival_interval_secs")]
+    pub interval_secs: u64,
+    /// Seconds since its last write after which a value is archived
+    #[serde(default = "default_archival_idle_secs")]
+    pub idle_secs: u64,
+    /// Most keys archived per run
+    #[serde(default = "default_archival_max_keys_per_run")]
+    pub max_keys_per_run: usize,
+}
+
+fn default_archival_interval_secs() -> u64 {
+    300
+}
+
+fn default_archival_idle_secs() -> u64 {
+    3600
+}
+
+fn default_archival_max_keys_per_run() -> usize {
+    10_000
+}
+
+impl Default for AutoArchivalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: default_archival_interval_secs(),
+            idle_secs: default_archival_idle_secs(),
+            max_keys_per_run: default_archival_max_keys_per_run(),
+        }
+    }
 }
 
 /// S3 storage configuration
@@ -91,6 +363,25 @@ pub struct S3Config {
     /// Maximum retry attempts
     #[serde(default = "default_max_retries")]
     pub max_retries: u32,
+    /// Source of rotatable credentials (`file`, `env` or `command`), read instead
+    /// of the static keys
+    #[serde(default)]
+    pub credentials: Option<CredentialSource>,
+    /// Seconds between reads of the credential source
+    #[serde(default = "default_credential_refresh_secs")]
+    pub credential_refresh_secs: u64,
+}
+
+fn default_segment_max_age_secs() -> u64 {
+    600
+}
+
+fn default_flush_every_ms() -> u64 {
+    500
+}
+
+fn default_tombstone_retention_secs() -> u64 {
+    7 * 24 * 3600
 }
 
 fn default_pool_size() -> usize {
@@ -105,6 +396,10 @@ fn default_max_retries() -> u32 {
     3
 }
 
+fn default_credential_refresh_secs() -> u64 {
+    DEFAULT_CREDENTIAL_REFRESH_SECS
+}
+
 /// Consensus configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConsensusConfig {
@@ -125,6 +420,116 @@ pub struct ConsensusConfig {
     /// Maximum number of entries to send in a single append entries request
     #[serde(default = "default_max_in_snapshot_log_to_keep")]
     pub max_in_snapshot_log_to_keep: u64,
+    /// Rate limits for sending snapshots to and receiving them from peers
+    #[serde(default)]
+    pub snapshot_transfer: SnapshotTransferConfig,
+    /// Local history of Raft metrics samples
+    #[serde(default)]
+    pub metrics_history: MetricsHistoryConfig,
+    /// Serve linearizable reads on followers after confirming the leader's commit
+    /// index (ReadIndex) instead of redirecting them to the leader
+    #[serde(default = "default_follower_reads")]
+    pub follower_reads: bool,
+}
+
+/// Periodic Raft metrics samples kept on disk for post-incident analysis
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetricsHistoryConfig {
+    /// Whether to record samples
+    #[serde(default = "default_metrics_history_enabled")]
+    pub enabled: bool,
+    /// Seconds between samples
+    #[serde(default = "default_metrics_history_interval_secs")]
+    pub interval_secs: u64,
+    /// Seconds of samples to keep
+    #[serde(default = "default_metrics_history_retention_secs")]
+    pub retention_secs: u64,
+}
+
+fn default_metrics_history_enabled() -> bool {
+    MetricsHistoryConfig::standard().enabled
+}
+
+fn default_metrics_history_interval_secs() -> u64 {
+    MetricsHistoryConfig::standard().interval_secs
+}
+
+fn default_metrics_history_retention_secs() -> u64 {
+    MetricsHistoryConfig::standard().retention_secs
+}
+
+impl MetricsHistoryConfig {
+    /// A sample every 10 seconds, kept for a day
+    pub const fn standard() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 10,
+            retention_secs: 24 * 60 * 60,
+        }
+    }
+}
+
+impl Default for MetricsHistoryConfig {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+/// Longest a snapshot chunk may take at the configured rates, in seconds
+///
+/// Peers wait 10 seconds for the answer to a chunk.
+const MAX_SNAPSHOT_CHUNK_SECS: u64 = 5;
+
+/// Snapshot transfer throttling, so bootstrapping a replica leaves I/O and bandwidth
+/// for client traffic
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotTransferConfig {
+    /// Snapshot bytes per second sent to all peers together; 0 is unlimited
+    #[serde(default)]
+    pub send_bytes_per_sec: u64,
+    /// Snapshot bytes per second accepted from peers; 0 is unlimited
+    #[serde(default)]
+    pub receive_bytes_per_sec: u64,
+    /// Size of the chunks snapshots are sent in
+    #[serde(default = "default_snapshot_chunk_bytes")]
+    pub chunk_size_bytes: u64,
+}
+
+fn default_snapshot_chunk_bytes() -> u64 {
+    SnapshotTransferConfig::unlimited().chunk_size_bytes
+}
+
+impl SnapshotTransferConfig {
+    /// No rate limits, with Raft's default chunk size
+    pub const fn unlimited() -> Self {
+        Self {
+            send_bytes_per_sec: 0,
+            receive_bytes_per_sec: 0,
+            chunk_size_bytes: 3 * 1024 * 1024,
+        }
+    }
+
+    /// How long Raft waits for one chunk to be answered, in milliseconds
+    ///
+    /// Covers a chunk queued behind chunks to other peers at the lowest configured
+    /// rate; peers are assumed to be configured alike. `None` keeps Raft's default.
+    pub fn chunk_timeout_ms(&self) -> Option<u64> {
+        let rate = [self.send_bytes_per_sec, self.receive_bytes_per_sec]
+            .into_iter()
+            .filter(|rate| *rate > 0)
+            .min()?;
+        Some(1000 + 4 * self.chunk_size_bytes.saturating_mul(1000) / rate)
+    }
+}
+
+impl Default for SnapshotTransferConfig {
+    fn default() -> Self {
+        Self::unlimited()
+    }
+}
+
+fn default_follower_reads() -> bool {
+    true
 }
 
 fn default_election_timeout_min() -> u64 {
@@ -162,6 +567,30 @@ pub struct ApiConfig {
     /// Cache capacity for hot data
     #[serde(default = "default_cache_capacity")]
     pub cache_capacity: usize,
+    /// Entries evicted from the hot data cache kept on disk; 0 drops them
+    #[serde(default)]
+    pub cache_overflow_capacity: usize,
+    /// Recently used keys saved to warm the hot data cache on restart; 0 disables
+    #[serde(default)]
+    pub cache_warm_keys: usize,
+    /// Seconds between saves of the recently used keys
+    #[serde(default = "default_cache_warm_save_interval_secs")]
+    pub cache_warm_save_interval_secs: u64,
+    /// Maximum number of client requests processed concurrently
+    #[serde(default = "default_max_in_flight_requests")]
+    pub max_in_flight_requests: usize,
+    /// Percentage of request slots low priority requests may occupy
+    #[serde(default = "default_low_priority_share")]
+    pub low_priority_share: u8,
+    /// Percentage of request slots reserved for high priority requests
+    #[serde(default = "default_high_priority_reserve")]
+    pub high_priority_reserve: u8,
+    /// Maximum number of unacked items per durable queue
+    #[serde(default = "default_max_queue_length")]
+    pub max_queue_length: usize,
+    /// Storage tier order of reads, per namespace
+    #[serde(default)]
+    pub read_path: ReadPathConfig,
 }
 
 fn default_write_timeout_secs() -> u64 {
@@ -180,6 +609,26 @@ fn default_cache_capacity() -> usize {
     1000
 }
 
+fn default_cache_warm_save_interval_secs() -> u64 {
+    60
+}
+
+fn default_max_in_flight_requests() -> usize {
+    512
+}
+
+fn default_low_priority_share() -> u8 {
+    50
+}
+
+fn default_high_priority_reserve() -> u8 {
+    10
+}
+
+fn default_max_queue_length() -> usize {
+    10_000
+}
+
 impl Default for ApiConfig {
     fn default() -> Self {
         Self {
@@ -187,6 +636,14 @@ impl Default for ApiConfig {
             read_timeout_secs: default_read_timeout_secs(),
             max_batch_size: default_api_batch_size(),
             cache_capacity: default_cache_capacity(),
+            cache_overflow_capacity: 0,
+            cache_warm_keys: 0,
+            cache_warm_save_interval_secs: default_cache_warm_save_interval_secs(),
+            max_in_flight_requests: default_max_in_flight_requests(),
+            low_priority_share: default_low_priority_share(),
+            high_priority_reserve: default_high_priority_reserve(),
+            max_queue_length: default_max_queue_length(),
+            read_path: ReadPathConfig::default(),
         }
     }
 }
@@ -210,6 +667,19 @@ pub struct DiscoveryConfig {
     /// Nodes must have matching tokens to join the same cluster across networks
     #[serde(default)]
     pub cluster_secret: Option<String>,
+    /// Cluster ID embedded in join tokens (default: "scribe-ledger")
+    #[serde(default = "default_cluster_id")]
+    pub cluster_id: String,
+    /// Require new nodes to present a join token (requires cluster_secret)
+    #[serde(default)]
+    pub require_join_token: bool,
+    /// Join token presented by this node when joining a cluster (optional)
+    #[serde(default)]
+    pub join_token: Option<String>,
+    /// Encoding of the discovery messages this node sends: "protobuf" (default) or
+    /// "bincode", for rolling upgrades from releases without wire versioning
+    #[serde(default)]
+    pub wire_format: WireFormat,
 }
 
 fn default_discovery_heartbeat_ms() -> u64 {
@@ -228,6 +698,10 @@ fn default_broadcast_addr() -> String {
     "255.255.255.255".to_string()
 }
 
+fn default_cluster_id() -> String {
+    "scribe-ledger".to_string()
+}
+
 impl Default for DiscoveryConfig {
     fn default() -> Self {
         Self {
@@ -236,18 +710,259 @@ impl Default for DiscoveryConfig {
             discovery_port: default_discovery_port(),
             broadcast_addr: default_broadcast_addr(),
             cluster_secret: None,
+            cluster_id: default_cluster_id(),
+            require_join_token: false,
+            join_token: None,
+            wire_format: WireFormat::default(),
+        }
+    }
+}
+
+/// Security configuration
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SecurityConfig {
+    /// TLS settings for the client-facing HTTP API
+    #[serde(default)]
+    pub tls: TlsConfig,
+    /// Per-tenant encryption of archived segments
+    #[serde(default)]
+    pub encryption: EncryptionConfig,
+    /// Key-level access tracing for regulated namespaces
+    #[serde(default)]
+    pub access_trace: AccessTraceConfig,
+}
+
+/// Shadow write configuration for migration rehearsals
+///
+/// Exactly one of `target_url` or `namespace` must be set when enabled.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShadowConfig {
+    /// Mirror successful writes to the shadow target
+    #[serde(default)]
+    pub enabled: bool,
+    /// Client API URL of the shadow cluster
+    #[serde(default)]
+    pub target_url: Option<String>,
+    /// Key prefix in this cluster to mirror writes into
+    #[serde(default)]
+    pub namespace: Option<String>,
+    /// Maximum queued shadow writes before new ones are dropped
+    #[serde(default = "default_shadow_queue_capacity")]
+    pub queue_capacity: usize,
+}
+
+fn default_shadow_queue_capacity() -> usize {
+    10_000
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target_url: None,
+            namespace: None,
+            queue_capacity: default_shadow_queue_capacity(),
+        }
+    }
+}
+
+/// Read mirroring configuration for canary testing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanaryConfig {
+    /// Mirror sampled reads to the canary
+    #[serde(default)]
+    pub enabled: bool,
+    /// Client API URL of the canary node or cluster
+    #[serde(default)]
+    pub target_url: Option<String>,
+    /// Percentage of reads mirrored (0 to 100)
+    #[serde(default = "default_canary_sample_percent")]
+    pub sample_percent: f64,
+    /// Maximum queued canary reads before new ones are dropped
+    #[serde(default = "default_canary_queue_capacity")]
+    pub queue_capacity: usize,
+    /// Timeout of each canary read in milliseconds
+    #[serde(default = "default_canary_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_canary_sample_percent() -> f64 {
+    1.0
+}
+
+fn default_canary_queue_capacity() -> usize {
+    1_000
+}
+
+fn default_canary_timeout_ms() -> u64 {
+    2_000
+}
+
+/// Logging configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    /// How keys are shown in log lines, reports and audit entries
+    #[serde(default)]
+    pub keys: KeyLoggingConfig,
+    /// HTTP access log
+    #[serde(default)]
+    pub access: AccessLogConfig,
+}
+
+/// Metrics export configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetricsConfig {
+    /// Push metrics to a Prometheus pushgateway or remote-write endpoint
+    #[serde(default)]
+    pub push: MetricsPushConfig,
+}
+
+/// Protocol metrics are pushed with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricsPushMode {
+    /// Replace this node's group on a Prometheus pushgateway
+    #[default]
+    Pushgateway,
+    /// Send samples with the Prometheus remote-write protocol
+    RemoteWrite,
+}
+
+/// Push-based metrics export for nodes that cannot be scraped
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricsPushConfig {
+    /// Push metrics periodically
+    #[serde(default)]
+    pub enabled: bool,
+    /// Push protocol
+    #[serde(default)]
+    pub mode: MetricsPushMode,
+    /// Pushgateway base URL or remote-write endpoint URL
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Seconds between pushes
+    #[serde(default = "default_metrics_push_interval_secs")]
+    pub interval_secs: u64,
+    /// Job label of the pushed metrics
+    #[serde(default = "default_metrics_push_job")]
+    pub job: String,
+    /// Retries of a failed push before it is given up until the next interval
+    #[serde(default = "default_metrics_push_max_retries")]
+    pub max_retries: u32,
+    /// Timeout of each push request in milliseconds
+    #[serde(default = "default_metrics_push_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Samples sent per remote-write request
+    #[serde(default = "default_metrics_push_max_samples_per_send")]
+    pub max_samples_per_send: usize,
+    /// Remote-write batches kept for retry while the endpoint is unreachable; the
+    /// oldest is dropped when full
+    #[serde(default = "default_metrics_push_max_pending_batches")]
+    pub max_pending_batches: usize,
+}
+
+fn default_metrics_push_interval_secs() -> u64 {
+    15
+}
+
+fn default_metrics_push_job() -> String {
+    "scribe-ledger".to_string()
+}
+
+fn default_metrics_push_max_retries() -> u32 {
+    3
+}
+
+fn default_metrics_push_timeout_ms() -> u64 {
+    5_000
+}
+
+fn default_metrics_push_max_samples_per_send() -> usize {
+    5_000
+}
+
+fn default_metrics_push_max_pending_batches() -> usize {
+    20
+}
+
+impl Default for MetricsPushConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            mode: MetricsPushMode::default(),
+            url: None,
+            interval_secs: default_metrics_push_interval_secs(),
+            job: default_metrics_push_job(),
+            max_retries: default_metrics_push_max_retries(),
+            timeout_ms: default_metrics_push_timeout_ms(),
+            max_samples_per_send: default_metrics_push_max_samples_per_send(),
+            max_pending_batches: default_metrics_push_max_pending_batches(),
+        }
+    }
+}
+
+impl Default for CanaryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target_url: None,
+            sample_percent: default_canary_sample_percent(),
+            queue_capacity: default_canary_queue_capacity(),
+            timeout_ms: default_canary_timeout_ms(),
         }
     }
 }
 
+/// Read a TOML file into a table
+fn read_table(path: &Path) -> Result<toml::Table> {
+    let contents = std::fs::read_to_string(path).map_err(|e| {
+        ScribeError::Configuration(format!(
+            "Failed to read config file {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    Ok(toml::from_str(&contents)?)
+}
+
 impl Config {
     /// Load configuration from a TOML file
+    ///
+    /// The profile named by `SCRIBE_PROFILE`, if set, is layered over the file (see
+    /// [`Config::from_file_with_profile`]).
     pub fn from_file(path: &str) -> Result<Self> {
-        let contents = std::fs::read_to_string(path).map_err(|e| {
-            ScribeError::Configuration(format!("Failed to read config file: {}", e))
-        })?;
+        Self::from_file_with_profile(path, Profile::from_env()?)
+    }
+
+    /// Load configuration from a TOML file with a profile layered over it
+    ///
+    /// The profile's built-in defaults override the file, and `<name>.<profile>.toml`
+    /// next to the file, if it exists, overrides both.
+    pub fn from_file_with_profile(path: &str, profile: Option<Profile>) -> Result<Self> {
+        let mut table = read_table(Path::new(path))?;
+
+        if let Some(profile) = profile {
+            profile::merge(&mut table, profile.defaults());
+            let overlay = profile.overlay_path(Path::new(path));
+            if overlay.exists() {
+                profile::merge(&mut table, read_table(&overlay)?);
+            }
+        }
+        Self::from_table(table)
+    }
+
+    /// Default configuration of a node with a profile's defaults layered over it
+    pub fn for_profile(node_id: u64, profile: Profile) -> Result<Self> {
+        let mut table = toml::Table::try_from(Self::default_for_node(node_id))
+            .map_err(|e| ScribeError::Configuration(e.to_string()))?;
+        profile::merge(&mut table, profile.defaults());
+        Self::from_table(table)
+    }
 
-        let mut config: Config = toml::from_str(&contents)?;
+    /// Deserialize a merged configuration, then apply environment overrides and
+    /// validate it
+    fn from_table(table: toml::Table) -> Result<Self> {
+        let mut config: Config = toml::Value::Table(table).try_into()?;
 
         // Apply environment variable overrides
         config.apply_env_overrides();
@@ -273,11 +988,25 @@ impl Config {
                 client_port: (8000 + node_id) as u16,
                 raft_port: (9000 + node_id) as u16,
                 seed_peers: Vec::new(),
+                raft_compression: RaftCompressionConfig::default(),
+                admin: AdminListenerConfig::default(),
+                rpc_auth: RpcAuthConfig::default(),
+                permissive_cors: default_permissive_cors(),
+                policy: NetworkPolicyConfig::default(),
             },
             storage: StorageConfig {
                 segment_size: 64 * 1024 * 1024,    // 64MB
                 max_cache_size: 256 * 1024 * 1024, // 256MB
-                s3: None,                          // No S3 by default
+                segment_max_age_secs: default_segment_max_age_secs(),
+                flush_every_ms: default_flush_every_ms(),
+                tombstone_retention_secs: default_tombstone_retention_secs(),
+                mmap_segments: false,
+                segment_ids: IdScheme::default(),
+                startup_check: StartupCheckMode::default(),
+                classes: StorageClasses::default(),
+                s3: None, // No S3 by default
+                archival: AutoArchivalConfig::default(),
+                snapshots: SnapshotStoreConfig::default(),
             },
             consensus: ConsensusConfig {
                 election_timeout_min: 1500,
@@ -286,9 +1015,22 @@ impl Config {
                 max_payload_entries: 300,
                 snapshot_logs_since_last: 5000,
                 max_in_snapshot_log_to_keep: 1000,
+                snapshot_transfer: SnapshotTransferConfig::default(),
+                metrics_history: MetricsHistoryConfig::default(),
+                follower_reads: true,
             },
             api: ApiConfig::default(),
             discovery: DiscoveryConfig::default(),
+            security: SecurityConfig::default(),
+            shadow: ShadowConfig::default(),
+            canary: CanaryConfig::default(),
+            metrics: MetricsConfig::default(),
+            runtime: RuntimeConfig::default(),
+            logging: LoggingConfig::default(),
+            health: HealthConfig::default(),
+            placement: PlacementConfig::default(),
+            quotas: QuotaConfig::default(),
+            clock: ClockConfig::default(),
         }
     }
 
@@ -323,6 +1065,11 @@ impl Config {
                 self.network.raft_port = parsed_port;
             }
         }
+        if let Ok(port) = std::env::var("SCRIBE_ADMIN_PORT") {
+            if let Ok(parsed_port) = port.parse() {
+                self.network.admin.port = Some(parsed_port);
+            }
+        }
 
         // Storage config overrides
         if let Ok(size) = std::env::var("SCRIBE_SEGMENT_SIZE") {
@@ -335,6 +1082,11 @@ impl Config {
                 self.storage.max_cache_size = parsed_size;
             }
         }
+        if let Ok(age) = std::env::var("SCRIBE_SEGMENT_MAX_AGE_SECS") {
+            if let Ok(parsed_age) = age.parse() {
+                self.storage.segment_max_age_secs = parsed_age;
+            }
+        }
 
         // Consensus config overrides
         if let Ok(timeout) = std::env::var("SCRIBE_ELECTION_TIMEOUT_MIN_MS") {
@@ -365,6 +1117,12 @@ impl Config {
         if let Ok(secret) = std::env::var("SCRIBE_CLUSTER_SECRET") {
             self.discovery.cluster_secret = Some(secret);
         }
+        if let Ok(cluster_id) = std::env::var("SCRIBE_CLUSTER_ID") {
+            self.discovery.cluster_id = cluster_id;
+        }
+        if let Ok(token) = std::env::var("SCRIBE_JOIN_TOKEN") {
+            self.discovery.join_token = Some(token);
+        }
         if let Ok(interval) = std::env::var("SCRIBE_DISCOVERY_HEARTBEAT_MS") {
             if let Ok(parsed_interval) = interval.parse() {
                 self.discovery.heartbeat_interval_ms = parsed_interval;
@@ -375,6 +1133,24 @@ impl Config {
                 self.discovery.failure_timeout_ms = parsed_timeout;
             }
         }
+        if let Ok(format) = std::env::var("SCRIBE_DISCOVERY_WIRE_FORMAT") {
+            match format.as_str() {
+                "protobuf" => self.discovery.wire_format = WireFormat::Protobuf,
+                "bincode" => self.discovery.wire_format = WireFormat::Bincode,
+                _ => {}
+            }
+        }
+
+        // Security config overrides
+        if let Ok(master_key) = std::env::var("SCRIBE_MASTER_KEY") {
+            self.security.encryption.master_key = Some(master_key);
+        }
+
+        // Runtime config overrides
+        self.runtime.apply_env_overrides();
+
+        // Logging config overrides
+        self.logging.keys.apply_env_overrides();
     }
 
     /// Validate the configuration
@@ -407,6 +1183,37 @@ impl Config {
                 "Client port and Raft port must be different".to_string(),
             ));
         }
+        if !(1..=9).contains(&self.network.raft_compression.level) {
+            return Err(ScribeError::Configuration(
+                "Raft compression level must be between 1 and 9".to_string(),
+            ));
+        }
+        if let Some(admin_port) = self.network.admin.port {
+            if admin_port == 0 {
+                return Err(ScribeError::Configuration(
+                    "Admin port must be greater than 0".to_string(),
+                ));
+            }
+            if admin_port == self.network.client_port || admin_port == self.network.raft_port {
+                return Err(ScribeError::Configuration(
+                    "Admin port must differ from the client and Raft ports".to_string(),
+                ));
+            }
+        }
+        if self.network.admin.localhost_only && self.network.admin.bind_address.is_some() {
+            return Err(ScribeError::Configuration(
+                "Admin localhost_only and bind_address are mutually exclusive".to_string(),
+            ));
+        }
+        self.network
+            .policy
+            .validate()
+            .map_err(ScribeError::Configuration)?;
+        self.network
+            .admin
+            .policy
+            .validate()
+            .map_err(ScribeError::Configuration)?;
 
         // Validate storage config
         if self.storage.segment_size == 0 {
@@ -420,10 +1227,55 @@ impl Config {
             ));
         }
 
-        // Validate consensus config
-        if self.consensus.election_timeout_min == 0 {
+        if self.storage.segment_ids == IdScheme::Snowflake && self.node.id > MAX_SNOWFLAKE_NODE_ID {
+            return Err(ScribeError::Configuration(format!(
+                "Snowflake segment IDs require a node ID of at most {}",
+                MAX_SNOWFLAKE_NODE_ID
+            )));
+        }
+
+        self.storage
+            .classes
+            .validate()
+            .map_err(ScribeError::Configuration)?;
+        if !self.storage.classes.is_empty() && self.storage.s3.is_none() {
             return Err(ScribeError::Configuration(
-                "Election timeout minimum must be greater than 0".to_string(),
+                "Storage classes require S3 storage".to_string(),
+            ));
+        }
+
+        let archival = &self.storage.archival;
+        if archival.enabled && self.storage.s3.is_none() {
+            return Err(ScribeError::Configuration(
+                "Automatic archival requires S3 storage".to_string(),
+            ));
+        }
+        if archival.enabled && (archival.interval_secs == 0 || archival.max_keys_per_run == 0) {
+            return Err(ScribeError::Configuration(
+                "Archival interval and keys per run must be greater than 0".to_string(),
+            ));
+        }
+        self.storage.snapshots.layout.validate()?;
+
+        if let Some(s3) = &self.storage.s3 {
+            if s3.credentials.is_some() && s3.credential_refresh_secs == 0 {
+                return Err(ScribeError::Configuration(
+                    "S3 credential refresh interval must be greater than 0".to_string(),
+                ));
+            }
+            if let Some(CredentialSource::Command { command }) = &s3.credentials {
+                if command.is_empty() {
+                    return Err(ScribeError::Configuration(
+                        "S3 credential command cannot be empty".to_string(),
+                    ));
+                }
+            }
+        }
+
+        // Validate consensus config
+        if self.consensus.election_timeout_min == 0 {
+            return Err(ScribeError::Configuration(
+                "Election timeout minimum must be greater than 0".to_string(),
             ));
         }
         if self.consensus.election_timeout_max == 0 {
@@ -446,10 +1298,201 @@ impl Config {
                 "Heartbeat interval must be less than election timeout minimum".to_string(),
             ));
         }
+        let transfer = &self.consensus.snapshot_transfer;
+        if transfer.chunk_size_bytes == 0 {
+            return Err(ScribeError::Configuration(
+                "Snapshot chunk size must be greater than 0".to_string(),
+            ));
+        }
+        for rate in [transfer.send_bytes_per_sec, transfer.receive_bytes_per_sec] {
+            if rate > 0 && transfer.chunk_size_bytes > rate * MAX_SNAPSHOT_CHUNK_SECS {
+                return Err(ScribeError::Configuration(format!(
+                    "Snapshot chunks must take at most {} seconds at the transfer rate; \
+                     lower chunk_size_bytes or raise the rate",
+                    MAX_SNAPSHOT_CHUNK_SECS
+                )));
+            }
+        }
+
+        let history = &self.consensus.metrics_history;
+        if history.enabled && history.interval_secs == 0 {
+            return Err(ScribeError::Configuration(
+                "Metrics history interval must be greater than 0".to_string(),
+            ));
+        }
+        if history.enabled && history.retention_secs < history.interval_secs {
+            return Err(ScribeError::Configuration(
+                "Metrics history retention must be at least the sampling interval".to_string(),
+            ));
+        }
+
+        // Validate API config
+        if self.api.max_in_flight_requests == 0 {
+            return Err(ScribeError::Configuration(
+                "Max in-flight requests must be greater than 0".to_string(),
+            ));
+        }
+        if self.api.low_priority_share > 100 || self.api.high_priority_reserve > 100 {
+            return Err(ScribeError::Configuration(
+                "Priority shares must be percentages between 0 and 100".to_string(),
+            ));
+        }
+        if self.api.max_queue_length == 0 {
+            return Err(ScribeError::Configuration(
+                "Max queue length must be greater than 0".to_string(),
+            ));
+        }
+        if self.api.cache_warm_keys > 0 && self.api.cache_warm_save_interval_secs == 0 {
+            return Err(ScribeError::Configuration(
+                "Cache warm save interval must be greater than 0".to_string(),
+            ));
+        }
+        self.api
+            .read_path
+            .validate()
+            .map_err(ScribeError::Configuration)?;
+
+        // Validate discovery config
+        if self.discovery.require_join_token && self.discovery.cluster_secret.is_none() {
+            return Err(ScribeError::Configuration(
+                "Join tokens require a cluster secret".to_string(),
+            ));
+        }
+        if self.network.rpc_auth.enabled {
+            if self.discovery.cluster_secret.is_none() {
+                return Err(ScribeError::Configuration(
+                    "RPC authentication requires a cluster secret".to_string(),
+                ));
+            }
+            if self.network.rpc_auth.max_clock_skew_ms == 0 {
+                return Err(ScribeError::Configuration(
+                    "RPC max clock skew must be greater than 0".to_string(),
+                ));
+            }
+        }
+
+        // Validate security config
+        self.security
+            .tls
+            .validate()
+            .map_err(ScribeError::Configuration)?;
+        self.security
+            .encryption
+            .validate()
+            .map_err(ScribeError::Configuration)?;
+        self.security
+            .access_trace
+            .validate()
+            .map_err(ScribeError::Configuration)?;
+
+        self.runtime
+            .validate()
+            .map_err(ScribeError::Configuration)?;
+        self.logging
+            .keys
+            .validate()
+            .map_err(ScribeError::Configuration)?;
+        self.logging
+            .access
+            .validate()
+            .map_err(ScribeError::Configuration)?;
+        self.health.validate().map_err(ScribeError::Configuration)?;
+        self.placement
+            .validate()
+            .map_err(ScribeError::Configuration)?;
+        self.quotas.validate().map_err(ScribeError::Configuration)?;
+        self.clock.validate().map_err(ScribeError::Configuration)?;
+
+        // Validate shadow config
+        if self.shadow.enabled {
+            if self.shadow.target_url.is_some() == self.shadow.namespace.is_some() {
+                return Err(ScribeError::Configuration(
+                    "Shadow mode requires exactly one of target_url or namespace".to_string(),
+                ));
+            }
+            if self.shadow.queue_capacity == 0 {
+                return Err(ScribeError::Configuration(
+                    "Shadow queue capacity must be greater than 0".to_string(),
+                ));
+            }
+        }
+
+        // Validate canary config
+        if self.canary.enabled {
+            if self.canary.target_url.is_none() {
+                return Err(ScribeError::Configuration(
+                    "Canary mirroring requires target_url".to_string(),
+                ));
+            }
+            if !(0.0..=100.0).contains(&self.canary.sample_percent) {
+                return Err(ScribeError::Configuration(
+                    "Canary sample percentage must be between 0 and 100".to_string(),
+                ));
+            }
+            if self.canary.queue_capacity == 0 || self.canary.timeout_ms == 0 {
+                return Err(ScribeError::Configuration(
+                    "Canary queue capacity and timeout must be greater than 0".to_string(),
+                ));
+            }
+        }
+
+        // Validate metrics push config
+        let push = &self.metrics.push;
+        if push.enabled {
+            if push.url.is_none() {
+                return Err(ScribeError::Configuration(
+                    "Metrics push requires url".to_string(),
+                ));
+            }
+            if push.job.is_empty() {
+                return Err(ScribeError::Configuration(
+                    "Metrics push job must not be empty".to_string(),
+                ));
+            }
+            if push.interval_secs == 0
+                || push.timeout_ms == 0
+                || push.max_samples_per_send == 0
+                || push.max_pending_batches == 0
+            {
+                return Err(ScribeError::Configuration(
+                    "Metrics push interval, timeout, samples per send and pending batches \
+                     must be greater than 0"
+                        .to_string(),
+                ));
+            }
+        }
 
         Ok(())
     }
 
+    /// Hash of the settings that are expected to match across the cluster
+    ///
+    /// Covers the consensus and API sections, the local storage settings and the
+    /// cluster ID. Per-node settings (node, network, ports, S3 buckets and credentials,
+    /// secrets) are excluded so that identically configured nodes report the same hash.
+    pub fn config_hash(&self) -> String {
+        let shared = serde_json::json!({
+            "consensus": self.consensus,
+            "api": self.api,
+            "storage": {
+                "segment_size": self.storage.segment_size,
+                "max_cache_size": self.storage.max_cache_size,
+                "segment_max_age_secs": self.storage.segment_max_age_secs,
+            },
+            "cluster_id": self.discovery.cluster_id,
+            "clock": self.clock,
+        });
+        hex::encode(Sha256::digest(shared.to_string().as_bytes()))
+    }
+
+    /// Get the maximum active segment age as Duration, `None` if disabled
+    pub fn segment_max_age(&self) -> Option<Duration> {
+        match self.storage.segment_max_age_secs {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        }
+    }
+
     /// Get election timeout minimum as Duration
     pub fn election_timeout_min(&self) -> Duration {
         Duration::from_millis(self.consensus.election_timeout_min)
@@ -469,6 +1512,9 @@ impl Config {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::logging::{KeyRedaction, DEFAULT_KEY_TRUNCATE_LEN};
+    use crate::read_path::ReadTier;
+    use crate::storage::classes::StorageClass;
     use std::env;
 
     // Test constants to avoid hardcoded values
@@ -512,6 +1558,140 @@ mod tests {
         assert!(config.validate().is_err());
     }
 
+    #[test]
+    fn test_config_validation_snowflake_node_id() {
+        let mut config = Config::default_for_node(MAX_SNOWFLAKE_NODE_ID + 1);
+        assert!(config.validate().is_err());
+
+        config.storage.segment_ids = IdScheme::Sequential;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_config_validation_s3_credential_source() {
+        let s3: S3Config = toml::from_str(
+            r#"
+            bucket = "ledger"
+            region = "us-east-1"
+            credentials = { type = "command", command = [] }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(s3.credential_refresh_secs, DEFAULT_CREDENTIAL_REFRESH_SECS);
+
+        let mut config = Config::default_for_node(TEST_NODE_ID);
+        config.storage.s3 = Some(s3);
+        assert!(config.validate().is_err());
+
+        let s3 = config.storage.s3.as_mut().unwrap();
+        s3.credentials = Some(CredentialSource::File {
+            path: PathBuf::from("/run/secrets/s3.json"),
+        });
+        assert!(config.validate().is_ok());
+        config.storage.s3.as_mut().unwrap().credential_refresh_secs = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_config_validation_storage_classes() {
+        let mut config = Config::default_for_node(TEST_NODE_ID);
+        config.storage.classes = toml::from_str(
+            r#"
+            [logs]
+            prefixes = ["logs/"]
+            retention_secs = 604800
+            "#,
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+
+        config.storage.s3 = Some(
+            toml::from_str(
+                r#"
+                bucket = "ledger"
+                region = "us-east-1"
+                "#,
+            )
+            .unwrap(),
+        );
+        assert!(config.validate().is_ok());
+
+        config.storage.classes.insert(
+            "other-logs",
+            StorageClass {
+                prefixes: vec!["logs/".to_string()],
+                compression: true,
+                archive_after_secs: None,
+                retention_secs: None,
+                replicate_to_bucket: None,
+            },
+        );
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_config_validation_auto_archival() {
+        let mut config = Config::default_for_node(TEST_NODE_ID);
+        config.storage.archival.enabled = true;
+        assert!(config.validate().is_err());
+
+        config.storage.s3 = Some(
+            toml::from_str(
+                r#"
+                bucket = "ledger"
+                region = "us-east-1"
+                "#,
+            )
+            .unwrap(),
+        );
+        assert!(config.validate().is_ok());
+
+        config.storage.archival.max_keys_per_run = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_config_snapshot_store() {
+        let mut config = Config::default_for_node(TEST_NODE_ID);
+        assert!(!config.storage.snapshots.persist);
+
+        config.storage.snapshots = toml::from_str(
+            r#"
+            persist = true
+            namespaces = ["tenant-a/", "tenant-b/"]
+            checksums = true
+
+            [transformers]
+            "logs/" = ["gzip"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.storage.snapshots.layout.namespaces.len(), 2);
+        assert!(config.storage.snapshots.layout.checksums);
+        assert_eq!(
+            config.storage.snapshots.layout.transformers["logs/"],
+            vec!["gzip".to_string()]
+        );
+        assert!(config.validate().is_ok());
+
+        config
+            .storage
+            .snapshots
+            .layout
+            .transformers
+            .insert(String::new(), vec!["rot13".to_string()]);
+        assert!(config.validate().is_err());
+        config.storage.snapshots.layout.transformers.clear();
+
+        config
+            .storage
+            .snapshots
+            .layout
+            .namespaces
+            .push(String::new());
+        assert!(config.validate().is_err());
+    }
+
     #[test]
     fn test_config_validation_heartbeat_timeout() {
         let mut config = Config::default_for_node(TEST_NODE_ID);
@@ -521,6 +1701,305 @@ mod tests {
         assert!(config.validate().is_err());
     }
 
+    #[test]
+    fn test_config_validation_tls_missing_key() {
+        let mut config = Config::default_for_node(TEST_NODE_ID);
+        config.security.tls.enabled = true;
+        config.security.tls.cert_path = Some(PathBuf::from("/cert.pem"));
+
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_config_validation_encryption_master_key() {
+        let mut config = Config::default_for_node(TEST_NODE_ID);
+        config.security.encryption.enabled = true;
+        assert!(config.validate().is_err());
+
+        config.security.encryption.master_key = Some("not hex".to_string());
+        assert!(config.validate().is_err());
+
+        config.security.encryption.master_key = Some("0f".repeat(32));
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_config_validation_shadow_target() {
+        let mut config = Config::default_for_node(TEST_NODE_ID);
+        config.shadow.enabled = true;
+        assert!(config.validate().is_err());
+
+        config.shadow.namespace = Some("shadow/".to_string());
+        assert!(config.validate().is_ok());
+
+        config.shadow.target_url = Some("http://10.0.2.1:8001".to_string());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_config_validation_canary() {
+        let mut config = Config::default_for_node(TEST_NODE_ID);
+        config.canary.enabled = true;
+        assert!(config.validate().is_err());
+
+        config.canary.target_url = Some("http://10.0.3.1:8001".to_string());
+        assert!(config.validate().is_ok());
+
+        config.canary.sample_percent = 101.0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_config_validation_metrics_push() {
+        let mut config = Config::default_for_node(TEST_NODE_ID);
+        assert!(!config.metrics.push.enabled);
+        config.metrics.push.enabled = true;
+        assert!(config.validate().is_err());
+
+        config.metrics.push.url = Some("http://pushgateway:9091".to_string());
+        assert!(config.validate().is_ok());
+        config.metrics.push.interval_secs = 0;
+        assert!(config.validate().is_err());
+
+        let metrics: MetricsConfig = toml::from_str(
+            r#"
+            [push]
+            enabled = true
+            mode = "remote_write"
+            url = "http://prometheus:9090/api/v1/write"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(metrics.push.mode, MetricsPushMode::RemoteWrite);
+        assert_eq!(metrics.push.interval_secs, 15);
+    }
+
+    #[test]
+    fn test_config_validation_read_path() {
+        let mut config = Config::default_for_node(TEST_NODE_ID);
+        config.api.read_path.namespaces.insert(
+            "quotes/".to_string(),
+            vec![ReadTier::Cache, ReadTier::Local],
+        );
+        assert!(config.validate().is_ok());
+
+        config
+            .api
+            .read_path
+            .namespaces
+            .insert("ticks/".to_string(), vec![ReadTier::Cache, ReadTier::S3]);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_raft_compression_config() {
+        let mut config = Config::default_for_node(TEST_NODE_ID);
+        assert!(!config.network.raft_compression.applies_to(2));
+
+        config.network.raft_compression.enabled = true;
+        assert!(config.network.raft_compression.applies_to(2));
+        config.network.raft_compression.peers = vec![3];
+        assert!(!config.network.raft_compression.applies_to(2));
+        assert!(config.network.raft_compression.applies_to(3));
+
+        config.network.raft_compression.level = 0;
+        assert!(config.validate().is_err());
+        config.network.raft_compression.level = 9;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_admin_listener_config() {
+        let mut config = Config::default_for_node(TEST_NODE_ID);
+        assert_eq!(config.network.admin.listen_addr(), None);
+
+        config.network.admin.port = Some(8601);
+        assert_eq!(
+            config.network.admin.listen_addr().as_deref(),
+            Some("0.0.0.0:8601")
+        );
+        config.network.admin.localhost_only = true;
+        assert_eq!(
+            config.network.admin.listen_addr().as_deref(),
+            Some("127.0.0.1:8601")
+        );
+        assert!(config.validate().is_ok());
+
+        config.network.admin.bind_address = Some("10.0.0.5".to_string());
+        assert!(config.validate().is_err());
+        config.network.admin.localhost_only = false;
+        assert_eq!(
+            config.network.admin.listen_addr().as_deref(),
+            Some("10.0.0.5:8601")
+        );
+        assert!(config.validate().is_ok());
+
+        config.network.admin.port = Some(config.network.client_port);
+        assert!(config.validate().is_err());
+        config.network.admin.port = Some(0);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_rpc_auth_config() {
+        let mut config = Config::default_for_node(TEST_NODE_ID);
+        assert!(!config.network.rpc_auth.enabled);
+        assert_eq!(config.network.rpc_auth.max_clock_skew_ms, 30_000);
+
+        config.network.rpc_auth.enabled = true;
+        assert!(config.validate().is_err());
+        config.discovery.cluster_secret = Some("secret".to_string());
+        assert!(config.validate().is_ok());
+        config.network.rpc_auth.max_clock_skew_ms = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_snapshot_transfer_config() {
+        let mut config = Config::default_for_node(TEST_NODE_ID);
+        assert_eq!(config.consensus.snapshot_transfer.chunk_timeout_ms(), None);
+
+        let transfer = &mut config.consensus.snapshot_transfer;
+        transfer.chunk_size_bytes = 1024 * 1024;
+        transfer.send_bytes_per_sec = 4 * 1024 * 1024;
+        transfer.receive_bytes_per_sec = 1024 * 1024;
+        // Four chunks at the slower receive rate, plus a second
+        assert_eq!(transfer.chunk_timeout_ms(), Some(5000));
+        assert!(config.validate().is_ok());
+
+        config.consensus.snapshot_transfer.receive_bytes_per_sec = 100 * 1024;
+        assert!(config.validate().is_err());
+        config.consensus.snapshot_transfer.chunk_size_bytes = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_metrics_history_config() {
+        let mut config = Config::default_for_node(TEST_NODE_ID);
+        assert!(config.consensus.metrics_history.enabled);
+        assert!(config.validate().is_ok());
+
+        config.consensus.metrics_history.retention_secs = 5;
+        assert!(config.validate().is_err());
+        config.consensus.metrics_history.enabled = false;
+        assert!(config.validate().is_ok());
+
+        let config: ConsensusConfig = toml::from_str(
+            "heartbeat_interval_ms = 300\n[metrics_history]\nretention_secs = 3600\n",
+        )
+        .unwrap();
+        assert_eq!(config.metrics_history.interval_secs, 10);
+        assert_eq!(config.metrics_history.retention_secs, 3600);
+    }
+
+    #[test]
+    fn test_config_validation_join_token_requires_secret() {
+        let mut config = Config::default_for_node(TEST_NODE_ID);
+        config.discovery.require_join_token = true;
+        assert!(config.validate().is_err());
+
+        config.discovery.cluster_secret = Some("secret".to_string());
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_config_validation_network_policy() {
+        let mut config = Config::default_for_node(TEST_NODE_ID);
+        config.network.policy.allow = vec!["10.0.0.0/8".to_string()];
+        config.network.admin.policy.deny = vec!["fd00::/8".to_string()];
+        assert!(config.validate().is_ok());
+
+        config.network.admin.policy.allow = vec!["10.0.0.0/40".to_string()];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_config_validation_key_logging() {
+        let mut config = Config::default_for_node(TEST_NODE_ID);
+        config.logging = toml::from_str("[keys]\nmode = \"truncate\"").unwrap();
+        assert_eq!(config.logging.keys.mode, KeyRedaction::Truncate);
+        assert_eq!(config.logging.keys.truncate_len, DEFAULT_KEY_TRUNCATE_LEN);
+        assert!(config.validate().is_ok());
+
+        config.logging.keys.truncate_len = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_discovery_wire_format() {
+        let config = Config::default_for_node(TEST_NODE_ID);
+        assert_eq!(config.discovery.wire_format, WireFormat::Protobuf);
+
+        let discovery: DiscoveryConfig = toml::from_str("wire_format = \"bincode\"").unwrap();
+        assert_eq!(discovery.wire_format, WireFormat::Bincode);
+        assert!(toml::from_str::<DiscoveryConfig>("wire_format = \"json\"").is_err());
+    }
+
+    #[test]
+    fn test_config_validation_health() {
+        let mut config = Config::default_for_node(TEST_NODE_ID);
+        config.health = toml::from_str(
+            r#"
+            interval_secs = 30
+
+            [[actions]]
+            action = "restart_archival"
+            component = "archival"
+            below = 40
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.health.actions[0].cooldown_secs, 300);
+        assert_eq!(config.health.weights.storage, 3);
+        assert!(config.validate().is_ok());
+
+        config.health.actions[0].below = 101;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_config_validation_quotas() {
+        let mut config = Config::default_for_node(TEST_NODE_ID);
+        config.quotas = toml::from_str(
+            r#"
+            enabled = true
+
+            [default]
+            daily_ops = 10000
+
+            [keys.2bb80d537b1da3e3]
+            monthly_bytes = 1073741824
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.quotas.flush_interval_ms, 1000);
+        assert_eq!(
+            config.quotas.limits_for("0000000000000000").daily_ops,
+            Some(10000)
+        );
+        let limits = config.quotas.limits_for("2bb80d537b1da3e3");
+        assert_eq!(limits.daily_ops, None);
+        assert_eq!(limits.monthly_bytes, Some(1 << 30));
+        assert!(config.validate().is_ok());
+
+        config.quotas.flush_interval_ms = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_config_hash() {
+        let config1 = Config::default_for_node(TEST_NODE_ID);
+        let config2 = Config::default_for_node(TEST_NODE_ID_2);
+
+        // Node-specific settings do not affect the hash
+        assert_eq!(config1.config_hash(), config2.config_hash());
+        assert_eq!(config1.config_hash().len(), 64);
+
+        let mut changed = config1.clone();
+        changed.consensus.heartbeat_interval_ms += 1;
+        assert_ne!(config1.config_hash(), changed.config_hash());
+    }
+
     #[test]
     fn test_duration_helpers() {
         let config = Config::default_for_node(TEST_NODE_ID);
@@ -530,6 +2009,71 @@ mod tests {
         assert_eq!(config.heartbeat_interval(), Duration::from_millis(300));
     }
 
+    #[test]
+    fn test_segment_max_age() {
+        let mut config = Config::default_for_node(TEST_NODE_ID);
+        assert_eq!(config.segment_max_age(), Some(Duration::from_secs(600)));
+
+        config.storage.segment_max_age_secs = 0;
+        assert_eq!(config.segment_max_age(), None);
+    }
+
+    #[test]
+    fn test_profile_layering() {
+        let dir = std::env::temp_dir().join(format!("scribe-profile-{}", fastrand::u64(..)));
+        std::fs::create_dir_all(&dir).unwrap();
+        let base = dir.join("node.toml");
+        let mut config = Config::default_for_node(TEST_NODE_ID);
+        config.discovery.cluster_secret = Some("secret".to_string());
+        config.storage.segment_size = 4096;
+        std::fs::write(&base, toml::to_string(&config).unwrap()).unwrap();
+        std::fs::write(
+            dir.join("node.prod.toml"),
+            "[storage]\nflush_every_ms = 10\n",
+        )
+        .unwrap();
+        let base = base.to_str().unwrap();
+
+        let plain = Config::from_file_with_profile(base, None).unwrap();
+        assert!(!plain.network.rpc_auth.enabled);
+        assert_eq!(plain.storage.flush_every_ms, 500);
+
+        let prod = Config::from_file_with_profile(base, Some(Profile::Prod)).unwrap();
+        assert_eq!(prod.storage.segment_size, 4096);
+        assert!(prod.network.rpc_auth.enabled);
+        assert!(prod.discovery.require_join_token);
+        assert!(!prod.network.permissive_cors);
+        assert_eq!(prod.storage.flush_every_ms, 10);
+
+        // No overlay file for staging: only the built-in defaults apply
+        let staging = Config::from_file_with_profile(base, Some(Profile::Staging)).unwrap();
+        assert_eq!(staging.storage.flush_every_ms, 50);
+        assert!(staging.network.permissive_cors);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let dev = Config::for_profile(TEST_NODE_ID, Profile::Dev).unwrap();
+        assert!(dev.node.data_dir.starts_with(std::env::temp_dir()));
+        assert!(dev.network.seed_peers.is_empty());
+        // Production requires a cluster secret for authenticated traffic
+        assert!(Config::for_profile(TEST_NODE_ID, Profile::Prod).is_err());
+    }
+
+    #[test]
+    fn test_storage_startup_check() {
+        let config = Config::default_for_node(TEST_NODE_ID);
+        assert_eq!(config.storage.startup_check, StartupCheckMode::Report);
+
+        let storage: StorageConfig = toml::from_str(
+            r#"
+            segment_size = 1024
+            max_cache_size = 1024
+            startup_check = "repair"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(storage.startup_check, StartupCheckMode::Repair);
+    }
+
     #[test]
     fn test_env_override_node_id() {
         env::set_var("SCRIBE_NODE_ID", "42");
@@ -564,5 +2108,6 @@ mod tests {
 
         assert_eq!(deserialized.node.id, config.node.id);
         assert_eq!(deserialized.network.client_port, config.network.client_port);
+        assert!(!deserialized.security.tls.enabled);
     }
 }
@@ -6,26 +6,107 @@
 #![allow(clippy::io_other_error)]
 
 pub mod network;
+pub mod snapshot_store;
 pub mod state_machine;
 pub mod storage;
+pub mod throttle;
 pub mod type_config;
+pub mod wire;
 
-pub use network::{Network, NetworkFactory};
-pub use state_machine::{SnapshotBuilder, StateMachine, StateMachineStore};
+pub use network::{
+    serve_raft_rpc, serve_raft_rpc_throttled, serve_raft_rpc_with_auth, Network, NetworkFactory,
+};
+pub use snapshot_store::SnapshotStore;
+pub use state_machine::{
+    ChangeEvent, ChangeKind, KeyChange, SnapshotBuilder, StateMachine, StateMachineStore,
+    StoredValue, TaggedState,
+};
 pub use storage::{LogReader, RaftStorage};
-pub use type_config::{AppRequest, AppResponse, TypeConfig};
+pub use throttle::TransferThrottle;
+pub use type_config::{AppRequest, AppResponse, TypeConfig, STAMP_WIRE_VERSION};
 
-use openraft::{BasicNode, Config, Raft};
-use std::collections::BTreeSet;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use openraft::error::{CheckIsLeaderError, ClientWriteError, RaftError};
+use openraft::{BasicNode, ChangeMembers, Config, LogId, Raft, RaftMetrics};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, watch, RwLock};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
 
-use crate::config::ConsensusConfig as ScribeConsensusConfig;
-use crate::types::NodeId;
+use crate::config::{
+    ConsensusConfig as ScribeConsensusConfig, MetricsHistoryConfig, RaftCompressionConfig,
+    SnapshotTransferConfig,
+};
+use crate::error::ScribeError;
+use crate::learner_progress::{LearnerProgressReport, LearnerProgressTracker, LearnerReplication};
+use crate::metrics::{LINEARIZABLE_READS, RAFT_COMMIT_LATENCY};
+use crate::placement::PeerLatencies;
+use crate::quota::KeyUsage;
+use crate::raft_history::{RaftHistory, RaftSample};
+use crate::security::RpcAuthenticator;
+use crate::types::{Key, LedgerTag, LockLease, NodeId, QueueStats, SegmentAnchor, SegmentId};
+use crate::wire::{PeerVersions, LEGACY_WIRE_VERSION};
 
 /// Type alias for the Raft instance
 pub type RaftInstance = Raft<TypeConfig>;
 
+/// Share of the election timeout the leader read lease gives up to clock drift
+const READ_LEASE_DRIFT_PERCENT: u64 = 10;
+
+/// How long a follower waits to apply up to a read index before redirecting the read
+const FOLLOWER_READ_APPLY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Capacity of the leadership change channel; slow subscribers miss older changes
+const LEADERSHIP_EVENT_CAPACITY: usize = 64;
+
+/// How long compacting the log waits for the snapshot to be built
+pub const SNAPSHOT_BUILD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Leadership as seen by one node
+///
+/// Emitted by [`ConsensusNode::subscribe_leadership`] whenever the known leader or
+/// the node's own leader role changes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeadershipChange {
+    /// Node reporting the change
+    pub node_id: NodeId,
+    /// Raft term the change was observed in
+    pub term: u64,
+    /// Current leader, `None` while an election is in progress
+    pub leader_id: Option<NodeId>,
+    /// Whether the reporting node is the leader
+    pub is_leader: bool,
+}
+
+impl LeadershipChange {
+    fn from_metrics(node_id: NodeId, metrics: &RaftMetrics<NodeId, BasicNode>) -> Self {
+        Self {
+            node_id,
+            term: metrics.current_term,
+            leader_id: metrics.current_leader,
+            is_leader: metrics.state.is_leader(),
+        }
+    }
+
+    /// Whether `other` reports a different leader or leader role
+    fn differs_from(&self, other: &Self) -> bool {
+        self.leader_id != other.leader_id || self.is_leader != other.is_leader
+    }
+}
+
+/// Lease held by the leader after a quorum confirmed its leadership
+#[derive(Debug, Clone, Copy)]
+struct ReadLease {
+    /// Term the leadership was confirmed in
+    term: u64,
+    /// Instant after which the lease can no longer be trusted
+    expires_at: Instant,
+}
+
 /// Consensus node that integrates OpenRaft with storage, state machine, and network
 pub struct ConsensusNode {
     /// The Raft instance
@@ -34,8 +115,34 @@ pub struct ConsensusNode {
     network_factory: Arc<RwLock<NetworkFactory>>,
     /// State machine store for direct reads
     state_machine: Arc<StateMachineStore>,
+    /// Database backing the Raft log, for storage usage reporting
+    db: sled::Db,
     /// Node ID
     node_id: NodeId,
+    /// How long a quorum confirmation lets the leader serve reads locally
+    read_lease_duration: Duration,
+    /// Current leader read lease, if any
+    read_lease: Mutex<Option<ReadLease>>,
+    /// Whether a follower serves linearizable reads after asking the leader for a
+    /// read index
+    follower_reads: AtomicBool,
+    /// Leadership changes observed on this node
+    leadership_events: broadcast::Sender<LeadershipChange>,
+    /// Task turning Raft metrics updates into leadership changes
+    leadership_watcher: JoinHandle<()>,
+    /// Paces snapshot chunks sent to peers
+    snapshot_send_throttle: Arc<TransferThrottle>,
+    /// Paces snapshot chunks received from peers
+    snapshot_receive_throttle: Arc<TransferThrottle>,
+    /// Catch-up progress of learners, while this node leads
+    learner_progress: LearnerProgressTracker,
+    /// Wire versions advertised by other nodes
+    peer_versions: PeerVersions,
+    /// Round trips of heartbeats and votes to other nodes
+    peer_latencies: PeerLatencies,
+    /// Most log entries a joining node replays; beyond this the log is compacted
+    /// first so it installs a snapshot instead
+    join_log_limit: u64,
 }
 
 impl ConsensusNode {
@@ -52,6 +159,9 @@ impl ConsensusNode {
             max_payload_entries: 300,
             snapshot_logs_since_last: 5000,
             max_in_snapshot_log_to_keep: 1000,
+            snapshot_transfer: SnapshotTransferConfig::default(),
+            metrics_history: MetricsHistoryConfig::default(),
+            follower_reads: true,
         };
 
         Self::new_with_scribe_config(node_id, db, &scribe_config).await
@@ -63,7 +173,29 @@ impl ConsensusNode {
         db: sled::Db,
         scribe_config: &ScribeConsensusConfig,
     ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        let config = Config {
+        Self::from_scribe_config(node_id, db, scribe_config, StateMachineStore::new()).await
+    }
+
+    /// Create a new consensus node from Scribe configuration that also writes its
+    /// snapshots to `snapshots`, resuming from the latest snapshot stored there
+    pub async fn new_with_snapshot_store(
+        node_id: NodeId,
+        db: sled::Db,
+        scribe_config: &ScribeConsensusConfig,
+        snapshots: Arc<SnapshotStore>,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let state_machine = StateMachineStore::with_snapshot_store(snapshots).await?;
+        Self::from_scribe_config(node_id, db, scribe_config, state_machine).await
+    }
+
+    async fn from_scribe_config(
+        node_id: NodeId,
+        db: sled::Db,
+        scribe_config: &ScribeConsensusConfig,
+        state_machine: StateMachineStore,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let transfer = &scribe_config.snapshot_transfer;
+        let mut config = Config {
             heartbeat_interval: scribe_config.heartbeat_interval_ms,
             election_timeout_min: scribe_config.election_timeout_min,
             election_timeout_max: scribe_config.election_timeout_max,
@@ -74,10 +206,20 @@ impl ConsensusNode {
                 scribe_config.snapshot_logs_since_last,
             ),
             max_in_snapshot_log_to_keep: scribe_config.max_in_snapshot_log_to_keep,
+            snapshot_max_chunk_size: transfer.chunk_size_bytes,
             ..Default::default()
         };
+        if let Some(timeout_ms) = transfer.chunk_timeout_ms() {
+            config.install_snapshot_timeout = config.install_snapshot_timeout.max(timeout_ms);
+        }
 
-        Self::new_with_config(node_id, db, config).await
+        let node = Self::with_state_machine(node_id, db, config, state_machine).await?;
+        node.set_snapshot_transfer_rates(
+            transfer.send_bytes_per_sec,
+            transfer.receive_bytes_per_sec,
+        );
+        node.set_follower_reads(scribe_config.follower_reads);
+        Ok(node)
     }
 
     /// Create a new consensus node with custom configuration
@@ -86,17 +228,35 @@ impl ConsensusNode {
         db: sled::Db,
         config: Config,
     ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        // Create storage
-        let storage = RaftStorage::new(db);
+        Self::with_state_machine(node_id, db, config, StateMachineStore::new()).await
+    }
 
-        // Create separate state machine instance (not from storage)
-        let state_machine = StateMachineStore::new();
+    async fn with_state_machine(
+        node_id: NodeId,
+        db: sled::Db,
+        config: Config,
+        state_machine: StateMachineStore,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        // No other node can be elected before followers' election timeout elapses,
+        // minus a safety margin for clock drift
+        let read_lease_duration = Duration::from_millis(
+            config.election_timeout_min * (100 - READ_LEASE_DRIFT_PERCENT) / 100,
+        );
+
+        let join_log_limit = config.max_in_snapshot_log_to_keep;
+
+        // Create storage
+        let storage = RaftStorage::new(db.clone());
 
         // Keep a reference to the state machine for direct reads
         let state_machine_ref = Arc::new(state_machine.clone());
 
         // Create network factory
         let network_factory = NetworkFactory::new(node_id);
+        let snapshot_send_throttle = network_factory.snapshot_throttle();
+        let learner_progress = LearnerProgressTracker::new(network_factory.transfers());
+        let peer_versions = network_factory.peer_versions();
+        let peer_latencies = network_factory.latencies();
 
         // Create Raft instance with separate log store and state machine
         let raft = Raft::new(
@@ -114,11 +274,27 @@ impl ConsensusNode {
             )) as Box<dyn std::error::Error + Send + Sync>
         })?;
 
+        let (leadership_events, _) = broadcast::channel(LEADERSHIP_EVENT_CAPACITY);
+        let leadership_watcher =
+            spawn_leadership_watcher(node_id, raft.metrics(), leadership_events.clone());
+
         Ok(Self {
             raft: Arc::new(raft),
             network_factory: Arc::new(RwLock::new(network_factory)),
             state_machine: state_machine_ref,
+            db,
             node_id,
+            read_lease_duration,
+            read_lease: Mutex::new(None),
+            follower_reads: AtomicBool::new(true),
+            leadership_events,
+            leadership_watcher,
+            snapshot_send_throttle,
+            snapshot_receive_throttle: Arc::new(TransferThrottle::unlimited()),
+            learner_progress,
+            peer_versions,
+            peer_latencies,
+            join_log_limit,
         })
     }
 
@@ -138,6 +314,44 @@ impl ConsensusNode {
         network_factory.register_node(node_id, address).await;
     }
 
+    /// Set which peers get compressed Raft messages
+    ///
+    /// Call before peers are contacted; connections opened earlier keep their settings.
+    pub async fn set_raft_compression(&self, config: RaftCompressionConfig) {
+        let network_factory = self.network_factory.read().await;
+        network_factory.set_compression(config).await;
+    }
+
+    /// Sign Raft messages to peers with `rpc_auth`
+    ///
+    /// Call before peers are contacted; connections opened earlier keep their settings.
+    /// Serve RPCs with [`serve_raft_rpc_with_auth`] to verify peers' messages.
+    pub async fn set_rpc_auth(&self, rpc_auth: Option<Arc<RpcAuthenticator>>) {
+        let network_factory = self.network_factory.read().await;
+        network_factory.set_rpc_auth(rpc_auth).await;
+    }
+
+    /// Limit snapshot bytes per second sent to and received from peers (0 is unlimited)
+    ///
+    /// The send limit is shared by all peers, so several replicas bootstrapping at
+    /// once split it.
+    pub fn set_snapshot_transfer_rates(&self, send_bytes_per_sec: u64, receive_bytes_per_sec: u64) {
+        self.snapshot_send_throttle.set_rate(send_bytes_per_sec);
+        self.snapshot_receive_throttle
+            .set_rate(receive_bytes_per_sec);
+    }
+
+    /// Let followers serve linearizable reads (the default) or redirect them to the
+    /// leader
+    pub fn set_follower_reads(&self, enabled: bool) {
+        self.follower_reads.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Throttle of received snapshot chunks, for [`serve_raft_rpc_throttled`]
+    pub fn snapshot_receive_throttle(&self) -> Arc<TransferThrottle> {
+        Arc::clone(&self.snapshot_receive_throttle)
+    }
+
     /// Initialize the cluster (single-node cluster)
     pub async fn initialize(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let mut nodes = BTreeSet::new();
@@ -153,12 +367,42 @@ impl ConsensusNode {
         Ok(())
     }
 
+    /// Initialize the cluster with a full voter set
+    ///
+    /// `members` maps every initial voter, including this node, to its Raft address.
+    /// Peers are registered with the network layer before Raft is initialized.
+    pub async fn initialize_with_members(
+        &self,
+        members: BTreeMap<NodeId, String>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        for (node_id, addr) in &members {
+            if *node_id != self.node_id {
+                self.register_peer(*node_id, addr.clone()).await;
+            }
+        }
+
+        let nodes: BTreeMap<NodeId, BasicNode> = members
+            .into_iter()
+            .map(|(node_id, addr)| (node_id, BasicNode::new(addr)))
+            .collect();
+
+        self.raft.initialize(nodes).await.map_err(|e| {
+            Box::new(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("Failed to initialize cluster: {:?}", e),
+            )) as Box<dyn std::error::Error + Send + Sync>
+        })?;
+
+        Ok(())
+    }
+
     /// Add a learner to the cluster
     pub async fn add_learner(
         &self,
         node_id: NodeId,
         node: BasicNode,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.prepare_join(node_id).await;
         self.raft
             .add_learner(node_id, node, true)
             .await
@@ -190,6 +434,115 @@ impl ConsensusNode {
         Ok(())
     }
 
+    /// Add a learner without waiting for it to catch up
+    ///
+    /// Follow its progress with [`Self::learner_progress`] before promoting it.
+    pub async fn start_learner(&self, node_id: NodeId, node: BasicNode) -> Result<(), ScribeError> {
+        self.prepare_join(node_id).await;
+        self.raft
+            .add_learner(node_id, node, false)
+            .await
+            .map_err(|e| ScribeError::Consensus(format!("Failed to add learner: {}", e)))?;
+        Ok(())
+    }
+
+    /// Compact a long log before `node_id` joins, so it installs a snapshot of the
+    /// state instead of replaying every entry
+    ///
+    /// A node that is already a member keeps replicating as before. Failing to
+    /// compact only means the node replays the log, so it is not an error.
+    async fn prepare_join(&self, node_id: NodeId) {
+        if self.is_member(node_id) {
+            return;
+        }
+        let metrics = self.metrics().await;
+        let Some(applied) = metrics.last_applied else {
+            return;
+        };
+        let first = metrics.purged.map_or(0, |purged| purged.index + 1);
+        let entries = (applied.index + 1).saturating_sub(first);
+        if entries <= self.join_log_limit {
+            return;
+        }
+        info!(
+            "Compacting {} log entries before node {} joins",
+            entries, node_id
+        );
+        if let Err(e) = self.compact_log(SNAPSHOT_BUILD_TIMEOUT).await {
+            warn!(
+                "Node {} will replay the log, compaction failed: {}",
+                node_id, e
+            );
+        }
+    }
+
+    /// Build a snapshot of the applied state and purge the log entries it covers
+    ///
+    /// Peers missing any purged entry, such as nodes joining afterwards, then
+    /// install the snapshot, sent in chunks, instead of replaying the log. Entries
+    /// still being replicated to a peer are purged once that replication is done.
+    /// Returns the last log ID the snapshot covers.
+    pub async fn compact_log(&self, timeout: Duration) -> Result<LogId<NodeId>, ScribeError> {
+        let applied = self
+            .metrics()
+            .await
+            .last_applied
+            .ok_or_else(|| ScribeError::Consensus("No log entry applied yet".to_string()))?;
+        self.raft
+            .trigger()
+            .snapshot()
+            .await
+            .map_err(|e| ScribeError::Consensus(format!("Failed to build snapshot: {}", e)))?;
+        let metrics = self
+            .raft
+            .wait(Some(timeout))
+            .metrics(
+                |metrics| {
+                    metrics
+                        .snapshot
+                        .is_some_and(|snapshot| snapshot.index >= applied.index)
+                },
+                "snapshot built",
+            )
+            .await
+            .map_err(|e| ScribeError::Timeout(format!("Snapshot not built: {}", e)))?;
+        let snapshot = metrics.snapshot.unwrap_or(applied);
+        self.raft
+            .trigger()
+            .purge_log(snapshot.index)
+            .await
+            .map_err(|e| ScribeError::Consensus(format!("Failed to purge log: {}", e)))?;
+        Ok(snapshot)
+    }
+
+    /// Promote learner `node_id` to voter, keeping the other voters
+    pub async fn promote_learner(&self, node_id: NodeId) -> Result<(), ScribeError> {
+        self.raft
+            .change_membership(ChangeMembers::AddVoterIds(BTreeSet::from([node_id])), false)
+            .await
+            .map_err(|e| {
+                ScribeError::Consensus(format!("Failed to promote node {}: {}", node_id, e))
+            })?;
+        Ok(())
+    }
+
+    /// Remove `node_id` from the membership, whether voter or learner
+    pub async fn remove_member(&self, node_id: NodeId) -> Result<(), ScribeError> {
+        let ids = BTreeSet::from([node_id]);
+        let change = if self.voter_ids().contains(&node_id) {
+            ChangeMembers::RemoveVoters(ids)
+        } else {
+            ChangeMembers::RemoveNodes(ids)
+        };
+        self.raft
+            .change_membership(change, false)
+            .await
+            .map_err(|e| {
+                ScribeError::Consensus(format!("Failed to remove node {}: {}", node_id, e))
+            })?;
+        Ok(())
+    }
+
     /// Check if this node is the leader
     pub async fn is_leader(&self) -> bool {
         self.raft
@@ -198,11 +551,281 @@ impl ConsensusNode {
             .unwrap_or(false)
     }
 
+    /// Check if a node is a voter or learner in the current membership
+    pub fn is_member(&self, node_id: NodeId) -> bool {
+        self.raft
+            .metrics()
+            .borrow()
+            .membership_config
+            .membership()
+            .get_node(&node_id)
+            .is_some()
+    }
+
+    /// Voters of the current membership
+    pub fn voter_ids(&self) -> BTreeSet<NodeId> {
+        self.raft
+            .metrics()
+            .borrow()
+            .membership_config
+            .membership()
+            .voter_ids()
+            .collect()
+    }
+
+    /// Voters and learners of the current membership
+    fn members(&self) -> Vec<NodeId> {
+        self.raft
+            .metrics()
+            .borrow()
+            .membership_config
+            .membership()
+            .nodes()
+            .map(|(node_id, _)| *node_id)
+            .collect()
+    }
+
+    /// Wire versions other nodes advertised through Raft RPCs
+    ///
+    /// Share the table with discovery so versions learned there count too.
+    pub fn peer_versions(&self) -> PeerVersions {
+        self.peer_versions.clone()
+    }
+
+    /// Smoothed round trips of Raft heartbeats and votes to other nodes
+    pub fn peer_latencies(&self) -> PeerLatencies {
+        self.peer_latencies.clone()
+    }
+
+    /// Highest wire version every member of the cluster speaks
+    pub fn cluster_version(&self) -> u32 {
+        self.peer_versions
+            .cluster_version(self.node_id, self.members())
+    }
+
+    /// Whether every member speaks wire version `version`
+    fn speaks(&self, version: u32) -> bool {
+        self.peer_versions
+            .lagging(self.node_id, self.members(), version)
+            .is_empty()
+    }
+
+    /// Refuse a `kind` request needing wire version `required` while a member
+    /// is older
+    fn check_supported(&self, kind: &str, required: u32) -> Result<(), ScribeError> {
+        let lagging = self
+            .peer_versions
+            .lagging(self.node_id, self.members(), required);
+        if lagging.is_empty() {
+            return Ok(());
+        }
+        Err(ScribeError::Unsupported(format!(
+            "{} needs wire version {}, which nodes {:?} do not speak yet",
+            kind, required, lagging
+        )))
+    }
+
     /// Get current leader ID
     pub async fn current_leader(&self) -> Option<NodeId> {
         self.raft.current_leader().await
     }
 
+    /// Current leadership as seen by this node
+    pub fn leadership(&self) -> LeadershipChange {
+        LeadershipChange::from_metrics(self.node_id, &self.raft.metrics().borrow())
+    }
+
+    /// Subscribe to leadership changes observed on this node
+    ///
+    /// Use this to start leader-only background work when `is_leader` becomes true
+    /// and stop it when it becomes false, instead of polling [`Self::is_leader`].
+    /// Subscribe before reading [`Self::leadership`] so that no change is missed in
+    /// between; the first event may then repeat the state already read.
+    pub fn subscribe_leadership(&self) -> broadcast::Receiver<LeadershipChange> {
+        self.leadership_events.subscribe()
+    }
+
+    /// Hand leadership to another voter (leader only)
+    ///
+    /// Heartbeats are paused until followers time out and elect a new leader, or until
+    /// `timeout` elapses; they are resumed either way. Fails with a timeout when no
+    /// other voter took over, e.g. in a single-node cluster.
+    pub async fn step_down(&self, timeout: Duration) -> Result<(), ScribeError> {
+        let mut metrics = self.raft.metrics();
+        let leader = metrics.borrow().current_leader;
+        if leader != Some(self.node_id) {
+            return Err(ScribeError::NotLeader { leader_id: leader });
+        }
+
+        info!("Node {} stepping down as leader", self.node_id);
+        *self.read_lease.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        self.raft.runtime_config().heartbeat(false);
+        let replaced = tokio::time::timeout(timeout, async {
+            while metrics.borrow_and_update().current_leader == Some(self.node_id) {
+                if metrics.changed().await.is_err() {
+                    break;
+                }
+            }
+        })
+        .await;
+        self.raft.runtime_config().heartbeat(true);
+
+        replaced.map_err(|_| {
+            ScribeError::Timeout(format!(
+                "No other node took over leadership within {:?}",
+                timeout
+            ))
+        })
+    }
+
+    /// Hand leadership to the voter `target` (leader only)
+    ///
+    /// Heartbeats are paused as in [`Self::step_down`] while `campaign` asks `target`
+    /// to start elections (see [`Self::campaign`]), so it times out before the other
+    /// followers. Returns the new leader, which is another voter if its election
+    /// timer still fired first; fails when `campaign` does or nobody took over within
+    /// `timeout`. Heartbeats are resumed either way.
+    pub async fn transfer_leadership<F>(
+        &self,
+        target: NodeId,
+        timeout: Duration,
+        campaign: F,
+    ) -> Result<NodeId, ScribeError>
+    where
+        F: Future<Output = Result<(), ScribeError>>,
+    {
+        let mut metrics = self.raft.metrics();
+        let (leader, is_voter) = {
+            let metrics = metrics.borrow();
+            let is_voter = metrics
+                .membership_config
+                .membership()
+                .voter_ids()
+                .any(|id| id == target);
+            (metrics.current_leader, is_voter)
+        };
+        if leader != Some(self.node_id) {
+            return Err(ScribeError::NotLeader { leader_id: leader });
+        }
+        if target == self.node_id {
+            return Ok(target);
+        }
+        if !is_voter {
+            return Err(ScribeError::Cluster(format!(
+                "Node {} is not a voter and cannot lead",
+                target
+            )));
+        }
+
+        info!(
+            "Node {} transferring leadership to node {}",
+            self.node_id, target
+        );
+        *self.read_lease.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        self.raft.runtime_config().heartbeat(false);
+        let transferred = tokio::time::timeout(timeout, async {
+            campaign.await?;
+            loop {
+                let leader = metrics.borrow_and_update().current_leader;
+                if let Some(leader) = leader.filter(|leader| *leader != self.node_id) {
+                    return Ok(leader);
+                }
+                if metrics.changed().await.is_err() {
+                    return Err(ScribeError::Consensus("Raft stopped".to_string()));
+                }
+            }
+        })
+        .await;
+        self.raft.runtime_config().heartbeat(true);
+
+        let new_leader = transferred.map_err(|_| {
+            ScribeError::Timeout(format!(
+                "Node {} did not take over leadership within {:?}",
+                target, timeout
+            ))
+        })??;
+        if new_leader != target {
+            warn!(
+                "Leadership went to node {} instead of node {}",
+                new_leader, target
+            );
+        }
+        Ok(new_leader)
+    }
+
+    /// Start elections on this voter until it leads
+    ///
+    /// The leader refuses to be replaced while its lease lasts, so elections are
+    /// retried every `retry_interval`. Gives up once another node than `previous`
+    /// leads, or after `timeout`.
+    pub async fn campaign(
+        &self,
+        previous: Option<NodeId>,
+        retry_interval: Duration,
+        timeout: Duration,
+    ) -> Result<(), ScribeError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let leader = self.raft.metrics().borrow().current_leader;
+            if leader == Some(self.node_id) {
+                info!("Node {} took over leadership", self.node_id);
+                return Ok(());
+            }
+            if let Some(other) = leader.filter(|leader| Some(*leader) != previous) {
+                return Err(ScribeError::Cluster(format!(
+                    "Node {} took over leadership first",
+                    other
+                )));
+            }
+            if Instant::now() >= deadline {
+                return Err(ScribeError::Timeout(format!(
+                    "Node {} did not win an election within {:?}",
+                    self.node_id, timeout
+                )));
+            }
+            self.raft
+                .trigger()
+                .elect()
+                .await
+                .map_err(|e| ScribeError::Consensus(format!("Failed to start election: {}", e)))?;
+            tokio::time::sleep(retry_interval).await;
+        }
+    }
+
+    /// Catch-up progress of every learner (leader only)
+    ///
+    /// Learners trailing the leader's last log index by at most `max_lag` entries
+    /// are reported as ready to be promoted.
+    pub async fn learner_progress(
+        &self,
+        max_lag: u64,
+    ) -> Result<LearnerProgressReport, ScribeError> {
+        let metrics = self.metrics().await;
+        if metrics.current_leader != Some(self.node_id) {
+            return Err(ScribeError::NotLeader {
+                leader_id: metrics.current_leader,
+            });
+        }
+
+        let membership = metrics.membership_config.membership();
+        let replication = metrics.replication.as_ref();
+        let learners = membership
+            .nodes()
+            .filter(|(node_id, _)| !membership.voter_ids().any(|id| id == **node_id))
+            .map(|(node_id, node)| LearnerReplication {
+                node_id: *node_id,
+                raft_addr: node.addr.clone(),
+                matched_index: replication
+                    .and_then(|r| r.get(node_id))
+                    .and_then(|log_id| log_id.map(|l| l.index)),
+            })
+            .collect();
+
+        Ok(self
+            .learner_progress
+            .report(self.node_id, metrics.last_log_index, learners, max_lag))
+    }
+
     /// Perform a health check
     pub async fn health_check(&self) -> HealthStatus {
         let is_leader = self.is_leader().await;
@@ -221,6 +844,14 @@ impl ConsensusNode {
         }
     }
 
+    /// Report key count and on-disk size of this node's storage
+    pub async fn storage_usage(&self) -> StorageUsage {
+        StorageUsage {
+            keys: self.state_machine.key_count().await,
+            size_on_disk_bytes: self.db.size_on_disk().unwrap_or(0),
+        }
+    }
+
     /// Graceful shutdown of the consensus node
     pub async fn shutdown(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         self.raft.shutdown().await.map_err(|e| {
@@ -234,22 +865,84 @@ impl ConsensusNode {
     }
 
     /// Client write operation
+    ///
+    /// If this node is not the leader, the error is a boxed `ScribeError::NotLeader`
+    /// carrying the leader ID so callers can redirect the client. A request kind
+    /// some member cannot decode yet is refused with `ScribeError::Unsupported`.
     pub async fn client_write(
         &self,
         request: AppRequest,
     ) -> Result<AppResponse, Box<dyn std::error::Error + Send + Sync>> {
-        self.raft
-            .client_write(request)
+        self.client_write_indexed(request)
             .await
-            .map(|r| r.data)
-            .map_err(|e| {
-                Box::new(std::io::Error::new(
+            .map(|(response, _)| response)
+    }
+
+    /// Client write operation that also returns the log index of the applied entry
+    ///
+    /// The index is what followers must have applied to observe this write. The
+    /// leader stamps the entry with its clock, which the state machine records as the
+    /// time of the changes it makes, once every member can decode stamped entries.
+    pub async fn client_write_indexed(
+        &self,
+        request: AppRequest,
+    ) -> Result<(AppResponse, u64), Box<dyn std::error::Error + Send + Sync>> {
+        // Only the leader proposes; followers leave the check to it
+        let mut request = request;
+        if self.is_leader().await {
+            let required = request.required_version();
+            if required > LEGACY_WIRE_VERSION {
+                self.check_supported(request.kind(), required)?;
+            }
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0);
+            if self.speaks(STAMP_WIRE_VERSION) {
+                request = AppRequest::stamped(now, request);
+            }
+        }
+
+        let started = Instant::now();
+        let result = self.raft.client_write(request).await;
+        RAFT_COMMIT_LATENCY.observe(started.elapsed().as_secs_f64());
+
+        result
+            .map(|r| (r.data, r.log_id.index))
+            .map_err(|e| match e {
+                RaftError::APIError(ClientWriteError::ForwardToLeader(forward)) => {
+                    Box::new(ScribeError::NotLeader {
+                        leader_id: forward.leader_id,
+                    }) as Box<dyn std::error::Error + Send + Sync>
+                }
+                e => Box::new(std::io::Error::new(
                     std::io::ErrorKind::Other,
                     format!("Client write error: {:?}", e),
-                )) as Box<dyn std::error::Error + Send + Sync>
+                )) as Box<dyn std::error::Error + Send + Sync>,
             })
     }
 
+    /// Index of the last log entry applied to the local state machine
+    pub async fn applied_index(&self) -> u64 {
+        self.metrics()
+            .await
+            .last_applied
+            .map(|log_id| log_id.index)
+            .unwrap_or(0)
+    }
+
+    /// Wait until the local state machine has applied log entry `index`
+    ///
+    /// Fails with `ScribeError::Timeout` if it is not applied within `timeout`.
+    pub async fn wait_for_applied(&self, index: u64, timeout: Duration) -> Result<(), ScribeError> {
+        self.raft
+            .wait(Some(timeout))
+            .applied_index_at_least(Some(index), "consistency token")
+            .await
+            .map(|_| ())
+            .map_err(|e| ScribeError::Timeout(format!("Log index {} not applied: {}", index, e)))
+    }
+
     /// Client read operation (reads from local state machine)
     /// This provides stale reads - data is read from the local state machine
     /// without going through Raft consensus
@@ -257,25 +950,388 @@ impl ConsensusNode {
         self.state_machine.get(&key.to_vec()).await
     }
 
+    /// Stale read that also reports keys demoted to cold storage
+    pub async fn client_lookup_local(&self, key: &[u8]) -> Option<StoredValue> {
+        self.state_machine.lookup(&key.to_vec()).await
+    }
+
+    /// Stale read of a key together with its version, taken at the same log index
+    pub async fn lookup_versioned_local(&self, key: &[u8]) -> (Option<StoredValue>, Option<u64>) {
+        self.state_machine.lookup_versioned(&key.to_vec()).await
+    }
+
+    /// Stale read of the version of a key (log index of its last modification)
+    pub async fn key_version_local(&self, key: &[u8]) -> Option<u64> {
+        self.state_machine.key_version(&key.to_vec()).await
+    }
+
+    /// Stale read of the expiry deadline of a key, if it has a TTL
+    pub async fn expires_at_local(&self, key: &[u8]) -> Option<u64> {
+        self.state_machine.expires_at(&key.to_vec()).await
+    }
+
+    /// Stale read of every key, including keys demoted to cold storage
+    pub async fn entries_local(&self) -> Vec<(Vec<u8>, StoredValue)> {
+        self.state_machine.entries().await
+    }
+
+    /// Stale read of up to `limit` entries sorting after `after`, in key order,
+    /// including keys demoted to cold storage
+    pub async fn entries_after_local(
+        &self,
+        after: Option<&[u8]>,
+        limit: usize,
+    ) -> Vec<(Vec<u8>, StoredValue)> {
+        self.state_machine.entries_after(after, limit).await
+    }
+
+    /// Stale read of up to `limit` keys starting with `prefix` and sorting after
+    /// `after`, in key order
+    pub async fn keys_with_prefix_local(
+        &self,
+        prefix: &[u8],
+        after: Option<&[u8]>,
+        limit: usize,
+    ) -> Vec<Vec<u8>> {
+        self.state_machine
+            .keys_with_prefix(prefix, after, limit)
+            .await
+    }
+
+    /// Stale read of the unexpired lease on lock `name`
+    pub async fn lock_local(&self, name: &str) -> Option<LockLease> {
+        self.state_machine.lock(name).await
+    }
+
+    /// Stale read of the write fencing epoch of `namespace`
+    pub async fn epoch_local(&self, namespace: &str) -> u64 {
+        self.state_machine.epoch(namespace).await
+    }
+
+    /// Stale read of the recorded quota usage of API key `key_id`
+    pub async fn quota_usage_local(&self, key_id: &str) -> Option<KeyUsage> {
+        self.state_machine.quota_usage(key_id).await
+    }
+
+    /// Stale read of the recorded quota usage of every API key
+    pub async fn quota_usages_local(&self) -> Vec<KeyUsage> {
+        self.state_machine.quota_usages().await
+    }
+
+    /// Stale read of the committed Merkle root of the archived segment `segment_id`
+    pub async fn segment_anchor_local(&self, segment_id: SegmentId) -> Option<SegmentAnchor> {
+        self.state_machine.segment_anchor(segment_id).await
+    }
+
+    /// Stale read of the length of queue `name`
+    pub async fn queue_stats_local(&self, name: &str) -> QueueStats {
+        self.state_machine.queue_stats(name).await
+    }
+
+    /// Stale read of all tags, oldest first
+    pub async fn tags_local(&self) -> Vec<LedgerTag> {
+        self.state_machine.tags().await
+    }
+
+    /// Stale read of the state captured by tag `name`
+    pub async fn tagged_state_local(&self, name: &str) -> Option<Arc<TaggedState>> {
+        self.state_machine.tagged_state(name).await
+    }
+
+    /// Keys modified within `[since, until]` (milliseconds since UNIX epoch)
+    ///
+    /// Served from the local state machine, so followers may lag behind the leader.
+    pub async fn changes_between(&self, since: u64, until: u64) -> Vec<KeyChange> {
+        self.state_machine.changes_between(since, until).await
+    }
+
+    /// Get up to `limit` keys modified within `[since, until]`, starting after the
+    /// change at `after` (its timestamp and key)
+    pub async fn changes_page(
+        &self,
+        since: u64,
+        until: u64,
+        after: Option<&(u64, Key)>,
+        limit: usize,
+    ) -> Vec<KeyChange> {
+        self.state_machine
+            .changes_page(since, until, after, limit)
+            .await
+    }
+
+    /// Subscribe to change events (puts, deletes and expiries) applied on this node
+    pub fn subscribe_changes(&self) -> broadcast::Receiver<ChangeEvent> {
+        self.state_machine.subscribe()
+    }
+
+    /// Propose expiry of every key whose TTL elapsed at least `grace` ago
+    ///
+    /// Only the leader proposes expiries so that removals are replicated through the
+    /// log and every replica emits the same `Expire` events. A `grace` of the clock
+    /// skew between nodes keeps a leader whose clock runs ahead from expiring keys
+    /// before their TTL elapsed on the clock of the node that wrote them. Returns
+    /// the number of keys proposed for expiry.
+    pub async fn expire_due_keys(&self, grace: Duration) -> usize {
+        if !self.is_leader().await {
+            return 0;
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+            .saturating_sub(grace.as_millis() as u64);
+
+        let mut expired = 0;
+        for (key, expires_at) in self.state_machine.expired_keys(now).await {
+            match self
+                .client_write(AppRequest::Expire { key, expires_at })
+                .await
+            {
+                Ok(_) => expired += 1,
+                Err(e) => {
+                    warn!("Failed to propose key expiry: {}", e);
+                    break;
+                }
+            }
+        }
+
+        if expired > 0 {
+            debug!("Expired {} keys", expired);
+        }
+        expired
+    }
+
+    /// Spawn a background task that periodically expires keys whose TTL elapsed at
+    /// least `grace` ago
+    pub fn start_expiry_sweeper(
+        self: &Arc<Self>,
+        interval: Duration,
+        grace: Duration,
+    ) -> JoinHandle<()> {
+        let node = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                node.expire_due_keys(grace).await;
+            }
+        })
+    }
+
+    /// Propose forgetting the changes of keys deleted or expired more than
+    /// `retention` ago
+    ///
+    /// Deleted keys stay listed by [`changes_between`](Self::changes_between) so
+    /// incremental sync consumers learn of removals; without pruning, every key ever
+    /// deleted would be held in memory for good. Like expiries, pruning goes through
+    /// the log so every replica forgets the same changes. Only the leader proposes,
+    /// once every member can apply it and there is something to prune. Returns the
+    /// number of changes forgotten.
+    pub async fn prune_tombstones(&self, retention: Duration) -> u64 {
+        if !self.is_leader().await {
+            return 0;
+        }
+
+        let before = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+            .saturating_sub(retention.as_millis() as u64);
+        let request = AppRequest::PruneTombstones { before };
+        // Tombstones are kept until every member can apply the pruning
+        if !self.speaks(request.required_version())
+            || self.state_machine.tombstones_before(before).await == 0
+        {
+            return 0;
+        }
+
+        match self.client_write(request).await {
+            Ok(AppResponse::TombstonesPruned { pruned }) => {
+                debug!("Pruned {} tombstones", pruned);
+                pruned
+            }
+            Ok(_) => 0,
+            Err(e) => {
+                warn!("Failed to propose tombstone pruning: {}", e);
+                0
+            }
+        }
+    }
+
+    /// Spawn a background task that periodically prunes the tombstones of keys
+    /// deleted or expired more than `retention` ago
+    pub fn start_tombstone_pruner(
+        self: &Arc<Self>,
+        interval: Duration,
+        retention: Duration,
+    ) -> JoinHandle<()> {
+        let node = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                node.prune_tombstones(retention).await;
+            }
+        })
+    }
+
+    /// Sample of this node's current Raft metrics
+    pub fn metrics_sample(&self) -> RaftSample {
+        let metrics = self.raft.metrics().borrow().clone();
+        RaftSample {
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            term: metrics.current_term,
+            leader: metrics.current_leader,
+            state: format!("{:?}", metrics.state),
+            last_log_index: metrics.last_log_index,
+            committed_index: RaftStorage::persisted_committed_index(&self.db),
+            applied_index: metrics.last_applied.map(|log_id| log_id.index),
+        }
+    }
+
+    /// Spawn a background task that records a metrics sample into `history` every
+    /// `interval`
+    pub fn start_metrics_history(
+        self: &Arc<Self>,
+        history: RaftHistory,
+        interval: Duration,
+    ) -> JoinHandle<()> {
+        let node = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = history.record(&node.metrics_sample()) {
+                    warn!("Failed to record Raft metrics sample: {}", e);
+                }
+            }
+        })
+    }
+
     /// Client read operation with linearizable guarantee
-    /// This ensures the read sees the latest committed data by checking with the leader
+    ///
+    /// The leader serves these reads only after confirming it is still the leader:
+    /// checking leadership and then reading is racy, as a new leader may have
+    /// accepted writes in between. Confirmation uses ReadIndex (a heartbeat round
+    /// acknowledged by a quorum, then waiting until the commit index is applied).
+    /// Each confirmation grants a lease shorter than the election timeout; while it
+    /// holds, no other leader can exist, so reads are served locally without the
+    /// extra round trip.
+    ///
+    /// A follower asks the leader for a read index (the leader confirms its
+    /// leadership the same way), waits until it has applied up to that index and
+    /// reads locally. If the leader is unknown, unreachable, predates read index
+    /// requests or the follower lags too far behind, or follower reads are turned
+    /// off, the read fails with `ScribeError::NotLeader` so the client retries with
+    /// the leader. Values demoted to cold storage read as absent; use
+    /// [`ConsensusNode::client_lookup`] to find them.
     pub async fn client_read(
         &self,
         key: &[u8],
     ) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error + Send + Sync>> {
-        // For linearizable reads, we need to ensure we're reading the latest data
-        // The simplest approach is to check if we're the leader
+        Ok(match self.client_lookup(key).await? {
+            Some(StoredValue::Hot(value)) => Some(value),
+            _ => None,
+        })
+    }
+
+    /// Linearizable read that also reports keys demoted to cold storage
+    ///
+    /// See [`ConsensusNode::client_read`] for how leadership is confirmed.
+    pub async fn client_lookup(
+        &self,
+        key: &[u8],
+    ) -> Result<Option<StoredValue>, Box<dyn std::error::Error + Send + Sync>> {
         if !self.is_leader().await {
             // If not leader, return error indicating client should retry with leader
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Not leader - retry with current leader for linearizable read",
-            )) as Box<dyn std::error::Error + Send + Sync>);
+            let leader_id = self.current_leader().await;
+            return match leader_id {
+                Some(leader) if self.follower_reads.load(Ordering::Relaxed) => {
+                    self.await_read_index(leader).await?;
+                    LINEARIZABLE_READS
+                        .with_label_values(&["follower_read_index"])
+                        .inc();
+                    Ok(self.state_machine.lookup(&key.to_vec()).await)
+                }
+                _ => Err(Box::new(ScribeError::NotLeader { leader_id })
+                    as Box<dyn std::error::Error + Send + Sync>),
+            };
+        }
+
+        let term = self.raft.metrics().borrow().current_term;
+        if self.holds_read_lease(term) {
+            LINEARIZABLE_READS.with_label_values(&["lease"]).inc();
+        } else {
+            self.confirm_leadership(term).await?;
+            LINEARIZABLE_READS.with_label_values(&["read_index"]).inc();
         }
 
-        // Leader can perform linearizable read from local state machine
-        // because it has the most up-to-date data
-        Ok(self.state_machine.get(&key.to_vec()).await)
+        Ok(self.state_machine.lookup(&key.to_vec()).await)
+    }
+
+    /// Get a read index from `leader` and wait until this node has applied it
+    ///
+    /// Any failure is reported as `ScribeError::NotLeader`, pointing at the leader
+    /// the asked node knows of, so the read is retried there.
+    async fn await_read_index(&self, leader: NodeId) -> Result<(), ScribeError> {
+        let factory = self.network_factory.read().await.clone();
+        let index = match factory.read_index(leader).await {
+            Ok(index) => index,
+            Err(ScribeError::NotLeader { leader_id }) => {
+                return Err(ScribeError::NotLeader { leader_id });
+            }
+            Err(e) => {
+                debug!("No read index from leader {}: {}", leader, e);
+                return Err(ScribeError::NotLeader {
+                    leader_id: Some(leader),
+                });
+            }
+        };
+        self.wait_for_applied(index, FOLLOWER_READ_APPLY_TIMEOUT)
+            .await
+            .map_err(|e| {
+                debug!("Follower read not served: {}", e);
+                ScribeError::NotLeader {
+                    leader_id: Some(leader),
+                }
+            })
+    }
+
+    /// Check whether the leader read lease for `term` is still valid
+    fn holds_read_lease(&self, term: u64) -> bool {
+        let lease = *self.read_lease.lock().unwrap_or_else(|e| e.into_inner());
+        lease.is_some_and(|lease| lease.term == term && Instant::now() < lease.expires_at)
+    }
+
+    /// Confirm leadership with a quorum (ReadIndex) and renew the read lease
+    async fn confirm_leadership(
+        &self,
+        term: u64,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // The lease starts before the heartbeats are sent, so it never outlives them
+        let started = Instant::now();
+
+        self.raft.ensure_linearizable().await.map_err(|e| match e {
+            RaftError::APIError(CheckIsLeaderError::ForwardToLeader(forward)) => {
+                Box::new(ScribeError::NotLeader {
+                    leader_id: forward.leader_id,
+                }) as Box<dyn std::error::Error + Send + Sync>
+            }
+            e => Box::new(ScribeError::Consensus(format!(
+                "Failed to confirm leadership: {}",
+                e
+            ))) as Box<dyn std::error::Error + Send + Sync>,
+        })?;
+
+        *self.read_lease.lock().unwrap_or_else(|e| e.into_inner()) = Some(ReadLease {
+            term,
+            expires_at: started + self.read_lease_duration,
+        });
+        Ok(())
     }
 
     /// Get metrics from the Raft instance
@@ -284,6 +1340,38 @@ impl ConsensusNode {
     }
 }
 
+impl Drop for ConsensusNode {
+    fn drop(&mut self) {
+        self.leadership_watcher.abort();
+    }
+}
+
+/// Spawn a task that publishes a [`LeadershipChange`] whenever the Raft metrics show a
+/// new leader or leader role; it ends when Raft shuts down
+fn spawn_leadership_watcher(
+    node_id: NodeId,
+    mut metrics: watch::Receiver<RaftMetrics<NodeId, BasicNode>>,
+    events: broadcast::Sender<LeadershipChange>,
+) -> JoinHandle<()> {
+    // Read the starting state before the task runs, so a change made before it is
+    // first polled is still reported
+    let mut current = LeadershipChange::from_metrics(node_id, &metrics.borrow_and_update());
+    tokio::spawn(async move {
+        while metrics.changed().await.is_ok() {
+            let next = LeadershipChange::from_metrics(node_id, &metrics.borrow_and_update());
+            if next.differs_from(&current) {
+                info!(
+                    "Leadership changed in term {}: leader {:?}, this node leader: {}",
+                    next.term, next.leader_id, next.is_leader
+                );
+                // Having no subscribers is fine
+                let _ = events.send(next.clone());
+                current = next;
+            }
+        }
+    })
+}
+
 /// Health status information for a consensus node
 #[derive(Debug, Clone)]
 pub struct HealthStatus {
@@ -303,6 +1391,15 @@ pub struct HealthStatus {
     pub current_term: u64,
 }
 
+/// Storage usage of a consensus node
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageUsage {
+    /// Number of keys in the state machine
+    pub keys: usize,
+    /// Size of the node's database on disk in bytes
+    pub size_on_disk_bytes: u64,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -345,6 +1442,31 @@ mod tests {
         assert!(node.is_leader().await);
     }
 
+    #[tokio::test]
+    async fn test_leadership_changes() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let node = ConsensusNode::new(TEST_NODE_ID, db).await.unwrap();
+        let mut changes = node.subscribe_leadership();
+        assert!(!node.leadership().is_leader);
+
+        node.initialize().await.unwrap();
+
+        let change = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                let change = changes.recv().await.unwrap();
+                if change.is_leader {
+                    return change;
+                }
+            }
+        })
+        .await
+        .expect("no leadership change reported");
+        assert_eq!(change.node_id, TEST_NODE_ID);
+        assert_eq!(change.leader_id, Some(TEST_NODE_ID));
+        assert!(change.term >= 1);
+        assert_eq!(node.leadership().leader_id, Some(TEST_NODE_ID));
+    }
+
     #[tokio::test]
     async fn test_health_check() {
         let db = sled::Config::new().temporary(true).open().unwrap();
@@ -378,6 +1500,30 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_write_needing_newer_wire_version_is_refused() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let node = ConsensusNode::new(TEST_NODE_ID, db).await.unwrap();
+        node.initialize().await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(2000)).await;
+
+        let version = crate::wire::WIRE_VERSION;
+        assert_eq!(node.cluster_version(), version);
+        assert!(node.check_supported("put", version).is_ok());
+
+        let err = node
+            .check_supported("future_write", version + 1)
+            .unwrap_err();
+        assert!(matches!(err, ScribeError::Unsupported(_)));
+        assert!(err.to_string().contains("future_write"));
+        assert!(err.to_string().contains(&format!("[{}]", TEST_NODE_ID)));
+
+        // Versions of nodes outside the membership do not matter
+        node.peer_versions()
+            .record(TEST_NODE_ID_2, LEGACY_WIRE_VERSION);
+        assert_eq!(node.cluster_version(), version);
+    }
+
     #[tokio::test]
     async fn test_current_leader() {
         let db = sled::Config::new().temporary(true).open().unwrap();
@@ -396,4 +1542,170 @@ mod tests {
         // Shutdown should succeed
         node.shutdown().await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_expire_due_keys() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let node = ConsensusNode::new(TEST_NODE_ID, db).await.unwrap();
+        node.initialize().await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(2000)).await;
+
+        let mut events = node.subscribe_changes();
+        node.client_write(AppRequest::PutWithTtl {
+            key: b"ttl_key".to_vec(),
+            value: b"value".to_vec(),
+            expires_at: 1,
+        })
+        .await
+        .unwrap();
+        // Elapsed a second ago, within the grace for clock skew
+        let recent = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64
+            - 1000;
+        node.client_write(AppRequest::PutWithTtl {
+            key: b"recent_key".to_vec(),
+            value: b"value".to_vec(),
+            expires_at: recent,
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(node.expire_due_keys(Duration::from_secs(60)).await, 1);
+        assert_eq!(events.recv().await.unwrap().kind, ChangeKind::Put);
+        assert_eq!(events.recv().await.unwrap().kind, ChangeKind::Put);
+        assert_eq!(events.recv().await.unwrap().kind, ChangeKind::Expire);
+        assert_eq!(node.client_read_local(b"ttl_key").await, None);
+        // Reads already hide the key, but it is only removed once the grace passed
+        assert_eq!(node.client_read_local(b"recent_key").await, None);
+        assert_eq!(
+            node.state_machine.expired_keys(u64::MAX).await,
+            vec![(b"recent_key".to_vec(), recent)]
+        );
+
+        assert_eq!(node.expire_due_keys(Duration::ZERO).await, 1);
+        assert!(node.state_machine.expired_keys(u64::MAX).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_expire_due_keys_follower_noop() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let node = ConsensusNode::new(TEST_NODE_ID, db).await.unwrap();
+
+        // Not leader before initialization
+        assert_eq!(node.expire_due_keys(Duration::ZERO).await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_prune_tombstones() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let node = ConsensusNode::new(TEST_NODE_ID, db).await.unwrap();
+        assert_eq!(node.prune_tombstones(Duration::ZERO).await, 0);
+        node.initialize().await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(2000)).await;
+
+        for request in [
+            AppRequest::Delete {
+                key: b"gone".to_vec(),
+            },
+            AppRequest::Put {
+                key: b"kept".to_vec(),
+                value: b"value".to_vec(),
+            },
+        ] {
+            node.client_write(request).await.unwrap();
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+
+        // Still within the retention
+        assert_eq!(node.prune_tombstones(Duration::from_secs(60)).await, 0);
+        assert_eq!(node.changes_between(0, u64::MAX).await.len(), 2);
+
+        assert_eq!(node.prune_tombstones(Duration::ZERO).await, 1);
+        let changes = node.changes_between(0, u64::MAX).await;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].key, b"kept".to_vec());
+        assert_eq!(node.prune_tombstones(Duration::ZERO).await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_restart_resumes_from_stored_snapshot() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let snapshots = sled::Config::new().temporary(true).open().unwrap();
+        let layout = crate::storage::layout::StorageLayout::default();
+        let config = crate::config::Config::default_for_node(TEST_NODE_ID).consensus;
+
+        let store = Arc::new(SnapshotStore::with_db(snapshots.clone(), &layout).unwrap());
+        let node = ConsensusNode::new_with_snapshot_store(TEST_NODE_ID, db.clone(), &config, store)
+            .await
+            .unwrap();
+        node.initialize().await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(2000)).await;
+        node.client_write(AppRequest::Put {
+            key: b"key".to_vec(),
+            value: b"value".to_vec(),
+        })
+        .await
+        .unwrap();
+        let compacted = node.compact_log(Duration::from_secs(10)).await.unwrap();
+        node.shutdown().await.unwrap();
+        drop(node);
+
+        // The log up to the snapshot was purged, so the value comes from the snapshot
+        let store = Arc::new(SnapshotStore::with_db(snapshots, &layout).unwrap());
+        let node = ConsensusNode::new_with_snapshot_store(TEST_NODE_ID, db, &config, store)
+            .await
+            .unwrap();
+        assert_eq!(
+            node.client_read_local(b"key").await,
+            Some(b"value".to_vec())
+        );
+        node.wait_for_applied(compacted.index, Duration::from_secs(5))
+            .await
+            .unwrap();
+        node.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_client_read_renews_lease() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let node = ConsensusNode::new(TEST_NODE_ID, db).await.unwrap();
+        node.initialize().await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(2000)).await;
+
+        node.client_write(AppRequest::Put {
+            key: b"key".to_vec(),
+            value: b"value".to_vec(),
+        })
+        .await
+        .unwrap();
+
+        let term = node.metrics().await.current_term;
+        assert!(!node.holds_read_lease(term));
+
+        // The first read confirms leadership and grants a lease for later reads
+        assert_eq!(
+            node.client_read(b"key").await.unwrap(),
+            Some(b"value".to_vec())
+        );
+        assert!(node.holds_read_lease(term));
+        assert!(!node.holds_read_lease(term + 1));
+        assert_eq!(
+            node.client_read(b"key").await.unwrap(),
+            Some(b"value".to_vec())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_client_read_not_leader() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let node = ConsensusNode::new(TEST_NODE_ID, db).await.unwrap();
+
+        let err = node.client_read(b"key").await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ScribeError>(),
+            Some(ScribeError::NotLeader { .. })
+        ));
+    }
 }
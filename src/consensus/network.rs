@@ -1,7 +1,34 @@
 //! OpenRaft network layer implementation
 //!
 //! This module implements the RaftNetwork trait for node-to-node communication
-//! using TCP connections with connection pooling and retry logic.
+//! using TCP connections with connection pooling and retry logic, and the matching
+//! server that answers peers' RPCs.
+//!
+//! Messages are framed as a 4-byte big-endian length followed by the bincode
+//! payload. When compression is enabled for a peer, large payloads are deflated and
+//! the top bit of the length is set; the server answers a compressed request with a
+//! compressed response where that pays off. A peer is only sent compressed messages
+//! once it has advertised [`COMPRESSION_WIRE_VERSION`], so messages to a peer that
+//! has not answered yet, or runs an older release, stay plain.
+//!
+//! With RPC authentication configured, requests are wrapped in a signed envelope
+//! (see [`crate::security::rpc_auth`]) and the second-highest bit of the length is
+//! set. The server verifies the envelope, rejecting replayed or forged requests,
+//! before it decompresses or decodes anything.
+//!
+//! Snapshot chunks pass a [`TransferThrottle`] on both ends, so installing a snapshot
+//! on a new replica stays within the configured bandwidth.
+//!
+//! Besides Raft's own RPCs, followers send the leader read index requests to serve
+//! linearizable reads (see [`NetworkFactory::read_index`]). A leader that predates
+//! them cannot decode the request and closes the connection, which the follower
+//! takes as a refusal.
+//!
+//! Every request and response payload is followed by a [`VersionAdvert`] sealed in
+//! a wire envelope, from which the other end learns the sender's wire version, so a
+//! follower that becomes leader already knows the version of the leader it
+//! replaced. Older nodes decode the bincode message and ignore the bytes after it; a
+//! response without an advert comes from a node that predates versioning.
 
 // Allow large error types from OpenRaft - this is a library design choice
 #![allow(clippy::result_large_err)]
@@ -10,7 +37,11 @@
 // Allow type complexity as it's from library requirements
 #![allow(clippy::type_complexity)]
 
-use openraft::error::{InstallSnapshotError, NetworkError, RPCError, RaftError};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use openraft::error::{
+    CheckIsLeaderError, InstallSnapshotError, NetworkError, RPCError, RaftError,
+};
 use openraft::network::{RPCOption, RaftNetwork, RaftNetworkFactory};
 use openraft::raft::{
     AppendEntriesRequest, AppendEntriesResponse, InstallSnapshotRequest, InstallSnapshotResponse,
@@ -19,15 +50,29 @@ use openraft::raft::{
 use openraft::BasicNode;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::io::{Read, Write};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
+use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::RwLock;
 use tokio::time::timeout;
+use tracing::{debug, warn};
 
+use crate::config::RaftCompressionConfig;
+use crate::consensus::throttle::TransferThrottle;
 use crate::consensus::type_config::TypeConfig;
+use crate::consensus::RaftInstance;
+use crate::error::ScribeError;
+use crate::learner_progress::ReplicationTransfers;
+use crate::metrics::{
+    RAFT_COMPRESSION_SAVED_BYTES, RAFT_SENT_BYTES, SNAPSHOT_THROTTLE_WAIT, SNAPSHOT_TRANSFER_BYTES,
+    SNAPSHOT_TRANSFER_PROGRESS,
+};
+use crate::placement::PeerLatencies;
+use crate::security::rpc_auth::{strip_envelope, RpcAuthenticator};
 use crate::types::NodeId;
+use crate::wire::{self, Envelope, PeerVersions, VersionAdvert, WireMessage, LEGACY_WIRE_VERSION};
 
 /// Default timeout for network operations
 const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
@@ -35,12 +80,189 @@ const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
 /// Maximum number of retry attempts
 const MAX_RETRIES: u32 = 3;
 
+/// Largest RPC message accepted from a peer
+const MAX_MESSAGE_SIZE: usize = 256 * 1024 * 1024;
+
+/// Length prefix bit marking a deflate-compressed payload
+const COMPRESSED_FLAG: u32 = 1 << 31;
+
+/// Length prefix bit marking a payload wrapped in a signed envelope
+const SIGNED_FLAG: u32 = 1 << 30;
+
+/// Wire version from which nodes read compressed Raft messages
+pub const COMPRESSION_WIRE_VERSION: u32 = 8;
+
+/// Compression of responses to compressed requests; favours speed
+const RESPONSE_COMPRESSION: WireCompression = WireCompression {
+    level: 1,
+    min_size: 1024,
+};
+
+/// Compression settings for messages to one peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WireCompression {
+    /// Deflate level from 1 (fastest) to 9 (smallest)
+    pub level: u32,
+    /// Payloads smaller than this many bytes are sent uncompressed
+    pub min_size: usize,
+}
+
+impl WireCompression {
+    /// Settings for messages to `peer`, if compression applies to it
+    pub fn for_peer(config: &RaftCompressionConfig, peer: NodeId) -> Option<Self> {
+        config.applies_to(peer).then_some(Self {
+            level: config.level,
+            min_size: config.min_size_bytes,
+        })
+    }
+}
+
+/// Build the length prefix of a frame
+fn length_prefix(len: usize, compressed: bool) -> [u8; 4] {
+    let len = len as u32;
+    if compressed {
+        (len | COMPRESSED_FLAG).to_be_bytes()
+    } else {
+        len.to_be_bytes()
+    }
+}
+
+/// Mark a length prefix as carrying a signed envelope
+fn mark_signed(prefix: [u8; 4]) -> [u8; 4] {
+    (u32::from_be_bytes(prefix) | SIGNED_FLAG).to_be_bytes()
+}
+
+/// Whether a length prefix marks a signed envelope
+fn is_signed(prefix: [u8; 4]) -> bool {
+    u32::from_be_bytes(prefix) & SIGNED_FLAG != 0
+}
+
+/// Split a length prefix into the payload length and whether it is compressed
+fn parse_length_prefix(prefix: [u8; 4]) -> (usize, bool) {
+    let raw = u32::from_be_bytes(prefix);
+    (
+        (raw & !(COMPRESSED_FLAG | SIGNED_FLAG)) as usize,
+        raw & COMPRESSED_FLAG != 0,
+    )
+}
+
+/// Compress `payload` if the settings allow and it gets smaller
+///
+/// Returns the bytes to send and whether they are compressed.
+fn encode_payload(
+    payload: Vec<u8>,
+    compression: Option<WireCompression>,
+) -> std::io::Result<(Vec<u8>, bool)> {
+    let Some(compression) = compression.filter(|c| payload.len() >= c.min_size) else {
+        return Ok((payload, false));
+    };
+
+    let mut encoder = DeflateEncoder::new(
+        Vec::with_capacity(payload.len() / 2),
+        flate2::Compression::new(compression.level),
+    );
+    encoder.write_all(&payload)?;
+    let compressed = encoder.finish()?;
+    if compressed.len() < payload.len() {
+        Ok((compressed, true))
+    } else {
+        Ok((payload, false))
+    }
+}
+
+/// Inflate a compressed payload, refusing output beyond `MAX_MESSAGE_SIZE`
+fn decode_payload(payload: Vec<u8>, compressed: bool) -> std::io::Result<Vec<u8>> {
+    if !compressed {
+        return Ok(payload);
+    }
+
+    let mut decoded = Vec::with_capacity(payload.len() * 2);
+    DeflateDecoder::new(payload.as_slice())
+        .take(MAX_MESSAGE_SIZE as u64 + 1)
+        .read_to_end(&mut decoded)?;
+    if decoded.len() > MAX_MESSAGE_SIZE {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "Decompressed RPC message exceeds limit",
+        ));
+    }
+    Ok(decoded)
+}
+
+/// Pace a snapshot chunk moving in `direction` ("sent" or "received") and record it
+async fn throttle_snapshot_chunk(
+    throttle: &TransferThrottle,
+    direction: &str,
+    rpc: &InstallSnapshotRequest<TypeConfig>,
+) {
+    let chunk_len = rpc.data.len() as u64;
+    let waited = throttle.acquire(chunk_len).await;
+    SNAPSHOT_THROTTLE_WAIT
+        .with_label_values(&[direction])
+        .observe(waited.as_secs_f64());
+    SNAPSHOT_TRANSFER_BYTES
+        .with_label_values(&[direction])
+        .inc_by(chunk_len);
+    let progress = if rpc.done { 0 } else { rpc.offset + chunk_len };
+    SNAPSHOT_TRANSFER_PROGRESS
+        .with_label_values(&[direction])
+        .set(progress as i64);
+}
+
+/// Append this node's version advert to an encoded RPC response
+fn append_version_advert(response: &mut Vec<u8>, node_id: NodeId) {
+    response.extend(wire::seal(&VersionAdvert { node_id }));
+}
+
+/// Sender and wire version of the advert following a request payload
+///
+/// `None` if the request carries no readable advert, as from older nodes.
+fn request_advert(trailer: &[u8]) -> Option<(NodeId, u32)> {
+    if trailer.is_empty() {
+        return None;
+    }
+    let envelope = Envelope::from_bytes(trailer).ok()?;
+    let advert = envelope.open::<VersionAdvert>().ok()?;
+    Some((advert.node_id, envelope.version))
+}
+
+/// Wire version `peer` advertised after its response payload
+///
+/// `None` if the trailer is not a readable advert from `peer`.
+fn advertised_version(trailer: &[u8], peer: NodeId) -> Option<u32> {
+    if trailer.is_empty() {
+        return Some(LEGACY_WIRE_VERSION);
+    }
+    let envelope = match Envelope::from_bytes(trailer) {
+        Ok(envelope) => envelope,
+        Err(e) => {
+            debug!("Ignoring version advert from node {}: {}", peer, e);
+            return None;
+        }
+    };
+    // A newer node's advert may be unreadable here; its envelope still carries
+    // the node's version
+    if let Ok(advert) = envelope.open::<VersionAdvert>() {
+        if advert.node_id != peer {
+            warn!(
+                "Raft RPC to node {} was answered by node {}",
+                peer, advert.node_id
+            );
+            return None;
+        }
+    }
+    Some(envelope.version)
+}
+
 /// Network message types
 #[derive(Debug, Clone, Serialize, Deserialize)]
 enum NetworkMessage {
     AppendEntries(AppendEntriesRequest<TypeConfig>),
     Vote(VoteRequest<NodeId>),
     InstallSnapshot(InstallSnapshotRequest<TypeConfig>),
+    /// A follower asks the leader which log index to apply before serving a
+    /// linearizable read
+    ReadIndex,
 }
 
 /// Network response types
@@ -49,6 +271,15 @@ enum NetworkResponse {
     AppendEntries(Result<AppendEntriesResponse<NodeId>, String>),
     Vote(Result<VoteResponse<NodeId>, String>),
     InstallSnapshot(Result<InstallSnapshotResponse<NodeId>, String>),
+    ReadIndex(Result<u64, ReadIndexRefusal>),
+}
+
+/// Why a node did not grant a read index
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ReadIndexRefusal {
+    /// Leader known to the asked node, if it is not the leader itself
+    leader_id: Option<NodeId>,
+    message: String,
 }
 
 /// Connection pool for managing TCP connections to other nodes
@@ -87,12 +318,25 @@ impl ConnectionPool {
 /// Network implementation for Raft RPC
 pub struct Network {
     /// The target node ID for this network instance
-    #[allow(dead_code)]
     target: NodeId,
+    /// This node, advertised to the target with every request
+    source: Option<NodeId>,
     /// Target node address
     target_addr: String,
     /// Connection pool for reusing connections
     pool: ConnectionPool,
+    /// Compression of messages to the target, if enabled
+    compression: Option<WireCompression>,
+    /// Paces snapshot chunks sent to the target
+    snapshot_throttle: Arc<TransferThrottle>,
+    /// Signs messages to the target, if RPC authentication is enabled
+    rpc_auth: Option<Arc<RpcAuthenticator>>,
+    /// Counts log and snapshot bytes sent to the target
+    transfers: ReplicationTransfers,
+    /// Records the wire version the target advertises
+    peer_versions: PeerVersions,
+    /// Records round trips of heartbeats and votes to the target
+    latencies: PeerLatencies,
 }
 
 impl Network {
@@ -100,11 +344,70 @@ impl Network {
     pub fn new(target: NodeId, target_addr: String) -> Self {
         Self {
             target,
+            source: None,
             target_addr,
             pool: ConnectionPool::new(),
+            compression: None,
+            snapshot_throttle: Arc::new(TransferThrottle::unlimited()),
+            rpc_auth: None,
+            transfers: ReplicationTransfers::new(),
+            peer_versions: PeerVersions::new(),
+            latencies: PeerLatencies::new(),
         }
     }
 
+    /// Advertise this node's wire version to the target as node `source`
+    pub fn with_source(mut self, source: NodeId) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Record the target's advertised wire version in `peer_versions`
+    pub fn with_peer_versions(mut self, peer_versions: PeerVersions) -> Self {
+        self.peer_versions = peer_versions;
+        self
+    }
+
+    /// Record round trips of heartbeats and votes to the target in `latencies`
+    pub fn with_latencies(mut self, latencies: PeerLatencies) -> Self {
+        self.latencies = latencies;
+        self
+    }
+
+    /// Count bytes sent to the target in `transfers`
+    pub fn with_transfers(mut self, transfers: ReplicationTransfers) -> Self {
+        self.transfers = transfers;
+        self
+    }
+
+    /// Sign messages to the target with `rpc_auth`
+    pub fn with_rpc_auth(mut self, rpc_auth: Option<Arc<RpcAuthenticator>>) -> Self {
+        self.rpc_auth = rpc_auth;
+        self
+    }
+
+    /// Pace snapshot chunks sent to the target with `throttle`
+    pub fn with_snapshot_throttle(mut self, throttle: Arc<TransferThrottle>) -> Self {
+        self.snapshot_throttle = throttle;
+        self
+    }
+
+    /// Compress messages to the target with `compression`
+    pub fn with_compression(mut self, compression: Option<WireCompression>) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Compression to use for the next message
+    ///
+    /// `None` until the target has advertised a wire version that reads compressed
+    /// messages.
+    fn active_compression(&self) -> Option<WireCompression> {
+        let version = self.peer_versions.get(self.target)?;
+        self.compression
+            .filter(|_| version >= COMPRESSION_WIRE_VERSION)
+    }
+
     /// Send a message with retry logic
     async fn send_with_retry<T>(
         &self,
@@ -143,17 +446,57 @@ impl Network {
         let mut stream = self.pool.get_connection(&self.target_addr).await?;
 
         // Serialize and send the message
-        let msg_bytes = bincode::serialize(message).map_err(|e| {
+        let mut msg_bytes = bincode::serialize(message).map_err(|e| {
             RPCError::Network(NetworkError::new(&std::io::Error::new(
                 std::io::ErrorKind::Other,
                 format!("Serialization error: {}", e),
             )))
         })?;
+        if let Some(source) = self.source {
+            append_version_advert(&mut msg_bytes, source);
+        }
+        let raw_len = msg_bytes.len();
+        let (msg_bytes, compressed) = encode_payload(msg_bytes, self.active_compression())
+            .map_err(|e| RPCError::Network(NetworkError::new(&e)))?;
+
+        let peer = self.target.to_string();
+        if compressed {
+            RAFT_COMPRESSION_SAVED_BYTES
+                .with_label_values(&[&peer])
+                .inc_by((raw_len - msg_bytes.len()) as u64);
+        }
+
+        let mut prefix = length_prefix(msg_bytes.len(), compressed);
+        let msg_bytes = match &self.rpc_auth {
+            Some(rpc_auth) => {
+                let envelope = rpc_auth
+                    .seal(self.target, compressed as u8, &msg_bytes)
+                    .map_err(|e| {
+                        RPCError::Network(NetworkError::new(&std::io::Error::new(
+                            std::io::ErrorKind::Other,
+                            e.to_string(),
+                        )))
+                    })?;
+                prefix = mark_signed(length_prefix(envelope.len(), compressed));
+                envelope
+            }
+            None => msg_bytes,
+        };
+        RAFT_SENT_BYTES
+            .with_label_values(&[&peer])
+            .inc_by(msg_bytes.len() as u64);
+
+        // Round trips of messages without payload to process measure the network
+        let probe = match message {
+            NetworkMessage::AppendEntries(rpc) => rpc.entries.is_empty(),
+            NetworkMessage::Vote(_) => true,
+            NetworkMessage::InstallSnapshot(_) | NetworkMessage::ReadIndex => false,
+        };
+        let sent_at = Instant::now();
 
         // Send message length first (4 bytes)
-        let len = msg_bytes.len() as u32;
         stream
-            .write_all(&len.to_be_bytes())
+            .write_all(&prefix)
             .await
             .map_err(|e| RPCError::Network(NetworkError::new(&e)))?;
 
@@ -167,6 +510,16 @@ impl Network {
             .flush()
             .await
             .map_err(|e| RPCError::Network(NetworkError::new(&e)))?;
+        match message {
+            NetworkMessage::AppendEntries(_) => self
+                .transfers
+                .record_log(self.target, (prefix.len() + msg_bytes.len()) as u64),
+            NetworkMessage::InstallSnapshot(rpc) => {
+                self.transfers
+                    .record_snapshot_chunk(self.target, rpc.data.len() as u64, rpc.done)
+            }
+            NetworkMessage::Vote(_) | NetworkMessage::ReadIndex => {}
+        }
 
         // Read response length (4 bytes)
         let mut len_bytes = [0u8; 4];
@@ -180,7 +533,13 @@ impl Network {
             })?
             .map_err(|e| RPCError::Network(NetworkError::new(&e)))?;
 
-        let len = u32::from_be_bytes(len_bytes) as usize;
+        let (len, response_compressed) = parse_length_prefix(len_bytes);
+        if len > MAX_MESSAGE_SIZE {
+            return Err(RPCError::Network(NetworkError::new(&std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("RPC response of {} bytes exceeds limit", len),
+            ))));
+        }
 
         // Read response data
         let mut response_bytes = vec![0u8; len];
@@ -193,17 +552,51 @@ impl Network {
                 )))
             })?
             .map_err(|e| RPCError::Network(NetworkError::new(&e)))?;
+        let response_bytes = decode_payload(response_bytes, response_compressed)
+            .map_err(|e| RPCError::Network(NetworkError::new(&e)))?;
 
-        // Deserialize response
-        let response: T = bincode::deserialize(&response_bytes).map_err(|e| {
+        // Deserialize response, then the version advert trailing it
+        let mut reader = response_bytes.as_slice();
+        let response: T = bincode::deserialize_from(&mut reader).map_err(|e| {
             RPCError::Network(NetworkError::new(&std::io::Error::new(
                 std::io::ErrorKind::Other,
                 format!("Deserialization error: {}", e),
             )))
         })?;
+        if let Some(version) = advertised_version(reader, self.target) {
+            self.peer_versions.record(self.target, version);
+        }
+        if probe {
+            self.latencies.record(self.target, sent_at.elapsed());
+        }
 
         Ok(response)
     }
+
+    /// Ask the target, which should be the leader, for a read index
+    ///
+    /// Sent once: a follower that cannot get one quickly redirects the read instead.
+    async fn read_index(&self) -> Result<u64, ScribeError> {
+        let response: NetworkResponse =
+            self.try_send(&NetworkMessage::ReadIndex)
+                .await
+                .map_err(|e| {
+                    ScribeError::Network(format!(
+                        "Read index request to node {} failed: {}",
+                        self.target, e
+                    ))
+                })?;
+        match response {
+            NetworkResponse::ReadIndex(Ok(index)) => Ok(index),
+            NetworkResponse::ReadIndex(Err(refusal)) => match refusal.leader_id {
+                Some(leader_id) => Err(ScribeError::NotLeader {
+                    leader_id: Some(leader_id),
+                }),
+                None => Err(ScribeError::Consensus(refusal.message)),
+            },
+            _ => Err(ScribeError::Network("Invalid response type".to_string())),
+        }
+    }
 }
 
 impl RaftNetwork<TypeConfig> for Network {
@@ -259,6 +652,7 @@ impl RaftNetwork<TypeConfig> for Network {
         InstallSnapshotResponse<NodeId>,
         RPCError<NodeId, BasicNode, RaftError<NodeId, InstallSnapshotError>>,
     > {
+        throttle_snapshot_chunk(&self.snapshot_throttle, "sent", &rpc).await;
         let message = NetworkMessage::InstallSnapshot(rpc);
         let response: NetworkResponse =
             self.send_with_retry(message).await.map_err(|e| match e {
@@ -287,37 +681,275 @@ impl RaftNetwork<TypeConfig> for Network {
 /// Factory for creating network instances
 #[derive(Clone)]
 pub struct NetworkFactory {
+    node_id: NodeId,
     node_addresses: Arc<RwLock<HashMap<NodeId, String>>>,
+    compression: Arc<RwLock<RaftCompressionConfig>>,
+    /// Shared by all peers, so concurrent snapshot sends split the rate
+    snapshot_throttle: Arc<TransferThrottle>,
+    rpc_auth: Arc<RwLock<Option<Arc<RpcAuthenticator>>>>,
+    /// Shared by all peers' clients, so counts survive reconnects
+    transfers: ReplicationTransfers,
+    /// Wire versions peers advertise in their responses
+    peer_versions: PeerVersions,
+    /// Round trips to peers, shared by all peers' clients
+    latencies: PeerLatencies,
 }
 
 impl NetworkFactory {
     /// Create a new network factory
-    pub fn new(_node_id: NodeId) -> Self {
+    pub fn new(node_id: NodeId) -> Self {
         Self {
+            node_id,
             node_addresses: Arc::new(RwLock::new(HashMap::new())),
+            compression: Arc::new(RwLock::new(RaftCompressionConfig::default())),
+            snapshot_throttle: Arc::new(TransferThrottle::unlimited()),
+            rpc_auth: Arc::new(RwLock::new(None)),
+            transfers: ReplicationTransfers::new(),
+            peer_versions: PeerVersions::new(),
+            latencies: PeerLatencies::new(),
         }
     }
 
+    /// Wire versions peers advertised
+    pub fn peer_versions(&self) -> PeerVersions {
+        self.peer_versions.clone()
+    }
+
+    /// Round trips of heartbeats and votes to each peer
+    pub fn latencies(&self) -> PeerLatencies {
+        self.latencies.clone()
+    }
+
+    /// Bytes sent to each peer
+    pub fn transfers(&self) -> ReplicationTransfers {
+        self.transfers.clone()
+    }
+
+    /// Throttle of snapshot chunks sent to peers
+    pub fn snapshot_throttle(&self) -> Arc<TransferThrottle> {
+        Arc::clone(&self.snapshot_throttle)
+    }
+
     /// Register a node address
     pub async fn register_node(&self, node_id: NodeId, address: String) {
         let mut addresses = self.node_addresses.write().await;
         addresses.insert(node_id, address);
     }
-}
 
-impl RaftNetworkFactory<TypeConfig> for NetworkFactory {
-    type Network = Network;
+    /// Set which peers get compressed messages
+    ///
+    /// Applies to connections Raft opens from now on.
+    pub async fn set_compression(&self, config: RaftCompressionConfig) {
+        *self.compression.write().await = config;
+    }
 
-    async fn new_client(&mut self, target: NodeId, _node: &BasicNode) -> Self::Network {
-        let addresses = self.node_addresses.read().await;
-        let target_addr = addresses
+    /// Sign messages to peers with `rpc_auth` (or stop signing with `None`)
+    ///
+    /// Applies to connections Raft opens from now on.
+    pub async fn set_rpc_auth(&self, rpc_auth: Option<Arc<RpcAuthenticator>>) {
+        *self.rpc_auth.write().await = rpc_auth;
+    }
+
+    /// Ask `leader` for the log index a follower must apply before a read it serves
+    /// is linearizable
+    ///
+    /// The leader confirms its leadership with a quorum first, so the index covers
+    /// every write acknowledged before the request. Fails with
+    /// `ScribeError::NotLeader` if `leader` knows of a newer leader.
+    pub async fn read_index(&self, leader: NodeId) -> Result<u64, ScribeError> {
+        self.client(leader).await.read_index().await
+    }
+
+    /// Client of `target` with the factory's settings
+    async fn client(&self, target: NodeId) -> Network {
+        let target_addr = self
+            .node_addresses
+            .read()
+            .await
             .get(&target)
             .cloned()
             .unwrap_or_else(|| format!("127.0.0.1:{}", 5000 + target));
+        let compression = WireCompression::for_peer(&*self.compression.read().await, target);
         Network::new(target, target_addr)
+            .with_source(self.node_id)
+            .with_compression(compression)
+            .with_snapshot_throttle(self.snapshot_throttle())
+            .with_rpc_auth(self.rpc_auth.read().await.clone())
+            .with_transfers(self.transfers())
+            .with_peer_versions(self.peer_versions())
+            .with_latencies(self.latencies())
+    }
+}
+
+impl RaftNetworkFactory<TypeConfig> for NetworkFactory {
+    type Network = Network;
+
+    async fn new_client(&mut self, target: NodeId, _node: &BasicNode) -> Self::Network {
+        self.client(target).await
+    }
+}
+
+/// Serve Raft RPCs from peers on `listener`
+///
+/// Each connection carries length-prefixed bincode messages, as sent by [`Network`],
+/// and gets one response per message. Runs until accepting connections fails.
+pub async fn serve_raft_rpc(raft: Arc<RaftInstance>, listener: TcpListener) -> std::io::Result<()> {
+    serve_raft_rpc_throttled(raft, listener, Arc::new(TransferThrottle::unlimited())).await
+}
+
+/// Serve Raft RPCs like [`serve_raft_rpc`], pacing received snapshot chunks with
+/// `snapshot_throttle`
+///
+/// A chunk is answered only once the throttle admits it, which holds back the sender.
+pub async fn serve_raft_rpc_throttled(
+    raft: Arc<RaftInstance>,
+    listener: TcpListener,
+    snapshot_throttle: Arc<TransferThrottle>,
+) -> std::io::Result<()> {
+    serve_raft_rpc_with_auth(raft, listener, snapshot_throttle, None, PeerVersions::new()).await
+}
+
+/// Serve Raft RPCs like [`serve_raft_rpc_throttled`], verifying signed envelopes
+/// with `rpc_auth` and recording the wire versions requests advertise in
+/// `peer_versions`
+///
+/// Connections carrying a forged, replayed or (unless allowed) unsigned request are
+/// closed without an answer. Without `rpc_auth`, signed requests are accepted
+/// unverified, so signing can be enabled one node at a time.
+pub async fn serve_raft_rpc_with_auth(
+    raft: Arc<RaftInstance>,
+    listener: TcpListener,
+    snapshot_throttle: Arc<TransferThrottle>,
+    rpc_auth: Option<Arc<RpcAuthenticator>>,
+    peer_versions: PeerVersions,
+) -> std::io::Result<()> {
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let raft = Arc::clone(&raft);
+        let snapshot_throttle = Arc::clone(&snapshot_throttle);
+        let rpc_auth = rpc_auth.clone();
+        let peer_versions = peer_versions.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_rpc_connection(
+                &raft,
+                &snapshot_throttle,
+                rpc_auth.as_deref(),
+                &peer_versions,
+                stream,
+            )
+            .await
+            {
+                debug!("Raft RPC connection from {} closed: {}", peer, e);
+            }
+        });
+    }
+}
+
+/// Answer RPCs on one peer connection until the peer closes it
+async fn handle_rpc_connection(
+    raft: &RaftInstance,
+    snapshot_throttle: &TransferThrottle,
+    rpc_auth: Option<&RpcAuthenticator>,
+    peer_versions: &PeerVersions,
+    mut stream: TcpStream,
+) -> std::io::Result<()> {
+    let node_id = raft.metrics().borrow().id;
+    loop {
+        let mut len_bytes = [0u8; 4];
+        match stream.read_exact(&mut len_bytes).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        }
+
+        let (len, compressed) = parse_length_prefix(len_bytes);
+        if len > MAX_MESSAGE_SIZE {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("RPC message of {} bytes exceeds limit", len),
+            ));
+        }
+        let mut message_bytes = vec![0u8; len];
+        timeout(DEFAULT_TIMEOUT, stream.read_exact(&mut message_bytes))
+            .await
+            .map_err(|_| {
+                std::io::Error::new(std::io::ErrorKind::TimedOut, "Timeout reading message")
+            })??;
+        let message_bytes = match (is_signed(len_bytes), rpc_auth) {
+            (true, Some(rpc_auth)) => rpc_auth.open(compressed as u8, message_bytes),
+            (true, None) => strip_envelope(message_bytes),
+            (false, Some(rpc_auth)) => rpc_auth.check_unsigned().map(|_| message_bytes),
+            (false, None) => Ok(message_bytes),
+        }
+        .map_err(|e| {
+            warn!("Rejected Raft RPC: {}", e);
+            std::io::Error::new(std::io::ErrorKind::PermissionDenied, e.to_string())
+        })?;
+        let message_bytes = decode_payload(message_bytes, compressed)?;
+
+        let mut reader = message_bytes.as_slice();
+        let message: NetworkMessage = bincode::deserialize_from(&mut reader)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        if let Some((sender, version)) = request_advert(reader) {
+            peer_versions.record(sender, version);
+        }
+
+        let response = match message {
+            NetworkMessage::AppendEntries(rpc) => NetworkResponse::AppendEntries(
+                raft.append_entries(rpc).await.map_err(|e| e.to_string()),
+            ),
+            NetworkMessage::Vote(rpc) => {
+                NetworkResponse::Vote(raft.vote(rpc).await.map_err(|e| e.to_string()))
+            }
+            NetworkMessage::InstallSnapshot(rpc) => {
+                throttle_snapshot_chunk(snapshot_throttle, "received", &rpc).await;
+                NetworkResponse::InstallSnapshot(
+                    raft.install_snapshot(rpc).await.map_err(|e| e.to_string()),
+                )
+            }
+            NetworkMessage::ReadIndex => NetworkResponse::ReadIndex(grant_read_index(raft).await),
+        };
+        if let NetworkResponse::AppendEntries(Err(e))
+        | NetworkResponse::Vote(Err(e))
+        | NetworkResponse::InstallSnapshot(Err(e)) = &response
+        {
+            warn!("Raft RPC failed: {}", e);
+        }
+
+        // Only answer compressed to peers that showed they can decode it
+        let mut response_bytes = bincode::serialize(&response)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        append_version_advert(&mut response_bytes, node_id);
+        let (response_bytes, response_compressed) =
+            encode_payload(response_bytes, compressed.then_some(RESPONSE_COMPRESSION))?;
+        stream
+            .write_all(&length_prefix(response_bytes.len(), response_compressed))
+            .await?;
+        stream.write_all(&response_bytes).await?;
+        stream.flush().await?;
     }
 }
 
+/// Confirm this node still leads with a quorum, then report the log index it has
+/// applied, which covers every write committed before the confirmation
+async fn grant_read_index(raft: &RaftInstance) -> Result<u64, ReadIndexRefusal> {
+    raft.ensure_linearizable().await.map_err(|e| match e {
+        RaftError::APIError(CheckIsLeaderError::ForwardToLeader(forward)) => ReadIndexRefusal {
+            leader_id: forward.leader_id,
+            message: "Not the leader".to_string(),
+        },
+        e => ReadIndexRefusal {
+            leader_id: None,
+            message: format!("Failed to confirm leadership: {}", e),
+        },
+    })?;
+    Ok(raft
+        .metrics()
+        .borrow()
+        .last_applied
+        .map_or(0, |log_id| log_id.index))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -420,4 +1052,124 @@ mod tests {
             _ => panic!("Expected successful AppendEntries response"),
         }
     }
+
+    #[test]
+    fn test_response_version_advert() {
+        let response = NetworkResponse::Vote(Err("stale term".to_string()));
+        let mut bytes = bincode::serialize(&response).unwrap();
+        append_version_advert(&mut bytes, TEST_NODE_ID_2);
+
+        // Older nodes decode the response and ignore the advert
+        let legacy: NetworkResponse = bincode::deserialize(&bytes).unwrap();
+        assert!(matches!(legacy, NetworkResponse::Vote(Err(_))));
+
+        let mut reader = bytes.as_slice();
+        let _: NetworkResponse = bincode::deserialize_from(&mut reader).unwrap();
+        assert_eq!(
+            advertised_version(reader, TEST_NODE_ID_2),
+            Some(wire::WIRE_VERSION)
+        );
+        // An advert from another node than the one asked is not trusted
+        assert_eq!(advertised_version(reader, TEST_NODE_ID), None);
+        assert_eq!(advertised_version(&[0xff, 0xff], TEST_NODE_ID_2), None);
+        // Nodes from before versioning send no advert
+        assert_eq!(
+            advertised_version(&[], TEST_NODE_ID_2),
+            Some(LEGACY_WIRE_VERSION)
+        );
+    }
+
+    #[test]
+    fn test_request_version_advert() {
+        let mut bytes = bincode::serialize(&NetworkMessage::ReadIndex).unwrap();
+        append_version_advert(&mut bytes, TEST_NODE_ID_2);
+
+        // Older nodes decode the request and ignore the advert
+        let legacy: NetworkMessage = bincode::deserialize(&bytes).unwrap();
+        assert!(matches!(legacy, NetworkMessage::ReadIndex));
+
+        let mut reader = bytes.as_slice();
+        let _: NetworkMessage = bincode::deserialize_from(&mut reader).unwrap();
+        assert_eq!(
+            request_advert(reader),
+            Some((TEST_NODE_ID_2, wire::WIRE_VERSION))
+        );
+        // Requests of older nodes tell nothing about the sender
+        assert_eq!(request_advert(&[]), None);
+        assert_eq!(request_advert(&[0xff, 0xff]), None);
+    }
+
+    #[test]
+    fn test_length_prefix_flag() {
+        assert_eq!(
+            parse_length_prefix(length_prefix(1234, false)),
+            (1234, false)
+        );
+        assert_eq!(parse_length_prefix(length_prefix(1234, true)), (1234, true));
+        // Nodes without compression support reject flagged frames as oversized
+        assert!(u32::from_be_bytes(length_prefix(1, true)) as usize > MAX_MESSAGE_SIZE);
+
+        let signed = mark_signed(length_prefix(1234, true));
+        assert!(is_signed(signed));
+        assert!(!is_signed(length_prefix(1234, true)));
+        assert_eq!(parse_length_prefix(signed), (1234, true));
+        assert!(
+            u32::from_be_bytes(mark_signed(length_prefix(1, false))) as usize > MAX_MESSAGE_SIZE
+        );
+    }
+
+    #[test]
+    fn test_payload_compression_roundtrip() {
+        let compression = WireCompression {
+            level: 6,
+            min_size: 64,
+        };
+
+        let payload = b"entry".repeat(1000);
+        let (encoded, compressed) = encode_payload(payload.clone(), Some(compression)).unwrap();
+        assert!(compressed);
+        assert!(encoded.len() < payload.len());
+        assert_eq!(decode_payload(encoded, compressed).unwrap(), payload);
+
+        // Small payloads and disabled compression are sent as is
+        let (encoded, compressed) = encode_payload(b"tiny".to_vec(), Some(compression)).unwrap();
+        assert!(!compressed);
+        assert_eq!(encoded, b"tiny");
+        let (_, compressed) = encode_payload(payload, None).unwrap();
+        assert!(!compressed);
+
+        assert!(decode_payload(b"not deflate".to_vec(), true).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_factory_compression_per_peer() {
+        let mut factory = NetworkFactory::new(TEST_NODE_ID);
+        let node = BasicNode::default();
+        assert_eq!(
+            factory.new_client(TEST_NODE_ID_2, &node).await.compression,
+            None
+        );
+
+        factory
+            .set_compression(RaftCompressionConfig {
+                enabled: true,
+                peers: vec![TEST_NODE_ID_2],
+                ..Default::default()
+            })
+            .await;
+        let network = factory.new_client(TEST_NODE_ID_2, &node).await;
+        assert_eq!(network.compression.map(|c| c.level), Some(6));
+        assert_eq!(factory.new_client(3, &node).await.compression, None);
+
+        // Compressed messages wait for the target to advertise support
+        assert_eq!(network.active_compression(), None);
+        factory
+            .peer_versions()
+            .record(TEST_NODE_ID_2, COMPRESSION_WIRE_VERSION - 1);
+        assert_eq!(network.active_compression(), None);
+        factory
+            .peer_versions()
+            .record(TEST_NODE_ID_2, COMPRESSION_WIRE_VERSION);
+        assert_eq!(network.active_compression(), network.compression);
+    }
 }
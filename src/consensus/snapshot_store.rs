@@ -0,0 +1,258 @@
+//! Raft snapshots kept on disk
+//!
+//! The state machine builds and installs snapshots in memory. Given a
+//! [`SnapshotStore`], it also writes the latest snapshot to a sled database of its
+//! own, so a restarted node resumes from that snapshot instead of starting empty and
+//! depending on a log that may have been purged. The values of the snapshot are
+//! stored one per key with the configured [`StorageLayout`]; the rest of the state
+//! is stored as one record.
+
+use crate::consensus::state_machine::SnapshotData;
+use crate::error::{Result, ScribeError};
+use crate::storage::layout::{self, LayeredStorage, StorageLayout};
+use crate::storage::StorageBackend;
+use crate::types::{Key, NodeId, Value};
+use openraft::SnapshotMeta;
+use std::collections::HashMap;
+use std::path::Path;
+use tokio::sync::Mutex;
+
+/// Tree holding everything of the snapshot but its values
+const RECORD_TREE: &[u8] = b"__snapshot";
+
+/// Key of the snapshot metadata, written last so it marks a complete snapshot
+const META_KEY: &[u8] = b"meta";
+
+/// Key of the state machine state other than the values
+const STATE_KEY: &[u8] = b"state";
+
+/// Metadata of a stored snapshot
+pub type StoredSnapshotMeta = SnapshotMeta<NodeId, openraft::BasicNode>;
+
+/// Latest Raft snapshot of a node, kept in a sled database
+pub struct SnapshotStore {
+    record: sled::Tree,
+    values: LayeredStorage,
+    /// Held while a snapshot is written, so two saves never interleave
+    saving: Mutex<()>,
+}
+
+impl SnapshotStore {
+    /// Open the store at `path`, whose values are laid out with `layout`
+    ///
+    /// Fails if the values were written with another layout; run
+    /// [`migrate`](Self::migrate) first.
+    pub fn open<P: AsRef<Path>>(path: P, layout: &StorageLayout) -> Result<Self> {
+        Self::with_db(sled::open(path)?, layout)
+    }
+
+    /// Open the store in an open database
+    pub fn with_db(db: sled::Db, layout: &StorageLayout) -> Result<Self> {
+        Ok(Self {
+            record: db.open_tree(RECORD_TREE)?,
+            values: LayeredStorage::open(db, layout)?,
+            saving: Mutex::new(()),
+        })
+    }
+
+    /// Rewrite the store at `path` into `layout`, returning the number of keys rewritten
+    pub async fn migrate<P: AsRef<Path>>(path: P, layout: &StorageLayout) -> Result<usize> {
+        layout::migrate(sled::open(path)?, layout).await
+    }
+
+    /// Get the storage holding the values of the snapshot
+    pub fn values(&self) -> &LayeredStorage {
+        &self.values
+    }
+
+    /// Write `snapshot` as the latest snapshot
+    ///
+    /// A snapshot older than the stored one is left out and `false` returned. The
+    /// metadata is removed while the rest is rewritten, so a crash midway leaves no
+    /// snapshot rather than a mix of two.
+    pub async fn save(
+        &self,
+        meta: &StoredSnapshotMeta,
+        snapshot: &mut SnapshotData,
+    ) -> Result<bool> {
+        let _saving = self.saving.lock().await;
+        if let Some(stored) = self.meta()? {
+            if stored.last_log_id > meta.last_log_id {
+                return Ok(false);
+            }
+        }
+
+        self.record.remove(META_KEY)?;
+        self.record.flush_async().await?;
+        self.values.clear().await?;
+
+        // The values are stored one per key, not in the state record
+        let values = std::mem::take(&mut snapshot.data);
+        let written = self.write(meta, snapshot, &values).await;
+        snapshot.data = values;
+        written.map(|()| true)
+    }
+
+    async fn write(
+        &self,
+        meta: &StoredSnapshotMeta,
+        snapshot: &SnapshotData,
+        values: &HashMap<Key, Value>,
+    ) -> Result<()> {
+        for (key, value) in values {
+            self.values.put(key.clone(), value.clone()).await?;
+        }
+        self.values.flush().await?;
+        self.record
+            .insert(STATE_KEY, bincode::serialize(snapshot)?)?;
+        self.record.insert(META_KEY, bincode::serialize(meta)?)?;
+        self.record.flush_async().await?;
+        Ok(())
+    }
+
+    /// Read the latest snapshot, if a complete one was written
+    pub async fn load(&self) -> Result<Option<(StoredSnapshotMeta, SnapshotData)>> {
+        let Some(meta) = self.meta()? else {
+            return Ok(None);
+        };
+        let state = self
+            .record
+            .get(STATE_KEY)?
+            .ok_or_else(|| ScribeError::DataCorruption("snapshot state missing".to_string()))?;
+        let mut snapshot: SnapshotData = bincode::deserialize(&state).map_err(|e| {
+            ScribeError::DataCorruption(format!("unreadable snapshot state: {}", e))
+        })?;
+        snapshot.data = self.values.snapshot().await?;
+        Ok(Some((meta, snapshot)))
+    }
+
+    /// Metadata of the stored snapshot, if a complete one was written
+    pub fn meta(&self) -> Result<Option<StoredSnapshotMeta>> {
+        match self.record.get(META_KEY)? {
+            Some(bytes) => bincode::deserialize(&bytes).map(Some).map_err(|e| {
+                ScribeError::DataCorruption(format!("unreadable snapshot metadata: {}", e))
+            }),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consensus::state_machine::StateMachineStore;
+    use crate::consensus::type_config::{AppRequest, TypeConfig};
+    use openraft::storage::RaftStateMachine;
+    use openraft::{EntryPayload, LeaderId, LogId, RaftSnapshotBuilder};
+    use std::sync::Arc;
+
+    fn put(index: u64, key: &str) -> openraft::Entry<TypeConfig> {
+        openraft::Entry {
+            log_id: LogId::new(LeaderId::new(1, 1), index),
+            payload: EntryPayload::Normal(AppRequest::Put {
+                key: key.as_bytes().to_vec(),
+                value: key.as_bytes().to_vec(),
+            }),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_snapshots_survive_a_restart() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let layout = StorageLayout::default();
+
+        let store = Arc::new(SnapshotStore::with_db(db.clone(), &layout).unwrap());
+        let mut sm = StateMachineStore::with_snapshot_store(store.clone())
+            .await
+            .unwrap();
+        sm.apply(vec![put(1, "a/1"), put(2, "b/1")]).await.unwrap();
+        let snapshot = sm
+            .get_snapshot_builder()
+            .await
+            .build_snapshot()
+            .await
+            .unwrap();
+        assert_eq!(store.meta().unwrap(), Some(snapshot.meta.clone()));
+        drop(sm);
+
+        // A restarted node starts from the stored snapshot
+        let mut restored = StateMachineStore::with_snapshot_store(store.clone())
+            .await
+            .unwrap();
+        assert_eq!(restored.get(&b"a/1".to_vec()).await, Some(b"a/1".to_vec()));
+        let (applied, _) = restored.applied_state().await.unwrap();
+        assert_eq!(applied, snapshot.meta.last_log_id);
+        assert_eq!(
+            restored.current_snapshot_meta().await,
+            Some(snapshot.meta.clone())
+        );
+
+        // An older snapshot never replaces a newer one
+        let (_, mut older) = store.load().await.unwrap().unwrap();
+        older.data.clear();
+        let mut older_meta = snapshot.meta.clone();
+        older_meta.last_log_id = Some(LogId::new(LeaderId::new(1, 1), 1));
+        assert!(!store.save(&older_meta, &mut older).await.unwrap());
+        assert_eq!(store.load().await.unwrap().unwrap().1.data.len(), 2);
+        drop(restored);
+        drop(store);
+
+        // Another layout needs a migration first
+        let namespaced = StorageLayout {
+            namespaces: vec!["a/".to_string()],
+            ..StorageLayout::default()
+        };
+        assert!(SnapshotStore::with_db(db.clone(), &namespaced).is_err());
+        assert_eq!(layout::migrate(db.clone(), &namespaced).await.unwrap(), 1);
+        let store = SnapshotStore::with_db(db, &namespaced).unwrap();
+        assert_eq!(
+            store.values().trees().namespace_len(b"a/").await.unwrap(),
+            1
+        );
+        let (meta, snapshot) = store.load().await.unwrap().unwrap();
+        assert_eq!(meta.last_log_id, applied);
+        assert_eq!(snapshot.data.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_checksums_detect_corrupted_snapshots() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let store =
+            Arc::new(SnapshotStore::with_db(db.clone(), &StorageLayout::default()).unwrap());
+        let mut sm = StateMachineStore::with_snapshot_store(store.clone())
+            .await
+            .unwrap();
+        sm.apply(vec![put(1, "a/1"), put(2, "b/1")]).await.unwrap();
+        sm.get_snapshot_builder()
+            .await
+            .build_snapshot()
+            .await
+            .unwrap();
+        drop(sm);
+        drop(store);
+
+        // Values written without checksums are framed by the migration
+        let checksummed = StorageLayout {
+            checksums: true,
+            ..StorageLayout::default()
+        };
+        assert!(SnapshotStore::with_db(db.clone(), &checksummed).is_err());
+        assert_eq!(layout::migrate(db.clone(), &checksummed).await.unwrap(), 2);
+        let store = Arc::new(SnapshotStore::with_db(db.clone(), &checksummed).unwrap());
+        let restored = StateMachineStore::with_snapshot_store(store.clone())
+            .await
+            .unwrap();
+        assert_eq!(restored.get(&b"b/1".to_vec()).await, Some(b"b/1".to_vec()));
+        drop(restored);
+
+        // A flipped bit on disk fails the restore instead of being served
+        let key = b"a/1".to_vec();
+        let mut stored = store.values().trees().get(&key).await.unwrap().unwrap();
+        *stored.last_mut().unwrap() ^= 0x01;
+        store.values().trees().put(key, stored).await.unwrap();
+        assert!(matches!(
+            StateMachineStore::with_snapshot_store(store.clone()).await,
+            Err(ScribeError::DataCorruption(_))
+        ));
+    }
+}
@@ -12,13 +12,24 @@ use openraft::{
     LogId, RaftSnapshotBuilder, SnapshotMeta, StorageError, StorageIOError, StoredMembership,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap};
 use std::io::Cursor;
+use std::ops::Bound;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, RwLock};
 
-use crate::consensus::type_config::{AppRequest, AppResponse, TypeConfig};
-use crate::types::{Key, NodeId, Value};
+use crate::consensus::snapshot_store::SnapshotStore;
+use crate::consensus::type_config::{value_digest, AppRequest, AppResponse, TypeConfig};
+use crate::crypto::MerkleTree;
+use crate::merge_patch::apply_merge_patch;
+use crate::metrics::{APPLY_BATCH_SIZE, STATE_MACHINE_APPLY_LATENCY};
+use crate::quota::KeyUsage;
+use crate::types::{
+    Fence, Key, LedgerTag, LockLease, NodeId, QueueItem, QueueStats, SegmentAnchor, SegmentId,
+    SessionRecord, TxnWrite, Value,
+};
 
 /// Snapshot data structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,6 +40,125 @@ pub struct SnapshotData {
     pub last_membership: StoredMembership<NodeId, openraft::BasicNode>,
     /// State machine data (key-value pairs)
     pub data: HashMap<Key, Value>,
+    /// Last modification of every key, used to rebuild the time index
+    pub changes: Vec<KeyChange>,
+    /// Expiry deadlines of keys written with a TTL
+    pub expirations: HashMap<Key, u64>,
+    /// Archived segment holding the value of every demoted key
+    pub cold: HashMap<Key, SegmentId>,
+    /// Lock leases by lock name, including expired ones not yet taken over
+    pub locks: HashMap<String, LockLease>,
+    /// Unacked items of every durable queue, by queue name and item ID
+    pub queues: HashMap<String, BTreeMap<u64, QueueItem>>,
+    /// Tagged states by tag name
+    pub tags: BTreeMap<String, TaggedState>,
+    /// Write fencing epoch by namespace
+    pub epochs: BTreeMap<String, u64>,
+    /// Quota usage by API key ID
+    pub quota_usage: BTreeMap<String, KeyUsage>,
+    /// Committed Merkle roots of archived segments
+    pub anchors: BTreeMap<SegmentId, SegmentAnchor>,
+}
+
+/// Ledger state captured by a tag
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaggedState {
+    /// The tag
+    pub tag: LedgerTag,
+    /// Values held in memory when the tag was created
+    pub values: BTreeMap<Key, Value>,
+    /// Archived segment of every key that was demoted when the tag was created
+    pub cold: BTreeMap<Key, SegmentId>,
+}
+
+impl TaggedState {
+    /// Merkle root of the captured state, `None` if it holds no keys
+    ///
+    /// The state machine does not hold the values of demoted keys, so their leaves
+    /// commit to the archived segment instead.
+    pub fn merkle_root(&self) -> Option<Vec<u8>> {
+        let pairs = self
+            .values
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .chain(
+                self.cold
+                    .iter()
+                    .map(|(key, segment_id)| (key.clone(), cold_leaf(*segment_id))),
+            )
+            .collect();
+        MerkleTree::from_pairs(pairs).root_hash()
+    }
+}
+
+/// Merkle leaf value standing in for a value demoted to `segment_id`
+fn cold_leaf(segment_id: SegmentId) -> Vec<u8> {
+    format!("cold:{}", segment_id).into_bytes()
+}
+
+/// Current value of a key as held by the state machine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoredValue {
+    /// Value held in memory
+    Hot(Value),
+    /// Value demoted to an archived segment
+    Cold(SegmentId),
+}
+
+/// Capacity of the change event channel; slow subscribers miss older events
+const CHANGE_EVENT_CAPACITY: usize = 1024;
+
+/// Kind of change applied to a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// Key was written
+    Put,
+    /// Key was explicitly deleted by a client
+    Delete,
+    /// Key was removed because its TTL elapsed
+    Expire,
+}
+
+impl ChangeKind {
+    /// Get the change kind as a string
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeKind::Put => "put",
+            ChangeKind::Delete => "delete",
+            ChangeKind::Expire => "expire",
+        }
+    }
+}
+
+/// Change event emitted by the state machine when a log entry is applied
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeEvent {
+    /// The changed key
+    pub key: Key,
+    /// What happened to the key
+    pub kind: ChangeKind,
+    /// Time the change was applied (milliseconds since UNIX epoch)
+    pub timestamp: u64,
+    /// New version of the key (log index of the change)
+    pub version: u64,
+    /// Value written by a put, attached while the change stream has subscribers so
+    /// they can filter on it (see [`crate::subscription`]); never serialized
+    #[serde(skip)]
+    pub value: Option<Arc<Value>>,
+}
+
+/// Last modification of a key as recorded by the time index
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyChange {
+    /// The modified key
+    pub key: Key,
+    /// Time the change was applied (milliseconds since UNIX epoch)
+    pub timestamp: u64,
+    /// Whether the key was deleted
+    pub deleted: bool,
+    /// Log index of the change, which serves as the key's version
+    pub version: u64,
 }
 
 /// State machine for the key-value store
@@ -39,6 +169,28 @@ pub struct StateMachine {
     last_membership: StoredMembership<NodeId, openraft::BasicNode>,
     /// In-memory key-value store
     data: HashMap<Key, Value>,
+    /// Last modification per key
+    changes: HashMap<Key, KeyChange>,
+    /// Time index ordered by (timestamp, key)
+    time_index: BTreeSet<(u64, Key)>,
+    /// Highest timestamp handed out, keeps the index monotonic across clock jumps
+    last_timestamp: u64,
+    /// Expiry deadline per key (milliseconds since UNIX epoch)
+    expirations: HashMap<Key, u64>,
+    /// Archived segment per demoted key, whose value is no longer held in `data`
+    cold: HashMap<Key, SegmentId>,
+    /// Lock leases by lock name
+    locks: HashMap<String, LockLease>,
+    /// Unacked queue items by queue name, ordered by item ID (enqueue order)
+    queues: HashMap<String, BTreeMap<u64, QueueItem>>,
+    /// Tagged states by tag name, shared with readers exporting them
+    tags: BTreeMap<String, Arc<TaggedState>>,
+    /// Write fencing epoch by namespace; namespaces not listed are at epoch 0
+    epochs: BTreeMap<String, u64>,
+    /// Quota usage by API key ID
+    quota_usage: BTreeMap<String, KeyUsage>,
+    /// Committed Merkle roots of archived segments, by segment ID
+    anchors: BTreeMap<SegmentId, SegmentAnchor>,
 }
 
 impl StateMachine {
@@ -48,18 +200,435 @@ impl StateMachine {
             last_applied: None,
             last_membership: StoredMembership::default(),
             data: HashMap::new(),
+            changes: HashMap::new(),
+            time_index: BTreeSet::new(),
+            last_timestamp: 0,
+            expirations: HashMap::new(),
+            cold: HashMap::new(),
+            locks: HashMap::new(),
+            queues: HashMap::new(),
+            tags: BTreeMap::new(),
+            epochs: BTreeMap::new(),
+            quota_usage: BTreeMap::new(),
+            anchors: BTreeMap::new(),
         }
     }
 
     /// Get a value from the state machine
+    ///
+    /// Keys whose TTL elapsed are hidden even before the expiry is applied.
     pub fn get(&self, key: &Key) -> Option<Value> {
+        if let Some(expires_at) = self.expirations.get(key) {
+            if *expires_at <= now_millis() {
+                return None;
+            }
+        }
+        self.data.get(key).cloned()
+    }
+
+    /// Stored value of `key` unless its TTL elapsed by `now`
+    ///
+    /// Used while applying entries, where `now` comes from the log so replicas agree.
+    fn value_at(&self, key: &Key, now: u64) -> Option<Value> {
+        if self.expired_at(key, now) {
+            return None;
+        }
         self.data.get(key).cloned()
     }
 
+    /// Whether the TTL of `key` elapsed by `now`
+    fn expired_at(&self, key: &Key, now: u64) -> bool {
+        self.expirations
+            .get(key)
+            .is_some_and(|expires_at| *expires_at <= now)
+    }
+
+    /// Look up a key, including values demoted to cold storage
+    pub fn lookup(&self, key: &Key) -> Option<StoredValue> {
+        if let Some(value) = self.get(key) {
+            return Some(StoredValue::Hot(value));
+        }
+        if self
+            .expirations
+            .get(key)
+            .is_some_and(|expires_at| *expires_at <= now_millis())
+        {
+            return None;
+        }
+        self.cold
+            .get(key)
+            .map(|segment_id| StoredValue::Cold(*segment_id))
+    }
+
+    /// Look up a key together with its version, as of the same applied entry
+    pub fn lookup_versioned(&self, key: &Key) -> (Option<StoredValue>, Option<u64>) {
+        (self.lookup(key), self.key_version(key))
+    }
+
+    /// Get the lease on lock `name`, unless it expired by the local clock
+    pub fn lock(&self, name: &str) -> Option<LockLease> {
+        self.locks
+            .get(name)
+            .filter(|lease| lease.expires_at > now_millis())
+            .cloned()
+    }
+
+    /// Write fencing epoch of `namespace`
+    pub fn epoch(&self, namespace: &str) -> u64 {
+        self.epochs.get(namespace).copied().unwrap_or(0)
+    }
+
+    /// Recorded quota usage of the API key `key_id`
+    pub fn quota_usage(&self, key_id: &str) -> Option<KeyUsage> {
+        self.quota_usage.get(key_id).cloned()
+    }
+
+    /// Check a fenced request against the epoch of its namespace
+    ///
+    /// Only key writes within the namespace can be fenced.
+    fn check_fence(&self, fence: &Fence, request: &AppRequest) -> Option<AppResponse> {
+        let Some(keys) = request.written_keys() else {
+            return Some(AppResponse::Error {
+                message: "Only key writes can be fenced".to_string(),
+            });
+        };
+        if keys
+            .iter()
+            .any(|key| !key.starts_with(fence.namespace.as_bytes()))
+        {
+            return Some(AppResponse::Error {
+                message: format!("Fenced write leaves namespace '{}'", fence.namespace),
+            });
+        }
+        let current = self.epoch(&fence.namespace);
+        (fence.epoch < current).then_some(AppResponse::StaleEpoch { current })
+    }
+
+    /// Length of queue `name`, counting leases by the local clock
+    pub fn queue_stats(&self, name: &str) -> QueueStats {
+        let now = now_millis();
+        let items = self.queues.get(name);
+        QueueStats {
+            queue: name.to_string(),
+            length: items.map_or(0, |items| items.len()),
+            in_flight: items.map_or(0, |items| {
+                items.values().filter(|item| item.visible_at > now).count()
+            }),
+        }
+    }
+
+    /// All tags, oldest first
+    pub fn tags(&self) -> Vec<LedgerTag> {
+        let mut tags: Vec<LedgerTag> = self.tags.values().map(|state| state.tag.clone()).collect();
+        tags.sort_by_key(|tag| tag.raft_index);
+        tags
+    }
+
+    /// State captured by tag `name`
+    pub fn tagged_state(&self, name: &str) -> Option<Arc<TaggedState>> {
+        self.tags.get(name).cloned()
+    }
+
+    /// Capture the state left by the entries applied so far as tag `name`
+    ///
+    /// Keys whose TTL elapsed by `now` are left out, as readers no longer see them.
+    fn capture_tag(
+        &self,
+        name: &str,
+        raft_index: u64,
+        manifest_version: u64,
+        now: u64,
+    ) -> TaggedState {
+        let live = |key: &Key| {
+            self.expirations
+                .get(key)
+                .is_none_or(|expires_at| *expires_at > now)
+        };
+        let values: BTreeMap<Key, Value> = self
+            .data
+            .iter()
+            .filter(|(key, _)| live(key))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        let cold: BTreeMap<Key, SegmentId> = self
+            .cold
+            .iter()
+            .filter(|(key, _)| live(key))
+            .map(|(key, segment_id)| (key.clone(), *segment_id))
+            .collect();
+
+        let mut state = TaggedState {
+            tag: LedgerTag {
+                name: name.to_string(),
+                raft_index,
+                manifest_version,
+                merkle_root: None,
+                key_count: values.len() + cold.len(),
+                created_at: now,
+            },
+            values,
+            cold,
+        };
+        state.tag.merkle_root = state.merkle_root().map(hex::encode);
+        state
+    }
+
+    /// Get keys whose TTL elapsed at `now`, with their expiry deadlines
+    pub fn expired_keys(&self, now: u64) -> Vec<(Key, u64)> {
+        self.expirations
+            .iter()
+            .filter(|(_, expires_at)| **expires_at <= now)
+            .map(|(key, expires_at)| (key.clone(), *expires_at))
+            .collect()
+    }
+
+    /// Number of keys stored, including demoted keys
+    pub fn key_count(&self) -> usize {
+        self.data.len() + self.cold.len()
+    }
+
     /// Get all data from the state machine
     pub fn get_all(&self) -> HashMap<Key, Value> {
         self.data.clone()
     }
+
+    /// Every key readers can see, including demoted keys
+    pub fn entries(&self) -> Vec<(Key, StoredValue)> {
+        let now = now_millis();
+        let live = |key: &Key| {
+            self.expirations
+                .get(key)
+                .is_none_or(|expires_at| *expires_at > now)
+        };
+        self.data
+            .iter()
+            .filter(|(key, _)| live(key))
+            .map(|(key, value)| (key.clone(), StoredValue::Hot(value.clone())))
+            .chain(
+                self.cold
+                    .iter()
+                    .filter(|(key, _)| live(key))
+                    .map(|(key, segment_id)| (key.clone(), StoredValue::Cold(*segment_id))),
+            )
+            .collect()
+    }
+
+    /// Up to `limit` live entries sorting after `after`, in key order, including
+    /// demoted keys
+    ///
+    /// Only the page is held: the smallest keys are kept in a heap of `limit` keys
+    /// rather than sorting every key.
+    pub fn entries_after(&self, after: Option<&[u8]>, limit: usize) -> Vec<(Key, StoredValue)> {
+        let now = now_millis();
+        let mut page: BinaryHeap<&Key> = BinaryHeap::with_capacity(limit + 1);
+        for key in self.data.keys().chain(self.cold.keys()) {
+            if after.is_some_and(|after| key.as_slice() <= after)
+                || self
+                    .expirations
+                    .get(key)
+                    .is_some_and(|expires_at| *expires_at <= now)
+            {
+                continue;
+            }
+            if page.len() < limit {
+                page.push(key);
+            } else if page.peek().is_some_and(|largest| key < *largest) {
+                page.pop();
+                page.push(key);
+            }
+        }
+        page.into_sorted_vec()
+            .into_iter()
+            .filter_map(|key| {
+                let stored = match self.data.get(key) {
+                    Some(value) => StoredValue::Hot(value.clone()),
+                    None => StoredValue::Cold(*self.cold.get(key)?),
+                };
+                Some((key.clone(), stored))
+            })
+            .collect()
+    }
+
+    /// Up to `limit` live keys starting with `prefix` and sorting after `after`, in
+    /// key order, including demoted keys
+    pub fn keys_with_prefix(&self, prefix: &[u8], after: Option<&[u8]>, limit: usize) -> Vec<Key> {
+        let now = now_millis();
+        let mut keys: Vec<Key> = self
+            .data
+            .keys()
+            .chain(self.cold.keys())
+            .filter(|key| key.starts_with(prefix))
+            .filter(|key| after.is_none_or(|after| key.as_slice() > after))
+            .filter(|key| {
+                self.expirations
+                    .get(*key)
+                    .is_none_or(|expires_at| *expires_at > now)
+            })
+            .cloned()
+            .collect();
+        keys.sort_unstable();
+        keys.truncate(limit);
+        keys
+    }
+
+    /// Get keys modified within `[since, until]` (milliseconds since UNIX epoch)
+    ///
+    /// Results are ordered by modification time and include deleted keys.
+    pub fn changes_between(&self, since: u64, until: u64) -> Vec<KeyChange> {
+        self.changes_page(since, until, None, usize::MAX)
+    }
+
+    /// Up to `limit` keys modified within `[since, until]`, starting after the
+    /// change at `after` (its timestamp and key) if given
+    ///
+    /// Results are ordered by modification time, then key, and include deleted keys.
+    pub fn changes_page(
+        &self,
+        since: u64,
+        until: u64,
+        after: Option<&(u64, Key)>,
+        limit: usize,
+    ) -> Vec<KeyChange> {
+        if since > until {
+            return Vec::new();
+        }
+        let end = (until.saturating_add(1), Vec::new());
+        let start = match after {
+            Some(after) if after.0 >= since => Bound::Excluded(after.clone()),
+            _ => Bound::Included((since, Vec::new())),
+        };
+        // A cursor past the window leaves nothing, and a range that ends before it
+        // starts would panic
+        if matches!(&start, Bound::Excluded(after) if *after >= end) {
+            return Vec::new();
+        }
+
+        self.time_index
+            .range((start, Bound::Excluded(end)))
+            .take(limit)
+            .filter_map(|(_, key)| self.changes.get(key).cloned())
+            .collect()
+    }
+
+    /// Number of changes of deleted or expired keys, recorded before `before`, that
+    /// pruning would forget
+    pub fn tombstones_before(&self, before: u64) -> usize {
+        self.prunable_tombstones(before).count()
+    }
+
+    /// Forget the changes of keys deleted or expired before `before`, returning how
+    /// many were forgotten
+    ///
+    /// The latest change is always kept: restoring a snapshot derives the time of the
+    /// next change from it.
+    fn prune_tombstones(&mut self, before: u64) -> u64 {
+        let pruned: Vec<(u64, Key)> = self.prunable_tombstones(before).cloned().collect();
+        for entry in &pruned {
+            self.time_index.remove(entry);
+            self.changes.remove(&entry.1);
+        }
+        pruned.len() as u64
+    }
+
+    /// Entries of the time index that [`prune_tombstones`](Self::prune_tombstones)
+    /// removes
+    fn prunable_tombstones(&self, before: u64) -> impl Iterator<Item = &(u64, Key)> {
+        let latest = self.time_index.last();
+        self.time_index
+            .range(..(before, Vec::new()))
+            .filter(move |entry| Some(*entry) != latest)
+            .filter(|(_, key)| self.changes.get(key).is_some_and(|change| change.deleted))
+    }
+
+    /// Version of `key`: the log index of its last modification, including deletes
+    pub fn key_version(&self, key: &Key) -> Option<u64> {
+        self.changes.get(key).map(|change| change.version)
+    }
+
+    /// Expiry deadline of `key` (milliseconds since UNIX epoch), if it has a TTL
+    pub fn expires_at(&self, key: &Key) -> Option<u64> {
+        self.expirations.get(key).copied()
+    }
+
+    /// Record a modification of `key` at log index `version`, committed at
+    /// `committed_at`, in the time index, returning its timestamp
+    ///
+    /// Timestamps never go backwards, even if the clocks of successive leaders do.
+    fn record_change(&mut self, key: &Key, deleted: bool, version: u64, committed_at: u64) -> u64 {
+        let timestamp = committed_at.max(self.last_timestamp);
+        self.last_timestamp = timestamp;
+
+        let change = KeyChange {
+            key: key.clone(),
+            timestamp,
+            deleted,
+            version,
+        };
+        if let Some(previous) = self.changes.insert(key.clone(), change) {
+            self.time_index.remove(&(previous.timestamp, key.clone()));
+        }
+        self.time_index.insert((timestamp, key.clone()));
+        timestamp
+    }
+
+    /// Apply the puts and deletes of `writes` at log index `version`, committed at
+    /// `committed_at`
+    fn apply_writes(
+        &mut self,
+        writes: &[TxnWrite],
+        version: u64,
+        committed_at: u64,
+        events: &mut Vec<ChangeEvent>,
+    ) {
+        for write in writes {
+            let key = &write.key;
+            let deleted = match &write.value {
+                Some(value) => {
+                    self.data.insert(key.clone(), value.clone());
+                    false
+                }
+                None => {
+                    self.data.remove(key);
+                    true
+                }
+            };
+            self.expirations.remove(key);
+            self.cold.remove(key);
+            let timestamp = self.record_change(key, deleted, version, committed_at);
+            events.push(ChangeEvent {
+                key: key.clone(),
+                kind: if deleted {
+                    ChangeKind::Delete
+                } else {
+                    ChangeKind::Put
+                },
+                timestamp,
+                version,
+                value: None,
+            });
+        }
+    }
+
+    /// Replace the time index with the given changes
+    fn restore_changes(&mut self, changes: Vec<KeyChange>) {
+        self.time_index = changes
+            .iter()
+            .map(|change| (change.timestamp, change.key.clone()))
+            .collect();
+        self.last_timestamp = changes.iter().map(|c| c.timestamp).max().unwrap_or(0);
+        self.changes = changes
+            .into_iter()
+            .map(|change| (change.key.clone(), change))
+            .collect();
+    }
+}
+
+/// Current wall-clock time in milliseconds since UNIX epoch
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
 }
 
 impl Default for StateMachine {
@@ -68,40 +637,89 @@ impl Default for StateMachine {
     }
 }
 
+/// A serialized snapshot kept to be sent to peers
+struct StoredSnapshot {
+    meta: SnapshotMeta<NodeId, openraft::BasicNode>,
+    data: Vec<u8>,
+}
+
+/// The latest snapshot of a [`StateMachineStore`], built or installed
+type SnapshotSlot = Arc<RwLock<Option<StoredSnapshot>>>;
+
 /// Snapshot builder for creating snapshots
 pub struct SnapshotBuilder {
     snapshot_data: SnapshotData,
+    /// Where the built snapshot is kept, and how many were built before
+    keep: Option<(SnapshotSlot, u64)>,
+    /// Where the built snapshot is written to disk
+    persist: Option<Arc<SnapshotStore>>,
 }
 
 impl SnapshotBuilder {
     /// Create a new snapshot builder
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         last_applied: Option<LogId<NodeId>>,
         last_membership: StoredMembership<NodeId, openraft::BasicNode>,
         data: HashMap<Key, Value>,
+        changes: Vec<KeyChange>,
+        expirations: HashMap<Key, u64>,
+        cold: HashMap<Key, SegmentId>,
+        locks: HashMap<String, LockLease>,
+        queues: HashMap<String, BTreeMap<u64, QueueItem>>,
+        tags: BTreeMap<String, TaggedState>,
+        epochs: BTreeMap<String, u64>,
+        quota_usage: BTreeMap<String, KeyUsage>,
+        anchors: BTreeMap<SegmentId, SegmentAnchor>,
     ) -> Self {
         Self {
             snapshot_data: SnapshotData {
                 last_applied,
                 last_membership,
                 data,
+                changes,
+                expirations,
+                cold,
+                locks,
+                queues,
+                tags,
+                epochs,
+                quota_usage,
+                anchors,
             },
+            keep: None,
+            persist: None,
         }
     }
+
+    /// Keep the built snapshot in `slot` as the store's current snapshot; `build`
+    /// tells snapshots of the same log index apart
+    fn keep_in(mut self, slot: SnapshotSlot, build: u64) -> Self {
+        self.keep = Some((slot, build));
+        self
+    }
+
+    /// Write the built snapshot to `store`
+    fn persist_to(mut self, store: Option<Arc<SnapshotStore>>) -> Self {
+        self.persist = store;
+        self
+    }
 }
 
 impl RaftSnapshotBuilder<TypeConfig> for SnapshotBuilder {
     async fn build_snapshot(
         &mut self,
     ) -> Result<openraft::Snapshot<TypeConfig>, StorageError<NodeId>> {
-        let snapshot_id = format!(
-            "{:?}",
-            self.snapshot_data
-                .last_applied
-                .as_ref()
-                .map(|id| format!("{}-{}", id.leader_id, id.index))
-                .unwrap_or_else(|| "none".to_string())
-        );
+        let last_applied = self
+            .snapshot_data
+            .last_applied
+            .as_ref()
+            .map(|id| format!("{}-{}", id.leader_id, id.index))
+            .unwrap_or_else(|| "none".to_string());
+        let snapshot_id = match &self.keep {
+            Some((_, build)) => format!("{}-{}", last_applied, build),
+            None => last_applied,
+        };
 
         // Serialize snapshot data to bytes
         let data = bincode::serialize(&self.snapshot_data)
@@ -113,6 +731,25 @@ impl RaftSnapshotBuilder<TypeConfig> for SnapshotBuilder {
             snapshot_id: snapshot_id.clone(),
         };
 
+        // Fail the build rather than let the log be purged past what is on disk
+        if let Some(store) = &self.persist {
+            store
+                .save(&snapshot_meta, &mut self.snapshot_data)
+                .await
+                .map_err(|e| {
+                    StorageError::from(StorageIOError::write_snapshot(
+                        Some(snapshot_meta.signature()),
+                        &e,
+                    ))
+                })?;
+        }
+
+        if let Some((slot, _)) = &self.keep {
+            *slot.write().await = Some(StoredSnapshot {
+                meta: snapshot_meta.clone(),
+                data: data.clone(),
+            });
+        }
         let cursor = Cursor::new(data);
 
         Ok(openraft::Snapshot {
@@ -126,14 +763,77 @@ impl RaftSnapshotBuilder<TypeConfig> for SnapshotBuilder {
 #[derive(Clone)]
 pub struct StateMachineStore {
     inner: Arc<RwLock<StateMachine>>,
+    events: broadcast::Sender<ChangeEvent>,
+    /// Latest snapshot, sent to peers whose log entries were purged
+    current_snapshot: SnapshotSlot,
+    /// Snapshots built so far
+    snapshot_builds: Arc<AtomicU64>,
+    /// Where snapshots are written to disk, if anywhere
+    snapshot_store: Option<Arc<SnapshotStore>>,
 }
 
 impl StateMachineStore {
     /// Create a new state machine store
     pub fn new() -> Self {
+        let (events, _) = broadcast::channel(CHANGE_EVENT_CAPACITY);
         Self {
             inner: Arc::new(RwLock::new(StateMachine::new())),
+            events,
+            current_snapshot: Arc::new(RwLock::new(None)),
+            snapshot_builds: Arc::new(AtomicU64::new(0)),
+            snapshot_store: None,
+        }
+    }
+
+    /// Create a state machine store that also writes its snapshots to `store`,
+    /// starting from the latest snapshot stored there
+    pub async fn with_snapshot_store(store: Arc<SnapshotStore>) -> crate::error::Result<Self> {
+        let mut this = Self::new();
+        if let Some((meta, snapshot)) = store.load().await? {
+            let data = bincode::serialize(&snapshot)?;
+            this.restore(snapshot).await;
+            *this.current_snapshot.write().await = Some(StoredSnapshot { meta, data });
         }
+        this.snapshot_store = Some(store);
+        Ok(this)
+    }
+
+    /// Replace the whole state with that of a snapshot
+    async fn restore(&self, snapshot_data: SnapshotData) {
+        let mut sm = self.inner.write().await;
+        sm.last_applied = snapshot_data.last_applied;
+        sm.last_membership = snapshot_data.last_membership;
+        sm.data = snapshot_data.data;
+        sm.restore_changes(snapshot_data.changes);
+        sm.expirations = snapshot_data.expirations;
+        sm.cold = snapshot_data.cold;
+        sm.locks = snapshot_data.locks;
+        sm.queues = snapshot_data.queues;
+        sm.tags = snapshot_data
+            .tags
+            .into_iter()
+            .map(|(name, state)| (name, Arc::new(state)))
+            .collect();
+        sm.epochs = snapshot_data.epochs;
+        sm.quota_usage = snapshot_data.quota_usage;
+        sm.anchors = snapshot_data.anchors;
+    }
+
+    /// Metadata of the latest snapshot built or installed, if any
+    pub async fn current_snapshot_meta(&self) -> Option<SnapshotMeta<NodeId, openraft::BasicNode>> {
+        let current = self.current_snapshot.read().await;
+        current.as_ref().map(|snapshot| snapshot.meta.clone())
+    }
+
+    /// Subscribe to change events for applied entries
+    pub fn subscribe(&self) -> broadcast::Receiver<ChangeEvent> {
+        self.events.subscribe()
+    }
+
+    /// Get keys whose TTL elapsed at `now`, with their expiry deadlines
+    pub async fn expired_keys(&self, now: u64) -> Vec<(Key, u64)> {
+        let sm = self.inner.read().await;
+        sm.expired_keys(now)
     }
 
     /// Get a value from the state machine
@@ -142,11 +842,147 @@ impl StateMachineStore {
         sm.get(key)
     }
 
+    /// Look up a key, including values demoted to cold storage
+    pub async fn lookup(&self, key: &Key) -> Option<StoredValue> {
+        let sm = self.inner.read().await;
+        sm.lookup(key)
+    }
+
+    /// Look up a key together with its version
+    pub async fn lookup_versioned(&self, key: &Key) -> (Option<StoredValue>, Option<u64>) {
+        let sm = self.inner.read().await;
+        sm.lookup_versioned(key)
+    }
+
+    /// Get the unexpired lease on lock `name`
+    pub async fn lock(&self, name: &str) -> Option<LockLease> {
+        let sm = self.inner.read().await;
+        sm.lock(name)
+    }
+
+    /// Write fencing epoch of `namespace`
+    pub async fn epoch(&self, namespace: &str) -> u64 {
+        let sm = self.inner.read().await;
+        sm.epoch(namespace)
+    }
+
+    /// Recorded quota usage of the API key `key_id`
+    pub async fn quota_usage(&self, key_id: &str) -> Option<KeyUsage> {
+        let sm = self.inner.read().await;
+        sm.quota_usage(key_id)
+    }
+
+    /// Recorded quota usage of every API key, by key ID
+    pub async fn quota_usages(&self) -> Vec<KeyUsage> {
+        let sm = self.inner.read().await;
+        sm.quota_usage.values().cloned().collect()
+    }
+
+    /// Committed Merkle root of the archived segment `segment_id`
+    pub async fn segment_anchor(&self, segment_id: SegmentId) -> Option<SegmentAnchor> {
+        let sm = self.inner.read().await;
+        sm.anchors.get(&segment_id).cloned()
+    }
+
+    /// Committed Merkle roots of every anchored segment, by segment ID
+    pub async fn segment_anchors(&self) -> Vec<SegmentAnchor> {
+        let sm = self.inner.read().await;
+        sm.anchors.values().cloned().collect()
+    }
+
+    /// Length of queue `name`
+    pub async fn queue_stats(&self, name: &str) -> QueueStats {
+        let sm = self.inner.read().await;
+        sm.queue_stats(name)
+    }
+
+    /// All tags, oldest first
+    pub async fn tags(&self) -> Vec<LedgerTag> {
+        let sm = self.inner.read().await;
+        sm.tags()
+    }
+
+    /// State captured by tag `name`
+    pub async fn tagged_state(&self, name: &str) -> Option<Arc<TaggedState>> {
+        let sm = self.inner.read().await;
+        sm.tagged_state(name)
+    }
+
+    /// Number of keys stored
+    pub async fn key_count(&self) -> usize {
+        let sm = self.inner.read().await;
+        sm.key_count()
+    }
+
     /// Get all data from the state machine
     pub async fn get_all(&self) -> HashMap<Key, Value> {
         let sm = self.inner.read().await;
         sm.get_all()
     }
+
+    /// Every key readers can see, including demoted keys
+    pub async fn entries(&self) -> Vec<(Key, StoredValue)> {
+        let sm = self.inner.read().await;
+        sm.entries()
+    }
+
+    /// Up to `limit` live entries sorting after `after`, in key order
+    pub async fn entries_after(
+        &self,
+        after: Option<&[u8]>,
+        limit: usize,
+    ) -> Vec<(Key, StoredValue)> {
+        let sm = self.inner.read().await;
+        sm.entries_after(after, limit)
+    }
+
+    /// Up to `limit` live keys starting with `prefix` and sorting after `after`
+    pub async fn keys_with_prefix(
+        &self,
+        prefix: &[u8],
+        after: Option<&[u8]>,
+        limit: usize,
+    ) -> Vec<Key> {
+        let sm = self.inner.read().await;
+        sm.keys_with_prefix(prefix, after, limit)
+    }
+
+    /// Get keys modified within `[since, until]` (milliseconds since UNIX epoch)
+    pub async fn changes_between(&self, since: u64, until: u64) -> Vec<KeyChange> {
+        let sm = self.inner.read().await;
+        sm.changes_between(since, until)
+    }
+
+    /// Get up to `limit` keys modified within `[since, until]` after the change at
+    /// `after`
+    pub async fn changes_page(
+        &self,
+        since: u64,
+        until: u64,
+        after: Option<&(u64, Key)>,
+        limit: usize,
+    ) -> Vec<KeyChange> {
+        let sm = self.inner.read().await;
+        sm.changes_page(since, until, after, limit)
+    }
+
+    /// Number of tombstones recorded before `before` that pruning would forget
+    pub async fn tombstones_before(&self, before: u64) -> usize {
+        let sm = self.inner.read().await;
+        sm.tombstones_before(before)
+    }
+
+    /// Get the version (log index of the last modification) of a key
+    pub async fn key_version(&self, key: &Key) -> Option<u64> {
+        let sm = self.inner.read().await;
+        sm.key_version(key)
+    }
+
+    /// Expiry deadline of `key`, if it has a TTL
+    pub async fn expires_at(&self, key: &Key) -> Option<u64> {
+        let sm = self.inner.read().await;
+        sm.expires_at(key)
+    }
 }
 
 impl Default for StateMachineStore {
@@ -176,10 +1012,14 @@ impl RaftStateMachine<TypeConfig> for StateMachineStore {
         I: IntoIterator<Item = openraft::Entry<TypeConfig>> + Send,
         I::IntoIter: Send,
     {
+        let started = Instant::now();
         let mut sm = self.inner.write().await;
         let mut responses = Vec::new();
+        let mut events = Vec::new();
+        let attach_values = self.events.receiver_count() > 0;
 
         for entry in entries {
+            let first_event = events.len();
             // Update last applied log id
             sm.last_applied = Some(entry.log_id);
 
@@ -188,18 +1028,509 @@ impl RaftStateMachine<TypeConfig> for StateMachineStore {
                 sm.last_membership = StoredMembership::new(Some(entry.log_id), membership.clone());
             }
 
+            // Changes are timestamped with the leader's clock when it proposed the
+            // entry, never the local one, so replicas and log replays agree. Entries
+            // of leaders before wire version 7 carry no stamp and take the time of
+            // the previous change.
+            let committed_at = match &entry.payload {
+                openraft::EntryPayload::Normal(request) => request.stamp(),
+                _ => None,
+            }
+            .unwrap_or(sm.last_timestamp);
+
+            // A fenced write is applied as the request it wraps, unless its epoch is stale
+            if let openraft::EntryPayload::Normal(request) = &entry.payload {
+                if let AppRequest::Fenced { fence, request } = request.unstamped() {
+                    if let Some(rejection) = sm.check_fence(fence, request) {
+                        responses.push(rejection);
+                        continue;
+                    }
+                }
+            }
+
             // Apply the log entry to state machine
             let response = match entry.payload {
                 openraft::EntryPayload::Blank => AppResponse::PutOk,
-                openraft::EntryPayload::Normal(ref req) => match req {
+                openraft::EntryPayload::Normal(ref req) => match req.unfenced() {
                     AppRequest::Put { key, value } => {
                         sm.data.insert(key.clone(), value.clone());
+                        sm.expirations.remove(key);
+                        sm.cold.remove(key);
+                        let timestamp =
+                            sm.record_change(key, false, entry.log_id.index, committed_at);
+                        events.push(ChangeEvent {
+                            key: key.clone(),
+                            kind: ChangeKind::Put,
+                            timestamp,
+                            version: entry.log_id.index,
+                            value: None,
+                        });
+                        AppResponse::PutOk
+                    }
+                    AppRequest::PutWithTtl {
+                        key,
+                        value,
+                        expires_at,
+                    } => {
+                        sm.data.insert(key.clone(), value.clone());
+                        sm.expirations.insert(key.clone(), *expires_at);
+                        sm.cold.remove(key);
+                        let timestamp =
+                            sm.record_change(key, false, entry.log_id.index, committed_at);
+                        events.push(ChangeEvent {
+                            key: key.clone(),
+                            kind: ChangeKind::Put,
+                            timestamp,
+                            version: entry.log_id.index,
+                            value: None,
+                        });
                         AppResponse::PutOk
                     }
                     AppRequest::Delete { key } => {
                         sm.data.remove(key);
+                        sm.expirations.remove(key);
+                        sm.cold.remove(key);
+                        let timestamp =
+                            sm.record_change(key, true, entry.log_id.index, committed_at);
+                        events.push(ChangeEvent {
+                            key: key.clone(),
+                            kind: ChangeKind::Delete,
+                            timestamp,
+                            version: entry.log_id.index,
+                            value: None,
+                        });
+                        AppResponse::DeleteOk
+                    }
+                    AppRequest::Expire { key, expires_at } => {
+                        // Only expire if the key still carries the same deadline
+                        if sm.expirations.get(key) == Some(expires_at) {
+                            sm.data.remove(key);
+                            sm.expirations.remove(key);
+                            sm.cold.remove(key);
+                            let timestamp =
+                                sm.record_change(key, true, entry.log_id.index, committed_at);
+                            events.push(ChangeEvent {
+                                key: key.clone(),
+                                kind: ChangeKind::Expire,
+                                timestamp,
+                                version: entry.log_id.index,
+                                value: None,
+                            });
+                        }
                         AppResponse::DeleteOk
                     }
+                    AppRequest::JsonMergePatch { key, .. }
+                        if sm.cold.contains_key(key) && !sm.expired_at(key, committed_at) =>
+                    {
+                        AppResponse::Error {
+                            message: "Value is archived in cold storage; rewrite it instead"
+                                .to_string(),
+                        }
+                    }
+                    AppRequest::JsonMergePatch { key, patch } => {
+                        // Expiry is judged by the commit time rather than the local
+                        // clock, so replicas agree. An expired value counts as absent
+                        // and its TTL is dropped; a live value keeps its TTL.
+                        let current = sm.value_at(key, committed_at);
+                        match apply_merge_patch(current.as_deref(), patch) {
+                            Ok(value) => {
+                                if current.is_none() {
+                                    sm.expirations.remove(key);
+                                    sm.cold.remove(key);
+                                }
+                                sm.data.insert(key.clone(), value.clone());
+                                let timestamp =
+                                    sm.record_change(key, false, entry.log_id.index, committed_at);
+                                events.push(ChangeEvent {
+                                    key: key.clone(),
+                                    kind: ChangeKind::Put,
+                                    timestamp,
+                                    version: entry.log_id.index,
+                                    value: None,
+                                });
+                                AppResponse::PatchOk { value }
+                            }
+                            Err(message) => AppResponse::Error { message },
+                        }
+                    }
+                    AppRequest::Rename { from, .. }
+                        if sm.cold.contains_key(from) && !sm.expired_at(from, committed_at) =>
+                    {
+                        AppResponse::Error {
+                            message: "Value is archived in cold storage; rewrite it instead"
+                                .to_string(),
+                        }
+                    }
+                    AppRequest::Rename { from, to } => {
+                        // Copy + tombstone in one entry, so no reader or replica ever
+                        // sees both keys or neither. The TTL moves with the value.
+                        // Expiry is judged by the commit time: an expired `from`
+                        // counts as missing, and an expired value at `to` is replaced
+                        // like a live one.
+                        match sm.value_at(from, committed_at) {
+                            Some(value) if from != to => {
+                                sm.data.remove(from);
+                                let expires_at = sm.expirations.remove(from);
+                                sm.data.insert(to.clone(), value.clone());
+                                sm.cold.remove(to);
+                                match expires_at {
+                                    Some(expires_at) => {
+                                        sm.expirations.insert(to.clone(), expires_at)
+                                    }
+                                    None => sm.expirations.remove(to),
+                                };
+
+                                let timestamp =
+                                    sm.record_change(from, true, entry.log_id.index, committed_at);
+                                events.push(ChangeEvent {
+                                    key: from.clone(),
+                                    kind: ChangeKind::Delete,
+                                    timestamp,
+                                    version: entry.log_id.index,
+                                    value: None,
+                                });
+                                let timestamp =
+                                    sm.record_change(to, false, entry.log_id.index, committed_at);
+                                events.push(ChangeEvent {
+                                    key: to.clone(),
+                                    kind: ChangeKind::Put,
+                                    timestamp,
+                                    version: entry.log_id.index,
+                                    value: None,
+                                });
+                                AppResponse::RenameOk { value: Some(value) }
+                            }
+                            value => AppResponse::RenameOk { value },
+                        }
+                    }
+                    AppRequest::GetAndSet { key, .. }
+                    | AppRequest::GetAndDelete { key, .. }
+                    | AppRequest::CompareAndSwap { key, .. }
+                        if sm.cold.contains_key(key) =>
+                    {
+                        AppResponse::Error {
+                            message: "Value is archived in cold storage; read it first".to_string(),
+                        }
+                    }
+                    AppRequest::GetAndSet { key, value, now } => {
+                        let previous = sm.value_at(key, *now);
+                        sm.data.insert(key.clone(), value.clone());
+                        sm.expirations.remove(key);
+                        let timestamp =
+                            sm.record_change(key, false, entry.log_id.index, committed_at);
+                        events.push(ChangeEvent {
+                            key: key.clone(),
+                            kind: ChangeKind::Put,
+                            timestamp,
+                            version: entry.log_id.index,
+                            value: None,
+                        });
+                        AppResponse::Previous { value: previous }
+                    }
+                    AppRequest::GetAndDelete { key, now } => {
+                        let previous = sm.value_at(key, *now);
+                        sm.data.remove(key);
+                        sm.expirations.remove(key);
+                        let timestamp =
+                            sm.record_change(key, true, entry.log_id.index, committed_at);
+                        events.push(ChangeEvent {
+                            key: key.clone(),
+                            kind: ChangeKind::Delete,
+                            timestamp,
+                            version: entry.log_id.index,
+                            value: None,
+                        });
+                        AppResponse::Previous { value: previous }
+                    }
+                    AppRequest::TouchSession { key, now } => {
+                        // The idle timeout comes from the stored record, so every
+                        // replica computes the same deadline. A lagging proposer
+                        // clock never shortens it.
+                        let touched = sm.value_at(key, *now).and_then(|value| {
+                            let record: SessionRecord = serde_json::from_slice(&value).ok()?;
+                            let expires_at = now
+                                .saturating_add(record.idle_timeout_ms)
+                                .max(sm.expirations.get(key).copied().unwrap_or(0));
+                            Some((value, expires_at))
+                        });
+                        match touched {
+                            Some((value, expires_at)) => {
+                                sm.expirations.insert(key.clone(), expires_at);
+                                AppResponse::SessionTouched {
+                                    value: Some(value),
+                                    expires_at,
+                                }
+                            }
+                            None => AppResponse::SessionTouched {
+                                value: None,
+                                expires_at: 0,
+                            },
+                        }
+                    }
+                    AppRequest::Demote {
+                        key,
+                        segment_id,
+                        value_digest: digest,
+                    } => {
+                        // The value is unchanged for readers, so no change event
+                        let unchanged = sm
+                            .data
+                            .get(key)
+                            .is_some_and(|value| value_digest(value) == *digest);
+                        if unchanged {
+                            sm.data.remove(key);
+                            sm.cold.insert(key.clone(), *segment_id);
+                        }
+                        AppResponse::DemoteOk { demoted: unchanged }
+                    }
+                    AppRequest::AcquireLock {
+                        name,
+                        holder,
+                        now,
+                        expires_at,
+                    } => match sm.locks.get(name) {
+                        Some(current) if current.expires_at > *now && current.holder != *holder => {
+                            AppResponse::LockDenied {
+                                current: Some(current.clone()),
+                            }
+                        }
+                        current => {
+                            // A retry by the holder keeps its token; any new grant gets
+                            // this entry's index, which exceeds every earlier token
+                            let token = match current {
+                                Some(current) if current.expires_at > *now => current.token,
+                                _ => entry.log_id.index,
+                            };
+                            let lease = LockLease {
+                                name: name.clone(),
+                                holder: holder.clone(),
+                                token,
+                                expires_at: *expires_at,
+                            };
+                            sm.locks.insert(name.clone(), lease.clone());
+                            AppResponse::LockOk { lease }
+                        }
+                    },
+                    AppRequest::RenewLock {
+                        name,
+                        token,
+                        now,
+                        expires_at,
+                    } => match sm.locks.get_mut(name) {
+                        Some(lease) if lease.token == *token && lease.expires_at > *now => {
+                            lease.expires_at = *expires_at;
+                            AppResponse::LockOk {
+                                lease: lease.clone(),
+                            }
+                        }
+                        current => AppResponse::LockDenied {
+                            current: current.filter(|lease| lease.expires_at > *now).cloned(),
+                        },
+                    },
+                    AppRequest::ReleaseLock { name, token } => {
+                        let held = sm
+                            .locks
+                            .get(name)
+                            .is_some_and(|lease| lease.token == *token);
+                        if held {
+                            sm.locks.remove(name);
+                        }
+                        AppResponse::LockReleased { released: held }
+                    }
+                    AppRequest::Enqueue {
+                        queue,
+                        payload,
+                        capacity,
+                    } => {
+                        let items = sm.queues.entry(queue.clone()).or_default();
+                        if items.len() >= *capacity {
+                            let length = items.len();
+                            if length == 0 {
+                                sm.queues.remove(queue);
+                            }
+                            AppResponse::QueueFull { length }
+                        } else {
+                            let id = entry.log_id.index;
+                            items.insert(
+                                id,
+                                QueueItem {
+                                    queue: queue.clone(),
+                                    id,
+                                    receipt: 0,
+                                    payload: payload.clone(),
+                                    attempts: 0,
+                                    visible_at: 0,
+                                },
+                            );
+                            AppResponse::Enqueued { id }
+                        }
+                    }
+                    AppRequest::Dequeue {
+                        queue,
+                        now,
+                        visible_until,
+                    } => {
+                        // Items whose lease ran out are handed out again in ID order
+                        let item = sm
+                            .queues
+                            .get_mut(queue)
+                            .and_then(|items| {
+                                items.values_mut().find(|item| item.visible_at <= *now)
+                            })
+                            .map(|item| {
+                                item.receipt = entry.log_id.index;
+                                item.attempts = item.attempts.saturating_add(1);
+                                item.visible_at = *visible_until;
+                                item.clone()
+                            });
+                        AppResponse::Dequeued { item }
+                    }
+                    AppRequest::Ack { queue, id, receipt } => {
+                        let acked = match sm.queues.get_mut(queue) {
+                            Some(items)
+                                if items.get(id).is_some_and(|item| {
+                                    item.attempts > 0 && item.receipt == *receipt
+                                }) =>
+                            {
+                                items.remove(id);
+                                if items.is_empty() {
+                                    sm.queues.remove(queue);
+                                }
+                                true
+                            }
+                            _ => false,
+                        };
+                        AppResponse::Acked { acked }
+                    }
+                    AppRequest::CreateTag {
+                        name,
+                        manifest_version,
+                        now,
+                    } => match sm.tags.get(name) {
+                        Some(existing) => AppResponse::TagExists {
+                            tag: existing.tag.clone(),
+                        },
+                        None => {
+                            let state =
+                                sm.capture_tag(name, entry.log_id.index, *manifest_version, *now);
+                            let tag = state.tag.clone();
+                            sm.tags.insert(name.clone(), Arc::new(state));
+                            AppResponse::TagCreated { tag }
+                        }
+                    },
+                    AppRequest::DeleteTag { name } => AppResponse::TagDeleted {
+                        deleted: sm.tags.remove(name).is_some(),
+                    },
+                    AppRequest::AdvanceEpoch { namespace, epoch } => {
+                        let current = sm.epoch(namespace);
+                        match epoch {
+                            Some(epoch) if *epoch < current => AppResponse::StaleEpoch { current },
+                            _ => {
+                                let epoch = epoch.unwrap_or(current + 1);
+                                sm.epochs.insert(namespace.clone(), epoch);
+                                AppResponse::EpochOk { epoch }
+                            }
+                        }
+                    }
+                    AppRequest::Fenced { .. } => AppResponse::Error {
+                        message: "Fenced requests cannot be nested".to_string(),
+                    },
+                    AppRequest::Stamped { .. } => AppResponse::Error {
+                        message: "Stamped requests cannot be nested".to_string(),
+                    },
+                    AppRequest::Transaction { reads, writes } => {
+                        // Every key read must still be at the version it was read at
+                        let stale = reads
+                            .iter()
+                            .find(|read| sm.key_version(&read.key) != read.version);
+                        match stale {
+                            Some(read) => AppResponse::TxnConflict {
+                                key: read.key.clone(),
+                            },
+                            None => {
+                                sm.apply_writes(
+                                    writes,
+                                    entry.log_id.index,
+                                    committed_at,
+                                    &mut events,
+                                );
+                                AppResponse::TxnOk
+                            }
+                        }
+                    }
+                    AppRequest::CompareAndSwap {
+                        key,
+                        expected,
+                        new,
+                        now,
+                    } => {
+                        let current = sm.value_at(key, *now);
+                        let swapped = current == *expected;
+                        if swapped {
+                            let deleted = match new {
+                                Some(value) => {
+                                    sm.data.insert(key.clone(), value.clone());
+                                    false
+                                }
+                                None => {
+                                    sm.data.remove(key);
+                                    true
+                                }
+                            };
+                            sm.expirations.remove(key);
+                            let timestamp =
+                                sm.record_change(key, deleted, entry.log_id.index, committed_at);
+                            events.push(ChangeEvent {
+                                key: key.clone(),
+                                kind: if deleted {
+                                    ChangeKind::Delete
+                                } else {
+                                    ChangeKind::Put
+                                },
+                                timestamp,
+                                version: entry.log_id.index,
+                                value: None,
+                            });
+                        }
+                        AppResponse::CasOk { swapped, current }
+                    }
+                    AppRequest::RecordUsage { usage } => {
+                        for delta in usage {
+                            sm.quota_usage
+                                .entry(delta.key_id.clone())
+                                .or_insert_with(|| KeyUsage::new(delta.key_id.clone(), delta.day))
+                                .apply(delta);
+                        }
+                        AppResponse::UsageRecorded
+                    }
+                    AppRequest::AnchorSegment {
+                        segment_id,
+                        merkle_root,
+                        key_count,
+                        now,
+                    } => match sm.anchors.get(segment_id) {
+                        Some(existing) => AppResponse::AnchorExists {
+                            anchor: existing.clone(),
+                        },
+                        None => {
+                            let anchor = SegmentAnchor {
+                                segment_id: *segment_id,
+                                merkle_root: hex::encode(merkle_root),
+                                key_count: *key_count,
+                                raft_index: entry.log_id.index,
+                                anchored_at: *now,
+                            };
+                            sm.anchors.insert(*segment_id, anchor.clone());
+                            AppResponse::SegmentAnchored { anchor }
+                        }
+                    },
+                    AppRequest::Batch { writes } => {
+                        sm.apply_writes(writes, entry.log_id.index, committed_at, &mut events);
+                        AppResponse::BatchOk
+                    }
+                    AppRequest::PruneTombstones { before } => AppResponse::TombstonesPruned {
+                        pruned: sm.prune_tombstones(*before),
+                    },
                     AppRequest::Get { .. } => {
                         // Get requests should not go through Raft log
                         // They should use client_read instead
@@ -213,14 +1544,51 @@ impl RaftStateMachine<TypeConfig> for StateMachineStore {
             };
 
             responses.push(response);
+
+            // An entry changes each key at most once, so what the key holds now is
+            // what this entry put
+            if attach_values {
+                for event in &mut events[first_event..] {
+                    if event.kind == ChangeKind::Put {
+                        event.value = sm.data.get(&event.key).cloned().map(Arc::new);
+                    }
+                }
+            }
+        }
+        drop(sm);
+        STATE_MACHINE_APPLY_LATENCY.observe(started.elapsed().as_secs_f64());
+        APPLY_BATCH_SIZE.observe(responses.len() as f64);
+
+        // Publish after releasing the lock; having no subscribers is fine
+        for event in events {
+            let _ = self.events.send(event);
         }
 
         Ok(responses)
     }
 
     async fn get_snapshot_builder(&mut self) -> Self::SnapshotBuilder {
+        let build = self.snapshot_builds.fetch_add(1, Ordering::Relaxed);
         let sm = self.inner.read().await;
-        SnapshotBuilder::new(sm.last_applied, sm.last_membership.clone(), sm.data.clone())
+        SnapshotBuilder::new(
+            sm.last_applied,
+            sm.last_membership.clone(),
+            sm.data.clone(),
+            sm.changes.values().cloned().collect(),
+            sm.expirations.clone(),
+            sm.cold.clone(),
+            sm.locks.clone(),
+            sm.queues.clone(),
+            sm.tags
+                .iter()
+                .map(|(name, state)| (name.clone(), TaggedState::clone(state)))
+                .collect(),
+            sm.epochs.clone(),
+            sm.quota_usage.clone(),
+            sm.anchors.clone(),
+        )
+        .keep_in(Arc::clone(&self.current_snapshot), build)
+        .persist_to(self.snapshot_store.clone())
     }
 
     async fn begin_receiving_snapshot(
@@ -235,14 +1603,22 @@ impl RaftStateMachine<TypeConfig> for StateMachineStore {
         snapshot: Box<Cursor<Vec<u8>>>,
     ) -> Result<(), StorageError<NodeId>> {
         let data = snapshot.into_inner();
-        let snapshot_data: SnapshotData = bincode::deserialize(&data).map_err(|e| {
+        let mut snapshot_data: SnapshotData = bincode::deserialize(&data).map_err(|e| {
             StorageError::from(StorageIOError::read_snapshot(Some(meta.signature()), &e))
         })?;
 
-        let mut sm = self.inner.write().await;
-        sm.last_applied = snapshot_data.last_applied;
-        sm.last_membership = snapshot_data.last_membership;
-        sm.data = snapshot_data.data;
+        if let Some(store) = &self.snapshot_store {
+            store.save(meta, &mut snapshot_data).await.map_err(|e| {
+                StorageError::from(StorageIOError::write_snapshot(Some(meta.signature()), &e))
+            })?;
+        }
+        self.restore(snapshot_data).await;
+
+        // Keep it to pass on, should this node lead before building its own
+        *self.current_snapshot.write().await = Some(StoredSnapshot {
+            meta: meta.clone(),
+            data,
+        });
 
         Ok(())
     }
@@ -250,15 +1626,21 @@ impl RaftStateMachine<TypeConfig> for StateMachineStore {
     async fn get_current_snapshot(
         &mut self,
     ) -> Result<Option<openraft::Snapshot<TypeConfig>>, StorageError<NodeId>> {
-        // For now, we don't keep a persistent snapshot
-        // This will be built on-demand by get_snapshot_builder
-        Ok(None)
+        // Snapshots are kept in memory, and on disk given a snapshot store; without
+        // one, the next snapshot after a restart is built by the snapshot policy or
+        // by `ConsensusNode::compact_log`
+        let current = self.current_snapshot.read().await;
+        Ok(current.as_ref().map(|snapshot| openraft::Snapshot {
+            meta: snapshot.meta.clone(),
+            snapshot: Box::new(Cursor::new(snapshot.data.clone())),
+        }))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::{TxnRead, TxnWrite, UsageDelta};
     use openraft::{EntryPayload, LeaderId};
 
     #[tokio::test]
@@ -351,11 +1733,31 @@ mod tests {
         };
         sm.apply(vec![entry]).await.unwrap();
 
+        assert!(sm.get_current_snapshot().await.unwrap().is_none());
+
         // Build snapshot
         let mut builder = sm.get_snapshot_builder().await;
         let snapshot = builder.build_snapshot().await.unwrap();
 
         assert_eq!(snapshot.meta.last_log_id, Some(log_id));
+
+        // The built snapshot is kept to be sent to peers
+        let current = sm.get_current_snapshot().await.unwrap().unwrap();
+        assert_eq!(current.meta, snapshot.meta);
+        assert_eq!(
+            current.snapshot.into_inner(),
+            snapshot.snapshot.into_inner()
+        );
+
+        // Another build at the same index gets its own ID
+        let rebuilt = sm
+            .get_snapshot_builder()
+            .await
+            .build_snapshot()
+            .await
+            .unwrap();
+        assert_ne!(rebuilt.meta.snapshot_id, current.meta.snapshot_id);
+        assert_eq!(sm.current_snapshot_meta().await, Some(rebuilt.meta));
     }
 
     #[tokio::test]
@@ -372,10 +1774,24 @@ mod tests {
             last_applied: Some(log_id),
             last_membership: StoredMembership::default(),
             data,
+            changes: vec![KeyChange {
+                key: b"key1".to_vec(),
+                timestamp: 1_000,
+                deleted: false,
+                version: 3,
+            }],
+            expirations: HashMap::new(),
+            cold: HashMap::new(),
+            locks: HashMap::new(),
+            queues: HashMap::new(),
+            tags: BTreeMap::new(),
+            epochs: BTreeMap::from([("jobs/".to_string(), 4)]),
+            quota_usage: BTreeMap::new(),
+            anchors: BTreeMap::new(),
         };
 
         let bytes = bincode::serialize(&snapshot_data).unwrap();
-        let cursor = Box::new(Cursor::new(bytes));
+        let cursor = Box::new(Cursor::new(bytes.clone()));
 
         let meta = SnapshotMeta {
             last_log_id: Some(log_id),
@@ -391,7 +1807,1427 @@ mod tests {
         let value2 = sm.get(&b"key2".to_vec()).await;
         assert_eq!(value2, Some(b"value2".to_vec()));
 
+        let changes = sm.changes_between(0, 2_000).await;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].key, b"key1".to_vec());
+
         let (last_applied, _) = sm.applied_state().await.unwrap();
         assert_eq!(last_applied, Some(log_id));
+        assert_eq!(sm.epoch("jobs/").await, 4);
+
+        // The installed snapshot can be passed on
+        let current = sm.get_current_snapshot().await.unwrap().unwrap();
+        assert_eq!(current.meta.snapshot_id, "test-snapshot");
+        assert_eq!(current.snapshot.into_inner(), bytes);
+    }
+
+    #[tokio::test]
+    async fn test_time_index_tracks_changes() {
+        let mut sm = StateMachineStore::new();
+
+        let entries = vec![
+            openraft::Entry {
+                log_id: LogId::new(LeaderId::new(1, 1), 1),
+                payload: EntryPayload::Normal(AppRequest::Put {
+                    key: b"key1".to_vec(),
+                    value: b"value1".to_vec(),
+                }),
+            },
+            openraft::Entry {
+                log_id: LogId::new(LeaderId::new(1, 1), 2),
+                payload: EntryPayload::Normal(AppRequest::Put {
+                    key: b"key2".to_vec(),
+                    value: b"value2".to_vec(),
+                }),
+            },
+            openraft::Entry {
+                log_id: LogId::new(LeaderId::new(1, 1), 3),
+                payload: EntryPayload::Normal(AppRequest::Delete {
+                    key: b"key1".to_vec(),
+                }),
+            },
+        ];
+        sm.apply(entries).await.unwrap();
+
+        let changes = sm.changes_between(0, u64::MAX).await;
+        assert_eq!(changes.len(), 2);
+        let key1 = changes.iter().find(|c| c.key == b"key1".to_vec()).unwrap();
+        let key2 = changes.iter().find(|c| c.key == b"key2".to_vec()).unwrap();
+        assert!(key1.deleted);
+        assert!(!key2.deleted);
+        assert!(key2.timestamp <= key1.timestamp);
+        assert_eq!(key1.version, 3);
+        assert_eq!(sm.key_version(&b"key2".to_vec()).await, Some(2));
+        assert_eq!(sm.key_version(&b"key3".to_vec()).await, None);
+
+        // Window after the last change is empty
+        let after = key1.timestamp + 1;
+        assert!(sm.changes_between(after, u64::MAX).await.is_empty());
+
+        // Inverted window is empty
+        assert!(sm.changes_between(10, 5).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_changes_are_paged_and_tombstones_pruned() {
+        let entry = |index, now, request| openraft::Entry {
+            log_id: LogId::new(LeaderId::new(1, 1), index),
+            payload: EntryPayload::Normal(AppRequest::stamped(now, request)),
+        };
+        let put = |key: &[u8]| AppRequest::Put {
+            key: key.to_vec(),
+            value: b"v".to_vec(),
+        };
+        let delete = |key: &[u8]| AppRequest::Delete { key: key.to_vec() };
+        let mut sm = StateMachineStore::new();
+        sm.apply(vec![
+            entry(1, 1_000, put(b"a")),
+            entry(2, 1_000, delete(b"b")),
+            entry(3, 2_000, delete(b"c")),
+            entry(4, 3_000, put(b"d")),
+            entry(5, 4_000, delete(b"e")),
+        ])
+        .await
+        .unwrap();
+
+        // Pages follow (timestamp, key) order from the cursor on
+        let keys = |changes: Vec<KeyChange>| -> Vec<Key> {
+            changes.into_iter().map(|change| change.key).collect()
+        };
+        let first = sm.changes_page(0, u64::MAX, None, 2).await;
+        assert_eq!(keys(first.clone()), vec![b"a".to_vec(), b"b".to_vec()]);
+        let cursor = (first[1].timestamp, first[1].key.clone());
+        let second = sm.changes_page(0, u64::MAX, Some(&cursor), 2).await;
+        assert_eq!(keys(second), vec![b"c".to_vec(), b"d".to_vec()]);
+        assert!(sm
+            .changes_page(0, 2_500, Some(&(3_000, Vec::new())), 2)
+            .await
+            .is_empty());
+        assert_eq!(
+            keys(sm.changes_page(2_000, u64::MAX, Some(&cursor), 10).await).len(),
+            3
+        );
+
+        // Only tombstones older than the cutoff are pruned, never the latest change
+        assert_eq!(sm.tombstones_before(2_001).await, 2);
+        let responses = sm
+            .apply(vec![
+                openraft::Entry {
+                    log_id: LogId::new(LeaderId::new(1, 1), 6),
+                    payload: EntryPayload::Normal(AppRequest::PruneTombstones { before: 2_001 }),
+                },
+                openraft::Entry {
+                    log_id: LogId::new(LeaderId::new(1, 1), 7),
+                    payload: EntryPayload::Normal(AppRequest::PruneTombstones { before: 5_000 }),
+                },
+            ])
+            .await
+            .unwrap();
+        assert!(matches!(
+            responses[0],
+            AppResponse::TombstonesPruned { pruned: 2 }
+        ));
+        assert!(matches!(
+            responses[1],
+            AppResponse::TombstonesPruned { pruned: 0 }
+        ));
+        assert_eq!(
+            keys(sm.changes_between(0, u64::MAX).await),
+            vec![b"a".to_vec(), b"d".to_vec(), b"e".to_vec()]
+        );
+        assert_eq!(sm.key_version(&b"b".to_vec()).await, None);
+        assert_eq!(sm.key_version(&b"e".to_vec()).await, Some(5));
+    }
+
+    #[tokio::test]
+    async fn test_change_timestamps_come_from_the_leader() {
+        let entry = |index, request| openraft::Entry {
+            log_id: LogId::new(LeaderId::new(1, 1), index),
+            payload: EntryPayload::Normal(request),
+        };
+        let put = |key: &[u8]| AppRequest::Put {
+            key: key.to_vec(),
+            value: b"v".to_vec(),
+        };
+        let entries = vec![
+            entry(1, AppRequest::stamped(1_000, put(b"a"))),
+            // A new leader whose clock is behind does not move timestamps back
+            entry(2, AppRequest::stamped(900, put(b"b"))),
+            // An entry without a stamp takes the time of the previous change
+            entry(3, put(b"c")),
+            entry(4, AppRequest::stamped(2_000, put(b"d"))),
+        ];
+
+        // Replaying the same log, e.g. after a restart, records the same times
+        let mut first = StateMachineStore::new();
+        first.apply(entries.clone()).await.unwrap();
+        let mut replay = StateMachineStore::new();
+        replay.apply(entries).await.unwrap();
+
+        let changes = first.changes_between(0, u64::MAX).await;
+        let timestamps: Vec<(Key, u64)> = changes
+            .iter()
+            .map(|change| (change.key.clone(), change.timestamp))
+            .collect();
+        assert_eq!(
+            timestamps,
+            vec![
+                (b"a".to_vec(), 1_000),
+                (b"b".to_vec(), 1_000),
+                (b"c".to_vec(), 1_000),
+                (b"d".to_vec(), 2_000),
+            ]
+        );
+        assert_eq!(replay.changes_between(0, u64::MAX).await, changes);
+        assert_eq!(first.changes_between(1_001, u64::MAX).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_expire_emits_event() {
+        let mut sm = StateMachineStore::new();
+        let mut events = sm.subscribe();
+
+        let entries = vec![
+            openraft::Entry {
+                log_id: LogId::new(LeaderId::new(1, 1), 1),
+                payload: EntryPayload::Normal(AppRequest::PutWithTtl {
+                    key: b"key1".to_vec(),
+                    value: b"value1".to_vec(),
+                    expires_at: 1,
+                }),
+            },
+            openraft::Entry {
+                log_id: LogId::new(LeaderId::new(1, 1), 2),
+                payload: EntryPayload::Normal(AppRequest::Expire {
+                    key: b"key1".to_vec(),
+                    expires_at: 1,
+                }),
+            },
+        ];
+        sm.apply(entries).await.unwrap();
+
+        // The put carries its value although a later entry of the batch removed it
+        let event = events.recv().await.unwrap();
+        assert_eq!(event.kind, ChangeKind::Put);
+        assert_eq!(event.value.as_deref(), Some(&b"value1".to_vec()));
+        let event = events.recv().await.unwrap();
+        assert_eq!(event.kind, ChangeKind::Expire);
+        assert_eq!(event.key, b"key1".to_vec());
+        assert_eq!(event.value, None);
+        assert!(sm.expired_keys(u64::MAX).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_expire_ignored_after_rewrite() {
+        let mut sm = StateMachineStore::new();
+
+        let entries = vec![
+            openraft::Entry {
+                log_id: LogId::new(LeaderId::new(1, 1), 1),
+                payload: EntryPayload::Normal(AppRequest::PutWithTtl {
+                    key: b"key1".to_vec(),
+                    value: b"value1".to_vec(),
+                    expires_at: 1,
+                }),
+            },
+            openraft::Entry {
+                log_id: LogId::new(LeaderId::new(1, 1), 2),
+                payload: EntryPayload::Normal(AppRequest::Put {
+                    key: b"key1".to_vec(),
+                    value: b"value2".to_vec(),
+                }),
+            },
+            openraft::Entry {
+                log_id: LogId::new(LeaderId::new(1, 1), 3),
+                payload: EntryPayload::Normal(AppRequest::Expire {
+                    key: b"key1".to_vec(),
+                    expires_at: 1,
+                }),
+            },
+        ];
+        sm.apply(entries).await.unwrap();
+
+        assert_eq!(sm.get(&b"key1".to_vec()).await, Some(b"value2".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_expired_key_hidden_before_sweep() {
+        let mut sm = StateMachineStore::new();
+
+        let entry = openraft::Entry {
+            log_id: LogId::new(LeaderId::new(1, 1), 1),
+            payload: EntryPayload::Normal(AppRequest::PutWithTtl {
+                key: b"key1".to_vec(),
+                value: b"value1".to_vec(),
+                expires_at: 1,
+            }),
+        };
+        sm.apply(vec![entry]).await.unwrap();
+
+        assert_eq!(sm.get(&b"key1".to_vec()).await, None);
+        assert_eq!(sm.expired_keys(u64::MAX).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_keys_with_prefix() {
+        let mut sm = StateMachineStore::new();
+
+        let put = |index: u64, key: &[u8]| openraft::Entry {
+            log_id: LogId::new(LeaderId::new(1, 1), index),
+            payload: EntryPayload::Normal(AppRequest::Put {
+                key: key.to_vec(),
+                value: b"v".to_vec(),
+            }),
+        };
+        let entries = vec![
+            put(1, b"jobs/c"),
+            put(2, b"jobs/a"),
+            put(3, b"users/a"),
+            put(4, b"jobs/b"),
+            openraft::Entry {
+                log_id: LogId::new(LeaderId::new(1, 1), 5),
+                payload: EntryPayload::Normal(AppRequest::PutWithTtl {
+                    key: b"jobs/expired".to_vec(),
+                    value: b"v".to_vec(),
+                    expires_at: 1,
+                }),
+            },
+        ];
+        sm.apply(entries).await.unwrap();
+
+        let keys = sm.keys_with_prefix(b"jobs/", None, 10).await;
+        assert_eq!(
+            keys,
+            vec![b"jobs/a".to_vec(), b"jobs/b".to_vec(), b"jobs/c".to_vec()]
+        );
+        let keys = sm.keys_with_prefix(b"jobs/", Some(b"jobs/a"), 1).await;
+        assert_eq!(keys, vec![b"jobs/b".to_vec()]);
+        assert!(sm
+            .keys_with_prefix(b"jobs/", Some(b"jobs/c"), 10)
+            .await
+            .is_empty());
+
+        // Paging through every key
+        let keys = |page: Vec<(Key, StoredValue)>| -> Vec<Key> {
+            page.into_iter().map(|(key, _)| key).collect()
+        };
+        assert_eq!(
+            keys(sm.entries_after(None, 2).await),
+            vec![b"jobs/a".to_vec(), b"jobs/b".to_vec()]
+        );
+        assert_eq!(
+            keys(sm.entries_after(Some(b"jobs/b"), 2).await),
+            vec![b"jobs/c".to_vec(), b"users/a".to_vec()]
+        );
+        assert!(sm.entries_after(Some(b"users/a"), 2).await.is_empty());
+        assert!(sm.entries_after(None, 0).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_apply_json_merge_patch() {
+        let mut sm = StateMachineStore::new();
+
+        let entries = vec![
+            openraft::Entry {
+                log_id: LogId::new(LeaderId::new(1, 1), 1),
+                payload: EntryPayload::Normal(AppRequest::Put {
+                    key: b"doc".to_vec(),
+                    value: br#"{"name":"a","tags":["x"]}"#.to_vec(),
+                }),
+            },
+            openraft::Entry {
+                log_id: LogId::new(LeaderId::new(1, 1), 2),
+                payload: EntryPayload::Normal(AppRequest::JsonMergePatch {
+                    key: b"doc".to_vec(),
+                    patch: br#"{"tags":null,"n":1}"#.to_vec(),
+                }),
+            },
+            openraft::Entry {
+                log_id: LogId::new(LeaderId::new(1, 1), 3),
+                payload: EntryPayload::Normal(AppRequest::Put {
+                    key: b"raw".to_vec(),
+                    value: b"not json".to_vec(),
+                }),
+            },
+            openraft::Entry {
+                log_id: LogId::new(LeaderId::new(1, 1), 4),
+                payload: EntryPayload::Normal(AppRequest::JsonMergePatch {
+                    key: b"raw".to_vec(),
+                    patch: br#"{"a":1}"#.to_vec(),
+                }),
+            },
+        ];
+        let responses = sm.apply(entries).await.unwrap();
+
+        let doc = sm.get(&b"doc".to_vec()).await.unwrap();
+        let doc: serde_json::Value = serde_json::from_slice(&doc).unwrap();
+        assert_eq!(doc, serde_json::json!({"name": "a", "n": 1}));
+        match &responses[1] {
+            AppResponse::PatchOk { value } => {
+                assert_eq!(
+                    serde_json::from_slice::<serde_json::Value>(value).unwrap(),
+                    doc
+                )
+            }
+            other => panic!("Expected PatchOk, got {:?}", other),
+        }
+
+        // Non-JSON values are left untouched
+        assert!(matches!(responses[3], AppResponse::Error { .. }));
+        assert_eq!(sm.get(&b"raw".to_vec()).await, Some(b"not json".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_json_merge_patch_of_expired_value() {
+        let mut sm = StateMachineStore::new();
+
+        let entries = vec![
+            openraft::Entry {
+                log_id: LogId::new(LeaderId::new(1, 1), 1),
+                payload: EntryPayload::Normal(AppRequest::PutWithTtl {
+                    key: b"doc".to_vec(),
+                    value: br#"{"name":"a"}"#.to_vec(),
+                    expires_at: 1_000,
+                }),
+            },
+            openraft::Entry {
+                log_id: LogId::new(LeaderId::new(1, 1), 2),
+                payload: EntryPayload::Normal(AppRequest::stamped(
+                    2_000,
+                    AppRequest::JsonMergePatch {
+                        key: b"doc".to_vec(),
+                        patch: br#"{"n":1}"#.to_vec(),
+                    },
+                )),
+            },
+        ];
+        let responses = sm.apply(entries).await.unwrap();
+
+        // The expired document counts as absent, and its TTL no longer applies
+        let expected = serde_json::json!({"n": 1});
+        match &responses[1] {
+            AppResponse::PatchOk { value } => assert_eq!(
+                serde_json::from_slice::<serde_json::Value>(value).unwrap(),
+                expected
+            ),
+            other => panic!("Expected PatchOk, got {:?}", other),
+        }
+        let doc = sm.get(&b"doc".to_vec()).await.unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&doc).unwrap(),
+            expected
+        );
+        assert!(sm.expired_keys(u64::MAX).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_apply_rename() {
+        let mut sm = StateMachineStore::new();
+        let mut events = sm.subscribe();
+
+        let entries = vec![
+            openraft::Entry {
+                log_id: LogId::new(LeaderId::new(1, 1), 1),
+                payload: EntryPayload::Normal(AppRequest::PutWithTtl {
+                    key: b"old".to_vec(),
+                    value: b"value".to_vec(),
+                    expires_at: u64::MAX,
+                }),
+            },
+            openraft::Entry {
+                log_id: LogId::new(LeaderId::new(1, 1), 2),
+                payload: EntryPayload::Normal(AppRequest::Put {
+                    key: b"new".to_vec(),
+                    value: b"replaced".to_vec(),
+                }),
+            },
+            openraft::Entry {
+                log_id: LogId::new(LeaderId::new(1, 1), 3),
+                payload: EntryPayload::Normal(AppRequest::Rename {
+                    from: b"old".to_vec(),
+                    to: b"new".to_vec(),
+                }),
+            },
+            openraft::Entry {
+                log_id: LogId::new(LeaderId::new(1, 1), 4),
+                payload: EntryPayload::Normal(AppRequest::Rename {
+                    from: b"missing".to_vec(),
+                    to: b"other".to_vec(),
+                }),
+            },
+        ];
+        let responses = sm.apply(entries).await.unwrap();
+
+        assert!(matches!(
+            &responses[2],
+            AppResponse::RenameOk { value: Some(v) } if v == b"value"
+        ));
+        assert!(matches!(
+            responses[3],
+            AppResponse::RenameOk { value: None }
+        ));
+        assert_eq!(sm.get(&b"old".to_vec()).await, None);
+        assert_eq!(sm.get(&b"new".to_vec()).await, Some(b"value".to_vec()));
+        assert_eq!(sm.get(&b"other".to_vec()).await, None);
+
+        // The TTL moved with the value
+        let expired = sm.expired_keys(u64::MAX).await;
+        assert_eq!(expired, vec![(b"new".to_vec(), u64::MAX)]);
+
+        // Both keys appear in the change history
+        let kinds: Vec<_> = (0..4)
+            .map(|_| events.try_recv().unwrap())
+            .map(|e| (e.key, e.kind))
+            .collect();
+        assert_eq!(kinds[2], (b"old".to_vec(), ChangeKind::Delete));
+        assert_eq!(kinds[3], (b"new".to_vec(), ChangeKind::Put));
+        let changes = sm.changes_between(0, u64::MAX).await;
+        assert!(changes
+            .iter()
+            .any(|c| c.key == b"old".to_vec() && c.deleted));
+    }
+
+    #[tokio::test]
+    async fn test_rename_of_expired_keys() {
+        let mut sm = StateMachineStore::new();
+        let entry = |index, request| openraft::Entry {
+            log_id: LogId::new(LeaderId::new(1, 1), index),
+            payload: EntryPayload::Normal(request),
+        };
+        let put_with_ttl = |key: &[u8]| AppRequest::PutWithTtl {
+            key: key.to_vec(),
+            value: b"stale".to_vec(),
+            expires_at: 1_000,
+        };
+        let rename = |from: &[u8], to: &[u8]| {
+            AppRequest::stamped(
+                2_000,
+                AppRequest::Rename {
+                    from: from.to_vec(),
+                    to: to.to_vec(),
+                },
+            )
+        };
+
+        let responses = sm
+            .apply(vec![
+                entry(1, put_with_ttl(b"gone")),
+                entry(2, put_with_ttl(b"dest")),
+                entry(
+                    3,
+                    AppRequest::Put {
+                        key: b"live".to_vec(),
+                        value: b"value".to_vec(),
+                    },
+                ),
+                entry(4, rename(b"gone", b"other")),
+                entry(5, rename(b"live", b"dest")),
+            ])
+            .await
+            .unwrap();
+
+        // An expired source counts as missing
+        assert!(matches!(
+            responses[3],
+            AppResponse::RenameOk { value: None }
+        ));
+        assert_eq!(sm.get(&b"other".to_vec()).await, None);
+
+        // An expired destination is replaced, and its TTL with it
+        assert!(matches!(
+            &responses[4],
+            AppResponse::RenameOk { value: Some(v) } if v == b"value"
+        ));
+        assert_eq!(sm.get(&b"dest".to_vec()).await, Some(b"value".to_vec()));
+        assert_eq!(
+            sm.expired_keys(u64::MAX).await,
+            vec![(b"gone".to_vec(), 1_000)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apply_demote() {
+        let mut sm = StateMachineStore::new();
+        let entry = |index, request| openraft::Entry {
+            log_id: LogId::new(LeaderId::new(1, 1), index),
+            payload: EntryPayload::Normal(request),
+        };
+
+        let entries = vec![
+            entry(
+                1,
+                AppRequest::Put {
+                    key: b"doc".to_vec(),
+                    value: b"{}".to_vec(),
+                },
+            ),
+            // Stale demotion: the value no longer matches
+            entry(2, AppRequest::demote(b"doc".to_vec(), 3, b"older")),
+            entry(3, AppRequest::demote(b"doc".to_vec(), 4, b"{}")),
+            entry(
+                4,
+                AppRequest::JsonMergePatch {
+                    key: b"doc".to_vec(),
+                    patch: br#"{"a":1}"#.to_vec(),
+                },
+            ),
+        ];
+        let responses = sm.apply(entries).await.unwrap();
+
+        assert!(matches!(
+            responses[1],
+            AppResponse::DemoteOk { demoted: false }
+        ));
+        assert!(matches!(
+            responses[2],
+            AppResponse::DemoteOk { demoted: true }
+        ));
+        // Patching needs the value, which is no longer in memory
+        assert!(matches!(responses[3], AppResponse::Error { .. }));
+        assert_eq!(sm.get(&b"doc".to_vec()).await, None);
+        assert_eq!(
+            sm.lookup(&b"doc".to_vec()).await,
+            Some(StoredValue::Cold(4))
+        );
+        assert_eq!(sm.key_count().await, 1);
+
+        // Demoted keys survive snapshots
+        let mut builder = sm.get_snapshot_builder().await;
+        let snapshot = builder.build_snapshot().await.unwrap();
+        let mut restored = StateMachineStore::new();
+        restored
+            .install_snapshot(&snapshot.meta, snapshot.snapshot)
+            .await
+            .unwrap();
+        assert_eq!(
+            restored.lookup(&b"doc".to_vec()).await,
+            Some(StoredValue::Cold(4))
+        );
+
+        // Rewriting the key brings it back to memory
+        sm.apply(vec![entry(
+            5,
+            AppRequest::Put {
+                key: b"doc".to_vec(),
+                value: b"new".to_vec(),
+            },
+        )])
+        .await
+        .unwrap();
+        assert_eq!(
+            sm.lookup(&b"doc".to_vec()).await,
+            Some(StoredValue::Hot(b"new".to_vec()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apply_get_and_set_and_delete() {
+        let mut sm = StateMachineStore::new();
+        let entry = |index, request| openraft::Entry {
+            log_id: LogId::new(LeaderId::new(1, 1), index),
+            payload: EntryPayload::Normal(request),
+        };
+        let get_and_set = |value: &[u8], now| AppRequest::GetAndSet {
+            key: b"job".to_vec(),
+            value: value.to_vec(),
+            now,
+        };
+        let previous = |response: &AppResponse| match response {
+            AppResponse::Previous { value } => value.clone(),
+            other => panic!("Expected Previous, got {:?}", other),
+        };
+
+        let responses = sm
+            .apply(vec![
+                entry(1, get_and_set(b"first", 100)),
+                entry(2, get_and_set(b"second", 100)),
+                entry(
+                    3,
+                    AppRequest::GetAndDelete {
+                        key: b"job".to_vec(),
+                        now: 100,
+                    },
+                ),
+                entry(
+                    4,
+                    AppRequest::GetAndDelete {
+                        key: b"job".to_vec(),
+                        now: 100,
+                    },
+                ),
+                entry(
+                    5,
+                    AppRequest::PutWithTtl {
+                        key: b"job".to_vec(),
+                        value: b"expiring".to_vec(),
+                        expires_at: 200,
+                    },
+                ),
+                // The TTL elapsed by the proposer's clock
+                entry(6, get_and_set(b"fresh", 250)),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(previous(&responses[0]), None);
+        assert_eq!(previous(&responses[1]), Some(b"first".to_vec()));
+        assert_eq!(previous(&responses[2]), Some(b"second".to_vec()));
+        assert_eq!(previous(&responses[3]), None);
+        assert_eq!(previous(&responses[5]), None);
+
+        // The set cleared the TTL
+        assert_eq!(sm.get(&b"job".to_vec()).await, Some(b"fresh".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_apply_touch_session() {
+        let mut sm = StateMachineStore::new();
+        let entry = |index, request| openraft::Entry {
+            log_id: LogId::new(LeaderId::new(1, 1), index),
+            payload: EntryPayload::Normal(request),
+        };
+        let touch = |key: &[u8], now| AppRequest::TouchSession {
+            key: key.to_vec(),
+            now,
+        };
+        let touched = |response: &AppResponse| match response {
+            AppResponse::SessionTouched { value, expires_at } => {
+                value.as_ref().map(|_| *expires_at)
+            }
+            other => panic!("Expected SessionTouched, got {:?}", other),
+        };
+        let record = serde_json::to_vec(&SessionRecord {
+            data: serde_json::json!({"user": "alice"}),
+            idle_timeout_ms: 500,
+            created_at: 500,
+        })
+        .unwrap();
+
+        let responses = sm
+            .apply(vec![
+                entry(
+                    1,
+                    AppRequest::PutWithTtl {
+                        key: b"session".to_vec(),
+                        value: record.clone(),
+                        expires_at: 1000,
+                    },
+                ),
+                entry(2, touch(b"session", 800)),
+                // A lagging proposer clock does not pull the deadline back
+                entry(3, touch(b"session", 100)),
+                entry(4, touch(b"session", 1300)),
+                entry(
+                    5,
+                    AppRequest::Put {
+                        key: b"plain".to_vec(),
+                        value: b"not a session".to_vec(),
+                    },
+                ),
+                entry(6, touch(b"plain", 100)),
+                entry(7, touch(b"missing", 100)),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(touched(&responses[1]), Some(1300));
+        assert_eq!(touched(&responses[2]), Some(1300));
+        // The session expired by then
+        assert_eq!(touched(&responses[3]), None);
+        assert_eq!(touched(&responses[5]), None);
+        assert_eq!(touched(&responses[6]), None);
+
+        // Touching never rewrites the value
+        assert_eq!(sm.key_version(&b"session".to_vec()).await, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_apply_locks() {
+        let mut sm = StateMachineStore::new();
+        let entry = |index, request| openraft::Entry {
+            log_id: LogId::new(LeaderId::new(1, 1), index),
+            payload: EntryPayload::Normal(request),
+        };
+        let acquire = |holder: &str, now, expires_at| AppRequest::AcquireLock {
+            name: "jobs".to_string(),
+            holder: holder.to_string(),
+            now,
+            expires_at,
+        };
+
+        let responses = sm
+            .apply(vec![
+                entry(1, acquire("a", 100, 200)),
+                // Held by "a" until 200
+                entry(2, acquire("b", 150, 250)),
+                // Retry by the holder keeps the token
+                entry(3, acquire("a", 160, 300)),
+                // Lease of "a" expired by 300
+                entry(4, acquire("b", 300, 400)),
+            ])
+            .await
+            .unwrap();
+        let token = |response: &AppResponse| match response {
+            AppResponse::LockOk { lease } => lease.token,
+            other => panic!("Expected LockOk, got {:?}", other),
+        };
+        assert_eq!(token(&responses[0]), 1);
+        assert!(matches!(
+            &responses[1],
+            AppResponse::LockDenied { current: Some(lease) } if lease.holder == "a"
+        ));
+        assert_eq!(token(&responses[2]), 1);
+        assert_eq!(token(&responses[3]), 4);
+
+        let responses = sm
+            .apply(vec![
+                // The old token lost the lock
+                entry(
+                    5,
+                    AppRequest::RenewLock {
+                        name: "jobs".to_string(),
+                        token: 1,
+                        now: 310,
+                        expires_at: 500,
+                    },
+                ),
+                entry(
+                    6,
+                    AppRequest::RenewLock {
+                        name: "jobs".to_string(),
+                        token: 4,
+                        now: 310,
+                        expires_at: 500,
+                    },
+                ),
+                entry(
+                    7,
+                    AppRequest::ReleaseLock {
+                        name: "jobs".to_string(),
+                        token: 1,
+                    },
+                ),
+            ])
+            .await
+            .unwrap();
+        assert!(matches!(
+            &responses[0],
+            AppResponse::LockDenied { current: Some(lease) } if lease.token == 4
+        ));
+        assert!(matches!(
+            &responses[1],
+            AppResponse::LockOk { lease } if lease.expires_at == 500
+        ));
+        assert!(matches!(
+            responses[2],
+            AppResponse::LockReleased { released: false }
+        ));
+
+        // Leases survive snapshots
+        let mut builder = sm.get_snapshot_builder().await;
+        let snapshot = builder.build_snapshot().await.unwrap();
+        let mut restored = StateMachineStore::new();
+        restored
+            .install_snapshot(&snapshot.meta, snapshot.snapshot)
+            .await
+            .unwrap();
+        assert_eq!(
+            restored.inner.read().await.locks["jobs"].holder,
+            "b".to_string()
+        );
+
+        let responses = sm
+            .apply(vec![entry(
+                8,
+                AppRequest::ReleaseLock {
+                    name: "jobs".to_string(),
+                    token: 4,
+                },
+            )])
+            .await
+            .unwrap();
+        assert!(matches!(
+            responses[0],
+            AppResponse::LockReleased { released: true }
+        ));
+        assert!(sm.inner.read().await.locks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_apply_queue() {
+        let mut sm = StateMachineStore::new();
+        let entry = |index, request| openraft::Entry {
+            log_id: LogId::new(LeaderId::new(1, 1), index),
+            payload: EntryPayload::Normal(request),
+        };
+        let enqueue = |payload: &[u8]| AppRequest::Enqueue {
+            queue: "jobs".to_string(),
+            payload: payload.to_vec(),
+            capacity: 2,
+        };
+        let dequeue = |now, visible_until| AppRequest::Dequeue {
+            queue: "jobs".to_string(),
+            now,
+            visible_until,
+        };
+        let ack = |id, receipt| AppRequest::Ack {
+            queue: "jobs".to_string(),
+            id,
+            receipt,
+        };
+
+        let responses = sm
+            .apply(vec![
+                entry(1, enqueue(b"first")),
+                entry(2, enqueue(b"second")),
+                entry(3, enqueue(b"third")),
+                // Never delivered, so there is no receipt to ack with
+                entry(4, ack(1, 0)),
+                entry(5, dequeue(100, 200)),
+                entry(6, dequeue(110, 210)),
+                // Both items are leased
+                entry(7, dequeue(120, 220)),
+                // The lease of "first" ran out, so it is delivered again
+                entry(8, dequeue(200, 300)),
+            ])
+            .await
+            .unwrap();
+        assert!(matches!(responses[0], AppResponse::Enqueued { id: 1 }));
+        assert!(matches!(responses[1], AppResponse::Enqueued { id: 2 }));
+        assert!(matches!(responses[2], AppResponse::QueueFull { length: 2 }));
+        assert!(matches!(responses[3], AppResponse::Acked { acked: false }));
+        let delivered = |response: &AppResponse| match response {
+            AppResponse::Dequeued { item: Some(item) } => item.clone(),
+            other => panic!("Expected an item, got {:?}", other),
+        };
+        let first = delivered(&responses[4]);
+        assert_eq!((first.id, first.receipt, first.attempts), (1, 5, 1));
+        assert_eq!(first.payload, b"first".to_vec());
+        assert_eq!(delivered(&responses[5]).id, 2);
+        assert!(matches!(responses[6], AppResponse::Dequeued { item: None }));
+        let redelivered = delivered(&responses[7]);
+        assert_eq!((redelivered.id, redelivered.receipt), (1, 8));
+        assert_eq!(redelivered.attempts, 2);
+
+        // Queues survive snapshots
+        let mut builder = sm.get_snapshot_builder().await;
+        let snapshot = builder.build_snapshot().await.unwrap();
+        let mut restored = StateMachineStore::new();
+        restored
+            .install_snapshot(&snapshot.meta, snapshot.snapshot)
+            .await
+            .unwrap();
+        assert_eq!(restored.inner.read().await.queues["jobs"].len(), 2);
+
+        let responses = sm
+            .apply(vec![
+                // The first delivery's receipt is stale
+                entry(9, ack(1, 5)),
+                entry(10, ack(1, 8)),
+                entry(11, ack(2, 6)),
+            ])
+            .await
+            .unwrap();
+        assert!(matches!(responses[0], AppResponse::Acked { acked: false }));
+        assert!(matches!(responses[1], AppResponse::Acked { acked: true }));
+        assert!(matches!(responses[2], AppResponse::Acked { acked: true }));
+        assert!(sm.inner.read().await.queues.is_empty());
+        assert_eq!(
+            sm.queue_stats("jobs").await,
+            QueueStats {
+                queue: "jobs".to_string(),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apply_tags() {
+        let mut sm = StateMachineStore::new();
+        let entry = |index, request| openraft::Entry {
+            log_id: LogId::new(LeaderId::new(1, 1), index),
+            payload: EntryPayload::Normal(request),
+        };
+        let put = |key: &[u8], value: &[u8]| AppRequest::Put {
+            key: key.to_vec(),
+            value: value.to_vec(),
+        };
+        let create_tag = |name: &str, now| AppRequest::CreateTag {
+            name: name.to_string(),
+            manifest_version: 7,
+            now,
+        };
+
+        let responses = sm
+            .apply(vec![
+                entry(1, put(b"a", b"1")),
+                entry(2, put(b"b", b"2")),
+                entry(
+                    3,
+                    AppRequest::PutWithTtl {
+                        key: b"short".to_vec(),
+                        value: b"lived".to_vec(),
+                        expires_at: 50,
+                    },
+                ),
+                entry(4, create_tag("v1", 100)),
+                entry(5, put(b"a", b"changed")),
+                entry(6, create_tag("v1", 200)),
+            ])
+            .await
+            .unwrap();
+        let tag = match &responses[3] {
+            AppResponse::TagCreated { tag } => tag.clone(),
+            other => panic!("Expected a tag, got {:?}", other),
+        };
+        assert_eq!((tag.raft_index, tag.manifest_version), (4, 7));
+        assert_eq!((tag.key_count, tag.created_at), (2, 100));
+        // Tags are immutable; the existing one is returned unchanged
+        assert!(
+            matches!(&responses[5], AppResponse::TagExists { tag: existing } if *existing == tag)
+        );
+
+        // The tag keeps the state as of its entry, with a root over exactly that state
+        let state = sm.tagged_state("v1").await.unwrap();
+        assert_eq!(state.values[&b"a".to_vec()], b"1".to_vec());
+        assert!(!state.values.contains_key(b"short".as_slice()));
+        let expected = MerkleTree::from_pairs(vec![
+            (b"a".to_vec(), b"1".to_vec()),
+            (b"b".to_vec(), b"2".to_vec()),
+        ]);
+        assert_eq!(tag.merkle_root, expected.root_hash().map(hex::encode));
+        assert_eq!(sm.get(&b"a".to_vec()).await, Some(b"changed".to_vec()));
+
+        // Tags survive snapshots
+        let mut builder = sm.get_snapshot_builder().await;
+        let snapshot = builder.build_snapshot().await.unwrap();
+        let mut restored = StateMachineStore::new();
+        restored
+            .install_snapshot(&snapshot.meta, snapshot.snapshot)
+            .await
+            .unwrap();
+        assert_eq!(restored.tags().await, vec![tag]);
+        assert_eq!(restored.tagged_state("v1").await.unwrap(), state);
+
+        let responses = sm
+            .apply(vec![
+                entry(
+                    7,
+                    AppRequest::DeleteTag {
+                        name: "v1".to_string(),
+                    },
+                ),
+                entry(
+                    8,
+                    AppRequest::DeleteTag {
+                        name: "v1".to_string(),
+                    },
+                ),
+            ])
+            .await
+            .unwrap();
+        assert!(matches!(
+            responses[0],
+            AppResponse::TagDeleted { deleted: true }
+        ));
+        assert!(matches!(
+            responses[1],
+            AppResponse::TagDeleted { deleted: false }
+        ));
+        assert!(sm.tags().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_apply_fenced_writes() {
+        let mut sm = StateMachineStore::new();
+        let entry = |index, request| openraft::Entry {
+            log_id: LogId::new(LeaderId::new(1, 1), index),
+            payload: EntryPayload::Normal(request),
+        };
+        let fenced_put = |epoch, key: &[u8], value: &[u8]| {
+            AppRequest::fenced(
+                Fence {
+                    namespace: "jobs/".to_string(),
+                    epoch,
+                },
+                AppRequest::Put {
+                    key: key.to_vec(),
+                    value: value.to_vec(),
+                },
+            )
+        };
+        let advance = |epoch| AppRequest::AdvanceEpoch {
+            namespace: "jobs/".to_string(),
+            epoch,
+        };
+
+        let responses = sm
+            .apply(vec![
+                // Namespaces start at epoch 0
+                entry(1, fenced_put(0, b"jobs/1", b"zombie")),
+                entry(2, advance(None)),
+                entry(3, advance(Some(5))),
+                entry(4, advance(Some(5))),
+                entry(5, advance(Some(2))),
+                entry(6, fenced_put(5, b"jobs/1", b"current")),
+                // The writer fenced off at epoch 0 resumes
+                entry(7, fenced_put(0, b"jobs/1", b"stale")),
+                entry(8, fenced_put(5, b"other/1", b"escape")),
+                entry(
+                    9,
+                    AppRequest::fenced(
+                        Fence {
+                            namespace: "jobs/".to_string(),
+                            epoch: 5,
+                        },
+                        AppRequest::DeleteTag {
+                            name: "v1".to_string(),
+                        },
+                    ),
+                ),
+            ])
+            .await
+            .unwrap();
+        assert!(matches!(responses[0], AppResponse::PutOk));
+        assert!(matches!(responses[1], AppResponse::EpochOk { epoch: 1 }));
+        assert!(matches!(responses[2], AppResponse::EpochOk { epoch: 5 }));
+        assert!(matches!(responses[3], AppResponse::EpochOk { epoch: 5 }));
+        assert!(matches!(
+            responses[4],
+            AppResponse::StaleEpoch { current: 5 }
+        ));
+        assert!(matches!(responses[5], AppResponse::PutOk));
+        assert!(matches!(
+            responses[6],
+            AppResponse::StaleEpoch { current: 5 }
+        ));
+        assert!(matches!(responses[7], AppResponse::Error { .. }));
+        assert!(matches!(responses[8], AppResponse::Error { .. }));
+
+        assert_eq!(sm.get(&b"jobs/1".to_vec()).await, Some(b"current".to_vec()));
+        assert_eq!(sm.get(&b"other/1".to_vec()).await, None);
+        assert_eq!(sm.epoch("jobs/").await, 5);
+        assert_eq!(sm.epoch("other/").await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_apply_record_usage() {
+        let mut sm = StateMachineStore::new();
+        let delta = |key_id: &str, day, ops| UsageDelta {
+            key_id: key_id.to_string(),
+            day,
+            ops,
+            bytes: ops * 10,
+        };
+        let responses = sm
+            .apply(vec![
+                openraft::Entry {
+                    log_id: LogId::new(LeaderId::new(1, 1), 1),
+                    payload: EntryPayload::Normal(AppRequest::RecordUsage {
+                        usage: vec![delta("a", 20_742, 3), delta("b", 20_742, 1)],
+                    }),
+                },
+                openraft::Entry {
+                    log_id: LogId::new(LeaderId::new(1, 1), 2),
+                    payload: EntryPayload::Normal(AppRequest::RecordUsage {
+                        usage: vec![delta("a", 20_743, 2)],
+                    }),
+                },
+            ])
+            .await
+            .unwrap();
+        assert!(matches!(responses[1], AppResponse::UsageRecorded));
+
+        let usage = sm.quota_usage("a").await.unwrap();
+        assert_eq!(usage.day, 20_743);
+        assert_eq!((usage.daily.ops, usage.monthly.ops), (2, 5));
+        assert_eq!(usage.monthly.bytes, 50);
+        assert!(sm.quota_usage("c").await.is_none());
+
+        // Usage survives snapshots
+        let mut builder = sm.get_snapshot_builder().await;
+        let snapshot = builder.build_snapshot().await.unwrap();
+        let mut restored = StateMachineStore::new();
+        restored
+            .install_snapshot(&snapshot.meta, snapshot.snapshot)
+            .await
+            .unwrap();
+        assert_eq!(restored.quota_usages().await, sm.quota_usages().await);
+    }
+
+    #[tokio::test]
+    async fn test_apply_anchor_segment() {
+        let mut sm = StateMachineStore::new();
+        let anchor_entry = |index, merkle_root: Vec<u8>| openraft::Entry {
+            log_id: LogId::new(LeaderId::new(1, 1), index),
+            payload: EntryPayload::Normal(AppRequest::AnchorSegment {
+                segment_id: 7,
+                merkle_root,
+                key_count: 2,
+                now: 1_700_000_000_000,
+            }),
+        };
+        let responses = sm
+            .apply(vec![
+                anchor_entry(1, vec![0xab; 32]),
+                anchor_entry(2, vec![0xcd; 32]),
+            ])
+            .await
+            .unwrap();
+        let anchored = match &responses[0] {
+            AppResponse::SegmentAnchored { anchor } => anchor.clone(),
+            other => panic!("unexpected response {:?}", other),
+        };
+        assert_eq!(anchored.merkle_root, "ab".repeat(32));
+        assert_eq!(anchored.raft_index, 1);
+
+        // The first root sticks
+        assert!(matches!(
+            &responses[1],
+            AppResponse::AnchorExists { anchor } if *anchor == anchored
+        ));
+        assert_eq!(sm.segment_anchor(7).await, Some(anchored.clone()));
+        assert_eq!(sm.segment_anchor(8).await, None);
+
+        // Anchors survive snapshots
+        let mut builder = sm.get_snapshot_builder().await;
+        let snapshot = builder.build_snapshot().await.unwrap();
+        let mut restored = StateMachineStore::new();
+        restored
+            .install_snapshot(&snapshot.meta, snapshot.snapshot)
+            .await
+            .unwrap();
+        assert_eq!(restored.segment_anchors().await, vec![anchored]);
+    }
+
+    #[tokio::test]
+    async fn test_apply_transaction() {
+        let mut sm = StateMachineStore::new();
+        let mut events = sm.subscribe();
+        let entry = |index, request| openraft::Entry {
+            log_id: LogId::new(LeaderId::new(1, 1), index),
+            payload: EntryPayload::Normal(request),
+        };
+        let read = |key: &[u8], version| TxnRead {
+            key: key.to_vec(),
+            version,
+        };
+        let write = |key: &[u8], value: Option<&[u8]>| TxnWrite {
+            key: key.to_vec(),
+            value: value.map(<[u8]>::to_vec),
+        };
+
+        let responses = sm
+            .apply(vec![
+                entry(
+                    1,
+                    AppRequest::Put {
+                        key: b"alice".to_vec(),
+                        value: b"100".to_vec(),
+                    },
+                ),
+                entry(
+                    2,
+                    AppRequest::Transaction {
+                        reads: vec![read(b"alice", Some(1)), read(b"bob", None)],
+                        writes: vec![write(b"alice", Some(b"70")), write(b"bob", Some(b"30"))],
+                    },
+                ),
+                // Read before the previous transaction: neither write is applied
+                entry(
+                    3,
+                    AppRequest::Transaction {
+                        reads: vec![read(b"alice", Some(1))],
+                        writes: vec![write(b"alice", None), write(b"carol", Some(b"100"))],
+                    },
+                ),
+                entry(
+                    4,
+                    AppRequest::Transaction {
+                        reads: vec![read(b"alice", Some(2))],
+                        writes: vec![write(b"alice", None)],
+                    },
+                ),
+            ])
+            .await
+            .unwrap();
+        assert!(matches!(responses[1], AppResponse::TxnOk));
+        assert!(matches!(responses[2], AppResponse::TxnConflict { ref key } if key == b"alice"));
+        assert!(matches!(responses[3], AppResponse::TxnOk));
+
+        assert_eq!(sm.get(&b"alice".to_vec()).await, None);
+        assert_eq!(sm.get(&b"bob".to_vec()).await, Some(b"30".to_vec()));
+        assert_eq!(sm.get(&b"carol".to_vec()).await, None);
+        assert_eq!(
+            sm.lookup_versioned(&b"bob".to_vec()).await,
+            (Some(StoredValue::Hot(b"30".to_vec())), Some(2))
+        );
+        assert_eq!(
+            sm.lookup_versioned(&b"alice".to_vec()).await,
+            (None, Some(4))
+        );
+
+        let mut kinds = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            kinds.push((event.key, event.kind, event.version));
+        }
+        assert_eq!(
+            kinds[1..],
+            [
+                (b"alice".to_vec(), ChangeKind::Put, 2),
+                (b"bob".to_vec(), ChangeKind::Put, 2),
+                (b"alice".to_vec(), ChangeKind::Delete, 4),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apply_batch() {
+        let mut sm = StateMachineStore::new();
+        let mut events = sm.subscribe();
+        let entry = |index, request| openraft::Entry {
+            log_id: LogId::new(LeaderId::new(1, 1), index),
+            payload: EntryPayload::Normal(request),
+        };
+        let write = |key: &[u8], value: Option<&[u8]>| TxnWrite {
+            key: key.to_vec(),
+            value: value.map(<[u8]>::to_vec),
+        };
+        let fence = |epoch| Fence {
+            namespace: "orders/".to_string(),
+            epoch,
+        };
+
+        let responses = sm
+            .apply(vec![
+                entry(
+                    1,
+                    AppRequest::Put {
+                        key: b"orders/1".to_vec(),
+                        value: b"old".to_vec(),
+                    },
+                ),
+                entry(
+                    2,
+                    AppRequest::Batch {
+                        writes: vec![
+                            write(b"orders/1", None),
+                            write(b"orders/2", Some(b"b")),
+                            write(b"orders/3", Some(b"c")),
+                        ],
+                    },
+                ),
+                entry(
+                    3,
+                    AppRequest::AdvanceEpoch {
+                        namespace: "orders/".to_string(),
+                        epoch: Some(2),
+                    },
+                ),
+                // A fenced batch is refused as a whole once the fence is stale
+                entry(
+                    4,
+                    AppRequest::fenced(
+                        fence(1),
+                        AppRequest::Batch {
+                            writes: vec![write(b"orders/4", Some(b"d"))],
+                        },
+                    ),
+                ),
+                entry(
+                    5,
+                    AppRequest::fenced(
+                        fence(2),
+                        AppRequest::Batch {
+                            writes: vec![write(b"orders/2", None), write(b"other", Some(b"e"))],
+                        },
+                    ),
+                ),
+            ])
+            .await
+            .unwrap();
+        assert!(matches!(responses[1], AppResponse::BatchOk));
+        assert!(matches!(
+            responses[3],
+            AppResponse::StaleEpoch { current: 2 }
+        ));
+        assert!(matches!(responses[4], AppResponse::Error { .. }));
+
+        assert_eq!(sm.get(&b"orders/1".to_vec()).await, None);
+        assert_eq!(sm.get(&b"orders/2".to_vec()).await, Some(b"b".to_vec()));
+        assert_eq!(sm.get(&b"orders/4".to_vec()).await, None);
+        assert_eq!(sm.get(&b"other".to_vec()).await, None);
+        assert_eq!(
+            sm.lookup_versioned(&b"orders/3".to_vec()).await,
+            (Some(StoredValue::Hot(b"c".to_vec())), Some(2))
+        );
+
+        let mut kinds = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            kinds.push((event.key, event.kind, event.version));
+        }
+        assert_eq!(
+            kinds[1..],
+            [
+                (b"orders/1".to_vec(), ChangeKind::Delete, 2),
+                (b"orders/2".to_vec(), ChangeKind::Put, 2),
+                (b"orders/3".to_vec(), ChangeKind::Put, 2),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apply_compare_and_swap() {
+        let mut sm = StateMachineStore::new();
+        let entry = |index, request| openraft::Entry {
+            log_id: LogId::new(LeaderId::new(1, 1), index),
+            payload: EntryPayload::Normal(request),
+        };
+        let cas = |expected: Option<&[u8]>, new: Option<&[u8]>, now| AppRequest::CompareAndSwap {
+            key: b"k".to_vec(),
+            expected: expected.map(<[u8]>::to_vec),
+            new: new.map(<[u8]>::to_vec),
+            now,
+        };
+
+        let responses = sm
+            .apply(vec![
+                entry(1, cas(None, Some(b"v1"), 0)),
+                // The key exists now, so a second put-if-absent fails
+                entry(2, cas(None, Some(b"v2"), 0)),
+                entry(3, cas(Some(b"v0"), Some(b"v2"), 0)),
+                entry(4, cas(Some(b"v1"), Some(b"v2"), 0)),
+                entry(
+                    5,
+                    AppRequest::PutWithTtl {
+                        key: b"k".to_vec(),
+                        value: b"v3".to_vec(),
+                        expires_at: 100,
+                    },
+                ),
+                // An expired value counts as absent
+                entry(6, cas(None, Some(b"v4"), 100)),
+                entry(7, cas(Some(b"v4"), None, 100)),
+            ])
+            .await
+            .unwrap();
+        let outcomes: Vec<(bool, Option<Value>)> = responses
+            .into_iter()
+            .filter_map(|response| match response {
+                AppResponse::CasOk { swapped, current } => Some((swapped, current)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                (true, None),
+                (false, Some(b"v1".to_vec())),
+                (false, Some(b"v1".to_vec())),
+                (true, Some(b"v1".to_vec())),
+                (true, None),
+                (true, Some(b"v4".to_vec())),
+            ]
+        );
+        assert_eq!(sm.get(&b"k".to_vec()).await, None);
+        assert_eq!(sm.key_version(&b"k".to_vec()).await, Some(7));
     }
 }
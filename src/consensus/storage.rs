@@ -39,6 +39,18 @@ impl RaftStorage {
         Arc::clone(&self.state_machine)
     }
 
+    /// Committed log index last persisted in `db`, if any
+    pub fn persisted_committed_index(db: &sled::Db) -> Option<u64> {
+        let value = db
+            .open_tree(Self::TREE_STATE)
+            .ok()?
+            .get(Self::KEY_COMMITTED)
+            .ok()??;
+        bincode::deserialize::<LogId<NodeId>>(&value)
+            .ok()
+            .map(|log_id| log_id.index)
+    }
+
     /// Tree names for different types of data
     const TREE_LOGS: &'static str = "logs";
     const TREE_VOTE: &'static str = "vote";
@@ -251,13 +263,16 @@ impl RaftLogStorage<TypeConfig> for RaftStorage {
     {
         let logs = self.logs()?;
 
+        // One sled batch per append, so entries proposed together are written at once
+        let mut batch = sled::Batch::default();
         for entry in entries {
             let key = Self::log_key(entry.log_id.index);
             let value = bincode::serialize(&entry)
                 .map_err(|e| StorageError::from(StorageIOError::write_logs(&e)))?;
-            logs.insert(key, value)
-                .map_err(|e| StorageError::from(StorageIOError::write_logs(&e)))?;
+            batch.insert(key, value);
         }
+        logs.apply_batch(batch)
+            .map_err(|e| StorageError::from(StorageIOError::write_logs(&e)))?;
 
         // Flush to disk
         logs.flush()
@@ -390,6 +405,20 @@ mod tests {
         assert_eq!(entries[1].log_id, log_id2);
     }
 
+    #[test]
+    fn test_log_entry_overhead() {
+        let entry: openraft::Entry<TypeConfig> = openraft::Entry {
+            log_id: LogId::new(LeaderId::new(7, 3), 42),
+            payload: EntryPayload::Normal(AppRequest::Put {
+                key: vec![b'k'; 32],
+                value: vec![b'v'; 512],
+            }),
+        };
+        let encoded = bincode::serialize(&entry).unwrap();
+        let stored = (RaftStorage::log_key(42).len() + encoded.len()) as u64;
+        assert_eq!(stored, 32 + 512 + crate::capacity::LOG_ENTRY_OVERHEAD);
+    }
+
     #[tokio::test]
     async fn test_get_log_state() {
         let mut storage = create_test_storage();
@@ -474,5 +503,6 @@ mod tests {
 
         let committed = storage.read_committed().await.unwrap();
         assert_eq!(committed, Some(log_id));
+        assert_eq!(RaftStorage::persisted_committed_index(&storage.db), Some(5));
     }
 }
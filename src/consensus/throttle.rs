@@ -0,0 +1,102 @@
+//! Rate limiting of snapshot transfers
+//!
+//! A [`TransferThrottle`] paces snapshot chunks to a byte rate. Chunks are scheduled
+//! one after another on a shared timeline, so a throttle shared by several transfers
+//! splits the rate between them instead of multiplying it.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Paces byte transfers to a configurable rate
+pub struct TransferThrottle {
+    /// Allowed bytes per second; 0 is unlimited
+    bytes_per_sec: AtomicU64,
+    /// When the next chunk may start
+    next_slot: Mutex<Option<Instant>>,
+}
+
+impl TransferThrottle {
+    /// Create a throttle allowing `bytes_per_sec` (0 is unlimited)
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec: AtomicU64::new(bytes_per_sec),
+            next_slot: Mutex::new(None),
+        }
+    }
+
+    /// Create a throttle that never waits
+    pub fn unlimited() -> Self {
+        Self::new(0)
+    }
+
+    /// Allowed bytes per second; 0 is unlimited
+    pub fn rate(&self) -> u64 {
+        self.bytes_per_sec.load(Ordering::Relaxed)
+    }
+
+    /// Change the allowed bytes per second (0 is unlimited)
+    pub fn set_rate(&self, bytes_per_sec: u64) {
+        self.bytes_per_sec.store(bytes_per_sec, Ordering::Relaxed);
+    }
+
+    /// Wait until `bytes` may be transferred and return how long that took
+    ///
+    /// A chunk starts when the chunks before it have used up their share of the rate;
+    /// the first chunk after an idle period starts at once.
+    pub async fn acquire(&self, bytes: u64) -> Duration {
+        let rate = self.rate();
+        if rate == 0 {
+            return Duration::ZERO;
+        }
+
+        let cost = Duration::from_secs_f64(bytes as f64 / rate as f64);
+        let start = {
+            let mut next_slot = self.next_slot.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            let start = next_slot.filter(|slot| *slot > now).unwrap_or(now);
+            *next_slot = Some(start + cost);
+            start
+        };
+
+        let wait = start.saturating_duration_since(Instant::now());
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+        wait
+    }
+}
+
+impl Default for TransferThrottle {
+    fn default() -> Self {
+        Self::unlimited()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_unlimited_never_waits() {
+        let throttle = TransferThrottle::unlimited();
+        for _ in 0..10 {
+            assert_eq!(throttle.acquire(u64::MAX).await, Duration::ZERO);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chunks_are_paced() {
+        // 100 bytes take 50ms at this rate
+        let throttle = TransferThrottle::new(2000);
+        assert_eq!(throttle.acquire(100).await, Duration::ZERO);
+
+        let started = Instant::now();
+        throttle.acquire(100).await;
+        throttle.acquire(100).await;
+        assert!(started.elapsed() >= Duration::from_millis(90));
+
+        throttle.set_rate(0);
+        assert_eq!(throttle.acquire(100).await, Duration::ZERO);
+    }
+}
@@ -5,9 +5,23 @@
 use openraft::raft::responder::OneshotResponder;
 use openraft::{BasicNode, Entry, TokioRuntime};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::io::Cursor;
 
-use crate::types::{Key, NodeId, Value};
+use crate::types::{
+    Fence, Key, LedgerTag, LockLease, NodeId, QueueItem, SegmentAnchor, SegmentId, TxnRead,
+    TxnWrite, UsageDelta, Value,
+};
+use crate::wire::LEGACY_WIRE_VERSION;
+
+/// Wire version that introduced entries stamped with the leader's clock
+pub const STAMP_WIRE_VERSION: u32 = 7;
+
+/// Wire version required by the request kinds added before version negotiation
+///
+/// Nodes from before negotiation only decode puts, gets and deletes, so every other
+/// kind is held back until they are gone.
+const EXTENDED_KINDS_WIRE_VERSION: u32 = 9;
 
 /// Client request type for log entries
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -18,6 +32,290 @@ pub enum AppRequest {
     Get { key: Key },
     /// Delete a key
     Delete { key: Key },
+    /// Put a key-value pair that expires at `expires_at` (milliseconds since UNIX epoch)
+    PutWithTtl {
+        key: Key,
+        value: Value,
+        expires_at: u64,
+    },
+    /// Remove a key whose TTL elapsed; ignored if the key was rewritten since
+    Expire { key: Key, expires_at: u64 },
+    /// Apply an RFC 7386 merge patch (serialized JSON) to the JSON document at `key`
+    JsonMergePatch { key: Key, patch: Value },
+    /// Move the value (and TTL) at `from` to `to`, replacing any value at `to`
+    Rename { from: Key, to: Key },
+    /// Drop the in-memory value at `key` in favour of the archived segment holding it;
+    /// ignored unless the value's SHA-256 still equals `value_digest`
+    Demote {
+        key: Key,
+        segment_id: SegmentId,
+        value_digest: Vec<u8>,
+    },
+    /// Grant the lock `name` to `holder` until `expires_at`, unless another holder's
+    /// lease is still valid at `now` (both milliseconds since UNIX epoch, taken from
+    /// the proposer's clock so replicas agree). Retries by the current holder extend
+    /// the lease and keep its fencing token.
+    AcquireLock {
+        name: String,
+        holder: String,
+        now: u64,
+        expires_at: u64,
+    },
+    /// Extend the lease with fencing token `token`, if it is still valid at `now`
+    RenewLock {
+        name: String,
+        token: u64,
+        now: u64,
+        expires_at: u64,
+    },
+    /// Release the lease with fencing token `token`
+    ReleaseLock { name: String, token: u64 },
+    /// Put a key-value pair and return the value it replaced; a value whose TTL
+    /// elapsed by `now` (milliseconds since UNIX epoch, from the proposer's clock)
+    /// counts as absent
+    GetAndSet { key: Key, value: Value, now: u64 },
+    /// Delete a key and return its value, treating TTLs like `GetAndSet`
+    GetAndDelete { key: Key, now: u64 },
+    /// Move the expiry of the session stored at `key` to `now` plus the session's
+    /// idle timeout, unless it already expired by `now` (milliseconds since UNIX
+    /// epoch, from the proposer's clock)
+    TouchSession { key: Key, now: u64 },
+    /// Append `payload` to `queue`, unless the queue already holds `capacity` items
+    Enqueue {
+        queue: String,
+        payload: Value,
+        capacity: usize,
+    },
+    /// Hand out the oldest item of `queue` that is visible at `now` and hide it until
+    /// `visible_until` (both milliseconds since UNIX epoch, from the proposer's clock)
+    Dequeue {
+        queue: String,
+        now: u64,
+        visible_until: u64,
+    },
+    /// Remove item `id` from `queue`, provided `receipt` is its latest delivery
+    Ack {
+        queue: String,
+        id: u64,
+        receipt: u64,
+    },
+    /// Capture the current state under the tag `name`, unless the name is taken. Keys
+    /// whose TTL elapsed by `now` (milliseconds since UNIX epoch, from the proposer's
+    /// clock) are left out; `manifest_version` is recorded as given.
+    CreateTag {
+        name: String,
+        manifest_version: u64,
+        now: u64,
+    },
+    /// Drop the tag `name` and the state it captured
+    DeleteTag { name: String },
+    /// Raise the epoch of `namespace` to `epoch`, or by one if `None`; refused if
+    /// `epoch` is below the current epoch
+    AdvanceEpoch {
+        namespace: String,
+        epoch: Option<u64>,
+    },
+    /// Apply `request`, a key write within `fence.namespace`, unless the namespace
+    /// has moved past `fence.epoch`
+    Fenced {
+        fence: Fence,
+        request: Box<AppRequest>,
+    },
+    /// Apply `writes` together, unless a key of `reads` is no longer at the version
+    /// it was read at
+    Transaction {
+        reads: Vec<TxnRead>,
+        writes: Vec<TxnWrite>,
+    },
+    /// Replace the value of `key` with `new` if it is still `expected`; `None` stands
+    /// for an absent key, and as `new` deletes it. A value whose TTL elapsed by `now`
+    /// (milliseconds since UNIX epoch, from the proposer's clock) counts as absent.
+    CompareAndSwap {
+        key: Key,
+        expected: Option<Value>,
+        new: Option<Value>,
+        now: u64,
+    },
+    /// Add the API key usage served by a node to the replicated quota counters
+    RecordUsage { usage: Vec<UsageDelta> },
+    /// Commit `merkle_root` as the root of the archived segment `segment_id`, unless
+    /// the segment is already anchored; `now` (milliseconds since UNIX epoch, from
+    /// the proposer's clock) is recorded as the anchoring time
+    AnchorSegment {
+        segment_id: SegmentId,
+        merkle_root: Vec<u8>,
+        key_count: usize,
+        now: u64,
+    },
+    /// Apply `writes`, puts or deletes where the value is `None`, as one entry. Each
+    /// key is written at most once; unlike a transaction, nothing is checked first.
+    Batch { writes: Vec<TxnWrite> },
+    /// Apply `request` as of `now`, the leader's clock when it proposed the entry
+    /// (milliseconds since UNIX epoch). Change timestamps and expiry checks of the
+    /// entry use it, so every replica and every replay of the log agree.
+    Stamped { now: u64, request: Box<AppRequest> },
+    /// Forget the changes recorded for keys deleted or expired before `before`
+    /// (milliseconds since UNIX epoch), so their tombstones do not pile up
+    PruneTombstones { before: u64 },
+}
+
+impl AppRequest {
+    /// Wrap a key write so it is only applied while `fence` is current
+    pub fn fenced(fence: Fence, request: AppRequest) -> Self {
+        AppRequest::Fenced {
+            fence,
+            request: Box::new(request),
+        }
+    }
+
+    /// Stamp `request` with the leader's clock
+    pub fn stamped(now: u64, request: AppRequest) -> Self {
+        AppRequest::Stamped {
+            now,
+            request: Box::new(request),
+        }
+    }
+
+    /// Leader time the request is stamped with, if any
+    pub fn stamp(&self) -> Option<u64> {
+        match self {
+            AppRequest::Stamped { now, .. } => Some(*now),
+            _ => None,
+        }
+    }
+
+    /// The request a stamped request wraps, or the request itself
+    pub fn unstamped(&self) -> &AppRequest {
+        match self {
+            AppRequest::Stamped { request, .. } => request,
+            request => request,
+        }
+    }
+
+    /// The request a stamped or fenced request wraps, or the request itself
+    pub fn unfenced(&self) -> &AppRequest {
+        match self.unstamped() {
+            AppRequest::Fenced { request, .. } => request,
+            request => request,
+        }
+    }
+
+    /// Keys a key write modifies; `None` for requests that are not key writes
+    pub fn written_keys(&self) -> Option<Vec<&Key>> {
+        match self {
+            AppRequest::Put { key, .. }
+            | AppRequest::Delete { key }
+            | AppRequest::PutWithTtl { key, .. }
+            | AppRequest::JsonMergePatch { key, .. }
+            | AppRequest::GetAndSet { key, .. }
+            | AppRequest::GetAndDelete { key, .. }
+            | AppRequest::CompareAndSwap { key, .. } => Some(vec![key]),
+            AppRequest::Rename { from, to } => Some(vec![from, to]),
+            AppRequest::Transaction { writes, .. } | AppRequest::Batch { writes } => {
+                Some(writes.iter().map(|write| &write.key).collect())
+            }
+            _ => None,
+        }
+    }
+
+    /// Name of the request kind, for logs and errors
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AppRequest::Put { .. } => "put",
+            AppRequest::Get { .. } => "get",
+            AppRequest::Delete { .. } => "delete",
+            AppRequest::PutWithTtl { .. } => "put_with_ttl",
+            AppRequest::Expire { .. } => "expire",
+            AppRequest::JsonMergePatch { .. } => "json_merge_patch",
+            AppRequest::Rename { .. } => "rename",
+            AppRequest::Demote { .. } => "demote",
+            AppRequest::AcquireLock { .. } => "acquire_lock",
+            AppRequest::RenewLock { .. } => "renew_lock",
+            AppRequest::ReleaseLock { .. } => "release_lock",
+            AppRequest::GetAndSet { .. } => "get_and_set",
+            AppRequest::GetAndDelete { .. } => "get_and_delete",
+            AppRequest::TouchSession { .. } => "touch_session",
+            AppRequest::Enqueue { .. } => "enqueue",
+            AppRequest::Dequeue { .. } => "dequeue",
+            AppRequest::Ack { .. } => "ack",
+            AppRequest::CreateTag { .. } => "create_tag",
+            AppRequest::DeleteTag { .. } => "delete_tag",
+            AppRequest::AdvanceEpoch { .. } => "advance_epoch",
+            AppRequest::Fenced { .. } => "fenced",
+            AppRequest::Transaction { .. } => "transaction",
+            AppRequest::CompareAndSwap { .. } => "compare_and_swap",
+            AppRequest::RecordUsage { .. } => "record_usage",
+            AppRequest::AnchorSegment { .. } => "anchor_segment",
+            AppRequest::Batch { .. } => "batch",
+            AppRequest::Stamped { .. } => "stamped",
+            AppRequest::PruneTombstones { .. } => "prune_tombstones",
+        }
+    }
+
+    /// Oldest wire version able to decode and apply this entry
+    ///
+    /// The leader refuses to propose the entry until every member speaks this
+    /// version. A new request kind takes the `WIRE_VERSION` of the release that
+    /// introduces it; only puts, gets and deletes are understood by nodes that
+    /// predate version negotiation.
+    pub fn required_version(&self) -> u32 {
+        match self {
+            AppRequest::Put { .. } | AppRequest::Get { .. } | AppRequest::Delete { .. } => {
+                LEGACY_WIRE_VERSION
+            }
+            // Added before version negotiation, marked with wire version 9
+            AppRequest::Fenced { request, .. } => {
+                request.required_version().max(EXTENDED_KINDS_WIRE_VERSION)
+            }
+            AppRequest::PutWithTtl { .. }
+            | AppRequest::Expire { .. }
+            | AppRequest::JsonMergePatch { .. }
+            | AppRequest::Rename { .. }
+            | AppRequest::Demote { .. }
+            | AppRequest::AcquireLock { .. }
+            | AppRequest::RenewLock { .. }
+            | AppRequest::ReleaseLock { .. }
+            | AppRequest::GetAndSet { .. }
+            | AppRequest::GetAndDelete { .. }
+            | AppRequest::TouchSession { .. }
+            | AppRequest::Enqueue { .. }
+            | AppRequest::Dequeue { .. }
+            | AppRequest::Ack { .. }
+            | AppRequest::CreateTag { .. }
+            | AppRequest::DeleteTag { .. }
+            | AppRequest::AdvanceEpoch { .. } => EXTENDED_KINDS_WIRE_VERSION,
+            // Introduced with wire version 2
+            AppRequest::Transaction { .. } => 2,
+            // Introduced with wire version 3
+            AppRequest::CompareAndSwap { .. } => 3,
+            // Introduced with wire version 4
+            AppRequest::RecordUsage { .. } => 4,
+            // Introduced with wire version 5
+            AppRequest::AnchorSegment { .. } => 5,
+            // Introduced with wire version 6
+            AppRequest::Batch { .. } => 6,
+            // Introduced with wire version 7
+            AppRequest::Stamped { request, .. } => {
+                request.required_version().max(STAMP_WIRE_VERSION)
+            }
+            // Introduced with wire version 10
+            AppRequest::PruneTombstones { .. } => 10,
+        }
+    }
+
+    /// Demote `key`, provided its value is still `value` when the entry is applied
+    pub fn demote(key: Key, segment_id: SegmentId, value: &[u8]) -> Self {
+        AppRequest::Demote {
+            key,
+            segment_id,
+            value_digest: value_digest(value),
+        }
+    }
+}
+
+/// Digest guarding a demotion against concurrent writes
+pub fn value_digest(value: &[u8]) -> Vec<u8> {
+    Sha256::digest(value).to_vec()
 }
 
 /// Client response type for operations
@@ -29,8 +327,66 @@ pub enum AppResponse {
     GetOk { value: Option<Value> },
     /// Successful delete operation
     DeleteOk,
+    /// Successful merge patch with the resulting document
+    PatchOk { value: Value },
+    /// Rename applied; `value` is the moved value, `None` if `from` did not exist
+    RenameOk { value: Option<Value> },
+    /// Demotion processed; `demoted` is false if the value changed in the meantime
+    DemoteOk { demoted: bool },
+    /// Lock acquired or renewed
+    LockOk { lease: LockLease },
+    /// Lock not granted; `current` is the valid lease of another holder, if any
+    LockDenied { current: Option<LockLease> },
+    /// Release processed; `released` is false if the token no longer held the lock
+    LockReleased { released: bool },
+    /// Get-and-set or get-and-delete applied; `value` is what the key held before
+    Previous { value: Option<Value> },
+    /// Session touched; `value` is its stored record, `None` if it expired or never
+    /// existed
+    SessionTouched {
+        value: Option<Value>,
+        expires_at: u64,
+    },
+    /// Item enqueued with ID `id`
+    Enqueued { id: u64 },
+    /// Enqueue refused; the queue holds `length` items, its capacity
+    QueueFull { length: usize },
+    /// Dequeue processed; `item` is `None` if no item was visible
+    Dequeued { item: Option<QueueItem> },
+    /// Ack processed; `acked` is false if the item is gone or was redelivered
+    Acked { acked: bool },
+    /// Tag created
+    TagCreated { tag: LedgerTag },
+    /// Tag not created; `tag` is the existing tag of the same name
+    TagExists { tag: LedgerTag },
+    /// Tag deletion processed; `deleted` is false if no such tag existed
+    TagDeleted { deleted: bool },
+    /// Epoch advanced to `epoch`
+    EpochOk { epoch: u64 },
+    /// Refused because the namespace is at epoch `current`, newer than the request's
+    StaleEpoch { current: u64 },
     /// Error response
     Error { message: String },
+    /// Transaction applied
+    TxnOk,
+    /// Transaction not applied because `key` changed since it was read
+    TxnConflict { key: Key },
+    /// Compare-and-swap processed; `current` is the value the key held, which the
+    /// swap replaced if `swapped`
+    CasOk {
+        swapped: bool,
+        current: Option<Value>,
+    },
+    /// Quota usage added
+    UsageRecorded,
+    /// Segment root committed
+    SegmentAnchored { anchor: SegmentAnchor },
+    /// The segment was already anchored; `anchor` holds the root committed first
+    AnchorExists { anchor: SegmentAnchor },
+    /// Batch applied
+    BatchOk,
+    /// Tombstones pruned; `pruned` counts the changes forgotten
+    TombstonesPruned { pruned: u64 },
 }
 
 /// Type configuration for OpenRaft
@@ -66,6 +422,7 @@ impl openraft::RaftTypeConfig for TypeConfig {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::wire::{PeerVersions, WIRE_VERSION};
 
     #[test]
     fn test_app_request_serialization() {
@@ -120,6 +477,248 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_app_request_expire() {
+        let request = AppRequest::Expire {
+            key: b"key".to_vec(),
+            expires_at: 42,
+        };
+
+        let json = serde_json::to_string(&request).unwrap();
+        let deserialized: AppRequest = serde_json::from_str(&json).unwrap();
+
+        match deserialized {
+            AppRequest::Expire { key, expires_at } => {
+                assert_eq!(key, b"key".to_vec());
+                assert_eq!(expires_at, 42);
+            }
+            _ => panic!("Expected Expire request"),
+        }
+    }
+
+    #[test]
+    fn test_app_request_json_merge_patch() {
+        let request = AppRequest::JsonMergePatch {
+            key: b"doc".to_vec(),
+            patch: br#"{"a":null}"#.to_vec(),
+        };
+
+        let json = serde_json::to_string(&request).unwrap();
+        let deserialized: AppRequest = serde_json::from_str(&json).unwrap();
+
+        match deserialized {
+            AppRequest::JsonMergePatch { key, patch } => {
+                assert_eq!(key, b"doc".to_vec());
+                assert_eq!(patch, br#"{"a":null}"#.to_vec());
+            }
+            _ => panic!("Expected JsonMergePatch request"),
+        }
+    }
+
+    #[test]
+    fn test_app_request_rename() {
+        let request = AppRequest::Rename {
+            from: b"old".to_vec(),
+            to: b"new".to_vec(),
+        };
+
+        let json = serde_json::to_string(&request).unwrap();
+        let deserialized: AppRequest = serde_json::from_str(&json).unwrap();
+
+        match deserialized {
+            AppRequest::Rename { from, to } => {
+                assert_eq!(from, b"old".to_vec());
+                assert_eq!(to, b"new".to_vec());
+            }
+            _ => panic!("Expected Rename request"),
+        }
+    }
+
+    #[test]
+    fn test_app_request_demote() {
+        let request = AppRequest::demote(b"key".to_vec(), 7, b"value");
+
+        let json = serde_json::to_string(&request).unwrap();
+        let deserialized: AppRequest = serde_json::from_str(&json).unwrap();
+
+        match deserialized {
+            AppRequest::Demote {
+                key,
+                segment_id,
+                value_digest: digest,
+            } => {
+                assert_eq!(key, b"key".to_vec());
+                assert_eq!(segment_id, 7);
+                assert_eq!(digest, value_digest(b"value"));
+                assert_ne!(digest, value_digest(b"other"));
+            }
+            _ => panic!("Expected Demote request"),
+        }
+    }
+
+    #[test]
+    fn test_app_request_acquire_lock() {
+        let request = AppRequest::AcquireLock {
+            name: "jobs".to_string(),
+            holder: "worker-1".to_string(),
+            now: 1_000,
+            expires_at: 31_000,
+        };
+
+        let json = serde_json::to_string(&request).unwrap();
+        let deserialized: AppRequest = serde_json::from_str(&json).unwrap();
+
+        match deserialized {
+            AppRequest::AcquireLock {
+                name,
+                holder,
+                now,
+                expires_at,
+            } => {
+                assert_eq!(name, "jobs");
+                assert_eq!(holder, "worker-1");
+                assert_eq!(now, 1_000);
+                assert_eq!(expires_at, 31_000);
+            }
+            _ => panic!("Expected AcquireLock request"),
+        }
+    }
+
+    #[test]
+    fn test_app_request_required_version() {
+        let put = AppRequest::Put {
+            key: b"k".to_vec(),
+            value: b"v".to_vec(),
+        };
+        assert_eq!(put.kind(), "put");
+        assert_eq!(put.required_version(), LEGACY_WIRE_VERSION);
+
+        // A fenced write needs what the write it wraps needs
+        let fenced = AppRequest::fenced(
+            Fence {
+                namespace: "orders".to_string(),
+                epoch: 1,
+            },
+            put,
+        );
+        assert_eq!(fenced.kind(), "fenced");
+        assert_eq!(fenced.required_version(), 9);
+
+        let transaction = AppRequest::Transaction {
+            reads: Vec::new(),
+            writes: vec![TxnWrite {
+                key: b"k".to_vec(),
+                value: None,
+            }],
+        };
+        assert_eq!(transaction.kind(), "transaction");
+        assert_eq!(transaction.required_version(), 2);
+        assert_eq!(transaction.written_keys(), Some(vec![&b"k".to_vec()]));
+
+        let cas = AppRequest::CompareAndSwap {
+            key: b"k".to_vec(),
+            expected: None,
+            new: Some(b"v".to_vec()),
+            now: 0,
+        };
+        assert_eq!(cas.kind(), "compare_and_swap");
+        assert_eq!(cas.required_version(), 3);
+        assert_eq!(cas.written_keys(), Some(vec![&b"k".to_vec()]));
+
+        let usage = AppRequest::RecordUsage { usage: Vec::new() };
+        assert_eq!(usage.kind(), "record_usage");
+        assert_eq!(usage.required_version(), 4);
+        assert_eq!(usage.written_keys(), None);
+
+        let anchor = AppRequest::AnchorSegment {
+            segment_id: 7,
+            merkle_root: vec![0; 32],
+            key_count: 1,
+            now: 0,
+        };
+        assert_eq!(anchor.kind(), "anchor_segment");
+        assert_eq!(anchor.required_version(), 5);
+        assert_eq!(anchor.written_keys(), None);
+
+        let batch = AppRequest::Batch {
+            writes: vec![
+                TxnWrite {
+                    key: b"a".to_vec(),
+                    value: Some(b"1".to_vec()),
+                },
+                TxnWrite {
+                    key: b"b".to_vec(),
+                    value: None,
+                },
+            ],
+        };
+        assert_eq!(batch.kind(), "batch");
+        assert_eq!(batch.required_version(), 6);
+        assert_eq!(
+            batch.written_keys(),
+            Some(vec![&b"a".to_vec(), &b"b".to_vec()])
+        );
+
+        // A stamp needs version 7, or more if the request it wraps does
+        let stamped = AppRequest::stamped(1_000, fenced);
+        assert_eq!(stamped.kind(), "stamped");
+        assert_eq!(stamped.required_version(), 9);
+        assert_eq!(stamped.stamp(), Some(1_000));
+        assert_eq!(stamped.unfenced().kind(), "put");
+        assert_eq!(AppRequest::stamped(0, batch).required_version(), 7);
+
+        let prune = AppRequest::PruneTombstones { before: 1_000 };
+        assert_eq!(prune.kind(), "prune_tombstones");
+        assert_eq!(prune.required_version(), 10);
+        assert_eq!(prune.written_keys(), None);
+    }
+
+    #[test]
+    fn test_legacy_member_blocks_kinds_added_before_negotiation() {
+        let versions = PeerVersions::new();
+        versions.record(2, LEGACY_WIRE_VERSION);
+        versions.record(3, WIRE_VERSION);
+
+        let key = || b"k".to_vec();
+        let requests = vec![
+            AppRequest::PutWithTtl {
+                key: key(),
+                value: b"v".to_vec(),
+                expires_at: 1,
+            },
+            AppRequest::Expire {
+                key: key(),
+                expires_at: 1,
+            },
+            AppRequest::Rename {
+                from: key(),
+                to: b"to".to_vec(),
+            },
+            AppRequest::GetAndDelete { key: key(), now: 0 },
+            AppRequest::AdvanceEpoch {
+                namespace: "orders".to_string(),
+                epoch: None,
+            },
+        ];
+        for request in requests {
+            assert_eq!(
+                versions.lagging(1, [1, 2, 3], request.required_version()),
+                vec![2],
+                "{} must wait for the legacy member",
+                request.kind()
+            );
+            assert!(versions
+                .lagging(1, [1, 3], request.required_version())
+                .is_empty());
+        }
+
+        // Legacy nodes decode puts, gets and deletes
+        let delete = AppRequest::Delete { key: key() };
+        assert!(versions
+            .lagging(1, [1, 2, 3], delete.required_version())
+            .is_empty());
+    }
+
     #[test]
     fn test_app_response_serialization() {
         let response = AppResponse::PutOk;
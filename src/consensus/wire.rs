@@ -0,0 +1,811 @@
+//! Protobuf schemas of application requests and responses
+//!
+//! See `proto/scribe/v1/raft.proto`. Each request and response kind is a case of a
+//! `oneof`, numbered in declaration order; new kinds are only ever appended, so a
+//! node that receives a kind it does not know reports it instead of misreading it.
+
+use super::type_config::{AppRequest, AppResponse};
+use crate::error::{Result, ScribeError};
+use crate::wire::codec::unknown_case;
+use crate::wire::{Encoder, Fields, WireMessage};
+
+/// Number of `AppRequest` cases in this wire version
+const REQUEST_CASES: u32 = 28;
+
+/// Number of `AppResponse` cases in this wire version
+const RESPONSE_CASES: u32 = 29;
+
+impl WireMessage for AppRequest {
+    const KIND: &'static str = "scribe.v1.AppRequest";
+
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            AppRequest::Put { key, value } => encoder.nested(1, |put| {
+                put.bytes(1, key);
+                put.bytes(2, value);
+            }),
+            AppRequest::Get { key } => encoder.nested(2, |get| get.bytes(1, key)),
+            AppRequest::Delete { key } => encoder.nested(3, |delete| delete.bytes(1, key)),
+            AppRequest::PutWithTtl {
+                key,
+                value,
+                expires_at,
+            } => encoder.nested(4, |put| {
+                put.bytes(1, key);
+                put.bytes(2, value);
+                put.uint64(3, *expires_at);
+            }),
+            AppRequest::Expire { key, expires_at } => encoder.nested(5, |expire| {
+                expire.bytes(1, key);
+                expire.uint64(2, *expires_at);
+            }),
+            AppRequest::JsonMergePatch { key, patch } => encoder.nested(6, |merge| {
+                merge.bytes(1, key);
+                merge.bytes(2, patch);
+            }),
+            AppRequest::Rename { from, to } => encoder.nested(7, |rename| {
+                rename.bytes(1, from);
+                rename.bytes(2, to);
+            }),
+            AppRequest::Demote {
+                key,
+                segment_id,
+                value_digest,
+            } => encoder.nested(8, |demote| {
+                demote.bytes(1, key);
+                demote.uint64(2, *segment_id);
+                demote.bytes(3, value_digest);
+            }),
+            AppRequest::AcquireLock {
+                name,
+                holder,
+                now,
+                expires_at,
+            } => encoder.nested(9, |acquire| {
+                acquire.string(1, name);
+                acquire.string(2, holder);
+                acquire.uint64(3, *now);
+                acquire.uint64(4, *expires_at);
+            }),
+            AppRequest::RenewLock {
+                name,
+                token,
+                now,
+                expires_at,
+            } => encoder.nested(10, |renew| {
+                renew.string(1, name);
+                renew.uint64(2, *token);
+                renew.uint64(3, *now);
+                renew.uint64(4, *expires_at);
+            }),
+            AppRequest::ReleaseLock { name, token } => encoder.nested(11, |release| {
+                release.string(1, name);
+                release.uint64(2, *token);
+            }),
+            AppRequest::GetAndSet { key, value, now } => encoder.nested(12, |swap| {
+                swap.bytes(1, key);
+                swap.bytes(2, value);
+                swap.uint64(3, *now);
+            }),
+            AppRequest::GetAndDelete { key, now } => encoder.nested(13, |take| {
+                take.bytes(1, key);
+                take.uint64(2, *now);
+            }),
+            AppRequest::TouchSession { key, now } => encoder.nested(14, |touch| {
+                touch.bytes(1, key);
+                touch.uint64(2, *now);
+            }),
+            AppRequest::Enqueue {
+                queue,
+                payload,
+                capacity,
+            } => encoder.nested(15, |enqueue| {
+                enqueue.string(1, queue);
+                enqueue.bytes(2, payload);
+                enqueue.uint64(3, *capacity as u64);
+            }),
+            AppRequest::Dequeue {
+                queue,
+                now,
+                visible_until,
+            } => encoder.nested(16, |dequeue| {
+                dequeue.string(1, queue);
+                dequeue.uint64(2, *now);
+                dequeue.uint64(3, *visible_until);
+            }),
+            AppRequest::Ack { queue, id, receipt } => encoder.nested(17, |ack| {
+                ack.string(1, queue);
+                ack.uint64(2, *id);
+                ack.uint64(3, *receipt);
+            }),
+            AppRequest::CreateTag {
+                name,
+                manifest_version,
+                now,
+            } => encoder.nested(18, |create| {
+                create.string(1, name);
+                create.uint64(2, *manifest_version);
+                create.uint64(3, *now);
+            }),
+            AppRequest::DeleteTag { name } => encoder.nested(19, |delete| delete.string(1, name)),
+            AppRequest::AdvanceEpoch { namespace, epoch } => encoder.nested(20, |advance| {
+                advance.string(1, namespace);
+                if let Some(epoch) = epoch {
+                    advance.uint64(2, *epoch);
+                }
+            }),
+            AppRequest::Fenced { fence, request } => encoder.nested(21, |fenced| {
+                fenced.message(1, fence);
+                fenced.message(2, request.as_ref());
+            }),
+            AppRequest::Transaction { reads, writes } => encoder.nested(22, |transaction| {
+                for read in reads {
+                    transaction.message(1, read);
+                }
+                for write in writes {
+                    transaction.message(2, write);
+                }
+            }),
+            AppRequest::CompareAndSwap {
+                key,
+                expected,
+                new,
+                now,
+            } => encoder.nested(23, |cas| {
+                cas.bytes(1, key);
+                if let Some(expected) = expected {
+                    cas.bytes(2, expected);
+                }
+                if let Some(new) = new {
+                    cas.bytes(3, new);
+                }
+                cas.uint64(4, *now);
+            }),
+            AppRequest::RecordUsage { usage } => encoder.nested(24, |record| {
+                for delta in usage {
+                    record.message(1, delta);
+                }
+            }),
+            AppRequest::AnchorSegment {
+                segment_id,
+                merkle_root,
+                key_count,
+                now,
+            } => encoder.nested(25, |anchor| {
+                anchor.uint64(1, *segment_id);
+                anchor.bytes(2, merkle_root);
+                anchor.uint64(3, *key_count as u64);
+                anchor.uint64(4, *now);
+            }),
+            AppRequest::Batch { writes } => encoder.nested(26, |batch| {
+                for write in writes {
+                    batch.message(1, write);
+                }
+            }),
+            AppRequest::Stamped { now, request } => encoder.nested(27, |stamped| {
+                stamped.uint64(1, *now);
+                stamped.message(2, request.as_ref());
+            }),
+            AppRequest::PruneTombstones { before } => {
+                encoder.nested(28, |prune| prune.uint64(1, *before))
+            }
+        }
+    }
+
+    fn decode(fields: &Fields<'_>) -> Result<Self> {
+        let Some((case, f)) = fields.oneof(REQUEST_CASES)? else {
+            return Err(unknown_case(Self::KIND));
+        };
+        Ok(match case {
+            1 => AppRequest::Put {
+                key: f.bytes(1)?,
+                value: f.bytes(2)?,
+            },
+            2 => AppRequest::Get { key: f.bytes(1)? },
+            3 => AppRequest::Delete { key: f.bytes(1)? },
+            4 => AppRequest::PutWithTtl {
+                key: f.bytes(1)?,
+                value: f.bytes(2)?,
+                expires_at: f.uint64(3)?,
+            },
+            5 => AppRequest::Expire {
+                key: f.bytes(1)?,
+                expires_at: f.uint64(2)?,
+            },
+            6 => AppRequest::JsonMergePatch {
+                key: f.bytes(1)?,
+                patch: f.bytes(2)?,
+            },
+            7 => AppRequest::Rename {
+                from: f.bytes(1)?,
+                to: f.bytes(2)?,
+            },
+            8 => AppRequest::Demote {
+                key: f.bytes(1)?,
+                segment_id: f.uint64(2)?,
+                value_digest: f.bytes(3)?,
+            },
+            9 => AppRequest::AcquireLock {
+                name: f.string(1)?,
+                holder: f.string(2)?,
+                now: f.uint64(3)?,
+                expires_at: f.uint64(4)?,
+            },
+            10 => AppRequest::RenewLock {
+                name: f.string(1)?,
+                token: f.uint64(2)?,
+                now: f.uint64(3)?,
+                expires_at: f.uint64(4)?,
+            },
+            11 => AppRequest::ReleaseLock {
+                name: f.string(1)?,
+                token: f.uint64(2)?,
+            },
+            12 => AppRequest::GetAndSet {
+                key: f.bytes(1)?,
+                value: f.bytes(2)?,
+                now: f.uint64(3)?,
+            },
+            13 => AppRequest::GetAndDelete {
+                key: f.bytes(1)?,
+                now: f.uint64(2)?,
+            },
+            14 => AppRequest::TouchSession {
+                key: f.bytes(1)?,
+                now: f.uint64(2)?,
+            },
+            15 => AppRequest::Enqueue {
+                queue: f.string(1)?,
+                payload: f.bytes(2)?,
+                capacity: f.usize(3)?,
+            },
+            16 => AppRequest::Dequeue {
+                queue: f.string(1)?,
+                now: f.uint64(2)?,
+                visible_until: f.uint64(3)?,
+            },
+            17 => AppRequest::Ack {
+                queue: f.string(1)?,
+                id: f.uint64(2)?,
+                receipt: f.uint64(3)?,
+            },
+            18 => AppRequest::CreateTag {
+                name: f.string(1)?,
+                manifest_version: f.uint64(2)?,
+                now: f.uint64(3)?,
+            },
+            19 => AppRequest::DeleteTag { name: f.string(1)? },
+            20 => AppRequest::AdvanceEpoch {
+                namespace: f.string(1)?,
+                epoch: f.optional_uint64(2)?,
+            },
+            21 => {
+                let request: AppRequest = f.required(2)?;
+                // Fences do not nest, which also bounds the decoding depth
+                if matches!(request, AppRequest::Fenced { .. }) {
+                    return Err(ScribeError::Serialization(
+                        "Fenced request wraps another fenced request".to_string(),
+                    ));
+                }
+                AppRequest::fenced(f.required(1)?, request)
+            }
+            22 => AppRequest::Transaction {
+                reads: f.repeated_message(1)?,
+                writes: f.repeated_message(2)?,
+            },
+            23 => AppRequest::CompareAndSwap {
+                key: f.bytes(1)?,
+                expected: f.optional_bytes(2)?,
+                new: f.optional_bytes(3)?,
+                now: f.uint64(4)?,
+            },
+            24 => AppRequest::RecordUsage {
+                usage: f.repeated_message(1)?,
+            },
+            25 => AppRequest::AnchorSegment {
+                segment_id: f.uint64(1)?,
+                merkle_root: f.bytes(2)?,
+                key_count: f.usize(3)?,
+                now: f.uint64(4)?,
+            },
+            26 => AppRequest::Batch {
+                writes: f.repeated_message(1)?,
+            },
+            27 => {
+                let request: AppRequest = f.required(2)?;
+                // Stamps do not nest, which also bounds the decoding depth
+                if matches!(request, AppRequest::Stamped { .. }) {
+                    return Err(ScribeError::Serialization(
+                        "Stamped request wraps another stamped request".to_string(),
+                    ));
+                }
+                AppRequest::stamped(f.uint64(1)?, request)
+            }
+            _ => AppRequest::PruneTombstones {
+                before: f.uint64(1)?,
+            },
+        })
+    }
+}
+
+impl WireMessage for AppResponse {
+    const KIND: &'static str = "scribe.v1.AppResponse";
+
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            AppResponse::PutOk => encoder.nested(1, |_| {}),
+            AppResponse::GetOk { value } => encoder.nested(2, |get| optional_value(get, value)),
+            AppResponse::DeleteOk => encoder.nested(3, |_| {}),
+            AppResponse::PatchOk { value } => encoder.nested(4, |patch| patch.bytes(1, value)),
+            AppResponse::RenameOk { value } => {
+                encoder.nested(5, |rename| optional_value(rename, value))
+            }
+            AppResponse::DemoteOk { demoted } => {
+                encoder.nested(6, |demote| demote.bool(1, *demoted))
+            }
+            AppResponse::LockOk { lease } => encoder.nested(7, |lock| lock.message(1, lease)),
+            AppResponse::LockDenied { current } => encoder.nested(8, |denied| {
+                if let Some(current) = current {
+                    denied.message(1, current);
+                }
+            }),
+            AppResponse::LockReleased { released } => {
+                encoder.nested(9, |release| release.bool(1, *released))
+            }
+            AppResponse::Previous { value } => {
+                encoder.nested(10, |previous| optional_value(previous, value))
+            }
+            AppResponse::SessionTouched { value, expires_at } => encoder.nested(11, |touched| {
+                optional_value(touched, value);
+                touched.uint64(2, *expires_at);
+            }),
+            AppResponse::Enqueued { id } => encoder.nested(12, |enqueued| enqueued.uint64(1, *id)),
+            AppResponse::QueueFull { length } => {
+                encoder.nested(13, |full| full.uint64(1, *length as u64))
+            }
+            AppResponse::Dequeued { item } => encoder.nested(14, |dequeued| {
+                if let Some(item) = item {
+                    dequeued.message(1, item);
+                }
+            }),
+            AppResponse::Acked { acked } => encoder.nested(15, |ack| ack.bool(1, *acked)),
+            AppResponse::TagCreated { tag } => {
+                encoder.nested(16, |created| created.message(1, tag))
+            }
+            AppResponse::TagExists { tag } => encoder.nested(17, |exists| exists.message(1, tag)),
+            AppResponse::TagDeleted { deleted } => {
+                encoder.nested(18, |delete| delete.bool(1, *deleted))
+            }
+            AppResponse::EpochOk { epoch } => encoder.nested(19, |ok| ok.uint64(1, *epoch)),
+            AppResponse::StaleEpoch { current } => {
+                encoder.nested(20, |stale| stale.uint64(1, *current))
+            }
+            AppResponse::Error { message } => encoder.nested(21, |error| error.string(1, message)),
+            AppResponse::TxnOk => encoder.nested(22, |_| {}),
+            AppResponse::TxnConflict { key } => {
+                encoder.nested(23, |conflict| conflict.bytes(1, key))
+            }
+            AppResponse::CasOk { swapped, current } => encoder.nested(24, |cas| {
+                cas.bool(1, *swapped);
+                if let Some(current) = current {
+                    cas.bytes(2, current);
+                }
+            }),
+            AppResponse::UsageRecorded => encoder.nested(25, |_| {}),
+            AppResponse::SegmentAnchored { anchor } => {
+                encoder.nested(26, |anchored| anchored.message(1, anchor))
+            }
+            AppResponse::AnchorExists { anchor } => {
+                encoder.nested(27, |exists| exists.message(1, anchor))
+            }
+            AppResponse::BatchOk => encoder.nested(28, |_| {}),
+            AppResponse::TombstonesPruned { pruned } => {
+                encoder.nested(29, |response| response.uint64(1, *pruned))
+            }
+        }
+    }
+
+    fn decode(fields: &Fields<'_>) -> Result<Self> {
+        let Some((case, f)) = fields.oneof(RESPONSE_CASES)? else {
+            return Err(unknown_case(Self::KIND));
+        };
+        Ok(match case {
+            1 => AppResponse::PutOk,
+            2 => AppResponse::GetOk {
+                value: f.optional_bytes(1)?,
+            },
+            3 => AppResponse::DeleteOk,
+            4 => AppResponse::PatchOk { value: f.bytes(1)? },
+            5 => AppResponse::RenameOk {
+                value: f.optional_bytes(1)?,
+            },
+            6 => AppResponse::DemoteOk {
+                demoted: f.bool(1)?,
+            },
+            7 => AppResponse::LockOk {
+                lease: f.required(1)?,
+            },
+            8 => AppResponse::LockDenied {
+                current: f.message(1)?,
+            },
+            9 => AppResponse::LockReleased {
+                released: f.bool(1)?,
+            },
+            10 => AppResponse::Previous {
+                value: f.optional_bytes(1)?,
+            },
+            11 => AppResponse::SessionTouched {
+                value: f.optional_bytes(1)?,
+                expires_at: f.uint64(2)?,
+            },
+            12 => AppResponse::Enqueued { id: f.uint64(1)? },
+            13 => AppResponse::QueueFull {
+                length: f.usize(1)?,
+            },
+            14 => AppResponse::Dequeued {
+                item: f.message(1)?,
+            },
+            15 => AppResponse::Acked { acked: f.bool(1)? },
+            16 => AppResponse::TagCreated {
+                tag: f.required(1)?,
+            },
+            17 => AppResponse::TagExists {
+                tag: f.required(1)?,
+            },
+            18 => AppResponse::TagDeleted {
+                deleted: f.bool(1)?,
+            },
+            19 => AppResponse::EpochOk {
+                epoch: f.uint64(1)?,
+            },
+            20 => AppResponse::StaleEpoch {
+                current: f.uint64(1)?,
+            },
+            21 => AppResponse::Error {
+                message: f.string(1)?,
+            },
+            22 => AppResponse::TxnOk,
+            23 => AppResponse::TxnConflict { key: f.bytes(1)? },
+            24 => AppResponse::CasOk {
+                swapped: f.bool(1)?,
+                current: f.optional_bytes(2)?,
+            },
+            25 => AppResponse::UsageRecorded,
+            26 => AppResponse::SegmentAnchored {
+                anchor: f.required(1)?,
+            },
+            27 => AppResponse::AnchorExists {
+                anchor: f.required(1)?,
+            },
+            28 => AppResponse::BatchOk,
+            _ => AppResponse::TombstonesPruned {
+                pruned: f.uint64(1)?,
+            },
+        })
+    }
+}
+
+/// Write an `optional bytes value = 1` field
+fn optional_value(encoder: &mut Encoder, value: &Option<Vec<u8>>) {
+    if let Some(value) = value {
+        encoder.bytes(1, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        Fence, LedgerTag, LockLease, QueueItem, SegmentAnchor, TxnRead, TxnWrite, UsageDelta,
+    };
+    use crate::wire::{open, seal};
+
+    fn round_trip<M: WireMessage + std::fmt::Debug>(message: &M) {
+        let decoded = open::<M>(&seal(message)).unwrap();
+        assert_eq!(format!("{:?}", decoded), format!("{:?}", message));
+    }
+
+    #[test]
+    fn test_app_requests_round_trip() {
+        let key = b"key".to_vec();
+        let requests = vec![
+            AppRequest::Put {
+                key: key.clone(),
+                value: b"value".to_vec(),
+            },
+            AppRequest::Get { key: key.clone() },
+            AppRequest::Delete { key: key.clone() },
+            AppRequest::PutWithTtl {
+                key: key.clone(),
+                value: Vec::new(),
+                expires_at: 42,
+            },
+            AppRequest::Expire {
+                key: key.clone(),
+                expires_at: 42,
+            },
+            AppRequest::JsonMergePatch {
+                key: key.clone(),
+                patch: br#"{"a":1}"#.to_vec(),
+            },
+            AppRequest::Rename {
+                from: key.clone(),
+                to: b"other".to_vec(),
+            },
+            AppRequest::demote(key.clone(), 7, b"value"),
+            AppRequest::AcquireLock {
+                name: "lock".to_string(),
+                holder: "me".to_string(),
+                now: 1,
+                expires_at: 2,
+            },
+            AppRequest::RenewLock {
+                name: "lock".to_string(),
+                token: 3,
+                now: 1,
+                expires_at: 2,
+            },
+            AppRequest::ReleaseLock {
+                name: "lock".to_string(),
+                token: 3,
+            },
+            AppRequest::GetAndSet {
+                key: key.clone(),
+                value: b"new".to_vec(),
+                now: 1,
+            },
+            AppRequest::GetAndDelete {
+                key: key.clone(),
+                now: 1,
+            },
+            AppRequest::TouchSession {
+                key: key.clone(),
+                now: 1,
+            },
+            AppRequest::Enqueue {
+                queue: "jobs".to_string(),
+                payload: b"job".to_vec(),
+                capacity: 100,
+            },
+            AppRequest::Dequeue {
+                queue: "jobs".to_string(),
+                now: 1,
+                visible_until: 2,
+            },
+            AppRequest::Ack {
+                queue: "jobs".to_string(),
+                id: 5,
+                receipt: 6,
+            },
+            AppRequest::CreateTag {
+                name: "v1".to_string(),
+                manifest_version: 3,
+                now: 1,
+            },
+            AppRequest::DeleteTag {
+                name: "v1".to_string(),
+            },
+            AppRequest::AdvanceEpoch {
+                namespace: "orders".to_string(),
+                epoch: None,
+            },
+            AppRequest::AdvanceEpoch {
+                namespace: "orders".to_string(),
+                epoch: Some(0),
+            },
+            AppRequest::fenced(
+                Fence {
+                    namespace: "orders".to_string(),
+                    epoch: 2,
+                },
+                AppRequest::Delete { key: key.clone() },
+            ),
+            AppRequest::Transaction {
+                reads: vec![
+                    TxnRead {
+                        key: key.clone(),
+                        version: Some(0),
+                    },
+                    TxnRead {
+                        key: b"other".to_vec(),
+                        version: None,
+                    },
+                ],
+                writes: vec![
+                    TxnWrite {
+                        key: key.clone(),
+                        value: Some(Vec::new()),
+                    },
+                    TxnWrite {
+                        key: key.clone(),
+                        value: None,
+                    },
+                ],
+            },
+            AppRequest::Transaction {
+                reads: Vec::new(),
+                writes: Vec::new(),
+            },
+            AppRequest::CompareAndSwap {
+                key: key.clone(),
+                expected: None,
+                new: Some(b"v".to_vec()),
+                now: 7,
+            },
+            AppRequest::CompareAndSwap {
+                key,
+                expected: Some(Vec::new()),
+                new: None,
+                now: 0,
+            },
+            AppRequest::RecordUsage {
+                usage: vec![UsageDelta {
+                    key_id: "0123456789abcdef".to_string(),
+                    day: 20_000,
+                    ops: 12,
+                    bytes: 0,
+                }],
+            },
+            AppRequest::RecordUsage { usage: Vec::new() },
+            AppRequest::AnchorSegment {
+                segment_id: 90_215_344_128_000,
+                merkle_root: vec![0xab; 32],
+                key_count: 3,
+                now: 1,
+            },
+            AppRequest::Batch {
+                writes: vec![
+                    TxnWrite {
+                        key: b"a".to_vec(),
+                        value: Some(Vec::new()),
+                    },
+                    TxnWrite {
+                        key: b"b".to_vec(),
+                        value: None,
+                    },
+                ],
+            },
+            AppRequest::Batch { writes: Vec::new() },
+            AppRequest::stamped(1_700_000_000_000, AppRequest::Delete { key: b"k".to_vec() }),
+            AppRequest::PruneTombstones {
+                before: 1_700_000_000_000,
+            },
+        ];
+        for request in &requests {
+            round_trip(request);
+        }
+    }
+
+    #[test]
+    fn test_app_responses_round_trip() {
+        let lease = LockLease {
+            name: "lock".to_string(),
+            holder: "me".to_string(),
+            token: 3,
+            expires_at: 2,
+        };
+        let tag = LedgerTag {
+            name: "v1".to_string(),
+            raft_index: 10,
+            manifest_version: 0,
+            merkle_root: None,
+            key_count: 4,
+            created_at: 1,
+        };
+        let anchor = SegmentAnchor {
+            segment_id: 8,
+            merkle_root: "ab".repeat(32),
+            key_count: 3,
+            raft_index: 11,
+            anchored_at: 1,
+        };
+        let responses = vec![
+            AppResponse::PutOk,
+            AppResponse::GetOk { value: None },
+            AppResponse::GetOk {
+                value: Some(Vec::new()),
+            },
+            AppResponse::DeleteOk,
+            AppResponse::PatchOk {
+                value: b"{}".to_vec(),
+            },
+            AppResponse::RenameOk {
+                value: Some(b"v".to_vec()),
+            },
+            AppResponse::DemoteOk { demoted: true },
+            AppResponse::LockOk {
+                lease: lease.clone(),
+            },
+            AppResponse::LockDenied { current: None },
+            AppResponse::LockDenied {
+                current: Some(lease),
+            },
+            AppResponse::LockReleased { released: false },
+            AppResponse::Previous { value: None },
+            AppResponse::SessionTouched {
+                value: Some(b"{}".to_vec()),
+                expires_at: 9,
+            },
+            AppResponse::Enqueued { id: 5 },
+            AppResponse::QueueFull { length: 100 },
+            AppResponse::Dequeued { item: None },
+            AppResponse::Dequeued {
+                item: Some(QueueItem {
+                    queue: "jobs".to_string(),
+                    id: 5,
+                    receipt: 6,
+                    payload: b"job".to_vec(),
+                    attempts: 1,
+                    visible_at: 2,
+                }),
+            },
+            AppResponse::Acked { acked: true },
+            AppResponse::TagCreated { tag: tag.clone() },
+            AppResponse::TagExists { tag },
+            AppResponse::TagDeleted { deleted: true },
+            AppResponse::EpochOk { epoch: 4 },
+            AppResponse::StaleEpoch { current: 5 },
+            AppResponse::Error {
+                message: "boom".to_string(),
+            },
+            AppResponse::TxnOk,
+            AppResponse::TxnConflict {
+                key: b"key".to_vec(),
+            },
+            AppResponse::CasOk {
+                swapped: false,
+                current: Some(Vec::new()),
+            },
+            AppResponse::CasOk {
+                swapped: true,
+                current: None,
+            },
+            AppResponse::UsageRecorded,
+            AppResponse::SegmentAnchored {
+                anchor: anchor.clone(),
+            },
+            AppResponse::AnchorExists { anchor },
+            AppResponse::BatchOk,
+            AppResponse::TombstonesPruned { pruned: 3 },
+        ];
+        for response in &responses {
+            round_trip(response);
+        }
+    }
+
+    #[test]
+    fn test_app_request_compatibility() {
+        // A field added to Put by a later version is skipped
+        let mut encoder = Encoder::new();
+        encoder.nested(1, |put| {
+            put.bytes(1, b"key");
+            put.bytes(2, b"value");
+            put.uint64(10, 99);
+        });
+        let request = AppRequest::from_bytes(&encoder.into_bytes()).unwrap();
+        assert!(matches!(request, AppRequest::Put { ref key, .. } if key == b"key"));
+
+        // A request kind added by a later version is rejected
+        let mut encoder = Encoder::new();
+        encoder.nested(REQUEST_CASES + 1, |scan| scan.bytes(1, b"prefix"));
+        assert!(AppRequest::from_bytes(&encoder.into_bytes()).is_err());
+
+        // Fences do not nest
+        let fence = Fence {
+            namespace: "orders".to_string(),
+            epoch: 1,
+        };
+        let nested = AppRequest::fenced(
+            fence.clone(),
+            AppRequest::fenced(fence, AppRequest::Get { key: Vec::new() }),
+        );
+        assert!(AppRequest::from_bytes(&nested.to_bytes()).is_err());
+
+        // Neither do stamps
+        let nested = AppRequest::stamped(
+            1,
+            AppRequest::stamped(2, AppRequest::Get { key: Vec::new() }),
+        );
+        assert!(AppRequest::from_bytes(&nested.to_bytes()).is_err());
+    }
+}
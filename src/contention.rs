@@ -0,0 +1,277 @@
+//! Write contention tracking
+//!
+//! [`ContentionTracker`] counts conditional writes per key prefix (see
+//! [`crate::hotkeys`] for how prefixes are cut) and how many of them lost to a
+//! concurrent write: compare-and-swaps that did not swap and transactions whose
+//! reads had changed by the time they were applied. Prefixes with many conflicts are
+//! where clients burn their retries, so they are the first place to look when
+//! conditional writes slow down.
+//!
+//! Counters are totals since startup. Conflicts are also exported as
+//! `scribe_ledger_write_conflicts_total` by operation and prefix, with prefixes
+//! beyond the first [`MAX_PREFIXES`] folded into one bucket to keep the number of
+//! series bounded.
+
+use crate::hotkeys::{key_prefix, OTHER_PREFIX};
+use crate::metrics::{CONDITIONAL_WRITES, WRITE_CONFLICTS};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
+
+/// Distinct prefixes tracked, including the shared [`OTHER_PREFIX`] bucket
+pub const MAX_PREFIXES: usize = 64;
+
+/// Kind of conditional write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConditionalOp {
+    /// Compare-and-swap, including put-if-absent
+    CompareAndSwap,
+    /// Optimistic transaction
+    Transaction,
+}
+
+impl ConditionalOp {
+    /// Label of the operation in metrics
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ConditionalOp::CompareAndSwap => "cas",
+            ConditionalOp::Transaction => "transaction",
+        }
+    }
+}
+
+/// Conditional writes and conflicts of one key prefix
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PrefixContention {
+    /// The prefix (lossy UTF-8)
+    pub prefix: String,
+    pub cas_attempts: u64,
+    pub cas_conflicts: u64,
+    pub txn_attempts: u64,
+    pub txn_conflicts: u64,
+}
+
+impl PrefixContention {
+    /// Conflicts of every kind
+    pub fn conflicts(&self) -> u64 {
+        self.cas_conflicts + self.txn_conflicts
+    }
+
+    /// Attempts of every kind
+    pub fn attempts(&self) -> u64 {
+        self.cas_attempts + self.txn_attempts
+    }
+
+    /// Share of attempts that conflicted, from 0.0 to 1.0
+    pub fn conflict_rate(&self) -> f64 {
+        match self.attempts() {
+            0 => 0.0,
+            attempts => self.conflicts() as f64 / attempts as f64,
+        }
+    }
+}
+
+/// A prefix in a [`ContentionReport`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ContendedPrefix {
+    #[serde(flatten)]
+    pub counts: PrefixContention,
+    /// Share of attempts that conflicted
+    pub conflict_rate: f64,
+}
+
+/// Snapshot of write contention, served at `GET /admin/contention`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ContentionReport {
+    /// Prefixes with the most conflicts first
+    pub prefixes: Vec<ContendedPrefix>,
+    /// Conditional writes attempted on every prefix
+    pub total_attempts: u64,
+    /// Conditional writes that conflicted on every prefix
+    pub total_conflicts: u64,
+}
+
+/// Per-prefix conflict counters of conditional writes for the API layer
+#[derive(Default)]
+pub struct ContentionTracker {
+    prefixes: Mutex<HashMap<String, PrefixContention>>,
+}
+
+impl ContentionTracker {
+    /// Create an empty tracker
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a compare-and-swap on `key`, which conflicted unless it swapped
+    pub fn record_cas(&self, key: &[u8], swapped: bool) {
+        self.record(key, ConditionalOp::CompareAndSwap, !swapped);
+    }
+
+    /// Record an attempt to commit a transaction touching `keys`, which conflicted
+    /// on `conflict` if it is set
+    ///
+    /// Each prefix among `keys` counts one attempt; only the prefix of the key that
+    /// changed counts the conflict.
+    pub fn record_transaction<'a>(
+        &self,
+        keys: impl IntoIterator<Item = &'a [u8]>,
+        conflict: Option<&[u8]>,
+    ) {
+        let mut touched: BTreeSet<String> = keys.into_iter().map(key_prefix).collect();
+        let conflict = conflict.map(key_prefix);
+        if let Some(prefix) = &conflict {
+            touched.insert(prefix.clone());
+        }
+        let mut prefixes = self.prefixes.lock().unwrap();
+        for prefix in touched {
+            let conflicted = conflict.as_ref() == Some(&prefix);
+            Self::count(
+                &mut prefixes,
+                prefix,
+                ConditionalOp::Transaction,
+                conflicted,
+            );
+        }
+    }
+
+    /// Record a conditional write on `key`
+    pub fn record(&self, key: &[u8], op: ConditionalOp, conflicted: bool) {
+        let mut prefixes = self.prefixes.lock().unwrap();
+        Self::count(&mut prefixes, key_prefix(key), op, conflicted);
+    }
+
+    fn count(
+        prefixes: &mut HashMap<String, PrefixContention>,
+        prefix: String,
+        op: ConditionalOp,
+        conflicted: bool,
+    ) {
+        let prefix = if prefixes.contains_key(&prefix) || prefixes.len() < MAX_PREFIXES - 1 {
+            prefix
+        } else {
+            OTHER_PREFIX.to_string()
+        };
+
+        CONDITIONAL_WRITES.with_label_values(&[op.as_str()]).inc();
+        if conflicted {
+            WRITE_CONFLICTS
+                .with_label_values(&[op.as_str(), &prefix])
+                .inc();
+        }
+
+        let counts = prefixes
+            .entry(prefix)
+            .or_insert_with_key(|prefix| PrefixContention {
+                prefix: prefix.clone(),
+                ..Default::default()
+            });
+        match op {
+            ConditionalOp::CompareAndSwap => {
+                counts.cas_attempts += 1;
+                counts.cas_conflicts += u64::from(conflicted);
+            }
+            ConditionalOp::Transaction => {
+                counts.txn_attempts += 1;
+                counts.txn_conflicts += u64::from(conflicted);
+            }
+        }
+    }
+
+    /// The `limit` most contended prefixes: most conflicts first, then the highest
+    /// conflict rate
+    pub fn report(&self, limit: usize) -> ContentionReport {
+        let prefixes = self.prefixes.lock().unwrap();
+        let total_attempts = prefixes.values().map(PrefixContention::attempts).sum();
+        let total_conflicts = prefixes.values().map(PrefixContention::conflicts).sum();
+
+        let mut contended: Vec<ContendedPrefix> = prefixes
+            .values()
+            .filter(|counts| counts.conflicts() > 0)
+            .map(|counts| ContendedPrefix {
+                counts: counts.clone(),
+                conflict_rate: counts.conflict_rate(),
+            })
+            .collect();
+        contended.sort_by(|a, b| {
+            b.counts
+                .conflicts()
+                .cmp(&a.counts.conflicts())
+                .then_with(|| b.conflict_rate.total_cmp(&a.conflict_rate))
+                .then_with(|| a.counts.prefix.cmp(&b.counts.prefix))
+        });
+        contended.truncate(limit);
+
+        ContentionReport {
+            prefixes: contended,
+            total_attempts,
+            total_conflicts,
+        }
+    }
+
+    /// Forget everything recorded so far
+    pub fn reset(&self) {
+        self.prefixes.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_ranks_contended_prefixes() {
+        let tracker = ContentionTracker::new();
+        for swapped in [true, false, false, false] {
+            tracker.record_cas(b"counter:hits", swapped);
+        }
+        tracker.record_cas(b"lock/a", false);
+        tracker.record_cas(b"lock/b", true);
+        tracker.record_cas(b"quiet:1", true);
+        tracker.record_transaction([&b"counter:a"[..], b"user:1", b"user:2"], None);
+        tracker.record_transaction([&b"user:1"[..], b"account:9"], Some(b"user:1"));
+
+        let report = tracker.report(10);
+        assert_eq!(report.total_attempts, 11);
+        assert_eq!(report.total_conflicts, 5);
+
+        let prefixes: Vec<&str> = report
+            .prefixes
+            .iter()
+            .map(|prefix| prefix.counts.prefix.as_str())
+            .collect();
+        // Prefixes without conflicts are left out; ties go to the higher rate, then by name
+        assert_eq!(prefixes, vec!["counter", "lock", "user"]);
+
+        let counter = &report.prefixes[0];
+        assert_eq!(counter.counts.cas_attempts, 4);
+        assert_eq!(counter.counts.cas_conflicts, 3);
+        assert_eq!(counter.counts.txn_attempts, 1);
+        assert_eq!(counter.counts.txn_conflicts, 0);
+        assert!((counter.conflict_rate - 0.6).abs() < 1e-9);
+
+        // Both user keys share one attempt per transaction
+        let user = &report.prefixes[2];
+        assert_eq!(user.counts.txn_attempts, 2);
+        assert_eq!(user.counts.txn_conflicts, 1);
+
+        assert_eq!(tracker.report(1).prefixes.len(), 1);
+        tracker.reset();
+        assert_eq!(tracker.report(10).total_attempts, 0);
+    }
+
+    #[test]
+    fn test_prefix_count_is_bounded() {
+        let tracker = ContentionTracker::new();
+        for i in 0..MAX_PREFIXES * 2 {
+            tracker.record_cas(format!("p{}:k", i).as_bytes(), false);
+        }
+        let report = tracker.report(usize::MAX);
+        assert_eq!(report.prefixes.len(), MAX_PREFIXES);
+        assert_eq!(report.total_conflicts, (MAX_PREFIXES * 2) as u64);
+        assert!(report
+            .prefixes
+            .iter()
+            .any(|p| p.counts.prefix == OTHER_PREFIX));
+    }
+}
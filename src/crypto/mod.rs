@@ -37,6 +37,9 @@ pub struct MerkleTree {
 }
 
 /// A proof for a specific key in the Merkle tree
+///
+/// Encoded with `to_bytes`/`from_bytes` of [`crate::wire::WireMessage`] and sealed
+/// with its root in a versioned [`crate::wire::ProofBundle`] for offline checks.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MerkleProof {
     /// The key being proven
@@ -242,26 +245,9 @@ impl MerkleTree {
 
     /// Verify a proof against a root hash
     pub fn verify_proof(proof: &MerkleProof, root_hash: &[u8]) -> bool {
-        if proof.siblings.len() != proof.directions.len() {
-            return false;
-        }
-
-        // Start with leaf hash
-        let mut current_hash = Self::hash_leaf(&proof.key, &proof.value);
-
-        // Traverse up the tree using siblings and directions
-        for (sibling, &is_right) in proof.siblings.iter().zip(proof.directions.iter()) {
-            current_hash = if is_right {
-                // Current node is on the right, sibling is on the left
-                Self::hash_internal(sibling, &current_hash)
-            } else {
-                // Current node is on the left, sibling is on the right
-                Self::hash_internal(&current_hash, sibling)
-            };
-        }
-
-        // Check if computed hash matches root hash
-        current_hash == root_hash
+        proof
+            .root_hash()
+            .is_some_and(|computed| computed == root_hash)
     }
 
     /// Get the number of leaf nodes in the tree
@@ -275,6 +261,34 @@ impl MerkleTree {
     }
 }
 
+impl MerkleProof {
+    /// Hash of the leaf holding the key-value pair
+    pub fn leaf_hash(&self) -> Vec<u8> {
+        MerkleTree::hash_leaf(&self.key, &self.value)
+    }
+
+    /// Root hash the proof leads to, `None` if its siblings and directions differ in
+    /// number
+    pub fn root_hash(&self) -> Option<Vec<u8>> {
+        if self.siblings.len() != self.directions.len() {
+            return None;
+        }
+
+        // Traverse up the tree using siblings and directions
+        let mut current_hash = self.leaf_hash();
+        for (sibling, &is_right) in self.siblings.iter().zip(self.directions.iter()) {
+            current_hash = if is_right {
+                // Current node is on the right, sibling is on the left
+                MerkleTree::hash_internal(sibling, &current_hash)
+            } else {
+                // Current node is on the left, sibling is on the right
+                MerkleTree::hash_internal(&current_hash, sibling)
+            };
+        }
+        Some(current_hash)
+    }
+}
+
 impl Default for MerkleTree {
     fn default() -> Self {
         Self::new()
@@ -412,6 +426,23 @@ mod tests {
         assert!(!MerkleTree::verify_proof(&proof, &wrong_root));
     }
 
+    #[test]
+    fn test_proof_root_hash() {
+        let pairs = vec![
+            (b"key1".to_vec(), b"value1".to_vec()),
+            (b"key2".to_vec(), b"value2".to_vec()),
+            (b"key3".to_vec(), b"value3".to_vec()),
+        ];
+        let tree = MerkleTree::from_pairs(pairs);
+
+        let mut proof = tree.get_proof(b"key3").unwrap();
+        assert_eq!(proof.root_hash(), tree.root_hash());
+        assert_eq!(proof.leaf_hash(), MerkleTree::hash_leaf(b"key3", b"value3"));
+
+        proof.directions.pop();
+        assert_eq!(proof.root_hash(), None);
+    }
+
     #[test]
     fn test_nonexistent_key() {
         let pairs = vec![
@@ -1,14 +1,18 @@
 //! Node discovery service for automatic cluster formation
 //!
 //! This module provides UDP broadcast-based node discovery with heartbeat
-//! and failure detection mechanisms.
+//! and failure detection mechanisms. Heartbeats carry the sender's clock, so
+//! receivers can monitor clock skew (see [`crate::clock_skew`]).
 
+use crate::clock_skew::ClockSkewMonitor;
 use crate::error::{Result, ScribeError};
+use crate::security::JoinTokenManager;
+use crate::wire::{self, PeerVersions, WireFormat, WireMessage};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::{SocketAddr, UdpSocket};
 use std::sync::{Arc, RwLock};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::time::sleep;
 use tracing::{debug, error, info, warn};
 
@@ -34,12 +38,21 @@ pub enum DiscoveryMessage {
         client_addr: SocketAddr,
         /// Cluster secret for authentication (optional)
         cluster_secret: Option<String>,
+        /// Join token presented by a node asking to join (optional); only
+        /// carried in protobuf envelopes, so bincode announces keep the layout
+        /// nodes without join tokens read
+        #[serde(skip)]
+        join_token: Option<String>,
     },
     /// Heartbeat to indicate node is alive
     Heartbeat {
         node_id: u64,
         /// Cluster secret for authentication (optional)
         cluster_secret: Option<String>,
+        /// Sender's clock when sending (milliseconds since the Unix epoch); only
+        /// carried in protobuf envelopes, bincode peers predate it
+        #[serde(skip)]
+        sent_at_ms: Option<u64>,
     },
     /// Request peer list from other nodes
     PeerListRequest {
@@ -105,12 +118,65 @@ impl Default for DiscoveryConfig {
     }
 }
 
+/// Admission check applied to announces from unknown nodes
+///
+/// Once the local node is a cluster member, an unknown node is admitted only if it is
+/// already a member or presents a valid join token. Nodes that have not joined a
+/// cluster yet admit everyone, so a joining node can find the existing members.
+#[derive(Clone)]
+pub struct JoinPolicy {
+    tokens: Arc<JoinTokenManager>,
+    is_member: Arc<dyn Fn(u64) -> bool + Send + Sync>,
+}
+
+impl JoinPolicy {
+    /// Create a policy verifying tokens with `tokens` and membership with `is_member`
+    pub fn new(
+        tokens: Arc<JoinTokenManager>,
+        is_member: impl Fn(u64) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            tokens,
+            is_member: Arc::new(is_member),
+        }
+    }
+
+    /// Check whether `local_node_id` should admit `node_id`
+    pub fn admits(&self, local_node_id: u64, node_id: u64, join_token: Option<&str>) -> bool {
+        if !(self.is_member)(local_node_id) || (self.is_member)(node_id) {
+            return true;
+        }
+
+        match join_token.map(|token| self.tokens.verify(token)) {
+            Some(Ok(_)) => true,
+            Some(Err(e)) => {
+                debug!("Join token from node {} rejected: {}", node_id, e);
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+/// Join token settings of a discovery service
+#[derive(Clone, Default)]
+struct JoinSettings {
+    /// Token this node presents in its announces
+    token: Option<String>,
+    /// Admission policy for unknown nodes
+    policy: Option<JoinPolicy>,
+}
+
 /// Node discovery service
 pub struct DiscoveryService {
     config: DiscoveryConfig,
+    join: JoinSettings,
+    wire_format: WireFormat,
+    peer_versions: PeerVersions,
     peers: Arc<RwLock<HashMap<u64, PeerState>>>,
     socket: Arc<UdpSocket>,
     running: Arc<RwLock<bool>>,
+    clock_skew: Option<ClockSkewMonitor>,
 }
 
 impl DiscoveryService {
@@ -169,12 +235,61 @@ impl DiscoveryService {
 
         Ok(Self {
             config,
+            join: JoinSettings::default(),
+            wire_format: WireFormat::default(),
+            peer_versions: PeerVersions::new(),
             peers: Arc::new(RwLock::new(HashMap::new())),
             socket: Arc::new(socket),
             running: Arc::new(RwLock::new(false)),
+            clock_skew: None,
         })
     }
 
+    /// Present a join token in announces from this node
+    pub fn with_join_token(mut self, token: String) -> Self {
+        self.join.token = Some(token);
+        self
+    }
+
+    /// Require unknown nodes to pass `policy` before they are tracked as peers
+    pub fn with_join_policy(mut self, policy: JoinPolicy) -> Self {
+        self.join.policy = Some(policy);
+        self
+    }
+
+    /// Send messages in `format`
+    ///
+    /// Messages in either format are always accepted, and announces are answered in
+    /// the format they arrived in.
+    pub fn with_wire_format(mut self, format: WireFormat) -> Self {
+        self.wire_format = format;
+        self
+    }
+
+    /// Record the wire versions peers' envelopes carry in `peer_versions`
+    ///
+    /// Peers sending bincode are not recorded; their version is learned elsewhere
+    /// or they count as legacy.
+    pub fn with_peer_versions(mut self, peer_versions: PeerVersions) -> Self {
+        self.peer_versions = peer_versions;
+        self
+    }
+
+    /// Record the clock offsets of peers' heartbeats in `monitor`
+    pub fn with_clock_skew(mut self, monitor: ClockSkewMonitor) -> Self {
+        self.clock_skew = Some(monitor);
+        self
+    }
+
+    /// Get this node's own peer information
+    pub fn local_info(&self) -> PeerInfo {
+        PeerInfo {
+            node_id: self.config.node_id,
+            raft_addr: self.config.raft_addr,
+            client_addr: self.config.client_addr,
+        }
+    }
+
     /// Start the discovery service
     pub async fn start(&self) -> Result<()> {
         {
@@ -198,12 +313,24 @@ impl DiscoveryService {
         // Spawn background tasks
         let peers_clone = Arc::clone(&self.peers);
         let config_clone = self.config.clone();
+        let join_clone = self.join.clone();
+        let versions_clone = self.peer_versions.clone();
         let socket_clone = Arc::clone(&self.socket);
         let running_clone = Arc::clone(&self.running);
+        let skew_clone = self.clock_skew.clone();
 
         // Receiver task
         tokio::spawn(async move {
-            Self::receiver_loop(peers_clone, config_clone, socket_clone, running_clone).await;
+            Self::receiver_loop(
+                peers_clone,
+                config_clone,
+                join_clone,
+                versions_clone,
+                socket_clone,
+                running_clone,
+                skew_clone,
+            )
+            .await;
         });
 
         // Heartbeat task
@@ -258,6 +385,7 @@ impl DiscoveryService {
             raft_addr: self.config.raft_addr,
             client_addr: self.config.client_addr,
             cluster_secret: self.config.cluster_secret.clone(),
+            join_token: self.join.token.clone(),
         };
 
         self.broadcast_message(&msg)?;
@@ -270,6 +398,7 @@ impl DiscoveryService {
         let msg = DiscoveryMessage::Heartbeat {
             node_id: self.config.node_id,
             cluster_secret: self.config.cluster_secret.clone(),
+            sent_at_ms: Some(now_ms()),
         };
 
         self.broadcast_message(&msg)?;
@@ -279,9 +408,7 @@ impl DiscoveryService {
 
     /// Broadcast a discovery message
     fn broadcast_message(&self, msg: &DiscoveryMessage) -> Result<()> {
-        let data = bincode::serialize(msg).map_err(|e| {
-            ScribeError::Serialization(format!("Failed to serialize message: {}", e))
-        })?;
+        let data = encode_message(msg, self.wire_format)?;
 
         if data.len() > MAX_UDP_PACKET_SIZE {
             return Err(ScribeError::Discovery(format!(
@@ -322,8 +449,11 @@ impl DiscoveryService {
     async fn receiver_loop(
         peers: Arc<RwLock<HashMap<u64, PeerState>>>,
         config: DiscoveryConfig,
+        join: JoinSettings,
+        peer_versions: PeerVersions,
         socket: Arc<UdpSocket>,
         running: Arc<RwLock<bool>>,
+        clock_skew: Option<ClockSkewMonitor>,
     ) {
         let mut buf = vec![0u8; MAX_UDP_PACKET_SIZE];
 
@@ -338,11 +468,29 @@ impl DiscoveryService {
 
             // Try to receive message
             match socket.recv_from(&mut buf) {
-                Ok((size, from_addr)) => {
-                    if let Ok(msg) = bincode::deserialize::<DiscoveryMessage>(&buf[..size]) {
-                        Self::handle_message(&peers, &config, &msg, &socket, from_addr);
+                Ok((size, from_addr)) => match decode_message(&buf[..size]) {
+                    Ok((msg, format, version)) => {
+                        let received_at_ms = now_ms();
+                        let sender = Self::handle_message(
+                            &peers, &config, &join, &msg, format, &socket, from_addr,
+                        );
+                        if let (Some(node_id), Some(version)) = (sender, version) {
+                            peer_versions.record(node_id, version);
+                        }
+                        if let (
+                            Some(node_id),
+                            Some(monitor),
+                            DiscoveryMessage::Heartbeat {
+                                sent_at_ms: Some(sent_at_ms),
+                                ..
+                            },
+                        ) = (sender, &clock_skew, &msg)
+                        {
+                            monitor.record(node_id, *sent_at_ms, received_at_ms);
+                        }
                     }
-                }
+                    Err(e) => debug!("Ignoring discovery message from {}: {}", from_addr, e),
+                },
                 Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
                     // No data available, sleep briefly
                     sleep(Duration::from_millis(10)).await;
@@ -358,23 +506,28 @@ impl DiscoveryService {
     }
 
     /// Handle incoming discovery message
+    ///
+    /// Returns the node an announce or heartbeat was accepted from.
     fn handle_message(
         peers: &Arc<RwLock<HashMap<u64, PeerState>>>,
         config: &DiscoveryConfig,
+        join: &JoinSettings,
         msg: &DiscoveryMessage,
+        format: WireFormat,
         socket: &Arc<UdpSocket>,
         from_addr: SocketAddr,
-    ) {
+    ) -> Option<u64> {
         match msg {
             DiscoveryMessage::Announce {
                 node_id,
                 raft_addr,
                 client_addr,
                 cluster_secret,
+                join_token,
             } => {
                 // Ignore our own announces
                 if *node_id == config.node_id {
-                    return;
+                    return None;
                 }
 
                 // Validate cluster secret if configured
@@ -383,7 +536,20 @@ impl DiscoveryService {
                         "Rejected announce from node {} - invalid cluster secret",
                         node_id
                     );
-                    return;
+                    return None;
+                }
+
+                // Unknown nodes must pass the join policy, if one is configured
+                if let Some(policy) = &join.policy {
+                    let is_known = peers.read().unwrap().contains_key(node_id);
+                    if !is_known && !policy.admits(config.node_id, *node_id, join_token.as_deref())
+                    {
+                        warn!(
+                            "Rejected announce from node {} - missing or invalid join token",
+                            node_id
+                        );
+                        return None;
+                    }
                 }
 
                 let mut peers_map = peers.write().unwrap();
@@ -413,10 +579,11 @@ impl DiscoveryService {
                         raft_addr: config.raft_addr,
                         client_addr: config.client_addr,
                         cluster_secret: config.cluster_secret.clone(),
+                        join_token: join.token.clone(),
                     };
 
-                    if let Ok(data) = bincode::serialize(&response) {
-                        // Send directly back to the sender's address
+                    if let Ok(data) = encode_message(&response, format) {
+                        // Send directly back to the sender's address, in a format it reads
                         let _ = socket.send_to(&data, from_addr);
                         debug!(
                             "Sent announce response to new peer {} at {}",
@@ -424,14 +591,16 @@ impl DiscoveryService {
                         );
                     }
                 }
+                Some(*node_id)
             }
             DiscoveryMessage::Heartbeat {
                 node_id,
                 cluster_secret,
+                ..
             } => {
                 // Ignore our own heartbeats
                 if *node_id == config.node_id {
-                    return;
+                    return None;
                 }
 
                 // Validate cluster secret if configured
@@ -440,15 +609,17 @@ impl DiscoveryService {
                         "Rejected heartbeat from node {} - invalid cluster secret",
                         node_id
                     );
-                    return;
+                    return None;
                 }
 
                 let mut peers_map = peers.write().unwrap();
                 if let Some(state) = peers_map.get_mut(node_id) {
                     state.last_seen = Instant::now();
                     debug!("Received heartbeat from node {}", node_id);
+                    Some(*node_id)
                 } else {
                     debug!("Received heartbeat from unknown node {}, ignoring", node_id);
+                    None
                 }
             }
             DiscoveryMessage::PeerListRequest {
@@ -461,17 +632,19 @@ impl DiscoveryService {
                         "Rejected peer list request from node {} - invalid cluster secret",
                         node_id
                     );
-                    return;
+                    return None;
                 }
 
                 debug!("Received peer list request from node {}", node_id);
                 // Implementation for peer list exchange would go here
                 // For now, receiving an announce is sufficient for discovery
+                None
             }
             DiscoveryMessage::PeerListResponse { peers: _peer_list } => {
                 debug!("Received peer list response");
                 // Implementation for peer list exchange would go here
                 // For now, direct announces are sufficient
+                None
             }
         }
     }
@@ -527,6 +700,9 @@ impl DiscoveryService {
 
             for node_id in dead_peers {
                 peers.remove(&node_id);
+                if let Some(monitor) = &self.clock_skew {
+                    monitor.remove(node_id);
+                }
                 warn!("Removed dead peer node {}", node_id);
             }
         }
@@ -552,16 +728,52 @@ impl DiscoveryService {
     fn clone_for_task(&self) -> Self {
         Self {
             config: self.config.clone(),
+            join: self.join.clone(),
+            wire_format: self.wire_format,
+            peer_versions: self.peer_versions.clone(),
             peers: Arc::clone(&self.peers),
             socket: Arc::clone(&self.socket),
             running: Arc::clone(&self.running),
+            clock_skew: self.clock_skew.clone(),
         }
     }
 }
 
+/// Current time in milliseconds since the Unix epoch
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Encode a discovery message in `format`
+fn encode_message(msg: &DiscoveryMessage, format: WireFormat) -> Result<Vec<u8>> {
+    match format {
+        WireFormat::Protobuf => Ok(wire::seal(msg)),
+        WireFormat::Bincode => bincode::serialize(msg)
+            .map_err(|e| ScribeError::Serialization(format!("Failed to serialize message: {}", e))),
+    }
+}
+
+/// Decode a discovery message in either format
+///
+/// Returns the format it was in and, for an envelope, the sender's wire version.
+fn decode_message(data: &[u8]) -> Result<(DiscoveryMessage, WireFormat, Option<u32>)> {
+    if wire::is_envelope(data) {
+        let envelope = wire::Envelope::from_bytes(data)?;
+        let msg = envelope.open()?;
+        return Ok((msg, WireFormat::Protobuf, Some(envelope.version)));
+    }
+    let msg = bincode::deserialize(data)
+        .map_err(|e| ScribeError::Serialization(format!("Failed to deserialize message: {}", e)))?;
+    Ok((msg, WireFormat::Bincode, None))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::wire::WireMessage;
 
     // Test constants to avoid hardcoded values
     const TEST_NODE_ID: u64 = 1;
@@ -612,6 +824,7 @@ mod tests {
             raft_addr: test_raft_addr(TEST_RAFT_PORT),
             client_addr: test_client_addr(TEST_CLIENT_PORT),
             cluster_secret: None,
+            join_token: None,
         };
 
         let serialized = bincode::serialize(&msg).unwrap();
@@ -625,6 +838,7 @@ mod tests {
         let msg = DiscoveryMessage::Heartbeat {
             node_id: TEST_HEARTBEAT_NODE_ID,
             cluster_secret: None,
+            sent_at_ms: None,
         };
 
         let serialized = bincode::serialize(&msg).unwrap();
@@ -716,6 +930,56 @@ mod tests {
         assert!(!service.is_peer_alive(TEST_NONEXISTENT_NODE_ID));
     }
 
+    #[tokio::test]
+    async fn test_handle_message_reports_sender() {
+        let config = DiscoveryConfig {
+            node_id: TEST_NODE_ID,
+            discovery_port: 17952,
+            broadcast_addr: TEST_IP.to_string(),
+            ..DiscoveryConfig::default()
+        };
+        let service = DiscoveryService::new(config.clone()).unwrap();
+        let join = JoinSettings::default();
+        let from_addr = test_raft_addr(TEST_RAFT_PORT_2);
+        let handle = |msg: &DiscoveryMessage| {
+            DiscoveryService::handle_message(
+                &service.peers,
+                &config,
+                &join,
+                msg,
+                WireFormat::Protobuf,
+                &service.socket,
+                from_addr,
+            )
+        };
+
+        let heartbeat = DiscoveryMessage::Heartbeat {
+            node_id: TEST_NODE_ID_2,
+            cluster_secret: None,
+            sent_at_ms: None,
+        };
+        // Heartbeats only count from known peers
+        assert_eq!(handle(&heartbeat), None);
+
+        let announce = DiscoveryMessage::Announce {
+            node_id: TEST_NODE_ID_2,
+            raft_addr: test_raft_addr(TEST_RAFT_PORT_2),
+            client_addr: test_client_addr(TEST_CLIENT_PORT_2),
+            cluster_secret: None,
+            join_token: None,
+        };
+        assert_eq!(handle(&announce), Some(TEST_NODE_ID_2));
+        assert_eq!(handle(&heartbeat), Some(TEST_NODE_ID_2));
+
+        // Messages failing the secret check are not attributed to their sender
+        let heartbeat = DiscoveryMessage::Heartbeat {
+            node_id: TEST_NODE_ID_2,
+            cluster_secret: Some("wrong".to_string()),
+            sent_at_ms: None,
+        };
+        assert_eq!(handle(&heartbeat), None);
+    }
+
     #[tokio::test]
     async fn test_peer_expiration() {
         let config = DiscoveryConfig {
@@ -795,9 +1059,109 @@ mod tests {
             raft_addr: test_raft_addr(TEST_RAFT_PORT),
             client_addr: test_client_addr(TEST_CLIENT_PORT),
             cluster_secret: None,
+            join_token: None,
         };
 
         let serialized = bincode::serialize(&msg).unwrap();
         assert!(serialized.len() <= MAX_UDP_PACKET_SIZE);
+
+        // Announces carrying a secret and a join token must still fit
+        let tokens = JoinTokenManager::new("scribe-ledger", "secret").unwrap();
+        let (token, _) = tokens.issue(Duration::from_secs(3600)).unwrap();
+        let msg = DiscoveryMessage::Announce {
+            node_id: TEST_NODE_ID,
+            raft_addr: test_raft_addr(TEST_RAFT_PORT),
+            client_addr: test_client_addr(TEST_CLIENT_PORT),
+            cluster_secret: Some("a".repeat(64)),
+            join_token: Some(token),
+        };
+
+        let serialized = bincode::serialize(&msg).unwrap();
+        assert!(serialized.len() <= MAX_UDP_PACKET_SIZE);
+        let sealed = encode_message(&msg, WireFormat::Protobuf).unwrap();
+        assert!(sealed.len() <= MAX_UDP_PACKET_SIZE);
+    }
+
+    #[test]
+    fn test_decode_either_wire_format() {
+        let msg = DiscoveryMessage::Heartbeat {
+            node_id: TEST_HEARTBEAT_NODE_ID,
+            cluster_secret: Some("secret".to_string()),
+            sent_at_ms: None,
+        };
+
+        for format in [WireFormat::Protobuf, WireFormat::Bincode] {
+            let data = encode_message(&msg, format).unwrap();
+            let version = (format == WireFormat::Protobuf).then_some(wire::WIRE_VERSION);
+            assert_eq!(
+                decode_message(&data).unwrap(),
+                (msg.clone(), format, version)
+            );
+        }
+
+        // Envelopes a node cannot read are rejected, not misread as bincode
+        let mut envelope = wire::Envelope::new(&msg);
+        envelope.min_reader_version = wire::WIRE_VERSION + 1;
+        assert!(decode_message(&envelope.to_bytes()).is_err());
+        assert!(decode_message(&[0xff; 8]).is_err());
+
+        // Join tokens only travel in envelopes; bincode announces are unchanged
+        let announce = |join_token| DiscoveryMessage::Announce {
+            node_id: TEST_NODE_ID,
+            raft_addr: test_raft_addr(TEST_RAFT_PORT),
+            client_addr: test_client_addr(TEST_CLIENT_PORT),
+            cluster_secret: None,
+            join_token,
+        };
+        let msg = announce(Some("token".to_string()));
+        let (decoded, _, _) =
+            decode_message(&encode_message(&msg, WireFormat::Protobuf).unwrap()).unwrap();
+        assert_eq!(decoded, msg);
+        let bincode = encode_message(&msg, WireFormat::Bincode).unwrap();
+        assert_eq!(
+            bincode,
+            encode_message(&announce(None), WireFormat::Bincode).unwrap()
+        );
+        assert_eq!(decode_message(&bincode).unwrap().0, announce(None));
+
+        // The heartbeat clock only travels in envelopes
+        let msg = DiscoveryMessage::Heartbeat {
+            node_id: TEST_HEARTBEAT_NODE_ID,
+            cluster_secret: None,
+            sent_at_ms: Some(1_700_000_000_000),
+        };
+        let (decoded, _, _) =
+            decode_message(&encode_message(&msg, WireFormat::Protobuf).unwrap()).unwrap();
+        assert_eq!(decoded, msg);
+        let (decoded, _, _) =
+            decode_message(&encode_message(&msg, WireFormat::Bincode).unwrap()).unwrap();
+        assert!(matches!(
+            decoded,
+            DiscoveryMessage::Heartbeat {
+                sent_at_ms: None,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_join_policy_admits() {
+        let tokens = Arc::new(JoinTokenManager::new("scribe-ledger", "secret").unwrap());
+        let (token, _) = tokens.issue(Duration::from_secs(60)).unwrap();
+
+        let policy = JoinPolicy::new(tokens, |node_id| {
+            node_id == TEST_NODE_ID || node_id == TEST_HEARTBEAT_NODE_ID
+        });
+
+        // Unknown node needs a valid token
+        assert!(!policy.admits(TEST_NODE_ID, TEST_NODE_ID_2, None));
+        assert!(!policy.admits(TEST_NODE_ID, TEST_NODE_ID_2, Some("bogus")));
+        assert!(policy.admits(TEST_NODE_ID, TEST_NODE_ID_2, Some(&token)));
+
+        // Members are always admitted
+        assert!(policy.admits(TEST_NODE_ID, TEST_HEARTBEAT_NODE_ID, None));
+
+        // Nodes outside any cluster do not gate announces
+        assert!(policy.admits(TEST_NONEXISTENT_NODE_ID, TEST_NODE_ID_2, None));
     }
 }
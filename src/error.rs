@@ -2,6 +2,7 @@
 //!
 //! This module defines all error types that can occur in the distributed ledger system.
 
+use crate::types::NodeId;
 use thiserror::Error;
 
 /// Main error type for Scribe Ledger operations
@@ -43,6 +44,33 @@ pub enum ScribeError {
     #[error("Manifest error: {0}")]
     Manifest(String),
 
+    /// Request must be served by the Raft leader
+    #[error("Not leader (current leader: {leader_id:?})")]
+    NotLeader {
+        /// Leader known to this node, if any
+        leader_id: Option<NodeId>,
+    },
+
+    /// Encryption and key management errors
+    #[error("Encryption error: {0}")]
+    Encryption(String),
+
+    /// Operation did not complete in time
+    #[error("Timeout: {0}")]
+    Timeout(String),
+
+    /// Request conflicts with the current state (e.g. a lock held by someone else)
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    /// Operation needs a wire version not every cluster member speaks yet
+    #[error("Unsupported operation: {0}")]
+    Unsupported(String),
+
+    /// Stored data failed its integrity check (e.g. a value checksum mismatch)
+    #[error("Data corruption: {0}")]
+    DataCorruption(String),
+
     /// Cluster initialization and management errors
     #[error("Cluster error: {0}")]
     Cluster(String),
@@ -103,6 +131,9 @@ mod tests {
 
         let err = ScribeError::Serialization("test serialization error".to_string());
         assert!(err.to_string().contains("Serialization error"));
+
+        let err = ScribeError::DataCorruption("checksum mismatch".to_string());
+        assert!(err.to_string().contains("Data corruption"));
     }
 
     #[test]
@@ -146,4 +177,39 @@ mod tests {
         assert!(err.to_string().contains("Cluster error"));
         assert!(err.to_string().contains("test cluster error"));
     }
+
+    #[test]
+    fn test_timeout_error() {
+        let err = ScribeError::Timeout("log index 7 not applied".to_string());
+        assert!(err.to_string().contains("Timeout"));
+        assert!(err.to_string().contains("log index 7"));
+    }
+
+    #[test]
+    fn test_conflict_error() {
+        let err = ScribeError::Conflict("lock jobs is held by worker-1".to_string());
+        assert!(err.to_string().contains("Conflict"));
+        assert!(err.to_string().contains("worker-1"));
+    }
+
+    #[test]
+    fn test_unsupported_error() {
+        let err = ScribeError::Unsupported("dequeue needs wire version 2".to_string());
+        assert!(err.to_string().contains("Unsupported operation"));
+        assert!(err.to_string().contains("wire version 2"));
+    }
+
+    #[test]
+    fn test_encryption_error() {
+        let err = ScribeError::Encryption("data key acme-1 is missing".to_string());
+        assert!(err.to_string().contains("Encryption error"));
+        assert!(err.to_string().contains("acme-1"));
+    }
+
+    #[test]
+    fn test_not_leader_error() {
+        let err = ScribeError::NotLeader { leader_id: Some(2) };
+        assert!(err.to_string().contains("Not leader"));
+        assert!(err.to_string().contains("Some(2)"));
+    }
 }
@@ -0,0 +1,309 @@
+//! Bounded history of recent errors per subsystem
+//!
+//! When an alert fires, operators want the last few failures of the affected
+//! subsystem without searching the logs. [`ErrorHistoryLayer`] is a tracing layer
+//! that copies every warning and error logged by the storage, consensus, discovery
+//! and archival code into an in-memory ring buffer per subsystem; the node serves the
+//! buffers at `GET /admin/errors`. The history is local to the node and lost on
+//! restart.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+
+/// Errors kept per subsystem by default
+pub const DEFAULT_ERRORS_PER_SUBSYSTEM: usize = 100;
+
+/// Subsystem an error was logged by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Subsystem {
+    /// Local storage: sled, segments and the storage layer
+    Storage,
+    /// Raft consensus and cluster membership
+    Consensus,
+    /// Peer discovery
+    Discovery,
+    /// S3 archival and the archive mirror
+    Archival,
+}
+
+impl Subsystem {
+    /// Subsystem of a tracing target (module path), if it belongs to one
+    pub fn from_target(target: &str) -> Option<Self> {
+        let (krate, path) = target.split_once("::").unwrap_or((target, ""));
+        match krate {
+            "openraft" => return Some(Subsystem::Consensus),
+            "sled" => return Some(Subsystem::Storage),
+            "hyra_scribe_ledger" => {}
+            _ => return None,
+        }
+        let module = |name: &str| path == name || path.starts_with(&format!("{}::", name));
+        if module("storage::archival") || module("storage::s3") || module("mirror") {
+            Some(Subsystem::Archival)
+        } else if module("storage") || module("storage_ops") || module("async_storage_ops") {
+            Some(Subsystem::Storage)
+        } else if module("consensus") || module("cluster") {
+            Some(Subsystem::Consensus)
+        } else if module("discovery") {
+            Some(Subsystem::Discovery)
+        } else {
+            None
+        }
+    }
+}
+
+/// One logged error
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorRecord {
+    /// Time the error was logged (milliseconds since the Unix epoch)
+    pub timestamp_ms: u64,
+    /// Subsystem that logged it
+    pub subsystem: Subsystem,
+    /// `error` or `warn`
+    pub level: String,
+    /// Module that logged it
+    pub target: String,
+    /// Log message
+    pub message: String,
+    /// Structured fields of the log event
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, String>,
+}
+
+/// Query of the error history
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ErrorFilter {
+    /// Only errors of this subsystem
+    pub subsystem: Option<Subsystem>,
+    /// Only errors of this level (`error` or `warn`)
+    pub level: Option<String>,
+    /// Maximum number of errors to return (defaults to all retained)
+    pub limit: Option<usize>,
+}
+
+/// Recent errors, newest first, with the number logged since startup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorReport {
+    /// Errors logged per subsystem since startup, including evicted ones
+    pub totals: BTreeMap<Subsystem, u64>,
+    /// Retained errors matching the query, newest first
+    pub errors: Vec<ErrorRecord>,
+}
+
+#[derive(Default)]
+struct Buffers {
+    recent: HashMap<Subsystem, VecDeque<ErrorRecord>>,
+    totals: BTreeMap<Subsystem, u64>,
+}
+
+/// Ring buffers of the last errors of each subsystem
+///
+/// Clones share the buffers.
+#[derive(Clone)]
+pub struct ErrorHistory {
+    buffers: Arc<Mutex<Buffers>>,
+    capacity: usize,
+}
+
+impl ErrorHistory {
+    /// Create a history keeping the last `capacity` errors of each subsystem
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buffers: Arc::new(Mutex::new(Buffers::default())),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Tracing layer recording into this history
+    pub fn layer(&self) -> ErrorHistoryLayer {
+        ErrorHistoryLayer {
+            history: self.clone(),
+        }
+    }
+
+    /// Record an error, evicting the subsystem's oldest one when full
+    pub fn record(&self, record: ErrorRecord) {
+        let mut buffers = self.buffers.lock().unwrap_or_else(|e| e.into_inner());
+        *buffers.totals.entry(record.subsystem).or_default() += 1;
+        let recent = buffers.recent.entry(record.subsystem).or_default();
+        if recent.len() == self.capacity {
+            recent.pop_front();
+        }
+        recent.push_back(record);
+    }
+
+    /// Retained errors matching `filter`, newest first
+    pub fn query(&self, filter: &ErrorFilter) -> ErrorReport {
+        let buffers = self.buffers.lock().unwrap_or_else(|e| e.into_inner());
+        let mut errors: Vec<ErrorRecord> = buffers
+            .recent
+            .iter()
+            .filter(|(subsystem, _)| filter.subsystem.is_none_or(|s| s == **subsystem))
+            .flat_map(|(_, recent)| recent.iter())
+            .filter(|record| filter.level.as_ref().is_none_or(|l| *l == record.level))
+            .cloned()
+            .collect();
+        errors.sort_by_key(|record| std::cmp::Reverse(record.timestamp_ms));
+        if let Some(limit) = filter.limit {
+            errors.truncate(limit);
+        }
+        ErrorReport {
+            totals: buffers.totals.clone(),
+            errors,
+        }
+    }
+}
+
+impl Default for ErrorHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_ERRORS_PER_SUBSYSTEM)
+    }
+}
+
+/// Tracing layer copying warnings and errors into an [`ErrorHistory`]
+pub struct ErrorHistoryLayer {
+    history: ErrorHistory,
+}
+
+impl<S: Subscriber> Layer<S> for ErrorHistoryLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if *metadata.level() > Level::WARN {
+            return;
+        }
+        let Some(subsystem) = Subsystem::from_target(metadata.target()) else {
+            return;
+        };
+
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        self.history.record(ErrorRecord {
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_millis() as u64)
+                .unwrap_or(0),
+            subsystem,
+            level: metadata.level().as_str().to_ascii_lowercase(),
+            target: metadata.target().to_string(),
+            message: visitor.message,
+            fields: visitor.fields,
+        });
+    }
+}
+
+/// Collects the message and other fields of an event
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    fields: BTreeMap<String, String>,
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message = value.to_string(),
+            name => {
+                self.fields.insert(name.to_string(), value.to_string());
+            }
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        match field.name() {
+            "message" => self.message = format!("{:?}", value),
+            name => {
+                self.fields.insert(name.to_string(), format!("{:?}", value));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_subsystem_from_target() {
+        let subsystem = Subsystem::from_target;
+        assert_eq!(
+            subsystem("hyra_scribe_ledger::storage::s3"),
+            Some(Subsystem::Archival)
+        );
+        assert_eq!(
+            subsystem("hyra_scribe_ledger::storage::segment"),
+            Some(Subsystem::Storage)
+        );
+        assert_eq!(
+            subsystem("hyra_scribe_ledger::storage_ops"),
+            Some(Subsystem::Storage)
+        );
+        assert_eq!(
+            subsystem("hyra_scribe_ledger::consensus::network"),
+            Some(Subsystem::Consensus)
+        );
+        assert_eq!(
+            subsystem("openraft::core::raft_core"),
+            Some(Subsystem::Consensus)
+        );
+        assert_eq!(
+            subsystem("hyra_scribe_ledger::discovery"),
+            Some(Subsystem::Discovery)
+        );
+        assert_eq!(subsystem("hyra_scribe_ledger::mirrored"), None);
+        assert_eq!(subsystem("hyra_scribe_ledger::api"), None);
+        assert_eq!(subsystem("scribe_node"), None);
+    }
+
+    #[test]
+    fn test_layer_records_bounded_history() {
+        let history = ErrorHistory::new(2);
+        let subscriber = tracing_subscriber::registry().with(history.layer());
+        tracing::subscriber::with_default(subscriber, || {
+            for i in 0..3 {
+                tracing::warn!(target: "hyra_scribe_ledger::discovery", "peer {} unreachable", i);
+            }
+            tracing::error!(
+                target: "hyra_scribe_ledger::storage::s3",
+                segment_id = 7,
+                "upload failed"
+            );
+            tracing::info!(target: "hyra_scribe_ledger::discovery", "not an error");
+            tracing::error!(target: "hyra_scribe_ledger::api", "not a subsystem");
+        });
+
+        let report = history.query(&ErrorFilter::default());
+        assert_eq!(report.totals[&Subsystem::Discovery], 3);
+        assert_eq!(report.totals[&Subsystem::Archival], 1);
+        assert_eq!(report.errors.len(), 3);
+
+        let discovery = history.query(&ErrorFilter {
+            subsystem: Some(Subsystem::Discovery),
+            ..ErrorFilter::default()
+        });
+        let messages: Vec<&str> = discovery
+            .errors
+            .iter()
+            .map(|record| record.message.as_str())
+            .collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages.contains(&"peer 1 unreachable"));
+        assert!(messages.contains(&"peer 2 unreachable"));
+
+        let errors = history.query(&ErrorFilter {
+            level: Some("error".to_string()),
+            limit: Some(5),
+            ..ErrorFilter::default()
+        });
+        assert_eq!(errors.errors.len(), 1);
+        assert_eq!(errors.errors[0].subsystem, Subsystem::Archival);
+        assert_eq!(errors.errors[0].fields["segment_id"], "7");
+    }
+}
@@ -0,0 +1,749 @@
+//! Composite node health score and self-healing hooks
+//!
+//! [`HealthMonitor`] turns readings of the node's subsystems ([`HealthSignals`]) into
+//! a score from 0 to 100 per [`HealthComponent`] and a weighted composite score,
+//! exported as the `scribe_ledger_node_health_score` and
+//! `scribe_ledger_node_health_component_score` gauges.
+//!
+//! Self-healing actions are registered under a name with
+//! [`HealthMonitor::with_action`]; the node registers [`RESTART_ARCHIVAL`],
+//! [`CLEAR_CACHE`] and [`STEP_DOWN`]. [`HealingRule`]s in the configuration bind an
+//! action to a score threshold: while the composite score, or the score of the named
+//! component, is below the threshold the action runs, at most once per cooldown.
+
+use crate::error::{Result, ScribeError};
+use crate::metrics::{HEALING_ACTIONS, NODE_HEALTH, NODE_HEALTH_COMPONENT, NODE_HEALTH_SCORE};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// Action re-running interrupted-roll recovery and an archival pass
+pub const RESTART_ARCHIVAL: &str = "restart_archival";
+
+/// Action emptying the hot data cache
+pub const CLEAR_CACHE: &str = "clear_cache";
+
+/// Action handing leadership to another node
+pub const STEP_DOWN: &str = "step_down";
+
+/// Highest score
+const MAX_SCORE: u32 = 100;
+
+/// Subsystem scored separately
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthComponent {
+    /// The local database accepts writes
+    Storage,
+    /// A leader is known and the applied log keeps up with it
+    Consensus,
+    /// Segments waiting to be archived
+    Archival,
+    /// Free space on the data directory's file system
+    Disk,
+    /// Discovered peers that are alive
+    Peers,
+}
+
+impl HealthComponent {
+    /// Every component, in report order
+    pub const ALL: [HealthComponent; 5] = [
+        HealthComponent::Storage,
+        HealthComponent::Consensus,
+        HealthComponent::Archival,
+        HealthComponent::Disk,
+        HealthComponent::Peers,
+    ];
+
+    /// Name used in metrics labels and reports
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HealthComponent::Storage => "storage",
+            HealthComponent::Consensus => "consensus",
+            HealthComponent::Archival => "archival",
+            HealthComponent::Disk => "disk",
+            HealthComponent::Peers => "peers",
+        }
+    }
+}
+
+/// Weight of each component in the composite score
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthWeights {
+    /// Weight of [`HealthComponent::Storage`]
+    pub storage: u32,
+    /// Weight of [`HealthComponent::Consensus`]
+    pub consensus: u32,
+    /// Weight of [`HealthComponent::Archival`]
+    pub archival: u32,
+    /// Weight of [`HealthComponent::Disk`]
+    pub disk: u32,
+    /// Weight of [`HealthComponent::Peers`]
+    pub peers: u32,
+}
+
+impl Default for HealthWeights {
+    fn default() -> Self {
+        Self {
+            storage: 3,
+            consensus: 3,
+            archival: 1,
+            disk: 2,
+            peers: 1,
+        }
+    }
+}
+
+impl HealthWeights {
+    /// Weight of `component`
+    pub fn of(&self, component: HealthComponent) -> u32 {
+        match component {
+            HealthComponent::Storage => self.storage,
+            HealthComponent::Consensus => self.consensus,
+            HealthComponent::Archival => self.archival,
+            HealthComponent::Disk => self.disk,
+            HealthComponent::Peers => self.peers,
+        }
+    }
+}
+
+/// Self-healing action bound to a score threshold
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealingRule {
+    /// Name of the registered action to run
+    pub action: String,
+    /// Component whose score is checked; the composite score if unset
+    #[serde(default)]
+    pub component: Option<HealthComponent>,
+    /// Score below which the action runs
+    pub below: u32,
+    /// Minimum seconds between two runs of the action by this rule
+    #[serde(default = "default_healing_cooldown_secs")]
+    pub cooldown_secs: u64,
+}
+
+impl HealingRule {
+    /// Score of `report` the rule checks
+    fn score(&self, report: &HealthReport) -> u32 {
+        match self.component {
+            Some(component) => report.component(component).map_or(MAX_SCORE, |c| c.score),
+            None => report.score,
+        }
+    }
+
+    /// Description of the breached threshold, e.g. `archival < 50`
+    fn trigger(&self) -> String {
+        let scope = self
+            .component
+            .map_or("score", |component| component.as_str());
+        format!("{} < {}", scope, self.below)
+    }
+}
+
+/// Health scoring configuration (`[health]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthConfig {
+    /// Score the node periodically
+    #[serde(default = "default_health_enabled")]
+    pub enabled: bool,
+    /// Seconds between two evaluations
+    #[serde(default = "default_health_interval_secs")]
+    pub interval_secs: u64,
+    /// Composite score below which `scribe_ledger_node_health` reports 0
+    #[serde(default = "default_min_healthy_score")]
+    pub min_healthy_score: u32,
+    /// Applied-index lag behind the last log index at which consensus scores 50
+    #[serde(default = "default_max_apply_lag")]
+    pub max_apply_lag: u64,
+    /// Segments waiting to be archived at which archival scores 0
+    #[serde(default = "default_max_archival_backlog")]
+    pub max_archival_backlog: usize,
+    /// Share of free disk space below which the disk score drops
+    #[serde(default = "default_disk_low_ratio")]
+    pub disk_low_ratio: f64,
+    /// Share of free disk space at which the disk scores 0
+    #[serde(default = "default_disk_critical_ratio")]
+    pub disk_critical_ratio: f64,
+    /// Weight of each component in the composite score
+    #[serde(default)]
+    pub weights: HealthWeights,
+    /// Self-healing actions and their thresholds
+    #[serde(default)]
+    pub actions: Vec<HealingRule>,
+}
+
+fn default_health_enabled() -> bool {
+    true
+}
+
+fn default_health_interval_secs() -> u64 {
+    15
+}
+
+fn default_min_healthy_score() -> u32 {
+    50
+}
+
+fn default_max_apply_lag() -> u64 {
+    1000
+}
+
+fn default_max_archival_backlog() -> usize {
+    100
+}
+
+fn default_disk_low_ratio() -> f64 {
+    0.2
+}
+
+fn default_disk_critical_ratio() -> f64 {
+    0.05
+}
+
+fn default_healing_cooldown_secs() -> u64 {
+    300
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_health_enabled(),
+            interval_secs: default_health_interval_secs(),
+            min_healthy_score: default_min_healthy_score(),
+            max_apply_lag: default_max_apply_lag(),
+            max_archival_backlog: default_max_archival_backlog(),
+            disk_low_ratio: default_disk_low_ratio(),
+            disk_critical_ratio: default_disk_critical_ratio(),
+            weights: HealthWeights::default(),
+            actions: Vec::new(),
+        }
+    }
+}
+
+impl HealthConfig {
+    /// Check intervals, thresholds, weights and rules
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.interval_secs == 0 {
+            return Err("Health interval_secs must be greater than 0".to_string());
+        }
+        if self.min_healthy_score > MAX_SCORE {
+            return Err(format!(
+                "Health min_healthy_score must be at most {}",
+                MAX_SCORE
+            ));
+        }
+        if self.max_apply_lag == 0 || self.max_archival_backlog == 0 {
+            return Err(
+                "Health max_apply_lag and max_archival_backlog must be greater than 0".to_string(),
+            );
+        }
+        if !(0.0..1.0).contains(&self.disk_critical_ratio)
+            || !(self.disk_critical_ratio..=1.0).contains(&self.disk_low_ratio)
+            || self.disk_critical_ratio == self.disk_low_ratio
+        {
+            return Err(
+                "Health disk ratios must satisfy 0 <= disk_critical_ratio < disk_low_ratio <= 1"
+                    .to_string(),
+            );
+        }
+        if HealthComponent::ALL
+            .iter()
+            .all(|component| self.weights.of(*component) == 0)
+        {
+            return Err("At least one health weight must be greater than 0".to_string());
+        }
+        for rule in &self.actions {
+            if rule.action.is_empty() {
+                return Err("Health action names cannot be empty".to_string());
+            }
+            if rule.below == 0 || rule.below > MAX_SCORE {
+                return Err(format!(
+                    "Health action {} must have a threshold between 1 and {}",
+                    rule.action, MAX_SCORE
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Time between two evaluations
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs)
+    }
+}
+
+/// Readings of the node's subsystems, collected before each evaluation
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HealthSignals {
+    /// Why the last storage probe failed, if it did
+    pub storage_error: Option<String>,
+    /// Whether a leader is known
+    pub has_leader: bool,
+    /// Log entries not yet applied
+    pub apply_lag: u64,
+    /// Segments waiting to be archived (`None` without archival)
+    pub archival_backlog: Option<usize>,
+    /// Share of free space on the data directory's file system, if known
+    pub disk_free_ratio: Option<f64>,
+    /// Discovered peers
+    pub peers_total: usize,
+    /// Discovered peers seen recently
+    pub peers_alive: usize,
+}
+
+/// Score of one component
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ComponentHealth {
+    /// Component scored
+    pub component: HealthComponent,
+    /// Score from 0 (failed) to 100 (healthy)
+    pub score: u32,
+    /// What the score is based on
+    pub detail: String,
+}
+
+/// A self-healing action run by an evaluation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealingOutcome {
+    /// Action run
+    pub action: String,
+    /// Threshold whose breach triggered it, e.g. `archival < 50`
+    pub trigger: String,
+    /// Why the action failed, if it did
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Result of one evaluation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthReport {
+    /// Weighted composite score from 0 to 100
+    pub score: u32,
+    /// Whether the score reaches `min_healthy_score`
+    pub healthy: bool,
+    /// Score of each component
+    pub components: Vec<ComponentHealth>,
+    /// Self-healing actions triggered by this evaluation
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<HealingOutcome>,
+    /// Time of the evaluation (Unix seconds)
+    pub evaluated_at: u64,
+}
+
+impl HealthReport {
+    /// Score of `component`
+    pub fn component(&self, component: HealthComponent) -> Option<&ComponentHealth> {
+        self.components.iter().find(|c| c.component == component)
+    }
+}
+
+/// A registered self-healing action
+type HealingAction = Arc<dyn Fn() -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
+/// Periodic health scoring with self-healing hooks
+pub struct HealthMonitor {
+    config: HealthConfig,
+    actions: HashMap<String, HealingAction>,
+    /// Last run of each rule, by index in `config.actions`
+    last_runs: Mutex<HashMap<usize, Instant>>,
+    last_report: Mutex<Option<HealthReport>>,
+}
+
+impl HealthMonitor {
+    /// Create a monitor without actions
+    pub fn new(config: HealthConfig) -> Self {
+        Self {
+            config,
+            actions: HashMap::new(),
+            last_runs: Mutex::new(HashMap::new()),
+            last_report: Mutex::new(None),
+        }
+    }
+
+    /// Register `action` under `name`, replacing an action of the same name
+    pub fn with_action<F, Fut>(mut self, name: impl Into<String>, action: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let action: HealingAction = Arc::new(move || Box::pin(action()));
+        self.actions.insert(name.into(), action);
+        self
+    }
+
+    /// Fail if a rule names an action that was not registered
+    pub fn check_actions(&self) -> Result<()> {
+        match self
+            .config
+            .actions
+            .iter()
+            .find(|rule| !self.actions.contains_key(&rule.action))
+        {
+            Some(rule) => Err(ScribeError::Configuration(format!(
+                "Unknown health action {} (registered: {})",
+                rule.action,
+                self.action_names().join(", ")
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Names of the registered actions, sorted
+    pub fn action_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.actions.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Score `signals` without running actions or updating metrics
+    pub fn score(&self, signals: &HealthSignals) -> HealthReport {
+        let components: Vec<ComponentHealth> = HealthComponent::ALL
+            .iter()
+            .map(|component| self.score_component(*component, signals))
+            .collect();
+
+        let weights = &self.config.weights;
+        let total_weight: u32 = components.iter().map(|c| weights.of(c.component)).sum();
+        let weighted: u32 = components
+            .iter()
+            .map(|c| weights.of(c.component) * c.score)
+            .sum();
+        let score = (weighted + total_weight / 2)
+            .checked_div(total_weight)
+            .unwrap_or(MAX_SCORE);
+
+        HealthReport {
+            score,
+            healthy: score >= self.config.min_healthy_score,
+            components,
+            actions: Vec::new(),
+            evaluated_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        }
+    }
+
+    /// Score `signals` and run the actions whose threshold is breached
+    pub async fn evaluate(&self, signals: &HealthSignals) -> HealthReport {
+        let mut report = self.score(signals);
+        for (index, rule) in self.due_rules(&report) {
+            let Some(action) = self.actions.get(&rule.action) else {
+                continue;
+            };
+            let trigger = rule.trigger();
+            info!("Running health action {} ({})", rule.action, trigger);
+            let error = match action().await {
+                Ok(()) => None,
+                Err(e) => {
+                    warn!("Health action {} failed: {}", rule.action, e);
+                    Some(e.to_string())
+                }
+            };
+            HEALING_ACTIONS
+                .with_label_values(&[&rule.action, if error.is_none() { "ok" } else { "failed" }])
+                .inc();
+            self.last_runs.lock().unwrap().insert(index, Instant::now());
+            report.actions.push(HealingOutcome {
+                action: rule.action.clone(),
+                trigger,
+                error,
+            });
+        }
+
+        *self.last_report.lock().unwrap() = Some(report.clone());
+        report
+    }
+
+    /// Report of the last evaluation
+    pub fn last_report(&self) -> Option<HealthReport> {
+        self.last_report.lock().unwrap().clone()
+    }
+
+    /// Evaluate the signals returned by `collect` every interval
+    pub fn start<F, Fut>(self: &Arc<Self>, collect: F) -> tokio::task::JoinHandle<()>
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = HealthSignals> + Send,
+    {
+        let monitor = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(monitor.config.interval());
+            loop {
+                ticker.tick().await;
+                let signals = collect().await;
+                export_metrics(&monitor.evaluate(&signals).await);
+            }
+        })
+    }
+
+    /// Rules whose threshold is breached and whose cooldown has elapsed
+    fn due_rules(&self, report: &HealthReport) -> Vec<(usize, &HealingRule)> {
+        let last_runs = self.last_runs.lock().unwrap();
+        self.config
+            .actions
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| rule.score(report) < rule.below)
+            .filter(|(index, rule)| {
+                last_runs
+                    .get(index)
+                    .is_none_or(|last| last.elapsed() >= Duration::from_secs(rule.cooldown_secs))
+            })
+            .collect()
+    }
+
+    fn score_component(
+        &self,
+        component: HealthComponent,
+        signals: &HealthSignals,
+    ) -> ComponentHealth {
+        let config = &self.config;
+        let (score, detail) = match component {
+            HealthComponent::Storage => match &signals.storage_error {
+                None => (MAX_SCORE, "writable".to_string()),
+                Some(e) => (0, format!("probe failed: {}", e)),
+            },
+            HealthComponent::Consensus if !signals.has_leader => (0, "no known leader".to_string()),
+            HealthComponent::Consensus => {
+                let lag = signals.apply_lag.min(config.max_apply_lag);
+                (
+                    MAX_SCORE - (lag * 50 / config.max_apply_lag) as u32,
+                    format!("apply lag {}", signals.apply_lag),
+                )
+            }
+            HealthComponent::Archival => match signals.archival_backlog {
+                None => (MAX_SCORE, "not configured".to_string()),
+                Some(backlog) => {
+                    let pending = backlog.min(config.max_archival_backlog);
+                    (
+                        MAX_SCORE - (pending * 100 / config.max_archival_backlog) as u32,
+                        format!("{} segments pending", backlog),
+                    )
+                }
+            },
+            HealthComponent::Disk => match signals.disk_free_ratio {
+                None => (MAX_SCORE, "free space unknown".to_string()),
+                Some(free) => {
+                    let range = config.disk_low_ratio - config.disk_critical_ratio;
+                    let share = ((free - config.disk_critical_ratio) / range).clamp(0.0, 1.0);
+                    (
+                        (share * MAX_SCORE as f64).round() as u32,
+                        format!("{:.1}% free", free * 100.0),
+                    )
+                }
+            },
+            HealthComponent::Peers if signals.peers_total == 0 => {
+                (MAX_SCORE, "no peers discovered".to_string())
+            }
+            HealthComponent::Peers => (
+                (signals.peers_alive.min(signals.peers_total) * 100 / signals.peers_total) as u32,
+                format!("{}/{} alive", signals.peers_alive, signals.peers_total),
+            ),
+        };
+        ComponentHealth {
+            component,
+            score,
+            detail,
+        }
+    }
+}
+
+/// Set the health gauges from `report`
+fn export_metrics(report: &HealthReport) {
+    NODE_HEALTH_SCORE.set(report.score as i64);
+    NODE_HEALTH.set(i64::from(report.healthy));
+    for component in &report.components {
+        NODE_HEALTH_COMPONENT
+            .with_label_values(&[component.component.as_str()])
+            .set(component.score as i64);
+    }
+}
+
+/// Share of free space on the file system holding `path`
+#[cfg(unix)]
+pub fn disk_free_ratio(path: &Path) -> std::io::Result<f64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    // SAFETY: `path` is NUL-terminated and `stat` is a valid out-pointer
+    let stat = unsafe {
+        let mut stat: libc::statvfs = std::mem::zeroed();
+        if libc::statvfs(path.as_ptr(), &mut stat) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        stat
+    };
+    if stat.f_blocks == 0 {
+        return Ok(1.0);
+    }
+    Ok(stat.f_bavail as f64 / stat.f_blocks as f64)
+}
+
+/// Share of free space on the file system holding `path`
+#[cfg(not(unix))]
+pub fn disk_free_ratio(_path: &Path) -> std::io::Result<f64> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "free space is only measured on Unix",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn healthy() -> HealthSignals {
+        HealthSignals {
+            has_leader: true,
+            archival_backlog: Some(0),
+            disk_free_ratio: Some(0.5),
+            peers_total: 2,
+            peers_alive: 2,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_scores_components() {
+        let monitor = HealthMonitor::new(HealthConfig::default());
+        let report = monitor.score(&healthy());
+        assert_eq!(report.score, 100);
+        assert!(report.healthy);
+
+        let signals = HealthSignals {
+            apply_lag: 500,
+            archival_backlog: Some(25),
+            disk_free_ratio: Some(0.125),
+            peers_alive: 1,
+            ..healthy()
+        };
+        let report = monitor.score(&signals);
+        let score = |component| report.component(component).unwrap().score;
+        assert_eq!(score(HealthComponent::Storage), 100);
+        assert_eq!(score(HealthComponent::Consensus), 75);
+        assert_eq!(score(HealthComponent::Archival), 75);
+        assert_eq!(score(HealthComponent::Disk), 50);
+        assert_eq!(score(HealthComponent::Peers), 50);
+        // (3 * 100 + 3 * 75 + 75 + 2 * 50 + 50) / 10
+        assert_eq!(report.score, 75);
+
+        let signals = HealthSignals {
+            storage_error: Some("read-only".to_string()),
+            has_leader: false,
+            ..healthy()
+        };
+        let report = monitor.score(&signals);
+        assert_eq!(report.score, 40);
+        assert!(!report.healthy);
+    }
+
+    #[tokio::test]
+    async fn test_runs_actions_below_threshold_with_cooldown() {
+        let config = HealthConfig {
+            actions: vec![
+                HealingRule {
+                    action: CLEAR_CACHE.to_string(),
+                    component: Some(HealthComponent::Archival),
+                    below: 50,
+                    cooldown_secs: 3600,
+                },
+                HealingRule {
+                    action: STEP_DOWN.to_string(),
+                    component: Some(HealthComponent::Consensus),
+                    below: 50,
+                    cooldown_secs: 0,
+                },
+            ],
+            ..Default::default()
+        };
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counted = Arc::clone(&runs);
+        let monitor = HealthMonitor::new(config.clone()).with_action(CLEAR_CACHE, move || {
+            counted.fetch_add(1, Ordering::SeqCst);
+            async { Ok(()) }
+        });
+        assert!(monitor.check_actions().is_err());
+        let monitor = monitor.with_action(STEP_DOWN, || async {
+            Err(ScribeError::Consensus("single node".to_string()))
+        });
+        monitor.check_actions().unwrap();
+
+        let report = monitor.evaluate(&healthy()).await;
+        assert!(report.actions.is_empty());
+
+        let backlogged = HealthSignals {
+            archival_backlog: Some(80),
+            ..healthy()
+        };
+        let report = monitor.evaluate(&backlogged).await;
+        assert_eq!(
+            report.actions,
+            vec![HealingOutcome {
+                action: CLEAR_CACHE.to_string(),
+                trigger: "archival < 50".to_string(),
+                error: None,
+            }]
+        );
+        assert_eq!(monitor.evaluate(&backlogged).await.actions.len(), 0);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(monitor.last_report().unwrap().score, 92);
+
+        let failed = HealthSignals {
+            has_leader: false,
+            ..backlogged
+        };
+        let report = monitor.evaluate(&failed).await;
+        assert_eq!(report.actions.len(), 1);
+        assert_eq!(report.actions[0].action, STEP_DOWN);
+        assert!(report.actions[0].error.is_some());
+    }
+
+    #[test]
+    fn test_config_validation() {
+        assert!(HealthConfig::default().validate().is_ok());
+        assert!(HealthConfig {
+            disk_low_ratio: 0.05,
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+        assert!(HealthConfig {
+            weights: HealthWeights {
+                storage: 0,
+                consensus: 0,
+                archival: 0,
+                disk: 0,
+                peers: 0,
+            },
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+        assert!(HealthConfig {
+            actions: vec![HealingRule {
+                action: CLEAR_CACHE.to_string(),
+                component: None,
+                below: 0,
+                cooldown_secs: 60,
+            }],
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_disk_free_ratio() {
+        let ratio = disk_free_ratio(&std::env::temp_dir()).unwrap();
+        assert!((0.0..=1.0).contains(&ratio));
+    }
+}
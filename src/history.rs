@@ -0,0 +1,145 @@
+//! Write history of the local ledger
+//!
+//! A ledger opened with [`crate::HyraScribeLedger::with_history`] keeps every value
+//! each key held, not just the latest. Every put, delete and successful
+//! compare-and-swap records a [`KeyRevision`] in a tree of its own, in the same sled
+//! transaction as the write, so the history never disagrees with the current value.
+//!
+//! Revisions carry a version drawn from a counter shared by every key, which orders
+//! writes across the ledger, and the time of the write. Times never go backwards
+//! for one key, even if the clock does, so [`crate::HyraScribeLedger::get_at`] can
+//! answer "what did this key hold at time T" from the newest revision written at or
+//! before T.
+//!
+//! Writes made through [`crate::HyraScribeLedger::transaction`] are recorded in the
+//! transaction that applies them. Batches carry no record of their writes, so a
+//! ledger that keeps its history refuses them.
+
+use serde::{Deserialize, Serialize};
+use sled::transaction::{ConflictableTransactionError, TransactionalTree};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::types::Value;
+
+/// Name of the sled tree holding the history
+pub const HISTORY_TREE: &str = "__history";
+
+/// Marks the time of the latest revision of a key
+const HEAD_TAG: u8 = 0;
+
+/// Marks a revision of a key
+const REVISION_TAG: u8 = 1;
+
+/// Value of a key as written at one point in time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyRevision {
+    /// Ledger-wide write counter when the revision was written
+    pub version: u64,
+    /// Time of the write (milliseconds since the Unix epoch)
+    pub timestamp_ms: u64,
+    /// Value written, `None` if the key was deleted
+    pub value: Option<Value>,
+}
+
+/// Outcome of a step of a history transaction
+type RecordResult<T, E> = Result<T, ConflictableTransactionError<E>>;
+
+/// Start of the revisions of `key`: the length of the key keeps the revisions of
+/// `a` apart from those of `ab`
+fn revision_prefix(key: &[u8]) -> Vec<u8> {
+    let mut prefix = Vec::with_capacity(key.len() + 13);
+    prefix.push(REVISION_TAG);
+    prefix.extend_from_slice(&(key.len() as u32).to_be_bytes());
+    prefix.extend_from_slice(key);
+    prefix
+}
+
+/// History key of the revision of `key` at `version`; big-endian so revisions sort
+/// by version
+fn revision_key(key: &[u8], version: u64) -> Vec<u8> {
+    let mut revision_key = revision_prefix(key);
+    revision_key.extend_from_slice(&version.to_be_bytes());
+    revision_key
+}
+
+fn head_key(key: &[u8]) -> Vec<u8> {
+    let mut head_key = Vec::with_capacity(key.len() + 1);
+    head_key.push(HEAD_TAG);
+    head_key.extend_from_slice(key);
+    head_key
+}
+
+/// Record that `key` now holds `value` (`None`: deleted) within a transaction over
+/// the history tree
+pub(crate) fn record<E: From<bincode::Error>>(
+    history: &TransactionalTree,
+    key: &[u8],
+    value: Option<&[u8]>,
+) -> RecordResult<KeyRevision, E> {
+    let previous = history
+        .get(head_key(key))?
+        .and_then(|bytes| <[u8; 8]>::try_from(bytes.as_ref()).ok())
+        .map_or(0, u64::from_be_bytes);
+    let revision = KeyRevision {
+        version: history.generate_id()?,
+        timestamp_ms: now_millis().max(previous),
+        value: value.map(|value| value.to_vec()),
+    };
+    let encoded =
+        bincode::serialize(&revision).map_err(|e| ConflictableTransactionError::Abort(e.into()))?;
+    history.insert(revision_key(key, revision.version), encoded)?;
+    history.insert(head_key(key), &revision.timestamp_ms.to_be_bytes())?;
+    Ok(revision)
+}
+
+/// Revisions of `key`, newest first
+pub(crate) fn revisions(
+    history: &sled::Tree,
+    key: &[u8],
+) -> impl Iterator<Item = anyhow::Result<KeyRevision>> {
+    history
+        .scan_prefix(revision_prefix(key))
+        .rev()
+        .map(|item| Ok(bincode::deserialize(&item?.1)?))
+}
+
+/// Current wall-clock time in milliseconds since UNIX epoch
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_revisions_of_prefixed_keys_stay_apart() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let history = db.open_tree(HISTORY_TREE).unwrap();
+        for (key, value) in [(&b"a"[..], &b"1"[..]), (b"ab", b"2"), (b"a", b"3")] {
+            history
+                .transaction(|tree| record::<anyhow::Error>(tree, key, Some(value)))
+                .unwrap();
+        }
+        history
+            .transaction(|tree| record::<anyhow::Error>(tree, b"ab", None))
+            .unwrap();
+
+        let values = |key: &[u8]| -> Vec<Option<Value>> {
+            revisions(&history, key)
+                .map(|revision| revision.unwrap().value)
+                .collect()
+        };
+        assert_eq!(values(b"a"), vec![Some(b"3".to_vec()), Some(b"1".to_vec())]);
+        assert_eq!(values(b"ab"), vec![None, Some(b"2".to_vec())]);
+        assert!(values(b"b").is_empty());
+
+        // Versions increase across keys, and times never go backwards per key
+        let a: Vec<KeyRevision> = revisions(&history, b"a").map(Result::unwrap).collect();
+        assert!(a[0].version > a[1].version);
+        assert!(a[0].timestamp_ms >= a[1].timestamp_ms);
+    }
+}
@@ -0,0 +1,389 @@
+//! Hot key and per-prefix traffic tracking
+//!
+//! [`HotKeyTracker`] counts operations per key prefix and keeps an approximate list of
+//! the hottest keys. Key frequencies are estimated with a count-min sketch, so memory
+//! stays fixed no matter how many distinct keys are touched; only the current top-K
+//! candidates are stored by name. Key estimates are halved every decay interval so the
+//! list follows the current workload rather than all-time totals. Prefix counters are
+//! totals since startup.
+
+use crate::logging::log_key;
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Number of hash rows in the sketch
+const SKETCH_DEPTH: usize = 4;
+
+/// Counters per sketch row
+const SKETCH_WIDTH: usize = 4096;
+
+/// Default number of hot keys tracked
+const DEFAULT_TOP_K: usize = 32;
+
+/// Default interval after which key estimates are halved
+const DEFAULT_DECAY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Distinct prefixes tracked, including the shared [`OTHER_PREFIX`] bucket
+const MAX_PREFIXES: usize = 1024;
+
+/// Bucket for prefixes beyond [`MAX_PREFIXES`]
+pub(crate) const OTHER_PREFIX: &str = "(other)";
+
+/// Bucket for keys without a delimiter
+const NO_PREFIX: &str = "(none)";
+
+/// Bytes that end a key's prefix (e.g. `user` in `user:42` or `logs/2024`)
+const PREFIX_DELIMITERS: &[u8] = b":/";
+
+/// Kind of operation recorded for a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyOp {
+    Read,
+    Write,
+    Delete,
+}
+
+/// An estimated hot key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HotKey {
+    /// The key (lossy UTF-8, redacted as configured for logs)
+    pub key: String,
+    /// Estimated operations, decayed over time; may overcount, never undercounts
+    pub estimated_ops: u64,
+}
+
+/// Operation counts of one key prefix
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PrefixStats {
+    /// The prefix (lossy UTF-8)
+    pub prefix: String,
+    pub reads: u64,
+    pub writes: u64,
+    pub deletes: u64,
+}
+
+impl PrefixStats {
+    /// Operations of every kind
+    pub fn total(&self) -> u64 {
+        self.reads + self.writes + self.deletes
+    }
+}
+
+/// Snapshot of the tracked traffic, served at `GET /admin/hotkeys`
+#[derive(Debug, Clone, Serialize)]
+pub struct HotKeyReport {
+    /// Hottest keys first
+    pub top_keys: Vec<HotKey>,
+    /// Busiest prefixes first
+    pub prefixes: Vec<PrefixStats>,
+    /// Operations recorded since startup
+    pub total_ops: u64,
+    /// Seconds after which key estimates are halved
+    pub decay_interval_secs: u64,
+}
+
+/// Count-min sketch of per-key operation counts
+struct CountMinSketch {
+    counters: Vec<u64>,
+}
+
+impl CountMinSketch {
+    fn new() -> Self {
+        Self {
+            counters: vec![0; SKETCH_DEPTH * SKETCH_WIDTH],
+        }
+    }
+
+    fn slot(row: usize, key: &[u8]) -> usize {
+        let mut hasher = DefaultHasher::new();
+        row.hash(&mut hasher);
+        key.hash(&mut hasher);
+        row * SKETCH_WIDTH + (hasher.finish() as usize % SKETCH_WIDTH)
+    }
+
+    /// Count one operation on `key` and return its new estimate
+    fn increment(&mut self, key: &[u8]) -> u64 {
+        (0..SKETCH_DEPTH)
+            .map(|row| {
+                let counter = &mut self.counters[Self::slot(row, key)];
+                *counter = counter.saturating_add(1);
+                *counter
+            })
+            .min()
+            .unwrap_or(0)
+    }
+
+    fn halve(&mut self, times: u32) {
+        for counter in &mut self.counters {
+            *counter = counter.checked_shr(times).unwrap_or(0);
+        }
+    }
+}
+
+struct TrackerState {
+    sketch: CountMinSketch,
+    /// Current top-K candidates and their estimates
+    top: HashMap<Vec<u8>, u64>,
+    prefixes: HashMap<String, PrefixStats>,
+    total_ops: u64,
+    last_decay: Instant,
+}
+
+impl TrackerState {
+    fn new() -> Self {
+        Self {
+            sketch: CountMinSketch::new(),
+            top: HashMap::new(),
+            prefixes: HashMap::new(),
+            total_ops: 0,
+            last_decay: Instant::now(),
+        }
+    }
+
+    /// Halve key estimates once per elapsed decay interval
+    fn decay(&mut self, interval: Duration) {
+        if interval.is_zero() {
+            return;
+        }
+        let elapsed = self.last_decay.elapsed();
+        let times = (elapsed.as_nanos() / interval.as_nanos()).min(u32::MAX as u128) as u32;
+        if times == 0 {
+            return;
+        }
+
+        self.sketch.halve(times);
+        self.top.retain(|_, count| {
+            *count = count.checked_shr(times).unwrap_or(0);
+            *count > 0
+        });
+        self.last_decay = Instant::now();
+    }
+
+    fn record_prefix(&mut self, key: &[u8], op: KeyOp) {
+        let prefix = key_prefix(key);
+        let prefix =
+            if self.prefixes.contains_key(&prefix) || self.prefixes.len() < MAX_PREFIXES - 1 {
+                prefix
+            } else {
+                OTHER_PREFIX.to_string()
+            };
+
+        let stats = self
+            .prefixes
+            .entry(prefix)
+            .or_insert_with_key(|prefix| PrefixStats {
+                prefix: prefix.clone(),
+                ..Default::default()
+            });
+        match op {
+            KeyOp::Read => stats.reads += 1,
+            KeyOp::Write => stats.writes += 1,
+            KeyOp::Delete => stats.deletes += 1,
+        }
+    }
+
+    fn record_key(&mut self, key: &[u8], top_k: usize) {
+        let estimate = self.sketch.increment(key);
+        if let Some(count) = self.top.get_mut(key) {
+            *count = estimate;
+            return;
+        }
+        if self.top.len() < top_k {
+            self.top.insert(key.to_vec(), estimate);
+            return;
+        }
+
+        // Replace the coldest candidate if this key overtook it
+        let coldest = self
+            .top
+            .iter()
+            .min_by_key(|(_, count)| **count)
+            .filter(|(_, count)| **count < estimate)
+            .map(|(key, _)| key.clone());
+        if let Some(coldest) = coldest {
+            self.top.remove(&coldest);
+            self.top.insert(key.to_vec(), estimate);
+        }
+    }
+}
+
+/// Prefix of `key` as reported per prefix: the part before the first delimiter
+pub(crate) fn key_prefix(key: &[u8]) -> String {
+    match key.iter().position(|b| PREFIX_DELIMITERS.contains(b)) {
+        Some(end) => String::from_utf8_lossy(&key[..end]).into_owned(),
+        None => NO_PREFIX.to_string(),
+    }
+}
+
+/// Approximate per-prefix and hot key statistics for the API layer
+pub struct HotKeyTracker {
+    top_k: usize,
+    decay_interval: Duration,
+    state: Mutex<TrackerState>,
+}
+
+impl HotKeyTracker {
+    /// Create a tracker with default settings
+    pub fn new() -> Self {
+        Self {
+            top_k: DEFAULT_TOP_K,
+            decay_interval: DEFAULT_DECAY_INTERVAL,
+            state: Mutex::new(TrackerState::new()),
+        }
+    }
+
+    /// Set how many hot keys are tracked
+    pub fn with_top_k(mut self, top_k: usize) -> Self {
+        self.top_k = top_k.max(1);
+        self
+    }
+
+    /// Set how often key estimates are halved (zero disables decay)
+    pub fn with_decay_interval(mut self, decay_interval: Duration) -> Self {
+        self.decay_interval = decay_interval;
+        self
+    }
+
+    /// Record an operation on `key`
+    pub fn record(&self, key: &[u8], op: KeyOp) {
+        let mut state = self.state.lock().unwrap();
+        state.decay(self.decay_interval);
+        state.total_ops += 1;
+        state.record_prefix(key, op);
+        state.record_key(key, self.top_k);
+    }
+
+    /// The `limit` hottest keys and busiest prefixes
+    pub fn report(&self, limit: usize) -> HotKeyReport {
+        let mut state = self.state.lock().unwrap();
+        state.decay(self.decay_interval);
+
+        let mut top_keys: Vec<HotKey> = state
+            .top
+            .iter()
+            .map(|(key, count)| HotKey {
+                key: log_key(key).to_string(),
+                estimated_ops: *count,
+            })
+            .collect();
+        top_keys.sort_by(|a, b| {
+            b.estimated_ops
+                .cmp(&a.estimated_ops)
+                .then_with(|| a.key.cmp(&b.key))
+        });
+        top_keys.truncate(limit);
+
+        let mut prefixes: Vec<PrefixStats> = state.prefixes.values().cloned().collect();
+        prefixes.sort_by(|a, b| {
+            b.total()
+                .cmp(&a.total())
+                .then_with(|| a.prefix.cmp(&b.prefix))
+        });
+        prefixes.truncate(limit);
+
+        HotKeyReport {
+            top_keys,
+            prefixes,
+            total_ops: state.total_ops,
+            decay_interval_secs: self.decay_interval.as_secs(),
+        }
+    }
+
+    /// Forget everything recorded so far
+    pub fn reset(&self) {
+        *self.state.lock().unwrap() = TrackerState::new();
+    }
+}
+
+impl Default for HotKeyTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_top_keys_find_hottest() {
+        let tracker = HotKeyTracker::new().with_top_k(3);
+        for i in 0..500 {
+            tracker.record(format!("cold:{}", i).as_bytes(), KeyOp::Read);
+        }
+        for _ in 0..200 {
+            tracker.record(b"user:hot", KeyOp::Read);
+        }
+        for _ in 0..100 {
+            tracker.record(b"user:warm", KeyOp::Write);
+        }
+
+        let report = tracker.report(10);
+        assert_eq!(report.total_ops, 800);
+        assert_eq!(report.top_keys.len(), 3);
+        assert_eq!(report.top_keys[0].key, "user:hot");
+        assert!(report.top_keys[0].estimated_ops >= 200);
+        assert_eq!(report.top_keys[1].key, "user:warm");
+        assert!(report.top_keys[1].estimated_ops >= 100);
+
+        assert_eq!(tracker.report(1).top_keys.len(), 1);
+    }
+
+    #[test]
+    fn test_prefix_counters() {
+        let tracker = HotKeyTracker::new();
+        tracker.record(b"user:1", KeyOp::Read);
+        tracker.record(b"user:2", KeyOp::Write);
+        tracker.record(b"user:2", KeyOp::Delete);
+        tracker.record(b"logs/2024/01", KeyOp::Write);
+        tracker.record(b"plain", KeyOp::Read);
+
+        let report = tracker.report(10);
+        assert_eq!(
+            report.prefixes[0],
+            PrefixStats {
+                prefix: "user".to_string(),
+                reads: 1,
+                writes: 1,
+                deletes: 1,
+            }
+        );
+        let names: Vec<&str> = report.prefixes.iter().map(|p| p.prefix.as_str()).collect();
+        assert!(names.contains(&"logs"));
+        assert!(names.contains(&NO_PREFIX));
+    }
+
+    #[test]
+    fn test_prefix_count_is_bounded() {
+        let tracker = HotKeyTracker::new();
+        for i in 0..MAX_PREFIXES + 10 {
+            tracker.record(format!("p{}:key", i).as_bytes(), KeyOp::Write);
+        }
+
+        let state = tracker.state.lock().unwrap();
+        assert_eq!(state.prefixes.len(), MAX_PREFIXES);
+        assert_eq!(state.prefixes[OTHER_PREFIX].writes, 11);
+    }
+
+    #[test]
+    fn test_estimates_decay() {
+        let tracker = HotKeyTracker::new().with_decay_interval(Duration::from_millis(20));
+        for _ in 0..64 {
+            tracker.record(b"burst", KeyOp::Read);
+        }
+        std::thread::sleep(Duration::from_millis(50));
+
+        let report = tracker.report(10);
+        assert!(report.top_keys[0].estimated_ops <= 32);
+        // Prefix totals do not decay
+        assert_eq!(report.prefixes[0].reads, 64);
+        assert_eq!(report.total_ops, 64);
+
+        tracker.reset();
+        assert!(tracker.report(10).top_keys.is_empty());
+    }
+}
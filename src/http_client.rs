@@ -1,9 +1,103 @@
-use reqwest::Client;
+use crate::error::{Result, ScribeError};
+use crate::retry::{RetryEvent, RetryPolicy, RetryReason};
+use crate::sync::{BucketDigest, KeyDigest};
+use crate::trace_context;
+use crate::types::{Fence, LockLease, QueueItem, QueueStats, Session};
+use reqwest::header::{HeaderMap, ETAG, IF_MATCH, IF_NONE_MATCH, LOCATION, RETRY_AFTER};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
 /// Maximum concurrent requests to prevent resource exhaustion and ensure linear scaling
 const MAX_CONCURRENCY: usize = 20;
 
+/// Maximum number of leader redirects followed for a single request
+const MAX_LEADER_REDIRECTS: usize = 5;
+
+/// Header carrying the leader's client address on NotLeader responses
+pub const RAFT_LEADER_HEADER: &str = "x-raft-leader";
+
+/// Header carrying the leader's node ID on NotLeader responses
+pub const RAFT_LEADER_ID_HEADER: &str = "x-raft-leader-id";
+
+/// Header carrying the consistency token of a successful write, or of the state a
+/// read was served from
+pub const CONSISTENCY_TOKEN_HEADER: &str = "x-consistency-token";
+
+/// Header asking a read to wait until the serving node applied a consistency token
+pub const MIN_APPLIED_HEADER: &str = "x-min-applied";
+
+/// Header marking values read through from cold storage (`cold`)
+pub const TIER_HEADER: &str = "x-tier";
+
+/// Header carrying the ID of the archived segment a mirror served a value from
+pub const SEGMENT_ID_HEADER: &str = "x-segment-id";
+
+/// Header carrying the ID of a dequeued item
+pub const QUEUE_ITEM_ID_HEADER: &str = "x-queue-item-id";
+
+/// Header carrying the receipt of a dequeued item, presented when acking it
+pub const QUEUE_RECEIPT_HEADER: &str = "x-queue-receipt";
+
+/// Header carrying how often a dequeued item has been delivered
+pub const QUEUE_ATTEMPTS_HEADER: &str = "x-queue-attempts";
+
+/// Header carrying when a dequeued item's lease runs out (milliseconds since UNIX epoch)
+pub const QUEUE_VISIBLE_AT_HEADER: &str = "x-queue-visible-at";
+
+/// Header carrying the Raft index captured by an exported tag
+pub const TAG_RAFT_INDEX_HEADER: &str = "x-tag-raft-index";
+
+/// Header carrying the Merkle root (hex) of an exported tag
+pub const TAG_MERKLE_ROOT_HEADER: &str = "x-tag-merkle-root";
+
+/// Header carrying the ID of the node that served a request
+pub const SERVED_BY_HEADER: &str = "x-served-by";
+
+/// Header carrying the share of the serving node's request slots in use (percent)
+pub const NODE_LOAD_HEADER: &str = "x-node-load";
+
+/// Header carrying the serving node's admission queue depth, rounded down to a power
+/// of ten
+pub const NODE_QUEUE_DEPTH_HEADER: &str = "x-node-queue-depth";
+
+/// Header carrying the serving node's Raft role (`leader` or `follower`)
+pub const NODE_ROLE_HEADER: &str = "x-node-role";
+
+/// Header carrying the version of a key: the log index of its last put or delete
+pub const KEY_VERSION_HEADER: &str = "x-key-version";
+
+/// Header carrying the namespace of a fenced write
+pub const FENCE_NAMESPACE_HEADER: &str = "x-fence-namespace";
+
+/// Header carrying the epoch of a fenced write
+pub const FENCE_EPOCH_HEADER: &str = "x-fence-epoch";
+
+/// Header negotiating the HTTP API version: the versions a client accepts on
+/// requests (e.g. `2, 1`), the version served on responses
+pub const API_VERSION_HEADER: &str = "x-api-version";
+
+/// Current HTTP API version, served under `/v1`
+pub const API_VERSION: u32 = 1;
+
+/// HTTP API versions this node serves
+pub const SUPPORTED_API_VERSIONS: &[u32] = &[API_VERSION];
+
+/// Longest a long-polling read (`GET /:key?wait=...`) is held
+pub const MAX_LONG_POLL_WAIT: Duration = Duration::from_secs(120);
+
+/// Default number of attempts [`ClusterClient`] makes before giving up
+const DEFAULT_FAILOVER_ATTEMPTS: usize = 20;
+
+/// Default pause between [`ClusterClient`] attempts
+const DEFAULT_FAILOVER_DELAY: Duration = Duration::from_millis(250);
+
+/// Default timeout of a single [`ClusterClient`] request
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PutRequest {
     pub value: String,
@@ -14,6 +108,1140 @@ pub struct GetResponse {
     pub value: Option<String>,
 }
 
+/// Body of `POST /locks/:name/acquire`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcquireLockRequest {
+    /// Identity of the caller, e.g. a hostname plus process ID
+    pub holder: String,
+    /// Lease length in seconds
+    pub ttl_secs: u64,
+}
+
+/// Body of `POST /locks/:name/renew`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenewLockRequest {
+    /// Fencing token of the lease being renewed
+    pub token: u64,
+    /// New lease length in seconds, counted from now
+    pub ttl_secs: u64,
+}
+
+/// Body of `POST /locks/:name/release`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReleaseLockRequest {
+    /// Fencing token of the lease being released
+    pub token: u64,
+}
+
+/// Response of `POST /locks/:name/release`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReleaseLockResponse {
+    /// Whether the token still held the lock
+    pub released: bool,
+}
+
+/// Body of `POST /sessions`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateSessionRequest {
+    /// Application data of the session
+    #[serde(default)]
+    pub data: serde_json::Value,
+    /// Seconds without a touch after which the session expires
+    pub idle_timeout_secs: u64,
+}
+
+/// Response of `POST /sessions/:id/touch`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TouchSessionResponse {
+    /// New expiry of the session (milliseconds since UNIX epoch)
+    pub expires_at: u64,
+}
+
+/// Body of `POST /admin/epochs/:namespace`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AdvanceEpochRequest {
+    /// Epoch to move to; the current epoch plus one if absent
+    #[serde(default)]
+    pub epoch: Option<u64>,
+}
+
+/// Response of `GET` and `POST /admin/epochs/:namespace`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EpochResponse {
+    /// Namespace (key prefix)
+    pub namespace: String,
+    /// Current epoch of the namespace
+    pub epoch: u64,
+}
+
+/// Response of `POST /queues/:name`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnqueueResponse {
+    /// ID of the enqueued item
+    pub id: u64,
+}
+
+/// Body of `POST /queues/:name/dequeue`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DequeueRequest {
+    /// How long the item stays hidden from other consumers, in seconds
+    pub visibility_timeout_secs: u64,
+}
+
+/// Body of `POST /queues/:name/ack`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AckRequest {
+    /// ID of the item
+    pub id: u64,
+    /// Receipt of the delivery being acked
+    pub receipt: u64,
+}
+
+/// Response of `POST /queues/:name/ack`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AckResponse {
+    /// Whether the item was removed; false if it was acked already or redelivered
+    pub acked: bool,
+}
+
+/// Operation of `POST /batch`; the body is an array of them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOp {
+    /// Store `value` at `key`
+    Put { key: String, value: String },
+    /// Delete `key`
+    Delete { key: String },
+}
+
+/// Entity tag of a value, as served in the `ETag` header of `GET /:key`
+///
+/// Derived from the value alone, so every node serves the same tag for the same value.
+pub fn value_etag(value: &[u8]) -> String {
+    let digest = Sha256::digest(value);
+    format!("\"{}\"", hex::encode(&digest[..16]))
+}
+
+/// Outcome of a conditional read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConditionalGet {
+    /// The value still matches the presented entity tag
+    NotModified,
+    /// The key holds a (different) value
+    Found {
+        value: Vec<u8>,
+        etag: Option<String>,
+    },
+    /// The key does not exist
+    NotFound,
+}
+
+/// Outcome of a long-polling read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyWatch {
+    /// The key's version did not exceed the given one before the wait ran out
+    Unchanged,
+    /// The key changed; `value` is `None` if it was deleted
+    Changed {
+        version: u64,
+        value: Option<Vec<u8>>,
+    },
+}
+
+/// Pick the API version to serve for an `x-api-version` request header
+///
+/// The header lists acceptable versions, optionally prefixed with `v`; the highest
+/// supported one is served. Without the header, the current version is served.
+/// Fails if none of the listed versions is supported.
+pub fn negotiate_api_version(requested: Option<&str>) -> Result<u32> {
+    let Some(requested) = requested else {
+        return Ok(API_VERSION);
+    };
+    requested
+        .split(',')
+        .filter_map(|version| {
+            let version = version.trim();
+            version.strip_prefix('v').unwrap_or(version).parse().ok()
+        })
+        .filter(|version| SUPPORTED_API_VERSIONS.contains(version))
+        .max()
+        .ok_or_else(|| {
+            ScribeError::Other(format!(
+                "Unsupported API version {:?} (supported: {})",
+                requested,
+                SUPPORTED_API_VERSIONS
+                    .iter()
+                    .map(|version| version.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        })
+}
+
+/// Parse the `wait` parameter of a long-polling read
+///
+/// Accepts a number with an `ms`, `s` or `m` unit (`500ms`, `30s`, `2m`) or plain
+/// seconds. Fails if the duration exceeds [`MAX_LONG_POLL_WAIT`].
+pub fn parse_wait(wait: &str) -> Result<Duration> {
+    let wait = wait.trim();
+    let (number, unit) = match wait.find(|c: char| !c.is_ascii_digit()) {
+        Some(split) => wait.split_at(split),
+        None => (wait, "s"),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| ScribeError::Other(format!("Invalid wait duration {:?}", wait)))?;
+    let duration = match unit {
+        "ms" => Duration::from_millis(number),
+        "s" => Duration::from_secs(number),
+        "m" => Duration::from_secs(number.saturating_mul(60)),
+        _ => {
+            return Err(ScribeError::Other(format!(
+                "Invalid wait duration {:?} (use ms, s or m)",
+                wait
+            )))
+        }
+    };
+    if duration > MAX_LONG_POLL_WAIT {
+        return Err(ScribeError::Other(format!(
+            "Wait duration {:?} exceeds the maximum of {}s",
+            wait,
+            MAX_LONG_POLL_WAIT.as_secs()
+        )));
+    }
+    Ok(duration)
+}
+
+/// Load hints a node attaches to every response
+///
+/// Smart clients can use these to send stale reads to the least loaded follower.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadHint {
+    /// Node that served the request
+    pub node_id: u64,
+    /// Share of the node's request slots in use, in percent
+    pub load_percent: u8,
+    /// Requests waiting for a slot, rounded down to a power of ten
+    pub queue_depth: u64,
+    /// Whether the node is the Raft leader
+    pub is_leader: bool,
+}
+
+impl LoadHint {
+    /// Read the hints from response headers; `None` if any hint is missing
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+        Some(Self {
+            node_id: header(SERVED_BY_HEADER)?.parse().ok()?,
+            load_percent: header(NODE_LOAD_HEADER)?.parse().ok()?,
+            queue_depth: header(NODE_QUEUE_DEPTH_HEADER)?.parse().ok()?,
+            is_leader: header(NODE_ROLE_HEADER)? == "leader",
+        })
+    }
+
+    /// Cost of sending a stale read to this node; lower is better
+    ///
+    /// Queued requests weigh more than busy slots, and followers win ties so the
+    /// leader keeps its capacity for writes.
+    pub fn read_cost(&self) -> (u64, u8, bool) {
+        (self.queue_depth, self.load_percent, self.is_leader)
+    }
+}
+
+/// Create an HTTP client that leaves leader redirects to [`send_following_leader`]
+pub fn leader_aware_client() -> reqwest::Result<Client> {
+    Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+}
+
+/// Build the client API URL for a key, percent-encoding it as a path segment
+pub fn key_url(base_url: &str, key: &[u8]) -> Result<String> {
+    let mut url = reqwest::Url::parse(base_url)
+        .map_err(|e| ScribeError::Configuration(format!("Invalid base URL: {}", e)))?;
+    url.path_segments_mut()
+        .map_err(|_| ScribeError::Configuration("Invalid base URL".to_string()))?
+        .pop_if_empty()
+        .push(&String::from_utf8_lossy(key));
+    Ok(url.to_string())
+}
+
+/// Build the client API URL of a lock endpoint, e.g. `/locks/:name/acquire`
+pub fn lock_url(base_url: &str, name: &str, action: Option<&str>) -> Result<String> {
+    named_url(base_url, "locks", name, action)
+}
+
+/// Build the client API URL of a queue endpoint, e.g. `/queues/:name/dequeue`
+pub fn queue_url(base_url: &str, name: &str, action: Option<&str>) -> Result<String> {
+    named_url(base_url, "queues", name, action)
+}
+
+/// Build the client API URL of a session endpoint, e.g. `/sessions/:id/touch`, or
+/// of `/sessions` itself without an ID
+pub fn session_url(base_url: &str, id: Option<&str>, action: Option<&str>) -> Result<String> {
+    match id {
+        Some(id) => named_url(base_url, "sessions", id, action),
+        None => key_url(base_url, b"sessions"),
+    }
+}
+
+/// Build `/<collection>/:name[/<action>]`, percent-encoding the name
+fn named_url(base_url: &str, collection: &str, name: &str, action: Option<&str>) -> Result<String> {
+    let mut url = reqwest::Url::parse(base_url)
+        .map_err(|e| ScribeError::Configuration(format!("Invalid base URL: {}", e)))?;
+    {
+        let mut segments = url
+            .path_segments_mut()
+            .map_err(|_| ScribeError::Configuration("Invalid base URL".to_string()))?;
+        segments.pop_if_empty().push(collection).push(name);
+        if let Some(action) = action {
+            segments.push(action);
+        }
+    }
+    Ok(url.to_string())
+}
+
+/// Build the URL of `/sync/buckets[/:bucket]?buckets=<buckets>`
+fn sync_url(base_url: &str, bucket: Option<u32>, buckets: u32) -> Result<String> {
+    let mut url = reqwest::Url::parse(base_url)
+        .map_err(|e| ScribeError::Configuration(format!("Invalid base URL: {}", e)))?;
+    {
+        let mut segments = url
+            .path_segments_mut()
+            .map_err(|_| ScribeError::Configuration("Invalid base URL".to_string()))?;
+        segments.pop_if_empty().push("sync").push("buckets");
+        if let Some(bucket) = bucket {
+            segments.push(&bucket.to_string());
+        }
+    }
+    url.query_pairs_mut()
+        .append_pair("buckets", &buckets.to_string());
+    Ok(url.to_string())
+}
+
+/// Resolve where a NotLeader response points to
+///
+/// Prefers the `Location` header; otherwise rewrites the authority of `current_url`
+/// with the address in `X-Raft-Leader`. Returns `None` for non-redirect statuses.
+pub fn leader_redirect_target(
+    current_url: &str,
+    status: StatusCode,
+    headers: &HeaderMap,
+) -> Option<String> {
+    if !matches!(
+        status,
+        StatusCode::TEMPORARY_REDIRECT
+            | StatusCode::PERMANENT_REDIRECT
+            | StatusCode::MISDIRECTED_REQUEST
+    ) {
+        return None;
+    }
+
+    if let Some(location) = headers.get(LOCATION).and_then(|v| v.to_str().ok()) {
+        return reqwest::Url::parse(current_url)
+            .and_then(|base| base.join(location))
+            .ok()
+            .map(|url| url.to_string());
+    }
+
+    let leader = headers.get(RAFT_LEADER_HEADER)?.to_str().ok()?;
+    let mut url = reqwest::Url::parse(current_url).ok()?;
+    let (host, port) = leader.rsplit_once(':')?;
+    url.set_host(Some(host)).ok()?;
+    url.set_port(Some(port.parse().ok()?)).ok()?;
+    Some(url.to_string())
+}
+
+/// Pause a `Retry-After` header asks for, if it gives one in seconds
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let seconds = headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+/// Send a request, following NotLeader redirects (307/308/421) to the Raft leader
+///
+/// `build` creates the request for a given URL and is called again on every hop, so
+/// the body and headers are resent unchanged. After `MAX_LEADER_REDIRECTS` hops the
+/// last response is returned as-is. Inside a traced request every hop carries its
+/// `traceparent` (see [`crate::trace_context`]).
+pub async fn send_following_leader<F>(url: &str, build: F) -> reqwest::Result<Response>
+where
+    F: Fn(&str) -> RequestBuilder,
+{
+    let mut url = url.to_string();
+    let mut redirects = 0;
+
+    loop {
+        let response = trace_context::inject(build(&url)).send().await?;
+
+        if redirects >= MAX_LEADER_REDIRECTS {
+            return Ok(response);
+        }
+
+        match leader_redirect_target(&url, response.status(), response.headers()) {
+            Some(next) => {
+                url = next;
+                redirects += 1;
+            }
+            None => return Ok(response),
+        }
+    }
+}
+
+/// Read-your-writes session of a [`ClusterClient`]
+///
+/// Tracks the highest consistency token the client has seen in responses, and sends
+/// it as `X-Min-Applied` with every request, so whichever node serves a read first
+/// catches up with the session's own writes and with everything it read before.
+/// The token is a plain log index: store it (e.g. in a cookie) and [`resume`] it to
+/// carry the session over to another process. Clones share the same token.
+///
+/// [`resume`]: ClientSession::resume
+#[derive(Debug, Clone, Default)]
+pub struct ClientSession {
+    applied: Arc<AtomicU64>,
+}
+
+impl ClientSession {
+    /// Start a session that has seen nothing yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Continue a session whose last token was `token`
+    pub fn resume(token: u64) -> Self {
+        Self {
+            applied: Arc::new(AtomicU64::new(token)),
+        }
+    }
+
+    /// Highest consistency token seen, 0 before the first response carrying one
+    pub fn token(&self) -> u64 {
+        self.applied.load(Ordering::Relaxed)
+    }
+
+    /// Advance the session to `token`; older tokens are ignored
+    pub fn observe(&self, token: u64) {
+        self.applied.fetch_max(token, Ordering::Relaxed);
+    }
+
+    /// Attach the session's token to a request
+    fn attach(&self, request: RequestBuilder) -> RequestBuilder {
+        match self.token() {
+            0 => request,
+            token => request.header(MIN_APPLIED_HEADER, token),
+        }
+    }
+
+    /// Advance the session to the token of a response, if it carries one
+    fn observe_headers(&self, headers: &HeaderMap) {
+        let token = headers
+            .get(CONSISTENCY_TOKEN_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse().ok());
+        if let Some(token) = token {
+            self.observe(token);
+        }
+    }
+}
+
+/// Client for a cluster's HTTP API that fails over between nodes
+///
+/// Requests go to the last node that answered and follow NotLeader redirects. When a
+/// redirect leads to one of the configured nodes, later requests go to that node
+/// directly, so only the first request after a leader change pays the extra hop. When
+/// a node is unreachable, returns a server error, or has no known leader (e.g. during
+/// an election), the client moves on to the next node and retries after a short
+/// pause, as its [`RetryPolicy`] allows. Retried writes may be applied twice, which is
+/// harmless for puts and deletes.
+/// With [`with_session`](Self::with_session), reads also observe the client's own
+/// earlier writes on whichever node serves them.
+#[derive(Clone)]
+pub struct ClusterClient {
+    client: Client,
+    endpoints: Arc<Vec<String>>,
+    current: Arc<AtomicUsize>,
+    retry: RetryPolicy,
+    session: Option<ClientSession>,
+}
+
+impl ClusterClient {
+    /// Create a client for the nodes at `endpoints` (e.g. "http://10.0.0.1:8001")
+    pub fn new(endpoints: Vec<String>) -> Result<Self> {
+        if endpoints.is_empty() {
+            return Err(ScribeError::Configuration(
+                "At least one endpoint is required".to_string(),
+            ));
+        }
+
+        let client = Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .timeout(DEFAULT_REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| ScribeError::Network(format!("Failed to create HTTP client: {}", e)))?;
+
+        Ok(Self {
+            client,
+            endpoints: Arc::new(endpoints),
+            current: Arc::new(AtomicUsize::new(0)),
+            retry: RetryPolicy::fixed(DEFAULT_FAILOVER_ATTEMPTS, DEFAULT_FAILOVER_DELAY),
+            session: None,
+        })
+    }
+
+    /// Set how many attempts a request makes and the pause between them
+    pub fn with_retries(mut self, max_attempts: usize, retry_delay: Duration) -> Self {
+        self.retry = self
+            .retry
+            .with_max_attempts(max_attempts)
+            .with_backoff(retry_delay, retry_delay)
+            .with_jitter(false);
+        self
+    }
+
+    /// Double the pause after every failed attempt, from `initial` up to `max`
+    ///
+    /// Each pause is drawn at random from its upper half, so clients that failed
+    /// together do not retry in lockstep.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.retry = self.retry.with_backoff(initial, max).with_jitter(true);
+        self
+    }
+
+    /// Retry failed requests as `policy` says, replacing the attempts and pauses
+    /// set so far
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Policy for retrying failed requests
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry
+    }
+
+    /// Send requests through `client`, e.g. one with its own pool and timeouts
+    ///
+    /// The client must not follow redirects itself, or NotLeader redirects are
+    /// followed without the client learning where the leader is.
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Carry `session` through every request (read-your-writes across nodes)
+    pub fn with_session(mut self, session: ClientSession) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    /// Session the client carries, if any
+    pub fn session(&self) -> Option<&ClientSession> {
+        self.session.as_ref()
+    }
+
+    /// Node currently receiving requests
+    pub fn current_endpoint(&self) -> &str {
+        &self.endpoints[self.current.load(Ordering::Relaxed) % self.endpoints.len()]
+    }
+
+    /// Every configured node
+    pub fn endpoints(&self) -> &[String] {
+        &self.endpoints
+    }
+
+    /// Store a value
+    pub async fn put(&self, key: &[u8], value: Vec<u8>) -> Result<()> {
+        let response = self
+            .send(key, |client, url| client.put(url).body(value.clone()))
+            .await?;
+        expect_success(response).await.map(|_| ())
+    }
+
+    /// Store a value on behalf of a writer holding `fence`
+    ///
+    /// Fails with `ScribeError::Conflict` once the namespace has moved past the
+    /// fence's epoch.
+    pub async fn put_fenced(&self, key: &[u8], value: Vec<u8>, fence: &Fence) -> Result<()> {
+        let response = self
+            .send(key, |client, url| {
+                fenced(client.put(url), fence).body(value.clone())
+            })
+            .await?;
+        expect_success_or_conflict(response).await.map(|_| ())
+    }
+
+    /// Delete a value on behalf of a writer holding `fence`
+    pub async fn delete_fenced(&self, key: &[u8], fence: &Fence) -> Result<()> {
+        let response = self
+            .send(key, |client, url| fenced(client.delete(url), fence))
+            .await?;
+        expect_success_or_conflict(response).await.map(|_| ())
+    }
+
+    /// Apply puts and deletes in one Raft entry
+    pub async fn write_batch(&self, ops: &[BatchOp]) -> Result<()> {
+        let response = self
+            .send(b"batch", |client, url| client.post(url).json(ops))
+            .await?;
+        expect_success(response).await.map(|_| ())
+    }
+
+    /// Read a value (served by the leader)
+    pub async fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let response = self.send(key, |client, url| client.get(url)).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response = expect_success(response).await?;
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| ScribeError::Network(format!("Failed to read response: {}", e)))?;
+        Ok(Some(body.to_vec()))
+    }
+
+    /// Read a value unless it still matches `etag` (sent as `If-None-Match`)
+    pub async fn get_conditional(&self, key: &[u8], etag: Option<&str>) -> Result<ConditionalGet> {
+        let response = self
+            .send(key, |client, url| match etag {
+                Some(etag) => client.get(url).header(IF_NONE_MATCH, etag),
+                None => client.get(url),
+            })
+            .await?;
+        match response.status() {
+            StatusCode::NOT_MODIFIED => return Ok(ConditionalGet::NotModified),
+            StatusCode::NOT_FOUND => return Ok(ConditionalGet::NotFound),
+            _ => {}
+        }
+
+        let response = expect_success(response).await?;
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let value = response
+            .bytes()
+            .await
+            .map_err(|e| ScribeError::Network(format!("Failed to read response: {}", e)))?;
+        Ok(ConditionalGet::Found {
+            value: value.to_vec(),
+            etag,
+        })
+    }
+
+    /// Wait up to `wait` for the version of `key` to exceed `version`
+    ///
+    /// Start with version 0 and pass the version of each [`KeyWatch::Changed`] to
+    /// the next call to follow every change of the key.
+    pub async fn wait_for_change(
+        &self,
+        key: &[u8],
+        version: u64,
+        wait: Duration,
+    ) -> Result<KeyWatch> {
+        let wait = wait.min(MAX_LONG_POLL_WAIT);
+        let query = [
+            ("wait", format!("{}ms", wait.as_millis())),
+            ("if-version", version.to_string()),
+        ];
+        let response = self
+            .send(key, |client, url| {
+                client
+                    .get(url)
+                    .query(&query)
+                    .timeout(wait + DEFAULT_REQUEST_TIMEOUT)
+            })
+            .await?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(KeyWatch::Unchanged);
+        }
+        let version = response
+            .headers()
+            .get(KEY_VERSION_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok())
+            .ok_or_else(|| ScribeError::Network("Response carries no key version".to_string()))?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(KeyWatch::Changed {
+                version,
+                value: None,
+            });
+        }
+        let value = expect_success(response)
+            .await?
+            .bytes()
+            .await
+            .map_err(|e| ScribeError::Network(format!("Failed to read response: {}", e)))?;
+        Ok(KeyWatch::Changed {
+            version,
+            value: Some(value.to_vec()),
+        })
+    }
+
+    /// Delete a value
+    pub async fn delete(&self, key: &[u8]) -> Result<()> {
+        let response = self.send(key, |client, url| client.delete(url)).await?;
+        expect_success(response).await.map(|_| ())
+    }
+
+    /// Store a value unless the key already exists (sent as `If-None-Match: *`)
+    ///
+    /// Returns whether the value was stored.
+    pub async fn put_if_absent(&self, key: &[u8], value: Vec<u8>) -> Result<bool> {
+        let response = self
+            .send(key, |client, url| {
+                client
+                    .put(url)
+                    .header(IF_NONE_MATCH, "*")
+                    .body(value.clone())
+            })
+            .await?;
+        precondition_met(response).await
+    }
+
+    /// Replace the value of `key` with `new`, or delete it for `None`, if it still
+    /// holds `expected`
+    ///
+    /// Sent as `If-Match` with the entity tag of `expected`; the server compares and
+    /// swaps atomically. Returns whether the swap took place.
+    pub async fn compare_and_swap(
+        &self,
+        key: &[u8],
+        expected: &[u8],
+        new: Option<Vec<u8>>,
+    ) -> Result<bool> {
+        let etag = value_etag(expected);
+        let response = self
+            .send(key, |client, url| match &new {
+                Some(value) => client.put(url).header(IF_MATCH, &etag).body(value.clone()),
+                None => client.delete(url).header(IF_MATCH, &etag),
+            })
+            .await?;
+        precondition_met(response).await
+    }
+
+    /// Store a value and return the one it replaced, atomically
+    pub async fn get_and_set(&self, key: &[u8], value: Vec<u8>) -> Result<Option<Vec<u8>>> {
+        let response = self
+            .send_to(
+                |base| key_url(base, key).map(|url| format!("{}/swap", url)),
+                |client, url| client.post(url).body(value.clone()),
+            )
+            .await?;
+        read_previous_value(response).await
+    }
+
+    /// Delete a value and return it, atomically
+    ///
+    /// Of several clients consuming the same key, at most one receives the value.
+    pub async fn get_and_delete(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let response = self
+            .send(key, |client, url| {
+                client.delete(url).query(&[("return", "previous")])
+            })
+            .await?;
+        read_previous_value(response).await
+    }
+
+    /// Acquire the lock `name` for `holder`, leased for `ttl` (whole seconds)
+    ///
+    /// Fails with `ScribeError::Conflict` while another holder's lease is valid.
+    pub async fn acquire_lock(&self, name: &str, holder: &str, ttl: Duration) -> Result<LockLease> {
+        let body = AcquireLockRequest {
+            holder: holder.to_string(),
+            ttl_secs: ttl.as_secs(),
+        };
+        let response = self
+            .send_to(
+                |base| lock_url(base, name, Some("acquire")),
+                |client, url| client.post(url).json(&body),
+            )
+            .await?;
+        read_json(expect_success_or_conflict(response).await?).await
+    }
+
+    /// Extend the lease with fencing token `token` to `ttl` from now
+    ///
+    /// Fails with `ScribeError::Conflict` if the lease expired or was released.
+    pub async fn renew_lock(&self, name: &str, token: u64, ttl: Duration) -> Result<LockLease> {
+        let body = RenewLockRequest {
+            token,
+            ttl_secs: ttl.as_secs(),
+        };
+        let response = self
+            .send_to(
+                |base| lock_url(base, name, Some("renew")),
+                |client, url| client.post(url).json(&body),
+            )
+            .await?;
+        read_json(expect_success_or_conflict(response).await?).await
+    }
+
+    /// Release the lease with fencing token `token`; `false` if it no longer held the lock
+    pub async fn release_lock(&self, name: &str, token: u64) -> Result<bool> {
+        let body = ReleaseLockRequest { token };
+        let response = self
+            .send_to(
+                |base| lock_url(base, name, Some("release")),
+                |client, url| client.post(url).json(&body),
+            )
+            .await?;
+        let response: ReleaseLockResponse = read_json(expect_success(response).await?).await?;
+        Ok(response.released)
+    }
+
+    /// Current lease on lock `name`, if any
+    pub async fn lock_info(&self, name: &str) -> Result<Option<LockLease>> {
+        let response = self
+            .send_to(
+                |base| lock_url(base, name, None),
+                |client, url| client.get(url),
+            )
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        read_json(expect_success(response).await?).await.map(Some)
+    }
+
+    /// Create a session holding `data` that expires after `idle_timeout` (whole
+    /// seconds) without a touch
+    pub async fn create_session(
+        &self,
+        data: serde_json::Value,
+        idle_timeout: Duration,
+    ) -> Result<Session> {
+        let body = CreateSessionRequest {
+            data,
+            idle_timeout_secs: idle_timeout.as_secs(),
+        };
+        let response = self
+            .send_to(
+                |base| session_url(base, None, None),
+                |client, url| client.post(url).json(&body),
+            )
+            .await?;
+        read_json(expect_success(response).await?).await
+    }
+
+    /// Push the expiry of session `id` to its idle timeout from now; returns the new
+    /// expiry, `None` if the session expired or never existed
+    pub async fn touch_session(&self, id: &str) -> Result<Option<u64>> {
+        let response = self
+            .send_to(
+                |base| session_url(base, Some(id), Some("touch")),
+                |client, url| client.post(url),
+            )
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response: TouchSessionResponse = read_json(expect_success(response).await?).await?;
+        Ok(Some(response.expires_at))
+    }
+
+    /// Read session `id`, which touches it; `None` if it expired or never existed
+    pub async fn get_session(&self, id: &str) -> Result<Option<Session>> {
+        let response = self
+            .send_to(
+                |base| session_url(base, Some(id), None),
+                |client, url| client.get(url),
+            )
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        read_json(expect_success(response).await?).await.map(Some)
+    }
+
+    /// Destroy session `id`; `false` if it expired or never existed
+    pub async fn destroy_session(&self, id: &str) -> Result<bool> {
+        let response = self
+            .send_to(
+                |base| session_url(base, Some(id), None),
+                |client, url| client.delete(url),
+            )
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        expect_success(response).await?;
+        Ok(true)
+    }
+
+    /// Append `payload` to `queue` and return the item's ID
+    ///
+    /// Fails with `ScribeError::Conflict` while the queue is full.
+    pub async fn enqueue(&self, queue: &str, payload: Vec<u8>) -> Result<u64> {
+        let response = self
+            .send_to(
+                |base| queue_url(base, queue, None),
+                |client, url| client.post(url).body(payload.clone()),
+            )
+            .await?;
+        let response: EnqueueResponse =
+            read_json(expect_success_or_conflict(response).await?).await?;
+        Ok(response.id)
+    }
+
+    /// Take the oldest visible item of `queue`, leased for `visibility_timeout`
+    /// (whole seconds); `None` if no item is visible
+    pub async fn dequeue(
+        &self,
+        queue: &str,
+        visibility_timeout: Duration,
+    ) -> Result<Option<QueueItem>> {
+        let body = DequeueRequest {
+            visibility_timeout_secs: visibility_timeout.as_secs(),
+        };
+        let response = self
+            .send_to(
+                |base| queue_url(base, queue, Some("dequeue")),
+                |client, url| client.post(url).json(&body),
+            )
+            .await?;
+        let response = expect_success(response).await?;
+        if response.status() == StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+
+        let header = |name: &str| -> Result<u64> {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| ScribeError::Serialization(format!("Missing {} header", name)))
+        };
+        let id = header(QUEUE_ITEM_ID_HEADER)?;
+        let receipt = header(QUEUE_RECEIPT_HEADER)?;
+        let attempts = header(QUEUE_ATTEMPTS_HEADER)? as u32;
+        let visible_at = header(QUEUE_VISIBLE_AT_HEADER)?;
+        let payload = response
+            .bytes()
+            .await
+            .map_err(|e| ScribeError::Network(format!("Failed to read response: {}", e)))?;
+        Ok(Some(QueueItem {
+            queue: queue.to_string(),
+            id,
+            receipt,
+            payload: payload.to_vec(),
+            attempts,
+            visible_at,
+        }))
+    }
+
+    /// Remove a processed item from its queue; `false` if it was acked already or
+    /// delivered again after its lease ran out
+    pub async fn ack(&self, item: &QueueItem) -> Result<bool> {
+        let body = AckRequest {
+            id: item.id,
+            receipt: item.receipt,
+        };
+        let response = self
+            .send_to(
+                |base| queue_url(base, &item.queue, Some("ack")),
+                |client, url| client.post(url).json(&body),
+            )
+            .await?;
+        let response: AckResponse = read_json(expect_success(response).await?).await?;
+        Ok(response.acked)
+    }
+
+    /// Length of `queue`, as applied on the answering node
+    pub async fn queue_stats(&self, queue: &str) -> Result<QueueStats> {
+        let response = self
+            .send_to(
+                |base| queue_url(base, queue, None),
+                |client, url| client.get(url),
+            )
+            .await?;
+        read_json(expect_success(response).await?).await
+    }
+
+    /// Merkle root of every sync bucket (see [`crate::sync`])
+    pub async fn sync_buckets(&self, buckets: u32) -> Result<Vec<BucketDigest>> {
+        let response = self
+            .send_to(
+                |base| sync_url(base, None, buckets),
+                |client, url| client.get(url),
+            )
+            .await?;
+        read_json(expect_success(response).await?).await
+    }
+
+    /// Value digest of every key in sync bucket `bucket`
+    pub async fn sync_bucket_keys(&self, bucket: u32, buckets: u32) -> Result<Vec<KeyDigest>> {
+        let response = self
+            .send_to(
+                |base| sync_url(base, Some(bucket), buckets),
+                |client, url| client.get(url),
+            )
+            .await?;
+        read_json(expect_success(response).await?).await
+    }
+
+    /// Send a request for `key`, failing over between nodes
+    async fn send<F>(&self, key: &[u8], build: F) -> Result<Response>
+    where
+        F: Fn(&Client, &str) -> RequestBuilder,
+    {
+        self.send_to(|base| key_url(base, key), build).await
+    }
+
+    /// Send a request to the URL `url_for` builds from a node's base URL, failing
+    /// over between nodes
+    async fn send_to<U, F>(&self, url_for: U, build: F) -> Result<Response>
+    where
+        U: Fn(&str) -> Result<String>,
+        F: Fn(&Client, &str) -> RequestBuilder,
+    {
+        let max_attempts = self.retry.max_attempts().max(1);
+        let mut attempt = 1;
+
+        loop {
+            let index = self.current.load(Ordering::Relaxed);
+            let url = url_for(&self.endpoints[index % self.endpoints.len()])?;
+
+            let request = |url: &str| match &self.session {
+                Some(session) => session.attach(build(&self.client, url)),
+                None => build(&self.client, url),
+            };
+            let (reason, error, response) = match send_following_leader(&url, request).await {
+                Ok(response) => match RetryReason::for_status(response.status()) {
+                    Some(reason) => (
+                        reason,
+                        format!("{} returned {}", url, response.status()),
+                        Some(response),
+                    ),
+                    None => {
+                        self.remember_leader(index, response.url());
+                        if let Some(session) = &self.session {
+                            session.observe_headers(response.headers());
+                        }
+                        return Ok(response);
+                    }
+                },
+                Err(e) => (RetryReason::for_error(&e), format!("{}: {}", url, e), None),
+            };
+
+            // Move on to the next node, unless another request already did
+            let _ = self.current.compare_exchange(
+                index,
+                index.wrapping_add(1),
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            );
+
+            let retry_after = response
+                .as_ref()
+                .and_then(|response| retry_after(response.headers()));
+            let Some(delay) = self.retry.delay(attempt, reason, retry_after) else {
+                if attempt == max_attempts && self.retry.retries(reason) {
+                    return Err(ScribeError::Network(format!(
+                        "No node answered after {} attempts (last error: {})",
+                        max_attempts, error
+                    )));
+                }
+                // Not retried: the caller gets the answer itself
+                return match response {
+                    Some(response) => Ok(response),
+                    None => Err(ScribeError::Network(error)),
+                };
+            };
+            self.retry.observe(&RetryEvent {
+                attempt,
+                reason,
+                url,
+                error,
+                delay,
+            });
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Send later requests to the node at `answered` if it is a configured node
+    /// other than the one at `index`, i.e. a redirect led to the leader
+    fn remember_leader(&self, index: usize, answered: &reqwest::Url) {
+        let leader = self.endpoints.iter().position(|endpoint| {
+            reqwest::Url::parse(endpoint).is_ok_and(|url| url.origin() == answered.origin())
+        });
+        if let Some(leader) = leader.filter(|leader| *leader != index % self.endpoints.len()) {
+            // Unless another request already moved on
+            let _ =
+                self.current
+                    .compare_exchange(index, leader, Ordering::Relaxed, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Attach the fence headers to a write
+fn fenced(request: RequestBuilder, fence: &Fence) -> RequestBuilder {
+    request
+        .header(FENCE_NAMESPACE_HEADER, fence.namespace.as_str())
+        .header(FENCE_EPOCH_HEADER, fence.epoch)
+}
+
+/// Turn an unsuccessful response into an error
+async fn expect_success(response: Response) -> Result<Response> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    Err(ScribeError::Network(format!(
+        "Request failed with {}: {}",
+        status, body
+    )))
+}
+
+/// Like [`expect_success`], reporting `409 Conflict` as `ScribeError::Conflict`
+async fn expect_success_or_conflict(response: Response) -> Result<Response> {
+    if response.status() == StatusCode::CONFLICT {
+        let body = response.text().await.unwrap_or_default();
+        return Err(ScribeError::Conflict(body));
+    }
+    expect_success(response).await
+}
+
+/// Whether a conditional write succeeded, `412 Precondition Failed` meaning it did not
+async fn precondition_met(response: Response) -> Result<bool> {
+    if response.status() == StatusCode::PRECONDITION_FAILED {
+        return Ok(false);
+    }
+    expect_success(response).await.map(|_| true)
+}
+
+/// Read the previous value answered by a swap or a delete with `return=previous`
+async fn read_previous_value(response: Response) -> Result<Option<Vec<u8>>> {
+    let response = expect_success(response).await?;
+    if response.status() == StatusCode::NO_CONTENT {
+        return Ok(None);
+    }
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| ScribeError::Network(format!("Failed to read response: {}", e)))?;
+    Ok(Some(body.to_vec()))
+}
+
+/// Decode a JSON response body
+async fn read_json<T: serde::de::DeserializeOwned>(response: Response) -> Result<T> {
+    response
+        .json()
+        .await
+        .map_err(|e| ScribeError::Serialization(format!("Invalid response: {}", e)))
+}
+
 /// Perform batched HTTP PUT operations with controlled concurrency
 ///
 /// # Arguments
@@ -42,7 +1270,9 @@ pub async fn batched_put_operations(
             let payload = payloads[j].clone();
 
             handles.push(tokio::spawn(async move {
-                let _response = client.put(&url).json(&payload).send().await.unwrap();
+                let _response = send_following_leader(&url, |url| client.put(url).json(&payload))
+                    .await
+                    .unwrap();
             }));
         }
 
@@ -118,7 +1348,10 @@ pub async fn batched_mixed_operations(
             let handle = if j % 2 == 0 {
                 let payload = payloads[j].clone();
                 tokio::spawn(async move {
-                    let _response = client.put(&url).json(&payload).send().await.unwrap();
+                    let _response =
+                        send_following_leader(&url, |url| client.put(url).json(&payload))
+                            .await
+                            .unwrap();
                 })
             } else {
                 tokio::spawn(async move {
@@ -137,3 +1370,290 @@ pub async fn batched_mixed_operations(
 
     ops
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_cluster_client_requires_endpoints() {
+        assert!(ClusterClient::new(Vec::new()).is_err());
+
+        let client = ClusterClient::new(vec!["http://10.0.0.1:8001".to_string()]).unwrap();
+        assert_eq!(client.current_endpoint(), "http://10.0.0.1:8001");
+    }
+
+    #[test]
+    fn test_batch_ops_json() {
+        let ops: Vec<BatchOp> = serde_json::from_str(
+            r#"[{"op": "put", "key": "a", "value": "1"}, {"op": "delete", "key": "b"}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            ops,
+            vec![
+                BatchOp::Put {
+                    key: "a".to_string(),
+                    value: "1".to_string()
+                },
+                BatchOp::Delete {
+                    key: "b".to_string()
+                },
+            ]
+        );
+        assert!(serde_json::from_str::<Vec<BatchOp>>(r#"[{"op": "get", "key": "a"}]"#).is_err());
+    }
+
+    #[test]
+    fn test_cluster_client_remembers_leader() {
+        let client = ClusterClient::new(vec![
+            "http://10.0.0.1:8001".to_string(),
+            "http://10.0.0.2:8001".to_string(),
+        ])
+        .unwrap();
+
+        // A redirect to a node the client does not know leaves routing alone
+        client.remember_leader(0, &reqwest::Url::parse("http://10.0.0.9:8001/k").unwrap());
+        assert_eq!(client.current_endpoint(), "http://10.0.0.1:8001");
+
+        client.remember_leader(0, &reqwest::Url::parse("http://10.0.0.2:8001/k").unwrap());
+        assert_eq!(client.current_endpoint(), "http://10.0.0.2:8001");
+    }
+
+    #[test]
+    fn test_cluster_client_backs_off() {
+        let endpoints = vec!["http://10.0.0.1:8001".to_string()];
+        let fixed = ClusterClient::new(endpoints.clone())
+            .unwrap()
+            .with_retries(3, Duration::from_millis(100));
+        assert_eq!(fixed.retry_policy().backoff(1), Duration::from_millis(100));
+        assert_eq!(fixed.retry_policy().backoff(5), Duration::from_millis(100));
+
+        let backoff = ClusterClient::new(endpoints)
+            .unwrap()
+            .with_backoff(Duration::from_millis(100), Duration::from_secs(1));
+        for (attempt, ceiling) in [
+            (1, 100),
+            (2, 200),
+            (3, 400),
+            (4, 800),
+            (5, 1000),
+            (60, 1000),
+        ] {
+            let pause = backoff.retry_policy().backoff(attempt);
+            let ceiling = Duration::from_millis(ceiling);
+            assert!(pause >= ceiling / 2 && pause <= ceiling, "{:?}", pause);
+        }
+    }
+
+    #[test]
+    fn test_client_session_tracks_highest_token() {
+        let session = ClientSession::new();
+        let client = ClusterClient::new(vec!["http://10.0.0.1:8001".to_string()])
+            .unwrap()
+            .with_session(session.clone());
+        let request = |session: &ClientSession| {
+            session
+                .attach(client.client.get("http://10.0.0.1:8001/k"))
+                .build()
+                .unwrap()
+        };
+
+        // Nothing to wait for before the first token
+        assert_eq!(client.session().unwrap().token(), 0);
+        assert!(request(&session)
+            .headers()
+            .get(MIN_APPLIED_HEADER)
+            .is_none());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONSISTENCY_TOKEN_HEADER, HeaderValue::from(42u64));
+        session.observe_headers(&headers);
+        headers.insert(CONSISTENCY_TOKEN_HEADER, HeaderValue::from(7u64));
+        session.observe_headers(&headers);
+        session.observe_headers(&HeaderMap::new());
+        assert_eq!(client.session().unwrap().token(), 42);
+        assert_eq!(
+            request(&session).headers().get(MIN_APPLIED_HEADER).unwrap(),
+            "42"
+        );
+
+        // A resumed session picks up where the stored token left off
+        let resumed = ClientSession::resume(session.token());
+        assert_eq!(
+            request(&resumed).headers().get(MIN_APPLIED_HEADER).unwrap(),
+            "42"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cluster_client_fails_over_unreachable_node() {
+        // Nothing listens on port 1 of either address
+        let client = ClusterClient::new(vec![
+            "http://127.0.0.1:1".to_string(),
+            "http://127.0.0.2:1".to_string(),
+        ])
+        .unwrap()
+        .with_retries(2, Duration::from_millis(1));
+
+        assert!(client.get(b"key").await.is_err());
+        // Two failed attempts moved the client around the whole ring
+        assert_eq!(client.current_endpoint(), "http://127.0.0.1:1");
+        assert!(client.put(b"key", b"value".to_vec()).await.is_err());
+    }
+
+    #[test]
+    fn test_value_etag() {
+        let etag = value_etag(b"value");
+        assert!(etag.starts_with('"') && etag.ends_with('"'));
+        assert_eq!(etag.len(), 34);
+        assert_eq!(etag, value_etag(b"value"));
+        assert_ne!(etag, value_etag(b"other"));
+    }
+
+    #[test]
+    fn test_load_hint_from_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(LoadHint::from_headers(&headers), None);
+
+        headers.insert(SERVED_BY_HEADER, HeaderValue::from_static("2"));
+        headers.insert(NODE_LOAD_HEADER, HeaderValue::from_static("40"));
+        headers.insert(NODE_QUEUE_DEPTH_HEADER, HeaderValue::from_static("0"));
+        headers.insert(NODE_ROLE_HEADER, HeaderValue::from_static("follower"));
+        let follower = LoadHint::from_headers(&headers).unwrap();
+        assert_eq!(
+            follower,
+            LoadHint {
+                node_id: 2,
+                load_percent: 40,
+                queue_depth: 0,
+                is_leader: false,
+            }
+        );
+
+        headers.insert(NODE_ROLE_HEADER, HeaderValue::from_static("leader"));
+        let leader = LoadHint {
+            node_id: 1,
+            ..LoadHint::from_headers(&headers).unwrap()
+        };
+        assert!(leader.is_leader);
+        assert!(follower.read_cost() < leader.read_cost());
+
+        let queued = LoadHint {
+            queue_depth: 10,
+            load_percent: 0,
+            ..follower
+        };
+        assert!(leader.read_cost() < queued.read_cost());
+
+        headers.insert(NODE_LOAD_HEADER, HeaderValue::from_static("busy"));
+        assert_eq!(LoadHint::from_headers(&headers), None);
+    }
+
+    #[test]
+    fn test_parse_wait() {
+        assert_eq!(parse_wait("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_wait("250ms").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse_wait("2m").unwrap(), Duration::from_secs(120));
+        assert_eq!(parse_wait("15").unwrap(), Duration::from_secs(15));
+        assert_eq!(parse_wait("0s").unwrap(), Duration::ZERO);
+
+        assert!(parse_wait("").is_err());
+        assert!(parse_wait("s").is_err());
+        assert!(parse_wait("10h").is_err());
+        assert!(parse_wait("-5s").is_err());
+        assert!(parse_wait("3m").is_err());
+    }
+
+    #[test]
+    fn test_negotiate_api_version() {
+        assert_eq!(negotiate_api_version(None).unwrap(), API_VERSION);
+        assert_eq!(negotiate_api_version(Some("1")).unwrap(), 1);
+        assert_eq!(negotiate_api_version(Some("v1")).unwrap(), 1);
+        assert_eq!(negotiate_api_version(Some("3, 1")).unwrap(), 1);
+
+        let err = negotiate_api_version(Some("2")).unwrap_err();
+        assert!(err.to_string().contains("supported: 1"));
+        assert!(negotiate_api_version(Some("latest")).is_err());
+    }
+
+    #[test]
+    fn test_session_url() {
+        assert_eq!(
+            session_url("http://10.0.0.1:8001", Some("ab12"), Some("touch")).unwrap(),
+            "http://10.0.0.1:8001/sessions/ab12/touch"
+        );
+        assert_eq!(
+            session_url("http://10.0.0.1:8001/", None, None).unwrap(),
+            "http://10.0.0.1:8001/sessions"
+        );
+    }
+
+    #[test]
+    fn test_lock_url() {
+        assert_eq!(
+            lock_url("http://10.0.0.1:8001", "jobs/nightly", Some("acquire")).unwrap(),
+            "http://10.0.0.1:8001/locks/jobs%2Fnightly/acquire"
+        );
+        assert_eq!(
+            lock_url("http://10.0.0.1:8001/", "jobs", None).unwrap(),
+            "http://10.0.0.1:8001/locks/jobs"
+        );
+        assert_eq!(
+            queue_url("http://10.0.0.1:8001", "jobs", Some("ack")).unwrap(),
+            "http://10.0.0.1:8001/queues/jobs/ack"
+        );
+    }
+
+    #[test]
+    fn test_leader_redirect_target_location() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            LOCATION,
+            HeaderValue::from_static("http://10.0.0.2:8002/key"),
+        );
+
+        let target = leader_redirect_target(
+            "http://10.0.0.1:8001/key",
+            StatusCode::TEMPORARY_REDIRECT,
+            &headers,
+        );
+        assert_eq!(target.as_deref(), Some("http://10.0.0.2:8002/key"));
+    }
+
+    #[test]
+    fn test_leader_redirect_target_leader_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            RAFT_LEADER_HEADER,
+            HeaderValue::from_static("10.0.0.3:8003"),
+        );
+
+        let target = leader_redirect_target(
+            "http://10.0.0.1:8001/key?x=1",
+            StatusCode::MISDIRECTED_REQUEST,
+            &headers,
+        );
+        assert_eq!(target.as_deref(), Some("http://10.0.0.3:8003/key?x=1"));
+    }
+
+    #[test]
+    fn test_leader_redirect_target_ignores_other_status() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            RAFT_LEADER_HEADER,
+            HeaderValue::from_static("10.0.0.3:8003"),
+        );
+
+        assert!(
+            leader_redirect_target("http://10.0.0.1:8001/key", StatusCode::OK, &headers).is_none()
+        );
+        assert!(leader_redirect_target(
+            "http://10.0.0.1:8001/key",
+            StatusCode::MISDIRECTED_REQUEST,
+            &HeaderMap::new()
+        )
+        .is_none());
+    }
+}
@@ -0,0 +1,169 @@
+//! Per-route HTTP metrics middleware
+//!
+//! [`HttpMetricsLayer`] records every request handled by an axum router in the
+//! Prometheus registry: a latency histogram and a request counter labelled by method,
+//! route and status code, and a gauge of requests in flight by method and route.
+//! Routes are labelled with their pattern (`/:key`, not `/user-42`) so the number of
+//! series stays bounded; requests that match no route are labelled `unmatched`.
+
+use crate::metrics::{record_http_request, HTTP_REQUESTS_IN_FLIGHT};
+use axum::extract::MatchedPath;
+use axum::http::{Method, Request, Response};
+use futures::future::BoxFuture;
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tower::{Layer, Service};
+
+/// Route label for requests that match no route
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Status label for requests whose handler failed without producing a response
+const ERROR_STATUS: &str = "error";
+
+/// Layer adding [`HttpMetrics`] to a router
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HttpMetricsLayer;
+
+impl<S> Layer<S> for HttpMetricsLayer {
+    type Service = HttpMetrics<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HttpMetrics { inner }
+    }
+}
+
+/// Service recording latency, status and concurrency of the requests it handles
+#[derive(Debug, Clone)]
+pub struct HttpMetrics<S> {
+    inner: S,
+}
+
+impl<S, B, ResBody> Service<Request<B>> for HttpMetrics<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let method = method_label(request.method());
+        let route = request
+            .extensions()
+            .get::<MatchedPath>()
+            .map_or(UNMATCHED_ROUTE, MatchedPath::as_str)
+            .to_string();
+        let in_flight = InFlight::start(method, &route);
+        let started = Instant::now();
+        let response = self.inner.call(request);
+
+        Box::pin(async move {
+            let response = response.await;
+            let status = match &response {
+                Ok(response) => response.status().as_str().to_string(),
+                Err(_) => ERROR_STATUS.to_string(),
+            };
+            record_http_request(method, &route, &status, started);
+            drop(in_flight);
+            response
+        })
+    }
+}
+
+/// Counts a request in the in-flight gauge until dropped, even if the request is cancelled
+struct InFlight {
+    method: &'static str,
+    route: String,
+}
+
+impl InFlight {
+    fn start(method: &'static str, route: &str) -> Self {
+        HTTP_REQUESTS_IN_FLIGHT
+            .with_label_values(&[method, route])
+            .inc();
+        Self {
+            method,
+            route: route.to_string(),
+        }
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        HTTP_REQUESTS_IN_FLIGHT
+            .with_label_values(&[self.method, &self.route])
+            .dec();
+    }
+}
+
+/// Method label, folding non-standard methods into `OTHER`
+fn method_label(method: &Method) -> &'static str {
+    match *method {
+        Method::GET => "GET",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::DELETE => "DELETE",
+        Method::PATCH => "PATCH",
+        Method::HEAD => "HEAD",
+        Method::OPTIONS => "OPTIONS",
+        _ => "OTHER",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{init_metrics, HTTP_REQUESTS, HTTP_REQUEST_DURATION};
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use axum::Router;
+
+    fn request_count(method: &str, route: &str, status: &str) -> u64 {
+        HTTP_REQUESTS
+            .with_label_values(&[method, route, status])
+            .get()
+    }
+
+    #[tokio::test]
+    async fn test_records_route_pattern_and_status() {
+        init_metrics();
+        let mut app = Router::new()
+            .route("/metrics-test/:id", get(|| async { StatusCode::NOT_FOUND }))
+            .layer(HttpMetricsLayer);
+
+        let before = request_count("GET", "/metrics-test/:id", "404");
+        // A router is always ready, so it can be called without polling readiness
+        let request = Request::get("/metrics-test/42")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(request_count("GET", "/metrics-test/:id", "404"), before + 1);
+
+        let samples = HTTP_REQUEST_DURATION
+            .with_label_values(&["GET", "/metrics-test/:id", "404"])
+            .get_sample_count();
+        assert!(samples >= 1);
+        assert_eq!(
+            HTTP_REQUESTS_IN_FLIGHT
+                .with_label_values(&["GET", "/metrics-test/:id"])
+                .get(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_method_label() {
+        assert_eq!(method_label(&Method::PUT), "PUT");
+        assert_eq!(
+            method_label(&Method::from_bytes(b"PURGE").unwrap()),
+            "OTHER"
+        );
+    }
+}
@@ -0,0 +1,226 @@
+//! Cluster-wide ID generation
+//!
+//! Segment IDs used to come from a per-node counter starting at 0, so segments
+//! flushed by different nodes could share an ID. [`SnowflakeIds`] composes each ID
+//! from a millisecond timestamp, the node ID and a per-millisecond sequence: IDs of
+//! different nodes never collide, and IDs sort by creation time across the cluster.
+//! [`SequentialIds`] keeps the plain counter for single-node setups and tests.
+
+use crate::error::{Result, ScribeError};
+use crate::types::NodeId;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Start of snowflake timestamps (2024-01-01T00:00:00Z, in milliseconds since the
+/// Unix epoch)
+pub const SNOWFLAKE_EPOCH_MS: u64 = 1_704_067_200_000;
+
+/// Bits of a snowflake ID holding the node ID
+const NODE_BITS: u32 = 10;
+
+/// Bits of a snowflake ID holding the sequence within a millisecond
+const SEQUENCE_BITS: u32 = 12;
+
+const SEQUENCE_MASK: u64 = (1 << SEQUENCE_BITS) - 1;
+
+const TIMESTAMP_SHIFT: u32 = NODE_BITS + SEQUENCE_BITS;
+
+/// Largest node ID snowflake IDs can hold
+pub const MAX_SNOWFLAKE_NODE_ID: NodeId = (1 << NODE_BITS) - 1;
+
+/// Source of unique, increasing IDs
+pub trait IdGenerator: Send + Sync + std::fmt::Debug {
+    /// Next ID, greater than every ID handed out or observed before
+    fn next_id(&self) -> u64;
+
+    /// Make later IDs greater than `id`, e.g. an ID persisted by an earlier run
+    fn observe(&self, id: u64);
+}
+
+/// ID scheme, as configured
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdScheme {
+    /// Time-ordered IDs unique across the cluster ([`SnowflakeIds`])
+    #[default]
+    Snowflake,
+    /// Per-node counter starting at 0 ([`SequentialIds`])
+    Sequential,
+}
+
+impl IdScheme {
+    /// Generator of this scheme for node `node_id`
+    pub fn generator(self, node_id: NodeId) -> Result<Arc<dyn IdGenerator>> {
+        Ok(match self {
+            IdScheme::Snowflake => Arc::new(SnowflakeIds::new(node_id)?),
+            IdScheme::Sequential => Arc::new(SequentialIds::new()),
+        })
+    }
+}
+
+/// Counter handing out 0, 1, 2, ...
+#[derive(Debug, Default)]
+pub struct SequentialIds {
+    next: AtomicU64,
+}
+
+impl SequentialIds {
+    /// Create a counter starting at 0
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl IdGenerator for SequentialIds {
+    fn next_id(&self) -> u64 {
+        self.next.fetch_add(1, Ordering::SeqCst)
+    }
+
+    fn observe(&self, id: u64) {
+        self.next.fetch_max(id.saturating_add(1), Ordering::SeqCst);
+    }
+}
+
+/// Snowflake-style IDs: 41 bits of milliseconds since [`SNOWFLAKE_EPOCH_MS`], 10 bits
+/// of node ID and 12 bits of sequence
+///
+/// IDs keep increasing if the clock steps back: the generator then continues from
+/// the last timestamp it used, borrowing from the following milliseconds once a
+/// millisecond's 4096 sequence numbers are used up.
+#[derive(Debug)]
+pub struct SnowflakeIds {
+    node_bits: u64,
+    last: AtomicU64,
+}
+
+impl SnowflakeIds {
+    /// Create a generator for node `node_id`, which must not exceed
+    /// [`MAX_SNOWFLAKE_NODE_ID`]
+    pub fn new(node_id: NodeId) -> Result<Self> {
+        if node_id > MAX_SNOWFLAKE_NODE_ID {
+            return Err(ScribeError::Configuration(format!(
+                "Node ID {} does not fit in snowflake IDs (max {})",
+                node_id, MAX_SNOWFLAKE_NODE_ID
+            )));
+        }
+        Ok(Self {
+            node_bits: node_id << SEQUENCE_BITS,
+            last: AtomicU64::new(0),
+        })
+    }
+
+    /// ID of this node with `timestamp` and `sequence`
+    fn compose(&self, timestamp: u64, sequence: u64) -> u64 {
+        (timestamp << TIMESTAMP_SHIFT) | self.node_bits | sequence
+    }
+
+    /// Next ID at `now` (milliseconds since [`SNOWFLAKE_EPOCH_MS`]) after `last`
+    fn next_after(&self, last: u64, now: u64) -> u64 {
+        let timestamp = last >> TIMESTAMP_SHIFT;
+        let sequence = last & SEQUENCE_MASK;
+        if now > timestamp {
+            self.compose(now, 0)
+        } else if sequence < SEQUENCE_MASK {
+            self.compose(timestamp, sequence + 1)
+        } else {
+            self.compose(timestamp + 1, 0)
+        }
+    }
+}
+
+impl IdGenerator for SnowflakeIds {
+    fn next_id(&self) -> u64 {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or(0)
+            .saturating_sub(SNOWFLAKE_EPOCH_MS);
+        let mut last = self.last.load(Ordering::SeqCst);
+        loop {
+            let next = self.next_after(last, now);
+            match self
+                .last
+                .compare_exchange_weak(last, next, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                Ok(_) => return next,
+                Err(actual) => last = actual,
+            }
+        }
+    }
+
+    fn observe(&self, id: u64) {
+        // Later IDs start after the observed millisecond
+        let timestamp = id >> TIMESTAMP_SHIFT;
+        self.last
+            .fetch_max(self.compose(timestamp, SEQUENCE_MASK), Ordering::SeqCst);
+    }
+}
+
+/// Split a snowflake ID into its creation time (milliseconds since the Unix epoch),
+/// node ID and sequence
+pub fn snowflake_parts(id: u64) -> (u64, NodeId, u64) {
+    (
+        (id >> TIMESTAMP_SHIFT) + SNOWFLAKE_EPOCH_MS,
+        (id >> SEQUENCE_BITS) & MAX_SNOWFLAKE_NODE_ID,
+        id & SEQUENCE_MASK,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snowflake_ids_are_unique_per_node_and_time_ordered() {
+        let node1 = SnowflakeIds::new(1).unwrap();
+        let node2 = SnowflakeIds::new(2).unwrap();
+
+        let ids1: Vec<u64> = (0..10_000).map(|_| node1.next_id()).collect();
+        let ids2: Vec<u64> = (0..10_000).map(|_| node2.next_id()).collect();
+        assert!(ids1.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(ids2.iter().all(|id| !ids1.contains(id)));
+
+        let (created_at, node_id, _) = snowflake_parts(ids2[0]);
+        assert_eq!(node_id, 2);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        assert!(created_at <= now && now - created_at < 60_000);
+
+        assert!(SnowflakeIds::new(MAX_SNOWFLAKE_NODE_ID).is_ok());
+        assert!(SnowflakeIds::new(MAX_SNOWFLAKE_NODE_ID + 1).is_err());
+    }
+
+    #[test]
+    fn test_snowflake_survives_clock_steps_and_sequence_overflow() {
+        let ids = SnowflakeIds::new(5).unwrap();
+        let last = ids.compose(1_000, SEQUENCE_MASK - 1);
+
+        // The clock stepped back: continue from the last timestamp
+        let next = ids.next_after(last, 900);
+        assert_eq!(snowflake_parts(next).2, SEQUENCE_MASK);
+        // Sequence exhausted: borrow the next millisecond, keeping the node bits
+        let next = ids.next_after(next, 900);
+        assert_eq!(next, ids.compose(1_001, 0));
+        assert_eq!(snowflake_parts(next).1, 5);
+
+        // An observed future ID pushes later IDs past it
+        let future = ids.compose(u64::MAX >> (TIMESTAMP_SHIFT + 1), 7);
+        ids.observe(future);
+        assert!(ids.next_id() > future);
+    }
+
+    #[test]
+    fn test_sequential_ids() {
+        let ids = SequentialIds::new();
+        assert_eq!(ids.next_id(), 0);
+        assert_eq!(ids.next_id(), 1);
+        ids.observe(9);
+        assert_eq!(ids.next_id(), 10);
+        ids.observe(3);
+        assert_eq!(ids.next_id(), 11);
+    }
+}
@@ -0,0 +1,486 @@
+//! Order-preserving composite keys
+//!
+//! Keys built with [`KeyBuilder`] sort bytewise in the same order as their parts, so
+//! a prefix or range scan returns entities in id or time order. Ad hoc keys such as
+//! `format!("user:{}:{}", id, ts)` do not: `user:10` sorts before `user:9`.
+//!
+//! Encodings:
+//! - unsigned integers: fixed-width big-endian
+//! - signed integers: big-endian with the sign bit flipped, so negatives come first
+//! - timestamps: microseconds since the Unix epoch as a `u64`
+//! - strings and byte strings: `0x00` bytes escaped as `0x00 0xFF`, terminated by
+//!   `0x00 0x01`, so a string sorts before any longer string it is a prefix of
+//!
+//! [`TypedKey`] ties an encoding to a Rust type under a fixed namespace and entity
+//! type.
+
+use crate::error::{Result, ScribeError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Byte that escapes or terminates in string parts
+const ESCAPE: u8 = 0x00;
+
+/// Follows [`ESCAPE`] for a literal zero byte
+const ESCAPED_ZERO: u8 = 0xFF;
+
+/// Follows [`ESCAPE`] at the end of a string part
+const TERMINATOR: u8 = 0x01;
+
+fn invalid_key(message: &str) -> ScribeError {
+    ScribeError::Serialization(format!("Invalid key: {}", message))
+}
+
+/// A value that can be one part of a composite key
+pub trait KeyPart: Sized {
+    /// Append the order-preserving encoding of `self` to `out`
+    fn encode_key(&self, out: &mut Vec<u8>);
+
+    /// Decode a value from the front of `input`, advancing it past the value
+    fn decode_key(input: &mut &[u8]) -> Result<Self>;
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if input.len() < len {
+        return Err(invalid_key("truncated part"));
+    }
+    let (head, rest) = input.split_at(len);
+    *input = rest;
+    Ok(head)
+}
+
+macro_rules! unsigned_key_part {
+    ($($ty:ty),*) => {$(
+        impl KeyPart for $ty {
+            fn encode_key(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_be_bytes());
+            }
+
+            fn decode_key(input: &mut &[u8]) -> Result<Self> {
+                let bytes = take(input, std::mem::size_of::<$ty>())?;
+                Ok(<$ty>::from_be_bytes(bytes.try_into().expect("length checked")))
+            }
+        }
+    )*};
+}
+
+macro_rules! signed_key_part {
+    ($($ty:ty => $unsigned:ty),*) => {$(
+        impl KeyPart for $ty {
+            fn encode_key(&self, out: &mut Vec<u8>) {
+                ((*self as $unsigned) ^ (1 << (<$unsigned>::BITS - 1))).encode_key(out);
+            }
+
+            fn decode_key(input: &mut &[u8]) -> Result<Self> {
+                let flipped = <$unsigned>::decode_key(input)?;
+                Ok((flipped ^ (1 << (<$unsigned>::BITS - 1))) as $ty)
+            }
+        }
+    )*};
+}
+
+unsigned_key_part!(u8, u16, u32, u64);
+signed_key_part!(i32 => u32, i64 => u64);
+
+impl KeyPart for Vec<u8> {
+    fn encode_key(&self, out: &mut Vec<u8>) {
+        encode_bytes(self, out);
+    }
+
+    fn decode_key(input: &mut &[u8]) -> Result<Self> {
+        let mut decoded = Vec::new();
+        loop {
+            let position = input
+                .iter()
+                .position(|byte| *byte == ESCAPE)
+                .ok_or_else(|| invalid_key("unterminated string part"))?;
+            decoded.extend_from_slice(&input[..position]);
+            match input.get(position + 1) {
+                Some(&TERMINATOR) => {
+                    *input = &input[position + 2..];
+                    return Ok(decoded);
+                }
+                Some(&ESCAPED_ZERO) => {
+                    decoded.push(0);
+                    *input = &input[position + 2..];
+                }
+                _ => return Err(invalid_key("bad escape in string part")),
+            }
+        }
+    }
+}
+
+impl KeyPart for String {
+    fn encode_key(&self, out: &mut Vec<u8>) {
+        encode_bytes(self.as_bytes(), out);
+    }
+
+    fn decode_key(input: &mut &[u8]) -> Result<Self> {
+        String::from_utf8(Vec::decode_key(input)?)
+            .map_err(|_| invalid_key("string part is not UTF-8"))
+    }
+}
+
+impl KeyPart for SystemTime {
+    /// Times before the epoch encode as the epoch
+    fn encode_key(&self, out: &mut Vec<u8>) {
+        let micros = self
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_micros().min(u64::MAX as u128) as u64)
+            .unwrap_or(0);
+        micros.encode_key(out);
+    }
+
+    fn decode_key(input: &mut &[u8]) -> Result<Self> {
+        Ok(UNIX_EPOCH + Duration::from_micros(u64::decode_key(input)?))
+    }
+}
+
+fn encode_bytes(bytes: &[u8], out: &mut Vec<u8>) {
+    for byte in bytes {
+        if *byte == ESCAPE {
+            out.extend_from_slice(&[ESCAPE, ESCAPED_ZERO]);
+        } else {
+            out.push(*byte);
+        }
+    }
+    out.extend_from_slice(&[ESCAPE, TERMINATOR]);
+}
+
+/// Builds a composite key part by part
+///
+/// ```
+/// use hyra_scribe_ledger::keys::KeyBuilder;
+///
+/// let key = KeyBuilder::new().str("app").str("user").u64(42).build();
+/// let later = KeyBuilder::new().str("app").str("user").u64(100).build();
+/// assert!(key < later);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyBuilder {
+    buf: Vec<u8>,
+}
+
+impl KeyBuilder {
+    /// Start an empty key
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append any [`KeyPart`]
+    pub fn part<P: KeyPart>(mut self, part: &P) -> Self {
+        part.encode_key(&mut self.buf);
+        self
+    }
+
+    /// Append a string part, e.g. a namespace or entity type
+    pub fn str(mut self, part: &str) -> Self {
+        encode_bytes(part.as_bytes(), &mut self.buf);
+        self
+    }
+
+    /// Append a byte string part
+    pub fn bytes(mut self, part: &[u8]) -> Self {
+        encode_bytes(part, &mut self.buf);
+        self
+    }
+
+    /// Append an unsigned integer part
+    pub fn u64(self, part: u64) -> Self {
+        self.part(&part)
+    }
+
+    /// Append a signed integer part
+    pub fn i64(self, part: i64) -> Self {
+        self.part(&part)
+    }
+
+    /// Append a timestamp part, with microsecond precision
+    pub fn timestamp(self, part: SystemTime) -> Self {
+        self.part(&part)
+    }
+
+    /// The key built so far
+    pub fn build(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+/// Reads the parts of a composite key in the order they were built
+#[derive(Debug, Clone)]
+pub struct KeyReader<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> KeyReader<'a> {
+    /// Start reading `key` from its first part
+    pub fn new(key: &'a [u8]) -> Self {
+        Self { rest: key }
+    }
+
+    /// Read any [`KeyPart`]
+    pub fn part<P: KeyPart>(&mut self) -> Result<P> {
+        P::decode_key(&mut self.rest)
+    }
+
+    /// Read a string part
+    pub fn str(&mut self) -> Result<String> {
+        self.part()
+    }
+
+    /// Read a byte string part
+    pub fn bytes(&mut self) -> Result<Vec<u8>> {
+        self.part()
+    }
+
+    /// Read an unsigned integer part
+    pub fn u64(&mut self) -> Result<u64> {
+        self.part()
+    }
+
+    /// Read a signed integer part
+    pub fn i64(&mut self) -> Result<i64> {
+        self.part()
+    }
+
+    /// Read a timestamp part
+    pub fn timestamp(&mut self) -> Result<SystemTime> {
+        self.part()
+    }
+
+    /// Read a string part and check that it is `expected`
+    pub fn expect_str(&mut self, expected: &str) -> Result<()> {
+        let actual = self.str()?;
+        if actual == expected {
+            Ok(())
+        } else {
+            Err(invalid_key(&format!(
+                "expected '{}', found '{}'",
+                expected, actual
+            )))
+        }
+    }
+
+    /// Check that every part was read
+    pub fn finish(self) -> Result<()> {
+        if self.rest.is_empty() {
+            Ok(())
+        } else {
+            Err(invalid_key("unexpected trailing bytes"))
+        }
+    }
+}
+
+/// A Rust type stored under keys of one namespace and entity type
+///
+/// ```
+/// use hyra_scribe_ledger::error::Result;
+/// use hyra_scribe_ledger::keys::{KeyBuilder, KeyReader, TypedKey};
+///
+/// #[derive(Debug, PartialEq)]
+/// struct UserEvent {
+///     user_id: u64,
+///     seq: u64,
+/// }
+///
+/// impl TypedKey for UserEvent {
+///     const NAMESPACE: &'static str = "app";
+///     const ENTITY: &'static str = "user_event";
+///
+///     fn write_parts(&self, key: KeyBuilder) -> KeyBuilder {
+///         key.u64(self.user_id).u64(self.seq)
+///     }
+///
+///     fn read_parts(key: &mut KeyReader<'_>) -> Result<Self> {
+///         Ok(Self { user_id: key.u64()?, seq: key.u64()? })
+///     }
+/// }
+///
+/// let event = UserEvent { user_id: 7, seq: 1 };
+/// assert_eq!(UserEvent::from_key(&event.to_key()).unwrap(), event);
+/// assert!(event.to_key().starts_with(&UserEvent::key_prefix()));
+/// ```
+pub trait TypedKey: Sized {
+    /// Namespace every key of this type starts with
+    const NAMESPACE: &'static str;
+    /// Entity type following the namespace
+    const ENTITY: &'static str;
+
+    /// Append the parts identifying `self`
+    fn write_parts(&self, key: KeyBuilder) -> KeyBuilder;
+
+    /// Read the parts written by [`TypedKey::write_parts`]
+    fn read_parts(key: &mut KeyReader<'_>) -> Result<Self>;
+
+    /// Builder positioned after the namespace and entity type
+    fn key_builder() -> KeyBuilder {
+        KeyBuilder::new().str(Self::NAMESPACE).str(Self::ENTITY)
+    }
+
+    /// Prefix shared by every key of this type, for prefix scans
+    fn key_prefix() -> Vec<u8> {
+        Self::key_builder().build()
+    }
+
+    /// The key of `self`
+    fn to_key(&self) -> Vec<u8> {
+        self.write_parts(Self::key_builder()).build()
+    }
+
+    /// Decode a key of this type
+    fn from_key(key: &[u8]) -> Result<Self> {
+        let mut reader = KeyReader::new(key);
+        reader.expect_str(Self::NAMESPACE)?;
+        reader.expect_str(Self::ENTITY)?;
+        let value = Self::read_parts(&mut reader)?;
+        reader.finish()?;
+        Ok(value)
+    }
+}
+
+/// Smallest key greater than every key starting with `prefix`
+///
+/// The exclusive upper bound of a prefix scan; `None` if no such key exists
+/// (`prefix` is empty or all `0xFF`).
+pub fn prefix_end(prefix: &[u8]) -> Option<Vec<u8>> {
+    let last = prefix.iter().rposition(|byte| *byte != 0xFF)?;
+    let mut end = prefix[..=last].to_vec();
+    end[last] += 1;
+    Some(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Reading {
+        sensor: String,
+        at: SystemTime,
+    }
+
+    impl TypedKey for Reading {
+        const NAMESPACE: &'static str = "metrics";
+        const ENTITY: &'static str = "reading";
+
+        fn write_parts(&self, key: KeyBuilder) -> KeyBuilder {
+            key.str(&self.sensor).timestamp(self.at)
+        }
+
+        fn read_parts(key: &mut KeyReader<'_>) -> Result<Self> {
+            Ok(Self {
+                sensor: key.str()?,
+                at: key.timestamp()?,
+            })
+        }
+    }
+
+    fn sorted(mut keys: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn test_integer_order() {
+        let unsigned: Vec<Vec<u8>> = [0u64, 2, 9, 10, 255, 256, u64::MAX]
+            .iter()
+            .map(|id| KeyBuilder::new().str("user").u64(*id).build())
+            .collect();
+        assert_eq!(sorted(unsigned.clone()), unsigned);
+
+        let signed: Vec<Vec<u8>> = [i64::MIN, -10, -1, 0, 1, 10, i64::MAX]
+            .iter()
+            .map(|n| KeyBuilder::new().i64(*n).build())
+            .collect();
+        assert_eq!(sorted(signed.clone()), signed);
+    }
+
+    #[test]
+    fn test_string_order_and_escaping() {
+        let keys: Vec<Vec<u8>> = ["", "a", "a\0", "a\0b", "ab", "b"]
+            .iter()
+            .map(|s| KeyBuilder::new().str(s).u64(u64::MAX).build())
+            .collect();
+        assert_eq!(sorted(keys.clone()), keys);
+
+        // A string part never runs into the next part
+        let key = KeyBuilder::new().str("a\0\x01").u64(0xFF01).build();
+        let mut reader = KeyReader::new(&key);
+        assert_eq!(reader.str().unwrap(), "a\0\x01");
+        assert_eq!(reader.u64().unwrap(), 0xFF01);
+        reader.finish().unwrap();
+    }
+
+    #[test]
+    fn test_roundtrip_parts() {
+        let at = UNIX_EPOCH + Duration::from_micros(1_700_000_000_123_456);
+        let key = KeyBuilder::new()
+            .str("ns")
+            .bytes(&[0, 1, 0xFF])
+            .i64(-5)
+            .timestamp(at)
+            .part(&7u32)
+            .build();
+
+        let mut reader = KeyReader::new(&key);
+        reader.expect_str("ns").unwrap();
+        assert_eq!(reader.bytes().unwrap(), vec![0, 1, 0xFF]);
+        assert_eq!(reader.i64().unwrap(), -5);
+        assert_eq!(reader.timestamp().unwrap(), at);
+        assert_eq!(reader.part::<u32>().unwrap(), 7);
+        reader.finish().unwrap();
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert!(KeyReader::new(&[0, 1]).u64().is_err());
+        assert!(KeyReader::new(b"abc").str().is_err());
+        assert!(KeyReader::new(&[b'a', 0, 7]).str().is_err());
+        assert!(KeyReader::new(&[0xFF, 0xFE]).str().is_err());
+        assert!(KeyReader::new(&[1]).finish().is_err());
+        assert!(KeyReader::new(&KeyBuilder::new().str("a").build())
+            .expect_str("b")
+            .is_err());
+    }
+
+    #[test]
+    fn test_typed_key() {
+        let reading = Reading {
+            sensor: "temp".to_string(),
+            at: UNIX_EPOCH + Duration::from_secs(60),
+        };
+        let key = reading.to_key();
+        assert!(key.starts_with(&Reading::key_prefix()));
+        assert_eq!(Reading::from_key(&key).unwrap(), reading);
+
+        // Later readings of a sensor sort after earlier ones
+        let later = Reading {
+            sensor: "temp".to_string(),
+            at: UNIX_EPOCH + Duration::from_secs(600),
+        };
+        assert!(later.to_key() > key);
+
+        let mut other = KeyBuilder::new().str("metrics").str("other").build();
+        other.extend_from_slice(&key[Reading::key_prefix().len()..]);
+        assert!(Reading::from_key(&other).is_err());
+        let mut trailing = key.clone();
+        trailing.push(0);
+        assert!(Reading::from_key(&trailing).is_err());
+    }
+
+    #[test]
+    fn test_prefix_end() {
+        assert_eq!(prefix_end(b"ab"), Some(b"ac".to_vec()));
+        assert_eq!(prefix_end(&[1, 0xFF, 0xFF]), Some(vec![2]));
+        assert_eq!(prefix_end(&[0xFF]), None);
+        assert_eq!(prefix_end(&[]), None);
+
+        let prefix = Reading::key_prefix();
+        let end = prefix_end(&prefix).unwrap();
+        let key = Reading {
+            sensor: "\u{10FFFF}".to_string(),
+            at: UNIX_EPOCH + Duration::from_micros(u64::MAX),
+        }
+        .to_key();
+        assert!(prefix < key && key < end);
+    }
+}
@@ -0,0 +1,348 @@
+//! Catch-up progress of Raft learners
+//!
+//! A node joins as a learner and is promoted to voter once it has caught up with the
+//! leader. Raft metrics only expose the index matched by each peer, which leaves
+//! operators to work out how far behind a learner is and how fast it is closing the
+//! gap. The leader serves a [`LearnerProgressReport`] on `GET /cluster/learners`
+//! instead, with, per learner:
+//!
+//! - the transfer phase: installing a snapshot, replicating the log, or caught up
+//! - log and snapshot bytes sent to it
+//! - entries remaining until it matches the leader's last log index
+//! - its replication rate and the estimated time until it is caught up
+//!
+//! [`ReplicationTransfers`] is fed by the Raft network layer with every message sent
+//! to a peer. [`LearnerProgressTracker`] combines it with the matched indexes from
+//! the Raft metrics. Rates are measured between two reports, so the first report
+//! after a learner joins (or after a leader change) has no estimate yet.
+
+use crate::types::NodeId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Default number of entries a learner may trail the leader by and count as caught up
+pub const DEFAULT_CAUGHT_UP_LAG: u64 = 100;
+
+/// Snapshot transfers without a chunk for this long are considered abandoned
+const SNAPSHOT_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Shortest interval a replication rate is measured over
+const MIN_RATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Weight of the latest measurement in the smoothed replication rate
+const RATE_SMOOTHING: f64 = 0.5;
+
+/// Phase of a learner's catch-up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferPhase {
+    /// A snapshot is being sent to the learner
+    Snapshot,
+    /// Log entries are being replicated to the learner
+    Log,
+    /// The learner trails the leader by no more than the allowed lag
+    CaughtUp,
+}
+
+/// Bytes sent to one peer
+#[derive(Debug, Clone, Copy, Default)]
+struct PeerTransfer {
+    log_bytes: u64,
+    snapshot_bytes: u64,
+    /// When the last chunk of a snapshot still in transfer was sent
+    snapshot_chunk_at: Option<Instant>,
+}
+
+/// Bytes of log entries and snapshots sent to each peer
+///
+/// Cheap to clone; clones share the counters.
+#[derive(Debug, Clone, Default)]
+pub struct ReplicationTransfers {
+    peers: Arc<Mutex<HashMap<NodeId, PeerTransfer>>>,
+}
+
+impl ReplicationTransfers {
+    /// Create empty counters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count an append-entries message of `bytes` sent to `peer`
+    pub fn record_log(&self, peer: NodeId, bytes: u64) {
+        let mut peers = self.peers.lock().unwrap_or_else(|e| e.into_inner());
+        peers.entry(peer).or_default().log_bytes += bytes;
+    }
+
+    /// Count a snapshot chunk of `bytes` sent to `peer`; `done` marks the last chunk
+    pub fn record_snapshot_chunk(&self, peer: NodeId, bytes: u64, done: bool) {
+        self.record_snapshot_chunk_at(peer, bytes, done, Instant::now());
+    }
+
+    fn record_snapshot_chunk_at(&self, peer: NodeId, bytes: u64, done: bool, now: Instant) {
+        let mut peers = self.peers.lock().unwrap_or_else(|e| e.into_inner());
+        let transfer = peers.entry(peer).or_default();
+        transfer.snapshot_bytes += bytes;
+        transfer.snapshot_chunk_at = (!done).then_some(now);
+    }
+
+    fn get(&self, peer: NodeId) -> PeerTransfer {
+        let peers = self.peers.lock().unwrap_or_else(|e| e.into_inner());
+        peers.get(&peer).copied().unwrap_or_default()
+    }
+}
+
+/// Replication state of a learner, as known from the Raft metrics of the leader
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LearnerReplication {
+    /// Node ID
+    pub node_id: NodeId,
+    /// Raft address from the membership config
+    pub raft_addr: String,
+    /// Last log index replicated to the learner, if any
+    pub matched_index: Option<u64>,
+}
+
+/// Catch-up progress of one learner
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LearnerProgress {
+    /// Node ID
+    pub node_id: NodeId,
+    /// Raft address from the membership config
+    pub raft_addr: String,
+    /// Current transfer phase
+    pub phase: TransferPhase,
+    /// Last log index replicated to the learner
+    pub matched_index: Option<u64>,
+    /// Entries the learner is behind the leader's last log index
+    pub entries_remaining: u64,
+    /// Bytes of append-entries messages sent to the learner by this leader
+    pub log_bytes_sent: u64,
+    /// Bytes of snapshot data sent to the learner by this leader
+    pub snapshot_bytes_sent: u64,
+    /// Entries replicated per second, once measured
+    pub entries_per_sec: Option<f64>,
+    /// Estimated seconds until the learner is caught up, while a rate is known
+    pub eta_secs: Option<u64>,
+    /// Whether the learner is caught up and can be promoted to voter
+    pub ready_to_promote: bool,
+}
+
+/// Catch-up progress of every learner, as reported by the leader
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LearnerProgressReport {
+    /// Leader that produced the report
+    pub leader: NodeId,
+    /// Index of the leader's last log entry
+    pub last_log_index: Option<u64>,
+    /// Entries a learner may trail by and count as caught up
+    pub max_lag: u64,
+    /// Learners ordered by node ID
+    pub learners: Vec<LearnerProgress>,
+}
+
+/// Matched index of a learner at the previous report
+#[derive(Debug, Clone, Copy)]
+struct RateSample {
+    matched_index: u64,
+    at: Instant,
+    entries_per_sec: Option<f64>,
+}
+
+/// Turns replication state into [`LearnerProgress`], measuring replication rates
+/// between reports
+pub struct LearnerProgressTracker {
+    transfers: ReplicationTransfers,
+    samples: Mutex<HashMap<NodeId, RateSample>>,
+}
+
+impl LearnerProgressTracker {
+    /// Track progress using the byte counters in `transfers`
+    pub fn new(transfers: ReplicationTransfers) -> Self {
+        Self {
+            transfers,
+            samples: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Report the progress of `learners` towards `last_log_index`
+    ///
+    /// A learner counts as caught up once it trails by at most `max_lag` entries.
+    pub fn report(
+        &self,
+        leader: NodeId,
+        last_log_index: Option<u64>,
+        learners: Vec<LearnerReplication>,
+        max_lag: u64,
+    ) -> LearnerProgressReport {
+        self.report_at(leader, last_log_index, learners, max_lag, Instant::now())
+    }
+
+    fn report_at(
+        &self,
+        leader: NodeId,
+        last_log_index: Option<u64>,
+        mut learners: Vec<LearnerReplication>,
+        max_lag: u64,
+        now: Instant,
+    ) -> LearnerProgressReport {
+        learners.sort_by_key(|learner| learner.node_id);
+        let mut samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        // Forget learners that were promoted or removed
+        samples.retain(|id, _| learners.iter().any(|l| l.node_id == *id));
+
+        let learners = learners
+            .into_iter()
+            .map(|learner| {
+                let matched = learner.matched_index.unwrap_or(0);
+                let entries_remaining = last_log_index.unwrap_or(0).saturating_sub(matched);
+                let transfer = self.transfers.get(learner.node_id);
+                let in_snapshot = transfer
+                    .snapshot_chunk_at
+                    .is_some_and(|at| now.duration_since(at) < SNAPSHOT_IDLE_TIMEOUT);
+                let phase = if in_snapshot {
+                    TransferPhase::Snapshot
+                } else if entries_remaining <= max_lag {
+                    TransferPhase::CaughtUp
+                } else {
+                    TransferPhase::Log
+                };
+
+                let entries_per_sec = match samples.get_mut(&learner.node_id) {
+                    Some(sample) => update_rate(sample, matched, now),
+                    None => {
+                        samples.insert(
+                            learner.node_id,
+                            RateSample {
+                                matched_index: matched,
+                                at: now,
+                                entries_per_sec: None,
+                            },
+                        );
+                        None
+                    }
+                };
+                let eta_secs = match phase {
+                    TransferPhase::CaughtUp => Some(0),
+                    TransferPhase::Snapshot => None,
+                    TransferPhase::Log => entries_per_sec
+                        .filter(|rate| *rate > 0.0)
+                        .map(|rate| ((entries_remaining - max_lag) as f64 / rate).ceil() as u64),
+                };
+
+                LearnerProgress {
+                    node_id: learner.node_id,
+                    raft_addr: learner.raft_addr,
+                    phase,
+                    matched_index: learner.matched_index,
+                    entries_remaining,
+                    log_bytes_sent: transfer.log_bytes,
+                    snapshot_bytes_sent: transfer.snapshot_bytes,
+                    entries_per_sec,
+                    eta_secs,
+                    ready_to_promote: phase == TransferPhase::CaughtUp,
+                }
+            })
+            .collect();
+
+        LearnerProgressReport {
+            leader,
+            last_log_index,
+            max_lag,
+            learners,
+        }
+    }
+}
+
+/// Fold the progress since `sample` into its smoothed rate
+fn update_rate(sample: &mut RateSample, matched: u64, now: Instant) -> Option<f64> {
+    let elapsed = now.duration_since(sample.at);
+    if elapsed < MIN_RATE_INTERVAL {
+        return sample.entries_per_sec;
+    }
+    let rate = matched.saturating_sub(sample.matched_index) as f64 / elapsed.as_secs_f64();
+    let smoothed = match sample.entries_per_sec {
+        Some(previous) => previous + RATE_SMOOTHING * (rate - previous),
+        None => rate,
+    };
+    *sample = RateSample {
+        matched_index: matched,
+        at: now,
+        entries_per_sec: Some(smoothed),
+    };
+    Some(smoothed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn learner(node_id: NodeId, matched_index: Option<u64>) -> LearnerReplication {
+        LearnerReplication {
+            node_id,
+            raft_addr: format!("10.0.0.{}:9000", node_id),
+            matched_index,
+        }
+    }
+
+    #[test]
+    fn test_phases_and_eta() {
+        let transfers = ReplicationTransfers::new();
+        let tracker = LearnerProgressTracker::new(transfers.clone());
+        let start = Instant::now();
+
+        transfers.record_snapshot_chunk_at(3, 4096, false, start);
+        transfers.record_log(4, 512);
+        let report = tracker.report_at(
+            1,
+            Some(10_000),
+            vec![learner(4, Some(2_000)), learner(3, None)],
+            100,
+            start,
+        );
+        assert_eq!(report.learners[0].node_id, 3);
+        assert_eq!(report.learners[0].phase, TransferPhase::Snapshot);
+        assert_eq!(report.learners[0].snapshot_bytes_sent, 4096);
+        assert_eq!(report.learners[0].entries_remaining, 10_000);
+        assert_eq!(report.learners[1].phase, TransferPhase::Log);
+        assert_eq!(report.learners[1].log_bytes_sent, 512);
+        assert_eq!(report.learners[1].entries_per_sec, None);
+        assert_eq!(report.learners[1].eta_secs, None);
+
+        // Node 4 replicated 1000 entries in 2 seconds; node 3 finished its snapshot
+        transfers.record_snapshot_chunk_at(3, 1024, true, start + Duration::from_secs(1));
+        let report = tracker.report_at(
+            1,
+            Some(10_000),
+            vec![learner(3, Some(9_950)), learner(4, Some(3_000))],
+            100,
+            start + Duration::from_secs(2),
+        );
+        let (node3, node4) = (&report.learners[0], &report.learners[1]);
+        assert_eq!(node3.phase, TransferPhase::CaughtUp);
+        assert_eq!(node3.snapshot_bytes_sent, 5120);
+        assert!(node3.ready_to_promote);
+        assert_eq!(node3.eta_secs, Some(0));
+        assert_eq!(node4.entries_per_sec, Some(500.0));
+        // 7000 entries remaining, 6900 beyond the allowed lag
+        assert_eq!(node4.eta_secs, Some(14));
+        assert!(!node4.ready_to_promote);
+    }
+
+    #[test]
+    fn test_abandoned_snapshot_and_removed_learners() {
+        let transfers = ReplicationTransfers::new();
+        let tracker = LearnerProgressTracker::new(transfers.clone());
+        let start = Instant::now();
+
+        transfers.record_snapshot_chunk_at(3, 4096, false, start);
+        let later = start + SNAPSHOT_IDLE_TIMEOUT;
+        let report = tracker.report_at(1, Some(500), vec![learner(3, Some(100))], 100, later);
+        assert_eq!(report.learners[0].phase, TransferPhase::Log);
+
+        // Samples of learners no longer reported are dropped
+        tracker.report_at(1, Some(500), Vec::new(), 100, later);
+        assert!(tracker.samples.lock().unwrap().is_empty());
+    }
+}
@@ -1,31 +1,78 @@
 use anyhow::Result;
-use sled::Db;
+use sled::{Db, Transactional};
+use std::ops::RangeBounds;
 use std::path::Path;
 
 // New modules for distributed ledger functionality
+pub mod access_log;
+pub mod admin_events;
+pub mod admission;
 pub mod api;
 pub mod async_storage_ops;
+pub mod backup;
+pub mod bulk;
 pub mod cache;
+pub mod canary;
+pub mod capacity;
+pub mod client;
+pub mod client_cache;
+pub mod clock_skew;
 pub mod cluster;
 pub mod config;
 pub mod consensus;
+pub mod contention;
 pub mod crypto;
+pub mod demo;
 pub mod discovery;
 pub mod error;
+pub mod error_history;
+pub mod health;
+pub mod history;
+pub mod hotkeys;
 pub mod http_client;
+pub mod http_metrics;
+pub mod ids;
 pub mod json_ops;
+pub mod keys;
+pub mod learner_progress;
 pub mod logging;
 pub mod manifest;
+pub mod membership;
+pub mod merge_patch;
 pub mod metrics;
+pub mod metrics_push;
+pub mod migration;
+pub mod mirror;
+pub mod namespace_migration;
 pub mod network;
+pub mod placement;
+pub mod quota;
+pub mod raft_history;
+pub mod read_path;
+pub mod replication;
+pub mod retry;
+pub mod runtime;
+pub mod runtime_info;
 pub mod security;
+pub mod selftest;
+pub mod shadow;
+pub mod status;
 pub mod storage;
 pub mod storage_ops;
+pub mod subscription;
+pub mod sync;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod trace_context;
+pub mod transaction;
 pub mod types;
+pub mod wire;
 
 /// Hyra Scribe Ledger - A minimal key-value storage engine using sled
 pub struct HyraScribeLedger {
     db: Db,
+    /// Earlier values of every key, if the history is kept (see [`history`])
+    history: Option<sled::Tree>,
 }
 
 impl HyraScribeLedger {
@@ -37,7 +84,7 @@ impl HyraScribeLedger {
             .flush_every_ms(Some(5000)) // Flush every 5 seconds for better write throughput
             .mode(sled::Mode::HighThroughput) // Optimize for write throughput
             .open()?;
-        Ok(Self { db })
+        Ok(Self { db, history: None })
     }
 
     /// Create a temporary in-memory instance for testing with optimized config
@@ -48,7 +95,21 @@ impl HyraScribeLedger {
             .flush_every_ms(None) // Let sled manage flushing for temp instances (best perf)
             .mode(sled::Mode::HighThroughput) // Optimize for write throughput
             .open()?;
-        Ok(Self { db })
+        Ok(Self { db, history: None })
+    }
+
+    /// Keep the earlier values of every key, readable with [`Self::get_at`] and
+    /// [`Self::history`]
+    ///
+    /// History recorded by an earlier run is picked up again.
+    pub fn with_history(mut self) -> Result<Self> {
+        self.history = Some(self.db.open_tree(history::HISTORY_TREE)?);
+        Ok(self)
+    }
+
+    /// Whether the ledger keeps the earlier values of keys
+    pub fn has_history(&self) -> bool {
+        self.history.is_some()
     }
 
     /// Put a key-value pair into the storage
@@ -57,10 +118,104 @@ impl HyraScribeLedger {
         K: AsRef<[u8]>,
         V: AsRef<[u8]>,
     {
-        self.db.insert(key.as_ref(), value.as_ref())?;
+        let (key, value) = (key.as_ref(), value.as_ref());
+        match &self.history {
+            Some(history) => self.write_recorded(history, key, Some(value)),
+            None => {
+                self.db.insert(key, value)?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Delete `key`, returning whether it existed
+    pub fn delete<K>(&self, key: K) -> Result<bool>
+    where
+        K: AsRef<[u8]>,
+    {
+        let key = key.as_ref();
+        match &self.history {
+            Some(history) => {
+                if self.db.get(key)?.is_none() {
+                    return Ok(false);
+                }
+                self.write_recorded(history, key, None)?;
+                Ok(true)
+            }
+            None => Ok(self.db.remove(key)?.is_some()),
+        }
+    }
+
+    /// Write `value` (`None`: delete) at `key` and record it in `history` atomically
+    fn write_recorded(&self, history: &sled::Tree, key: &[u8], value: Option<&[u8]>) -> Result<()> {
+        (&*self.db, history)
+            .transaction(|(data, history)| {
+                match value {
+                    Some(value) => data.insert(key, value)?,
+                    None => data.remove(key)?,
+                };
+                history::record::<anyhow::Error>(history, key, value)
+            })
+            .map_err(|e| match e {
+                sled::transaction::TransactionError::Abort(e) => e,
+                sled::transaction::TransactionError::Storage(e) => e.into(),
+            })?;
         Ok(())
     }
 
+    /// Put a key-value pair unless the key already exists
+    ///
+    /// Returns whether the value was stored.
+    pub fn put_if_absent<K, V>(&self, key: K, value: V) -> Result<bool>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        self.compare_and_swap(key, None::<&[u8]>, Some(value))
+    }
+
+    /// Atomically replace the value of `key` with `new` if it is still `expected`
+    ///
+    /// `None` stands for an absent key, both as the expected value and as the new
+    /// one, which deletes the key. Returns whether the swap took place.
+    pub fn compare_and_swap<K, E, N>(
+        &self,
+        key: K,
+        expected: Option<E>,
+        new: Option<N>,
+    ) -> Result<bool>
+    where
+        K: AsRef<[u8]>,
+        E: AsRef<[u8]>,
+        N: AsRef<[u8]>,
+    {
+        let new = new.as_ref().map(|value| value.as_ref());
+        let Some(history) = &self.history else {
+            return Ok(self
+                .db
+                .compare_and_swap(key.as_ref(), expected, new)?
+                .is_ok());
+        };
+        let expected = expected.as_ref().map(|value| value.as_ref());
+        (&*self.db, history)
+            .transaction(|(data, history)| {
+                let current = data.get(key.as_ref())?;
+                if current.as_deref() != expected {
+                    return Ok(false);
+                }
+                match new {
+                    Some(value) => data.insert(key.as_ref(), value)?,
+                    None => data.remove(key.as_ref())?,
+                };
+                history::record(history, key.as_ref(), new)?;
+                Ok(true)
+            })
+            .map_err(|e| match e {
+                sled::transaction::TransactionError::Abort(e) => e,
+                sled::transaction::TransactionError::Storage(e) => e.into(),
+            })
+    }
+
     /// Get a value by key from the storage (optimized, zero-copy when possible)
     pub fn get<K>(&self, key: K) -> Result<Option<Vec<u8>>>
     where
@@ -70,6 +225,41 @@ impl HyraScribeLedger {
         Ok(result.map(|ivec| ivec.to_vec()))
     }
 
+    /// Get the value `key` held at `timestamp_ms` (milliseconds since the Unix
+    /// epoch), `None` if it did not exist or was deleted then
+    ///
+    /// Fails unless the ledger keeps its history.
+    pub fn get_at<K>(&self, key: K, timestamp_ms: u64) -> Result<Option<Vec<u8>>>
+    where
+        K: AsRef<[u8]>,
+    {
+        for revision in history::revisions(self.history_tree()?, key.as_ref()) {
+            let revision = revision?;
+            if revision.timestamp_ms <= timestamp_ms {
+                return Ok(revision.value);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Get up to `limit` revisions of `key`, newest first
+    ///
+    /// Fails unless the ledger keeps its history.
+    pub fn history<K>(&self, key: K, limit: usize) -> Result<Vec<history::KeyRevision>>
+    where
+        K: AsRef<[u8]>,
+    {
+        history::revisions(self.history_tree()?, key.as_ref())
+            .take(limit)
+            .collect()
+    }
+
+    fn history_tree(&self) -> Result<&sled::Tree> {
+        self.history
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("The ledger does not keep a write history"))
+    }
+
     /// Get a value by key without copying (returns reference to internal buffer)
     /// This is more efficient but requires careful lifetime management
     pub fn get_ref<K>(&self, key: K) -> Result<Option<sled::IVec>>
@@ -101,24 +291,34 @@ impl HyraScribeLedger {
         Ok(())
     }
 
-    /// Clear all data from the storage
+    /// Clear all data from the storage, including its history
     pub fn clear(&self) -> Result<()> {
         self.db.clear()?;
+        if let Some(history) = &self.history {
+            history.clear()?;
+        }
         Ok(())
     }
 
     /// Apply a batch of operations atomically
+    ///
+    /// Fails if the ledger keeps its history, which batches would bypass; use
+    /// [`Self::transaction`] instead.
     pub fn apply_batch(&self, batch: sled::Batch) -> Result<()> {
+        self.check_batches_allowed()?;
         self.db.apply_batch(batch)?;
         Ok(())
     }
 
     /// Apply multiple batches atomically without intermediate flushing (best performance)
     /// Optimized to minimize allocations and synchronization overhead
+    ///
+    /// Fails if the ledger keeps its history, like [`Self::apply_batch`].
     pub fn apply_batches<I>(&self, batches: I) -> Result<()>
     where
         I: IntoIterator<Item = sled::Batch>,
     {
+        self.check_batches_allowed()?;
         for batch in batches {
             self.db.apply_batch(batch)?;
         }
@@ -126,10 +326,13 @@ impl HyraScribeLedger {
     }
 
     /// Apply batches with final flush (ensures durability)
+    ///
+    /// Fails if the ledger keeps its history, like [`Self::apply_batch`].
     pub fn apply_batches_with_flush<I>(&self, batches: I) -> Result<()>
     where
         I: IntoIterator<Item = sled::Batch>,
     {
+        self.check_batches_allowed()?;
         for batch in batches {
             self.db.apply_batch(batch)?;
         }
@@ -137,6 +340,41 @@ impl HyraScribeLedger {
         Ok(())
     }
 
+    /// Refuse batches on a ledger that keeps its history: a batch does not expose
+    /// its writes, so they could not be recorded
+    fn check_batches_allowed(&self) -> Result<()> {
+        if self.history.is_some() {
+            return Err(anyhow::anyhow!(
+                "Batches bypass the write history; use a transaction on a ledger that keeps one"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Run `f` as a transaction over several keys
+    ///
+    /// Its writes are applied atomically and in isolation from concurrent
+    /// transactions. `f` may run more than once (see [`crate::transaction`]);
+    /// aborting with [`transaction::abort`] discards its writes and returns the error.
+    /// A ledger that keeps its history records the writes in the same transaction.
+    pub fn transaction<F, A>(&self, f: F) -> Result<A>
+    where
+        F: Fn(&transaction::Transaction<'_>) -> transaction::TxnResult<A>,
+    {
+        let result = match &self.history {
+            Some(history) => (&*self.db, history).transaction(|(data, history)| {
+                f(&transaction::Transaction::new(data, Some(history)))
+            }),
+            None => self
+                .db
+                .transaction(|tree| f(&transaction::Transaction::new(tree, None))),
+        };
+        result.map_err(|e| match e {
+            sled::transaction::TransactionError::Abort(e) => e.into(),
+            sled::transaction::TransactionError::Storage(e) => e.into(),
+        })
+    }
+
     /// Create a new batch for bulk operations
     pub fn new_batch() -> sled::Batch {
         sled::Batch::default()
@@ -149,8 +387,7 @@ impl HyraScribeLedger {
         V: serde::Serialize,
     {
         let encoded = bincode::serialize(value)?;
-        self.db.insert(key.as_ref(), encoded)?;
-        Ok(())
+        self.put(key, encoded)
     }
 
     /// Get and deserialize a value using binary encoding
@@ -167,6 +404,29 @@ impl HyraScribeLedger {
         }
     }
 
+    /// Iterate over all key-value pairs in key order without loading them into memory
+    pub fn iter(&self) -> sled::Iter {
+        self.db.iter()
+    }
+
+    /// Iterate in key order over the key-value pairs whose key starts with `prefix`
+    pub fn scan<P>(&self, prefix: P) -> sled::Iter
+    where
+        P: AsRef<[u8]>,
+    {
+        self.db.scan_prefix(prefix)
+    }
+
+    /// Iterate in key order over the key-value pairs whose key is within `range`,
+    /// e.g. `ledger.range("user:a".."user:m")`
+    pub fn range<K, R>(&self, range: R) -> sled::Iter
+    where
+        K: AsRef<[u8]>,
+        R: RangeBounds<K>,
+    {
+        self.db.range(range)
+    }
+
     /// Get all key-value pairs from the storage
     ///
     /// Note: This is an expensive operation that should be used sparingly,
@@ -209,6 +469,32 @@ impl HyraScribeLedger {
         let tree = crypto::MerkleTree::from_pairs(pairs);
         Ok(tree.get_proof(key.as_ref()))
     }
+
+    /// Export a Merkle proof for `key` with the root it proves against
+    ///
+    /// The proof and the root come from the same tree, so the bundle verifies even
+    /// if the ledger is written to meanwhile. Seal it with [`wire::seal`] to hand it
+    /// to someone checking it offline (see `scribe-verify`); `signer` signs the
+    /// root. Returns `None` if the key does not exist.
+    pub fn proof_bundle<K>(
+        &self,
+        key: K,
+        signer: Option<&backup::BackupSigner>,
+    ) -> Result<Option<wire::ProofBundle>>
+    where
+        K: AsRef<[u8]>,
+    {
+        let tree = crypto::MerkleTree::from_pairs(self.get_all()?);
+        let (Some(proof), Some(root_hash)) = (tree.get_proof(key.as_ref()), tree.root_hash())
+        else {
+            return Ok(None);
+        };
+        let bundle = wire::ProofBundle::new(proof, root_hash);
+        Ok(Some(match signer {
+            Some(signer) => bundle.signed(signer),
+            None => bundle,
+        }))
+    }
 }
 
 impl Drop for HyraScribeLedger {
@@ -708,6 +994,194 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_compare_and_swap() -> Result<()> {
+        let ledger = HyraScribeLedger::temp()?;
+
+        assert!(ledger.put_if_absent("counter", "1")?);
+        assert!(!ledger.put_if_absent("counter", "5")?);
+
+        assert!(!ledger.compare_and_swap("counter", Some("0"), Some("2"))?);
+        assert_eq!(ledger.get("counter")?, Some(b"1".to_vec()));
+        assert!(ledger.compare_and_swap("counter", Some("1"), Some("2"))?);
+        assert_eq!(ledger.get("counter")?, Some(b"2".to_vec()));
+
+        // Swapping in nothing deletes the key
+        assert!(ledger.compare_and_swap("counter", Some("2"), None::<&str>)?);
+        assert_eq!(ledger.get("counter")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_history_and_point_in_time_reads() -> Result<()> {
+        let ledger = HyraScribeLedger::temp()?;
+        assert!(ledger.history("price", 10).is_err());
+        let ledger = ledger.with_history()?;
+        assert!(ledger.has_history());
+
+        ledger.put("price", "10")?;
+        let first = ledger.history("price", 10)?[0].clone();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        ledger.put("price", "12")?;
+        assert!(!ledger.compare_and_swap("price", Some("10"), Some("11"))?);
+        assert!(ledger.compare_and_swap("price", Some("12"), Some("15"))?);
+        assert!(ledger.delete("price")?);
+        assert!(!ledger.delete("price")?);
+
+        let history = ledger.history("price", 10)?;
+        let values: Vec<Option<Vec<u8>>> = history.iter().map(|r| r.value.clone()).collect();
+        assert_eq!(
+            values,
+            vec![
+                None,
+                Some(b"15".to_vec()),
+                Some(b"12".to_vec()),
+                Some(b"10".to_vec())
+            ]
+        );
+        assert_eq!(ledger.history("price", 1)?.len(), 1);
+
+        assert_eq!(ledger.get("price")?, None);
+        assert_eq!(
+            ledger.get_at("price", first.timestamp_ms)?,
+            Some(b"10".to_vec())
+        );
+        assert_eq!(ledger.get_at("price", first.timestamp_ms - 1)?, None);
+        assert_eq!(ledger.get_at("price", u64::MAX)?, None);
+
+        // The history does not count as data
+        assert!(ledger.is_empty());
+        ledger.clear()?;
+        assert!(ledger.history("price", 10)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_history_covers_transactions_and_refuses_batches() -> Result<()> {
+        let ledger = HyraScribeLedger::temp()?.with_history()?;
+        ledger.put("a", "1")?;
+        ledger.transaction(|txn| {
+            txn.put("a", "2")?;
+            txn.put("b", "3")?;
+            txn.delete("a")?;
+            // Nothing to delete, so nothing recorded
+            txn.delete("c")?;
+            Ok(())
+        })?;
+
+        let values = |key: &str| -> Result<Vec<Option<Vec<u8>>>> {
+            Ok(ledger
+                .history(key, 10)?
+                .into_iter()
+                .map(|r| r.value)
+                .collect())
+        };
+        assert_eq!(
+            values("a")?,
+            vec![None, Some(b"2".to_vec()), Some(b"1".to_vec())]
+        );
+        assert_eq!(values("b")?, vec![Some(b"3".to_vec())]);
+        assert!(values("c")?.is_empty());
+
+        // An aborted transaction records nothing
+        let aborted: Result<()> = ledger.transaction(|txn| {
+            txn.put("b", "4")?;
+            transaction::abort(error::ScribeError::Conflict("no".to_string()))
+        });
+        assert!(aborted.is_err());
+        assert_eq!(values("b")?.len(), 1);
+
+        ledger.put_bincode("d", &7u32)?;
+        assert_eq!(values("d")?.len(), 1);
+
+        let mut batch = HyraScribeLedger::new_batch();
+        batch.insert("e", "5");
+        assert!(ledger.apply_batch(batch.clone()).is_err());
+        assert!(ledger.apply_batches([batch.clone()]).is_err());
+        assert!(ledger.apply_batches_with_flush([batch]).is_err());
+        assert_eq!(ledger.get("e")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_transaction_moves_balance() -> Result<()> {
+        let ledger = HyraScribeLedger::temp()?;
+        ledger.put("alice", 100u64.to_be_bytes())?;
+
+        let transfer = |amount: u64| {
+            ledger.transaction(|txn| {
+                let balance = |key: &str| -> transaction::TxnResult<u64> {
+                    Ok(txn
+                        .get(key)?
+                        .map(|value| u64::from_be_bytes(value.try_into().unwrap()))
+                        .unwrap_or(0))
+                };
+                let available = balance("alice")?;
+                if available < amount {
+                    return transaction::abort(error::ScribeError::Conflict(
+                        "Insufficient balance".to_string(),
+                    ));
+                }
+                let credited = balance("bob")?;
+                txn.put("alice", (available - amount).to_be_bytes())?;
+                txn.put("bob", (credited + amount).to_be_bytes())?;
+                Ok(available - amount)
+            })
+        };
+
+        assert_eq!(transfer(30)?, 70);
+        assert_eq!(ledger.get("bob")?, Some(30u64.to_be_bytes().to_vec()));
+
+        // An aborted transaction leaves both keys untouched
+        let err = transfer(500).unwrap_err();
+        assert!(err.to_string().contains("Insufficient balance"));
+        assert_eq!(ledger.get("alice")?, Some(70u64.to_be_bytes().to_vec()));
+        assert_eq!(ledger.get("bob")?, Some(30u64.to_be_bytes().to_vec()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_and_range() -> Result<()> {
+        let ledger = HyraScribeLedger::temp()?;
+        for key in ["user:carol", "user:alice", "order:1", "user:bob", "users"] {
+            ledger.put(key, key.to_uppercase())?;
+        }
+
+        fn keys(
+            iter: impl Iterator<Item = sled::Result<(sled::IVec, sled::IVec)>>,
+        ) -> Result<Vec<String>> {
+            iter.map(|item| Ok(String::from_utf8(item?.0.to_vec())?))
+                .collect()
+        }
+        assert_eq!(
+            keys(ledger.scan("user:"))?,
+            vec!["user:alice", "user:bob", "user:carol"]
+        );
+        assert_eq!(
+            ledger
+                .scan("user:")
+                .next()
+                .transpose()?
+                .map(|(_, v)| v.to_vec()),
+            Some(b"USER:ALICE".to_vec())
+        );
+        assert!(keys(ledger.scan("missing"))?.is_empty());
+        assert_eq!(keys(ledger.scan(""))?.len(), 5);
+
+        assert_eq!(keys(ledger.range("user:b".."user:c"))?, vec!["user:bob"]);
+        assert_eq!(
+            keys(ledger.range("user:bob"..="users"))?,
+            vec!["user:bob", "user:carol", "users"]
+        );
+        assert_eq!(keys(ledger.range(.."user"))?, vec!["order:1"]);
+        assert_eq!(
+            keys(ledger.range("user:b"..).rev())?,
+            vec!["users", "user:carol", "user:bob"]
+        );
+
+        Ok(())
+    }
+
     #[test]
     #[allow(unused_imports)]
     fn test_module_structure() {
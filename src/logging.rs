@@ -2,7 +2,15 @@
 ///
 /// This module provides production-ready logging capabilities using the tracing framework,
 /// including structured logging, log levels, log rotation, and request correlation IDs.
+///
+/// Keys can hold personal data. Log lines, reports and audit entries name keys through
+/// [`log_key`] and [`log_path`], which apply the process-wide [`KeyLoggingConfig`] set
+/// with [`set_key_logging`]; only the data path sees full keys.
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt::{self as std_fmt, Display};
 use std::io;
+use std::sync::OnceLock;
 use tracing::Level;
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_subscriber::{
@@ -175,6 +183,164 @@ pub fn generate_correlation_id() -> String {
 // Re-export fastrand for correlation ID generation
 use fastrand;
 
+/// Default number of leading bytes kept by [`KeyRedaction::Truncate`]
+pub const DEFAULT_KEY_TRUNCATE_LEN: usize = 8;
+
+/// Hex digits of the SHA-256 shown by [`KeyRedaction::Hash`]
+const KEY_HASH_HEX_LEN: usize = 16;
+
+/// Top-level routes whose next path segment is a route name rather than a key
+const CONTROL_ROUTES: &[&str] = &["admin", "cluster", "debug", "health", "metrics"];
+
+/// How keys are shown outside the data path
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyRedaction {
+    /// Show keys in full
+    #[default]
+    None,
+    /// Show a prefix of the key's SHA-256, e.g. `sha256:9f86d081884c7d65`
+    Hash,
+    /// Show the first bytes of the key followed by `...`
+    Truncate,
+}
+
+/// Redaction of keys in log lines, reports and audit entries (`[logging.keys]`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyLoggingConfig {
+    /// How keys are shown
+    #[serde(default)]
+    pub mode: KeyRedaction,
+    /// Leading bytes kept in `truncate` mode
+    #[serde(default = "default_key_truncate_len")]
+    pub truncate_len: usize,
+}
+
+fn default_key_truncate_len() -> usize {
+    DEFAULT_KEY_TRUNCATE_LEN
+}
+
+impl Default for KeyLoggingConfig {
+    fn default() -> Self {
+        Self {
+            mode: KeyRedaction::None,
+            truncate_len: DEFAULT_KEY_TRUNCATE_LEN,
+        }
+    }
+}
+
+impl KeyLoggingConfig {
+    /// Apply `SCRIBE_LOG_KEYS` (`none`, `hash` or `truncate`) and
+    /// `SCRIBE_LOG_KEY_TRUNCATE_LEN`
+    pub fn apply_env_overrides(&mut self) {
+        if let Ok(mode) = std::env::var("SCRIBE_LOG_KEYS") {
+            match mode.as_str() {
+                "none" => self.mode = KeyRedaction::None,
+                "hash" => self.mode = KeyRedaction::Hash,
+                "truncate" => self.mode = KeyRedaction::Truncate,
+                _ => {}
+            }
+        }
+        if let Ok(len) = std::env::var("SCRIBE_LOG_KEY_TRUNCATE_LEN") {
+            if let Ok(parsed_len) = len.parse() {
+                self.truncate_len = parsed_len;
+            }
+        }
+    }
+
+    /// Check the truncation length
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.mode == KeyRedaction::Truncate && self.truncate_len == 0 {
+            return Err("Key truncate_len must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+
+    /// `key` as it may appear outside the data path
+    pub fn redact(&self, key: &[u8]) -> String {
+        match self.mode {
+            KeyRedaction::None => String::from_utf8_lossy(key).into_owned(),
+            KeyRedaction::Hash => {
+                let mut hash = hex::encode(Sha256::digest(key));
+                hash.truncate(KEY_HASH_HEX_LEN);
+                format!("sha256:{}", hash)
+            }
+            KeyRedaction::Truncate if key.len() > self.truncate_len => {
+                format!("{}...", String::from_utf8_lossy(&key[..self.truncate_len]))
+            }
+            KeyRedaction::Truncate => String::from_utf8_lossy(key).into_owned(),
+        }
+    }
+
+    /// Request `path` with the key segments redacted
+    ///
+    /// The `/v1` and `/v1/kv` prefixes and the route names of control-plane paths
+    /// (`/admin/demote`, `/cluster/learners`, ...) are kept; everything after them is
+    /// treated as one key.
+    pub fn redact_path(&self, path: &str) -> String {
+        let trimmed = path.trim_start_matches('/');
+        if self.mode == KeyRedaction::None || trimmed.is_empty() {
+            return path.to_string();
+        }
+
+        let segments: Vec<&str> = trimmed.split('/').collect();
+        let mut kept = usize::from(segments[0] == "v1");
+        match segments.get(kept) {
+            Some(&"kv") if kept == 1 => kept += 1,
+            Some(segment) if CONTROL_ROUTES.contains(segment) => kept += 2,
+            _ => {}
+        }
+        let (route, key) = segments.split_at(kept.min(segments.len()));
+
+        let mut redacted: String = route
+            .iter()
+            .map(|segment| format!("/{}", segment))
+            .collect();
+        if !key.is_empty() {
+            redacted.push('/');
+            redacted.push_str(&self.redact(key.join("/").as_bytes()));
+        }
+        redacted
+    }
+}
+
+static KEY_LOGGING: OnceLock<KeyLoggingConfig> = OnceLock::new();
+
+/// Set how keys are shown for the rest of the process
+///
+/// Called once at startup, before keys are logged; later calls are ignored and
+/// return `false`. Until it is called keys are shown in full.
+pub fn set_key_logging(config: KeyLoggingConfig) -> bool {
+    KEY_LOGGING.set(config).is_ok()
+}
+
+/// The process-wide key redaction
+pub fn key_logging() -> &'static KeyLoggingConfig {
+    KEY_LOGGING.get_or_init(KeyLoggingConfig::default)
+}
+
+/// A key displayed with the process-wide redaction
+///
+/// Use it wherever a key leaves the data path, e.g. `warn!(key = %log_key(&key), ..)`
+/// or `log_key(&key).to_string()` in a report.
+pub struct LoggedKey<'a>(&'a [u8]);
+
+impl Display for LoggedKey<'_> {
+    fn fmt(&self, f: &mut std_fmt::Formatter<'_>) -> std_fmt::Result {
+        f.write_str(&key_logging().redact(self.0))
+    }
+}
+
+/// Display `key` with the process-wide redaction
+pub fn log_key(key: &[u8]) -> LoggedKey<'_> {
+    LoggedKey(key)
+}
+
+/// Request `path` with its keys redacted by the process-wide redaction
+pub fn log_path(path: &str) -> String {
+    key_logging().redact_path(path)
+}
+
 /// Audit event types for security logging
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AuditEvent {
@@ -329,6 +495,54 @@ mod tests {
         assert_eq!(AuditEvent::DataWrite.as_str(), "data_write");
     }
 
+    #[test]
+    fn test_key_redaction() {
+        let key = b"user:alice@example.com";
+        assert_eq!(
+            KeyLoggingConfig::default().redact(key),
+            "user:alice@example.com"
+        );
+
+        let hash = KeyLoggingConfig {
+            mode: KeyRedaction::Hash,
+            ..Default::default()
+        };
+        let hashed = hash.redact(key);
+        assert_eq!(hashed, hash.redact(key));
+        assert_eq!(hashed.len(), "sha256:".len() + KEY_HASH_HEX_LEN);
+        assert!(!hashed.contains("alice"));
+
+        let truncate = KeyLoggingConfig {
+            mode: KeyRedaction::Truncate,
+            truncate_len: 5,
+        };
+        assert_eq!(truncate.redact(key), "user:...");
+        assert_eq!(truncate.redact(b"abc"), "abc");
+        assert!(KeyLoggingConfig {
+            truncate_len: 0,
+            ..truncate.clone()
+        }
+        .validate()
+        .is_err());
+
+        assert_eq!(truncate.redact_path("/v1/kv/user:alice"), "/v1/kv/user:...");
+        assert_eq!(truncate.redact_path("/user:alice"), "/user:...");
+        assert_eq!(
+            truncate.redact_path("/admin/demote/user:alice"),
+            "/admin/demote/user:..."
+        );
+        assert_eq!(
+            truncate.redact_path("/cluster/learners"),
+            "/cluster/learners"
+        );
+        assert_eq!(truncate.redact_path("/health"), "/health");
+        assert_eq!(truncate.redact_path("/"), "/");
+        assert_eq!(
+            KeyLoggingConfig::default().redact_path("/user:alice"),
+            "/user:alice"
+        );
+    }
+
     #[test]
     fn test_audit_log_function() {
         // Just verify the function can be called without panic
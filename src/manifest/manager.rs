@@ -81,6 +81,14 @@ impl ManifestManager {
         Ok(manifest.remove_entry(segment_id))
     }
 
+    /// Record the Raft log index the segment's Merkle root was committed at
+    ///
+    /// Returns `false` if the manifest does not track the segment.
+    pub async fn anchor_segment(&self, segment_id: SegmentId, raft_index: u64) -> Result<bool> {
+        let mut manifest = self.cached_manifest.write().await;
+        Ok(manifest.anchor_entry(segment_id, raft_index))
+    }
+
     /// Update the cached manifest with a new version
     ///
     /// This is typically called when a manifest update is applied through
@@ -235,6 +243,23 @@ mod tests {
         assert_eq!(not_found, None);
     }
 
+    #[tokio::test]
+    async fn test_anchor_segment() {
+        let manager = ManifestManager::new();
+        let entry = ManifestEntry::new(1, 1234567890, vec![1, 2, 3, 4], 1024);
+        manager.add_segment(entry).await.unwrap();
+        let version = manager.get_version().await;
+
+        assert!(manager.anchor_segment(1, 42).await.unwrap());
+        assert_eq!(manager.get_segment(1).await.unwrap().anchor_index, Some(42));
+        assert_eq!(manager.get_version().await, version + 1);
+
+        // Recording the same anchor again leaves the version alone
+        assert!(manager.anchor_segment(1, 42).await.unwrap());
+        assert_eq!(manager.get_version().await, version + 1);
+        assert!(!manager.anchor_segment(2, 43).await.unwrap());
+    }
+
     #[tokio::test]
     async fn test_update_cache_newer_version() {
         let manager = ManifestManager::new();
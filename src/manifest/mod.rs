@@ -92,6 +92,13 @@ pub struct ManifestEntry {
     pub merkle_root: Vec<u8>,
     /// Size of the segment in bytes
     pub size: usize,
+    /// IDs of the tenant data keys the archived segment is encrypted with
+    #[serde(default)]
+    pub key_ids: Vec<String>,
+    /// Raft log index of the entry that committed the Merkle root, `None` until the
+    /// root is anchored
+    #[serde(default)]
+    pub anchor_index: Option<u64>,
 }
 
 impl ManifestEntry {
@@ -102,6 +109,8 @@ impl ManifestEntry {
             timestamp,
             merkle_root,
             size,
+            key_ids: Vec::new(),
+            anchor_index: None,
         }
     }
 
@@ -116,8 +125,16 @@ impl ManifestEntry {
             timestamp: current_timestamp_secs(),
             merkle_root,
             size,
+            key_ids: Vec::new(),
+            anchor_index: None,
         }
     }
+
+    /// Record the data keys the segment is encrypted with
+    pub fn with_key_ids(mut self, key_ids: Vec<String>) -> Self {
+        self.key_ids = key_ids;
+        self
+    }
 }
 
 /// Cluster-wide manifest tracking all segments and metadata
@@ -167,11 +184,36 @@ impl ClusterManifest {
         }
     }
 
+    /// Record that the Merkle root of segment `segment_id` was committed at Raft
+    /// log index `raft_index`
+    ///
+    /// Returns `false` if the manifest does not track the segment.
+    pub fn anchor_entry(&mut self, segment_id: SegmentId, raft_index: u64) -> bool {
+        let Some(entry) = self.entries.iter_mut().find(|e| e.segment_id == segment_id) else {
+            return false;
+        };
+        if entry.anchor_index != Some(raft_index) {
+            entry.anchor_index = Some(raft_index);
+            self.increment_version();
+        }
+        true
+    }
+
     /// Get an entry by segment ID
     pub fn get_entry(&self, segment_id: SegmentId) -> Option<&ManifestEntry> {
         self.entries.iter().find(|e| e.segment_id == segment_id)
     }
 
+    /// Get the entries of segments encrypted with the data key `key_id`
+    ///
+    /// These are the segments made unreadable when the key is destroyed.
+    pub fn entries_with_key(&self, key_id: &str) -> Vec<&ManifestEntry> {
+        self.entries
+            .iter()
+            .filter(|e| e.key_ids.iter().any(|id| id == key_id))
+            .collect()
+    }
+
     /// Get all entries sorted by timestamp (newest first)
     pub fn get_entries_sorted(&self) -> Vec<ManifestEntry> {
         let mut sorted = self.entries.clone();
@@ -432,6 +474,30 @@ mod tests {
         assert_eq!(entry.size, 1024);
     }
 
+    #[test]
+    fn test_manifest_entries_with_key() {
+        let mut manifest = ClusterManifest::new();
+        manifest.add_entry(
+            ManifestEntry::new(1, 1000, vec![1], 100)
+                .with_key_ids(vec!["acme-1".to_string(), "globex-1".to_string()]),
+        );
+        manifest.add_entry(
+            ManifestEntry::new(2, 2000, vec![2], 200).with_key_ids(vec!["globex-1".to_string()]),
+        );
+        manifest.add_entry(ManifestEntry::new(3, 3000, vec![3], 300));
+
+        let ids = |key_id| {
+            manifest
+                .entries_with_key(key_id)
+                .iter()
+                .map(|e| e.segment_id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids("acme-1"), vec![1]);
+        assert_eq!(ids("globex-1"), vec![1, 2]);
+        assert!(ids("missing").is_empty());
+    }
+
     #[test]
     fn test_cluster_manifest_new() {
         let manifest = ClusterManifest::new();
@@ -0,0 +1,287 @@
+//! Membership changes requested over HTTP
+//!
+//! The leader adds and removes nodes on `POST /cluster/nodes/add` and
+//! `POST /cluster/nodes/remove`. Adding a voter goes through the steps that keep
+//! the cluster available: the node joins as a learner, the leader replicates to it
+//! until it trails by at most `max_lag` entries (see [`crate::learner_progress`]),
+//! and only then is it promoted, so the new quorum never waits on a node that is
+//! still catching up. Each membership change is committed by Raft through joint
+//! consensus, and only one change runs at a time.
+//!
+//! Changes run in the background; [`MembershipChanges`] keeps the running change
+//! and the most recent finished ones with their phase and catch-up progress, served
+//! at `GET /cluster/nodes/changes`.
+
+use crate::learner_progress::LearnerProgress;
+use crate::types::NodeId;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Finished changes kept for reporting
+pub const MAX_FINISHED_CHANGES: usize = 32;
+
+/// What a membership change does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MembershipOp {
+    /// Add a learner and promote it to voter once caught up
+    AddVoter,
+    /// Add a learner only
+    AddLearner,
+    /// Remove a voter or learner
+    Remove,
+}
+
+/// Step a membership change is at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangePhase {
+    /// The node is being added as a learner
+    AddingLearner,
+    /// The learner is being replicated to until it is caught up
+    CatchingUp,
+    /// The caught-up learner is being promoted to voter
+    Promoting,
+    /// The node is being removed
+    Removing,
+    /// The change is committed
+    Completed,
+    /// The change failed; see its error
+    Failed,
+}
+
+impl ChangePhase {
+    /// Whether the change has finished, successfully or not
+    pub fn is_finished(self) -> bool {
+        matches!(self, ChangePhase::Completed | ChangePhase::Failed)
+    }
+}
+
+/// A membership change and how far it got
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MembershipChange {
+    /// ID of the change on this leader
+    pub id: u64,
+    /// Node added or removed
+    pub node_id: NodeId,
+    pub operation: MembershipOp,
+    pub phase: ChangePhase,
+    /// Raft address of an added node
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raft_addr: Option<String>,
+    /// Catch-up progress of an added learner, as last measured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<LearnerProgress>,
+    /// Why the change failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// When the change started (milliseconds since the Unix epoch)
+    pub started_at_ms: u64,
+    /// When the change last moved on
+    pub updated_at_ms: u64,
+}
+
+#[derive(Default)]
+struct ChangeLog {
+    next_id: u64,
+    running: Option<MembershipChange>,
+    /// Newest last
+    finished: VecDeque<MembershipChange>,
+}
+
+/// Running and recent membership changes of this node
+///
+/// Cheap to clone; clones share the changes.
+#[derive(Clone, Default)]
+pub struct MembershipChanges {
+    log: Arc<Mutex<ChangeLog>>,
+}
+
+impl MembershipChanges {
+    /// Create an empty log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a change of `operation` on `node_id`, or refuse if one is running
+    pub fn begin(
+        &self,
+        node_id: NodeId,
+        operation: MembershipOp,
+        raft_addr: Option<String>,
+    ) -> Result<MembershipChange, String> {
+        let mut log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(running) = &log.running {
+            return Err(format!(
+                "Membership change {} on node {} is still running",
+                running.id, running.node_id
+            ));
+        }
+        log.next_id += 1;
+        let now = now_ms();
+        let change = MembershipChange {
+            id: log.next_id,
+            node_id,
+            operation,
+            phase: match operation {
+                MembershipOp::AddVoter | MembershipOp::AddLearner => ChangePhase::AddingLearner,
+                MembershipOp::Remove => ChangePhase::Removing,
+            },
+            raft_addr,
+            progress: None,
+            error: None,
+            started_at_ms: now,
+            updated_at_ms: now,
+        };
+        log.running = Some(change.clone());
+        Ok(change)
+    }
+
+    /// Move the running change `id` to `phase`
+    pub fn set_phase(&self, id: u64, phase: ChangePhase) {
+        self.update(id, |change| change.phase = phase);
+    }
+
+    /// Record the latest catch-up progress of the running change `id`
+    pub fn set_progress(&self, id: u64, progress: LearnerProgress) {
+        self.update(id, |change| change.progress = Some(progress));
+    }
+
+    /// Finish the running change `id`, completed or failed with an error
+    pub fn finish(&self, id: u64, result: Result<(), String>) {
+        let mut log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(mut change) = log.running.take_if(|change| change.id == id) else {
+            return;
+        };
+        change.updated_at_ms = now_ms();
+        match result {
+            Ok(()) => change.phase = ChangePhase::Completed,
+            Err(e) => {
+                change.phase = ChangePhase::Failed;
+                change.error = Some(e);
+            }
+        }
+        if log.finished.len() == MAX_FINISHED_CHANGES {
+            log.finished.pop_front();
+        }
+        log.finished.push_back(change);
+    }
+
+    /// Change `id`, running or finished
+    pub fn get(&self, id: u64) -> Option<MembershipChange> {
+        let log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        log.running
+            .iter()
+            .chain(log.finished.iter())
+            .find(|change| change.id == id)
+            .cloned()
+    }
+
+    /// The running change, if any, then finished changes, newest first
+    pub fn list(&self) -> Vec<MembershipChange> {
+        let log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        log.running
+            .iter()
+            .chain(log.finished.iter().rev())
+            .cloned()
+            .collect()
+    }
+
+    fn update(&self, id: u64, apply: impl FnOnce(&mut MembershipChange)) {
+        let mut log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(change) = log.running.as_mut().filter(|change| change.id == id) {
+            apply(change);
+            change.updated_at_ms = now_ms();
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::learner_progress::TransferPhase;
+
+    #[test]
+    fn test_one_change_at_a_time() {
+        let changes = MembershipChanges::new();
+        let add = changes
+            .begin(
+                4,
+                MembershipOp::AddVoter,
+                Some("10.0.1.13:9004".to_string()),
+            )
+            .unwrap();
+        assert_eq!(add.phase, ChangePhase::AddingLearner);
+        assert!(changes.begin(5, MembershipOp::Remove, None).is_err());
+
+        changes.set_phase(add.id, ChangePhase::CatchingUp);
+        changes.set_progress(
+            add.id,
+            LearnerProgress {
+                node_id: 4,
+                raft_addr: "10.0.1.13:9004".to_string(),
+                phase: TransferPhase::Log,
+                matched_index: Some(900),
+                entries_remaining: 100,
+                log_bytes_sent: 4096,
+                snapshot_bytes_sent: 0,
+                entries_per_sec: None,
+                eta_secs: None,
+                ready_to_promote: false,
+            },
+        );
+        let running = changes.get(add.id).unwrap();
+        assert_eq!(running.phase, ChangePhase::CatchingUp);
+        assert_eq!(running.progress.unwrap().entries_remaining, 100);
+
+        // Finishing another ID leaves the running change alone
+        changes.finish(add.id + 1, Ok(()));
+        assert!(!changes.get(add.id).unwrap().phase.is_finished());
+        changes.finish(add.id, Ok(()));
+        assert_eq!(changes.get(add.id).unwrap().phase, ChangePhase::Completed);
+
+        let remove = changes.begin(5, MembershipOp::Remove, None).unwrap();
+        assert_eq!(remove.phase, ChangePhase::Removing);
+        changes.finish(remove.id, Err("Node 5 is not a member".to_string()));
+        let listed: Vec<(u64, ChangePhase)> = changes
+            .list()
+            .iter()
+            .map(|change| (change.id, change.phase))
+            .collect();
+        assert_eq!(
+            listed,
+            vec![
+                (remove.id, ChangePhase::Failed),
+                (add.id, ChangePhase::Completed)
+            ]
+        );
+        assert_eq!(
+            changes.get(remove.id).unwrap().error.as_deref(),
+            Some("Node 5 is not a member")
+        );
+    }
+
+    #[test]
+    fn test_finished_changes_are_bounded() {
+        let changes = MembershipChanges::new();
+        for node_id in 0..MAX_FINISHED_CHANGES as u64 + 5 {
+            let change = changes
+                .begin(node_id, MembershipOp::AddLearner, None)
+                .unwrap();
+            changes.finish(change.id, Ok(()));
+        }
+        let listed = changes.list();
+        assert_eq!(listed.len(), MAX_FINISHED_CHANGES);
+        assert_eq!(listed[0].node_id, MAX_FINISHED_CHANGES as u64 + 4);
+        assert!(changes.get(1).is_none());
+    }
+}
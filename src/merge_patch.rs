@@ -0,0 +1,127 @@
+//! JSON merge patch (RFC 7386)
+//!
+//! Used by the state machine to update JSON documents in place, so clients don't
+//! need racy read-modify-write cycles or to resend large documents.
+
+use serde_json::Value as Json;
+
+/// Apply `patch` to `target` following RFC 7386
+///
+/// Object members in the patch replace the target's members, `null` members remove
+/// them, and any non-object patch replaces the target entirely.
+pub fn merge_patch(target: &mut Json, patch: &Json) {
+    let Json::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+
+    if !target.is_object() {
+        *target = Json::Object(serde_json::Map::new());
+    }
+    if let Json::Object(target) = target {
+        for (name, value) in patch {
+            if value.is_null() {
+                target.remove(name);
+            } else {
+                merge_patch(target.entry(name.clone()).or_insert(Json::Null), value);
+            }
+        }
+    }
+}
+
+/// Apply a serialized patch to a serialized document
+///
+/// A missing document is patched as `null`. Fails if either side is not valid JSON.
+pub fn apply_merge_patch(document: Option<&[u8]>, patch: &[u8]) -> Result<Vec<u8>, String> {
+    let mut target = match document {
+        Some(bytes) => serde_json::from_slice(bytes)
+            .map_err(|e| format!("Stored value is not a JSON document: {}", e))?,
+        None => Json::Null,
+    };
+    let patch: Json =
+        serde_json::from_slice(patch).map_err(|e| format!("Invalid merge patch: {}", e))?;
+
+    merge_patch(&mut target, &patch);
+    serde_json::to_vec(&target).map_err(|e| format!("Failed to serialize document: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn patched(target: Json, patch: Json) -> Json {
+        let mut target = target;
+        merge_patch(&mut target, &patch);
+        target
+    }
+
+    #[test]
+    fn test_rfc7386_examples() {
+        // RFC 7386 appendix A
+        let cases = [
+            (json!({"a": "b"}), json!({"a": "c"}), json!({"a": "c"})),
+            (
+                json!({"a": "b"}),
+                json!({"b": "c"}),
+                json!({"a": "b", "b": "c"}),
+            ),
+            (json!({"a": "b"}), json!({"a": null}), json!({})),
+            (
+                json!({"a": "b", "b": "c"}),
+                json!({"a": null}),
+                json!({"b": "c"}),
+            ),
+            (json!({"a": ["b"]}), json!({"a": "c"}), json!({"a": "c"})),
+            (json!({"a": "c"}), json!({"a": ["b"]}), json!({"a": ["b"]})),
+            (
+                json!({"a": {"b": "c"}}),
+                json!({"a": {"b": "d", "c": null}}),
+                json!({"a": {"b": "d"}}),
+            ),
+            (
+                json!({"a": [{"b": "c"}]}),
+                json!({"a": [1]}),
+                json!({"a": [1]}),
+            ),
+            (json!(["a", "b"]), json!(["c", "d"]), json!(["c", "d"])),
+            (json!({"a": "b"}), json!(["c"]), json!(["c"])),
+            (json!({"a": "foo"}), json!(null), json!(null)),
+            (json!({"a": "foo"}), json!("bar"), json!("bar")),
+            (
+                json!({"e": null}),
+                json!({"a": 1}),
+                json!({"e": null, "a": 1}),
+            ),
+            (
+                json!([1, 2]),
+                json!({"a": "b", "c": null}),
+                json!({"a": "b"}),
+            ),
+            (
+                json!({}),
+                json!({"a": {"bb": {"ccc": null}}}),
+                json!({"a": {"bb": {}}}),
+            ),
+        ];
+
+        for (target, patch, expected) in cases {
+            assert_eq!(patched(target, patch), expected);
+        }
+    }
+
+    #[test]
+    fn test_apply_merge_patch_bytes() {
+        let merged = apply_merge_patch(Some(br#"{"name":"a","n":1}"#), br#"{"n":2}"#).unwrap();
+        let merged: Json = serde_json::from_slice(&merged).unwrap();
+        assert_eq!(merged, json!({"name": "a", "n": 2}));
+
+        // Missing documents are created from the patch
+        let created = apply_merge_patch(None, br#"{"a":{"b":null,"c":1}}"#).unwrap();
+        let created: Json = serde_json::from_slice(&created).unwrap();
+        assert_eq!(created, json!({"a": {"c": 1}}));
+
+        assert!(apply_merge_patch(Some(b"not json"), b"{}").is_err());
+        assert!(apply_merge_patch(None, b"{").is_err());
+    }
+}
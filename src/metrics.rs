@@ -3,60 +3,104 @@
 /// This module provides comprehensive metrics tracking for monitoring system performance,
 /// including request latency, throughput, storage metrics, and Raft consensus metrics.
 use lazy_static::lazy_static;
-use prometheus::{Histogram, HistogramOpts, IntCounter, IntGauge, Registry};
+use prometheus::{
+    Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry,
+};
 use std::sync::Once;
+use std::time::Instant;
 
 lazy_static! {
     /// Global metrics registry
     pub static ref REGISTRY: Registry = Registry::new();
 
-    // Request counters
-    /// Total number of GET requests
-    pub static ref GET_REQUESTS: IntCounter = IntCounter::new(
-        "scribe_ledger_get_requests_total",
-        "Total number of GET requests"
+    // HTTP request metrics, recorded by `http_metrics::HttpMetricsLayer`
+    /// HTTP request latency in seconds, by method, route and status code
+    pub static ref HTTP_REQUEST_DURATION: HistogramVec = HistogramVec::new(
+        HistogramOpts::new(
+            "scribe_ledger_http_request_duration_seconds",
+            "HTTP request latency in seconds by method, route and status code"
+        )
+        .buckets(vec![0.001, 0.005, 0.010, 0.025, 0.050, 0.100, 0.250, 0.500, 1.0, 2.5, 5.0]),
+        &["method", "route", "status"]
     ).unwrap();
 
-    /// Total number of PUT requests
-    pub static ref PUT_REQUESTS: IntCounter = IntCounter::new(
-        "scribe_ledger_put_requests_total",
-        "Total number of PUT requests"
+    /// Total number of HTTP requests, by method, route and status code
+    pub static ref HTTP_REQUESTS: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "scribe_ledger_http_requests_total",
+            "Total number of HTTP requests by method, route and status code"
+        ),
+        &["method", "route", "status"]
     ).unwrap();
 
-    /// Total number of DELETE requests
-    pub static ref DELETE_REQUESTS: IntCounter = IntCounter::new(
-        "scribe_ledger_delete_requests_total",
-        "Total number of DELETE requests"
+    /// HTTP requests being handled, by method and route
+    pub static ref HTTP_REQUESTS_IN_FLIGHT: IntGaugeVec = IntGaugeVec::new(
+        Opts::new(
+            "scribe_ledger_http_requests_in_flight",
+            "HTTP requests being handled by method and route"
+        ),
+        &["method", "route"]
     ).unwrap();
 
-    // Request latency histograms
-    /// GET request latency in seconds
-    pub static ref GET_LATENCY: Histogram = Histogram::with_opts(
+    // Layer latency histograms, to tell HTTP, consensus and storage time apart
+    /// DistributedApi operation latency in seconds, by operation
+    pub static ref API_OPERATION_LATENCY: HistogramVec = HistogramVec::new(
         HistogramOpts::new(
-            "scribe_ledger_get_latency_seconds",
-            "GET request latency in seconds"
+            "scribe_ledger_api_operation_latency_seconds",
+            "DistributedApi operation latency in seconds by operation"
         )
-        .buckets(vec![0.001, 0.005, 0.010, 0.025, 0.050, 0.100, 0.250, 0.500, 1.0])
+        .buckets(vec![0.001, 0.005, 0.010, 0.025, 0.050, 0.100, 0.250, 0.500, 1.0]),
+        &["operation"]
     ).unwrap();
 
-    /// PUT request latency in seconds
-    pub static ref PUT_LATENCY: Histogram = Histogram::with_opts(
+    /// Read path latency per storage tier, by tier and outcome (`hit` or `miss`)
+    pub static ref READ_TIER_LATENCY: HistogramVec = HistogramVec::new(
         HistogramOpts::new(
-            "scribe_ledger_put_latency_seconds",
-            "PUT request latency in seconds"
+            "scribe_ledger_read_tier_latency_seconds",
+            "Read path latency in seconds by storage tier and outcome"
         )
-        .buckets(vec![0.001, 0.005, 0.010, 0.025, 0.050, 0.100, 0.250, 0.500, 1.0])
+        .buckets(vec![0.0001, 0.001, 0.005, 0.010, 0.025, 0.050, 0.100, 0.250, 0.500, 1.0, 2.5]),
+        &["tier", "outcome"]
     ).unwrap();
 
-    /// DELETE request latency in seconds
-    pub static ref DELETE_LATENCY: Histogram = Histogram::with_opts(
+    /// Time from proposing a write to Raft until it is committed and applied
+    pub static ref RAFT_COMMIT_LATENCY: Histogram = Histogram::with_opts(
         HistogramOpts::new(
-            "scribe_ledger_delete_latency_seconds",
-            "DELETE request latency in seconds"
+            "scribe_ledger_raft_commit_latency_seconds",
+            "Raft write latency from proposal to commit and apply in seconds"
         )
         .buckets(vec![0.001, 0.005, 0.010, 0.025, 0.050, 0.100, 0.250, 0.500, 1.0])
     ).unwrap();
 
+    /// Time the state machine spends applying a batch of log entries
+    pub static ref STATE_MACHINE_APPLY_LATENCY: Histogram = Histogram::with_opts(
+        HistogramOpts::new(
+            "scribe_ledger_state_machine_apply_latency_seconds",
+            "State machine apply latency per batch of log entries in seconds"
+        )
+        .buckets(vec![0.0001, 0.0005, 0.001, 0.005, 0.010, 0.025, 0.050, 0.100, 0.250])
+    ).unwrap();
+
+    // Batching metrics
+    /// Number of log entries applied by the state machine at once
+    pub static ref APPLY_BATCH_SIZE: Histogram = Histogram::with_opts(
+        HistogramOpts::new(
+            "scribe_ledger_apply_batch_entries",
+            "Number of log entries per state machine apply"
+        )
+        .buckets(vec![1.0, 2.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0])
+    ).unwrap();
+
+    /// Number of items per DistributedApi batch write
+    pub static ref API_BATCH_SIZE: Histogram = Histogram::with_opts(
+        HistogramOpts::new(
+            "scribe_ledger_api_batch_items",
+            "Number of items per batch write"
+        )
+        .buckets(vec![1.0, 2.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0])
+    ).unwrap();
+
     // Storage metrics
     /// Total number of keys stored
     pub static ref STORAGE_KEYS: IntGauge = IntGauge::new(
@@ -95,6 +139,30 @@ lazy_static! {
         "Node health status (1 = healthy, 0 = unhealthy)"
     ).unwrap();
 
+    /// Composite node health score (0-100), see `health::HealthMonitor`
+    pub static ref NODE_HEALTH_SCORE: IntGauge = IntGauge::new(
+        "scribe_ledger_node_health_score",
+        "Weighted composite node health score from 0 to 100"
+    ).unwrap();
+
+    /// Health score (0-100) of each subsystem
+    pub static ref NODE_HEALTH_COMPONENT: IntGaugeVec = IntGaugeVec::new(
+        Opts::new(
+            "scribe_ledger_node_health_component_score",
+            "Node health score from 0 to 100 by subsystem"
+        ),
+        &["component"]
+    ).unwrap();
+
+    /// Self-healing actions run, by action and result (ok, failed)
+    pub static ref HEALING_ACTIONS: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "scribe_ledger_healing_actions_total",
+            "Total number of self-healing actions run by action and result"
+        ),
+        &["action", "result"]
+    ).unwrap();
+
     // Throughput metrics
     /// Operations per second counter
     pub static ref OPS_TOTAL: IntCounter = IntCounter::new(
@@ -108,6 +176,179 @@ lazy_static! {
         "scribe_ledger_errors_total",
         "Total number of errors"
     ).unwrap();
+
+    /// Values whose checksum did not match when they were read
+    pub static ref CORRUPTED_VALUES: IntCounter = IntCounter::new(
+        "scribe_ledger_corrupted_values_total",
+        "Total number of stored values that failed their checksum on read"
+    ).unwrap();
+
+    /// Raft RPCs rejected by request authentication, by reason
+    pub static ref RPC_AUTH_REJECTED: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "scribe_ledger_rpc_auth_rejected_total",
+            "Total number of Raft RPCs rejected by authentication by reason"
+        ),
+        &["reason"]
+    ).unwrap();
+
+    /// HTTP requests rejected by the network policy of their listener
+    pub static ref NETWORK_POLICY_REJECTED: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "scribe_ledger_network_policy_rejected_total",
+            "Total number of HTTP requests rejected by a listener's IP allowlist or denylist"
+        ),
+        &["listener"]
+    ).unwrap();
+
+    /// HTTP requests refused because their API key ran out of quota, by limit
+    pub static ref QUOTA_REJECTED: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "scribe_ledger_quota_rejected_total",
+            "Total number of HTTP requests refused because their API key exhausted a quota"
+        ),
+        &["limit"]
+    ).unwrap();
+
+    // Shadow write metrics
+    /// Shadow writes by outcome (mirrored, diverged, dropped)
+    pub static ref SHADOW_WRITES: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "scribe_ledger_shadow_writes_total",
+            "Total number of shadow writes by outcome"
+        ),
+        &["outcome"]
+    ).unwrap();
+
+    // Canary read mirroring metrics
+    /// Reads mirrored to the canary by outcome (matched, diverged, failed, dropped)
+    pub static ref CANARY_READS: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "scribe_ledger_canary_reads_total",
+            "Total number of reads mirrored to the canary by outcome"
+        ),
+        &["outcome"]
+    ).unwrap();
+
+    /// Total number of metrics pushes, by outcome (ok, failed or dropped)
+    pub static ref METRICS_PUSHES: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "scribe_ledger_metrics_pushes_total",
+            "Total number of metrics pushes by outcome"
+        ),
+        &["outcome"]
+    ).unwrap();
+
+    // Admission control metrics
+    /// Requests that had to wait for an admission slot, by priority
+    pub static ref ADMISSION_QUEUED: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "scribe_ledger_admission_queued_total",
+            "Total number of requests queued for admission by priority"
+        ),
+        &["priority"]
+    ).unwrap();
+
+    // Write contention metrics
+    /// Conditional writes attempted, by operation (cas, transaction)
+    pub static ref CONDITIONAL_WRITES: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "scribe_ledger_conditional_writes_total",
+            "Total number of compare-and-swaps and transaction commits attempted by operation"
+        ),
+        &["operation"]
+    ).unwrap();
+
+    /// Conditional writes that lost to a concurrent write, by operation and key prefix
+    pub static ref WRITE_CONFLICTS: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "scribe_ledger_write_conflicts_total",
+            "Total number of conditional writes that conflicted by operation and key prefix"
+        ),
+        &["operation", "prefix"]
+    ).unwrap();
+
+    // Read path metrics
+    /// Hot data cache lookups by outcome (memory_hit, disk_hit, miss)
+    pub static ref CACHE_LOOKUPS: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "scribe_ledger_cache_lookups_total",
+            "Total number of hot data cache lookups by outcome"
+        ),
+        &["outcome"]
+    ).unwrap();
+
+    /// Linearizable reads by how leadership was confirmed (lease, read_index)
+    pub static ref LINEARIZABLE_READS: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "scribe_ledger_linearizable_reads_total",
+            "Total number of linearizable reads by leadership confirmation method"
+        ),
+        &["confirmation"]
+    ).unwrap();
+
+    // Raft transport metrics
+    /// Raft RPC payload bytes sent to each peer, as put on the wire
+    pub static ref RAFT_SENT_BYTES: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "scribe_ledger_raft_sent_bytes_total",
+            "Total Raft RPC payload bytes sent by peer"
+        ),
+        &["peer"]
+    ).unwrap();
+
+    /// Raft RPC payload bytes saved by compression, per peer
+    pub static ref RAFT_COMPRESSION_SAVED_BYTES: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "scribe_ledger_raft_compression_saved_bytes_total",
+            "Total Raft RPC payload bytes saved by compression by peer"
+        ),
+        &["peer"]
+    ).unwrap();
+
+    // Snapshot transfer metrics
+    /// Snapshot bytes sent to and received from peers
+    pub static ref SNAPSHOT_TRANSFER_BYTES: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "scribe_ledger_snapshot_transfer_bytes_total",
+            "Total snapshot bytes transferred by direction"
+        ),
+        &["direction"]
+    ).unwrap();
+
+    /// Bytes of the snapshot currently being transferred, 0 when none is
+    pub static ref SNAPSHOT_TRANSFER_PROGRESS: IntGaugeVec = IntGaugeVec::new(
+        Opts::new(
+            "scribe_ledger_snapshot_transfer_progress_bytes",
+            "Bytes transferred of the snapshot in progress by direction"
+        ),
+        &["direction"]
+    ).unwrap();
+
+    /// Time snapshot chunks waited for the transfer rate limit
+    pub static ref SNAPSHOT_THROTTLE_WAIT: HistogramVec = HistogramVec::new(
+        HistogramOpts::new(
+            "scribe_ledger_snapshot_throttle_wait_seconds",
+            "Time snapshot chunks waited for the transfer rate limit by direction"
+        )
+        .buckets(vec![0.001, 0.01, 0.1, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0]),
+        &["direction"]
+    ).unwrap();
+
+    /// Estimated offset of each peer's clock from this node's, see `clock_skew`
+    pub static ref CLOCK_SKEW: IntGaugeVec = IntGaugeVec::new(
+        Opts::new(
+            "scribe_ledger_clock_skew_milliseconds",
+            "Estimated offset of a peer's wall clock from this node's by peer"
+        ),
+        &["peer"]
+    ).unwrap();
+
+    /// Largest absolute clock offset of any peer
+    pub static ref CLOCK_SKEW_MAX: IntGauge = IntGauge::new(
+        "scribe_ledger_clock_skew_max_milliseconds",
+        "Largest absolute offset of a peer's wall clock from this node's"
+    ).unwrap();
 }
 
 static INIT: Once = Once::new();
@@ -115,27 +356,16 @@ static INIT: Once = Once::new();
 /// Initialize and register all metrics (idempotent - can be called multiple times)
 pub fn init_metrics() {
     INIT.call_once(|| {
-        // Register request counters
-        REGISTRY
-            .register(Box::new(GET_REQUESTS.clone()))
-            .expect("Failed to register GET_REQUESTS metric");
+        // Register HTTP request metrics
         REGISTRY
-            .register(Box::new(PUT_REQUESTS.clone()))
-            .expect("Failed to register PUT_REQUESTS metric");
+            .register(Box::new(HTTP_REQUEST_DURATION.clone()))
+            .expect("Failed to register HTTP_REQUEST_DURATION metric");
         REGISTRY
-            .register(Box::new(DELETE_REQUESTS.clone()))
-            .expect("Failed to register DELETE_REQUESTS metric");
-
-        // Register latency histograms
-        REGISTRY
-            .register(Box::new(GET_LATENCY.clone()))
-            .expect("Failed to register GET_LATENCY metric");
+            .register(Box::new(HTTP_REQUESTS.clone()))
+            .expect("Failed to register HTTP_REQUESTS metric");
         REGISTRY
-            .register(Box::new(PUT_LATENCY.clone()))
-            .expect("Failed to register PUT_LATENCY metric");
-        REGISTRY
-            .register(Box::new(DELETE_LATENCY.clone()))
-            .expect("Failed to register DELETE_LATENCY metric");
+            .register(Box::new(HTTP_REQUESTS_IN_FLIGHT.clone()))
+            .expect("Failed to register HTTP_REQUESTS_IN_FLIGHT metric");
 
         // Register storage metrics
         REGISTRY
@@ -158,6 +388,15 @@ pub fn init_metrics() {
         REGISTRY
             .register(Box::new(NODE_HEALTH.clone()))
             .expect("Failed to register NODE_HEALTH metric");
+        REGISTRY
+            .register(Box::new(NODE_HEALTH_SCORE.clone()))
+            .expect("Failed to register NODE_HEALTH_SCORE metric");
+        REGISTRY
+            .register(Box::new(NODE_HEALTH_COMPONENT.clone()))
+            .expect("Failed to register NODE_HEALTH_COMPONENT metric");
+        REGISTRY
+            .register(Box::new(HEALING_ACTIONS.clone()))
+            .expect("Failed to register HEALING_ACTIONS metric");
 
         // Register throughput metrics
         REGISTRY
@@ -168,9 +407,103 @@ pub fn init_metrics() {
         REGISTRY
             .register(Box::new(ERRORS_TOTAL.clone()))
             .expect("Failed to register ERRORS_TOTAL metric");
+        REGISTRY
+            .register(Box::new(CORRUPTED_VALUES.clone()))
+            .expect("Failed to register CORRUPTED_VALUES metric");
+        REGISTRY
+            .register(Box::new(RPC_AUTH_REJECTED.clone()))
+            .expect("Failed to register RPC_AUTH_REJECTED metric");
+        REGISTRY
+            .register(Box::new(NETWORK_POLICY_REJECTED.clone()))
+            .expect("Failed to register NETWORK_POLICY_REJECTED metric");
+        REGISTRY
+            .register(Box::new(QUOTA_REJECTED.clone()))
+            .expect("Failed to register QUOTA_REJECTED metric");
+
+        // Register shadow write metrics
+        REGISTRY
+            .register(Box::new(SHADOW_WRITES.clone()))
+            .expect("Failed to register SHADOW_WRITES metric");
+
+        // Register canary read mirroring metrics
+        REGISTRY
+            .register(Box::new(CANARY_READS.clone()))
+            .expect("Failed to register CANARY_READS metric");
+        REGISTRY
+            .register(Box::new(METRICS_PUSHES.clone()))
+            .expect("Failed to register METRICS_PUSHES metric");
+
+        // Register admission control metrics
+        REGISTRY
+            .register(Box::new(ADMISSION_QUEUED.clone()))
+            .expect("Failed to register ADMISSION_QUEUED metric");
+
+        // Register write contention metrics
+        REGISTRY
+            .register(Box::new(CONDITIONAL_WRITES.clone()))
+            .expect("Failed to register CONDITIONAL_WRITES metric");
+        REGISTRY
+            .register(Box::new(WRITE_CONFLICTS.clone()))
+            .expect("Failed to register WRITE_CONFLICTS metric");
+
+        // Register read path metrics
+        REGISTRY
+            .register(Box::new(CACHE_LOOKUPS.clone()))
+            .expect("Failed to register CACHE_LOOKUPS metric");
+        REGISTRY
+            .register(Box::new(LINEARIZABLE_READS.clone()))
+            .expect("Failed to register LINEARIZABLE_READS metric");
+
+        // Register Raft transport metrics
+        REGISTRY
+            .register(Box::new(RAFT_SENT_BYTES.clone()))
+            .expect("Failed to register RAFT_SENT_BYTES metric");
+        REGISTRY
+            .register(Box::new(RAFT_COMPRESSION_SAVED_BYTES.clone()))
+            .expect("Failed to register RAFT_COMPRESSION_SAVED_BYTES metric");
+
+        // Register snapshot transfer metrics
+        REGISTRY
+            .register(Box::new(SNAPSHOT_TRANSFER_BYTES.clone()))
+            .expect("Failed to register SNAPSHOT_TRANSFER_BYTES metric");
+        REGISTRY
+            .register(Box::new(SNAPSHOT_TRANSFER_PROGRESS.clone()))
+            .expect("Failed to register SNAPSHOT_TRANSFER_PROGRESS metric");
+        REGISTRY
+            .register(Box::new(SNAPSHOT_THROTTLE_WAIT.clone()))
+            .expect("Failed to register SNAPSHOT_THROTTLE_WAIT metric");
+
+        // Register clock skew metrics
+        REGISTRY
+            .register(Box::new(CLOCK_SKEW.clone()))
+            .expect("Failed to register CLOCK_SKEW metric");
+        REGISTRY
+            .register(Box::new(CLOCK_SKEW_MAX.clone()))
+            .expect("Failed to register CLOCK_SKEW_MAX metric");
+
+        // Register layer latency and batching metrics
+        REGISTRY
+            .register(Box::new(API_OPERATION_LATENCY.clone()))
+            .expect("Failed to register API_OPERATION_LATENCY metric");
+        REGISTRY
+            .register(Box::new(READ_TIER_LATENCY.clone()))
+            .expect("Failed to register READ_TIER_LATENCY metric");
+        REGISTRY
+            .register(Box::new(RAFT_COMMIT_LATENCY.clone()))
+            .expect("Failed to register RAFT_COMMIT_LATENCY metric");
+        REGISTRY
+            .register(Box::new(STATE_MACHINE_APPLY_LATENCY.clone()))
+            .expect("Failed to register STATE_MACHINE_APPLY_LATENCY metric");
+        REGISTRY
+            .register(Box::new(APPLY_BATCH_SIZE.clone()))
+            .expect("Failed to register APPLY_BATCH_SIZE metric");
+        REGISTRY
+            .register(Box::new(API_BATCH_SIZE.clone()))
+            .expect("Failed to register API_BATCH_SIZE metric");
 
         // Set initial node health to healthy
         NODE_HEALTH.set(1);
+        NODE_HEALTH_SCORE.set(100);
     });
 }
 
@@ -192,6 +525,46 @@ pub fn update_storage_metrics(keys: usize, size: u64) {
     STORAGE_SIZE.set(size as i64);
 }
 
+/// Record the latency of a DistributedApi operation started at `started`
+pub fn observe_api_latency(operation: &str, started: Instant) {
+    API_OPERATION_LATENCY
+        .with_label_values(&[operation])
+        .observe(started.elapsed().as_secs_f64());
+}
+
+/// DistributedApi operations recorded so far and the seconds they took in total
+pub fn api_operation_totals() -> (u64, f64) {
+    use prometheus::core::Collector;
+    API_OPERATION_LATENCY
+        .collect()
+        .iter()
+        .flat_map(|family| family.get_metric())
+        .map(|metric| metric.get_histogram())
+        .fold((0, 0.0), |(count, sum), histogram| {
+            (
+                count + histogram.get_sample_count(),
+                sum + histogram.get_sample_sum(),
+            )
+        })
+}
+
+/// Record the time a read spent in one storage tier, started at `started`
+pub fn observe_read_tier(tier: &str, hit: bool, started: Instant) {
+    let outcome = if hit { "hit" } else { "miss" };
+    READ_TIER_LATENCY
+        .with_label_values(&[tier, outcome])
+        .observe(started.elapsed().as_secs_f64());
+}
+
+/// Record a finished HTTP request in the latency histogram and request counter
+pub fn record_http_request(method: &str, route: &str, status: &str, started: Instant) {
+    let labels = [method, route, status];
+    HTTP_REQUEST_DURATION
+        .with_label_values(&labels)
+        .observe(started.elapsed().as_secs_f64());
+    HTTP_REQUESTS.with_label_values(&labels).inc();
+}
+
 /// Update Raft metrics
 pub fn update_raft_metrics(term: u64, commit_index: u64, last_applied: u64) {
     RAFT_TERM.set(term as i64);
@@ -206,36 +579,73 @@ mod tests {
     #[test]
     fn test_metrics_initialization() {
         init_metrics();
+        HTTP_REQUESTS
+            .with_label_values(&["GET", "/:key", "200"])
+            .inc();
         // Verify that metrics can be gathered
         let metrics = get_metrics();
-        assert!(metrics.contains("scribe_ledger_get_requests_total"));
-        assert!(metrics.contains("scribe_ledger_put_requests_total"));
+        assert!(metrics.contains("scribe_ledger_http_requests_total"));
         assert!(metrics.contains("scribe_ledger_node_health"));
     }
 
     #[test]
     fn test_request_counters() {
         init_metrics();
-        let initial_gets = GET_REQUESTS.get();
-        GET_REQUESTS.inc();
-        assert_eq!(GET_REQUESTS.get(), initial_gets + 1);
-
-        let initial_puts = PUT_REQUESTS.get();
-        PUT_REQUESTS.inc();
-        assert_eq!(PUT_REQUESTS.get(), initial_puts + 1);
+        let gets = HTTP_REQUESTS.with_label_values(&["GET", "/:key", "200"]);
+        let initial_gets = gets.get();
+        gets.inc();
+        assert_eq!(gets.get(), initial_gets + 1);
+
+        let puts = HTTP_REQUESTS.with_label_values(&["PUT", "/:key", "500"]);
+        let initial_puts = puts.get();
+        puts.inc();
+        assert_eq!(puts.get(), initial_puts + 1);
     }
 
     #[test]
     fn test_latency_histogram() {
         init_metrics();
         // Record some latencies
-        GET_LATENCY.observe(0.001);
-        GET_LATENCY.observe(0.010);
-        GET_LATENCY.observe(0.100);
+        let latency = HTTP_REQUEST_DURATION.with_label_values(&["GET", "/:key", "200"]);
+        latency.observe(0.001);
+        latency.observe(0.010);
+        latency.observe(0.100);
 
         // Verify histogram has recorded observations
         let metrics = get_metrics();
-        assert!(metrics.contains("scribe_ledger_get_latency_seconds"));
+        assert!(metrics.contains("scribe_ledger_http_request_duration_seconds"));
+        assert!(metrics.contains("route=\"/:key\""));
+    }
+
+    #[test]
+    fn test_layer_latency_histograms() {
+        init_metrics();
+        let initial = API_OPERATION_LATENCY
+            .with_label_values(&["put"])
+            .get_sample_count();
+        observe_api_latency("put", Instant::now());
+        assert_eq!(
+            API_OPERATION_LATENCY
+                .with_label_values(&["put"])
+                .get_sample_count(),
+            initial + 1
+        );
+        let (operations, seconds) = api_operation_totals();
+        assert!(operations > initial);
+        assert!(seconds >= 0.0);
+
+        RAFT_COMMIT_LATENCY.observe(0.002);
+        STATE_MACHINE_APPLY_LATENCY.observe(0.0002);
+        APPLY_BATCH_SIZE.observe(3.0);
+        API_BATCH_SIZE.observe(100.0);
+
+        let metrics = get_metrics();
+        assert!(metrics.contains("scribe_ledger_api_operation_latency_seconds"));
+        assert!(metrics.contains("operation=\"put\""));
+        assert!(metrics.contains("scribe_ledger_raft_commit_latency_seconds"));
+        assert!(metrics.contains("scribe_ledger_state_machine_apply_latency_seconds"));
+        assert!(metrics.contains("scribe_ledger_apply_batch_entries"));
+        assert!(metrics.contains("scribe_ledger_api_batch_items"));
     }
 
     #[test]
@@ -0,0 +1,492 @@
+//! Push-based metrics export
+//!
+//! Nodes that are short-lived or sit behind a firewall cannot be scraped.
+//! [`MetricsPusher`] sends the metrics registry out instead, every interval:
+//!
+//! - to a Prometheus pushgateway, replacing the node's group
+//!   (`/metrics/job/<job>/instance/<instance>`) with the text exposition format
+//! - to a Prometheus remote-write endpoint, as snappy-compressed protobuf
+//!   `WriteRequest`s
+//!
+//! Failed pushes are retried with exponential backoff. Remote-write batches that
+//! still fail are kept (up to `max_pending_batches`) and sent ahead of newer ones once
+//! the endpoint is reachable again, so gaps are filled in; a pushgateway only ever
+//! needs the latest state, so failed pushes to it are not kept.
+
+use crate::config::{MetricsPushConfig, MetricsPushMode};
+use crate::error::{Result, ScribeError};
+use crate::metrics::{METRICS_PUSHES, REGISTRY};
+use prometheus::proto::{MetricFamily, MetricType};
+use prometheus::Encoder;
+use reqwest::Url;
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+/// Delay before the first retry of a failed push, doubled on each further retry
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// One remote-write time series: sorted labels and their samples
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeSeries {
+    /// Label names and values, including `__name__`, sorted by name
+    pub labels: Vec<(String, String)>,
+    /// Sample values and their timestamps (milliseconds since the Unix epoch)
+    pub samples: Vec<(f64, i64)>,
+}
+
+/// Periodic push of the metrics registry
+pub struct MetricsPusher {
+    mode: MetricsPushMode,
+    /// Pushgateway group URL or remote-write endpoint
+    url: Url,
+    job: String,
+    instance: String,
+    client: reqwest::Client,
+    interval: Duration,
+    max_retries: u32,
+    max_samples_per_send: usize,
+    max_pending_batches: usize,
+    /// Remote-write batches not sent yet, oldest first
+    pending: Mutex<VecDeque<Vec<TimeSeries>>>,
+}
+
+impl MetricsPusher {
+    /// Create a pusher labelling the metrics with `instance`
+    pub fn new(config: &MetricsPushConfig, instance: impl Into<String>) -> Result<Self> {
+        let instance = instance.into();
+        let base = config
+            .url
+            .as_deref()
+            .ok_or_else(|| ScribeError::Configuration("Metrics push requires url".to_string()))?;
+        let mut url = Url::parse(base).map_err(|e| {
+            ScribeError::Configuration(format!("Invalid metrics push URL {:?}: {}", base, e))
+        })?;
+        if config.mode == MetricsPushMode::Pushgateway {
+            url.path_segments_mut()
+                .map_err(|_| {
+                    ScribeError::Configuration(format!("Invalid pushgateway URL {:?}", base))
+                })?
+                .pop_if_empty()
+                .extend(["metrics", "job", &config.job, "instance", &instance]);
+        }
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()
+            .map_err(|e| ScribeError::Network(format!("Failed to create HTTP client: {}", e)))?;
+        Ok(Self {
+            mode: config.mode,
+            url,
+            job: config.job.clone(),
+            instance,
+            client,
+            interval: Duration::from_secs(config.interval_secs),
+            max_retries: config.max_retries,
+            max_samples_per_send: config.max_samples_per_send.max(1),
+            max_pending_batches: config.max_pending_batches.max(1),
+            pending: Mutex::new(VecDeque::new()),
+        })
+    }
+
+    /// URL metrics are pushed to
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Push the current metrics, retrying failed requests
+    pub async fn push(&self) -> Result<()> {
+        let families = REGISTRY.gather();
+        match self.mode {
+            MetricsPushMode::Pushgateway => {
+                let mut body = Vec::new();
+                prometheus::TextEncoder::new()
+                    .encode(&families, &mut body)
+                    .map_err(|e| ScribeError::Other(format!("Failed to encode metrics: {}", e)))?;
+                let result = self
+                    .send_with_retry(|| {
+                        self.client
+                            .put(self.url.clone())
+                            .header(reqwest::header::CONTENT_TYPE, "text/plain; version=0.0.4")
+                            .body(body.clone())
+                    })
+                    .await;
+                record(result.is_ok());
+                result.map_err(|(error, _)| error)
+            }
+            MetricsPushMode::RemoteWrite => {
+                let series = to_time_series(&families, &self.job, &self.instance, now_ms());
+                self.queue(series).await;
+                self.flush_pending().await
+            }
+        }
+    }
+
+    /// Push every interval until the task is aborted
+    pub fn start(self: &std::sync::Arc<Self>) -> tokio::task::JoinHandle<()> {
+        let pusher = std::sync::Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(pusher.interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = pusher.push().await {
+                    warn!("Metrics push to {} failed: {}", pusher.url, e);
+                }
+            }
+        })
+    }
+
+    /// Split `series` into batches and queue them, dropping the oldest when full
+    async fn queue(&self, series: Vec<TimeSeries>) {
+        let mut pending = self.pending.lock().await;
+        let mut batch = Vec::new();
+        let mut samples = 0;
+        for item in series {
+            if samples + item.samples.len() > self.max_samples_per_send && !batch.is_empty() {
+                pending.push_back(std::mem::take(&mut batch));
+                samples = 0;
+            }
+            samples += item.samples.len();
+            batch.push(item);
+        }
+        if !batch.is_empty() {
+            pending.push_back(batch);
+        }
+        while pending.len() > self.max_pending_batches {
+            pending.pop_front();
+            METRICS_PUSHES.with_label_values(&["dropped"]).inc();
+        }
+    }
+
+    /// Send queued remote-write batches oldest first, stopping at the first failure
+    async fn flush_pending(&self) -> Result<()> {
+        let mut pending = self.pending.lock().await;
+        // Batches the endpoint rejects are dropped, others are kept for the next push
+        while let Some(batch) = pending.front() {
+            let body = snap::raw::Encoder::new()
+                .compress_vec(&encode_write_request(batch))
+                .map_err(|e| ScribeError::Other(format!("Failed to compress metrics: {}", e)))?;
+            let result = self
+                .send_with_retry(|| {
+                    self.client
+                        .post(self.url.clone())
+                        .header(reqwest::header::CONTENT_TYPE, "application/x-protobuf")
+                        .header(reqwest::header::CONTENT_ENCODING, "snappy")
+                        .header("X-Prometheus-Remote-Write-Version", "0.1.0")
+                        .body(body.clone())
+                })
+                .await;
+            record(result.is_ok());
+            match result {
+                Ok(()) => {}
+                Err((error, rejected)) => {
+                    if rejected {
+                        pending.pop_front();
+                    }
+                    return Err(error);
+                }
+            }
+            pending.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Send a request, retrying failures and 5xx responses with exponential backoff
+    ///
+    /// Returns whether the endpoint rejected the data (a 4xx response), which
+    /// sending it again would not change.
+    async fn send_with_retry<F>(&self, build: F) -> std::result::Result<(), (ScribeError, bool)>
+    where
+        F: Fn() -> reqwest::RequestBuilder,
+    {
+        let mut attempt = 0;
+        loop {
+            let error = match build().send().await {
+                Ok(response) if response.status().is_success() => return Ok(()),
+                Ok(response) if response.status().is_client_error() => {
+                    let error = format!("Metrics push rejected: {}", response.status());
+                    return Err((ScribeError::Network(error), true));
+                }
+                Ok(response) => format!("Metrics push returned {}", response.status()),
+                Err(e) => format!("Metrics push failed: {}", e),
+            };
+            if attempt >= self.max_retries {
+                return Err((ScribeError::Network(error), false));
+            }
+            debug!("{}, retrying", error);
+            tokio::time::sleep(RETRY_BASE_DELAY * 2u32.saturating_pow(attempt)).await;
+            attempt += 1;
+        }
+    }
+}
+
+fn record(ok: bool) {
+    let outcome = if ok { "ok" } else { "failed" };
+    METRICS_PUSHES.with_label_values(&[outcome]).inc();
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// Flatten metric families into remote-write time series sampled at `timestamp`
+///
+/// Histograms and summaries become their `_bucket`/quantile, `_sum` and `_count`
+/// series, as in the text exposition format. Every series is labelled with `job`
+/// and `instance`.
+pub fn to_time_series(
+    families: &[MetricFamily],
+    job: &str,
+    instance: &str,
+    timestamp: i64,
+) -> Vec<TimeSeries> {
+    let mut series = Vec::new();
+    for family in families {
+        let name = family.get_name();
+        for metric in family.get_metric() {
+            let labels: Vec<(String, String)> = metric
+                .get_label()
+                .iter()
+                .map(|label| (label.get_name().to_string(), label.get_value().to_string()))
+                .collect();
+            let mut push = |suffix: &str, extra: Option<(&str, String)>, value: f64| {
+                let mut labels = labels.clone();
+                labels.push(("__name__".to_string(), format!("{}{}", name, suffix)));
+                labels.push(("job".to_string(), job.to_string()));
+                labels.push(("instance".to_string(), instance.to_string()));
+                if let Some((label, value)) = extra {
+                    labels.push((label.to_string(), value));
+                }
+                labels.sort_by(|a, b| a.0.cmp(&b.0));
+                series.push(TimeSeries {
+                    labels,
+                    samples: vec![(value, timestamp)],
+                });
+            };
+
+            match family.get_field_type() {
+                MetricType::COUNTER => push("", None, metric.get_counter().get_value()),
+                MetricType::GAUGE => push("", None, metric.get_gauge().get_value()),
+                MetricType::UNTYPED => push("", None, metric.get_untyped().get_value()),
+                MetricType::HISTOGRAM => {
+                    let histogram = metric.get_histogram();
+                    for bucket in histogram.get_bucket() {
+                        push(
+                            "_bucket",
+                            Some(("le", bucket.get_upper_bound().to_string())),
+                            bucket.get_cumulative_count() as f64,
+                        );
+                    }
+                    let count = histogram.get_sample_count() as f64;
+                    push("_bucket", Some(("le", "+Inf".to_string())), count);
+                    push("_sum", None, histogram.get_sample_sum());
+                    push("_count", None, count);
+                }
+                MetricType::SUMMARY => {
+                    let summary = metric.get_summary();
+                    for quantile in summary.get_quantile() {
+                        push(
+                            "",
+                            Some(("quantile", quantile.get_quantile().to_string())),
+                            quantile.get_value(),
+                        );
+                    }
+                    push("_sum", None, summary.get_sample_sum());
+                    push("_count", None, summary.get_sample_count() as f64);
+                }
+            }
+        }
+    }
+    series
+}
+
+/// Encode a remote-write `WriteRequest` protobuf message
+///
+/// ```text
+/// message WriteRequest { repeated TimeSeries timeseries = 1; }
+/// message TimeSeries { repeated Label labels = 1; repeated Sample samples = 2; }
+/// message Label { string name = 1; string value = 2; }
+/// message Sample { double value = 1; int64 timestamp = 2; }
+/// ```
+pub fn encode_write_request(series: &[TimeSeries]) -> Vec<u8> {
+    let mut request = Vec::new();
+    for item in series {
+        let mut encoded = Vec::new();
+        for (name, value) in &item.labels {
+            let mut label = Vec::new();
+            put_bytes(&mut label, 1, name.as_bytes());
+            put_bytes(&mut label, 2, value.as_bytes());
+            put_bytes(&mut encoded, 1, &label);
+        }
+        for (value, timestamp) in &item.samples {
+            let mut sample = Vec::new();
+            put_varint(&mut sample, (1 << 3) | 1);
+            sample.extend_from_slice(&value.to_le_bytes());
+            put_varint(&mut sample, 2 << 3);
+            put_varint(&mut sample, *timestamp as u64);
+            put_bytes(&mut encoded, 2, &sample);
+        }
+        put_bytes(&mut request, 1, &encoded);
+    }
+    request
+}
+
+/// Append a length-delimited field
+fn put_bytes(buffer: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    put_varint(buffer, (field << 3) | 2);
+    put_varint(buffer, bytes.len() as u64);
+    buffer.extend_from_slice(bytes);
+}
+
+fn put_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Bytes;
+    use axum::extract::State;
+    use axum::http::{StatusCode, Uri};
+    use axum::routing::any;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_encode_write_request() {
+        let series = vec![TimeSeries {
+            labels: vec![("__name__".to_string(), "up".to_string())],
+            samples: vec![(1.0, 1)],
+        }];
+        let mut expected = vec![0x0a, 0x1d, 0x0a, 0x0e, 0x0a, 0x08];
+        expected.extend_from_slice(b"__name__");
+        expected.extend_from_slice(&[0x12, 0x02]);
+        expected.extend_from_slice(b"up");
+        expected.extend_from_slice(&[0x12, 0x0b, 0x09]);
+        expected.extend_from_slice(&1.0f64.to_le_bytes());
+        expected.extend_from_slice(&[0x10, 0x01]);
+        assert_eq!(encode_write_request(&series), expected);
+
+        let mut varint = Vec::new();
+        put_varint(&mut varint, 300);
+        assert_eq!(varint, vec![0xac, 0x02]);
+    }
+
+    #[test]
+    fn test_histogram_time_series() {
+        let registry = prometheus::Registry::new();
+        let histogram = prometheus::Histogram::with_opts(
+            prometheus::HistogramOpts::new("latency_seconds", "Latency").buckets(vec![0.1, 1.0]),
+        )
+        .unwrap();
+        registry.register(Box::new(histogram.clone())).unwrap();
+        histogram.observe(0.5);
+
+        let series = to_time_series(&registry.gather(), "job", "node-1", 42);
+        let names: Vec<String> = series
+            .iter()
+            .map(|s| {
+                let get = |name: &str| {
+                    s.labels
+                        .iter()
+                        .find(|(label, _)| label == name)
+                        .map(|(_, value)| value.clone())
+                        .unwrap_or_default()
+                };
+                format!("{}{{{}}}={}", get("__name__"), get("le"), s.samples[0].0)
+            })
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "latency_seconds_bucket{0.1}=0",
+                "latency_seconds_bucket{1}=1",
+                "latency_seconds_bucket{+Inf}=1",
+                "latency_seconds_sum{}=0.5",
+                "latency_seconds_count{}=1",
+            ]
+        );
+        assert!(series[0].labels.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(series[0]
+            .labels
+            .contains(&("instance".to_string(), "node-1".to_string())));
+    }
+
+    /// Requests received by a test endpoint
+    #[derive(Clone, Default)]
+    struct Received {
+        requests: Arc<AtomicUsize>,
+        paths: Arc<Mutex<Vec<String>>>,
+    }
+
+    /// Endpoint failing the first `failures` requests, recording the paths it served
+    async fn endpoint(failures: usize) -> (String, Received) {
+        let received = Received::default();
+        let app = axum::Router::new()
+            .fallback(any(
+                move |State(received): State<Received>, uri: Uri, body: Bytes| async move {
+                    if received.requests.fetch_add(1, Ordering::SeqCst) < failures {
+                        return StatusCode::SERVICE_UNAVAILABLE;
+                    }
+                    assert!(!body.is_empty());
+                    received.paths.lock().await.push(uri.path().to_string());
+                    StatusCode::OK
+                },
+            ))
+            .with_state(received.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (format!("http://{}", addr), received)
+    }
+
+    #[tokio::test]
+    async fn test_pushgateway_push_retries() {
+        crate::metrics::init_metrics();
+        let (url, received) = endpoint(1).await;
+        let config = MetricsPushConfig {
+            enabled: true,
+            url: Some(url),
+            ..MetricsPushConfig::default()
+        };
+        let pusher = MetricsPusher::new(&config, "node 1").unwrap();
+        pusher.push().await.unwrap();
+
+        assert_eq!(received.requests.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            *received.paths.lock().await,
+            vec!["/metrics/job/scribe-ledger/instance/node%201".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remote_write_keeps_failed_batches() {
+        crate::metrics::init_metrics();
+        let (url, received) = endpoint(1).await;
+        let config = MetricsPushConfig {
+            enabled: true,
+            mode: MetricsPushMode::RemoteWrite,
+            url: Some(format!("{}/api/v1/write", url)),
+            max_retries: 0,
+            max_samples_per_send: 1_000_000,
+            ..MetricsPushConfig::default()
+        };
+        let pusher = MetricsPusher::new(&config, "node-1").unwrap();
+
+        // The first push fails and its batch is kept, then both are sent in order
+        assert!(pusher.push().await.is_err());
+        assert_eq!(pusher.pending.lock().await.len(), 1);
+        pusher.push().await.unwrap();
+        assert!(pusher.pending.lock().await.is_empty());
+        assert_eq!(received.requests.load(Ordering::SeqCst), 3);
+        assert_eq!(received.paths.lock().await.len(), 2);
+    }
+}
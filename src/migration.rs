@@ -0,0 +1,238 @@
+//! Migration of standalone ledgers into a cluster
+//!
+//! Early deployments ran the single-process [`HyraScribeLedger`] engine, which keeps
+//! every key in the default tree of one sled database. `scribe-node migrate-legacy`
+//! bootstraps a fresh node and streams such a database into it through Raft
+//! proposals, so the imported keys are replicated and snapshotted like any other
+//! write. This module provides the pieces the command is built from:
+//!
+//! - [`open_legacy`] opens the standalone database, refusing paths without one
+//! - [`LegacyBatches`] streams its pairs in key order, a batch at a time
+//! - [`MigrationProgress`] tracks keys and bytes written for progress reports
+//! - [`MigrationVerification`] compares the imported values with the originals
+//!
+//! The standalone database is only read; it can be kept as a fallback until the
+//! cluster is trusted.
+
+use crate::error::{Result, ScribeError};
+use crate::types::{Key, Value};
+use crate::HyraScribeLedger;
+use std::fmt;
+use std::path::Path;
+use std::time::Instant;
+
+/// Default number of pairs proposed per batch
+pub const DEFAULT_MIGRATION_BATCH_SIZE: usize = 500;
+
+/// Number of failing keys kept as examples in a verification report
+const MAX_EXAMPLE_KEYS: usize = 10;
+
+/// Open the standalone ledger at `path`
+///
+/// Fails instead of creating an empty database when `path` holds none, so a
+/// mistyped path is not silently migrated as zero keys.
+pub fn open_legacy(path: &Path) -> Result<HyraScribeLedger> {
+    if !path.join("conf").is_file() {
+        return Err(ScribeError::Storage(format!(
+            "No sled database found at {:?}",
+            path
+        )));
+    }
+    HyraScribeLedger::new(path).map_err(|e| {
+        ScribeError::Storage(format!("Failed to open legacy ledger {:?}: {}", path, e))
+    })
+}
+
+/// Pairs of a standalone ledger in key order, `batch_size` at a time
+pub struct LegacyBatches {
+    iter: sled::Iter,
+    batch_size: usize,
+}
+
+impl LegacyBatches {
+    /// Stream the pairs of `ledger`
+    pub fn new(ledger: &HyraScribeLedger, batch_size: usize) -> Self {
+        Self {
+            iter: ledger.iter(),
+            batch_size: batch_size.max(1),
+        }
+    }
+}
+
+impl Iterator for LegacyBatches {
+    type Item = Result<Vec<(Key, Value)>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut batch = Vec::with_capacity(self.batch_size);
+        for item in self.iter.by_ref() {
+            match item {
+                Ok((key, value)) => batch.push((key.to_vec(), value.to_vec())),
+                Err(e) => return Some(Err(e.into())),
+            }
+            if batch.len() == self.batch_size {
+                break;
+            }
+        }
+        (!batch.is_empty()).then_some(Ok(batch))
+    }
+}
+
+/// Keys and bytes written so far
+#[derive(Debug, Clone)]
+pub struct MigrationProgress {
+    /// Keys in the standalone ledger
+    pub total_keys: usize,
+    /// Keys written
+    pub keys: usize,
+    /// Bytes of keys and values written
+    pub bytes: u64,
+    started: Instant,
+}
+
+impl MigrationProgress {
+    /// Start tracking a migration of `total_keys` keys
+    pub fn new(total_keys: usize) -> Self {
+        Self {
+            total_keys,
+            keys: 0,
+            bytes: 0,
+            started: Instant::now(),
+        }
+    }
+
+    /// Count a written batch
+    pub fn record(&mut self, batch: &[(Key, Value)]) {
+        self.keys += batch.len();
+        self.bytes += batch
+            .iter()
+            .map(|(key, value)| (key.len() + value.len()) as u64)
+            .sum::<u64>();
+    }
+
+    /// Share of the keys written, in percent
+    pub fn percent(&self) -> f64 {
+        if self.total_keys == 0 {
+            return 100.0;
+        }
+        self.keys as f64 * 100.0 / self.total_keys as f64
+    }
+
+    /// Keys written per second since the start
+    pub fn keys_per_sec(&self) -> f64 {
+        let secs = self.started.elapsed().as_secs_f64();
+        if secs > 0.0 {
+            self.keys as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+impl fmt::Display for MigrationProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} keys ({:.1}%), {:.1} MiB, {:.0} keys/s",
+            self.keys,
+            self.total_keys,
+            self.percent(),
+            self.bytes as f64 / (1024.0 * 1024.0),
+            self.keys_per_sec()
+        )
+    }
+}
+
+/// Comparison of the imported values with the standalone ledger
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MigrationVerification {
+    /// Keys compared
+    pub checked: usize,
+    /// Keys absent from the cluster
+    pub missing: usize,
+    /// Keys whose value in the cluster differs
+    pub mismatched: usize,
+    /// First failing keys, for the report
+    pub examples: Vec<Key>,
+}
+
+impl MigrationVerification {
+    /// Compare the value `key` has in the cluster with the original
+    pub fn check(&mut self, key: &[u8], expected: &[u8], actual: Option<&[u8]>) {
+        self.checked += 1;
+        let failed = match actual {
+            None => {
+                self.missing += 1;
+                true
+            }
+            Some(actual) if actual != expected => {
+                self.mismatched += 1;
+                true
+            }
+            Some(_) => false,
+        };
+        if failed && self.examples.len() < MAX_EXAMPLE_KEYS {
+            self.examples.push(key.to_vec());
+        }
+    }
+
+    /// Whether every key was found with its original value
+    pub fn is_ok(&self) -> bool {
+        self.missing == 0 && self.mismatched == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legacy_batches_and_progress() {
+        let path = std::env::temp_dir().join(format!("scribe-legacy-{}", fastrand::u64(..)));
+        assert!(open_legacy(&path).is_err());
+        assert!(!path.exists());
+
+        {
+            let ledger = HyraScribeLedger::new(&path).unwrap();
+            for i in 0..7 {
+                ledger.put(format!("key{}", i), "value").unwrap();
+            }
+            ledger.flush().unwrap();
+        }
+
+        let ledger = open_legacy(&path).unwrap();
+        let batches: Vec<Vec<(Key, Value)>> = LegacyBatches::new(&ledger, 3)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            batches.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![3, 3, 1]
+        );
+        assert_eq!(batches[0][0].0, b"key0".to_vec());
+
+        let mut progress = MigrationProgress::new(ledger.len());
+        for batch in &batches {
+            progress.record(batch);
+        }
+        assert_eq!(progress.keys, 7);
+        assert_eq!(progress.bytes, 7 * 9);
+        assert_eq!(progress.percent(), 100.0);
+
+        drop(ledger);
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_verification() {
+        let mut verification = MigrationVerification::default();
+        verification.check(b"a", b"1", Some(b"1"));
+        assert!(verification.is_ok());
+
+        verification.check(b"b", b"2", None);
+        verification.check(b"c", b"3", Some(b"4"));
+        assert!(!verification.is_ok());
+        assert_eq!(verification.checked, 3);
+        assert_eq!(verification.missing, 1);
+        assert_eq!(verification.mismatched, 1);
+        assert_eq!(verification.examples, vec![b"b".to_vec(), b"c".to_vec()]);
+    }
+}
@@ -0,0 +1,428 @@
+//! Read-only mirror serving archived data straight from S3
+//!
+//! A [`SegmentMirror`] indexes the segments archived in an S3 bucket and answers reads
+//! from them, without Raft or sled. The manifest is rebuilt from the segment metadata
+//! objects, so archived segments can be re-verified against it. Mirrors keep no local
+//! state and can be added freely as a cheap serving tier for historical data and
+//! audits; [`router`] serves `GET /:key`, `GET /manifest`, `GET /mirror/status` and
+//! `POST /admin/verify-segment/:id`.
+//!
+//! A mirror serves the last archived value of every key: writes that have not been
+//! archived yet are not visible, and deletes are never archived, so deleted keys keep
+//! their last archived value.
+
+use crate::error::Result;
+use crate::http_client::{value_etag, SEGMENT_ID_HEADER, TIER_HEADER};
+use crate::manifest::{ClusterManifest, ManifestManager};
+use crate::storage::archival::{ArchivalManager, TieringPolicy};
+use crate::storage::s3::S3StorageConfig;
+use crate::storage::segment::{Segment, SegmentManager};
+use crate::types::{Key, SegmentId, Value};
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::Router;
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+/// Number of decoded segments a mirror keeps in memory unless configured otherwise
+pub const DEFAULT_MIRROR_CACHE_SEGMENTS: usize = 64;
+
+/// Interval between index refreshes unless configured otherwise
+pub const DEFAULT_MIRROR_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Outcome of an index refresh
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RefreshReport {
+    /// Segments indexed by this refresh
+    pub segments_added: usize,
+    /// Segments no longer in the bucket, dropped from the index
+    pub segments_removed: usize,
+    /// Segments that could not be read (retried on the next refresh)
+    pub segments_unreadable: usize,
+}
+
+/// Summary of what a mirror serves
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MirrorStatus {
+    /// Indexed segments
+    pub segments: usize,
+    /// Distinct keys across the indexed segments
+    pub keys: usize,
+    /// Version of the rebuilt manifest
+    pub manifest_version: u64,
+    /// Segments listed in the bucket that could not be read, e.g. because they are
+    /// encrypted with per-tenant keys the mirror does not hold
+    pub unreadable_segments: Vec<SegmentId>,
+    /// Time of the last successful refresh (milliseconds since UNIX epoch)
+    pub refreshed_at: Option<u64>,
+}
+
+/// Segment holding the newest archived value of every key
+#[derive(Debug, Default)]
+struct MirrorIndex {
+    /// Newest segment holding each key
+    keys: HashMap<Key, SegmentId>,
+    /// Keys of every indexed segment
+    segments: BTreeMap<SegmentId, Vec<Key>>,
+    /// Listed segments that could not be read
+    unreadable: BTreeSet<SegmentId>,
+    /// Time of the last successful refresh
+    refreshed_at: Option<u64>,
+}
+
+impl MirrorIndex {
+    /// Index the keys of a segment; a key held by several segments is served from
+    /// the one with the highest ID
+    fn insert(&mut self, segment_id: SegmentId, keys: Vec<Key>) {
+        for key in &keys {
+            let newest = self.keys.entry(key.clone()).or_insert(segment_id);
+            *newest = (*newest).max(segment_id);
+        }
+        self.unreadable.remove(&segment_id);
+        self.segments.insert(segment_id, keys);
+    }
+
+    /// Drop a segment, falling back to older segments for the keys it held
+    fn remove(&mut self, segment_id: SegmentId) {
+        self.unreadable.remove(&segment_id);
+        let Some(keys) = self.segments.remove(&segment_id) else {
+            return;
+        };
+        for key in keys {
+            if self.keys.get(&key) != Some(&segment_id) {
+                continue;
+            }
+            let older = self
+                .segments
+                .iter()
+                .rev()
+                .find(|(_, keys)| keys.contains(&key))
+                .map(|(id, _)| *id);
+            match older {
+                Some(id) => self.keys.insert(key, id),
+                None => self.keys.remove(&key),
+            };
+        }
+    }
+}
+
+/// Read-only view of the segments archived in an S3 bucket
+pub struct SegmentMirror {
+    archival: ArchivalManager,
+    manifest: Arc<ManifestManager>,
+    index: RwLock<MirrorIndex>,
+    cache: Mutex<LruCache<SegmentId, Arc<Segment>>>,
+    /// Serializes refreshes so concurrent callers do not download segments twice
+    refresh_lock: tokio::sync::Mutex<()>,
+}
+
+impl SegmentMirror {
+    /// Create a mirror of the bucket in `config`; the index is empty until
+    /// [`refresh`](Self::refresh) runs
+    pub async fn new(config: S3StorageConfig) -> Result<Self> {
+        let manifest = Arc::new(ManifestManager::new());
+        let policy = TieringPolicy {
+            enable_auto_archival: false,
+            ..TieringPolicy::default()
+        };
+        let archival = ArchivalManager::new(config, Arc::new(SegmentManager::new()), policy)
+            .await?
+            .with_manifest(Arc::clone(&manifest));
+
+        Ok(Self {
+            archival,
+            manifest,
+            index: RwLock::new(MirrorIndex::default()),
+            cache: Mutex::new(LruCache::new(
+                NonZeroUsize::new(DEFAULT_MIRROR_CACHE_SEGMENTS).unwrap(),
+            )),
+            refresh_lock: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    /// Keep up to `segments` decoded segments in memory
+    pub fn with_cache_segments(self, segments: usize) -> Self {
+        let capacity = NonZeroUsize::new(segments).unwrap_or(NonZeroUsize::new(1).unwrap());
+        self.cache.lock().unwrap().resize(capacity);
+        self
+    }
+
+    /// Index segments archived since the last refresh and drop deleted ones
+    ///
+    /// New segments are downloaded once to learn their keys and recorded in the
+    /// manifest. Segments that cannot be read are skipped and retried next time.
+    pub async fn refresh(&self) -> Result<RefreshReport> {
+        let _guard = self.refresh_lock.lock().await;
+        let listed: BTreeSet<SegmentId> = self
+            .archival
+            .list_archived_segments()
+            .await?
+            .into_iter()
+            .collect();
+        let indexed: BTreeSet<SegmentId> = self
+            .index
+            .read()
+            .unwrap()
+            .segments
+            .keys()
+            .copied()
+            .collect();
+
+        let mut report = RefreshReport::default();
+        for &segment_id in indexed.difference(&listed) {
+            self.index.write().unwrap().remove(segment_id);
+            self.cache.lock().unwrap().pop(&segment_id);
+            self.manifest.remove_segment(segment_id).await?;
+            report.segments_removed += 1;
+        }
+
+        for &segment_id in listed.difference(&indexed) {
+            match self.index_segment(segment_id).await {
+                Ok(true) => report.segments_added += 1,
+                // Metadata not written yet: the segment is still being archived
+                Ok(false) => {}
+                Err(e) => {
+                    warn!("Mirror could not read segment {}: {}", segment_id, e);
+                    self.index.write().unwrap().unreadable.insert(segment_id);
+                    report.segments_unreadable += 1;
+                }
+            }
+        }
+
+        let mut index = self.index.write().unwrap();
+        index
+            .unreadable
+            .retain(|segment_id| listed.contains(segment_id));
+        index.refreshed_at = Some(now_millis());
+        Ok(report)
+    }
+
+    /// Download a segment, index its keys and record it in the manifest
+    async fn index_segment(&self, segment_id: SegmentId) -> Result<bool> {
+        let Some(metadata) = self.archival.get_metadata(segment_id).await? else {
+            return Ok(false);
+        };
+        let Some(segment) = self.archival.fetch_segment(segment_id).await? else {
+            return Ok(false);
+        };
+
+        self.manifest.remove_segment(segment_id).await?;
+        self.manifest.add_segment(metadata.manifest_entry()).await?;
+        self.index
+            .write()
+            .unwrap()
+            .insert(segment_id, segment.data.keys().cloned().collect());
+        Ok(true)
+    }
+
+    /// Refresh the index every `interval` in the background
+    pub fn start_refresh(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let mirror = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(e) = mirror.refresh().await {
+                    warn!("Mirror refresh failed: {}", e);
+                }
+            }
+        })
+    }
+
+    /// Newest archived value of `key` and the segment holding it
+    pub async fn get(&self, key: &[u8]) -> Result<Option<(SegmentId, Value)>> {
+        let Some(segment_id) = self.index.read().unwrap().keys.get(key).copied() else {
+            return Ok(None);
+        };
+
+        let cached = self.cache.lock().unwrap().get(&segment_id).cloned();
+        let segment = match cached {
+            Some(segment) => segment,
+            None => {
+                let Some(segment) = self.archival.fetch_segment(segment_id).await? else {
+                    return Ok(None);
+                };
+                let segment = Arc::new(segment);
+                self.cache
+                    .lock()
+                    .unwrap()
+                    .put(segment_id, Arc::clone(&segment));
+                segment
+            }
+        };
+        Ok(segment
+            .data
+            .get(key)
+            .map(|value| (segment_id, value.clone())))
+    }
+
+    /// Manifest rebuilt from the metadata of the indexed segments
+    pub async fn manifest(&self) -> ClusterManifest {
+        self.manifest.get_latest().await
+    }
+
+    /// Archival manager reading the mirrored bucket
+    pub fn archival(&self) -> &ArchivalManager {
+        &self.archival
+    }
+
+    /// What the mirror currently serves
+    pub async fn status(&self) -> MirrorStatus {
+        let manifest_version = self.manifest.get_version().await;
+        let index = self.index.read().unwrap();
+        MirrorStatus {
+            segments: index.segments.len(),
+            keys: index.keys.len(),
+            manifest_version,
+            unreadable_segments: index.unreadable.iter().copied().collect(),
+            refreshed_at: index.refreshed_at,
+        }
+    }
+}
+
+/// HTTP API of a mirror; writes are answered with 405
+pub fn router(mirror: Arc<SegmentMirror>) -> Router {
+    Router::new()
+        .route("/health", get(health_handler))
+        .route("/manifest", get(manifest_handler))
+        .route("/mirror/status", get(status_handler))
+        .route("/admin/verify-segment/:id", post(verify_segment_handler))
+        .route("/:key", get(get_handler))
+        .with_state(mirror)
+}
+
+/// Health of the mirror; 503 until the first refresh succeeds
+async fn health_handler(State(mirror): State<Arc<SegmentMirror>>) -> Response {
+    let status = mirror.status().await;
+    let code = if status.refreshed_at.is_some() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let body = serde_json::json!({
+        "status": if code == StatusCode::OK { "ok" } else { "starting" },
+        "mode": "mirror",
+        "segments": status.segments,
+        "keys": status.keys,
+    });
+    (code, axum::Json(body)).into_response()
+}
+
+async fn manifest_handler(State(mirror): State<Arc<SegmentMirror>>) -> Response {
+    axum::Json(mirror.manifest().await).into_response()
+}
+
+async fn status_handler(State(mirror): State<Arc<SegmentMirror>>) -> Response {
+    axum::Json(mirror.status().await).into_response()
+}
+
+/// Re-validate an archived segment against its recorded Merkle roots
+async fn verify_segment_handler(
+    State(mirror): State<Arc<SegmentMirror>>,
+    Path(segment_id): Path<SegmentId>,
+) -> Response {
+    match mirror.archival().verify_segment(segment_id).await {
+        Ok(Some(report)) => axum::Json(report).into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            format!("Segment {} is not archived", segment_id),
+        )
+            .into_response(),
+        Err(e) => (StatusCode::BAD_GATEWAY, format!("Error: {}", e)).into_response(),
+    }
+}
+
+/// Newest archived value of a key
+async fn get_handler(
+    State(mirror): State<Arc<SegmentMirror>>,
+    Path(key): Path<String>,
+) -> Response {
+    match mirror.get(key.as_bytes()).await {
+        Ok(Some((segment_id, value))) => {
+            let mut response = value.clone().into_response();
+            let headers = response.headers_mut();
+            headers.insert(TIER_HEADER, HeaderValue::from_static("cold"));
+            headers.insert(SEGMENT_ID_HEADER, HeaderValue::from(segment_id));
+            if let Ok(etag) = HeaderValue::from_str(&value_etag(&value)) {
+                headers.insert(header::ETAG, etag);
+            }
+            response
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "Key not found".to_string()).into_response(),
+        Err(e) => (StatusCode::BAD_GATEWAY, format!("Error: {}", e)).into_response(),
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::IntoFuture;
+
+    /// Mirror whose S3 client is never used (the index is empty)
+    async fn unused_mirror() -> Arc<SegmentMirror> {
+        let config = S3StorageConfig {
+            bucket: "unused".to_string(),
+            endpoint: Some("http://127.0.0.1:9".to_string()),
+            access_key_id: Some("key".to_string()),
+            secret_access_key: Some("secret".to_string()),
+            ..S3StorageConfig::default()
+        };
+        Arc::new(SegmentMirror::new(config).await.unwrap())
+    }
+
+    #[test]
+    fn test_index_serves_newest_segment() {
+        let mut index = MirrorIndex::default();
+        index.insert(2, vec![b"a".to_vec(), b"b".to_vec()]);
+        index.insert(5, vec![b"b".to_vec(), b"c".to_vec()]);
+        // Segments indexed out of order still resolve to the newest one
+        index.insert(1, vec![b"a".to_vec(), b"c".to_vec()]);
+        assert_eq!(index.keys[&b"a".to_vec()], 2);
+        assert_eq!(index.keys[&b"b".to_vec()], 5);
+        assert_eq!(index.keys[&b"c".to_vec()], 5);
+
+        index.remove(5);
+        assert_eq!(index.keys[&b"b".to_vec()], 2);
+        assert_eq!(index.keys[&b"c".to_vec()], 1);
+        index.remove(1);
+        assert!(!index.keys.contains_key(b"c".as_slice()));
+        assert_eq!(index.keys.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_router_is_read_only() {
+        let mirror = unused_mirror().await;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(axum::serve(listener, router(Arc::clone(&mirror))).into_future());
+        let client = reqwest::Client::new();
+
+        let response = client.get(format!("{}/key", url)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 404);
+        let response = client.put(format!("{}/key", url)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 405);
+        let response = client.delete(format!("{}/key", url)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 405);
+
+        // Not healthy until the index has been built once
+        let response = client.get(format!("{}/health", url)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 503);
+        assert_eq!(mirror.status().await, MirrorStatus::default());
+    }
+}
@@ -0,0 +1,477 @@
+//! Moving and copying keys between namespaces
+//!
+//! An operator migrates every key under one prefix to another prefix with
+//! `POST /admin/migrations/:id`, e.g. `jobs/` to `jobs-v2/`. The leader walks the
+//! source keys in key order and commits them in batches, each batch one
+//! transaction through Raft (see [`crate::api::DistributedApi::transaction`]) that
+//! writes the target keys, deletes the source keys of a move and advances the
+//! migration's [`MigrationCheckpoint`] together. The checkpoint is stored under
+//! [`MIGRATION_KEY_PREFIX`], so it is replicated like any other key: after a
+//! pause, a failure or a leader change, starting the migration again with the same
+//! ID resumes after the last committed batch, and a key migrated twice is counted
+//! as unchanged rather than written again.
+//!
+//! A target key that already holds a different value is left alone and counted as
+//! skipped, unless the migration overwrites; the source key of a skipped move is
+//! kept too. Migrated keys lose their TTL. Writes to source keys behind the cursor
+//! are not picked up, so clients should stop writing to the source first.
+
+use crate::error::{Result, ScribeError};
+use crate::types::{Key, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Prefix of the keys holding migration checkpoints
+pub const MIGRATION_KEY_PREFIX: &str = "__migrations/";
+
+/// Source keys committed per batch unless the migration says otherwise
+pub const DEFAULT_BATCH_SIZE: usize = 500;
+
+/// Largest batch, keeping each transaction's log entry bounded
+pub const MAX_BATCH_SIZE: usize = 5_000;
+
+/// Longest migration ID
+const MAX_ID_LEN: usize = 128;
+
+/// What a migration does with the source keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationMode {
+    /// Write the target keys and keep the source keys
+    Copy,
+    /// Write the target keys and delete the source keys
+    Move,
+}
+
+/// Which keys a migration moves and how fast
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigrationSpec {
+    /// Prefix of the keys to migrate
+    pub source: String,
+    /// Prefix replacing `source` in the migrated keys
+    pub target: String,
+    pub mode: MigrationMode,
+    /// Source keys per batch
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// Most source keys migrated per second; unlimited if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_keys_per_sec: Option<u32>,
+    /// Replace target keys that already hold a different value
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+fn default_batch_size() -> usize {
+    DEFAULT_BATCH_SIZE
+}
+
+impl MigrationSpec {
+    /// Check that the prefixes and limits make sense
+    ///
+    /// The prefixes must not overlap: keys written under a target inside the source
+    /// would be migrated again, without end.
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.source.is_empty() || self.target.is_empty() {
+            return Err("Source and target prefixes must not be empty".to_string());
+        }
+        if self.source.starts_with(&self.target) || self.target.starts_with(&self.source) {
+            return Err(format!(
+                "Prefixes '{}' and '{}' overlap",
+                self.source, self.target
+            ));
+        }
+        if self.source.starts_with("__") || self.target.starts_with("__") {
+            return Err("Prefixes starting with '__' are reserved".to_string());
+        }
+        if self.batch_size == 0 || self.batch_size > MAX_BATCH_SIZE {
+            return Err(format!(
+                "Batch size must be between 1 and {}",
+                MAX_BATCH_SIZE
+            ));
+        }
+        if self.max_keys_per_sec == Some(0) {
+            return Err("max_keys_per_sec must be positive".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether `other` migrates the same keys the same way, if perhaps in other
+    /// batches or at another rate
+    pub fn same_keys(&self, other: &MigrationSpec) -> bool {
+        self.source == other.source
+            && self.target == other.target
+            && self.mode == other.mode
+            && self.overwrite == other.overwrite
+    }
+
+    /// Key `key` is migrated to, or `None` if it is not under the source prefix
+    pub fn target_key(&self, key: &[u8]) -> Option<Key> {
+        let rest = key.strip_prefix(self.source.as_bytes())?;
+        let mut target = self.target.as_bytes().to_vec();
+        target.extend_from_slice(rest);
+        Some(target)
+    }
+
+    /// Pause after a batch of `keys` keys to stay under `max_keys_per_sec`
+    pub fn throttle(&self, keys: usize) -> Duration {
+        match self.max_keys_per_sec {
+            Some(rate) if rate > 0 => Duration::from_secs_f64(keys as f64 / rate as f64),
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+/// State of a migration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationState {
+    /// Batches are being committed
+    Running,
+    /// Stopped by an operator; starting it again resumes it
+    Paused,
+    /// Every source key was migrated
+    Completed,
+    /// A batch failed; see the error. Starting it again resumes it
+    Failed,
+}
+
+/// A migration and how far it got, stored under [`MIGRATION_KEY_PREFIX`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigrationCheckpoint {
+    pub id: String,
+    #[serde(flatten)]
+    pub spec: MigrationSpec,
+    pub state: MigrationState,
+    /// Last source key of the last committed batch (hex)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    /// Target keys written
+    pub migrated: u64,
+    /// Source keys whose target already held the same value
+    pub unchanged: u64,
+    /// Source keys left alone because their target held a different value
+    pub skipped: u64,
+    /// Source keys deleted by a move
+    pub deleted: u64,
+    /// Batches committed
+    pub batches: u64,
+    /// Why the migration last failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// When the migration was first started (milliseconds since the Unix epoch)
+    pub started_at_ms: u64,
+    /// When the checkpoint was last written
+    pub updated_at_ms: u64,
+}
+
+/// A source key read by a batch, with the value of its target key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchKey {
+    pub source: Key,
+    /// Value of the source key; `None` if it was deleted since it was listed
+    pub value: Option<Value>,
+    pub target: Key,
+    /// Value of the target key
+    pub existing: Option<Value>,
+}
+
+impl MigrationCheckpoint {
+    /// A new running migration `id` of `spec`
+    pub fn new(id: &str, spec: MigrationSpec, now_ms: u64) -> Self {
+        Self {
+            id: id.to_string(),
+            spec,
+            state: MigrationState::Running,
+            cursor: None,
+            migrated: 0,
+            unchanged: 0,
+            skipped: 0,
+            deleted: 0,
+            batches: 0,
+            error: None,
+            started_at_ms: now_ms,
+            updated_at_ms: now_ms,
+        }
+    }
+
+    /// Decode a checkpoint as stored
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_json::from_slice(bytes)
+            .map_err(|e| ScribeError::Serialization(format!("Invalid migration checkpoint: {}", e)))
+    }
+
+    /// Encode the checkpoint for storage
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec(self)
+            .map_err(|e| ScribeError::Serialization(format!("Invalid migration checkpoint: {}", e)))
+    }
+
+    /// The cursor as a key: source keys after it are still to be migrated
+    pub fn cursor_key(&self) -> Option<Key> {
+        self.cursor
+            .as_deref()
+            .and_then(|cursor| hex::decode(cursor).ok())
+    }
+
+    /// Count `batch` and move the cursor past it; returns the writes that migrate it,
+    /// `None` values deleting the key
+    ///
+    /// `batch` must be in key order.
+    pub fn apply_batch(&mut self, batch: &[BatchKey], now_ms: u64) -> Vec<(Key, Option<Value>)> {
+        let mut writes = Vec::new();
+        for key in batch {
+            let Some(value) = &key.value else {
+                continue;
+            };
+            match &key.existing {
+                Some(existing) if existing == value => self.unchanged += 1,
+                Some(_) if !self.spec.overwrite => {
+                    self.skipped += 1;
+                    continue;
+                }
+                _ => {
+                    writes.push((key.target.clone(), Some(value.clone())));
+                    self.migrated += 1;
+                }
+            }
+            if self.spec.mode == MigrationMode::Move {
+                writes.push((key.source.clone(), None));
+                self.deleted += 1;
+            }
+        }
+        if let Some(last) = batch.last() {
+            self.cursor = Some(hex::encode(&last.source));
+        }
+        self.batches += 1;
+        self.updated_at_ms = now_ms;
+        writes
+    }
+}
+
+/// Key of the checkpoint of migration `id`
+pub fn checkpoint_key(id: &str) -> Key {
+    format!("{}{}", MIGRATION_KEY_PREFIX, id).into_bytes()
+}
+
+/// Check a migration ID: 1 to 128 ASCII letters, digits, `-`, `_` or `.`
+pub fn validate_id(id: &str) -> std::result::Result<(), String> {
+    let valid = !id.is_empty()
+        && id.len() <= MAX_ID_LEN
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Migration ID must be 1 to {} letters, digits, '-', '_' or '.'",
+            MAX_ID_LEN
+        ))
+    }
+}
+
+/// Migrations running on this node, so each runs at most once and can be paused
+///
+/// Cheap to clone; clones share the migrations.
+#[derive(Clone, Default)]
+pub struct RunningMigrations {
+    running: Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>,
+}
+
+impl RunningMigrations {
+    /// Create an empty set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Claim migration `id` for a run, or `None` if it is already running
+    pub fn start(&self, id: &str) -> Option<MigrationRun> {
+        let mut running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        if running.contains_key(id) {
+            return None;
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        running.insert(id.to_string(), stop.clone());
+        Some(MigrationRun {
+            id: id.to_string(),
+            stop,
+            running: self.clone(),
+        })
+    }
+
+    /// Ask migration `id` to stop after its current batch; `false` if it is not
+    /// running here
+    pub fn stop(&self, id: &str) -> bool {
+        let running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        match running.get(id) {
+            Some(stop) => {
+                stop.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether migration `id` is running here
+    pub fn is_running(&self, id: &str) -> bool {
+        let running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        running.contains_key(id)
+    }
+}
+
+/// Claim on a running migration, released when dropped
+pub struct MigrationRun {
+    id: String,
+    stop: Arc<AtomicBool>,
+    running: RunningMigrations,
+}
+
+impl MigrationRun {
+    /// ID of the migration
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Whether the migration was asked to stop
+    pub fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for MigrationRun {
+    fn drop(&mut self) {
+        let mut running = self
+            .running
+            .running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        running.remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(mode: MigrationMode, overwrite: bool) -> MigrationSpec {
+        MigrationSpec {
+            source: "jobs/".to_string(),
+            target: "jobs-v2/".to_string(),
+            mode,
+            batch_size: 2,
+            max_keys_per_sec: Some(100),
+            overwrite,
+        }
+    }
+
+    fn batch_key(name: &str, value: Option<&str>, existing: Option<&str>) -> BatchKey {
+        BatchKey {
+            source: format!("jobs/{}", name).into_bytes(),
+            value: value.map(|v| v.as_bytes().to_vec()),
+            target: format!("jobs-v2/{}", name).into_bytes(),
+            existing: existing.map(|v| v.as_bytes().to_vec()),
+        }
+    }
+
+    #[test]
+    fn test_validate_spec() {
+        let valid = spec(MigrationMode::Copy, false);
+        assert!(valid.validate().is_ok());
+        assert_eq!(valid.target_key(b"jobs/1"), Some(b"jobs-v2/1".to_vec()));
+        assert_eq!(valid.target_key(b"users/1"), None);
+        assert_eq!(valid.throttle(50), Duration::from_millis(500));
+        let faster = MigrationSpec {
+            batch_size: 100,
+            max_keys_per_sec: None,
+            ..valid.clone()
+        };
+        assert!(valid.same_keys(&faster));
+        assert_eq!(faster.throttle(50), Duration::ZERO);
+
+        let overlapping = MigrationSpec {
+            target: "jobs/archive/".to_string(),
+            ..valid.clone()
+        };
+        assert!(overlapping.validate().is_err());
+        let reserved = MigrationSpec {
+            source: "__sessions/".to_string(),
+            ..valid.clone()
+        };
+        assert!(reserved.validate().is_err());
+        let empty_batch = MigrationSpec {
+            batch_size: 0,
+            ..valid
+        };
+        assert!(empty_batch.validate().is_err());
+
+        assert!(validate_id("jobs-to-v2.1").is_ok());
+        assert!(validate_id("").is_err());
+        assert!(validate_id("a/b").is_err());
+    }
+
+    #[test]
+    fn test_apply_batch() {
+        let mut copy = MigrationCheckpoint::new("m", spec(MigrationMode::Copy, false), 1);
+        let writes = copy.apply_batch(
+            &[
+                batch_key("1", Some("a"), None),
+                batch_key("2", Some("b"), Some("b")),
+                batch_key("3", Some("c"), Some("other")),
+                batch_key("4", None, None),
+            ],
+            2,
+        );
+        assert_eq!(writes, vec![(b"jobs-v2/1".to_vec(), Some(b"a".to_vec()))]);
+        assert_eq!(
+            (copy.migrated, copy.unchanged, copy.skipped, copy.deleted),
+            (1, 1, 1, 0)
+        );
+        assert_eq!(copy.cursor_key(), Some(b"jobs/4".to_vec()));
+        assert_eq!((copy.batches, copy.updated_at_ms), (1, 2));
+
+        // A move deletes the sources it migrated, keeping those it skipped
+        let mut moved = MigrationCheckpoint::new("m", spec(MigrationMode::Move, false), 1);
+        let writes = moved.apply_batch(
+            &[
+                batch_key("1", Some("a"), Some("a")),
+                batch_key("2", Some("b"), Some("other")),
+            ],
+            2,
+        );
+        assert_eq!(writes, vec![(b"jobs/1".to_vec(), None)]);
+        assert_eq!((moved.unchanged, moved.skipped, moved.deleted), (1, 1, 1));
+
+        let mut overwrite = MigrationCheckpoint::new("m", spec(MigrationMode::Move, true), 1);
+        let writes = overwrite.apply_batch(&[batch_key("2", Some("b"), Some("other"))], 2);
+        assert_eq!(
+            writes,
+            vec![
+                (b"jobs-v2/2".to_vec(), Some(b"b".to_vec())),
+                (b"jobs/2".to_vec(), None)
+            ]
+        );
+
+        let bytes = overwrite.to_bytes().unwrap();
+        assert_eq!(MigrationCheckpoint::from_bytes(&bytes).unwrap(), overwrite);
+    }
+
+    #[test]
+    fn test_running_migrations() {
+        let running = RunningMigrations::new();
+        let run = running.start("m").unwrap();
+        assert!(running.start("m").is_none());
+        assert!(running.is_running("m"));
+        assert!(!run.is_stopped());
+        assert!(running.stop("m"));
+        assert!(run.is_stopped());
+        assert!(!running.stop("other"));
+
+        drop(run);
+        assert!(!running.is_running("m"));
+        assert!(running.start("m").is_some());
+    }
+}
@@ -0,0 +1,374 @@
+//! Latency-aware leader placement
+//!
+//! Every node measures the round trip of its Raft heartbeats and votes to each peer
+//! in [`PeerLatencies`] and reports them, together with how many client operations
+//! it served, as the [`NodeLatency`] part of its status. [`recommend_leader`]
+//! combines the reports of all members into the voter that minimizes the median
+//! latency clients see.
+//!
+//! A client operation entering the cluster at node `i` while `L` leads is estimated
+//! to take the round trip from `i` to `L` plus the commit latency of `L`, the round
+//! trip to the slowest voter of the fastest quorum around `L`. The median is taken
+//! over every node, weighted by the operations it served, so the leader moves
+//! towards the regions most clients talk to.
+//!
+//! Recommendations are advisory. With `[placement] auto_transfer` enabled the leader
+//! hands leadership over to the recommended voter once that cuts the estimated
+//! median by at least `min_improvement_ms`.
+
+use crate::metrics::api_operation_totals;
+use crate::types::NodeId;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// Weight of a new round trip in the smoothed estimate, as in TCP's SRTT (1/8)
+const RTT_SMOOTHING_SHIFT: u32 = 3;
+
+/// Leader placement configuration (`[placement]`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlacementConfig {
+    /// Hand leadership to the recommended voter automatically
+    #[serde(default)]
+    pub auto_transfer: bool,
+    /// Seconds between two evaluations of the leader, while auto-transfer is on
+    #[serde(default = "default_placement_interval_secs")]
+    pub interval_secs: u64,
+    /// Estimated median improvement a transfer must bring, in milliseconds
+    #[serde(default = "default_min_improvement_ms")]
+    pub min_improvement_ms: u64,
+}
+
+fn default_placement_interval_secs() -> u64 {
+    300
+}
+
+fn default_min_improvement_ms() -> u64 {
+    20
+}
+
+impl Default for PlacementConfig {
+    fn default() -> Self {
+        Self {
+            auto_transfer: false,
+            interval_secs: default_placement_interval_secs(),
+            min_improvement_ms: default_min_improvement_ms(),
+        }
+    }
+}
+
+impl PlacementConfig {
+    /// Check the evaluation interval
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.interval_secs == 0 {
+            return Err("Placement interval_secs must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+
+    /// Improvement a transfer must bring
+    pub fn min_improvement(&self) -> Duration {
+        Duration::from_millis(self.min_improvement_ms)
+    }
+}
+
+/// Smoothed round-trip times of Raft RPCs to each peer
+///
+/// Cheap to clone; clones share the table.
+#[derive(Debug, Clone, Default)]
+pub struct PeerLatencies {
+    rtt_us: Arc<RwLock<HashMap<NodeId, u64>>>,
+}
+
+impl PeerLatencies {
+    /// Create an empty table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fold a round trip of `rtt` to `peer` into its estimate
+    pub fn record(&self, peer: NodeId, rtt: Duration) {
+        let sample = rtt.as_micros().min(u64::MAX as u128) as u64;
+        let mut rtt_us = self.rtt_us.write().unwrap_or_else(|e| e.into_inner());
+        rtt_us
+            .entry(peer)
+            .and_modify(|estimate| {
+                *estimate = *estimate - (*estimate >> RTT_SMOOTHING_SHIFT)
+                    + (sample >> RTT_SMOOTHING_SHIFT);
+            })
+            .or_insert(sample);
+    }
+
+    /// Smoothed round trip to `peer`, if it was measured
+    pub fn get(&self, peer: NodeId) -> Option<Duration> {
+        let rtt_us = self.rtt_us.read().unwrap_or_else(|e| e.into_inner());
+        rtt_us.get(&peer).map(|us| Duration::from_micros(*us))
+    }
+
+    /// Smoothed round trip to every measured peer, in microseconds
+    pub fn snapshot(&self) -> BTreeMap<NodeId, u64> {
+        let rtt_us = self.rtt_us.read().unwrap_or_else(|e| e.into_inner());
+        rtt_us.iter().map(|(peer, us)| (*peer, *us)).collect()
+    }
+}
+
+/// Latencies a node reports for leader placement
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeLatency {
+    /// Smoothed Raft round trip to each peer, in microseconds
+    #[serde(default)]
+    pub peer_rtt_us: BTreeMap<NodeId, u64>,
+    /// Client operations served since the node started
+    #[serde(default)]
+    pub client_requests: u64,
+    /// Mean time to serve a client operation, in microseconds
+    #[serde(default)]
+    pub client_latency_us: Option<u64>,
+}
+
+impl NodeLatency {
+    /// Collect the latencies of the local node
+    pub fn collect(peers: &PeerLatencies) -> Self {
+        let (client_requests, client_seconds) = api_operation_totals();
+        Self {
+            peer_rtt_us: peers.snapshot(),
+            client_requests,
+            client_latency_us: (client_requests > 0)
+                .then(|| (client_seconds * 1e6 / client_requests as f64) as u64),
+        }
+    }
+}
+
+/// Estimated latencies with one voter as leader
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeaderCandidate {
+    /// Voter
+    pub node_id: NodeId,
+    /// Round trip to the slowest voter of its fastest quorum, in microseconds
+    pub commit_latency_us: Option<u64>,
+    /// Weighted median latency of client operations, in microseconds
+    pub median_latency_us: Option<u64>,
+}
+
+/// Leader recommended for the lowest median client latency
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlacementReport {
+    /// Node that produced the report
+    pub reported_by: NodeId,
+    /// Current leader
+    pub current_leader: Option<NodeId>,
+    /// Voter with the lowest estimated median, `None` without enough measurements
+    pub recommended_leader: Option<NodeId>,
+    /// Estimated median saved by moving leadership to the recommended voter,
+    /// in microseconds
+    pub improvement_us: Option<u64>,
+    /// Estimates of every voter, ordered by node ID
+    pub candidates: Vec<LeaderCandidate>,
+    /// Weight of each node in the median, the client operations it served
+    pub weights: BTreeMap<NodeId, u64>,
+}
+
+impl PlacementReport {
+    /// Voter to hand leadership to, if moving it saves at least `min_improvement`
+    pub fn transfer_target(&self, min_improvement: Duration) -> Option<NodeId> {
+        let target = self.recommended_leader?;
+        let improvement = Duration::from_micros(self.improvement_us?);
+        (Some(target) != self.current_leader && improvement >= min_improvement).then_some(target)
+    }
+}
+
+/// Recommend the voter whose leadership minimizes the median client latency
+///
+/// `latencies` holds what each member reported. A round trip missing in one
+/// direction is taken from the other. Voters lacking the round trips to a quorum or
+/// to a node serving clients get no estimate. Nodes that served no client yet are
+/// all weighted equally while no node did.
+pub fn recommend_leader(
+    reported_by: NodeId,
+    voters: &[NodeId],
+    latencies: &BTreeMap<NodeId, NodeLatency>,
+    current_leader: Option<NodeId>,
+) -> PlacementReport {
+    let mut weights: BTreeMap<NodeId, u64> = latencies
+        .iter()
+        .map(|(node_id, latency)| (*node_id, latency.client_requests))
+        .collect();
+    if weights.values().all(|weight| *weight == 0) {
+        weights.values_mut().for_each(|weight| *weight = 1);
+    }
+
+    let rtt = |from: NodeId, to: NodeId| -> Option<u64> {
+        if from == to {
+            return Some(0);
+        }
+        let measured = |a: NodeId, b: NodeId| latencies.get(&a)?.peer_rtt_us.get(&b).copied();
+        measured(from, to).or_else(|| measured(to, from))
+    };
+
+    let mut voters = voters.to_vec();
+    voters.sort_unstable();
+    voters.dedup();
+    let quorum = voters.len() / 2 + 1;
+
+    let candidates: Vec<LeaderCandidate> = voters
+        .iter()
+        .map(|&leader| {
+            let mut quorum_rtts: Vec<u64> = voters
+                .iter()
+                .filter_map(|&voter| rtt(leader, voter))
+                .collect();
+            quorum_rtts.sort_unstable();
+            let commit_latency_us = quorum_rtts.get(quorum - 1).copied();
+
+            let median_latency_us = commit_latency_us.and_then(|commit| {
+                let samples = weights
+                    .iter()
+                    .filter(|(_, weight)| **weight > 0)
+                    .map(|(&node_id, &weight)| Some((rtt(node_id, leader)? + commit, weight)))
+                    .collect::<Option<Vec<_>>>()?;
+                weighted_median(samples)
+            });
+
+            LeaderCandidate {
+                node_id: leader,
+                commit_latency_us,
+                median_latency_us,
+            }
+        })
+        .collect();
+
+    // Ties keep the current leader, then favor the lowest node ID
+    let recommended = candidates
+        .iter()
+        .filter_map(|candidate| Some((candidate.median_latency_us?, candidate)))
+        .min_by_key(|(median, candidate)| {
+            (
+                *median,
+                Some(candidate.node_id) != current_leader,
+                candidate.node_id,
+            )
+        });
+    let current_median = candidates
+        .iter()
+        .find(|candidate| Some(candidate.node_id) == current_leader)
+        .and_then(|candidate| candidate.median_latency_us);
+
+    PlacementReport {
+        reported_by,
+        current_leader,
+        recommended_leader: recommended.map(|(_, candidate)| candidate.node_id),
+        improvement_us: recommended
+            .zip(current_median)
+            .map(|((median, _), current)| current.saturating_sub(median)),
+        candidates,
+        weights,
+    }
+}
+
+/// Smallest value at or above which half of the total weight lies
+fn weighted_median(mut samples: Vec<(u64, u64)>) -> Option<u64> {
+    samples.sort_unstable();
+    let total: u64 = samples.iter().map(|(_, weight)| weight).sum();
+    let mut seen = 0;
+    for (value, weight) in samples {
+        seen += weight;
+        if seen * 2 >= total {
+            return Some(value);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Latencies of three regions: 1 and 2 are 10ms apart, 3 is 80ms from both
+    fn regions(client_requests: [u64; 3]) -> BTreeMap<NodeId, NodeLatency> {
+        let rtt = [
+            (1, BTreeMap::from([(2, 10_000), (3, 80_000)])),
+            (2, BTreeMap::from([(1, 10_000), (3, 80_000)])),
+            (3, BTreeMap::from([(1, 80_000)])),
+        ];
+        rtt.into_iter()
+            .zip(client_requests)
+            .map(|((node_id, peer_rtt_us), client_requests)| {
+                (
+                    node_id,
+                    NodeLatency {
+                        peer_rtt_us,
+                        client_requests,
+                        client_latency_us: None,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_recommends_leader_near_clients() {
+        // Most clients talk to node 2, so leading from 3 costs them a WAN round trip
+        let report = recommend_leader(1, &[1, 2, 3], &regions([10, 100, 5]), Some(3));
+        assert_eq!(report.recommended_leader, Some(2));
+        let candidate = |node_id: NodeId| {
+            report
+                .candidates
+                .iter()
+                .find(|candidate| candidate.node_id == node_id)
+                .unwrap()
+                .clone()
+        };
+        assert_eq!(candidate(2).commit_latency_us, Some(10_000));
+        assert_eq!(candidate(2).median_latency_us, Some(10_000));
+        // Node 3 takes the round trip to 2 from the other direction
+        assert_eq!(candidate(3).commit_latency_us, Some(80_000));
+        assert_eq!(candidate(3).median_latency_us, Some(160_000));
+        assert_eq!(report.improvement_us, Some(150_000));
+        assert_eq!(report.transfer_target(Duration::from_millis(20)), Some(2));
+        assert_eq!(report.transfer_target(Duration::from_millis(200)), None);
+
+        // Clients spread evenly tie 1 and 2; the current leader stays
+        let report = recommend_leader(1, &[1, 2, 3], &regions([0, 0, 0]), Some(2));
+        assert_eq!(report.recommended_leader, Some(2));
+        assert_eq!(report.improvement_us, Some(0));
+        assert_eq!(report.transfer_target(Duration::ZERO), None);
+    }
+
+    #[test]
+    fn test_missing_round_trips_leave_no_estimate() {
+        let mut latencies = regions([1, 1, 1]);
+        latencies.remove(&3);
+        latencies.get_mut(&1).unwrap().peer_rtt_us.remove(&3);
+
+        // Node 3 did not report, so only 1 and 2 count as clients; the round trip
+        // between 1 and 3 is unknown either way, leaving 3 without an estimate
+        let report = recommend_leader(1, &[1, 2, 3], &latencies, None);
+        let medians: Vec<_> = report
+            .candidates
+            .iter()
+            .map(|candidate| candidate.median_latency_us)
+            .collect();
+        assert_eq!(medians, vec![Some(10_000), Some(10_000), None]);
+        assert_eq!(report.recommended_leader, Some(1));
+        assert_eq!(report.improvement_us, None);
+        assert_eq!(report.transfer_target(Duration::ZERO), None);
+
+        let report = recommend_leader(1, &[1], &BTreeMap::new(), Some(1));
+        assert_eq!(report.candidates[0].commit_latency_us, Some(0));
+        assert_eq!(report.recommended_leader, None);
+    }
+
+    #[test]
+    fn test_peer_latencies_smooth_round_trips() {
+        let latencies = PeerLatencies::new();
+        assert_eq!(latencies.get(2), None);
+        latencies.record(2, Duration::from_millis(8));
+        assert_eq!(latencies.get(2), Some(Duration::from_millis(8)));
+
+        // A single slow round trip moves the estimate by an eighth of the difference
+        latencies.clone().record(2, Duration::from_millis(16));
+        assert_eq!(latencies.get(2), Some(Duration::from_millis(9)));
+        assert_eq!(latencies.snapshot(), BTreeMap::from([(2, 9_000)]));
+    }
+}
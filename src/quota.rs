@@ -0,0 +1,572 @@
+//! Request quotas per API key
+//!
+//! Callers that present an API key (`X-API-Key: <key>` or `Authorization: Bearer
+//! <key>`) are metered: every request they make counts one operation plus the bytes
+//! of its request and response bodies, by UTC day and by calendar month. With
+//! `[quotas]` enabled, a key that used up one of its limits gets `429 Too Many
+//! Requests` until the period rolls over. Requests without an API key are not
+//! metered.
+//!
+//! Keys are known by their ID, the first 16 hex digits of the SHA-256 of the key
+//! (see [`api_key_id`]), so neither the configuration nor the usage reports hold the
+//! keys themselves.
+//!
+//! Each node counts the requests it serves in a [`QuotaTracker`] and periodically
+//! flushes them as [`UsageDelta`]s through Raft (followers hand theirs to the
+//! leader), so the counters survive failover and every node enforces the usage of
+//! the whole cluster. Between flushes a node adds what it has not flushed yet, so a
+//! key can overrun its quota by at most what the other nodes served during one
+//! flush interval.
+
+use crate::types::UsageDelta;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+/// Milliseconds in a day
+const DAY_MS: u64 = 86_400_000;
+
+/// Hex digits of the SHA-256 of an API key that make up its ID
+pub const KEY_ID_LEN: usize = 16;
+
+/// Limits of an API key; unset limits are unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaLimits {
+    /// Requests per UTC day
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_ops: Option<u64>,
+    /// Requests per calendar month
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_ops: Option<u64>,
+    /// Request and response body bytes per UTC day
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_bytes: Option<u64>,
+    /// Request and response body bytes per calendar month
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_bytes: Option<u64>,
+}
+
+/// Quota configuration (`[quotas]`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaConfig {
+    /// Meter API keys and enforce their limits
+    #[serde(default)]
+    pub enabled: bool,
+    /// Milliseconds between two flushes of the usage counted by a node
+    #[serde(default = "default_flush_interval_ms")]
+    pub flush_interval_ms: u64,
+    /// Limits of keys not listed in `keys`
+    #[serde(default)]
+    pub default: QuotaLimits,
+    /// Limits by key ID
+    #[serde(default)]
+    pub keys: BTreeMap<String, QuotaLimits>,
+}
+
+fn default_flush_interval_ms() -> u64 {
+    1000
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            flush_interval_ms: default_flush_interval_ms(),
+            default: QuotaLimits::default(),
+            keys: BTreeMap::new(),
+        }
+    }
+}
+
+impl QuotaConfig {
+    /// Check the flush interval and key IDs
+    pub fn validate(&self) -> Result<(), String> {
+        if self.flush_interval_ms == 0 {
+            return Err("Quota flush_interval_ms must be greater than 0".to_string());
+        }
+        for key_id in self.keys.keys() {
+            let is_id = key_id.len() == KEY_ID_LEN
+                && key_id.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'));
+            if !is_id {
+                return Err(format!(
+                    "Quota key '{}' is not a key ID ({} lowercase hex digits of the SHA-256 of the API key)",
+                    key_id, KEY_ID_LEN
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Limits of the key `key_id`
+    pub fn limits_for(&self, key_id: &str) -> QuotaLimits {
+        self.keys.get(key_id).copied().unwrap_or(self.default)
+    }
+}
+
+/// ID of an API key: the first [`KEY_ID_LEN`] hex digits of its SHA-256
+pub fn api_key_id(api_key: &str) -> String {
+    let mut id = hex::encode(Sha256::digest(api_key.as_bytes()));
+    id.truncate(KEY_ID_LEN);
+    id
+}
+
+/// Requests and bytes counted in one period
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    pub ops: u64,
+    pub bytes: u64,
+}
+
+impl Usage {
+    fn add(&mut self, ops: u64, bytes: u64) {
+        self.ops = self.ops.saturating_add(ops);
+        self.bytes = self.bytes.saturating_add(bytes);
+    }
+}
+
+/// Usage of an API key in its latest day and month, as replicated through Raft
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyUsage {
+    /// ID of the API key
+    pub key_id: String,
+    /// Day `daily` covers, in days since the Unix epoch (UTC)
+    pub day: u64,
+    /// Usage on `day`
+    pub daily: Usage,
+    /// Month `monthly` covers, in months since January 1970
+    pub month: u64,
+    /// Usage in `month`
+    pub monthly: Usage,
+}
+
+impl KeyUsage {
+    /// No usage of `key_id` yet, as of `day`
+    pub fn new(key_id: impl Into<String>, day: u64) -> Self {
+        Self {
+            key_id: key_id.into(),
+            day,
+            month: month_of_day(day),
+            ..Default::default()
+        }
+    }
+
+    /// Add `delta`, starting a new day or month if it falls on a later one
+    ///
+    /// Usage of an earlier day, flushed late, still counts towards its month but no
+    /// longer towards a day that has ended.
+    pub fn apply(&mut self, delta: &UsageDelta) {
+        let month = month_of_day(delta.day);
+        if delta.day > self.day {
+            if month != self.month {
+                self.month = month;
+                self.monthly = Usage::default();
+            }
+            self.day = delta.day;
+            self.daily = Usage::default();
+        }
+        if delta.day == self.day {
+            self.daily.add(delta.ops, delta.bytes);
+        }
+        if month == self.month {
+            self.monthly.add(delta.ops, delta.bytes);
+        }
+    }
+
+    /// The usage as seen on `day`: counters of periods that ended before read zero
+    pub fn as_of(&self, day: u64) -> KeyUsage {
+        let mut usage = self.clone();
+        usage.apply(&UsageDelta {
+            key_id: self.key_id.clone(),
+            day,
+            ops: 0,
+            bytes: 0,
+        });
+        usage
+    }
+}
+
+/// A limit of [`QuotaLimits`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaLimit {
+    DailyOps,
+    MonthlyOps,
+    DailyBytes,
+    MonthlyBytes,
+}
+
+impl QuotaLimit {
+    /// Name of the limit in configuration and metrics
+    pub fn as_str(self) -> &'static str {
+        match self {
+            QuotaLimit::DailyOps => "daily_ops",
+            QuotaLimit::MonthlyOps => "monthly_ops",
+            QuotaLimit::DailyBytes => "daily_bytes",
+            QuotaLimit::MonthlyBytes => "monthly_bytes",
+        }
+    }
+}
+
+/// Why a request was refused
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QuotaExceeded {
+    /// ID of the API key
+    pub key_id: String,
+    /// Limit that was reached
+    pub limit: QuotaLimit,
+    /// Value of the limit
+    pub allowed: u64,
+    /// Usage in the limit's period
+    pub used: u64,
+    /// Seconds until the period rolls over
+    pub retry_after_secs: u64,
+}
+
+/// Usage and limits of an API key, served at `GET /admin/quotas/:key_id`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QuotaReport {
+    /// ID of the API key
+    pub key_id: String,
+    /// Current UTC day (`YYYY-MM-DD`)
+    pub date: String,
+    /// Current month (`YYYY-MM`)
+    pub month: String,
+    /// Usage today
+    pub daily: Usage,
+    /// Usage this month
+    pub monthly: Usage,
+    /// Limits of the key
+    pub limits: QuotaLimits,
+}
+
+/// Counts the requests this node serves per API key and enforces their quotas
+pub struct QuotaTracker {
+    config: QuotaConfig,
+    /// Usage not flushed yet, by key ID and day
+    pending: Mutex<HashMap<(String, u64), Usage>>,
+}
+
+impl QuotaTracker {
+    /// Create a tracker enforcing the limits of `config`
+    pub fn new(config: QuotaConfig) -> Self {
+        Self {
+            config,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Quota configuration
+    pub fn config(&self) -> &QuotaConfig {
+        &self.config
+    }
+
+    /// Count a request of `key_id` moving `bytes` body bytes at `now_ms`
+    pub fn record(&self, key_id: &str, bytes: u64, now_ms: u64) {
+        let mut pending = self.pending.lock().unwrap();
+        pending
+            .entry((key_id.to_string(), now_ms / DAY_MS))
+            .or_default()
+            .add(1, bytes);
+    }
+
+    /// Usage of `key_id` at `now_ms`: `recorded`, the replicated usage, plus what
+    /// this node has not flushed yet
+    pub fn usage(&self, key_id: &str, recorded: Option<&KeyUsage>, now_ms: u64) -> KeyUsage {
+        let today = now_ms / DAY_MS;
+        let mut usage = match recorded {
+            Some(recorded) => recorded.as_of(today),
+            None => KeyUsage::new(key_id, today),
+        };
+        let pending = self.pending.lock().unwrap();
+        for ((id, day), counted) in pending.iter() {
+            if id == key_id {
+                usage.apply(&UsageDelta {
+                    key_id: id.clone(),
+                    day: *day,
+                    ops: counted.ops,
+                    bytes: counted.bytes,
+                });
+            }
+        }
+        usage
+    }
+
+    /// Refuse a request of `key_id` at `now_ms` if the key used up one of its limits
+    pub fn check(
+        &self,
+        key_id: &str,
+        recorded: Option<&KeyUsage>,
+        now_ms: u64,
+    ) -> Result<(), QuotaExceeded> {
+        let limits = self.config.limits_for(key_id);
+        let usage = self.usage(key_id, recorded, now_ms);
+        let checks = [
+            (QuotaLimit::DailyOps, limits.daily_ops, usage.daily.ops),
+            (
+                QuotaLimit::DailyBytes,
+                limits.daily_bytes,
+                usage.daily.bytes,
+            ),
+            (
+                QuotaLimit::MonthlyOps,
+                limits.monthly_ops,
+                usage.monthly.ops,
+            ),
+            (
+                QuotaLimit::MonthlyBytes,
+                limits.monthly_bytes,
+                usage.monthly.bytes,
+            ),
+        ];
+        for (limit, allowed, used) in checks {
+            let Some(allowed) = allowed else {
+                continue;
+            };
+            if used >= allowed {
+                let resets_at = match limit {
+                    QuotaLimit::DailyOps | QuotaLimit::DailyBytes => (usage.day + 1) * DAY_MS,
+                    QuotaLimit::MonthlyOps | QuotaLimit::MonthlyBytes => {
+                        first_day_of_month(usage.month + 1) * DAY_MS
+                    }
+                };
+                return Err(QuotaExceeded {
+                    key_id: key_id.to_string(),
+                    limit,
+                    allowed,
+                    used,
+                    retry_after_secs: resets_at.saturating_sub(now_ms).div_ceil(1000),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Usage and limits of `key_id` at `now_ms`
+    pub fn report(&self, key_id: &str, recorded: Option<&KeyUsage>, now_ms: u64) -> QuotaReport {
+        let usage = self.usage(key_id, recorded, now_ms);
+        QuotaReport {
+            key_id: key_id.to_string(),
+            date: format_day(usage.day),
+            month: format_month(usage.month),
+            daily: usage.daily,
+            monthly: usage.monthly,
+            limits: self.config.limits_for(key_id),
+        }
+    }
+
+    /// Key IDs with usage this node has not flushed yet
+    pub fn pending_keys(&self) -> Vec<String> {
+        let pending = self.pending.lock().unwrap();
+        let mut keys: Vec<String> = pending.keys().map(|(key_id, _)| key_id.clone()).collect();
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+
+    /// Take the usage counted since the last flush
+    pub fn take_pending(&self) -> Vec<UsageDelta> {
+        let mut pending = self.pending.lock().unwrap();
+        pending
+            .drain()
+            .map(|((key_id, day), usage)| UsageDelta {
+                key_id,
+                day,
+                ops: usage.ops,
+                bytes: usage.bytes,
+            })
+            .collect()
+    }
+
+    /// Put back usage whose flush failed, to be flushed with the next batch
+    pub fn restore(&self, deltas: Vec<UsageDelta>) {
+        let mut pending = self.pending.lock().unwrap();
+        for delta in deltas {
+            pending
+                .entry((delta.key_id, delta.day))
+                .or_default()
+                .add(delta.ops, delta.bytes);
+        }
+    }
+}
+
+/// Month of `day`, in months since January 1970
+pub fn month_of_day(day: u64) -> u64 {
+    let (year, month, _) = civil_from_days(day);
+    (year - 1970) * 12 + u64::from(month) - 1
+}
+
+/// First day of `month` (months since January 1970), in days since the Unix epoch
+fn first_day_of_month(month: u64) -> u64 {
+    // Month lengths vary, so step from a day that is surely in the month before
+    let mut day = month * 365 / 12;
+    while month_of_day(day) < month {
+        day += 1;
+    }
+    while day > 0 && month_of_day(day - 1) == month {
+        day -= 1;
+    }
+    day
+}
+
+/// `YYYY-MM-DD` of `day`
+fn format_day(day: u64) -> String {
+    let (year, month, day) = civil_from_days(day);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// `YYYY-MM` of `month`
+fn format_month(month: u64) -> String {
+    format!("{:04}-{:02}", 1970 + month / 12, month % 12 + 1)
+}
+
+/// Year, month (1-12) and day of month (1-31) of `days` since the Unix epoch, in
+/// the proleptic Gregorian calendar
+fn civil_from_days(days: u64) -> (u64, u32, u32) {
+    // Shift the epoch to 0000-03-01 so leap days end the year
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2026-10-16, in days since the Unix epoch
+    const OCT_16: u64 = 20_742;
+
+    fn delta(day: u64, ops: u64, bytes: u64) -> UsageDelta {
+        UsageDelta {
+            key_id: "k".to_string(),
+            day,
+            ops,
+            bytes,
+        }
+    }
+
+    #[test]
+    fn test_calendar() {
+        assert_eq!(format_day(0), "1970-01-01");
+        assert_eq!(format_day(OCT_16), "2026-10-16");
+        assert_eq!(format_day(19_782), "2024-02-29");
+        assert_eq!(format_month(month_of_day(OCT_16)), "2026-10");
+        assert_eq!(
+            format_day(first_day_of_month(month_of_day(OCT_16) + 1)),
+            "2026-11-01"
+        );
+        assert_eq!(first_day_of_month(0), 0);
+        assert_eq!(
+            format_day(first_day_of_month(month_of_day(19_782))),
+            "2024-02-01"
+        );
+    }
+
+    #[test]
+    fn test_usage_rolls_over_by_day_and_month() {
+        let mut usage = KeyUsage::new("k", OCT_16);
+        usage.apply(&delta(OCT_16, 3, 100));
+        usage.apply(&delta(OCT_16 + 1, 2, 10));
+        assert_eq!(usage.daily, Usage { ops: 2, bytes: 10 });
+        assert_eq!(usage.monthly, Usage { ops: 5, bytes: 110 });
+
+        // Late usage of an ended day only counts towards its month
+        usage.apply(&delta(OCT_16, 1, 1));
+        assert_eq!(usage.daily.ops, 2);
+        assert_eq!(usage.monthly.ops, 6);
+
+        // November starts both counters over
+        let november = first_day_of_month(usage.month + 1);
+        assert_eq!(usage.as_of(november).monthly, Usage::default());
+        usage.apply(&delta(november, 1, 0));
+        assert_eq!(usage.day, november);
+        assert_eq!(usage.monthly.ops, 1);
+
+        // October usage flushed in November is dropped
+        usage.apply(&delta(OCT_16 + 2, 9, 9));
+        assert_eq!(usage.monthly.ops, 1);
+    }
+
+    #[test]
+    fn test_tracker_enforces_limits() {
+        let key_id = api_key_id("secret");
+        assert_eq!(key_id.len(), KEY_ID_LEN);
+        let config = QuotaConfig {
+            enabled: true,
+            default: QuotaLimits {
+                daily_ops: Some(3),
+                ..Default::default()
+            },
+            keys: BTreeMap::from([(
+                key_id.clone(),
+                QuotaLimits {
+                    daily_ops: Some(10),
+                    monthly_bytes: Some(1000),
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+        let tracker = QuotaTracker::new(config);
+        let now = OCT_16 * DAY_MS + 1000;
+
+        // Replicated and unflushed usage add up
+        let mut recorded = KeyUsage::new(key_id.clone(), OCT_16);
+        recorded.apply(&UsageDelta {
+            key_id: key_id.clone(),
+            day: OCT_16,
+            ops: 8,
+            bytes: 900,
+        });
+        assert!(tracker.check(&key_id, Some(&recorded), now).is_ok());
+        tracker.record(&key_id, 150, now);
+        let exceeded = tracker.check(&key_id, Some(&recorded), now).unwrap_err();
+        assert_eq!(exceeded.limit, QuotaLimit::MonthlyBytes);
+        assert_eq!(exceeded.used, 1050);
+        assert_eq!(
+            exceeded.retry_after_secs,
+            (first_day_of_month(recorded.month + 1) * DAY_MS - now) / 1000
+        );
+
+        // Other keys get the default limits
+        for _ in 0..3 {
+            assert!(tracker.check("0000000000000000", None, now).is_ok());
+            tracker.record("0000000000000000", 0, now);
+        }
+        let exceeded = tracker.check("0000000000000000", None, now).unwrap_err();
+        assert_eq!(exceeded.limit, QuotaLimit::DailyOps);
+        assert_eq!(exceeded.retry_after_secs, DAY_MS / 1000 - 1);
+
+        // A failed flush is retried with the next one
+        let deltas = tracker.take_pending();
+        assert_eq!(deltas.len(), 2);
+        assert!(tracker.check("0000000000000000", None, now).is_ok());
+        tracker.restore(deltas);
+        assert_eq!(tracker.pending_keys().len(), 2);
+        let report = tracker.report("0000000000000000", None, now);
+        assert_eq!(report.date, "2026-10-16");
+        assert_eq!(report.daily.ops, 3);
+    }
+
+    #[test]
+    fn test_config_rejects_raw_keys() {
+        let mut config = QuotaConfig::default();
+        config
+            .keys
+            .insert("my-api-key".to_string(), QuotaLimits::default());
+        assert!(config.validate().is_err());
+    }
+}
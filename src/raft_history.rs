@@ -0,0 +1,178 @@
+//! Persistent history of Raft metrics
+//!
+//! Each node samples its Raft metrics periodically and keeps the samples of the last
+//! retention window in a dedicated sled tree, served by `GET /raft/metrics/history`.
+//! After a crash or an outage of the metrics pipeline, the history shows how term,
+//! leadership and log progress evolved up to the incident. Like the admin event log,
+//! the history is local to the node and not replicated.
+
+use crate::error::{Result, ScribeError};
+use crate::types::NodeId;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Name of the sled tree holding the samples
+pub const RAFT_HISTORY_TREE: &str = "raft_metrics_history";
+
+/// Largest number of samples returned by a single query
+pub const MAX_SAMPLE_LIMIT: usize = 10_000;
+
+/// Raft metrics of a node at one point in time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RaftSample {
+    /// Time of the sample (milliseconds since the Unix epoch)
+    pub timestamp_ms: u64,
+    /// Current term
+    pub term: u64,
+    /// Leader known to the node, if any
+    pub leader: Option<NodeId>,
+    /// Raft role of the node (e.g. "Leader", "Follower")
+    pub state: String,
+    /// Index of the last log entry
+    pub last_log_index: Option<u64>,
+    /// Highest log index known to be committed, as last persisted
+    pub committed_index: Option<u64>,
+    /// Highest log index applied to the state machine
+    pub applied_index: Option<u64>,
+}
+
+/// Ring buffer of [`RaftSample`]s covering the last retention window
+#[derive(Clone)]
+pub struct RaftHistory {
+    tree: sled::Tree,
+    retention: Duration,
+}
+
+impl RaftHistory {
+    /// Open the history in `db`, keeping samples for `retention`
+    pub fn open(db: &sled::Db, retention: Duration) -> Result<Self> {
+        Ok(Self {
+            tree: db.open_tree(RAFT_HISTORY_TREE)?,
+            retention,
+        })
+    }
+
+    /// Store `sample` and drop samples older than the retention window before it
+    pub fn record(&self, sample: &RaftSample) -> Result<()> {
+        self.tree.insert(
+            sample.timestamp_ms.to_be_bytes(),
+            serde_json::to_vec(sample)?,
+        )?;
+
+        let cutoff = sample
+            .timestamp_ms
+            .saturating_sub(self.retention.as_millis() as u64);
+        for entry in self.tree.range(..cutoff.to_be_bytes()) {
+            let (key, _) = entry?;
+            self.tree.remove(key)?;
+        }
+        Ok(())
+    }
+
+    /// Samples taken at or after `since_ms`, oldest first, at most `limit` of them
+    /// (capped at [`MAX_SAMPLE_LIMIT`])
+    pub fn since(&self, since_ms: u64, limit: usize) -> Result<Vec<RaftSample>> {
+        self.tree
+            .range(since_ms.to_be_bytes()..)
+            .take(limit.min(MAX_SAMPLE_LIMIT))
+            .map(|entry| {
+                let (_, value) = entry?;
+                serde_json::from_slice(&value)
+                    .map_err(|e| ScribeError::Storage(format!("Corrupt Raft sample: {}", e)))
+            })
+            .collect()
+    }
+
+    /// Number of stored samples
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    /// Check if no samples are stored
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+}
+
+/// Parse the `since` parameter of a history query into milliseconds since the Unix
+/// epoch
+///
+/// Accepts an absolute time in milliseconds since the epoch, or a duration before
+/// `now_ms` with an `s`, `m`, `h` or `d` unit (`90s`, `15m`, `6h`, `1d`).
+pub fn parse_since(since: &str, now_ms: u64) -> Result<u64> {
+    let since = since.trim();
+    let invalid = || {
+        ScribeError::Other(format!(
+            "Invalid since {:?} (use milliseconds since the epoch or e.g. 15m, 6h)",
+            since
+        ))
+    };
+    let (number, unit) = match since.find(|c: char| !c.is_ascii_digit()) {
+        Some(split) => since.split_at(split),
+        None => return since.parse().map_err(|_| invalid()),
+    };
+    let number: u64 = number.parse().map_err(|_| invalid())?;
+    let unit_ms: u64 = match unit {
+        "s" => 1_000,
+        "m" => 60_000,
+        "h" => 3_600_000,
+        "d" => 86_400_000,
+        _ => return Err(invalid()),
+    };
+    Ok(now_ms.saturating_sub(number.saturating_mul(unit_ms)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(timestamp_ms: u64, term: u64) -> RaftSample {
+        RaftSample {
+            timestamp_ms,
+            term,
+            leader: Some(1),
+            state: "Leader".to_string(),
+            last_log_index: Some(term * 10),
+            committed_index: Some(term * 10),
+            applied_index: Some(term * 10 - 1),
+        }
+    }
+
+    #[test]
+    fn test_record_prunes_and_queries_in_order() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let history = RaftHistory::open(&db, Duration::from_secs(60)).unwrap();
+        assert!(history.is_empty());
+
+        for (i, timestamp_ms) in [1_000, 30_000, 61_000, 62_000].into_iter().enumerate() {
+            history.record(&sample(timestamp_ms, i as u64 + 1)).unwrap();
+        }
+        // The first sample fell out of the 60 second window
+        assert_eq!(history.len(), 3);
+
+        let samples = history.since(0, 100).unwrap();
+        let times: Vec<u64> = samples.iter().map(|s| s.timestamp_ms).collect();
+        assert_eq!(times, vec![30_000, 61_000, 62_000]);
+        assert_eq!(samples[0], sample(30_000, 2));
+
+        assert_eq!(history.since(61_000, 100).unwrap().len(), 2);
+        assert_eq!(history.since(0, 1).unwrap()[0].timestamp_ms, 30_000);
+        assert!(history.since(70_000, 100).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_since() {
+        let now = 10_000_000;
+        assert_eq!(
+            parse_since("1760620000000", now).unwrap(),
+            1_760_620_000_000
+        );
+        assert_eq!(parse_since("90s", now).unwrap(), now - 90_000);
+        assert_eq!(parse_since("15m", now).unwrap(), now - 900_000);
+        assert_eq!(parse_since("2h", now).unwrap(), now - 7_200_000);
+        assert_eq!(parse_since("1d", now).unwrap(), 0);
+        assert!(parse_since("15x", now).is_err());
+        assert!(parse_since("m", now).is_err());
+        assert!(parse_since("", now).is_err());
+    }
+}
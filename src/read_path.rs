@@ -0,0 +1,197 @@
+//! Read path tier ordering
+//!
+//! A read walks the storage tiers in a configured order until one answers:
+//!
+//! - `cache`: the hot data cache (stale reads only)
+//! - `local`: the state machine, which holds the value or the archived segment it
+//!   was demoted to, and answers "not found" authoritatively
+//! - `segments`: segments held on this node, either flushed locally or already
+//!   fetched into the segment cache
+//! - `s3`: the archived segment in S3
+//!
+//! The order is set per namespace (key prefix, the longest match wins) with a
+//! default for all other keys. A namespace whose reads must never block on object
+//! storage leaves out `s3`: a demoted value that no local tier holds is then
+//! reported as not found instead of being fetched. The time spent in each tier is
+//! recorded in `scribe_ledger_read_tier_latency_seconds`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A storage tier of the read path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadTier {
+    /// Hot data cache
+    Cache,
+    /// State machine backed by local sled storage
+    Local,
+    /// Segments held on this node
+    Segments,
+    /// Archived segments in S3
+    S3,
+}
+
+impl ReadTier {
+    /// Name of the tier, as used in configuration and metric labels
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReadTier::Cache => "cache",
+            ReadTier::Local => "local",
+            ReadTier::Segments => "segments",
+            ReadTier::S3 => "s3",
+        }
+    }
+}
+
+/// Tier order consulted by reads
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadPathConfig {
+    /// Order for keys outside the configured namespaces
+    #[serde(default = "default_read_order")]
+    pub order: Vec<ReadTier>,
+    /// Order per namespace (key prefix); the longest matching namespace wins
+    #[serde(default)]
+    pub namespaces: BTreeMap<String, Vec<ReadTier>>,
+}
+
+fn default_read_order() -> Vec<ReadTier> {
+    vec![
+        ReadTier::Cache,
+        ReadTier::Local,
+        ReadTier::Segments,
+        ReadTier::S3,
+    ]
+}
+
+impl Default for ReadPathConfig {
+    fn default() -> Self {
+        Self {
+            order: default_read_order(),
+            namespaces: BTreeMap::new(),
+        }
+    }
+}
+
+impl ReadPathConfig {
+    /// Tier order for `key`
+    pub fn order_for(&self, key: &[u8]) -> &[ReadTier] {
+        self.namespaces
+            .iter()
+            .filter(|(namespace, _)| key.starts_with(namespace.as_bytes()))
+            .max_by_key(|(namespace, _)| namespace.len())
+            .map_or(&self.order, |(_, order)| order)
+    }
+
+    /// Validate the read path configuration
+    ///
+    /// Every order must list `local`, which locates the value; `cache` may only come
+    /// before it and `segments` and `s3` only after it.
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        validate_order("default", &self.order)?;
+        for (namespace, order) in &self.namespaces {
+            if namespace.is_empty() {
+                return Err("Read path namespaces must not be empty".to_string());
+            }
+            validate_order(namespace, order)?;
+        }
+        Ok(())
+    }
+}
+
+fn validate_order(name: &str, order: &[ReadTier]) -> std::result::Result<(), String> {
+    let position = |tier: ReadTier| order.iter().position(|t| *t == tier);
+    let Some(local) = position(ReadTier::Local) else {
+        return Err(format!("Read order of '{}' must include local", name));
+    };
+    for (i, tier) in order.iter().enumerate() {
+        if order[..i].contains(tier) {
+            return Err(format!(
+                "Read order of '{}' lists {} twice",
+                name,
+                tier.as_str()
+            ));
+        }
+    }
+    if position(ReadTier::Cache).is_some_and(|cache| cache > local) {
+        return Err(format!(
+            "Read order of '{}' must consult cache before local",
+            name
+        ));
+    }
+    for tier in [ReadTier::Segments, ReadTier::S3] {
+        if position(tier).is_some_and(|i| i < local) {
+            return Err(format!(
+                "Read order of '{}' must consult {} after local",
+                name,
+                tier.as_str()
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_order_for_longest_namespace() {
+        let config: ReadPathConfig = toml::from_str(
+            r#"
+            [namespaces]
+            "quotes/" = ["cache", "local", "segments"]
+            "quotes/eod/" = ["local", "s3"]
+            "#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+
+        assert_eq!(
+            config.order_for(b"users/1"),
+            default_read_order().as_slice()
+        );
+        assert_eq!(
+            config.order_for(b"quotes/AAPL"),
+            &[ReadTier::Cache, ReadTier::Local, ReadTier::Segments]
+        );
+        assert_eq!(
+            config.order_for(b"quotes/eod/AAPL"),
+            &[ReadTier::Local, ReadTier::S3]
+        );
+    }
+
+    #[test]
+    fn test_validate_order() {
+        let with_order = |order: Vec<ReadTier>| ReadPathConfig {
+            order,
+            namespaces: BTreeMap::new(),
+        };
+        assert!(with_order(vec![ReadTier::Local]).validate().is_ok());
+        assert!(
+            with_order(vec![ReadTier::Local, ReadTier::S3, ReadTier::Segments])
+                .validate()
+                .is_ok()
+        );
+        assert!(with_order(vec![ReadTier::Cache, ReadTier::S3])
+            .validate()
+            .is_err());
+        assert!(with_order(vec![ReadTier::Local, ReadTier::Cache])
+            .validate()
+            .is_err());
+        assert!(with_order(vec![ReadTier::S3, ReadTier::Local])
+            .validate()
+            .is_err());
+        assert!(
+            with_order(vec![ReadTier::Local, ReadTier::S3, ReadTier::S3])
+                .validate()
+                .is_err()
+        );
+
+        let mut config = ReadPathConfig::default();
+        config
+            .namespaces
+            .insert(String::new(), default_read_order());
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,255 @@
+//! Conflict detection between actively replicated clusters
+//!
+//! When two clusters both accept writes and replicate to each other, a key written on
+//! both sides before replication catches up ends up with different values.
+//! [`ConflictDetector`] finds such keys with the sync digests (see [`crate::sync`]):
+//! only the buckets whose roots differ are listed key by key, and each differing key
+//! is reported with its digest, version and modification time on both clusters and
+//! a suggested resolution. Nothing is written to either cluster.
+//!
+//! Suggestions follow last-writer-wins on the time each cluster applied the key's
+//! last change. Versions are log indexes local to each cluster and are reported for
+//! reference only; they cannot be compared across clusters.
+
+use crate::error::Result;
+use crate::http_client::ClusterClient;
+use crate::sync::{differing_buckets, KeyDigest, DEFAULT_SYNC_BUCKETS, MAX_SYNC_BUCKETS};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// State of a key on one cluster
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyState {
+    /// SHA-256 (hex) of the value
+    pub digest: String,
+    /// Version of the key on that cluster
+    pub version: Option<u64>,
+    /// Time that cluster applied the key's last change (milliseconds since the Unix
+    /// epoch)
+    pub modified_at: Option<u64>,
+}
+
+impl From<&KeyDigest> for KeyState {
+    fn from(digest: &KeyDigest) -> Self {
+        Self {
+            digest: digest.digest.clone(),
+            version: digest.version,
+            modified_at: digest.modified_at,
+        }
+    }
+}
+
+/// How a key diverged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    /// Both clusters hold the key with different values
+    Diverged,
+    /// Only the left cluster holds the key
+    OnlyLeft,
+    /// Only the right cluster holds the key
+    OnlyRight,
+}
+
+/// Suggested resolution of a conflict
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    /// Copy the left cluster's value to the right cluster
+    KeepLeft,
+    /// Copy the right cluster's value to the left cluster
+    KeepRight,
+    /// Both changes were applied at the same time or without a recorded time
+    Review,
+}
+
+/// One key that differs between the clusters
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyConflict {
+    /// The key
+    pub key: String,
+    /// How the key diverged
+    pub kind: ConflictKind,
+    /// State on the left cluster, `None` if the key is missing there
+    pub left: Option<KeyState>,
+    /// State on the right cluster, `None` if the key is missing there
+    pub right: Option<KeyState>,
+    /// Suggested resolution
+    pub suggestion: Resolution,
+}
+
+/// Divergence report of two clusters
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConflictReport {
+    /// Number of buckets compared
+    pub buckets_compared: u32,
+    /// Buckets whose roots differed
+    pub buckets_differing: u32,
+    /// Keys held by both clusters with different values
+    pub diverged: usize,
+    /// Keys only the left cluster holds
+    pub only_left: usize,
+    /// Keys only the right cluster holds
+    pub only_right: usize,
+    /// Differing keys, ordered by key
+    pub conflicts: Vec<KeyConflict>,
+}
+
+/// Suggest a resolution by last-writer-wins on the modification times
+pub fn suggest(left: Option<&KeyState>, right: Option<&KeyState>) -> Resolution {
+    let modified_at = |state: Option<&KeyState>| state.and_then(|state| state.modified_at);
+    match (left, right) {
+        (Some(_), None) => Resolution::KeepLeft,
+        (None, Some(_)) => Resolution::KeepRight,
+        _ => match (modified_at(left), modified_at(right)) {
+            (Some(l), Some(r)) if l > r => Resolution::KeepLeft,
+            (Some(l), Some(r)) if r > l => Resolution::KeepRight,
+            _ => Resolution::Review,
+        },
+    }
+}
+
+/// Keys that differ between the key digests of one bucket on both clusters
+pub fn find_conflicts(left: &[KeyDigest], right: &[KeyDigest]) -> Vec<KeyConflict> {
+    let mut keys: BTreeMap<&str, (Option<KeyState>, Option<KeyState>)> = BTreeMap::new();
+    for digest in left {
+        keys.entry(&digest.key).or_default().0 = Some(digest.into());
+    }
+    for digest in right {
+        keys.entry(&digest.key).or_default().1 = Some(digest.into());
+    }
+
+    keys.into_iter()
+        .filter_map(|(key, (left, right))| {
+            let kind = match (&left, &right) {
+                (Some(l), Some(r)) if l.digest == r.digest => return None,
+                (Some(_), Some(_)) => ConflictKind::Diverged,
+                (Some(_), None) => ConflictKind::OnlyLeft,
+                _ => ConflictKind::OnlyRight,
+            };
+            Some(KeyConflict {
+                key: key.to_string(),
+                kind,
+                suggestion: suggest(left.as_ref(), right.as_ref()),
+                left,
+                right,
+            })
+        })
+        .collect()
+}
+
+/// Compares two actively replicated clusters key by key
+pub struct ConflictDetector {
+    left: ClusterClient,
+    right: ClusterClient,
+    buckets: u32,
+}
+
+impl ConflictDetector {
+    /// Create a detector comparing the `left` and `right` clusters
+    pub fn new(left: ClusterClient, right: ClusterClient) -> Self {
+        Self {
+            left,
+            right,
+            buckets: DEFAULT_SYNC_BUCKETS,
+        }
+    }
+
+    /// Split keys into `buckets` buckets (more buckets narrow down differences further)
+    pub fn with_buckets(mut self, buckets: u32) -> Self {
+        self.buckets = buckets.clamp(1, MAX_SYNC_BUCKETS);
+        self
+    }
+
+    /// Compare both clusters and report the keys that differ
+    pub async fn run(&self) -> Result<ConflictReport> {
+        let (left, right) = tokio::try_join!(
+            self.left.sync_buckets(self.buckets),
+            self.right.sync_buckets(self.buckets)
+        )?;
+        let differing = differing_buckets(&left, &right)?;
+
+        let mut report = ConflictReport {
+            buckets_compared: self.buckets,
+            buckets_differing: differing.len() as u32,
+            ..Default::default()
+        };
+        for bucket in differing {
+            let (left, right) = tokio::try_join!(
+                self.left.sync_bucket_keys(bucket, self.buckets),
+                self.right.sync_bucket_keys(bucket, self.buckets)
+            )?;
+            report.conflicts.extend(find_conflicts(&left, &right));
+        }
+
+        report.conflicts.sort_by(|a, b| a.key.cmp(&b.key));
+        for conflict in &report.conflicts {
+            match conflict.kind {
+                ConflictKind::Diverged => report.diverged += 1,
+                ConflictKind::OnlyLeft => report.only_left += 1,
+                ConflictKind::OnlyRight => report.only_right += 1,
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest(key: &str, digest: &str, modified_at: Option<u64>) -> KeyDigest {
+        KeyDigest {
+            key: key.to_string(),
+            digest: digest.to_string(),
+            version: Some(1),
+            modified_at,
+        }
+    }
+
+    #[test]
+    fn test_find_conflicts() {
+        let left = vec![
+            digest("newer-left", "a", Some(200)),
+            digest("newer-right", "a", Some(100)),
+            digest("only-left", "a", Some(100)),
+            digest("same", "a", Some(100)),
+            digest("tie", "a", Some(100)),
+        ];
+        let right = vec![
+            digest("newer-left", "b", Some(100)),
+            digest("newer-right", "b", Some(200)),
+            digest("only-right", "b", None),
+            digest("same", "a", Some(300)),
+            digest("tie", "b", Some(100)),
+        ];
+
+        let conflicts = find_conflicts(&left, &right);
+        let summary: Vec<(&str, ConflictKind, Resolution)> = conflicts
+            .iter()
+            .map(|c| (c.key.as_str(), c.kind, c.suggestion))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("newer-left", ConflictKind::Diverged, Resolution::KeepLeft),
+                ("newer-right", ConflictKind::Diverged, Resolution::KeepRight),
+                ("only-left", ConflictKind::OnlyLeft, Resolution::KeepLeft),
+                ("only-right", ConflictKind::OnlyRight, Resolution::KeepRight),
+                ("tie", ConflictKind::Diverged, Resolution::Review),
+            ]
+        );
+        assert_eq!(conflicts[0].right.as_ref().unwrap().modified_at, Some(100));
+    }
+
+    #[test]
+    fn test_suggest_without_times() {
+        let state = KeyState {
+            digest: "a".to_string(),
+            version: None,
+            modified_at: None,
+        };
+        assert_eq!(suggest(Some(&state), Some(&state)), Resolution::Review);
+        assert_eq!(suggest(Some(&state), None), Resolution::KeepLeft);
+    }
+}
@@ -0,0 +1,383 @@
+//! Retry policy of the HTTP clients
+//!
+//! A [`RetryPolicy`] decides which failed requests [`ClusterClient`] and
+//! [`ScribeClient`] retry on the next node, how often, and how long they pause in
+//! between. Failures are classified into [`RetryReason`]s; each reason can be turned
+//! on or off, and all of them are retried by default. Answers that carry a verdict
+//! on the request itself, such as `409 Conflict`, `412 Precondition Failed` or
+//! `404 Not Found`, have no reason and are never retried: sending the same request
+//! again would only get the same answer.
+//!
+//! Pauses double after every failed attempt, from the initial backoff up to the cap,
+//! and are drawn at random from their upper half (jitter) so clients that failed
+//! together do not retry in lockstep. A `Retry-After` longer than the pause is
+//! honoured, unless it exceeds the cap, in which case the request fails right away
+//! rather than stalling the caller.
+//!
+//! An observer set with [`RetryPolicy::on_retry`] sees every retry before its pause,
+//! for logging or metrics in the embedding application.
+//!
+//! [`ClusterClient`]: crate::http_client::ClusterClient
+//! [`ScribeClient`]: crate::client::ScribeClient
+
+use reqwest::StatusCode;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Default attempts of a request before giving up
+pub const DEFAULT_MAX_ATTEMPTS: usize = 8;
+
+/// Default pause before the first retry
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(50);
+
+/// Default longest pause between retries
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Why an attempt failed in a way that may succeed when retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RetryReason {
+    /// The node could not be reached
+    Unreachable,
+    /// The request timed out, or the node answered `504 Gateway Timeout`
+    Timeout,
+    /// The node knows no leader to redirect to, e.g. during an election (`421`)
+    NotLeader,
+    /// The node is overloaded or out of quota (`429`, `503`)
+    Backpressure,
+    /// Any other server error (`5xx`)
+    ServerError,
+}
+
+impl RetryReason {
+    /// Every reason
+    pub const ALL: [RetryReason; 5] = [
+        RetryReason::Unreachable,
+        RetryReason::Timeout,
+        RetryReason::NotLeader,
+        RetryReason::Backpressure,
+        RetryReason::ServerError,
+    ];
+
+    /// Reason of an answer with `status`; `None` if the answer is final
+    pub fn for_status(status: StatusCode) -> Option<Self> {
+        match status {
+            StatusCode::MISDIRECTED_REQUEST => Some(RetryReason::NotLeader),
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => {
+                Some(RetryReason::Backpressure)
+            }
+            StatusCode::GATEWAY_TIMEOUT => Some(RetryReason::Timeout),
+            status if status.is_server_error() => Some(RetryReason::ServerError),
+            _ => None,
+        }
+    }
+
+    /// Reason of a request that got no answer
+    pub fn for_error(error: &reqwest::Error) -> Self {
+        if error.is_timeout() {
+            RetryReason::Timeout
+        } else {
+            RetryReason::Unreachable
+        }
+    }
+
+    /// Name of the reason in logs and metrics
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RetryReason::Unreachable => "unreachable",
+            RetryReason::Timeout => "timeout",
+            RetryReason::NotLeader => "not_leader",
+            RetryReason::Backpressure => "backpressure",
+            RetryReason::ServerError => "server_error",
+        }
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+impl fmt::Display for RetryReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A retry about to happen, as passed to the observer of a [`RetryPolicy`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryEvent {
+    /// Number of the attempt that failed, from 1
+    pub attempt: usize,
+    /// Why it failed
+    pub reason: RetryReason,
+    /// URL of the failed attempt
+    pub url: String,
+    /// What went wrong
+    pub error: String,
+    /// Pause before the next attempt
+    pub delay: Duration,
+}
+
+/// Observer of retries
+type RetryObserver = Arc<dyn Fn(&RetryEvent) + Send + Sync>;
+
+/// When and how often a client retries a failed request
+///
+/// Cheap to clone; clones share the observer.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_attempts: usize,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    jitter: bool,
+    /// Bit set of the [`RetryReason`]s retried
+    retry_on: u8,
+    observer: Option<RetryObserver>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            jitter: true,
+            retry_on: RetryReason::ALL
+                .iter()
+                .fold(0, |bits, reason| bits | reason.bit()),
+            observer: None,
+        }
+    }
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let retry_on: Vec<RetryReason> = RetryReason::ALL
+            .into_iter()
+            .filter(|reason| self.retries(*reason))
+            .collect();
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("jitter", &self.jitter)
+            .field("retry_on", &retry_on)
+            .field("observer", &self.observer.is_some())
+            .finish()
+    }
+}
+
+impl RetryPolicy {
+    /// Retry every [`RetryReason`] up to 8 attempts, backing off from 50ms to 2s
+    /// with jitter
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Never retry: every request makes a single attempt
+    pub fn no_retries() -> Self {
+        Self::default().with_max_attempts(1)
+    }
+
+    /// Pause `delay` between attempts, without backing off or jitter
+    pub fn fixed(max_attempts: usize, delay: Duration) -> Self {
+        Self::default()
+            .with_max_attempts(max_attempts)
+            .with_backoff(delay, delay)
+            .with_jitter(false)
+    }
+
+    /// Attempts a request makes before failing, including the first one
+    pub fn with_max_attempts(mut self, attempts: usize) -> Self {
+        self.max_attempts = attempts;
+        self
+    }
+
+    /// Pause before the first retry, doubled after every further failure up to
+    /// `max`
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Draw each pause at random from its upper half (default: on)
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Retry only failures for these reasons
+    pub fn with_retry_on(mut self, reasons: impl IntoIterator<Item = RetryReason>) -> Self {
+        self.retry_on = reasons
+            .into_iter()
+            .fold(0, |bits, reason| bits | reason.bit());
+        self
+    }
+
+    /// Retry failures for `reason`, or stop retrying them
+    pub fn with_retry(mut self, reason: RetryReason, retry: bool) -> Self {
+        if retry {
+            self.retry_on |= reason.bit();
+        } else {
+            self.retry_on &= !reason.bit();
+        }
+        self
+    }
+
+    /// Call `observer` before every retry
+    pub fn on_retry<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(&RetryEvent) + Send + Sync + 'static,
+    {
+        self.observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// Attempts a request makes before failing
+    pub fn max_attempts(&self) -> usize {
+        self.max_attempts
+    }
+
+    /// Longest pause between attempts
+    pub fn max_backoff(&self) -> Duration {
+        self.max_backoff
+    }
+
+    /// Whether failures for `reason` are retried
+    pub fn retries(&self, reason: RetryReason) -> bool {
+        self.retry_on & reason.bit() != 0
+    }
+
+    /// Pause before retry number `attempt` (from 1)
+    pub fn backoff(&self, attempt: usize) -> Duration {
+        let doublings = attempt.saturating_sub(1).min(31) as u32;
+        let delay = self
+            .initial_backoff
+            .saturating_mul(1 << doublings)
+            .min(self.max_backoff);
+        if !self.jitter {
+            return delay;
+        }
+        delay / 2 + delay.mul_f64(fastrand::f64() / 2.0)
+    }
+
+    /// Pause before retry number `attempt` after a failure for `reason`, honouring
+    /// the server's `retry_after`; `None` if the failure must not be retried
+    pub fn delay(
+        &self,
+        attempt: usize,
+        reason: RetryReason,
+        retry_after: Option<Duration>,
+    ) -> Option<Duration> {
+        if !self.retries(reason) || attempt >= self.max_attempts {
+            return None;
+        }
+        let delay = self.backoff(attempt);
+        match retry_after {
+            Some(retry_after) if retry_after > self.max_backoff => None,
+            Some(retry_after) => Some(delay.max(retry_after)),
+            None => Some(delay),
+        }
+    }
+
+    /// Tell the observer, if any, about a retry
+    pub fn observe(&self, event: &RetryEvent) {
+        if let Some(observer) = &self.observer {
+            observer(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_classification() {
+        let cases = [
+            (StatusCode::OK, None),
+            (StatusCode::NOT_FOUND, None),
+            (StatusCode::CONFLICT, None),
+            (StatusCode::PRECONDITION_FAILED, None),
+            (
+                StatusCode::MISDIRECTED_REQUEST,
+                Some(RetryReason::NotLeader),
+            ),
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                Some(RetryReason::Backpressure),
+            ),
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Some(RetryReason::Backpressure),
+            ),
+            (StatusCode::GATEWAY_TIMEOUT, Some(RetryReason::Timeout)),
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Some(RetryReason::ServerError),
+            ),
+        ];
+        for (status, reason) in cases {
+            assert_eq!(RetryReason::for_status(status), reason, "{}", status);
+        }
+    }
+
+    #[test]
+    fn test_backoff_and_reasons() {
+        let fixed = RetryPolicy::fixed(3, Duration::from_millis(100));
+        assert_eq!(fixed.backoff(1), Duration::from_millis(100));
+        assert_eq!(fixed.backoff(5), Duration::from_millis(100));
+
+        let policy = RetryPolicy::new()
+            .with_max_attempts(4)
+            .with_backoff(Duration::from_millis(100), Duration::from_secs(1))
+            .with_retry(RetryReason::Backpressure, false);
+        for (attempt, ceiling) in [(1, 100), (2, 200), (3, 400), (4, 800), (60, 1000)] {
+            let pause = policy.backoff(attempt);
+            let ceiling = Duration::from_millis(ceiling);
+            assert!(pause >= ceiling / 2 && pause <= ceiling, "{:?}", pause);
+        }
+
+        assert!(policy.delay(1, RetryReason::NotLeader, None).is_some());
+        assert!(policy.delay(1, RetryReason::Backpressure, None).is_none());
+        // The last attempt is not retried
+        assert!(policy.delay(4, RetryReason::Timeout, None).is_none());
+        assert!(RetryPolicy::no_retries()
+            .delay(1, RetryReason::Unreachable, None)
+            .is_none());
+
+        // Retry-After is honoured up to the cap
+        let policy = policy.with_retry_on([RetryReason::Backpressure]);
+        assert!(!policy.retries(RetryReason::ServerError));
+        let delay = policy.delay(
+            1,
+            RetryReason::Backpressure,
+            Some(Duration::from_millis(700)),
+        );
+        assert_eq!(delay, Some(Duration::from_millis(700)));
+        let delay = policy.delay(1, RetryReason::Backpressure, Some(Duration::from_secs(60)));
+        assert_eq!(delay, None);
+    }
+
+    #[test]
+    fn test_observer() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let policy = RetryPolicy::new().on_retry({
+            let seen = seen.clone();
+            move |event| seen.lock().unwrap().push(event.reason)
+        });
+        let event = RetryEvent {
+            attempt: 1,
+            reason: RetryReason::Timeout,
+            url: "http://node1:8001/k".to_string(),
+            error: "timed out".to_string(),
+            delay: Duration::from_millis(50),
+        };
+        policy.clone().observe(&event);
+        RetryPolicy::new().observe(&event);
+        assert_eq!(*seen.lock().unwrap(), vec![RetryReason::Timeout]);
+        assert!(format!("{:?}", policy).contains("observer: true"));
+    }
+}
@@ -0,0 +1,183 @@
+//! Tokio runtime sizing and CPU pinning
+//!
+//! The binaries build their multi-threaded runtime from a [`RuntimeConfig`] (the
+//! `[runtime]` section of the node configuration, or `SCRIBE_*` environment
+//! variables) rather than a fixed worker count. On large machines, more workers let
+//! the HTTP layer use the idle cores; pinning the runtime threads to a set of cores
+//! keeps them on one NUMA node, away from cores reserved for other processes.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Highest CPU index threads can be pinned to, plus one
+pub const MAX_CPUS: usize = 1024;
+
+/// Runtime sizing and pinning options
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuntimeConfig {
+    /// Async worker threads (default: one per CPU core)
+    #[serde(default)]
+    pub worker_threads: Option<usize>,
+    /// Upper limit of the blocking thread pool (default: tokio's 512)
+    #[serde(default)]
+    pub max_blocking_threads: Option<usize>,
+    /// CPU cores runtime threads are pinned to, one core per thread in turn
+    /// (default: no pinning)
+    #[serde(default)]
+    pub pin_cores: Vec<usize>,
+}
+
+impl RuntimeConfig {
+    /// Override settings from `SCRIBE_WORKER_THREADS`, `SCRIBE_MAX_BLOCKING_THREADS`
+    /// and `SCRIBE_PIN_CORES` (a core list such as `0-7,16`)
+    pub fn apply_env_overrides(&mut self) {
+        if let Ok(threads) = std::env::var("SCRIBE_WORKER_THREADS") {
+            if let Ok(parsed_threads) = threads.parse() {
+                self.worker_threads = Some(parsed_threads);
+            }
+        }
+        if let Ok(threads) = std::env::var("SCRIBE_MAX_BLOCKING_THREADS") {
+            if let Ok(parsed_threads) = threads.parse() {
+                self.max_blocking_threads = Some(parsed_threads);
+            }
+        }
+        if let Ok(cores) = std::env::var("SCRIBE_PIN_CORES") {
+            if let Ok(parsed_cores) = parse_core_list(&cores) {
+                self.pin_cores = parsed_cores;
+            }
+        }
+    }
+
+    /// Check thread counts and core indices
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.worker_threads == Some(0) || self.max_blocking_threads == Some(0) {
+            return Err("Runtime thread counts must be greater than 0".to_string());
+        }
+        if let Some(core) = self.pin_cores.iter().find(|core| **core >= MAX_CPUS) {
+            return Err(format!("Cannot pin to CPU {} (max {})", core, MAX_CPUS - 1));
+        }
+        Ok(())
+    }
+
+    /// Build a multi-threaded runtime with these settings
+    ///
+    /// With `pin_cores`, every runtime thread, workers first, is pinned to the next
+    /// core of the list. A thread that cannot be pinned (e.g. outside Linux) logs a
+    /// warning and runs unpinned.
+    pub fn build(&self) -> std::io::Result<tokio::runtime::Runtime> {
+        let mut builder = tokio::runtime::Builder::new_multi_thread();
+        builder.enable_all();
+        if let Some(threads) = self.worker_threads {
+            builder.worker_threads(threads);
+        }
+        if let Some(threads) = self.max_blocking_threads {
+            builder.max_blocking_threads(threads);
+        }
+        if !self.pin_cores.is_empty() {
+            let cores: Arc<[usize]> = self.pin_cores.clone().into();
+            let started = AtomicUsize::new(0);
+            builder.on_thread_start(move || {
+                let core = cores[started.fetch_add(1, Ordering::Relaxed) % cores.len()];
+                if let Err(e) = pin_current_thread(core) {
+                    tracing::warn!("Failed to pin runtime thread to CPU {}: {}", core, e);
+                }
+            });
+        }
+        builder.build()
+    }
+}
+
+/// Parse a core list such as `0-7,16,18-19`
+pub fn parse_core_list(list: &str) -> std::result::Result<Vec<usize>, String> {
+    let invalid = || format!("Invalid core list {:?} (use e.g. 0-7,16)", list);
+    let mut cores = Vec::new();
+    for part in list
+        .split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+    {
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (first.trim(), last.trim()),
+            None => (part, part),
+        };
+        let first: usize = first.parse().map_err(|_| invalid())?;
+        let last: usize = last.parse().map_err(|_| invalid())?;
+        if first > last {
+            return Err(invalid());
+        }
+        cores.extend(first..=last);
+    }
+    Ok(cores)
+}
+
+/// Restrict the calling thread to CPU `core`
+#[cfg(target_os = "linux")]
+fn pin_current_thread(core: usize) -> std::io::Result<()> {
+    if core >= MAX_CPUS {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("CPU {} is out of range", core),
+        ));
+    }
+    // SAFETY: `set` is a plain bit mask, zeroed before the core's bit is set, and
+    // outlives the call reading it
+    let result = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    match result {
+        0 => Ok(()),
+        _ => Err(std::io::Error::last_os_error()),
+    }
+}
+
+/// Restrict the calling thread to CPU `core`
+#[cfg(not(target_os = "linux"))]
+fn pin_current_thread(_core: usize) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "CPU pinning is only supported on Linux",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_core_list() {
+        assert_eq!(parse_core_list("0-3,8").unwrap(), vec![0, 1, 2, 3, 8]);
+        assert_eq!(parse_core_list(" 5 , 6-7 ").unwrap(), vec![5, 6, 7]);
+        assert!(parse_core_list("").unwrap().is_empty());
+        assert!(parse_core_list("3-1").is_err());
+        assert!(parse_core_list("a-b").is_err());
+    }
+
+    #[test]
+    fn test_build_sized_and_pinned_runtime() {
+        let config = RuntimeConfig {
+            worker_threads: Some(2),
+            max_blocking_threads: Some(4),
+            pin_cores: vec![0],
+        };
+        assert!(config.validate().is_ok());
+
+        let runtime = config.build().unwrap();
+        assert_eq!(runtime.metrics().num_workers(), 2);
+        let answer = runtime.block_on(async { tokio::spawn(async { 42 }).await.unwrap() });
+        assert_eq!(answer, 42);
+
+        let invalid = RuntimeConfig {
+            worker_threads: Some(0),
+            ..RuntimeConfig::default()
+        };
+        assert!(invalid.validate().is_err());
+        let invalid = RuntimeConfig {
+            pin_cores: vec![MAX_CPUS],
+            ..RuntimeConfig::default()
+        };
+        assert!(invalid.validate().is_err());
+    }
+}
@@ -0,0 +1,222 @@
+//! Runtime introspection for debugging stuck nodes
+//!
+//! [`RuntimeReport::collect`] gathers tokio scheduler metrics, process memory and file
+//! descriptor usage, and build information into one report, served as JSON by
+//! `GET /debug/runtime`. Process figures come from `/proc` and are absent on
+//! platforms without it.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+/// Build information compiled into the binary
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildInfo {
+    /// Crate version
+    pub version: String,
+    /// Git commit the binary was built from
+    pub git_sha: String,
+    /// Version of the compiler that built the binary
+    pub rustc_version: String,
+    /// Cargo profile (`debug` or `release`)
+    pub profile: String,
+}
+
+impl BuildInfo {
+    /// Build information of this binary
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_sha: option_env!("SCRIBE_GIT_SHA")
+                .unwrap_or("unknown")
+                .to_string(),
+            rustc_version: option_env!("SCRIBE_RUSTC_VERSION")
+                .unwrap_or("unknown")
+                .to_string(),
+            profile: if cfg!(debug_assertions) {
+                "debug"
+            } else {
+                "release"
+            }
+            .to_string(),
+        }
+    }
+}
+
+/// Scheduler metrics of the tokio runtime
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokioStats {
+    /// Number of worker threads
+    pub workers: usize,
+    /// Tasks currently alive
+    pub alive_tasks: usize,
+    /// Tasks waiting in the global (injection) queue
+    pub global_queue_depth: usize,
+    /// Time each worker has spent busy, in milliseconds
+    pub worker_busy_ms: Vec<u64>,
+    /// Times each worker has parked; a worker whose count stops growing while busy
+    /// time keeps rising is stuck in a task
+    pub worker_park_count: Vec<u64>,
+}
+
+impl TokioStats {
+    /// Metrics of the runtime this is called from, `None` outside a runtime
+    pub fn collect() -> Option<Self> {
+        let metrics = tokio::runtime::Handle::try_current().ok()?.metrics();
+        let workers = metrics.num_workers();
+        Some(Self {
+            workers,
+            alive_tasks: metrics.num_alive_tasks(),
+            global_queue_depth: metrics.global_queue_depth(),
+            worker_busy_ms: (0..workers)
+                .map(|worker| metrics.worker_total_busy_duration(worker).as_millis() as u64)
+                .collect(),
+            worker_park_count: (0..workers)
+                .map(|worker| metrics.worker_park_count(worker))
+                .collect(),
+        })
+    }
+}
+
+/// Memory use of the process
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryStats {
+    /// Global allocator the binary was built with
+    pub allocator: String,
+    /// Resident set size in bytes
+    pub resident_bytes: Option<u64>,
+    /// Peak resident set size in bytes
+    pub peak_resident_bytes: Option<u64>,
+    /// Virtual memory size in bytes
+    pub virtual_bytes: Option<u64>,
+    /// Number of OS threads
+    pub threads: Option<u64>,
+}
+
+impl MemoryStats {
+    /// Memory use of this process
+    pub fn collect() -> Self {
+        let status = fs::read_to_string("/proc/self/status").unwrap_or_default();
+        Self::from_proc_status(&status)
+    }
+
+    /// Parse the contents of `/proc/<pid>/status`
+    pub fn from_proc_status(status: &str) -> Self {
+        let field = |name: &str| {
+            status.lines().find_map(|line| {
+                let value = line.strip_prefix(name)?.strip_prefix(':')?.trim();
+                match value.strip_suffix(" kB") {
+                    Some(kb) => kb.trim().parse::<u64>().ok().map(|kb| kb * 1024),
+                    None => value.parse().ok(),
+                }
+            })
+        };
+        Self {
+            allocator: "system".to_string(),
+            resident_bytes: field("VmRSS"),
+            peak_resident_bytes: field("VmHWM"),
+            virtual_bytes: field("VmSize"),
+            threads: field("Threads"),
+        }
+    }
+}
+
+/// File descriptor use of the process
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStats {
+    /// Open file descriptors, including sockets
+    pub open: Option<usize>,
+    /// Soft limit on open file descriptors
+    pub soft_limit: Option<u64>,
+}
+
+impl FileStats {
+    /// File descriptor use of this process
+    pub fn collect() -> Self {
+        let open = fs::read_dir("/proc/self/fd")
+            .ok()
+            .map(|entries| entries.count());
+        let limits = fs::read_to_string("/proc/self/limits").unwrap_or_default();
+        Self {
+            open,
+            soft_limit: parse_open_files_limit(&limits),
+        }
+    }
+}
+
+/// Soft limit from the `Max open files` row of `/proc/<pid>/limits`
+fn parse_open_files_limit(limits: &str) -> Option<u64> {
+    limits
+        .lines()
+        .find_map(|line| line.strip_prefix("Max open files"))
+        .and_then(|row| row.split_whitespace().next())
+        .and_then(|soft| soft.parse().ok())
+}
+
+/// Everything `GET /debug/runtime` reports
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuntimeReport {
+    /// Build information
+    pub build: BuildInfo,
+    /// Tokio scheduler metrics
+    pub tokio: Option<TokioStats>,
+    /// Process memory use
+    pub memory: MemoryStats,
+    /// Open file descriptors
+    pub files: FileStats,
+}
+
+impl RuntimeReport {
+    /// Collect a report for this process
+    pub fn collect() -> Self {
+        Self {
+            build: BuildInfo::current(),
+            tokio: TokioStats::collect(),
+            memory: MemoryStats::collect(),
+            files: FileStats::collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_files() {
+        let status = "Name:\tscribe-node\nVmSize:\t  204800 kB\nVmHWM:\t   10240 kB\n\
+                      VmRSS:\t    8192 kB\nThreads:\t12\n";
+        let memory = MemoryStats::from_proc_status(status);
+        assert_eq!(memory.resident_bytes, Some(8192 * 1024));
+        assert_eq!(memory.peak_resident_bytes, Some(10240 * 1024));
+        assert_eq!(memory.virtual_bytes, Some(204800 * 1024));
+        assert_eq!(memory.threads, Some(12));
+        assert_eq!(MemoryStats::from_proc_status("").resident_bytes, None);
+
+        let limits = "Limit                     Soft Limit           Hard Limit           Units\n\
+                      Max processes             63422                63422                processes\n\
+                      Max open files            1024                 524288               files\n";
+        assert_eq!(parse_open_files_limit(limits), Some(1024));
+        assert_eq!(parse_open_files_limit(""), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_collect_report() {
+        let report = RuntimeReport::collect();
+        assert_eq!(report.build.version, env!("CARGO_PKG_VERSION"));
+
+        let tokio = report.tokio.unwrap();
+        assert_eq!(tokio.workers, 2);
+        assert_eq!(tokio.worker_busy_ms.len(), 2);
+        assert_eq!(tokio.worker_park_count.len(), 2);
+
+        if cfg!(target_os = "linux") {
+            assert!(report.memory.resident_bytes.unwrap() > 0);
+            assert!(report.files.open.unwrap() > 0);
+        }
+        assert!(TokioStats::collect().is_some());
+        assert!(std::thread::spawn(TokioStats::collect)
+            .join()
+            .unwrap()
+            .is_none());
+    }
+}
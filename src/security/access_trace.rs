@@ -0,0 +1,401 @@
+//! Key-level access tracing for regulated namespaces
+//!
+//! For namespaces listed in `security.access_trace.namespaces`, every read and write
+//! through the client API is recorded as an [`AccessRecord`]: who (the principal
+//! established by the auth layer), what (operation and a SHA-256 hash of the key, so
+//! the trace does not leak key contents) and when. Records go to a dedicated sled tree
+//! that is only ever appended to and pruned by age and count, and are emitted as
+//! audit events for the log pipeline. Keys outside the listed namespaces cost a
+//! prefix check and nothing else.
+
+use crate::error::{Result, ScribeError};
+use crate::logging::{audit_log, AuditEvent};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Name of the sled tree holding the trace
+pub const ACCESS_TRACE_TREE: &str = "access_trace";
+
+/// Default number of records returned by a query
+pub const DEFAULT_RECORD_LIMIT: usize = 100;
+
+/// Largest number of records returned by a single query
+pub const MAX_RECORD_LIMIT: usize = 10_000;
+
+/// Records appended between checks of the count limit
+const COUNT_CHECK_INTERVAL: u64 = 1024;
+
+/// Access tracing configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessTraceConfig {
+    /// Namespaces (key prefixes) whose accesses are traced; tracing is off if empty
+    #[serde(default)]
+    pub namespaces: Vec<String>,
+    /// Seconds a record is kept
+    #[serde(default = "default_retention_secs")]
+    pub retention_secs: u64,
+    /// Most records kept; the oldest are dropped beyond this
+    #[serde(default = "default_max_records")]
+    pub max_records: usize,
+}
+
+fn default_retention_secs() -> u64 {
+    90 * 24 * 60 * 60
+}
+
+fn default_max_records() -> usize {
+    10_000_000
+}
+
+impl Default for AccessTraceConfig {
+    fn default() -> Self {
+        Self {
+            namespaces: Vec::new(),
+            retention_secs: default_retention_secs(),
+            max_records: default_max_records(),
+        }
+    }
+}
+
+impl AccessTraceConfig {
+    /// Whether any namespace is traced
+    pub fn enabled(&self) -> bool {
+        !self.namespaces.is_empty()
+    }
+
+    /// Validate the access trace configuration
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if !self.enabled() {
+            return Ok(());
+        }
+        if self.namespaces.iter().any(String::is_empty) {
+            return Err("Traced namespaces must not be empty".to_string());
+        }
+        if self.retention_secs == 0 || self.max_records == 0 {
+            return Err("Access trace retention and max_records must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Kind of access
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessOp {
+    /// The value was read
+    Read,
+    /// The value was written or modified
+    Write,
+    /// The key was deleted
+    Delete,
+}
+
+impl AccessOp {
+    /// Name of the operation
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccessOp::Read => "read",
+            AccessOp::Write => "write",
+            AccessOp::Delete => "delete",
+        }
+    }
+
+    fn audit_event(&self) -> AuditEvent {
+        match self {
+            AccessOp::Read => AuditEvent::DataRead,
+            AccessOp::Write => AuditEvent::DataWrite,
+            AccessOp::Delete => AuditEvent::DataDelete,
+        }
+    }
+}
+
+/// A traced access to a key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessRecord {
+    /// Time of the access (milliseconds since the Unix epoch)
+    pub timestamp_ms: u64,
+    /// Identity of the caller, e.g. `cert:<fingerprint>`
+    pub principal: String,
+    /// Operation
+    pub op: AccessOp,
+    /// Traced namespace the key belongs to
+    pub namespace: String,
+    /// SHA-256 of the key, in hex
+    pub key_hash: String,
+    /// HTTP status the request was answered with
+    pub status: u16,
+}
+
+/// Filter for querying the trace
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessTraceFilter {
+    /// Only accesses by this principal
+    pub principal: Option<String>,
+    /// Only accesses to this key (hashed before matching)
+    pub key: Option<String>,
+    /// Only accesses at or after this time (milliseconds since the Unix epoch)
+    pub since_ms: Option<u64>,
+    /// Only accesses before this time (milliseconds since the Unix epoch)
+    pub until_ms: Option<u64>,
+    /// Maximum number of records to return (defaults to [`DEFAULT_RECORD_LIMIT`])
+    pub limit: Option<usize>,
+}
+
+/// Append-only trace of accesses to keys in the traced namespaces
+#[derive(Clone)]
+pub struct AccessTrace {
+    db: sled::Db,
+    tree: sled::Tree,
+    config: Arc<AccessTraceConfig>,
+    appended: Arc<AtomicU64>,
+}
+
+impl AccessTrace {
+    /// Open the trace in `db`
+    pub fn open(db: &sled::Db, config: AccessTraceConfig) -> Result<Self> {
+        Ok(Self {
+            db: db.clone(),
+            tree: db.open_tree(ACCESS_TRACE_TREE)?,
+            config: Arc::new(config),
+            appended: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    /// Traced namespace `key` belongs to, if any
+    pub fn namespace_of(&self, key: &[u8]) -> Option<&str> {
+        self.config
+            .namespaces
+            .iter()
+            .find(|namespace| key.starts_with(namespace.as_bytes()))
+            .map(String::as_str)
+    }
+
+    /// Record an access to `key` by `principal`, if the key is in a traced namespace
+    ///
+    /// The record is also emitted as an audit event. Returns the record, or `None`
+    /// for untraced keys.
+    pub fn record(
+        &self,
+        principal: &str,
+        op: AccessOp,
+        key: &[u8],
+        status: u16,
+    ) -> Result<Option<AccessRecord>> {
+        let Some(namespace) = self.namespace_of(key) else {
+            return Ok(None);
+        };
+        let record = AccessRecord {
+            timestamp_ms: now_ms(),
+            principal: principal.to_string(),
+            op,
+            namespace: namespace.to_string(),
+            key_hash: key_hash(key),
+            status,
+        };
+
+        // Time-ordered keys, unique through the ID suffix
+        let mut trace_key = record.timestamp_ms.to_be_bytes().to_vec();
+        trace_key.extend_from_slice(&self.db.generate_id()?.to_be_bytes());
+        self.tree.insert(trace_key, serde_json::to_vec(&record)?)?;
+
+        audit_log(
+            op.audit_event(),
+            Some(&record.principal),
+            op.as_str(),
+            Some(&record.namespace),
+            &record.status.to_string(),
+            Some(&record.key_hash),
+        );
+
+        self.prune(record.timestamp_ms)?;
+        Ok(Some(record))
+    }
+
+    /// Drop records past the retention period, and the oldest records beyond the
+    /// count limit
+    fn prune(&self, now_ms: u64) -> Result<()> {
+        let retention = Duration::from_secs(self.config.retention_secs).as_millis() as u64;
+        let cutoff = now_ms.saturating_sub(retention);
+        for entry in self.tree.range(..cutoff.to_be_bytes()) {
+            let (key, _) = entry?;
+            self.tree.remove(key)?;
+        }
+
+        // Counting the tree is linear, so only check now and then
+        if self
+            .appended
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(COUNT_CHECK_INTERVAL)
+        {
+            let excess = self.tree.len().saturating_sub(self.config.max_records);
+            for _ in 0..excess {
+                self.tree.pop_min()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Query records matching `filter`, newest first
+    pub fn query(&self, filter: &AccessTraceFilter) -> Result<Vec<AccessRecord>> {
+        let limit = filter
+            .limit
+            .unwrap_or(DEFAULT_RECORD_LIMIT)
+            .min(MAX_RECORD_LIMIT);
+        let key_hash = filter.key.as_deref().map(|key| key_hash(key.as_bytes()));
+        let since = filter.since_ms.unwrap_or(0).to_be_bytes();
+        let until = filter.until_ms.unwrap_or(u64::MAX).to_be_bytes();
+
+        let mut records = Vec::new();
+        for entry in self.tree.range(since..until).rev() {
+            if records.len() >= limit {
+                break;
+            }
+            let (_, value) = entry?;
+            let record: AccessRecord = serde_json::from_slice(&value)
+                .map_err(|e| ScribeError::Storage(format!("Corrupt access record: {}", e)))?;
+            let matches = filter
+                .principal
+                .as_deref()
+                .is_none_or(|principal| principal == record.principal)
+                && key_hash
+                    .as_deref()
+                    .is_none_or(|hash| hash == record.key_hash);
+            if matches {
+                records.push(record);
+            }
+        }
+        Ok(records)
+    }
+
+    /// Number of stored records
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    /// Check if no records are stored
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+}
+
+/// SHA-256 of a key in hex, as stored in [`AccessRecord::key_hash`]
+pub fn key_hash(key: &[u8]) -> String {
+    hex::encode(Sha256::digest(key))
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trace(config: AccessTraceConfig) -> AccessTrace {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        AccessTrace::open(&db, config).unwrap()
+    }
+
+    fn traced(namespaces: &[&str]) -> AccessTraceConfig {
+        AccessTraceConfig {
+            namespaces: namespaces.iter().map(|ns| ns.to_string()).collect(),
+            ..AccessTraceConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_records_only_traced_namespaces() {
+        let trace = trace(traced(&["bank/", "health/"]));
+        let record = trace
+            .record("cert:ab12", AccessOp::Read, b"bank/acct-1", 200)
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.namespace, "bank/");
+        assert_eq!(record.key_hash, key_hash(b"bank/acct-1"));
+        assert!(!record.key_hash.contains("acct"));
+
+        assert!(trace
+            .record("cert:ab12", AccessOp::Write, b"public/page", 200)
+            .unwrap()
+            .is_none());
+        trace
+            .record("anonymous", AccessOp::Delete, b"health/p-7", 204)
+            .unwrap();
+        assert_eq!(trace.len(), 2);
+
+        let records = trace.query(&AccessTraceFilter::default()).unwrap();
+        assert_eq!(records[0].op, AccessOp::Delete);
+        assert_eq!(records[1].op, AccessOp::Read);
+    }
+
+    #[test]
+    fn test_query_filters() {
+        let trace = trace(traced(&["bank/"]));
+        for (principal, key) in [("alice", "bank/1"), ("bob", "bank/1"), ("alice", "bank/2")] {
+            trace
+                .record(principal, AccessOp::Read, key.as_bytes(), 200)
+                .unwrap();
+        }
+
+        let filter = AccessTraceFilter {
+            principal: Some("alice".to_string()),
+            ..AccessTraceFilter::default()
+        };
+        assert_eq!(trace.query(&filter).unwrap().len(), 2);
+
+        let filter = AccessTraceFilter {
+            key: Some("bank/1".to_string()),
+            limit: Some(1),
+            ..AccessTraceFilter::default()
+        };
+        let records = trace.query(&filter).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].principal, "bob");
+
+        let filter = AccessTraceFilter {
+            since_ms: Some(now_ms() + 60_000),
+            ..AccessTraceFilter::default()
+        };
+        assert!(trace.query(&filter).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_count_limit() {
+        let trace = trace(AccessTraceConfig {
+            max_records: 2,
+            ..traced(&["bank/"])
+        });
+        // The count is checked on the first append after every interval
+        for i in 0..=COUNT_CHECK_INTERVAL {
+            let key = format!("bank/{}", i);
+            trace
+                .record("alice", AccessOp::Write, key.as_bytes(), 200)
+                .unwrap();
+        }
+        assert_eq!(trace.len(), 2);
+        let newest = &trace.query(&AccessTraceFilter::default()).unwrap()[0];
+        assert_eq!(
+            newest.key_hash,
+            key_hash(format!("bank/{}", COUNT_CHECK_INTERVAL).as_bytes())
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(AccessTraceConfig::default().validate().is_ok());
+        assert!(!AccessTraceConfig::default().enabled());
+        assert!(traced(&["bank/"]).validate().is_ok());
+        assert!(traced(&[""]).validate().is_err());
+        let config = AccessTraceConfig {
+            max_records: 0,
+            ..traced(&["bank/"])
+        };
+        assert!(config.validate().is_err());
+    }
+}
@@ -71,6 +71,16 @@ impl Role {
         Self::new("admin", permissions)
     }
 
+    /// Look up one of the built-in roles by name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "read_only" => Some(Self::read_only()),
+            "read_write" => Some(Self::read_write()),
+            "admin" => Some(Self::admin()),
+            _ => None,
+        }
+    }
+
     /// Check if role has a specific permission
     pub fn has_permission(&self, permission: Permission) -> bool {
         self.permissions.contains(&permission)
@@ -139,7 +149,7 @@ impl AuthMiddleware {
     }
 
     /// Extract API key from request headers
-    fn extract_api_key(headers: &HeaderMap) -> Option<String> {
+    pub fn extract_api_key(headers: &HeaderMap) -> Option<String> {
         // Support both Authorization: Bearer <token> and X-API-Key: <key>
         if let Some(auth_header) = headers.get("authorization") {
             if let Ok(auth_str) = auth_header.to_str() {
@@ -161,7 +171,10 @@ impl AuthMiddleware {
     /// Determine required permission for a request
     pub fn required_permission(method: &str, path: &str) -> Permission {
         // Admin endpoints
-        if path.starts_with("/cluster/") || path.starts_with("/metrics") {
+        if path.starts_with("/cluster/")
+            || path.starts_with("/admin/")
+            || path.starts_with("/metrics")
+        {
             return Permission::Admin;
         }
 
@@ -217,6 +230,19 @@ impl AuthMiddleware {
         }
 
         let role = role.unwrap();
+        if let Err(required_perm) = Self::authorize_role(role, method, path) {
+            return Err(Self::forbidden_response(required_perm));
+        }
+
+        debug!(
+            "Authentication successful: Role '{}' granted access to {} {}",
+            role.name, method, path
+        );
+        Ok(())
+    }
+
+    /// Check that a role may perform a request, returning the missing permission otherwise
+    pub fn authorize_role(role: &Role, method: &str, path: &str) -> Result<(), Permission> {
         let required_perm = Self::required_permission(method, path);
 
         // Check if role has required permission
@@ -225,21 +251,22 @@ impl AuthMiddleware {
                 "Authorization failed: Role '{}' lacks {:?} permission for {} {}",
                 role.name, required_perm, method, path
             );
-            return Err((
-                StatusCode::FORBIDDEN,
-                Json(serde_json::json!({
-                    "error": format!("Insufficient permissions. Required: {:?}", required_perm)
-                })),
-            )
-                .into_response());
+            return Err(required_perm);
         }
 
-        debug!(
-            "Authentication successful: Role '{}' granted access to {} {}",
-            role.name, method, path
-        );
         Ok(())
     }
+
+    /// Build the 403 response for a missing permission
+    pub fn forbidden_response(required_perm: Permission) -> Response {
+        (
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({
+                "error": format!("Insufficient permissions. Required: {:?}", required_perm)
+            })),
+        )
+            .into_response()
+    }
 }
 
 /// Axum middleware function for authentication
@@ -298,6 +325,14 @@ mod tests {
         assert!(role.has_permission(Permission::Admin));
     }
 
+    #[test]
+    fn test_role_from_name() {
+        assert_eq!(Role::from_name("read_only"), Some(Role::read_only()));
+        assert_eq!(Role::from_name("read_write"), Some(Role::read_write()));
+        assert_eq!(Role::from_name("admin"), Some(Role::admin()));
+        assert!(Role::from_name("unknown").is_none());
+    }
+
     #[test]
     fn test_auth_config_default() {
         let config = AuthConfig::default();
@@ -371,6 +406,10 @@ mod tests {
             AuthMiddleware::required_permission("GET", "/cluster/info"),
             Permission::Admin
         );
+        assert_eq!(
+            AuthMiddleware::required_permission("GET", "/admin/events"),
+            Permission::Admin
+        );
     }
 
     #[tokio::test]
@@ -0,0 +1,474 @@
+//! Per-tenant encryption keys for archived segments
+//!
+//! In multi-tenant mode every tenant owns a namespace (key prefix) and a random
+//! AES-256-GCM data key. Data keys are only stored wrapped (encrypted) by the cluster
+//! master key, and each archived segment records the IDs of the keys it was sealed
+//! with. Destroying a tenant's data key makes everything sealed with it unreadable
+//! (crypto-shredding), so offboarding a tenant does not require rewriting history.
+
+use crate::error::{Result, ScribeError};
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Length of the master key and of tenant data keys in bytes
+pub const KEY_LEN: usize = 32;
+
+/// Length of AES-GCM nonces in bytes
+const NONCE_LEN: usize = 12;
+
+/// Sled key prefix of wrapped data keys, followed by the key ID
+const WRAPPED_KEY_PREFIX: &str = "key/";
+
+/// Sled key prefix of a tenant's active key ID, followed by the tenant name
+const TENANT_PREFIX: &str = "tenant/";
+
+/// Segment encryption configuration
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct EncryptionConfig {
+    /// Encrypt archived segments with per-tenant data keys
+    #[serde(default)]
+    pub enabled: bool,
+    /// Cluster master key wrapping the data keys (64 hex characters)
+    #[serde(default)]
+    pub master_key: Option<String>,
+    /// Tenant names mapped to the namespace (key prefix) they own
+    #[serde(default)]
+    pub tenants: HashMap<String, String>,
+}
+
+impl EncryptionConfig {
+    /// Validate the encryption configuration
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        let master_key = self
+            .master_key
+            .as_deref()
+            .ok_or("A master key is required when segment encryption is enabled")?;
+        MasterKey::from_hex(master_key)?;
+
+        let mut namespaces = HashSet::new();
+        for (tenant, namespace) in &self.tenants {
+            if tenant.is_empty() || namespace.is_empty() {
+                return Err("Tenant names and namespaces must not be empty".to_string());
+            }
+            if !namespaces.insert(namespace) {
+                return Err(format!(
+                    "Namespace '{}' is assigned to more than one tenant",
+                    namespace
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Cluster master key used to wrap tenant data keys
+#[derive(Clone)]
+pub struct MasterKey(Aes256Gcm);
+
+impl MasterKey {
+    /// Create a master key from raw key bytes
+    pub fn from_bytes(bytes: &[u8]) -> std::result::Result<Self, String> {
+        if bytes.len() != KEY_LEN {
+            return Err(format!("Master key must be {} bytes", KEY_LEN));
+        }
+        Aes256Gcm::new_from_slice(bytes)
+            .map(Self)
+            .map_err(|e| format!("Invalid master key: {}", e))
+    }
+
+    /// Create a master key from its hex encoding
+    pub fn from_hex(hex_key: &str) -> std::result::Result<Self, String> {
+        let bytes = hex::decode(hex_key.trim())
+            .map_err(|e| format!("Master key is not valid hex: {}", e))?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Generate a random master key
+    pub fn generate() -> Self {
+        Self(Aes256Gcm::new(&Aes256Gcm::generate_key(OsRng)))
+    }
+}
+
+impl fmt::Debug for MasterKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MasterKey(..)")
+    }
+}
+
+/// A tenant data key encrypted by the master key, as persisted
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WrappedKey {
+    /// Key ID recorded in segment metadata and the manifest
+    pub key_id: String,
+    /// Tenant owning the key
+    pub tenant: String,
+    /// Creation time (seconds since the Unix epoch)
+    pub created_at: u64,
+    /// Nonce used to wrap the key
+    nonce: Vec<u8>,
+    /// The data key encrypted by the master key
+    ciphertext: Vec<u8>,
+}
+
+/// Data sealed with a tenant data key
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SealedData {
+    /// ID of the data key used
+    pub key_id: String,
+    /// AES-GCM nonce
+    pub nonce: Vec<u8>,
+    /// Ciphertext including the authentication tag
+    pub ciphertext: Vec<u8>,
+}
+
+/// Tenant namespaces and their wrapped data keys
+///
+/// Wrapped keys are persisted in a sled tree. Namespaces are registered at startup
+/// from the configuration; registering a tenant without a key creates a new one, so
+/// an offboarded tenant must also be removed from the configuration.
+pub struct TenantKeyring {
+    master_key: MasterKey,
+    tree: sled::Tree,
+    namespaces: RwLock<BTreeMap<Vec<u8>, String>>,
+}
+
+impl TenantKeyring {
+    /// Open a keyring persisted in `tree`
+    pub fn open(tree: sled::Tree, master_key: MasterKey) -> Self {
+        Self {
+            master_key,
+            tree,
+            namespaces: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Open a keyring and register the tenants of an enabled configuration
+    pub fn from_config(config: &EncryptionConfig, tree: sled::Tree) -> Result<Self> {
+        config.validate().map_err(ScribeError::Configuration)?;
+        let master_key = config
+            .master_key
+            .as_deref()
+            .ok_or_else(|| ScribeError::Configuration("Missing master key".to_string()))?;
+        let keyring = Self::open(
+            tree,
+            MasterKey::from_hex(master_key).map_err(ScribeError::Configuration)?,
+        );
+
+        for (tenant, namespace) in &config.tenants {
+            keyring.register_tenant(tenant, namespace.as_bytes())?;
+        }
+        Ok(keyring)
+    }
+
+    /// Assign `namespace` to `tenant`, creating the tenant's data key if needed
+    ///
+    /// Returns the ID of the tenant's data key.
+    pub fn register_tenant(&self, tenant: &str, namespace: &[u8]) -> Result<String> {
+        let key_id = match self.active_key_id(tenant)? {
+            Some(key_id) => key_id,
+            None => self.create_data_key(tenant)?,
+        };
+
+        self.namespaces
+            .write()
+            .map_err(|e| ScribeError::Other(format!("Failed to acquire write lock: {}", e)))?
+            .insert(namespace.to_vec(), tenant.to_string());
+        Ok(key_id)
+    }
+
+    /// Find the tenant owning `key` using the longest matching namespace
+    pub fn tenant_for_key(&self, key: &[u8]) -> Option<String> {
+        let namespaces = self.namespaces.read().ok()?;
+        namespaces
+            .iter()
+            .filter(|(namespace, _)| key.starts_with(namespace))
+            .max_by_key(|(namespace, _)| namespace.len())
+            .map(|(_, tenant)| tenant.clone())
+    }
+
+    /// ID of the data key currently used for `tenant`
+    pub fn active_key_id(&self, tenant: &str) -> Result<Option<String>> {
+        Ok(self
+            .tree
+            .get(tenant_entry(tenant))?
+            .map(|id| String::from_utf8_lossy(&id).into_owned()))
+    }
+
+    /// Whether the data key with `key_id` still exists
+    pub fn has_key(&self, key_id: &str) -> Result<bool> {
+        Ok(self.tree.contains_key(wrapped_key_entry(key_id))?)
+    }
+
+    /// Encrypt `plaintext` with the tenant's data key
+    ///
+    /// `aad` is authenticated but not encrypted, and must be passed again to open.
+    pub fn seal(&self, tenant: &str, plaintext: &[u8], aad: &[u8]) -> Result<SealedData> {
+        let key_id = self.active_key_id(tenant)?.ok_or_else(|| {
+            ScribeError::Encryption(format!("Tenant '{}' has no data key", tenant))
+        })?;
+        let cipher = self
+            .data_key(&key_id)?
+            .ok_or_else(|| ScribeError::Encryption(format!("Data key {} is missing", key_id)))?;
+
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad,
+                },
+            )
+            .map_err(|e| ScribeError::Encryption(format!("Failed to encrypt: {}", e)))?;
+
+        Ok(SealedData {
+            key_id,
+            nonce: nonce.to_vec(),
+            ciphertext,
+        })
+    }
+
+    /// Decrypt data sealed with a tenant data key
+    ///
+    /// Returns `None` if the data key was destroyed (the data has been shredded), and
+    /// an error if the data was tampered with.
+    pub fn open_sealed(&self, sealed: &SealedData, aad: &[u8]) -> Result<Option<Vec<u8>>> {
+        let Some(cipher) = self.data_key(&sealed.key_id)? else {
+            return Ok(None);
+        };
+        if sealed.nonce.len() != NONCE_LEN {
+            return Err(ScribeError::Encryption("Invalid nonce length".to_string()));
+        }
+
+        cipher
+            .decrypt(
+                Nonce::from_slice(&sealed.nonce),
+                Payload {
+                    msg: &sealed.ciphertext,
+                    aad,
+                },
+            )
+            .map(Some)
+            .map_err(|_| {
+                ScribeError::Encryption(format!(
+                    "Failed to decrypt data sealed with key {}",
+                    sealed.key_id
+                ))
+            })
+    }
+
+    /// Destroy the tenant's data key, making all data sealed with it unreadable
+    ///
+    /// Returns the ID of the destroyed key. The tenant's namespace stays registered,
+    /// so new data for it cannot be sealed until the tenant is registered again.
+    pub fn destroy_tenant_key(&self, tenant: &str) -> Result<Option<String>> {
+        let Some(key_id) = self.active_key_id(tenant)? else {
+            return Ok(None);
+        };
+
+        self.tree.remove(wrapped_key_entry(&key_id))?;
+        self.tree.remove(tenant_entry(tenant))?;
+        self.tree.flush()?;
+        Ok(Some(key_id))
+    }
+
+    /// Generate, wrap and persist a new data key for `tenant`
+    fn create_data_key(&self, tenant: &str) -> Result<String> {
+        let key_id = format!("{}-{:016x}", tenant, fastrand::u64(..));
+        let data_key = Aes256Gcm::generate_key(OsRng);
+
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .master_key
+            .0
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: data_key.as_slice(),
+                    aad: key_id.as_bytes(),
+                },
+            )
+            .map_err(|e| ScribeError::Encryption(format!("Failed to wrap data key: {}", e)))?;
+
+        let wrapped = WrappedKey {
+            key_id: key_id.clone(),
+            tenant: tenant.to_string(),
+            created_at: current_timestamp(),
+            nonce: nonce.to_vec(),
+            ciphertext,
+        };
+        self.tree
+            .insert(wrapped_key_entry(&key_id), bincode::serialize(&wrapped)?)?;
+        self.tree.insert(tenant_entry(tenant), key_id.as_bytes())?;
+        self.tree.flush()?;
+        Ok(key_id)
+    }
+
+    /// Unwrap the data key with `key_id`, `None` if it was destroyed
+    fn data_key(&self, key_id: &str) -> Result<Option<Aes256Gcm>> {
+        let Some(bytes) = self.tree.get(wrapped_key_entry(key_id))? else {
+            return Ok(None);
+        };
+        let wrapped: WrappedKey = bincode::deserialize(&bytes)?;
+        if wrapped.nonce.len() != NONCE_LEN {
+            return Err(ScribeError::Encryption("Invalid nonce length".to_string()));
+        }
+
+        let key = self
+            .master_key
+            .0
+            .decrypt(
+                Nonce::from_slice(&wrapped.nonce),
+                Payload {
+                    msg: &wrapped.ciphertext,
+                    aad: wrapped.key_id.as_bytes(),
+                },
+            )
+            .map_err(|_| {
+                ScribeError::Encryption(format!(
+                    "Failed to unwrap data key {} (wrong master key?)",
+                    key_id
+                ))
+            })?;
+        Aes256Gcm::new_from_slice(&key)
+            .map(Some)
+            .map_err(|e| ScribeError::Encryption(format!("Invalid data key: {}", e)))
+    }
+}
+
+fn wrapped_key_entry(key_id: &str) -> String {
+    format!("{}{}", WRAPPED_KEY_PREFIX, key_id)
+}
+
+fn tenant_entry(tenant: &str) -> String {
+    format!("{}{}", TENANT_PREFIX, tenant)
+}
+
+/// Get current Unix timestamp
+fn current_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyring() -> TenantKeyring {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        TenantKeyring::open(db.open_tree("keys").unwrap(), MasterKey::generate())
+    }
+
+    #[test]
+    fn test_master_key_parsing() {
+        assert!(MasterKey::from_hex(&"ab".repeat(KEY_LEN)).is_ok());
+        assert!(MasterKey::from_hex("abcd").is_err());
+        assert!(MasterKey::from_hex(&"zz".repeat(KEY_LEN)).is_err());
+        assert_eq!(format!("{:?}", MasterKey::generate()), "MasterKey(..)");
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let mut config = EncryptionConfig::default();
+        assert!(config.validate().is_ok());
+
+        config.enabled = true;
+        assert!(config.validate().is_err());
+
+        config.master_key = Some("00".repeat(KEY_LEN));
+        config
+            .tenants
+            .insert("acme".to_string(), "acme/".to_string());
+        assert!(config.validate().is_ok());
+
+        config
+            .tenants
+            .insert("other".to_string(), "acme/".to_string());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        let keyring = keyring();
+        let key_id = keyring.register_tenant("acme", b"acme/").unwrap();
+        assert_eq!(keyring.active_key_id("acme").unwrap(), Some(key_id.clone()));
+        assert_eq!(keyring.register_tenant("acme", b"acme/").unwrap(), key_id);
+
+        let sealed = keyring.seal("acme", b"secret", b"segment-1").unwrap();
+        assert_eq!(sealed.key_id, key_id);
+        assert_ne!(sealed.ciphertext, b"secret");
+        assert_eq!(
+            keyring.open_sealed(&sealed, b"segment-1").unwrap(),
+            Some(b"secret".to_vec())
+        );
+
+        // Ciphertext is bound to its associated data
+        assert!(keyring.open_sealed(&sealed, b"segment-2").is_err());
+        assert!(keyring.seal("unknown", b"secret", b"").is_err());
+    }
+
+    #[test]
+    fn test_tenant_for_key_longest_prefix() {
+        let keyring = keyring();
+        keyring.register_tenant("acme", b"acme/").unwrap();
+        keyring.register_tenant("acme-eu", b"acme/eu/").unwrap();
+
+        assert_eq!(keyring.tenant_for_key(b"acme/x"), Some("acme".to_string()));
+        assert_eq!(
+            keyring.tenant_for_key(b"acme/eu/x"),
+            Some("acme-eu".to_string())
+        );
+        assert_eq!(keyring.tenant_for_key(b"other"), None);
+    }
+
+    #[test]
+    fn test_destroyed_key_shreds_data() {
+        let keyring = keyring();
+        let key_id = keyring.register_tenant("acme", b"acme/").unwrap();
+        keyring.register_tenant("globex", b"globex/").unwrap();
+        let acme = keyring.seal("acme", b"acme data", b"").unwrap();
+        let globex = keyring.seal("globex", b"globex data", b"").unwrap();
+
+        assert_eq!(
+            keyring.destroy_tenant_key("acme").unwrap(),
+            Some(key_id.clone())
+        );
+        assert!(!keyring.has_key(&key_id).unwrap());
+        assert_eq!(keyring.open_sealed(&acme, b"").unwrap(), None);
+        assert_eq!(
+            keyring.open_sealed(&globex, b"").unwrap(),
+            Some(b"globex data".to_vec())
+        );
+        assert!(keyring.seal("acme", b"more", b"").is_err());
+        assert_eq!(keyring.destroy_tenant_key("acme").unwrap(), None);
+
+        // Re-registering creates a new key; old data stays unreadable
+        let new_key_id = keyring.register_tenant("acme", b"acme/").unwrap();
+        assert_ne!(new_key_id, key_id);
+        assert_eq!(keyring.open_sealed(&acme, b"").unwrap(), None);
+    }
+
+    #[test]
+    fn test_wrong_master_key_cannot_unwrap() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let tree = db.open_tree("keys").unwrap();
+        let keyring = TenantKeyring::open(tree.clone(), MasterKey::generate());
+        keyring.register_tenant("acme", b"acme/").unwrap();
+        let sealed = keyring.seal("acme", b"data", b"").unwrap();
+
+        let reopened = TenantKeyring::open(tree, MasterKey::generate());
+        assert!(reopened.open_sealed(&sealed, b"").is_err());
+    }
+}
@@ -0,0 +1,330 @@
+//! One-time join tokens for admitting new nodes
+//!
+//! Tokens are generated on the leader and presented by a new node during discovery
+//! and when asking the leader to add it as a learner. A token embeds the cluster ID,
+//! a random token ID and an expiry time, authenticated with an HMAC-SHA256 keyed by
+//! the cluster secret:
+//!
+//! ```text
+//! scribe1.<cluster_id>.<token_id>.<expires_at>.<hmac>
+//! ```
+//!
+//! Any node holding the cluster secret can verify a token; only the leader consumes
+//! tokens, so each one admits a single node. Consumed token IDs are kept in memory
+//! until they expire, so a token may be reused after a leader change until its expiry.
+
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Token format version prefix
+const TOKEN_VERSION: &str = "scribe1";
+
+/// SHA-256 block size used by HMAC
+const HMAC_BLOCK_SIZE: usize = 64;
+
+/// Longest TTL accepted when issuing a token
+pub const MAX_JOIN_TOKEN_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Decoded contents of a valid join token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JoinToken {
+    /// Cluster the token was issued for
+    pub cluster_id: String,
+    /// Unique token ID
+    pub token_id: String,
+    /// Expiry time (seconds since the Unix epoch)
+    pub expires_at: u64,
+}
+
+/// Issues, verifies and consumes join tokens for one cluster
+pub struct JoinTokenManager {
+    cluster_id: String,
+    secret: Vec<u8>,
+    consumed: Mutex<HashMap<String, u64>>,
+}
+
+impl JoinTokenManager {
+    /// Create a manager for `cluster_id`, keyed by the cluster secret
+    pub fn new(cluster_id: impl Into<String>, secret: impl AsRef<[u8]>) -> Result<Self, String> {
+        let cluster_id = cluster_id.into();
+        validate_cluster_id(&cluster_id)?;
+        if secret.as_ref().is_empty() {
+            return Err("Join tokens require a non-empty cluster secret".to_string());
+        }
+
+        Ok(Self {
+            cluster_id,
+            secret: secret.as_ref().to_vec(),
+            consumed: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Get the cluster ID tokens are issued for
+    pub fn cluster_id(&self) -> &str {
+        &self.cluster_id
+    }
+
+    /// Check an operator credential against the cluster secret
+    pub fn matches_secret(&self, presented: &str) -> bool {
+        constant_time_eq(presented.as_bytes(), &self.secret)
+    }
+
+    /// Issue a token valid for `ttl`
+    pub fn issue(&self, ttl: Duration) -> Result<(String, JoinToken), String> {
+        if ttl.is_zero() || ttl > MAX_JOIN_TOKEN_TTL {
+            return Err(format!(
+                "Join token TTL must be between 1s and {}s",
+                MAX_JOIN_TOKEN_TTL.as_secs()
+            ));
+        }
+
+        let token = JoinToken {
+            cluster_id: self.cluster_id.clone(),
+            token_id: format!("{:016x}{:016x}", fastrand::u64(..), fastrand::u64(..)),
+            expires_at: now_secs() + ttl.as_secs(),
+        };
+        let mac = self.mac(&token);
+        let encoded = format!(
+            "{}.{}.{}.{}.{}",
+            TOKEN_VERSION, token.cluster_id, token.token_id, token.expires_at, mac
+        );
+
+        Ok((encoded, token))
+    }
+
+    /// Verify a token's format, cluster, HMAC and expiry without consuming it
+    pub fn verify(&self, encoded: &str) -> Result<JoinToken, String> {
+        let parts: Vec<&str> = encoded.trim().split('.').collect();
+        let [version, cluster_id, token_id, expires_at, mac] = parts[..] else {
+            return Err("Malformed join token".to_string());
+        };
+
+        if version != TOKEN_VERSION {
+            return Err(format!("Unsupported join token version '{}'", version));
+        }
+
+        let token = JoinToken {
+            cluster_id: cluster_id.to_string(),
+            token_id: token_id.to_string(),
+            expires_at: expires_at
+                .parse()
+                .map_err(|_| "Malformed join token expiry".to_string())?,
+        };
+
+        if !constant_time_eq(self.mac(&token).as_bytes(), mac.as_bytes()) {
+            return Err("Invalid join token signature".to_string());
+        }
+
+        if token.cluster_id != self.cluster_id {
+            return Err(format!(
+                "Join token was issued for cluster '{}'",
+                token.cluster_id
+            ));
+        }
+
+        if token.expires_at <= now_secs() {
+            return Err("Join token has expired".to_string());
+        }
+
+        Ok(token)
+    }
+
+    /// Verify a token and mark it used; a token can be consumed only once
+    pub fn consume(&self, encoded: &str) -> Result<JoinToken, String> {
+        let token = self.verify(encoded)?;
+
+        let mut consumed = self
+            .consumed
+            .lock()
+            .map_err(|e| format!("Lock error: {}", e))?;
+        let now = now_secs();
+        consumed.retain(|_, expires_at| *expires_at > now);
+
+        if consumed.contains_key(&token.token_id) {
+            return Err("Join token has already been used".to_string());
+        }
+        consumed.insert(token.token_id.clone(), token.expires_at);
+
+        Ok(token)
+    }
+
+    /// Hex HMAC-SHA256 over the token fields
+    fn mac(&self, token: &JoinToken) -> String {
+        let message = format!(
+            "{}.{}.{}.{}",
+            TOKEN_VERSION, token.cluster_id, token.token_id, token.expires_at
+        );
+        hex::encode(hmac_sha256(&self.secret, message.as_bytes()))
+    }
+}
+
+/// Parse a TTL such as "90s", "30m", "1h", "7d" or a plain number of seconds
+pub fn parse_ttl(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    let (digits, multiplier) = match input.char_indices().last() {
+        Some((i, 's')) => (&input[..i], 1),
+        Some((i, 'm')) => (&input[..i], 60),
+        Some((i, 'h')) => (&input[..i], 60 * 60),
+        Some((i, 'd')) => (&input[..i], 24 * 60 * 60),
+        _ => (input, 1),
+    };
+
+    let value: u64 = digits
+        .parse()
+        .map_err(|_| format!("Invalid TTL '{}' (expected e.g. 90s, 30m, 1h, 7d)", input))?;
+    value
+        .checked_mul(multiplier)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("TTL '{}' is too large", input))
+}
+
+/// Cluster IDs are embedded in tokens, so they must not contain the separator
+fn validate_cluster_id(cluster_id: &str) -> Result<(), String> {
+    if cluster_id.is_empty()
+        || !cluster_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!(
+            "Invalid cluster ID '{}' (use letters, digits, '-' and '_')",
+            cluster_id
+        ));
+    }
+    Ok(())
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut block = [0u8; HMAC_BLOCK_SIZE];
+    if key.len() > HMAC_BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha256::new();
+    inner.update(block.map(|b| b ^ 0x36));
+    inner.update(message);
+
+    let mut outer = Sha256::new();
+    outer.update(block.map(|b| b ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().to_vec()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager() -> JoinTokenManager {
+        JoinTokenManager::new("test-cluster", "secret").unwrap()
+    }
+
+    #[test]
+    fn test_hmac_sha256_rfc4231() {
+        // RFC 4231 test case 2
+        let mac = hmac_sha256(b"Jefe", b"what do ya want for nothing?");
+        assert_eq!(
+            hex::encode(mac),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_join_token_issue_and_verify() {
+        let manager = manager();
+        let (encoded, issued) = manager.issue(Duration::from_secs(3600)).unwrap();
+
+        assert!(encoded.starts_with("scribe1.test-cluster."));
+        assert!(manager.matches_secret("secret"));
+        assert!(!manager.matches_secret("wrong"));
+        assert_eq!(manager.verify(&encoded).unwrap(), issued);
+    }
+
+    #[test]
+    fn test_join_token_one_time() {
+        let manager = manager();
+        let (encoded, _) = manager.issue(Duration::from_secs(60)).unwrap();
+
+        assert!(manager.consume(&encoded).is_ok());
+        assert!(manager.consume(&encoded).is_err());
+        // Verification alone stays stateless
+        assert!(manager.verify(&encoded).is_ok());
+    }
+
+    #[test]
+    fn test_join_token_rejects_tampering() {
+        let manager = manager();
+        let (encoded, token) = manager.issue(Duration::from_secs(60)).unwrap();
+
+        let extended = encoded.replace(
+            &token.expires_at.to_string(),
+            &(token.expires_at + 3600).to_string(),
+        );
+        assert!(manager.verify(&extended).is_err());
+
+        let other_secret = JoinTokenManager::new("test-cluster", "other").unwrap();
+        assert!(other_secret.verify(&encoded).is_err());
+
+        let other_cluster = JoinTokenManager::new("other-cluster", "secret").unwrap();
+        assert!(other_cluster.verify(&encoded).is_err());
+
+        assert!(manager.verify("garbage").is_err());
+    }
+
+    #[test]
+    fn test_join_token_expired() {
+        let manager = manager();
+        let token = JoinToken {
+            cluster_id: "test-cluster".to_string(),
+            token_id: "abc".to_string(),
+            expires_at: now_secs() - 1,
+        };
+        let encoded = format!(
+            "scribe1.test-cluster.abc.{}.{}",
+            token.expires_at,
+            manager.mac(&token)
+        );
+
+        assert_eq!(
+            manager.verify(&encoded).unwrap_err(),
+            "Join token has expired"
+        );
+    }
+
+    #[test]
+    fn test_join_token_manager_validation() {
+        assert!(JoinTokenManager::new("bad.cluster", "secret").is_err());
+        assert!(JoinTokenManager::new("", "secret").is_err());
+        assert!(JoinTokenManager::new("cluster", "").is_err());
+
+        let manager = manager();
+        assert!(manager.issue(Duration::ZERO).is_err());
+        assert!(manager
+            .issue(MAX_JOIN_TOKEN_TTL + Duration::from_secs(1))
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_ttl() {
+        assert_eq!(parse_ttl("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_ttl("30m").unwrap(), Duration::from_secs(1800));
+        assert_eq!(parse_ttl("1h").unwrap(), Duration::from_secs(3600));
+        assert_eq!(parse_ttl("2d").unwrap(), Duration::from_secs(172_800));
+        assert_eq!(parse_ttl("120").unwrap(), Duration::from_secs(120));
+        assert!(parse_ttl("h").is_err());
+        assert!(parse_ttl("1w").is_err());
+    }
+}
@@ -1,19 +1,36 @@
 //! Security module for TLS, authentication, rate limiting, and access control
 //!
 //! This module provides security features for the Hyra Scribe Ledger including:
-//! - TLS encryption for node-to-node communication
+//! - TLS encryption for node-to-node communication and the client API (HTTP/2)
+//! - Per-tenant encryption keys for archived segments (crypto-shredding)
 //! - API authentication (bearer tokens, API keys)
 //! - Request rate limiting
+//! - IP allowlists and denylists per HTTP listener
 //! - Role-based access control (RBAC)
+//! - One-time join tokens for admitting new nodes
+//! - Signing and replay protection of Raft RPCs between nodes
 //! - Audit logging for security events
+//! - Key-level access tracing for regulated namespaces
 
+pub mod access_trace;
 pub mod auth;
+pub mod encryption;
+pub mod join_token;
+pub mod network_policy;
 pub mod rate_limit;
+pub mod rpc_auth;
 pub mod tls;
 
+pub use access_trace::{AccessOp, AccessTrace, AccessTraceConfig};
 pub use auth::{AuthConfig, AuthMiddleware, Permission, Role};
+pub use encryption::{EncryptionConfig, MasterKey, SealedData, TenantKeyring};
+pub use join_token::{parse_ttl, JoinToken, JoinTokenManager};
+pub use network_policy::{NetworkPolicy, NetworkPolicyConfig, NetworkPolicyLayer};
 pub use rate_limit::{RateLimiter, RateLimiterConfig};
-pub use tls::{TlsConfig, TlsServerConfig};
+pub use rpc_auth::RpcAuthenticator;
+pub use tls::{
+    client_cert_auth, ClientCertAcceptor, ClientCertIdentity, TlsConfig, TlsServerConfig,
+};
 
 #[cfg(test)]
 mod tests {
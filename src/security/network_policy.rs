@@ -0,0 +1,397 @@
+//! IP allowlists and denylists for the HTTP listeners
+//!
+//! Each listener (the data listener serving the client API and the admin listener)
+//! can carry a [`NetworkPolicyConfig`] of CIDR ranges. [`NetworkPolicyLayer`]
+//! enforces it in front of the listener's routes:
+//!
+//! - a client address in `deny` is rejected
+//! - with a non-empty `allow`, a client address outside every range is rejected
+//! - any other client address is let through
+//!
+//! Rejected requests are answered with 403 Forbidden, counted in
+//! `scribe_ledger_network_policy_rejected_total{listener}` and, with an
+//! [`AdminEventLog`] attached, recorded as `request_rejected` events. A client that
+//! keeps knocking is recorded at most once per [`AUDIT_INTERVAL`] so a scan cannot
+//! flood the log. IPv4-mapped IPv6 addresses are matched as IPv4.
+//!
+//! The client address is taken from the connection, so the layer needs the router to
+//! be served with `into_make_service_with_connect_info::<SocketAddr>()`; requests
+//! without a known address are rejected. Addresses set by proxies in forwarding
+//! headers are not trusted.
+
+use crate::admin_events::{AdminAction, AdminEventLog};
+use crate::logging::log_path;
+use crate::metrics::NETWORK_POLICY_REJECTED;
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, Response, StatusCode};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tower::{Layer, Service};
+
+/// Shortest time between two audit log entries for the same client address
+pub const AUDIT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Client addresses remembered for audit throttling before the memory is reset
+const MAX_AUDITED_ADDRS: usize = 4096;
+
+/// A range of IP addresses in CIDR notation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpRange {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpRange {
+    /// Whether `ip` lies in the range
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => prefix_matches(
+                u32::from(net).into(),
+                u32::from(ip).into(),
+                32,
+                self.prefix_len,
+            ),
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                prefix_matches(u128::from(net), u128::from(ip), 128, self.prefix_len)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Whether the top `prefix_len` of `bits` bits of `a` and `b` agree
+fn prefix_matches(a: u128, b: u128, bits: u8, prefix_len: u8) -> bool {
+    if prefix_len == 0 {
+        return true;
+    }
+    let shift = bits - prefix_len;
+    a >> shift == b >> shift
+}
+
+impl FromStr for IpRange {
+    type Err = String;
+
+    /// Parse `10.0.0.0/8`, `fd00::/8` or a single address
+    fn from_str(s: &str) -> std::result::Result<Self, String> {
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, len)) => (addr, Some(len)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid IP range '{}'", s))?;
+        let addr = addr.to_canonical();
+        let max_len = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(len) => len
+                .trim()
+                .parse::<u8>()
+                .ok()
+                .filter(|len| *len <= max_len)
+                .ok_or_else(|| format!("Invalid prefix length in IP range '{}'", s))?,
+            None => max_len,
+        };
+        Ok(Self { addr, prefix_len })
+    }
+}
+
+/// Allowed and denied client addresses of a listener
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkPolicyConfig {
+    /// CIDR ranges allowed to connect; empty allows every address not denied
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// CIDR ranges always rejected, even if they are also allowed
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+impl NetworkPolicyConfig {
+    /// Whether the policy lets every address through
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// Validate the CIDR ranges
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        NetworkPolicy::from_config(self).map(|_| ())
+    }
+}
+
+/// Why a client address was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// The address is in a denied range
+    Denied,
+    /// An allowlist is set and the address is outside it
+    NotAllowed,
+}
+
+impl Rejection {
+    /// Description of the rejection, as recorded in the audit log
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rejection::Denied => "address is denied",
+            Rejection::NotAllowed => "address is not allowed",
+        }
+    }
+}
+
+/// Parsed allowlist and denylist of a listener
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetworkPolicy {
+    allow: Vec<IpRange>,
+    deny: Vec<IpRange>,
+}
+
+impl NetworkPolicy {
+    /// Parse the ranges of `config`
+    pub fn from_config(config: &NetworkPolicyConfig) -> std::result::Result<Self, String> {
+        let parse = |ranges: &[String]| {
+            ranges
+                .iter()
+                .map(|range| range.parse())
+                .collect::<std::result::Result<Vec<IpRange>, String>>()
+        };
+        Ok(Self {
+            allow: parse(&config.allow)?,
+            deny: parse(&config.deny)?,
+        })
+    }
+
+    /// Check a client address against the policy
+    pub fn check(&self, ip: IpAddr) -> std::result::Result<(), Rejection> {
+        if self.deny.iter().any(|range| range.contains(ip)) {
+            return Err(Rejection::Denied);
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|range| range.contains(ip)) {
+            return Err(Rejection::NotAllowed);
+        }
+        Ok(())
+    }
+}
+
+/// Layer enforcing a [`NetworkPolicy`] on the requests of a listener
+#[derive(Clone)]
+pub struct NetworkPolicyLayer {
+    policy: Arc<NetworkPolicy>,
+    listener: &'static str,
+    audit: Option<AdminEventLog>,
+    audited: Arc<Mutex<HashMap<IpAddr, Instant>>>,
+}
+
+impl NetworkPolicyLayer {
+    /// Enforce `policy` on the listener named `listener` ("data" or "admin")
+    pub fn new(policy: NetworkPolicy, listener: &'static str) -> Self {
+        Self {
+            policy: Arc::new(policy),
+            listener,
+            audit: None,
+            audited: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Record rejected requests in `log`
+    pub fn with_audit_log(mut self, log: AdminEventLog) -> Self {
+        self.audit = Some(log);
+        self
+    }
+
+    /// Count and audit a rejected request
+    fn reject<B>(&self, ip: Option<IpAddr>, request: &Request<B>, reason: &str) {
+        NETWORK_POLICY_REJECTED
+            .with_label_values(&[self.listener])
+            .inc();
+        let actor = ip.map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+        tracing::warn!(
+            "Rejected {} {} from {} on the {} listener: {}",
+            request.method(),
+            log_path(request.uri().path()),
+            actor,
+            self.listener,
+            reason
+        );
+
+        let Some(log) = &self.audit else {
+            return;
+        };
+        if let Some(ip) = ip {
+            let mut audited = self.audited.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            if audited
+                .get(&ip)
+                .is_some_and(|last| now.duration_since(*last) < AUDIT_INTERVAL)
+            {
+                return;
+            }
+            if audited.len() >= MAX_AUDITED_ADDRS {
+                audited.clear();
+            }
+            audited.insert(ip, now);
+        }
+        let action = AdminAction::RequestRejected {
+            listener: self.listener.to_string(),
+            method: request.method().to_string(),
+            path: log_path(request.uri().path()),
+        };
+        if let Err(e) = log.record(actor, action, Err(reason.to_string())) {
+            tracing::warn!("Failed to record rejected request: {}", e);
+        }
+    }
+}
+
+impl<S> Layer<S> for NetworkPolicyLayer {
+    type Service = NetworkPolicyService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        NetworkPolicyService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// Service rejecting requests from client addresses its policy does not admit
+#[derive(Clone)]
+pub struct NetworkPolicyService<S> {
+    inner: S,
+    layer: NetworkPolicyLayer,
+}
+
+impl<S, B> Service<Request<B>> for NetworkPolicyService<S>
+where
+    S: Service<Request<B>, Response = Response<Body>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, std::result::Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<std::result::Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let ip = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        let verdict = match ip {
+            Some(ip) => self.layer.policy.check(ip).map_err(|r| r.as_str()),
+            None => Err("client address unknown"),
+        };
+        if let Err(reason) = verdict {
+            self.layer.reject(ip, &request, reason);
+            let response = Response::builder()
+                .status(StatusCode::FORBIDDEN)
+                .body(Body::from("Forbidden by network policy"))
+                .expect("static response");
+            return Box::pin(async move { Ok(response) });
+        }
+        Box::pin(self.inner.call(request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::admin_events::AdminEventFilter;
+    use axum::routing::get;
+    use axum::Router;
+
+    fn policy(allow: &[&str], deny: &[&str]) -> NetworkPolicy {
+        NetworkPolicy::from_config(&NetworkPolicyConfig {
+            allow: allow.iter().map(|s| s.to_string()).collect(),
+            deny: deny.iter().map(|s| s.to_string()).collect(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_ip_range() {
+        let range: IpRange = "10.1.0.0/16".parse().unwrap();
+        assert!(range.contains("10.1.200.3".parse().unwrap()));
+        assert!(!range.contains("10.2.0.1".parse().unwrap()));
+        assert!(range.contains("::ffff:10.1.0.9".parse().unwrap()));
+
+        let range: IpRange = "fd00::/8".parse().unwrap();
+        assert!(range.contains("fd12::1".parse().unwrap()));
+        assert!(!range.contains("10.1.0.1".parse().unwrap()));
+
+        let any: IpRange = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains("192.0.2.1".parse().unwrap()));
+        let single: IpRange = "192.0.2.1".parse().unwrap();
+        assert!(!single.contains("192.0.2.2".parse().unwrap()));
+
+        assert!("10.0.0.0/33".parse::<IpRange>().is_err());
+        assert!("10.0.0/8".parse::<IpRange>().is_err());
+    }
+
+    #[test]
+    fn test_policy_check() {
+        let open = policy(&[], &[]);
+        assert!(open.check("203.0.113.7".parse().unwrap()).is_ok());
+
+        let policy = policy(&["10.0.0.0/8", "127.0.0.1"], &["10.9.0.0/16"]);
+        assert!(policy.check("10.1.2.3".parse().unwrap()).is_ok());
+        assert!(policy.check("127.0.0.1".parse().unwrap()).is_ok());
+        assert_eq!(
+            policy.check("10.9.0.1".parse().unwrap()),
+            Err(Rejection::Denied)
+        );
+        assert_eq!(
+            policy.check("203.0.113.7".parse().unwrap()),
+            Err(Rejection::NotAllowed)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_layer_rejects_and_audits() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let log = AdminEventLog::open(&db, 1).unwrap();
+        let layer = NetworkPolicyLayer::new(policy(&["10.0.0.0/8"], &[]), "admin")
+            .with_audit_log(log.clone());
+        let mut app = Router::new()
+            .route("/admin/events", get(|| async { StatusCode::OK }))
+            .layer(layer);
+        let request = |ip: &str| {
+            let mut request = Request::get("/admin/events").body(Body::empty()).unwrap();
+            let addr = SocketAddr::new(ip.parse().unwrap(), 40000);
+            request.extensions_mut().insert(ConnectInfo(addr));
+            request
+        };
+
+        // A router is always ready, so it can be called without polling readiness
+        let response = app.call(request("10.0.0.5")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        for _ in 0..3 {
+            let response = app.call(request("203.0.113.7")).await.unwrap();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        }
+        let request = Request::get("/admin/events").body(Body::empty()).unwrap();
+        let response = app.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // Repeated rejections of one address are recorded once
+        let events = log.query(&AdminEventFilter::default()).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].actor, "203.0.113.7");
+        assert!(!events[1].success);
+        assert_eq!(
+            events[1].action,
+            AdminAction::RequestRejected {
+                listener: "admin".to_string(),
+                method: "GET".to_string(),
+                path: "/admin/events".to_string(),
+            }
+        );
+    }
+}
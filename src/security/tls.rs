@@ -1,9 +1,36 @@
 //! TLS configuration and support for secure communication
 //!
 //! This module provides TLS encryption for node-to-node communication and HTTPS API endpoints.
+//! The client API is served over rustls with HTTP/2 negotiated via ALPN, and client
+//! certificates can optionally be mapped to RBAC roles by their SHA-256 fingerprint.
 
+use crate::security::auth::{AuthMiddleware, Role};
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::{AddExtension, Next},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use axum_server::accept::Accept;
+use axum_server::tls_rustls::{RustlsAcceptor, RustlsConfig};
+use futures::future::BoxFuture;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::server::TlsStream;
+use tower::Layer;
+use tracing::{debug, warn};
+
+/// ALPN protocols advertised by the client API (HTTP/2 preferred)
+const ALPN_PROTOCOLS: [&[u8]; 2] = [b"h2", b"http/1.1"];
 
 /// TLS configuration for client and server
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -20,6 +47,10 @@ pub struct TlsConfig {
     /// Require client certificates (mutual TLS)
     #[serde(default)]
     pub require_client_cert: bool,
+    /// Client certificate SHA-256 fingerprints (hex) mapped to role names
+    /// (`read_only`, `read_write`, `admin`)
+    #[serde(default)]
+    pub client_cert_roles: HashMap<String, String>,
 }
 
 impl TlsConfig {
@@ -31,6 +62,7 @@ impl TlsConfig {
             key_path: Some(key_path),
             ca_cert_path: None,
             require_client_cert: false,
+            client_cert_roles: HashMap::new(),
         }
     }
 
@@ -41,6 +73,24 @@ impl TlsConfig {
         self
     }
 
+    /// Map a client certificate fingerprint to a role name
+    pub fn with_client_cert_role(
+        mut self,
+        fingerprint: impl Into<String>,
+        role: impl Into<String>,
+    ) -> Self {
+        self.client_cert_roles
+            .insert(fingerprint.into().to_lowercase(), role.into());
+        self
+    }
+
+    /// Resolve the role for a client certificate fingerprint
+    pub fn role_for_fingerprint(&self, fingerprint: &str) -> Option<Role> {
+        self.client_cert_roles
+            .get(&fingerprint.to_lowercase())
+            .and_then(|name| Role::from_name(name))
+    }
+
     /// Validate TLS configuration
     pub fn validate(&self) -> Result<(), String> {
         if !self.enabled {
@@ -61,10 +111,31 @@ impl TlsConfig {
             );
         }
 
+        if !self.client_cert_roles.is_empty() && self.ca_cert_path.is_none() {
+            return Err(
+                "CA certificate path is required when client certificate roles are configured"
+                    .to_string(),
+            );
+        }
+
+        for (fingerprint, role) in &self.client_cert_roles {
+            if Role::from_name(role).is_none() {
+                return Err(format!(
+                    "Unknown role '{}' for client certificate {}",
+                    role, fingerprint
+                ));
+            }
+        }
+
         Ok(())
     }
 }
 
+/// Compute the hex-encoded SHA-256 fingerprint of a DER certificate
+pub fn cert_fingerprint(der: &[u8]) -> String {
+    hex::encode(Sha256::digest(der))
+}
+
 /// TLS server configuration wrapper
 #[derive(Debug, Clone)]
 pub struct TlsServerConfig {
@@ -87,6 +158,179 @@ impl TlsServerConfig {
     pub fn is_enabled(&self) -> bool {
         self.config.enabled
     }
+
+    /// Build a rustls server configuration with HTTP/2 and HTTP/1.1 ALPN
+    ///
+    /// When a CA certificate is configured, client certificates are verified against
+    /// it. They are only mandatory if `require_client_cert` is set.
+    pub fn build_rustls_config(&self) -> Result<ServerConfig, String> {
+        let cert_path = self
+            .config
+            .cert_path
+            .as_ref()
+            .ok_or("TLS certificate path is not configured")?;
+        let key_path = self
+            .config
+            .key_path
+            .as_ref()
+            .ok_or("TLS key path is not configured")?;
+
+        let certs = load_certs(cert_path)?;
+        let key = load_private_key(key_path)?;
+
+        let builder = ServerConfig::builder();
+        let mut server_config = match &self.config.ca_cert_path {
+            Some(ca_path) => {
+                let mut roots = RootCertStore::empty();
+                for cert in load_certs(ca_path)? {
+                    roots
+                        .add(cert)
+                        .map_err(|e| format!("Invalid CA certificate: {}", e))?;
+                }
+
+                let verifier = WebPkiClientVerifier::builder(Arc::new(roots));
+                let verifier = if self.config.require_client_cert {
+                    verifier.build()
+                } else {
+                    verifier.allow_unauthenticated().build()
+                }
+                .map_err(|e| format!("Failed to build client verifier: {}", e))?;
+
+                builder
+                    .with_client_cert_verifier(verifier)
+                    .with_single_cert(certs, key)
+            }
+            None => builder.with_no_client_auth().with_single_cert(certs, key),
+        }
+        .map_err(|e| format!("Invalid TLS certificate or key: {}", e))?;
+
+        server_config.alpn_protocols = ALPN_PROTOCOLS.iter().map(|p| p.to_vec()).collect();
+
+        Ok(server_config)
+    }
+
+    /// Create an acceptor that terminates TLS and records the client certificate
+    pub fn acceptor(&self) -> Result<ClientCertAcceptor, String> {
+        let rustls_config = RustlsConfig::from_config(Arc::new(self.build_rustls_config()?));
+        Ok(ClientCertAcceptor::new(RustlsAcceptor::new(rustls_config)))
+    }
+}
+
+/// Load all PEM certificates from a file
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, String> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to open certificate {:?}: {}", path, e))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to parse certificate {:?}: {}", path, e))?;
+
+    if certs.is_empty() {
+        return Err(format!("No certificates found in {:?}", path));
+    }
+    Ok(certs)
+}
+
+/// Load the first PEM private key from a file
+fn load_private_key(path: &Path) -> Result<PrivateKeyDer<'static>, String> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to open private key {:?}: {}", path, e))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .map_err(|e| format!("Failed to parse private key {:?}: {}", path, e))?
+        .ok_or_else(|| format!("No private key found in {:?}", path))
+}
+
+/// Identity of the client certificate presented on a TLS connection
+#[derive(Debug, Clone, Default)]
+pub struct ClientCertIdentity {
+    /// SHA-256 fingerprint of the leaf certificate, if one was presented
+    pub fingerprint: Option<String>,
+}
+
+/// TLS acceptor that attaches the peer certificate identity to every request
+#[derive(Debug, Clone)]
+pub struct ClientCertAcceptor {
+    inner: RustlsAcceptor,
+}
+
+impl ClientCertAcceptor {
+    /// Wrap a rustls acceptor
+    pub fn new(inner: RustlsAcceptor) -> Self {
+        Self { inner }
+    }
+}
+
+impl<I, S> Accept<I, S> for ClientCertAcceptor
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Send + 'static,
+{
+    type Stream = TlsStream<I>;
+    type Service = AddExtension<S, ClientCertIdentity>;
+    type Future = BoxFuture<'static, std::io::Result<(Self::Stream, Self::Service)>>;
+
+    fn accept(&self, stream: I, service: S) -> Self::Future {
+        let acceptor = self.inner.clone();
+
+        Box::pin(async move {
+            let (stream, service) = acceptor.accept(stream, service).await?;
+            let identity = ClientCertIdentity {
+                fingerprint: stream
+                    .get_ref()
+                    .1
+                    .peer_certificates()
+                    .and_then(|certs| certs.first())
+                    .map(|cert| cert_fingerprint(cert.as_ref())),
+            };
+            let service = Extension(identity).layer(service);
+
+            Ok((stream, service))
+        })
+    }
+}
+
+/// Axum middleware that authorizes requests using the client certificate role
+///
+/// Requests without a mapped certificate are rejected once any role mapping is
+/// configured; the health endpoint is always allowed.
+pub async fn client_cert_auth(
+    State(tls): State<Arc<TlsServerConfig>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if tls.config().client_cert_roles.is_empty() || request.uri().path() == "/health" {
+        return next.run(request).await;
+    }
+
+    let fingerprint = request
+        .extensions()
+        .get::<ClientCertIdentity>()
+        .and_then(|identity| identity.fingerprint.clone());
+
+    let role = match fingerprint
+        .as_deref()
+        .and_then(|f| tls.config().role_for_fingerprint(f))
+    {
+        Some(role) => role,
+        None => {
+            warn!("Client certificate missing or not mapped to a role");
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(serde_json::json!({
+                    "error": "Client certificate is not authorized"
+                })),
+            )
+                .into_response();
+        }
+    };
+
+    let method = request.method().as_str().to_string();
+    let path = request.uri().path().to_string();
+    if let Err(required_perm) = AuthMiddleware::authorize_role(&role, &method, &path) {
+        return AuthMiddleware::forbidden_response(required_perm);
+    }
+
+    debug!("Client certificate authorized with role '{}'", role.name);
+    next.run(request).await
 }
 
 #[cfg(test)]
@@ -179,6 +423,50 @@ mod tests {
         assert!(config.validate().is_err());
     }
 
+    #[test]
+    fn test_tls_config_client_cert_roles() {
+        let config = TlsConfig::new(PathBuf::from("/cert.pem"), PathBuf::from("/key.pem"))
+            .with_mutual_tls(PathBuf::from("/ca.pem"))
+            .with_client_cert_role("ABCDEF", "read_only");
+        assert!(config.validate().is_ok());
+
+        let role = config.role_for_fingerprint("abcdef").unwrap();
+        assert_eq!(role.name, "read_only");
+        assert!(config.role_for_fingerprint("012345").is_none());
+    }
+
+    #[test]
+    fn test_tls_config_client_cert_roles_unknown_role() {
+        let config = TlsConfig::new(PathBuf::from("/cert.pem"), PathBuf::from("/key.pem"))
+            .with_mutual_tls(PathBuf::from("/ca.pem"))
+            .with_client_cert_role("abcdef", "superuser");
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_tls_config_client_cert_roles_require_ca() {
+        let config = TlsConfig::new(PathBuf::from("/cert.pem"), PathBuf::from("/key.pem"))
+            .with_client_cert_role("abcdef", "admin");
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_cert_fingerprint() {
+        let fingerprint = cert_fingerprint(b"certificate");
+        assert_eq!(fingerprint.len(), 64);
+        assert_eq!(fingerprint, cert_fingerprint(b"certificate"));
+    }
+
+    #[test]
+    fn test_build_rustls_config_missing_files() {
+        let config = TlsConfig::new(
+            PathBuf::from("/nonexistent/cert.pem"),
+            PathBuf::from("/nonexistent/key.pem"),
+        );
+        let server_config = TlsServerConfig::new(config).unwrap();
+        assert!(server_config.build_rustls_config().is_err());
+    }
+
     #[test]
     fn test_tls_config_validate_mutual_tls_valid() {
         let config = TlsConfig::new(PathBuf::from("/cert.pem"), PathBuf::from("/key.pem"))
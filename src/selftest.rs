@@ -0,0 +1,314 @@
+//! Node self-test
+//!
+//! Exercises each subsystem a node depends on (local storage, Merkle proofs, S3,
+//! discovery socket, Raft storage) without starting the node, so provisioning
+//! pipelines can validate a host before it joins a production cluster.
+
+use crate::config::Config;
+use crate::consensus::RaftStorage;
+use crate::crypto::MerkleTree;
+use crate::discovery::{DiscoveryConfig, DiscoveryService};
+use crate::error::{Result, ScribeError};
+use crate::storage::s3::{S3Storage, S3StorageConfig};
+use crate::storage::{SledStorage, StorageBackend};
+use openraft::storage::RaftLogStorage;
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// Outcome of a single subsystem check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckOutcome {
+    /// The subsystem works
+    Passed,
+    /// The subsystem is broken
+    Failed,
+    /// The subsystem is not configured
+    Skipped,
+}
+
+impl fmt::Display for CheckOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckOutcome::Passed => write!(f, "PASS"),
+            CheckOutcome::Failed => write!(f, "FAIL"),
+            CheckOutcome::Skipped => write!(f, "SKIP"),
+        }
+    }
+}
+
+/// Result of a single subsystem check
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    /// Subsystem name
+    pub name: &'static str,
+    /// Outcome of the check
+    pub outcome: CheckOutcome,
+    /// What was verified, or why the check failed
+    pub detail: String,
+    /// Time taken by the check
+    pub elapsed: Duration,
+}
+
+/// Results of every subsystem check
+#[derive(Debug, Clone, Default)]
+pub struct SelfTestReport {
+    /// Checks in the order they ran
+    pub checks: Vec<CheckResult>,
+}
+
+impl SelfTestReport {
+    /// Check whether no subsystem failed (skipped checks do not count as failures)
+    pub fn passed(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.outcome != CheckOutcome::Failed)
+    }
+
+    async fn run<F>(&mut self, name: &'static str, check: F)
+    where
+        F: std::future::Future<Output = Result<Option<String>>>,
+    {
+        let start = Instant::now();
+        let (outcome, detail) = match check.await {
+            Ok(Some(detail)) => (CheckOutcome::Passed, detail),
+            Ok(None) => (CheckOutcome::Skipped, "not configured".to_string()),
+            Err(e) => (CheckOutcome::Failed, e.to_string()),
+        };
+        self.checks.push(CheckResult {
+            name,
+            outcome,
+            detail,
+            elapsed: start.elapsed(),
+        });
+    }
+}
+
+impl fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            writeln!(
+                f,
+                "[{}] {:<10} {} ({} ms)",
+                check.outcome,
+                check.name,
+                check.detail,
+                check.elapsed.as_millis()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Run every subsystem check against `config`
+///
+/// Checks never stop at the first failure, so a single run reports every broken
+/// subsystem. The Raft storage check opens the node's database, so it fails while
+/// the node itself is running.
+pub async fn run(config: &Config) -> SelfTestReport {
+    let mut report = SelfTestReport::default();
+    report.run("storage", check_storage(config)).await;
+    report.run("merkle", check_merkle()).await;
+    report.run("s3", check_s3(config)).await;
+    report.run("discovery", check_discovery(config)).await;
+    report.run("raft", check_raft_storage(config)).await;
+    report
+}
+
+/// Write, read back and flush a key in a scratch database under the data directory
+async fn check_storage(config: &Config) -> Result<Option<String>> {
+    std::fs::create_dir_all(&config.node.data_dir)?;
+    let path = config
+        .node
+        .data_dir
+        .join(format!("selftest-{}", std::process::id()));
+
+    let result = async {
+        let storage = SledStorage::new(&path)?;
+        let key = b"selftest".to_vec();
+        let value = b"scribe-ledger selftest".to_vec();
+
+        storage.put(key.clone(), value.clone()).await?;
+        if storage.get(&key).await?.as_ref() != Some(&value) {
+            return Err(ScribeError::Storage(
+                "Read back a different value than written".to_string(),
+            ));
+        }
+        storage.flush().await?;
+        Ok(())
+    }
+    .await;
+
+    let _ = std::fs::remove_dir_all(&path);
+    result?;
+
+    Ok(Some(format!(
+        "write/read/flush under {}",
+        config.node.data_dir.display()
+    )))
+}
+
+/// Build a Merkle tree and verify a generated proof against its root
+async fn check_merkle() -> Result<Option<String>> {
+    let pairs: Vec<(Vec<u8>, Vec<u8>)> = (0..16)
+        .map(|i| {
+            (
+                format!("key-{}", i).into_bytes(),
+                format!("value-{}", i).into_bytes(),
+            )
+        })
+        .collect();
+    let tree = MerkleTree::from_pairs(pairs);
+
+    let root = tree
+        .root_hash()
+        .ok_or_else(|| ScribeError::Other("Merkle tree has no root".to_string()))?;
+    let proof = tree
+        .get_proof(b"key-7")
+        .ok_or_else(|| ScribeError::Other("No Merkle proof generated".to_string()))?;
+
+    if !MerkleTree::verify_proof(&proof, &root) {
+        return Err(ScribeError::Other(
+            "Merkle proof failed verification".to_string(),
+        ));
+    }
+
+    let mut tampered = proof;
+    tampered.value = b"tampered".to_vec();
+    if MerkleTree::verify_proof(&tampered, &root) {
+        return Err(ScribeError::Other(
+            "Tampered Merkle proof passed verification".to_string(),
+        ));
+    }
+
+    Ok(Some(format!("proof verified over {} keys", tree.len())))
+}
+
+/// Connect to the configured S3 bucket
+async fn check_s3(config: &Config) -> Result<Option<String>> {
+    let Some(s3) = &config.storage.s3 else {
+        return Ok(None);
+    };
+
+    let storage = S3Storage::new(S3StorageConfig {
+        bucket: s3.bucket.clone(),
+        region: s3.region.clone(),
+        endpoint: s3.endpoint.clone(),
+        access_key_id: s3.access_key_id.clone(),
+        secret_access_key: s3.secret_access_key.clone(),
+        path_style: s3.path_style,
+        timeout_secs: s3.timeout_secs,
+        max_retries: s3.max_retries,
+        credential_source: s3.credentials.clone(),
+        credential_refresh_secs: s3.credential_refresh_secs,
+    })
+    .await?;
+    storage.health_check().await?;
+
+    Ok(Some(format!("bucket '{}' reachable", s3.bucket)))
+}
+
+/// Bind the discovery socket with the node's addresses
+async fn check_discovery(config: &Config) -> Result<Option<String>> {
+    let parse = |port: u16| {
+        format!("{}:{}", config.node.address, port)
+            .parse::<SocketAddr>()
+            .map_err(|e| {
+                ScribeError::Configuration(format!(
+                    "Invalid node address '{}': {}",
+                    config.node.address, e
+                ))
+            })
+    };
+
+    let discovery = DiscoveryService::new(DiscoveryConfig {
+        node_id: config.node.id,
+        raft_addr: parse(config.network.raft_port)?,
+        client_addr: parse(config.network.client_port)?,
+        discovery_port: config.discovery.discovery_port,
+        broadcast_addr: config.discovery.broadcast_addr.clone(),
+        seed_addrs: config.network.seed_peers.clone(),
+        heartbeat_interval_ms: config.discovery.heartbeat_interval_ms,
+        failure_timeout_ms: config.discovery.failure_timeout_ms,
+        cluster_secret: config.discovery.cluster_secret.clone(),
+    })?;
+    drop(discovery);
+
+    Ok(Some(format!(
+        "bound UDP port {}",
+        config.discovery.discovery_port
+    )))
+}
+
+/// Open the node's database as Raft storage and read the log state
+async fn check_raft_storage(config: &Config) -> Result<Option<String>> {
+    let db_path = config.node.data_dir.join("db");
+    let db = sled::open(&db_path)?;
+
+    let mut storage = RaftStorage::new(db);
+    let state = storage
+        .get_log_state()
+        .await
+        .map_err(|e| ScribeError::Consensus(format!("Failed to read log state: {}", e)))?;
+
+    Ok(Some(match state.last_log_id {
+        Some(log_id) => format!(
+            "opened {}, last log index {}",
+            db_path.display(),
+            log_id.index
+        ),
+        None => format!("opened {}, log empty", db_path.display()),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_selftest_passes_on_fresh_node() {
+        let data_dir = std::env::temp_dir().join(format!("scribe-selftest-{}", fastrand::u64(..)));
+        let mut config = Config::default_for_node(1);
+        config.node.data_dir = data_dir.clone();
+        config.node.address = "127.0.0.1".to_string();
+        config.discovery.discovery_port = 17031;
+
+        let report = run(&config).await;
+        let _ = std::fs::remove_dir_all(&data_dir);
+
+        let outcome = |name: &str| {
+            report
+                .checks
+                .iter()
+                .find(|check| check.name == name)
+                .map(|check| check.outcome)
+        };
+        assert_eq!(outcome("storage"), Some(CheckOutcome::Passed));
+        assert_eq!(outcome("merkle"), Some(CheckOutcome::Passed));
+        assert_eq!(outcome("s3"), Some(CheckOutcome::Skipped));
+        assert_eq!(outcome("discovery"), Some(CheckOutcome::Passed));
+        assert_eq!(outcome("raft"), Some(CheckOutcome::Passed));
+        assert!(report.passed(), "{}", report);
+    }
+
+    #[tokio::test]
+    async fn test_selftest_reports_failures() {
+        let data_dir = std::env::temp_dir().join(format!("scribe-selftest-{}", fastrand::u64(..)));
+        let mut config = Config::default_for_node(1);
+        config.node.data_dir = data_dir.clone();
+        config.node.address = "not an address".to_string();
+        config.discovery.discovery_port = 17032;
+
+        let report = run(&config).await;
+        let _ = std::fs::remove_dir_all(&data_dir);
+
+        assert!(!report.passed());
+        let discovery = report
+            .checks
+            .iter()
+            .find(|check| check.name == "discovery")
+            .unwrap();
+        assert_eq!(discovery.outcome, CheckOutcome::Failed);
+        assert!(report.to_string().contains("[FAIL] discovery"));
+    }
+}
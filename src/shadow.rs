@@ -0,0 +1,248 @@
+//! Shadow writes for migration rehearsals
+//!
+//! In shadow mode every successful write is applied normally and then mirrored
+//! asynchronously to a second target: another cluster's client API or a key prefix
+//! (namespace) in this cluster. Mirroring never blocks or fails the primary write;
+//! outcomes are tracked in divergence counters instead. TTLs are not mirrored; keys
+//! written with a TTL are mirrored as plain puts.
+
+use crate::consensus::{AppRequest, AppResponse, ConsensusNode};
+use crate::error::{Result, ScribeError};
+use crate::http_client::{key_url, leader_aware_client, send_following_leader};
+use crate::metrics::SHADOW_WRITES;
+use crate::types::{Key, Value};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+/// Where shadow writes are mirrored to
+#[derive(Clone)]
+pub enum ShadowTarget {
+    /// Client API of another cluster (e.g. "http://10.0.2.1:8001")
+    Cluster { base_url: String },
+    /// Key prefix within this cluster
+    Namespace {
+        consensus: Arc<ConsensusNode>,
+        prefix: Vec<u8>,
+    },
+}
+
+/// Write operation to mirror
+#[derive(Debug, Clone)]
+pub enum ShadowOp {
+    /// Mirror a put
+    Put { key: Key, value: Value },
+    /// Mirror a delete
+    Delete { key: Key },
+}
+
+/// Point-in-time view of the shadow counters
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShadowStats {
+    /// Writes applied to the shadow target
+    pub mirrored: u64,
+    /// Writes that succeeded on the primary but failed on the shadow target
+    pub diverged: u64,
+    /// Writes never mirrored because the queue was full
+    pub dropped: u64,
+}
+
+#[derive(Default)]
+struct ShadowCounters {
+    mirrored: AtomicU64,
+    diverged: AtomicU64,
+    dropped: AtomicU64,
+}
+
+/// Asynchronous mirror of primary writes
+pub struct ShadowWriter {
+    sender: mpsc::Sender<ShadowOp>,
+    counters: Arc<ShadowCounters>,
+}
+
+impl ShadowWriter {
+    /// Start a shadow writer and its background worker
+    pub fn start(target: ShadowTarget, queue_capacity: usize) -> Result<Self> {
+        if queue_capacity == 0 {
+            return Err(ScribeError::Configuration(
+                "Shadow queue capacity must be greater than 0".to_string(),
+            ));
+        }
+
+        let client = leader_aware_client()
+            .map_err(|e| ScribeError::Network(format!("Failed to create HTTP client: {}", e)))?;
+        let (sender, mut receiver) = mpsc::channel(queue_capacity);
+        let counters = Arc::new(ShadowCounters::default());
+
+        let worker_counters = Arc::clone(&counters);
+        tokio::spawn(async move {
+            while let Some(op) = receiver.recv().await {
+                match apply(&client, &target, op).await {
+                    Ok(()) => {
+                        worker_counters.mirrored.fetch_add(1, Ordering::Relaxed);
+                        SHADOW_WRITES.with_label_values(&["mirrored"]).inc();
+                    }
+                    Err(e) => {
+                        warn!("Shadow write diverged: {}", e);
+                        worker_counters.diverged.fetch_add(1, Ordering::Relaxed);
+                        SHADOW_WRITES.with_label_values(&["diverged"]).inc();
+                    }
+                }
+            }
+            debug!("Shadow writer stopped");
+        });
+
+        Ok(Self { sender, counters })
+    }
+
+    /// Queue a write for mirroring without waiting for it
+    pub fn mirror(&self, op: ShadowOp) {
+        if self.sender.try_send(op).is_err() {
+            self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+            SHADOW_WRITES.with_label_values(&["dropped"]).inc();
+        }
+    }
+
+    /// Get the current divergence counters
+    pub fn stats(&self) -> ShadowStats {
+        ShadowStats {
+            mirrored: self.counters.mirrored.load(Ordering::Relaxed),
+            diverged: self.counters.diverged.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Apply one operation to the shadow target
+async fn apply(client: &reqwest::Client, target: &ShadowTarget, op: ShadowOp) -> Result<()> {
+    match target {
+        ShadowTarget::Cluster { base_url } => {
+            let key = match &op {
+                ShadowOp::Put { key, .. } | ShadowOp::Delete { key } => key,
+            };
+            let url = key_url(base_url, key)?;
+
+            let response = match op {
+                ShadowOp::Put { value, .. } => {
+                    send_following_leader(&url, |url| client.put(url).body(value.clone())).await
+                }
+                ShadowOp::Delete { .. } => {
+                    send_following_leader(&url, |url| client.delete(url)).await
+                }
+            }
+            .map_err(|e| ScribeError::Network(format!("Shadow request failed: {}", e)))?;
+
+            if response.status().is_success() {
+                Ok(())
+            } else {
+                Err(ScribeError::Network(format!(
+                    "Shadow target returned {}",
+                    response.status()
+                )))
+            }
+        }
+        ShadowTarget::Namespace { consensus, prefix } => {
+            let request = match op {
+                ShadowOp::Put { key, value } => AppRequest::Put {
+                    key: [prefix.as_slice(), key.as_slice()].concat(),
+                    value,
+                },
+                ShadowOp::Delete { key } => AppRequest::Delete {
+                    key: [prefix.as_slice(), key.as_slice()].concat(),
+                },
+            };
+
+            match consensus.client_write(request).await {
+                Ok(AppResponse::Error { message }) => Err(ScribeError::Consensus(message)),
+                Ok(_) => Ok(()),
+                Err(e) => Err(ScribeError::Consensus(e.to_string())),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_url() {
+        let url = key_url("http://10.0.0.1:8001", b"a key").unwrap();
+        assert_eq!(url, "http://10.0.0.1:8001/a%20key");
+
+        let url = key_url("http://10.0.0.1:8001/", b"key").unwrap();
+        assert_eq!(url, "http://10.0.0.1:8001/key");
+    }
+
+    #[tokio::test]
+    async fn test_shadow_writer_zero_capacity() {
+        let target = ShadowTarget::Cluster {
+            base_url: "http://127.0.0.1:1".to_string(),
+        };
+        assert!(ShadowWriter::start(target, 0).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_shadow_writer_counts_divergence() {
+        // Nothing listens on port 1, so every mirrored write diverges
+        let target = ShadowTarget::Cluster {
+            base_url: "http://127.0.0.1:1".to_string(),
+        };
+        let shadow = ShadowWriter::start(target, 16).unwrap();
+
+        shadow.mirror(ShadowOp::Put {
+            key: b"key".to_vec(),
+            value: b"value".to_vec(),
+        });
+        shadow.mirror(ShadowOp::Delete {
+            key: b"key".to_vec(),
+        });
+
+        for _ in 0..50 {
+            if shadow.stats().diverged == 2 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+
+        let stats = shadow.stats();
+        assert_eq!(stats.diverged, 2);
+        assert_eq!(stats.mirrored, 0);
+        assert_eq!(stats.dropped, 0);
+    }
+
+    #[tokio::test]
+    async fn test_shadow_namespace_mirrors_writes() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
+        consensus.initialize().await.unwrap();
+
+        // Wait for election
+        tokio::time::sleep(std::time::Duration::from_millis(2000)).await;
+
+        let target = ShadowTarget::Namespace {
+            consensus: consensus.clone(),
+            prefix: b"shadow/".to_vec(),
+        };
+        let shadow = ShadowWriter::start(target, 16).unwrap();
+        shadow.mirror(ShadowOp::Put {
+            key: b"key".to_vec(),
+            value: b"value".to_vec(),
+        });
+
+        for _ in 0..50 {
+            if shadow.stats().mirrored == 1 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+
+        assert_eq!(shadow.stats().mirrored, 1);
+        assert_eq!(
+            consensus.client_read_local(b"shadow/key").await,
+            Some(b"value".to_vec())
+        );
+    }
+}
@@ -0,0 +1,347 @@
+//! Cluster status aggregation
+//!
+//! Every node reports its own [`NodeStatus`] (Raft state, storage usage, cache and
+//! archival backlog, config hash, wire version, latencies). [`cluster_overview`]
+//! combines the local status with the status of every other member, fetched from
+//! their `GET /cluster/node` endpoint, so operators get the whole cluster in one
+//! response. [`placement_report`] turns the latencies in an overview into a leader
+//! recommendation.
+
+use crate::api::DistributedApi;
+use crate::consensus::{ConsensusNode, StorageUsage};
+use crate::discovery::DiscoveryService;
+use crate::placement::{recommend_leader, NodeLatency, PlacementReport};
+use crate::storage::archival::{ArchivalBacklog, ArchivalManager};
+use crate::trace_context;
+use crate::types::NodeId;
+use crate::wire::WIRE_VERSION;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::warn;
+
+/// Timeout for fetching a peer's status
+const PEER_STATUS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Hot cache statistics of a node
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStats {
+    /// Entries currently cached
+    pub size: usize,
+    /// Maximum number of entries
+    pub capacity: usize,
+}
+
+/// Status reported by a single node
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeStatus {
+    /// Node ID
+    pub node_id: NodeId,
+    /// Raft server state (Leader, Follower, Candidate, Learner)
+    pub state: String,
+    /// Current Raft term
+    pub current_term: u64,
+    /// Leader known to this node
+    pub current_leader: Option<NodeId>,
+    /// Index of the last log entry
+    pub last_log_index: Option<u64>,
+    /// Index of the last applied log entry
+    pub last_applied_index: Option<u64>,
+    /// Storage usage
+    pub storage: StorageUsage,
+    /// Hot cache statistics
+    pub cache: CacheStats,
+    /// Archival backlog (when archival is configured)
+    pub archival: Option<ArchivalBacklog>,
+    /// Hash of the cluster-wide configuration
+    pub config_hash: String,
+    /// Wire version the node speaks; missing from nodes that predate versioning
+    #[serde(default)]
+    pub wire_version: u32,
+    /// Round trips to peers and client operations served, for leader placement
+    #[serde(default)]
+    pub latency: NodeLatency,
+}
+
+impl NodeStatus {
+    /// Collect the status of the local node
+    pub async fn collect(
+        consensus: &ConsensusNode,
+        api: &DistributedApi,
+        archival: Option<&ArchivalManager>,
+        config_hash: String,
+    ) -> Self {
+        let metrics = consensus.metrics().await;
+
+        let archival = archival.and_then(|manager| match manager.backlog() {
+            Ok(backlog) => Some(backlog),
+            Err(e) => {
+                warn!("Failed to read archival backlog: {}", e);
+                None
+            }
+        });
+
+        Self {
+            node_id: consensus.node_id(),
+            state: format!("{:?}", metrics.state),
+            current_term: metrics.current_term,
+            current_leader: metrics.current_leader,
+            last_log_index: metrics.last_log_index,
+            last_applied_index: metrics.last_applied.map(|log_id| log_id.index),
+            storage: consensus.storage_usage().await,
+            cache: CacheStats {
+                size: api.cache_size(),
+                capacity: api.cache_capacity(),
+            },
+            archival,
+            config_hash,
+            wire_version: WIRE_VERSION,
+            latency: NodeLatency::collect(&consensus.peer_latencies()),
+        }
+    }
+}
+
+/// Membership role of a node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemberRole {
+    /// Voting member
+    Voter,
+    /// Non-voting member receiving replication
+    Learner,
+}
+
+/// One member of the cluster as seen by the reporting node
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemberStatus {
+    /// Node ID
+    pub node_id: NodeId,
+    /// Raft address from the membership config
+    pub raft_addr: String,
+    /// Membership role
+    pub role: MemberRole,
+    /// Whether this member is the current leader
+    pub is_leader: bool,
+    /// Whether the member's status could be fetched
+    pub healthy: bool,
+    /// Last log index replicated to this member (reported by the leader only)
+    pub matched_index: Option<u64>,
+    /// Entries the member is behind the leader (reported by the leader only)
+    pub replication_lag: Option<u64>,
+    /// Status reported by the member itself
+    pub status: Option<NodeStatus>,
+    /// Why the member's status is missing
+    pub error: Option<String>,
+}
+
+/// Aggregated view of the whole cluster
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClusterOverview {
+    /// Node that produced the overview
+    pub reported_by: NodeId,
+    /// Current leader
+    pub leader: Option<NodeId>,
+    /// Current Raft term
+    pub current_term: u64,
+    /// Members ordered by node ID
+    pub members: Vec<MemberStatus>,
+    /// Whether every reachable member reports the same config hash
+    pub config_consistent: bool,
+    /// Highest wire version every member speaks, as known to the reporting node
+    pub cluster_wire_version: u32,
+}
+
+/// Build the cluster overview from the local status and every member's status
+///
+/// Peers are reached through the client address known to discovery, using
+/// `scheme` ("http" or "https"). Unreachable members are reported as unhealthy
+/// rather than failing the whole overview.
+pub async fn cluster_overview(
+    consensus: &ConsensusNode,
+    discovery: &DiscoveryService,
+    client: &reqwest::Client,
+    scheme: &str,
+    local: NodeStatus,
+) -> ClusterOverview {
+    let metrics = consensus.metrics().await;
+    let local_id = consensus.node_id();
+    let membership = metrics.membership_config.membership();
+
+    let mut members: Vec<(NodeId, String, MemberRole)> = membership
+        .nodes()
+        .map(|(node_id, node)| {
+            let role = if membership.voter_ids().any(|id| id == *node_id) {
+                MemberRole::Voter
+            } else {
+                MemberRole::Learner
+            };
+            (*node_id, node.addr.clone(), role)
+        })
+        .collect();
+    members.sort_by_key(|(node_id, _, _)| *node_id);
+
+    let fetches = members.iter().map(|(node_id, _, _)| {
+        let local = &local;
+        async move {
+            if *node_id == local_id {
+                return Ok(local.clone());
+            }
+            let peer = discovery
+                .get_peer(*node_id)
+                .ok_or_else(|| "Client address unknown to discovery".to_string())?;
+            fetch_node_status(client, scheme, &peer.client_addr.to_string()).await
+        }
+    });
+    let statuses = join_all(fetches).await;
+
+    let is_leader = metrics.current_leader == Some(local_id);
+    let leader_last_index = metrics.last_log_index.unwrap_or(0);
+    let replication = metrics.replication.as_ref();
+
+    let members: Vec<MemberStatus> = members
+        .into_iter()
+        .zip(statuses)
+        .map(|((node_id, raft_addr, role), status)| {
+            let matched_index = if node_id == local_id && is_leader {
+                metrics.last_log_index
+            } else {
+                replication
+                    .and_then(|r| r.get(&node_id))
+                    .map(|log_id| log_id.map(|l| l.index).unwrap_or(0))
+            };
+            let (status, error) = match status {
+                Ok(status) => (Some(status), None),
+                Err(e) => (None, Some(e)),
+            };
+
+            MemberStatus {
+                node_id,
+                raft_addr,
+                role,
+                is_leader: metrics.current_leader == Some(node_id),
+                healthy: status.is_some(),
+                matched_index,
+                replication_lag: matched_index
+                    .map(|matched| leader_last_index.saturating_sub(matched)),
+                status,
+                error,
+            }
+        })
+        .collect();
+
+    let mut hashes = members
+        .iter()
+        .filter_map(|m| m.status.as_ref().map(|s| s.config_hash.as_str()));
+    let config_consistent = match hashes.next() {
+        Some(first) => hashes.all(|hash| hash == first),
+        None => true,
+    };
+
+    ClusterOverview {
+        reported_by: local_id,
+        leader: metrics.current_leader,
+        current_term: metrics.current_term,
+        members,
+        config_consistent,
+        cluster_wire_version: consensus.cluster_version(),
+    }
+}
+
+/// Recommend a leader from the latencies members reported in `overview`
+///
+/// Members whose status could not be fetched count neither as clients nor with
+/// their measured round trips.
+pub fn placement_report(overview: &ClusterOverview) -> PlacementReport {
+    let voters: Vec<NodeId> = overview
+        .members
+        .iter()
+        .filter(|member| member.role == MemberRole::Voter)
+        .map(|member| member.node_id)
+        .collect();
+    let latencies = overview
+        .members
+        .iter()
+        .filter_map(|member| Some((member.node_id, member.status.as_ref()?.latency.clone())))
+        .collect();
+    recommend_leader(overview.reported_by, &voters, &latencies, overview.leader)
+}
+
+/// Fetch a peer's status from its `GET /cluster/node` endpoint
+async fn fetch_node_status(
+    client: &reqwest::Client,
+    scheme: &str,
+    client_addr: &str,
+) -> Result<NodeStatus, String> {
+    let url = format!("{}://{}/cluster/node", scheme, client_addr);
+    let response = trace_context::inject(client.get(&url))
+        .timeout(PEER_STATUS_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err(format!("Peer returned {}", response.status()));
+    }
+
+    response
+        .json()
+        .await
+        .map_err(|e| format!("Invalid status response: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discovery::DiscoveryConfig;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_cluster_overview_single_node() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
+        consensus.initialize().await.unwrap();
+
+        // Wait for election
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+
+        let api = DistributedApi::new(consensus.clone());
+        api.put(b"key".to_vec(), b"value".to_vec()).await.unwrap();
+
+        let discovery = DiscoveryService::new(DiscoveryConfig {
+            node_id: 1,
+            discovery_port: 17021,
+            broadcast_addr: "127.0.0.1".to_string(),
+            ..DiscoveryConfig::default()
+        })
+        .unwrap();
+
+        let local = NodeStatus::collect(&consensus, &api, None, "hash".to_string()).await;
+        assert_eq!(local.node_id, 1);
+        assert_eq!(local.current_leader, Some(1));
+        assert_eq!(local.storage.keys, 1);
+        assert!(local.archival.is_none());
+        assert_eq!(local.wire_version, WIRE_VERSION);
+
+        let client = reqwest::Client::new();
+        let overview = cluster_overview(&consensus, &discovery, &client, "http", local).await;
+
+        assert_eq!(overview.reported_by, 1);
+        assert_eq!(overview.leader, Some(1));
+        assert!(overview.config_consistent);
+        assert_eq!(overview.cluster_wire_version, WIRE_VERSION);
+        assert_eq!(overview.members.len(), 1);
+
+        let member = &overview.members[0];
+        assert_eq!(member.role, MemberRole::Voter);
+        assert!(member.is_leader);
+        assert!(member.healthy);
+        assert_eq!(member.replication_lag, Some(0));
+
+        // A single voter leads with no round trip to wait for
+        let report = placement_report(&overview);
+        assert_eq!(report.current_leader, Some(1));
+        assert_eq!(report.recommended_leader, Some(1));
+        assert_eq!(report.improvement_us, Some(0));
+        assert_eq!(report.candidates[0].commit_latency_us, Some(0));
+    }
+}
@@ -2,18 +2,29 @@
 //!
 //! This module provides automatic segment archival to S3 with compression,
 //! read-through caching, and tiering policies based on age and access patterns.
+//! With a [`TenantKeyring`], each tenant's entries are sealed with its own data key
+//! so tenants can be offboarded by destroying their key.
 
 use crate::error::{Result, ScribeError};
+use crate::logging::log_key;
+use crate::manifest::{ManifestEntry, ManifestManager};
+use crate::security::{SealedData, TenantKeyring};
+use crate::storage::checksum::{ChecksumDiff, ChecksumIndex};
+use crate::storage::classes::StorageClass;
+use crate::storage::consistency::{self, ConsistencyReport};
+use crate::storage::credentials::CredentialStatus;
+use crate::storage::faults::S3FaultInjector;
+use crate::storage::roll_journal::{RollRecovery, RollStep};
 use crate::storage::s3::{S3Storage, S3StorageConfig};
 use crate::storage::segment::{Segment, SegmentManager};
-use crate::types::SegmentId;
+use crate::types::{Key, SegmentId, Value};
 use flate2::read::GzDecoder;
 use flate2::write::GzEncoder;
 use flate2::Compression;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::io::{Read, Write};
 use std::sync::Arc;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
 use tokio::time::interval;
 
@@ -36,6 +47,8 @@ pub struct TieringPolicy {
     pub enable_auto_archival: bool,
     /// Interval for checking segments to archive (seconds)
     pub archival_check_interval_secs: u64,
+    /// Read each uploaded segment back before recording it in metadata and the manifest
+    pub verify_uploads: bool,
 }
 
 impl Default for TieringPolicy {
@@ -46,6 +59,7 @@ impl Default for TieringPolicy {
             compression_level: 6,
             enable_auto_archival: true,
             archival_check_interval_secs: DEFAULT_ARCHIVAL_INTERVAL_SECS,
+            verify_uploads: true,
         }
     }
 }
@@ -69,6 +83,118 @@ pub struct SegmentMetadata {
     pub entry_count: usize,
     /// Merkle root hash for verification
     pub merkle_root: Vec<u8>,
+    /// Whether tenant entries are sealed with per-tenant data keys
+    #[serde(default)]
+    pub is_encrypted: bool,
+    /// IDs of the data keys the segment is sealed with
+    #[serde(default)]
+    pub key_ids: Vec<String>,
+    /// Storage class the segment was archived under, `None` for the default pipeline
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage_class: Option<String>,
+    /// Whether a checksum index of the entries is stored next to the segment
+    #[serde(default)]
+    pub has_checksum_index: bool,
+}
+
+impl SegmentMetadata {
+    /// Manifest entry recording this archived segment and its data keys
+    pub fn manifest_entry(&self) -> ManifestEntry {
+        ManifestEntry::new(
+            self.segment_id,
+            self.created_at,
+            self.merkle_root.clone(),
+            self.original_size,
+        )
+        .with_key_ids(self.key_ids.clone())
+    }
+}
+
+/// Outcome of re-validating an archived segment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerificationStatus {
+    /// The recomputed Merkle root matches every recorded root
+    Verified,
+    /// The recomputed Merkle root differs from a recorded root
+    Mismatch,
+    /// The downloaded data could not be decoded
+    Corrupt,
+    /// Tenant data keys were destroyed, so the full root cannot be recomputed
+    Shredded,
+}
+
+/// Report of an on-demand segment verification
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SegmentVerification {
+    /// Segment ID
+    pub segment_id: SegmentId,
+    /// Verification outcome
+    pub status: VerificationStatus,
+    /// Merkle root recomputed from the downloaded data (hex)
+    pub computed_root: Option<String>,
+    /// Merkle root recorded in the segment metadata (hex)
+    pub metadata_root: String,
+    /// Merkle root recorded in the manifest (hex), if the manifest tracks the segment
+    pub manifest_root: Option<String>,
+    /// Number of key-value pairs decoded from the downloaded data
+    pub entry_count: usize,
+    /// Size of the downloaded object in bytes
+    pub downloaded_bytes: usize,
+    /// Why the data could not be decoded, for corrupt segments
+    pub error: Option<String>,
+    /// Keys whose entries do not match the checksum index, for mismatching segments
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub corrupt_keys: Vec<String>,
+    /// Indexed entries absent from the downloaded data, for mismatching segments
+    #[serde(default)]
+    pub missing_entries: usize,
+    /// Time taken by the verification in milliseconds
+    pub duration_ms: u64,
+}
+
+/// Part of an encrypted archived segment
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+enum SegmentSection {
+    /// Entries outside every tenant namespace
+    Plain(Vec<u8>),
+    /// Entries of one tenant, sealed with its data key
+    Sealed(SealedData),
+}
+
+/// Segments held locally that are waiting to be archived
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ArchivalBacklog {
+    /// Flushed segments still held locally
+    pub local_segments: usize,
+    /// Local segments older than the tiering age threshold
+    pub pending_segments: usize,
+    /// Size of the pending segments in bytes
+    pub pending_bytes: usize,
+}
+
+/// State of the S3 archive as seen by this node
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct StorageStats {
+    /// Bucket segments are archived to
+    pub bucket: String,
+    /// Segments waiting to be archived
+    pub backlog: ArchivalBacklog,
+    /// S3 credentials in use
+    pub credentials: CredentialStatus,
+}
+
+/// Local segments and archival settings of one storage class
+#[derive(Clone)]
+struct ClassPipeline {
+    /// Name of the class
+    name: String,
+    /// Settings of the class
+    class: StorageClass,
+    /// Segments holding the class's keys
+    segments: Arc<SegmentManager>,
+    /// Secondary bucket archived segments are copied to
+    replica: Option<Arc<S3Storage>>,
 }
 
 /// Archival manager for automatic segment archival to S3
@@ -83,6 +209,12 @@ pub struct ArchivalManager {
     segment_cache: Arc<RwLock<HashMap<SegmentId, Segment>>>,
     /// Cache for segment metadata
     metadata_cache: Arc<RwLock<HashMap<SegmentId, SegmentMetadata>>>,
+    /// Tenant data keys for multi-tenant mode (segments are not encrypted if unset)
+    keyring: Option<Arc<TenantKeyring>>,
+    /// Manifest recording archived segments (optional)
+    manifest: Option<Arc<ManifestManager>>,
+    /// Storage classes with their own segments, routed by key prefix
+    classes: Vec<ClassPipeline>,
 }
 
 impl ArchivalManager {
@@ -100,31 +232,151 @@ impl ArchivalManager {
             policy,
             segment_cache: Arc::new(RwLock::new(HashMap::new())),
             metadata_cache: Arc::new(RwLock::new(HashMap::new())),
+            keyring: None,
+            manifest: None,
+            classes: Vec::new(),
         })
     }
 
+    /// Encrypt archived segments with per-tenant data keys
+    pub fn with_keyring(mut self, keyring: Arc<TenantKeyring>) -> Self {
+        self.keyring = Some(keyring);
+        self
+    }
+
+    /// Record archived segments in the manifest
+    pub fn with_manifest(mut self, manifest: Arc<ManifestManager>) -> Self {
+        self.manifest = Some(manifest);
+        self
+    }
+
+    /// Route keys of the storage class `name` to `segments`, archived with the
+    /// class's settings (see [`crate::storage::classes`])
+    pub fn with_storage_class(
+        mut self,
+        name: &str,
+        class: StorageClass,
+        segments: Arc<SegmentManager>,
+    ) -> Self {
+        let replica = class
+            .replicate_to_bucket
+            .as_deref()
+            .map(|bucket| Arc::new(self.s3_storage.with_bucket(bucket)));
+        self.classes.push(ClassPipeline {
+            name: name.to_string(),
+            class,
+            segments,
+            replica,
+        });
+        self
+    }
+
+    /// Write a key-value pair into the local segments of its storage class
+    pub fn put(&self, key: Key, value: Value) -> Result<()> {
+        match self.class_for(&key) {
+            Some(pipeline) => pipeline.segments.put(key, value),
+            None => self.segment_manager.put(key, value),
+        }
+    }
+
+    /// Local segment managers, the default pipeline's first
+    pub fn segment_managers(&self) -> Vec<&Arc<SegmentManager>> {
+        self.pipelines()
+            .into_iter()
+            .map(|(segments, _)| segments)
+            .collect()
+    }
+
+    /// Storage class `key` is routed to, `None` for the default pipeline
+    fn class_for(&self, key: &[u8]) -> Option<&ClassPipeline> {
+        self.classes
+            .iter()
+            .filter_map(|pipeline| Some((pipeline.class.matches(key)?, pipeline)))
+            .max_by_key(|(len, _)| *len)
+            .map(|(_, pipeline)| pipeline)
+    }
+
+    /// Every pipeline's segments with its storage class, the default pipeline first
+    fn pipelines(&self) -> Vec<(&Arc<SegmentManager>, Option<&ClassPipeline>)> {
+        std::iter::once((&self.segment_manager, None))
+            .chain(
+                self.classes
+                    .iter()
+                    .map(|pipeline| (&pipeline.segments, Some(pipeline))),
+            )
+            .collect()
+    }
+
+    /// Age in seconds after which segments of `class` are archived
+    fn archive_after(&self, class: Option<&ClassPipeline>) -> u64 {
+        class
+            .and_then(|pipeline| pipeline.class.archive_after_secs)
+            .unwrap_or(self.policy.age_threshold_secs)
+    }
+
+    /// Manifest recording archived segments, if configured
+    pub fn manifest(&self) -> Option<&Arc<ManifestManager>> {
+        self.manifest.as_ref()
+    }
+
+    /// Inject the faults armed on `faults` into S3 requests (for testing)
+    pub fn with_fault_injector(mut self, faults: Arc<S3FaultInjector>) -> Self {
+        self.s3_storage = Arc::new((*self.s3_storage).clone().with_fault_injector(faults));
+        self
+    }
+
     /// Archive a segment to S3 with optional compression
+    ///
+    /// The segment data is uploaded (and, if the policy asks for it, read back and
+    /// compared) before its metadata and manifest entry are written, so neither ever
+    /// refers to an upload that did not complete. Segments of a storage class are
+    /// archived with the class's compression setting and copied to its replication
+    /// bucket. With a segment directory, each step is recorded as a roll intent first,
+    /// so a crash part way is reconciled by
+    /// [`recover_interrupted_rolls`](Self::recover_interrupted_rolls).
     pub async fn archive_segment(&self, segment: &Segment) -> Result<SegmentMetadata> {
         let original_size = segment.size;
         let entry_count = segment.len();
+        let class = segment
+            .data
+            .keys()
+            .next()
+            .and_then(|key| self.class_for(key));
+        let compress = class.map_or(self.policy.enable_compression, |pipeline| {
+            pipeline.class.compression
+        });
+        let segments = class.map_or(&self.segment_manager, |pipeline| &pipeline.segments);
 
         // Compute Merkle root for verification
         let merkle_root = segment
             .compute_merkle_root()
             .unwrap_or_else(|| vec![0u8; 32]); // Use zeros for empty segments
 
-        // Serialize segment
-        let data = segment.serialize()?;
+        // Seal per tenant in multi-tenant mode, otherwise serialize the whole segment
+        let (final_data, is_compressed, key_ids) = match &self.keyring {
+            Some(keyring) => {
+                let (sealed, key_ids) = self.seal_segment(keyring, segment, compress)?;
+                (sealed, compress, key_ids)
+            }
+            None => {
+                let data = segment.serialize()?;
 
-        // Compress if enabled
-        let (final_data, is_compressed, compressed_size) = if self.policy.enable_compression {
-            let compressed = self.compress_data(&data)?;
-            let compressed_size = compressed.len();
-            (compressed, true, compressed_size)
-        } else {
-            let size = data.len();
-            (data, false, size)
+                // Compress if enabled
+                if compress {
+                    (self.compress_data(&data)?, true, Vec::new())
+                } else {
+                    (data, false, Vec::new())
+                }
+            }
         };
+        let compressed_size = final_data.len();
+
+        // Index the entries' checksums, except for sealed segments whose value
+        // checksums would leak information about the plaintext
+        let checksums = self
+            .keyring
+            .is_none()
+            .then(|| ChecksumIndex::build(&segment.data).encode());
 
         // Create metadata
         let metadata = SegmentMetadata {
@@ -136,19 +388,57 @@ impl ArchivalManager {
             is_compressed,
             entry_count,
             merkle_root,
+            is_encrypted: self.keyring.is_some(),
+            key_ids,
+            storage_class: class.map(|pipeline| pipeline.name.clone()),
+            has_checksum_index: checksums.is_some(),
         };
 
-        // Store segment data
-        self.s3_storage
-            .put_object(&Self::segment_key(segment.segment_id), final_data)
-            .await?;
+        // Store segment data, copying it to the class's replication bucket
+        segments.record_roll_step(segment.segment_id, RollStep::Uploading)?;
+        let segment_key = Self::segment_key(segment.segment_id);
+        let replica = class.and_then(|pipeline| pipeline.replica.as_ref());
+        if let Some(replica) = replica {
+            replica.put_object(&segment_key, final_data.clone()).await?;
+        }
+        let expected = self.policy.verify_uploads.then(|| final_data.clone());
+        self.s3_storage.put_object(&segment_key, final_data).await?;
+        if let Some(expected) = expected {
+            self.verify_upload(&segment_key, &expected).await?;
+        }
+
+        // Store the checksum index before the metadata that announces it
+        if let Some(checksums) = checksums {
+            let checksums_key = Self::checksums_key(segment.segment_id);
+            if let Some(replica) = replica {
+                replica
+                    .put_object(&checksums_key, checksums.clone())
+                    .await?;
+            }
+            self.s3_storage
+                .put_object(&checksums_key, checksums)
+                .await?;
+        }
 
         // Store metadata
         let metadata_json =
             serde_json::to_vec(&metadata).map_err(|e| ScribeError::Serialization(e.to_string()))?;
+        let metadata_key = Self::metadata_key(segment.segment_id);
+        if let Some(replica) = replica {
+            replica
+                .put_object(&metadata_key, metadata_json.clone())
+                .await?;
+        }
         self.s3_storage
-            .put_object(&Self::metadata_key(segment.segment_id), metadata_json)
+            .put_object(&metadata_key, metadata_json)
             .await?;
+        segments.record_roll_step(segment.segment_id, RollStep::Uploaded)?;
+
+        // Record the segment and its data keys in the manifest
+        if let Some(manifest) = &self.manifest {
+            manifest.remove_segment(segment.segment_id).await?;
+            manifest.add_segment(metadata.manifest_entry()).await?;
+        }
 
         // Cache metadata
         self.metadata_cache
@@ -169,6 +459,23 @@ impl ArchivalManager {
             }
         }
 
+        let Some(segment) = self.fetch_segment(segment_id).await? else {
+            return Ok(None);
+        };
+
+        // Cache the segment
+        self.segment_cache
+            .write()
+            .await
+            .insert(segment_id, segment.clone());
+
+        Ok(Some(segment))
+    }
+
+    /// Download and decode a segment from S3 without going through the segment cache
+    ///
+    /// Returns `None` until both the segment data and its metadata are stored.
+    pub async fn fetch_segment(&self, segment_id: SegmentId) -> Result<Option<Segment>> {
         // Get metadata
         let metadata = self.get_metadata(segment_id).await?;
         if metadata.is_none() {
@@ -187,23 +494,111 @@ impl ArchivalManager {
         }
         let data = data.unwrap();
 
-        // Decompress if needed
-        let final_data = if metadata.is_compressed {
-            self.decompress_data(&data)?
-        } else {
-            data
+        self.decode_segment(&metadata, data).map(Some)
+    }
+
+    /// Download an archived segment and re-validate it against its recorded roots
+    ///
+    /// Bypasses the caches, recomputes the Merkle root of the downloaded data and
+    /// compares it with the root in the segment metadata and, if the segment is
+    /// tracked there, in the manifest. Returns `None` if the segment is not archived.
+    pub async fn verify_segment(
+        &self,
+        segment_id: SegmentId,
+    ) -> Result<Option<SegmentVerification>> {
+        let started = Instant::now();
+
+        let Some(metadata) = self
+            .s3_storage
+            .get_object(&Self::metadata_key(segment_id))
+            .await?
+        else {
+            return Ok(None);
+        };
+        let metadata: SegmentMetadata = serde_json::from_slice(&metadata)
+            .map_err(|e| ScribeError::Serialization(e.to_string()))?;
+        let Some(data) = self
+            .s3_storage
+            .get_object(&Self::segment_key(segment_id))
+            .await?
+        else {
+            return Ok(None);
         };
 
-        // Deserialize segment
-        let segment = Segment::deserialize(&final_data)?;
+        let manifest_root = match &self.manifest {
+            Some(manifest) => manifest
+                .get_segment(segment_id)
+                .await
+                .map(|entry| entry.merkle_root),
+            None => None,
+        };
+        let downloaded_bytes = data.len();
 
-        // Cache the segment
-        self.segment_cache
-            .write()
-            .await
-            .insert(segment_id, segment.clone());
+        let mut diff = ChecksumDiff::default();
+        let (status, computed_root, entry_count, error) = match self.decode_segment(&metadata, data)
+        {
+            Ok(segment) => {
+                let root = segment
+                    .compute_merkle_root()
+                    .unwrap_or_else(|| vec![0u8; 32]);
+                let status = if self.has_destroyed_keys(&metadata)? {
+                    VerificationStatus::Shredded
+                } else if root == metadata.merkle_root
+                    && manifest_root.as_ref().is_none_or(|r| *r == root)
+                {
+                    VerificationStatus::Verified
+                } else {
+                    VerificationStatus::Mismatch
+                };
+                if status == VerificationStatus::Mismatch {
+                    if let Some(found) = self.checksum_diff(&metadata, &segment).await? {
+                        diff = found;
+                    }
+                }
+                (status, Some(hex::encode(root)), segment.len(), None)
+            }
+            Err(e) => (VerificationStatus::Corrupt, None, 0, Some(e.to_string())),
+        };
 
-        Ok(Some(segment))
+        Ok(Some(SegmentVerification {
+            segment_id,
+            status,
+            computed_root,
+            metadata_root: hex::encode(&metadata.merkle_root),
+            manifest_root: manifest_root.map(hex::encode),
+            entry_count,
+            downloaded_bytes,
+            error,
+            corrupt_keys: diff
+                .corrupt
+                .iter()
+                .map(|key| log_key(key).to_string())
+                .collect(),
+            missing_entries: diff.missing,
+            duration_ms: started.elapsed().as_millis() as u64,
+        }))
+    }
+
+    /// Compare a downloaded segment with its checksum index
+    ///
+    /// Names the keys whose entries changed, so repairs can be limited to them.
+    /// `None` if the segment was archived without an index or the index is gone.
+    pub async fn checksum_diff(
+        &self,
+        metadata: &SegmentMetadata,
+        segment: &Segment,
+    ) -> Result<Option<ChecksumDiff>> {
+        if !metadata.has_checksum_index {
+            return Ok(None);
+        }
+        let Some(data) = self
+            .s3_storage
+            .get_object(&Self::checksums_key(metadata.segment_id))
+            .await?
+        else {
+            return Ok(None);
+        };
+        Ok(Some(ChecksumIndex::decode(&data)?.compare(&segment.data)))
     }
 
     /// Get metadata for a segment
@@ -239,30 +634,180 @@ impl ArchivalManager {
     }
 
     /// Archive old segments based on tiering policy
+    ///
+    /// Segments of a storage class use the class's age threshold when it sets one.
     pub async fn archive_old_segments(&self) -> Result<Vec<SegmentId>> {
         let mut archived_ids = Vec::new();
         let now = current_timestamp();
-        let threshold = now.saturating_sub(self.policy.age_threshold_secs);
+        let mut failures = Vec::new();
 
-        // Get flushed segments from segment manager
-        let segments = self.segment_manager.get_flushed_segments()?;
+        for (segment_manager, class) in self.pipelines() {
+            let threshold = now.saturating_sub(self.archive_after(class));
 
-        for segment in segments {
-            if segment.timestamp < threshold {
-                // Archive the segment
-                self.archive_segment(&segment).await?;
-                archived_ids.push(segment.segment_id);
+            // Close an active segment that outlived its maximum age so it can be archived
+            segment_manager.roll_if_expired()?;
+
+            // Get flushed segments from segment manager
+            let segments = segment_manager.get_flushed_segments()?;
+            let mut pipeline_ids = Vec::new();
+
+            for segment in segments {
+                if segment.timestamp < threshold {
+                    // Archive the segment, moving on to the next one if it fails
+                    match self.archive_segment(&segment).await {
+                        Ok(_) => pipeline_ids.push(segment.segment_id),
+                        Err(e) => failures.push(format!("segment {}: {}", segment.segment_id, e)),
+                    }
+                }
+            }
+
+            // Drop only the archived segments; the rest stay local and are retried next time
+            if !pipeline_ids.is_empty() {
+                segment_manager.remove_flushed(&pipeline_ids)?;
             }
+            archived_ids.extend(pipeline_ids);
         }
 
-        // Clear archived segments from local storage
-        if !archived_ids.is_empty() {
-            self.segment_manager.clear_flushed()?;
+        if !failures.is_empty() {
+            return Err(ScribeError::Storage(format!(
+                "Failed to archive {} segment(s), archived {}: {}",
+                failures.len(),
+                archived_ids.len(),
+                failures.join("; ")
+            )));
         }
 
         Ok(archived_ids)
     }
 
+    /// Report the segments waiting to be archived under the tiering policy
+    pub fn backlog(&self) -> Result<ArchivalBacklog> {
+        let now = current_timestamp();
+        let mut backlog = ArchivalBacklog::default();
+        for (segment_manager, class) in self.pipelines() {
+            let threshold = now.saturating_sub(self.archive_after(class));
+            let segments = segment_manager.get_flushed_segments()?;
+            let pending: Vec<&Segment> = segments
+                .iter()
+                .filter(|segment| segment.timestamp < threshold)
+                .collect();
+
+            backlog.local_segments += segments.len();
+            backlog.pending_segments += pending.len();
+            backlog.pending_bytes += pending.iter().map(|segment| segment.size).sum::<usize>();
+        }
+        Ok(backlog)
+    }
+
+    /// Report the archive's bucket, backlog and credentials
+    pub fn storage_stats(&self) -> Result<StorageStats> {
+        Ok(StorageStats {
+            bucket: self.s3_storage.bucket().to_string(),
+            backlog: self.backlog()?,
+            credentials: self.s3_storage.credential_status(),
+        })
+    }
+
+    /// Cross-check the manifest against the local segments, fixing them if `repair`
+    ///
+    /// See [`consistency`](crate::storage::consistency) for what is reported and how
+    /// repairs work. Fails if no manifest is attached.
+    pub async fn check_consistency(&self, repair: bool) -> Result<ConsistencyReport> {
+        let manifest = self.manifest.as_ref().ok_or_else(|| {
+            ScribeError::Storage("No manifest attached to the archival manager".to_string())
+        })?;
+        let mut local = Vec::new();
+        for (segment_manager, _) in self.pipelines() {
+            local.extend(segment_manager.flushed_segment_ids()?);
+        }
+        let entries = manifest.get_segments().await;
+        let (missing_locally, not_in_manifest) = consistency::compare(
+            entries.iter().map(|entry| entry.segment_id),
+            local.iter().copied(),
+        );
+
+        let mut report = ConsistencyReport {
+            local_segments: local.len(),
+            manifest_segments: entries.len(),
+            missing_locally,
+            ..ConsistencyReport::default()
+        };
+        let mut archived = Vec::new();
+        for segment_id in not_in_manifest {
+            match self.get_metadata(segment_id).await? {
+                Some(metadata) => {
+                    report.unregistered.push(segment_id);
+                    archived.push(metadata);
+                }
+                None => report.pending_archival.push(segment_id),
+            }
+        }
+        if !repair {
+            return Ok(report);
+        }
+
+        for &segment_id in &report.missing_locally {
+            if self.retrieve_segment(segment_id).await?.is_some() {
+                report.fetched.push(segment_id);
+            } else {
+                manifest.remove_segment(segment_id).await?;
+                report.dropped.push(segment_id);
+            }
+        }
+        for metadata in archived {
+            manifest.add_segment(metadata.manifest_entry()).await?;
+            report.registered.push(metadata.segment_id);
+        }
+        Ok(report)
+    }
+
+    /// Reconcile segment rolls a crash interrupted, before archival starts
+    ///
+    /// A segment whose metadata reached S3 was uploaded completely: it is registered
+    /// in the manifest and its local copy removed. Otherwise whatever part of it was
+    /// uploaded is deleted, and the local segment is archived again later. The report
+    /// includes the rolls reconciled when the segment directories were opened.
+    pub async fn recover_interrupted_rolls(&self) -> Result<RollRecovery> {
+        let mut recovery = RollRecovery::default();
+        for (segment_manager, class) in self.pipelines() {
+            recovery.merge(segment_manager.recovered_writes().clone());
+            for intent in segment_manager.interrupted_rolls()? {
+                let segment_id = intent.segment_id;
+                match self.get_metadata(segment_id).await? {
+                    Some(metadata) => {
+                        if let Some(manifest) = &self.manifest {
+                            manifest.remove_segment(segment_id).await?;
+                            manifest.add_segment(metadata.manifest_entry()).await?;
+                        }
+                        segment_manager.remove_flushed(&[segment_id])?;
+                        recovery.completed.push(segment_id);
+                    }
+                    None => {
+                        if let Some(replica) = class.and_then(|p| p.replica.as_deref()) {
+                            Self::delete_segment_objects(replica, segment_id).await?;
+                        }
+                        self.delete_archived_segment(segment_id).await?;
+                        recovery.rolled_back.push(segment_id);
+                    }
+                }
+                segment_manager.clear_roll(segment_id)?;
+                tracing::info!(
+                    "Recovered the interrupted archival of segment {} ({:?} step)",
+                    segment_id,
+                    intent.step
+                );
+            }
+        }
+        Ok(recovery)
+    }
+
+    /// Rebuild the S3 client whenever the configured credential source rotates
+    ///
+    /// Returns `None` when the credentials are static.
+    pub fn start_credential_watcher(&self) -> Option<tokio::task::JoinHandle<()>> {
+        self.s3_storage.start_credential_watcher()
+    }
+
     /// Start automatic archival background task
     pub fn start_auto_archival(&self) -> tokio::task::JoinHandle<()> {
         let manager = self.clone_arc();
@@ -275,7 +820,10 @@ impl ArchivalManager {
                 ticker.tick().await;
 
                 if let Err(e) = manager.archive_old_segments().await {
-                    eprintln!("Archival error: {}", e);
+                    tracing::warn!("Archival error: {}", e);
+                }
+                if let Err(e) = manager.enforce_retention().await {
+                    tracing::warn!("Retention error: {}", e);
                 }
             }
         })
@@ -283,8 +831,11 @@ impl ArchivalManager {
 
     /// Read-through: Get value from local or S3
     pub async fn get_value(&self, segment_id: SegmentId, key: &[u8]) -> Result<Option<Vec<u8>>> {
-        // Try local segment manager first
-        if let Ok(Some(value)) = self.segment_manager.get(&key.to_vec()) {
+        // Try the local segments of the key's pipeline first
+        let segment_manager = self
+            .class_for(key)
+            .map_or(&self.segment_manager, |pipeline| &pipeline.segments);
+        if let Ok(Some(value)) = segment_manager.get(&key.to_vec()) {
             return Ok(Some(value));
         }
 
@@ -296,6 +847,70 @@ impl ArchivalManager {
         Ok(None)
     }
 
+    /// Get a value from segment `segment_id` without contacting S3
+    ///
+    /// Looks in the segments already fetched into the segment cache and in the flushed
+    /// segments still held locally; `None` if neither holds the segment (or the key).
+    pub async fn local_value(&self, segment_id: SegmentId, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let key = key.to_vec();
+        if let Some(segment) = self.segment_cache.read().await.get(&segment_id) {
+            return Ok(segment.get(&key).cloned());
+        }
+        self.class_for(&key)
+            .map_or(&self.segment_manager, |pipeline| &pipeline.segments)
+            .get_in_segment(segment_id, &key)
+    }
+
+    /// Delete archived segments that outlived their storage class's retention
+    ///
+    /// The segments are removed from the archive, the replication bucket and the
+    /// manifest. Returns the IDs of the deleted segments.
+    pub async fn enforce_retention(&self) -> Result<Vec<SegmentId>> {
+        if self
+            .classes
+            .iter()
+            .all(|pipeline| pipeline.class.retention_secs.is_none())
+        {
+            return Ok(Vec::new());
+        }
+
+        let now = current_timestamp();
+        let mut expired = Vec::new();
+        for segment_id in self.list_archived_segments().await? {
+            let Some(metadata) = self.get_metadata(segment_id).await? else {
+                continue;
+            };
+            let Some(pipeline) = metadata
+                .storage_class
+                .as_deref()
+                .and_then(|name| self.classes.iter().find(|p| p.name == name))
+            else {
+                continue;
+            };
+            let Some(retention) = pipeline.class.retention_secs else {
+                continue;
+            };
+            if metadata.archived_at.saturating_add(retention) > now {
+                continue;
+            }
+
+            if let Some(replica) = &pipeline.replica {
+                Self::delete_segment_objects(replica, segment_id).await?;
+            }
+            self.delete_archived_segment(segment_id).await?;
+            if let Some(manifest) = &self.manifest {
+                manifest.remove_segment(segment_id).await?;
+            }
+            tracing::info!(
+                "Deleted segment {} past the retention of storage class '{}'",
+                segment_id,
+                pipeline.name
+            );
+            expired.push(segment_id);
+        }
+        Ok(expired)
+    }
+
     /// List all archived segment IDs
     pub async fn list_archived_segments(&self) -> Result<Vec<SegmentId>> {
         self.s3_storage.list_segments().await
@@ -303,15 +918,8 @@ impl ArchivalManager {
 
     /// Delete archived segment
     pub async fn delete_archived_segment(&self, segment_id: SegmentId) -> Result<()> {
-        // Delete segment data
-        self.s3_storage
-            .delete_object(&Self::segment_key(segment_id))
-            .await?;
-
-        // Delete metadata
-        self.s3_storage
-            .delete_object(&Self::metadata_key(segment_id))
-            .await?;
+        // Delete segment data, checksum index and metadata
+        Self::delete_segment_objects(&self.s3_storage, segment_id).await?;
 
         // Remove from cache
         self.segment_cache.write().await.remove(&segment_id);
@@ -320,6 +928,156 @@ impl ArchivalManager {
         Ok(())
     }
 
+    /// Delete the objects of an archived segment from `storage`
+    async fn delete_segment_objects(storage: &S3Storage, segment_id: SegmentId) -> Result<()> {
+        for key in [
+            Self::segment_key(segment_id),
+            Self::checksums_key(segment_id),
+            Self::metadata_key(segment_id),
+        ] {
+            storage.delete_object(&key).await?;
+        }
+        Ok(())
+    }
+
+    /// Offboard a tenant by destroying its data key (crypto-shredding)
+    ///
+    /// Archived segments are left untouched, but the tenant's entries in them can no
+    /// longer be decrypted. Returns the ID of the destroyed key.
+    pub async fn shred_tenant(&self, tenant: &str) -> Result<Option<String>> {
+        let keyring = self.keyring.as_ref().ok_or_else(|| {
+            ScribeError::Encryption("Segment encryption is not enabled".to_string())
+        })?;
+
+        let key_id = keyring.destroy_tenant_key(tenant)?;
+        if key_id.is_some() {
+            // Drop decrypted copies of the tenant's data
+            self.segment_cache.write().await.clear();
+        }
+        Ok(key_id)
+    }
+
+    /// Decode a downloaded segment object
+    fn decode_segment(&self, metadata: &SegmentMetadata, data: Vec<u8>) -> Result<Segment> {
+        if metadata.is_encrypted {
+            return self.open_segment(metadata, &data);
+        }
+
+        // Decompress if needed
+        let final_data = if metadata.is_compressed {
+            self.decompress_data(&data)?
+        } else {
+            data
+        };
+
+        // Deserialize segment
+        Segment::deserialize(&final_data)
+    }
+
+    /// Whether any data key the segment was sealed with has been destroyed
+    fn has_destroyed_keys(&self, metadata: &SegmentMetadata) -> Result<bool> {
+        let Some(keyring) = &self.keyring else {
+            return Ok(false);
+        };
+        for key_id in &metadata.key_ids {
+            if !keyring.has_key(key_id)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Split a segment by tenant and seal each part with the tenant's data key
+    ///
+    /// Entries outside every tenant namespace are stored unencrypted, and entries of
+    /// tenants whose key was destroyed are dropped. Returns the serialized sections
+    /// and the IDs of the keys used.
+    fn seal_segment(
+        &self,
+        keyring: &TenantKeyring,
+        segment: &Segment,
+        compress: bool,
+    ) -> Result<(Vec<u8>, Vec<String>)> {
+        let mut parts: BTreeMap<Option<String>, Segment> = BTreeMap::new();
+        for (key, value) in &segment.data {
+            parts
+                .entry(keyring.tenant_for_key(key))
+                .or_insert_with(|| Segment::new(segment.segment_id))
+                .put(key.clone(), value.clone());
+        }
+
+        let aad = segment.segment_id.to_be_bytes();
+        let mut sections = Vec::with_capacity(parts.len());
+        let mut key_ids = Vec::new();
+        for (tenant, part) in parts {
+            if let Some(tenant) = &tenant {
+                if keyring.active_key_id(tenant)?.is_none() {
+                    tracing::warn!(
+                        "Dropping {} entries of shredded tenant '{}' from segment {}",
+                        part.len(),
+                        tenant,
+                        segment.segment_id
+                    );
+                    continue;
+                }
+            }
+
+            let mut data = part.serialize()?;
+            if compress {
+                data = self.compress_data(&data)?;
+            }
+
+            match tenant {
+                Some(tenant) => {
+                    let sealed = keyring.seal(&tenant, &data, &aad)?;
+                    key_ids.push(sealed.key_id.clone());
+                    sections.push(SegmentSection::Sealed(sealed));
+                }
+                None => sections.push(SegmentSection::Plain(data)),
+            }
+        }
+
+        Ok((bincode::serialize(&sections)?, key_ids))
+    }
+
+    /// Reassemble a segment from its sealed sections
+    ///
+    /// Sections sealed with a destroyed key are skipped, so a shredded tenant's
+    /// entries are simply absent from the returned segment.
+    fn open_segment(&self, metadata: &SegmentMetadata, data: &[u8]) -> Result<Segment> {
+        let keyring = self.keyring.as_ref().ok_or_else(|| {
+            ScribeError::Encryption(format!(
+                "Segment {} is encrypted but no keyring is configured",
+                metadata.segment_id
+            ))
+        })?;
+
+        let sections: Vec<SegmentSection> = bincode::deserialize(data)?;
+        let aad = metadata.segment_id.to_be_bytes();
+        let mut segment = Segment::new(metadata.segment_id);
+        segment.timestamp = metadata.created_at;
+
+        for section in sections {
+            let data = match section {
+                SegmentSection::Plain(data) => data,
+                SegmentSection::Sealed(sealed) => match keyring.open_sealed(&sealed, &aad)? {
+                    Some(data) => data,
+                    None => continue,
+                },
+            };
+            let data = if metadata.is_compressed {
+                self.decompress_data(&data)?
+            } else {
+                data
+            };
+            for (key, value) in Segment::deserialize(&data)?.data {
+                segment.put(key, value);
+            }
+        }
+
+        Ok(segment)
+    }
+
     /// Compress data using gzip
     fn compress_data(&self, data: &[u8]) -> Result<Vec<u8>> {
         let mut encoder =
@@ -347,11 +1105,37 @@ impl ArchivalManager {
         format!("segments/segment-{:016x}.bin", segment_id)
     }
 
+    /// Read an uploaded object back and compare it with what was sent
+    ///
+    /// A mismatching object is deleted so a later upload starts clean.
+    async fn verify_upload(&self, key: &str, expected: &[u8]) -> Result<()> {
+        let stored = self.s3_storage.get_object(key).await.map_err(|e| {
+            ScribeError::Storage(format!("Could not verify upload of {}: {}", key, e))
+        })?;
+        if stored.as_deref() == Some(expected) {
+            return Ok(());
+        }
+
+        // Best effort: the segment stays local either way
+        let _ = self.s3_storage.delete_object(key).await;
+        Err(ScribeError::Storage(format!(
+            "Upload of {} does not match the data sent ({} bytes sent, {} stored)",
+            key,
+            expected.len(),
+            stored.map_or(0, |data| data.len())
+        )))
+    }
+
     /// Generate S3 key for segment metadata
     fn metadata_key(segment_id: SegmentId) -> String {
         format!("segments/segment-{:016x}.meta.json", segment_id)
     }
 
+    /// Generate S3 key for the checksum index of a segment
+    fn checksums_key(segment_id: SegmentId) -> String {
+        format!("segments/segment-{:016x}.sums", segment_id)
+    }
+
     /// Clone as Arc for background tasks
     fn clone_arc(&self) -> Arc<Self> {
         Arc::new(Self {
@@ -360,6 +1144,9 @@ impl ArchivalManager {
             policy: self.policy.clone(),
             segment_cache: self.segment_cache.clone(),
             metadata_cache: self.metadata_cache.clone(),
+            keyring: self.keyring.clone(),
+            manifest: self.manifest.clone(),
+            classes: self.classes.clone(),
         })
     }
 }
@@ -375,6 +1162,116 @@ fn current_timestamp() -> u64 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::security::MasterKey;
+
+    /// Manager whose S3 client is never used (sealing works without a bucket)
+    async fn encrypted_manager() -> (ArchivalManager, Arc<TenantKeyring>) {
+        let config = S3StorageConfig {
+            bucket: "unused".to_string(),
+            endpoint: Some("http://127.0.0.1:9".to_string()),
+            access_key_id: Some("key".to_string()),
+            secret_access_key: Some("secret".to_string()),
+            ..S3StorageConfig::default()
+        };
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let keyring = Arc::new(TenantKeyring::open(
+            db.open_tree("keys").unwrap(),
+            MasterKey::generate(),
+        ));
+        keyring.register_tenant("acme", b"acme/").unwrap();
+        keyring.register_tenant("globex", b"globex/").unwrap();
+
+        let manager = ArchivalManager::new(
+            config,
+            Arc::new(SegmentManager::new()),
+            TieringPolicy::default(),
+        )
+        .await
+        .unwrap()
+        .with_keyring(Arc::clone(&keyring));
+        (manager, keyring)
+    }
+
+    fn encrypted_metadata(segment: &Segment, key_ids: Vec<String>) -> SegmentMetadata {
+        SegmentMetadata {
+            segment_id: segment.segment_id,
+            created_at: segment.timestamp,
+            archived_at: segment.timestamp,
+            original_size: segment.size,
+            compressed_size: 0,
+            is_compressed: true,
+            entry_count: segment.len(),
+            merkle_root: Vec::new(),
+            is_encrypted: true,
+            key_ids,
+            storage_class: None,
+            has_checksum_index: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_seal_and_open_segment() {
+        let (manager, keyring) = encrypted_manager().await;
+        let mut segment = Segment::new(7);
+        segment.put(b"acme/a".to_vec(), b"1".to_vec());
+        segment.put(b"globex/b".to_vec(), b"2".to_vec());
+        segment.put(b"other".to_vec(), b"3".to_vec());
+
+        let (sealed, key_ids) = manager.seal_segment(&keyring, &segment, true).unwrap();
+        assert_eq!(key_ids.len(), 2);
+        assert!(!sealed.windows(b"acme/a".len()).any(|w| w == b"acme/a"));
+
+        let metadata = encrypted_metadata(&segment, key_ids);
+        let opened = manager.open_segment(&metadata, &sealed).unwrap();
+        assert_eq!(opened.data, segment.data);
+        assert_eq!(opened.timestamp, segment.timestamp);
+
+        // Sections are bound to their segment ID
+        let mut moved = metadata.clone();
+        moved.segment_id = 8;
+        assert!(manager.open_segment(&moved, &sealed).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_shredded_tenant_is_absent() {
+        let (manager, keyring) = encrypted_manager().await;
+        let mut segment = Segment::new(9);
+        segment.put(b"acme/a".to_vec(), b"1".to_vec());
+        segment.put(b"globex/b".to_vec(), b"2".to_vec());
+
+        let (sealed, key_ids) = manager.seal_segment(&keyring, &segment, true).unwrap();
+        let acme_key = keyring.active_key_id("acme").unwrap();
+        assert_eq!(manager.shred_tenant("acme").await.unwrap(), acme_key);
+
+        let opened = manager
+            .open_segment(&encrypted_metadata(&segment, key_ids), &sealed)
+            .unwrap();
+        assert_eq!(opened.get(&b"acme/a".to_vec()), None);
+        assert_eq!(opened.get(&b"globex/b".to_vec()), Some(&b"2".to_vec()));
+
+        // Later segments drop the shredded tenant's entries instead of failing
+        let (sealed, key_ids) = manager.seal_segment(&keyring, &segment, true).unwrap();
+        assert_eq!(
+            key_ids,
+            vec![keyring.active_key_id("globex").unwrap().unwrap()]
+        );
+        let opened = manager
+            .open_segment(&encrypted_metadata(&segment, key_ids), &sealed)
+            .unwrap();
+        assert_eq!(opened.len(), 1);
+    }
+
+    #[test]
+    fn test_metadata_manifest_entry() {
+        let mut segment = Segment::new(3);
+        segment.put(b"k".to_vec(), b"v".to_vec());
+        let metadata = encrypted_metadata(&segment, vec!["acme-1".to_string()]);
+
+        let entry = metadata.manifest_entry();
+        assert_eq!(entry.segment_id, 3);
+        assert_eq!(entry.size, segment.size);
+        assert_eq!(entry.key_ids, vec!["acme-1".to_string()]);
+    }
 
     #[test]
     fn test_default_tiering_policy() {
@@ -383,6 +1280,98 @@ mod tests {
         assert!(policy.enable_compression);
         assert_eq!(policy.compression_level, 6);
         assert!(policy.enable_auto_archival);
+        assert!(policy.verify_uploads);
+    }
+
+    #[tokio::test]
+    async fn test_failed_archival_keeps_segments_local() {
+        let config = S3StorageConfig {
+            bucket: "unused".to_string(),
+            endpoint: Some("http://127.0.0.1:9".to_string()),
+            access_key_id: Some("key".to_string()),
+            secret_access_key: Some("secret".to_string()),
+            max_retries: 0,
+            ..S3StorageConfig::default()
+        };
+        let segments = Arc::new(SegmentManager::new());
+        let policy = TieringPolicy {
+            age_threshold_secs: 0,
+            ..TieringPolicy::default()
+        };
+        let faults = Arc::new(S3FaultInjector::new());
+        let manager = ArchivalManager::new(config, Arc::clone(&segments), policy)
+            .await
+            .unwrap()
+            .with_fault_injector(Arc::clone(&faults));
+
+        segments.put(b"key".to_vec(), b"value".to_vec()).unwrap();
+        segments.flush_active().unwrap();
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+
+        faults.set_outage(true);
+        let err = manager.archive_old_segments().await.unwrap_err();
+        assert!(err.to_string().contains("Failed to archive 1 segment"));
+        assert_eq!(segments.flushed_count().unwrap(), 1);
+        assert_eq!(
+            manager.get_value(0, b"key").await.unwrap(),
+            Some(b"value".to_vec())
+        );
+        let segment_id = segments.flushed_segment_ids().unwrap()[0];
+        assert_eq!(
+            manager.local_value(segment_id, b"key").await.unwrap(),
+            Some(b"value".to_vec())
+        );
+        assert!(faults.injected_faults() > 0);
+    }
+
+    #[tokio::test]
+    async fn test_storage_class_routing() {
+        let config = S3StorageConfig {
+            bucket: "unused".to_string(),
+            endpoint: Some("http://127.0.0.1:9".to_string()),
+            access_key_id: Some("key".to_string()),
+            secret_access_key: Some("secret".to_string()),
+            ..S3StorageConfig::default()
+        };
+        let segments = Arc::new(SegmentManager::new());
+        let logs = Arc::new(SegmentManager::new());
+        let class = StorageClass {
+            prefixes: vec!["logs/".to_string()],
+            compression: false,
+            archive_after_secs: Some(3600),
+            retention_secs: Some(60),
+            replicate_to_bucket: Some("logs-dr".to_string()),
+        };
+        let manager = ArchivalManager::new(config, Arc::clone(&segments), TieringPolicy::default())
+            .await
+            .unwrap()
+            .with_storage_class("logs", class, Arc::clone(&logs));
+
+        manager.put(b"logs/a".to_vec(), b"1".to_vec()).unwrap();
+        manager.put(b"users/b".to_vec(), b"2".to_vec()).unwrap();
+        assert_eq!(logs.get(&b"logs/a".to_vec()).unwrap(), Some(b"1".to_vec()));
+        assert_eq!(logs.get(&b"users/b".to_vec()).unwrap(), None);
+        assert_eq!(
+            segments.get(&b"users/b".to_vec()).unwrap(),
+            Some(b"2".to_vec())
+        );
+        assert_eq!(manager.segment_managers().len(), 2);
+
+        logs.flush_active().unwrap();
+        segments.flush_active().unwrap();
+        assert_eq!(manager.backlog().unwrap().local_segments, 2);
+        let segment_id = logs.flushed_segment_ids().unwrap()[0];
+        assert_eq!(
+            manager.local_value(segment_id, b"logs/a").await.unwrap(),
+            Some(b"1".to_vec())
+        );
+        assert_eq!(
+            manager.get_value(0, b"users/b").await.unwrap(),
+            Some(b"2".to_vec())
+        );
+
+        let replica = manager.classes[0].replica.as_ref().unwrap();
+        assert_eq!(replica.bucket(), "logs-dr");
     }
 
     #[test]
@@ -396,6 +1385,10 @@ mod tests {
             is_compressed: true,
             entry_count: 10,
             merkle_root: vec![1, 2, 3, 4],
+            is_encrypted: false,
+            key_ids: Vec::new(),
+            storage_class: None,
+            has_checksum_index: false,
         };
 
         let json = serde_json::to_string(&metadata).unwrap();
@@ -408,6 +1401,18 @@ mod tests {
         assert_eq!(deserialized.merkle_root, metadata.merkle_root);
     }
 
+    #[test]
+    fn test_verification_status_serialization() {
+        assert_eq!(
+            serde_json::to_string(&VerificationStatus::Verified).unwrap(),
+            "\"verified\""
+        );
+        assert_eq!(
+            serde_json::from_str::<VerificationStatus>("\"shredded\"").unwrap(),
+            VerificationStatus::Shredded
+        );
+    }
+
     #[test]
     fn test_segment_key_generation() {
         let key = ArchivalManager::segment_key(42);
@@ -419,4 +1424,10 @@ mod tests {
         let key = ArchivalManager::metadata_key(42);
         assert_eq!(key, "segments/segment-000000000000002a.meta.json");
     }
+
+    #[test]
+    fn test_checksums_key_generation() {
+        let key = ArchivalManager::checksums_key(42);
+        assert_eq!(key, "segments/segment-000000000000002a.sums");
+    }
 }
@@ -0,0 +1,445 @@
+//! Per-value checksums for detecting corruption in local storage
+//!
+//! [`ChecksummedStorage`] frames every value it writes with a small header holding a
+//! CRC32C of the value, and verifies the checksum on every read:
+//!
+//! ```text
+//! magic (1 byte, 0xC5) | version (1 byte) | CRC32C of the value (u32, big-endian) | value
+//! ```
+//!
+//! A value whose header is missing or whose checksum does not match is reported as
+//! [`ScribeError::DataCorruption`] and counted in the `scribe_ledger_corrupted_values_total`
+//! metric, so a bit flip on disk surfaces as an error instead of being served or
+//! hashed into a Merkle proof.
+//!
+//! Archived segments carry a [`ChecksumIndex`] instead: a sorted list of
+//! (CRC64 of the key, CRC32C of the value) pairs, 12 bytes per entry, stored next
+//! to the segment. When a segment's Merkle root no longer matches, comparing the
+//! decoded entries with the index names the corrupt keys, so only those need to be
+//! repaired.
+
+use crate::error::{Result, ScribeError};
+use crate::logging::log_key;
+use crate::metrics::CORRUPTED_VALUES;
+use crate::storage::{KeyRange, StorageBackend};
+use crate::types::{Key, Value};
+use async_trait::async_trait;
+use crc_fast::CrcAlgorithm;
+use std::collections::HashMap;
+
+/// First byte of a framed value
+const FRAME_MAGIC: u8 = 0xC5;
+
+/// Frame format version
+const FRAME_VERSION: u8 = 1;
+
+/// Size of the frame header
+pub const FRAME_HEADER_LEN: usize = 6;
+
+/// CRC32C (Castagnoli) of `data`
+pub fn crc32c(data: &[u8]) -> u32 {
+    crc_fast::checksum(CrcAlgorithm::Crc32Iscsi, data) as u32
+}
+
+/// Prefix a value with its checksum header
+pub fn frame_value(value: &[u8]) -> Value {
+    let mut framed = Vec::with_capacity(FRAME_HEADER_LEN + value.len());
+    framed.push(FRAME_MAGIC);
+    framed.push(FRAME_VERSION);
+    framed.extend_from_slice(&crc32c(value).to_be_bytes());
+    framed.extend_from_slice(value);
+    framed
+}
+
+/// Whether `value` is framed with a header whose checksum matches
+///
+/// Unlike [`unframe_value`], a mismatch is not counted as corruption.
+pub fn is_framed(value: &[u8]) -> bool {
+    value.len() >= FRAME_HEADER_LEN
+        && value[0] == FRAME_MAGIC
+        && value[1] == FRAME_VERSION
+        && u32::from_be_bytes([value[2], value[3], value[4], value[5]])
+            == crc32c(&value[FRAME_HEADER_LEN..])
+}
+
+/// Check a framed value against its checksum and strip the header
+///
+/// Fails with [`ScribeError::DataCorruption`] (and counts the failure) if the header
+/// is malformed or the checksum does not match.
+pub fn unframe_value(key: &[u8], mut framed: Value) -> Result<Value> {
+    if framed.len() < FRAME_HEADER_LEN || framed[0] != FRAME_MAGIC {
+        return Err(corruption(key, "missing checksum header".to_string()));
+    }
+    if framed[1] != FRAME_VERSION {
+        return Err(corruption(
+            key,
+            format!("unknown checksum frame version {}", framed[1]),
+        ));
+    }
+
+    let expected = u32::from_be_bytes([framed[2], framed[3], framed[4], framed[5]]);
+    let actual = crc32c(&framed[FRAME_HEADER_LEN..]);
+    if actual != expected {
+        return Err(corruption(
+            key,
+            format!(
+                "checksum mismatch (stored {:08x}, computed {:08x})",
+                expected, actual
+            ),
+        ));
+    }
+    framed.drain(..FRAME_HEADER_LEN);
+    Ok(framed)
+}
+
+/// Count a corrupted value and describe it
+fn corruption(key: &[u8], reason: String) -> ScribeError {
+    CORRUPTED_VALUES.inc();
+    ScribeError::DataCorruption(format!(
+        "value of key {:?}: {}",
+        log_key(key).to_string(),
+        reason
+    ))
+}
+
+/// Storage backend that checksums every value written to another backend
+///
+/// Values already in the wrapped backend must have been written through a
+/// `ChecksummedStorage`; unframed values read as corrupt. Persisted snapshots use it
+/// when their layout has checksums, and [`layout::migrate`](super::layout::migrate)
+/// frames values written without them.
+pub struct ChecksummedStorage<S: StorageBackend> {
+    inner: S,
+}
+
+impl<S: StorageBackend> ChecksummedStorage<S> {
+    /// Wrap a storage backend
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+
+    /// Get the wrapped storage backend
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Read every value and return the keys whose checksum does not match
+    pub async fn scrub(&self) -> Result<Vec<Key>> {
+        let mut corrupted: Vec<Key> = self
+            .inner
+            .snapshot()
+            .await?
+            .into_iter()
+            .filter(|(key, value)| unframe_value(key, value.clone()).is_err())
+            .map(|(key, _)| key)
+            .collect();
+        corrupted.sort();
+        Ok(corrupted)
+    }
+}
+
+#[async_trait]
+impl<S: StorageBackend> StorageBackend for ChecksummedStorage<S> {
+    async fn put(&self, key: Key, value: Value) -> Result<()> {
+        self.inner.put(key, frame_value(&value)).await
+    }
+
+    async fn get(&self, key: &Key) -> Result<Option<Value>> {
+        match self.inner.get(key).await? {
+            Some(framed) => Ok(Some(unframe_value(key, framed)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn delete(&self, key: &Key) -> Result<()> {
+        self.inner.delete(key).await
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.inner.flush().await
+    }
+
+    async fn snapshot(&self) -> Result<HashMap<Key, Value>> {
+        self.inner
+            .snapshot()
+            .await?
+            .into_iter()
+            .map(|(key, framed)| {
+                let value = unframe_value(&key, framed)?;
+                Ok((key, value))
+            })
+            .collect()
+    }
+
+    async fn range(&self, range: KeyRange) -> Result<Vec<(Key, Value)>> {
+        self.inner
+            .range(range)
+            .await?
+            .into_iter()
+            .map(|(key, framed)| {
+                let value = unframe_value(&key, framed)?;
+                Ok((key, value))
+            })
+            .collect()
+    }
+
+    async fn compare_and_swap(
+        &self,
+        key: Key,
+        expected: Option<Value>,
+        new: Option<Value>,
+    ) -> Result<bool> {
+        // Compare decoded values, then swap against the framed bytes just read so a
+        // concurrent write still makes the swap fail
+        let current = self.inner.get(&key).await?;
+        let decoded = match &current {
+            Some(framed) => Some(unframe_value(&key, framed.clone())?),
+            None => None,
+        };
+        if decoded != expected {
+            return Ok(false);
+        }
+        let new = new.map(|value| frame_value(&value));
+        self.inner.compare_and_swap(key, current, new).await
+    }
+}
+
+/// Format version of an encoded [`ChecksumIndex`]
+const INDEX_VERSION: u8 = 1;
+
+/// Encoded size of one index entry
+const INDEX_ENTRY_LEN: usize = 12;
+
+/// CRC64 of a key, as recorded in a [`ChecksumIndex`]
+pub fn key_hash(key: &[u8]) -> u64 {
+    crc_fast::checksum(CrcAlgorithm::Crc64Nvme, key)
+}
+
+/// Key hash to value checksum of every entry of a segment
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChecksumIndex {
+    /// (key hash, value checksum), sorted by key hash
+    entries: Vec<(u64, u32)>,
+}
+
+/// Entries of a segment that do not match its [`ChecksumIndex`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChecksumDiff {
+    /// Keys whose value checksum differs, or whose key is not in the index
+    pub corrupt: Vec<Key>,
+    /// Indexed entries absent from the segment
+    pub missing: usize,
+}
+
+impl ChecksumDiff {
+    /// Whether every entry matches the index
+    pub fn is_clean(&self) -> bool {
+        self.corrupt.is_empty() && self.missing == 0
+    }
+}
+
+impl ChecksumIndex {
+    /// Index the entries of a segment
+    pub fn build<'a>(entries: impl IntoIterator<Item = (&'a Key, &'a Value)>) -> Self {
+        let mut entries: Vec<(u64, u32)> = entries
+            .into_iter()
+            .map(|(key, value)| (key_hash(key), crc32c(value)))
+            .collect();
+        entries.sort_unstable();
+        Self { entries }
+    }
+
+    /// Number of indexed entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no entry is indexed
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Encode as a version byte followed by the big-endian entries
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(1 + self.entries.len() * INDEX_ENTRY_LEN);
+        data.push(INDEX_VERSION);
+        for (key, value) in &self.entries {
+            data.extend_from_slice(&key.to_be_bytes());
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+        data
+    }
+
+    /// Decode an index written by [`encode`](Self::encode)
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let Some((&version, body)) = data.split_first() else {
+            return Err(ScribeError::DataCorruption(
+                "empty checksum index".to_string(),
+            ));
+        };
+        if version != INDEX_VERSION {
+            return Err(ScribeError::DataCorruption(format!(
+                "unknown checksum index version {}",
+                version
+            )));
+        }
+        if body.len() % INDEX_ENTRY_LEN != 0 {
+            return Err(ScribeError::DataCorruption(format!(
+                "truncated checksum index ({} bytes)",
+                data.len()
+            )));
+        }
+        let entries = body
+            .chunks_exact(INDEX_ENTRY_LEN)
+            .map(|entry| {
+                let (key, value) = entry.split_at(8);
+                (
+                    u64::from_be_bytes(key.try_into().expect("8-byte key hash")),
+                    u32::from_be_bytes(value.try_into().expect("4-byte value checksum")),
+                )
+            })
+            .collect();
+        Ok(Self { entries })
+    }
+
+    /// Compare the entries of a segment with the index
+    pub fn compare<'a>(
+        &self,
+        entries: impl IntoIterator<Item = (&'a Key, &'a Value)>,
+    ) -> ChecksumDiff {
+        let mut matched = vec![false; self.entries.len()];
+        let mut diff = ChecksumDiff::default();
+        for (key, value) in entries {
+            let hash = key_hash(key);
+            let start = self.entries.partition_point(|(h, _)| *h < hash);
+            let checksum = crc32c(value);
+            let found = (start..self.entries.len())
+                .take_while(|&i| self.entries[i].0 == hash)
+                .find(|&i| !matched[i] && self.entries[i].1 == checksum);
+            match found {
+                Some(i) => matched[i] = true,
+                None => diff.corrupt.push(key.clone()),
+            }
+        }
+        diff.corrupt.sort();
+        diff.missing = matched
+            .iter()
+            .filter(|matched| !**matched)
+            .count()
+            .saturating_sub(diff.corrupt.len());
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::SledStorage;
+
+    #[test]
+    fn test_frame_roundtrip() {
+        // Standard CRC32C check value
+        assert_eq!(crc32c(b"123456789"), 0xe306_9283);
+
+        for value in [b"".to_vec(), b"value".to_vec(), vec![0xC5; 100]] {
+            let framed = frame_value(&value);
+            assert_eq!(framed.len(), FRAME_HEADER_LEN + value.len());
+            assert_eq!(unframe_value(b"key", framed).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_detects_bit_flips() {
+        let framed = frame_value(b"important value");
+        let before = CORRUPTED_VALUES.get();
+
+        for bit in [0, 9, 20, 50, framed.len() * 8 - 1] {
+            let mut flipped = framed.clone();
+            flipped[bit / 8] ^= 1 << (bit % 8);
+            assert!(matches!(
+                unframe_value(b"key", flipped),
+                Err(ScribeError::DataCorruption(_))
+            ));
+        }
+        assert!(matches!(
+            unframe_value(b"key", b"raw".to_vec()),
+            Err(ScribeError::DataCorruption(_))
+        ));
+        assert!(CORRUPTED_VALUES.get() >= before + 6);
+    }
+
+    #[tokio::test]
+    async fn test_checksummed_storage() {
+        let storage = ChecksummedStorage::new(SledStorage::temp().unwrap());
+        let key = b"key".to_vec();
+        storage.put(key.clone(), b"value".to_vec()).await.unwrap();
+        storage
+            .put(b"other".to_vec(), b"fine".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(storage.get(&key).await.unwrap(), Some(b"value".to_vec()));
+        assert_eq!(
+            storage.scan(b"k").await.unwrap(),
+            vec![(key.clone(), b"value".to_vec())]
+        );
+        assert!(storage.scrub().await.unwrap().is_empty());
+
+        // Flip a bit behind the wrapper's back
+        let mut raw = storage.inner().get(&key).await.unwrap().unwrap();
+        raw[FRAME_HEADER_LEN] ^= 0x01;
+        storage.inner().put(key.clone(), raw).await.unwrap();
+
+        let err = storage.get(&key).await.unwrap_err();
+        assert!(matches!(err, ScribeError::DataCorruption(_)));
+        assert!(err.to_string().contains("key"));
+        assert!(storage.snapshot().await.is_err());
+        assert!(storage.scan(b"k").await.is_err());
+        assert_eq!(storage.scan(b"o").await.unwrap().len(), 1);
+        assert_eq!(storage.scrub().await.unwrap(), vec![key.clone()]);
+        assert_eq!(
+            storage.get(&b"other".to_vec()).await.unwrap(),
+            Some(b"fine".to_vec())
+        );
+
+        // Swaps compare unframed values and refuse corrupted ones
+        assert!(storage
+            .compare_and_swap(
+                b"other".to_vec(),
+                Some(b"fine".to_vec()),
+                Some(b"better".to_vec())
+            )
+            .await
+            .unwrap());
+        assert_eq!(
+            storage.get(&b"other".to_vec()).await.unwrap(),
+            Some(b"better".to_vec())
+        );
+        assert!(storage
+            .compare_and_swap(key, Some(b"value".to_vec()), None)
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_checksum_index() {
+        let mut entries: HashMap<Key, Value> = (0..20)
+            .map(|i| (format!("key{}", i).into_bytes(), vec![i as u8; 10]))
+            .collect();
+        let index = ChecksumIndex::build(&entries);
+        assert_eq!(index.len(), 20);
+        assert_eq!(index.encode().len(), 1 + 20 * INDEX_ENTRY_LEN);
+        let index = ChecksumIndex::decode(&index.encode()).unwrap();
+        assert!(index.compare(&entries).is_clean());
+
+        // A changed value and a lost entry are told apart
+        entries.insert(b"key3".to_vec(), b"flipped".to_vec());
+        entries.remove(b"key7".as_slice());
+        let diff = index.compare(&entries);
+        assert_eq!(diff.corrupt, vec![b"key3".to_vec()]);
+        assert_eq!(diff.missing, 1);
+
+        assert!(ChecksumIndex::decode(&[]).is_err());
+        assert!(ChecksumIndex::decode(&[INDEX_VERSION, 0, 1]).is_err());
+        assert!(ChecksumIndex::decode(&[9]).is_err());
+    }
+}
@@ -0,0 +1,197 @@
+//! Storage classes routing keys through the segment and archival pipeline
+//!
+//! A storage class claims key prefixes and sets how their segments are archived:
+//! whether they are compressed, how old they get before they are archived, how long
+//! the archived copy is kept, and an optional secondary bucket every archived
+//! segment is copied to. Each class fills its own segments, so one cluster can keep
+//! short-lived logs and long-lived models side by side without one workload's
+//! settings applying to the other. Keys no class claims follow the tiering policy.
+//!
+//! When prefixes of several classes match a key, the longest one wins.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Archival settings of keys under some prefixes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageClass {
+    /// Key prefixes routed to this class
+    pub prefixes: Vec<String>,
+    /// Compress archived segments
+    #[serde(default = "default_compression")]
+    pub compression: bool,
+    /// Age in seconds after which segments are archived (default: the tiering
+    /// policy's threshold)
+    #[serde(default)]
+    pub archive_after_secs: Option<u64>,
+    /// Seconds archived segments are kept before they are deleted (default: kept)
+    #[serde(default)]
+    pub retention_secs: Option<u64>,
+    /// Bucket every archived segment is also copied to
+    #[serde(default)]
+    pub replicate_to_bucket: Option<String>,
+}
+
+fn default_compression() -> bool {
+    true
+}
+
+impl StorageClass {
+    /// Length of the longest prefix of this class matching `key`
+    pub fn matches(&self, key: &[u8]) -> Option<usize> {
+        self.prefixes
+            .iter()
+            .filter(|prefix| key.starts_with(prefix.as_bytes()))
+            .map(String::len)
+            .max()
+    }
+}
+
+/// Storage classes by name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct StorageClasses(BTreeMap<String, StorageClass>);
+
+impl StorageClasses {
+    /// Whether no class is configured
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Add the class `name`, replacing a class of the same name
+    pub fn insert(&mut self, name: impl Into<String>, class: StorageClass) {
+        self.0.insert(name.into(), class);
+    }
+
+    /// Classes ordered by name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &StorageClass)> {
+        self.0.iter().map(|(name, class)| (name.as_str(), class))
+    }
+
+    /// Name of the class `key` is routed to, `None` for the default pipeline
+    pub fn class_for(&self, key: &[u8]) -> Option<&str> {
+        self.iter()
+            .filter_map(|(name, class)| Some((class.matches(key)?, name)))
+            .max_by_key(|(len, _)| *len)
+            .map(|(_, name)| name)
+    }
+
+    /// Validate the storage classes
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        let mut owners: BTreeMap<&str, &str> = BTreeMap::new();
+        for (name, class) in self.iter() {
+            let valid_name = !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if !valid_name {
+                return Err(format!(
+                    "Storage class name '{}' must consist of letters, digits, '-' and '_'",
+                    name
+                ));
+            }
+            if class.prefixes.is_empty() || class.prefixes.iter().any(String::is_empty) {
+                return Err(format!(
+                    "Storage class '{}' needs at least one non-empty prefix",
+                    name
+                ));
+            }
+            for prefix in &class.prefixes {
+                if let Some(other) = owners.insert(prefix, name) {
+                    return Err(format!(
+                        "Prefix '{}' is claimed by storage classes '{}' and '{}'",
+                        prefix, other, name
+                    ));
+                }
+            }
+            if class.retention_secs == Some(0) {
+                return Err(format!(
+                    "Retention of storage class '{}' must be greater than 0",
+                    name
+                ));
+            }
+            if class.replicate_to_bucket.as_deref() == Some("") {
+                return Err(format!(
+                    "Replication bucket of storage class '{}' must not be empty",
+                    name
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classes() -> StorageClasses {
+        toml::from_str(
+            r#"
+            [logs]
+            prefixes = ["logs/"]
+            archive_after_secs = 60
+            retention_secs = 86400
+
+            [audit-logs]
+            prefixes = ["logs/audit/"]
+
+            [models]
+            prefixes = ["models/", "weights/"]
+            compression = false
+            replicate_to_bucket = "models-dr"
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_class_for_longest_prefix() {
+        let classes = classes();
+        classes.validate().unwrap();
+
+        assert_eq!(classes.class_for(b"logs/app/1"), Some("logs"));
+        assert_eq!(classes.class_for(b"logs/audit/1"), Some("audit-logs"));
+        assert_eq!(classes.class_for(b"weights/v2"), Some("models"));
+        assert_eq!(classes.class_for(b"users/1"), None);
+
+        let (_, models) = classes.iter().find(|(name, _)| *name == "models").unwrap();
+        assert!(!models.compression);
+        let (_, logs) = classes.iter().find(|(name, _)| *name == "logs").unwrap();
+        assert!(logs.compression);
+    }
+
+    #[test]
+    fn test_validate_classes() {
+        let class = |prefix: &str| StorageClass {
+            prefixes: vec![prefix.to_string()],
+            compression: true,
+            archive_after_secs: None,
+            retention_secs: None,
+            replicate_to_bucket: None,
+        };
+
+        let mut classes = StorageClasses::default();
+        classes.insert("logs", class("logs/"));
+        classes.insert("other", class("logs/"));
+        assert!(classes.validate().is_err());
+
+        let mut classes = StorageClasses::default();
+        classes.insert("bad name", class("logs/"));
+        assert!(classes.validate().is_err());
+
+        let mut classes = StorageClasses::default();
+        classes.insert("logs", class(""));
+        assert!(classes.validate().is_err());
+
+        let mut classes = StorageClasses::default();
+        classes.insert(
+            "logs",
+            StorageClass {
+                retention_secs: Some(0),
+                ..class("logs/")
+            },
+        );
+        assert!(classes.validate().is_err());
+    }
+}
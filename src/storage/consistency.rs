@@ -0,0 +1,103 @@
+//! Startup consistency check between the manifest and local segments
+//!
+//! A crash between archiving a segment and dropping its local copy, or a manifest
+//! that lost entries, leaves the manifest and the segments on disk disagreeing. The
+//! check run at startup reports both directions:
+//!
+//! - segments the manifest references that are not present locally, which reads
+//!   have to fetch from S3
+//! - local segments the manifest does not reference although they were archived,
+//!   which can be registered again from their archived metadata
+//!
+//! Local segments that were never archived are not discrepancies: they are
+//! registered when the archival task uploads them. In repair mode
+//! (`ArchivalManager::check_consistency`), referenced segments are fetched into the
+//! segment cache (entries whose archived copy is gone are dropped from the
+//! manifest) and archived local segments are registered again.
+
+use crate::types::SegmentId;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// What the node does with the consistency check at startup
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StartupCheckMode {
+    /// Skip the check
+    Off,
+    /// Log the discrepancies found
+    #[default]
+    Report,
+    /// Log the discrepancies and fix them
+    Repair,
+}
+
+/// Outcome of a consistency check
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsistencyReport {
+    /// Number of segments present locally
+    pub local_segments: usize,
+    /// Number of segments referenced by the manifest
+    pub manifest_segments: usize,
+    /// Segments referenced by the manifest but not present locally
+    pub missing_locally: Vec<SegmentId>,
+    /// Archived local segments the manifest does not reference
+    pub unregistered: Vec<SegmentId>,
+    /// Local segments not archived yet (expected, not a discrepancy)
+    pub pending_archival: Vec<SegmentId>,
+    /// Missing segments fetched from S3 by a repair
+    pub fetched: Vec<SegmentId>,
+    /// Manifest entries dropped by a repair because their archived copy is gone
+    pub dropped: Vec<SegmentId>,
+    /// Segments registered in the manifest again by a repair
+    pub registered: Vec<SegmentId>,
+}
+
+impl ConsistencyReport {
+    /// Whether the manifest and the local segments agree
+    pub fn is_consistent(&self) -> bool {
+        self.missing_locally.is_empty() && self.unregistered.is_empty()
+    }
+}
+
+/// Split the segments present on only one side
+///
+/// Returns the segments referenced by the manifest but absent locally, and the local
+/// segments absent from the manifest, both in ascending order.
+pub fn compare(
+    manifest: impl IntoIterator<Item = SegmentId>,
+    local: impl IntoIterator<Item = SegmentId>,
+) -> (Vec<SegmentId>, Vec<SegmentId>) {
+    let manifest: BTreeSet<SegmentId> = manifest.into_iter().collect();
+    let local: BTreeSet<SegmentId> = local.into_iter().collect();
+    (
+        manifest.difference(&local).copied().collect(),
+        local.difference(&manifest).copied().collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_manifest_and_local() {
+        let (missing, unregistered) = compare([5, 1, 3], [3, 4, 2]);
+        assert_eq!(missing, vec![1, 5]);
+        assert_eq!(unregistered, vec![2, 4]);
+
+        let (missing, unregistered) = compare([1, 2], [2, 1]);
+        assert!(missing.is_empty() && unregistered.is_empty());
+
+        let report = ConsistencyReport {
+            pending_archival: vec![7],
+            ..ConsistencyReport::default()
+        };
+        assert!(report.is_consistent());
+        let report = ConsistencyReport {
+            unregistered: vec![7],
+            ..ConsistencyReport::default()
+        };
+        assert!(!report.is_consistent());
+    }
+}
@@ -0,0 +1,238 @@
+//! Rotatable S3 credential sources
+//!
+//! Static keys in the configuration cannot change without a restart. A
+//! [`CredentialSource`] is read again periodically instead: a file rewritten by a
+//! secret agent, the process environment, or a command fetching credentials from a
+//! secret provider. Files and commands use the JSON format of the AWS CLI's
+//! `credential_process`:
+//!
+//! ```json
+//! {"Version": 1, "AccessKeyId": "...", "SecretAccessKey": "...",
+//!  "SessionToken": "...", "Expiration": "2026-01-01T00:00:00Z"}
+//! ```
+//!
+//! When the credentials read differ from those in use, the S3 backend swaps in a new
+//! client (see `S3Storage::refresh_credentials`).
+
+use crate::error::{Result, ScribeError};
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Default interval between reads of a credential source, in seconds
+pub const DEFAULT_CREDENTIAL_REFRESH_SECS: u64 = 60;
+
+/// Where rotatable S3 credentials are read from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CredentialSource {
+    /// JSON file in the `credential_process` format
+    File {
+        /// Path of the file
+        path: PathBuf,
+    },
+    /// `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`
+    Env,
+    /// Command printing credentials in the `credential_process` format
+    Command {
+        /// Program and arguments
+        command: Vec<String>,
+    },
+}
+
+impl CredentialSource {
+    /// Short name of the source kind
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CredentialSource::File { .. } => "file",
+            CredentialSource::Env => "env",
+            CredentialSource::Command { .. } => "command",
+        }
+    }
+
+    /// Read the current credentials
+    pub async fn load(&self) -> Result<S3Credentials> {
+        match self {
+            CredentialSource::File { path } => {
+                let contents = tokio::fs::read(path).await.map_err(|e| {
+                    ScribeError::Configuration(format!(
+                        "Failed to read S3 credentials from {}: {}",
+                        path.display(),
+                        e
+                    ))
+                })?;
+                S3Credentials::from_process_output(&contents)
+            }
+            CredentialSource::Env => {
+                let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+                match (var("AWS_ACCESS_KEY_ID"), var("AWS_SECRET_ACCESS_KEY")) {
+                    (Some(access_key_id), Some(secret_access_key)) => Ok(S3Credentials {
+                        access_key_id,
+                        secret_access_key,
+                        session_token: var("AWS_SESSION_TOKEN"),
+                        expires_at: None,
+                    }),
+                    _ => Err(ScribeError::Configuration(
+                        "AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY must be set".to_string(),
+                    )),
+                }
+            }
+            CredentialSource::Command { command } => {
+                let Some((program, args)) = command.split_first() else {
+                    return Err(ScribeError::Configuration(
+                        "S3 credential command is empty".to_string(),
+                    ));
+                };
+                let output = tokio::process::Command::new(program)
+                    .args(args)
+                    .kill_on_drop(true)
+                    .output()
+                    .await?;
+                if !output.status.success() {
+                    return Err(ScribeError::Configuration(format!(
+                        "S3 credential command failed ({}): {}",
+                        output.status,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    )));
+                }
+                S3Credentials::from_process_output(&output.stdout)
+            }
+        }
+    }
+}
+
+/// A set of S3 credentials
+#[derive(Clone, PartialEq, Eq)]
+pub struct S3Credentials {
+    /// Access key ID
+    pub access_key_id: String,
+    /// Secret access key
+    pub secret_access_key: String,
+    /// Session token of temporary credentials
+    pub session_token: Option<String>,
+    /// Expiry of temporary credentials (milliseconds since the Unix epoch)
+    pub expires_at: Option<u64>,
+}
+
+// Keeps the secret parts out of logs
+impl std::fmt::Debug for S3Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("S3Credentials")
+            .field("access_key_id", &self.access_key_id)
+            .field("expires_at", &self.expires_at)
+            .finish_non_exhaustive()
+    }
+}
+
+/// `credential_process` output
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ProcessCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    #[serde(default)]
+    session_token: Option<String>,
+    #[serde(default)]
+    expiration: Option<String>,
+}
+
+impl S3Credentials {
+    /// Parse credentials in the `credential_process` JSON format
+    pub fn from_process_output(output: &[u8]) -> Result<Self> {
+        let parsed: ProcessCredentials = serde_json::from_slice(output)
+            .map_err(|e| ScribeError::Configuration(format!("Invalid S3 credentials: {}", e)))?;
+        let expires_at = parsed
+            .expiration
+            .map(|expiration| {
+                DateTime::from_str(&expiration, DateTimeFormat::DateTime)
+                    .map(|at| at.secs().max(0) as u64 * 1000 + at.subsec_nanos() as u64 / 1_000_000)
+                    .map_err(|e| {
+                        ScribeError::Configuration(format!(
+                            "Invalid S3 credential expiration {:?}: {}",
+                            expiration, e
+                        ))
+                    })
+            })
+            .transpose()?;
+        Ok(Self {
+            access_key_id: parsed.access_key_id,
+            secret_access_key: parsed.secret_access_key,
+            session_token: parsed.session_token.filter(|token| !token.is_empty()),
+            expires_at,
+        })
+    }
+}
+
+/// Credentials in use by an S3 backend, as reported by the storage stats
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CredentialStatus {
+    /// Kind of source the credentials come from (`static`, `file`, `env` or
+    /// `command`)
+    pub source: String,
+    /// Access key ID in use
+    pub access_key_id: Option<String>,
+    /// Expiry of the credentials in use (milliseconds since the Unix epoch)
+    pub expires_at: Option<u64>,
+    /// Time the credentials were last rotated (milliseconds since the Unix epoch)
+    pub rotated_at: Option<u64>,
+    /// Number of rotations since startup
+    pub rotations: u64,
+    /// Error of the last failed read of the source, cleared by the next success
+    pub last_error: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_process_output() {
+        let credentials = S3Credentials::from_process_output(
+            br#"{"Version": 1, "AccessKeyId": "AKIA1", "SecretAccessKey": "secret",
+                "SessionToken": "token", "Expiration": "2026-01-01T00:00:00Z"}"#,
+        )
+        .unwrap();
+        assert_eq!(credentials.access_key_id, "AKIA1");
+        assert_eq!(credentials.session_token.as_deref(), Some("token"));
+        assert_eq!(credentials.expires_at, Some(1_767_225_600_000));
+        assert!(!format!("{:?}", credentials).contains("secret"));
+
+        let long_lived = S3Credentials::from_process_output(
+            br#"{"AccessKeyId": "AKIA2", "SecretAccessKey": "secret"}"#,
+        )
+        .unwrap();
+        assert_eq!(long_lived.expires_at, None);
+
+        assert!(S3Credentials::from_process_output(b"{}").is_err());
+        assert!(S3Credentials::from_process_output(
+            br#"{"AccessKeyId": "A", "SecretAccessKey": "S", "Expiration": "soon"}"#
+        )
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_load_file_and_command_sources() {
+        let path = std::env::temp_dir().join(format!("scribe-s3-creds-{}", fastrand::u64(..)));
+        std::fs::write(
+            &path,
+            br#"{"AccessKeyId": "AKIA3", "SecretAccessKey": "s"}"#,
+        )
+        .unwrap();
+        let file = CredentialSource::File { path: path.clone() };
+        assert_eq!(file.load().await.unwrap().access_key_id, "AKIA3");
+        std::fs::remove_file(&path).unwrap();
+        assert!(file.load().await.is_err());
+
+        let command = CredentialSource::Command {
+            command: vec![
+                "echo".to_string(),
+                r#"{"AccessKeyId": "AKIA4", "SecretAccessKey": "s"}"#.to_string(),
+            ],
+        };
+        assert_eq!(command.load().await.unwrap().access_key_id, "AKIA4");
+        let failing = CredentialSource::Command {
+            command: vec!["false".to_string()],
+        };
+        assert!(failing.load().await.is_err());
+    }
+}
@@ -0,0 +1,188 @@
+//! Fault injection for the S3 backend
+//!
+//! An [`S3FaultInjector`] attached to an [`S3Storage`] makes its requests fail or its
+//! uploads arrive damaged, so the archival pipeline can be exercised against S3
+//! outages without taking the object store down. Storage without an injector is
+//! unaffected.
+//!
+//! [`S3Storage`]: crate::storage::s3::S3Storage
+
+use crate::error::{Result, ScribeError};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Kind of S3 request, as seen by the fault injector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum S3Operation {
+    /// Object upload
+    Put,
+    /// Object download
+    Get,
+    /// Object deletion
+    Delete,
+    /// Bucket listing
+    List,
+    /// Bucket health check
+    HeadBucket,
+}
+
+/// Faults to inject into S3 requests
+///
+/// Failures are injected per attempt, so a request retried by the storage backend
+/// consumes one pending failure for each attempt.
+#[derive(Debug, Default)]
+pub struct S3FaultInjector {
+    /// Fail every request
+    outage: AtomicBool,
+    /// Number of upcoming upload attempts to fail
+    failing_puts: AtomicU32,
+    /// Number of upcoming uploads to truncate while reporting success
+    torn_puts: AtomicU32,
+    /// Fail every upload to a key containing this pattern
+    failing_pattern: Mutex<Option<String>>,
+    /// Number of faults injected so far
+    injected: AtomicU64,
+}
+
+impl S3FaultInjector {
+    /// Create an injector with no faults armed
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fail every request until the outage is lifted
+    pub fn set_outage(&self, outage: bool) {
+        self.outage.store(outage, Ordering::SeqCst);
+    }
+
+    /// Fail the next `count` upload attempts
+    pub fn fail_next_puts(&self, count: u32) {
+        self.failing_puts.store(count, Ordering::SeqCst);
+    }
+
+    /// Store only the first half of the next `count` uploads, reporting success
+    pub fn tear_next_puts(&self, count: u32) {
+        self.torn_puts.store(count, Ordering::SeqCst);
+    }
+
+    /// Fail every upload to a key containing `pattern`, or none if `None`
+    pub fn fail_puts_matching(&self, pattern: Option<&str>) {
+        *self.lock_pattern() = pattern.map(str::to_string);
+    }
+
+    /// Disarm all faults
+    pub fn reset(&self) {
+        self.set_outage(false);
+        self.fail_next_puts(0);
+        self.tear_next_puts(0);
+        self.fail_puts_matching(None);
+    }
+
+    /// Number of faults injected so far
+    pub fn injected_faults(&self) -> u64 {
+        self.injected.load(Ordering::SeqCst)
+    }
+
+    /// Fail the attempt if a fault is armed for it
+    ///
+    /// The error never looks like a missing object, so reads fail rather than
+    /// reporting the object as absent.
+    pub(crate) fn check(&self, operation: S3Operation, key: &str) -> Result<()> {
+        let fault = if self.outage.load(Ordering::SeqCst) {
+            Some("simulated outage")
+        } else if operation == S3Operation::Put && take_one(&self.failing_puts) {
+            Some("simulated upload failure")
+        } else if operation == S3Operation::Put
+            && self
+                .lock_pattern()
+                .as_deref()
+                .is_some_and(|pattern| key.contains(pattern))
+        {
+            Some("simulated upload failure for matching key")
+        } else {
+            None
+        };
+
+        match fault {
+            Some(fault) => {
+                self.injected.fetch_add(1, Ordering::SeqCst);
+                Err(ScribeError::Storage(format!(
+                    "Injected S3 fault on {:?} {}: {}",
+                    operation, key, fault
+                )))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Upload body as it will arrive, truncated if a torn upload is armed
+    pub(crate) fn tear(&self, mut data: Vec<u8>) -> Vec<u8> {
+        if take_one(&self.torn_puts) {
+            self.injected.fetch_add(1, Ordering::SeqCst);
+            data.truncate(data.len() / 2);
+        }
+        data
+    }
+
+    fn lock_pattern(&self) -> std::sync::MutexGuard<'_, Option<String>> {
+        self.failing_pattern
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Decrement `counter` if it is positive, returning whether it was
+fn take_one(counter: &AtomicU32) -> bool {
+    counter
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+        .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failing_puts_are_consumed_per_attempt() {
+        let faults = S3FaultInjector::new();
+        assert!(faults.check(S3Operation::Put, "a").is_ok());
+
+        faults.fail_next_puts(2);
+        assert!(faults.check(S3Operation::Get, "a").is_ok());
+        assert!(faults.check(S3Operation::Put, "a").is_err());
+        assert!(faults.check(S3Operation::Put, "a").is_err());
+        assert!(faults.check(S3Operation::Put, "a").is_ok());
+        assert_eq!(faults.injected_faults(), 2);
+    }
+
+    #[test]
+    fn test_outage_and_pattern_faults() {
+        let faults = S3FaultInjector::new();
+        faults.fail_puts_matching(Some(".meta.json"));
+        assert!(faults
+            .check(S3Operation::Put, "segments/x.meta.json")
+            .is_err());
+        assert!(faults.check(S3Operation::Put, "segments/x.bin").is_ok());
+        assert!(faults
+            .check(S3Operation::Get, "segments/x.meta.json")
+            .is_ok());
+
+        faults.set_outage(true);
+        let err = faults
+            .check(S3Operation::Get, "segments/x.bin")
+            .unwrap_err();
+        assert!(!err.to_string().contains("NoSuchKey"));
+        assert!(!matches!(err, ScribeError::NotFound(_)));
+
+        faults.reset();
+        assert!(faults.check(S3Operation::Put, "segments/x.bin").is_ok());
+    }
+
+    #[test]
+    fn test_torn_puts_truncate_data() {
+        let faults = S3FaultInjector::new();
+        faults.tear_next_puts(1);
+        assert_eq!(faults.tear(vec![0u8; 10]).len(), 5);
+        assert_eq!(faults.tear(vec![0u8; 10]).len(), 10);
+    }
+}
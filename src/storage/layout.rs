@@ -0,0 +1,413 @@
+//! Layout of the values stored in a sled database
+//!
+//! A [`StorageLayout`] describes how values are laid out on disk: which namespaces
+//! (key prefixes) are kept in sled trees of their own (see [`NamespacedSledStorage`])
+//! whether every value is framed with a checksum (see [`ChecksummedStorage`]) and
+//! which transformers values pass through, per namespace (see
+//! [`TransformingStorage`]). [`LayeredStorage`] opens a database with a layout and records it there on first
+//! use. Data read with another layout than it was written with would be misread, so
+//! opening a database with a different layout fails until [`migrate`] has rewritten
+//! it.
+
+use crate::error::{Result, ScribeError};
+use crate::storage::checksum::{self, ChecksummedStorage};
+use crate::storage::namespaced::NamespacedSledStorage;
+use crate::storage::transform::{TransformerChain, TransformerRegistry, TransformingStorage};
+use crate::storage::{KeyRange, StorageBackend};
+use crate::types::{Key, Value};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sled::Db;
+use std::collections::{BTreeMap, HashMap};
+
+/// Tree recording the layout of a database
+const LAYOUT_TREE: &[u8] = b"__layout";
+
+/// Key of the recorded layout
+const LAYOUT_KEY: &[u8] = b"layout";
+
+/// How values are laid out in a sled database
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageLayout {
+    /// Key prefixes kept in sled trees of their own; the longest match wins
+    #[serde(default)]
+    pub namespaces: Vec<String>,
+    /// Frame every value with a CRC32C, checked on every read
+    #[serde(default)]
+    pub checksums: bool,
+    /// Names of the transformers values are encoded with, by key prefix; the longest
+    /// match wins and the empty prefix matches every key
+    #[serde(default)]
+    pub transformers: BTreeMap<String, Vec<String>>,
+}
+
+impl StorageLayout {
+    /// Check that every namespace is a non-empty prefix and every transformer exists
+    pub fn validate(&self) -> Result<()> {
+        if self.namespaces.iter().any(String::is_empty) {
+            return Err(ScribeError::Configuration(
+                "Storage namespaces must not be empty".to_string(),
+            ));
+        }
+        self.registry()?;
+        Ok(())
+    }
+
+    /// Registry of the transformer chains of this layout
+    pub fn registry(&self) -> Result<TransformerRegistry> {
+        let registry = TransformerRegistry::new();
+        for (namespace, names) in &self.transformers {
+            registry.register(namespace.as_bytes(), TransformerChain::named(names)?)?;
+        }
+        Ok(registry)
+    }
+
+    /// Names of the transformers applied to `key`
+    fn chain_for(&self, key: &[u8]) -> &[String] {
+        self.transformers
+            .iter()
+            .filter(|(namespace, _)| key.starts_with(namespace.as_bytes()))
+            .max_by_key(|(namespace, _)| namespace.len())
+            .map(|(_, names)| names.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Layout recorded in `db`, if it was opened with one
+    pub fn recorded(db: &Db) -> Result<Option<Self>> {
+        match db.open_tree(LAYOUT_TREE)?.get(LAYOUT_KEY)? {
+            Some(bytes) => serde_json::from_slice(&bytes).map(Some).map_err(|e| {
+                ScribeError::DataCorruption(format!("unreadable storage layout: {}", e))
+            }),
+            None => Ok(None),
+        }
+    }
+
+    /// Record this layout in `db`
+    fn record(&self, db: &Db) -> Result<()> {
+        db.open_tree(LAYOUT_TREE)?
+            .insert(LAYOUT_KEY, serde_json::to_vec(self)?)?;
+        db.flush()?;
+        Ok(())
+    }
+}
+
+/// Values of a sled database, stored with a [`StorageLayout`]
+pub struct LayeredStorage {
+    trees: NamespacedSledStorage,
+    /// The trees, behind the checksums and transformers of the layout
+    values: Box<dyn StorageBackend>,
+}
+
+impl LayeredStorage {
+    /// Open the values of `db` with `layout`
+    ///
+    /// Fails if the database was written with another layout; [`migrate`] it first.
+    pub fn open(db: Db, layout: &StorageLayout) -> Result<Self> {
+        layout.validate()?;
+        match StorageLayout::recorded(&db)? {
+            Some(recorded) if recorded != *layout => {
+                return Err(ScribeError::Configuration(format!(
+                    "Storage was written with layout {:?}, not the configured {:?}; migrate it first",
+                    recorded, layout
+                )));
+            }
+            Some(_) => {}
+            None => layout.record(&db)?,
+        }
+        Self::with_layout(db, layout)
+    }
+
+    /// Open the values of `db` with `layout`, whatever layout it was written with
+    fn with_layout(db: Db, layout: &StorageLayout) -> Result<Self> {
+        let namespaces = layout
+            .namespaces
+            .iter()
+            .map(|namespace| namespace.as_bytes());
+        let trees = NamespacedSledStorage::with_db(db, namespaces)?;
+        let values: Box<dyn StorageBackend> =
+            match (layout.checksums, layout.transformers.is_empty()) {
+                (false, true) => Box::new(trees.clone()),
+                (true, true) => Box::new(ChecksummedStorage::new(trees.clone())),
+                (false, false) => {
+                    Box::new(TransformingStorage::new(trees.clone(), layout.registry()?))
+                }
+                (true, false) => Box::new(TransformingStorage::new(
+                    ChecksummedStorage::new(trees.clone()),
+                    layout.registry()?,
+                )),
+            };
+        Ok(Self { trees, values })
+    }
+
+    /// Get the namespace trees holding the values, as stored
+    pub fn trees(&self) -> &NamespacedSledStorage {
+        &self.trees
+    }
+
+    /// Delete every value
+    pub async fn clear(&self) -> Result<()> {
+        self.trees.clear().await
+    }
+}
+
+#[async_trait]
+impl StorageBackend for LayeredStorage {
+    async fn put(&self, key: Key, value: Value) -> Result<()> {
+        self.values.put(key, value).await
+    }
+
+    async fn get(&self, key: &Key) -> Result<Option<Value>> {
+        self.values.get(key).await
+    }
+
+    async fn delete(&self, key: &Key) -> Result<()> {
+        self.values.delete(key).await
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.values.flush().await
+    }
+
+    async fn snapshot(&self) -> Result<HashMap<Key, Value>> {
+        self.values.snapshot().await
+    }
+
+    async fn range(&self, range: KeyRange) -> Result<Vec<(Key, Value)>> {
+        self.values.range(range).await
+    }
+
+    async fn compare_and_swap(
+        &self,
+        key: Key,
+        expected: Option<Value>,
+        new: Option<Value>,
+    ) -> Result<bool> {
+        self.values.compare_and_swap(key, expected, new).await
+    }
+}
+
+/// Rewrite the data of `db` into `layout` and record it
+///
+/// Keys are moved into the tree of their namespace (see
+/// [`NamespacedSledStorage::migrate`]), values are framed with a checksum or
+/// unframed if the layout turns checksums on or off, and values whose transformers
+/// changed are decoded and encoded again. Returns the number of keys rewritten.
+pub async fn migrate(db: Db, layout: &StorageLayout) -> Result<usize> {
+    layout.validate()?;
+    let recorded = StorageLayout::recorded(&db)?.unwrap_or_default();
+    let storage = LayeredStorage::with_layout(db.clone(), layout)?;
+    let mut rewritten = storage.trees.migrate().await?;
+    if recorded.checksums != layout.checksums {
+        rewritten += reframe(&storage.trees, layout.checksums).await?;
+    }
+    if recorded.transformers != layout.transformers {
+        rewritten += retransform(&db, &storage.trees, &recorded, layout).await?;
+    }
+    layout.record(&db)?;
+    Ok(rewritten)
+}
+
+/// Decode the values whose transformers differ between `from` and `to` and encode
+/// them again, returning the number of values rewritten
+///
+/// The values are written in one transaction that also records `to`, so an
+/// interrupted run leaves every value as it was and can be repeated.
+async fn retransform(
+    db: &Db,
+    trees: &NamespacedSledStorage,
+    from: &StorageLayout,
+    to: &StorageLayout,
+) -> Result<usize> {
+    let (decode, encode) = (from.registry()?, to.registry()?);
+    let mut entries = Vec::new();
+    for (key, stored) in trees.snapshot().await? {
+        if from.chain_for(&key) == to.chain_for(&key) {
+            continue;
+        }
+        let stored = match to.checksums {
+            true => checksum::unframe_value(&key, stored)?,
+            false => stored,
+        };
+        let value = encode.encode(&key, decode.decode(&key, stored)?)?;
+        let value = match to.checksums {
+            true => checksum::frame_value(&value),
+            false => value,
+        };
+        entries.push((key, value));
+    }
+
+    let rewritten = entries.len();
+    let record = (LAYOUT_KEY.to_vec(), serde_json::to_vec(to)?);
+    trees
+        .put_all_recorded(entries, db.open_tree(LAYOUT_TREE)?, record)
+        .await?;
+    Ok(rewritten)
+}
+
+/// Frame every value that has no checksum, or unframe every value that has one,
+/// returning the number of values rewritten
+///
+/// Values already in the wanted form are left as they are, so an interrupted run
+/// can be repeated.
+async fn reframe(trees: &NamespacedSledStorage, checksums: bool) -> Result<usize> {
+    let mut rewritten = 0;
+    for (key, value) in trees.snapshot().await? {
+        let value = match (checksums, checksum::is_framed(&value)) {
+            (true, false) => checksum::frame_value(&value),
+            (false, true) => value[checksum::FRAME_HEADER_LEN..].to_vec(),
+            _ => continue,
+        };
+        trees.put(key, value).await?;
+        rewritten += 1;
+    }
+    trees.flush().await?;
+    Ok(rewritten)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(namespaces: &[&str]) -> StorageLayout {
+        StorageLayout {
+            namespaces: namespaces.iter().map(|n| n.to_string()).collect(),
+            ..StorageLayout::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_layout_is_recorded_and_migrated() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        assert_eq!(StorageLayout::recorded(&db).unwrap(), None);
+
+        let storage = LayeredStorage::open(db.clone(), &StorageLayout::default()).unwrap();
+        for key in ["a/1", "a/2", "b/1"] {
+            storage
+                .put(key.as_bytes().to_vec(), key.as_bytes().to_vec())
+                .await
+                .unwrap();
+        }
+        drop(storage);
+        assert_eq!(
+            StorageLayout::recorded(&db).unwrap(),
+            Some(StorageLayout::default())
+        );
+
+        // Another layout is refused until the data is migrated to it
+        let namespaced = layout(&["a/"]);
+        assert!(matches!(
+            LayeredStorage::open(db.clone(), &namespaced),
+            Err(ScribeError::Configuration(_))
+        ));
+        assert_eq!(migrate(db.clone(), &namespaced).await.unwrap(), 2);
+        let storage = LayeredStorage::open(db.clone(), &namespaced).unwrap();
+        assert_eq!(storage.trees().namespace_len(b"a/").await.unwrap(), 2);
+        assert_eq!(
+            storage.get(&b"b/1".to_vec()).await.unwrap(),
+            Some(b"b/1".to_vec())
+        );
+
+        drop(storage);
+
+        // Turning checksums on frames every value, and turning them off unframes it
+        let checksummed = StorageLayout {
+            checksums: true,
+            ..namespaced.clone()
+        };
+        assert_eq!(migrate(db.clone(), &checksummed).await.unwrap(), 3);
+        let storage = LayeredStorage::open(db.clone(), &checksummed).unwrap();
+        let stored = storage
+            .trees()
+            .get(&b"a/1".to_vec())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.len(), 3 + checksum::FRAME_HEADER_LEN);
+        assert_eq!(
+            storage.get(&b"a/1".to_vec()).await.unwrap(),
+            Some(b"a/1".to_vec())
+        );
+        drop(storage);
+        assert_eq!(migrate(db.clone(), &namespaced).await.unwrap(), 3);
+        let storage = LayeredStorage::open(db.clone(), &namespaced).unwrap();
+        assert_eq!(
+            storage.trees().get(&b"a/1".to_vec()).await.unwrap(),
+            Some(b"a/1".to_vec())
+        );
+
+        storage.clear().await.unwrap();
+        assert!(storage.snapshot().await.unwrap().is_empty());
+        assert!(LayeredStorage::open(db, &layout(&[""])).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_transformers_are_applied_and_migrated() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let plain = StorageLayout {
+            checksums: true,
+            ..layout(&["logs/"])
+        };
+        let storage = LayeredStorage::open(db.clone(), &plain).unwrap();
+        let value = vec![b'x'; 4096];
+        for key in ["logs/1", "users/1"] {
+            storage
+                .put(key.as_bytes().to_vec(), value.clone())
+                .await
+                .unwrap();
+        }
+        drop(storage);
+
+        // Compressing the logs rewrites only them
+        let mut compressed = plain.clone();
+        compressed
+            .transformers
+            .insert("logs/".to_string(), vec!["gzip".to_string()]);
+        assert!(LayeredStorage::open(db.clone(), &compressed).is_err());
+        assert_eq!(migrate(db.clone(), &compressed).await.unwrap(), 1);
+        let storage = LayeredStorage::open(db.clone(), &compressed).unwrap();
+        let stored = storage
+            .trees()
+            .get(&b"logs/1".to_vec())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(stored.len() < value.len());
+        assert_eq!(
+            storage.get(&b"logs/1".to_vec()).await.unwrap(),
+            Some(value.clone())
+        );
+
+        // New writes are compressed too, and reads decompress them
+        storage
+            .put(b"logs/2".to_vec(), value.clone())
+            .await
+            .unwrap();
+        let stored = storage
+            .trees()
+            .get(&b"logs/2".to_vec())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(stored.len() < value.len());
+        assert_eq!(storage.snapshot().await.unwrap().len(), 3);
+        drop(storage);
+
+        // Dropping the transformer decompresses every log again
+        assert_eq!(migrate(db.clone(), &plain).await.unwrap(), 2);
+        let storage = LayeredStorage::open(db, &plain).unwrap();
+        let stored = storage
+            .trees()
+            .get(&b"logs/2".to_vec())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.len(), value.len() + checksum::FRAME_HEADER_LEN);
+        assert_eq!(storage.get(&b"logs/2".to_vec()).await.unwrap(), Some(value));
+
+        let unknown = StorageLayout {
+            transformers: [("logs/".to_string(), vec!["zstd".to_string()])].into(),
+            ..StorageLayout::default()
+        };
+        assert!(unknown.validate().is_err());
+    }
+}
@@ -0,0 +1,436 @@
+//! Flat, memory-mappable segment format for flushed segments
+//!
+//! A flushed segment is written once and only read afterwards, so it is stored in a
+//! layout that can be searched in place instead of being deserialized into a
+//! `HashMap`:
+//!
+//! ```text
+//! magic "SCRBSEG1"
+//! data    key and value bytes of every entry, in key order
+//! index   one 16-byte record per entry: key offset (u64), key length (u32),
+//!         value length (u32); the value follows its key
+//! bloom   bloom filter over the keys
+//! footer  segment ID, timestamp, entry count, data size, index offset, bloom offset,
+//!         bloom length (u64 each), bloom hash count, flags (u32 each),
+//!         Merkle root (32 bytes), magic
+//! ```
+//!
+//! All integers are little-endian. Lookups check the bloom filter, then
+//! binary-search the index, so a [`MappedSegment`] costs no more memory than the
+//! pages the lookups touch.
+
+use crate::error::{Result, ScribeError};
+use crate::storage::segment::Segment;
+use crate::types::SegmentId;
+use memmap2::Mmap;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+/// Marks the start and end of a flat segment
+const MAGIC: &[u8; 8] = b"SCRBSEG1";
+
+/// Size of one index record
+const INDEX_ENTRY_LEN: usize = 16;
+
+/// Size of the footer
+const FOOTER_LEN: usize = 7 * 8 + 2 * 4 + 32 + MAGIC.len();
+
+/// Bloom filter bits per key (about 1% false positives with 7 hashes)
+const BLOOM_BITS_PER_KEY: usize = 10;
+
+/// Number of bloom filter hash functions
+const BLOOM_HASHES: u32 = 7;
+
+/// Footer flag: the segment has a Merkle root (empty segments do not)
+const FLAG_HAS_MERKLE_ROOT: u32 = 1;
+
+/// A flat segment held in any byte buffer
+///
+/// The layout is validated when the segment is opened, so lookups cannot read out
+/// of bounds.
+#[derive(Debug)]
+pub struct FlatSegment<B> {
+    bytes: B,
+    footer: Footer,
+}
+
+/// A flat segment mapped from a file
+pub type MappedSegment = FlatSegment<Mmap>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Footer {
+    segment_id: SegmentId,
+    timestamp: u64,
+    entry_count: usize,
+    size: usize,
+    index_offset: usize,
+    bloom_offset: usize,
+    bloom_len: usize,
+    bloom_hashes: u32,
+    merkle_root: Option<[u8; 32]>,
+}
+
+impl<B: AsRef<[u8]>> FlatSegment<B> {
+    /// Open a flat segment from its bytes, validating the layout
+    pub fn from_bytes(bytes: B) -> Result<Self> {
+        let data = bytes.as_ref();
+        let footer = Footer::parse(data)?;
+
+        // Every entry must lie inside the data section, in strictly ascending key order
+        let mut previous: Option<&[u8]> = None;
+        for i in 0..footer.entry_count {
+            let (key_offset, key_len, value_len) = index_entry(data, footer.index_offset, i);
+            let end = key_offset
+                .checked_add(key_len)
+                .and_then(|end| end.checked_add(value_len))
+                .filter(|&end| key_offset >= MAGIC.len() && end <= footer.index_offset)
+                .ok_or_else(|| corrupt(format!("entry {} is out of bounds", i)))?;
+            let key = &data[key_offset..end - value_len];
+            if previous.is_some_and(|previous| previous >= key) {
+                return Err(corrupt(format!("entry {} is out of order", i)));
+            }
+            previous = Some(key);
+        }
+
+        Ok(Self { bytes, footer })
+    }
+
+    /// ID of the segment
+    pub fn segment_id(&self) -> SegmentId {
+        self.footer.segment_id
+    }
+
+    /// Unix timestamp when the segment was created
+    pub fn timestamp(&self) -> u64 {
+        self.footer.timestamp
+    }
+
+    /// Number of key-value pairs
+    pub fn len(&self) -> usize {
+        self.footer.entry_count
+    }
+
+    /// Whether the segment holds no entries
+    pub fn is_empty(&self) -> bool {
+        self.footer.entry_count == 0
+    }
+
+    /// Total size of the keys and values in bytes
+    pub fn size(&self) -> usize {
+        self.footer.size
+    }
+
+    /// Merkle root of the entries, `None` for an empty segment
+    pub fn merkle_root(&self) -> Option<&[u8]> {
+        self.footer.merkle_root.as_ref().map(|root| root.as_slice())
+    }
+
+    /// Whether the key may be present (false positives are possible, false negatives not)
+    pub fn may_contain(&self, key: &[u8]) -> bool {
+        let bloom = &self.bytes.as_ref()
+            [self.footer.bloom_offset..self.footer.bloom_offset + self.footer.bloom_len];
+        bloom_positions(key, bloom.len() * 8, self.footer.bloom_hashes)
+            .all(|bit| bloom[bit / 8] & (1 << (bit % 8)) != 0)
+    }
+
+    /// Look up a value by key
+    pub fn get(&self, key: &[u8]) -> Option<&[u8]> {
+        if !self.may_contain(key) {
+            return None;
+        }
+
+        let (mut low, mut high) = (0, self.footer.entry_count);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let (entry_key, value) = self.entry(mid);
+            match entry_key.cmp(key) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => return Some(value),
+            }
+        }
+        None
+    }
+
+    /// Iterate over the entries in key order
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], &[u8])> {
+        (0..self.footer.entry_count).map(move |i| self.entry(i))
+    }
+
+    /// Copy the entries into an in-memory [`Segment`]
+    pub fn to_segment(&self) -> Segment {
+        let data: HashMap<_, _> = self
+            .iter()
+            .map(|(key, value)| (key.to_vec(), value.to_vec()))
+            .collect();
+        Segment {
+            segment_id: self.footer.segment_id,
+            timestamp: self.footer.timestamp,
+            data,
+            size: self.footer.size,
+        }
+    }
+
+    fn entry(&self, i: usize) -> (&[u8], &[u8]) {
+        let data = self.bytes.as_ref();
+        let (key_offset, key_len, value_len) = index_entry(data, self.footer.index_offset, i);
+        let value_offset = key_offset + key_len;
+        (
+            &data[key_offset..value_offset],
+            &data[value_offset..value_offset + value_len],
+        )
+    }
+}
+
+impl MappedSegment {
+    /// Map a flat segment file
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: segment files are written to a temporary path and renamed into
+        // place, and never modified afterwards
+        let mmap = unsafe { Mmap::map(&file)? };
+        Self::from_bytes(mmap)
+            .map_err(|e| ScribeError::Storage(format!("{}: {}", path.display(), e)))
+    }
+}
+
+/// Encode a segment in the flat format
+pub fn encode(segment: &Segment) -> Vec<u8> {
+    let mut entries: Vec<(&Vec<u8>, &Vec<u8>)> = segment.data.iter().collect();
+    entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+
+    let data_len: usize = entries.iter().map(|(k, v)| k.len() + v.len()).sum();
+    let bloom_bits = (entries.len() * BLOOM_BITS_PER_KEY)
+        .max(64)
+        .next_multiple_of(8);
+    let mut out = Vec::with_capacity(
+        MAGIC.len() + data_len + entries.len() * INDEX_ENTRY_LEN + bloom_bits / 8 + FOOTER_LEN,
+    );
+    out.extend_from_slice(MAGIC);
+
+    let mut index = Vec::with_capacity(entries.len() * INDEX_ENTRY_LEN);
+    let mut bloom = vec![0u8; bloom_bits / 8];
+    for (key, value) in &entries {
+        index.extend_from_slice(&(out.len() as u64).to_le_bytes());
+        index.extend_from_slice(&(key.len() as u32).to_le_bytes());
+        index.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        out.extend_from_slice(key);
+        out.extend_from_slice(value);
+        for bit in bloom_positions(key, bloom_bits, BLOOM_HASHES) {
+            bloom[bit / 8] |= 1 << (bit % 8);
+        }
+    }
+
+    let index_offset = out.len();
+    out.extend_from_slice(&index);
+    let bloom_offset = out.len();
+    out.extend_from_slice(&bloom);
+
+    let merkle_root = segment.compute_merkle_root();
+    for field in [
+        segment.segment_id,
+        segment.timestamp,
+        entries.len() as u64,
+        segment.size as u64,
+        index_offset as u64,
+        bloom_offset as u64,
+        bloom.len() as u64,
+    ] {
+        out.extend_from_slice(&field.to_le_bytes());
+    }
+    out.extend_from_slice(&BLOOM_HASHES.to_le_bytes());
+    let flags = if merkle_root.is_some() {
+        FLAG_HAS_MERKLE_ROOT
+    } else {
+        0
+    };
+    out.extend_from_slice(&flags.to_le_bytes());
+    let mut root = [0u8; 32];
+    if let Some(merkle_root) = &merkle_root {
+        root.copy_from_slice(&merkle_root[..32]);
+    }
+    out.extend_from_slice(&root);
+    out.extend_from_slice(MAGIC);
+    out
+}
+
+/// Write a segment to `path` in the flat format
+///
+/// The file is written under a temporary name, synced and renamed into place, so a
+/// segment file is either complete or absent.
+pub fn write(segment: &Segment, path: &Path) -> Result<()> {
+    let temp = path.with_extension("seg.tmp");
+    let mut file = File::create(&temp)?;
+    file.write_all(&encode(segment))?;
+    file.sync_all()?;
+    std::fs::rename(&temp, path)?;
+    Ok(())
+}
+
+impl Footer {
+    fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < MAGIC.len() + FOOTER_LEN || &data[..MAGIC.len()] != MAGIC {
+            return Err(corrupt("missing header".to_string()));
+        }
+        let footer = &data[data.len() - FOOTER_LEN..];
+        if &footer[FOOTER_LEN - MAGIC.len()..] != MAGIC {
+            return Err(corrupt("missing footer".to_string()));
+        }
+
+        let field = |i: usize| read_u64(footer, i * 8);
+        let to_usize = |value: u64| {
+            usize::try_from(value).map_err(|_| corrupt("offset overflows".to_string()))
+        };
+        let flags = read_u32(footer, 7 * 8 + 4);
+        let mut merkle_root = [0u8; 32];
+        merkle_root.copy_from_slice(&footer[7 * 8 + 8..7 * 8 + 8 + 32]);
+
+        let parsed = Self {
+            segment_id: field(0),
+            timestamp: field(1),
+            entry_count: to_usize(field(2))?,
+            size: to_usize(field(3))?,
+            index_offset: to_usize(field(4))?,
+            bloom_offset: to_usize(field(5))?,
+            bloom_len: to_usize(field(6))?,
+            bloom_hashes: read_u32(footer, 7 * 8),
+            merkle_root: (flags & FLAG_HAS_MERKLE_ROOT != 0).then_some(merkle_root),
+        };
+
+        // The sections must tile the file exactly
+        let index_end = parsed
+            .entry_count
+            .checked_mul(INDEX_ENTRY_LEN)
+            .and_then(|len| len.checked_add(parsed.index_offset));
+        let bloom_end = parsed.bloom_offset.checked_add(parsed.bloom_len);
+        if parsed.index_offset < MAGIC.len()
+            || index_end != Some(parsed.bloom_offset)
+            || bloom_end != Some(data.len() - FOOTER_LEN)
+            || parsed.bloom_len == 0
+            || parsed.bloom_hashes == 0
+        {
+            return Err(corrupt("inconsistent section offsets".to_string()));
+        }
+        Ok(parsed)
+    }
+}
+
+/// Key offset, key length and value length of index record `i`
+fn index_entry(data: &[u8], index_offset: usize, i: usize) -> (usize, usize, usize) {
+    let record = index_offset + i * INDEX_ENTRY_LEN;
+    (
+        read_u64(data, record) as usize,
+        read_u32(data, record + 8) as usize,
+        read_u32(data, record + 12) as usize,
+    )
+}
+
+fn read_u64(data: &[u8], at: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&data[at..at + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+fn read_u32(data: &[u8], at: usize) -> u32 {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&data[at..at + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+/// Bloom filter bits for a key, by double hashing
+fn bloom_positions(key: &[u8], bits: usize, hashes: u32) -> impl Iterator<Item = usize> {
+    let h1 = fnv1a(0xcbf2_9ce4_8422_2325, key);
+    let h2 = fnv1a(0x8422_2325_cbf2_9ce4, key) | 1;
+    (0..hashes as u64).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % bits as u64) as usize)
+}
+
+/// FNV-1a with a custom offset basis; stable across platforms and releases
+fn fnv1a(basis: u64, data: &[u8]) -> u64 {
+    data.iter().fold(basis, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+fn corrupt(reason: String) -> ScribeError {
+    ScribeError::Storage(format!("Corrupt flat segment: {}", reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_segment() -> Segment {
+        let mut segment = Segment::new(42);
+        for i in 0..100u32 {
+            segment.put(
+                format!("key-{:03}", i).into_bytes(),
+                format!("value-{}", i).into_bytes(),
+            );
+        }
+        segment
+    }
+
+    #[test]
+    fn test_encode_and_lookup() {
+        let segment = sample_segment();
+        let flat = FlatSegment::from_bytes(encode(&segment)).unwrap();
+
+        assert_eq!(flat.segment_id(), 42);
+        assert_eq!(flat.timestamp(), segment.timestamp);
+        assert_eq!(flat.len(), 100);
+        assert_eq!(flat.size(), segment.size);
+        assert_eq!(
+            flat.merkle_root().map(<[u8]>::to_vec),
+            segment.compute_merkle_root()
+        );
+        for (key, value) in &segment.data {
+            assert!(flat.may_contain(key));
+            assert_eq!(flat.get(key), Some(value.as_slice()));
+        }
+        assert_eq!(flat.get(b"key-100"), None);
+        assert_eq!(flat.get(b""), None);
+
+        let keys: Vec<&[u8]> = flat.iter().map(|(key, _)| key).collect();
+        assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(flat.to_segment().data, segment.data);
+    }
+
+    #[test]
+    fn test_empty_segment() {
+        let segment = Segment::new(1);
+        let flat = FlatSegment::from_bytes(encode(&segment)).unwrap();
+        assert!(flat.is_empty());
+        assert_eq!(flat.merkle_root(), None);
+        assert_eq!(flat.get(b"anything"), None);
+    }
+
+    #[test]
+    fn test_rejects_corrupt_bytes() {
+        let bytes = encode(&sample_segment());
+        assert!(FlatSegment::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(FlatSegment::from_bytes(&bytes[1..]).is_err());
+
+        // Point the first index record past the data section
+        let index_offset = read_u64(&bytes, bytes.len() - FOOTER_LEN + 4 * 8) as usize;
+        let mut bad = bytes.clone();
+        bad[index_offset..index_offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(FlatSegment::from_bytes(bad).is_err());
+    }
+
+    #[test]
+    fn test_write_and_map_file() {
+        let dir = std::env::temp_dir().join(format!("scribe-mapped-{}", fastrand::u64(..)));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("segment.seg");
+
+        let segment = sample_segment();
+        write(&segment, &path).unwrap();
+        let mapped = MappedSegment::open(&path).unwrap();
+        assert_eq!(mapped.get(b"key-007"), Some(b"value-7".as_slice()));
+        assert_eq!(mapped.len(), segment.len());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
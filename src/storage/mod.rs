@@ -3,16 +3,45 @@
 //! This module contains the storage abstraction layer and Sled implementation.
 
 pub mod archival;
+pub mod checksum;
+pub mod classes;
+pub mod consistency;
+pub mod credentials;
+pub mod faults;
+pub mod layout;
+pub mod mapped;
+pub mod namespaced;
+pub mod roll_journal;
 pub mod s3;
 pub mod segment;
+pub mod transform;
 
 use crate::error::{Result, ScribeError};
 use crate::types::{Key, Value};
 use async_trait::async_trait;
 use sled::Db;
 use std::collections::HashMap;
+use std::ops::{Bound, RangeBounds};
 use std::path::Path;
 
+/// Bounds of a key range, as taken by [`StorageBackend::range`]
+pub type KeyRange = (Bound<Key>, Bound<Key>);
+
+/// Range of the keys starting with `prefix`
+pub fn prefix_range(prefix: &[u8]) -> KeyRange {
+    // The first key after the prefix range: the prefix with its last byte below 0xff
+    // incremented and the bytes after it dropped
+    let end = match prefix.iter().rposition(|byte| *byte != 0xff) {
+        Some(last) => {
+            let mut end = prefix[..=last].to_vec();
+            end[last] += 1;
+            Bound::Excluded(end)
+        }
+        None => Bound::Unbounded,
+    };
+    (Bound::Included(prefix.to_vec()), end)
+}
+
 /// Storage backend trait for async operations
 ///
 /// This trait provides an async abstraction over the underlying storage engine.
@@ -33,6 +62,50 @@ pub trait StorageBackend: Send + Sync {
 
     /// Take a snapshot of all data in storage
     async fn snapshot(&self) -> Result<HashMap<Key, Value>>;
+
+    /// Key-value pairs whose key is within `range`, in key order
+    ///
+    /// The default implementation filters a full snapshot; backends that keep keys
+    /// ordered should read only the range.
+    async fn range(&self, range: KeyRange) -> Result<Vec<(Key, Value)>> {
+        let mut pairs: Vec<(Key, Value)> = self
+            .snapshot()
+            .await?
+            .into_iter()
+            .filter(|(key, _)| range.contains(key))
+            .collect();
+        pairs.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(pairs)
+    }
+
+    /// Key-value pairs whose key starts with `prefix`, in key order
+    async fn scan(&self, prefix: &[u8]) -> Result<Vec<(Key, Value)>> {
+        self.range(prefix_range(prefix)).await
+    }
+
+    /// Atomically replace the value of `key` with `new` if it is still `expected`
+    ///
+    /// `None` stands for an absent key, both as the expected value and as the new
+    /// one, which deletes the key. Returns whether the swap took place. Backends
+    /// without atomic updates do not support it.
+    async fn compare_and_swap(
+        &self,
+        key: Key,
+        expected: Option<Value>,
+        new: Option<Value>,
+    ) -> Result<bool> {
+        let _ = (key, expected, new);
+        Err(ScribeError::Unsupported(
+            "Compare-and-swap is not supported by this storage backend".to_string(),
+        ))
+    }
+
+    /// Store `value` at `key` unless the key already exists
+    ///
+    /// Returns whether the value was stored.
+    async fn put_if_absent(&self, key: Key, value: Value) -> Result<bool> {
+        self.compare_and_swap(key, None, Some(value)).await
+    }
 }
 
 /// Sled-based storage implementation
@@ -142,6 +215,35 @@ impl StorageBackend for SledStorage {
         .await
         .map_err(|e| ScribeError::Other(format!("Task join error: {}", e)))?
     }
+
+    async fn range(&self, range: KeyRange) -> Result<Vec<(Key, Value)>> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || {
+            db.range(range)
+                .map(|item| {
+                    let (key, value) = item?;
+                    Ok((key.to_vec(), value.to_vec()))
+                })
+                .collect::<Result<Vec<_>>>()
+        })
+        .await
+        .map_err(|e| ScribeError::Other(format!("Task join error: {}", e)))?
+    }
+
+    async fn compare_and_swap(
+        &self,
+        key: Key,
+        expected: Option<Value>,
+        new: Option<Value>,
+    ) -> Result<bool> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || {
+            let swapped = db.compare_and_swap(key, expected, new)?.is_ok();
+            Ok::<bool, ScribeError>(swapped)
+        })
+        .await
+        .map_err(|e| ScribeError::Other(format!("Task join error: {}", e)))?
+    }
 }
 
 #[cfg(test)]
@@ -223,6 +325,136 @@ mod tests {
         assert_eq!(storage.len().await.unwrap(), 1);
     }
 
+    #[test]
+    fn test_prefix_range() {
+        assert_eq!(
+            prefix_range(b"user:"),
+            (
+                Bound::Included(b"user:".to_vec()),
+                Bound::Excluded(b"user;".to_vec())
+            )
+        );
+        assert_eq!(
+            prefix_range(&[b'a', 0xff, 0xff]),
+            (
+                Bound::Included(vec![b'a', 0xff, 0xff]),
+                Bound::Excluded(b"b".to_vec())
+            )
+        );
+        assert_eq!(
+            prefix_range(&[0xff]),
+            (Bound::Included(vec![0xff]), Bound::Unbounded)
+        );
+        assert_eq!(
+            prefix_range(b""),
+            (Bound::Included(Vec::new()), Bound::Unbounded)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_storage_scan_and_range() {
+        let storage = SledStorage::temp().unwrap();
+        for key in ["user:2", "user:1", "user;", "order:1", "user:3"] {
+            storage
+                .put(key.as_bytes().to_vec(), key.as_bytes().to_vec())
+                .await
+                .unwrap();
+        }
+        let keys = |pairs: Vec<(Key, Value)>| -> Vec<Key> {
+            pairs.into_iter().map(|(key, _)| key).collect()
+        };
+
+        assert_eq!(
+            keys(storage.scan(b"user:").await.unwrap()),
+            vec![b"user:1".to_vec(), b"user:2".to_vec(), b"user:3".to_vec()]
+        );
+        assert_eq!(
+            keys(
+                storage
+                    .range((
+                        Bound::Excluded(b"user:1".to_vec()),
+                        Bound::Included(b"user;".to_vec())
+                    ))
+                    .await
+                    .unwrap()
+            ),
+            vec![b"user:2".to_vec(), b"user:3".to_vec(), b"user;".to_vec()]
+        );
+
+        // The default implementation, over a snapshot, agrees
+        struct SnapshotOnly(SledStorage);
+
+        #[async_trait]
+        impl StorageBackend for SnapshotOnly {
+            async fn put(&self, key: Key, value: Value) -> Result<()> {
+                self.0.put(key, value).await
+            }
+            async fn get(&self, key: &Key) -> Result<Option<Value>> {
+                self.0.get(key).await
+            }
+            async fn delete(&self, key: &Key) -> Result<()> {
+                self.0.delete(key).await
+            }
+            async fn flush(&self) -> Result<()> {
+                self.0.flush().await
+            }
+            async fn snapshot(&self) -> Result<HashMap<Key, Value>> {
+                self.0.snapshot().await
+            }
+        }
+
+        let storage = SnapshotOnly(storage);
+        assert_eq!(
+            storage.scan(b"user:").await.unwrap(),
+            storage.0.scan(b"user:").await.unwrap()
+        );
+        assert_eq!(
+            storage
+                .range((Bound::Unbounded, Bound::Excluded(b"user:2".to_vec())))
+                .await
+                .unwrap(),
+            storage
+                .0
+                .range((Bound::Unbounded, Bound::Excluded(b"user:2".to_vec())))
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_storage_compare_and_swap() {
+        let storage = SledStorage::temp().unwrap();
+        let key = b"test_key".to_vec();
+
+        assert!(storage
+            .put_if_absent(key.clone(), b"v1".to_vec())
+            .await
+            .unwrap());
+        assert!(!storage
+            .put_if_absent(key.clone(), b"v2".to_vec())
+            .await
+            .unwrap());
+        assert_eq!(storage.get(&key).await.unwrap(), Some(b"v1".to_vec()));
+
+        // A stale expected value leaves the key alone
+        assert!(!storage
+            .compare_and_swap(key.clone(), Some(b"v0".to_vec()), Some(b"v2".to_vec()))
+            .await
+            .unwrap());
+        assert!(storage
+            .compare_and_swap(key.clone(), Some(b"v1".to_vec()), Some(b"v2".to_vec()))
+            .await
+            .unwrap());
+        assert_eq!(storage.get(&key).await.unwrap(), Some(b"v2".to_vec()));
+
+        // Swapping in nothing deletes the key
+        assert!(storage
+            .compare_and_swap(key.clone(), Some(b"v2".to_vec()), None)
+            .await
+            .unwrap());
+        assert_eq!(storage.get(&key).await.unwrap(), None);
+    }
+
     #[tokio::test]
     async fn test_storage_clear() {
         let storage = SledStorage::temp().unwrap();
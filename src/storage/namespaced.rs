@@ -0,0 +1,608 @@
+//! Sled storage with a separate tree per namespace
+//!
+//! [`NamespacedSledStorage`] keeps the keys of each configured namespace (a key
+//! prefix, as in [`crate::storage::transform`]) in a sled tree of its own, named
+//! `ns:<namespace>`, and every other key in the default tree. Keys are stored
+//! unchanged, so the layout is invisible through [`StorageBackend`], but:
+//!
+//! - dropping a namespace drops its tree instead of deleting keys one by one
+//! - per-namespace entry counts and sizes only look at that namespace's data
+//! - scanning a namespace never touches other tenants' keys
+//!
+//! When a key matches several namespaces, the longest one wins.
+//! [`NamespacedSledStorage::migrate`] moves keys written under the single-tree
+//! prefix layout (or under a different namespace list) into the tree they belong in.
+
+use crate::error::{Result, ScribeError};
+use crate::storage::{KeyRange, StorageBackend};
+use crate::types::{Key, Value};
+use async_trait::async_trait;
+use sled::transaction::TransactionError;
+use sled::{Db, Transactional, Tree};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+/// Prefix of the names of namespace trees
+const TREE_PREFIX: &[u8] = b"ns:";
+
+/// Keys moved per transaction during migration
+const MIGRATION_BATCH: usize = 1000;
+
+/// Configured namespaces and their trees, longest namespace first
+type NamespaceTrees = Vec<(Vec<u8>, Tree)>;
+
+/// Sled-based storage with one tree per namespace
+///
+/// Clones share the database and namespace trees.
+#[derive(Clone)]
+pub struct NamespacedSledStorage {
+    db: Db,
+    trees: Arc<RwLock<NamespaceTrees>>,
+}
+
+impl NamespacedSledStorage {
+    /// Open storage at the given path with the given namespaces
+    pub fn new<P, I, N>(path: P, namespaces: I) -> Result<Self>
+    where
+        P: AsRef<Path>,
+        I: IntoIterator<Item = N>,
+        N: Into<Vec<u8>>,
+    {
+        Self::with_db(sled::open(path)?, namespaces)
+    }
+
+    /// Create a temporary instance for testing
+    pub fn temp<I, N>(namespaces: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = N>,
+        N: Into<Vec<u8>>,
+    {
+        Self::with_db(sled::Config::new().temporary(true).open()?, namespaces)
+    }
+
+    /// Open storage in an open database with the given namespaces
+    pub fn with_db<I, N>(db: Db, namespaces: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = N>,
+        N: Into<Vec<u8>>,
+    {
+        let mut namespaces: Vec<Vec<u8>> = namespaces.into_iter().map(Into::into).collect();
+        if namespaces.iter().any(|namespace| namespace.is_empty()) {
+            return Err(ScribeError::Configuration(
+                "Storage namespaces must not be empty".to_string(),
+            ));
+        }
+        namespaces.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        namespaces.dedup();
+
+        let trees = namespaces
+            .into_iter()
+            .map(|namespace| {
+                let tree = db.open_tree(tree_name(&namespace))?;
+                Ok((namespace, tree))
+            })
+            .collect::<Result<NamespaceTrees>>()?;
+        Ok(Self {
+            db,
+            trees: Arc::new(RwLock::new(trees)),
+        })
+    }
+
+    /// Configured namespaces, longest first
+    pub fn namespaces(&self) -> Result<Vec<Vec<u8>>> {
+        let trees = self
+            .trees
+            .read()
+            .map_err(|e| ScribeError::Other(format!("Lock error: {}", e)))?;
+        Ok(trees
+            .iter()
+            .map(|(namespace, _)| namespace.clone())
+            .collect())
+    }
+
+    /// Get the number of entries across all namespaces
+    pub async fn len(&self) -> Result<usize> {
+        self.with_trees(|db, trees| {
+            Ok(db.len() + trees.iter().map(|(_, tree)| tree.len()).sum::<usize>())
+        })
+        .await
+    }
+
+    /// Check if storage is empty
+    pub async fn is_empty(&self) -> Result<bool> {
+        self.with_trees(|db, trees| {
+            Ok(db.is_empty() && trees.iter().all(|(_, tree)| tree.is_empty()))
+        })
+        .await
+    }
+
+    /// Get the number of entries in a namespace
+    pub async fn namespace_len(&self, namespace: &[u8]) -> Result<usize> {
+        let namespace = namespace.to_vec();
+        self.with_trees(move |_, trees| Ok(find_tree(trees, &namespace)?.len()))
+            .await
+    }
+
+    /// Get the total size in bytes of the keys and values in a namespace
+    pub async fn namespace_size(&self, namespace: &[u8]) -> Result<u64> {
+        let namespace = namespace.to_vec();
+        self.with_trees(move |_, trees| {
+            let mut size = 0u64;
+            for item in find_tree(trees, &namespace)?.iter() {
+                let (key, value) = item?;
+                size += (key.len() + value.len()) as u64;
+            }
+            Ok(size)
+        })
+        .await
+    }
+
+    /// Read every entry in a namespace, in key order
+    pub async fn scan_namespace(&self, namespace: &[u8]) -> Result<Vec<(Key, Value)>> {
+        let namespace = namespace.to_vec();
+        self.with_trees(move |_, trees| {
+            find_tree(trees, &namespace)?
+                .iter()
+                .map(|item| {
+                    let (key, value) = item?;
+                    Ok((key.to_vec(), value.to_vec()))
+                })
+                .collect()
+        })
+        .await
+    }
+
+    /// Delete every entry in a namespace by dropping its tree
+    ///
+    /// The namespace stays configured and starts out empty.
+    pub async fn drop_namespace(&self, namespace: &[u8]) -> Result<()> {
+        let db = self.db.clone();
+        let trees = self.trees.clone();
+        let namespace = namespace.to_vec();
+        tokio::task::spawn_blocking(move || {
+            let mut trees = trees
+                .write()
+                .map_err(|e| ScribeError::Other(format!("Lock error: {}", e)))?;
+            let entry = trees
+                .iter_mut()
+                .find(|(configured, _)| *configured == namespace)
+                .ok_or_else(|| not_configured(&namespace))?;
+            let name = tree_name(&namespace);
+            db.drop_tree(&name)?;
+            entry.1 = db.open_tree(&name)?;
+            Ok::<(), ScribeError>(())
+        })
+        .await
+        .map_err(|e| ScribeError::Other(format!("Task join error: {}", e)))?
+    }
+
+    /// Delete every entry, dropping the tree of each namespace
+    pub async fn clear(&self) -> Result<()> {
+        let db = self.db.clone();
+        let trees = self.trees.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut trees = trees
+                .write()
+                .map_err(|e| ScribeError::Other(format!("Lock error: {}", e)))?;
+            db.clear()?;
+            for (namespace, tree) in trees.iter_mut() {
+                let name = tree_name(namespace);
+                db.drop_tree(&name)?;
+                *tree = db.open_tree(&name)?;
+            }
+            Ok::<(), ScribeError>(())
+        })
+        .await
+        .map_err(|e| ScribeError::Other(format!("Task join error: {}", e)))?
+    }
+
+    /// Write `entries` and insert `record` into `tree`, all in one transaction
+    ///
+    /// Either every entry is written along with the record, or nothing is.
+    pub async fn put_all_recorded(
+        &self,
+        entries: Vec<(Key, Value)>,
+        tree: Tree,
+        record: (Vec<u8>, Vec<u8>),
+    ) -> Result<()> {
+        self.with_trees(move |db, trees| {
+            let mut all: Vec<Tree> = vec![(**db).clone()];
+            all.extend(trees.iter().map(|(_, tree)| tree.clone()));
+            let targets: Vec<usize> = entries
+                .iter()
+                .map(|(key, _)| {
+                    let target = tree_for(db, trees, key).name();
+                    all.iter()
+                        .position(|tree| tree.name() == target)
+                        .unwrap_or(0)
+                })
+                .collect();
+            all.push(tree);
+            all.as_slice()
+                .transaction(|txs| {
+                    for ((key, value), target) in entries.iter().zip(&targets) {
+                        txs[*target].insert(key.as_slice(), value.as_slice())?;
+                    }
+                    txs[txs.len() - 1].insert(record.0.as_slice(), record.1.as_slice())?;
+                    Ok(())
+                })
+                .map_err(|e: TransactionError<()>| {
+                    ScribeError::Storage(format!("Transaction failed: {:?}", e))
+                })
+        })
+        .await
+    }
+
+    /// Move keys into the tree of their namespace
+    ///
+    /// Covers keys written to the default tree under the single-tree prefix layout,
+    /// keys in the tree of a shorter namespace than the one they now match, and keys
+    /// in trees of namespaces that are no longer configured, whose trees are dropped
+    /// once empty. Each batch of keys is moved in one transaction, so an interrupted
+    /// migration can simply be run again. Returns the number of keys moved.
+    pub async fn migrate(&self) -> Result<usize> {
+        let db = self.db.clone();
+        let trees = self.trees.clone();
+        tokio::task::spawn_blocking(move || {
+            let trees = trees
+                .write()
+                .map_err(|e| ScribeError::Other(format!("Lock error: {}", e)))?;
+            let configured: Vec<Vec<u8>> = trees
+                .iter()
+                .map(|(namespace, _)| tree_name(namespace))
+                .collect();
+
+            let mut sources: Vec<(Tree, bool)> = vec![((*db).clone(), false)];
+            sources.extend(trees.iter().map(|(_, tree)| (tree.clone(), false)));
+            for name in db.tree_names() {
+                if name.starts_with(TREE_PREFIX) && !configured.contains(&name.to_vec()) {
+                    sources.push((db.open_tree(&name)?, true));
+                }
+            }
+
+            let mut moved = 0;
+            for (source, stale) in sources {
+                moved += migrate_tree(&db, &trees, &source)?;
+                if stale {
+                    db.drop_tree(source.name())?;
+                }
+            }
+            db.flush()?;
+            Ok::<usize, ScribeError>(moved)
+        })
+        .await
+        .map_err(|e| ScribeError::Other(format!("Task join error: {}", e)))?
+    }
+
+    /// Run a blocking closure over the database and namespace trees
+    async fn with_trees<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Db, &NamespaceTrees) -> Result<T> + Send + 'static,
+    {
+        let db = self.db.clone();
+        let trees = self.trees.clone();
+        tokio::task::spawn_blocking(move || {
+            // Held for the whole operation so it cannot race a namespace drop
+            let trees = trees
+                .read()
+                .map_err(|e| ScribeError::Other(format!("Lock error: {}", e)))?;
+            f(&db, &trees)
+        })
+        .await
+        .map_err(|e| ScribeError::Other(format!("Task join error: {}", e)))?
+    }
+}
+
+/// Name of the tree holding a namespace
+fn tree_name(namespace: &[u8]) -> Vec<u8> {
+    [TREE_PREFIX, namespace].concat()
+}
+
+/// Tree a key belongs in, by longest matching namespace
+fn tree_for<'a>(db: &'a Db, trees: &'a NamespaceTrees, key: &[u8]) -> &'a Tree {
+    trees
+        .iter()
+        .find(|(namespace, _)| key.starts_with(namespace))
+        .map(|(_, tree)| tree)
+        .unwrap_or(db)
+}
+
+/// Tree of a configured namespace
+fn find_tree<'a>(trees: &'a NamespaceTrees, namespace: &[u8]) -> Result<&'a Tree> {
+    trees
+        .iter()
+        .find(|(configured, _)| configured == namespace)
+        .map(|(_, tree)| tree)
+        .ok_or_else(|| not_configured(namespace))
+}
+
+fn not_configured(namespace: &[u8]) -> ScribeError {
+    ScribeError::NotFound(format!(
+        "Storage namespace {:?} is not configured",
+        String::from_utf8_lossy(namespace)
+    ))
+}
+
+/// Move the keys of `source` that belong in another tree, returning how many moved
+fn migrate_tree(db: &Db, trees: &NamespaceTrees, source: &Tree) -> Result<usize> {
+    let mut moved = 0;
+    let mut batch: Vec<(sled::IVec, sled::IVec, Tree)> = Vec::new();
+    for item in source.iter() {
+        let (key, value) = item?;
+        let target = tree_for(db, trees, &key);
+        if target.name() != source.name() {
+            batch.push((key, value, target.clone()));
+        }
+        if batch.len() >= MIGRATION_BATCH {
+            moved += move_batch(source, &mut batch)?;
+        }
+    }
+    moved += move_batch(source, &mut batch)?;
+    Ok(moved)
+}
+
+/// Move a batch of entries out of `source` in one transaction per target tree
+fn move_batch(source: &Tree, batch: &mut Vec<(sled::IVec, sled::IVec, Tree)>) -> Result<usize> {
+    let mut by_target: HashMap<sled::IVec, (Tree, Vec<(sled::IVec, sled::IVec)>)> = HashMap::new();
+    for (key, value, target) in batch.drain(..) {
+        by_target
+            .entry(target.name())
+            .or_insert_with(|| (target, Vec::new()))
+            .1
+            .push((key, value));
+    }
+
+    let mut moved = 0;
+    for (target, entries) in by_target.into_values() {
+        (source, &target)
+            .transaction(|(source, target)| {
+                for (key, value) in &entries {
+                    target.insert(key, value)?;
+                    source.remove(key)?;
+                }
+                Ok(())
+            })
+            .map_err(|e: TransactionError<()>| {
+                ScribeError::Storage(format!("Namespace migration failed: {:?}", e))
+            })?;
+        moved += entries.len();
+    }
+    Ok(moved)
+}
+
+#[async_trait]
+impl StorageBackend for NamespacedSledStorage {
+    async fn put(&self, key: Key, value: Value) -> Result<()> {
+        self.with_trees(move |db, trees| {
+            tree_for(db, trees, &key).insert(key, value)?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get(&self, key: &Key) -> Result<Option<Value>> {
+        let key = key.clone();
+        self.with_trees(move |db, trees| {
+            Ok(tree_for(db, trees, &key)
+                .get(&key)?
+                .map(|ivec| ivec.to_vec()))
+        })
+        .await
+    }
+
+    async fn delete(&self, key: &Key) -> Result<()> {
+        let key = key.clone();
+        self.with_trees(move |db, trees| {
+            tree_for(db, trees, &key).remove(&key)?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.with_trees(|db, _| {
+            db.flush()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn snapshot(&self) -> Result<HashMap<Key, Value>> {
+        self.with_trees(|db, trees| {
+            let mut snapshot = HashMap::new();
+            let all_trees = std::iter::once(&**db).chain(trees.iter().map(|(_, tree)| tree));
+            for tree in all_trees {
+                for item in tree.iter() {
+                    let (key, value) = item?;
+                    snapshot.insert(key.to_vec(), value.to_vec());
+                }
+            }
+            Ok(snapshot)
+        })
+        .await
+    }
+
+    async fn range(&self, range: KeyRange) -> Result<Vec<(Key, Value)>> {
+        self.with_trees(move |db, trees| {
+            // A range can span namespaces, so every tree contributes
+            let mut pairs = Vec::new();
+            let all_trees = std::iter::once(&**db).chain(trees.iter().map(|(_, tree)| tree));
+            for tree in all_trees {
+                for item in tree.range(range.clone()) {
+                    let (key, value) = item?;
+                    pairs.push((key.to_vec(), value.to_vec()));
+                }
+            }
+            pairs.sort_by(|a, b| a.0.cmp(&b.0));
+            Ok(pairs)
+        })
+        .await
+    }
+
+    async fn compare_and_swap(
+        &self,
+        key: Key,
+        expected: Option<Value>,
+        new: Option<Value>,
+    ) -> Result<bool> {
+        self.with_trees(move |db, trees| {
+            Ok(tree_for(db, trees, &key)
+                .compare_and_swap(&key, expected, new)?
+                .is_ok())
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_namespaces_use_own_trees() {
+        let storage =
+            NamespacedSledStorage::temp(["tenant-a/", "tenant-a/logs/", "tenant-b/"]).unwrap();
+        for key in [
+            "tenant-a/1",
+            "tenant-a/logs/1",
+            "tenant-a/logs/2",
+            "tenant-b/1",
+            "misc",
+        ] {
+            storage
+                .put(key.as_bytes().to_vec(), b"value".to_vec())
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(storage.len().await.unwrap(), 5);
+        assert_eq!(storage.namespace_len(b"tenant-a/").await.unwrap(), 1);
+        assert_eq!(storage.namespace_len(b"tenant-a/logs/").await.unwrap(), 2);
+        assert_eq!(
+            storage.namespace_size(b"tenant-b/").await.unwrap(),
+            ("tenant-b/1".len() + "value".len()) as u64
+        );
+        assert_eq!(
+            storage.scan_namespace(b"tenant-a/logs/").await.unwrap(),
+            vec![
+                (b"tenant-a/logs/1".to_vec(), b"value".to_vec()),
+                (b"tenant-a/logs/2".to_vec(), b"value".to_vec()),
+            ]
+        );
+        assert_eq!(storage.snapshot().await.unwrap().len(), 5);
+        // Scans cross namespace trees in key order
+        let keys: Vec<Key> = storage
+            .scan(b"tenant-a/")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                b"tenant-a/1".to_vec(),
+                b"tenant-a/logs/1".to_vec(),
+                b"tenant-a/logs/2".to_vec()
+            ]
+        );
+        assert_eq!(
+            storage
+                .range((
+                    std::ops::Bound::Excluded(b"tenant-a/logs/2".to_vec()),
+                    std::ops::Bound::Unbounded
+                ))
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(matches!(
+            storage.namespace_len(b"tenant-c/").await,
+            Err(ScribeError::NotFound(_))
+        ));
+
+        storage.drop_namespace(b"tenant-a/logs/").await.unwrap();
+        assert_eq!(storage.namespace_len(b"tenant-a/logs/").await.unwrap(), 0);
+        assert_eq!(storage.len().await.unwrap(), 3);
+        assert_eq!(
+            storage.get(&b"tenant-a/1".to_vec()).await.unwrap(),
+            Some(b"value".to_vec())
+        );
+
+        // The dropped namespace keeps working
+        storage
+            .put(b"tenant-a/logs/3".to_vec(), b"new".to_vec())
+            .await
+            .unwrap();
+        storage.delete(&b"misc".to_vec()).await.unwrap();
+        assert_eq!(storage.namespace_len(b"tenant-a/logs/").await.unwrap(), 1);
+        assert_eq!(storage.get(&b"misc".to_vec()).await.unwrap(), None);
+
+        // Swaps land in the namespace's tree
+        assert!(!storage
+            .put_if_absent(b"tenant-b/1".to_vec(), b"other".to_vec())
+            .await
+            .unwrap());
+        assert!(storage
+            .compare_and_swap(
+                b"tenant-b/1".to_vec(),
+                Some(b"value".to_vec()),
+                Some(b"swapped".to_vec())
+            )
+            .await
+            .unwrap());
+        assert_eq!(
+            storage.scan_namespace(b"tenant-b/").await.unwrap(),
+            vec![(b"tenant-b/1".to_vec(), b"swapped".to_vec())]
+        );
+
+        assert!(NamespacedSledStorage::temp([""]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_migrate_from_prefix_layout() {
+        let dir = std::env::temp_dir().join(format!("scribe-namespaced-{}", fastrand::u64(..)));
+
+        // Prefix layout: everything in the default tree
+        {
+            let storage = NamespacedSledStorage::new(&dir, Vec::<Vec<u8>>::new()).unwrap();
+            for key in ["a/1", "a/2", "b/1", "c/1", "other"] {
+                storage
+                    .put(key.as_bytes().to_vec(), key.as_bytes().to_vec())
+                    .await
+                    .unwrap();
+            }
+            storage.flush().await.unwrap();
+        }
+
+        {
+            let storage = NamespacedSledStorage::new(&dir, ["a/", "b/"]).unwrap();
+            assert_eq!(storage.namespace_len(b"a/").await.unwrap(), 0);
+            assert_eq!(storage.migrate().await.unwrap(), 3);
+            assert_eq!(storage.migrate().await.unwrap(), 0);
+            assert_eq!(storage.namespace_len(b"a/").await.unwrap(), 2);
+            assert_eq!(storage.namespace_len(b"b/").await.unwrap(), 1);
+            assert_eq!(storage.len().await.unwrap(), 5);
+            assert_eq!(
+                storage.get(&b"a/2".to_vec()).await.unwrap(),
+                Some(b"a/2".to_vec())
+            );
+            storage.flush().await.unwrap();
+        }
+
+        // Dropping "b/" from the configuration moves its keys back
+        let storage = NamespacedSledStorage::new(&dir, ["a/", "c/"]).unwrap();
+        assert_eq!(storage.migrate().await.unwrap(), 2);
+        assert_eq!(storage.namespace_len(b"c/").await.unwrap(), 1);
+        assert_eq!(
+            storage.get(&b"b/1".to_vec()).await.unwrap(),
+            Some(b"b/1".to_vec())
+        );
+        assert_eq!(storage.snapshot().await.unwrap().len(), 5);
+        drop(storage);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
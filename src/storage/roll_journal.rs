@@ -0,0 +1,174 @@
+//! Write-ahead intents for segment rolls
+//!
+//! A rolled segment goes through several steps before it is safely archived: its
+//! file is written to the segment directory, its data and metadata are uploaded to
+//! S3, and it is registered in the manifest before the local file is removed. A
+//! crash between two steps could strand the segment, for example uploaded but never
+//! registered. Before each step, a [`RollIntent`] naming the step is written next to
+//! the segment files, and it is removed once the segment is archived.
+//!
+//! On startup, intents left behind are reconciled:
+//!
+//! - [`RollStep::Writing`]: the segment file is kept if it was completed, otherwise
+//!   the partial file is removed (the roll is rolled back)
+//! - [`RollStep::Uploading`] and [`RollStep::Uploaded`]: if the metadata reached S3
+//!   the upload completed, and the segment is registered and its local file removed;
+//!   otherwise the partial upload is deleted and the segment stays local, to be
+//!   archived again
+//!
+//! The first step is handled by [`SegmentManager::with_segment_dir`], the others by
+//! [`ArchivalManager::recover_interrupted_rolls`].
+//!
+//! [`SegmentManager::with_segment_dir`]: crate::storage::segment::SegmentManager::with_segment_dir
+//! [`ArchivalManager::recover_interrupted_rolls`]: crate::storage::archival::ArchivalManager::recover_interrupted_rolls
+
+use crate::error::Result;
+use crate::types::SegmentId;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Step of a segment roll an intent was recorded for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RollStep {
+    /// The segment file is being written to the segment directory
+    Writing,
+    /// The segment is being uploaded to S3
+    Uploading,
+    /// Data and metadata are in S3; registration and local removal are pending
+    Uploaded,
+}
+
+/// An interrupted-roll marker for one segment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RollIntent {
+    /// Segment being rolled
+    pub segment_id: SegmentId,
+    /// Step started last
+    pub step: RollStep,
+    /// When the step started (Unix seconds)
+    pub started_at: u64,
+}
+
+/// Segments whose interrupted rolls were reconciled on startup
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RollRecovery {
+    /// Rolls carried through to the end
+    pub completed: Vec<SegmentId>,
+    /// Rolls undone, leaving the segment where it was before
+    pub rolled_back: Vec<SegmentId>,
+}
+
+impl RollRecovery {
+    /// Whether no interrupted roll was found
+    pub fn is_empty(&self) -> bool {
+        self.completed.is_empty() && self.rolled_back.is_empty()
+    }
+
+    /// Add the segments of `other`
+    pub fn merge(&mut self, other: RollRecovery) {
+        self.completed.extend(other.completed);
+        self.rolled_back.extend(other.rolled_back);
+    }
+}
+
+/// Intent files of the segment rolls in one segment directory
+#[derive(Debug, Clone)]
+pub struct RollJournal {
+    dir: PathBuf,
+}
+
+impl RollJournal {
+    /// Keep intents in `dir`, which must exist
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Durably record that `step` of the roll of `segment_id` is starting
+    pub fn record(&self, segment_id: SegmentId, step: RollStep) -> Result<()> {
+        let intent = RollIntent {
+            segment_id,
+            step,
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        };
+        let path = self.path(segment_id);
+        let temp = path.with_extension("roll.tmp");
+        let mut file = std::fs::File::create(&temp)?;
+        file.write_all(&serde_json::to_vec(&intent)?)?;
+        file.sync_all()?;
+        std::fs::rename(&temp, &path)?;
+        Ok(())
+    }
+
+    /// Remove the intent of `segment_id`, if any
+    pub fn clear(&self, segment_id: SegmentId) -> Result<()> {
+        match std::fs::remove_file(self.path(segment_id)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Intents left in the directory, oldest segment first
+    ///
+    /// Unreadable intent files are logged and skipped.
+    pub fn intents(&self) -> Result<Vec<RollIntent>> {
+        let mut intents = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "roll") {
+                continue;
+            }
+            match std::fs::read(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|data| serde_json::from_slice(&data).map_err(|e| e.to_string()))
+            {
+                Ok(intent) => intents.push(intent),
+                Err(e) => tracing::warn!("Skipping roll intent {}: {}", path.display(), e),
+            }
+        }
+        intents.sort_by_key(|intent: &RollIntent| intent.segment_id);
+        Ok(intents)
+    }
+
+    /// Path of the intent file of `segment_id`
+    fn path(&self, segment_id: SegmentId) -> PathBuf {
+        self.dir.join(format!("segment-{:016x}.roll", segment_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_clear_intents() {
+        let dir = std::env::temp_dir().join(format!("scribe-rolls-{}", fastrand::u64(..)));
+        std::fs::create_dir_all(&dir).unwrap();
+        let journal = RollJournal::new(&dir);
+
+        journal.record(9, RollStep::Writing).unwrap();
+        journal.record(3, RollStep::Uploading).unwrap();
+        journal.record(9, RollStep::Uploaded).unwrap();
+        std::fs::write(dir.join("segment-0000000000000005.roll"), b"{torn").unwrap();
+
+        let intents = journal.intents().unwrap();
+        assert_eq!(
+            intents
+                .iter()
+                .map(|intent| (intent.segment_id, intent.step))
+                .collect::<Vec<_>>(),
+            vec![(3, RollStep::Uploading), (9, RollStep::Uploaded)]
+        );
+
+        journal.clear(9).unwrap();
+        journal.clear(9).unwrap();
+        assert_eq!(journal.intents().unwrap().len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -4,15 +4,22 @@
 //! to object storage. It supports both AWS S3 and MinIO for local development.
 
 use crate::error::{Result, ScribeError};
+use crate::storage::credentials::{
+    CredentialSource, CredentialStatus, S3Credentials, DEFAULT_CREDENTIAL_REFRESH_SECS,
+};
+use crate::storage::faults::{S3FaultInjector, S3Operation};
 use crate::storage::segment::Segment;
+use crate::trace_context;
 use crate::types::SegmentId;
 use aws_config::BehaviorVersion;
-use aws_sdk_s3::config::{Credentials, Region};
+use aws_sdk_s3::config::interceptors::BeforeTransmitInterceptorContextMut;
+use aws_sdk_s3::config::{ConfigBag, Credentials, Intercept, Region, RuntimeComponents};
+use aws_sdk_s3::error::BoxError;
 use aws_sdk_s3::primitives::ByteStream;
 use aws_sdk_s3::Client as S3Client;
 use bytes::Bytes;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// S3 storage backend configuration
 #[derive(Debug, Clone)]
@@ -33,6 +40,10 @@ pub struct S3StorageConfig {
     pub timeout_secs: u64,
     /// Maximum retry attempts
     pub max_retries: u32,
+    /// Source of rotatable credentials, read instead of the static keys
+    pub credential_source: Option<CredentialSource>,
+    /// Seconds between reads of the credential source
+    pub credential_refresh_secs: u64,
 }
 
 impl Default for S3StorageConfig {
@@ -46,6 +57,8 @@ impl Default for S3StorageConfig {
             path_style: false,
             timeout_secs: 30,
             max_retries: 3,
+            credential_source: None,
+            credential_refresh_secs: DEFAULT_CREDENTIAL_REFRESH_SECS,
         }
     }
 }
@@ -55,11 +68,26 @@ impl Default for S3StorageConfig {
 /// This backend provides async operations for storing and retrieving segments
 /// from S3-compatible object storage. It includes connection pooling, retry logic,
 /// and support for MinIO for local development.
-#[derive(Debug)]
+///
+/// With a credential source, the client is rebuilt whenever the source yields new
+/// credentials (see [`S3Storage::refresh_credentials`]). Clones share the client.
+#[derive(Debug, Clone)]
 pub struct S3Storage {
-    client: Arc<S3Client>,
+    state: Arc<RwLock<ClientState>>,
+    config: Arc<S3StorageConfig>,
     bucket: String,
     max_retries: u32,
+    faults: Option<Arc<S3FaultInjector>>,
+}
+
+/// Client in use and the credentials it was built with
+#[derive(Debug)]
+struct ClientState {
+    client: Arc<S3Client>,
+    credentials: Option<S3Credentials>,
+    rotated_at: Option<u64>,
+    rotations: u64,
+    last_error: Option<String>,
 }
 
 impl S3Storage {
@@ -79,22 +107,162 @@ impl S3Storage {
             ));
         }
 
-        let client = Self::create_client(&config).await?;
+        let credentials = match &config.credential_source {
+            Some(source) => Some(source.load().await?),
+            None => None,
+        };
+        let client = Self::create_client(&config, credentials.as_ref()).await?;
 
         Ok(Self {
-            client: Arc::new(client),
-            bucket: config.bucket,
+            state: Arc::new(RwLock::new(ClientState {
+                client: Arc::new(client),
+                credentials,
+                rotated_at: None,
+                rotations: 0,
+                last_error: None,
+            })),
+            bucket: config.bucket.clone(),
             max_retries: config.max_retries,
+            config: Arc::new(config),
+            faults: None,
         })
     }
 
-    /// Create an S3 client with the given configuration
-    async fn create_client(config: &S3StorageConfig) -> Result<S3Client> {
+    /// Name of the bucket segments are archived to
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
+    /// The same backend storing into `bucket` instead, sharing the client and
+    /// credentials
+    pub fn with_bucket(&self, bucket: &str) -> Self {
+        Self {
+            bucket: bucket.to_string(),
+            ..self.clone()
+        }
+    }
+
+    /// Inject the faults armed on `faults` into this backend's requests (for testing)
+    pub fn with_fault_injector(mut self, faults: Arc<S3FaultInjector>) -> Self {
+        self.faults = Some(faults);
+        self
+    }
+
+    /// Re-read the credential source and rebuild the client if the credentials changed
+    ///
+    /// Requests already sent keep the client they started with; later requests,
+    /// including retries, use the new one. Returns whether the credentials changed.
+    pub async fn refresh_credentials(&self) -> Result<bool> {
+        let Some(source) = &self.config.credential_source else {
+            return Ok(false);
+        };
+        let credentials = match source.load().await {
+            Ok(credentials) => credentials,
+            Err(e) => {
+                self.state_mut().last_error = Some(e.to_string());
+                return Err(e);
+            }
+        };
+        if self.state().credentials.as_ref() == Some(&credentials) {
+            self.state_mut().last_error = None;
+            return Ok(false);
+        }
+
+        let client = Self::create_client(&self.config, Some(&credentials)).await?;
+        let mut state = self.state_mut();
+        state.client = Arc::new(client);
+        state.credentials = Some(credentials);
+        state.rotated_at = Some(now_millis());
+        state.rotations += 1;
+        state.last_error = None;
+        Ok(true)
+    }
+
+    /// Re-read the credential source every `credential_refresh_secs` seconds
+    ///
+    /// Returns `None` without a credential source.
+    pub fn start_credential_watcher(&self) -> Option<tokio::task::JoinHandle<()>> {
+        let source = self.config.credential_source.as_ref()?.kind();
+        let storage = self.clone();
+        let period = Duration::from_secs(self.config.credential_refresh_secs.max(1));
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(period);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                match storage.refresh_credentials().await {
+                    Ok(true) => {
+                        let status = storage.credential_status();
+                        tracing::info!(
+                            "Rotated S3 credentials from {} source (access key {}, expires at {:?})",
+                            source,
+                            status.access_key_id.unwrap_or_default(),
+                            status.expires_at
+                        );
+                    }
+                    Ok(false) => {}
+                    Err(e) => tracing::warn!("Failed to refresh S3 credentials: {}", e),
+                }
+            }
+        }))
+    }
+
+    /// Credentials in use, their expiry and rotation history
+    pub fn credential_status(&self) -> CredentialStatus {
+        let state = self.state();
+        let source = match &self.config.credential_source {
+            Some(source) => source.kind(),
+            None => "static",
+        };
+        let access_key_id = match &state.credentials {
+            Some(credentials) => Some(credentials.access_key_id.clone()),
+            None => self.config.access_key_id.clone(),
+        };
+        CredentialStatus {
+            source: source.to_string(),
+            access_key_id,
+            expires_at: state.credentials.as_ref().and_then(|c| c.expires_at),
+            rotated_at: state.rotated_at,
+            rotations: state.rotations,
+            last_error: state.last_error.clone(),
+        }
+    }
+
+    /// Client for the next request
+    fn client(&self) -> Arc<S3Client> {
+        self.state().client.clone()
+    }
+
+    fn state(&self) -> std::sync::RwLockReadGuard<'_, ClientState> {
+        self.state.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn state_mut(&self) -> std::sync::RwLockWriteGuard<'_, ClientState> {
+        self.state.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Create an S3 client with the given configuration, signing with `credentials`
+    /// if given and the configured static keys otherwise
+    async fn create_client(
+        config: &S3StorageConfig,
+        credentials: Option<&S3Credentials>,
+    ) -> Result<S3Client> {
         let mut aws_config = aws_config::defaults(BehaviorVersion::latest())
             .region(Region::new(config.region.clone()));
 
         // Set custom credentials if provided
-        if let (Some(access_key), Some(secret_key)) =
+        if let Some(credentials) = credentials {
+            let expiry = credentials
+                .expires_at
+                .map(|at| UNIX_EPOCH + Duration::from_millis(at));
+            aws_config = aws_config.credentials_provider(Credentials::new(
+                credentials.access_key_id.clone(),
+                credentials.secret_access_key.clone(),
+                credentials.session_token.clone(),
+                expiry,
+                "scribe-rotating",
+            ));
+        } else if let (Some(access_key), Some(secret_key)) =
             (&config.access_key_id, &config.secret_access_key)
         {
             let credentials =
@@ -103,11 +271,13 @@ impl S3Storage {
         }
 
         let sdk_config = aws_config.load().await;
-        let mut s3_config = aws_sdk_s3::config::Builder::from(&sdk_config).timeout_config(
-            aws_sdk_s3::config::timeout::TimeoutConfig::builder()
-                .operation_timeout(Duration::from_secs(config.timeout_secs))
-                .build(),
-        );
+        let mut s3_config = aws_sdk_s3::config::Builder::from(&sdk_config)
+            .timeout_config(
+                aws_sdk_s3::config::timeout::TimeoutConfig::builder()
+                    .operation_timeout(Duration::from_secs(config.timeout_secs))
+                    .build(),
+            )
+            .interceptor(TraceContextInterceptor);
 
         // Set custom endpoint if provided (for MinIO)
         if let Some(endpoint) = &config.endpoint {
@@ -230,11 +400,12 @@ impl S3Storage {
 
         loop {
             let mut request = self
-                .client
+                .client()
                 .list_objects_v2()
                 .bucket(&self.bucket)
                 .prefix("segments/");
 
+            self.inject_fault(S3Operation::List, "segments/")?;
             if let Some(token) = continuation_token {
                 request = request.continuation_token(token);
             }
@@ -268,7 +439,8 @@ impl S3Storage {
     ///
     /// Ok(()) if the bucket is accessible, or an error
     pub async fn health_check(&self) -> Result<()> {
-        self.client
+        self.inject_fault(S3Operation::HeadBucket, &self.bucket)?;
+        self.client()
             .head_bucket()
             .bucket(&self.bucket)
             .send()
@@ -293,6 +465,14 @@ impl S3Storage {
         None
     }
 
+    /// Fail the request if the fault injector has a fault armed for it
+    fn inject_fault(&self, operation: S3Operation, key: &str) -> Result<()> {
+        match &self.faults {
+            Some(faults) => faults.check(operation, key),
+            None => Ok(()),
+        }
+    }
+
     /// Put data to S3 with retry logic
     async fn put_with_retry(&self, key: &str, data: Vec<u8>) -> Result<()> {
         let mut last_error = None;
@@ -303,18 +483,27 @@ impl S3Storage {
                 tokio::time::sleep(backoff).await;
             }
 
+            if let Err(e) = self.inject_fault(S3Operation::Put, key) {
+                last_error = Some(e.to_string());
+                continue;
+            }
+
+            let body = match &self.faults {
+                Some(faults) => faults.tear(data.clone()),
+                None => data.clone(),
+            };
             match self
-                .client
+                .client()
                 .put_object()
                 .bucket(&self.bucket)
                 .key(key)
-                .body(ByteStream::from(Bytes::from(data.clone())))
+                .body(ByteStream::from(Bytes::from(body)))
                 .send()
                 .await
             {
                 Ok(_) => return Ok(()),
                 Err(e) => {
-                    last_error = Some(e);
+                    last_error = Some(e.to_string());
                 }
             }
         }
@@ -336,8 +525,13 @@ impl S3Storage {
                 tokio::time::sleep(backoff).await;
             }
 
+            if let Err(e) = self.inject_fault(S3Operation::Get, key) {
+                last_error = Some(e.to_string());
+                continue;
+            }
+
             match self
-                .client
+                .client()
                 .get_object()
                 .bucket(&self.bucket)
                 .key(key)
@@ -363,7 +557,7 @@ impl S3Storage {
                             key
                         )));
                     }
-                    last_error = Some(e);
+                    last_error = Some(e.to_string());
                 }
             }
         }
@@ -385,8 +579,13 @@ impl S3Storage {
                 tokio::time::sleep(backoff).await;
             }
 
+            if let Err(e) = self.inject_fault(S3Operation::Delete, key) {
+                last_error = Some(e.to_string());
+                continue;
+            }
+
             match self
-                .client
+                .client()
                 .delete_object()
                 .bucket(&self.bucket)
                 .key(key)
@@ -395,7 +594,7 @@ impl S3Storage {
             {
                 Ok(_) => return Ok(()),
                 Err(e) => {
-                    last_error = Some(e);
+                    last_error = Some(e.to_string());
                 }
             }
         }
@@ -408,6 +607,39 @@ impl S3Storage {
     }
 }
 
+/// Current time in milliseconds since the Unix epoch
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Adds the current request's `traceparent`/`tracestate` to every S3 request
+///
+/// Runs after signing, so the headers are not part of the signature.
+#[derive(Debug)]
+struct TraceContextInterceptor;
+
+impl Intercept for TraceContextInterceptor {
+    fn name(&self) -> &'static str {
+        "TraceContextInterceptor"
+    }
+
+    fn modify_before_transmit(
+        &self,
+        context: &mut BeforeTransmitInterceptorContextMut<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> std::result::Result<(), BoxError> {
+        let headers = context.request_mut().headers_mut();
+        for (name, value) in trace_context::outgoing_headers() {
+            headers.insert(name, value);
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -453,4 +685,50 @@ mod tests {
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("bucket"));
     }
+
+    #[tokio::test]
+    async fn test_rotate_credentials_from_file() {
+        let path = std::env::temp_dir().join(format!("scribe-s3-rotate-{}", fastrand::u64(..)));
+        let write = |key: &str| {
+            let json = format!(
+                r#"{{"AccessKeyId": "{}", "SecretAccessKey": "s", "Expiration": "2030-01-01T00:00:00Z"}}"#,
+                key
+            );
+            std::fs::write(&path, json).unwrap();
+        };
+        write("AKIA-OLD");
+        let storage = S3Storage::new(S3StorageConfig {
+            bucket: "rotation".to_string(),
+            endpoint: Some("http://127.0.0.1:9000".to_string()),
+            credential_source: Some(CredentialSource::File { path: path.clone() }),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let status = storage.credential_status();
+        assert_eq!(status.source, "file");
+        assert_eq!(status.access_key_id.as_deref(), Some("AKIA-OLD"));
+        assert_eq!(status.expires_at, Some(1_893_456_000_000));
+        assert_eq!(status.rotations, 0);
+
+        // Unchanged credentials keep the client
+        let before = storage.client();
+        assert!(!storage.refresh_credentials().await.unwrap());
+        assert!(Arc::ptr_eq(&before, &storage.client()));
+
+        write("AKIA-NEW");
+        assert!(storage.refresh_credentials().await.unwrap());
+        assert!(!Arc::ptr_eq(&before, &storage.client()));
+        let status = storage.clone().credential_status();
+        assert_eq!(status.access_key_id.as_deref(), Some("AKIA-NEW"));
+        assert_eq!(status.rotations, 1);
+        assert!(status.rotated_at.is_some());
+
+        // A failed read keeps the current credentials
+        std::fs::remove_file(&path).unwrap();
+        assert!(storage.refresh_credentials().await.is_err());
+        let status = storage.credential_status();
+        assert_eq!(status.access_key_id.as_deref(), Some("AKIA-NEW"));
+        assert!(status.last_error.is_some());
+    }
 }
@@ -5,16 +5,25 @@
 
 use crate::crypto::MerkleTree;
 use crate::error::{Result, ScribeError};
+use crate::ids::{IdGenerator, SnowflakeIds};
+use crate::storage::mapped::{self, MappedSegment};
+use crate::storage::roll_journal::{RollIntent, RollJournal, RollRecovery, RollStep};
 use crate::types::{Key, SegmentId, Value};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 /// Default segment size threshold (10MB)
 pub const DEFAULT_SEGMENT_SIZE_THRESHOLD: usize = 10 * 1024 * 1024;
 
+/// Shortest interval at which the rolling task checks segment age
+const MIN_ROLL_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Longest interval at which the rolling task checks segment age
+const MAX_ROLL_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
 /// A segment containing data with metadata
 ///
 /// Segments are the unit of data organization for multi-tier storage.
@@ -124,35 +133,49 @@ impl Segment {
 
 /// A pending segment for buffering writes before flushing
 ///
-/// PendingSegment accumulates writes until it reaches a size threshold,
-/// at which point it can be flushed to persistent storage.
+/// PendingSegment accumulates writes until it reaches a size threshold or,
+/// optionally, a maximum age, at which point it can be flushed to persistent storage.
 #[derive(Debug)]
 pub struct PendingSegment {
     /// The underlying segment data
     segment: Segment,
     /// Size threshold for triggering flush
     size_threshold: usize,
+    /// Age (since the first write) after which to flush regardless of size
+    max_age: Option<Duration>,
+    /// Time of the first write into this segment
+    first_write_at: Option<Instant>,
 }
 
 impl PendingSegment {
     /// Create a new pending segment with the given ID
     pub fn new(segment_id: SegmentId) -> Self {
-        Self {
-            segment: Segment::new(segment_id),
-            size_threshold: DEFAULT_SEGMENT_SIZE_THRESHOLD,
-        }
+        Self::with_threshold(segment_id, DEFAULT_SEGMENT_SIZE_THRESHOLD)
     }
 
     /// Create a new pending segment with a custom size threshold
     pub fn with_threshold(segment_id: SegmentId, size_threshold: usize) -> Self {
+        Self::with_limits(segment_id, size_threshold, None)
+    }
+
+    /// Create a new pending segment that also flushes once `max_age` has passed
+    /// since its first write
+    pub fn with_limits(
+        segment_id: SegmentId,
+        size_threshold: usize,
+        max_age: Option<Duration>,
+    ) -> Self {
         Self {
             segment: Segment::new(segment_id),
             size_threshold,
+            max_age,
+            first_write_at: None,
         }
     }
 
     /// Add a key-value pair to the pending segment
     pub fn put(&mut self, key: Key, value: Value) {
+        self.first_write_at.get_or_insert_with(Instant::now);
         self.segment.put(key, value);
     }
 
@@ -161,9 +184,22 @@ impl PendingSegment {
         self.segment.get(key)
     }
 
-    /// Check if the segment should be flushed based on size threshold
+    /// Check if the segment should be flushed based on size threshold or age
     pub fn should_flush(&self) -> bool {
-        self.segment.size >= self.size_threshold
+        self.segment.size >= self.size_threshold || self.is_expired()
+    }
+
+    /// Check if the segment holds data older than the maximum age
+    pub fn is_expired(&self) -> bool {
+        match (self.max_age, self.age()) {
+            (Some(max_age), Some(age)) => !self.segment.is_empty() && age >= max_age,
+            _ => false,
+        }
+    }
+
+    /// Time since the first write, `None` if nothing was written yet
+    pub fn age(&self) -> Option<Duration> {
+        self.first_write_at.map(|at| at.elapsed())
     }
 
     /// Get the current size of the segment
@@ -184,23 +220,36 @@ impl PendingSegment {
     /// Clear the pending segment
     pub fn clear(&mut self) {
         self.segment = Segment::new(self.segment.segment_id + 1);
+        self.first_write_at = None;
     }
 }
 
 /// Manager for tracking active and flushed segments
 ///
 /// SegmentManager coordinates between active pending segments and
-/// flushed segments that are ready for archival or retrieval.
+/// flushed segments that are ready for archival or retrieval. With a segment
+/// directory, flushed segments are written there in the flat format and read through
+/// memory maps instead of being kept in memory.
 #[derive(Debug)]
 pub struct SegmentManager {
     /// Currently active pending segment
     active_segment: Arc<RwLock<PendingSegment>>,
-    /// Flushed segments ready for archival
+    /// Flushed segments ready for archival, kept in memory
     flushed_segments: Arc<RwLock<Vec<Segment>>>,
-    /// Next segment ID to use
-    next_segment_id: Arc<AtomicU64>,
+    /// Flushed segments ready for archival, mapped from the segment directory
+    mapped_segments: Arc<RwLock<Vec<MappedSegment>>>,
+    /// Directory for flushed segment files (flushed segments stay in memory if unset)
+    segment_dir: Option<PathBuf>,
+    /// Source of segment IDs
+    ids: Arc<dyn IdGenerator>,
     /// Size threshold for segments
     size_threshold: usize,
+    /// Maximum age of the active segment (disabled if `None`)
+    max_age: Option<Duration>,
+    /// Intents of segment rolls in progress, kept in the segment directory
+    journal: Option<RollJournal>,
+    /// Rolls found interrupted while writing their segment file, reconciled on open
+    recovered: RollRecovery,
 }
 
 impl SegmentManager {
@@ -211,18 +260,157 @@ impl SegmentManager {
 
     /// Create a new segment manager with a custom size threshold
     pub fn with_threshold(size_threshold: usize) -> Self {
-        let segment_id = 0;
+        Self::with_limits(size_threshold, None)
+    }
+
+    /// Create a new segment manager that rolls the active segment on size or age
+    ///
+    /// With `max_age`, the active segment is also closed once its oldest write is
+    /// `max_age` old, so low-traffic data is still archived regularly. Writes check
+    /// the age; [`start_time_rolling`](Self::start_time_rolling) covers idle periods.
+    ///
+    /// Segment IDs are snowflake IDs of node 0; use
+    /// [`with_id_generator`](Self::with_id_generator) to include the node's own ID.
+    pub fn with_limits(size_threshold: usize, max_age: Option<Duration>) -> Self {
+        let ids: Arc<dyn IdGenerator> =
+            Arc::new(SnowflakeIds::new(0).expect("node 0 fits in snowflake IDs"));
         Self {
-            active_segment: Arc::new(RwLock::new(PendingSegment::with_threshold(
-                segment_id,
+            active_segment: Arc::new(RwLock::new(PendingSegment::with_limits(
+                ids.next_id(),
                 size_threshold,
+                max_age,
             ))),
             flushed_segments: Arc::new(RwLock::new(Vec::new())),
-            next_segment_id: Arc::new(AtomicU64::new(segment_id + 1)),
+            mapped_segments: Arc::new(RwLock::new(Vec::new())),
+            segment_dir: None,
+            ids,
             size_threshold,
+            max_age,
+            journal: None,
+            recovered: RollRecovery::default(),
+        }
+    }
+
+    /// Take segment IDs from `ids`, including the ID of the active segment
+    ///
+    /// Call before writing: the active segment is replaced.
+    pub fn with_id_generator(mut self, ids: Arc<dyn IdGenerator>) -> Self {
+        self.active_segment = Arc::new(RwLock::new(PendingSegment::with_limits(
+            ids.next_id(),
+            self.size_threshold,
+            self.max_age,
+        )));
+        self.ids = ids;
+        self
+    }
+
+    /// Write flushed segments to `dir` and read them through memory maps
+    ///
+    /// Segment files left in `dir` by an earlier run are mapped again, and new
+    /// segments get IDs after theirs. Rolls interrupted while writing a segment file
+    /// are reconciled first (see [`crate::storage::roll_journal`]).
+    pub fn with_segment_dir(mut self, dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        let journal = RollJournal::new(&dir);
+        self.recovered = Self::recover_writes(&dir, &journal)?;
+
+        let mut existing = Vec::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if Self::parse_segment_file(&path).is_some() {
+                existing.push(MappedSegment::open(&path)?);
+            }
+        }
+        existing.sort_by_key(|segment| segment.segment_id());
+
+        if let Some(last) = existing.last() {
+            self.ids.observe(last.segment_id());
+            *self.active_segment.write().map_err(|e| {
+                ScribeError::Other(format!("Failed to acquire write lock: {}", e))
+            })? =
+                PendingSegment::with_limits(self.ids.next_id(), self.size_threshold, self.max_age);
+        }
+        self.mapped_segments = Arc::new(RwLock::new(existing));
+        self.segment_dir = Some(dir);
+        self.journal = Some(journal);
+        Ok(self)
+    }
+
+    /// Keep segment files whose write completed and remove partial ones, clearing
+    /// their intents
+    fn recover_writes(dir: &Path, journal: &RollJournal) -> Result<RollRecovery> {
+        let mut recovery = RollRecovery::default();
+        for intent in journal.intents()? {
+            if intent.step != RollStep::Writing {
+                continue;
+            }
+            let path = Self::segment_file(dir, intent.segment_id);
+            if MappedSegment::open(&path).is_ok() {
+                recovery.completed.push(intent.segment_id);
+            } else {
+                for partial in [path.with_extension("seg.tmp"), path] {
+                    if let Err(e) = std::fs::remove_file(&partial) {
+                        if e.kind() != std::io::ErrorKind::NotFound {
+                            return Err(e.into());
+                        }
+                    }
+                }
+                tracing::warn!(
+                    "Rolled back segment {}: the process stopped while writing its file",
+                    intent.segment_id
+                );
+                recovery.rolled_back.push(intent.segment_id);
+            }
+            journal.clear(intent.segment_id)?;
+        }
+        Ok(recovery)
+    }
+
+    /// Rolls interrupted while writing their segment file, reconciled when the
+    /// segment directory was opened
+    pub fn recovered_writes(&self) -> &RollRecovery {
+        &self.recovered
+    }
+
+    /// Record that `step` of archiving the flushed segment `segment_id` is starting
+    ///
+    /// Does nothing unless flushed segments are kept in a segment directory. The
+    /// intent is cleared when the segment is removed with
+    /// [`remove_flushed`](Self::remove_flushed).
+    pub fn record_roll_step(&self, segment_id: SegmentId, step: RollStep) -> Result<()> {
+        match &self.journal {
+            Some(journal) => journal.record(segment_id, step),
+            None => Ok(()),
+        }
+    }
+
+    /// Intents of rolls a crash interrupted after the segment file was written
+    pub fn interrupted_rolls(&self) -> Result<Vec<RollIntent>> {
+        match &self.journal {
+            Some(journal) => journal.intents(),
+            None => Ok(Vec::new()),
         }
     }
 
+    /// Forget the roll intent of `segment_id`, if any
+    pub fn clear_roll(&self, segment_id: SegmentId) -> Result<()> {
+        match &self.journal {
+            Some(journal) => journal.clear(segment_id),
+            None => Ok(()),
+        }
+    }
+
+    /// Directory holding flushed segment files, if flushed segments are mapped
+    pub fn segment_dir(&self) -> Option<&Path> {
+        self.segment_dir.as_deref()
+    }
+
+    /// Maximum age of the active segment, if time-based rolling is enabled
+    pub fn max_age(&self) -> Option<Duration> {
+        self.max_age
+    }
+
     /// Put a key-value pair, potentially triggering a segment flush
     pub fn put(&self, key: Key, value: Value) -> Result<()> {
         let mut active = self
@@ -234,23 +422,97 @@ impl SegmentManager {
 
         // Check if we should flush the active segment
         if active.should_flush() {
-            let segment_id = self.next_segment_id.fetch_add(1, Ordering::SeqCst);
-            let old_segment = std::mem::replace(
-                &mut *active,
-                PendingSegment::with_threshold(segment_id, self.size_threshold),
-            );
-
-            // Move the old segment to flushed segments
-            let mut flushed = self
-                .flushed_segments
-                .write()
-                .map_err(|e| ScribeError::Other(format!("Failed to acquire write lock: {}", e)))?;
-            flushed.push(old_segment.into_segment());
+            self.roll(&mut active)?;
         }
 
         Ok(())
     }
 
+    /// Replace the active segment with a new one and queue the old one as flushed
+    fn roll(&self, active: &mut PendingSegment) -> Result<()> {
+        let segment_id = self.ids.next_id();
+        let old_segment = std::mem::replace(
+            active,
+            PendingSegment::with_limits(segment_id, self.size_threshold, self.max_age),
+        );
+
+        // Move the old segment to flushed segments
+        let segment = old_segment.into_segment();
+        let segment = match &self.segment_dir {
+            Some(dir) => match self.write_segment(dir, &segment) {
+                Ok(mapped) => {
+                    self.mapped_segments
+                        .write()
+                        .map_err(|e| {
+                            ScribeError::Other(format!("Failed to acquire write lock: {}", e))
+                        })?
+                        .push(mapped);
+                    return Ok(());
+                }
+                Err(e) => {
+                    // Keep the segment in memory rather than lose it
+                    tracing::warn!(
+                        "Failed to write segment {} to {}: {}",
+                        segment.segment_id,
+                        dir.display(),
+                        e
+                    );
+                    segment
+                }
+            },
+            None => segment,
+        };
+
+        let mut flushed = self
+            .flushed_segments
+            .write()
+            .map_err(|e| ScribeError::Other(format!("Failed to acquire write lock: {}", e)))?;
+        flushed.push(segment);
+        Ok(())
+    }
+
+    /// Write a flushed segment to the segment directory under a roll intent and map it
+    fn write_segment(&self, dir: &Path, segment: &Segment) -> Result<MappedSegment> {
+        self.record_roll_step(segment.segment_id, RollStep::Writing)?;
+        let mapped = Self::map_segment(dir, segment);
+        // A failed write keeps the segment in memory, so there is nothing to recover
+        self.clear_roll(segment.segment_id)?;
+        mapped
+    }
+
+    /// Write a flushed segment to the segment directory and map it
+    fn map_segment(dir: &Path, segment: &Segment) -> Result<MappedSegment> {
+        let path = Self::segment_file(dir, segment.segment_id);
+        mapped::write(segment, &path)?;
+        MappedSegment::open(&path)
+    }
+
+    /// Path of a flushed segment file
+    fn segment_file(dir: &Path, segment_id: SegmentId) -> PathBuf {
+        dir.join(format!("segment-{:016x}.seg", segment_id))
+    }
+
+    /// Segment ID of a flushed segment file
+    fn parse_segment_file(path: &Path) -> Option<SegmentId> {
+        let name = path.file_name()?.to_str()?;
+        let id = name.strip_prefix("segment-")?.strip_suffix(".seg")?;
+        SegmentId::from_str_radix(id, 16).ok()
+    }
+
+    /// Delete the files of mapped segments that are no longer tracked
+    fn remove_segment_files(&self, removed: Vec<MappedSegment>) {
+        let Some(dir) = &self.segment_dir else {
+            return;
+        };
+        for segment in removed {
+            let path = Self::segment_file(dir, segment.segment_id());
+            drop(segment);
+            if let Err(e) = std::fs::remove_file(&path) {
+                tracing::warn!("Failed to remove segment file {}: {}", path.display(), e);
+            }
+        }
+    }
+
     /// Get a value by key from active or flushed segments
     pub fn get(&self, key: &Key) -> Result<Option<Value>> {
         // First check active segment
@@ -267,13 +529,47 @@ impl SegmentManager {
             .flushed_segments
             .read()
             .map_err(|e| ScribeError::Other(format!("Failed to acquire read lock: {}", e)))?;
-        for segment in flushed.iter().rev() {
-            if let Some(value) = segment.get(key) {
-                return Ok(Some(value.clone()));
-            }
+        let in_memory = flushed
+            .iter()
+            .rev()
+            .find_map(|segment| Some((segment.segment_id, segment.get(key)?.clone())));
+
+        let mapped = self
+            .mapped_segments
+            .read()
+            .map_err(|e| ScribeError::Other(format!("Failed to acquire read lock: {}", e)))?;
+        let from_file = mapped
+            .iter()
+            .rev()
+            .find_map(|segment| Some((segment.segment_id(), segment.get(key)?.to_vec())));
+
+        // Segments kept in memory after a failed write may be newer or older than mapped ones
+        Ok(match (in_memory, from_file) {
+            (Some(a), Some(b)) => Some(if a.0 > b.0 { a.1 } else { b.1 }),
+            (a, b) => a.or(b).map(|(_, value)| value),
+        })
+    }
+
+    /// Get a value by key from the flushed segment `segment_id`, if it is still held
+    /// locally
+    pub fn get_in_segment(&self, segment_id: SegmentId, key: &Key) -> Result<Option<Value>> {
+        let flushed = self
+            .flushed_segments
+            .read()
+            .map_err(|e| ScribeError::Other(format!("Failed to acquire read lock: {}", e)))?;
+        if let Some(segment) = flushed.iter().find(|s| s.segment_id == segment_id) {
+            return Ok(segment.get(key).cloned());
         }
 
-        Ok(None)
+        let mapped = self
+            .mapped_segments
+            .read()
+            .map_err(|e| ScribeError::Other(format!("Failed to acquire read lock: {}", e)))?;
+        Ok(mapped
+            .iter()
+            .find(|segment| segment.segment_id() == segment_id)
+            .and_then(|segment| segment.get(key))
+            .map(<[u8]>::to_vec))
     }
 
     /// Get the number of flushed segments
@@ -282,16 +578,52 @@ impl SegmentManager {
             .flushed_segments
             .read()
             .map_err(|e| ScribeError::Other(format!("Failed to acquire read lock: {}", e)))?;
-        Ok(flushed.len())
+        let mapped = self
+            .mapped_segments
+            .read()
+            .map_err(|e| ScribeError::Other(format!("Failed to acquire read lock: {}", e)))?;
+        Ok(flushed.len() + mapped.len())
     }
 
-    /// Get all flushed segments
+    /// IDs of all flushed segments, in memory or mapped, oldest first
+    pub fn flushed_segment_ids(&self) -> Result<Vec<SegmentId>> {
+        let flushed = self
+            .flushed_segments
+            .read()
+            .map_err(|e| ScribeError::Other(format!("Failed to acquire read lock: {}", e)))?;
+        let mapped = self
+            .mapped_segments
+            .read()
+            .map_err(|e| ScribeError::Other(format!("Failed to acquire read lock: {}", e)))?;
+        let mut ids: Vec<SegmentId> = flushed
+            .iter()
+            .map(|segment| segment.segment_id)
+            .chain(mapped.iter().map(MappedSegment::segment_id))
+            .collect();
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    /// Get all flushed segments, oldest first
+    ///
+    /// Mapped segments are copied into memory.
     pub fn get_flushed_segments(&self) -> Result<Vec<Segment>> {
         let flushed = self
             .flushed_segments
             .read()
             .map_err(|e| ScribeError::Other(format!("Failed to acquire read lock: {}", e)))?;
-        Ok(flushed.clone())
+        let mapped = self
+            .mapped_segments
+            .read()
+            .map_err(|e| ScribeError::Other(format!("Failed to acquire read lock: {}", e)))?;
+        if mapped.is_empty() {
+            return Ok(flushed.clone());
+        }
+
+        let mut segments: Vec<Segment> = flushed.clone();
+        segments.extend(mapped.iter().map(MappedSegment::to_segment));
+        segments.sort_by_key(|segment| segment.segment_id);
+        Ok(segments)
     }
 
     /// Clear all flushed segments (e.g., after successful S3 upload)
@@ -300,7 +632,46 @@ impl SegmentManager {
             .flushed_segments
             .write()
             .map_err(|e| ScribeError::Other(format!("Failed to acquire write lock: {}", e)))?;
-        flushed.clear();
+        let mut removed_ids: Vec<SegmentId> = flushed
+            .drain(..)
+            .map(|segment| segment.segment_id)
+            .collect();
+        let removed = std::mem::take(
+            &mut *self
+                .mapped_segments
+                .write()
+                .map_err(|e| ScribeError::Other(format!("Failed to acquire write lock: {}", e)))?,
+        );
+        removed_ids.extend(removed.iter().map(MappedSegment::segment_id));
+        self.remove_segment_files(removed);
+        for segment_id in removed_ids {
+            self.clear_roll(segment_id)?;
+        }
+        Ok(())
+    }
+
+    /// Remove the given flushed segments (e.g., after they were archived), keeping the rest
+    pub fn remove_flushed(&self, segment_ids: &[SegmentId]) -> Result<()> {
+        let mut flushed = self
+            .flushed_segments
+            .write()
+            .map_err(|e| ScribeError::Other(format!("Failed to acquire write lock: {}", e)))?;
+        flushed.retain(|segment| !segment_ids.contains(&segment.segment_id));
+
+        let mut mapped = self
+            .mapped_segments
+            .write()
+            .map_err(|e| ScribeError::Other(format!("Failed to acquire write lock: {}", e)))?;
+        let (removed, kept) = std::mem::take(&mut *mapped)
+            .into_iter()
+            .partition(|segment| segment_ids.contains(&segment.segment_id()));
+        *mapped = kept;
+        drop(mapped);
+        self.remove_segment_files(removed);
+        // Only now is the roll of the removed segments complete
+        for &segment_id in segment_ids {
+            self.clear_roll(segment_id)?;
+        }
         Ok(())
     }
 
@@ -312,21 +683,47 @@ impl SegmentManager {
             .map_err(|e| ScribeError::Other(format!("Failed to acquire write lock: {}", e)))?;
 
         if !active.segment().is_empty() {
-            let segment_id = self.next_segment_id.fetch_add(1, Ordering::SeqCst);
-            let old_segment = std::mem::replace(
-                &mut *active,
-                PendingSegment::with_threshold(segment_id, self.size_threshold),
-            );
-
-            let mut flushed = self
-                .flushed_segments
-                .write()
-                .map_err(|e| ScribeError::Other(format!("Failed to acquire write lock: {}", e)))?;
-            flushed.push(old_segment.into_segment());
+            self.roll(&mut active)?;
         }
 
         Ok(())
     }
+
+    /// Flush the active segment if it exceeded the maximum age
+    ///
+    /// Returns whether a segment was rolled.
+    pub fn roll_if_expired(&self) -> Result<bool> {
+        let mut active = self
+            .active_segment
+            .write()
+            .map_err(|e| ScribeError::Other(format!("Failed to acquire write lock: {}", e)))?;
+
+        if active.is_expired() {
+            self.roll(&mut active)?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Start a background task rolling the active segment once it is too old
+    ///
+    /// Needed because writes only check the age when they arrive. Returns `None` if
+    /// time-based rolling is disabled.
+    pub fn start_time_rolling(self: &Arc<Self>) -> Option<tokio::task::JoinHandle<()>> {
+        let check_interval =
+            (self.max_age? / 10).clamp(MIN_ROLL_CHECK_INTERVAL, MAX_ROLL_CHECK_INTERVAL);
+        let manager = Arc::clone(self);
+
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(check_interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = manager.roll_if_expired() {
+                    tracing::warn!("Failed to roll expired segment: {}", e);
+                }
+            }
+        }))
+    }
 }
 
 impl Default for SegmentManager {
@@ -346,6 +743,12 @@ fn current_timestamp() -> u64 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ids::SequentialIds;
+
+    /// Segment manager numbering segments 0, 1, 2, ...
+    fn sequential_manager() -> SegmentManager {
+        SegmentManager::new().with_id_generator(Arc::new(SequentialIds::new()))
+    }
 
     #[test]
     fn test_segment_new() {
@@ -516,6 +919,86 @@ mod tests {
         assert_eq!(manager.flushed_count().unwrap(), 1);
     }
 
+    #[test]
+    fn test_pending_segment_max_age() {
+        let mut pending =
+            PendingSegment::with_limits(1, usize::MAX, Some(Duration::from_millis(20)));
+
+        // Age is counted from the first write, so an idle empty segment never expires
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(pending.age().is_none());
+        assert!(!pending.should_flush());
+
+        pending.put(b"key".to_vec(), b"value".to_vec());
+        assert!(!pending.should_flush());
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(pending.is_expired());
+        assert!(pending.should_flush());
+
+        pending.clear();
+        assert!(pending.age().is_none());
+        assert!(!pending.should_flush());
+    }
+
+    #[test]
+    fn test_segment_manager_time_rolling() {
+        let manager = SegmentManager::with_limits(usize::MAX, Some(Duration::from_millis(20)));
+        assert!(!manager.roll_if_expired().unwrap());
+
+        manager.put(b"key1".to_vec(), b"value1".to_vec()).unwrap();
+        assert!(!manager.roll_if_expired().unwrap());
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(manager.roll_if_expired().unwrap());
+        assert_eq!(manager.flushed_count().unwrap(), 1);
+        assert_eq!(
+            manager.get(&b"key1".to_vec()).unwrap(),
+            Some(b"value1".to_vec())
+        );
+
+        // The replacement segment starts with a fresh age
+        assert!(!manager.roll_if_expired().unwrap());
+
+        // Writes into an expired segment roll it as well
+        manager.put(b"key2".to_vec(), b"value2".to_vec()).unwrap();
+        std::thread::sleep(Duration::from_millis(30));
+        manager.put(b"key3".to_vec(), b"value3".to_vec()).unwrap();
+        assert_eq!(manager.flushed_count().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_segment_manager_size_rolling_without_max_age() {
+        let manager = SegmentManager::with_limits(100, None);
+        assert_eq!(manager.max_age(), None);
+
+        manager.put(b"key".to_vec(), vec![0u8; 50]).unwrap();
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(!manager.roll_if_expired().unwrap());
+        assert_eq!(manager.flushed_count().unwrap(), 0);
+
+        manager.put(b"key2".to_vec(), vec![0u8; 60]).unwrap();
+        assert_eq!(manager.flushed_count().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_segment_manager_background_rolling() {
+        let manager = Arc::new(SegmentManager::with_limits(
+            usize::MAX,
+            Some(Duration::from_millis(50)),
+        ));
+        assert!(Arc::new(SegmentManager::new())
+            .start_time_rolling()
+            .is_none());
+
+        let handle = manager.start_time_rolling().unwrap();
+        manager.put(b"key".to_vec(), b"value".to_vec()).unwrap();
+
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert_eq!(manager.flushed_count().unwrap(), 1);
+        handle.abort();
+    }
+
     #[test]
     fn test_segment_manager_get_from_flushed() {
         let threshold = 100;
@@ -546,9 +1029,107 @@ mod tests {
         assert_eq!(manager.flushed_count().unwrap(), 0);
     }
 
+    #[test]
+    fn test_segment_manager_remove_flushed() {
+        let manager = sequential_manager();
+
+        for key in [b"key1", b"key2", b"key3"] {
+            manager.put(key.to_vec(), b"value".to_vec()).unwrap();
+            manager.flush_active().unwrap();
+        }
+
+        manager.remove_flushed(&[0, 2]).unwrap();
+        let flushed = manager.get_flushed_segments().unwrap();
+        assert_eq!(flushed.len(), 1);
+        assert_eq!(flushed[0].segment_id, 1);
+    }
+
+    #[test]
+    fn test_segment_manager_maps_flushed_segments() {
+        let dir = std::env::temp_dir().join(format!("scribe-segments-{}", fastrand::u64(..)));
+        let manager = sequential_manager().with_segment_dir(&dir).unwrap();
+
+        manager.put(b"key1".to_vec(), b"old".to_vec()).unwrap();
+        manager.flush_active().unwrap();
+        manager.put(b"key1".to_vec(), b"new".to_vec()).unwrap();
+        manager.put(b"key2".to_vec(), b"value2".to_vec()).unwrap();
+        manager.flush_active().unwrap();
+
+        assert_eq!(manager.flushed_count().unwrap(), 2);
+        assert_eq!(
+            manager.get(&b"key1".to_vec()).unwrap(),
+            Some(b"new".to_vec())
+        );
+        assert!(dir.join("segment-0000000000000001.seg").exists());
+        let flushed = manager.get_flushed_segments().unwrap();
+        assert_eq!(flushed[1].get(&b"key2".to_vec()), Some(&b"value2".to_vec()));
+
+        // A new manager picks the files up and numbers segments after them
+        drop(manager);
+        let manager = sequential_manager().with_segment_dir(&dir).unwrap();
+        assert_eq!(manager.flushed_count().unwrap(), 2);
+        manager.put(b"key3".to_vec(), b"value3".to_vec()).unwrap();
+        manager.flush_active().unwrap();
+        assert!(dir.join("segment-0000000000000002.seg").exists());
+
+        manager.remove_flushed(&[0, 2]).unwrap();
+        assert_eq!(manager.flushed_count().unwrap(), 1);
+        assert!(!dir.join("segment-0000000000000000.seg").exists());
+        assert_eq!(
+            manager.get(&b"key2".to_vec()).unwrap(),
+            Some(b"value2".to_vec())
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_segment_manager_recovers_interrupted_rolls() {
+        let dir = std::env::temp_dir().join(format!("scribe-segments-{}", fastrand::u64(..)));
+        let manager = sequential_manager().with_segment_dir(&dir).unwrap();
+        manager.put(b"key0".to_vec(), b"value0".to_vec()).unwrap();
+        manager.flush_active().unwrap();
+        assert!(manager.interrupted_rolls().unwrap().is_empty());
+        // Crash while uploading segment 0
+        manager.record_roll_step(0, RollStep::Uploading).unwrap();
+        drop(manager);
+
+        // Crash while writing segment 1, and after writing segment 2 but before
+        // clearing its intent
+        let journal = RollJournal::new(&dir);
+        journal.record(1, RollStep::Writing).unwrap();
+        std::fs::write(dir.join("segment-0000000000000001.seg.tmp"), b"partial").unwrap();
+        journal.record(2, RollStep::Writing).unwrap();
+        let mut segment = Segment::new(2);
+        segment.put(b"key2".to_vec(), b"value2".to_vec());
+        mapped::write(&segment, &dir.join("segment-0000000000000002.seg")).unwrap();
+
+        let manager = sequential_manager().with_segment_dir(&dir).unwrap();
+        assert_eq!(manager.recovered_writes().completed, vec![2]);
+        assert_eq!(manager.recovered_writes().rolled_back, vec![1]);
+        assert!(!dir.join("segment-0000000000000001.seg.tmp").exists());
+        assert_eq!(manager.flushed_segment_ids().unwrap(), vec![0, 2]);
+        assert_eq!(
+            manager.get(&b"key2".to_vec()).unwrap(),
+            Some(b"value2".to_vec())
+        );
+
+        // The upload intent is left for the archival manager, and cleared on removal
+        let intents = manager.interrupted_rolls().unwrap();
+        assert_eq!(intents.len(), 1);
+        assert_eq!(
+            (intents[0].segment_id, intents[0].step),
+            (0, RollStep::Uploading)
+        );
+        manager.remove_flushed(&[0]).unwrap();
+        assert!(manager.interrupted_rolls().unwrap().is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn test_segment_manager_get_flushed_segments() {
-        let manager = SegmentManager::new();
+        let manager = sequential_manager();
 
         manager.put(b"key1".to_vec(), b"value1".to_vec()).unwrap();
         manager.flush_active().unwrap();
@@ -561,6 +1142,51 @@ mod tests {
         assert_eq!(flushed[1].segment_id, 1);
     }
 
+    #[test]
+    fn test_segment_manager_get_in_segment() {
+        let manager = sequential_manager();
+
+        manager.put(b"key1".to_vec(), b"old".to_vec()).unwrap();
+        manager.flush_active().unwrap();
+        manager.put(b"key1".to_vec(), b"new".to_vec()).unwrap();
+        manager.flush_active().unwrap();
+
+        let key = b"key1".to_vec();
+        assert_eq!(
+            manager.get_in_segment(0, &key).unwrap(),
+            Some(b"old".to_vec())
+        );
+        assert_eq!(
+            manager.get_in_segment(1, &key).unwrap(),
+            Some(b"new".to_vec())
+        );
+        assert_eq!(manager.get_in_segment(2, &key).unwrap(), None);
+    }
+
+    #[test]
+    fn test_segment_manager_snowflake_ids() {
+        let node1 = SegmentManager::new()
+            .with_id_generator(Arc::new(crate::ids::SnowflakeIds::new(1).unwrap()));
+        let node2 = SegmentManager::new()
+            .with_id_generator(Arc::new(crate::ids::SnowflakeIds::new(2).unwrap()));
+
+        for manager in [&node1, &node2, &node1] {
+            manager.put(b"key".to_vec(), b"value".to_vec()).unwrap();
+            manager.flush_active().unwrap();
+        }
+
+        let ids1: Vec<SegmentId> = node1
+            .get_flushed_segments()
+            .unwrap()
+            .iter()
+            .map(|segment| segment.segment_id)
+            .collect();
+        let id2 = node2.get_flushed_segments().unwrap()[0].segment_id;
+        assert!(ids1[0] < ids1[1]);
+        assert!(!ids1.contains(&id2));
+        assert_eq!(crate::ids::snowflake_parts(id2).1, 2);
+    }
+
     #[test]
     fn test_current_timestamp() {
         let ts = current_timestamp();
@@ -0,0 +1,467 @@
+//! Pluggable value transformers for the storage write/read path
+//!
+//! Transformers encode values before they are written and decode them after they are
+//! read (compression, encryption, schema upgrades). Chains are registered per
+//! namespace, where a namespace is a key prefix; the longest matching prefix wins.
+//! Configuration names transformers (see [`transformer_named`]), and persisted
+//! snapshots apply the chains of their [`StorageLayout`](super::layout::StorageLayout).
+
+use crate::error::{Result, ScribeError};
+use crate::storage::{KeyRange, StorageBackend};
+use crate::types::{Key, Value};
+use async_trait::async_trait;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::{Arc, RwLock};
+
+/// A reversible transformation applied to values in the storage path
+pub trait ValueTransformer: Send + Sync {
+    /// Name of the transformer, used in error messages
+    fn name(&self) -> &str;
+
+    /// Transform a value before it is written
+    fn encode(&self, key: &Key, value: Value) -> Result<Value>;
+
+    /// Reverse the transformation after a value is read
+    fn decode(&self, key: &Key, value: Value) -> Result<Value>;
+}
+
+/// Ordered list of transformers
+///
+/// Encoding applies transformers in registration order; decoding applies them in
+/// reverse order.
+#[derive(Clone, Default)]
+pub struct TransformerChain {
+    transformers: Vec<Arc<dyn ValueTransformer>>,
+}
+
+impl TransformerChain {
+    /// Create an empty chain
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a transformer to the chain
+    pub fn with(mut self, transformer: Arc<dyn ValueTransformer>) -> Self {
+        self.transformers.push(transformer);
+        self
+    }
+
+    /// Create a chain of the transformers with the given configuration names
+    pub fn named<S: AsRef<str>>(names: &[S]) -> Result<Self> {
+        names.iter().try_fold(Self::new(), |chain, name| {
+            Ok(chain.with(transformer_named(name.as_ref())?))
+        })
+    }
+
+    /// Number of transformers in the chain
+    pub fn len(&self) -> usize {
+        self.transformers.len()
+    }
+
+    /// Check if the chain has no transformers
+    pub fn is_empty(&self) -> bool {
+        self.transformers.is_empty()
+    }
+
+    /// Encode a value through every transformer in order
+    pub fn encode(&self, key: &Key, value: Value) -> Result<Value> {
+        self.transformers
+            .iter()
+            .try_fold(value, |value, t| t.encode(key, value))
+    }
+
+    /// Decode a value through every transformer in reverse order
+    pub fn decode(&self, key: &Key, value: Value) -> Result<Value> {
+        self.transformers
+            .iter()
+            .rev()
+            .try_fold(value, |value, t| t.decode(key, value))
+    }
+}
+
+/// Registry of transformer chains keyed by namespace (key prefix)
+#[derive(Clone, Default)]
+pub struct TransformerRegistry {
+    chains: Arc<RwLock<HashMap<Vec<u8>, TransformerChain>>>,
+}
+
+impl TransformerRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a chain for keys starting with `namespace`
+    ///
+    /// An empty namespace applies to every key without a more specific match.
+    pub fn register(&self, namespace: impl Into<Vec<u8>>, chain: TransformerChain) -> Result<()> {
+        let mut chains = self
+            .chains
+            .write()
+            .map_err(|e| ScribeError::Other(format!("Lock error: {}", e)))?;
+        chains.insert(namespace.into(), chain);
+        Ok(())
+    }
+
+    /// Remove the chain registered for `namespace`
+    pub fn unregister(&self, namespace: &[u8]) -> Result<Option<TransformerChain>> {
+        let mut chains = self
+            .chains
+            .write()
+            .map_err(|e| ScribeError::Other(format!("Lock error: {}", e)))?;
+        Ok(chains.remove(namespace))
+    }
+
+    /// Find the chain for a key using the longest matching namespace
+    pub fn chain_for(&self, key: &Key) -> Result<Option<TransformerChain>> {
+        let chains = self
+            .chains
+            .read()
+            .map_err(|e| ScribeError::Other(format!("Lock error: {}", e)))?;
+        Ok(chains
+            .iter()
+            .filter(|(namespace, _)| key.starts_with(namespace))
+            .max_by_key(|(namespace, _)| namespace.len())
+            .map(|(_, chain)| chain.clone()))
+    }
+
+    /// Encode a value for `key` (no-op when no chain matches)
+    pub fn encode(&self, key: &Key, value: Value) -> Result<Value> {
+        match self.chain_for(key)? {
+            Some(chain) => chain.encode(key, value),
+            None => Ok(value),
+        }
+    }
+
+    /// Decode a value for `key` (no-op when no chain matches)
+    pub fn decode(&self, key: &Key, value: Value) -> Result<Value> {
+        match self.chain_for(key)? {
+            Some(chain) => chain.decode(key, value),
+            None => Ok(value),
+        }
+    }
+}
+
+/// Transformer with the configuration name `name`
+///
+/// `gzip` compresses at the default level and `gzip:<level>` at level 0-9.
+pub fn transformer_named(name: &str) -> Result<Arc<dyn ValueTransformer>> {
+    match name.split_once(':') {
+        None if name == "gzip" => Ok(Arc::new(GzipTransformer::default())),
+        Some(("gzip", level)) => match level.parse::<u32>() {
+            Ok(level) if level <= 9 => Ok(Arc::new(GzipTransformer::new(level))),
+            _ => Err(ScribeError::Configuration(format!(
+                "Invalid gzip level in value transformer {:?}",
+                name
+            ))),
+        },
+        _ => Err(ScribeError::Configuration(format!(
+            "Unknown value transformer {:?}",
+            name
+        ))),
+    }
+}
+
+/// Gzip compression transformer
+#[derive(Debug, Clone)]
+pub struct GzipTransformer {
+    level: u32,
+}
+
+impl GzipTransformer {
+    /// Create a gzip transformer with the given compression level (0-9)
+    pub fn new(level: u32) -> Self {
+        Self {
+            level: level.min(9),
+        }
+    }
+}
+
+impl Default for GzipTransformer {
+    fn default() -> Self {
+        Self::new(6)
+    }
+}
+
+impl ValueTransformer for GzipTransformer {
+    fn name(&self) -> &str {
+        "gzip"
+    }
+
+    fn encode(&self, _key: &Key, value: Value) -> Result<Value> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::new(self.level));
+        encoder
+            .write_all(&value)
+            .map_err(|e| ScribeError::Storage(format!("Gzip encode failed: {}", e)))?;
+        encoder
+            .finish()
+            .map_err(|e| ScribeError::Storage(format!("Gzip encode failed: {}", e)))
+    }
+
+    fn decode(&self, _key: &Key, value: Value) -> Result<Value> {
+        let mut decoder = GzDecoder::new(&value[..]);
+        let mut decoded = Vec::new();
+        decoder
+            .read_to_end(&mut decoded)
+            .map_err(|e| ScribeError::Storage(format!("Gzip decode failed: {}", e)))?;
+        Ok(decoded)
+    }
+}
+
+/// Storage backend that applies registered transformers around another backend
+pub struct TransformingStorage<S: StorageBackend> {
+    inner: S,
+    registry: TransformerRegistry,
+}
+
+impl<S: StorageBackend> TransformingStorage<S> {
+    /// Wrap a storage backend with a transformer registry
+    pub fn new(inner: S, registry: TransformerRegistry) -> Self {
+        Self { inner, registry }
+    }
+
+    /// Get the transformer registry
+    pub fn registry(&self) -> &TransformerRegistry {
+        &self.registry
+    }
+
+    /// Get the wrapped storage backend
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+#[async_trait]
+impl<S: StorageBackend> StorageBackend for TransformingStorage<S> {
+    async fn put(&self, key: Key, value: Value) -> Result<()> {
+        let value = self.registry.encode(&key, value)?;
+        self.inner.put(key, value).await
+    }
+
+    async fn get(&self, key: &Key) -> Result<Option<Value>> {
+        match self.inner.get(key).await? {
+            Some(value) => Ok(Some(self.registry.decode(key, value)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn delete(&self, key: &Key) -> Result<()> {
+        self.inner.delete(key).await
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.inner.flush().await
+    }
+
+    async fn snapshot(&self) -> Result<HashMap<Key, Value>> {
+        self.inner
+            .snapshot()
+            .await?
+            .into_iter()
+            .map(|(key, value)| {
+                let value = self.registry.decode(&key, value)?;
+                Ok((key, value))
+            })
+            .collect()
+    }
+
+    async fn range(&self, range: KeyRange) -> Result<Vec<(Key, Value)>> {
+        self.inner
+            .range(range)
+            .await?
+            .into_iter()
+            .map(|(key, value)| {
+                let value = self.registry.decode(&key, value)?;
+                Ok((key, value))
+            })
+            .collect()
+    }
+
+    async fn compare_and_swap(
+        &self,
+        key: Key,
+        expected: Option<Value>,
+        new: Option<Value>,
+    ) -> Result<bool> {
+        // Encoding need not be deterministic, so compare decoded values and swap
+        // against the stored bytes just read
+        let current = self.inner.get(&key).await?;
+        let decoded = match &current {
+            Some(value) => Some(self.registry.decode(&key, value.clone())?),
+            None => None,
+        };
+        if decoded != expected {
+            return Ok(false);
+        }
+        let new = match new {
+            Some(value) => Some(self.registry.encode(&key, value)?),
+            None => None,
+        };
+        self.inner.compare_and_swap(key, current, new).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::SledStorage;
+
+    /// XORs every byte, so encoding twice restores the input
+    struct XorTransformer(u8);
+
+    impl ValueTransformer for XorTransformer {
+        fn name(&self) -> &str {
+            "xor"
+        }
+
+        fn encode(&self, _key: &Key, value: Value) -> Result<Value> {
+            Ok(value.into_iter().map(|b| b ^ self.0).collect())
+        }
+
+        fn decode(&self, key: &Key, value: Value) -> Result<Value> {
+            self.encode(key, value)
+        }
+    }
+
+    /// Appends a marker byte, to verify ordering
+    struct SuffixTransformer(u8);
+
+    impl ValueTransformer for SuffixTransformer {
+        fn name(&self) -> &str {
+            "suffix"
+        }
+
+        fn encode(&self, _key: &Key, mut value: Value) -> Result<Value> {
+            value.push(self.0);
+            Ok(value)
+        }
+
+        fn decode(&self, _key: &Key, mut value: Value) -> Result<Value> {
+            match value.pop() {
+                Some(b) if b == self.0 => Ok(value),
+                _ => Err(ScribeError::Storage("missing suffix".to_string())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_chain_encode_decode_order() {
+        let chain = TransformerChain::new()
+            .with(Arc::new(SuffixTransformer(1)))
+            .with(Arc::new(SuffixTransformer(2)));
+        let key = b"key".to_vec();
+
+        let encoded = chain.encode(&key, b"v".to_vec()).unwrap();
+        assert_eq!(encoded, vec![b'v', 1, 2]);
+
+        let decoded = chain.decode(&key, encoded).unwrap();
+        assert_eq!(decoded, b"v".to_vec());
+    }
+
+    #[test]
+    fn test_gzip_roundtrip() {
+        let gzip = GzipTransformer::default();
+        let key = b"key".to_vec();
+        let value = vec![b'a'; 4096];
+
+        let encoded = gzip.encode(&key, value.clone()).unwrap();
+        assert!(encoded.len() < value.len());
+        assert_eq!(gzip.decode(&key, encoded).unwrap(), value);
+    }
+
+    #[test]
+    fn test_named_transformers() {
+        let chain = TransformerChain::named(&["gzip", "gzip:9"]).unwrap();
+        assert_eq!(chain.len(), 2);
+        let key = b"key".to_vec();
+        let encoded = chain.encode(&key, b"value".to_vec()).unwrap();
+        assert_eq!(chain.decode(&key, encoded).unwrap(), b"value".to_vec());
+
+        for name in ["zstd", "gzip:10", "gzip:"] {
+            assert!(matches!(
+                transformer_named(name),
+                Err(ScribeError::Configuration(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_registry_longest_prefix() {
+        let registry = TransformerRegistry::new();
+        registry
+            .register(
+                b"users/".to_vec(),
+                TransformerChain::new().with(Arc::new(SuffixTransformer(1))),
+            )
+            .unwrap();
+        registry
+            .register(
+                b"users/admin/".to_vec(),
+                TransformerChain::new().with(Arc::new(SuffixTransformer(2))),
+            )
+            .unwrap();
+
+        let encoded = registry
+            .encode(&b"users/admin/1".to_vec(), b"v".to_vec())
+            .unwrap();
+        assert_eq!(encoded, vec![b'v', 2]);
+
+        let encoded = registry
+            .encode(&b"users/1".to_vec(), b"v".to_vec())
+            .unwrap();
+        assert_eq!(encoded, vec![b'v', 1]);
+
+        let encoded = registry.encode(&b"other".to_vec(), b"v".to_vec()).unwrap();
+        assert_eq!(encoded, b"v".to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_transforming_storage_roundtrip() {
+        let registry = TransformerRegistry::new();
+        registry
+            .register(
+                b"secret/".to_vec(),
+                TransformerChain::new()
+                    .with(Arc::new(GzipTransformer::default()))
+                    .with(Arc::new(XorTransformer(0x5a))),
+            )
+            .unwrap();
+
+        let storage = TransformingStorage::new(SledStorage::temp().unwrap(), registry);
+        let key = b"secret/1".to_vec();
+        let value = b"top secret value".to_vec();
+
+        storage.put(key.clone(), value.clone()).await.unwrap();
+
+        // Stored bytes are transformed
+        let raw = storage.inner().get(&key).await.unwrap().unwrap();
+        assert_ne!(raw, value);
+
+        assert_eq!(storage.get(&key).await.unwrap(), Some(value.clone()));
+
+        let snapshot = storage.snapshot().await.unwrap();
+        assert_eq!(snapshot.get(&key), Some(&value));
+        assert_eq!(
+            storage.scan(b"secret/").await.unwrap(),
+            vec![(key.clone(), value.clone())]
+        );
+
+        // Swaps compare decoded values
+        assert!(!storage
+            .put_if_absent(key.clone(), b"other".to_vec())
+            .await
+            .unwrap());
+        assert!(storage
+            .compare_and_swap(key.clone(), Some(value), Some(b"new value".to_vec()))
+            .await
+            .unwrap());
+        assert_eq!(
+            storage.get(&key).await.unwrap(),
+            Some(b"new value".to_vec())
+        );
+    }
+}
@@ -0,0 +1,266 @@
+//! Server-side filters for change stream subscriptions
+//!
+//! A subscriber to `GET /events` interested in a small slice of a busy ledger
+//! describes that slice in the query string, and the node drops every other event
+//! before it is sent:
+//!
+//! - `prefix`: keys starting with this prefix
+//! - `namespace`: keys in one of these namespaces (comma-separated key prefixes)
+//! - `kind`: changes of these kinds (comma-separated `put`, `delete`, `expire`)
+//! - `min_size`, `max_size`: puts whose value is within this many bytes, inclusive
+//! - `field`, `equals`: puts of JSON documents whose field at this JSON pointer
+//!   (e.g. `/status`) equals this value, parsed as JSON if it is valid JSON and taken
+//!   as a string otherwise
+//!
+//! Conditions combine with AND. Deletions and expiries carry no value, so they never
+//! match a size or field condition.
+
+use crate::consensus::{ChangeEvent, ChangeKind};
+use serde::Deserialize;
+
+/// Filter of a change stream subscription, as given in the query string
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct SubscriptionQuery {
+    /// Only keys starting with this prefix
+    pub prefix: Option<String>,
+    /// Only keys in one of these comma-separated namespaces
+    pub namespace: Option<String>,
+    /// Only these comma-separated kinds of change
+    pub kind: Option<String>,
+    /// Only puts of values at least this many bytes long
+    pub min_size: Option<u64>,
+    /// Only puts of values at most this many bytes long
+    pub max_size: Option<u64>,
+    /// JSON pointer of the document field compared with `equals`
+    pub field: Option<String>,
+    /// Value the document field must hold
+    pub equals: Option<String>,
+}
+
+impl SubscriptionQuery {
+    /// Check the query and turn it into a filter
+    pub fn compile(&self) -> Result<ChangeFilter, String> {
+        let kinds = match &self.kind {
+            Some(kinds) => kinds
+                .split(',')
+                .map(|kind| match kind.trim() {
+                    "put" => Ok(ChangeKind::Put),
+                    "delete" => Ok(ChangeKind::Delete),
+                    "expire" => Ok(ChangeKind::Expire),
+                    other => Err(format!(
+                        "Unknown change kind '{}', expected put, delete or expire",
+                        other
+                    )),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => Vec::new(),
+        };
+        if let (Some(min), Some(max)) = (self.min_size, self.max_size) {
+            if min > max {
+                return Err("min_size must not exceed max_size".to_string());
+            }
+        }
+        let field = match (&self.field, &self.equals) {
+            (Some(pointer), Some(expected)) => {
+                if !pointer.is_empty() && !pointer.starts_with('/') {
+                    return Err(format!(
+                        "field must be a JSON pointer such as '/status', got '{}'",
+                        pointer
+                    ));
+                }
+                let expected = serde_json::from_str(expected)
+                    .unwrap_or_else(|_| serde_json::Value::String(expected.clone()));
+                Some((pointer.clone(), expected))
+            }
+            (None, None) => None,
+            _ => return Err("field and equals must be given together".to_string()),
+        };
+        Ok(ChangeFilter {
+            prefix: self.prefix.clone().map(String::into_bytes),
+            namespaces: self
+                .namespace
+                .iter()
+                .flat_map(|namespaces| namespaces.split(','))
+                .filter(|namespace| !namespace.is_empty())
+                .map(|namespace| namespace.as_bytes().to_vec())
+                .collect(),
+            kinds,
+            min_size: self.min_size,
+            max_size: self.max_size,
+            field,
+        })
+    }
+}
+
+/// Compiled filter of a change stream subscription
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChangeFilter {
+    prefix: Option<Vec<u8>>,
+    namespaces: Vec<Vec<u8>>,
+    kinds: Vec<ChangeKind>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    field: Option<(String, serde_json::Value)>,
+}
+
+impl ChangeFilter {
+    /// Whether the filter passes every event
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether `event` is sent to the subscriber
+    pub fn matches(&self, event: &ChangeEvent) -> bool {
+        if self
+            .prefix
+            .as_ref()
+            .is_some_and(|prefix| !event.key.starts_with(prefix))
+        {
+            return false;
+        }
+        if !self.namespaces.is_empty()
+            && !self
+                .namespaces
+                .iter()
+                .any(|namespace| event.key.starts_with(namespace))
+        {
+            return false;
+        }
+        if !self.kinds.is_empty() && !self.kinds.contains(&event.kind) {
+            return false;
+        }
+        if self.min_size.is_none() && self.max_size.is_none() && self.field.is_none() {
+            return true;
+        }
+
+        let Some(value) = event.value.as_deref() else {
+            return false;
+        };
+        let size = value.len() as u64;
+        if self.min_size.is_some_and(|min| size < min)
+            || self.max_size.is_some_and(|max| size > max)
+        {
+            return false;
+        }
+        match &self.field {
+            Some((pointer, expected)) => serde_json::from_slice::<serde_json::Value>(value)
+                .ok()
+                .and_then(|document| document.pointer(pointer).cloned())
+                .is_some_and(|actual| actual == *expected),
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn event(key: &str, kind: ChangeKind, value: Option<&str>) -> ChangeEvent {
+        ChangeEvent {
+            key: key.as_bytes().to_vec(),
+            kind,
+            timestamp: 1_760_000_000_000,
+            version: 7,
+            value: value.map(|value| Arc::new(value.as_bytes().to_vec())),
+        }
+    }
+
+    fn query() -> SubscriptionQuery {
+        SubscriptionQuery::default()
+    }
+
+    fn text(value: &str) -> Option<String> {
+        Some(value.to_string())
+    }
+
+    #[test]
+    fn test_key_and_kind_filters() {
+        let filter = query().compile().unwrap();
+        assert!(filter.is_empty());
+        assert!(filter.matches(&event("a", ChangeKind::Expire, None)));
+
+        let filter = SubscriptionQuery {
+            prefix: text("orders/"),
+            kind: text("put,delete"),
+            ..query()
+        }
+        .compile()
+        .unwrap();
+        assert!(filter.matches(&event("orders/1", ChangeKind::Put, Some("x"))));
+        assert!(filter.matches(&event("orders/1", ChangeKind::Delete, None)));
+        assert!(!filter.matches(&event("orders/1", ChangeKind::Expire, None)));
+        assert!(!filter.matches(&event("users/1", ChangeKind::Put, Some("x"))));
+
+        let filter = SubscriptionQuery {
+            namespace: text("acme/,globex/"),
+            ..query()
+        }
+        .compile()
+        .unwrap();
+        assert!(filter.matches(&event("acme/1", ChangeKind::Put, None)));
+        assert!(filter.matches(&event("globex/1", ChangeKind::Put, None)));
+        assert!(!filter.matches(&event("initech/1", ChangeKind::Put, None)));
+
+        let invalid = [
+            SubscriptionQuery {
+                kind: text("update"),
+                ..query()
+            },
+            SubscriptionQuery {
+                min_size: Some(10),
+                max_size: Some(5),
+                ..query()
+            },
+            SubscriptionQuery {
+                field: text("/status"),
+                ..query()
+            },
+            SubscriptionQuery {
+                field: text("status"),
+                equals: text("active"),
+                ..query()
+            },
+        ];
+        for query in invalid {
+            assert!(query.compile().is_err(), "{:?}", query);
+        }
+    }
+
+    #[test]
+    fn test_value_filters() {
+        let filter = SubscriptionQuery {
+            min_size: Some(2),
+            max_size: Some(4),
+            ..query()
+        }
+        .compile()
+        .unwrap();
+        assert!(filter.matches(&event("k", ChangeKind::Put, Some("abc"))));
+        assert!(!filter.matches(&event("k", ChangeKind::Put, Some("a"))));
+        assert!(!filter.matches(&event("k", ChangeKind::Put, Some("abcde"))));
+        assert!(!filter.matches(&event("k", ChangeKind::Delete, None)));
+
+        // Unquoted values compare as strings, valid JSON as JSON
+        let field = |pointer: &str, equals: &str| {
+            SubscriptionQuery {
+                field: text(pointer),
+                equals: text(equals),
+                ..query()
+            }
+            .compile()
+            .unwrap()
+        };
+        let filter = field("/order/status", "shipped");
+        let shipped = r#"{"order":{"status":"shipped"}}"#;
+        assert!(filter.matches(&event("k", ChangeKind::Put, Some(shipped))));
+        let pending = r#"{"order":{"status":"pending"}}"#;
+        assert!(!filter.matches(&event("k", ChangeKind::Put, Some(pending))));
+        assert!(!filter.matches(&event("k", ChangeKind::Put, Some("not json"))));
+
+        let filter = field("/qty", "3");
+        assert!(filter.matches(&event("k", ChangeKind::Put, Some(r#"{"qty":3}"#))));
+        assert!(!filter.matches(&event("k", ChangeKind::Put, Some(r#"{"qty":"3"}"#))));
+    }
+}
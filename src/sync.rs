@@ -0,0 +1,328 @@
+//! Differential sync between two ledgers
+//!
+//! Every node splits its keys into buckets by a hash of the key and serves a Merkle
+//! root per bucket (`GET /sync/buckets`) and the value digests of the keys in a
+//! bucket (`GET /sync/buckets/:bucket`). [`DifferentialSync`] compares the bucket
+//! roots of a source and a target cluster, lists keys only in the buckets whose roots
+//! differ, and copies only the keys whose values differ, so a lagging replica cluster
+//! or an air-gapped copy catches up without a full re-export.
+
+use crate::crypto::MerkleTree;
+use crate::error::{Result, ScribeError};
+use crate::http_client::ClusterClient;
+use crate::types::{Key, Value};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/// Number of buckets keys are split into unless configured otherwise
+pub const DEFAULT_SYNC_BUCKETS: u32 = 256;
+
+/// Largest accepted number of buckets
+pub const MAX_SYNC_BUCKETS: u32 = 65_536;
+
+/// Keys copied to the target concurrently
+const COPY_CONCURRENCY: usize = 16;
+
+/// Merkle root over the keys of one bucket
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BucketDigest {
+    /// Bucket number
+    pub bucket: u32,
+    /// Number of keys in the bucket
+    pub key_count: usize,
+    /// Merkle root (hex) over the keys and value digests, `None` if the bucket is empty
+    pub root: Option<String>,
+}
+
+/// SHA-256 digest of the value of one key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyDigest {
+    /// The key
+    pub key: String,
+    /// SHA-256 (hex) of the value
+    pub digest: String,
+    /// Version of the key on the serving cluster (log index of its last change)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u64>,
+    /// Time the serving cluster applied the key's last change (milliseconds since
+    /// the Unix epoch)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified_at: Option<u64>,
+}
+
+/// Keys a target must change to match a source
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyDiff {
+    /// Keys missing on the target or holding a different value there
+    pub to_copy: Vec<String>,
+    /// Keys the target holds but the source does not
+    pub to_delete: Vec<String>,
+}
+
+/// Outcome of a sync run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncReport {
+    /// Number of buckets compared
+    pub buckets_compared: u32,
+    /// Buckets whose roots differed
+    pub buckets_differing: u32,
+    /// Keys written to the target (or that would be, in a dry run)
+    pub keys_copied: usize,
+    /// Keys deleted from the target (or that would be, in a dry run)
+    pub keys_deleted: usize,
+    /// Keys only on the target, left in place because deletes were not requested
+    pub keys_extra: usize,
+    /// Value bytes written to the target
+    pub bytes_copied: u64,
+    /// Whether the target was left unchanged
+    pub dry_run: bool,
+}
+
+/// Bucket of `key` among `buckets` buckets
+pub fn bucket_of(key: &[u8], buckets: u32) -> u32 {
+    let hash = Sha256::digest(key);
+    let prefix = u32::from_be_bytes([hash[0], hash[1], hash[2], hash[3]]);
+    prefix % buckets.max(1)
+}
+
+/// Digest of every bucket, including empty ones, ordered by bucket number
+pub fn bucket_digests(pairs: &[(Key, Value)], buckets: u32) -> Vec<BucketDigest> {
+    let buckets = buckets.max(1);
+    let mut leaves: BTreeMap<u32, Vec<(Key, Vec<u8>)>> = BTreeMap::new();
+    for (key, value) in pairs {
+        leaves
+            .entry(bucket_of(key, buckets))
+            .or_default()
+            .push((key.clone(), Sha256::digest(value).to_vec()));
+    }
+
+    (0..buckets)
+        .map(|bucket| {
+            let leaves = leaves.remove(&bucket).unwrap_or_default();
+            BucketDigest {
+                bucket,
+                key_count: leaves.len(),
+                root: MerkleTree::from_pairs(leaves).root_hash().map(hex::encode),
+            }
+        })
+        .collect()
+}
+
+/// Value digest of every key, ordered by key
+pub fn key_digests(pairs: &[(Key, Value)]) -> Vec<KeyDigest> {
+    let mut digests: Vec<KeyDigest> = pairs
+        .iter()
+        .map(|(key, value)| KeyDigest {
+            key: String::from_utf8_lossy(key).to_string(),
+            digest: hex::encode(Sha256::digest(value)),
+            version: None,
+            modified_at: None,
+        })
+        .collect();
+    digests.sort_by(|a, b| a.key.cmp(&b.key));
+    digests
+}
+
+/// Buckets whose roots differ between `source` and `target`
+///
+/// Fails if the two sides split their keys into a different number of buckets.
+pub fn differing_buckets(source: &[BucketDigest], target: &[BucketDigest]) -> Result<Vec<u32>> {
+    if source.len() != target.len() {
+        return Err(ScribeError::Other(format!(
+            "Bucket counts differ: source has {}, target has {}",
+            source.len(),
+            target.len()
+        )));
+    }
+    Ok(source
+        .iter()
+        .zip(target)
+        .filter(|(source, target)| source.root != target.root)
+        .map(|(source, _)| source.bucket)
+        .collect())
+}
+
+/// Keys the target must copy or delete to match the source
+pub fn diff_keys(source: &[KeyDigest], target: &[KeyDigest]) -> KeyDiff {
+    let target: BTreeMap<&str, &str> = target
+        .iter()
+        .map(|key| (key.key.as_str(), key.digest.as_str()))
+        .collect();
+    let source_keys: BTreeMap<&str, &str> = source
+        .iter()
+        .map(|key| (key.key.as_str(), key.digest.as_str()))
+        .collect();
+
+    KeyDiff {
+        to_copy: source_keys
+            .iter()
+            .filter(|(key, digest)| target.get(*key) != Some(*digest))
+            .map(|(key, _)| key.to_string())
+            .collect(),
+        to_delete: target
+            .keys()
+            .filter(|key| !source_keys.contains_key(*key))
+            .map(|key| key.to_string())
+            .collect(),
+    }
+}
+
+/// Copies the keys that differ from a source cluster to a target cluster
+///
+/// Both clusters are read through their `/sync` endpoints, which serve each node's
+/// local state, so a follower may report slightly stale digests; values are then read
+/// from the source leader. Values are copied without their TTL, and keys demoted to
+/// cold storage on the source arrive on the target as ordinary values.
+pub struct DifferentialSync {
+    source: ClusterClient,
+    target: ClusterClient,
+    buckets: u32,
+    delete_extra: bool,
+    dry_run: bool,
+}
+
+impl DifferentialSync {
+    /// Create a sync from `source` to `target`
+    pub fn new(source: ClusterClient, target: ClusterClient) -> Self {
+        Self {
+            source,
+            target,
+            buckets: DEFAULT_SYNC_BUCKETS,
+            delete_extra: false,
+            dry_run: false,
+        }
+    }
+
+    /// Split keys into `buckets` buckets (more buckets narrow down differences further)
+    pub fn with_buckets(mut self, buckets: u32) -> Self {
+        self.buckets = buckets.clamp(1, MAX_SYNC_BUCKETS);
+        self
+    }
+
+    /// Delete keys the target holds but the source does not
+    pub fn with_delete_extra(mut self, delete_extra: bool) -> Self {
+        self.delete_extra = delete_extra;
+        self
+    }
+
+    /// Only report what would change, leaving the target untouched
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Compare both clusters and bring the target in line with the source
+    pub async fn run(&self) -> Result<SyncReport> {
+        let (source, target) = tokio::try_join!(
+            self.source.sync_buckets(self.buckets),
+            self.target.sync_buckets(self.buckets)
+        )?;
+        let differing = differing_buckets(&source, &target)?;
+
+        let mut report = SyncReport {
+            buckets_compared: self.buckets,
+            buckets_differing: differing.len() as u32,
+            dry_run: self.dry_run,
+            ..Default::default()
+        };
+        for bucket in differing {
+            let (source, target) = tokio::try_join!(
+                self.source.sync_bucket_keys(bucket, self.buckets),
+                self.target.sync_bucket_keys(bucket, self.buckets)
+            )?;
+            let diff = diff_keys(&source, &target);
+            self.apply(diff, &mut report).await?;
+        }
+        Ok(report)
+    }
+
+    /// Copy and delete the keys of one bucket
+    async fn apply(&self, diff: KeyDiff, report: &mut SyncReport) -> Result<()> {
+        let to_delete = if self.delete_extra {
+            diff.to_delete
+        } else {
+            report.keys_extra += diff.to_delete.len();
+            Vec::new()
+        };
+
+        if self.dry_run {
+            report.keys_copied += diff.to_copy.len();
+            report.keys_deleted += to_delete.len();
+            return Ok(());
+        }
+
+        let copied: Vec<Option<u64>> = stream::iter(diff.to_copy)
+            .map(|key| async move {
+                let Some(value) = self.source.get(key.as_bytes()).await? else {
+                    return Ok(None);
+                };
+                let size = value.len() as u64;
+                self.target.put(key.as_bytes(), value).await?;
+                Ok::<_, ScribeError>(Some(size))
+            })
+            .buffer_unordered(COPY_CONCURRENCY)
+            .try_collect()
+            .await?;
+        // Keys deleted on the source meanwhile are picked up by the next run
+        for size in copied.into_iter().flatten() {
+            report.keys_copied += 1;
+            report.bytes_copied += size;
+        }
+
+        stream::iter(&to_delete)
+            .map(|key| self.target.delete(key.as_bytes()))
+            .buffer_unordered(COPY_CONCURRENCY)
+            .try_collect::<Vec<()>>()
+            .await?;
+        report.keys_deleted += to_delete.len();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(entries: &[(&str, &str)]) -> Vec<(Key, Value)> {
+        entries
+            .iter()
+            .map(|(key, value)| (key.as_bytes().to_vec(), value.as_bytes().to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn test_bucket_digests_isolate_changes() {
+        let source = pairs(&[("a", "1"), ("b", "2"), ("c", "3"), ("d", "4")]);
+        let mut target = source.clone();
+        target[2].1 = b"changed".to_vec();
+
+        let source_digests = bucket_digests(&source, 16);
+        let target_digests = bucket_digests(&target, 16);
+        assert_eq!(source_digests.len(), 16);
+        assert_eq!(source_digests.iter().map(|d| d.key_count).sum::<usize>(), 4);
+        assert_eq!(
+            differing_buckets(&source_digests, &target_digests).unwrap(),
+            vec![bucket_of(b"c", 16)]
+        );
+
+        // Insertion order does not matter
+        let mut reordered = source.clone();
+        reordered.reverse();
+        assert_eq!(bucket_digests(&reordered, 16), source_digests);
+
+        assert!(differing_buckets(&source_digests, &bucket_digests(&source, 8)).is_err());
+    }
+
+    #[test]
+    fn test_diff_keys() {
+        let source = key_digests(&pairs(&[("same", "1"), ("changed", "2"), ("new", "3")]));
+        let target = key_digests(&pairs(&[("same", "1"), ("changed", "old"), ("gone", "4")]));
+
+        let diff = diff_keys(&source, &target);
+        assert_eq!(diff.to_copy, vec!["changed".to_string(), "new".to_string()]);
+        assert_eq!(diff.to_delete, vec!["gone".to_string()]);
+        assert_eq!(diff_keys(&source, &source), KeyDiff::default());
+    }
+}
@@ -0,0 +1,585 @@
+//! In-process cluster harness for integration tests
+//!
+//! [`TestCluster`] starts a multi-node cluster inside the current process. Nodes talk
+//! to each other through the real Raft TCP transport and serve a minimal client API
+//! (`PUT`/`GET`/`DELETE /:key`, with NotLeader redirects and ETags, the
+//! `GET /events` change stream and the `/locks`, `/queues` and `/sync` endpoints) on loopback ports, so client behaviour such as
+//! failover and cache invalidation can be exercised end to end. Nodes can be killed
+//! to simulate crashes. Enabled with the `testing` feature.
+
+use crate::api::{DistributedApi, ReadConsistency};
+use crate::config::{ConsensusConfig, MetricsHistoryConfig, SnapshotTransferConfig};
+use crate::consensus::{serve_raft_rpc_with_auth, ConsensusNode};
+use crate::error::{Result, ScribeError};
+use crate::http_client::{
+    value_etag, AckRequest, AckResponse, AcquireLockRequest, DequeueRequest, EnqueueResponse,
+    ReleaseLockRequest, ReleaseLockResponse, RenewLockRequest, QUEUE_ATTEMPTS_HEADER,
+    QUEUE_ITEM_ID_HEADER, QUEUE_RECEIPT_HEADER, QUEUE_VISIBLE_AT_HEADER, RAFT_LEADER_HEADER,
+};
+use crate::subscription::SubscriptionQuery;
+use crate::sync::DEFAULT_SYNC_BUCKETS;
+use crate::types::NodeId;
+use axum::body::Bytes;
+use axum::extract::{OriginalUri, Path, Query, Request, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::Router;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+/// Raft timings short enough for fast failover in tests
+const TEST_CONSENSUS: ConsensusConfig = ConsensusConfig {
+    election_timeout_min: 500,
+    election_timeout_max: 1000,
+    heartbeat_interval_ms: 100,
+    max_payload_entries: 300,
+    snapshot_logs_since_last: 5000,
+    max_in_snapshot_log_to_keep: 1000,
+    snapshot_transfer: SnapshotTransferConfig::unlimited(),
+    metrics_history: MetricsHistoryConfig::standard(),
+    follower_reads: true,
+};
+
+/// A node of a [`TestCluster`]
+pub struct TestNode {
+    /// Node ID
+    pub node_id: NodeId,
+    /// Address of the Raft RPC server
+    pub raft_addr: SocketAddr,
+    /// Address of the client API
+    pub client_addr: SocketAddr,
+    consensus: Arc<ConsensusNode>,
+    api: Arc<DistributedApi>,
+    tasks: Vec<JoinHandle<()>>,
+    alive: bool,
+}
+
+impl TestNode {
+    /// Base URL of the node's client API
+    pub fn url(&self) -> String {
+        format!("http://{}", self.client_addr)
+    }
+
+    /// The node's consensus layer
+    pub fn consensus(&self) -> &Arc<ConsensusNode> {
+        &self.consensus
+    }
+
+    /// The node's distributed API
+    pub fn api(&self) -> &Arc<DistributedApi> {
+        &self.api
+    }
+
+    /// Whether the node is still running
+    pub fn is_alive(&self) -> bool {
+        self.alive
+    }
+}
+
+/// Multi-node cluster running in the current process
+pub struct TestCluster {
+    nodes: Vec<TestNode>,
+}
+
+impl TestCluster {
+    /// Start a cluster of `size` voters and wait until it elects a leader
+    ///
+    /// Every node listens on ephemeral loopback ports, so clusters in parallel tests
+    /// do not collide.
+    pub async fn start(size: usize) -> Result<Self> {
+        if size == 0 {
+            return Err(ScribeError::Configuration(
+                "A test cluster needs at least one node".to_string(),
+            ));
+        }
+
+        let mut listeners = Vec::with_capacity(size);
+        for _ in 0..size {
+            listeners.push((
+                TcpListener::bind("127.0.0.1:0").await?,
+                TcpListener::bind("127.0.0.1:0").await?,
+            ));
+        }
+
+        let mut members = BTreeMap::new();
+        let mut client_addrs = HashMap::new();
+        for (i, (raft, client)) in listeners.iter().enumerate() {
+            let node_id = i as NodeId + 1;
+            members.insert(node_id, raft.local_addr()?.to_string());
+            client_addrs.insert(node_id, client.local_addr()?);
+        }
+        let client_addrs = Arc::new(client_addrs);
+
+        let mut nodes = Vec::with_capacity(size);
+        for (i, (raft_listener, client_listener)) in listeners.into_iter().enumerate() {
+            let node_id = i as NodeId + 1;
+            let db = sled::Config::new().temporary(true).open()?;
+            let consensus = Arc::new(
+                ConsensusNode::new_with_scribe_config(node_id, db, &TEST_CONSENSUS)
+                    .await
+                    .map_err(|e| ScribeError::Consensus(e.to_string()))?,
+            );
+            for (peer_id, addr) in &members {
+                if *peer_id != node_id {
+                    consensus.register_peer(*peer_id, addr.clone()).await;
+                }
+            }
+            let api = Arc::new(DistributedApi::new(Arc::clone(&consensus)));
+
+            let raft_addr = raft_listener.local_addr()?;
+            let client_addr = client_listener.local_addr()?;
+
+            let raft = consensus.raft();
+            let snapshot_throttle = consensus.snapshot_receive_throttle();
+            let peer_versions = consensus.peer_versions();
+            let raft_task = tokio::spawn(async move {
+                let _ = serve_raft_rpc_with_auth(
+                    raft,
+                    raft_listener,
+                    snapshot_throttle,
+                    None,
+                    peer_versions,
+                )
+                .await;
+            });
+
+            let app = Router::new()
+                .route("/events", get(events_handler))
+                .route("/sync/buckets", get(sync_buckets_handler))
+                .route("/sync/buckets/:bucket", get(sync_bucket_keys_handler))
+                .route("/locks/:name", get(lock_info_handler))
+                .route("/locks/:name/acquire", post(lock_acquire_handler))
+                .route("/locks/:name/renew", post(lock_renew_handler))
+                .route("/locks/:name/release", post(lock_release_handler))
+                .route(
+                    "/queues/:name",
+                    get(queue_stats_handler).post(enqueue_handler),
+                )
+                .route("/queues/:name/dequeue", post(dequeue_handler))
+                .route("/queues/:name/ack", post(ack_handler))
+                .route("/:key/swap", post(swap_handler))
+                .route(
+                    "/:key",
+                    get(get_handler).put(put_handler).delete(delete_handler),
+                )
+                .layer(axum::middleware::from_fn(leader_redirect_middleware))
+                .with_state(NodeState {
+                    api: Arc::clone(&api),
+                    client_addrs: Arc::clone(&client_addrs),
+                });
+            let client_task = tokio::spawn(async move {
+                let _ = axum::serve(client_listener, app).await;
+            });
+
+            nodes.push(TestNode {
+                node_id,
+                raft_addr,
+                client_addr,
+                consensus,
+                api,
+                tasks: vec![raft_task, client_task],
+                alive: true,
+            });
+        }
+
+        nodes[0]
+            .consensus
+            .initialize_with_members(members)
+            .await
+            .map_err(|e| ScribeError::Cluster(format!("Failed to initialize: {}", e)))?;
+
+        let cluster = Self { nodes };
+        cluster.wait_for_leader(Duration::from_secs(10)).await?;
+        Ok(cluster)
+    }
+
+    /// Every node, including killed ones
+    pub fn nodes(&self) -> &[TestNode] {
+        &self.nodes
+    }
+
+    /// Look up a node by ID
+    pub fn node(&self, node_id: NodeId) -> Option<&TestNode> {
+        self.nodes.iter().find(|node| node.node_id == node_id)
+    }
+
+    /// Client API base URLs of every node, including killed ones
+    pub fn client_urls(&self) -> Vec<String> {
+        self.nodes.iter().map(TestNode::url).collect()
+    }
+
+    /// Wait until a live node is leader and every live node agrees on it
+    pub async fn wait_for_leader(&self, timeout: Duration) -> Result<NodeId> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(leader) = self.agreed_leader().await {
+                return Ok(leader);
+            }
+            if Instant::now() >= deadline {
+                return Err(ScribeError::Timeout(format!(
+                    "No leader elected within {:?}",
+                    timeout
+                )));
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    async fn agreed_leader(&self) -> Option<NodeId> {
+        let mut leader = None;
+        for node in self.nodes.iter().filter(|node| node.alive) {
+            let current = node.consensus.current_leader().await?;
+            if leader.is_some_and(|leader| leader != current) {
+                return None;
+            }
+            leader = Some(current);
+        }
+        leader.filter(|leader| self.node(*leader).is_some_and(|node| node.alive))
+    }
+
+    /// Crash a node: stop Raft and close its RPC and client ports
+    pub async fn kill(&mut self, node_id: NodeId) -> Result<()> {
+        let node = self
+            .nodes
+            .iter_mut()
+            .find(|node| node.node_id == node_id)
+            .ok_or_else(|| ScribeError::NotFound(format!("Node {}", node_id)))?;
+        if !node.alive {
+            return Ok(());
+        }
+
+        for task in node.tasks.drain(..) {
+            task.abort();
+            let _ = task.await;
+        }
+        node.consensus
+            .shutdown()
+            .await
+            .map_err(|e| ScribeError::Consensus(e.to_string()))?;
+        node.alive = false;
+        Ok(())
+    }
+}
+
+impl Drop for TestCluster {
+    fn drop(&mut self) {
+        for node in &self.nodes {
+            for task in &node.tasks {
+                task.abort();
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+struct NodeState {
+    api: Arc<DistributedApi>,
+    client_addrs: Arc<HashMap<NodeId, SocketAddr>>,
+}
+
+#[derive(Deserialize)]
+struct PutQuery {
+    ttl: Option<u64>,
+}
+
+async fn put_handler(
+    State(state): State<NodeState>,
+    Path(key): Path<String>,
+    Query(query): Query<PutQuery>,
+    body: Bytes,
+) -> Response {
+    let result = match query.ttl {
+        Some(ttl) => {
+            state
+                .api
+                .put_with_ttl(key.into_bytes(), body.to_vec(), Duration::from_secs(ttl))
+                .await
+        }
+        None => state.api.put(key.into_bytes(), body.to_vec()).await,
+    };
+    match result {
+        Ok(_) => StatusCode::OK.into_response(),
+        Err(e) => error_response(&state, e),
+    }
+}
+
+async fn get_handler(
+    State(state): State<NodeState>,
+    Path(key): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    match state
+        .api
+        .get(key.clone().into_bytes(), ReadConsistency::Linearizable)
+        .await
+    {
+        Ok(Some(value)) => {
+            let etag = value_etag(&value);
+            if headers
+                .get(header::IF_NONE_MATCH)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|tag| tag == etag)
+            {
+                return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+            }
+            (StatusCode::OK, [(header::ETAG, etag)], value).into_response()
+        }
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => error_response(&state, e),
+    }
+}
+
+#[derive(Deserialize)]
+struct DeleteQuery {
+    #[serde(rename = "return")]
+    return_value: Option<String>,
+}
+
+async fn delete_handler(
+    State(state): State<NodeState>,
+    Path(key): Path<String>,
+    Query(query): Query<DeleteQuery>,
+) -> Response {
+    if query.return_value.as_deref() == Some("previous") {
+        return match state.api.get_and_delete(key.clone().into_bytes()).await {
+            Ok((previous, _)) => previous_value_response(previous),
+            Err(e) => error_response(&state, e),
+        };
+    }
+    match state.api.delete(key.clone().into_bytes()).await {
+        Ok(_) => StatusCode::OK.into_response(),
+        Err(e) => error_response(&state, e),
+    }
+}
+
+async fn swap_handler(
+    State(state): State<NodeState>,
+    Path(key): Path<String>,
+    body: Bytes,
+) -> Response {
+    match state
+        .api
+        .get_and_set(key.clone().into_bytes(), body.to_vec())
+        .await
+    {
+        Ok((previous, _)) => previous_value_response(previous),
+        Err(e) => error_response(&state, e),
+    }
+}
+
+fn previous_value_response(previous: Option<Vec<u8>>) -> Response {
+    match previous {
+        Some(value) => (StatusCode::OK, value).into_response(),
+        None => StatusCode::NO_CONTENT.into_response(),
+    }
+}
+
+async fn lock_acquire_handler(
+    State(state): State<NodeState>,
+    Path(name): Path<String>,
+    axum::Json(request): axum::Json<AcquireLockRequest>,
+) -> Response {
+    let ttl = Duration::from_secs(request.ttl_secs);
+    match state.api.acquire_lock(&name, &request.holder, ttl).await {
+        Ok(lease) => axum::Json(lease).into_response(),
+        Err(e) => error_response(&state, e),
+    }
+}
+
+async fn lock_renew_handler(
+    State(state): State<NodeState>,
+    Path(name): Path<String>,
+    axum::Json(request): axum::Json<RenewLockRequest>,
+) -> Response {
+    let ttl = Duration::from_secs(request.ttl_secs);
+    match state.api.renew_lock(&name, request.token, ttl).await {
+        Ok(lease) => axum::Json(lease).into_response(),
+        Err(e) => error_response(&state, e),
+    }
+}
+
+async fn lock_release_handler(
+    State(state): State<NodeState>,
+    Path(name): Path<String>,
+    axum::Json(request): axum::Json<ReleaseLockRequest>,
+) -> Response {
+    match state.api.release_lock(&name, request.token).await {
+        Ok(released) => axum::Json(ReleaseLockResponse { released }).into_response(),
+        Err(e) => error_response(&state, e),
+    }
+}
+
+async fn lock_info_handler(State(state): State<NodeState>, Path(name): Path<String>) -> Response {
+    match state.api.lock_info(&name).await {
+        Some(lease) => axum::Json(lease).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn enqueue_handler(
+    State(state): State<NodeState>,
+    Path(name): Path<String>,
+    body: Bytes,
+) -> Response {
+    match state.api.enqueue(&name, body.to_vec()).await {
+        Ok(id) => (StatusCode::CREATED, axum::Json(EnqueueResponse { id })).into_response(),
+        Err(e) => error_response(&state, e),
+    }
+}
+
+async fn dequeue_handler(
+    State(state): State<NodeState>,
+    Path(name): Path<String>,
+    axum::Json(request): axum::Json<DequeueRequest>,
+) -> Response {
+    let visibility_timeout = Duration::from_secs(request.visibility_timeout_secs);
+    match state.api.dequeue(&name, visibility_timeout).await {
+        Ok(Some(item)) => {
+            let mut response = (StatusCode::OK, item.payload).into_response();
+            let headers = response.headers_mut();
+            headers.insert(QUEUE_ITEM_ID_HEADER, HeaderValue::from(item.id));
+            headers.insert(QUEUE_RECEIPT_HEADER, HeaderValue::from(item.receipt));
+            headers.insert(QUEUE_ATTEMPTS_HEADER, HeaderValue::from(item.attempts));
+            headers.insert(QUEUE_VISIBLE_AT_HEADER, HeaderValue::from(item.visible_at));
+            response
+        }
+        Ok(None) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => error_response(&state, e),
+    }
+}
+
+async fn ack_handler(
+    State(state): State<NodeState>,
+    Path(name): Path<String>,
+    axum::Json(request): axum::Json<AckRequest>,
+) -> Response {
+    match state.api.ack(&name, request.id, request.receipt).await {
+        Ok(acked) => axum::Json(AckResponse { acked }).into_response(),
+        Err(e) => error_response(&state, e),
+    }
+}
+
+async fn queue_stats_handler(State(state): State<NodeState>, Path(name): Path<String>) -> Response {
+    axum::Json(state.api.queue_stats(&name).await).into_response()
+}
+
+#[derive(Deserialize)]
+struct SyncQuery {
+    buckets: Option<u32>,
+}
+
+async fn sync_buckets_handler(
+    State(state): State<NodeState>,
+    Query(query): Query<SyncQuery>,
+) -> Response {
+    let buckets = query.buckets.unwrap_or(DEFAULT_SYNC_BUCKETS);
+    match state.api.sync_buckets(buckets).await {
+        Ok(digests) => axum::Json(digests).into_response(),
+        Err(e) => (StatusCode::BAD_GATEWAY, e.to_string()).into_response(),
+    }
+}
+
+async fn sync_bucket_keys_handler(
+    State(state): State<NodeState>,
+    Path(bucket): Path<u32>,
+    Query(query): Query<SyncQuery>,
+) -> Response {
+    let buckets = query.buckets.unwrap_or(DEFAULT_SYNC_BUCKETS);
+    match state.api.sync_bucket_keys(bucket, buckets).await {
+        Ok(digests) => axum::Json(digests).into_response(),
+        Err(e) => (StatusCode::BAD_GATEWAY, e.to_string()).into_response(),
+    }
+}
+
+/// Stream change events as server-sent events, filtered like `scribe-node` does
+async fn events_handler(
+    State(state): State<NodeState>,
+    Query(query): Query<SubscriptionQuery>,
+) -> Response {
+    let filter = match query.compile() {
+        Ok(filter) => filter,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let receiver = state.api.subscribe_changes();
+    let stream = futures::stream::unfold(receiver, move |mut receiver| {
+        let filter = filter.clone();
+        async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) if filter.matches(&event) => {
+                        let data = serde_json::json!({
+                            "key": String::from_utf8_lossy(&event.key),
+                            "kind": event.kind.as_str(),
+                            "timestamp": event.timestamp,
+                        });
+                        let event = Event::default()
+                            .event(event.kind.as_str())
+                            .json_data(data)
+                            .unwrap_or_default();
+                        return Some((Ok::<_, Infallible>(event), receiver));
+                    }
+                    Ok(_) => continue,
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        }
+    });
+
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// Redirect NotLeader errors to the leader, like `scribe-node` does
+///
+/// The `Location` is added by [`leader_redirect_middleware`].
+fn error_response(state: &NodeState, err: ScribeError) -> Response {
+    let leader_id = match err {
+        ScribeError::NotLeader { leader_id } => leader_id,
+        ScribeError::Conflict(message) => return (StatusCode::CONFLICT, message).into_response(),
+        err => return (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
+    };
+
+    match leader_id.and_then(|id| state.client_addrs.get(&id)) {
+        Some(addr) => {
+            let mut response = StatusCode::TEMPORARY_REDIRECT.into_response();
+            if let Ok(value) = HeaderValue::from_str(&addr.to_string()) {
+                response.headers_mut().insert(RAFT_LEADER_HEADER, value);
+            }
+            response
+        }
+        None => StatusCode::MISDIRECTED_REQUEST.into_response(),
+    }
+}
+
+/// Point a leader redirect at the path and query of the original request on the
+/// leader, like `scribe-node` does
+async fn leader_redirect_middleware(
+    OriginalUri(uri): OriginalUri,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(request).await;
+    let leader = response
+        .headers()
+        .get(RAFT_LEADER_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    if let Some(leader) = leader.filter(|_| response.status() == StatusCode::TEMPORARY_REDIRECT) {
+        let path = uri.path_and_query().map_or("/", |path| path.as_str());
+        if let Ok(value) = HeaderValue::from_str(&format!("http://{}{}", leader, path)) {
+            response.headers_mut().insert(header::LOCATION, value);
+        }
+    }
+    response
+}
@@ -0,0 +1,287 @@
+//! W3C Trace Context propagation
+//!
+//! Incoming `traceparent`/`tracestate` headers are parsed into a [`TraceContext`]. The
+//! HTTP layer runs each request inside [`TraceContext::scope`] with a span of its own, so
+//! log lines carry the caller's trace ID, and outgoing calls made while handling the
+//! request (leader redirects followed by [`send_following_leader`], S3 requests) carry a
+//! `traceparent` naming the request's span as their parent. Requests without a valid
+//! `traceparent` start a new trace.
+//!
+//! [`send_following_leader`]: crate::http_client::send_following_leader
+
+use reqwest::RequestBuilder;
+use std::future::Future;
+use tracing::Instrument;
+
+/// Header carrying the trace ID, parent span ID and flags
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// Header carrying vendor-specific trace state, passed on unchanged
+pub const TRACESTATE_HEADER: &str = "tracestate";
+
+/// `traceparent` version written by this implementation
+const VERSION: &str = "00";
+
+/// Length of a version 00 `traceparent`
+const TRACEPARENT_LEN: usize = 55;
+
+/// Longest `tracestate` passed on; longer values are dropped as the spec allows
+const MAX_TRACESTATE_LEN: usize = 512;
+
+/// Trace flag asking downstream services to record the trace
+const SAMPLED_FLAG: u8 = 0x01;
+
+tokio::task_local! {
+    static CURRENT: TraceContext;
+}
+
+/// Position of a span in a distributed trace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    trace_id: u128,
+    span_id: u64,
+    parent_span_id: Option<u64>,
+    flags: u8,
+    tracestate: Option<String>,
+}
+
+impl TraceContext {
+    /// Context of the caller's span, from its `traceparent` and `tracestate` headers
+    ///
+    /// Returns `None` if `traceparent` is malformed, in which case both headers are
+    /// ignored.
+    pub fn parse(traceparent: &str, tracestate: Option<&str>) -> Option<Self> {
+        let traceparent = traceparent.trim();
+        if traceparent.len() < TRACEPARENT_LEN || !traceparent.is_ascii() {
+            return None;
+        }
+        let (fields, rest) = traceparent.split_at(TRACEPARENT_LEN);
+
+        let mut parts = fields.split('-');
+        let version = parts.next()?;
+        let trace_id = parts.next()?;
+        let span_id = parts.next()?;
+        let flags = parts.next()?;
+        if parts.next().is_some() {
+            return None;
+        }
+
+        // Later versions may append fields; version 00 may not
+        if !is_lower_hex(version, 2) || version == "ff" {
+            return None;
+        }
+        if !rest.is_empty() && (version == VERSION || !rest.starts_with('-')) {
+            return None;
+        }
+        if !is_lower_hex(trace_id, 32) || !is_lower_hex(span_id, 16) || !is_lower_hex(flags, 2) {
+            return None;
+        }
+
+        let trace_id = u128::from_str_radix(trace_id, 16).ok()?;
+        let span_id = u64::from_str_radix(span_id, 16).ok()?;
+        if trace_id == 0 || span_id == 0 {
+            return None;
+        }
+
+        let tracestate = tracestate
+            .map(str::trim)
+            .filter(|state| !state.is_empty() && state.len() <= MAX_TRACESTATE_LEN)
+            .map(str::to_string);
+        Some(Self {
+            trace_id,
+            span_id,
+            parent_span_id: None,
+            flags: u8::from_str_radix(flags, 16).ok()?,
+            tracestate,
+        })
+    }
+
+    /// Context of a new sampled trace
+    pub fn new_root() -> Self {
+        Self {
+            trace_id: random_nonzero_u128(),
+            span_id: random_nonzero_u64(),
+            parent_span_id: None,
+            flags: SAMPLED_FLAG,
+            tracestate: None,
+        }
+    }
+
+    /// Context for handling a request with the given headers: a child of the caller's
+    /// span, or a new trace if the request carries no valid `traceparent`
+    pub fn for_request(traceparent: Option<&str>, tracestate: Option<&str>) -> Self {
+        traceparent
+            .and_then(|traceparent| Self::parse(traceparent, tracestate))
+            .map(|parent| parent.child())
+            .unwrap_or_else(Self::new_root)
+    }
+
+    /// Context of a new span inside this one
+    pub fn child(&self) -> Self {
+        Self {
+            trace_id: self.trace_id,
+            span_id: random_nonzero_u64(),
+            parent_span_id: Some(self.span_id),
+            flags: self.flags,
+            tracestate: self.tracestate.clone(),
+        }
+    }
+
+    /// Trace ID as 32 hex digits
+    pub fn trace_id(&self) -> String {
+        format!("{:032x}", self.trace_id)
+    }
+
+    /// Span ID as 16 hex digits
+    pub fn span_id(&self) -> String {
+        format!("{:016x}", self.span_id)
+    }
+
+    /// ID of the parent span, if the trace started elsewhere
+    pub fn parent_span_id(&self) -> Option<String> {
+        self.parent_span_id.map(|id| format!("{:016x}", id))
+    }
+
+    /// Whether the caller asked for the trace to be recorded
+    pub fn sampled(&self) -> bool {
+        self.flags & SAMPLED_FLAG != 0
+    }
+
+    /// `traceparent` header naming this span as the parent
+    pub fn traceparent(&self) -> String {
+        format!(
+            "{}-{:032x}-{:016x}-{:02x}",
+            VERSION, self.trace_id, self.span_id, self.flags
+        )
+    }
+
+    /// `tracestate` header received from the caller
+    pub fn tracestate(&self) -> Option<&str> {
+        self.tracestate.as_deref()
+    }
+
+    /// Tracing span recording this context's IDs
+    pub fn span(&self) -> tracing::Span {
+        tracing::info_span!(
+            "request",
+            trace_id = %self.trace_id(),
+            span_id = %self.span_id(),
+            parent_span_id = self.parent_span_id().unwrap_or_default(),
+        )
+    }
+
+    /// Run `future` with this context as the current one, inside its span
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        let span = self.span();
+        CURRENT.scope(self, future.instrument(span)).await
+    }
+
+    /// Context of the request being handled by the current task, if any
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(Clone::clone).ok()
+    }
+}
+
+/// `traceparent` and `tracestate` headers for an outgoing call made on behalf of the
+/// current request; empty outside of [`TraceContext::scope`]
+pub fn outgoing_headers() -> Vec<(&'static str, String)> {
+    let Some(context) = TraceContext::current() else {
+        return Vec::new();
+    };
+    let mut headers = vec![(TRACEPARENT_HEADER, context.traceparent())];
+    if let Some(tracestate) = context.tracestate() {
+        headers.push((TRACESTATE_HEADER, tracestate.to_string()));
+    }
+    headers
+}
+
+/// Add the current request's trace headers to an outgoing HTTP request
+pub fn inject(mut request: RequestBuilder) -> RequestBuilder {
+    for (name, value) in outgoing_headers() {
+        request = request.header(name, value);
+    }
+    request
+}
+
+fn is_lower_hex(field: &str, len: usize) -> bool {
+    field.len() == len
+        && field
+            .bytes()
+            .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+fn random_nonzero_u64() -> u64 {
+    fastrand::u64(1..)
+}
+
+fn random_nonzero_u128() -> u128 {
+    ((fastrand::u64(..) as u128) << 64) | fastrand::u64(1..) as u128
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn test_parse_traceparent() {
+        let context = TraceContext::parse(EXAMPLE, Some("congo=t61rcWkgMzE")).unwrap();
+        assert_eq!(context.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(context.span_id(), "00f067aa0ba902b7");
+        assert!(context.sampled());
+        assert_eq!(context.tracestate(), Some("congo=t61rcWkgMzE"));
+        assert_eq!(context.traceparent(), EXAMPLE);
+
+        // Later versions may carry more fields
+        let future = "cc-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00-extra";
+        assert!(!TraceContext::parse(future, None).unwrap().sampled());
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_traceparent() {
+        for invalid in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "00_4bf92f3577b34da6a3ce929d0e0e4736_00f067aa0ba902b7_01",
+        ] {
+            assert_eq!(TraceContext::parse(invalid, None), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_request_context_continues_trace() {
+        let context = TraceContext::for_request(Some(EXAMPLE), Some("congo=t61rcWkgMzE"));
+        assert_eq!(context.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(
+            context.parent_span_id().as_deref(),
+            Some("00f067aa0ba902b7")
+        );
+        assert_ne!(context.span_id(), "00f067aa0ba902b7");
+        assert_eq!(context.tracestate(), Some("congo=t61rcWkgMzE"));
+
+        // A malformed header starts a new trace
+        let root = TraceContext::for_request(Some("garbage"), Some("congo=t61rcWkgMzE"));
+        assert_eq!(root.parent_span_id(), None);
+        assert_eq!(root.tracestate(), None);
+        assert!(TraceContext::parse(&root.traceparent(), None).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_outgoing_headers_follow_scope() {
+        assert!(outgoing_headers().is_empty());
+
+        let context = TraceContext::for_request(Some(EXAMPLE), Some("congo=t61rcWkgMzE"));
+        let expected = vec![
+            (TRACEPARENT_HEADER, context.traceparent()),
+            (TRACESTATE_HEADER, "congo=t61rcWkgMzE".to_string()),
+        ];
+        let headers = context.scope(async { outgoing_headers() }).await;
+        assert_eq!(headers, expected);
+    }
+}
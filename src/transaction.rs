@@ -0,0 +1,303 @@
+//! Multi-key transactions
+//!
+//! A transaction is a closure that reads and writes several keys and commits
+//! atomically: either every write is applied or none is, and the reads it made are
+//! still current when the writes land.
+//!
+//! The same closure shape serves both the local ledger and the cluster:
+//!
+//! - [`crate::HyraScribeLedger::transaction`] runs the closure against a
+//!   [`Transaction`], backed by sled's transactional trees. A ledger that keeps its
+//!   history records the writes in the same transaction.
+//! - [`crate::api::DistributedApi::transaction`] runs it against a
+//!   [`DistributedTxn`], which buffers the writes and proposes them together with
+//!   the versions of the keys read as a single Raft log entry. The state machine
+//!   only applies the entry if none of those keys changed in the meantime.
+//!
+//! In both cases the closure may run several times, whenever a conflicting write is
+//! detected or a key it reads still has to be loaded, so it must not have side
+//! effects outside the transaction. Reads and writes fail with
+//! [`UnabortableTransactionError`], which the closure passes on with `?` to have
+//! itself run again. It gives up with [`abort`], whose error is returned to the
+//! caller and discards every write.
+
+use crate::error::ScribeError;
+use crate::history;
+use crate::types::{Key, TxnRead, TxnWrite, Value};
+use sled::transaction::TransactionalTree;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+
+pub use sled::transaction::{
+    abort, ConflictableTransactionError, ConflictableTransactionResult, UnabortableTransactionError,
+};
+
+/// Outcome of a transaction closure
+pub type TxnResult<A> = ConflictableTransactionResult<A, ScribeError>;
+
+/// Reads and writes of a transaction on the local ledger
+pub struct Transaction<'a> {
+    tree: &'a TransactionalTree,
+    /// History tree of the ledger, if it keeps one
+    history: Option<&'a TransactionalTree>,
+}
+
+impl<'a> Transaction<'a> {
+    pub(crate) fn new(tree: &'a TransactionalTree, history: Option<&'a TransactionalTree>) -> Self {
+        Self { tree, history }
+    }
+
+    /// Get the value of `key`, including writes made earlier in the transaction
+    pub fn get<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+    ) -> Result<Option<Value>, UnabortableTransactionError> {
+        Ok(self.tree.get(key.as_ref())?.map(|value| value.to_vec()))
+    }
+
+    /// Store `value` at `key` when the transaction commits
+    pub fn put<K, V>(&self, key: K, value: V) -> TxnResult<()>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        let (key, value) = (key.as_ref(), value.as_ref());
+        self.tree.insert(key, value)?;
+        if let Some(history) = self.history {
+            history::record(history, key, Some(value))?;
+        }
+        Ok(())
+    }
+
+    /// Delete `key` when the transaction commits
+    pub fn delete<K: AsRef<[u8]>>(&self, key: K) -> TxnResult<()> {
+        let key = key.as_ref();
+        let existed = self.tree.remove(key)?.is_some();
+        // Like a plain delete, deleting an absent key records no revision
+        if let (Some(history), true) = (self.history, existed) {
+            history::record(history, key, None)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads and writes of a transaction proposed through Raft
+///
+/// Reads are served from values loaded from the local state machine, together with
+/// the version each was read at. A key not loaded yet makes the read fail with a
+/// conflict; the key is then loaded and the closure run again. Writes are buffered
+/// until the closure returns.
+#[derive(Debug, Default)]
+pub struct DistributedTxn {
+    state: RefCell<TxnState>,
+}
+
+#[derive(Debug, Default)]
+struct TxnState {
+    /// Values loaded so far and the version they were read at
+    loaded: BTreeMap<Key, (Option<Value>, Option<u64>)>,
+    /// Keys read by the current run
+    reads: BTreeSet<Key>,
+    /// Writes of the current run; `None` deletes the key
+    writes: BTreeMap<Key, Option<Value>>,
+    /// Keys the current run read before they were loaded
+    missing: BTreeSet<Key>,
+}
+
+impl DistributedTxn {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the value of `key`, including writes made earlier in the transaction
+    pub fn get<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+    ) -> Result<Option<Value>, UnabortableTransactionError> {
+        let key = key.as_ref();
+        let mut state = self.state.borrow_mut();
+        if let Some(value) = state.writes.get(key) {
+            return Ok(value.clone());
+        }
+        let loaded = state.loaded.get(key).map(|(value, _)| value.clone());
+        match loaded {
+            Some(value) => {
+                state.reads.insert(key.to_vec());
+                Ok(value)
+            }
+            None => {
+                state.missing.insert(key.to_vec());
+                Err(UnabortableTransactionError::Conflict)
+            }
+        }
+    }
+
+    /// Store `value` at `key` when the transaction commits
+    pub fn put<K, V>(&self, key: K, value: V) -> Result<(), UnabortableTransactionError>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        let mut state = self.state.borrow_mut();
+        state
+            .writes
+            .insert(key.as_ref().to_vec(), Some(value.as_ref().to_vec()));
+        Ok(())
+    }
+
+    /// Delete `key` when the transaction commits
+    pub fn delete<K: AsRef<[u8]>>(&self, key: K) -> Result<(), UnabortableTransactionError> {
+        self.state
+            .borrow_mut()
+            .writes
+            .insert(key.as_ref().to_vec(), None);
+        Ok(())
+    }
+
+    /// Forget the reads and writes of the previous run, keeping the loaded values
+    pub(crate) fn begin(&self) {
+        let mut state = self.state.borrow_mut();
+        state.reads.clear();
+        state.writes.clear();
+        state.missing.clear();
+    }
+
+    /// Keys the last run needed that are not loaded yet
+    pub(crate) fn missing(&self) -> Vec<Key> {
+        self.state.borrow().missing.iter().cloned().collect()
+    }
+
+    /// Make `value`, read at `version`, the value of `key` for the next runs
+    pub(crate) fn load(&self, key: Key, value: Option<Value>, version: Option<u64>) {
+        self.state.borrow_mut().loaded.insert(key, (value, version));
+    }
+
+    /// Drop every loaded value, so the next run reads current values
+    pub(crate) fn reload(&self) {
+        self.state.borrow_mut().loaded.clear();
+    }
+
+    /// Reads of the last run with their versions, and its writes, in key order
+    pub(crate) fn operations(&self) -> (Vec<TxnRead>, Vec<TxnWrite>) {
+        let state = self.state.borrow();
+        let reads = state
+            .reads
+            .iter()
+            .map(|key| TxnRead {
+                key: key.clone(),
+                version: state.loaded.get(key).and_then(|(_, version)| *version),
+            })
+            .collect();
+        let writes = state
+            .writes
+            .iter()
+            .map(|(key, value)| TxnWrite {
+                key: key.clone(),
+                value: value.clone(),
+            })
+            .collect();
+        (reads, writes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Move `amount` from `from` to `to`, both holding decimal balances
+    fn transfer(txn: &DistributedTxn, from: &[u8], to: &[u8], amount: u64) -> TxnResult<()> {
+        let balance = |key: &[u8]| -> TxnResult<u64> {
+            Ok(txn
+                .get(key)?
+                .map(|value| String::from_utf8_lossy(&value).parse().unwrap())
+                .unwrap_or(0))
+        };
+        let available = balance(from)?;
+        if available < amount {
+            return abort(ScribeError::Conflict("Insufficient balance".to_string()));
+        }
+        let credited = balance(to)?;
+        txn.put(from, (available - amount).to_string())?;
+        txn.put(to, (credited + amount).to_string())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_distributed_txn_loads_keys_on_demand() {
+        let txn = DistributedTxn::new();
+
+        // The first run stops at the first key that is not loaded
+        txn.begin();
+        assert!(matches!(
+            transfer(&txn, b"alice", b"bob", 30),
+            Err(ConflictableTransactionError::Conflict)
+        ));
+        assert_eq!(txn.missing(), vec![b"alice".to_vec()]);
+        txn.load(b"alice".to_vec(), Some(b"100".to_vec()), Some(4));
+
+        txn.begin();
+        assert!(transfer(&txn, b"alice", b"bob", 30).is_err());
+        assert_eq!(txn.missing(), vec![b"bob".to_vec()]);
+        txn.load(b"bob".to_vec(), None, None);
+
+        txn.begin();
+        transfer(&txn, b"alice", b"bob", 30).unwrap();
+        assert!(txn.missing().is_empty());
+        let (reads, writes) = txn.operations();
+        assert_eq!(
+            reads,
+            vec![
+                TxnRead {
+                    key: b"alice".to_vec(),
+                    version: Some(4),
+                },
+                TxnRead {
+                    key: b"bob".to_vec(),
+                    version: None,
+                },
+            ]
+        );
+        assert_eq!(writes.len(), 2);
+        assert_eq!(writes[0].value, Some(b"70".to_vec()));
+        assert_eq!(writes[1].value, Some(b"30".to_vec()));
+
+        // Aborting discards the buffered writes of earlier runs
+        txn.begin();
+        let result = transfer(&txn, b"alice", b"bob", 500);
+        assert!(matches!(
+            result,
+            Err(ConflictableTransactionError::Abort(ScribeError::Conflict(
+                _
+            )))
+        ));
+        assert!(txn.operations().1.is_empty());
+    }
+
+    #[test]
+    fn test_distributed_txn_reads_its_own_writes() {
+        let txn = DistributedTxn::new();
+        txn.begin();
+        txn.put(b"k", b"v").unwrap();
+        assert_eq!(txn.get(b"k").unwrap(), Some(b"v".to_vec()));
+        txn.delete(b"k").unwrap();
+        assert_eq!(txn.get(b"k").unwrap(), None);
+
+        // Keys only written are not validated at commit
+        let (reads, writes) = txn.operations();
+        assert!(reads.is_empty());
+        assert_eq!(
+            writes,
+            vec![TxnWrite {
+                key: b"k".to_vec(),
+                value: None,
+            }]
+        );
+
+        // Reloading drops loaded values
+        txn.load(b"k".to_vec(), Some(b"v".to_vec()), Some(1));
+        txn.reload();
+        txn.begin();
+        assert!(txn.get(b"k").is_err());
+        assert_eq!(txn.missing(), vec![b"k".to_vec()]);
+    }
+}
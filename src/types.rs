@@ -19,6 +19,164 @@ pub type Key = Vec<u8>;
 /// Value type for storage operations
 pub type Value = Vec<u8>;
 
+/// Lease on a named lock, granted through consensus
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockLease {
+    /// Lock name
+    pub name: String,
+    /// Client-chosen identity of the holder
+    pub holder: String,
+    /// Fencing token: the Raft log index of the acquisition, increasing with every
+    /// new grant of any lock
+    pub token: u64,
+    /// Lease expiry (milliseconds since UNIX epoch)
+    pub expires_at: u64,
+}
+
+/// Epoch of a namespace that a write claims to act under
+///
+/// External coordinators advance a namespace's epoch on failover; writes carrying
+/// an older epoch are rejected, so a writer that missed the failover cannot
+/// overwrite its successor's data.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fence {
+    /// Namespace (key prefix) the epoch belongs to
+    pub namespace: String,
+    /// Epoch the writer was granted
+    pub epoch: u64,
+}
+
+/// Key read by a transaction, with the version it was read at
+///
+/// The transaction only commits if the key is still at this version when its log
+/// entry is applied.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxnRead {
+    /// Key that was read
+    pub key: Key,
+    /// Log index of the key's last modification, `None` if it was never written
+    pub version: Option<u64>,
+}
+
+/// Write of a transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxnWrite {
+    /// Key to write
+    pub key: Key,
+    /// New value, or `None` to delete the key
+    pub value: Option<Value>,
+}
+
+/// Requests and bytes one node served for an API key on one day, added to the
+/// replicated quota counters (see [`crate::quota`])
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageDelta {
+    /// ID of the API key
+    pub key_id: String,
+    /// Day the usage falls on, in days since the Unix epoch (UTC)
+    pub day: u64,
+    /// Requests served
+    pub ops: u64,
+    /// Request and response body bytes
+    pub bytes: u64,
+}
+
+/// Value stored at a session's key, as JSON
+///
+/// The state machine reads `idle_timeout_ms` when a session is touched, so the
+/// sliding expiry is computed the same way on every replica.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionRecord {
+    /// Application data of the session
+    pub data: serde_json::Value,
+    /// Time without a touch after which the session expires, in milliseconds
+    pub idle_timeout_ms: u64,
+    /// Creation time (milliseconds since UNIX epoch, from the proposer's clock)
+    pub created_at: u64,
+}
+
+/// Session held in the ledger, with its current expiry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    /// Session ID
+    pub id: String,
+    /// Application data of the session
+    pub data: serde_json::Value,
+    /// Time without a touch after which the session expires, in milliseconds
+    pub idle_timeout_ms: u64,
+    /// Creation time (milliseconds since UNIX epoch)
+    pub created_at: u64,
+    /// Expiry (milliseconds since UNIX epoch); every touch moves it forward
+    pub expires_at: u64,
+}
+
+/// Item of a durable queue, as handed to a consumer by a dequeue
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueueItem {
+    /// Queue name
+    pub queue: String,
+    /// Item ID: the Raft log index of the enqueue, so IDs follow enqueue order
+    pub id: u64,
+    /// Receipt of the latest delivery: the Raft log index of the dequeue. An ack
+    /// must present it, so a consumer whose lease ran out cannot ack a redelivery.
+    pub receipt: u64,
+    /// Item payload
+    pub payload: Value,
+    /// Number of deliveries so far, including this one
+    pub attempts: u32,
+    /// End of the current delivery's lease (milliseconds since UNIX epoch); the item
+    /// is handed out again after this unless acked
+    pub visible_at: u64,
+}
+
+/// Length of a durable queue
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueueStats {
+    /// Queue name
+    pub queue: String,
+    /// Items not yet acked, including in-flight ones
+    pub length: usize,
+    /// Items delivered to a consumer whose lease has not run out
+    pub in_flight: usize,
+}
+
+/// Named, immutable checkpoint of the ledger state, created through consensus
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LedgerTag {
+    /// Tag name
+    pub name: String,
+    /// Raft log index of the entry that created the tag; the tag captures the state
+    /// left by every earlier entry
+    pub raft_index: u64,
+    /// Version of the archival manifest on the node that proposed the tag (0 if S3
+    /// archival is not configured)
+    pub manifest_version: u64,
+    /// Merkle root (hex) of the tagged state, `None` if the ledger was empty
+    pub merkle_root: Option<String>,
+    /// Number of keys captured, including keys demoted to cold storage
+    pub key_count: usize,
+    /// Creation time (milliseconds since UNIX epoch, from the proposer's clock)
+    pub created_at: u64,
+}
+
+/// Merkle root of an archived segment, committed through consensus
+///
+/// Once anchored, a segment's root cannot change, so a value proven to be part of
+/// the segment cannot have been altered after it was archived.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SegmentAnchor {
+    /// Anchored segment
+    pub segment_id: SegmentId,
+    /// Merkle root (hex) of the segment's key-value pairs
+    pub merkle_root: String,
+    /// Number of key-value pairs in the segment
+    pub key_count: usize,
+    /// Raft log index of the entry that committed the root
+    pub raft_index: u64,
+    /// Anchoring time (milliseconds since UNIX epoch, from the proposer's clock)
+    pub anchored_at: u64,
+}
+
 /// Request types for client-server communication
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Request {
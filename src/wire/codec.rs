@@ -0,0 +1,386 @@
+//! Protocol buffers binary encoding
+//!
+//! A small encoder and decoder for the subset of the protobuf format used by the
+//! schemas in `proto/scribe/v1`: varint scalars, strings, bytes, nested messages and
+//! repeated fields. Every field is written, defaults included; on decoding, absent
+//! fields take their default and fields a node does not know are skipped, so a
+//! message gaining fields still decodes on nodes built before the change.
+
+use super::WireMessage;
+use crate::error::{Result, ScribeError};
+
+/// Wire type of varint fields
+const WIRE_VARINT: u64 = 0;
+/// Wire type of 64-bit fixed-width fields
+const WIRE_FIXED64: u64 = 1;
+/// Wire type of length-delimited fields
+const WIRE_LEN: u64 = 2;
+/// Wire type of 32-bit fixed-width fields
+const WIRE_FIXED32: u64 = 5;
+
+/// Builder of one encoded message
+#[derive(Debug, Default)]
+pub struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    /// Start an empty message
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Encoded message
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+
+    /// Append an unsigned integer field
+    pub fn uint64(&mut self, field: u32, value: u64) {
+        self.tag(field, WIRE_VARINT);
+        put_varint(&mut self.buf, value);
+    }
+
+    /// Append a boolean field
+    pub fn bool(&mut self, field: u32, value: bool) {
+        self.uint64(field, value as u64);
+    }
+
+    /// Append a bytes field
+    pub fn bytes(&mut self, field: u32, value: &[u8]) {
+        self.tag(field, WIRE_LEN);
+        put_varint(&mut self.buf, value.len() as u64);
+        self.buf.extend_from_slice(value);
+    }
+
+    /// Append a string field
+    pub fn string(&mut self, field: u32, value: &str) {
+        self.bytes(field, value.as_bytes());
+    }
+
+    /// Append a nested message field
+    pub fn message<M: WireMessage>(&mut self, field: u32, value: &M) {
+        self.bytes(field, &value.to_bytes());
+    }
+
+    /// Append a nested message field whose fields are written by `build`
+    pub fn nested(&mut self, field: u32, build: impl FnOnce(&mut Encoder)) {
+        let mut nested = Encoder::new();
+        build(&mut nested);
+        self.bytes(field, &nested.buf);
+    }
+
+    fn tag(&mut self, field: u32, wire_type: u64) {
+        put_varint(&mut self.buf, ((field as u64) << 3) | wire_type);
+    }
+}
+
+/// Raw value of one decoded field
+#[derive(Debug, Clone, Copy)]
+enum Value<'a> {
+    Varint(u64),
+    Fixed(u64),
+    Bytes(&'a [u8]),
+}
+
+/// Fields of a decoded message, looked up by field number
+///
+/// As in protobuf, the last occurrence of a singular field wins.
+#[derive(Debug, Clone)]
+pub struct Fields<'a> {
+    fields: Vec<(u32, Value<'a>)>,
+}
+
+impl<'a> Fields<'a> {
+    /// Split an encoded message into its fields
+    pub fn parse(mut buf: &'a [u8]) -> Result<Self> {
+        let mut fields = Vec::new();
+        while !buf.is_empty() {
+            let key = take_varint(&mut buf)?;
+            let field = u32::try_from(key >> 3)
+                .ok()
+                .filter(|field| *field != 0)
+                .ok_or_else(|| malformed(format!("invalid field number {}", key >> 3)))?;
+            let value = match key & 0x7 {
+                WIRE_VARINT => Value::Varint(take_varint(&mut buf)?),
+                WIRE_FIXED64 => Value::Fixed(u64::from_le_bytes(take_array(&mut buf)?)),
+                WIRE_LEN => {
+                    let len = usize::try_from(take_varint(&mut buf)?)
+                        .map_err(|_| malformed("length overflows".to_string()))?;
+                    Value::Bytes(take(&mut buf, len)?)
+                }
+                WIRE_FIXED32 => Value::Fixed(u32::from_le_bytes(take_array(&mut buf)?) as u64),
+                other => return Err(malformed(format!("unsupported wire type {}", other))),
+            };
+            fields.push((field, value));
+        }
+        Ok(Self { fields })
+    }
+
+    /// Whether `field` is present
+    pub fn has(&self, field: u32) -> bool {
+        self.fields.iter().any(|(number, _)| *number == field)
+    }
+
+    /// Unsigned integer field, 0 if absent
+    pub fn uint64(&self, field: u32) -> Result<u64> {
+        Ok(self.optional_uint64(field)?.unwrap_or_default())
+    }
+
+    /// Unsigned integer field that must fit in 32 bits, 0 if absent
+    pub fn uint32(&self, field: u32) -> Result<u32> {
+        u32::try_from(self.uint64(field)?)
+            .map_err(|_| malformed(format!("field {} overflows 32 bits", field)))
+    }
+
+    /// Unsigned integer field holding a count or size, 0 if absent
+    pub fn usize(&self, field: u32) -> Result<usize> {
+        usize::try_from(self.uint64(field)?)
+            .map_err(|_| malformed(format!("field {} overflows usize", field)))
+    }
+
+    /// Boolean field, false if absent
+    pub fn bool(&self, field: u32) -> Result<bool> {
+        Ok(self.uint64(field)? != 0)
+    }
+
+    /// Bytes field, empty if absent
+    pub fn bytes(&self, field: u32) -> Result<Vec<u8>> {
+        Ok(self.optional_bytes(field)?.unwrap_or_default())
+    }
+
+    /// String field, empty if absent
+    pub fn string(&self, field: u32) -> Result<String> {
+        Ok(self.optional_string(field)?.unwrap_or_default())
+    }
+
+    /// Unsigned integer field marked `optional`
+    pub fn optional_uint64(&self, field: u32) -> Result<Option<u64>> {
+        self.last(field)
+            .map(|value| match value {
+                Value::Varint(value) | Value::Fixed(value) => Ok(value),
+                Value::Bytes(_) => Err(wrong_type(field)),
+            })
+            .transpose()
+    }
+
+    /// Bytes field marked `optional`
+    pub fn optional_bytes(&self, field: u32) -> Result<Option<Vec<u8>>> {
+        self.last(field)
+            .map(|value| as_bytes(field, value).map(<[u8]>::to_vec))
+            .transpose()
+    }
+
+    /// String field marked `optional`
+    pub fn optional_string(&self, field: u32) -> Result<Option<String>> {
+        self.last(field)
+            .map(|value| as_string(field, value))
+            .transpose()
+    }
+
+    /// Nested message field, `None` if absent
+    pub fn message<M: WireMessage>(&self, field: u32) -> Result<Option<M>> {
+        self.last(field)
+            .map(|value| M::from_bytes(as_bytes(field, value)?))
+            .transpose()
+    }
+
+    /// Nested message field that must be present
+    pub fn required<M: WireMessage>(&self, field: u32) -> Result<M> {
+        self.message(field)?
+            .ok_or_else(|| malformed(format!("missing {} in field {}", M::KIND, field)))
+    }
+
+    /// Repeated bytes field
+    pub fn repeated_bytes(&self, field: u32) -> Result<Vec<Vec<u8>>> {
+        self.all(field)
+            .map(|value| as_bytes(field, value).map(<[u8]>::to_vec))
+            .collect()
+    }
+
+    /// Repeated string field
+    pub fn repeated_string(&self, field: u32) -> Result<Vec<String>> {
+        self.all(field)
+            .map(|value| as_string(field, value))
+            .collect()
+    }
+
+    /// Repeated message field
+    pub fn repeated_message<M: WireMessage>(&self, field: u32) -> Result<Vec<M>> {
+        self.all(field)
+            .map(|value| M::from_bytes(as_bytes(field, value)?))
+            .collect()
+    }
+
+    /// Repeated boolean field, packed or not
+    pub fn repeated_bool(&self, field: u32) -> Result<Vec<bool>> {
+        let mut values = Vec::new();
+        for value in self.all(field) {
+            match value {
+                Value::Varint(value) => values.push(value != 0),
+                Value::Bytes(mut packed) => {
+                    while !packed.is_empty() {
+                        values.push(take_varint(&mut packed)? != 0);
+                    }
+                }
+                Value::Fixed(_) => return Err(wrong_type(field)),
+            }
+        }
+        Ok(values)
+    }
+
+    /// Case of a `oneof` whose cases are numbered 1 to `cases`, with its fields
+    ///
+    /// `None` if no known case is set, for example because the message was written by
+    /// a newer node using a case added since.
+    pub fn oneof(&self, cases: u32) -> Result<Option<(u32, Fields<'a>)>> {
+        self.fields
+            .iter()
+            .rev()
+            .find(|(number, _)| (1..=cases).contains(number))
+            .map(|(number, value)| Ok((*number, Fields::parse(as_bytes(*number, *value)?)?)))
+            .transpose()
+    }
+
+    fn last(&self, field: u32) -> Option<Value<'a>> {
+        self.all(field).last()
+    }
+
+    fn all(&self, field: u32) -> impl Iterator<Item = Value<'a>> + '_ {
+        self.fields
+            .iter()
+            .filter(move |(number, _)| *number == field)
+            .map(|(_, value)| *value)
+    }
+}
+
+/// Error for a `oneof` of `kind` with no case this node knows
+pub fn unknown_case(kind: &str) -> ScribeError {
+    malformed(format!("{} has no case known to this version", kind))
+}
+
+/// Append `value` as a base-128 varint
+pub fn put_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn take_varint(buf: &mut &[u8]) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = buf
+            .split_first()
+            .ok_or_else(|| malformed("truncated varint".to_string()))?;
+        *buf = rest;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(malformed("varint longer than 10 bytes".to_string()))
+}
+
+fn take<'a>(buf: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if buf.len() < len {
+        return Err(malformed(format!(
+            "field of {} bytes truncated at {}",
+            len,
+            buf.len()
+        )));
+    }
+    let (value, rest) = buf.split_at(len);
+    *buf = rest;
+    Ok(value)
+}
+
+fn take_array<const N: usize>(buf: &mut &[u8]) -> Result<[u8; N]> {
+    Ok(take(buf, N)?.try_into().expect("slice has N bytes"))
+}
+
+fn as_bytes<'a>(field: u32, value: Value<'a>) -> Result<&'a [u8]> {
+    match value {
+        Value::Bytes(bytes) => Ok(bytes),
+        _ => Err(wrong_type(field)),
+    }
+}
+
+fn as_string(field: u32, value: Value<'_>) -> Result<String> {
+    String::from_utf8(as_bytes(field, value)?.to_vec())
+        .map_err(|_| malformed(format!("field {} is not valid UTF-8", field)))
+}
+
+fn wrong_type(field: u32) -> ScribeError {
+    malformed(format!("field {} has an unexpected wire type", field))
+}
+
+fn malformed(message: String) -> ScribeError {
+    ScribeError::Serialization(format!("Malformed protobuf message: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_varint_round_trip() {
+        for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let mut buf = Vec::new();
+            put_varint(&mut buf, value);
+            let mut slice = buf.as_slice();
+            assert_eq!(take_varint(&mut slice).unwrap(), value);
+            assert!(slice.is_empty());
+        }
+
+        let mut buf = Vec::new();
+        put_varint(&mut buf, 300);
+        assert_eq!(buf, vec![0xac, 0x02]);
+        assert!(take_varint(&mut &[0x80u8][..]).is_err());
+        assert!(take_varint(&mut &[0xffu8; 11][..]).is_err());
+    }
+
+    #[test]
+    fn test_fields_defaults_and_unknown_fields() {
+        let mut encoder = Encoder::new();
+        encoder.uint64(1, 7);
+        encoder.string(2, "first");
+        encoder.string(2, "second");
+        encoder.bool(3, true);
+        // Fields from a newer schema, of every wire type
+        encoder.uint64(90, 1);
+        encoder.bytes(91, b"future");
+        let mut bytes = encoder.into_bytes();
+        put_varint(&mut bytes, (92 << 3) | WIRE_FIXED64);
+        bytes.extend_from_slice(&1u64.to_le_bytes());
+        put_varint(&mut bytes, (93 << 3) | WIRE_FIXED32);
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+
+        let fields = Fields::parse(&bytes).unwrap();
+        assert_eq!(fields.uint64(1).unwrap(), 7);
+        assert_eq!(fields.string(2).unwrap(), "second");
+        assert_eq!(fields.repeated_string(2).unwrap(), vec!["first", "second"]);
+        assert!(fields.bool(3).unwrap());
+        assert_eq!(fields.uint64(4).unwrap(), 0);
+        assert_eq!(fields.optional_uint64(4).unwrap(), None);
+        assert!(fields.bytes(5).unwrap().is_empty());
+        assert!(fields.uint64(2).is_err());
+
+        assert!(Fields::parse(&[0x0a, 0x05, b'a']).is_err());
+        assert!(Fields::parse(&[0x03]).is_err());
+    }
+
+    #[test]
+    fn test_packed_repeated_bool() {
+        let mut encoder = Encoder::new();
+        encoder.bytes(1, &[1, 0, 1]);
+        encoder.bool(1, false);
+        let bytes = encoder.into_bytes();
+        let fields = Fields::parse(&bytes).unwrap();
+        assert_eq!(
+            fields.repeated_bool(1).unwrap(),
+            vec![true, false, true, false]
+        );
+    }
+}
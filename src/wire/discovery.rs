@@ -0,0 +1,196 @@
+//! Schema of discovery messages
+//!
+//! See `proto/scribe/v1/discovery.proto`. Socket addresses travel as
+//! `host:port` strings.
+
+use super::codec::unknown_case;
+use super::{Encoder, Fields, WireMessage};
+use crate::discovery::{DiscoveryMessage, PeerInfo};
+use crate::error::{Result, ScribeError};
+use std::net::SocketAddr;
+
+impl WireMessage for PeerInfo {
+    const KIND: &'static str = "scribe.v1.PeerInfo";
+
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.uint64(1, self.node_id);
+        encoder.string(2, &self.raft_addr.to_string());
+        encoder.string(3, &self.client_addr.to_string());
+    }
+
+    fn decode(fields: &Fields<'_>) -> Result<Self> {
+        Ok(Self {
+            node_id: fields.uint64(1)?,
+            raft_addr: socket_addr(fields, 2)?,
+            client_addr: socket_addr(fields, 3)?,
+        })
+    }
+}
+
+impl WireMessage for DiscoveryMessage {
+    const KIND: &'static str = "scribe.v1.DiscoveryMessage";
+
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            DiscoveryMessage::Announce {
+                node_id,
+                raft_addr,
+                client_addr,
+                cluster_secret,
+                join_token,
+            } => encoder.nested(1, |announce| {
+                announce.uint64(1, *node_id);
+                announce.string(2, &raft_addr.to_string());
+                announce.string(3, &client_addr.to_string());
+                if let Some(secret) = cluster_secret {
+                    announce.string(4, secret);
+                }
+                if let Some(token) = join_token {
+                    announce.string(5, token);
+                }
+            }),
+            DiscoveryMessage::Heartbeat {
+                node_id,
+                cluster_secret,
+                sent_at_ms,
+            } => encoder.nested(2, |heartbeat| {
+                heartbeat.uint64(1, *node_id);
+                if let Some(secret) = cluster_secret {
+                    heartbeat.string(2, secret);
+                }
+                if let Some(sent_at_ms) = sent_at_ms {
+                    heartbeat.uint64(3, *sent_at_ms);
+                }
+            }),
+            DiscoveryMessage::PeerListRequest {
+                node_id,
+                cluster_secret,
+            } => encoder.nested(3, |request| {
+                request.uint64(1, *node_id);
+                if let Some(secret) = cluster_secret {
+                    request.string(2, secret);
+                }
+            }),
+            DiscoveryMessage::PeerListResponse { peers } => encoder.nested(4, |response| {
+                for peer in peers {
+                    response.message(1, peer);
+                }
+            }),
+        }
+    }
+
+    fn decode(fields: &Fields<'_>) -> Result<Self> {
+        match fields.oneof(4)? {
+            Some((1, announce)) => Ok(DiscoveryMessage::Announce {
+                node_id: announce.uint64(1)?,
+                raft_addr: socket_addr(&announce, 2)?,
+                client_addr: socket_addr(&announce, 3)?,
+                cluster_secret: announce.optional_string(4)?,
+                join_token: announce.optional_string(5)?,
+            }),
+            Some((2, heartbeat)) => Ok(DiscoveryMessage::Heartbeat {
+                node_id: heartbeat.uint64(1)?,
+                cluster_secret: heartbeat.optional_string(2)?,
+                sent_at_ms: heartbeat.optional_uint64(3)?,
+            }),
+            Some((3, request)) => Ok(DiscoveryMessage::PeerListRequest {
+                node_id: request.uint64(1)?,
+                cluster_secret: request.optional_string(2)?,
+            }),
+            Some((_, response)) => Ok(DiscoveryMessage::PeerListResponse {
+                peers: response.repeated_message(1)?,
+            }),
+            None => Err(unknown_case(Self::KIND)),
+        }
+    }
+}
+
+fn socket_addr(fields: &Fields<'_>, field: u32) -> Result<SocketAddr> {
+    let addr = fields.string(field)?;
+    addr.parse().map_err(|e| {
+        ScribeError::Serialization(format!("Invalid socket address {:?}: {}", addr, e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wire::{open, seal};
+
+    fn peer(node_id: u64) -> PeerInfo {
+        PeerInfo {
+            node_id,
+            raft_addr: format!("10.0.0.{}:9001", node_id).parse().unwrap(),
+            client_addr: "[::1]:8001".parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_discovery_messages_round_trip() {
+        let messages = [
+            DiscoveryMessage::Announce {
+                node_id: 1,
+                raft_addr: peer(1).raft_addr,
+                client_addr: peer(1).client_addr,
+                cluster_secret: Some(String::new()),
+                join_token: None,
+            },
+            DiscoveryMessage::Heartbeat {
+                node_id: 2,
+                cluster_secret: None,
+                sent_at_ms: Some(1_700_000_000_000),
+            },
+            DiscoveryMessage::Heartbeat {
+                node_id: 2,
+                cluster_secret: None,
+                sent_at_ms: None,
+            },
+            DiscoveryMessage::PeerListRequest {
+                node_id: 3,
+                cluster_secret: Some("secret".to_string()),
+            },
+            DiscoveryMessage::PeerListResponse {
+                peers: vec![peer(1), peer(2)],
+            },
+            DiscoveryMessage::PeerListResponse { peers: Vec::new() },
+        ];
+        for message in messages {
+            assert_eq!(open::<DiscoveryMessage>(&seal(&message)).unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn test_announce_from_newer_version() {
+        // An announce carrying a field added later, next to a message kind added later
+        let mut encoder = Encoder::new();
+        encoder.nested(1, |announce| {
+            announce.uint64(1, 5);
+            announce.string(2, "127.0.0.1:9005");
+            announce.string(3, "127.0.0.1:8005");
+            announce.string(6, "zone-b");
+        });
+        let message = DiscoveryMessage::from_bytes(&encoder.into_bytes()).unwrap();
+        assert_eq!(
+            message,
+            DiscoveryMessage::Announce {
+                node_id: 5,
+                raft_addr: "127.0.0.1:9005".parse().unwrap(),
+                client_addr: "127.0.0.1:8005".parse().unwrap(),
+                cluster_secret: None,
+                join_token: None,
+            }
+        );
+
+        let mut encoder = Encoder::new();
+        encoder.nested(5, |leave| leave.uint64(1, 5));
+        assert!(DiscoveryMessage::from_bytes(&encoder.into_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_invalid_address_is_rejected() {
+        let mut encoder = Encoder::new();
+        encoder.uint64(1, 1);
+        encoder.string(2, "not-an-address");
+        assert!(PeerInfo::from_bytes(&encoder.into_bytes()).is_err());
+    }
+}
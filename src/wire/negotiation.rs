@@ -0,0 +1,158 @@
+//! Wire version negotiation between cluster members
+//!
+//! Nodes learn each other's wire version from the envelopes of their discovery
+//! messages and from a [`VersionAdvert`] trailing every Raft RPC request and
+//! response (see `crate::consensus::network`). A member that was never heard from,
+//! or that answers Raft RPCs without an advert, predates versioning and counts as
+//! [`LEGACY_WIRE_VERSION`].
+//!
+//! The leader only proposes a log entry once every member reaches the wire version
+//! that entry needs (see `AppRequest::required_version`), so a mixed-version
+//! cluster refuses the new operations instead of replicating entries that older
+//! members cannot decode.
+
+use super::{Encoder, Fields, WireMessage, WIRE_VERSION};
+use crate::error::Result;
+use crate::types::NodeId;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+use tracing::info;
+
+/// Wire version of nodes from before version negotiation
+pub const LEGACY_WIRE_VERSION: u32 = 0;
+
+/// A node announcing itself in the trailer of a Raft RPC response
+///
+/// The sender's wire version is the version of the envelope the advert travels in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionAdvert {
+    /// Node sending the advert
+    pub node_id: NodeId,
+}
+
+impl WireMessage for VersionAdvert {
+    const KIND: &'static str = "scribe.v1.VersionAdvert";
+
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.uint64(1, self.node_id);
+    }
+
+    fn decode(fields: &Fields<'_>) -> Result<Self> {
+        Ok(Self {
+            node_id: fields.uint64(1)?,
+        })
+    }
+}
+
+/// Wire versions advertised by other nodes
+///
+/// Cheap to clone; clones share the same table.
+#[derive(Debug, Clone, Default)]
+pub struct PeerVersions {
+    versions: Arc<RwLock<HashMap<NodeId, u32>>>,
+}
+
+impl PeerVersions {
+    /// Create an empty table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the wire version `node_id` last advertised
+    pub fn record(&self, node_id: NodeId, version: u32) {
+        let previous = self.versions.write().unwrap().insert(node_id, version);
+        if let Some(previous) = previous.filter(|previous| *previous != version) {
+            info!(
+                "Node {} now speaks wire version {} (was {})",
+                node_id, version, previous
+            );
+        }
+    }
+
+    /// Wire version `node_id` last advertised, if it was heard from
+    pub fn get(&self, node_id: NodeId) -> Option<u32> {
+        self.versions.read().unwrap().get(&node_id).copied()
+    }
+
+    /// Every recorded version, by node
+    pub fn snapshot(&self) -> BTreeMap<NodeId, u32> {
+        self.versions
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(node_id, version)| (*node_id, *version))
+            .collect()
+    }
+
+    /// Wire version of `node_id` as seen from `local`
+    ///
+    /// `local` speaks this build's version; nodes never heard from are legacy.
+    pub fn version_of(&self, local: NodeId, node_id: NodeId) -> u32 {
+        if node_id == local {
+            WIRE_VERSION
+        } else {
+            self.get(node_id).unwrap_or(LEGACY_WIRE_VERSION)
+        }
+    }
+
+    /// Highest wire version every one of `members` speaks
+    pub fn cluster_version(&self, local: NodeId, members: impl IntoIterator<Item = NodeId>) -> u32 {
+        members
+            .into_iter()
+            .map(|node_id| self.version_of(local, node_id))
+            .min()
+            .unwrap_or(WIRE_VERSION)
+    }
+
+    /// Members of `members` below wire version `required`, in ascending order
+    pub fn lagging(
+        &self,
+        local: NodeId,
+        members: impl IntoIterator<Item = NodeId>,
+        required: u32,
+    ) -> Vec<NodeId> {
+        let mut lagging: Vec<NodeId> = members
+            .into_iter()
+            .filter(|node_id| self.version_of(local, *node_id) < required)
+            .collect();
+        lagging.sort_unstable();
+        lagging
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wire::{open, seal, Envelope};
+
+    #[test]
+    fn test_cluster_version_is_lowest_member() {
+        let versions = PeerVersions::new();
+        assert_eq!(versions.cluster_version(1, [1]), WIRE_VERSION);
+        assert_eq!(versions.cluster_version(1, []), WIRE_VERSION);
+
+        // Members not heard from yet count as legacy
+        assert_eq!(versions.cluster_version(1, [1, 2, 3]), LEGACY_WIRE_VERSION);
+        assert_eq!(versions.lagging(1, [3, 1, 2], 1), vec![2, 3]);
+
+        versions.record(2, WIRE_VERSION);
+        versions.record(3, WIRE_VERSION + 1);
+        assert_eq!(versions.cluster_version(1, [1, 2, 3]), WIRE_VERSION);
+        assert!(versions.lagging(1, [1, 2, 3], WIRE_VERSION).is_empty());
+        assert_eq!(versions.lagging(1, [1, 2, 3], WIRE_VERSION + 1), vec![1, 2]);
+
+        // A downgraded node is tracked at its new version
+        let shared = versions.clone();
+        shared.record(3, LEGACY_WIRE_VERSION);
+        assert_eq!(versions.get(3), Some(LEGACY_WIRE_VERSION));
+        assert_eq!(versions.snapshot().len(), 2);
+    }
+
+    #[test]
+    fn test_version_advert_round_trip() {
+        let advert = VersionAdvert { node_id: 7 };
+        let sealed = seal(&advert);
+        assert_eq!(open::<VersionAdvert>(&sealed).unwrap(), advert);
+        assert_eq!(Envelope::from_bytes(&sealed).unwrap().version, WIRE_VERSION);
+    }
+}
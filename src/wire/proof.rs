@@ -0,0 +1,480 @@
+//! Schema of Merkle proofs
+//!
+//! See `proto/scribe/v1/proof.proto`.
+//!
+//! A [`ProofBundle`] sealed with [`crate::wire::seal`] is the file handed to
+//! auditors: it carries the key, its value, the path to the root and, if the
+//! exporting node holds a signing key, its signature of the root, so
+//! [`ProofBundle::check`] needs nothing from the cluster.
+//!
+//! For auditors without a protobuf decoder, [`ProofBundle::to_json`] writes the same
+//! bundle as JSON with hex-encoded byte strings, versioned by its `version` field.
+//! A leaf hash is SHA-256 of `"leaf:" || key || ":" || value`, and each step up the
+//! path SHA-256 of `"internal:" || left || ":" || right`, where the sibling is the
+//! left child if its direction is `true`.
+
+use super::{is_envelope, open, Encoder, Fields, WireMessage};
+use crate::backup::BackupSigner;
+use crate::crypto::{MerkleProof, MerkleTree};
+use crate::error::{Result, ScribeError};
+use ring::signature::{UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Prefix of the message a root signature is taken over, so that it cannot be
+/// mistaken for a signature of anything else
+const ROOT_SIGNATURE_CONTEXT: &[u8] = b"scribe.v1.ProofBundle.root_hash\0";
+
+/// Version of the JSON form of proof bundles written by this node
+pub const PROOF_JSON_VERSION: u32 = 1;
+
+/// Ed25519 signature of a root hash by the node that exported the proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootSignature {
+    /// Public key of the signer
+    pub public_key: Vec<u8>,
+    /// Detached signature of the root hash
+    pub signature: Vec<u8>,
+}
+
+/// A Merkle proof together with the root it proves against
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofBundle {
+    /// Inclusion proof of one key
+    pub proof: MerkleProof,
+    /// Root hash of the tree the proof was taken from
+    pub root_hash: Vec<u8>,
+    /// Signature of `root_hash`, if the exporting node signs its roots
+    pub signature: Option<RootSignature>,
+}
+
+/// Outcome of checking the signature of a bundle's root
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureStatus {
+    /// The bundle carries no signature
+    Unsigned,
+    /// Signed by one of the trusted keys
+    Trusted,
+    /// Signed by the key in the bundle, which is not among the trusted keys
+    Untrusted,
+    /// The signature does not match the root
+    Invalid,
+}
+
+/// Result of checking a bundle, meant to be printed for an auditor
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProofVerdict {
+    /// Whether the bundle proves its value, and its root is signed by a trusted
+    /// key if any were given
+    pub valid: bool,
+    /// The key (lossy UTF-8)
+    pub key: String,
+    /// The key, hex-encoded
+    pub key_hex: String,
+    /// Length of the value in bytes
+    pub value_len: usize,
+    /// SHA-256 of the value, hex-encoded
+    pub value_sha256: String,
+    /// Root hash, hex-encoded
+    pub root_hash: String,
+    /// Number of sibling hashes between the value and the root
+    pub path_length: usize,
+    /// Whether hashing the value up the path yields the root
+    pub inclusion: bool,
+    /// Outcome of the root signature check
+    pub signature: SignatureStatus,
+    /// Public key of the signer, hex-encoded
+    pub signer: Option<String>,
+    /// Why the bundle is not valid; empty if it is
+    pub problems: Vec<String>,
+}
+
+impl ProofBundle {
+    /// Bundle `proof` with the root it was taken from
+    pub fn new(proof: MerkleProof, root_hash: Vec<u8>) -> Self {
+        Self {
+            proof,
+            root_hash,
+            signature: None,
+        }
+    }
+
+    /// Sign the root with `signer`
+    pub fn signed(mut self, signer: &BackupSigner) -> Self {
+        self.signature = Some(RootSignature {
+            public_key: signer.public_key().to_vec(),
+            signature: signer.sign_bytes(&root_signature_message(&self.root_hash)),
+        });
+        self
+    }
+
+    /// Whether the proof holds against the bundled root
+    pub fn verify(&self) -> bool {
+        MerkleTree::verify_proof(&self.proof, &self.root_hash)
+    }
+
+    /// Check the proof and the root signature offline
+    ///
+    /// `trusted_keys` are hex-encoded Ed25519 public keys. If any are given, the
+    /// root must be signed by one of them; otherwise a signature is checked against
+    /// the key in the bundle, which shows the bundle is intact but not who made it.
+    pub fn check(&self, trusted_keys: &[String]) -> ProofVerdict {
+        let inclusion = self.verify();
+        let signature = match &self.signature {
+            None => SignatureStatus::Unsigned,
+            Some(signed) => {
+                let matches = UnparsedPublicKey::new(&ED25519, &signed.public_key)
+                    .verify(&root_signature_message(&self.root_hash), &signed.signature)
+                    .is_ok();
+                let signer = hex::encode(&signed.public_key);
+                if !matches {
+                    SignatureStatus::Invalid
+                } else if trusted_keys
+                    .iter()
+                    .any(|key| key.trim().eq_ignore_ascii_case(&signer))
+                {
+                    SignatureStatus::Trusted
+                } else {
+                    SignatureStatus::Untrusted
+                }
+            }
+        };
+
+        let mut problems = Vec::new();
+        if !inclusion {
+            problems.push("The value does not hash up to the root".to_string());
+        }
+        match signature {
+            SignatureStatus::Invalid => {
+                problems.push("The root signature does not match the root".to_string())
+            }
+            SignatureStatus::Unsigned if !trusted_keys.is_empty() => {
+                problems.push("The root is not signed".to_string())
+            }
+            SignatureStatus::Untrusted if !trusted_keys.is_empty() => {
+                problems.push("The root is not signed by a trusted key".to_string())
+            }
+            _ => {}
+        }
+
+        ProofVerdict {
+            valid: problems.is_empty(),
+            key: String::from_utf8_lossy(&self.proof.key).into_owned(),
+            key_hex: hex::encode(&self.proof.key),
+            value_len: self.proof.value.len(),
+            value_sha256: hex::encode(Sha256::digest(&self.proof.value)),
+            root_hash: hex::encode(&self.root_hash),
+            path_length: self.proof.siblings.len(),
+            inclusion,
+            signature,
+            signer: self
+                .signature
+                .as_ref()
+                .map(|signed| hex::encode(&signed.public_key)),
+            problems,
+        }
+    }
+}
+
+/// JSON form of a [`ProofBundle`]; byte strings are hex-encoded
+#[derive(Serialize, Deserialize)]
+struct JsonBundle {
+    kind: String,
+    version: u32,
+    key: String,
+    value: String,
+    siblings: Vec<String>,
+    directions: Vec<bool>,
+    root_hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<JsonSignature>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonSignature {
+    public_key: String,
+    signature: String,
+}
+
+impl ProofBundle {
+    /// Encode the bundle as JSON, for auditors without a protobuf decoder
+    pub fn to_json(&self) -> String {
+        let bundle = JsonBundle {
+            kind: Self::KIND.to_string(),
+            version: PROOF_JSON_VERSION,
+            key: hex::encode(&self.proof.key),
+            value: hex::encode(&self.proof.value),
+            siblings: self.proof.siblings.iter().map(hex::encode).collect(),
+            directions: self.proof.directions.clone(),
+            root_hash: hex::encode(&self.root_hash),
+            signature: self.signature.as_ref().map(|signed| JsonSignature {
+                public_key: hex::encode(&signed.public_key),
+                signature: hex::encode(&signed.signature),
+            }),
+        };
+        serde_json::to_string_pretty(&bundle).expect("proof bundles always encode as JSON")
+    }
+
+    /// Decode a bundle written by [`ProofBundle::to_json`]
+    pub fn from_json(json: &[u8]) -> Result<Self> {
+        let bundle: JsonBundle = serde_json::from_slice(json)
+            .map_err(|e| ScribeError::Serialization(format!("Invalid proof JSON: {}", e)))?;
+        if bundle.kind != Self::KIND {
+            return Err(ScribeError::Serialization(format!(
+                "Expected a {} document, got {}",
+                Self::KIND,
+                bundle.kind
+            )));
+        }
+        if bundle.version > PROOF_JSON_VERSION {
+            return Err(ScribeError::Serialization(format!(
+                "Proof JSON has version {}, this node reads up to {}",
+                bundle.version, PROOF_JSON_VERSION
+            )));
+        }
+        if bundle.siblings.len() != bundle.directions.len() {
+            return Err(ScribeError::Serialization(format!(
+                "Merkle proof has {} siblings but {} directions",
+                bundle.siblings.len(),
+                bundle.directions.len()
+            )));
+        }
+
+        Ok(Self {
+            proof: MerkleProof {
+                key: decode_hex("key", &bundle.key)?,
+                value: decode_hex("value", &bundle.value)?,
+                siblings: bundle
+                    .siblings
+                    .iter()
+                    .map(|sibling| decode_hex("sibling", sibling))
+                    .collect::<Result<_>>()?,
+                directions: bundle.directions,
+            },
+            root_hash: decode_hex("root_hash", &bundle.root_hash)?,
+            signature: bundle
+                .signature
+                .map(|signed| -> Result<RootSignature> {
+                    Ok(RootSignature {
+                        public_key: decode_hex("public_key", &signed.public_key)?,
+                        signature: decode_hex("signature", &signed.signature)?,
+                    })
+                })
+                .transpose()?,
+        })
+    }
+
+    /// Decode an exported bundle, sealed with [`crate::wire::seal`] or as JSON
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if is_envelope(bytes) {
+            open(bytes)
+        } else {
+            Self::from_json(bytes)
+        }
+    }
+}
+
+fn decode_hex(field: &str, value: &str) -> Result<Vec<u8>> {
+    hex::decode(value)
+        .map_err(|e| ScribeError::Serialization(format!("Invalid hex in proof {}: {}", field, e)))
+}
+
+/// Message a root signature is taken over
+fn root_signature_message(root_hash: &[u8]) -> Vec<u8> {
+    [ROOT_SIGNATURE_CONTEXT, root_hash].concat()
+}
+
+impl WireMessage for MerkleProof {
+    const KIND: &'static str = "scribe.v1.MerkleProof";
+
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.bytes(1, &self.key);
+        encoder.bytes(2, &self.value);
+        for sibling in &self.siblings {
+            encoder.bytes(3, sibling);
+        }
+        for direction in &self.directions {
+            encoder.bool(4, *direction);
+        }
+    }
+
+    fn decode(fields: &Fields<'_>) -> Result<Self> {
+        let proof = Self {
+            key: fields.bytes(1)?,
+            value: fields.bytes(2)?,
+            siblings: fields.repeated_bytes(3)?,
+            directions: fields.repeated_bool(4)?,
+        };
+        if proof.siblings.len() != proof.directions.len() {
+            return Err(ScribeError::Serialization(format!(
+                "Merkle proof has {} siblings but {} directions",
+                proof.siblings.len(),
+                proof.directions.len()
+            )));
+        }
+        Ok(proof)
+    }
+}
+
+impl WireMessage for RootSignature {
+    const KIND: &'static str = "scribe.v1.RootSignature";
+
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.bytes(1, &self.public_key);
+        encoder.bytes(2, &self.signature);
+    }
+
+    fn decode(fields: &Fields<'_>) -> Result<Self> {
+        Ok(Self {
+            public_key: fields.bytes(1)?,
+            signature: fields.bytes(2)?,
+        })
+    }
+}
+
+impl WireMessage for ProofBundle {
+    const KIND: &'static str = "scribe.v1.ProofBundle";
+
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.message(1, &self.proof);
+        encoder.bytes(2, &self.root_hash);
+        if let Some(signature) = &self.signature {
+            encoder.message(3, signature);
+        }
+    }
+
+    fn decode(fields: &Fields<'_>) -> Result<Self> {
+        Ok(Self {
+            proof: fields.required(1)?,
+            root_hash: fields.bytes(2)?,
+            signature: fields.message(3)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wire::{open, seal};
+
+    #[test]
+    fn test_proof_bundle_round_trip() {
+        let tree =
+            MerkleTree::from_pairs((0..5u8).map(|i| (vec![b'k', i], vec![b'v', i])).collect());
+        let bundle = ProofBundle::new(
+            tree.get_proof(&[b'k', 3]).unwrap(),
+            tree.root_hash().unwrap(),
+        );
+        assert!(!bundle.proof.siblings.is_empty());
+
+        let decoded = open::<ProofBundle>(&seal(&bundle)).unwrap();
+        assert_eq!(decoded, bundle);
+        assert!(decoded.verify());
+
+        let mut tampered = decoded;
+        tampered.proof.value = b"forged".to_vec();
+        assert!(!tampered.verify());
+    }
+
+    #[test]
+    fn test_check_signed_root() {
+        let tree = MerkleTree::from_pairs(vec![
+            (b"a".to_vec(), b"1".to_vec()),
+            (b"b".to_vec(), b"2".to_vec()),
+        ]);
+        let (signer, _) = BackupSigner::generate().unwrap();
+        let bundle = ProofBundle::new(tree.get_proof(b"b").unwrap(), tree.root_hash().unwrap())
+            .signed(&signer);
+        let bundle = open::<ProofBundle>(&seal(&bundle)).unwrap();
+        let trusted = vec![signer.public_key_hex().to_uppercase()];
+
+        let verdict = bundle.check(&trusted);
+        assert!(verdict.valid, "{:?}", verdict.problems);
+        assert_eq!(verdict.key, "b");
+        assert_eq!(verdict.value_len, 1);
+        assert_eq!(verdict.path_length, 1);
+        assert_eq!(verdict.signature, SignatureStatus::Trusted);
+
+        // Without trusted keys a matching signature is reported but not required
+        let verdict = bundle.check(&[]);
+        assert!(verdict.valid);
+        assert_eq!(verdict.signature, SignatureStatus::Untrusted);
+        let (other, _) = BackupSigner::generate().unwrap();
+        let verdict = bundle.check(&[other.public_key_hex()]);
+        assert!(!verdict.valid);
+        assert_eq!(verdict.problems.len(), 1);
+
+        // A root swapped after signing breaks both the path and the signature
+        let mut forged = bundle.clone();
+        forged.root_hash = vec![0; 32];
+        let verdict = forged.check(&[]);
+        assert!(!verdict.inclusion);
+        assert_eq!(verdict.signature, SignatureStatus::Invalid);
+        assert_eq!(verdict.problems.len(), 2);
+
+        let unsigned = ProofBundle {
+            signature: None,
+            ..bundle
+        };
+        assert!(unsigned.check(&[]).valid);
+        assert!(!unsigned.check(&trusted).valid);
+    }
+
+    #[test]
+    fn test_proof_bundle_json() {
+        let tree =
+            MerkleTree::from_pairs((0..5u8).map(|i| (vec![b'k', i], vec![b'v', i])).collect());
+        let (signer, _) = BackupSigner::generate().unwrap();
+        let bundle = ProofBundle::new(
+            tree.get_proof(&[b'k', 4]).unwrap(),
+            tree.root_hash().unwrap(),
+        )
+        .signed(&signer);
+
+        let json = bundle.to_json();
+        let document: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(document["kind"], "scribe.v1.ProofBundle");
+        assert_eq!(document["version"], PROOF_JSON_VERSION);
+        assert_eq!(document["key"], "6b04");
+        assert_eq!(ProofBundle::from_json(json.as_bytes()).unwrap(), bundle);
+
+        // Both export formats are read back the same way
+        assert_eq!(ProofBundle::parse(json.as_bytes()).unwrap(), bundle);
+        assert_eq!(ProofBundle::parse(&seal(&bundle)).unwrap(), bundle);
+        let unsigned = ProofBundle::new(bundle.proof.clone(), bundle.root_hash.clone());
+        assert!(!unsigned.to_json().contains("signature"));
+        assert_eq!(
+            ProofBundle::from_json(unsigned.to_json().as_bytes()).unwrap(),
+            unsigned
+        );
+
+        // Newer versions, other documents and broken paths are refused
+        let newer = json.replace(
+            &format!("\"version\": {}", PROOF_JSON_VERSION),
+            &format!("\"version\": {}", PROOF_JSON_VERSION + 1),
+        );
+        assert!(ProofBundle::from_json(newer.as_bytes()).is_err());
+        let other = json.replace("scribe.v1.ProofBundle", "scribe.v1.MerkleProof");
+        assert!(ProofBundle::from_json(other.as_bytes()).is_err());
+        let mut truncated = document.clone();
+        truncated["directions"].as_array_mut().unwrap().pop();
+        assert!(ProofBundle::from_json(truncated.to_string().as_bytes()).is_err());
+        let mut garbled = document;
+        garbled["root_hash"] = "zz".into();
+        assert!(ProofBundle::from_json(garbled.to_string().as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_proof_with_mismatched_path_is_rejected() {
+        let mut encoder = Encoder::new();
+        encoder.bytes(1, b"k");
+        encoder.bytes(3, &[0u8; 32]);
+        assert!(MerkleProof::from_bytes(&encoder.into_bytes()).is_err());
+
+        // A bundle without its proof is incomplete
+        let mut encoder = Encoder::new();
+        encoder.bytes(2, &[0u8; 32]);
+        assert!(ProofBundle::from_bytes(&encoder.into_bytes()).is_err());
+    }
+}
@@ -0,0 +1,342 @@
+//! Schemas of the common ledger types and the client payloads
+//!
+//! See `proto/scribe/v1/ledger.proto` and `proto/scribe/v1/client.proto`.
+
+use super::codec::unknown_case;
+use super::{Encoder, Fields, WireMessage};
+use crate::error::Result;
+use crate::types::{
+    Fence, LedgerTag, LockLease, QueueItem, Request, Response, SegmentAnchor, TxnRead, TxnWrite,
+    UsageDelta,
+};
+
+impl WireMessage for LockLease {
+    const KIND: &'static str = "scribe.v1.LockLease";
+
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.string(1, &self.name);
+        encoder.string(2, &self.holder);
+        encoder.uint64(3, self.token);
+        encoder.uint64(4, self.expires_at);
+    }
+
+    fn decode(fields: &Fields<'_>) -> Result<Self> {
+        Ok(Self {
+            name: fields.string(1)?,
+            holder: fields.string(2)?,
+            token: fields.uint64(3)?,
+            expires_at: fields.uint64(4)?,
+        })
+    }
+}
+
+impl WireMessage for Fence {
+    const KIND: &'static str = "scribe.v1.Fence";
+
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.string(1, &self.namespace);
+        encoder.uint64(2, self.epoch);
+    }
+
+    fn decode(fields: &Fields<'_>) -> Result<Self> {
+        Ok(Self {
+            namespace: fields.string(1)?,
+            epoch: fields.uint64(2)?,
+        })
+    }
+}
+
+impl WireMessage for QueueItem {
+    const KIND: &'static str = "scribe.v1.QueueItem";
+
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.string(1, &self.queue);
+        encoder.uint64(2, self.id);
+        encoder.uint64(3, self.receipt);
+        encoder.bytes(4, &self.payload);
+        encoder.uint64(5, self.attempts as u64);
+        encoder.uint64(6, self.visible_at);
+    }
+
+    fn decode(fields: &Fields<'_>) -> Result<Self> {
+        Ok(Self {
+            queue: fields.string(1)?,
+            id: fields.uint64(2)?,
+            receipt: fields.uint64(3)?,
+            payload: fields.bytes(4)?,
+            attempts: fields.uint32(5)?,
+            visible_at: fields.uint64(6)?,
+        })
+    }
+}
+
+impl WireMessage for LedgerTag {
+    const KIND: &'static str = "scribe.v1.LedgerTag";
+
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.string(1, &self.name);
+        encoder.uint64(2, self.raft_index);
+        encoder.uint64(3, self.manifest_version);
+        if let Some(root) = &self.merkle_root {
+            encoder.string(4, root);
+        }
+        encoder.uint64(5, self.key_count as u64);
+        encoder.uint64(6, self.created_at);
+    }
+
+    fn decode(fields: &Fields<'_>) -> Result<Self> {
+        Ok(Self {
+            name: fields.string(1)?,
+            raft_index: fields.uint64(2)?,
+            manifest_version: fields.uint64(3)?,
+            merkle_root: fields.optional_string(4)?,
+            key_count: fields.usize(5)?,
+            created_at: fields.uint64(6)?,
+        })
+    }
+}
+
+impl WireMessage for SegmentAnchor {
+    const KIND: &'static str = "scribe.v1.SegmentAnchor";
+
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.uint64(1, self.segment_id);
+        encoder.string(2, &self.merkle_root);
+        encoder.uint64(3, self.key_count as u64);
+        encoder.uint64(4, self.raft_index);
+        encoder.uint64(5, self.anchored_at);
+    }
+
+    fn decode(fields: &Fields<'_>) -> Result<Self> {
+        Ok(Self {
+            segment_id: fields.uint64(1)?,
+            merkle_root: fields.string(2)?,
+            key_count: fields.usize(3)?,
+            raft_index: fields.uint64(4)?,
+            anchored_at: fields.uint64(5)?,
+        })
+    }
+}
+
+impl WireMessage for TxnRead {
+    const KIND: &'static str = "scribe.v1.TxnRead";
+
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.bytes(1, &self.key);
+        if let Some(version) = self.version {
+            encoder.uint64(2, version);
+        }
+    }
+
+    fn decode(fields: &Fields<'_>) -> Result<Self> {
+        Ok(Self {
+            key: fields.bytes(1)?,
+            version: fields.optional_uint64(2)?,
+        })
+    }
+}
+
+impl WireMessage for TxnWrite {
+    const KIND: &'static str = "scribe.v1.TxnWrite";
+
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.bytes(1, &self.key);
+        if let Some(value) = &self.value {
+            encoder.bytes(2, value);
+        }
+    }
+
+    fn decode(fields: &Fields<'_>) -> Result<Self> {
+        Ok(Self {
+            key: fields.bytes(1)?,
+            value: fields.optional_bytes(2)?,
+        })
+    }
+}
+
+impl WireMessage for UsageDelta {
+    const KIND: &'static str = "scribe.v1.UsageDelta";
+
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.string(1, &self.key_id);
+        encoder.uint64(2, self.day);
+        encoder.uint64(3, self.ops);
+        encoder.uint64(4, self.bytes);
+    }
+
+    fn decode(fields: &Fields<'_>) -> Result<Self> {
+        Ok(Self {
+            key_id: fields.string(1)?,
+            day: fields.uint64(2)?,
+            ops: fields.uint64(3)?,
+            bytes: fields.uint64(4)?,
+        })
+    }
+}
+
+impl WireMessage for Request {
+    const KIND: &'static str = "scribe.v1.Request";
+
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            Request::Put { key, value } => encoder.nested(1, |put| {
+                put.bytes(1, key);
+                put.bytes(2, value);
+            }),
+            Request::Get { key } => encoder.nested(2, |get| get.bytes(1, key)),
+            Request::Delete { key } => encoder.nested(3, |delete| delete.bytes(1, key)),
+        }
+    }
+
+    fn decode(fields: &Fields<'_>) -> Result<Self> {
+        match fields.oneof(3)? {
+            Some((1, put)) => Ok(Request::Put {
+                key: put.bytes(1)?,
+                value: put.bytes(2)?,
+            }),
+            Some((2, get)) => Ok(Request::Get { key: get.bytes(1)? }),
+            Some((_, delete)) => Ok(Request::Delete {
+                key: delete.bytes(1)?,
+            }),
+            None => Err(unknown_case(Self::KIND)),
+        }
+    }
+}
+
+impl WireMessage for Response {
+    const KIND: &'static str = "scribe.v1.Response";
+
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            Response::PutOk => encoder.nested(1, |_| {}),
+            Response::GetOk { value } => encoder.nested(2, |get| {
+                if let Some(value) = value {
+                    get.bytes(1, value);
+                }
+            }),
+            Response::DeleteOk => encoder.nested(3, |_| {}),
+            Response::Error { message } => encoder.nested(4, |error| error.string(1, message)),
+        }
+    }
+
+    fn decode(fields: &Fields<'_>) -> Result<Self> {
+        match fields.oneof(4)? {
+            Some((1, _)) => Ok(Response::PutOk),
+            Some((2, get)) => Ok(Response::GetOk {
+                value: get.optional_bytes(1)?,
+            }),
+            Some((3, _)) => Ok(Response::DeleteOk),
+            Some((_, error)) => Ok(Response::Error {
+                message: error.string(1)?,
+            }),
+            None => Err(unknown_case(Self::KIND)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ledger_types_round_trip() {
+        let lease = LockLease {
+            name: "leader".to_string(),
+            holder: "worker-1".to_string(),
+            token: 42,
+            expires_at: 1_700_000_000_000,
+        };
+        assert_eq!(LockLease::from_bytes(&lease.to_bytes()).unwrap(), lease);
+
+        let item = QueueItem {
+            queue: "jobs".to_string(),
+            id: 7,
+            receipt: 9,
+            payload: vec![0, 1, 2],
+            attempts: 2,
+            visible_at: 1_700_000_030_000,
+        };
+        assert_eq!(QueueItem::from_bytes(&item.to_bytes()).unwrap(), item);
+
+        for merkle_root in [None, Some("ab".repeat(32))] {
+            let tag = LedgerTag {
+                name: "v1".to_string(),
+                raft_index: 100,
+                manifest_version: 0,
+                merkle_root,
+                key_count: 12,
+                created_at: 1_700_000_000_000,
+            };
+            assert_eq!(LedgerTag::from_bytes(&tag.to_bytes()).unwrap(), tag);
+        }
+
+        let anchor = SegmentAnchor {
+            segment_id: 90_215_344_128_000,
+            merkle_root: "cd".repeat(32),
+            key_count: 3,
+            raft_index: 812,
+            anchored_at: 1_700_000_060_000,
+        };
+        assert_eq!(
+            SegmentAnchor::from_bytes(&anchor.to_bytes()).unwrap(),
+            anchor
+        );
+
+        // A version of 0 and an empty value are distinct from missing ones
+        for version in [None, Some(0)] {
+            let read = TxnRead {
+                key: b"k".to_vec(),
+                version,
+            };
+            assert_eq!(TxnRead::from_bytes(&read.to_bytes()).unwrap(), read);
+        }
+        for value in [None, Some(Vec::new())] {
+            let write = TxnWrite {
+                key: b"k".to_vec(),
+                value,
+            };
+            assert_eq!(TxnWrite::from_bytes(&write.to_bytes()).unwrap(), write);
+        }
+    }
+
+    #[test]
+    fn test_client_payloads_round_trip() {
+        let requests = [
+            Request::Put {
+                key: b"k".to_vec(),
+                value: Vec::new(),
+            },
+            Request::Get { key: b"k".to_vec() },
+            Request::Delete { key: b"k".to_vec() },
+        ];
+        for request in requests {
+            let decoded = Request::from_bytes(&request.to_bytes()).unwrap();
+            assert_eq!(format!("{:?}", decoded), format!("{:?}", request));
+        }
+
+        // An empty value is distinct from a missing one
+        let responses = [
+            Response::PutOk,
+            Response::GetOk { value: None },
+            Response::GetOk {
+                value: Some(Vec::new()),
+            },
+            Response::DeleteOk,
+            Response::Error {
+                message: "boom".to_string(),
+            },
+        ];
+        for response in responses {
+            let decoded = Response::from_bytes(&response.to_bytes()).unwrap();
+            assert_eq!(format!("{:?}", decoded), format!("{:?}", response));
+        }
+    }
+
+    #[test]
+    fn test_unknown_case_is_rejected() {
+        // A request kind added by a newer version
+        let mut encoder = Encoder::new();
+        encoder.nested(9, |scan| scan.bytes(1, b"prefix"));
+        assert!(Request::from_bytes(&encoder.into_bytes()).is_err());
+    }
+}
@@ -0,0 +1,355 @@
+//! Archival Failure-Injection Tests
+//!
+//! These tests run the archival pipeline against MinIO while an `S3FaultInjector`
+//! simulates outages and damaged uploads. They verify that segments which fail to
+//! archive stay local for the next attempt, that the manifest never references an
+//! upload that could not be verified, that reads fall back correctly, and that
+//! archivals interrupted by a crash are reconciled on restart.
+//!
+//! Run with `cargo test --test archival_fault_tests -- --ignored`. If nothing is
+//! listening on the S3 endpoint, MinIO is started from `docker-compose-minio.yml`
+//! (set `SCRIBE_TEST_COMPOSE=0` to skip this).
+
+use hyra_scribe_ledger::ids::SequentialIds;
+use hyra_scribe_ledger::manifest::ManifestManager;
+use hyra_scribe_ledger::storage::archival::{ArchivalManager, TieringPolicy, VerificationStatus};
+use hyra_scribe_ledger::storage::faults::S3FaultInjector;
+use hyra_scribe_ledger::storage::s3::S3StorageConfig;
+use hyra_scribe_ledger::storage::segment::SegmentManager;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::process::Command;
+use std::sync::{Arc, Once};
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, MutexGuard};
+
+const DEFAULT_ENDPOINT: &str = "http://localhost:9000";
+
+/// Tests share segment IDs in one bucket, so they run one at a time
+static SERIAL: Mutex<()> = Mutex::const_new(());
+
+static START_MINIO: Once = Once::new();
+
+/// Get test S3 configuration for MinIO
+fn get_test_config() -> S3StorageConfig {
+    S3StorageConfig {
+        bucket: std::env::var("S3_BUCKET").unwrap_or_else(|_| "test-bucket".to_string()),
+        region: std::env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+        endpoint: Some(
+            std::env::var("S3_ENDPOINT").unwrap_or_else(|_| DEFAULT_ENDPOINT.to_string()),
+        ),
+        access_key_id: std::env::var("S3_ACCESS_KEY_ID")
+            .ok()
+            .or_else(|| Some("minioadmin".to_string())),
+        secret_access_key: std::env::var("S3_SECRET_ACCESS_KEY")
+            .ok()
+            .or_else(|| Some("minioadmin".to_string())),
+        path_style: true,
+        timeout_secs: 30,
+        max_retries: 3,
+        ..Default::default()
+    }
+}
+
+/// Whether something accepts connections at the endpoint's host and port
+fn endpoint_reachable(endpoint: &str) -> bool {
+    let authority = endpoint
+        .trim_start_matches("http://")
+        .trim_start_matches("https://")
+        .split('/')
+        .next()
+        .unwrap_or_default();
+    authority
+        .to_socket_addrs()
+        .map(|mut addrs| {
+            addrs.any(|addr| TcpStream::connect_timeout(&addr, Duration::from_secs(1)).is_ok())
+        })
+        .unwrap_or(false)
+}
+
+/// Start MinIO with docker compose unless the endpoint is already up
+fn ensure_minio(endpoint: &str) {
+    START_MINIO.call_once(|| {
+        if endpoint_reachable(endpoint)
+            || std::env::var("SCRIBE_TEST_COMPOSE").is_ok_and(|v| v == "0")
+        {
+            return;
+        }
+
+        let compose_file = concat!(env!("CARGO_MANIFEST_DIR"), "/docker-compose-minio.yml");
+        let status = Command::new("docker")
+            .args(["compose", "-f", compose_file, "up", "-d"])
+            .status()
+            .expect("Failed to run docker compose");
+        assert!(status.success(), "docker compose up failed");
+
+        let deadline = Instant::now() + Duration::from_secs(60);
+        while !endpoint_reachable(endpoint) {
+            assert!(Instant::now() < deadline, "MinIO did not come up");
+            std::thread::sleep(Duration::from_millis(500));
+        }
+        // Give minio-setup time to create the buckets
+        std::thread::sleep(Duration::from_secs(10));
+    });
+}
+
+/// Test fixture: an archival manager with a fault injector and a manifest
+struct Fixture {
+    _serial: MutexGuard<'static, ()>,
+    config: S3StorageConfig,
+    segments: Arc<SegmentManager>,
+    manifest: Arc<ManifestManager>,
+    faults: Arc<S3FaultInjector>,
+    manager: ArchivalManager,
+}
+
+impl Fixture {
+    /// Set up a fixture, removing anything earlier runs archived for `segment_ids`
+    async fn new(segment_ids: &[u64]) -> Self {
+        Self::with_segments(segment_ids, Self::segments(None)).await
+    }
+
+    /// Set up a fixture whose flushed segments are files in `dir`
+    async fn with_segment_dir(segment_ids: &[u64], dir: &Path) -> Self {
+        Self::with_segments(segment_ids, Self::segments(Some(dir))).await
+    }
+
+    /// Segment manager with sequential IDs, mapping flushed segments from `dir`
+    fn segments(dir: Option<&Path>) -> Arc<SegmentManager> {
+        let segments = SegmentManager::new().with_id_generator(Arc::new(SequentialIds::new()));
+        Arc::new(match dir {
+            Some(dir) => segments.with_segment_dir(dir).unwrap(),
+            None => segments,
+        })
+    }
+
+    async fn with_segments(segment_ids: &[u64], segments: Arc<SegmentManager>) -> Self {
+        let serial = SERIAL.lock().await;
+        let config = get_test_config();
+        ensure_minio(config.endpoint.as_deref().unwrap_or(DEFAULT_ENDPOINT));
+
+        let manifest = Arc::new(ManifestManager::new());
+        let faults = Arc::new(S3FaultInjector::new());
+        let manager = Self::manager(&config, &segments, &manifest, &faults).await;
+        for &segment_id in segment_ids {
+            manager.delete_archived_segment(segment_id).await.unwrap();
+        }
+
+        Self {
+            _serial: serial,
+            config,
+            segments,
+            manifest,
+            faults,
+            manager,
+        }
+    }
+
+    async fn manager(
+        config: &S3StorageConfig,
+        segments: &Arc<SegmentManager>,
+        manifest: &Arc<ManifestManager>,
+        faults: &Arc<S3FaultInjector>,
+    ) -> ArchivalManager {
+        let policy = TieringPolicy {
+            age_threshold_secs: 0,
+            enable_auto_archival: false,
+            ..Default::default()
+        };
+        ArchivalManager::new(config.clone(), Arc::clone(segments), policy)
+            .await
+            .unwrap()
+            .with_manifest(Arc::clone(manifest))
+            .with_fault_injector(Arc::clone(faults))
+    }
+
+    /// Flush one segment per key and wait until they are old enough to archive
+    async fn flush_segments(&self, keys: &[&[u8]]) {
+        for key in keys {
+            self.segments.put(key.to_vec(), b"value".to_vec()).unwrap();
+            self.segments.flush_active().unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+    }
+}
+
+#[tokio::test]
+#[ignore] // Requires MinIO to be running
+async fn test_outage_mid_upload_keeps_segment_for_retry() {
+    let fixture = Fixture::new(&[0, 1]).await;
+    fixture.flush_segments(&[b"first", b"second"]).await;
+
+    // Every attempt at the first upload fails; the second segment still goes through
+    fixture
+        .faults
+        .fail_next_puts(fixture.config.max_retries + 1);
+    let err = fixture.manager.archive_old_segments().await.unwrap_err();
+    assert!(err.to_string().contains("segment 0"), "{}", err);
+
+    let local = fixture.segments.get_flushed_segments().unwrap();
+    assert_eq!(local.len(), 1);
+    assert_eq!(local[0].segment_id, 0);
+    assert!(fixture.manifest.get_segment(0).await.is_none());
+    assert!(fixture.manifest.get_segment(1).await.is_some());
+
+    // The next run archives the segment left behind
+    let archived = fixture.manager.archive_old_segments().await.unwrap();
+    assert_eq!(archived, vec![0]);
+    assert_eq!(fixture.segments.flushed_count().unwrap(), 0);
+    assert!(fixture.manifest.get_segment(0).await.is_some());
+}
+
+#[tokio::test]
+#[ignore] // Requires MinIO to be running
+async fn test_full_outage_archives_nothing() {
+    let fixture = Fixture::new(&[0, 1]).await;
+    fixture.flush_segments(&[b"first", b"second"]).await;
+
+    fixture.faults.set_outage(true);
+    assert!(fixture.manager.archive_old_segments().await.is_err());
+    assert_eq!(fixture.segments.flushed_count().unwrap(), 2);
+    assert!(fixture.manifest.get_segments().await.is_empty());
+
+    fixture.faults.set_outage(false);
+    let archived = fixture.manager.archive_old_segments().await.unwrap();
+    assert_eq!(archived, vec![0, 1]);
+    assert_eq!(fixture.manifest.get_segments().await.len(), 2);
+}
+
+#[tokio::test]
+#[ignore] // Requires MinIO to be running
+async fn test_torn_upload_is_never_recorded() {
+    let fixture = Fixture::new(&[0]).await;
+    fixture.flush_segments(&[b"torn"]).await;
+
+    // The upload reports success but only half the data arrives
+    fixture.faults.tear_next_puts(1);
+    let err = fixture.manager.archive_old_segments().await.unwrap_err();
+    assert!(err.to_string().contains("does not match"), "{}", err);
+
+    assert!(fixture.manifest.get_segment(0).await.is_none());
+    assert!(fixture.manager.get_metadata(0).await.unwrap().is_none());
+    assert!(!fixture
+        .manager
+        .list_archived_segments()
+        .await
+        .unwrap()
+        .contains(&0));
+    assert_eq!(fixture.segments.flushed_count().unwrap(), 1);
+
+    // A clean retry archives a segment that verifies
+    fixture.manager.archive_old_segments().await.unwrap();
+    let verification = fixture.manager.verify_segment(0).await.unwrap().unwrap();
+    assert_eq!(verification.status, VerificationStatus::Verified);
+}
+
+#[tokio::test]
+#[ignore] // Requires MinIO to be running
+async fn test_failed_metadata_upload_is_never_recorded() {
+    let fixture = Fixture::new(&[0]).await;
+    fixture.flush_segments(&[b"meta"]).await;
+
+    fixture.faults.fail_puts_matching(Some(".meta.json"));
+    assert!(fixture.manager.archive_old_segments().await.is_err());
+    assert!(fixture.manifest.get_segment(0).await.is_none());
+    assert_eq!(fixture.segments.flushed_count().unwrap(), 1);
+
+    fixture.faults.reset();
+    assert_eq!(
+        fixture.manager.archive_old_segments().await.unwrap(),
+        vec![0]
+    );
+    assert!(fixture.manifest.get_segment(0).await.is_some());
+}
+
+#[tokio::test]
+#[ignore] // Requires MinIO to be running
+async fn test_reads_fall_back_during_outage() {
+    let fixture = Fixture::new(&[0]).await;
+    fixture.flush_segments(&[b"archived"]).await;
+    assert_eq!(
+        fixture.manager.archive_old_segments().await.unwrap(),
+        vec![0]
+    );
+
+    // Read once so the archived segment is cached, then keep a segment local
+    assert_eq!(
+        fixture.manager.get_value(0, b"archived").await.unwrap(),
+        Some(b"value".to_vec())
+    );
+    fixture.flush_segments(&[b"local"]).await;
+
+    fixture.faults.set_outage(true);
+    assert_eq!(
+        fixture.manager.get_value(1, b"local").await.unwrap(),
+        Some(b"value".to_vec())
+    );
+    assert_eq!(
+        fixture.manager.get_value(0, b"archived").await.unwrap(),
+        Some(b"value".to_vec())
+    );
+
+    // Without a cached copy the read fails instead of reporting the key as absent
+    let cold = Fixture::manager(
+        &fixture.config,
+        &fixture.segments,
+        &fixture.manifest,
+        &fixture.faults,
+    )
+    .await;
+    assert!(cold.get_value(0, b"archived").await.is_err());
+
+    fixture.faults.set_outage(false);
+    assert_eq!(
+        cold.get_value(0, b"archived").await.unwrap(),
+        Some(b"value".to_vec())
+    );
+}
+
+#[tokio::test]
+#[ignore] // Requires MinIO to be running
+async fn test_archival_interrupted_by_crash_is_recovered() {
+    let dir = std::env::temp_dir().join(format!("scribe-crash-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let fixture = Fixture::with_segment_dir(&[0, 1, 2], &dir).await;
+    fixture
+        .flush_segments(&[b"uploading", b"uploaded", b"registered"])
+        .await;
+    let local = fixture.segments.get_flushed_segments().unwrap();
+
+    // Segment 0: the process dies after uploading the data, before the metadata
+    fixture
+        .faults
+        .fail_puts_matching(Some("segment-0000000000000000.meta.json"));
+    assert!(fixture.manager.archive_segment(&local[0]).await.is_err());
+    fixture.faults.reset();
+    // Segment 1: the process dies after uploading the metadata, before the
+    // segment is registered and removed locally
+    fixture.manager.archive_segment(&local[1]).await.unwrap();
+    // Segment 2: the process dies after removing the local file
+    fixture.manager.archive_segment(&local[2]).await.unwrap();
+    std::fs::remove_file(dir.join("segment-0000000000000002.seg")).unwrap();
+    drop(fixture.manager);
+
+    // On restart, the upload of segment 0 is rolled back and the others completed
+    let segments = Fixture::segments(Some(&dir));
+    let manifest = Arc::new(ManifestManager::new());
+    let manager = Fixture::manager(&fixture.config, &segments, &manifest, &fixture.faults).await;
+    let recovery = manager.recover_interrupted_rolls().await.unwrap();
+    assert_eq!(recovery.completed, vec![1, 2]);
+    assert_eq!(recovery.rolled_back, vec![0]);
+    assert!(segments.interrupted_rolls().unwrap().is_empty());
+
+    assert_eq!(segments.flushed_segment_ids().unwrap(), vec![0]);
+    assert!(manager.get_metadata(0).await.unwrap().is_none());
+    assert!(!manager.list_archived_segments().await.unwrap().contains(&0));
+    assert!(manifest.get_segment(1).await.is_some());
+    assert!(manifest.get_segment(2).await.is_some());
+
+    // The rolled back segment is archived by the next run
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert_eq!(manager.archive_old_segments().await.unwrap(), vec![0]);
+    assert!(segments.interrupted_rolls().unwrap().is_empty());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,57 @@
+//! Get-and-set and get-and-delete tests
+//!
+//! These tests run a 3-node in-process cluster and check that swaps and
+//! consuming deletes return the previous value exactly once. Run with
+//! `cargo test --features testing --test atomic_ops_tests`.
+
+use hyra_scribe_ledger::http_client::ClusterClient;
+use hyra_scribe_ledger::testing::TestCluster;
+
+#[tokio::test]
+async fn test_get_and_set_returns_previous_value() {
+    let cluster = TestCluster::start(3).await.unwrap();
+    let client = ClusterClient::new(cluster.client_urls()).unwrap();
+
+    assert_eq!(
+        client.get_and_set(b"counter", b"1".to_vec()).await.unwrap(),
+        None
+    );
+    assert_eq!(
+        client.get_and_set(b"counter", b"2".to_vec()).await.unwrap(),
+        Some(b"1".to_vec())
+    );
+    assert_eq!(client.get(b"counter").await.unwrap(), Some(b"2".to_vec()));
+
+    // An empty value is still a value
+    client.put(b"empty", Vec::new()).await.unwrap();
+    assert_eq!(
+        client.get_and_delete(b"empty").await.unwrap(),
+        Some(Vec::new())
+    );
+    assert_eq!(client.get_and_delete(b"empty").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_get_and_delete_hands_out_a_value_once() {
+    let cluster = TestCluster::start(3).await.unwrap();
+    let client = ClusterClient::new(cluster.client_urls()).unwrap();
+
+    for round in 0..5 {
+        let item = format!("item-{}", round).into_bytes();
+        client.put(b"slot", item.clone()).await.unwrap();
+
+        // Consumers race for the item; exactly one of them gets it
+        let consumers: Vec<_> = (0..4)
+            .map(|_| {
+                let client = client.clone();
+                tokio::spawn(async move { client.get_and_delete(b"slot").await.unwrap() })
+            })
+            .collect();
+        let mut received = Vec::new();
+        for consumer in consumers {
+            received.extend(consumer.await.unwrap());
+        }
+        assert_eq!(received, vec![item]);
+    }
+    assert_eq!(client.get(b"slot").await.unwrap(), None);
+}
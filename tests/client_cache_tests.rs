@@ -0,0 +1,89 @@
+//! Client-side cache tests
+//!
+//! These tests run a 3-node in-process cluster and check that `CachingClient`
+//! serves hot keys from memory and drops them when the change stream reports a
+//! write from another client. Run with
+//! `cargo test --features testing --test client_cache_tests`.
+
+use hyra_scribe_ledger::client_cache::CachingClient;
+use hyra_scribe_ledger::http_client::ClusterClient;
+use hyra_scribe_ledger::testing::TestCluster;
+use std::time::{Duration, Instant};
+
+/// Wait until the cache follows the change stream
+async fn wait_subscribed(cached: &CachingClient) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !cached.is_subscribed() {
+        assert!(Instant::now() < deadline, "change stream never connected");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+/// Read through the cache until it returns `expected`
+async fn wait_for_value(cached: &CachingClient, key: &[u8], expected: Option<Vec<u8>>) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        if cached.get(key).await.unwrap() == expected {
+            return;
+        }
+        assert!(Instant::now() < deadline, "cache never observed the change");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_cache_serves_hot_keys_from_memory() {
+    let cluster = TestCluster::start(3).await.unwrap();
+    let client = ClusterClient::new(cluster.client_urls()).unwrap();
+    let cached = CachingClient::new(client).with_max_staleness(Duration::from_secs(60));
+    wait_subscribed(&cached).await;
+
+    cached.put(b"hot", b"value".to_vec()).await.unwrap();
+    // Let the change event of the put arrive before the value is cached
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    for _ in 0..10 {
+        assert_eq!(cached.get(b"hot").await.unwrap(), Some(b"value".to_vec()));
+    }
+
+    let stats = cached.stats();
+    assert_eq!(stats.misses, 1);
+    assert_eq!(stats.hits, 9);
+}
+
+#[tokio::test]
+async fn test_cache_invalidated_by_other_writers() {
+    let cluster = TestCluster::start(3).await.unwrap();
+    let writer = ClusterClient::new(cluster.client_urls()).unwrap();
+    // Far beyond the test duration, so only invalidation can refresh the cache
+    let cached = CachingClient::new(ClusterClient::new(cluster.client_urls()).unwrap())
+        .with_max_staleness(Duration::from_secs(600));
+    wait_subscribed(&cached).await;
+
+    writer.put(b"shared", b"v1".to_vec()).await.unwrap();
+    wait_for_value(&cached, b"shared", Some(b"v1".to_vec())).await;
+
+    writer.put(b"shared", b"v2".to_vec()).await.unwrap();
+    wait_for_value(&cached, b"shared", Some(b"v2".to_vec())).await;
+
+    writer.delete(b"shared").await.unwrap();
+    wait_for_value(&cached, b"shared", None).await;
+}
+
+#[tokio::test]
+async fn test_cache_revalidates_stale_entries() {
+    let cluster = TestCluster::start(3).await.unwrap();
+    let client = ClusterClient::new(cluster.client_urls()).unwrap();
+    let cached = CachingClient::new(client).with_max_staleness(Duration::ZERO);
+    wait_subscribed(&cached).await;
+
+    cached.put(b"key", b"value".to_vec()).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(cached.get(b"key").await.unwrap(), Some(b"value".to_vec()));
+    assert_eq!(cached.get(b"key").await.unwrap(), Some(b"value".to_vec()));
+
+    // The second read asked the server, which confirmed the value by its ETag
+    let stats = cached.stats();
+    assert_eq!(stats.hits, 0);
+    assert_eq!(stats.misses, 1);
+    assert_eq!(stats.revalidations, 1);
+}
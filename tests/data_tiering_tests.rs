@@ -27,6 +27,7 @@ fn get_test_config() -> S3StorageConfig {
         path_style: true,
         timeout_secs: 30,
         max_retries: 3,
+        ..Default::default()
     }
 }
 
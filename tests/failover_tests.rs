@@ -0,0 +1,208 @@
+//! Client failover tests
+//!
+//! These tests run a 3-node in-process cluster over the real Raft transport,
+//! crash the leader and verify that `ClusterClient` keeps working without the
+//! caller noticing. Run with `cargo test --features testing --test failover_tests`.
+
+use hyra_scribe_ledger::api::ReadConsistency;
+use hyra_scribe_ledger::error::ScribeError;
+use hyra_scribe_ledger::http_client::ClusterClient;
+use hyra_scribe_ledger::testing::TestCluster;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_cluster_replicates_writes() {
+    let cluster = TestCluster::start(3).await.unwrap();
+    let client = ClusterClient::new(cluster.client_urls()).unwrap();
+
+    client.put(b"replicated", b"value".to_vec()).await.unwrap();
+    assert_eq!(
+        client.get(b"replicated").await.unwrap(),
+        Some(b"value".to_vec())
+    );
+
+    // Every node applies the write through the Raft transport
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    for node in cluster.nodes() {
+        assert_eq!(
+            node.consensus().client_read_local(b"replicated").await,
+            Some(b"value".to_vec()),
+            "node {} did not apply the write",
+            node.node_id
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_client_routes_to_leader_directly() {
+    let cluster = TestCluster::start(3).await.unwrap();
+    let leader = cluster
+        .wait_for_leader(Duration::from_secs(5))
+        .await
+        .unwrap();
+
+    // A follower is listed first
+    let mut urls = cluster.client_urls();
+    let leader_url = cluster.node(leader).unwrap().url();
+    urls.retain(|url| *url != leader_url);
+    urls.push(leader_url.clone());
+    let client = ClusterClient::new(urls).unwrap();
+    assert_ne!(client.current_endpoint(), leader_url);
+
+    // The first request is redirected; the client then sticks to the leader
+    client.put(b"routed", b"value".to_vec()).await.unwrap();
+    assert_eq!(client.current_endpoint(), leader_url);
+    assert_eq!(
+        client.get(b"routed").await.unwrap(),
+        Some(b"value".to_vec())
+    );
+    assert_eq!(client.current_endpoint(), leader_url);
+}
+
+#[tokio::test]
+async fn test_client_fails_over_when_leader_dies() {
+    let mut cluster = TestCluster::start(3).await.unwrap();
+    let leader = cluster
+        .wait_for_leader(Duration::from_secs(5))
+        .await
+        .unwrap();
+
+    // Point the client at the leader first so the crash hits it directly
+    let mut urls = cluster.client_urls();
+    let leader_url = cluster.node(leader).unwrap().url();
+    urls.retain(|url| *url != leader_url);
+    urls.insert(0, leader_url.clone());
+    let client = ClusterClient::new(urls).unwrap();
+
+    client.put(b"before", b"1".to_vec()).await.unwrap();
+    assert_eq!(client.current_endpoint(), leader_url);
+
+    cluster.kill(leader).await.unwrap();
+
+    // No error surfaces to the caller while the cluster elects a new leader
+    client.put(b"after", b"2".to_vec()).await.unwrap();
+    assert_ne!(client.current_endpoint(), leader_url);
+    assert_eq!(client.get(b"before").await.unwrap(), Some(b"1".to_vec()));
+    assert_eq!(client.get(b"after").await.unwrap(), Some(b"2".to_vec()));
+
+    let new_leader = cluster
+        .wait_for_leader(Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert_ne!(new_leader, leader);
+
+    client.delete(b"before").await.unwrap();
+    assert_eq!(client.get(b"before").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_client_skips_dead_follower() {
+    let mut cluster = TestCluster::start(3).await.unwrap();
+    let leader = cluster
+        .wait_for_leader(Duration::from_secs(5))
+        .await
+        .unwrap();
+    let follower = cluster
+        .nodes()
+        .iter()
+        .map(|node| node.node_id)
+        .find(|id| *id != leader)
+        .unwrap();
+
+    // The dead follower is listed first
+    let mut urls = cluster.client_urls();
+    let follower_url = cluster.node(follower).unwrap().url();
+    urls.retain(|url| *url != follower_url);
+    urls.insert(0, follower_url);
+    let client = ClusterClient::new(urls).unwrap();
+
+    cluster.kill(follower).await.unwrap();
+    assert!(!cluster.node(follower).unwrap().is_alive());
+
+    client.put(b"key", b"value".to_vec()).await.unwrap();
+    assert_eq!(client.get(b"key").await.unwrap(), Some(b"value".to_vec()));
+}
+
+#[tokio::test]
+async fn test_follower_serves_linearizable_reads() {
+    let cluster = TestCluster::start(3).await.unwrap();
+    let leader = cluster
+        .wait_for_leader(Duration::from_secs(5))
+        .await
+        .unwrap();
+    let follower = cluster
+        .nodes()
+        .iter()
+        .find(|node| node.node_id != leader)
+        .unwrap();
+
+    let leader_api = cluster.node(leader).unwrap().api();
+    leader_api
+        .put(b"fresh".to_vec(), b"value".to_vec())
+        .await
+        .unwrap();
+
+    // The follower catches up with the leader's read index before reading
+    assert_eq!(
+        follower
+            .api()
+            .get(b"fresh".to_vec(), ReadConsistency::Linearizable)
+            .await
+            .unwrap(),
+        Some(b"value".to_vec())
+    );
+
+    follower.consensus().set_follower_reads(false);
+    match follower
+        .api()
+        .get(b"fresh".to_vec(), ReadConsistency::Linearizable)
+        .await
+    {
+        Err(ScribeError::NotLeader { leader_id }) => assert_eq!(leader_id, Some(leader)),
+        other => panic!("expected a redirect to the leader, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_redirected_put_keeps_its_ttl() {
+    let cluster = TestCluster::start(3).await.unwrap();
+    let leader = cluster
+        .wait_for_leader(Duration::from_secs(5))
+        .await
+        .unwrap();
+    let follower = cluster
+        .nodes()
+        .iter()
+        .find(|node| node.node_id != leader)
+        .unwrap();
+
+    // The follower redirects to the leader with the query intact
+    let response = reqwest::Client::new()
+        .put(format!("{}/expiring?ttl=1", follower.url()))
+        .body("value")
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(
+        response.url().as_str(),
+        format!("{}/expiring?ttl=1", cluster.node(leader).unwrap().url())
+    );
+
+    let leader_api = cluster.node(leader).unwrap().api();
+    assert_eq!(
+        leader_api
+            .get(b"expiring".to_vec(), ReadConsistency::Stale)
+            .await
+            .unwrap(),
+        Some(b"value".to_vec())
+    );
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert_eq!(
+        leader_api
+            .get(b"expiring".to_vec(), ReadConsistency::Stale)
+            .await
+            .unwrap(),
+        None
+    );
+}
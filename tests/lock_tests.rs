@@ -0,0 +1,79 @@
+//! Distributed lock tests
+//!
+//! These tests run a 3-node in-process cluster and exercise lock leases through
+//! `ClusterClient`, including takeover after the leader fails. Run with
+//! `cargo test --features testing --test lock_tests`.
+
+use hyra_scribe_ledger::error::ScribeError;
+use hyra_scribe_ledger::http_client::ClusterClient;
+use hyra_scribe_ledger::testing::TestCluster;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_lock_excludes_other_holders() {
+    let cluster = TestCluster::start(3).await.unwrap();
+    let client = ClusterClient::new(cluster.client_urls()).unwrap();
+    let ttl = Duration::from_secs(30);
+
+    let lease = client.acquire_lock("jobs", "worker-1", ttl).await.unwrap();
+    assert_eq!(lease.name, "jobs");
+    assert_eq!(lease.holder, "worker-1");
+    assert!(matches!(
+        client.acquire_lock("jobs", "worker-2", ttl).await,
+        Err(ScribeError::Conflict(_))
+    ));
+
+    let renewed = client.renew_lock("jobs", lease.token, ttl).await.unwrap();
+    assert_eq!(renewed.token, lease.token);
+    assert!(renewed.expires_at >= lease.expires_at);
+
+    assert!(client.release_lock("jobs", lease.token).await.unwrap());
+    let next = client.acquire_lock("jobs", "worker-2", ttl).await.unwrap();
+    assert!(next.token > lease.token);
+    assert!(matches!(
+        client.renew_lock("jobs", lease.token, ttl).await,
+        Err(ScribeError::Conflict(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_lock_expires_and_survives_failover() {
+    let mut cluster = TestCluster::start(3).await.unwrap();
+    let client = ClusterClient::new(cluster.client_urls()).unwrap();
+
+    let short = client
+        .acquire_lock("short", "worker-1", Duration::from_secs(1))
+        .await
+        .unwrap();
+    let held = client
+        .acquire_lock("held", "worker-1", Duration::from_secs(60))
+        .await
+        .unwrap();
+
+    let leader = cluster
+        .wait_for_leader(Duration::from_secs(5))
+        .await
+        .unwrap();
+    cluster.kill(leader).await.unwrap();
+    cluster
+        .wait_for_leader(Duration::from_secs(10))
+        .await
+        .unwrap();
+
+    // The new leader still knows the long lease
+    assert!(matches!(
+        client
+            .acquire_lock("held", "worker-2", Duration::from_secs(60))
+            .await,
+        Err(ScribeError::Conflict(_))
+    ));
+    assert_eq!(client.lock_info("held").await.unwrap(), Some(held));
+
+    // The short lease ran out during the election
+    tokio::time::sleep(Duration::from_millis(1200)).await;
+    let taken = client
+        .acquire_lock("short", "worker-2", Duration::from_secs(60))
+        .await
+        .unwrap();
+    assert!(taken.token > short.token);
+}
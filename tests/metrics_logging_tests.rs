@@ -6,19 +6,20 @@
 /// - Request correlation IDs
 /// - Metrics endpoint functionality
 use hyra_scribe_ledger::{logging, metrics};
+use std::time::Instant;
 
 #[test]
 fn test_metrics_initialization() {
     // Initialize metrics system
     metrics::init_metrics();
+    metrics::record_http_request("GET", "/:key", "200", Instant::now());
 
     // Verify metrics can be gathered
     let metrics_output = metrics::get_metrics();
 
     // Check for key metrics
-    assert!(metrics_output.contains("scribe_ledger_get_requests_total"));
-    assert!(metrics_output.contains("scribe_ledger_put_requests_total"));
-    assert!(metrics_output.contains("scribe_ledger_delete_requests_total"));
+    assert!(metrics_output.contains("scribe_ledger_http_requests_total"));
+    assert!(metrics_output.contains("scribe_ledger_http_request_duration_seconds"));
     assert!(metrics_output.contains("scribe_ledger_node_health"));
     assert!(metrics_output.contains("scribe_ledger_operations_total"));
 }
@@ -28,18 +29,25 @@ fn test_request_counter_metrics() {
     metrics::init_metrics();
 
     // Track some requests
-    let initial_gets = metrics::GET_REQUESTS.get();
-    metrics::GET_REQUESTS.inc();
-    assert_eq!(metrics::GET_REQUESTS.get(), initial_gets + 1);
-
-    let initial_puts = metrics::PUT_REQUESTS.get();
-    metrics::PUT_REQUESTS.inc();
-    metrics::PUT_REQUESTS.inc();
-    assert_eq!(metrics::PUT_REQUESTS.get(), initial_puts + 2);
-
-    let initial_deletes = metrics::DELETE_REQUESTS.get();
-    metrics::DELETE_REQUESTS.inc();
-    assert_eq!(metrics::DELETE_REQUESTS.get(), initial_deletes + 1);
+    let requests = |method: &str, status: &str| {
+        metrics::HTTP_REQUESTS
+            .with_label_values(&[method, "/:key", status])
+            .get()
+    };
+
+    let initial_gets = requests("GET", "200");
+    metrics::record_http_request("GET", "/:key", "200", Instant::now());
+    assert_eq!(requests("GET", "200"), initial_gets + 1);
+
+    let initial_puts = requests("PUT", "200");
+    metrics::record_http_request("PUT", "/:key", "200", Instant::now());
+    metrics::record_http_request("PUT", "/:key", "200", Instant::now());
+    assert_eq!(requests("PUT", "200"), initial_puts + 2);
+
+    // Status codes are counted separately
+    let initial_deletes = requests("DELETE", "404");
+    metrics::record_http_request("DELETE", "/:key", "404", Instant::now());
+    assert_eq!(requests("DELETE", "404"), initial_deletes + 1);
 }
 
 #[test]
@@ -47,20 +55,23 @@ fn test_latency_metrics() {
     metrics::init_metrics();
 
     // Observe some latencies
-    metrics::GET_LATENCY.observe(0.001); // 1ms
-    metrics::GET_LATENCY.observe(0.010); // 10ms
-    metrics::GET_LATENCY.observe(0.050); // 50ms
+    let latency =
+        |method: &str| metrics::HTTP_REQUEST_DURATION.with_label_values(&[method, "/:key", "200"]);
+    latency("GET").observe(0.001); // 1ms
+    latency("GET").observe(0.010); // 10ms
+    latency("GET").observe(0.050); // 50ms
 
-    metrics::PUT_LATENCY.observe(0.005); // 5ms
-    metrics::PUT_LATENCY.observe(0.025); // 25ms
+    latency("PUT").observe(0.005); // 5ms
+    latency("PUT").observe(0.025); // 25ms
 
-    metrics::DELETE_LATENCY.observe(0.001); // 1ms
+    latency("DELETE").observe(0.001); // 1ms
 
-    // Verify histograms are in the output
+    // Verify histograms are in the output, per method
     let metrics_output = metrics::get_metrics();
-    assert!(metrics_output.contains("scribe_ledger_get_latency_seconds"));
-    assert!(metrics_output.contains("scribe_ledger_put_latency_seconds"));
-    assert!(metrics_output.contains("scribe_ledger_delete_latency_seconds"));
+    assert!(metrics_output.contains("scribe_ledger_http_request_duration_seconds"));
+    assert!(metrics_output.contains("method=\"GET\""));
+    assert!(metrics_output.contains("method=\"PUT\""));
+    assert!(metrics_output.contains("method=\"DELETE\""));
 }
 
 #[test]
@@ -138,8 +149,10 @@ fn test_metrics_prometheus_format() {
     metrics::init_metrics();
 
     // Increment some counters
-    metrics::GET_REQUESTS.inc();
-    metrics::PUT_REQUESTS.inc();
+    metrics::record_http_request("GET", "/:key", "200", Instant::now());
+    metrics::HTTP_REQUESTS_IN_FLIGHT
+        .with_label_values(&["PUT", "/:key"])
+        .set(0);
     metrics::OPS_TOTAL.inc();
 
     let metrics_output = metrics::get_metrics();
@@ -149,11 +162,11 @@ fn test_metrics_prometheus_format() {
     assert!(metrics_output.contains("# TYPE"));
 
     // Check for counter types
-    assert!(metrics_output.contains("TYPE scribe_ledger_get_requests_total counter"));
-    assert!(metrics_output.contains("TYPE scribe_ledger_put_requests_total counter"));
+    assert!(metrics_output.contains("TYPE scribe_ledger_http_requests_total counter"));
+    assert!(metrics_output.contains("TYPE scribe_ledger_operations_total counter"));
 
     // Check for histogram types
-    assert!(metrics_output.contains("TYPE scribe_ledger_get_latency_seconds histogram"));
+    assert!(metrics_output.contains("TYPE scribe_ledger_http_request_duration_seconds histogram"));
 
     // Check for gauge types
     assert!(metrics_output.contains("TYPE scribe_ledger_storage_keys_total gauge"));
@@ -239,7 +252,9 @@ fn test_latency_percentiles() {
     ];
 
     for latency in latencies.iter() {
-        metrics::GET_LATENCY.observe(*latency);
+        metrics::HTTP_REQUEST_DURATION
+            .with_label_values(&["GET", "/:key", "200"])
+            .observe(*latency);
     }
 
     let metrics_output = metrics::get_metrics();
@@ -261,8 +276,13 @@ fn test_metrics_idempotent_initialization() {
     metrics::init_metrics();
 
     // Metrics should still work
-    metrics::GET_REQUESTS.inc();
-    assert!(metrics::GET_REQUESTS.get() > 0);
+    metrics::record_http_request("GET", "/health", "200", Instant::now());
+    assert!(
+        metrics::HTTP_REQUESTS
+            .with_label_values(&["GET", "/health", "200"])
+            .get()
+            > 0
+    );
 }
 
 #[test]
@@ -275,10 +295,9 @@ fn test_concurrent_metric_updates() {
         .map(|_| {
             thread::spawn(|| {
                 for _ in 0..100 {
-                    metrics::GET_REQUESTS.inc();
-                    metrics::PUT_REQUESTS.inc();
+                    metrics::record_http_request("GET", "/concurrent", "200", Instant::now());
+                    metrics::record_http_request("PUT", "/concurrent", "200", Instant::now());
                     metrics::OPS_TOTAL.inc();
-                    metrics::GET_LATENCY.observe(0.001);
                 }
             })
         })
@@ -289,8 +308,13 @@ fn test_concurrent_metric_updates() {
     }
 
     // Verify metrics were updated (should be at least 1000 each)
-    assert!(metrics::GET_REQUESTS.get() >= 1000);
-    assert!(metrics::PUT_REQUESTS.get() >= 1000);
+    let requests = |method: &str| {
+        metrics::HTTP_REQUESTS
+            .with_label_values(&[method, "/concurrent", "200"])
+            .get()
+    };
+    assert!(requests("GET") >= 1000);
+    assert!(requests("PUT") >= 1000);
     assert!(metrics::OPS_TOTAL.get() >= 1000);
 }
 
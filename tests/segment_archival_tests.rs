@@ -3,8 +3,15 @@
 //! These tests verify the segment archival functionality with compression,
 //! read-through, and lifecycle management.
 
-use hyra_scribe_ledger::storage::archival::{ArchivalManager, SegmentMetadata, TieringPolicy};
-use hyra_scribe_ledger::storage::s3::S3StorageConfig;
+use hyra_scribe_ledger::api::{DistributedApi, ReadConsistency, StorageTier};
+use hyra_scribe_ledger::consensus::ConsensusNode;
+use hyra_scribe_ledger::manifest::ManifestManager;
+use hyra_scribe_ledger::mirror::SegmentMirror;
+use hyra_scribe_ledger::security::{MasterKey, TenantKeyring};
+use hyra_scribe_ledger::storage::archival::{
+    ArchivalManager, SegmentMetadata, TieringPolicy, VerificationStatus,
+};
+use hyra_scribe_ledger::storage::s3::{S3Storage, S3StorageConfig};
 use hyra_scribe_ledger::storage::segment::{Segment, SegmentManager};
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -26,6 +33,7 @@ fn get_test_config() -> S3StorageConfig {
         path_style: true,
         timeout_secs: 30,
         max_retries: 3,
+        ..Default::default()
     }
 }
 
@@ -268,6 +276,220 @@ async fn test_large_segment_archival() {
     assert_eq!(retrieved_segment.data.len(), segment.data.len());
 }
 
+#[tokio::test]
+#[ignore] // Requires MinIO to be running
+async fn test_tenant_crypto_shredding() {
+    let config = get_test_config();
+    let segment_mgr = Arc::new(SegmentManager::new());
+    let policy = TieringPolicy::default();
+
+    let db = sled::Config::new().temporary(true).open().unwrap();
+    let keyring = Arc::new(TenantKeyring::open(
+        db.open_tree("tenant_keys").unwrap(),
+        MasterKey::generate(),
+    ));
+    let acme_key = keyring.register_tenant("acme", b"acme/").unwrap();
+    let globex_key = keyring.register_tenant("globex", b"globex/").unwrap();
+
+    let manager = ArchivalManager::new(config, segment_mgr, policy)
+        .await
+        .unwrap()
+        .with_keyring(keyring);
+
+    // Archive a segment shared by two tenants and an unowned key
+    let mut data = HashMap::new();
+    data.insert(b"acme/invoice".to_vec(), b"acme_value".to_vec());
+    data.insert(b"globex/order".to_vec(), b"globex_value".to_vec());
+    data.insert(b"shared".to_vec(), b"shared_value".to_vec());
+    let segment = Segment::from_data(900, data);
+
+    let metadata = manager.archive_segment(&segment).await.unwrap();
+    assert!(metadata.is_encrypted);
+    assert_eq!(metadata.key_ids.len(), 2);
+    assert!(metadata.key_ids.contains(&acme_key));
+    assert!(metadata.key_ids.contains(&globex_key));
+    assert_eq!(metadata.manifest_entry().key_ids, metadata.key_ids);
+
+    let retrieved = manager.retrieve_segment(900).await.unwrap().unwrap();
+    assert_eq!(retrieved.data, segment.data);
+
+    // Destroying the key shreds only that tenant's entries
+    assert_eq!(manager.shred_tenant("acme").await.unwrap(), Some(acme_key));
+    let retrieved = manager.retrieve_segment(900).await.unwrap().unwrap();
+    assert_eq!(retrieved.get(&b"acme/invoice".to_vec()), None);
+    assert_eq!(
+        retrieved.get(&b"globex/order".to_vec()),
+        Some(&b"globex_value".to_vec())
+    );
+    assert_eq!(
+        retrieved.get(&b"shared".to_vec()),
+        Some(&b"shared_value".to_vec())
+    );
+
+    manager.delete_archived_segment(900).await.unwrap();
+}
+
+#[tokio::test]
+#[ignore] // Requires MinIO to be running
+async fn test_verify_archived_segment() {
+    let config = get_test_config();
+    let segment_mgr = Arc::new(SegmentManager::new());
+    let policy = TieringPolicy::default();
+    let manifest = Arc::new(ManifestManager::new());
+
+    let manager = ArchivalManager::new(config.clone(), segment_mgr, policy)
+        .await
+        .unwrap()
+        .with_manifest(Arc::clone(&manifest));
+
+    let mut data = HashMap::new();
+    data.insert(b"verify_key".to_vec(), b"verify_value".to_vec());
+    let segment = Segment::from_data(1000, data);
+    let metadata = manager.archive_segment(&segment).await.unwrap();
+    assert_eq!(
+        manifest.get_segment(1000).await.unwrap().merkle_root,
+        metadata.merkle_root
+    );
+
+    let report = manager.verify_segment(1000).await.unwrap().unwrap();
+    assert_eq!(report.status, VerificationStatus::Verified);
+    assert_eq!(report.computed_root, Some(report.metadata_root.clone()));
+    assert_eq!(report.manifest_root, Some(report.metadata_root.clone()));
+    assert_eq!(report.entry_count, 1);
+
+    // Replace the object with a different, well-formed segment
+    let s3 = S3Storage::new(config).await.unwrap();
+    let mut other = HashMap::new();
+    other.insert(b"verify_key".to_vec(), b"tampered".to_vec());
+    let tampered = Segment::from_data(1000, other).serialize().unwrap();
+    s3.put_object("segments/segment-00000000000003e8.bin", tampered)
+        .await
+        .unwrap();
+    let report = manager.verify_segment(1000).await.unwrap().unwrap();
+    assert_eq!(report.status, VerificationStatus::Mismatch);
+
+    // Garbage cannot be decoded at all
+    s3.put_object("segments/segment-00000000000003e8.bin", b"garbage".to_vec())
+        .await
+        .unwrap();
+    let report = manager.verify_segment(1000).await.unwrap().unwrap();
+    assert_eq!(report.status, VerificationStatus::Corrupt);
+    assert!(report.error.is_some());
+
+    manager.delete_archived_segment(1000).await.unwrap();
+    assert!(manager.verify_segment(1000).await.unwrap().is_none());
+}
+
+#[tokio::test]
+#[ignore] // Requires MinIO to be running
+async fn test_read_through_demoted_key() {
+    let manager = ArchivalManager::new(
+        get_test_config(),
+        Arc::new(SegmentManager::new()),
+        TieringPolicy::default(),
+    )
+    .await
+    .unwrap();
+    let manager = Arc::new(manager);
+
+    let db = sled::Config::new().temporary(true).open().unwrap();
+    let consensus = Arc::new(ConsensusNode::new(1, db).await.unwrap());
+    consensus.initialize().await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(2000)).await;
+    let api = DistributedApi::new(consensus).with_archival(Arc::clone(&manager));
+
+    api.put(b"cold_key".to_vec(), b"cold_value".to_vec())
+        .await
+        .unwrap();
+    let mut data = HashMap::new();
+    data.insert(b"cold_key".to_vec(), b"cold_value".to_vec());
+    manager
+        .archive_segment(&Segment::from_data(1100, data))
+        .await
+        .unwrap();
+
+    // Segments that do not hold the current value are refused
+    assert!(api.demote(b"cold_key".to_vec(), 1101).await.is_err());
+    assert!(api.demote(b"cold_key".to_vec(), 1100).await.unwrap());
+
+    let (value, tier) = api
+        .get_tiered(b"cold_key".to_vec(), ReadConsistency::Linearizable)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(value, b"cold_value".to_vec());
+    assert_eq!(tier, StorageTier::Cold);
+
+    // A new write brings the key back to memory
+    api.put(b"cold_key".to_vec(), b"hot_value".to_vec())
+        .await
+        .unwrap();
+    let (_, tier) = api
+        .get_tiered(b"cold_key".to_vec(), ReadConsistency::Stale)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(tier, StorageTier::Hot);
+
+    manager.delete_archived_segment(1100).await.unwrap();
+}
+
+#[tokio::test]
+#[ignore] // Requires MinIO to be running
+async fn test_mirror_serves_archived_segments() {
+    let config = get_test_config();
+    let manager = ArchivalManager::new(
+        config.clone(),
+        Arc::new(SegmentManager::new()),
+        TieringPolicy::default(),
+    )
+    .await
+    .unwrap();
+
+    let mut older = HashMap::new();
+    older.insert(b"mirror_a".to_vec(), b"old".to_vec());
+    older.insert(b"mirror_b".to_vec(), b"kept".to_vec());
+    manager
+        .archive_segment(&Segment::from_data(1200, older))
+        .await
+        .unwrap();
+    let mut newer = HashMap::new();
+    newer.insert(b"mirror_a".to_vec(), b"new".to_vec());
+    manager
+        .archive_segment(&Segment::from_data(1201, newer))
+        .await
+        .unwrap();
+
+    let mirror = SegmentMirror::new(config).await.unwrap();
+    let report = mirror.refresh().await.unwrap();
+    assert!(report.segments_added >= 2);
+    assert_eq!(
+        mirror.get(b"mirror_a").await.unwrap(),
+        Some((1201, b"new".to_vec()))
+    );
+    assert_eq!(
+        mirror.get(b"mirror_b").await.unwrap(),
+        Some((1200, b"kept".to_vec()))
+    );
+    assert!(mirror.manifest().await.get_entry(1201).is_some());
+    let verification = mirror
+        .archival()
+        .verify_segment(1201)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(verification.status, VerificationStatus::Verified);
+
+    // Deleted segments drop out on the next refresh
+    manager.delete_archived_segment(1201).await.unwrap();
+    assert_eq!(mirror.refresh().await.unwrap().segments_removed, 1);
+    assert_eq!(
+        mirror.get(b"mirror_a").await.unwrap(),
+        Some((1200, b"old".to_vec()))
+    );
+    manager.delete_archived_segment(1200).await.unwrap();
+}
+
 #[test]
 fn test_tiering_policy_defaults() {
     let policy = TieringPolicy::default();
@@ -290,6 +512,10 @@ fn test_segment_metadata_serialization() {
         is_compressed: true,
         entry_count: 50,
         merkle_root: vec![1, 2, 3, 4],
+        is_encrypted: false,
+        key_ids: Vec::new(),
+        storage_class: None,
+        has_checksum_index: false,
     };
 
     let json = serde_json::to_string(&metadata).unwrap();
@@ -0,0 +1,85 @@
+//! Differential sync tests
+//!
+//! These tests run two in-process clusters and check that `DifferentialSync` brings
+//! the target in line with the source while copying only the keys that differ. Run
+//! with `cargo test --features testing --test sync_tests`.
+
+use hyra_scribe_ledger::http_client::ClusterClient;
+use hyra_scribe_ledger::sync::DifferentialSync;
+use hyra_scribe_ledger::testing::TestCluster;
+
+async fn clients() -> (TestCluster, TestCluster, ClusterClient, ClusterClient) {
+    let source = TestCluster::start(3).await.unwrap();
+    let target = TestCluster::start(1).await.unwrap();
+    let source_client = ClusterClient::new(source.client_urls()).unwrap();
+    let target_client = ClusterClient::new(target.client_urls()).unwrap();
+    (source, target, source_client, target_client)
+}
+
+#[tokio::test]
+async fn test_sync_copies_only_differing_keys() {
+    let (_source, _target, source, target) = clients().await;
+    for i in 0..50 {
+        let value = format!("value-{}", i).into_bytes();
+        source
+            .put(format!("key-{}", i).as_bytes(), value.clone())
+            .await
+            .unwrap();
+        // The target already holds most keys
+        if i >= 3 {
+            target
+                .put(format!("key-{}", i).as_bytes(), value)
+                .await
+                .unwrap();
+        }
+    }
+    target.put(b"key-10", b"stale".to_vec()).await.unwrap();
+    target.put(b"extra", b"only-here".to_vec()).await.unwrap();
+
+    let sync = DifferentialSync::new(source.clone(), target.clone()).with_buckets(16);
+    let report = sync.run().await.unwrap();
+    assert_eq!(report.keys_copied, 4);
+    assert_eq!(report.keys_deleted, 0);
+    assert_eq!(report.keys_extra, 1);
+    assert!(report.buckets_differing <= 5);
+    assert_eq!(
+        target.get(b"key-0").await.unwrap(),
+        Some(b"value-0".to_vec())
+    );
+    assert_eq!(
+        target.get(b"key-10").await.unwrap(),
+        Some(b"value-10".to_vec())
+    );
+    assert_eq!(
+        target.get(b"extra").await.unwrap(),
+        Some(b"only-here".to_vec())
+    );
+
+    // Deleting extra keys leaves both clusters identical
+    let report = sync.with_delete_extra(true).run().await.unwrap();
+    assert_eq!((report.keys_copied, report.keys_deleted), (0, 1));
+    assert_eq!(target.get(b"extra").await.unwrap(), None);
+    assert_eq!(
+        source.sync_buckets(16).await.unwrap(),
+        target.sync_buckets(16).await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_dry_run_leaves_target_untouched() {
+    let (_source, _target, source, target) = clients().await;
+    source.put(b"new", b"value".to_vec()).await.unwrap();
+    target.put(b"extra", b"value".to_vec()).await.unwrap();
+
+    let report = DifferentialSync::new(source, target.clone())
+        .with_delete_extra(true)
+        .with_dry_run(true)
+        .run()
+        .await
+        .unwrap();
+    assert!(report.dry_run);
+    assert_eq!((report.keys_copied, report.keys_deleted), (1, 1));
+    assert_eq!(report.bytes_copied, 0);
+    assert_eq!(target.get(b"new").await.unwrap(), None);
+    assert_eq!(target.get(b"extra").await.unwrap(), Some(b"value".to_vec()));
+}
@@ -0,0 +1,84 @@
+//! Ledger tag tests
+//!
+//! These tests run a 3-node in-process cluster and check that tags capture the same
+//! state on every replica, keep exporting that state after later writes, and survive
+//! the loss of the leader. Run with `cargo test --features testing --test tag_tests`.
+
+use hyra_scribe_ledger::error::ScribeError;
+use hyra_scribe_ledger::testing::TestCluster;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_tag_is_identical_on_every_replica() {
+    let cluster = TestCluster::start(3).await.unwrap();
+    let leader = cluster
+        .wait_for_leader(Duration::from_secs(5))
+        .await
+        .unwrap();
+    let api = cluster.node(leader).unwrap().api();
+
+    for i in 0..10 {
+        api.put(
+            format!("sample:{}", i).into_bytes(),
+            format!("text-{}", i).into_bytes(),
+        )
+        .await
+        .unwrap();
+    }
+    let tag = api.create_tag("dataset-v1").await.unwrap();
+    assert_eq!(tag.key_count, 10);
+    assert!(tag.merkle_root.is_some());
+
+    // Writes after the tag do not reach it
+    api.put(b"sample:0".to_vec(), b"edited".to_vec())
+        .await
+        .unwrap();
+    api.delete(b"sample:1".to_vec()).await.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let expected = api.export_tag("dataset-v1").await.unwrap().unwrap();
+    assert_eq!(expected.len(), 10);
+    assert_eq!(expected[0], (b"sample:0".to_vec(), b"text-0".to_vec()));
+    for node in cluster.nodes() {
+        assert_eq!(
+            node.api().tag("dataset-v1").await,
+            Some(tag.clone()),
+            "node {} did not apply the tag",
+            node.node_id
+        );
+        assert_eq!(
+            node.api().export_tag("dataset-v1").await.unwrap(),
+            Some(expected.clone())
+        );
+        let verification = node.api().verify_tag("dataset-v1").await.unwrap().unwrap();
+        assert!(verification.verified, "node {}", node.node_id);
+    }
+}
+
+#[tokio::test]
+async fn test_tags_survive_leader_loss() {
+    let mut cluster = TestCluster::start(3).await.unwrap();
+    let leader = cluster
+        .wait_for_leader(Duration::from_secs(5))
+        .await
+        .unwrap();
+    let api = cluster.node(leader).unwrap().api().clone();
+    api.put(b"key".to_vec(), b"value".to_vec()).await.unwrap();
+    let tag = api.create_tag("checkpoint").await.unwrap();
+
+    cluster.kill(leader).await.unwrap();
+    let new_leader = cluster
+        .wait_for_leader(Duration::from_secs(10))
+        .await
+        .unwrap();
+    let api = cluster.node(new_leader).unwrap().api();
+
+    assert_eq!(api.tags().await, vec![tag]);
+    // Tags stay immutable under the new leader
+    assert!(matches!(
+        api.create_tag("checkpoint").await,
+        Err(ScribeError::Conflict(_))
+    ));
+    assert!(api.delete_tag("checkpoint").await.unwrap());
+    assert!(api.tags().await.is_empty());
+}
@@ -203,3 +203,31 @@ fn test_proof_structure() {
     assert!(!proof.siblings.is_empty());
     assert_eq!(proof.siblings.len(), proof.directions.len());
 }
+
+#[test]
+fn test_proof_bundle_verifies_offline() {
+    use hyra_scribe_ledger::backup::BackupSigner;
+    use hyra_scribe_ledger::wire::{open, seal, ProofBundle, SignatureStatus};
+
+    let ledger = HyraScribeLedger::temp().unwrap();
+    ledger.put("alice", "data1").unwrap();
+    ledger.put("bob", "data2").unwrap();
+    ledger.put("charlie", "data3").unwrap();
+    let (signer, _) = BackupSigner::generate().unwrap();
+
+    // The sealed file is all an auditor needs
+    let file = seal(&ledger.proof_bundle("bob", Some(&signer)).unwrap().unwrap());
+    let bundle = open::<ProofBundle>(&file).unwrap();
+    let verdict = bundle.check(&[signer.public_key_hex()]);
+    assert!(verdict.valid);
+    assert_eq!(verdict.key, "bob");
+    assert_eq!(verdict.signature, SignatureStatus::Trusted);
+    assert_eq!(
+        bundle.root_hash,
+        ledger.compute_merkle_root().unwrap().unwrap()
+    );
+
+    assert!(ledger.proof_bundle("dave", None).unwrap().is_none());
+    let unsigned = ledger.proof_bundle("alice", None).unwrap().unwrap();
+    assert_eq!(unsigned.check(&[]).signature, SignatureStatus::Unsigned);
+}